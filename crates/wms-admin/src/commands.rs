@@ -0,0 +1,204 @@
+//! Command Execution
+
+use std::path::Path;
+use std::sync::Arc;
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use serde_json::Value;
+use wms_core::backup::BackupService;
+use wms_core::context::RequestContext;
+use wms_core::db::Database;
+use wms_core::instance_lock::InstanceLock;
+use wms_core::search::SearchService;
+use wms_core::users::UserService;
+use wms_crm::CrmService;
+use wms_inventory::InventoryService;
+use wms_sync::SyncEngine;
+use crate::{Cli, Command};
+
+/// Lock holder name shown to anyone else opening the database
+const LOCK_HOLDER: &str = "wms-admin";
+
+/// What a command printed: a JSON value for `--json`, text otherwise
+pub struct Output {
+    json: Value,
+    text: String,
+}
+
+impl Output {
+    fn new<T: Serialize>(value: &T, text: String) -> Result<Self> {
+        Ok(Self {
+            json: serde_json::to_value(value)?,
+            text,
+        })
+    }
+
+    pub fn print(&self, json: bool) {
+        if json {
+            println!("{}", self.json);
+        } else if !self.text.is_empty() {
+            println!("{}", self.text.trim_end());
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct MigrationReport {
+    applied: bool,
+    migrations: Vec<&'static str>,
+}
+
+#[derive(Serialize)]
+struct ExportReport {
+    path: String,
+    bytes: usize,
+}
+
+#[derive(Serialize)]
+struct RestoreReport {
+    restored_from: String,
+}
+
+pub async fn run(cli: &Cli) -> Result<Output> {
+    if !cli.db.is_file() {
+        bail!("Database {:?} not found", cli.db);
+    }
+    let _lock = InstanceLock::acquire(&cli.db, LOCK_HOLDER)?;
+
+    let db = Arc::new(Database::new(&cli.db, &cli.key)?);
+    // Fails fast on a schema from a newer build or a changed migration
+    let pending = db.check_schema()
+        .context("Unable to read the database; check the key")?;
+
+    if let Command::Migrate { dry_run, check } = cli.command {
+        if check && !pending.is_empty() {
+            bail!("{} migrations are pending:\n{}", pending.len(), pending.join("\n"));
+        }
+        return migrate(&db, pending, dry_run || check);
+    }
+    if !pending.is_empty() {
+        bail!(
+            "{} migrations are pending; run `wms-admin migrate` first",
+            pending.len()
+        );
+    }
+
+    match &cli.command {
+        Command::Migrate { .. } => unreachable!("handled above"),
+        Command::SyncStatus => {
+            let status = SyncEngine::new(db)?.get_status();
+            let text = format!(
+                "Pending changes: {} ({} stuck)\nLast sync: {}\nLast error: {}",
+                status.pending_changes,
+                status.stuck_changes,
+                status.last_sync_at.map(|t| t.to_rfc3339()).unwrap_or_else(|| "never".to_string()),
+                status.last_error.as_deref().unwrap_or("none"),
+            );
+            Output::new(&status, text)
+        }
+        Command::SyncNow => {
+            let status = SyncEngine::new(db)?.sync_now().await?;
+            if let Some(error) = &status.last_error {
+                bail!("Sync failed: {}", error);
+            }
+            let text = format!("Sync complete; {} changes pending", status.pending_changes);
+            Output::new(&status, text)
+        }
+        Command::CompactCrdt { yes } => {
+            require_confirmation(*yes, "compact-crdt rewrites stored sync documents")?;
+            let compaction = SyncEngine::new(db)?.compact_crdt_documents()?;
+            let text = format!(
+                "Compacted {} documents ({} rewritten): {} -> {} bytes",
+                compaction.documents, compaction.rewritten, compaction.bytes_before, compaction.bytes_after,
+            );
+            Output::new(&compaction, text)
+        }
+        Command::ReindexSearch => {
+            db.reindex()?;
+            SearchService::new(db).rebuild_index()?;
+            Output::new(&serde_json::json!({ "reindexed": true }), "Indexes rebuilt".to_string())
+        }
+        Command::SetPin { username, pin } => {
+            let users = UserService::new(db);
+            let user = users.get_user_by_username(username).await?
+                .with_context(|| format!("No user {}", username))?;
+            users.set_password(&RequestContext::for_user(&user.id), &user.id, pin).await?;
+            let text = format!("PIN changed for {}", user.username);
+            Output::new(&user, text)
+        }
+        Command::ExportInventory { output } => {
+            let csv = InventoryService::new(db).export_items_csv().await?;
+            export(csv, output.as_deref())
+        }
+        Command::ExportCustomers { output } => {
+            let csv = CrmService::new(db).export_customers_csv().await?;
+            export(csv, output.as_deref())
+        }
+        Command::Backup { directory, passphrase } => {
+            let info = BackupService::new(db)
+                .create_backup(directory, passphrase.as_deref())
+                .await?;
+            let text = format!("Backup written to {} ({} bytes)", info.path, info.size_bytes);
+            Output::new(&info, text)
+        }
+        Command::Restore { backup, passphrase, yes } => {
+            require_confirmation(*yes, "restore replaces the current database")?;
+            BackupService::new(db.clone())
+                .restore_backup(backup, passphrase.as_deref())
+                .await?;
+            // Restored sync cursors no longer match the server
+            SyncEngine::new(db)?.require_full_resync()?;
+            let report = RestoreReport { restored_from: backup.display().to_string() };
+            let text = format!("Restored from {}; the next sync will do a full handshake", report.restored_from);
+            Output::new(&report, text)
+        }
+    }
+}
+
+fn migrate(db: &Database, pending: Vec<&'static str>, dry_run: bool) -> Result<Output> {
+    let pending = if dry_run || pending.is_empty() {
+        pending
+    } else {
+        db.migrate_to_latest()?
+    };
+
+    let text = match (pending.is_empty(), dry_run) {
+        (true, _) => "No pending migrations".to_string(),
+        (false, true) => format!("Pending migrations:\n{}", pending.join("\n")),
+        (false, false) => format!("Applied migrations:\n{}", pending.join("\n")),
+    };
+    let report = MigrationReport {
+        applied: !dry_run && !pending.is_empty(),
+        migrations: pending,
+    };
+    Output::new(&report, text)
+}
+
+/// Write CSV to `path`, or return it as the command's text output
+fn export(csv: Vec<u8>, path: Option<&Path>) -> Result<Output> {
+    match path {
+        Some(path) => {
+            std::fs::write(path, &csv).with_context(|| format!("Unable to write {:?}", path))?;
+            let report = ExportReport {
+                path: path.display().to_string(),
+                bytes: csv.len(),
+            };
+            let text = format!("Wrote {} bytes to {}", report.bytes, report.path);
+            Output::new(&report, text)
+        }
+        None => {
+            let text = String::from_utf8(csv)?;
+            Ok(Output {
+                json: serde_json::json!({ "csv": text }),
+                text,
+            })
+        }
+    }
+}
+
+fn require_confirmation(yes: bool, what: &str) -> Result<()> {
+    if !yes {
+        bail!("{}; pass --yes to continue", what);
+    }
+    Ok(())
+}
@@ -0,0 +1,126 @@
+//! WMS Admin
+//!
+//! Headless maintenance for a WMS database: migrations, sync, CRDT
+//! compaction, index rebuilds, CSV exports and backup/restore. Every
+//! command goes through the same services the desktop app uses. The tool
+//! takes the database's instance lock first, so it refuses to run while
+//! the app (or another admin run) has the database open.
+
+mod commands;
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+use clap::{Parser, Subcommand};
+
+/// Key used by development builds when `WMS_DB_KEY` is unset (matches the app)
+const DEV_DB_KEY: &str = "default-dev-key-change-in-production";
+
+#[derive(Debug, Parser)]
+#[command(name = "wms-admin", version, about = "Maintenance tool for WMS databases")]
+pub struct Cli {
+    /// Path to the database file
+    #[arg(long, env = "WMS_DB_PATH")]
+    pub db: PathBuf,
+
+    /// Database encryption key
+    #[arg(long, env = "WMS_DB_KEY", default_value = DEV_DB_KEY, hide_env_values = true)]
+    pub key: String,
+
+    /// Print results as JSON
+    #[arg(long, global = true)]
+    pub json: bool,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Apply pending schema migrations
+    Migrate {
+        /// List pending migrations without applying them
+        #[arg(long)]
+        dry_run: bool,
+        /// Fail unless the schema is current, without applying anything
+        #[arg(long, conflicts_with = "dry_run")]
+        check: bool,
+    },
+    /// Show the outbox and last sync time
+    SyncStatus,
+    /// Sync with the server configured in WMS_SERVER_URL
+    SyncNow,
+    /// Rewrite stored CRDT documents in compact form
+    CompactCrdt {
+        /// Confirm rewriting sync data
+        #[arg(long)]
+        yes: bool,
+    },
+    /// Rebuild the indexes behind item, customer and global search
+    ReindexSearch,
+    /// Set the PIN a user signs in with, e.g. when the admin's is lost
+    SetPin {
+        username: String,
+        pin: String,
+    },
+    /// Export active items with on-hand quantities as CSV
+    ExportInventory {
+        /// Write to a file instead of stdout
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Export active customers as CSV
+    ExportCustomers {
+        /// Write to a file instead of stdout
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Write a timestamped backup into a directory
+    Backup {
+        directory: PathBuf,
+        /// Protect the backup with its own passphrase
+        #[arg(long)]
+        passphrase: Option<String>,
+    },
+    /// Replace the database with a backup
+    Restore {
+        backup: PathBuf,
+        /// Passphrase the backup was created with
+        #[arg(long)]
+        passphrase: Option<String>,
+        /// Confirm replacing the current database
+        #[arg(long)]
+        yes: bool,
+    },
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::WARN)
+        .with_writer(std::io::stderr)
+        .init();
+
+    let runtime = match tokio::runtime::Builder::new_current_thread().enable_all().build() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match runtime.block_on(commands::run(&cli)) {
+        Ok(output) => {
+            output.print(cli.json);
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            if cli.json {
+                println!("{}", serde_json::json!({ "error": format!("{:#}", e) }));
+            } else {
+                eprintln!("error: {:#}", e);
+            }
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,188 @@
+//! Drives the wms-admin binary against a database in a temp directory
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use serde_json::Value;
+use wms_core::db::Database;
+use wms_core::instance_lock::InstanceLock;
+use wms_core::test_support::{CustomerBuilder, ItemBuilder};
+
+const KEY: &str = "cli-test-key";
+
+/// Temp directory holding `wms.db`, removed on drop
+struct TempDb {
+    dir: PathBuf,
+}
+
+impl TempDb {
+    fn new(migrated: bool) -> Self {
+        let dir = std::env::temp_dir().join(format!("wms-admin-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db = Database::new(&dir.join("wms.db"), KEY).unwrap();
+        if migrated {
+            db.migrate_to_latest().unwrap();
+        }
+        Self { dir }
+    }
+
+    fn path(&self) -> PathBuf {
+        self.dir.join("wms.db")
+    }
+
+    fn open(&self) -> Database {
+        Database::new(&self.path(), KEY).unwrap()
+    }
+
+    fn admin(&self, args: &[&str]) -> Output {
+        Command::new(env!("CARGO_BIN_EXE_wms-admin"))
+            .arg("--db")
+            .arg(self.path())
+            .args(args)
+            .env("WMS_DB_KEY", KEY)
+            .env_remove("WMS_SERVER_URL")
+            .output()
+            .unwrap()
+    }
+
+    fn admin_json(&self, args: &[&str]) -> Value {
+        let output = self.admin(&[args, &["--json"]].concat());
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        serde_json::from_slice(&output.stdout).unwrap()
+    }
+}
+
+impl Drop for TempDb {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).to_string()
+}
+
+#[test]
+fn test_migrate_prints_then_applies_pending() {
+    let temp = TempDb::new(false);
+
+    let check = temp.admin(&["migrate", "--check"]);
+    assert!(!check.status.success());
+    assert!(stderr(&check).contains("migrations are pending"));
+
+    let planned = temp.admin_json(&["migrate", "--dry-run"]);
+    assert_eq!(planned["applied"], false);
+    assert_eq!(planned["migrations"][0], "001_initial_schema");
+
+    // Other commands wait for the schema to be current
+    let blocked = temp.admin(&["sync-status"]);
+    assert!(!blocked.status.success());
+    assert!(stderr(&blocked).contains("wms-admin migrate"));
+
+    let applied = temp.admin_json(&["migrate"]);
+    assert_eq!(applied["applied"], true);
+    assert!(temp.open().pending_migrations().unwrap().is_empty());
+    assert!(temp.admin(&["migrate", "--check"]).status.success());
+
+    let text = temp.admin(&["migrate"]);
+    assert_eq!(String::from_utf8_lossy(&text.stdout).trim(), "No pending migrations");
+}
+
+#[test]
+fn test_exports_and_sync_status() {
+    let temp = TempDb::new(true);
+    {
+        let db = temp.open();
+        ItemBuilder::new("WIDGET").name("Widget").stock("A-01", 12.0).insert::<Value>(&db);
+        CustomerBuilder::new("C-100").company_name("Acme Corp").insert::<Value>(&db);
+    }
+
+    let inventory = temp.admin(&["export-inventory"]);
+    assert!(inventory.status.success(), "{}", stderr(&inventory));
+    let csv = String::from_utf8_lossy(&inventory.stdout);
+    assert!(csv.starts_with("SKU,Name,"));
+    assert!(csv.contains("WIDGET,Widget,"));
+
+    let file = temp.dir.join("customers.csv");
+    let report = temp.admin_json(&["export-customers", "--output", file.to_str().unwrap()]);
+    assert!(report["bytes"].as_u64().unwrap() > 0);
+    assert!(std::fs::read_to_string(&file).unwrap().contains("C-100,Acme Corp"));
+
+    let status = temp.admin_json(&["sync-status"]);
+    assert_eq!(status["is_syncing"], false);
+    assert!(status["last_sync_at"].is_null());
+
+    let failed = temp.admin(&["sync-now"]);
+    assert!(!failed.status.success());
+    assert!(stderr(&failed).contains("No server URL"));
+}
+
+#[test]
+fn test_set_pin_lets_the_user_sign_in() {
+    let temp = TempDb::new(true);
+    temp.open().execute(
+        "INSERT INTO users (id, username, email, full_name, role) VALUES ('a1', 'admin', 'admin@localhost', 'Admin', 'admin')",
+        [],
+    ).unwrap();
+
+    let user = temp.admin_json(&["set-pin", "admin", "8642"]);
+    assert_eq!(user["id"], "a1");
+    let hash: String = temp.open()
+        .query_row("SELECT password_hash FROM users WHERE id = 'a1'", [], |row| row.get(0))
+        .unwrap()
+        .unwrap();
+    assert!(wms_core::users::verify_password("8642", &hash));
+
+    assert!(!temp.admin(&["set-pin", "admin", "12"]).status.success());
+    assert!(stderr(&temp.admin(&["set-pin", "nobody", "8642"])).contains("No user nobody"));
+}
+
+#[test]
+fn test_refuses_to_run_while_database_is_locked() {
+    let temp = TempDb::new(true);
+    let app = InstanceLock::acquire(&temp.path(), "desktop app").unwrap();
+
+    let refused = temp.admin(&["sync-status"]);
+    assert!(!refused.status.success());
+    assert!(stderr(&refused).contains("in use"));
+    assert!(stderr(&refused).contains("desktop app"));
+
+    drop(app);
+    assert!(temp.admin(&["sync-status"]).status.success());
+}
+
+#[test]
+fn test_destructive_commands_need_yes() {
+    let temp = TempDb::new(true);
+
+    let refused = temp.admin(&["compact-crdt"]);
+    assert!(!refused.status.success());
+    assert!(stderr(&refused).contains("--yes"));
+    assert_eq!(temp.admin_json(&["compact-crdt", "--yes"])["documents"], 0);
+
+    assert_eq!(temp.admin_json(&["reindex-search"])["reindexed"], true);
+}
+
+#[test]
+fn test_backup_and_restore_round_trip() {
+    let temp = TempDb::new(true);
+    ItemBuilder::new("KEEP").insert::<Value>(&temp.open());
+
+    let backups = temp.dir.join("backups");
+    std::fs::create_dir_all(&backups).unwrap();
+    let info = temp.admin_json(&["backup", backups.to_str().unwrap()]);
+    let backup = info["path"].as_str().unwrap().to_string();
+    assert!(Path::new(&backup).is_file());
+
+    temp.open().execute("DELETE FROM inventory_items", []).unwrap();
+
+    let refused = temp.admin(&["restore", &backup]);
+    assert!(!refused.status.success());
+    assert!(stderr(&refused).contains("--yes"));
+
+    let restored = temp.admin_json(&["restore", &backup, "--yes"]);
+    assert_eq!(restored["restored_from"], backup.as_str());
+    let skus: Vec<String> = temp.open()
+        .query_map("SELECT sku FROM inventory_items", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(skus, vec!["KEEP".to_string()]);
+}
@@ -0,0 +1,417 @@
+//! Attachments
+//!
+//! Files attached to records: item photos, proof-of-delivery and damage
+//! photos, and documents. Content is kept in the database, or on disk when
+//! a storage directory is configured. Images get a small JPEG thumbnail so
+//! lists and pick lists can show them without loading the full photo.
+
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::path::PathBuf;
+use std::sync::Arc;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use image::{DynamicImage, ImageFormat};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+use crate::db::Database;
+use crate::error::{WmsError, Result};
+use crate::types::{new_id, required_timestamp, stored_status};
+
+/// Largest attachment accepted by default
+pub const DEFAULT_MAX_ATTACHMENT_BYTES: usize = 10 * 1024 * 1024;
+
+/// Longest side of a generated thumbnail, in pixels
+pub const THUMBNAIL_MAX_DIMENSION: u32 = 200;
+
+/// What an attachment is
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AttachmentKind {
+    /// Photo of the record, e.g. an item image
+    Image,
+    /// Photo taken at the door as proof of delivery
+    ProofOfDelivery,
+    /// Recipient's signature captured at the door
+    Signature,
+    /// Photo of damaged goods, kept for a claim
+    DamageEvidence,
+    /// Any other file
+    Document,
+}
+
+impl AttachmentKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Image => "image",
+            Self::ProofOfDelivery => "proof_of_delivery",
+            Self::Signature => "signature",
+            Self::DamageEvidence => "damage_evidence",
+            Self::Document => "document",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "image" => Some(Self::Image),
+            "proof_of_delivery" => Some(Self::ProofOfDelivery),
+            "signature" => Some(Self::Signature),
+            "damage_evidence" => Some(Self::DamageEvidence),
+            "document" => Some(Self::Document),
+            _ => None,
+        }
+    }
+
+    /// Whether content must be a decodable image
+    pub fn is_image(&self) -> bool {
+        matches!(self, Self::Image | Self::ProofOfDelivery | Self::Signature | Self::DamageEvidence)
+    }
+}
+
+/// A file to attach
+#[derive(Debug, Clone)]
+pub struct NewAttachment {
+    pub owner_type: String,
+    pub owner_id: String,
+    pub kind: AttachmentKind,
+    pub filename: String,
+    pub content: Vec<u8>,
+    pub created_by: String,
+}
+
+/// Stored attachment metadata (without the content itself)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Attachment {
+    pub id: String,
+    pub owner_type: String,
+    pub owner_id: String,
+    pub kind: AttachmentKind,
+    pub filename: String,
+    pub content_type: String,
+    pub size_bytes: u64,
+    /// Base64 encoded JPEG thumbnail (images only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thumbnail: Option<String>,
+    pub is_primary: bool,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Downscale an image to fit within `THUMBNAIL_MAX_DIMENSION`, encoded as JPEG
+pub fn generate_thumbnail(data: &[u8]) -> Result<Vec<u8>> {
+    let image = image::load_from_memory(data)
+        .map_err(|e| WmsError::validation(format!("Unsupported image: {}", e)))?;
+    let thumbnail = DynamicImage::ImageRgb8(
+        image.thumbnail(THUMBNAIL_MAX_DIMENSION, THUMBNAIL_MAX_DIMENSION).to_rgb8(),
+    );
+
+    let mut encoded = Cursor::new(Vec::new());
+    thumbnail.write_to(&mut encoded, ImageFormat::Jpeg)
+        .map_err(|e| WmsError::Unknown(format!("Thumbnail encoding failed: {}", e)))?;
+    Ok(encoded.into_inner())
+}
+
+/// Keep only characters that are safe in a file name
+fn safe_filename(filename: &str) -> String {
+    filename.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') { c } else { '_' })
+        .collect()
+}
+
+/// Stores, lists and deletes attachments
+pub struct AttachmentService {
+    db: Arc<Database>,
+    max_size_bytes: usize,
+    storage_dir: Option<PathBuf>,
+}
+
+impl AttachmentService {
+    pub fn new(db: Arc<Database>) -> Self {
+        Self {
+            db,
+            max_size_bytes: DEFAULT_MAX_ATTACHMENT_BYTES,
+            storage_dir: None,
+        }
+    }
+
+    /// Reject attachments larger than `bytes`
+    pub fn with_max_size(mut self, bytes: usize) -> Self {
+        self.max_size_bytes = bytes;
+        self
+    }
+
+    /// Write content to files in `dir` instead of the database
+    pub fn with_storage_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.storage_dir = Some(dir.into());
+        self
+    }
+
+    /// Store a new attachment. Images are validated and thumbnailed.
+    pub async fn add(&self, upload: NewAttachment) -> Result<Attachment> {
+        if upload.content.is_empty() {
+            return Err(WmsError::validation(format!("{} is empty", upload.filename)));
+        }
+        if upload.content.len() > self.max_size_bytes {
+            return Err(WmsError::validation(format!(
+                "{} is {} bytes; attachments are limited to {} bytes",
+                upload.filename, upload.content.len(), self.max_size_bytes
+            )));
+        }
+
+        let (content_type, thumbnail) = if upload.kind.is_image() {
+            let format = image::guess_format(&upload.content)
+                .map_err(|_| WmsError::validation(format!("{} is not a supported image", upload.filename)))?;
+            (format.to_mime_type().to_string(), Some(generate_thumbnail(&upload.content)?))
+        } else {
+            ("application/octet-stream".to_string(), None)
+        };
+
+        let id = new_id();
+        let created_at = Utc::now();
+        let file_path = match &self.storage_dir {
+            Some(dir) => {
+                std::fs::create_dir_all(dir)?;
+                let path = dir.join(format!("{}-{}", id, safe_filename(&upload.filename)));
+                std::fs::write(&path, &upload.content)?;
+                Some(path.to_string_lossy().to_string())
+            }
+            None => None,
+        };
+        let content = file_path.is_none().then_some(&upload.content);
+
+        self.db.execute(
+            "INSERT INTO attachments (
+                id, owner_type, owner_id, kind, filename, content_type, size_bytes,
+                content, file_path, thumbnail, created_by, created_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            params![
+                &id,
+                &upload.owner_type,
+                &upload.owner_id,
+                upload.kind.as_str(),
+                &upload.filename,
+                &content_type,
+                upload.content.len() as i64,
+                content,
+                &file_path,
+                &thumbnail,
+                &upload.created_by,
+                created_at.to_rfc3339(),
+            ],
+        )?;
+
+        info!("Attached {} to {} {}", upload.filename, upload.owner_type, upload.owner_id);
+        self.get(&id).await?
+            .ok_or_else(|| WmsError::not_found("Attachment not found"))
+    }
+
+    /// Get attachment metadata
+    pub async fn get(&self, id: &str) -> Result<Option<Attachment>> {
+        self.db.query_row(
+            "SELECT * FROM attachments WHERE id = ?",
+            params![id],
+            row_to_attachment,
+        )
+    }
+
+    /// Attachments of a record, primary first, then oldest first
+    pub async fn list(&self, owner_type: &str, owner_id: &str) -> Result<Vec<Attachment>> {
+        self.db.query_map(
+            "SELECT * FROM attachments
+             WHERE owner_type = ? AND owner_id = ?
+             ORDER BY is_primary DESC, created_at, id",
+            params![owner_type, owner_id],
+            row_to_attachment,
+        )
+    }
+
+    /// Full content of an attachment
+    pub async fn content(&self, id: &str) -> Result<Vec<u8>> {
+        let stored = self.db.query_row(
+            "SELECT content, file_path FROM attachments WHERE id = ?",
+            params![id],
+            |row| Ok((row.get::<_, Option<Vec<u8>>>(0)?, row.get::<_, Option<String>>(1)?)),
+        )?;
+
+        match stored {
+            Some((Some(content), _)) => Ok(content),
+            Some((None, Some(path))) => Ok(std::fs::read(path)?),
+            Some((None, None)) => Err(WmsError::not_found(format!("Attachment {} has no content", id))),
+            None => Err(WmsError::not_found("Attachment not found")),
+        }
+    }
+
+    /// Make an attachment the primary one of its kind for its record
+    pub async fn set_primary(&self, id: &str) -> Result<Attachment> {
+        let attachment = self.get(id).await?
+            .ok_or_else(|| WmsError::not_found("Attachment not found"))?;
+
+        self.db.transaction(|conn| {
+            conn.execute(
+                "UPDATE attachments SET is_primary = (id = ?)
+                 WHERE owner_type = ? AND owner_id = ? AND kind = ?",
+                params![id, &attachment.owner_type, &attachment.owner_id, attachment.kind.as_str()],
+            )?;
+            Ok(())
+        })?;
+
+        Ok(Attachment { is_primary: true, ..attachment })
+    }
+
+    /// Base64 thumbnails of the primary image of each owner that has one
+    pub async fn primary_thumbnails(&self, owner_type: &str, owner_ids: &[String]) -> Result<HashMap<String, String>> {
+        let mut thumbnails = HashMap::new();
+        for owner_id in owner_ids {
+            if thumbnails.contains_key(owner_id) {
+                continue;
+            }
+            let thumbnail = self.db.query_row(
+                "SELECT thumbnail FROM attachments
+                 WHERE owner_type = ? AND owner_id = ? AND is_primary = 1 AND thumbnail IS NOT NULL",
+                params![owner_type, owner_id],
+                |row| row.get::<_, Vec<u8>>(0),
+            )?;
+            if let Some(thumbnail) = thumbnail {
+                thumbnails.insert(owner_id.clone(), base64::engine::general_purpose::STANDARD.encode(thumbnail));
+            }
+        }
+        Ok(thumbnails)
+    }
+
+    /// Delete an attachment and its file. If it was the primary one, the
+    /// oldest remaining attachment of the same kind takes its place.
+    pub async fn delete(&self, id: &str) -> Result<()> {
+        let stored = self.db.query_row(
+            "SELECT owner_type, owner_id, kind, is_primary, file_path FROM attachments WHERE id = ?",
+            params![id],
+            |row| Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, i32>(3)? == 1,
+                row.get::<_, Option<String>>(4)?,
+            )),
+        )?;
+        let Some((owner_type, owner_id, kind, was_primary, file_path)) = stored else {
+            return Err(WmsError::not_found("Attachment not found"));
+        };
+
+        self.db.transaction(|conn| {
+            conn.execute("DELETE FROM attachments WHERE id = ?", params![id])?;
+            if was_primary {
+                conn.execute(
+                    "UPDATE attachments SET is_primary = 1
+                     WHERE id = (
+                        SELECT id FROM attachments
+                        WHERE owner_type = ? AND owner_id = ? AND kind = ?
+                        ORDER BY created_at, id LIMIT 1
+                     )",
+                    params![&owner_type, &owner_id, &kind],
+                )?;
+            }
+            Ok(())
+        })?;
+
+        if let Some(path) = file_path
+            && let Err(e) = std::fs::remove_file(&path)
+        {
+            warn!("Could not remove attachment file {}: {}", path, e);
+        }
+
+        Ok(())
+    }
+}
+
+/// Convert database row to Attachment
+fn row_to_attachment(row: &rusqlite::Row) -> rusqlite::Result<Attachment> {
+    Ok(Attachment {
+        id: row.get("id")?,
+        owner_type: row.get("owner_type")?,
+        owner_id: row.get("owner_id")?,
+        kind: stored_status(row, "kind", AttachmentKind::parse)?,
+        filename: row.get("filename")?,
+        content_type: row.get("content_type")?,
+        size_bytes: row.get::<_, i64>("size_bytes")? as u64,
+        thumbnail: row.get::<_, Option<Vec<u8>>>("thumbnail")?
+            .map(|t| base64::engine::general_purpose::STANDARD.encode(t)),
+        is_primary: row.get::<_, i32>("is_primary")? == 1,
+        created_by: row.get("created_by")?,
+        created_at: required_timestamp(row, "created_at")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::RgbImage;
+
+    fn test_service() -> AttachmentService {
+        let db = Database::new(&PathBuf::from(":memory:"), "test-key").unwrap();
+        db.migrate_to_latest().unwrap();
+        db.execute(
+            "INSERT INTO users (id, username, email, full_name) VALUES ('u1', 'picker', 'picker@example.com', 'Pat Picker')",
+            [],
+        ).unwrap();
+        AttachmentService::new(Arc::new(db))
+    }
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut data = Cursor::new(Vec::new());
+        DynamicImage::ImageRgb8(RgbImage::from_pixel(width, height, image::Rgb([200, 40, 40])))
+            .write_to(&mut data, ImageFormat::Png)
+            .unwrap();
+        data.into_inner()
+    }
+
+    fn upload(filename: &str, content: Vec<u8>) -> NewAttachment {
+        NewAttachment {
+            owner_type: "inventory_item".to_string(),
+            owner_id: "item-1".to_string(),
+            kind: AttachmentKind::Image,
+            filename: filename.to_string(),
+            content,
+            created_by: "u1".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_thumbnail_keeps_aspect_ratio() {
+        let thumbnail = generate_thumbnail(&png(800, 400)).unwrap();
+
+        assert_eq!(image::guess_format(&thumbnail).unwrap(), ImageFormat::Jpeg);
+        let decoded = image::load_from_memory(&thumbnail).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (200, 100));
+
+        assert!(generate_thumbnail(b"not an image").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_oversized_attachment_rejected() {
+        let service = test_service().with_max_size(1024);
+
+        let result = service.add(upload("huge.png", vec![0; 2048])).await;
+
+        assert!(matches!(result, Err(WmsError::Validation(_))));
+        assert!(service.list("inventory_item", "item-1").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_store_and_delete_image() {
+        let service = test_service();
+        let first = service.add(upload("front.png", png(64, 64))).await.unwrap();
+        let second = service.add(upload("back.png", png(32, 48))).await.unwrap();
+
+        assert_eq!(first.content_type, "image/png");
+        assert!(first.thumbnail.is_some());
+        assert_eq!(service.content(&first.id).await.unwrap(), png(64, 64));
+
+        service.set_primary(&first.id).await.unwrap();
+        service.delete(&first.id).await.unwrap();
+
+        let remaining = service.list("inventory_item", "item-1").await.unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].id, second.id);
+        assert!(remaining[0].is_primary);
+    }
+}
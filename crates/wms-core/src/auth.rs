@@ -0,0 +1,285 @@
+//! Roles and Permissions
+//!
+//! Each user has a role (`users.role`), and the matrix in `Role::can` is
+//! the one place that decides which scopes a role may use. Commands
+//! require a scope before doing anything, drivers only ever see deliveries
+//! on their own routes, and timesheets other than your own need
+//! `Scope::TeamTimesheets`.
+//!
+//! Without a session nothing is allowed; the first run seeds an admin
+//! account so there is always someone to sign in as.
+
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use crate::db::Database;
+use crate::error::{WmsError, Result};
+
+/// What a user does in the warehouse
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Admin,
+    Supervisor,
+    Operator,
+    Picker,
+    Driver,
+}
+
+impl Role {
+    /// Parse a stored role
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "admin" => Some(Self::Admin),
+            "supervisor" => Some(Self::Supervisor),
+            "operator" => Some(Self::Operator),
+            "picker" => Some(Self::Picker),
+            "driver" => Some(Self::Driver),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Admin => "admin",
+            Self::Supervisor => "supervisor",
+            Self::Operator => "operator",
+            Self::Picker => "picker",
+            Self::Driver => "driver",
+        }
+    }
+
+    /// The permission matrix
+    pub fn can(&self, scope: Scope) -> bool {
+        use Scope::*;
+
+        match self {
+            Self::Admin => true,
+            Self::Supervisor => scope != Settings,
+            Self::Operator => matches!(
+                scope,
+                Dashboard | Inventory | Shipping | Receiving | Deliveries | Customers | Timesheets
+            ),
+            Self::Picker => matches!(scope, Dashboard | Inventory | Shipping | Receiving | Timesheets),
+            Self::Driver => matches!(scope, Deliveries | Timesheets),
+        }
+    }
+
+    /// Scopes the role may use
+    pub fn scopes(&self) -> Vec<Scope> {
+        Scope::ALL.into_iter().filter(|scope| self.can(*scope)).collect()
+    }
+}
+
+/// Area of the app a command belongs to
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Scope {
+    Dashboard,
+    Inventory,
+    Shipping,
+    Receiving,
+    Deliveries,
+    Customers,
+    Timesheets,
+    /// Other users' timesheets, not just your own
+    TeamTimesheets,
+    /// Expected quantities on blind receipts, and accepting their variances
+    ReceivingVariances,
+    /// Approving supervisor overrides and reviewing them
+    ApproveOverrides,
+    /// Adjusting stock down, and writing it off as damaged or scrapped
+    WriteOffStock,
+    /// Merging duplicate customers
+    MergeCustomers,
+    /// Backups and application settings
+    Settings,
+}
+
+impl Scope {
+    pub const ALL: [Self; 13] = [
+        Self::Dashboard,
+        Self::Inventory,
+        Self::Shipping,
+        Self::Receiving,
+        Self::Deliveries,
+        Self::Customers,
+        Self::Timesheets,
+        Self::TeamTimesheets,
+        Self::ReceivingVariances,
+        Self::ApproveOverrides,
+        Self::WriteOffStock,
+        Self::MergeCustomers,
+        Self::Settings,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Dashboard => "dashboard",
+            Self::Inventory => "inventory",
+            Self::Shipping => "shipping",
+            Self::Receiving => "receiving",
+            Self::Deliveries => "deliveries",
+            Self::Customers => "customers",
+            Self::Timesheets => "timesheets",
+            Self::TeamTimesheets => "team_timesheets",
+            Self::ReceivingVariances => "receiving_variances",
+            Self::ApproveOverrides => "approve_overrides",
+            Self::WriteOffStock => "write_off_stock",
+            Self::MergeCustomers => "merge_customers",
+            Self::Settings => "settings",
+        }
+    }
+}
+
+/// The signed-in user
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Session {
+    pub user_id: String,
+    pub role: Role,
+}
+
+impl Session {
+    /// Start a session for an active user
+    pub fn load(db: &Database, user_id: &str) -> Result<Self> {
+        let role: String = db.query_row(
+            "SELECT role FROM users WHERE id = ? AND is_active = 1",
+            params![user_id],
+            |row| row.get(0),
+        )?.ok_or_else(|| WmsError::not_found(format!("No active user {}", user_id)))?;
+        let role = Role::parse(&role)
+            .ok_or_else(|| WmsError::validation(format!("User {} has unknown role {}", user_id, role)))?;
+
+        Ok(Self { user_id: user_id.to_string(), role })
+    }
+
+    /// Fail unless the session's role may use `scope`
+    pub fn require(&self, scope: Scope) -> Result<()> {
+        if self.role.can(scope) {
+            Ok(())
+        } else {
+            Err(WmsError::permission_denied(format!(
+                "{} users cannot access {}",
+                self.role.as_str(),
+                scope.as_str(),
+            )))
+        }
+    }
+
+    /// Driver whose deliveries the session is limited to; `None` sees all
+    pub fn delivery_driver(&self) -> Option<&str> {
+        (self.role == Role::Driver).then_some(self.user_id.as_str())
+    }
+
+    /// Fail unless the session may see or record `user_id`'s time
+    pub fn require_timesheet_of(&self, user_id: &str) -> Result<()> {
+        self.require(Scope::Timesheets)?;
+        if user_id == self.user_id {
+            Ok(())
+        } else {
+            self.require(Scope::TeamTimesheets)
+        }
+    }
+}
+
+/// What the current session may use, for hiding parts of the UI
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Permissions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub role: Option<Role>,
+    pub scopes: Vec<Scope>,
+}
+
+impl Permissions {
+    /// Permissions of `session`, or nothing without one
+    pub fn of(session: Option<&Session>) -> Self {
+        match session {
+            Some(session) => Self {
+                user_id: Some(session.user_id.clone()),
+                role: Some(session.role),
+                scopes: session.role.scopes(),
+            },
+            None => Self { user_id: None, role: None, scopes: Vec::new() },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_db;
+
+    fn session(role: Role) -> Session {
+        Session { user_id: "u1".to_string(), role }
+    }
+
+    #[test]
+    fn test_admin_can_use_everything() {
+        assert_eq!(Role::Admin.scopes(), Scope::ALL.to_vec());
+    }
+
+    #[test]
+    fn test_supervisor_sees_team_timesheets_but_not_settings() {
+        let supervisor = session(Role::Supervisor);
+        assert!(supervisor.require_timesheet_of("u2").is_ok());
+        assert!(supervisor.require(Scope::Customers).is_ok());
+        assert!(supervisor.require(Scope::Settings).is_err());
+        assert_eq!(supervisor.delivery_driver(), None);
+    }
+
+    #[test]
+    fn test_operator_works_every_module_but_only_own_timesheet() {
+        let operator = session(Role::Operator);
+        for scope in [Scope::Inventory, Scope::Shipping, Scope::Receiving, Scope::Deliveries, Scope::Customers] {
+            assert!(operator.require(scope).is_ok(), "{:?}", scope);
+        }
+        assert!(operator.require_timesheet_of("u1").is_ok());
+        assert!(operator.require_timesheet_of("u2").is_err());
+        assert!(operator.require(Scope::Settings).is_err());
+        assert!(operator.require(Scope::MergeCustomers).is_err());
+        assert!(operator.require(Scope::WriteOffStock).is_err());
+        assert!(session(Role::Supervisor).require(Scope::WriteOffStock).is_ok());
+    }
+
+    #[test]
+    fn test_picker_has_no_customers_or_deliveries() {
+        let picker = session(Role::Picker);
+        assert!(picker.require(Scope::Inventory).is_ok());
+        assert!(picker.require(Scope::Shipping).is_ok());
+        assert!(picker.require(Scope::Customers).is_err());
+        assert!(picker.require(Scope::Deliveries).is_err());
+        assert!(picker.require(Scope::ReceivingVariances).is_err());
+        assert!(picker.require(Scope::ApproveOverrides).is_err());
+        assert!(picker.require_timesheet_of("u2").is_err());
+    }
+
+    #[test]
+    fn test_driver_only_sees_own_deliveries_and_timesheet() {
+        let driver = session(Role::Driver);
+        assert_eq!(Role::Driver.scopes(), vec![Scope::Deliveries, Scope::Timesheets]);
+        assert_eq!(driver.delivery_driver(), Some("u1"));
+        assert!(driver.require_timesheet_of("u1").is_ok());
+        assert!(driver.require_timesheet_of("u2").is_err());
+
+        let err = driver.require(Scope::Inventory).unwrap_err();
+        assert!(matches!(err, WmsError::PermissionDenied(_)));
+        assert!(driver.require(Scope::Customers).is_err());
+    }
+
+    #[test]
+    fn test_session_loads_active_users_only() {
+        let db = test_db();
+        db.execute(
+            "INSERT INTO users (id, username, email, full_name, role) VALUES
+                ('d1', 'dee', 'dee@example.com', 'Dee Driver', 'Driver'),
+                ('x1', 'exx', 'exx@example.com', 'Ex Employee', 'operator')",
+            [],
+        ).unwrap();
+        db.execute("UPDATE users SET is_active = 0 WHERE id = 'x1'", []).unwrap();
+
+        assert_eq!(Session::load(&db, "d1").unwrap().role, Role::Driver);
+        assert!(Session::load(&db, "x1").is_err());
+        assert!(Permissions::of(None).scopes.is_empty());
+    }
+}
@@ -0,0 +1,341 @@
+//! Database Backup and Restore
+//!
+//! Backups are taken with SQLite's online backup API, so they are
+//! consistent even while services keep writing. A backup is keyed like the
+//! live database unless the user protects it with its own passphrase.
+//! Restoring validates the backup's schema, then swaps it in place of the
+//! live file and runs any migrations it is missing.
+
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::sync::Arc;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+use crate::db::{applied_migrations, known_migrations, sidecar_path, Database};
+use crate::error::{WmsError, Result};
+use crate::events::{noop_emitter, SharedEmitter, WmsEvent};
+
+/// File name prefix for backups
+pub const BACKUP_PREFIX: &str = "wms-backup-";
+
+/// Timestamp format embedded in backup file names
+const BACKUP_TIMESTAMP_FORMAT: &str = "%Y%m%d-%H%M%S";
+
+/// Chunk size used when staging a backup for restore
+const COPY_CHUNK_BYTES: usize = 1024 * 1024;
+
+/// A backup file on disk
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupInfo {
+    pub path: String,
+    pub file_name: String,
+    pub size_bytes: u64,
+    pub created_at: DateTime<Utc>,
+}
+
+impl BackupInfo {
+    fn from_path(path: &Path) -> Option<Self> {
+        let file_name = path.file_name()?.to_str()?.to_string();
+        let stamp = file_name.strip_prefix(BACKUP_PREFIX)?.strip_suffix(".db")?;
+        let created_at = NaiveDateTime::parse_from_str(stamp, BACKUP_TIMESTAMP_FORMAT).ok()?.and_utc();
+        let size_bytes = std::fs::metadata(path).ok()?.len();
+
+        Some(Self {
+            path: path.to_string_lossy().to_string(),
+            file_name,
+            size_bytes,
+            created_at,
+        })
+    }
+}
+
+/// Check that a backup's applied migrations are all known to this build.
+///
+/// Older backups are accepted because the migration runner upgrades them;
+/// backups from a newer version are rejected.
+pub fn check_schema_compatibility(applied: &[String]) -> Result<()> {
+    if applied.is_empty() {
+        return Err(WmsError::validation("Backup has no schema version information"));
+    }
+
+    let known = known_migrations();
+    if let Some(unknown) = applied.iter().find(|name| !known.contains(&name.as_str())) {
+        return Err(WmsError::validation(format!(
+            "Backup was created by a newer version of the app (unknown migration {})",
+            unknown
+        )));
+    }
+
+    Ok(())
+}
+
+/// Backup and restore of the local database
+pub struct BackupService {
+    db: Arc<Database>,
+    events: SharedEmitter,
+}
+
+impl BackupService {
+    /// Create a new backup service
+    pub fn new(db: Arc<Database>) -> Self {
+        Self {
+            db,
+            events: noop_emitter(),
+        }
+    }
+
+    /// Publish progress events through the given emitter
+    pub fn with_event_emitter(mut self, events: SharedEmitter) -> Self {
+        self.events = events;
+        self
+    }
+
+    /// Write a timestamped backup into `directory`
+    pub async fn create_backup(&self, directory: &Path, passphrase: Option<&str>) -> Result<BackupInfo> {
+        if !directory.is_dir() {
+            return Err(WmsError::validation(format!("Backup directory {:?} does not exist", directory)));
+        }
+        if passphrase.is_some_and(|p| p.is_empty()) {
+            return Err(WmsError::validation("Backup passphrase cannot be empty"));
+        }
+
+        let file_name = format!(
+            "{}{}.db",
+            BACKUP_PREFIX,
+            Utc::now().format(BACKUP_TIMESTAMP_FORMAT)
+        );
+        let path = directory.join(&file_name);
+        if path.exists() {
+            return Err(WmsError::conflict(format!("Backup {} already exists", file_name)));
+        }
+
+        // Write under a temporary name so a failed backup never looks complete
+        let partial = sidecar_path(&path, ".partial");
+        let written = self.db.backup_to(&partial, passphrase, |copied, total| {
+            self.emit_progress("backup", copied, total);
+        });
+        if let Err(e) = written {
+            let _ = std::fs::remove_file(&partial);
+            return Err(e);
+        }
+        std::fs::rename(&partial, &path)?;
+
+        let info = BackupInfo::from_path(&path)
+            .ok_or_else(|| WmsError::Unknown(format!("Unable to read backup {}", file_name)))?;
+
+        info!("Created backup {} ({} bytes)", info.file_name, info.size_bytes);
+        Ok(info)
+    }
+
+    /// List backups in `directory`, newest first
+    pub async fn list_backups(&self, directory: &Path) -> Result<Vec<BackupInfo>> {
+        let mut backups: Vec<BackupInfo> = std::fs::read_dir(directory)?
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| BackupInfo::from_path(&entry.path()))
+            .collect();
+
+        backups.sort_by_key(|b| std::cmp::Reverse(b.created_at));
+        Ok(backups)
+    }
+
+    /// Replace the live database with a backup.
+    ///
+    /// The sync engine must be told to re-handshake afterwards, since the
+    /// restored sync cursors no longer match the server.
+    pub async fn restore_backup(&self, path: &Path, passphrase: Option<&str>) -> Result<()> {
+        if !path.is_file() {
+            return Err(WmsError::not_found(format!("Backup {:?} not found", path)));
+        }
+
+        let staged = sidecar_path(self.db.path(), ".restore");
+        let restored = self.copy_with_progress(path, &staged)
+            .and_then(|_| self.prepare_staged(&staged, passphrase))
+            .and_then(|_| self.db.replace_with(&staged));
+
+        if let Err(e) = restored {
+            if staged.exists() {
+                let _ = std::fs::remove_file(&staged);
+            }
+            warn!("Restore from {:?} failed: {}", path, e);
+            return Err(e);
+        }
+
+        info!("Restored database from {:?}", path);
+        Ok(())
+    }
+
+    /// Validate a staged backup and re-key it to the live database key
+    fn prepare_staged(&self, staged: &Path, passphrase: Option<&str>) -> Result<()> {
+        let conn = Connection::open(staged)?;
+        conn.pragma_update(None, "key", passphrase.unwrap_or(self.db.encryption_key()))?;
+
+        let applied = applied_migrations(&conn)
+            .map(|applied| applied.into_iter().map(|m| m.name).collect::<Vec<_>>())
+            .map_err(|_| {
+                WmsError::validation("Unable to read backup: wrong passphrase or not a WMS backup")
+            })?;
+        check_schema_compatibility(&applied)?;
+
+        if passphrase.is_some() {
+            conn.pragma_update(None, "rekey", self.db.encryption_key())?;
+        }
+
+        conn.close().map_err(|(_, e)| WmsError::from(e))
+    }
+
+    fn copy_with_progress(&self, from: &Path, to: &Path) -> Result<()> {
+        let total = std::fs::metadata(from)?.len();
+        let mut reader = File::open(from)?;
+        let mut writer = File::create(to)?;
+        let mut buffer = vec![0u8; COPY_CHUNK_BYTES];
+        let mut copied = 0u64;
+
+        loop {
+            let read = reader.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            writer.write_all(&buffer[..read])?;
+            copied += read as u64;
+            self.emit_progress("restore", copied, total);
+        }
+
+        writer.sync_all()?;
+        Ok(())
+    }
+
+    fn emit_progress(&self, operation: &str, completed: u64, total: u64) {
+        self.events.emit(WmsEvent::BackupProgress {
+            operation: operation.to_string(),
+            completed,
+            total,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use crate::events::RecordingEmitter;
+    use crate::types::new_id;
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("wms-backup-test-{}", new_id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn live_db(dir: &Path) -> Arc<Database> {
+        let db = Database::new(&dir.join("wms.db"), "live-key").unwrap();
+        db.migrate_to_latest().unwrap();
+        db.execute("INSERT INTO settings (key, value) VALUES ('marker', 'before')", []).unwrap();
+        Arc::new(db)
+    }
+
+    fn marker(db: &Database) -> String {
+        db.query_row("SELECT value FROM settings WHERE key = 'marker'", [], |row| row.get(0))
+            .unwrap()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_backup_and_list_with_progress() {
+        let dir = temp_dir();
+        let backups_dir = dir.join("backups");
+        std::fs::create_dir_all(&backups_dir).unwrap();
+
+        let events = Arc::new(RecordingEmitter::new());
+        let service = BackupService::new(live_db(&dir)).with_event_emitter(events.clone());
+
+        let backup = service.create_backup(&backups_dir, None).await.unwrap();
+        assert!(backup.file_name.starts_with(BACKUP_PREFIX));
+        assert!(backup.size_bytes > 0);
+
+        let listed = service.list_backups(&backups_dir).await.unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].file_name, backup.file_name);
+
+        let progress = events.events();
+        assert!(!progress.is_empty());
+        assert!(matches!(
+            progress.last(),
+            Some(WmsEvent::BackupProgress { completed, total, .. }) if completed == total
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_restore_older_schema_runs_migrations() {
+        let dir = temp_dir();
+        let db = live_db(&dir);
+        let service = BackupService::new(db.clone());
+        let backup = service.create_backup(&dir, None).await.unwrap();
+
+        // Roll the backup back to the schema before the latest migration
+        {
+            let conn = Connection::open(&backup.path).unwrap();
+            conn.pragma_update(None, "key", "live-key").unwrap();
+            conn.execute_batch(
+                "DELETE FROM schema_migrations WHERE name = '010_location_coordinates';
+                 ALTER TABLE locations DROP COLUMN x_m;
+                 ALTER TABLE locations DROP COLUMN y_m;",
+            ).unwrap();
+        }
+
+        db.execute("UPDATE settings SET value = 'after' WHERE key = 'marker'", []).unwrap();
+        service.restore_backup(Path::new(&backup.path), None).await.unwrap();
+
+        assert_eq!(marker(&db), "before");
+        let migrated: i64 = db.query_row(
+            "SELECT COUNT(*) FROM schema_migrations WHERE name = '010_location_coordinates'",
+            [],
+            |row| row.get(0),
+        ).unwrap().unwrap();
+        assert_eq!(migrated, 1);
+        db.query_map("SELECT x_m, y_m FROM locations", [], |row| row.get::<_, Option<f64>>(0)).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_restore_rejects_newer_schema() {
+        let dir = temp_dir();
+        let db = live_db(&dir);
+        let service = BackupService::new(db.clone());
+        let backup = service.create_backup(&dir, None).await.unwrap();
+
+        {
+            let conn = Connection::open(&backup.path).unwrap();
+            conn.pragma_update(None, "key", "live-key").unwrap();
+            conn.execute(
+                "INSERT INTO schema_migrations (version, name, checksum) VALUES (999, '999_from_the_future', '')",
+                [],
+            ).unwrap();
+        }
+
+        db.execute("UPDATE settings SET value = 'after' WHERE key = 'marker'", []).unwrap();
+        let result = service.restore_backup(Path::new(&backup.path), None).await;
+
+        assert!(matches!(result, Err(WmsError::Validation(_))));
+        // The live database is untouched
+        assert_eq!(marker(&db), "after");
+    }
+
+    #[tokio::test]
+    async fn test_passphrase_protected_backup() {
+        let dir = temp_dir();
+        let db = live_db(&dir);
+        let service = BackupService::new(db.clone());
+        let backup = service.create_backup(&dir, Some("tablet-7")).await.unwrap();
+
+        db.execute("UPDATE settings SET value = 'after' WHERE key = 'marker'", []).unwrap();
+
+        let wrong = service.restore_backup(Path::new(&backup.path), Some("wrong")).await;
+        assert!(matches!(wrong, Err(WmsError::Validation(_))));
+        assert_eq!(marker(&db), "after");
+
+        service.restore_backup(Path::new(&backup.path), Some("tablet-7")).await.unwrap();
+        assert_eq!(marker(&db), "before");
+    }
+}
@@ -0,0 +1,71 @@
+//! Change Capture
+//!
+//! Services report every record they write through a `ChangeRecorder`
+//! handle, on the same connection as the write, so the change and its
+//! record for sync are stored together. The default recorder queues the
+//! change in `sync_outbox` for the sync engine to send.
+
+use std::sync::Arc;
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use crate::error::Result;
+use crate::types::new_id;
+
+/// Kind of write being recorded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Insert,
+    Update,
+}
+
+impl ChangeKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Insert => "INSERT",
+            Self::Update => "UPDATE",
+        }
+    }
+}
+
+/// Sink for changes to synced records
+pub trait ChangeRecorder: Send + Sync {
+    /// Record a write to `record_id` in `table`; `payload` is the record as JSON
+    fn record(&self, conn: &Connection, table: &str, record_id: &str, kind: ChangeKind, payload: &str) -> Result<()>;
+}
+
+impl dyn ChangeRecorder {
+    /// Record a write with the model serialized as its payload
+    pub fn record_model<T: Serialize>(
+        &self,
+        conn: &Connection,
+        table: &str,
+        record_id: &str,
+        kind: ChangeKind,
+        model: &T,
+    ) -> Result<()> {
+        self.record(conn, table, record_id, kind, &serde_json::to_string(model)?)
+    }
+}
+
+/// Shared recorder handle held by services
+pub type SharedRecorder = Arc<dyn ChangeRecorder>;
+
+/// Recorder that queues changes in `sync_outbox`
+pub struct OutboxRecorder;
+
+impl ChangeRecorder for OutboxRecorder {
+    fn record(&self, conn: &Connection, table: &str, record_id: &str, kind: ChangeKind, payload: &str) -> Result<()> {
+        conn.execute(
+            "INSERT INTO sync_outbox (id, table_name, record_id, operation, payload, version, created_at)
+             VALUES (?, ?, ?, ?, ?, 1, datetime('now'))",
+            params![new_id(), table, record_id, kind.as_str(), payload],
+        )?;
+        Ok(())
+    }
+}
+
+/// Create a recorder handle that queues changes for sync
+pub fn outbox_recorder() -> SharedRecorder {
+    Arc::new(OutboxRecorder)
+}
+
@@ -0,0 +1,83 @@
+//! Clock
+//!
+//! Services read the current time through a `Clock` handle instead of calling
+//! `Utc::now()` directly, so time-dependent rules (overtime, forecast
+//! windows, expiry) can be tested against a pinned instant.
+
+use std::sync::{Arc, Mutex};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+
+/// Source of the current time
+pub trait Clock: Send + Sync {
+    /// Current instant
+    fn now(&self) -> DateTime<Utc>;
+
+    /// Current UTC date
+    fn today(&self) -> NaiveDate {
+        self.now().date_naive()
+    }
+}
+
+/// Shared clock handle held by services
+pub type SharedClock = Arc<dyn Clock>;
+
+/// Clock backed by the system time
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// Create a clock handle that reads the system time
+pub fn system_clock() -> SharedClock {
+    Arc::new(SystemClock)
+}
+
+/// Clock that stays at a set instant until moved (for tests)
+pub struct FixedClock {
+    now: Mutex<DateTime<Utc>>,
+}
+
+impl FixedClock {
+    /// Create a clock pinned at `now`
+    pub fn new(now: DateTime<Utc>) -> Self {
+        Self { now: Mutex::new(now) }
+    }
+
+    /// Move the clock to `now`
+    pub fn set(&self, now: DateTime<Utc>) {
+        if let Ok(mut current) = self.now.lock() {
+            *current = now;
+        }
+    }
+
+    /// Move the clock forward by `by`
+    pub fn advance(&self, by: Duration) {
+        if let Ok(mut current) = self.now.lock() {
+            *current += by;
+        }
+    }
+}
+
+impl Clock for FixedClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.now.lock().map(|now| *now).unwrap_or_else(|e| *e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixed_clock_moves_only_when_told() {
+        let start = DateTime::parse_from_rfc3339("2025-03-31T23:30:00Z").unwrap().with_timezone(&Utc);
+        let clock = FixedClock::new(start);
+        assert_eq!(clock.now(), start);
+
+        clock.advance(Duration::hours(1));
+        assert_eq!(clock.today(), NaiveDate::from_ymd_opt(2025, 4, 1).unwrap());
+    }
+}
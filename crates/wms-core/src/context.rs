@@ -0,0 +1,144 @@
+//! Request Context
+//!
+//! Who is acting, and from where, for one command invocation. Commands
+//! build a context from the session and hand it to services, which take the
+//! acting user from it instead of from loose `user_id` arguments, and check
+//! permissions against its role.
+//!
+//! Without a session every scope is denied. Only background work and tests
+//! run unrestricted, through `for_user`.
+
+use tracing::Span;
+use crate::auth::{Role, Scope, Session};
+use crate::error::{WmsError, Result};
+use crate::types::new_id;
+
+/// Identity and origin of one request
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequestContext {
+    /// Correlates the log lines of one invocation
+    pub request_id: String,
+    /// Acting user, if known
+    pub user_id: Option<String>,
+    /// Role of the signed-in user
+    pub role: Option<Role>,
+    pub warehouse_id: Option<String>,
+    pub device_id: Option<String>,
+    /// Set for background work and tests, which act without a session
+    pub(crate) unrestricted: bool,
+}
+
+impl RequestContext {
+    /// Context of the signed-in user; without a session every scope is
+    /// denied
+    pub fn new(session: Option<&Session>) -> Self {
+        Self {
+            request_id: new_id(),
+            user_id: session.map(|s| s.user_id.clone()),
+            role: session.map(|s| s.role),
+            warehouse_id: None,
+            device_id: None,
+            unrestricted: false,
+        }
+    }
+
+    /// Unrestricted context acting as `user_id`, for background work and
+    /// tests
+    pub fn for_user(user_id: &str) -> Self {
+        Self { user_id: Some(user_id.to_string()), unrestricted: true, ..Self::new(None) }
+    }
+
+    pub fn with_warehouse(mut self, warehouse_id: Option<String>) -> Self {
+        self.warehouse_id = warehouse_id;
+        self
+    }
+
+    pub fn with_device(mut self, device_id: Option<String>) -> Self {
+        self.device_id = device_id;
+        self
+    }
+
+    /// The acting user, for recording who did something
+    pub fn actor(&self) -> Result<&str> {
+        self.user_id.as_deref()
+            .ok_or_else(|| WmsError::validation("A user is required for this action"))
+    }
+
+    fn session(&self) -> Option<Session> {
+        Some(Session { user_id: self.user_id.clone()?, role: self.role? })
+    }
+
+    /// Fail unless the context's role may use `scope`
+    pub fn require(&self, scope: Scope) -> Result<()> {
+        match self.session() {
+            Some(session) => session.require(scope),
+            None if self.unrestricted => Ok(()),
+            None => Err(signed_out()),
+        }
+    }
+
+    /// Fail unless the context may see or record `user_id`'s time
+    pub fn require_timesheet_of(&self, user_id: &str) -> Result<()> {
+        match self.session() {
+            Some(session) => session.require_timesheet_of(user_id),
+            None if self.unrestricted => Ok(()),
+            None => Err(signed_out()),
+        }
+    }
+
+    /// Driver whose deliveries the request is limited to; `None` sees all
+    pub fn delivery_driver(&self) -> Option<&str> {
+        (self.role == Some(Role::Driver)).then_some(self.user_id.as_deref()).flatten()
+    }
+
+    /// Span carrying the request id, for instrumenting the request's work
+    pub fn span(&self) -> Span {
+        tracing::info_span!(
+            "request",
+            request_id = %self.request_id,
+            user_id = self.user_id.as_deref().unwrap_or("-"),
+        )
+    }
+}
+
+fn signed_out() -> WmsError {
+    WmsError::permission_denied("Sign in to continue")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_limits_the_context() {
+        let driver = Session { user_id: "d1".to_string(), role: Role::Driver };
+        let ctx = RequestContext::new(Some(&driver));
+
+        assert_eq!(ctx.actor().unwrap(), "d1");
+        assert_eq!(ctx.delivery_driver(), Some("d1"));
+        assert!(ctx.require(Scope::Deliveries).is_ok());
+        assert!(ctx.require(Scope::Inventory).is_err());
+        assert!(ctx.require_timesheet_of("u2").is_err());
+    }
+
+    #[test]
+    fn test_context_without_session_is_denied() {
+        let ctx = RequestContext::new(None);
+        assert!(ctx.actor().is_err());
+        let err = ctx.require(Scope::Dashboard).unwrap_err();
+        assert!(matches!(err, WmsError::PermissionDenied(_)));
+        assert_eq!(ctx.delivery_driver(), None);
+        assert!(ctx.require_timesheet_of("u2").is_err());
+
+        assert_ne!(RequestContext::new(None).request_id, ctx.request_id);
+    }
+
+    #[test]
+    fn test_background_context_is_unrestricted() {
+        let ctx = RequestContext::for_user("u2");
+        assert_eq!(ctx.actor().unwrap(), "u2");
+        assert!(ctx.require(Scope::Settings).is_ok());
+        assert!(ctx.require_timesheet_of("u3").is_ok());
+        assert_eq!(ctx.delivery_driver(), None);
+    }
+}
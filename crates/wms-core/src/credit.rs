@@ -0,0 +1,113 @@
+//! Customer Credit
+//!
+//! A customer's credit limit caps the value of goods they may have on
+//! order. Their exposure is the value of their shipments that are neither
+//! delivered nor cancelled, priced once confirmed and declared before
+//! that; a new order must fit under the limit together with it. Customers without a limit are unlimited.
+//! Orders past the limit need a supervisor's `credit_limit` override for
+//! the customer.
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use crate::db::Database;
+use crate::error::{WmsError, Result};
+
+/// Override entity type of credit limit overrides, which are approved for
+/// the customer
+pub const CUSTOMER_OWNER: &str = "customer";
+
+/// A customer's credit position for a new order
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CreditCheck {
+    pub customer_id: String,
+    /// None when the customer is unlimited
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<f64>,
+    /// Value of the customer's shipments not yet delivered
+    pub exposure: f64,
+    /// Value of the new order
+    pub requested: f64,
+}
+
+impl CreditCheck {
+    /// Credit left after the new order; None when unlimited
+    pub fn remaining(&self) -> Option<f64> {
+        self.limit.map(|limit| limit - self.exposure - self.requested)
+    }
+
+    /// The new order takes the customer past their limit
+    pub fn exceeded(&self) -> bool {
+        // Allow for rounding in summed currency amounts
+        self.remaining().is_some_and(|remaining| remaining < -1e-6)
+    }
+
+    /// The error rejecting the order
+    pub fn error(&self) -> WmsError {
+        WmsError::CreditLimitExceeded {
+            limit: self.limit.unwrap_or_default(),
+            exposure: self.exposure,
+            requested: self.requested,
+        }
+    }
+}
+
+/// Work out a customer's credit position for an order worth `requested`
+pub fn check_credit(db: &Database, customer_id: &str, requested: f64) -> Result<CreditCheck> {
+    db.transaction(|conn| check_credit_in(conn, customer_id, requested, None))
+}
+
+/// `check_credit` inside a transaction, leaving out the shipment
+/// `excluding` when it is the order being checked. Checking and writing
+/// the order in one transaction keeps two orders from both fitting under
+/// the same headroom.
+pub fn check_credit_in(
+    conn: &Connection,
+    customer_id: &str,
+    requested: f64,
+    excluding: Option<&str>,
+) -> Result<CreditCheck> {
+    if !requested.is_finite() || requested < 0.0 {
+        return Err(WmsError::validation("Order value must be zero or more"));
+    }
+
+    let limit: Option<f64> = conn.query_row(
+        "SELECT credit_limit FROM customers WHERE id = ?",
+        params![customer_id],
+        |row| row.get(0),
+    )
+    .optional()?
+    .ok_or_else(|| WmsError::not_found("Customer not found"))?;
+
+    let exposure: f64 = conn.query_row(
+        "SELECT COALESCE(SUM(COALESCE(order_total, declared_value)), 0) FROM shipments
+         WHERE customer_id = ?1 AND status NOT IN ('delivered', 'cancelled')
+           AND (?2 IS NULL OR id != ?2)",
+        params![customer_id, excluding],
+        |row| row.get(0),
+    )?;
+
+    Ok(CreditCheck {
+        customer_id: customer_id.to_string(),
+        limit,
+        exposure,
+        requested,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(limit: Option<f64>, exposure: f64, requested: f64) -> CreditCheck {
+        CreditCheck { customer_id: "cust-1".to_string(), limit, exposure, requested }
+    }
+
+    #[test]
+    fn test_limit_is_inclusive_and_unlimited_never_exceeds() {
+        assert!(!check(Some(1000.0), 600.0, 400.0).exceeded());
+        assert!(check(Some(1000.0), 600.0, 400.01).exceeded());
+        assert!(!check(None, 1e9, 1e9).exceeded());
+        // 0.1 + 0.2 is not exactly 0.3
+        assert!(!check(Some(0.3), 0.1, 0.2).exceeded());
+    }
+}
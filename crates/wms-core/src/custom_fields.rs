@@ -0,0 +1,498 @@
+//! Custom Fields
+//!
+//! Deployments add their own fields to items, customers, shipments and
+//! deliveries ("customer PO line", "hazmat class") without a schema
+//! change. Each field is defined once per entity with a type and whether
+//! it is required; values are kept as a JSON object in the record's
+//! `custom_fields` column, keyed by the field's key.
+//!
+//! Services check a record's values against the definitions when it is
+//! created or updated, and store the cleaned-up values: numbers parsed,
+//! blanks dropped. Every problem is reported at once, prefixed with the
+//! field's label, so a form can show them next to the fields.
+
+use std::sync::Arc;
+use chrono::{DateTime, NaiveDate, Utc};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tracing::info;
+use crate::clock::{SharedClock, system_clock};
+use crate::db::Database;
+use crate::error::{WmsError, Result};
+use crate::types::{new_id, required_timestamp, stored_status};
+
+/// Custom field values of one record, by field key
+pub type CustomFieldValues = Map<String, Value>;
+
+/// Kind of record a custom field belongs to
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum CustomFieldEntity {
+    InventoryItem,
+    Customer,
+    Shipment,
+    Delivery,
+}
+
+impl CustomFieldEntity {
+    pub const ALL: [Self; 4] = [Self::InventoryItem, Self::Customer, Self::Shipment, Self::Delivery];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::InventoryItem => "inventory_item",
+            Self::Customer => "customer",
+            Self::Shipment => "shipment",
+            Self::Delivery => "delivery",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|e| e.as_str() == s)
+    }
+
+    /// Table whose `custom_fields` column holds the values
+    fn table(&self) -> &'static str {
+        match self {
+            Self::InventoryItem => "inventory_items",
+            Self::Customer => "customers",
+            Self::Shipment => "shipments",
+            Self::Delivery => "deliveries",
+        }
+    }
+}
+
+/// Type of value a custom field holds
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CustomFieldType {
+    Text,
+    Number,
+    /// A calendar date, "YYYY-MM-DD"
+    Date,
+    /// One of the definition's options
+    Select,
+}
+
+impl CustomFieldType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Text => "text",
+            Self::Number => "number",
+            Self::Date => "date",
+            Self::Select => "select",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "text" => Some(Self::Text),
+            "number" => Some(Self::Number),
+            "date" => Some(Self::Date),
+            "select" => Some(Self::Select),
+            _ => None,
+        }
+    }
+
+    /// Whether text search looks at values of this type
+    pub fn is_searchable(&self) -> bool {
+        matches!(self, Self::Text | Self::Select)
+    }
+}
+
+/// A custom field of one entity
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CustomFieldDefinition {
+    /// Empty when creating a definition
+    #[serde(default)]
+    pub id: String,
+    pub entity: CustomFieldEntity,
+    /// Key of the value in `custom_fields`; fixed once created
+    pub key: String,
+    pub label: String,
+    pub field_type: CustomFieldType,
+    /// Allowed values of a select field
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub options: Vec<String>,
+    #[serde(default)]
+    pub required: bool,
+    /// Show as a column in the entity's list
+    #[serde(default)]
+    pub show_in_list: bool,
+    /// Position on forms and in exports, lowest first
+    #[serde(default)]
+    pub sort_order: i32,
+    #[serde(default = "Utc::now")]
+    pub created_at: DateTime<Utc>,
+}
+
+impl CustomFieldDefinition {
+    /// The value as stored, or why it is not allowed. Blank values are
+    /// None.
+    fn clean(&self, value: &Value) -> std::result::Result<Option<Value>, String> {
+        let text = match value {
+            Value::Null => return Ok(None),
+            Value::String(s) if s.trim().is_empty() => return Ok(None),
+            Value::String(s) => Some(s.trim()),
+            _ => None,
+        };
+
+        match self.field_type {
+            CustomFieldType::Text => match text {
+                Some(s) => Ok(Some(Value::String(s.to_string()))),
+                None => Ok(Some(Value::String(value.to_string()))),
+            },
+            CustomFieldType::Number => match (value, text) {
+                (Value::Number(_), _) => Ok(Some(value.clone())),
+                (_, Some(s)) => s.parse::<f64>()
+                    .ok()
+                    .filter(|n| n.is_finite())
+                    .and_then(serde_json::Number::from_f64)
+                    .map(|n| Some(Value::Number(n)))
+                    .ok_or_else(|| format!("'{}' is not a number", s)),
+                _ => Err("must be a number".to_string()),
+            },
+            CustomFieldType::Date => match text {
+                Some(s) => NaiveDate::parse_from_str(s, "%Y-%m-%d")
+                    .map(|d| Some(Value::String(d.to_string())))
+                    .map_err(|_| format!("'{}' is not a date (YYYY-MM-DD)", s)),
+                None => Err("must be a date (YYYY-MM-DD)".to_string()),
+            },
+            CustomFieldType::Select => match text {
+                Some(s) if self.options.iter().any(|o| o == s) => Ok(Some(Value::String(s.to_string()))),
+                _ => Err(format!("must be one of {}", self.options.join(", "))),
+            },
+        }
+    }
+}
+
+/// Check `values` against an entity's `definitions`, returning them as
+/// they should be stored. Unknown keys, missing required fields and values
+/// of the wrong type are all reported in one validation error.
+pub fn validate_custom_fields(
+    definitions: &[CustomFieldDefinition],
+    values: &CustomFieldValues,
+) -> Result<CustomFieldValues> {
+    let mut cleaned = CustomFieldValues::new();
+    let mut problems = Vec::new();
+
+    for key in values.keys() {
+        if !definitions.iter().any(|d| &d.key == key) {
+            problems.push(format!("{}: not a custom field here", key));
+        }
+    }
+    for definition in definitions {
+        let value = values.get(&definition.key).unwrap_or(&Value::Null);
+        match definition.clean(value) {
+            Ok(Some(value)) => {
+                cleaned.insert(definition.key.clone(), value);
+            }
+            Ok(None) if definition.required => problems.push(format!("{}: is required", definition.label)),
+            Ok(None) => {}
+            Err(problem) => problems.push(format!("{}: {}", definition.label, problem)),
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(cleaned)
+    } else {
+        Err(WmsError::validation(problems.join("; ")))
+    }
+}
+
+/// Stored form of custom field values: a JSON object, or NULL without any
+pub fn custom_fields_json(values: &CustomFieldValues) -> Option<String> {
+    if values.is_empty() {
+        None
+    } else {
+        serde_json::to_string(values).ok()
+    }
+}
+
+/// Custom field values read from a `custom_fields` column
+pub fn parse_custom_fields(json: Option<String>) -> CustomFieldValues {
+    json.and_then(|json| serde_json::from_str(&json).ok()).unwrap_or_default()
+}
+
+/// A value as text, for CSV columns
+pub fn display_value(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(s)) => s.clone(),
+        Some(other) => other.to_string(),
+    }
+}
+
+/// SQL condition matching rows of `entity` (aliased `alias`) whose text or
+/// select custom fields contain the next `?` parameter
+pub fn search_condition(entity: CustomFieldEntity, alias: &str) -> String {
+    format!(
+        "EXISTS (SELECT 1 FROM json_each(COALESCE({alias}.custom_fields, '{{}}')) f
+            JOIN custom_field_definitions d
+              ON d.entity = '{entity}' AND d.field_key = f.key AND d.field_type IN ('text', 'select')
+            WHERE f.value LIKE '%' || ? || '%')",
+        alias = alias,
+        entity = entity.as_str(),
+    )
+}
+
+/// Custom field definitions
+pub struct CustomFieldService {
+    db: Arc<Database>,
+    clock: SharedClock,
+}
+
+impl CustomFieldService {
+    pub fn new(db: Arc<Database>) -> Self {
+        Self { db, clock: system_clock() }
+    }
+
+    /// Read the current time from the given clock
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Definitions of one entity, or of all, in form order
+    pub fn list_definitions(&self, entity: Option<CustomFieldEntity>) -> Result<Vec<CustomFieldDefinition>> {
+        self.db.query_map(
+            "SELECT * FROM custom_field_definitions
+             WHERE ?1 IS NULL OR entity = ?1
+             ORDER BY entity, sort_order, label",
+            params![entity.map(|e| e.as_str())],
+            row_to_definition,
+        )
+    }
+
+    /// Create a definition (empty `id`) or change one. The entity and key
+    /// of an existing definition cannot change, since stored values are
+    /// keyed by them.
+    pub fn save_definition(&self, mut definition: CustomFieldDefinition) -> Result<CustomFieldDefinition> {
+        definition.key = definition.key.trim().to_string();
+        definition.label = definition.label.trim().to_string();
+        definition.options = definition.options.iter()
+            .map(|o| o.trim().to_string())
+            .filter(|o| !o.is_empty())
+            .collect();
+
+        if definition.key.is_empty()
+            || !definition.key.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        {
+            return Err(WmsError::validation(format!(
+                "Field key '{}' must be lowercase letters, digits and underscores", definition.key
+            )));
+        }
+        if definition.label.is_empty() {
+            return Err(WmsError::validation("Field label is required"));
+        }
+        if definition.field_type == CustomFieldType::Select && definition.options.is_empty() {
+            return Err(WmsError::validation(format!("Select field {} needs options", definition.label)));
+        }
+        let options = serde_json::to_string(&definition.options)?;
+
+        if definition.id.is_empty() {
+            let taken = self.db.query_row(
+                "SELECT 1 FROM custom_field_definitions WHERE entity = ? AND field_key = ?",
+                params![definition.entity.as_str(), &definition.key],
+                |row| row.get::<_, i32>(0),
+            )?.is_some();
+            if taken {
+                return Err(WmsError::conflict(format!(
+                    "{} already has a custom field {}", definition.entity.as_str(), definition.key
+                )));
+            }
+
+            definition.id = new_id();
+            definition.created_at = self.clock.now();
+            self.db.execute(
+                "INSERT INTO custom_field_definitions (
+                    id, entity, field_key, label, field_type, options, required, show_in_list,
+                    sort_order, created_at
+                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                params![
+                    &definition.id,
+                    definition.entity.as_str(),
+                    &definition.key,
+                    &definition.label,
+                    definition.field_type.as_str(),
+                    &options,
+                    definition.required,
+                    definition.show_in_list,
+                    definition.sort_order,
+                    definition.created_at.to_rfc3339(),
+                ],
+            )?;
+            info!("Added custom field {} to {}", definition.key, definition.entity.as_str());
+            return Ok(definition);
+        }
+
+        let current = self.get_definition(&definition.id)?;
+        if current.entity != definition.entity || current.key != definition.key {
+            return Err(WmsError::validation("A custom field's entity and key cannot change"));
+        }
+        self.db.execute(
+            "UPDATE custom_field_definitions SET
+                label = ?, field_type = ?, options = ?, required = ?, show_in_list = ?,
+                sort_order = ?, updated_at = ?
+             WHERE id = ?",
+            params![
+                &definition.label,
+                definition.field_type.as_str(),
+                &options,
+                definition.required,
+                definition.show_in_list,
+                definition.sort_order,
+                self.clock.now().to_rfc3339(),
+                &definition.id,
+            ],
+        )?;
+        definition.created_at = current.created_at;
+        Ok(definition)
+    }
+
+    /// Remove a definition along with its stored values
+    pub fn delete_definition(&self, id: &str) -> Result<()> {
+        let definition = self.get_definition(id)?;
+        self.db.transaction(|tx| {
+            tx.execute(
+                &format!(
+                    "UPDATE {} SET custom_fields = json_remove(custom_fields, '$.' || ?)
+                     WHERE custom_fields IS NOT NULL",
+                    definition.entity.table()
+                ),
+                params![&definition.key],
+            )?;
+            tx.execute("DELETE FROM custom_field_definitions WHERE id = ?", params![id])?;
+            Ok(())
+        })?;
+        info!("Removed custom field {} from {}", definition.key, definition.entity.as_str());
+        Ok(())
+    }
+
+    /// Check a record's values against its entity's definitions
+    pub fn validate(&self, entity: CustomFieldEntity, values: &CustomFieldValues) -> Result<CustomFieldValues> {
+        validate_custom_fields(&self.list_definitions(Some(entity))?, values)
+    }
+
+    fn get_definition(&self, id: &str) -> Result<CustomFieldDefinition> {
+        self.db.query_row(
+            "SELECT * FROM custom_field_definitions WHERE id = ?",
+            params![id],
+            row_to_definition,
+        )?.ok_or_else(|| WmsError::not_found(format!("Custom field {} not found", id)))
+    }
+}
+
+fn row_to_definition(row: &rusqlite::Row) -> rusqlite::Result<CustomFieldDefinition> {
+    Ok(CustomFieldDefinition {
+        id: row.get("id")?,
+        entity: stored_status(row, "entity", CustomFieldEntity::parse)?,
+        key: row.get("field_key")?,
+        label: row.get("label")?,
+        field_type: stored_status(row, "field_type", CustomFieldType::parse)?,
+        options: row.get::<_, Option<String>>("options")?
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default(),
+        required: row.get("required")?,
+        show_in_list: row.get("show_in_list")?,
+        sort_order: row.get("sort_order")?,
+        created_at: required_timestamp(row, "created_at")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::test_support::test_db;
+
+    fn definition(key: &str, label: &str, field_type: CustomFieldType) -> CustomFieldDefinition {
+        CustomFieldDefinition {
+            id: String::new(),
+            entity: CustomFieldEntity::InventoryItem,
+            key: key.to_string(),
+            label: label.to_string(),
+            field_type,
+            options: Vec::new(),
+            required: false,
+            show_in_list: false,
+            sort_order: 0,
+            created_at: Utc::now(),
+        }
+    }
+
+    fn values(value: Value) -> CustomFieldValues {
+        value.as_object().cloned().unwrap()
+    }
+
+    #[test]
+    fn test_values_checked_against_definitions() {
+        let hazmat = CustomFieldDefinition {
+            options: vec!["3".to_string(), "8".to_string()],
+            required: true,
+            ..definition("hazmat_class", "Hazmat class", CustomFieldType::Select)
+        };
+        let definitions = vec![
+            hazmat,
+            definition("case_qty", "Case qty", CustomFieldType::Number),
+            definition("first_sold", "First sold", CustomFieldType::Date),
+            definition("notes", "Notes", CustomFieldType::Text),
+        ];
+
+        let cleaned = validate_custom_fields(&definitions, &values(json!({
+            "hazmat_class": "8",
+            "case_qty": " 12 ",
+            "first_sold": "2025-01-15",
+            "notes": "",
+        }))).unwrap();
+        assert_eq!(Value::Object(cleaned), json!({
+            "hazmat_class": "8",
+            "case_qty": 12.0,
+            "first_sold": "2025-01-15",
+        }));
+
+        let err = validate_custom_fields(&definitions, &values(json!({
+            "case_qty": "a dozen",
+            "first_sold": "15/01/2025",
+            "colour": "red",
+        }))).unwrap_err().to_string();
+        assert!(err.contains("colour: not a custom field here"));
+        assert!(err.contains("Hazmat class: is required"));
+        assert!(err.contains("Case qty: 'a dozen' is not a number"));
+        assert!(err.contains("First sold: '15/01/2025' is not a date"));
+    }
+
+    #[test]
+    fn test_definition_crud_strips_deleted_values() {
+        let db = test_db();
+        let service = CustomFieldService::new(db.clone());
+
+        let saved = service.save_definition(definition("po_line", "Customer PO line", CustomFieldType::Text)).unwrap();
+        assert!(!saved.id.is_empty());
+        assert!(service.save_definition(definition("po_line", "Again", CustomFieldType::Text)).is_err());
+        assert!(service.save_definition(definition("PO Line", "Bad key", CustomFieldType::Text)).is_err());
+        assert!(service.save_definition(definition("class", "No options", CustomFieldType::Select)).is_err());
+
+        let renamed = service.save_definition(CustomFieldDefinition {
+            label: "PO line".to_string(),
+            required: true,
+            ..saved.clone()
+        }).unwrap();
+        assert_eq!(renamed.label, "PO line");
+        assert!(service.validate(CustomFieldEntity::InventoryItem, &CustomFieldValues::new()).is_err());
+        assert!(service.validate(CustomFieldEntity::Customer, &CustomFieldValues::new()).is_ok());
+
+        db.execute(
+            "INSERT INTO inventory_items (id, sku, name, custom_fields) VALUES ('i1', 'SKU-1', 'Widget', ?)",
+            params![r#"{"po_line":"7","other":"x"}"#],
+        ).unwrap();
+        service.delete_definition(&saved.id).unwrap();
+        assert!(service.list_definitions(None).unwrap().is_empty());
+        let stored: String = db.query_row(
+            "SELECT custom_fields FROM inventory_items WHERE id = 'i1'",
+            [],
+            |row| row.get(0),
+        ).unwrap().unwrap();
+        assert_eq!(serde_json::from_str::<Value>(&stored).unwrap(), json!({"other": "x"}));
+    }
+}
@@ -0,0 +1,779 @@
+//! Dashboard Metrics
+//!
+//! Headline counts for the dashboard, each compared with the same point in
+//! the previous period: today against the same weekday last week, this
+//! week to date against last week to the same weekday, and this month to
+//! date against last month to the same day. Stock levels in the past are
+//! rebuilt from the inventory transaction log.
+//!
+//! A background aggregator also stores one value per metric per day in
+//! `metrics_history`, which the dashboard sparklines read. Recording a day
+//! again overwrites its values, so the job can run as often as it likes.
+//!
+//! The activity feed merges the latest changes to shipments, receipts and
+//! deliveries with stock adjustments, newest first.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use chrono::{DateTime, Datelike, Duration, Months, NaiveDate, NaiveDateTime, Utc};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+use crate::clock::{SharedClock, system_clock};
+use crate::db::Database;
+use crate::error::Result;
+use crate::shutdown::TaskHandle;
+use crate::types::DateRange;
+
+/// Days of history behind each dashboard sparkline
+pub const SPARKLINE_DAYS: u32 = 14;
+
+/// Longest history `get_metric_history` returns
+pub const MAX_HISTORY_DAYS: u32 = 366;
+
+/// Entries in the activity feed when the caller doesn't say
+pub const DEFAULT_ACTIVITY_LIMIT: u32 = 10;
+
+/// Most entries `recent_activity` returns
+pub const MAX_ACTIVITY_LIMIT: u32 = 50;
+
+/// Delivery statuses that are finished one way or another
+const CLOSED_DELIVERY_STATUSES: &str = "'delivered', 'failed', 'returned'";
+
+/// Period the dashboard compares against
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum MetricPeriod {
+    Day,
+    #[default]
+    Week,
+    Month,
+}
+
+impl MetricPeriod {
+    /// The current period up to `today` and the comparable span of the
+    /// previous one
+    pub fn ranges(&self, today: NaiveDate) -> (DateRange, DateRange) {
+        match self {
+            Self::Day => {
+                let last_week = today - Duration::days(7);
+                (DateRange::new(today, today), DateRange::new(last_week, last_week))
+            }
+            Self::Week => {
+                let monday = today - Duration::days(today.weekday().num_days_from_monday() as i64);
+                (
+                    DateRange::new(monday, today),
+                    DateRange::new(monday - Duration::days(7), today - Duration::days(7)),
+                )
+            }
+            Self::Month => {
+                let first = today.with_day(1).unwrap_or(today);
+                let previous_first = first - Months::new(1);
+                // Clamped to the end of shorter months (Mar 31 -> Feb 28)
+                let previous_end = today - Months::new(1);
+                (DateRange::new(first, today), DateRange::new(previous_first, previous_end))
+            }
+        }
+    }
+}
+
+/// A metric's value now and at the same point in the previous period
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct MetricComparison {
+    pub current: f64,
+    /// None when there is no history to compare against
+    pub previous: Option<f64>,
+    /// None when there is no previous value or it is zero
+    pub change_percent: Option<f64>,
+}
+
+impl MetricComparison {
+    pub fn new(current: f64, previous: Option<f64>) -> Self {
+        Self {
+            current,
+            previous,
+            change_percent: percent_change(current, previous),
+        }
+    }
+}
+
+/// Percentage change from `previous` to `current`; None when it can't be
+/// expressed (no previous value, or a previous value of zero)
+pub fn percent_change(current: f64, previous: Option<f64>) -> Option<f64> {
+    let previous = previous?;
+    if previous == 0.0 || !previous.is_finite() || !current.is_finite() {
+        return None;
+    }
+    Some((current - previous) * 100.0 / previous.abs())
+}
+
+/// Headline dashboard metrics with prior-period comparisons
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DashboardMetrics {
+    pub period: MetricPeriod,
+    pub current_range: DateRange,
+    pub previous_range: DateRange,
+    /// Shipments created in the period
+    pub shipments: MetricComparison,
+    /// Deliveries scheduled in the period
+    pub deliveries: MetricComparison,
+    /// Items at or below their reorder point at the end of the period
+    pub low_stock_items: MetricComparison,
+    /// Units on hand at the end of the period
+    pub inventory_total: MetricComparison,
+    /// Active items in the catalogue
+    pub active_items: u64,
+    /// Shipments created each day of the week containing today, Monday
+    /// first
+    pub shipments_this_week: Vec<ShipmentDay>,
+    /// Deliveries scheduled for today
+    pub deliveries_today: DeliveryProgress,
+    /// Local changes the sync server hasn't acknowledged yet
+    pub pending_sync_changes: u64,
+}
+
+/// Shipments created on one day, by their current status
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ShipmentDay {
+    pub date: NaiveDate,
+    pub total: u64,
+    pub by_status: BTreeMap<String, u64>,
+}
+
+/// How far through a day's deliveries the drivers are
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DeliveryProgress {
+    pub completed: u64,
+    /// Neither delivered nor failed or returned yet
+    pub pending: u64,
+}
+
+/// Kind of record an activity feed entry is about
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ActivityKind {
+    Shipment,
+    Receipt,
+    Delivery,
+    Adjustment,
+}
+
+impl ActivityKind {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "shipment" => Some(Self::Shipment),
+            "receipt" => Some(Self::Receipt),
+            "delivery" => Some(Self::Delivery),
+            "adjustment" => Some(Self::Adjustment),
+            _ => None,
+        }
+    }
+}
+
+/// One entry of the dashboard activity feed
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ActivityEntry {
+    pub kind: ActivityKind,
+    /// Id of the shipment, receipt or delivery, or of the adjusted item
+    pub record_id: String,
+    pub title: String,
+    pub occurred_at: DateTime<Utc>,
+}
+
+/// Metric kept as a daily history
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DailyMetric {
+    /// Shipments created that day
+    Shipments,
+    /// Deliveries scheduled for that day
+    Deliveries,
+    /// Pick transactions that day
+    Picks,
+    /// Items at or below their reorder point at the end of the day
+    LowStockItems,
+    /// Units on hand at the end of the day
+    InventoryTotal,
+}
+
+impl DailyMetric {
+    pub const ALL: [Self; 5] = [
+        Self::Shipments,
+        Self::Deliveries,
+        Self::Picks,
+        Self::LowStockItems,
+        Self::InventoryTotal,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Shipments => "shipments",
+            Self::Deliveries => "deliveries",
+            Self::Picks => "picks",
+            Self::LowStockItems => "low_stock_items",
+            Self::InventoryTotal => "inventory_total",
+        }
+    }
+}
+
+/// A metric's value on one day
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct MetricPoint {
+    pub date: NaiveDate,
+    pub value: f64,
+}
+
+/// On-hand total and reorder point of each active item
+type StockLevels = Vec<(f64, Option<f64>)>;
+
+/// Computes dashboard metrics from the shared database
+pub struct DashboardService {
+    db: Arc<Database>,
+    clock: SharedClock,
+}
+
+impl DashboardService {
+    pub fn new(db: Arc<Database>) -> Self {
+        Self { db, clock: system_clock() }
+    }
+
+    /// Read the current time from the given clock
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Metrics for the period containing `today`
+    pub async fn get_metrics(&self, period: MetricPeriod, today: NaiveDate) -> Result<DashboardMetrics> {
+        let (current_range, previous_range) = period.ranges(today);
+
+        let shipments = MetricComparison::new(
+            self.count_in_range("SELECT COUNT(*) FROM shipments WHERE date(created_at) BETWEEN ? AND ?", current_range)?,
+            Some(self.count_in_range("SELECT COUNT(*) FROM shipments WHERE date(created_at) BETWEEN ? AND ?", previous_range)?),
+        );
+        let deliveries = MetricComparison::new(
+            self.count_in_range("SELECT COUNT(*) FROM deliveries WHERE date(scheduled_date) BETWEEN ? AND ?", current_range)?,
+            Some(self.count_in_range("SELECT COUNT(*) FROM deliveries WHERE date(scheduled_date) BETWEEN ? AND ?", previous_range)?),
+        );
+
+        let current_stock = self.current_stock()?;
+        let previous_stock = self.stock_at(previous_range.end)?;
+        let low_stock = |stock: &[(f64, Option<f64>)]| {
+            stock.iter()
+                .filter(|(quantity, reorder_point)| reorder_point.is_some_and(|r| *quantity <= r))
+                .count() as f64
+        };
+        let total = |stock: &[(f64, Option<f64>)]| stock.iter().map(|(quantity, _)| quantity).sum::<f64>();
+
+        let monday = today - Duration::days(today.weekday().num_days_from_monday() as i64);
+
+        Ok(DashboardMetrics {
+            period,
+            current_range,
+            previous_range,
+            shipments,
+            deliveries,
+            low_stock_items: MetricComparison::new(
+                low_stock(&current_stock),
+                previous_stock.as_deref().map(low_stock),
+            ),
+            inventory_total: MetricComparison::new(
+                total(&current_stock),
+                previous_stock.as_deref().map(total),
+            ),
+            active_items: self.count("SELECT COUNT(*) FROM inventory_items WHERE is_active = 1")?,
+            shipments_this_week: self.shipments_by_day(DateRange::new(monday, monday + Duration::days(6)))?,
+            deliveries_today: self.delivery_progress(today)?,
+            pending_sync_changes: self.count("SELECT COUNT(*) FROM sync_outbox WHERE acknowledged_at IS NULL")?,
+        })
+    }
+
+    /// The latest `limit` changes to shipments, receipts and deliveries and
+    /// stock adjustments, newest first
+    pub async fn recent_activity(&self, limit: u32) -> Result<Vec<ActivityEntry>> {
+        let limit = limit.clamp(1, MAX_ACTIVITY_LIMIT);
+        // Timestamps are written both as RFC 3339 and as SQLite's own
+        // format; datetime() brings them to one so they sort together
+        let rows = self.db.query_map(
+            "SELECT kind, record_id, reference, detail, occurred_at FROM (
+                SELECT * FROM (
+                    SELECT 'shipment' AS kind, id AS record_id, shipment_number AS reference, status AS detail,
+                           datetime(COALESCE(updated_at, created_at)) AS occurred_at
+                    FROM shipments ORDER BY occurred_at DESC LIMIT ?1
+                )
+                UNION ALL
+                SELECT * FROM (
+                    SELECT 'receipt', id, receipt_number, status,
+                           datetime(COALESCE(completed_at, created_at)) AS occurred_at
+                    FROM receipts ORDER BY occurred_at DESC LIMIT ?1
+                )
+                UNION ALL
+                SELECT * FROM (
+                    SELECT 'delivery', id, delivery_number, status,
+                           datetime(COALESCE(actual_departure_time, actual_arrival_time, updated_at, created_at)) AS occurred_at
+                    FROM deliveries ORDER BY occurred_at DESC LIMIT ?1
+                )
+                UNION ALL
+                SELECT * FROM (
+                    SELECT 'adjustment', t.item_id, i.sku, printf('%+g', t.quantity),
+                           datetime(t.created_at) AS occurred_at
+                    FROM inventory_transactions t
+                    JOIN inventory_items i ON i.id = t.item_id
+                    WHERE t.transaction_type = 'ADJUST'
+                    ORDER BY occurred_at DESC LIMIT ?1
+                )
+             )
+             WHERE occurred_at IS NOT NULL
+             ORDER BY occurred_at DESC, kind, reference
+             LIMIT ?1",
+            params![limit],
+            |row| Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, String>(4)?,
+            )),
+        )?;
+
+        Ok(rows.into_iter()
+            .filter_map(|(kind, record_id, reference, detail, occurred_at)| {
+                let kind = ActivityKind::parse(&kind)?;
+                let detail = detail.replace('_', " ");
+                let title = match kind {
+                    ActivityKind::Shipment => format!("Shipment {} {}", reference, detail),
+                    ActivityKind::Receipt => format!("Receipt {} {}", reference, detail),
+                    ActivityKind::Delivery => format!("Delivery {} {}", reference, detail),
+                    ActivityKind::Adjustment => format!("Stock of {} adjusted by {}", reference, detail),
+                };
+                Some(ActivityEntry {
+                    kind,
+                    record_id,
+                    title,
+                    occurred_at: NaiveDateTime::parse_from_str(&occurred_at, "%Y-%m-%d %H:%M:%S").ok()?.and_utc(),
+                })
+            })
+            .collect())
+    }
+
+    /// Store each metric's value for `day`, replacing any earlier values
+    /// for that day. Stock metrics for a past day are rebuilt from the
+    /// transaction log and skipped if it doesn't reach back that far.
+    /// Returns the number of metrics written.
+    pub async fn record_daily_metrics(&self, day: NaiveDate) -> Result<usize> {
+        let range = DateRange::new(day, day);
+        let mut values = vec![
+            (
+                DailyMetric::Shipments,
+                self.count_in_range("SELECT COUNT(*) FROM shipments WHERE date(created_at) BETWEEN ? AND ?", range)?,
+            ),
+            (
+                DailyMetric::Deliveries,
+                self.count_in_range("SELECT COUNT(*) FROM deliveries WHERE date(scheduled_date) BETWEEN ? AND ?", range)?,
+            ),
+            (
+                DailyMetric::Picks,
+                self.count_in_range(
+                    "SELECT COUNT(*) FROM inventory_transactions
+                     WHERE transaction_type = 'PICK' AND date(created_at) BETWEEN ? AND ?",
+                    range,
+                )?,
+            ),
+        ];
+
+        let stock = if day >= self.clock.now().date_naive() {
+            Some(self.current_stock()?)
+        } else {
+            self.stock_at(day)?
+        };
+        if let Some(stock) = stock {
+            let low_stock = stock.iter()
+                .filter(|(quantity, reorder_point)| reorder_point.is_some_and(|r| *quantity <= r))
+                .count() as f64;
+            values.push((DailyMetric::LowStockItems, low_stock));
+            values.push((DailyMetric::InventoryTotal, stock.iter().map(|(quantity, _)| quantity).sum()));
+        }
+
+        let day = day.to_string();
+        let recorded_at = self.clock.now().to_rfc3339();
+        self.db.transaction(|conn| {
+            for (metric, value) in &values {
+                conn.execute(
+                    "INSERT INTO metrics_history (metric, day, value, recorded_at) VALUES (?, ?, ?, ?)
+                     ON CONFLICT(metric, day) DO UPDATE SET
+                        value = excluded.value,
+                        recorded_at = excluded.recorded_at",
+                    params![metric.as_str(), &day, value, &recorded_at],
+                )?;
+            }
+            Ok(())
+        })?;
+
+        debug!("Recorded {} dashboard metrics for {}", values.len(), day);
+        Ok(values.len())
+    }
+
+    /// Recorded daily values of `metric` over the `days` days ending
+    /// `today`, oldest first. Days the aggregator didn't run are missing.
+    pub async fn get_metric_history(
+        &self,
+        metric: DailyMetric,
+        days: u32,
+        today: NaiveDate,
+    ) -> Result<Vec<MetricPoint>> {
+        let days = days.clamp(1, MAX_HISTORY_DAYS);
+        let start = today - Duration::days(days as i64 - 1);
+
+        let points = self.db.query_map(
+            "SELECT day, value FROM metrics_history
+             WHERE metric = ? AND day BETWEEN ? AND ?
+             ORDER BY day",
+            params![metric.as_str(), start.to_string(), today.to_string()],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, f64>(1)?)),
+        )?;
+
+        Ok(points.into_iter()
+            .filter_map(|(day, value)| Some(MetricPoint { date: day.parse().ok()?, value }))
+            .collect())
+    }
+
+    /// Record today's metrics every `interval` until shutdown. Yesterday is
+    /// recorded again too, so activity after the last run before midnight
+    /// still lands in its day.
+    pub async fn run_aggregator(self: Arc<Self>, interval: std::time::Duration, task: TaskHandle) {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {}
+                _ = task.shutdown_requested() => break,
+            }
+            let today = self.clock.now().date_naive();
+            for day in [today - Duration::days(1), today] {
+                if let Err(e) = self.record_daily_metrics(day).await {
+                    warn!("Unable to record dashboard metrics for {}: {}", day, e);
+                }
+            }
+        }
+        info!("Dashboard aggregator stopped");
+    }
+
+    fn count(&self, sql: &str) -> Result<u64> {
+        let count: i64 = self.db.query_row(sql, [], |row| row.get(0))?.unwrap_or(0);
+        Ok(count.max(0) as u64)
+    }
+
+    /// Shipments created on each day of `range`, days without any included
+    fn shipments_by_day(&self, range: DateRange) -> Result<Vec<ShipmentDay>> {
+        let counts = self.db.query_map(
+            "SELECT date(created_at), status, COUNT(*) FROM shipments
+             WHERE date(created_at) BETWEEN ? AND ?
+             GROUP BY date(created_at), status",
+            params![range.start.to_string(), range.end.to_string()],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, i64>(2)?)),
+        )?;
+
+        Ok(range.start.iter_days()
+            .take_while(|date| *date <= range.end)
+            .map(|date| {
+                let day = date.to_string();
+                let by_status: BTreeMap<String, u64> = counts.iter()
+                    .filter(|(created, _, _)| *created == day)
+                    .map(|(_, status, count)| (status.clone(), *count as u64))
+                    .collect();
+                ShipmentDay { date, total: by_status.values().sum(), by_status }
+            })
+            .collect())
+    }
+
+    /// Completed and open deliveries scheduled for `day`
+    fn delivery_progress(&self, day: NaiveDate) -> Result<DeliveryProgress> {
+        let (completed, pending): (i64, i64) = self.db.query_row(
+            &format!(
+                "SELECT COALESCE(SUM(status = 'delivered'), 0),
+                        COALESCE(SUM(status NOT IN ({})), 0)
+                 FROM deliveries WHERE date(scheduled_date) = ?",
+                CLOSED_DELIVERY_STATUSES
+            ),
+            params![day.to_string()],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?.unwrap_or_default();
+        Ok(DeliveryProgress { completed: completed as u64, pending: pending as u64 })
+    }
+
+    fn count_in_range(&self, sql: &str, range: DateRange) -> Result<f64> {
+        let count: i64 = self.db.query_row(
+            sql,
+            params![range.start.to_string(), range.end.to_string()],
+            |row| row.get(0),
+        )?.unwrap_or(0);
+        Ok(count as f64)
+    }
+
+    /// On-hand quantity and reorder point of each active item
+    fn current_stock(&self) -> Result<StockLevels> {
+        self.db.query_map(
+            "SELECT COALESCE(SUM(s.quantity), 0), i.reorder_point
+             FROM inventory_items i
+             LEFT JOIN inventory_stock s ON i.id = s.item_id
+             WHERE i.is_active = 1
+             GROUP BY i.id",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+    }
+
+    /// Stock of each item at the end of `date`, rebuilt from transactions.
+    /// None when the transaction log doesn't reach back that far.
+    fn stock_at(&self, date: NaiveDate) -> Result<Option<StockLevels>> {
+        let date = date.to_string();
+        let has_history: bool = self.db.query_row(
+            "SELECT EXISTS(SELECT 1 FROM inventory_transactions WHERE date(created_at) <= ?)",
+            params![&date],
+            |row| row.get(0),
+        )?.unwrap_or(false);
+        if !has_history {
+            return Ok(None);
+        }
+
+        // Last total on or before the date, else the total before the first
+        // later change, else the current total (no changes since)
+        let stock = self.db.query_map(
+            "SELECT COALESCE(
+                (SELECT t.new_quantity FROM inventory_transactions t
+                 WHERE t.item_id = i.id AND date(t.created_at) <= ?1
+                 ORDER BY t.created_at DESC, t.rowid DESC LIMIT 1),
+                (SELECT t.previous_quantity FROM inventory_transactions t
+                 WHERE t.item_id = i.id AND date(t.created_at) > ?1
+                 ORDER BY t.created_at, t.rowid LIMIT 1),
+                (SELECT COALESCE(SUM(s.quantity), 0) FROM inventory_stock s WHERE s.item_id = i.id)
+             ), i.reorder_point
+             FROM inventory_items i
+             WHERE i.is_active = 1 AND date(i.created_at) <= ?1",
+            params![&date],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        Ok(Some(stock))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use serde_json::Value;
+    use crate::test_support::{fixed_clock, test_db, DeliveryBuilder, ItemBuilder, ShipmentBuilder};
+
+    fn date(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    fn range(start: &str, end: &str) -> DateRange {
+        DateRange::new(date(start), date(end))
+    }
+
+    #[test]
+    fn test_week_spanning_new_year() {
+        // Wednesday 2025-01-01: the week started in December
+        let (current, previous) = MetricPeriod::Week.ranges(date("2025-01-01"));
+        assert_eq!(current, range("2024-12-30", "2025-01-01"));
+        assert_eq!(previous, range("2024-12-23", "2024-12-25"));
+
+        // Monday 2025-02-03 compares against the last days of January
+        let (current, previous) = MetricPeriod::Week.ranges(date("2025-02-03"));
+        assert_eq!(current, range("2025-02-03", "2025-02-03"));
+        assert_eq!(previous, range("2025-01-27", "2025-01-27"));
+
+        let (current, previous) = MetricPeriod::Day.ranges(date("2025-01-03"));
+        assert_eq!(current, range("2025-01-03", "2025-01-03"));
+        assert_eq!(previous, range("2024-12-27", "2024-12-27"));
+    }
+
+    #[test]
+    fn test_month_comparisons_clamp_and_cross_years() {
+        let (current, previous) = MetricPeriod::Month.ranges(date("2025-01-15"));
+        assert_eq!(current, range("2025-01-01", "2025-01-15"));
+        assert_eq!(previous, range("2024-12-01", "2024-12-15"));
+
+        let (current, previous) = MetricPeriod::Month.ranges(date("2025-03-31"));
+        assert_eq!(current, range("2025-03-01", "2025-03-31"));
+        assert_eq!(previous, range("2025-02-01", "2025-02-28"));
+    }
+
+    #[test]
+    fn test_percent_change_without_baseline() {
+        assert_eq!(percent_change(15.0, Some(10.0)), Some(50.0));
+        assert_eq!(percent_change(5.0, Some(10.0)), Some(-50.0));
+        assert_eq!(percent_change(5.0, Some(0.0)), None);
+        assert_eq!(percent_change(5.0, None), None);
+    }
+
+    #[tokio::test]
+    async fn test_metrics_compare_with_last_week() {
+        let db = Database::new(&PathBuf::from(":memory:"), "test-key").unwrap();
+        db.migrate_to_latest().unwrap();
+        db.execute(
+            "INSERT INTO users (id, username, email, full_name) VALUES ('u1', 'clerk', 'clerk@example.com', 'Casey Clerk')",
+            [],
+        ).unwrap();
+        let shipments = [
+            ("s1", "2024-12-23T09:00:00+00:00"),
+            ("s2", "2024-12-24T09:00:00+00:00"),
+            ("s3", "2024-12-30T09:00:00+00:00"),
+            ("s4", "2024-12-31T09:00:00+00:00"),
+            ("s5", "2025-01-01T09:00:00+00:00"),
+            // Later in the previous week than today's weekday; not comparable
+            ("s6", "2024-12-27T09:00:00+00:00"),
+        ];
+        for (id, created_at) in shipments {
+            db.execute(
+                "INSERT INTO shipments (
+                    id, shipment_number, ship_to_name, ship_to_address_line1, ship_to_city,
+                    ship_to_state, ship_to_postal_code, created_by, created_at
+                ) VALUES (?, ?, 'Acme', '1 Main St', 'Springfield', 'IL', '62701', 'u1', ?)",
+                params![id, id, created_at],
+            ).unwrap();
+        }
+        db.execute(
+            "INSERT INTO inventory_items (id, sku, name, reorder_point, created_at)
+             VALUES ('widget', 'WID-1', 'Widget', 5, '2024-12-01T00:00:00+00:00')",
+            [],
+        ).unwrap();
+        db.execute("INSERT INTO locations (id, code, zone) VALUES ('loc1', 'A-01-01', 'STORAGE')", []).unwrap();
+        db.execute("INSERT INTO inventory_stock (id, item_id, location_id, quantity) VALUES ('st1', 'widget', 'loc1', 4)", []).unwrap();
+        for (id, previous, new, at) in [("t1", 0.0, 20.0, "2024-12-20 08:00:00"), ("t2", 20.0, 4.0, "2024-12-31 08:00:00")] {
+            db.execute(
+                "INSERT INTO inventory_transactions (id, item_id, transaction_type, quantity, previous_quantity, new_quantity, user_id, created_at)
+                 VALUES (?, 'widget', 'PICK', ?, ?, ?, 'u1', ?)",
+                params![id, previous - new, previous, new, at],
+            ).unwrap();
+        }
+
+        let service = DashboardService::new(Arc::new(db));
+        let metrics = service.get_metrics(MetricPeriod::Week, date("2025-01-01")).await.unwrap();
+
+        assert_eq!(metrics.shipments, MetricComparison::new(3.0, Some(2.0)));
+        assert_eq!(metrics.shipments.change_percent, Some(50.0));
+        // No deliveries either week: no trend rather than infinity
+        assert_eq!(metrics.deliveries.change_percent, None);
+        assert_eq!(metrics.inventory_total, MetricComparison::new(4.0, Some(20.0)));
+        assert_eq!(metrics.low_stock_items.previous, Some(0.0));
+        assert_eq!(metrics.low_stock_items.change_percent, None);
+
+        // Before the transaction log starts there is nothing to compare with
+        let metrics = service.get_metrics(MetricPeriod::Month, date("2024-12-10")).await.unwrap();
+        assert_eq!(metrics.inventory_total.previous, None);
+    }
+
+    #[tokio::test]
+    async fn test_recording_a_day_again_replaces_its_values() {
+        let db = test_db();
+        ItemBuilder::new("WID-1").reorder_point(10.0).stock("loc1", 4.0).insert::<Value>(&db);
+        ShipmentBuilder::new("SHP-1").insert::<Value>(&db);
+        let service = DashboardService::new(db.clone()).with_clock(fixed_clock());
+        let today = date("2025-01-15");
+
+        assert_eq!(service.record_daily_metrics(today).await.unwrap(), 5);
+        ShipmentBuilder::new("SHP-2").insert::<Value>(&db);
+        service.record_daily_metrics(today).await.unwrap();
+
+        let shipments = service.get_metric_history(DailyMetric::Shipments, SPARKLINE_DAYS, today).await.unwrap();
+        assert_eq!(shipments, vec![MetricPoint { date: today, value: 2.0 }]);
+        let low_stock = service.get_metric_history(DailyMetric::LowStockItems, SPARKLINE_DAYS, today).await.unwrap();
+        assert_eq!(low_stock, vec![MetricPoint { date: today, value: 1.0 }]);
+
+        // No transaction log before today, so a past day only gets counts
+        assert_eq!(service.record_daily_metrics(date("2025-01-14")).await.unwrap(), 3);
+        let shipments = service.get_metric_history(DailyMetric::Shipments, SPARKLINE_DAYS, today).await.unwrap();
+        assert_eq!(shipments.iter().map(|p| p.value).collect::<Vec<_>>(), vec![0.0, 2.0]);
+        assert_eq!(service.get_metric_history(DailyMetric::Shipments, 1, today).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_shipments_are_bucketed_by_day_of_this_week() {
+        let db = test_db();
+        ItemBuilder::new("WID-1").stock("loc1", 4.0).insert::<Value>(&db);
+        ItemBuilder::new("OLD-1").insert::<Value>(&db);
+        db.execute("UPDATE inventory_items SET is_active = 0 WHERE id = 'old-1'", []).unwrap();
+        let shipments = [
+            // Sunday of the previous week
+            ("SHP-1", "pending", "2025-01-12T23:30:00+00:00"),
+            ("SHP-2", "pending", "2025-01-13T08:00:00+00:00"),
+            ("SHP-3", "shipped", "2025-01-13 17:45:00"),
+            ("SHP-4", "shipped", "2025-01-15T09:00:00Z"),
+            ("SHP-5", "shipped", "2025-01-15T10:00:00Z"),
+            ("SHP-6", "pending", "2025-01-19T23:59:00+00:00"),
+            ("SHP-7", "pending", "2025-01-20T00:00:00+00:00"),
+        ];
+        for (number, status, created_at) in shipments {
+            ShipmentBuilder::new(number).status(status).insert::<Value>(&db);
+            db.execute(
+                "UPDATE shipments SET created_at = ? WHERE shipment_number = ?",
+                params![created_at, number],
+            ).unwrap();
+        }
+        DeliveryBuilder::new("DEL-1").status("delivered").insert::<Value>(&db);
+        DeliveryBuilder::new("DEL-2").status("en_route").insert::<Value>(&db);
+        DeliveryBuilder::new("DEL-3").status("failed").insert::<Value>(&db);
+        DeliveryBuilder::new("DEL-4").scheduled(date("2025-01-16")).insert::<Value>(&db);
+        for (id, acknowledged_at) in [("o1", None), ("o2", Some("2025-01-15 08:00:00"))] {
+            db.execute(
+                "INSERT INTO sync_outbox (id, table_name, record_id, operation, payload, version, acknowledged_at)
+                 VALUES (?, 'shipments', 'shp-2', 'UPDATE', '{}', 2, ?)",
+                params![id, acknowledged_at],
+            ).unwrap();
+        }
+
+        let service = DashboardService::new(db.clone());
+        let metrics = service.get_metrics(MetricPeriod::Week, date("2025-01-15")).await.unwrap();
+
+        let week = &metrics.shipments_this_week;
+        assert_eq!(week.iter().map(|day| day.date).collect::<Vec<_>>(),
+            date("2025-01-13").iter_days().take(7).collect::<Vec<_>>());
+        assert_eq!(week.iter().map(|day| day.total).collect::<Vec<_>>(), vec![2, 0, 2, 0, 0, 0, 1]);
+        assert_eq!(week[0].by_status, BTreeMap::from([("pending".to_string(), 1), ("shipped".to_string(), 1)]));
+        assert_eq!(week[2].by_status, BTreeMap::from([("shipped".to_string(), 2)]));
+        assert!(week[1].by_status.is_empty());
+
+        assert_eq!(metrics.active_items, 1);
+        assert_eq!(metrics.deliveries_today, DeliveryProgress { completed: 1, pending: 1 });
+        assert_eq!(metrics.pending_sync_changes, 1);
+    }
+
+    #[tokio::test]
+    async fn test_activity_is_merged_newest_first() {
+        let db = test_db();
+        ItemBuilder::new("WID-1").insert::<Value>(&db);
+        ShipmentBuilder::new("SHP-1").status("in_transit").insert::<Value>(&db);
+        db.execute("UPDATE shipments SET updated_at = '2025-01-15T11:00:00+00:00' WHERE id = 'shp-1'", []).unwrap();
+        ShipmentBuilder::new("SHP-2").insert::<Value>(&db);
+        DeliveryBuilder::new("DEL-1").status("delivered").insert::<Value>(&db);
+        db.execute("UPDATE deliveries SET actual_departure_time = '2025-01-15T12:30:00Z' WHERE id = 'del-1'", []).unwrap();
+        db.execute(
+            "INSERT INTO receipts (id, receipt_number, status, created_by, created_at, completed_at)
+             VALUES ('r1', 'RCV-1', 'completed', 'u1', '2025-01-14 16:00:00', '2025-01-15T10:15:00+00:00')",
+            [],
+        ).unwrap();
+        db.execute(
+            "INSERT INTO inventory_transactions (id, item_id, transaction_type, quantity, previous_quantity, new_quantity, user_id, created_at)
+             VALUES ('t1', 'wid-1', 'ADJUST', -3, 10, 7, 'u1', '2025-01-15 13:00:00')",
+            [],
+        ).unwrap();
+
+        let service = DashboardService::new(db.clone());
+        let activity = service.recent_activity(DEFAULT_ACTIVITY_LIMIT).await.unwrap();
+        let titles: Vec<&str> = activity.iter().map(|entry| entry.title.as_str()).collect();
+        assert_eq!(titles, vec![
+            "Stock of WID-1 adjusted by -3",
+            "Delivery DEL-1 delivered",
+            "Shipment SHP-1 in transit",
+            "Receipt RCV-1 completed",
+            "Shipment SHP-2 draft",
+        ]);
+        assert_eq!(activity[0].kind, ActivityKind::Adjustment);
+        assert_eq!(activity[0].record_id, "wid-1");
+        assert_eq!(activity[1].occurred_at.to_rfc3339(), "2025-01-15T12:30:00+00:00");
+
+        let latest = service.recent_activity(2).await.unwrap();
+        assert_eq!(latest, activity[..2].to_vec());
+    }
+}
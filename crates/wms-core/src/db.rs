@@ -3,20 +3,64 @@
 //! Provides SQLite database connection management with SQLCipher encryption
 //! and schema migration support.
 
-use std::path::Path;
-use std::sync::Mutex;
-use rusqlite::{Connection, params};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+use rusqlite::backup::{Backup, StepResult};
+use rusqlite::{Connection, TransactionBehavior, params};
+use sha2::{Digest, Sha256};
 use tracing::{info, debug};
 use crate::error::{WmsError, Result};
 
-/// Database wrapper providing thread-safe access to SQLite with encryption
+/// Read connections opened alongside the writer
+const READ_CONNECTIONS: usize = 4;
+
+/// How long a statement waits on a lock held by another connection before
+/// failing with SQLITE_BUSY
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Pages copied per step of an online backup
+const BACKUP_PAGES_PER_STEP: i32 = 256;
+
+/// Warehouse that locations created before warehouses existed, or created
+/// without one, belong to
+pub const DEFAULT_WAREHOUSE_ID: &str = "default";
+
+/// Database wrapper providing thread-safe access to SQLite with encryption.
+///
+/// Writes go through a single connection, so writers queue in-process
+/// rather than on SQLite's file lock. Statements that only read run on a
+/// small pool of read connections, which under WAL proceed alongside the
+/// writer and each other.
 pub struct Database {
-    conn: Mutex<Connection>,
+    writer: Mutex<Connection>,
+    /// Empty for in-memory databases, where each connection would be a
+    /// separate database
+    readers: Vec<Mutex<Connection>>,
+    next_reader: AtomicUsize,
+    path: PathBuf,
+    encryption_key: String,
 }
 
 impl Database {
     /// Create a new database connection with encryption
     pub fn new(path: &Path, encryption_key: &str) -> Result<Self> {
+        let writer = Self::open_connection(path, encryption_key)?;
+        let readers = Self::open_readers(path, encryption_key)?;
+        
+        info!("Database connection established with {} readers", readers.len());
+        
+        Ok(Self {
+            writer: Mutex::new(writer),
+            readers,
+            next_reader: AtomicUsize::new(0),
+            path: path.to_path_buf(),
+            encryption_key: encryption_key.to_string(),
+        })
+    }
+    
+    fn open_connection(path: &Path, encryption_key: &str) -> Result<Connection> {
         let conn = Connection::open(path)?;
         
         // Enable SQLCipher encryption
@@ -28,52 +72,92 @@ impl Database {
         // Enable WAL mode for better concurrent access
         conn.pragma_update(None, "journal_mode", "WAL")?;
         
-        info!("Database connection established");
+        // Wait out checkpoints and other processes instead of failing
+        conn.busy_timeout(BUSY_TIMEOUT)?;
         
-        Ok(Self {
-            conn: Mutex::new(conn),
-        })
+        Ok(conn)
     }
     
-    /// Run all database migrations
-    pub fn run_migrations(&self) -> Result<()> {
-        let conn = self.conn.lock().map_err(|_| WmsError::LockError)?;
-        
-        // Create migrations tracking table
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS _migrations (
-                id INTEGER PRIMARY KEY,
-                name TEXT NOT NULL UNIQUE,
-                applied_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
-            )",
-            [],
-        )?;
-        
-        // Apply migrations
-        let migrations = get_migrations();
-        for (name, sql) in migrations {
-            if !self.migration_applied(&conn, name)? {
-                info!("Applying migration: {}", name);
-                conn.execute_batch(sql)?;
-                conn.execute(
-                    "INSERT INTO _migrations (name) VALUES (?)",
-                    params![name],
-                )?;
-            } else {
-                debug!("Migration already applied: {}", name);
-            }
+    fn open_readers(path: &Path, encryption_key: &str) -> Result<Vec<Mutex<Connection>>> {
+        if path.as_os_str().is_empty() || path == Path::new(":memory:") {
+            return Ok(Vec::new());
         }
-        
-        Ok(())
+        (0..READ_CONNECTIONS)
+            .map(|_| Self::open_connection(path, encryption_key).map(Mutex::new))
+            .collect()
     }
     
-    fn migration_applied(&self, conn: &Connection, name: &str) -> Result<bool> {
-        let count: i64 = conn.query_row(
-            "SELECT COUNT(*) FROM _migrations WHERE name = ?",
-            params![name],
-            |row| row.get(0),
-        )?;
-        Ok(count > 0)
+    fn writer(&self) -> Result<MutexGuard<'_, Connection>> {
+        self.writer.lock().map_err(|_| WmsError::LockError)
+    }
+    
+    /// A free read connection, or the next one in turn if all are busy
+    fn reader(&self) -> Result<Option<MutexGuard<'_, Connection>>> {
+        if self.readers.is_empty() {
+            return Ok(None);
+        }
+        if let Some(conn) = self.readers.iter().find_map(|reader| reader.try_lock().ok()) {
+            return Ok(Some(conn));
+        }
+        let next = self.next_reader.fetch_add(1, Ordering::Relaxed) % self.readers.len();
+        self.readers[next].lock().map(Some).map_err(|_| WmsError::LockError)
+    }
+    
+    /// Run `f` on a read connection if `sql` only reads, and on the writer
+    /// otherwise
+    fn with_connection_for<T>(&self, sql: &str, f: impl FnOnce(&Connection) -> Result<T>) -> Result<T> {
+        if let Some(reader) = self.reader()?
+            && reader.prepare_cached(sql)?.readonly()
+        {
+            return f(&reader);
+        }
+        // The reader is released first; `replace_with` takes the writer
+        // before the readers
+        f(&*self.writer()?)
+    }
+    
+    /// Location of the database file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    
+    pub(crate) fn encryption_key(&self) -> &str {
+        &self.encryption_key
+    }
+    
+    /// Bring the schema up to date, returning the migrations applied.
+    /// Each migration runs in its own transaction together with its
+    /// `schema_migrations` row. Nothing is applied if the database was
+    /// migrated by a newer build or an applied migration has changed.
+    pub fn migrate_to_latest(&self) -> Result<Vec<&'static str>> {
+        let mut conn = self.writer()?;
+        migrate(&mut conn, &get_migrations())
+    }
+    
+    /// Check the schema against this build without changing it, returning
+    /// the pending migrations. Fails like `migrate_to_latest` would.
+    pub fn check_schema(&self) -> Result<Vec<&'static str>> {
+        let conn = self.writer()?;
+        let migrations = get_migrations();
+        let applied = applied_migrations(&conn)?;
+        verify_applied(&applied, &migrations)?;
+        Ok(pending(&applied, &migrations).map(|m| m.name).collect())
+    }
+    
+    /// Migrations known to this build that haven't been applied, in order
+    pub fn pending_migrations(&self) -> Result<Vec<&'static str>> {
+        let conn = self.writer()?;
+        let migrations = get_migrations();
+        let applied = applied_migrations(&conn)?;
+        Ok(pending(&applied, &migrations).map(|m| m.name).collect())
+    }
+    
+    /// Rebuild every index and refresh the query planner's statistics
+    pub fn reindex(&self) -> Result<()> {
+        let conn = self.writer()?;
+        conn.execute_batch("REINDEX; ANALYZE;")?;
+        info!("Database indexes rebuilt");
+        Ok(())
     }
     
     /// Execute a query with parameters
@@ -81,7 +165,7 @@ impl Database {
     where
         P: rusqlite::Params,
     {
-        let conn = self.conn.lock().map_err(|_| WmsError::LockError)?;
+        let conn = self.writer()?;
         let rows = conn.execute(sql, params)?;
         Ok(rows)
     }
@@ -92,15 +176,16 @@ impl Database {
         P: rusqlite::Params,
         F: FnMut(&rusqlite::Row<'_>) -> rusqlite::Result<T>,
     {
-        let conn = self.conn.lock().map_err(|_| WmsError::LockError)?;
-        let mut stmt = conn.prepare(sql)?;
-        let rows = stmt.query_map(params, f)?;
-        
-        let mut results = Vec::new();
-        for row in rows {
-            results.push(row?);
-        }
-        Ok(results)
+        self.with_connection_for(sql, |conn| {
+            let mut stmt = conn.prepare_cached(sql)?;
+            let rows = stmt.query_map(params, f)?;
+            
+            let mut results = Vec::new();
+            for row in rows {
+                results.push(row?);
+            }
+            Ok(results)
+        })
     }
     
     /// Query a single row
@@ -109,37 +194,397 @@ impl Database {
         P: rusqlite::Params,
         F: FnOnce(&rusqlite::Row<'_>) -> rusqlite::Result<T>,
     {
-        let conn = self.conn.lock().map_err(|_| WmsError::LockError)?;
-        match conn.query_row(sql, params, f) {
-            Ok(result) => Ok(Some(result)),
-            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
-            Err(e) => Err(e.into()),
+        self.with_connection_for(sql, |conn| {
+            match conn.prepare_cached(sql)?.query_row(params, f) {
+                Ok(result) => Ok(Some(result)),
+                Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+                Err(e) => Err(e.into()),
+            }
+        })
+    }
+    
+    /// Read an application setting
+    pub fn get_setting(&self, key: &str) -> Result<Option<String>> {
+        self.query_row(
+            "SELECT value FROM settings WHERE key = ?",
+            params![key],
+            |row| row.get(0),
+        )
+    }
+    
+    /// Create or update an application setting
+    pub fn set_setting(&self, key: &str, value: &str) -> Result<()> {
+        self.execute(
+            "INSERT INTO settings (key, value, updated_at) VALUES (?1, ?2, datetime('now'))
+             ON CONFLICT(key) DO UPDATE SET value = ?2, updated_at = datetime('now')",
+            params![key, value],
+        )?;
+        Ok(())
+    }
+    
+    /// Advance the counter named `sequence` and return its new value,
+    /// starting at 1. The increment is a single statement, so concurrent
+    /// callers never see the same value.
+    pub fn next_value(&self, sequence: &str) -> Result<i64> {
+        self.transaction(|conn| next_value_in(conn, sequence))
+    }
+    
+    /// Copy a consistent snapshot into `dest` using SQLite's online backup
+    /// API, optionally re-keying the copy with its own passphrase.
+    ///
+    /// `progress` receives (copied_pages, total_pages) after every step.
+    pub fn backup_to<F>(&self, dest: &Path, passphrase: Option<&str>, mut progress: F) -> Result<()>
+    where
+        F: FnMut(u64, u64),
+    {
+        // Holding the lock keeps writers out until the snapshot is complete
+        let conn = self.writer()?;
+        
+        let mut target = Connection::open(dest)?;
+        target.pragma_update(None, "key", &self.encryption_key)?;
+        
+        {
+            let backup = Backup::new(&conn, &mut target)?;
+            loop {
+                let step = backup.step(BACKUP_PAGES_PER_STEP)?;
+                let p = backup.progress();
+                let total = p.pagecount.max(0) as u64;
+                progress(total.saturating_sub(p.remaining.max(0) as u64), total);
+                
+                match step {
+                    StepResult::Done => break,
+                    StepResult::More => {}
+                    _ => std::thread::sleep(Duration::from_millis(50)),
+                }
+            }
+        }
+        
+        if let Some(passphrase) = passphrase {
+            target.pragma_update(None, "rekey", passphrase)?;
+        }
+        
+        debug!("Backup written to {:?}", dest);
+        Ok(())
+    }
+    
+    /// Replace the live database with `staged`, which must already be keyed
+    /// with this database's key. The swap is a rename, so the live file is
+    /// either the old database or the new one. Pending migrations are
+    /// applied afterwards.
+    pub fn replace_with(&self, staged: &Path) -> Result<()> {
+        {
+            let mut writer = self.writer()?;
+            let mut readers = self.readers.iter()
+                .map(|reader| reader.lock().map_err(|_| WmsError::LockError))
+                .collect::<Result<Vec<_>>>()?;
+            
+            // Close the live connections so the file can be swapped
+            for conn in readers.iter_mut().map(|reader| &mut **reader).chain([&mut *writer]) {
+                let old = std::mem::replace(conn, Connection::open_in_memory()?);
+                if let Err((old, e)) = old.close() {
+                    *conn = old;
+                    return Err(e.into());
+                }
+            }
+            
+            // WAL and shared-memory files belong to the old database
+            for suffix in ["-wal", "-shm"] {
+                let sidecar = sidecar_path(&self.path, suffix);
+                if sidecar.exists() {
+                    std::fs::remove_file(sidecar)?;
+                }
+            }
+            
+            let swapped = std::fs::rename(staged, &self.path);
+            *writer = Self::open_connection(&self.path, &self.encryption_key)?;
+            for reader in readers.iter_mut() {
+                **reader = Self::open_connection(&self.path, &self.encryption_key)?;
+            }
+            swapped?;
         }
+        
+        self.migrate_to_latest()?;
+        info!("Database replaced from {:?}", staged);
+        
+        Ok(())
     }
     
-    /// Begin a transaction
+    /// Begin a transaction. It takes the write lock up front, so reads
+    /// inside it can't be invalidated by another process's write.
     pub fn transaction<F, T>(&self, f: F) -> Result<T>
     where
         F: FnOnce(&Connection) -> Result<T>,
     {
-        let mut conn = self.conn.lock().map_err(|_| WmsError::LockError)?;
-        let tx = conn.transaction()?;
+        let mut conn = self.writer()?;
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
         let result = f(&tx)?;
         tx.commit()?;
         Ok(result)
     }
 }
 
+/// `Database::next_value` inside a transaction, so the value is only
+/// used up if the transaction commits
+pub fn next_value_in(conn: &Connection, sequence: &str) -> Result<i64> {
+    Ok(conn.query_row(
+        "INSERT INTO document_sequences (name, prefix, last_value) VALUES (?1, upper(?1), 1)
+         ON CONFLICT(name) DO UPDATE SET last_value = last_value + 1, updated_at = datetime('now')
+         RETURNING last_value",
+        params![sequence],
+        |row| row.get(0),
+    )?)
+}
+
+/// Path next to the database file with `suffix` appended to its name
+pub(crate) fn sidecar_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// A schema change shipped with this build. Most are embedded SQL
+/// scripts; Rust migrations are for changes SQL can't express.
+#[derive(Clone, Copy)]
+pub struct Migration {
+    pub version: u32,
+    pub name: &'static str,
+    step: MigrationStep,
+}
+
+#[derive(Clone, Copy)]
+enum MigrationStep {
+    Sql(&'static str),
+    Rust(fn(&Connection) -> Result<()>),
+}
+
+impl Migration {
+    /// Migration from a SQL script; `name` starts with its version, as in
+    /// `001_initial_schema`
+    pub fn sql(name: &'static str, sql: &'static str) -> Self {
+        Self { version: version_of(name), name, step: MigrationStep::Sql(sql) }
+    }
+    
+    /// Migration run as code
+    pub fn rust(name: &'static str, apply: fn(&Connection) -> Result<()>) -> Self {
+        Self { version: version_of(name), name, step: MigrationStep::Rust(apply) }
+    }
+    
+    /// SHA-256 of the script, ignoring line endings so a CRLF checkout
+    /// still matches. Code can't be hashed, so a Rust migration's name
+    /// stands in for it.
+    pub fn checksum(&self) -> String {
+        let source = match self.step {
+            MigrationStep::Sql(sql) => sql.replace("\r\n", "\n"),
+            MigrationStep::Rust(_) => format!("rust:{}", self.name),
+        };
+        format!("{:x}", Sha256::digest(source.as_bytes()))
+    }
+    
+    fn apply(&self, conn: &Connection) -> Result<()> {
+        match self.step {
+            MigrationStep::Sql(sql) => Ok(conn.execute_batch(sql)?),
+            MigrationStep::Rust(apply) => apply(conn),
+        }
+    }
+}
+
+fn version_of(name: &str) -> u32 {
+    name.split('_')
+        .next()
+        .and_then(|prefix| prefix.parse().ok())
+        .unwrap_or_else(|| panic!("migration {} doesn't start with its version", name))
+}
+
+/// A migration recorded as applied in a database
+#[derive(Debug, Clone, PartialEq)]
+pub struct AppliedMigration {
+    pub version: u32,
+    pub name: String,
+    /// None for databases from before checksums were recorded
+    pub checksum: Option<String>,
+}
+
+fn table_exists(conn: &Connection, table: &str) -> Result<bool> {
+    let count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?",
+        params![table],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+/// Migrations applied to `conn`, oldest first. Databases from before
+/// `schema_migrations` are read from `_migrations`, which kept only names.
+pub(crate) fn applied_migrations(conn: &Connection) -> Result<Vec<AppliedMigration>> {
+    if table_exists(conn, "schema_migrations")? {
+        let mut stmt = conn.prepare("SELECT version, name, checksum FROM schema_migrations ORDER BY version")?;
+        let applied = stmt.query_map([], |row| Ok(AppliedMigration {
+            version: row.get(0)?,
+            name: row.get(1)?,
+            checksum: Some(row.get(2)?),
+        }))?.collect::<rusqlite::Result<Vec<_>>>()?;
+        return Ok(applied);
+    }
+    if table_exists(conn, "_migrations")? {
+        let mut stmt = conn.prepare("SELECT name FROM _migrations ORDER BY id")?;
+        let applied = stmt.query_map([], |row| row.get::<_, String>(0))?
+            .map(|name| name.map(|name| AppliedMigration {
+                version: name.split('_').next().and_then(|v| v.parse().ok()).unwrap_or(0),
+                name,
+                checksum: None,
+            }))
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        return Ok(applied);
+    }
+    Ok(Vec::new())
+}
+
+/// Fail if a migration was applied that this build doesn't know, which
+/// means a newer build has run against the database, or if one has
+/// changed since it was applied
+fn verify_applied(applied: &[AppliedMigration], migrations: &[Migration]) -> Result<()> {
+    let latest = migrations.iter().map(|m| m.version).max().unwrap_or(0);
+    for applied in applied {
+        let Some(known) = migrations.iter().find(|m| m.version == applied.version) else {
+            return Err(WmsError::Migration(if applied.version > latest {
+                format!("Database schema is newer than this build (migration {}); update the app", applied.name)
+            } else {
+                format!("Database has a migration this build doesn't know: {}", applied.name)
+            }));
+        };
+        let changed = applied.checksum.as_ref().is_some_and(|checksum| *checksum != known.checksum());
+        if known.name != applied.name || changed {
+            return Err(WmsError::Migration(format!(
+                "Migration {} has changed since it was applied",
+                applied.name
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn pending<'a>(
+    applied: &'a [AppliedMigration],
+    migrations: &'a [Migration],
+) -> impl Iterator<Item = &'a Migration> {
+    migrations.iter().filter(|m| !applied.iter().any(|a| a.version == m.version))
+}
+
+/// Apply the `migrations` that `conn` is missing, returning their names
+fn migrate(conn: &mut Connection, migrations: &[Migration]) -> Result<Vec<&'static str>> {
+    let applied = applied_migrations(conn)?;
+    verify_applied(&applied, migrations)?;
+    
+    let tx = conn.transaction()?;
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS schema_migrations (
+            version INTEGER PRIMARY KEY,
+            name TEXT NOT NULL UNIQUE,
+            checksum TEXT NOT NULL,
+            applied_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+    // `_migrations` kept no checksums; the scripts that ran are taken to
+    // be the ones in this build
+    if table_exists(&tx, "_migrations")? {
+        for migration in migrations.iter().filter(|m| applied.iter().any(|a| a.version == m.version)) {
+            tx.execute(
+                "INSERT INTO schema_migrations (version, name, checksum, applied_at)
+                 SELECT ?1, name, ?2, applied_at FROM _migrations WHERE name = ?3",
+                params![migration.version, migration.checksum(), migration.name],
+            )?;
+        }
+        tx.execute("DROP TABLE _migrations", [])?;
+        info!("Moved migration history to schema_migrations");
+    }
+    tx.commit()?;
+    
+    let mut names = Vec::new();
+    for migration in pending(&applied, migrations) {
+        info!("Applying migration: {}", migration.name);
+        let tx = conn.transaction()?;
+        migration.apply(&tx)?;
+        tx.execute(
+            "INSERT INTO schema_migrations (version, name, checksum) VALUES (?1, ?2, ?3)",
+            params![migration.version, migration.name, migration.checksum()],
+        )?;
+        tx.commit()?;
+        names.push(migration.name);
+    }
+    if names.is_empty() {
+        debug!("Schema is up to date");
+    }
+    Ok(names)
+}
+
+/// Names of all migrations known to this build, in order
+pub fn known_migrations() -> Vec<&'static str> {
+    get_migrations().into_iter().map(|m| m.name).collect()
+}
+
 /// Get all database migrations in order
-fn get_migrations() -> Vec<(&'static str, &'static str)> {
+fn get_migrations() -> Vec<Migration> {
     vec![
-        ("001_initial_schema", include_str!("migrations/001_initial_schema.sql")),
-        ("002_inventory_tables", include_str!("migrations/002_inventory_tables.sql")),
-        ("003_shipping_tables", include_str!("migrations/003_shipping_tables.sql")),
-        ("004_delivery_tables", include_str!("migrations/004_delivery_tables.sql")),
-        ("005_crm_tables", include_str!("migrations/005_crm_tables.sql")),
-        ("006_timesheet_tables", include_str!("migrations/006_timesheet_tables.sql")),
-        ("007_sync_tables", include_str!("migrations/007_sync_tables.sql")),
+        Migration::sql("001_initial_schema", include_str!("migrations/001_initial_schema.sql")),
+        Migration::sql("002_inventory_tables", include_str!("migrations/002_inventory_tables.sql")),
+        Migration::sql("003_shipping_tables", include_str!("migrations/003_shipping_tables.sql")),
+        Migration::sql("004_delivery_tables", include_str!("migrations/004_delivery_tables.sql")),
+        Migration::sql("005_crm_tables", include_str!("migrations/005_crm_tables.sql")),
+        Migration::sql("006_timesheet_tables", include_str!("migrations/006_timesheet_tables.sql")),
+        Migration::sql("007_sync_tables", include_str!("migrations/007_sync_tables.sql")),
+        Migration::sql("008_pricing", include_str!("migrations/008_pricing.sql")),
+        Migration::sql("009_inventory_reservations", include_str!("migrations/009_inventory_reservations.sql")),
+        Migration::sql("010_location_coordinates", include_str!("migrations/010_location_coordinates.sql")),
+        Migration::sql("011_shipment_splits", include_str!("migrations/011_shipment_splits.sql")),
+        Migration::sql("012_print_queue", include_str!("migrations/012_print_queue.sql")),
+        Migration::sql("013_quality_holds", include_str!("migrations/013_quality_holds.sql")),
+        Migration::sql("014_tracking_events", include_str!("migrations/014_tracking_events.sql")),
+        Migration::sql("015_measurement_units", include_str!("migrations/015_measurement_units.sql")),
+        Migration::sql("016_row_versions", include_str!("migrations/016_row_versions.sql")),
+        Migration::sql("017_carrier_invoices", include_str!("migrations/017_carrier_invoices.sql")),
+        Migration::sql("018_attachments", include_str!("migrations/018_attachments.sql")),
+        Migration::sql("019_scan_events", include_str!("migrations/019_scan_events.sql")),
+        Migration::sql("020_route_numbers", include_str!("migrations/020_route_numbers.sql")),
+        Migration::sql("021_item_variants", include_str!("migrations/021_item_variants.sql")),
+        Migration::sql("022_replenishment", include_str!("migrations/022_replenishment.sql")),
+        Migration::sql("023_shipping_requirements", include_str!("migrations/023_shipping_requirements.sql")),
+        Migration::sql("024_metrics_history", include_str!("migrations/024_metrics_history.sql")),
+        Migration::sql("025_line_evidence", include_str!("migrations/025_line_evidence.sql")),
+        Migration::sql("026_forecast_accuracy", include_str!("migrations/026_forecast_accuracy.sql")),
+        Migration::sql("027_delivery_notifications", include_str!("migrations/027_delivery_notifications.sql")),
+        Migration::sql("028_temperature_logging", include_str!("migrations/028_temperature_logging.sql")),
+        Migration::sql("029_customer_extracts", include_str!("migrations/029_customer_extracts.sql")),
+        Migration::sql("030_mispicks", include_str!("migrations/030_mispicks.sql")),
+        Migration::sql("031_custom_fields", include_str!("migrations/031_custom_fields.sql")),
+        Migration::sql("032_merge_policies", include_str!("migrations/032_merge_policies.sql")),
+        Migration::sql("033_blind_receiving", include_str!("migrations/033_blind_receiving.sql")),
+        Migration::sql("034_route_breaks", include_str!("migrations/034_route_breaks.sql")),
+        Migration::sql("035_overrides", include_str!("migrations/035_overrides.sql")),
+        Migration::sql("036_pallet_nesting", include_str!("migrations/036_pallet_nesting.sql")),
+        Migration::sql("037_document_sequences", include_str!("migrations/037_document_sequences.sql")),
+        Migration::sql("038_sync_merges", include_str!("migrations/038_sync_merges.sql")),
+        Migration::sql("039_short_picks", include_str!("migrations/039_short_picks.sql")),
+        Migration::sql("040_receipt_discrepancies", include_str!("migrations/040_receipt_discrepancies.sql")),
+        Migration::sql("041_stock_transfers", include_str!("migrations/041_stock_transfers.sql")),
+        Migration::sql("042_cycle_counts", include_str!("migrations/042_cycle_counts.sql")),
+        Migration::sql("043_warehouses", include_str!("migrations/043_warehouses.sql")),
+        Migration::sql("044_vehicle_shifts", include_str!("migrations/044_vehicle_shifts.sql")),
+        Migration::sql("045_route_odometer", include_str!("migrations/045_route_odometer.sql")),
+        Migration::sql("046_delivery_proofs", include_str!("migrations/046_delivery_proofs.sql")),
+        Migration::sql("047_geofence_states", include_str!("migrations/047_geofence_states.sql")),
+        Migration::sql("048_customer_merges", include_str!("migrations/048_customer_merges.sql")),
+        Migration::sql("049_shipment_declared_value", include_str!("migrations/049_shipment_declared_value.sql")),
+        Migration::sql("050_timesheet_periods", include_str!("migrations/050_timesheet_periods.sql")),
+        Migration::sql("051_time_entry_audit", include_str!("migrations/051_time_entry_audit.sql")),
+        Migration::sql("052_time_entry_auto_breaks", include_str!("migrations/052_time_entry_auto_breaks.sql")),
+        Migration::sql("053_time_entry_out_of_area", include_str!("migrations/053_time_entry_out_of_area.sql")),
+        Migration::sql("054_global_search", include_str!("migrations/054_global_search.sql")),
+        Migration::sql("055_user_credentials", include_str!("migrations/055_user_credentials.sql")),
+        Migration::sql("056_shipment_cancellation", include_str!("migrations/056_shipment_cancellation.sql")),
+        Migration::sql("057_shipment_line_picks", include_str!("migrations/057_shipment_line_picks.sql")),
+        Migration::sql("058_aisle_traversal", include_str!("migrations/058_aisle_traversal.sql")),
+        Migration::sql("059_login_lockout", include_str!("migrations/059_login_lockout.sql")),
     ]
 }
 
@@ -147,12 +592,203 @@ fn get_migrations() -> Vec<(&'static str, &'static str)> {
 mod tests {
     use super::*;
     use std::path::PathBuf;
+    use std::sync::Arc;
+    use std::time::Instant;
+    use crate::types::new_id;
+    
+    fn file_db() -> Arc<Database> {
+        let dir = std::env::temp_dir().join(format!("wms-db-test-{}", new_id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db = Database::new(&dir.join("wms.db"), "test-key").unwrap();
+        db.migrate_to_latest().unwrap();
+        Arc::new(db)
+    }
     
     #[test]
     fn test_database_creation() {
         let path = PathBuf::from(":memory:");
         let db = Database::new(&path, "test-key").unwrap();
-        db.run_migrations().unwrap();
+        assert_eq!(db.pending_migrations().unwrap(), known_migrations());
+        db.migrate_to_latest().unwrap();
+        assert!(db.pending_migrations().unwrap().is_empty());
+    }
+    
+    #[test]
+    fn test_locations_default_to_the_default_warehouse() {
+        let db = Database::new(&PathBuf::from(":memory:"), "test-key").unwrap();
+        db.migrate_to_latest().unwrap();
+        
+        db.execute("INSERT INTO locations (id, code, zone) VALUES ('loc1', 'A-01-01', 'STORAGE')", []).unwrap();
+        let warehouse: Option<String> = db.query_row(
+            "SELECT w.id FROM locations l JOIN warehouses w ON l.warehouse_id = w.id WHERE l.id = 'loc1'",
+            [],
+            |row| row.get(0),
+        ).unwrap();
+        assert_eq!(warehouse.as_deref(), Some(DEFAULT_WAREHOUSE_ID));
+    }
+    
+    fn create_bins(conn: &Connection) -> Result<()> {
+        conn.execute("INSERT INTO bins (code) VALUES ('A-01')", [])?;
+        Ok(())
+    }
+    
+    fn test_migrations() -> Vec<Migration> {
+        vec![
+            Migration::sql("001_bins", "CREATE TABLE bins (code TEXT NOT NULL);"),
+            Migration::rust("002_first_bin", create_bins),
+            Migration::sql("003_bin_zone", "ALTER TABLE bins ADD COLUMN zone TEXT;"),
+        ]
+    }
+    
+    fn applied_names(conn: &Connection) -> Vec<String> {
+        applied_migrations(conn).unwrap().into_iter().map(|m| m.name).collect()
+    }
+    
+    #[test]
+    fn test_migrate_from_empty_and_incrementally() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let migrations = test_migrations();
+        
+        assert_eq!(migrate(&mut conn, &migrations[..2]).unwrap(), vec!["001_bins", "002_first_bin"]);
+        assert_eq!(applied_names(&conn), vec!["001_bins", "002_first_bin"]);
+        
+        assert_eq!(migrate(&mut conn, &migrations).unwrap(), vec!["003_bin_zone"]);
+        assert!(migrate(&mut conn, &migrations).unwrap().is_empty());
+        let zone: Option<String> = conn.query_row("SELECT zone FROM bins", [], |row| row.get(0)).unwrap();
+        assert_eq!(zone, None);
+    }
+    
+    #[test]
+    fn test_failed_migration_is_rolled_back() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let mut migrations = test_migrations();
+        migrations.push(Migration::sql("004_broken", "ALTER TABLE bins ADD COLUMN aisle TEXT; SELECT * FROM missing;"));
+        
+        assert!(migrate(&mut conn, &migrations).is_err());
+        assert_eq!(applied_names(&conn).len(), 3);
+        assert!(conn.prepare("SELECT aisle FROM bins").is_err());
+    }
+    
+    #[test]
+    fn test_tampered_checksum_is_rejected() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let migrations = test_migrations();
+        migrate(&mut conn, &migrations[..1]).unwrap();
+        conn.execute("UPDATE schema_migrations SET checksum = 'edited' WHERE version = 1", []).unwrap();
+        
+        let result = migrate(&mut conn, &migrations);
+        assert!(matches!(result, Err(WmsError::Migration(ref m)) if m.contains("001_bins")));
+        // Nothing after the tampered migration ran
+        assert_eq!(applied_names(&conn), vec!["001_bins"]);
+    }
+    
+    #[test]
+    fn test_newer_schema_is_rejected() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let migrations = test_migrations();
+        migrate(&mut conn, &migrations).unwrap();
+        
+        let result = migrate(&mut conn, &migrations[..2]);
+        assert!(matches!(result, Err(WmsError::Migration(ref m)) if m.contains("newer than this build")));
+    }
+    
+    #[test]
+    fn test_legacy_history_is_adopted() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let migrations = test_migrations();
+        conn.execute_batch(
+            "CREATE TABLE _migrations (id INTEGER PRIMARY KEY, name TEXT NOT NULL UNIQUE, applied_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP);
+             CREATE TABLE bins (code TEXT NOT NULL);
+             INSERT INTO _migrations (name) VALUES ('001_bins');",
+        ).unwrap();
+        
+        assert_eq!(migrate(&mut conn, &migrations).unwrap(), vec!["002_first_bin", "003_bin_zone"]);
+        assert!(!table_exists(&conn, "_migrations").unwrap());
+        let applied = applied_migrations(&conn).unwrap();
+        assert_eq!(applied[0].checksum.as_deref(), Some(migrations[0].checksum().as_str()));
+    }
+    
+    #[test]
+    fn test_migration_versions_are_ordered() {
+        let versions: Vec<u32> = get_migrations().iter().map(|m| m.version).collect();
+        assert!(versions.windows(2).all(|w| w[0] < w[1]));
+    }
+    
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_reads_and_writes() {
+        let db = file_db();
+        
+        let mut tasks = Vec::new();
+        for writer in 0..4 {
+            let db = db.clone();
+            tasks.push(tokio::spawn(async move {
+                for i in 0..50 {
+                    db.set_setting(&format!("stress_{}_{}", writer, i), "1")?;
+                    db.next_value("stress")?;
+                }
+                Ok::<_, WmsError>(())
+            }));
+        }
+        for _ in 0..8 {
+            let db = db.clone();
+            tasks.push(tokio::spawn(async move {
+                for _ in 0..50 {
+                    db.query_map("SELECT key FROM settings WHERE key LIKE 'stress_%'", [], |row| row.get::<_, String>(0))?;
+                    db.get_setting("stress_0_0")?;
+                }
+                Ok::<_, WmsError>(())
+            }));
+        }
+        // A SQLITE_BUSY from any task fails here
+        for task in tasks {
+            task.await.unwrap().unwrap();
+        }
+        
+        let written: i64 = db.query_row("SELECT COUNT(*) FROM settings WHERE key LIKE 'stress_%'", [], |row| row.get(0))
+            .unwrap()
+            .unwrap();
+        assert_eq!(written, 200);
+        assert_eq!(db.next_value("stress").unwrap(), 201);
+    }
+    
+    #[test]
+    fn test_reads_run_alongside_a_long_query() {
+        // With a single connection the write and read below waited for the
+        // whole scan; on the pool they finish while it is still running.
+        let db = file_db();
+        let scan = {
+            let db = db.clone();
+            std::thread::spawn(move || {
+                let started = Instant::now();
+                db.query_row(
+                    "WITH RECURSIVE n(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM n WHERE x < 20000000)
+                     SELECT COUNT(*) FROM n",
+                    [],
+                    |row| row.get::<_, i64>(0),
+                ).unwrap();
+                started.elapsed()
+            })
+        };
+        std::thread::sleep(Duration::from_millis(50));
+        
+        let started = Instant::now();
+        db.set_setting("marker", "written").unwrap();
+        assert_eq!(db.get_setting("marker").unwrap().as_deref(), Some("written"));
+        let quick = started.elapsed();
+        
+        let scan = scan.join().unwrap();
+        assert!(quick < scan, "write and read took {:?} next to a {:?} scan", quick, scan);
+    }
+    
+    #[test]
+    fn test_writes_through_query_row_use_the_writer() {
+        let db = file_db();
+        let value: Option<i64> = db.query_row(
+            "INSERT INTO settings (key, value) VALUES ('returned', '7') RETURNING CAST(value AS INTEGER)",
+            [],
+            |row| row.get(0),
+        ).unwrap();
+        assert_eq!(value, Some(7));
+        assert_eq!(db.get_setting("returned").unwrap().as_deref(), Some("7"));
     }
 }
-
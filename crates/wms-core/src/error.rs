@@ -1,7 +1,12 @@
 //! Error Types for WMS
 
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+/// Separates a version conflict's message from the JSON of the current
+/// record in its display form, which is what reaches the frontend
+pub const CURRENT_RECORD_MARKER: &str = "\ncurrent: ";
+
 /// Result type alias for WMS operations
 pub type Result<T> = std::result::Result<T, WmsError>;
 
@@ -23,12 +28,43 @@ pub enum WmsError {
     #[error("Conflict: {0}")]
     Conflict(String),
     
+    /// An edit was based on a stale version of the record
+    #[error("Conflict: {message}{marker}{current}", marker = CURRENT_RECORD_MARKER)]
+    VersionConflict {
+        message: String,
+        /// The record as it is now, so the caller can reload or overwrite
+        current: serde_json::Value,
+    },
+    
+    /// An order would take a customer past their credit limit
+    #[error("Credit limit exceeded: {requested:.2} ordered with {exposure:.2} already open is over the limit of {limit:.2}")]
+    CreditLimitExceeded {
+        limit: f64,
+        /// Value of the customer's shipments not yet delivered
+        exposure: f64,
+        /// Value of the new order
+        requested: f64,
+    },
+    
+    /// A record can't move from its status to the one requested
+    #[error("Cannot change status from {from} to {to}; {}", allowed_next(.allowed))]
+    InvalidTransition {
+        from: String,
+        to: String,
+        /// Statuses the record can move to from `from`
+        allowed: Vec<String>,
+    },
+    
     #[error("Lock acquisition failed")]
     LockError,
     
     #[error("Sync error: {0}")]
     SyncError(String),
     
+    /// The schema on disk doesn't match the migrations in this build
+    #[error("Migration error: {0}")]
+    Migration(String),
+    
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
     
@@ -38,6 +74,10 @@ pub enum WmsError {
     #[error("Authentication error: {0}")]
     Auth(String),
     
+    /// The signed-in user's role does not allow the operation
+    #[error("Permission denied: {0}")]
+    PermissionDenied(String),
+    
     #[error("Barcode error: {0}")]
     Barcode(String),
     
@@ -50,6 +90,10 @@ pub enum WmsError {
     #[error("Export error: {0}")]
     Export(String),
     
+    /// A long-running operation was cancelled or ran out of time
+    #[error("Cancelled: {0}")]
+    Cancelled(String),
+    
     #[error("Unknown error: {0}")]
     Unknown(String),
 }
@@ -65,9 +109,197 @@ impl WmsError {
         Self::NotFound(msg.into())
     }
     
+    /// Create a permission denied error
+    pub fn permission_denied(msg: impl Into<String>) -> Self {
+        Self::PermissionDenied(msg.into())
+    }
+    
     /// Create a conflict error
     pub fn conflict(msg: impl Into<String>) -> Self {
         Self::Conflict(msg.into())
     }
+    
+    /// Create a conflict error for an edit based on a stale version
+    pub fn version_conflict(msg: impl Into<String>, current: &impl Serialize) -> Self {
+        Self::VersionConflict {
+            message: msg.into(),
+            current: serde_json::to_value(current).unwrap_or_default(),
+        }
+    }
+}
+
+fn allowed_next(allowed: &[String]) -> String {
+    if allowed.is_empty() {
+        "it can't change any further".to_string()
+    } else {
+        format!("it can move to {}", allowed.join(", "))
+    }
+}
+
+
+/// Stable code of an error sent to the frontend, which matches on it
+/// rather than on the message
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    Database,
+    /// The database is busy with another writer; worth retrying
+    DatabaseBusy,
+    Serialization,
+    Validation,
+    NotFound,
+    Conflict,
+    /// `details.current` is the record as it is now
+    VersionConflict,
+    /// `details` has the limit, exposure and requested values
+    CreditLimitExceeded,
+    /// `details.allowed` lists the statuses the record can move to
+    InvalidTransition,
+    LockError,
+    SyncError,
+    Migration,
+    Io,
+    Network,
+    Auth,
+    PermissionDenied,
+    Barcode,
+    RouteOptimization,
+    Forecast,
+    Export,
+    Cancelled,
+    Unknown,
+}
+
+/// An error as sent across the command boundary
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ErrorPayload {
+    pub code: ErrorCode,
+    pub message: String,
+    /// Structured data some codes carry
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+}
+
+impl ErrorPayload {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self { code, message: message.into(), details: None }
+    }
+}
+
+impl From<WmsError> for ErrorPayload {
+    fn from(error: WmsError) -> Self {
+        let message = error.to_string();
+        let (code, details) = match error {
+            WmsError::Database(ref e) => {
+                let busy = matches!(
+                    e.sqlite_error_code(),
+                    Some(rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked)
+                );
+                (if busy { ErrorCode::DatabaseBusy } else { ErrorCode::Database }, None)
+            }
+            WmsError::Serialization(_) => (ErrorCode::Serialization, None),
+            WmsError::Validation(_) => (ErrorCode::Validation, None),
+            WmsError::NotFound(_) => (ErrorCode::NotFound, None),
+            WmsError::Conflict(_) => (ErrorCode::Conflict, None),
+            WmsError::VersionConflict { message, current } => {
+                return Self {
+                    code: ErrorCode::VersionConflict,
+                    message: format!("Conflict: {}", message),
+                    details: Some(serde_json::json!({ "current": current })),
+                };
+            }
+            WmsError::CreditLimitExceeded { limit, exposure, requested } => (
+                ErrorCode::CreditLimitExceeded,
+                Some(serde_json::json!({ "limit": limit, "exposure": exposure, "requested": requested })),
+            ),
+            WmsError::InvalidTransition { from, to, allowed } => (
+                ErrorCode::InvalidTransition,
+                Some(serde_json::json!({ "from": from, "to": to, "allowed": allowed })),
+            ),
+            WmsError::LockError => (ErrorCode::LockError, None),
+            WmsError::SyncError(_) => (ErrorCode::SyncError, None),
+            WmsError::Migration(_) => (ErrorCode::Migration, None),
+            WmsError::Io(_) => (ErrorCode::Io, None),
+            WmsError::Network(_) => (ErrorCode::Network, None),
+            WmsError::Auth(_) => (ErrorCode::Auth, None),
+            WmsError::PermissionDenied(_) => (ErrorCode::PermissionDenied, None),
+            WmsError::Barcode(_) => (ErrorCode::Barcode, None),
+            WmsError::RouteOptimization(_) => (ErrorCode::RouteOptimization, None),
+            WmsError::Forecast(_) => (ErrorCode::Forecast, None),
+            WmsError::Export(_) => (ErrorCode::Export, None),
+            WmsError::Cancelled(_) => (ErrorCode::Cancelled, None),
+            WmsError::Unknown(_) => (ErrorCode::Unknown, None),
+        };
+        Self { code, message, details }
+    }
+}
+
+impl std::fmt::Display for ErrorPayload {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_errors_map_to_stable_codes() {
+        let cases = [
+            (WmsError::validation("SKU is required"), ErrorCode::Validation, "VALIDATION"),
+            (WmsError::not_found("Item not found"), ErrorCode::NotFound, "NOT_FOUND"),
+            (WmsError::conflict("SKU A-1 already exists"), ErrorCode::Conflict, "CONFLICT"),
+            (WmsError::SyncError("server gone".into()), ErrorCode::SyncError, "SYNC_ERROR"),
+            (WmsError::Forecast("too little history".into()), ErrorCode::Forecast, "FORECAST"),
+            (WmsError::Export("disk full".into()), ErrorCode::Export, "EXPORT"),
+            (WmsError::permission_denied("Settings"), ErrorCode::PermissionDenied, "PERMISSION_DENIED"),
+        ];
+        for (error, code, wire) in cases {
+            let message = error.to_string();
+            let payload = ErrorPayload::from(error);
+            assert_eq!(payload.code, code);
+            assert_eq!(payload.message, message);
+            assert_eq!(payload.details, None);
+            assert_eq!(serde_json::to_value(&payload).unwrap()["code"], wire);
+        }
+    }
+
+    #[test]
+    fn test_busy_database_is_distinguished() {
+        let busy = rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_BUSY),
+            Some("database is locked".into()),
+        );
+        assert_eq!(ErrorPayload::from(WmsError::from(busy)).code, ErrorCode::DatabaseBusy);
+        let missing = WmsError::from(rusqlite::Error::QueryReturnedNoRows);
+        assert_eq!(ErrorPayload::from(missing).code, ErrorCode::Database);
+    }
+
+    #[test]
+    fn test_structured_errors_carry_details() {
+        let conflict = WmsError::version_conflict("Item was changed", &serde_json::json!({ "version": 3 }));
+        let payload = ErrorPayload::from(conflict);
+        assert_eq!(payload.code, ErrorCode::VersionConflict);
+        assert_eq!(payload.message, "Conflict: Item was changed");
+        assert_eq!(payload.details.unwrap()["current"]["version"], 3);
+
+        let credit = WmsError::CreditLimitExceeded { limit: 1000.0, exposure: 800.0, requested: 300.0 };
+        let payload = ErrorPayload::from(credit);
+        assert_eq!(payload.code, ErrorCode::CreditLimitExceeded);
+        assert_eq!(payload.details.unwrap()["requested"], 300.0);
+
+        let transition = WmsError::InvalidTransition {
+            from: "draft".into(),
+            to: "shipped".into(),
+            allowed: vec!["confirmed".into(), "cancelled".into()],
+        };
+        assert_eq!(
+            transition.to_string(),
+            "Cannot change status from draft to shipped; it can move to confirmed, cancelled"
+        );
+        let payload = ErrorPayload::from(transition);
+        assert_eq!(payload.code, ErrorCode::InvalidTransition);
+        assert_eq!(payload.details.unwrap()["allowed"][1], "cancelled");
+    }
+}
@@ -0,0 +1,170 @@
+//! Domain Events
+//!
+//! Services publish typed events through an `EventEmitter` handle so the
+//! application shell can push live updates to every open window instead of
+//! relying on polling. Emission is fire-and-forget and never fails a call.
+
+use std::sync::{Arc, Mutex};
+use serde::Serialize;
+
+/// Events published by the WMS services
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum WmsEvent {
+    ShipmentStatusChanged {
+        shipment_id: String,
+        shipment_number: String,
+        status: String,
+    },
+    DeliveryStatusChanged {
+        delivery_id: String,
+        delivery_number: String,
+        status: String,
+    },
+    InventoryLowStock {
+        item_id: String,
+        sku: String,
+        quantity: f64,
+        reorder_point: f64,
+    },
+    SyncCompleted {
+        success: bool,
+        pending_changes: u64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    TimesheetClockedIn {
+        entry_id: String,
+        user_id: String,
+        clock_in_time: String,
+    },
+    BackupProgress {
+        /// "backup" or "restore"
+        operation: String,
+        completed: u64,
+        total: u64,
+    },
+    /// A customer extract failed and will not be retried until its next
+    /// scheduled run
+    ExtractFailed {
+        definition_id: String,
+        customer_id: String,
+        name: String,
+        attempts: u32,
+        error: String,
+    },
+    /// Progress of a long-running operation started with an operation id
+    OperationProgress {
+        operation_id: String,
+        /// "forecast", "route_optimization" or "import"
+        kind: String,
+        completed: u64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        total: Option<u64>,
+    },
+}
+
+impl WmsEvent {
+    /// Event channel name
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::ShipmentStatusChanged { .. } => "shipment.status_changed",
+            Self::DeliveryStatusChanged { .. } => "delivery.status_changed",
+            Self::InventoryLowStock { .. } => "inventory.low_stock",
+            Self::SyncCompleted { .. } => "sync.completed",
+            Self::TimesheetClockedIn { .. } => "timesheet.clocked_in",
+            Self::BackupProgress { .. } => "backup.progress",
+            Self::ExtractFailed { .. } => "extract.failed",
+            Self::OperationProgress { .. } => "operation.progress",
+        }
+    }
+}
+
+/// Sink for domain events
+pub trait EventEmitter: Send + Sync {
+    /// Publish an event
+    fn emit(&self, event: WmsEvent);
+}
+
+/// Shared emitter handle held by services
+pub type SharedEmitter = Arc<dyn EventEmitter>;
+
+/// Emitter that discards all events
+pub struct NoopEmitter;
+
+impl EventEmitter for NoopEmitter {
+    fn emit(&self, _event: WmsEvent) {}
+}
+
+/// Create an emitter handle that discards all events
+pub fn noop_emitter() -> SharedEmitter {
+    Arc::new(NoopEmitter)
+}
+
+/// Emitter that keeps events in memory (for tests and diagnostics)
+#[derive(Default)]
+pub struct RecordingEmitter {
+    events: Mutex<Vec<WmsEvent>>,
+}
+
+impl RecordingEmitter {
+    /// Create an empty recording emitter
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Events emitted so far, oldest first
+    pub fn events(&self) -> Vec<WmsEvent> {
+        self.events.lock().map(|e| e.clone()).unwrap_or_default()
+    }
+}
+
+impl EventEmitter for RecordingEmitter {
+    fn emit(&self, event: WmsEvent) {
+        if let Ok(mut events) = self.events.lock() {
+            events.push(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_names() {
+        let event = WmsEvent::ShipmentStatusChanged {
+            shipment_id: "s1".into(),
+            shipment_number: "SHP-00000001".into(),
+            status: "shipped".into(),
+        };
+        assert_eq!(event.name(), "shipment.status_changed");
+
+        let event = WmsEvent::SyncCompleted { success: true, pending_changes: 0, error: None };
+        assert_eq!(event.name(), "sync.completed");
+    }
+
+    #[test]
+    fn test_payload_is_flat_json() {
+        let event = WmsEvent::InventoryLowStock {
+            item_id: "i1".into(),
+            sku: "SKU-001".into(),
+            quantity: 4.0,
+            reorder_point: 10.0,
+        };
+
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["sku"], "SKU-001");
+        assert_eq!(json["quantity"], 4.0);
+        assert!(json.get("InventoryLowStock").is_none());
+    }
+
+    #[test]
+    fn test_recording_emitter() {
+        let emitter = RecordingEmitter::new();
+        emitter.emit(WmsEvent::SyncCompleted { success: false, pending_changes: 3, error: Some("offline".into()) });
+
+        assert_eq!(emitter.events().len(), 1);
+        assert_eq!(emitter.events()[0].name(), "sync.completed");
+    }
+}
@@ -0,0 +1,93 @@
+//! Single-Writer Lock
+//!
+//! The desktop app and the admin CLI both open the database file directly.
+//! Whichever starts first takes an OS file lock on `<db>.lock` and holds it
+//! until it exits, so maintenance never runs underneath a live app. The
+//! lock is released by the OS even if the holder crashes; the file itself
+//! is left in place and only records who last held it.
+
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use crate::db::sidecar_path;
+use crate::error::{WmsError, Result};
+
+/// Exclusive claim on a database file, released on drop
+#[derive(Debug)]
+pub struct InstanceLock {
+    file: File,
+    path: PathBuf,
+}
+
+impl InstanceLock {
+    /// Lock the database at `db_path` for this process. `holder` describes
+    /// the process (e.g. "desktop app") in the error others see.
+    pub fn acquire(db_path: &Path, holder: &str) -> Result<Self> {
+        let path = sidecar_path(db_path, ".lock");
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                let mut owner = String::new();
+                // Windows locks are mandatory, so the owner may be unreadable
+                let _ = file.read_to_string(&mut owner);
+                let owner = owner.lines().collect::<Vec<_>>().join(", ");
+                return Err(WmsError::conflict(if owner.is_empty() {
+                    format!("Database {:?} is in use by another process", db_path)
+                } else {
+                    format!("Database {:?} is in use ({})", db_path, owner)
+                }));
+            }
+            Err(TryLockError::Error(e)) => return Err(e.into()),
+        }
+
+        file.set_len(0)?;
+        file.seek(SeekFrom::Start(0))?;
+        writeln!(file, "{}", holder)?;
+        writeln!(file, "pid {}", std::process::id())?;
+        file.flush()?;
+
+        Ok(Self { file, path })
+    }
+
+    /// Location of the lock file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for InstanceLock {
+    fn drop(&mut self) {
+        let _ = self.file.unlock();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_second_holder_is_refused_until_release() {
+        let dir = std::env::temp_dir().join(format!("wms-lock-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db_path = dir.join("wms.db");
+
+        let app = InstanceLock::acquire(&db_path, "desktop app").unwrap();
+        let err = InstanceLock::acquire(&db_path, "wms-admin").unwrap_err();
+        assert!(matches!(err, WmsError::Conflict(_)));
+        assert!(err.to_string().contains("in use"));
+
+        drop(app);
+        let admin = InstanceLock::acquire(&db_path, "wms-admin").unwrap();
+        assert!(std::fs::read_to_string(admin.path()).unwrap().starts_with("wms-admin"));
+
+        drop(admin);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
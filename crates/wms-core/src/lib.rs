@@ -2,14 +2,83 @@
 //! 
 //! This crate provides core utilities shared across all WMS modules:
 //! - Database connection and migration management
-//! - Common types and traits
+//! - User roles and the permission matrix
+//! - User accounts with Argon2-hashed PINs, and signing in
+//! - Per-request context carrying the acting user
+//! - A lock file so only one process maintains the database at a time
+//! - Database backup and restore
+//! - Common types and traits, including measurement units
 //! - Error handling utilities
+//! - Domain event emission
+//! - PDF document generation
+//! - Attachment storage with image thumbnails
+//! - Dashboard metrics with prior-period comparisons and daily history
+//! - Full-text global search over items, customers, shipments and deliveries
+//! - Cold-chain temperature ranges and reading logs
+//! - Deployment-defined custom fields with value validation
+//! - Supervisor overrides with reason codes, redeemed once by the guarded action
+//! - Customer credit exposure checks against credit limits
+//! - An injectable clock, and fixtures for service tests (`test-support`)
+//! - Shutdown coordination for background tasks
+//! - Cancellation, time limits and progress for long-running operations
+//! - Document number sequences that never repeat a number
+//! - Capture of changed records for sync
 
+pub mod attachments;
+pub mod auth;
+pub mod backup;
+pub mod changes;
+pub mod clock;
+pub mod context;
+pub mod credit;
+pub mod custom_fields;
+pub mod dashboard;
 pub mod db;
 pub mod error;
+pub mod events;
+pub mod instance_lock;
+pub mod operations;
+pub mod overrides;
+pub mod pdf;
+pub mod search;
+pub mod sequences;
+pub mod shutdown;
+pub mod temperature;
 pub mod types;
+pub mod users;
 
+#[cfg(any(test, feature = "test-support"))]
+pub mod test_support;
+
+pub use attachments::{Attachment, AttachmentKind, AttachmentService, NewAttachment};
+pub use auth::{Permissions, Role, Scope, Session};
+pub use backup::{BackupInfo, BackupService};
+pub use changes::{ChangeKind, ChangeRecorder, SharedRecorder};
+pub use clock::{Clock, FixedClock, SharedClock, SystemClock};
+pub use context::RequestContext;
+pub use credit::{check_credit, check_credit_in, CreditCheck, CUSTOMER_OWNER};
+pub use custom_fields::{
+    CustomFieldDefinition, CustomFieldEntity, CustomFieldService, CustomFieldType, CustomFieldValues,
+};
+pub use dashboard::{
+    DailyMetric, DashboardMetrics, DashboardService, MetricComparison, MetricPeriod, MetricPoint,
+};
 pub use db::Database;
 pub use error::{WmsError, Result};
+pub use events::{EventEmitter, SharedEmitter, WmsEvent};
+pub use instance_lock::InstanceLock;
+pub use operations::{
+    CancellationToken, OperationKind, OperationRegistry, OperationTimeouts, RunningOperation,
+};
+pub use overrides::{
+    OverrideAction, OverrideApproval, OverrideGrouping, OverrideQuery, OverrideRecord, OverrideRequest,
+    OverrideService, OverrideTally, OverrideToken, ReasonCode,
+};
+pub use sequences::{DocumentSequence, SequenceFormat};
+pub use shutdown::{ShutdownCoordinator, ShutdownSignal, ShutdownSummary, TaskHandle};
+pub use temperature::{
+    TemperatureLog, TemperatureLogExport, TemperatureOwner, TemperatureRange, TemperatureReading,
+    TemperatureService,
+};
 pub use types::*;
 
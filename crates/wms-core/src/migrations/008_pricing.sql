@@ -0,0 +1,40 @@
+-- Item Pricing Tables
+
+-- Price lists (customer_id NULL = default list)
+CREATE TABLE IF NOT EXISTS price_lists (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    customer_id TEXT,
+    currency_code TEXT NOT NULL DEFAULT 'USD',
+    effective_from TEXT NOT NULL,
+    effective_to TEXT, -- NULL = open-ended
+    is_active INTEGER NOT NULL DEFAULT 1,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TEXT,
+    FOREIGN KEY (customer_id) REFERENCES customers(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_price_lists_customer ON price_lists(customer_id);
+CREATE INDEX IF NOT EXISTS idx_price_lists_effective ON price_lists(effective_from);
+
+-- Price list entries
+CREATE TABLE IF NOT EXISTS price_list_items (
+    id TEXT PRIMARY KEY,
+    price_list_id TEXT NOT NULL,
+    item_id TEXT NOT NULL,
+    unit_price REAL NOT NULL,
+    FOREIGN KEY (price_list_id) REFERENCES price_lists(id) ON DELETE CASCADE,
+    FOREIGN KEY (item_id) REFERENCES inventory_items(id),
+    UNIQUE(price_list_id, item_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_price_list_items_item ON price_list_items(item_id);
+
+-- Line values resolved at shipment confirmation
+ALTER TABLE shipment_items ADD COLUMN unit_price REAL;
+ALTER TABLE shipment_items ADD COLUMN extended_price REAL;
+
+-- Shipment totals (subtotal + shipping_cost = order_total)
+ALTER TABLE shipments ADD COLUMN subtotal REAL;
+ALTER TABLE shipments ADD COLUMN order_total REAL;
+ALTER TABLE shipments ADD COLUMN currency_code TEXT;
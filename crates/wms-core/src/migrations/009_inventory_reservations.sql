@@ -0,0 +1,21 @@
+-- Inventory Reservations
+
+-- Stock held back from promising (e.g. quotes, customer holds)
+CREATE TABLE IF NOT EXISTS inventory_reservations (
+    id TEXT PRIMARY KEY,
+    item_id TEXT NOT NULL,
+    location_id TEXT,
+    quantity REAL NOT NULL,
+    reference_type TEXT, -- QUOTE, HOLD, etc.
+    reference_id TEXT,
+    status TEXT NOT NULL DEFAULT 'active', -- active, released, fulfilled
+    expires_at TEXT,
+    created_by TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (item_id) REFERENCES inventory_items(id),
+    FOREIGN KEY (location_id) REFERENCES locations(id),
+    FOREIGN KEY (created_by) REFERENCES users(id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_reservations_item ON inventory_reservations(item_id);
+CREATE INDEX IF NOT EXISTS idx_reservations_status ON inventory_reservations(status);
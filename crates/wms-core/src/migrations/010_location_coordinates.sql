@@ -0,0 +1,5 @@
+-- Location Coordinates for Pick Path Optimization
+
+-- Position in meters: x across aisles, y along the aisle from the front cross-aisle
+ALTER TABLE locations ADD COLUMN x_m REAL;
+ALTER TABLE locations ADD COLUMN y_m REAL;
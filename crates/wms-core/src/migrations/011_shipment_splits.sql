@@ -0,0 +1,6 @@
+-- Shipment Splits
+
+-- Backorder shipments point at the shipment they were split from
+ALTER TABLE shipments ADD COLUMN parent_shipment_id TEXT REFERENCES shipments(id);
+
+CREATE INDEX IF NOT EXISTS idx_shipments_parent ON shipments(parent_shipment_id);
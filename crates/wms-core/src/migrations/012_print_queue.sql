@@ -0,0 +1,29 @@
+-- Label Printers and Print Queue
+
+-- Network label printers (raw ZPL over TCP)
+CREATE TABLE IF NOT EXISTS printers (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    host TEXT NOT NULL,
+    port INTEGER NOT NULL DEFAULT 9100,
+    paused_reason TEXT, -- set while the queue is held, e.g. paper out
+    is_active INTEGER NOT NULL DEFAULT 1,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Print jobs, drained serially per printer
+CREATE TABLE IF NOT EXISTS print_jobs (
+    id TEXT PRIMARY KEY,
+    label_id TEXT NOT NULL,
+    printer_id TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'queued', -- queued, printing, failed, done, cancelled
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT,
+    next_attempt_at TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TEXT,
+    FOREIGN KEY (label_id) REFERENCES shipping_labels(id),
+    FOREIGN KEY (printer_id) REFERENCES printers(id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_print_jobs_printer ON print_jobs(printer_id, status);
@@ -0,0 +1,21 @@
+-- Quality Holds
+
+-- Lots quarantined pending inspection; a hold is active until released
+CREATE TABLE IF NOT EXISTS quality_holds (
+    id TEXT PRIMARY KEY,
+    item_id TEXT NOT NULL,
+    lot_number TEXT NOT NULL,
+    reason TEXT NOT NULL,
+    placed_by TEXT NOT NULL,
+    placed_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    released_by TEXT,
+    released_at TEXT,
+    disposition TEXT, -- release, scrap, return_to_vendor
+    notes TEXT,
+    FOREIGN KEY (item_id) REFERENCES inventory_items(id),
+    FOREIGN KEY (placed_by) REFERENCES users(id),
+    FOREIGN KEY (released_by) REFERENCES users(id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_quality_holds_lot ON quality_holds(item_id, lot_number);
+CREATE INDEX IF NOT EXISTS idx_quality_holds_active ON quality_holds(released_at);
@@ -0,0 +1,23 @@
+-- Carrier Tracking Events
+
+-- Scans reported by carriers, from webhooks or polling
+CREATE TABLE IF NOT EXISTS tracking_events (
+    id TEXT PRIMARY KEY,
+    shipment_id TEXT NOT NULL,
+    carrier_code TEXT NOT NULL,
+    tracking_number TEXT NOT NULL,
+    carrier_status TEXT NOT NULL, -- carrier's own status code or text
+    status TEXT, -- shipment status implied by the event, if any
+    description TEXT,
+    location TEXT,
+    occurred_at TEXT NOT NULL,
+    source TEXT NOT NULL, -- webhook, poll
+    received_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (shipment_id) REFERENCES shipments(id),
+    UNIQUE(shipment_id, carrier_status, occurred_at)
+);
+
+CREATE INDEX IF NOT EXISTS idx_tracking_events_shipment ON tracking_events(shipment_id);
+
+-- Last time the carrier was polled for a shipment
+ALTER TABLE shipments ADD COLUMN tracking_checked_at TEXT;
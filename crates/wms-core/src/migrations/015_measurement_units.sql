@@ -0,0 +1,7 @@
+-- Measurement Units
+
+-- Display units per category (metric or imperial); stored values stay metric
+INSERT OR IGNORE INTO settings (key, value, description) VALUES
+    ('units_weight', 'metric', 'Units for displaying weights (metric: kg, imperial: lb)'),
+    ('units_length', 'metric', 'Units for displaying dimensions (metric: cm, imperial: in)'),
+    ('units_distance', 'metric', 'Units for displaying distances (metric: km, imperial: mi)');
@@ -0,0 +1,38 @@
+-- Row Versions
+
+-- Optimistic concurrency: every write to an editable record bumps its version.
+-- Edits carry the version they were based on and fail if it has moved on.
+ALTER TABLE inventory_items ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE customers ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE shipments ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE deliveries ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+
+-- Writes that don't set the version themselves (status changes, sync inbox
+-- upserts) are bumped here. Carrier polling bookkeeping is not an edit.
+CREATE TRIGGER IF NOT EXISTS trg_inventory_items_version
+AFTER UPDATE ON inventory_items
+WHEN NEW.version = OLD.version
+BEGIN
+    UPDATE inventory_items SET version = OLD.version + 1 WHERE id = NEW.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_customers_version
+AFTER UPDATE ON customers
+WHEN NEW.version = OLD.version
+BEGIN
+    UPDATE customers SET version = OLD.version + 1 WHERE id = NEW.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_shipments_version
+AFTER UPDATE ON shipments
+WHEN NEW.version = OLD.version AND NEW.tracking_checked_at IS OLD.tracking_checked_at
+BEGIN
+    UPDATE shipments SET version = OLD.version + 1 WHERE id = NEW.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_deliveries_version
+AFTER UPDATE ON deliveries
+WHEN NEW.version = OLD.version
+BEGIN
+    UPDATE deliveries SET version = OLD.version + 1 WHERE id = NEW.id;
+END;
@@ -0,0 +1,22 @@
+-- Carrier Invoices
+
+-- What carriers billed per tracking number. Re-importing an invoice row for
+-- the same tracking number replaces it rather than adding to it.
+CREATE TABLE IF NOT EXISTS carrier_invoices (
+    id TEXT PRIMARY KEY,
+    carrier_id TEXT NOT NULL,
+    tracking_number TEXT NOT NULL,
+    shipment_id TEXT, -- NULL until a shipment with this tracking number exists
+    invoice_number TEXT,
+    billed_amount REAL NOT NULL, -- before surcharges
+    surcharges TEXT NOT NULL DEFAULT '[]', -- JSON array of {surcharge_type, amount}
+    surcharge_total REAL NOT NULL DEFAULT 0,
+    billed_weight_kg REAL,
+    imported_at TEXT NOT NULL,
+    FOREIGN KEY (carrier_id) REFERENCES carriers(id),
+    FOREIGN KEY (shipment_id) REFERENCES shipments(id),
+    UNIQUE(carrier_id, tracking_number)
+);
+
+CREATE INDEX IF NOT EXISTS idx_carrier_invoices_shipment ON carrier_invoices(shipment_id);
+CREATE INDEX IF NOT EXISTS idx_shipments_tracking ON shipments(tracking_number);
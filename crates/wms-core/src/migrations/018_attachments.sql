@@ -0,0 +1,23 @@
+-- Attachments
+
+-- Files attached to records (item photos, proof of delivery, documents).
+-- Content lives in the blob, or on disk at file_path when a storage
+-- directory is configured.
+CREATE TABLE IF NOT EXISTS attachments (
+    id TEXT PRIMARY KEY,
+    owner_type TEXT NOT NULL, -- inventory_item, delivery, ...
+    owner_id TEXT NOT NULL,
+    kind TEXT NOT NULL, -- image, proof_of_delivery, document
+    filename TEXT NOT NULL,
+    content_type TEXT NOT NULL,
+    size_bytes INTEGER NOT NULL,
+    content BLOB,
+    file_path TEXT,
+    thumbnail BLOB, -- JPEG, images only
+    is_primary INTEGER NOT NULL DEFAULT 0,
+    created_by TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    FOREIGN KEY (created_by) REFERENCES users(id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_attachments_owner ON attachments(owner_type, owner_id);
@@ -0,0 +1,18 @@
+-- Scan Events
+
+-- Every accepted barcode scan in receiving, picking and putaway. Used to
+-- reject duplicate scans and to trace mispicks.
+CREATE TABLE IF NOT EXISTS scan_events (
+    id TEXT PRIMARY KEY,
+    context TEXT NOT NULL, -- receipt, pick, putaway
+    context_id TEXT NOT NULL, -- receipt or shipment ID
+    barcode TEXT NOT NULL,
+    entity_type TEXT NOT NULL, -- what the barcode resolved to
+    entity_id TEXT NOT NULL,
+    quantity REAL NOT NULL,
+    user_id TEXT NOT NULL,
+    scanned_at TEXT NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_scan_events_context ON scan_events(context_id, context, barcode, scanned_at);
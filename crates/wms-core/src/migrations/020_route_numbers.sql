@@ -0,0 +1,7 @@
+-- Route Numbers
+
+-- Optimized routes are persisted in delivery_routes; route_number is the
+-- identifier shown to dispatchers and drivers (RTE-00000001).
+ALTER TABLE delivery_routes ADD COLUMN route_number TEXT;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_routes_number ON delivery_routes(route_number);
@@ -0,0 +1,13 @@
+-- Item Variants
+
+-- A variant is an item linked to a parent item (e.g. a T-shirt in size M,
+-- red). variant_attributes holds the attribute values as a JSON object with
+-- sorted keys, so each combination is stored the same way and can be kept
+-- unique per parent.
+ALTER TABLE inventory_items ADD COLUMN parent_item_id TEXT REFERENCES inventory_items(id);
+ALTER TABLE inventory_items ADD COLUMN variant_attributes TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_inventory_items_parent ON inventory_items(parent_item_id);
+CREATE UNIQUE INDEX IF NOT EXISTS idx_inventory_items_variant
+    ON inventory_items(parent_item_id, variant_attributes)
+    WHERE parent_item_id IS NOT NULL;
@@ -0,0 +1,40 @@
+-- Pick-face Replenishment
+
+-- Min/max levels for an item at a pick-face location
+CREATE TABLE IF NOT EXISTS replenishment_rules (
+    id TEXT PRIMARY KEY,
+    item_id TEXT NOT NULL,
+    location_id TEXT NOT NULL,
+    min_quantity REAL NOT NULL,
+    max_quantity REAL NOT NULL,
+    is_active INTEGER NOT NULL DEFAULT 1,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TEXT,
+    FOREIGN KEY (item_id) REFERENCES inventory_items(id),
+    FOREIGN KEY (location_id) REFERENCES locations(id),
+    UNIQUE(item_id, location_id)
+);
+
+-- Moves from bulk storage to a pick face; open until confirmed
+CREATE TABLE IF NOT EXISTS replenishment_tasks (
+    id TEXT PRIMARY KEY,
+    rule_id TEXT NOT NULL,
+    item_id TEXT NOT NULL,
+    from_location_id TEXT NOT NULL,
+    to_location_id TEXT NOT NULL,
+    lot_number TEXT,
+    quantity REAL NOT NULL,
+    quantity_moved REAL,
+    priority TEXT NOT NULL DEFAULT 'normal', -- normal, urgent
+    status TEXT NOT NULL DEFAULT 'open', -- open, completed, cancelled
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    completed_by TEXT,
+    completed_at TEXT,
+    FOREIGN KEY (rule_id) REFERENCES replenishment_rules(id),
+    FOREIGN KEY (item_id) REFERENCES inventory_items(id),
+    FOREIGN KEY (from_location_id) REFERENCES locations(id),
+    FOREIGN KEY (to_location_id) REFERENCES locations(id),
+    FOREIGN KEY (completed_by) REFERENCES users(id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_replenishment_tasks_open ON replenishment_tasks(status, rule_id);
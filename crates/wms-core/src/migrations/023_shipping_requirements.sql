@@ -0,0 +1,28 @@
+-- Customer Shipping Requirements
+
+-- Routing guide a customer imposes on shipments to them
+CREATE TABLE IF NOT EXISTS customer_shipping_requirements (
+    customer_id TEXT PRIMARY KEY,
+    required_carrier_id TEXT,
+    required_service TEXT,
+    allow_partial INTEGER NOT NULL DEFAULT 1,
+    appointment_required INTEGER NOT NULL DEFAULT 0,
+    label_notes TEXT,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TEXT,
+    FOREIGN KEY (customer_id) REFERENCES customers(id),
+    FOREIGN KEY (required_carrier_id) REFERENCES carriers(id)
+);
+
+-- Shipments confirmed despite blocking compliance violations
+CREATE TABLE IF NOT EXISTS shipment_compliance_overrides (
+    id TEXT PRIMARY KEY,
+    shipment_id TEXT NOT NULL,
+    violations TEXT NOT NULL, -- JSON array of the violations overridden
+    reason TEXT NOT NULL,
+    overridden_by TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (shipment_id) REFERENCES shipments(id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_compliance_overrides_shipment ON shipment_compliance_overrides(shipment_id);
@@ -0,0 +1,10 @@
+-- Dashboard Metrics History
+
+-- One value per metric per day, rewritten as the day goes on
+CREATE TABLE IF NOT EXISTS metrics_history (
+    metric TEXT NOT NULL,
+    day TEXT NOT NULL,
+    value REAL NOT NULL,
+    recorded_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (metric, day)
+);
@@ -0,0 +1,15 @@
+-- Damage Evidence on Receipt and Shipment Lines
+
+-- Notes and photos recorded against a line for a damage claim
+CREATE TABLE IF NOT EXISTS line_evidence (
+    id TEXT PRIMARY KEY,
+    line_type TEXT NOT NULL, -- receipt_item, shipment_item
+    line_id TEXT NOT NULL,
+    note TEXT,
+    attachment_id TEXT, -- photo, stored with the line as owner
+    created_by TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (attachment_id) REFERENCES attachments(id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_line_evidence_line ON line_evidence(line_type, line_id);
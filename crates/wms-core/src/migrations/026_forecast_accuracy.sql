@@ -0,0 +1,33 @@
+-- Forecast Accuracy
+
+-- Each day a forecast predicted, from the primary model and the models it
+-- is compared with; actual demand is filled in once the day has passed
+CREATE TABLE IF NOT EXISTS forecast_predictions (
+    id TEXT PRIMARY KEY,
+    item_id TEXT NOT NULL,
+    model_type TEXT NOT NULL,
+    forecast_date TEXT NOT NULL,
+    target_date TEXT NOT NULL,
+    horizon_days INTEGER NOT NULL,
+    predicted_demand REAL NOT NULL,
+    actual_demand REAL,
+    evaluated_at TEXT,
+    FOREIGN KEY (item_id) REFERENCES inventory_items(id),
+    UNIQUE(item_id, model_type, forecast_date, target_date)
+);
+
+CREATE INDEX IF NOT EXISTS idx_forecast_predictions_target ON forecast_predictions(target_date);
+CREATE INDEX IF NOT EXISTS idx_forecast_predictions_item ON forecast_predictions(item_id);
+
+-- Rolling accuracy per item and model as of each evaluation; item_id ''
+-- holds the metrics across all items
+CREATE TABLE IF NOT EXISTS forecast_accuracy (
+    item_id TEXT NOT NULL,
+    model_type TEXT NOT NULL,
+    evaluated_on TEXT NOT NULL,
+    samples INTEGER NOT NULL,
+    mape REAL,
+    bias REAL NOT NULL,
+    mae REAL NOT NULL,
+    PRIMARY KEY (item_id, model_type, evaluated_on)
+);
@@ -0,0 +1,57 @@
+-- Delivery Notifications
+
+-- Customers who do not want delivery texts or emails
+ALTER TABLE customers ADD COLUMN notifications_opt_out INTEGER NOT NULL DEFAULT 0;
+
+-- Message templates per trigger; {{placeholders}} are filled per delivery
+CREATE TABLE IF NOT EXISTS notification_rules (
+    trigger_type TEXT PRIMARY KEY, -- route_started, up_next, delivered
+    sms_template TEXT,
+    email_subject TEXT,
+    email_template TEXT,
+    is_active INTEGER NOT NULL DEFAULT 1,
+    updated_at TEXT
+);
+
+INSERT OR IGNORE INTO notification_rules (trigger_type, sms_template, email_subject, email_template) VALUES
+    ('route_started',
+     'Your delivery {{delivery_number}} is on its way and should arrive between {{window_start}} and {{window_end}}.',
+     'Your delivery {{delivery_number}} is on its way',
+     'Hello {{customer_name}},
+
+Your delivery {{delivery_number}} left our warehouse and should arrive between {{window_start}} and {{window_end}}.'),
+    ('up_next',
+     'Your delivery {{delivery_number}} is next. Our driver should arrive around {{eta}}.',
+     'Your delivery {{delivery_number}} is next',
+     'Hello {{customer_name}},
+
+Our driver is on the way to you now and should arrive around {{eta}}.'),
+    ('delivered',
+     'Your delivery {{delivery_number}} was delivered at {{delivered_at}}. {{pod_summary}}',
+     'Your delivery {{delivery_number}} was delivered',
+     'Hello {{customer_name}},
+
+Your delivery {{delivery_number}} was delivered at {{delivered_at}}.
+
+{{pod_summary}}');
+
+-- Messages waiting for (or handed to) the SMS/email sender. A delivery gets
+-- each trigger once per channel.
+CREATE TABLE IF NOT EXISTS delivery_notifications (
+    id TEXT PRIMARY KEY,
+    delivery_id TEXT NOT NULL,
+    route_id TEXT,
+    trigger_type TEXT NOT NULL,
+    channel TEXT NOT NULL, -- sms, email
+    recipient TEXT NOT NULL,
+    subject TEXT,
+    body TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'queued', -- queued, sent, failed
+    created_at TEXT NOT NULL,
+    sent_at TEXT,
+    error TEXT,
+    FOREIGN KEY (delivery_id) REFERENCES deliveries(id),
+    UNIQUE(delivery_id, trigger_type, channel)
+);
+
+CREATE INDEX IF NOT EXISTS idx_delivery_notifications_status ON delivery_notifications(status);
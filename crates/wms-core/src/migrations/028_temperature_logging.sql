@@ -0,0 +1,30 @@
+-- Cold-Chain Temperature Logging
+
+-- Allowed temperatures for item categories that need a cold chain
+CREATE TABLE IF NOT EXISTS category_temperature_ranges (
+    category TEXT PRIMARY KEY,
+    min_celsius REAL,
+    max_celsius REAL,
+    updated_at TEXT
+);
+
+-- Probe readings taken on receipt lines and deliveries; the range in force
+-- is kept with each reading for audits
+CREATE TABLE IF NOT EXISTS temperature_readings (
+    id TEXT PRIMARY KEY,
+    owner_type TEXT NOT NULL, -- receipt_item, delivery
+    owner_id TEXT NOT NULL,
+    probe_id TEXT,
+    celsius REAL NOT NULL,
+    min_celsius REAL,
+    max_celsius REAL,
+    out_of_range INTEGER NOT NULL DEFAULT 0,
+    recorded_at TEXT NOT NULL,
+    recorded_by TEXT NOT NULL,
+    FOREIGN KEY (recorded_by) REFERENCES users(id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_temperature_readings_owner ON temperature_readings(owner_type, owner_id);
+
+-- Set when a reading on the delivery was out of range
+ALTER TABLE deliveries ADD COLUMN temperature_warning TEXT;
@@ -0,0 +1,49 @@
+-- Scheduled Customer Data Extracts
+
+-- Items stocked on consignment for a customer; included in their stock extract
+ALTER TABLE inventory_items ADD COLUMN consignment_customer_id TEXT REFERENCES customers(id);
+
+-- Nightly (or otherwise scheduled) files sent to customers
+CREATE TABLE IF NOT EXISTS extract_definitions (
+    id TEXT PRIMARY KEY,
+    customer_id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    dataset TEXT NOT NULL, -- shipments, deliveries, stock
+    format TEXT NOT NULL, -- csv, json
+    schedule TEXT NOT NULL, -- cron expression, UTC
+    destination_type TEXT NOT NULL, -- local, sftp
+    local_path TEXT,
+    sftp_host TEXT,
+    sftp_port INTEGER,
+    sftp_username TEXT,
+    sftp_password TEXT,
+    sftp_directory TEXT,
+    sftp_host_key TEXT, -- expected SHA-256 of the server's host key
+    is_active INTEGER NOT NULL DEFAULT 1,
+    next_run_at TEXT,
+    -- Consecutive failed attempts of the current scheduled run
+    failed_attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT,
+    created_at TEXT NOT NULL,
+    updated_at TEXT,
+    FOREIGN KEY (customer_id) REFERENCES customers(id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_extract_definitions_customer ON extract_definitions(customer_id);
+
+-- One row per attempt, successful or not
+CREATE TABLE IF NOT EXISTS extract_runs (
+    id TEXT PRIMARY KEY,
+    definition_id TEXT NOT NULL,
+    trigger_type TEXT NOT NULL, -- schedule, retry, manual
+    status TEXT NOT NULL, -- succeeded, failed
+    attempt INTEGER NOT NULL DEFAULT 1,
+    row_count INTEGER NOT NULL DEFAULT 0,
+    files TEXT, -- JSON list of files with sizes and checksums
+    error TEXT,
+    started_at TEXT NOT NULL,
+    finished_at TEXT NOT NULL,
+    FOREIGN KEY (definition_id) REFERENCES extract_definitions(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_extract_runs_definition ON extract_runs(definition_id, started_at);
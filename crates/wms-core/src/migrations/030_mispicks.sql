@@ -0,0 +1,20 @@
+-- Mispicks
+
+-- A pick that had to be corrected, counted against the picker's accuracy
+CREATE TABLE IF NOT EXISTS mispicks (
+    id TEXT PRIMARY KEY,
+    shipment_item_id TEXT NOT NULL,
+    item_id TEXT NOT NULL,
+    location_id TEXT,
+    quantity REAL NOT NULL, -- quantity put back
+    picked_by TEXT NOT NULL,
+    picked_at TEXT,
+    reason TEXT,
+    corrected_by TEXT NOT NULL,
+    corrected_at TEXT NOT NULL,
+    FOREIGN KEY (shipment_item_id) REFERENCES shipment_items(id),
+    FOREIGN KEY (picked_by) REFERENCES users(id),
+    FOREIGN KEY (corrected_by) REFERENCES users(id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_mispicks_picker ON mispicks(picked_by, corrected_at);
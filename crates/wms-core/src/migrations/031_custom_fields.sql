@@ -0,0 +1,23 @@
+-- Custom Fields
+
+-- Deployment-defined fields on items, customers, shipments and deliveries
+CREATE TABLE IF NOT EXISTS custom_field_definitions (
+    id TEXT PRIMARY KEY,
+    entity TEXT NOT NULL, -- inventory_item, customer, shipment, delivery
+    field_key TEXT NOT NULL,
+    label TEXT NOT NULL,
+    field_type TEXT NOT NULL, -- text, number, date, select
+    options TEXT, -- JSON array of select options
+    required INTEGER NOT NULL DEFAULT 0,
+    show_in_list INTEGER NOT NULL DEFAULT 0,
+    sort_order INTEGER NOT NULL DEFAULT 0,
+    created_at TEXT NOT NULL,
+    updated_at TEXT,
+    UNIQUE(entity, field_key)
+);
+
+-- Values as a JSON object keyed by field_key
+ALTER TABLE inventory_items ADD COLUMN custom_fields TEXT;
+ALTER TABLE customers ADD COLUMN custom_fields TEXT;
+ALTER TABLE shipments ADD COLUMN custom_fields TEXT;
+ALTER TABLE deliveries ADD COLUMN custom_fields TEXT;
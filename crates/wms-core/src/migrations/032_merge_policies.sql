@@ -0,0 +1,11 @@
+-- Sync Merge Policies
+
+-- Which table policy (crdt_merge, last_writer_wins, append_only_log)
+-- resolved each logged conflict; resolution_strategy records how
+ALTER TABLE sync_conflicts ADD COLUMN merge_policy TEXT;
+
+-- Append-only logs synced alongside the records they belong to
+INSERT OR IGNORE INTO sync_status (id, table_name) VALUES
+    ('sync_inventory_transactions', 'inventory_transactions'),
+    ('sync_customer_interactions', 'customer_interactions'),
+    ('sync_attachments', 'attachments');
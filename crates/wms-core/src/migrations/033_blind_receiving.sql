@@ -0,0 +1,10 @@
+-- Blind Receiving
+
+-- Counted without showing expected quantities when blind_receiving is
+-- per_receipt
+ALTER TABLE receipts ADD COLUMN blind_count INTEGER NOT NULL DEFAULT 0;
+
+-- Supervisor who accepted variances beyond tolerance, letting the receipt
+-- complete
+ALTER TABLE receipts ADD COLUMN variance_acknowledged_by TEXT REFERENCES users(id);
+ALTER TABLE receipts ADD COLUMN variance_acknowledged_at TEXT;
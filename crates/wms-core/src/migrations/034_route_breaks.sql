@@ -0,0 +1,13 @@
+-- Route Breaks
+
+-- Breaks the driver is scheduled to take on a route, so arrival estimates
+-- account for them
+CREATE TABLE IF NOT EXISTS route_breaks (
+    id TEXT PRIMARY KEY,
+    route_id TEXT NOT NULL,
+    starts_at TEXT NOT NULL,
+    duration_minutes INTEGER NOT NULL,
+    FOREIGN KEY (route_id) REFERENCES delivery_routes(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_route_breaks_route ON route_breaks(route_id);
@@ -0,0 +1,43 @@
+-- Supervisor Overrides
+
+-- Reasons a supervisor may give for overriding a guard; an action code
+-- limits a reason to one kind of override, NULL allows it for any
+CREATE TABLE IF NOT EXISTS override_reason_codes (
+    code TEXT PRIMARY KEY,
+    description TEXT NOT NULL,
+    action_code TEXT,
+    requires_note INTEGER NOT NULL DEFAULT 0,
+    is_active INTEGER NOT NULL DEFAULT 1
+);
+
+INSERT OR IGNORE INTO override_reason_codes (code, description, action_code, requires_note) VALUES
+    ('SUPPLIER_OVERSHIP', 'Supplier shipped more than ordered and it is accepted', 'over_receipt', 0),
+    ('DAMAGE_REPLACEMENT', 'Extra units sent to replace damaged goods', 'over_receipt', 0),
+    ('OTHER', 'Other reason', NULL, 1);
+
+-- Every approved override. A token is good for one use of the guard it
+-- was approved for, until it expires.
+CREATE TABLE IF NOT EXISTS overrides (
+    id TEXT PRIMARY KEY,
+    action_code TEXT NOT NULL,
+    entity_type TEXT NOT NULL,
+    entity_id TEXT NOT NULL,
+    reason_code TEXT NOT NULL,
+    note TEXT,
+    requested_by TEXT,
+    approved_by TEXT NOT NULL,
+    approval_method TEXT NOT NULL, -- session, pin
+    created_at TEXT NOT NULL,
+    expires_at TEXT NOT NULL,
+    used_at TEXT,
+    FOREIGN KEY (reason_code) REFERENCES override_reason_codes(code),
+    FOREIGN KEY (approved_by) REFERENCES users(id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_overrides_approved_by ON overrides(approved_by);
+CREATE INDEX IF NOT EXISTS idx_overrides_action ON overrides(action_code);
+CREATE INDEX IF NOT EXISTS idx_overrides_created ON overrides(created_at);
+
+-- Salted hash of the PIN a supervisor enters to approve at someone
+-- else's station
+ALTER TABLE users ADD COLUMN override_pin_hash TEXT;
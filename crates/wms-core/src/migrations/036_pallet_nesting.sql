@@ -0,0 +1,14 @@
+-- License Plates and Pallet Nesting
+
+-- Every package carries a license plate number (an SSCC-18). Cartons built
+-- onto a pallet point at the pallet's package.
+ALTER TABLE shipment_packages ADD COLUMN lpn TEXT;
+ALTER TABLE shipment_packages ADD COLUMN parent_package_id TEXT REFERENCES shipment_packages(id);
+
+-- Closed packages are packed and must be palletized or floor-loaded
+-- before the shipment ships
+ALTER TABLE shipment_packages ADD COLUMN closed_at TEXT;
+ALTER TABLE shipment_packages ADD COLUMN floor_loaded INTEGER NOT NULL DEFAULT 0;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_shipment_packages_lpn ON shipment_packages(lpn);
+CREATE INDEX IF NOT EXISTS idx_shipment_packages_parent ON shipment_packages(parent_package_id);
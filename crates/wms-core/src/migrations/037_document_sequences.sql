@@ -0,0 +1,25 @@
+-- Document Number Sequences
+
+-- Shipment, receipt, delivery, route and customer numbers are allocated
+-- from counters here rather than by counting rows, so concurrent creates
+-- and deleted records never produce the same number twice. Each sequence
+-- prints as PREFIX-000042, with a configurable prefix and zero padding.
+CREATE TABLE IF NOT EXISTS document_sequences (
+    name TEXT PRIMARY KEY,
+    prefix TEXT NOT NULL,
+    width INTEGER NOT NULL DEFAULT 8,
+    last_value INTEGER NOT NULL DEFAULT 0,
+    updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Continue after the highest number already issued
+INSERT OR IGNORE INTO document_sequences (name, prefix, width, last_value)
+SELECT 'shipment', 'SHP', 8, COALESCE(MAX(CAST(substr(shipment_number, 5) AS INTEGER)), 0) FROM shipments;
+INSERT OR IGNORE INTO document_sequences (name, prefix, width, last_value)
+SELECT 'receipt', 'RCV', 8, COALESCE(MAX(CAST(substr(receipt_number, 5) AS INTEGER)), 0) FROM receipts;
+INSERT OR IGNORE INTO document_sequences (name, prefix, width, last_value)
+SELECT 'delivery', 'DEL', 8, COALESCE(MAX(CAST(substr(delivery_number, 5) AS INTEGER)), 0) FROM deliveries;
+INSERT OR IGNORE INTO document_sequences (name, prefix, width, last_value)
+SELECT 'route', 'RTE', 8, COALESCE(MAX(CAST(substr(route_number, 5) AS INTEGER)), 0) FROM delivery_routes;
+INSERT OR IGNORE INTO document_sequences (name, prefix, width, last_value)
+SELECT 'customer', 'CUS', 6, COALESCE(MAX(CAST(substr(customer_number, 5) AS INTEGER)), 0) FROM customers;
@@ -0,0 +1,7 @@
+-- Sync Merges
+
+-- Stock ledger entries written when a synced stock document changes the
+-- local quantity (transaction_type MERGE) need an author. This account is
+-- inactive so it never appears among the people who can sign in.
+INSERT OR IGNORE INTO users (id, username, email, full_name, role, is_active)
+VALUES ('sync', 'sync', 'sync@localhost', 'Sync', 'operator', 0);
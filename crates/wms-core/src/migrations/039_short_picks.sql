@@ -0,0 +1,5 @@
+-- Short Picks
+
+-- Why a shipment line was closed with less picked than ordered. A line with
+-- a reason counts as done when picking is confirmed complete.
+ALTER TABLE shipment_items ADD COLUMN short_pick_reason TEXT;
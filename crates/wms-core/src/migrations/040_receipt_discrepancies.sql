@@ -0,0 +1,26 @@
+-- Receipt Discrepancies
+
+-- Quantity already scanned into a location, so completing the receipt only
+-- posts what is still on the dock
+ALTER TABLE receipt_items ADD COLUMN quantity_put_away REAL NOT NULL DEFAULT 0;
+
+-- Lines received differently from what was expected; one row per line and
+-- kind, kept current as the line is recounted
+CREATE TABLE IF NOT EXISTS receipt_discrepancies (
+    id TEXT PRIMARY KEY,
+    receipt_id TEXT NOT NULL,
+    receipt_item_id TEXT NOT NULL,
+    item_id TEXT NOT NULL,
+    discrepancy_type TEXT NOT NULL, -- over_receipt
+    quantity_expected REAL NOT NULL,
+    quantity_received REAL NOT NULL,
+    recorded_by TEXT,
+    recorded_at TEXT NOT NULL,
+    UNIQUE (receipt_item_id, discrepancy_type),
+    FOREIGN KEY (receipt_id) REFERENCES receipts(id) ON DELETE CASCADE,
+    FOREIGN KEY (receipt_item_id) REFERENCES receipt_items(id) ON DELETE CASCADE,
+    FOREIGN KEY (item_id) REFERENCES inventory_items(id),
+    FOREIGN KEY (recorded_by) REFERENCES users(id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_receipt_discrepancies_receipt ON receipt_discrepancies(receipt_id);
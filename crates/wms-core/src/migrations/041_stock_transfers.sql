@@ -0,0 +1,6 @@
+-- Stock Transfers
+
+-- Links the TRANSFER_OUT and TRANSFER_IN halves of a move between locations
+ALTER TABLE inventory_transactions ADD COLUMN transfer_id TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_inventory_tx_transfer ON inventory_transactions(transfer_id);
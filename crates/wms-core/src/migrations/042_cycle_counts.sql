@@ -0,0 +1,44 @@
+-- Cycle Counts
+
+-- A count of one zone or location, optionally limited to an ABC class
+CREATE TABLE IF NOT EXISTS count_sheets (
+    id TEXT PRIMARY KEY,
+    zone TEXT,
+    location_id TEXT,
+    abc_class TEXT,
+    status TEXT NOT NULL DEFAULT 'open', -- open, posted
+    created_by TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    posted_by TEXT,
+    posted_at TEXT,
+    FOREIGN KEY (location_id) REFERENCES locations(id),
+    FOREIGN KEY (created_by) REFERENCES users(id),
+    FOREIGN KEY (posted_by) REFERENCES users(id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_count_sheets_status ON count_sheets(status);
+
+-- Expected quantity per item, location and lot when the sheet was created,
+-- and what was counted. Lines off by more than the item's tolerance on
+-- their first count are flagged for a recount.
+CREATE TABLE IF NOT EXISTS count_lines (
+    id TEXT PRIMARY KEY,
+    count_id TEXT NOT NULL,
+    item_id TEXT NOT NULL,
+    location_id TEXT NOT NULL,
+    lot_number TEXT NOT NULL DEFAULT '',
+    expected_quantity REAL NOT NULL,
+    counted_quantity REAL,
+    count_attempts INTEGER NOT NULL DEFAULT 0,
+    requires_recount INTEGER NOT NULL DEFAULT 0,
+    counted_by TEXT,
+    counted_at TEXT,
+    posted_at TEXT,
+    UNIQUE (count_id, item_id, location_id, lot_number),
+    FOREIGN KEY (count_id) REFERENCES count_sheets(id) ON DELETE CASCADE,
+    FOREIGN KEY (item_id) REFERENCES inventory_items(id),
+    FOREIGN KEY (location_id) REFERENCES locations(id),
+    FOREIGN KEY (counted_by) REFERENCES users(id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_count_lines_count ON count_lines(count_id);
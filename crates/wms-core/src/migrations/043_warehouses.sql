@@ -0,0 +1,18 @@
+-- Warehouses
+
+-- Sites holding locations; stock belongs to a warehouse through its location
+CREATE TABLE IF NOT EXISTS warehouses (
+    id TEXT PRIMARY KEY,
+    code TEXT NOT NULL UNIQUE,
+    name TEXT NOT NULL,
+    address TEXT,
+    is_active INTEGER NOT NULL DEFAULT 1,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Existing locations all belong to the one site there was so far
+INSERT OR IGNORE INTO warehouses (id, code, name) VALUES ('default', 'MAIN', 'Main warehouse');
+
+ALTER TABLE locations ADD COLUMN warehouse_id TEXT NOT NULL DEFAULT 'default';
+
+CREATE INDEX IF NOT EXISTS idx_locations_warehouse ON locations(warehouse_id);
@@ -0,0 +1,4 @@
+-- Vehicle Shifts
+
+-- Longest route a vehicle may be planned for, in minutes; NULL is unlimited
+ALTER TABLE vehicles ADD COLUMN shift_minutes INTEGER;
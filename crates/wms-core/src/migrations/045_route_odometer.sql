@@ -0,0 +1,5 @@
+-- Route Odometer
+
+-- Vehicle odometer readings when a route starts and completes
+ALTER TABLE delivery_routes ADD COLUMN start_odometer_km REAL;
+ALTER TABLE delivery_routes ADD COLUMN end_odometer_km REAL;
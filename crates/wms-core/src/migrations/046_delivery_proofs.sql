@@ -0,0 +1,20 @@
+-- Proof of Delivery
+
+-- Signatures, photos and notes captured at the door
+CREATE TABLE IF NOT EXISTS delivery_proofs (
+    id TEXT PRIMARY KEY,
+    delivery_id TEXT NOT NULL,
+    proof_type TEXT NOT NULL, -- signature, photo, note
+    attachment_id TEXT, -- signature or photo, stored with the delivery as owner
+    note TEXT,
+    latitude REAL,
+    longitude REAL,
+    captured_by TEXT NOT NULL,
+    captured_at TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (delivery_id) REFERENCES deliveries(id),
+    FOREIGN KEY (attachment_id) REFERENCES attachments(id),
+    FOREIGN KEY (captured_by) REFERENCES users(id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_delivery_proofs_delivery ON delivery_proofs(delivery_id);
@@ -0,0 +1,12 @@
+-- Geofence States
+
+-- Tracked geofence state of each delivery, so arrival detection survives
+-- a restart
+CREATE TABLE IF NOT EXISTS delivery_geofence_states (
+    delivery_id TEXT PRIMARY KEY,
+    is_inside INTEGER NOT NULL DEFAULT 0,
+    entered_at TEXT,
+    dwell_reported INTEGER NOT NULL DEFAULT 0,
+    updated_at TEXT NOT NULL,
+    FOREIGN KEY (delivery_id) REFERENCES deliveries(id) ON DELETE CASCADE
+);
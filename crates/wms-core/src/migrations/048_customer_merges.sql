@@ -0,0 +1,5 @@
+-- Customer Merges
+
+-- Customer a duplicate was merged into; merged customers stay inactive so
+-- their history remains readable
+ALTER TABLE customers ADD COLUMN merged_into TEXT REFERENCES customers(id);
@@ -0,0 +1,9 @@
+-- Shipment Declared Value
+
+-- Value of the goods on a shipment, counted against the customer's credit
+-- limit until it is delivered
+ALTER TABLE shipments ADD COLUMN declared_value REAL;
+
+INSERT OR IGNORE INTO override_reason_codes (code, description, action_code, requires_note) VALUES
+    ('CREDIT_APPROVED', 'Finance approved the order over the credit limit', 'credit_limit', 0),
+    ('PREPAID', 'Customer paid for the order in advance', 'credit_limit', 0);
@@ -0,0 +1,26 @@
+-- Timesheet Periods
+
+-- A user's timesheet for a date range once submitted for approval, with
+-- the summary as it stood at submission
+CREATE TABLE IF NOT EXISTS timesheet_periods (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    start_date TEXT NOT NULL,
+    end_date TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'submitted', -- submitted, approved, rejected
+    regular_hours REAL NOT NULL DEFAULT 0,
+    overtime_hours REAL NOT NULL DEFAULT 0,
+    total_hours REAL NOT NULL DEFAULT 0,
+    total_breaks_minutes INTEGER NOT NULL DEFAULT 0,
+    days_worked INTEGER NOT NULL DEFAULT 0,
+    submitted_at TEXT NOT NULL,
+    reviewed_by TEXT,
+    reviewed_at TEXT,
+    rejection_reason TEXT,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (user_id) REFERENCES users(id),
+    FOREIGN KEY (reviewed_by) REFERENCES users(id),
+    UNIQUE(user_id, start_date, end_date)
+);
+
+CREATE INDEX IF NOT EXISTS idx_timesheet_periods_status ON timesheet_periods(status);
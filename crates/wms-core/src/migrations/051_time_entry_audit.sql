@@ -0,0 +1,21 @@
+-- Time Entry Audit
+
+-- Every manual correction of a time entry, with its times before and after
+CREATE TABLE IF NOT EXISTS time_entry_audit (
+    id TEXT PRIMARY KEY,
+    time_entry_id TEXT NOT NULL,
+    action TEXT NOT NULL, -- edited, added
+    before_clock_in TEXT,
+    before_clock_out TEXT,
+    before_total_hours REAL,
+    after_clock_in TEXT NOT NULL,
+    after_clock_out TEXT,
+    after_total_hours REAL,
+    reason TEXT NOT NULL,
+    edited_by TEXT NOT NULL,
+    edited_at TEXT NOT NULL,
+    FOREIGN KEY (time_entry_id) REFERENCES time_entries(id) ON DELETE CASCADE,
+    FOREIGN KEY (edited_by) REFERENCES users(id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_time_entry_audit_entry ON time_entry_audit(time_entry_id);
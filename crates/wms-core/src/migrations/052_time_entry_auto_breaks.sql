@@ -0,0 +1,5 @@
+-- Time Entry Auto Breaks
+
+-- Break minutes deducted at clock out because no unpaid break was logged;
+-- already included in break_duration_minutes
+ALTER TABLE time_entries ADD COLUMN auto_break_minutes INTEGER NOT NULL DEFAULT 0;
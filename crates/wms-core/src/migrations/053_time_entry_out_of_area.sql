@@ -0,0 +1,5 @@
+-- Time Entry Out Of Area
+
+-- Set when a clock in or out came from outside the site and the policy
+-- was to record it for review
+ALTER TABLE time_entries ADD COLUMN flagged_out_of_area INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,122 @@
+-- Global Search
+
+-- Full-text indexes behind the header search box. Each keeps the id of
+-- the record it indexes rather than sharing its rowid, which VACUUM may
+-- renumber on tables keyed by text.
+CREATE VIRTUAL TABLE IF NOT EXISTS inventory_items_fts USING fts5(
+    id UNINDEXED, sku, name, description,
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+
+CREATE VIRTUAL TABLE IF NOT EXISTS customers_fts USING fts5(
+    id UNINDEXED, customer_number, company_name, first_name, last_name, email,
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+
+CREATE VIRTUAL TABLE IF NOT EXISTS shipments_fts USING fts5(
+    id UNINDEXED, shipment_number, tracking_number,
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+
+CREATE VIRTUAL TABLE IF NOT EXISTS deliveries_fts USING fts5(
+    id UNINDEXED, delivery_number,
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+
+INSERT INTO inventory_items_fts (id, sku, name, description)
+SELECT id, sku, name, description FROM inventory_items;
+
+INSERT INTO customers_fts (id, customer_number, company_name, first_name, last_name, email)
+SELECT id, customer_number, company_name, first_name, last_name, email FROM customers;
+
+INSERT INTO shipments_fts (id, shipment_number, tracking_number)
+SELECT id, shipment_number, tracking_number FROM shipments;
+
+INSERT INTO deliveries_fts (id, delivery_number)
+SELECT id, delivery_number FROM deliveries;
+
+-- Kept in step with every write, sync included. Updates only reindex when
+-- a searched column changes.
+CREATE TRIGGER IF NOT EXISTS trg_inventory_items_fts_insert
+AFTER INSERT ON inventory_items
+BEGIN
+    INSERT INTO inventory_items_fts (id, sku, name, description)
+    VALUES (NEW.id, NEW.sku, NEW.name, NEW.description);
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_inventory_items_fts_update
+AFTER UPDATE OF id, sku, name, description ON inventory_items
+BEGIN
+    DELETE FROM inventory_items_fts WHERE id = OLD.id;
+    INSERT INTO inventory_items_fts (id, sku, name, description)
+    VALUES (NEW.id, NEW.sku, NEW.name, NEW.description);
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_inventory_items_fts_delete
+AFTER DELETE ON inventory_items
+BEGIN
+    DELETE FROM inventory_items_fts WHERE id = OLD.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_customers_fts_insert
+AFTER INSERT ON customers
+BEGIN
+    INSERT INTO customers_fts (id, customer_number, company_name, first_name, last_name, email)
+    VALUES (NEW.id, NEW.customer_number, NEW.company_name, NEW.first_name, NEW.last_name, NEW.email);
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_customers_fts_update
+AFTER UPDATE OF id, customer_number, company_name, first_name, last_name, email ON customers
+BEGIN
+    DELETE FROM customers_fts WHERE id = OLD.id;
+    INSERT INTO customers_fts (id, customer_number, company_name, first_name, last_name, email)
+    VALUES (NEW.id, NEW.customer_number, NEW.company_name, NEW.first_name, NEW.last_name, NEW.email);
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_customers_fts_delete
+AFTER DELETE ON customers
+BEGIN
+    DELETE FROM customers_fts WHERE id = OLD.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_shipments_fts_insert
+AFTER INSERT ON shipments
+BEGIN
+    INSERT INTO shipments_fts (id, shipment_number, tracking_number)
+    VALUES (NEW.id, NEW.shipment_number, NEW.tracking_number);
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_shipments_fts_update
+AFTER UPDATE OF id, shipment_number, tracking_number ON shipments
+BEGIN
+    DELETE FROM shipments_fts WHERE id = OLD.id;
+    INSERT INTO shipments_fts (id, shipment_number, tracking_number)
+    VALUES (NEW.id, NEW.shipment_number, NEW.tracking_number);
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_shipments_fts_delete
+AFTER DELETE ON shipments
+BEGIN
+    DELETE FROM shipments_fts WHERE id = OLD.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_deliveries_fts_insert
+AFTER INSERT ON deliveries
+BEGIN
+    INSERT INTO deliveries_fts (id, delivery_number)
+    VALUES (NEW.id, NEW.delivery_number);
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_deliveries_fts_update
+AFTER UPDATE OF id, delivery_number ON deliveries
+BEGIN
+    DELETE FROM deliveries_fts WHERE id = OLD.id;
+    INSERT INTO deliveries_fts (id, delivery_number)
+    VALUES (NEW.id, NEW.delivery_number);
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_deliveries_fts_delete
+AFTER DELETE ON deliveries
+BEGIN
+    DELETE FROM deliveries_fts WHERE id = OLD.id;
+END;
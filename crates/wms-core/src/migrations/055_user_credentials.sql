@@ -0,0 +1,6 @@
+-- User Credentials
+
+-- Argon2 hash of the PIN or password a user signs in with. Users without
+-- one cannot sign in.
+ALTER TABLE users ADD COLUMN password_hash TEXT;
+ALTER TABLE users ADD COLUMN last_login_at TEXT;
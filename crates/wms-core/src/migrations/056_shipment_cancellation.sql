@@ -0,0 +1,9 @@
+-- Shipment Cancellation
+
+-- Why and when a shipment was cancelled
+ALTER TABLE shipments ADD COLUMN cancel_reason TEXT;
+ALTER TABLE shipments ADD COLUMN cancelled_at TEXT;
+
+-- Labels of a cancelled shipment are kept for the record but can no
+-- longer be printed
+ALTER TABLE shipping_labels ADD COLUMN voided_at TEXT;
@@ -0,0 +1,16 @@
+-- Shipment Line Picks
+
+-- Where the picked quantity of a shipment line was taken from, so a
+-- correction or cancellation puts it back in the same location and lot
+CREATE TABLE IF NOT EXISTS shipment_line_picks (
+    id TEXT PRIMARY KEY,
+    shipment_item_id TEXT NOT NULL,
+    location_id TEXT,
+    lot_number TEXT,
+    quantity REAL NOT NULL, -- still on the line; reduced as picks are put back
+    picked_by TEXT NOT NULL,
+    picked_at TEXT NOT NULL,
+    FOREIGN KEY (shipment_item_id) REFERENCES shipment_items(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_shipment_line_picks_line ON shipment_line_picks(shipment_item_id);
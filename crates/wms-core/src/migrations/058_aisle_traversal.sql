@@ -0,0 +1,5 @@
+-- Aisle Traversal for Pick Path Optimization
+
+-- 'through' aisles open onto both cross-aisles; 'dead_end' aisles are closed
+-- at the back and must be left the way they were entered
+ALTER TABLE locations ADD COLUMN aisle_traversal TEXT NOT NULL DEFAULT 'through';
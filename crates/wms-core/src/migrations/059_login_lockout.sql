@@ -0,0 +1,6 @@
+-- Login Lockout
+
+-- Wrong PINs in a row since the last sign-in; enough of them lock the
+-- account until locked_until
+ALTER TABLE users ADD COLUMN failed_login_attempts INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE users ADD COLUMN locked_until TEXT;
@@ -0,0 +1,366 @@
+//! Cancellable Operations
+//!
+//! Long-running work (route optimization, forecasting, file imports) takes
+//! a `CancellationToken` and checks it between units of work, stopping with
+//! `WmsError::Cancelled` once the token is cancelled or its time limit has
+//! passed. The token also carries progress reports back to the caller.
+//!
+//! Commands start their work through an `OperationRegistry` under an
+//! operation id the frontend chose, so a Cancel button can reach the token
+//! while the command is still running. Each kind of operation has its own
+//! time limit, configured in the settings table.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use crate::db::Database;
+use crate::error::{Result, WmsError};
+use crate::events::{noop_emitter, SharedEmitter, WmsEvent};
+use crate::types::new_id;
+
+/// Least time between two progress reports passed on to the listener
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
+
+type ProgressFn = Box<dyn Fn(u64, Option<u64>) + Send + Sync>;
+
+struct TokenState {
+    label: String,
+    cancelled: AtomicBool,
+    deadline: Option<Instant>,
+    timed_out: AtomicBool,
+    progress: Option<ProgressFn>,
+    last_report: Mutex<Option<Instant>>,
+}
+
+/// Cancellation flag and progress sink shared between an operation and
+/// whoever may cancel it. Clones share the flag.
+#[derive(Clone)]
+pub struct CancellationToken {
+    state: Arc<TokenState>,
+}
+
+impl CancellationToken {
+    /// A token that stops only when cancelled, and reports nowhere
+    pub fn new() -> Self {
+        Self::build("Operation", None, None)
+    }
+
+    fn build(label: &str, deadline: Option<Instant>, progress: Option<ProgressFn>) -> Self {
+        Self {
+            state: Arc::new(TokenState {
+                label: label.to_string(),
+                cancelled: AtomicBool::new(false),
+                deadline,
+                timed_out: AtomicBool::new(false),
+                progress,
+                last_report: Mutex::new(None),
+            }),
+        }
+    }
+
+    /// Ask the operation to stop at its next check
+    pub fn cancel(&self) {
+        self.state.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether the operation has been cancelled or run out of time
+    pub fn is_cancelled(&self) -> bool {
+        self.check().is_err()
+    }
+
+    /// Fail with `WmsError::Cancelled` once cancelled or past the time
+    /// limit. Long-running loops call this between units of work.
+    pub fn check(&self) -> Result<()> {
+        if self.state.cancelled.load(Ordering::Relaxed) {
+            return Err(WmsError::Cancelled(format!("{} was cancelled", self.state.label)));
+        }
+        if self.state.deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            if !self.state.timed_out.swap(true, Ordering::Relaxed) {
+                warn!("{} ran past its time limit and was cancelled", self.state.label);
+            }
+            return Err(WmsError::Cancelled(format!("{} ran past its time limit", self.state.label)));
+        }
+        Ok(())
+    }
+
+    /// Report `completed` units of work out of `total`, if known. Reports
+    /// arriving faster than the listener needs are dropped, except the
+    /// final one.
+    pub fn report(&self, completed: u64, total: Option<u64>) {
+        let Some(progress) = &self.state.progress else {
+            return;
+        };
+        {
+            let now = Instant::now();
+            let mut last = self.state.last_report.lock().unwrap_or_else(|e| e.into_inner());
+            let finished = total == Some(completed);
+            if !finished && last.is_some_and(|at| now - at < PROGRESS_INTERVAL) {
+                return;
+            }
+            *last = Some(now);
+        }
+        progress(completed, total);
+    }
+}
+
+impl Default for CancellationToken {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Kinds of long-running operation, each with its own time limit
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum OperationKind {
+    Forecast,
+    RouteOptimization,
+    Import,
+}
+
+impl OperationKind {
+    pub const ALL: [Self; 3] = [Self::Forecast, Self::RouteOptimization, Self::Import];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Forecast => "forecast",
+            Self::RouteOptimization => "route_optimization",
+            Self::Import => "import",
+        }
+    }
+
+    /// Name used in messages and logs
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Forecast => "Forecast",
+            Self::RouteOptimization => "Route optimization",
+            Self::Import => "Import",
+        }
+    }
+
+    fn default_limit(&self) -> Duration {
+        match self {
+            Self::Forecast => Duration::from_secs(60),
+            Self::RouteOptimization => Duration::from_secs(120),
+            Self::Import => Duration::from_secs(600),
+        }
+    }
+
+    fn setting_key(&self) -> String {
+        format!("operation_timeout_{}_seconds", self.as_str())
+    }
+}
+
+/// Time limits of the kinds of long-running operation
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct OperationTimeouts {
+    /// Seconds each kind may run before it is cancelled; None runs
+    /// without a limit. Kinds left out use their default.
+    pub limits: HashMap<OperationKind, Option<u64>>,
+}
+
+impl Default for OperationTimeouts {
+    fn default() -> Self {
+        Self {
+            limits: OperationKind::ALL.iter()
+                .map(|kind| (*kind, Some(kind.default_limit().as_secs())))
+                .collect(),
+        }
+    }
+}
+
+impl OperationTimeouts {
+    /// Load from the settings table. A stored empty value means no limit;
+    /// missing or invalid values use the default.
+    pub fn load(db: &Database) -> Result<Self> {
+        let mut timeouts = Self::default();
+        for kind in OperationKind::ALL {
+            match db.get_setting(&kind.setting_key())?.as_deref() {
+                Some("") => {
+                    timeouts.limits.insert(kind, None);
+                }
+                Some(value) => {
+                    if let Some(seconds) = value.parse::<u64>().ok().filter(|s| *s > 0) {
+                        timeouts.limits.insert(kind, Some(seconds));
+                    }
+                }
+                None => {}
+            }
+        }
+        Ok(timeouts)
+    }
+
+    /// Store in the settings table
+    pub fn save(&self, db: &Database) -> Result<()> {
+        if self.limits.values().any(|limit| *limit == Some(0)) {
+            return Err(WmsError::validation("Time limits must be at least one second"));
+        }
+        for (kind, limit) in &self.limits {
+            let value = limit.map(|s| s.to_string()).unwrap_or_default();
+            db.set_setting(&kind.setting_key(), &value)?;
+        }
+        Ok(())
+    }
+
+    /// How long `kind` may run
+    pub fn limit(&self, kind: OperationKind) -> Option<Duration> {
+        match self.limits.get(&kind) {
+            Some(limit) => limit.map(Duration::from_secs),
+            None => Some(kind.default_limit()),
+        }
+    }
+}
+
+type Running = Arc<Mutex<HashMap<String, CancellationToken>>>;
+
+/// Operations currently running, by operation id
+#[derive(Clone)]
+pub struct OperationRegistry {
+    running: Running,
+    events: SharedEmitter,
+}
+
+impl OperationRegistry {
+    pub fn new() -> Self {
+        Self { running: Arc::default(), events: noop_emitter() }
+    }
+
+    /// Publish progress as `OperationProgress` events
+    pub fn with_event_emitter(mut self, events: SharedEmitter) -> Self {
+        self.events = events;
+        self
+    }
+
+    /// Register an operation under `operation_id`, or a new id when None,
+    /// cancelled once it runs longer than `time_limit`. It stays
+    /// registered until the returned handle is dropped.
+    pub fn start(
+        &self,
+        kind: OperationKind,
+        operation_id: Option<String>,
+        time_limit: Option<Duration>,
+    ) -> Result<RunningOperation> {
+        let id = operation_id.unwrap_or_else(new_id);
+        let mut running = self.running.lock().map_err(|_| WmsError::LockError)?;
+        if running.contains_key(&id) {
+            return Err(WmsError::conflict(format!("Operation {} is already running", id)));
+        }
+
+        let events = self.events.clone();
+        let operation_id = id.clone();
+        let progress: ProgressFn = Box::new(move |completed, total| {
+            events.emit(WmsEvent::OperationProgress {
+                operation_id: operation_id.clone(),
+                kind: kind.as_str().to_string(),
+                completed,
+                total,
+            });
+        });
+        let token = CancellationToken::build(
+            kind.label(),
+            time_limit.map(|limit| Instant::now() + limit),
+            Some(progress),
+        );
+        running.insert(id.clone(), token.clone());
+
+        Ok(RunningOperation { id, token, running: self.running.clone() })
+    }
+
+    /// Cancel a running operation. False when no operation has that id,
+    /// for example because it already finished.
+    pub fn cancel(&self, operation_id: &str) -> bool {
+        let running = self.running.lock().unwrap_or_else(|e| e.into_inner());
+        match running.get(operation_id) {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl Default for OperationRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A registered operation; dropping it unregisters the id
+pub struct RunningOperation {
+    id: String,
+    token: CancellationToken,
+    running: Running,
+}
+
+impl RunningOperation {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+}
+
+impl Drop for RunningOperation {
+    fn drop(&mut self) {
+        if let Ok(mut running) = self.running.lock() {
+            running.remove(&self.id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::RecordingEmitter;
+
+    #[test]
+    fn test_cancel_reaches_the_running_operation() {
+        let events = Arc::new(RecordingEmitter::new());
+        let registry = OperationRegistry::new().with_event_emitter(events.clone());
+
+        let operation = registry.start(OperationKind::Import, Some("op1".to_string()), None).unwrap();
+        assert!(registry.start(OperationKind::Import, Some("op1".to_string()), None).is_err());
+        operation.token().report(5, Some(10));
+        operation.token().report(6, Some(10));
+        operation.token().report(10, Some(10));
+        assert!(operation.token().check().is_ok());
+
+        assert!(registry.cancel("op1"));
+        assert!(matches!(operation.token().check(), Err(WmsError::Cancelled(_))));
+        drop(operation);
+        assert!(!registry.cancel("op1"));
+
+        // The second report came too soon after the first
+        let completed: Vec<u64> = events.events().into_iter()
+            .filter_map(|e| match e {
+                WmsEvent::OperationProgress { completed, .. } => Some(completed),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(completed, vec![5, 10]);
+    }
+
+    #[test]
+    fn test_time_limit_cancels_the_operation() {
+        let registry = OperationRegistry::new();
+        let operation = registry.start(OperationKind::Forecast, None, Some(Duration::ZERO)).unwrap();
+        let err = operation.token().check().unwrap_err();
+        assert!(err.to_string().contains("time limit"), "{}", err);
+
+        let db = crate::test_support::test_db();
+        let mut timeouts = OperationTimeouts::load(&db).unwrap();
+        assert_eq!(timeouts.limit(OperationKind::Import), Some(Duration::from_secs(600)));
+        timeouts.limits.insert(OperationKind::Import, None);
+        timeouts.limits.insert(OperationKind::Forecast, Some(5));
+        timeouts.save(&db).unwrap();
+        let loaded = OperationTimeouts::load(&db).unwrap();
+        assert_eq!(loaded.limit(OperationKind::Import), None);
+        assert_eq!(loaded.limit(OperationKind::Forecast), Some(Duration::from_secs(5)));
+    }
+}
@@ -0,0 +1,542 @@
+//! Supervisor Overrides
+//!
+//! One way to let a supervisor wave a request past a guard, whichever
+//! module the guard lives in. The supervisor approves an `OverrideRequest`
+//! for one action on one record, giving a reason code and signing in or
+//! entering their PIN at the requester's station. The approval is
+//! recorded and comes back as an `OverrideToken`; the service that owns
+//! the guard consumes the token when it lets the request through, so each
+//! approval covers exactly one use.
+
+use std::sync::Arc;
+use chrono::{DateTime, Duration, Utc};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::info;
+use crate::auth::{Role, Scope};
+use crate::clock::{SharedClock, system_clock};
+use crate::context::RequestContext;
+use crate::db::Database;
+use crate::error::{WmsError, Result};
+use crate::types::{new_id, required_timestamp, stored_status, stored_timestamp, unreadable, DateRange};
+
+/// How long an approved override waits to be used
+pub const OVERRIDE_TOKEN_TTL_MINUTES: i64 = 15;
+
+/// Shortest PIN a supervisor may set
+const MIN_PIN_LENGTH: usize = 4;
+
+/// Guards that can be overridden
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum OverrideAction {
+    /// Receiving more of a line than its tolerance allows
+    OverReceipt,
+    /// Ordering past a customer's credit limit
+    CreditLimit,
+}
+
+impl OverrideAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::OverReceipt => "over_receipt",
+            Self::CreditLimit => "credit_limit",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "over_receipt" => Some(Self::OverReceipt),
+            "credit_limit" => Some(Self::CreditLimit),
+            _ => None,
+        }
+    }
+
+    /// Scope of the work the override lets through
+    pub fn scope(&self) -> Scope {
+        match self {
+            Self::OverReceipt => Scope::Receiving,
+            Self::CreditLimit => Scope::Shipping,
+        }
+    }
+}
+
+/// A reason supervisors may pick
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ReasonCode {
+    pub code: String,
+    pub description: String,
+    /// Only offered for this action; None for any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub action: Option<OverrideAction>,
+    /// The override needs a note as well
+    #[serde(default)]
+    pub requires_note: bool,
+    #[serde(default = "default_true")]
+    pub is_active: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// How the supervisor vouches for an override
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum OverrideApproval {
+    /// The signed-in user approves it
+    Session,
+    /// A supervisor enters their PIN at the requester's station
+    Pin { username: String, pin: String },
+}
+
+impl OverrideApproval {
+    fn method(&self) -> &'static str {
+        match self {
+            Self::Session => "session",
+            Self::Pin { .. } => "pin",
+        }
+    }
+}
+
+/// An override to approve
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OverrideRequest {
+    pub action: OverrideAction,
+    /// Kind and id of the record the guard checks, e.g. a receipt line
+    pub entity_type: String,
+    pub entity_id: String,
+    pub reason_code: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    pub approval: OverrideApproval,
+}
+
+/// An approved override, handed back to the guarded service method
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct OverrideToken {
+    pub id: String,
+    pub action: OverrideAction,
+    pub entity_type: String,
+    pub entity_id: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// A recorded override
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OverrideRecord {
+    pub id: String,
+    pub action: OverrideAction,
+    pub entity_type: String,
+    pub entity_id: String,
+    pub reason_code: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub requested_by: Option<String>,
+    pub approved_by: String,
+    /// "session" or "pin"
+    pub approval_method: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    /// When the guard let the request through; None if never used
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub used_at: Option<DateTime<Utc>>,
+}
+
+/// Which overrides to list; unset fields match everything
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OverrideQuery {
+    #[serde(default)]
+    pub action: Option<OverrideAction>,
+    /// Overrides this user requested or approved
+    #[serde(default)]
+    pub user_id: Option<String>,
+    #[serde(default)]
+    pub period: Option<DateRange>,
+}
+
+/// What override counts are broken down by
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OverrideGrouping {
+    /// Approving supervisor
+    Approver,
+    Action,
+    ReasonCode,
+    /// Calendar day approved
+    Day,
+}
+
+impl OverrideGrouping {
+    fn column(&self) -> &'static str {
+        match self {
+            Self::Approver => "approved_by",
+            Self::Action => "action_code",
+            Self::ReasonCode => "reason_code",
+            Self::Day => "date(created_at)",
+        }
+    }
+}
+
+/// Overrides in one group of a report
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct OverrideTally {
+    pub key: String,
+    pub count: u32,
+    /// Approved but never used
+    pub unused: u32,
+}
+
+/// Approves, records and redeems overrides
+pub struct OverrideService {
+    db: Arc<Database>,
+    clock: SharedClock,
+}
+
+impl OverrideService {
+    pub fn new(db: Arc<Database>) -> Self {
+        Self { db, clock: system_clock() }
+    }
+
+    /// Read the current time from `clock`
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Active reason codes, limited to those offered for `action`
+    pub async fn get_reason_codes(&self, action: Option<OverrideAction>) -> Result<Vec<ReasonCode>> {
+        let codes = self.db.query_map(
+            "SELECT * FROM override_reason_codes WHERE is_active = 1 ORDER BY code",
+            [],
+            row_to_reason_code,
+        )?;
+        Ok(codes.into_iter()
+            .filter(|c| action.is_none() || c.action.is_none() || c.action == action)
+            .collect())
+    }
+
+    /// Add or change a reason code. Codes are never deleted, since
+    /// recorded overrides refer to them; deactivate them instead.
+    pub async fn save_reason_code(&self, code: ReasonCode) -> Result<ReasonCode> {
+        let code = ReasonCode {
+            code: code.code.trim().to_uppercase(),
+            description: code.description.trim().to_string(),
+            ..code
+        };
+        if code.code.is_empty() || code.description.is_empty() {
+            return Err(WmsError::validation("Reason codes need a code and a description"));
+        }
+
+        self.db.execute(
+            "INSERT INTO override_reason_codes (code, description, action_code, requires_note, is_active)
+             VALUES (?, ?, ?, ?, ?)
+             ON CONFLICT(code) DO UPDATE SET
+                description = excluded.description,
+                action_code = excluded.action_code,
+                requires_note = excluded.requires_note,
+                is_active = excluded.is_active",
+            params![
+                &code.code,
+                &code.description,
+                code.action.map(|a| a.as_str()),
+                code.requires_note,
+                code.is_active,
+            ],
+        )?;
+        Ok(code)
+    }
+
+    /// Set the PIN the signed-in supervisor approves overrides with at
+    /// other stations
+    pub async fn set_override_pin(&self, ctx: &RequestContext, pin: &str) -> Result<()> {
+        ctx.require(Scope::ApproveOverrides)?;
+        let user_id = ctx.actor()?;
+        if pin.len() < MIN_PIN_LENGTH || !pin.chars().all(|c| c.is_ascii_digit()) {
+            return Err(WmsError::validation(format!("PINs are at least {} digits", MIN_PIN_LENGTH)));
+        }
+
+        let salt = new_id();
+        let updated = self.db.execute(
+            "UPDATE users SET override_pin_hash = ? WHERE id = ?",
+            params![format!("{}${}", salt, hash_pin(&salt, pin)), user_id],
+        )?;
+        if updated == 0 {
+            return Err(WmsError::not_found(format!("No user {}", user_id)));
+        }
+        info!("Override PIN set for {}", user_id);
+        Ok(())
+    }
+
+    /// Check and record an override, returning the token that lets its
+    /// request past the guard once
+    pub async fn request_override(&self, ctx: &RequestContext, request: OverrideRequest) -> Result<OverrideToken> {
+        ctx.require(request.action.scope())?;
+        if request.entity_id.trim().is_empty() {
+            return Err(WmsError::validation("An override needs the record it applies to"));
+        }
+
+        let reason = self.get_reason_codes(Some(request.action)).await?
+            .into_iter()
+            .find(|c| c.code.eq_ignore_ascii_case(request.reason_code.trim()))
+            .ok_or_else(|| WmsError::validation(format!(
+                "{} is not a reason code for {} overrides", request.reason_code, request.action.as_str()
+            )))?;
+        let note = request.note.as_deref().map(str::trim).filter(|n| !n.is_empty());
+        if reason.requires_note && note.is_none() {
+            return Err(WmsError::validation(format!("Reason {} needs a note", reason.code)));
+        }
+
+        let approved_by = self.approver(ctx, &request.approval)?;
+        let now = self.clock.now();
+        let token = OverrideToken {
+            id: new_id(),
+            action: request.action,
+            entity_type: request.entity_type.clone(),
+            entity_id: request.entity_id.clone(),
+            expires_at: now + Duration::minutes(OVERRIDE_TOKEN_TTL_MINUTES),
+        };
+
+        self.db.execute(
+            "INSERT INTO overrides (
+                id, action_code, entity_type, entity_id, reason_code, note,
+                requested_by, approved_by, approval_method, created_at, expires_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            params![
+                &token.id,
+                token.action.as_str(),
+                &token.entity_type,
+                &token.entity_id,
+                &reason.code,
+                note,
+                &ctx.user_id,
+                &approved_by,
+                request.approval.method(),
+                now.to_rfc3339(),
+                token.expires_at.to_rfc3339(),
+            ],
+        )?;
+
+        info!(
+            "{} override for {} {} approved by {} ({})",
+            token.action.as_str(), token.entity_type, token.entity_id, approved_by, reason.code
+        );
+        Ok(token)
+    }
+
+    /// Redeem `token` for `action` on the given record. Fails if it was
+    /// approved for something else, has expired or was already used.
+    pub fn consume(
+        &self,
+        token: &OverrideToken,
+        action: OverrideAction,
+        entity_type: &str,
+        entity_id: &str,
+    ) -> Result<()> {
+        self.db.transaction(|conn| self.consume_in(conn, token, action, entity_type, entity_id))
+    }
+
+    /// `consume` inside the transaction that does what the override
+    /// allows, so the token is only used up if that commits
+    pub fn consume_in(
+        &self,
+        conn: &Connection,
+        token: &OverrideToken,
+        action: OverrideAction,
+        entity_type: &str,
+        entity_id: &str,
+    ) -> Result<()> {
+        let now = self.clock.now().to_rfc3339();
+        let used = conn.execute(
+            "UPDATE overrides SET used_at = ?
+             WHERE id = ? AND action_code = ? AND entity_type = ? AND entity_id = ?
+               AND used_at IS NULL AND expires_at > ?",
+            params![&now, &token.id, action.as_str(), entity_type, entity_id, &now],
+        )?;
+        if used == 0 {
+            return Err(WmsError::validation(
+                "The override does not apply here, has expired or was already used",
+            ));
+        }
+        info!("{} override {} used on {} {}", action.as_str(), token.id, entity_type, entity_id);
+        Ok(())
+    }
+
+    /// Recorded overrides, newest first
+    pub async fn list_overrides(&self, query: &OverrideQuery) -> Result<Vec<OverrideRecord>> {
+        let (start, end) = period_bounds(query.period.as_ref());
+        self.db.query_map(
+            "SELECT * FROM overrides
+             WHERE (?1 IS NULL OR action_code = ?1)
+               AND (?2 IS NULL OR approved_by = ?2 OR requested_by = ?2)
+               AND (?3 IS NULL OR date(created_at) >= ?3)
+               AND (?4 IS NULL OR date(created_at) <= ?4)
+             ORDER BY created_at DESC, rowid DESC",
+            params![query.action.map(|a| a.as_str()), &query.user_id, start, end],
+            row_to_override,
+        )
+    }
+
+    /// Override counts over `period`, broken down by `grouping`
+    pub async fn override_report(&self, period: &DateRange, grouping: OverrideGrouping) -> Result<Vec<OverrideTally>> {
+        let (start, end) = period_bounds(Some(period));
+        self.db.query_map(
+            &format!(
+                "SELECT {column} AS key, COUNT(*), SUM(CASE WHEN used_at IS NULL THEN 1 ELSE 0 END)
+                 FROM overrides
+                 WHERE date(created_at) >= ? AND date(created_at) <= ?
+                 GROUP BY key
+                 ORDER BY COUNT(*) DESC, key",
+                column = grouping.column(),
+            ),
+            params![start, end],
+            |row| Ok(OverrideTally { key: row.get(0)?, count: row.get(1)?, unused: row.get(2)? }),
+        )
+    }
+
+    /// User approving an override, after checking they may
+    fn approver(&self, ctx: &RequestContext, approval: &OverrideApproval) -> Result<String> {
+        match approval {
+            OverrideApproval::Session => {
+                ctx.require(Scope::ApproveOverrides)?;
+                Ok(ctx.actor()?.to_string())
+            }
+            OverrideApproval::Pin { username, pin } => {
+                let denied = || WmsError::Auth("Supervisor name or PIN is wrong".to_string());
+                let (user_id, role, stored): (String, String, Option<String>) = self.db.query_row(
+                    "SELECT id, role, override_pin_hash FROM users WHERE username = ? AND is_active = 1",
+                    params![username.trim()],
+                    |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+                )?.ok_or_else(denied)?;
+                let matches = stored.as_deref()
+                    .and_then(|stored| stored.split_once('$'))
+                    .is_some_and(|(salt, hash)| hash_pin(salt, pin) == hash);
+                if !matches {
+                    return Err(denied());
+                }
+                if !Role::parse(&role).is_some_and(|r| r.can(Scope::ApproveOverrides)) {
+                    return Err(WmsError::permission_denied(format!("{} cannot approve overrides", username)));
+                }
+                Ok(user_id)
+            }
+        }
+    }
+}
+
+fn hash_pin(salt: &str, pin: &str) -> String {
+    format!("{:x}", Sha256::digest(format!("{}:{}", salt, pin).as_bytes()))
+}
+
+fn period_bounds(period: Option<&DateRange>) -> (Option<String>, Option<String>) {
+    (period.map(|p| p.start.to_string()), period.map(|p| p.end.to_string()))
+}
+
+fn row_to_reason_code(row: &rusqlite::Row) -> rusqlite::Result<ReasonCode> {
+    let action = row.get::<_, Option<String>>("action_code")?
+        .map(|a| OverrideAction::parse(&a).ok_or_else(|| unreadable(row, "action_code", &a)))
+        .transpose()?;
+    Ok(ReasonCode {
+        code: row.get("code")?,
+        description: row.get("description")?,
+        action,
+        requires_note: row.get::<_, i32>("requires_note")? == 1,
+        is_active: row.get::<_, i32>("is_active")? == 1,
+    })
+}
+
+fn row_to_override(row: &rusqlite::Row) -> rusqlite::Result<OverrideRecord> {
+    Ok(OverrideRecord {
+        id: row.get("id")?,
+        action: stored_status(row, "action_code", OverrideAction::parse)?,
+        entity_type: row.get("entity_type")?,
+        entity_id: row.get("entity_id")?,
+        reason_code: row.get("reason_code")?,
+        note: row.get("note")?,
+        requested_by: row.get("requested_by")?,
+        approved_by: row.get("approved_by")?,
+        approval_method: row.get("approval_method")?,
+        created_at: required_timestamp(row, "created_at")?,
+        expires_at: required_timestamp(row, "expires_at")?,
+        used_at: stored_timestamp(row, "used_at")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::Session;
+    use crate::test_support::{fixed_clock, fixture_time, test_context, test_db};
+
+    fn request(approval: OverrideApproval) -> OverrideRequest {
+        OverrideRequest {
+            action: OverrideAction::OverReceipt,
+            entity_type: "receipt_item".to_string(),
+            entity_id: "line-1".to_string(),
+            reason_code: "supplier_overship".to_string(),
+            note: None,
+            approval,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_approvals_need_a_supervisor_and_a_valid_reason() {
+        let db = test_db();
+        db.execute(
+            "INSERT INTO users (id, username, email, full_name, role) VALUES
+                ('s1', 'sam', 'sam@example.com', 'Sam Supervisor', 'supervisor'),
+                ('o1', 'olly', 'olly@example.com', 'Olly Operator', 'operator')",
+            [],
+        ).unwrap();
+        let service = OverrideService::new(db).with_clock(fixed_clock());
+        let operator = RequestContext::new(Some(&Session { user_id: "o1".to_string(), role: Role::Operator }));
+        let supervisor = RequestContext::new(Some(&Session { user_id: "s1".to_string(), role: Role::Supervisor }));
+
+        // Operators can't approve their own overrides
+        let err = service.request_override(&operator, request(OverrideApproval::Session)).await.unwrap_err();
+        assert!(matches!(err, WmsError::PermissionDenied(_)));
+        assert!(service.set_override_pin(&operator, "1234").await.is_err());
+
+        // A supervisor's PIN at the operator's station
+        service.set_override_pin(&supervisor, "4821").await.unwrap();
+        let wrong = OverrideApproval::Pin { username: "sam".to_string(), pin: "0000".to_string() };
+        assert!(matches!(service.request_override(&operator, request(wrong)).await, Err(WmsError::Auth(_))));
+
+        let pin = || OverrideApproval::Pin { username: "sam".to_string(), pin: "4821".to_string() };
+        let unknown_reason = OverrideRequest { reason_code: "BECAUSE".to_string(), ..request(pin()) };
+        assert!(service.request_override(&operator, unknown_reason).await.is_err());
+        let bare_other = OverrideRequest { reason_code: "OTHER".to_string(), ..request(pin()) };
+        assert!(service.request_override(&operator, bare_other).await.is_err());
+
+        let token = service.request_override(&operator, request(pin())).await.unwrap();
+        assert_eq!(token.expires_at, fixture_time() + Duration::minutes(OVERRIDE_TOKEN_TTL_MINUTES));
+
+        let recorded = service.list_overrides(&OverrideQuery { user_id: Some("o1".to_string()), ..Default::default() })
+            .await
+            .unwrap();
+        assert_eq!(recorded.len(), 1);
+        assert_eq!((recorded[0].approved_by.as_str(), recorded[0].approval_method.as_str()), ("s1", "pin"));
+        assert_eq!(recorded[0].reason_code, "SUPPLIER_OVERSHIP");
+    }
+
+    #[tokio::test]
+    async fn test_tokens_are_used_once_on_the_record_they_were_approved_for() {
+        let clock = fixed_clock();
+        let service = OverrideService::new(test_db()).with_clock(clock.clone());
+        let ctx = test_context();
+
+        let token = service.request_override(&ctx, request(OverrideApproval::Session)).await.unwrap();
+        assert!(service.consume(&token, OverrideAction::OverReceipt, "receipt_item", "line-2").is_err());
+        service.consume(&token, OverrideAction::OverReceipt, "receipt_item", "line-1").unwrap();
+        assert!(service.consume(&token, OverrideAction::OverReceipt, "receipt_item", "line-1").is_err());
+
+        let stale = service.request_override(&ctx, request(OverrideApproval::Session)).await.unwrap();
+        clock.advance(Duration::minutes(OVERRIDE_TOKEN_TTL_MINUTES + 1));
+        assert!(service.consume(&stale, OverrideAction::OverReceipt, "receipt_item", "line-1").is_err());
+
+        let day = fixture_time().date_naive();
+        let report = service.override_report(&DateRange::new(day, day), OverrideGrouping::Approver).await.unwrap();
+        assert_eq!(report, vec![OverrideTally { key: "u1".to_string(), count: 2, unused: 1 }]);
+    }
+}
@@ -0,0 +1,152 @@
+//! PDF Documents
+//!
+//! Thin wrapper over printpdf used for shipping paperwork and customer
+//! statements.
+
+use printpdf::*;
+use std::io::BufWriter;
+
+/// PDF Generator for standard documents
+pub struct PdfGenerator {
+    doc: PdfDocumentReference,
+    current_page: PdfPageIndex,
+    current_layer: PdfLayerIndex,
+}
+
+impl PdfGenerator {
+    /// Create a new PDF document
+    pub fn new(title: &str) -> Self {
+        let (doc, page1, layer1) = PdfDocument::new(
+            title,
+            Mm(210.0), // A4 width
+            Mm(297.0), // A4 height
+            "Layer 1",
+        );
+
+        Self {
+            doc,
+            current_page: page1,
+            current_layer: layer1,
+        }
+    }
+
+    /// Create a shipping document (letter size)
+    pub fn new_shipping_doc(title: &str) -> Self {
+        let (doc, page1, layer1) = PdfDocument::new(
+            title,
+            Mm(215.9), // Letter width
+            Mm(279.4), // Letter height
+            "Layer 1",
+        );
+
+        Self {
+            doc,
+            current_page: page1,
+            current_layer: layer1,
+        }
+    }
+
+    /// Create a single-page document of the given size, e.g. a label
+    pub fn new_sized(title: &str, width_mm: f32, height_mm: f32) -> Self {
+        let (doc, page1, layer1) = PdfDocument::new(
+            title,
+            Mm(width_mm),
+            Mm(height_mm),
+            "Layer 1",
+        );
+
+        Self {
+            doc,
+            current_page: page1,
+            current_layer: layer1,
+        }
+    }
+
+    /// Add a new page
+    pub fn add_page(&mut self) {
+        let (page, layer) = self.doc.add_page(
+            Mm(215.9),
+            Mm(279.4),
+            "Layer 1",
+        );
+        self.current_page = page;
+        self.current_layer = layer;
+    }
+
+    /// Add text to the current page
+    pub fn add_text(&self, x: f32, y: f32, text: &str, font_size: f32) {
+        let font = self.doc.add_builtin_font(BuiltinFont::Helvetica).unwrap();
+        let current_layer = self.doc.get_page(self.current_page).get_layer(self.current_layer);
+
+        current_layer.use_text(text, font_size, Mm(x), Mm(y), &font);
+    }
+
+    /// Add bold text
+    pub fn add_bold_text(&self, x: f32, y: f32, text: &str, font_size: f32) {
+        let font = self.doc.add_builtin_font(BuiltinFont::HelveticaBold).unwrap();
+        let current_layer = self.doc.get_page(self.current_page).get_layer(self.current_layer);
+
+        current_layer.use_text(text, font_size, Mm(x), Mm(y), &font);
+    }
+
+    /// Draw a line
+    pub fn draw_line(&self, x1: f32, y1: f32, x2: f32, y2: f32) {
+        let current_layer = self.doc.get_page(self.current_page).get_layer(self.current_layer);
+
+        let points = vec![
+            (Point::new(Mm(x1), Mm(y1)), false),
+            (Point::new(Mm(x2), Mm(y2)), false),
+        ];
+
+        let line = Line {
+            points,
+            is_closed: false,
+        };
+
+        current_layer.add_line(line);
+    }
+
+    /// Draw a rectangle
+    pub fn draw_rect(&self, x: f32, y: f32, width: f32, height: f32) {
+        let current_layer = self.doc.get_page(self.current_page).get_layer(self.current_layer);
+
+        let points = vec![
+            (Point::new(Mm(x), Mm(y)), false),
+            (Point::new(Mm(x + width), Mm(y)), false),
+            (Point::new(Mm(x + width), Mm(y + height)), false),
+            (Point::new(Mm(x), Mm(y + height)), false),
+        ];
+
+        let rect = Line {
+            points,
+            is_closed: true,
+        };
+
+        current_layer.add_line(rect);
+    }
+
+    /// Draw a filled rectangle, e.g. a barcode bar
+    pub fn fill_rect(&self, x: f32, y: f32, width: f32, height: f32) {
+        let current_layer = self.doc.get_page(self.current_page).get_layer(self.current_layer);
+
+        let points = vec![
+            (Point::new(Mm(x), Mm(y)), false),
+            (Point::new(Mm(x + width), Mm(y)), false),
+            (Point::new(Mm(x + width), Mm(y + height)), false),
+            (Point::new(Mm(x), Mm(y + height)), false),
+        ];
+
+        current_layer.add_polygon(Polygon {
+            rings: vec![points],
+            mode: path::PaintMode::Fill,
+            winding_order: path::WindingOrder::NonZero,
+        });
+    }
+
+    /// Save to bytes
+    pub fn save_to_bytes(self) -> Vec<u8> {
+        let mut buffer = BufWriter::new(Vec::new());
+        self.doc.save(&mut buffer).unwrap();
+        buffer.into_inner().unwrap()
+    }
+}
@@ -0,0 +1,285 @@
+//! Global Search
+//!
+//! Backs the header search box. Inventory items, customers, shipments and
+//! deliveries each have an FTS5 index, kept in step with their tables by
+//! triggers. Every word of a query has to match the start of a word in
+//! the record, so "SKU-0012" finds SKU-00123 and "jane acme" finds Jane at
+//! Acme Corp. Results are grouped by kind, best match first, and only
+//! cover what the user's role may see.
+
+use std::sync::Arc;
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+use crate::auth::Scope;
+use crate::context::RequestContext;
+use crate::db::Database;
+use crate::error::Result;
+
+/// Results per group when the caller doesn't say
+pub const DEFAULT_SEARCH_LIMIT: u32 = 5;
+
+/// Most results `global_search` returns per group
+pub const MAX_SEARCH_LIMIT: u32 = 25;
+
+/// Words of a query past this many are ignored
+const MAX_QUERY_TERMS: usize = 8;
+
+/// Repopulates every search index from its table
+const REBUILD_SQL: &str = "
+    DELETE FROM inventory_items_fts;
+    INSERT INTO inventory_items_fts (id, sku, name, description)
+    SELECT id, sku, name, description FROM inventory_items;
+
+    DELETE FROM customers_fts;
+    INSERT INTO customers_fts (id, customer_number, company_name, first_name, last_name, email)
+    SELECT id, customer_number, company_name, first_name, last_name, email FROM customers;
+
+    DELETE FROM shipments_fts;
+    INSERT INTO shipments_fts (id, shipment_number, tracking_number)
+    SELECT id, shipment_number, tracking_number FROM shipments;
+
+    DELETE FROM deliveries_fts;
+    INSERT INTO deliveries_fts (id, delivery_number)
+    SELECT id, delivery_number FROM deliveries;
+";
+
+/// One record matching a search
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SearchHit {
+    pub id: String,
+    pub title: String,
+    pub subtitle: Option<String>,
+    /// Frontend route of the record's page
+    pub route: String,
+}
+
+/// Matches grouped by kind
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct GlobalSearchResults {
+    pub inventory: Vec<SearchHit>,
+    pub customers: Vec<SearchHit>,
+    pub shipments: Vec<SearchHit>,
+    pub deliveries: Vec<SearchHit>,
+}
+
+impl GlobalSearchResults {
+    pub fn is_empty(&self) -> bool {
+        self.inventory.is_empty()
+            && self.customers.is_empty()
+            && self.shipments.is_empty()
+            && self.deliveries.is_empty()
+    }
+}
+
+/// FTS5 query requiring each word of `query` as a prefix, or None when it
+/// has no words. Words are quoted, so nothing the user types is read as
+/// query syntax.
+pub fn match_expression(query: &str) -> Option<String> {
+    let terms: Vec<String> = query
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|term| !term.is_empty())
+        .take(MAX_QUERY_TERMS)
+        .map(|term| format!("\"{}\"*", term))
+        .collect();
+    (!terms.is_empty()).then(|| terms.join(" "))
+}
+
+/// Search across inventory, customers, shipments and deliveries
+pub struct SearchService {
+    db: Arc<Database>,
+}
+
+impl SearchService {
+    pub fn new(db: Arc<Database>) -> Self {
+        Self { db }
+    }
+
+    /// Up to `limit` matches of each kind `ctx` may see (5 by default).
+    /// Drivers only find deliveries on their own routes.
+    pub async fn global_search(
+        &self,
+        ctx: &RequestContext,
+        query: &str,
+        limit: Option<u32>,
+    ) -> Result<GlobalSearchResults> {
+        let Some(pattern) = match_expression(query) else {
+            return Ok(GlobalSearchResults::default());
+        };
+        let limit = limit.unwrap_or(DEFAULT_SEARCH_LIMIT).clamp(1, MAX_SEARCH_LIMIT);
+        let can = |scope| ctx.require(scope).is_ok();
+
+        let mut results = GlobalSearchResults::default();
+        if can(Scope::Inventory) {
+            results.inventory = self.hits(
+                "SELECT i.id, i.name, i.sku, i.sku
+                 FROM inventory_items_fts
+                 JOIN inventory_items i ON i.id = inventory_items_fts.id
+                 WHERE inventory_items_fts MATCH ?1 AND i.is_active = 1
+                 ORDER BY inventory_items_fts.rank
+                 LIMIT ?2",
+                params![pattern, limit],
+                "/inventory",
+            )?;
+        }
+        if can(Scope::Customers) {
+            results.customers = self.hits(
+                "SELECT c.id,
+                        COALESCE(
+                            NULLIF(c.company_name, ''),
+                            NULLIF(TRIM(COALESCE(c.first_name, '') || ' ' || COALESCE(c.last_name, '')), ''),
+                            c.customer_number
+                        ),
+                        c.customer_number || COALESCE(' · ' || c.email, ''),
+                        c.id
+                 FROM customers_fts
+                 JOIN customers c ON c.id = customers_fts.id
+                 WHERE customers_fts MATCH ?1 AND COALESCE(c.is_active, 1) = 1
+                 ORDER BY customers_fts.rank
+                 LIMIT ?2",
+                params![pattern, limit],
+                "/customers",
+            )?;
+        }
+        if can(Scope::Shipping) {
+            results.shipments = self.hits(
+                "SELECT s.id, s.shipment_number, s.ship_to_name || COALESCE(' · ' || s.tracking_number, ''), s.id
+                 FROM shipments_fts
+                 JOIN shipments s ON s.id = shipments_fts.id
+                 WHERE shipments_fts MATCH ?1
+                 ORDER BY shipments_fts.rank
+                 LIMIT ?2",
+                params![pattern, limit],
+                "/shipping",
+            )?;
+        }
+        if can(Scope::Deliveries) {
+            results.deliveries = self.hits(
+                "SELECT d.id, d.delivery_number, d.delivery_name, d.id
+                 FROM deliveries_fts
+                 JOIN deliveries d ON d.id = deliveries_fts.id
+                 WHERE deliveries_fts MATCH ?1
+                   AND (?3 IS NULL OR d.route_id IN (SELECT id FROM delivery_routes WHERE driver_id = ?3))
+                 ORDER BY deliveries_fts.rank
+                 LIMIT ?2",
+                params![pattern, limit, ctx.delivery_driver()],
+                "/deliveries",
+            )?;
+        }
+        Ok(results)
+    }
+
+    /// Repopulate the search indexes from their tables, for databases
+    /// written to with the triggers missing
+    pub fn rebuild_index(&self) -> Result<()> {
+        self.db.transaction(|conn| Ok(conn.execute_batch(REBUILD_SQL)?))?;
+        info!("Search indexes rebuilt");
+        Ok(())
+    }
+
+    /// Rows of (id, title, subtitle, key) as hits routed to `route/key`;
+    /// item pages are keyed by SKU and the rest by id
+    fn hits(&self, sql: &str, params: impl rusqlite::Params, route: &str) -> Result<Vec<SearchHit>> {
+        self.db.query_map(sql, params, |row| {
+            Ok(SearchHit {
+                id: row.get(0)?,
+                title: row.get(1)?,
+                subtitle: row.get(2)?,
+                route: format!("{}/{}", route, row.get::<_, String>(3)?),
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use crate::auth::Role;
+    use crate::test_support::{test_context, test_db, CustomerBuilder, DeliveryBuilder, ItemBuilder, ShipmentBuilder};
+
+    fn ids(hits: &[SearchHit]) -> Vec<&str> {
+        hits.iter().map(|hit| hit.id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_match_expression() {
+        assert_eq!(match_expression("SKU-0012").as_deref(), Some("\"SKU\"* \"0012\"*"));
+        assert_eq!(match_expression("  jane \"acme\" OR "), Some("\"jane\"* \"acme\"* \"OR\"*".to_string()));
+        assert_eq!(match_expression(" -* "), None);
+    }
+
+    #[tokio::test]
+    async fn test_index_follows_inserts_and_updates() {
+        let db = test_db();
+        let service = SearchService::new(db.clone());
+        let ctx = test_context();
+        ItemBuilder::new("SKU-00123").name("Blue Widget").insert::<Value>(&db);
+
+        let found = service.global_search(&ctx, "blue", None).await.unwrap();
+        assert_eq!(ids(&found.inventory), vec!["sku-00123"]);
+        assert_eq!(found.inventory[0].route, "/inventory/SKU-00123");
+        assert_eq!(found.inventory[0].subtitle.as_deref(), Some("SKU-00123"));
+
+        db.execute("UPDATE inventory_items SET name = 'Red Gadget' WHERE id = 'sku-00123'", []).unwrap();
+        assert!(service.global_search(&ctx, "blue", None).await.unwrap().is_empty());
+        let found = service.global_search(&ctx, "gadget", None).await.unwrap();
+        assert_eq!(found.inventory[0].title, "Red Gadget");
+
+        db.execute("DELETE FROM inventory_items WHERE id = 'sku-00123'", []).unwrap();
+        assert!(service.global_search(&ctx, "gadget", None).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_prefix_and_multi_word_queries() {
+        let db = test_db();
+        let service = SearchService::new(db.clone());
+        let ctx = test_context();
+        ItemBuilder::new("SKU-00123").insert::<Value>(&db);
+        ItemBuilder::new("SKU-00456").insert::<Value>(&db);
+        CustomerBuilder::new("C-100").company_name("Acme Corp").email("jane@acme.example").insert::<Value>(&db);
+        CustomerBuilder::new("C-200").company_name("Acme Logistics").insert::<Value>(&db);
+        ShipmentBuilder::new("SHP-0001").insert::<Value>(&db);
+        db.execute("UPDATE shipments SET tracking_number = '1Z999AA10123456784' WHERE id = 'shp-0001'", []).unwrap();
+
+        let found = service.global_search(&ctx, "SKU-0012", None).await.unwrap();
+        assert_eq!(ids(&found.inventory), vec!["sku-00123"]);
+
+        let found = service.global_search(&ctx, "acme jane", None).await.unwrap();
+        assert_eq!(ids(&found.customers), vec!["c-100"]);
+        assert_eq!(found.customers[0].subtitle.as_deref(), Some("C-100 · jane@acme.example"));
+
+        let found = service.global_search(&ctx, "1z999", None).await.unwrap();
+        assert_eq!(ids(&found.shipments), vec!["shp-0001"]);
+        assert_eq!(found.shipments[0].route, "/shipping/shp-0001");
+    }
+
+    #[tokio::test]
+    async fn test_results_are_grouped_and_limited() {
+        let db = test_db();
+        let service = SearchService::new(db.clone());
+        for n in 1..=4 {
+            ItemBuilder::new(&format!("ACME-{}", n)).insert::<Value>(&db);
+        }
+        CustomerBuilder::new("C-100").company_name("Acme Corp").insert::<Value>(&db);
+        DeliveryBuilder::new("DEL-ACME").insert::<Value>(&db);
+
+        let found = service.global_search(&test_context(), "acme", Some(2)).await.unwrap();
+        assert_eq!(found.inventory.len(), 2);
+        assert_eq!(ids(&found.customers), vec!["c-100"]);
+        assert_eq!(ids(&found.deliveries), vec!["del-acme"]);
+        assert!(found.shipments.is_empty());
+
+        // Pickers can't see customers, and drivers only their own deliveries
+        let picker = RequestContext { role: Some(Role::Picker), ..RequestContext::for_user("u1") };
+        let found = service.global_search(&picker, "acme", None).await.unwrap();
+        assert_eq!(found.inventory.len(), 4);
+        assert!(found.customers.is_empty());
+        assert!(found.deliveries.is_empty());
+
+        let driver = RequestContext { role: Some(Role::Driver), ..RequestContext::for_user("u1") };
+        let found = service.global_search(&driver, "acme", None).await.unwrap();
+        assert!(found.inventory.is_empty());
+        assert!(found.deliveries.is_empty());
+    }
+}
@@ -0,0 +1,137 @@
+//! Document Number Sequences
+//!
+//! Shipments, receipts, deliveries, routes and customers are numbered from
+//! counters in the `document_sequences` table. Each number is the
+//! sequence's prefix and its next value zero-padded to the configured
+//! width, e.g. SHP-00000042.
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use crate::db::{next_value_in, Database};
+use crate::error::{Result, WmsError};
+
+/// Longest zero padding a sequence may use
+const MAX_WIDTH: u8 = 12;
+
+/// Kinds of numbered document
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum DocumentSequence {
+    Shipment,
+    Receipt,
+    Delivery,
+    Route,
+    Customer,
+}
+
+impl DocumentSequence {
+    pub const ALL: [Self; 5] = [Self::Shipment, Self::Receipt, Self::Delivery, Self::Route, Self::Customer];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Shipment => "shipment",
+            Self::Receipt => "receipt",
+            Self::Delivery => "delivery",
+            Self::Route => "route",
+            Self::Customer => "customer",
+        }
+    }
+
+    fn default_format(&self) -> SequenceFormat {
+        let (prefix, width) = match self {
+            Self::Shipment => ("SHP", 8),
+            Self::Receipt => ("RCV", 8),
+            Self::Delivery => ("DEL", 8),
+            Self::Route => ("RTE", 8),
+            Self::Customer => ("CUS", 6),
+        };
+        SequenceFormat { sequence: *self, prefix: prefix.to_string(), width }
+    }
+
+    /// Allocate the next number of this sequence
+    pub fn next_number(&self, db: &Database) -> Result<String> {
+        db.transaction(|conn| self.next_number_in(conn))
+    }
+
+    /// Allocate the next number inside a transaction; if the transaction
+    /// rolls back the number is handed out again
+    pub fn next_number_in(&self, conn: &Connection) -> Result<String> {
+        let value = next_value_in(conn, self.as_str())?;
+        Ok(SequenceFormat::load_in(conn, *self)?.format(value))
+    }
+}
+
+/// How a sequence's numbers are printed
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SequenceFormat {
+    pub sequence: DocumentSequence,
+    /// Text before the dash, e.g. "SHP"
+    pub prefix: String,
+    /// Digits the value is zero-padded to
+    pub width: u8,
+}
+
+impl SequenceFormat {
+    /// Load the format of `sequence`, falling back to its default
+    pub fn load(db: &Database, sequence: DocumentSequence) -> Result<Self> {
+        db.transaction(|conn| Self::load_in(conn, sequence))
+    }
+
+    fn load_in(conn: &Connection, sequence: DocumentSequence) -> Result<Self> {
+        let stored = conn.query_row(
+            "SELECT prefix, width FROM document_sequences WHERE name = ?",
+            params![sequence.as_str()],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, u8>(1)?)),
+        ).optional()?;
+        Ok(match stored {
+            Some((prefix, width)) => Self { sequence, prefix, width },
+            None => sequence.default_format(),
+        })
+    }
+
+    /// Formats of every sequence
+    pub fn load_all(db: &Database) -> Result<Vec<Self>> {
+        DocumentSequence::ALL.iter().map(|sequence| Self::load(db, *sequence)).collect()
+    }
+
+    /// Store the format; numbers already issued keep their old form
+    pub fn save(&self, db: &Database) -> Result<()> {
+        if self.prefix.is_empty() || !self.prefix.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(WmsError::validation("Number prefix must be letters and digits"));
+        }
+        if !(1..=MAX_WIDTH).contains(&self.width) {
+            return Err(WmsError::validation(format!("Number width must be 1 to {} digits", MAX_WIDTH)));
+        }
+        db.execute(
+            "INSERT INTO document_sequences (name, prefix, width) VALUES (?1, ?2, ?3)
+             ON CONFLICT(name) DO UPDATE SET prefix = ?2, width = ?3, updated_at = datetime('now')",
+            params![self.sequence.as_str(), &self.prefix, self.width],
+        )?;
+        Ok(())
+    }
+
+    /// Print `value` in this format
+    pub fn format(&self, value: i64) -> String {
+        format!("{}-{:0width$}", self.prefix, value, width = self.width as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_db;
+
+    #[test]
+    fn test_numbers_never_repeat() {
+        let db = test_db();
+        assert_eq!(DocumentSequence::Shipment.next_number(&db).unwrap(), "SHP-00000001");
+        assert_eq!(DocumentSequence::Shipment.next_number(&db).unwrap(), "SHP-00000002");
+        assert_eq!(DocumentSequence::Customer.next_number(&db).unwrap(), "CUS-000001");
+
+        let format = SequenceFormat { sequence: DocumentSequence::Shipment, prefix: "OUT".to_string(), width: 5 };
+        format.save(&db).unwrap();
+        assert_eq!(DocumentSequence::Shipment.next_number(&db).unwrap(), "OUT-00003");
+        assert!(SequenceFormat { width: 0, ..format.clone() }.save(&db).is_err());
+        assert!(SequenceFormat { prefix: "SH-P".to_string(), ..format }.save(&db).is_err());
+    }
+}
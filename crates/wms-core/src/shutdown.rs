@@ -0,0 +1,234 @@
+//! Shutdown Coordination
+//!
+//! Background tasks register with a `ShutdownCoordinator` and watch the
+//! signal on the handle they get back. When shutdown is requested a task
+//! finishes its current unit of work, leaves its state safe to resume and
+//! drops the handle. The coordinator waits for that up to a grace period
+//! and reports any task that did not get there in time.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Notify, watch};
+use tokio::time::Instant;
+
+/// How long background tasks get to reach a safe checkpoint on exit
+pub const DEFAULT_SHUTDOWN_GRACE: Duration = Duration::from_secs(10);
+
+/// Settings key overriding the grace period, in seconds
+pub const SHUTDOWN_GRACE_SETTING: &str = "shutdown_grace_seconds";
+
+/// Read side of a shutdown request
+#[derive(Clone)]
+pub struct ShutdownSignal {
+    requested: watch::Receiver<bool>,
+}
+
+impl ShutdownSignal {
+    /// A signal that is never raised, for work run outside the app
+    pub fn never() -> Self {
+        let (_, requested) = watch::channel(false);
+        Self { requested }
+    }
+
+    /// Whether shutdown has been requested
+    pub fn is_requested(&self) -> bool {
+        *self.requested.borrow()
+    }
+
+    /// Wait until shutdown is requested
+    pub async fn requested(&self) {
+        let mut requested = self.requested.clone();
+        if requested.wait_for(|r| *r).await.is_err() {
+            // The coordinator is gone without asking; nothing will ever ask
+            std::future::pending::<()>().await;
+        }
+    }
+}
+
+#[derive(Default)]
+struct TaskRegistry {
+    next_id: u64,
+    running: BTreeMap<u64, String>,
+}
+
+/// A registered background task. Dropping it tells the coordinator the
+/// task has stopped.
+pub struct TaskHandle {
+    id: u64,
+    name: String,
+    signal: ShutdownSignal,
+    tasks: Arc<Mutex<TaskRegistry>>,
+    stopped: Arc<Notify>,
+}
+
+impl TaskHandle {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn signal(&self) -> &ShutdownSignal {
+        &self.signal
+    }
+
+    pub fn is_shutdown_requested(&self) -> bool {
+        self.signal.is_requested()
+    }
+
+    /// Wait until shutdown is requested
+    pub async fn shutdown_requested(&self) {
+        self.signal.requested().await
+    }
+}
+
+impl Drop for TaskHandle {
+    fn drop(&mut self) {
+        if let Ok(mut tasks) = self.tasks.lock() {
+            tasks.running.remove(&self.id);
+        }
+        self.stopped.notify_waiters();
+    }
+}
+
+/// What happened to background tasks during shutdown
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ShutdownSummary {
+    /// Tasks that reached a safe checkpoint
+    pub drained: Vec<String>,
+    /// Tasks still running when the grace period ran out
+    pub interrupted: Vec<String>,
+}
+
+impl ShutdownSummary {
+    pub fn is_clean(&self) -> bool {
+        self.interrupted.is_empty()
+    }
+}
+
+/// Tracks background tasks and stops them on request
+pub struct ShutdownCoordinator {
+    requested: watch::Sender<bool>,
+    tasks: Arc<Mutex<TaskRegistry>>,
+    stopped: Arc<Notify>,
+}
+
+impl Default for ShutdownCoordinator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShutdownCoordinator {
+    pub fn new() -> Self {
+        Self {
+            requested: watch::channel(false).0,
+            tasks: Arc::new(Mutex::new(TaskRegistry::default())),
+            stopped: Arc::new(Notify::new()),
+        }
+    }
+
+    /// Signal for work that should stop at shutdown but isn't a task of
+    /// its own (e.g. a sync started from a command)
+    pub fn signal(&self) -> ShutdownSignal {
+        ShutdownSignal { requested: self.requested.subscribe() }
+    }
+
+    /// Register a background task under a name used in the shutdown summary
+    pub fn register(&self, name: &str) -> TaskHandle {
+        let id = match self.tasks.lock() {
+            Ok(mut tasks) => {
+                tasks.next_id += 1;
+                let id = tasks.next_id;
+                tasks.running.insert(id, name.to_string());
+                id
+            }
+            Err(_) => 0,
+        };
+        TaskHandle {
+            id,
+            name: name.to_string(),
+            signal: self.signal(),
+            tasks: self.tasks.clone(),
+            stopped: self.stopped.clone(),
+        }
+    }
+
+    pub fn is_requested(&self) -> bool {
+        *self.requested.borrow()
+    }
+
+    /// Names of registered tasks that haven't stopped
+    pub fn running_tasks(&self) -> Vec<String> {
+        self.tasks.lock()
+            .map(|tasks| tasks.running.values().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Raise the signal without waiting for tasks to stop
+    pub fn request(&self) {
+        self.requested.send_replace(true);
+    }
+
+    /// Ask every task to stop and wait up to `grace` for them to do so
+    pub async fn shutdown(&self, grace: Duration) -> ShutdownSummary {
+        let started = self.running_tasks();
+        self.request();
+
+        let deadline = Instant::now() + grace;
+        loop {
+            // Created before the check so a task stopping in between still wakes us
+            let stopped = self.stopped.notified();
+            if self.running_tasks().is_empty() {
+                break;
+            }
+            if tokio::time::timeout_at(deadline, stopped).await.is_err() {
+                break;
+            }
+        }
+
+        let interrupted = self.running_tasks();
+        let mut drained = started;
+        for name in &interrupted {
+            if let Some(index) = drained.iter().position(|n| n == name) {
+                drained.remove(index);
+            }
+        }
+        ShutdownSummary { drained, interrupted }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_shutdown_waits_for_tasks_to_stop() {
+        let coordinator = ShutdownCoordinator::new();
+        let worker = coordinator.register("worker");
+        tokio::spawn(async move {
+            worker.shutdown_requested().await;
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            drop(worker);
+        });
+
+        let summary = coordinator.shutdown(Duration::from_secs(5)).await;
+
+        assert_eq!(summary.drained, vec!["worker".to_string()]);
+        assert!(summary.is_clean());
+        assert!(coordinator.signal().is_requested());
+    }
+
+    #[tokio::test]
+    async fn test_stuck_task_is_reported_after_grace() {
+        let coordinator = ShutdownCoordinator::new();
+        let _stuck = coordinator.register("stuck");
+        drop(coordinator.register("idle"));
+
+        let summary = coordinator.shutdown(Duration::from_millis(50)).await;
+
+        assert_eq!(summary.interrupted, vec!["stuck".to_string()]);
+        assert!(summary.drained.is_empty());
+        assert!(!ShutdownSignal::never().is_requested());
+    }
+}
@@ -0,0 +1,410 @@
+//! Cold-Chain Temperature Logging
+//!
+//! Refrigerated goods need a temperature record from the dock to the
+//! customer's door. Item categories that need a cold chain have an allowed
+//! range; probe readings are logged against receipt lines and deliveries
+//! with the range in force at the time, and a reading outside it is
+//! flagged. What a breach does to the owning document (a quality hold on a
+//! received lot, a warning on a delivery) is up to the service recording it.
+
+use std::sync::Arc;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use csv::Writer;
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+use crate::clock::{SharedClock, system_clock};
+use crate::context::RequestContext;
+use crate::db::Database;
+use crate::error::{WmsError, Result};
+use crate::types::{new_id, required_timestamp, unreadable};
+
+/// Readings outside this are taken to be probe faults, not temperatures
+const PLAUSIBLE_CELSIUS: std::ops::RangeInclusive<f64> = -100.0..=100.0;
+
+/// What a reading was taken for
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "type", content = "id", rename_all = "snake_case")]
+pub enum TemperatureOwner {
+    /// A receipt line, read at the dock
+    ReceiptItem(String),
+    /// A delivery, read on the truck or at the door
+    Delivery(String),
+}
+
+impl TemperatureOwner {
+    pub fn owner_type(&self) -> &'static str {
+        match self {
+            Self::ReceiptItem(_) => "receipt_item",
+            Self::Delivery(_) => "delivery",
+        }
+    }
+
+    pub fn owner_id(&self) -> &str {
+        match self {
+            Self::ReceiptItem(id) | Self::Delivery(id) => id,
+        }
+    }
+
+    fn from_parts(owner_type: &str, owner_id: String) -> Option<Self> {
+        match owner_type {
+            "receipt_item" => Some(Self::ReceiptItem(owner_id)),
+            "delivery" => Some(Self::Delivery(owner_id)),
+            _ => None,
+        }
+    }
+}
+
+/// Allowed temperatures for an item category. Categories with a range
+/// are cold-chain; either bound may be open.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TemperatureRange {
+    pub category: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_celsius: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_celsius: Option<f64>,
+}
+
+impl TemperatureRange {
+    /// Whether `celsius` is allowed
+    pub fn contains(&self, celsius: f64) -> bool {
+        self.min_celsius.is_none_or(|min| celsius >= min)
+            && self.max_celsius.is_none_or(|max| celsius <= max)
+    }
+
+    /// The range as shown to people, e.g. "2.0–8.0 °C"
+    pub fn describe(&self) -> String {
+        match (self.min_celsius, self.max_celsius) {
+            (Some(min), Some(max)) => format!("{:.1}–{:.1} °C", min, max),
+            (Some(min), None) => format!("at least {:.1} °C", min),
+            (None, Some(max)) => format!("at most {:.1} °C", max),
+            (None, None) => "any temperature".to_string(),
+        }
+    }
+
+    /// Narrowest range that satisfies every one of `ranges`; None without any
+    pub fn strictest<'a>(ranges: impl IntoIterator<Item = &'a TemperatureRange>) -> Option<Self> {
+        ranges.into_iter().cloned().reduce(|a, b| Self {
+            category: format!("{}, {}", a.category, b.category),
+            min_celsius: match (a.min_celsius, b.min_celsius) {
+                (Some(x), Some(y)) => Some(x.max(y)),
+                (x, y) => x.or(y),
+            },
+            max_celsius: match (a.max_celsius, b.max_celsius) {
+                (Some(x), Some(y)) => Some(x.min(y)),
+                (x, y) => x.or(y),
+            },
+        })
+    }
+}
+
+/// A logged probe reading
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemperatureReading {
+    pub id: String,
+    pub owner: TemperatureOwner,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub probe_id: Option<String>,
+    pub celsius: f64,
+    /// Range in force when the reading was taken
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_celsius: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_celsius: Option<f64>,
+    pub out_of_range: bool,
+    pub recorded_at: DateTime<Utc>,
+    pub recorded_by: String,
+}
+
+/// Every reading for a receipt line or delivery, oldest first
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemperatureLog {
+    pub owner: TemperatureOwner,
+    pub readings: Vec<TemperatureReading>,
+    pub out_of_range_count: usize,
+}
+
+/// Exported temperature log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemperatureLogExport {
+    /// Base64 encoded file data
+    pub data: String,
+    /// MIME content type
+    pub content_type: String,
+    /// Suggested filename
+    pub filename: String,
+}
+
+/// CSV of a temperature log for auditors
+pub fn render_temperature_csv(log: &TemperatureLog) -> Result<Vec<u8>> {
+    let csv_error = |e: csv::Error| WmsError::Export(format!("CSV write error: {}", e));
+    let bound = |value: Option<f64>| value.map(|v| v.to_string()).unwrap_or_default();
+
+    let mut writer = Writer::from_writer(Vec::new());
+    writer.write_record([
+        "Owner Type",
+        "Owner",
+        "Recorded At",
+        "Probe",
+        "Celsius",
+        "Min Celsius",
+        "Max Celsius",
+        "Out Of Range",
+        "Recorded By",
+    ]).map_err(csv_error)?;
+
+    for reading in &log.readings {
+        writer.write_record([
+            log.owner.owner_type().to_string(),
+            log.owner.owner_id().to_string(),
+            reading.recorded_at.to_rfc3339(),
+            reading.probe_id.clone().unwrap_or_default(),
+            reading.celsius.to_string(),
+            bound(reading.min_celsius),
+            bound(reading.max_celsius),
+            if reading.out_of_range { "yes" } else { "no" }.to_string(),
+            reading.recorded_by.clone(),
+        ]).map_err(csv_error)?;
+    }
+
+    writer.into_inner()
+        .map_err(|e| WmsError::Export(format!("CSV flush error: {}", e)))
+}
+
+/// Category ranges and the reading log
+pub struct TemperatureService {
+    db: Arc<Database>,
+    clock: SharedClock,
+}
+
+impl TemperatureService {
+    pub fn new(db: Arc<Database>) -> Self {
+        Self { db, clock: system_clock() }
+    }
+
+    /// Read the current time from `clock`
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Ranges of every cold-chain category
+    pub async fn get_ranges(&self) -> Result<Vec<TemperatureRange>> {
+        self.db.query_map(
+            "SELECT * FROM category_temperature_ranges ORDER BY category",
+            [],
+            row_to_range,
+        )
+    }
+
+    /// Set a category's range, making it cold-chain
+    pub async fn save_range(&self, range: TemperatureRange) -> Result<TemperatureRange> {
+        let category = range.category.trim().to_string();
+        if category.is_empty() {
+            return Err(WmsError::validation("Category is required"));
+        }
+        if range.min_celsius.is_none() && range.max_celsius.is_none() {
+            return Err(WmsError::validation("Set a minimum or maximum temperature"));
+        }
+        if let (Some(min), Some(max)) = (range.min_celsius, range.max_celsius)
+            && min > max
+        {
+            return Err(WmsError::validation(format!(
+                "Minimum {:.1} °C is above maximum {:.1} °C", min, max
+            )));
+        }
+
+        self.db.execute(
+            "INSERT INTO category_temperature_ranges (category, min_celsius, max_celsius, updated_at)
+             VALUES (?, ?, ?, ?)
+             ON CONFLICT(category) DO UPDATE SET
+                min_celsius = excluded.min_celsius,
+                max_celsius = excluded.max_celsius,
+                updated_at = excluded.updated_at",
+            params![&category, range.min_celsius, range.max_celsius, self.clock.now().to_rfc3339()],
+        )?;
+
+        let range = TemperatureRange { category, ..range };
+        info!("Cold-chain range for {} set to {}", range.category, range.describe());
+        Ok(range)
+    }
+
+    /// Stop treating a category as cold-chain
+    pub async fn delete_range(&self, category: &str) -> Result<()> {
+        self.db.execute(
+            "DELETE FROM category_temperature_ranges WHERE category = ?",
+            params![category],
+        )?;
+        Ok(())
+    }
+
+    /// Strictest range across the given item categories; None when none
+    /// of them is cold-chain
+    pub async fn range_for_categories(&self, categories: &[String]) -> Result<Option<TemperatureRange>> {
+        let ranges = self.get_ranges().await?;
+        Ok(TemperatureRange::strictest(
+            ranges.iter().filter(|r| categories.iter().any(|c| c.eq_ignore_ascii_case(&r.category))),
+        ))
+    }
+
+    /// Log a reading, flagging it when it falls outside `range`
+    pub async fn record(
+        &self,
+        ctx: &RequestContext,
+        owner: TemperatureOwner,
+        probe_id: Option<&str>,
+        celsius: f64,
+        range: Option<&TemperatureRange>,
+    ) -> Result<TemperatureReading> {
+        let recorded_by = ctx.actor()?;
+        if !PLAUSIBLE_CELSIUS.contains(&celsius) {
+            return Err(WmsError::validation(format!(
+                "{} °C is not a plausible reading; check the probe", celsius
+            )));
+        }
+
+        let reading = TemperatureReading {
+            id: new_id(),
+            probe_id: probe_id.map(str::trim).filter(|p| !p.is_empty()).map(String::from),
+            celsius,
+            min_celsius: range.and_then(|r| r.min_celsius),
+            max_celsius: range.and_then(|r| r.max_celsius),
+            out_of_range: range.is_some_and(|r| !r.contains(celsius)),
+            recorded_at: self.clock.now(),
+            recorded_by: recorded_by.to_string(),
+            owner,
+        };
+
+        self.db.execute(
+            "INSERT INTO temperature_readings (
+                id, owner_type, owner_id, probe_id, celsius, min_celsius, max_celsius,
+                out_of_range, recorded_at, recorded_by
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            params![
+                &reading.id,
+                reading.owner.owner_type(),
+                reading.owner.owner_id(),
+                &reading.probe_id,
+                reading.celsius,
+                reading.min_celsius,
+                reading.max_celsius,
+                reading.out_of_range,
+                reading.recorded_at.to_rfc3339(),
+                &reading.recorded_by,
+            ],
+        )?;
+
+        if reading.out_of_range {
+            warn!(
+                "Temperature {:.1} °C on {} {} is outside {}",
+                celsius,
+                reading.owner.owner_type(),
+                reading.owner.owner_id(),
+                range.map(|r| r.describe()).unwrap_or_default()
+            );
+        }
+        Ok(reading)
+    }
+
+    /// Every reading taken for `owner`
+    pub async fn get_temperature_log(&self, owner: &TemperatureOwner) -> Result<TemperatureLog> {
+        let readings = self.db.query_map(
+            "SELECT * FROM temperature_readings
+             WHERE owner_type = ? AND owner_id = ?
+             ORDER BY recorded_at, rowid",
+            params![owner.owner_type(), owner.owner_id()],
+            row_to_reading,
+        )?;
+
+        Ok(TemperatureLog {
+            owner: owner.clone(),
+            out_of_range_count: readings.iter().filter(|r| r.out_of_range).count(),
+            readings,
+        })
+    }
+
+    /// Export `owner`'s readings as CSV
+    pub async fn export_temperature_log(&self, owner: &TemperatureOwner) -> Result<TemperatureLogExport> {
+        let log = self.get_temperature_log(owner).await?;
+        let data = render_temperature_csv(&log)?;
+
+        Ok(TemperatureLogExport {
+            data: base64::engine::general_purpose::STANDARD.encode(&data),
+            content_type: "text/csv".to_string(),
+            filename: format!("temperature_{}_{}.csv", owner.owner_type(), owner.owner_id()),
+        })
+    }
+}
+
+fn row_to_range(row: &rusqlite::Row) -> rusqlite::Result<TemperatureRange> {
+    Ok(TemperatureRange {
+        category: row.get("category")?,
+        min_celsius: row.get("min_celsius")?,
+        max_celsius: row.get("max_celsius")?,
+    })
+}
+
+fn row_to_reading(row: &rusqlite::Row) -> rusqlite::Result<TemperatureReading> {
+    let owner_type: String = row.get("owner_type")?;
+    let owner = TemperatureOwner::from_parts(&owner_type, row.get("owner_id")?)
+        .ok_or_else(|| unreadable(row, "owner_type", &owner_type))?;
+
+    Ok(TemperatureReading {
+        id: row.get("id")?,
+        owner,
+        probe_id: row.get("probe_id")?,
+        celsius: row.get("celsius")?,
+        min_celsius: row.get("min_celsius")?,
+        max_celsius: row.get("max_celsius")?,
+        out_of_range: row.get::<_, i32>("out_of_range")? == 1,
+        recorded_at: required_timestamp(row, "recorded_at")?,
+        recorded_by: row.get("recorded_by")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{fixed_clock, test_context, test_db};
+
+    #[tokio::test]
+    async fn test_readings_flagged_against_strictest_category_range() {
+        let service = TemperatureService::new(test_db()).with_clock(fixed_clock());
+        service.save_range(TemperatureRange {
+            category: "Dairy".to_string(),
+            min_celsius: Some(0.0),
+            max_celsius: Some(5.0),
+        }).await.unwrap();
+        service.save_range(TemperatureRange {
+            category: "Produce".to_string(),
+            min_celsius: Some(2.0),
+            max_celsius: Some(8.0),
+        }).await.unwrap();
+        assert!(service.save_range(TemperatureRange {
+            category: "Frozen".to_string(),
+            min_celsius: Some(-15.0),
+            max_celsius: Some(-25.0),
+        }).await.is_err());
+
+        let range = service
+            .range_for_categories(&["dairy".to_string(), "Produce".to_string(), "Tools".to_string()])
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!((range.min_celsius, range.max_celsius), (Some(2.0), Some(5.0)));
+        assert!(service.range_for_categories(&["Tools".to_string()]).await.unwrap().is_none());
+
+        let owner = TemperatureOwner::Delivery("d1".to_string());
+        service.record(&test_context(), owner.clone(), Some("P-7"), 4.0, Some(&range)).await.unwrap();
+        let warm = service.record(&test_context(), owner.clone(), Some("P-7"), 6.5, Some(&range)).await.unwrap();
+        assert!(warm.out_of_range);
+        assert!(service.record(&test_context(), owner.clone(), None, 400.0, None).await.is_err());
+
+        let log = service.get_temperature_log(&owner).await.unwrap();
+        assert_eq!((log.readings.len(), log.out_of_range_count), (2, 1));
+        let csv = String::from_utf8(render_temperature_csv(&log).unwrap()).unwrap();
+        assert!(csv.lines().nth(2).unwrap().ends_with("6.5,2,5,yes,u1"), "{}", csv);
+    }
+}
@@ -0,0 +1,621 @@
+//! Test Support
+//!
+//! In-memory databases, a pinned clock and fixture builders for service
+//! tests. Available to this crate's tests and, through the `test-support`
+//! feature, to the other crates' tests:
+//!
+//! ```toml
+//! [dev-dependencies]
+//! wms-core = { path = "../wms-core", features = ["test-support"] }
+//! ```
+//!
+//! A service test sets up its data with the builders, then constructs the
+//! service under test over the same database:
+//!
+//! ```ignore
+//! let db = test_db();
+//! ItemBuilder::new("WID-1").stock("loc1", 6.0).insert::<InventoryItem>(&db);
+//! let clock = fixed_clock();
+//! let service = InventoryService::new(db).with_clock(clock.clone());
+//! ```
+//!
+//! The builders write rows directly rather than going through a service, so
+//! a fixture never depends on the code it is used to test. wms-core cannot
+//! name the module models, so each builder returns whichever model type the
+//! caller asks for, deserialized from the same values it inserted. `build`
+//! returns the model without inserting anything, for tests that pass it to
+//! a `create_*` method. Ids are derived from the builder's number so tests
+//! can refer to rows by literal id.
+//!
+//! New modules should add a builder here for their main record rather than
+//! hand-writing INSERTs in each test.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use chrono::{DateTime, NaiveDate, Utc};
+use rusqlite::params;
+use serde::de::DeserializeOwned;
+use serde_json::{Value, json};
+use crate::changes::{ChangeKind, ChangeRecorder};
+use crate::clock::FixedClock;
+use crate::context::RequestContext;
+use crate::db::Database;
+use crate::error::{Result, WmsError};
+
+/// User every fixture is created by
+pub const TEST_USER_ID: &str = "u1";
+
+/// Instant fixtures are stamped with and `fixed_clock` starts at
+pub const FIXTURE_TIME: &str = "2025-01-15T09:00:00Z";
+
+/// In-memory database with all migrations applied and the test user
+pub fn test_db() -> Arc<Database> {
+    let db = Database::new(&PathBuf::from(":memory:"), "test-key").expect("open in-memory database");
+    db.migrate_to_latest().expect("run migrations");
+    db.execute(
+        "INSERT INTO users (id, username, email, full_name) VALUES (?, 'tester', 'tester@example.com', 'Test User')",
+        params![TEST_USER_ID],
+    ).expect("insert test user");
+    Arc::new(db)
+}
+
+/// `FIXTURE_TIME` as a timestamp
+pub fn fixture_time() -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(FIXTURE_TIME).expect("valid fixture time").with_timezone(&Utc)
+}
+
+/// Clock pinned at `FIXTURE_TIME`
+pub fn fixed_clock() -> Arc<FixedClock> {
+    Arc::new(FixedClock::new(fixture_time()))
+}
+
+/// Unrestricted request context acting as the test user
+pub fn test_context() -> RequestContext {
+    RequestContext::for_user(TEST_USER_ID)
+}
+
+/// Insert a storage location, keyed and coded by `id`, if it doesn't exist
+pub fn insert_location(db: &Database, id: &str) {
+    db.execute(
+        "INSERT OR IGNORE INTO locations (id, code, zone) VALUES (?, ?, 'STORAGE')",
+        params![id, id],
+    ).expect("insert location");
+}
+
+/// Change recorder that refuses every change, for checking that a write
+/// is rolled back when its change can't be queued for sync
+pub struct FailingRecorder;
+
+impl ChangeRecorder for FailingRecorder {
+    fn record(&self, _conn: &rusqlite::Connection, table: &str, _record_id: &str, _kind: ChangeKind, _payload: &str) -> Result<()> {
+        Err(WmsError::conflict(format!("Change to {} could not be queued", table)))
+    }
+}
+
+fn into_model<T: DeserializeOwned>(value: Value) -> T {
+    serde_json::from_value(value).expect("fixture matches model")
+}
+
+fn fixture_address() -> Value {
+    json!({
+        "line1": "1 Main St",
+        "city": "Springfield",
+        "state": "IL",
+        "postal_code": "62701",
+        "country": "US"
+    })
+}
+
+// ============ Items ============
+
+/// Stock row created alongside an item
+struct StockFixture {
+    location_id: String,
+    quantity: f64,
+    lot_number: Option<String>,
+    expiry_date: Option<NaiveDate>,
+}
+
+/// Inventory item with optional stock
+pub struct ItemBuilder {
+    id: String,
+    sku: String,
+    name: String,
+    barcode: Option<String>,
+    reorder_point: Option<f64>,
+    lead_time_days: u32,
+    stock: Vec<StockFixture>,
+}
+
+impl ItemBuilder {
+    /// Item with the given SKU; the id is the lowercased SKU
+    pub fn new(sku: &str) -> Self {
+        Self {
+            id: sku.to_lowercase(),
+            sku: sku.to_string(),
+            name: sku.to_string(),
+            barcode: None,
+            reorder_point: None,
+            lead_time_days: 0,
+            stock: Vec::new(),
+        }
+    }
+
+    pub fn id(mut self, id: &str) -> Self {
+        self.id = id.to_string();
+        self
+    }
+
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    pub fn barcode(mut self, barcode: &str) -> Self {
+        self.barcode = Some(barcode.to_string());
+        self
+    }
+
+    pub fn reorder_point(mut self, reorder_point: f64) -> Self {
+        self.reorder_point = Some(reorder_point);
+        self
+    }
+
+    pub fn lead_time_days(mut self, days: u32) -> Self {
+        self.lead_time_days = days;
+        self
+    }
+
+    /// Unlotted stock at `location_id` (created if missing)
+    pub fn stock(mut self, location_id: &str, quantity: f64) -> Self {
+        self.stock.push(StockFixture {
+            location_id: location_id.to_string(),
+            quantity,
+            lot_number: None,
+            expiry_date: None,
+        });
+        self
+    }
+
+    /// Lot-tracked stock at `location_id` (created if missing)
+    pub fn lot(mut self, location_id: &str, lot_number: &str, quantity: f64, expiry_date: Option<NaiveDate>) -> Self {
+        self.stock.push(StockFixture {
+            location_id: location_id.to_string(),
+            quantity,
+            lot_number: Some(lot_number.to_string()),
+            expiry_date,
+        });
+        self
+    }
+
+    /// The item as a model, without inserting it
+    pub fn build<T: DeserializeOwned>(&self) -> T {
+        into_model(json!({
+            "id": self.id,
+            "sku": self.sku,
+            "name": self.name,
+            "barcode": self.barcode,
+            "reorder_point": self.reorder_point,
+            "lead_time_days": self.lead_time_days,
+            "created_at": FIXTURE_TIME,
+            "version": 1
+        }))
+    }
+
+    /// Insert the item and its stock rows
+    pub fn insert<T: DeserializeOwned>(self, db: &Database) -> T {
+        db.execute(
+            "INSERT INTO inventory_items (id, sku, name, barcode, reorder_point, lead_time_days, created_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
+            params![&self.id, &self.sku, &self.name, &self.barcode, self.reorder_point, self.lead_time_days, FIXTURE_TIME],
+        ).expect("insert item");
+
+        for (n, stock) in self.stock.iter().enumerate() {
+            insert_location(db, &stock.location_id);
+            db.execute(
+                "INSERT INTO inventory_stock (id, item_id, location_id, quantity, lot_number, expiry_date)
+                 VALUES (?, ?, ?, ?, ?, ?)",
+                params![
+                    format!("{}-stock-{}", self.id, n + 1),
+                    &self.id,
+                    &stock.location_id,
+                    stock.quantity,
+                    &stock.lot_number,
+                    stock.expiry_date.map(|d| d.to_string()),
+                ],
+            ).expect("insert stock");
+        }
+        self.build()
+    }
+}
+
+// ============ Customers ============
+
+/// CRM customer
+pub struct CustomerBuilder {
+    id: String,
+    customer_number: String,
+    company_name: String,
+    email: Option<String>,
+    credit_limit: Option<f64>,
+    payment_terms: Option<String>,
+}
+
+impl CustomerBuilder {
+    /// Customer with the given number; the id is the lowercased number
+    pub fn new(customer_number: &str) -> Self {
+        Self {
+            id: customer_number.to_lowercase(),
+            customer_number: customer_number.to_string(),
+            company_name: "Acme Corp".to_string(),
+            email: None,
+            credit_limit: None,
+            payment_terms: None,
+        }
+    }
+
+    pub fn id(mut self, id: &str) -> Self {
+        self.id = id.to_string();
+        self
+    }
+
+    pub fn company_name(mut self, company_name: &str) -> Self {
+        self.company_name = company_name.to_string();
+        self
+    }
+
+    pub fn email(mut self, email: &str) -> Self {
+        self.email = Some(email.to_string());
+        self
+    }
+
+    pub fn credit_limit(mut self, credit_limit: f64) -> Self {
+        self.credit_limit = Some(credit_limit);
+        self
+    }
+
+    pub fn payment_terms(mut self, payment_terms: &str) -> Self {
+        self.payment_terms = Some(payment_terms.to_string());
+        self
+    }
+
+    /// The customer as a model, without inserting it
+    pub fn build<T: DeserializeOwned>(&self) -> T {
+        into_model(json!({
+            "id": self.id,
+            "customer_number": self.customer_number,
+            "company_name": self.company_name,
+            "email": self.email,
+            "credit_limit": self.credit_limit,
+            "payment_terms": self.payment_terms,
+            "created_at": FIXTURE_TIME,
+            "version": 1
+        }))
+    }
+
+    /// Insert the customer
+    pub fn insert<T: DeserializeOwned>(self, db: &Database) -> T {
+        db.execute(
+            "INSERT INTO customers (
+                id, customer_number, company_name, email, credit_limit, payment_terms, tags, created_at
+            ) VALUES (?, ?, ?, ?, ?, ?, '[]', ?)",
+            params![
+                &self.id,
+                &self.customer_number,
+                &self.company_name,
+                &self.email,
+                self.credit_limit,
+                &self.payment_terms,
+                FIXTURE_TIME,
+            ],
+        ).expect("insert customer");
+        self.build()
+    }
+}
+
+// ============ Shipments ============
+
+/// Shipment line
+struct LineFixture {
+    item_id: String,
+    quantity: f64,
+    location_id: Option<String>,
+    lot_number: Option<String>,
+}
+
+/// Outbound shipment with lines, shipped to Acme Corp
+pub struct ShipmentBuilder {
+    id: String,
+    shipment_number: String,
+    status: String,
+    customer_id: Option<String>,
+    carrier_id: Option<String>,
+    service_type: Option<String>,
+    declared_value: Option<f64>,
+    lines: Vec<LineFixture>,
+}
+
+impl ShipmentBuilder {
+    /// Draft shipment with the given number; the id is the lowercased number
+    pub fn new(shipment_number: &str) -> Self {
+        Self {
+            id: shipment_number.to_lowercase(),
+            shipment_number: shipment_number.to_string(),
+            status: "draft".to_string(),
+            customer_id: None,
+            carrier_id: None,
+            service_type: None,
+            declared_value: None,
+            lines: Vec::new(),
+        }
+    }
+
+    pub fn id(mut self, id: &str) -> Self {
+        self.id = id.to_string();
+        self
+    }
+
+    /// Stored status, e.g. "picking"
+    pub fn status(mut self, status: &str) -> Self {
+        self.status = status.to_string();
+        self
+    }
+
+    /// Ship to a customer (insert it first, e.g. with `CustomerBuilder`)
+    pub fn customer(mut self, customer_id: &str) -> Self {
+        self.customer_id = Some(customer_id.to_string());
+        self
+    }
+
+    /// Ship with a carrier, e.g. one of the seeded "car_ups" or "car_fedex"
+    pub fn carrier(mut self, carrier_id: &str) -> Self {
+        self.carrier_id = Some(carrier_id.to_string());
+        self
+    }
+
+    pub fn service_type(mut self, service_type: &str) -> Self {
+        self.service_type = Some(service_type.to_string());
+        self
+    }
+
+    /// Value of the goods, counted against the customer's credit limit
+    pub fn declared_value(mut self, declared_value: f64) -> Self {
+        self.declared_value = Some(declared_value);
+        self
+    }
+
+    /// Line for `quantity` of an item, picked from anywhere
+    pub fn line(mut self, item_id: &str, quantity: f64) -> Self {
+        self.lines.push(LineFixture {
+            item_id: item_id.to_string(),
+            quantity,
+            location_id: None,
+            lot_number: None,
+        });
+        self
+    }
+
+    /// Line for `quantity` of an item from a specific location and lot
+    pub fn line_from(mut self, item_id: &str, quantity: f64, location_id: &str, lot_number: Option<&str>) -> Self {
+        self.lines.push(LineFixture {
+            item_id: item_id.to_string(),
+            quantity,
+            location_id: Some(location_id.to_string()),
+            lot_number: lot_number.map(String::from),
+        });
+        self
+    }
+
+    fn line_id(&self, n: usize) -> String {
+        format!("{}-line-{}", self.id, n + 1)
+    }
+
+    /// The shipment as a model, without inserting it
+    pub fn build<T: DeserializeOwned>(&self) -> T {
+        let items: Vec<Value> = self.lines.iter().enumerate()
+            .map(|(n, line)| json!({
+                "id": self.line_id(n),
+                "shipment_id": self.id,
+                "item_id": line.item_id,
+                "location_id": line.location_id,
+                "lot_number": line.lot_number,
+                "quantity_ordered": line.quantity,
+                "status": "pending"
+            }))
+            .collect();
+
+        into_model(json!({
+            "id": self.id,
+            "shipment_number": self.shipment_number,
+            "status": self.status,
+            "customer_id": self.customer_id,
+            "carrier_id": self.carrier_id,
+            "service_type": self.service_type,
+            "declared_value": self.declared_value,
+            "ship_to": {
+                "name": "Acme Corp",
+                "address": fixture_address()
+            },
+            "created_by": TEST_USER_ID,
+            "created_at": FIXTURE_TIME,
+            "version": 1,
+            "items": items
+        }))
+    }
+
+    /// Insert the shipment and its lines (items must already exist)
+    pub fn insert<T: DeserializeOwned>(self, db: &Database) -> T {
+        db.execute(
+            "INSERT INTO shipments (
+                id, shipment_number, status, customer_id, carrier_id, service_type, ship_to_name,
+                ship_to_address_line1, ship_to_city, ship_to_state, ship_to_postal_code,
+                declared_value, created_by, created_at
+            ) VALUES (?, ?, ?, ?, ?, ?, 'Acme Corp', '1 Main St', 'Springfield', 'IL', '62701', ?, ?, ?)",
+            params![
+                &self.id,
+                &self.shipment_number,
+                &self.status,
+                &self.customer_id,
+                &self.carrier_id,
+                &self.service_type,
+                &self.declared_value,
+                TEST_USER_ID,
+                FIXTURE_TIME,
+            ],
+        ).expect("insert shipment");
+
+        for (n, line) in self.lines.iter().enumerate() {
+            db.execute(
+                "INSERT INTO shipment_items (
+                    id, shipment_id, item_id, location_id, lot_number, quantity_ordered, status
+                ) VALUES (?, ?, ?, ?, ?, ?, 'pending')",
+                params![
+                    self.line_id(n),
+                    &self.id,
+                    &line.item_id,
+                    &line.location_id,
+                    &line.lot_number,
+                    line.quantity,
+                ],
+            ).expect("insert shipment line");
+        }
+        self.build()
+    }
+}
+
+// ============ Deliveries ============
+
+/// Delivery to Acme Corp
+pub struct DeliveryBuilder {
+    id: String,
+    delivery_number: String,
+    status: String,
+    shipment_id: Option<String>,
+    customer_id: Option<String>,
+    scheduled_date: NaiveDate,
+    location: Option<(f64, f64)>,
+}
+
+impl DeliveryBuilder {
+    /// Pending delivery scheduled on the fixture date; the id is the
+    /// lowercased number
+    pub fn new(delivery_number: &str) -> Self {
+        Self {
+            id: delivery_number.to_lowercase(),
+            delivery_number: delivery_number.to_string(),
+            status: "pending".to_string(),
+            shipment_id: None,
+            customer_id: None,
+            scheduled_date: fixture_time().date_naive(),
+            location: None,
+        }
+    }
+
+    pub fn id(mut self, id: &str) -> Self {
+        self.id = id.to_string();
+        self
+    }
+
+    /// Stored status, e.g. "assigned"
+    pub fn status(mut self, status: &str) -> Self {
+        self.status = status.to_string();
+        self
+    }
+
+    /// Deliver a shipment (insert it first, e.g. with `ShipmentBuilder`)
+    pub fn shipment(mut self, shipment_id: &str) -> Self {
+        self.shipment_id = Some(shipment_id.to_string());
+        self
+    }
+
+    /// Deliver to a customer (insert it first, e.g. with `CustomerBuilder`)
+    pub fn customer(mut self, customer_id: &str) -> Self {
+        self.customer_id = Some(customer_id.to_string());
+        self
+    }
+
+    pub fn scheduled(mut self, date: NaiveDate) -> Self {
+        self.scheduled_date = date;
+        self
+    }
+
+    /// Geocoded drop-off point
+    pub fn at(mut self, lat: f64, lng: f64) -> Self {
+        self.location = Some((lat, lng));
+        self
+    }
+
+    fn scheduled_at(&self) -> String {
+        self.scheduled_date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc().to_rfc3339()
+    }
+
+    /// The delivery as a model, without inserting it
+    pub fn build<T: DeserializeOwned>(&self) -> T {
+        into_model(json!({
+            "id": self.id,
+            "delivery_number": self.delivery_number,
+            "status": self.status,
+            "shipment_id": self.shipment_id,
+            "customer_id": self.customer_id,
+            "delivery_address": {
+                "name": "Acme Corp",
+                "address": fixture_address()
+            },
+            "location": self.location.map(|(lat, lng)| json!({ "lat": lat, "lng": lng })),
+            "scheduled_date": self.scheduled_at(),
+            "created_at": FIXTURE_TIME,
+            "version": 1
+        }))
+    }
+
+    /// Insert the delivery
+    pub fn insert<T: DeserializeOwned>(self, db: &Database) -> T {
+        db.execute(
+            "INSERT INTO deliveries (
+                id, delivery_number, status, shipment_id, customer_id, delivery_name,
+                delivery_address_line1, delivery_city, delivery_state, delivery_postal_code,
+                latitude, longitude, scheduled_date, created_at
+            ) VALUES (?, ?, ?, ?, ?, 'Acme Corp', '1 Main St', 'Springfield', 'IL', '62701', ?, ?, ?, ?)",
+            params![
+                &self.id,
+                &self.delivery_number,
+                &self.status,
+                &self.shipment_id,
+                &self.customer_id,
+                self.location.map(|(lat, _)| lat),
+                self.location.map(|(_, lng)| lng),
+                self.scheduled_at(),
+                FIXTURE_TIME,
+            ],
+        ).expect("insert delivery");
+        self.build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixtures_insert_related_rows() {
+        let db = test_db();
+        let item: Value = ItemBuilder::new("WID-1").stock("loc1", 6.0).lot("loc2", "L1", 4.0, None).insert(&db);
+        assert_eq!(item["id"], "wid-1");
+        CustomerBuilder::new("CUST-1").insert::<Value>(&db);
+        ShipmentBuilder::new("SHP-1").customer("cust-1").line("wid-1", 3.0).insert::<Value>(&db);
+        DeliveryBuilder::new("DEL-1").shipment("shp-1").customer("cust-1").insert::<Value>(&db);
+
+        let stock: f64 = db.query_row(
+            "SELECT SUM(quantity) FROM inventory_stock WHERE item_id = 'wid-1'",
+            [],
+            |row| row.get(0),
+        ).unwrap().unwrap();
+        assert_eq!(stock, 10.0);
+        let lines: i64 = db.query_row(
+            "SELECT COUNT(*) FROM shipment_items WHERE shipment_id = 'shp-1'",
+            [],
+            |row| row.get(0),
+        ).unwrap().unwrap();
+        assert_eq!(lines, 1);
+    }
+}
@@ -1,8 +1,11 @@
 //! Common Types for WMS
 
-use chrono::{DateTime, Utc};
+use base64::Engine;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
+use crate::db::Database;
+use crate::error::Result;
 
 /// Generate a new UUID v4
 pub fn new_id() -> String {
@@ -26,6 +29,69 @@ pub fn parse_timestamp(s: &str) -> Option<DateTime<Utc>> {
         .map(|dt| dt.with_timezone(&Utc))
 }
 
+/// Timestamp stored in `column`: RFC 3339 as services write it, SQLite's
+/// `datetime('now')` form, or a plain date meaning midnight UTC. Only NULL
+/// reads as no timestamp; anything else unparseable fails the load rather
+/// than being reported as some other time.
+pub fn stored_timestamp(row: &rusqlite::Row, column: &str) -> rusqlite::Result<Option<DateTime<Utc>>> {
+    let Some(value) = row.get::<_, Option<String>>(column)? else {
+        return Ok(None);
+    };
+    parse_timestamp(&value)
+        .or_else(|| NaiveDateTime::parse_from_str(&value, "%Y-%m-%d %H:%M:%S").ok().map(|t| t.and_utc()))
+        .or_else(|| {
+            NaiveDate::parse_from_str(&value, "%Y-%m-%d").ok()
+                .and_then(|d| d.and_hms_opt(0, 0, 0))
+                .map(|t| t.and_utc())
+        })
+        .map(Some)
+        .ok_or_else(|| unreadable(row, column, &value))
+}
+
+/// Timestamp stored in a NOT NULL `column`, read as `stored_timestamp` does
+pub fn required_timestamp(row: &rusqlite::Row, column: &str) -> rusqlite::Result<DateTime<Utc>> {
+    stored_timestamp(row, column)?.ok_or_else(|| unreadable(row, column, "NULL"))
+}
+
+/// `YYYY-MM-DD` date stored in `column`, or the date a stored timestamp
+/// falls on as written; NULL reads as no date
+pub fn stored_date(row: &rusqlite::Row, column: &str) -> rusqlite::Result<Option<NaiveDate>> {
+    let Some(value) = row.get::<_, Option<String>>(column)? else {
+        return Ok(None);
+    };
+    value.get(..10)
+        .and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+        .map(Some)
+        .ok_or_else(|| unreadable(row, column, &value))
+}
+
+/// Date stored in a NOT NULL `column`, read as `stored_date` does
+pub fn required_date(row: &rusqlite::Row, column: &str) -> rusqlite::Result<NaiveDate> {
+    stored_date(row, column)?.ok_or_else(|| unreadable(row, column, "NULL"))
+}
+
+/// Status or other enumerated value stored in `column`. An unknown value
+/// fails the load, since reading it as some other value could let a record
+/// skip its workflow.
+pub fn stored_status<T>(
+    row: &rusqlite::Row,
+    column: &str,
+    parse: fn(&str) -> Option<T>,
+) -> rusqlite::Result<T> {
+    let value: String = row.get(column)?;
+    parse(&value).ok_or_else(|| unreadable(row, column, &value))
+}
+
+/// Conversion error for a `column` value a row mapper can't read
+pub fn unreadable(row: &rusqlite::Row, column: &str, value: &str) -> rusqlite::Error {
+    let index = row.as_ref().column_index(column).unwrap_or_default();
+    rusqlite::Error::FromSqlConversionFailure(
+        index,
+        rusqlite::types::Type::Text,
+        format!("unreadable {} '{}'", column, value).into(),
+    )
+}
+
 /// Pagination parameters
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Pagination {
@@ -79,6 +145,73 @@ impl<T> PaginatedResponse<T> {
     }
 }
 
+/// A generated file handed to the frontend to save
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FileExport {
+    /// Base64 encoded file data
+    pub data: String,
+    /// MIME content type
+    pub content_type: String,
+    /// Suggested filename
+    pub filename: String,
+}
+
+impl FileExport {
+    pub fn new(data: &[u8], content_type: &str, filename: impl Into<String>) -> Self {
+        Self {
+            data: base64::engine::general_purpose::STANDARD.encode(data),
+            content_type: content_type.to_string(),
+            filename: filename.into(),
+        }
+    }
+}
+
+/// Inclusive calendar date range
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DateRange {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+}
+
+impl DateRange {
+    pub fn new(start: NaiveDate, end: NaiveDate) -> Self {
+        Self { start, end }
+    }
+
+    pub fn contains(&self, date: NaiveDate) -> bool {
+        self.start <= date && date <= self.end
+    }
+}
+
+/// Geographic point (latitude/longitude)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GeoPoint {
+    pub lat: f64,
+    pub lng: f64,
+}
+
+impl GeoPoint {
+    pub fn new(lat: f64, lng: f64) -> Self {
+        Self { lat, lng }
+    }
+
+    /// Calculate distance to another point in kilometers (Haversine formula)
+    pub fn distance_to(&self, other: &GeoPoint) -> f64 {
+        const EARTH_RADIUS_KM: f64 = 6371.0;
+
+        let lat1 = self.lat.to_radians();
+        let lat2 = other.lat.to_radians();
+        let dlat = (other.lat - self.lat).to_radians();
+        let dlng = (other.lng - self.lng).to_radians();
+
+        let a = (dlat / 2.0).sin().powi(2)
+            + lat1.cos() * lat2.cos() * (dlng / 2.0).sin().powi(2);
+        let c = 2.0 * a.sqrt().asin();
+
+        EARTH_RADIUS_KM * c
+    }
+}
+
 /// Sort direction
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
@@ -94,7 +227,7 @@ impl Default for SortDirection {
 }
 
 /// Address structure used across modules
-#[derive(Debug, Clone, Serialize, Deserialize, validator::Validate)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, validator::Validate)]
 pub struct Address {
     pub line1: String,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -176,6 +309,25 @@ impl Default for UnitOfMeasure {
     }
 }
 
+impl UnitOfMeasure {
+    /// Parse a stored unit ("each") or its display code ("EA"), ignoring
+    /// case
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "each" | "ea" => Some(Self::Each),
+            "case" | "cs" => Some(Self::Case),
+            "pallet" | "pl" => Some(Self::Pallet),
+            "kilogram" | "kg" => Some(Self::Kilogram),
+            "pound" | "lb" => Some(Self::Pound),
+            "liter" | "l" => Some(Self::Liter),
+            "gallon" | "gal" => Some(Self::Gallon),
+            "meter" | "m" => Some(Self::Meter),
+            "foot" | "ft" => Some(Self::Foot),
+            _ => None,
+        }
+    }
+}
+
 impl std::fmt::Display for UnitOfMeasure {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -192,3 +344,332 @@ impl std::fmt::Display for UnitOfMeasure {
     }
 }
 
+
+/// Unit system used to display one category of measurement
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum UnitSystem {
+    #[default]
+    Metric,
+    Imperial,
+}
+
+impl UnitSystem {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Metric => "metric",
+            Self::Imperial => "imperial",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "metric" => Some(Self::Metric),
+            "imperial" => Some(Self::Imperial),
+            _ => None,
+        }
+    }
+}
+
+/// Display units per measurement category. Values are always stored
+/// metric (kg, cm, km); these only affect rendering and parsing.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct MeasurementPreferences {
+    #[serde(default)]
+    pub weight: UnitSystem,
+    #[serde(default)]
+    pub length: UnitSystem,
+    #[serde(default)]
+    pub distance: UnitSystem,
+}
+
+impl MeasurementPreferences {
+    const WEIGHT_KEY: &'static str = "units_weight";
+    const LENGTH_KEY: &'static str = "units_length";
+    const DISTANCE_KEY: &'static str = "units_distance";
+
+    /// Imperial units for every category
+    pub fn imperial() -> Self {
+        Self {
+            weight: UnitSystem::Imperial,
+            length: UnitSystem::Imperial,
+            distance: UnitSystem::Imperial,
+        }
+    }
+
+    /// Load from the settings table; missing or unknown values are metric
+    pub fn load(db: &Database) -> Result<Self> {
+        let system = |key: &str| -> Result<UnitSystem> {
+            Ok(db.get_setting(key)?
+                .and_then(|value| UnitSystem::parse(&value))
+                .unwrap_or_default())
+        };
+
+        Ok(Self {
+            weight: system(Self::WEIGHT_KEY)?,
+            length: system(Self::LENGTH_KEY)?,
+            distance: system(Self::DISTANCE_KEY)?,
+        })
+    }
+
+    /// Store in the settings table
+    pub fn save(&self, db: &Database) -> Result<()> {
+        db.set_setting(Self::WEIGHT_KEY, self.weight.as_str())?;
+        db.set_setting(Self::LENGTH_KEY, self.length.as_str())?;
+        db.set_setting(Self::DISTANCE_KEY, self.distance.as_str())?;
+        Ok(())
+    }
+}
+
+/// A unit values can be displayed in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DisplayUnit {
+    pub symbol: &'static str,
+    /// Other spellings accepted when parsing
+    pub aliases: &'static [&'static str],
+    /// Display units per canonical unit
+    pub per_canonical: f64,
+    /// Decimal places shown
+    pub decimals: usize,
+}
+
+fn round_to(value: f64, decimals: usize) -> f64 {
+    let scale = 10f64.powi(decimals as i32);
+    (value * scale).round() / scale
+}
+
+/// A physical quantity stored in a canonical metric unit
+pub trait Measurement: Copy {
+    const METRIC: DisplayUnit;
+    const IMPERIAL: DisplayUnit;
+
+    fn from_canonical(value: f64) -> Self;
+
+    fn canonical(self) -> f64;
+
+    fn unit(units: UnitSystem) -> DisplayUnit {
+        match units {
+            UnitSystem::Metric => Self::METRIC,
+            UnitSystem::Imperial => Self::IMPERIAL,
+        }
+    }
+
+    /// Value in the display unit, rounded to the precision shown
+    fn display_value(self, units: UnitSystem) -> f64 {
+        let unit = Self::unit(units);
+        round_to(self.canonical() * unit.per_canonical, unit.decimals)
+    }
+
+    /// Render with unit symbol, e.g. `2.20 lb`
+    fn to_display(self, units: UnitSystem) -> String {
+        let unit = Self::unit(units);
+        format!("{:.*} {}", unit.decimals, self.display_value(units), unit.symbol)
+    }
+
+    /// Parse user input such as `2.2 lb`, `1,250 km` or `30`. A unit
+    /// symbol from either system is honored; a bare number is read in
+    /// `units`.
+    fn parse_from_display(input: &str, units: UnitSystem) -> Option<Self> {
+        let input = input.trim();
+        let split = input.find(char::is_alphabetic).unwrap_or(input.len());
+        let (number, symbol) = input.split_at(split);
+
+        let number: f64 = number.trim().replace(',', "").parse().ok()?;
+        if !number.is_finite() {
+            return None;
+        }
+
+        let symbol = symbol.trim().to_ascii_lowercase();
+        let unit = if symbol.is_empty() {
+            Self::unit(units)
+        } else {
+            [Self::METRIC, Self::IMPERIAL].into_iter()
+                .find(|unit| unit.symbol == symbol || unit.aliases.contains(&symbol.as_str()))?
+        };
+
+        Some(Self::from_canonical(number / unit.per_canonical))
+    }
+
+    /// Apply an edited display value. When the edit displays the same as
+    /// the current value, the stored value is kept unchanged so repeated
+    /// edits never drift through unit conversion.
+    fn apply_edit(self, input: &str, units: UnitSystem) -> Option<Self> {
+        let parsed = Self::parse_from_display(input, units)?;
+        if parsed.display_value(units) == self.display_value(units) {
+            Some(self)
+        } else {
+            Some(parsed)
+        }
+    }
+}
+
+/// Weight, stored in kilograms
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Weight(f64);
+
+impl Weight {
+    pub fn from_kg(kg: f64) -> Self {
+        Self(kg)
+    }
+
+    pub fn kg(self) -> f64 {
+        self.0
+    }
+}
+
+impl Measurement for Weight {
+    const METRIC: DisplayUnit = DisplayUnit { symbol: "kg", aliases: &["kgs"], per_canonical: 1.0, decimals: 2 };
+    const IMPERIAL: DisplayUnit = DisplayUnit { symbol: "lb", aliases: &["lbs"], per_canonical: 2.204_622_621_848_776, decimals: 2 };
+
+    fn from_canonical(value: f64) -> Self {
+        Self(value)
+    }
+
+    fn canonical(self) -> f64 {
+        self.0
+    }
+}
+
+/// Length (package dimensions), stored in centimeters
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Length(f64);
+
+impl Length {
+    pub fn from_cm(cm: f64) -> Self {
+        Self(cm)
+    }
+
+    pub fn cm(self) -> f64 {
+        self.0
+    }
+}
+
+impl Measurement for Length {
+    const METRIC: DisplayUnit = DisplayUnit { symbol: "cm", aliases: &[], per_canonical: 1.0, decimals: 1 };
+    const IMPERIAL: DisplayUnit = DisplayUnit { symbol: "in", aliases: &[], per_canonical: 1.0 / 2.54, decimals: 2 };
+
+    fn from_canonical(value: f64) -> Self {
+        Self(value)
+    }
+
+    fn canonical(self) -> f64 {
+        self.0
+    }
+}
+
+/// Travel distance, stored in kilometers
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Distance(f64);
+
+impl Distance {
+    pub fn from_km(km: f64) -> Self {
+        Self(km)
+    }
+
+    pub fn km(self) -> f64 {
+        self.0
+    }
+}
+
+impl Measurement for Distance {
+    const METRIC: DisplayUnit = DisplayUnit { symbol: "km", aliases: &[], per_canonical: 1.0, decimals: 2 };
+    const IMPERIAL: DisplayUnit = DisplayUnit { symbol: "mi", aliases: &[], per_canonical: 0.621_371_192_237_334, decimals: 2 };
+
+    fn from_canonical(value: f64) -> Self {
+        Self(value)
+    }
+
+    fn canonical(self) -> f64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_in_configured_units() {
+        let weight = Weight::from_kg(1.0);
+        assert_eq!(weight.to_display(UnitSystem::Metric), "1.00 kg");
+        assert_eq!(weight.to_display(UnitSystem::Imperial), "2.20 lb");
+
+        assert_eq!(Length::from_cm(30.48).to_display(UnitSystem::Imperial), "12.00 in");
+        assert_eq!(Distance::from_km(10.0).to_display(UnitSystem::Imperial), "6.21 mi");
+    }
+
+    #[test]
+    fn test_parse_honors_explicit_units() {
+        let parse = |s| Weight::parse_from_display(s, UnitSystem::Imperial).map(Weight::kg);
+
+        assert_eq!(parse("2.5 kg"), Some(2.5));
+        assert!((parse("10 lbs").unwrap() - 4.535_923_7).abs() < 1e-6);
+        assert!((parse("10").unwrap() - 4.535_923_7).abs() < 1e-6);
+        assert_eq!(Distance::parse_from_display("1,250 km", UnitSystem::Metric).map(Distance::km), Some(1250.0));
+        assert_eq!(parse("2.5 stone"), None);
+        assert_eq!(parse("heavy"), None);
+    }
+
+    #[test]
+    fn test_round_trip_is_stable_at_rounding_boundaries() {
+        for input in ["2.2046", "2.205", "2.215", "0.005", "99.995", "1.125"] {
+            let first = Weight::parse_from_display(input, UnitSystem::Imperial).unwrap();
+            let shown = first.to_display(UnitSystem::Imperial);
+
+            // Re-entering the displayed text never changes what is displayed
+            let mut value = first;
+            for _ in 0..10 {
+                value = Weight::parse_from_display(&value.to_display(UnitSystem::Imperial), UnitSystem::Imperial)
+                    .unwrap();
+                assert_eq!(value.to_display(UnitSystem::Imperial), shown, "input {}", input);
+            }
+        }
+
+        for input in ["12.345", "0.005", "39.375"] {
+            let first = Length::parse_from_display(input, UnitSystem::Imperial).unwrap();
+            let again = Length::parse_from_display(&first.to_display(UnitSystem::Imperial), UnitSystem::Imperial)
+                .unwrap();
+            assert_eq!(again.to_display(UnitSystem::Imperial), first.to_display(UnitSystem::Imperial));
+        }
+    }
+
+    #[test]
+    fn test_stored_values_fail_when_unreadable() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        let read = |value: Option<&str>| {
+            conn.query_row("SELECT ?1 AS at, ?1 AS day", [value], |row| {
+                Ok((stored_timestamp(row, "at")?, stored_date(row, "day")))
+            })
+        };
+
+        let (at, day) = read(Some("2025-03-03 10:00:00")).unwrap();
+        assert_eq!(at.unwrap().to_rfc3339(), "2025-03-03T10:00:00+00:00");
+        assert_eq!(day.unwrap(), NaiveDate::from_ymd_opt(2025, 3, 3));
+        let (at, day) = read(Some("2025-03-03")).unwrap();
+        assert_eq!(at.unwrap().to_rfc3339(), "2025-03-03T00:00:00+00:00");
+        assert_eq!(day.unwrap(), NaiveDate::from_ymd_opt(2025, 3, 3));
+        assert!(matches!(read(None).unwrap(), (None, Ok(None))));
+
+        let err = read(Some("last tuesday")).unwrap_err();
+        assert!(matches!(err, rusqlite::Error::FromSqlConversionFailure(0, _, _)));
+        assert!(conn.query_row("SELECT NULL AS at", [], |row| required_timestamp(row, "at")).is_err());
+        assert!(conn.query_row("SELECT 'closed' AS status", [], |row| {
+            stored_status(row, "status", |s| (s == "open").then_some(()))
+        }).is_err());
+    }
+
+    #[test]
+    fn test_unchanged_edit_keeps_stored_value() {
+        let stored = Weight::from_kg(1.0);
+
+        // 2.2046 lb shows as 2.20 lb; saving either text keeps exactly 1 kg
+        assert_eq!(stored.apply_edit("2.20 lb", UnitSystem::Imperial), Some(stored));
+        assert_eq!(stored.apply_edit("2.2046", UnitSystem::Imperial), Some(stored));
+
+        let changed = stored.apply_edit("2.5", UnitSystem::Imperial).unwrap();
+        assert_eq!(changed.to_display(UnitSystem::Imperial), "2.50 lb");
+    }
+}
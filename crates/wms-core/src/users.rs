@@ -0,0 +1,420 @@
+//! User Accounts
+//!
+//! Users sign in with their username and a PIN or password, kept as an
+//! Argon2 hash in `users.password_hash`. Signing in starts a `Session`
+//! with the user's role; accounts that are disabled or have no password
+//! cannot sign in.
+//!
+//! After `MAX_FAILED_LOGINS` wrong PINs in a row an account is locked for
+//! `LOGIN_LOCKOUT`, so a short PIN can't be guessed by trying them all.
+//! Setting a new PIN unlocks it.
+//!
+//! On first run nobody has a password yet, so an `admin` account is
+//! created with a generated PIN to set the rest up with.
+
+use std::sync::Arc;
+use argon2::Argon2;
+use chrono::{DateTime, Duration, Utc};
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+use uuid::Uuid;
+use crate::auth::{Role, Scope, Session};
+use crate::clock::{SharedClock, system_clock};
+use crate::context::RequestContext;
+use crate::db::Database;
+use crate::error::{WmsError, Result};
+use crate::types::{new_id, parse_timestamp};
+
+/// Shortest PIN or password accepted
+pub const MIN_PASSWORD_LENGTH: usize = 4;
+
+/// Wrong PINs in a row that lock an account
+pub const MAX_FAILED_LOGINS: i64 = 5;
+
+/// How long a locked account stays locked
+pub const LOGIN_LOCKOUT: Duration = Duration::minutes(15);
+
+/// Username of the account created on first run
+pub const INITIAL_ADMIN_USERNAME: &str = "admin";
+
+const USER_COLUMNS: &str = "id, username, email, full_name, role, department, is_active";
+
+/// A user account, without its credentials
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct User {
+    pub id: String,
+    pub username: String,
+    pub email: String,
+    pub full_name: String,
+    pub role: Role,
+    pub department: Option<String>,
+    pub is_active: bool,
+}
+
+/// A user to create
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewUser {
+    pub username: String,
+    pub email: String,
+    pub full_name: String,
+    pub role: Role,
+    #[serde(default)]
+    pub department: Option<String>,
+    pub password: String,
+}
+
+/// Argon2 hash of `password` in PHC string format
+pub fn hash_password(password: &str) -> Result<String> {
+    let salt = SaltString::encode_b64(Uuid::new_v4().as_bytes())
+        .map_err(|e| WmsError::Unknown(format!("Could not salt password: {}", e)))?;
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| WmsError::Unknown(format!("Could not hash password: {}", e)))
+}
+
+/// Whether `password` matches a hash from `hash_password`
+pub fn verify_password(password: &str, hash: &str) -> bool {
+    PasswordHash::new(hash)
+        .is_ok_and(|parsed| Argon2::default().verify_password(password.as_bytes(), &parsed).is_ok())
+}
+
+fn check_password(password: &str) -> Result<()> {
+    if password.chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(WmsError::validation(format!(
+            "PINs and passwords are at least {} characters",
+            MIN_PASSWORD_LENGTH
+        )));
+    }
+    Ok(())
+}
+
+/// User accounts and signing in
+pub struct UserService {
+    db: Arc<Database>,
+    clock: SharedClock,
+}
+
+impl UserService {
+    pub fn new(db: Arc<Database>) -> Self {
+        Self { db, clock: system_clock() }
+    }
+
+    /// Read the current time from `clock`
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Check a username and PIN, returning the user and their session.
+    /// Fails without checking the PIN while the account is locked.
+    pub async fn login(&self, username: &str, password: &str) -> Result<(User, Session)> {
+        let denied = || WmsError::Auth("Username or PIN is wrong".to_string());
+        let (user, hash, locked_until) = self.db.query_row(
+            &format!(
+                "SELECT {}, password_hash, locked_until FROM users WHERE username = ? COLLATE NOCASE",
+                USER_COLUMNS
+            ),
+            params![username.trim()],
+            |row| Ok((row_to_user(row)?, row.get::<_, Option<String>>(7)?, row.get::<_, Option<String>>(8)?)),
+        )?.ok_or_else(denied)?;
+
+        let now = self.clock.now();
+        if let Some(until) = locked_until.as_deref().and_then(parse_timestamp).filter(|until| *until > now) {
+            return Err(locked(&user, until));
+        }
+        if !hash.is_some_and(|hash| verify_password(password, &hash)) {
+            return Err(self.record_failed_login(&user, now)?.map_or_else(denied, |until| locked(&user, until)));
+        }
+        if !user.is_active {
+            return Err(WmsError::Auth(format!("{} has been disabled", user.username)));
+        }
+
+        self.db.execute(
+            "UPDATE users SET last_login_at = ?, failed_login_attempts = 0, locked_until = NULL WHERE id = ?",
+            params![now.to_rfc3339(), user.id],
+        )?;
+        info!("{} signed in", user.username);
+        let session = Session { user_id: user.id.clone(), role: user.role };
+        Ok((user, session))
+    }
+
+    /// Count a wrong PIN, locking the account on the last one allowed.
+    /// Returns when the lock ends if this locked it.
+    fn record_failed_login(&self, user: &User, now: DateTime<Utc>) -> Result<Option<DateTime<Utc>>> {
+        let until = now + LOGIN_LOCKOUT;
+        let locked: bool = self.db.query_row(
+            "UPDATE users SET
+                failed_login_attempts = CASE WHEN failed_login_attempts + 1 >= ?1 THEN 0
+                                             ELSE failed_login_attempts + 1 END,
+                locked_until = CASE WHEN failed_login_attempts + 1 >= ?1 THEN ?2 ELSE locked_until END
+             WHERE id = ?3
+             RETURNING locked_until IS ?2",
+            params![MAX_FAILED_LOGINS, until.to_rfc3339(), user.id],
+            |row| row.get(0),
+        )?.unwrap_or(false);
+        if !locked {
+            return Ok(None);
+        }
+        warn!("{} locked out after {} wrong PINs", user.username, MAX_FAILED_LOGINS);
+        Ok(Some(until))
+    }
+
+    pub async fn get_user(&self, id: &str) -> Result<Option<User>> {
+        self.db.query_row(
+            &format!("SELECT {} FROM users WHERE id = ?", USER_COLUMNS),
+            params![id],
+            row_to_user,
+        )
+    }
+
+    pub async fn get_user_by_username(&self, username: &str) -> Result<Option<User>> {
+        self.db.query_row(
+            &format!("SELECT {} FROM users WHERE username = ? COLLATE NOCASE", USER_COLUMNS),
+            params![username.trim()],
+            row_to_user,
+        )
+    }
+
+    /// Every user, active ones first
+    pub async fn list_users(&self, ctx: &RequestContext) -> Result<Vec<User>> {
+        ctx.require(Scope::Settings)?;
+        self.db.query_map(
+            &format!("SELECT {} FROM users ORDER BY is_active DESC, username COLLATE NOCASE", USER_COLUMNS),
+            [],
+            row_to_user,
+        )
+    }
+
+    pub async fn create_user(&self, ctx: &RequestContext, user: NewUser) -> Result<User> {
+        ctx.require(Scope::Settings)?;
+        let username = user.username.trim();
+        if username.is_empty() || user.full_name.trim().is_empty() {
+            return Err(WmsError::validation("Username and full name are required"));
+        }
+        check_password(&user.password)?;
+
+        let taken: Option<i64> = self.db.query_row(
+            "SELECT 1 FROM users WHERE username = ? COLLATE NOCASE OR email = ? COLLATE NOCASE",
+            params![username, user.email.trim()],
+            |row| row.get(0),
+        )?;
+        if taken.is_some() {
+            return Err(WmsError::conflict(format!("Username {} or its email is already in use", username)));
+        }
+
+        let id = new_id();
+        self.db.execute(
+            "INSERT INTO users (id, username, email, full_name, role, department, password_hash, created_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+            params![
+                id,
+                username,
+                user.email.trim(),
+                user.full_name.trim(),
+                user.role.as_str(),
+                user.department,
+                hash_password(&user.password)?,
+                self.clock.now().to_rfc3339(),
+            ],
+        )?;
+        info!("User {} created as {}", username, user.role.as_str());
+        self.get_user(&id).await?
+            .ok_or_else(|| WmsError::not_found(format!("No user {}", id)))
+    }
+
+    /// Change a user's PIN or password. Users may change their own; anyone
+    /// else's needs `Scope::Settings`.
+    pub async fn set_password(&self, ctx: &RequestContext, user_id: &str, password: &str) -> Result<()> {
+        if ctx.user_id.as_deref() != Some(user_id) {
+            ctx.require(Scope::Settings)?;
+        }
+        check_password(password)?;
+        let updated = self.db.execute(
+            "UPDATE users SET password_hash = ?, failed_login_attempts = 0, locked_until = NULL, updated_at = ?
+             WHERE id = ?",
+            params![hash_password(password)?, self.clock.now().to_rfc3339(), user_id],
+        )?;
+        if updated == 0 {
+            return Err(WmsError::not_found(format!("No user {}", user_id)));
+        }
+        Ok(())
+    }
+
+    /// Enable or disable an account. Disabled users keep their history but
+    /// cannot sign in.
+    pub async fn set_active(&self, ctx: &RequestContext, user_id: &str, active: bool) -> Result<User> {
+        ctx.require(Scope::Settings)?;
+        if !active && ctx.user_id.as_deref() == Some(user_id) {
+            return Err(WmsError::validation("You cannot disable your own account"));
+        }
+        let updated = self.db.execute(
+            "UPDATE users SET is_active = ?, updated_at = ? WHERE id = ?",
+            params![active, self.clock.now().to_rfc3339(), user_id],
+        )?;
+        if updated == 0 {
+            return Err(WmsError::not_found(format!("No user {}", user_id)));
+        }
+        self.get_user(user_id).await?
+            .ok_or_else(|| WmsError::not_found(format!("No user {}", user_id)))
+    }
+
+    /// Give `admin` a generated PIN when no user can sign in yet, returning
+    /// the PIN to show once. An existing `admin` user is made an active
+    /// admin.
+    pub fn seed_admin(&self) -> Result<Option<String>> {
+        let with_password: i64 = self.db.query_row(
+            "SELECT COUNT(*) FROM users WHERE password_hash IS NOT NULL",
+            [],
+            |row| row.get(0),
+        )?.unwrap_or(0);
+        if with_password > 0 {
+            return Ok(None);
+        }
+
+        let pin = format!("{:06}", Uuid::new_v4().as_u128() % 1_000_000);
+        self.db.execute(
+            "INSERT INTO users (id, username, email, full_name, role, password_hash, created_at)
+             VALUES (?, ?, 'admin@localhost', 'Administrator', 'admin', ?, ?)
+             ON CONFLICT (username) DO UPDATE SET
+                role = 'admin', is_active = 1, password_hash = excluded.password_hash",
+            params![new_id(), INITIAL_ADMIN_USERNAME, hash_password(&pin)?, self.clock.now().to_rfc3339()],
+        )?;
+        info!("Initial {} account created", INITIAL_ADMIN_USERNAME);
+        Ok(Some(pin))
+    }
+}
+
+fn locked(user: &User, until: DateTime<Utc>) -> WmsError {
+    WmsError::Auth(format!(
+        "Too many wrong PINs; {} is locked until {}",
+        user.username, until.format("%H:%M UTC")
+    ))
+}
+
+fn row_to_user(row: &rusqlite::Row) -> rusqlite::Result<User> {
+    let role: String = row.get(4)?;
+    Ok(User {
+        id: row.get(0)?,
+        username: row.get(1)?,
+        email: row.get(2)?,
+        full_name: row.get(3)?,
+        role: Role::parse(&role).unwrap_or(Role::Operator),
+        department: row.get(5)?,
+        is_active: row.get(6)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{fixed_clock, test_context, test_db, TEST_USER_ID};
+
+    fn new_user(username: &str, role: Role) -> NewUser {
+        NewUser {
+            username: username.to_string(),
+            email: format!("{}@example.com", username),
+            full_name: username.to_string(),
+            role,
+            department: None,
+            password: "4321".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_password_hash_round_trip() {
+        let hash = hash_password("1234").unwrap();
+        assert!(hash.starts_with("$argon2"));
+        assert!(verify_password("1234", &hash));
+        assert!(!verify_password("1235", &hash));
+        assert!(!verify_password("1234", "not a hash"));
+        // Salted, so the same PIN hashes differently each time
+        assert_ne!(hash, hash_password("1234").unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_login_checks_pin_and_rejects_inactive_users() {
+        let db = test_db();
+        let users = UserService::new(db.clone());
+        let admin = test_context();
+        let pat = users.create_user(&admin, new_user("pat", Role::Picker)).await.unwrap();
+
+        let (user, session) = users.login("Pat", "4321").await.unwrap();
+        assert_eq!(user, pat);
+        assert_eq!(session, Session { user_id: pat.id.clone(), role: Role::Picker });
+
+        assert!(matches!(users.login("pat", "0000").await.unwrap_err(), WmsError::Auth(_)));
+        assert!(matches!(users.login("nobody", "4321").await.unwrap_err(), WmsError::Auth(_)));
+        // The fixture user has never had a password
+        assert!(users.login("tester", "").await.is_err());
+
+        users.set_active(&admin, &pat.id, false).await.unwrap();
+        let err = users.login("pat", "4321").await.unwrap_err();
+        assert!(matches!(err, WmsError::Auth(ref msg) if msg.contains("disabled")));
+    }
+
+    #[tokio::test]
+    async fn test_repeated_wrong_pins_lock_the_account() {
+        let db = test_db();
+        let clock = fixed_clock();
+        let users = UserService::new(db.clone()).with_clock(clock.clone());
+        let pat = users.create_user(&test_context(), new_user("pat", Role::Picker)).await.unwrap();
+
+        // A sign-in in between starts the count again
+        for _ in 1..MAX_FAILED_LOGINS {
+            assert!(users.login("pat", "0000").await.is_err());
+        }
+        users.login("pat", "4321").await.unwrap();
+        for _ in 1..MAX_FAILED_LOGINS {
+            assert!(users.login("pat", "0000").await.is_err());
+        }
+        let err = users.login("pat", "0000").await.unwrap_err();
+        assert!(matches!(err, WmsError::Auth(ref msg) if msg.contains("locked")));
+
+        // Even the right PIN is refused until the lock runs out
+        assert!(users.login("pat", "4321").await.is_err());
+        clock.advance(LOGIN_LOCKOUT);
+        users.login("pat", "4321").await.unwrap();
+
+        // A new PIN unlocks the account at once
+        for _ in 0..MAX_FAILED_LOGINS {
+            assert!(users.login("pat", "0000").await.is_err());
+        }
+        users.set_password(&test_context(), &pat.id, "2468").await.unwrap();
+        users.login("pat", "2468").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_only_admins_manage_other_users() {
+        let db = test_db();
+        let users = UserService::new(db.clone());
+        let pat = users.create_user(&test_context(), new_user("pat", Role::Picker)).await.unwrap();
+        let picker = RequestContext::new(Some(&Session { user_id: pat.id.clone(), role: Role::Picker }));
+
+        let err = users.create_user(&picker, new_user("sam", Role::Admin)).await.unwrap_err();
+        assert!(matches!(err, WmsError::PermissionDenied(_)));
+        assert!(users.set_password(&picker, TEST_USER_ID, "9999").await.is_err());
+
+        users.set_password(&picker, &pat.id, "2468").await.unwrap();
+        assert!(users.login("pat", "2468").await.is_ok());
+
+        let err = users.create_user(&test_context(), new_user("PAT", Role::Driver)).await.unwrap_err();
+        assert!(matches!(err, WmsError::Conflict(_)));
+    }
+
+    #[tokio::test]
+    async fn test_admin_is_seeded_once() {
+        let db = test_db();
+        let users = UserService::new(db.clone());
+
+        let pin = users.seed_admin().unwrap().unwrap();
+        assert_eq!(pin.len(), 6);
+        let (admin, session) = users.login(INITIAL_ADMIN_USERNAME, &pin).await.unwrap();
+        assert_eq!(admin.role, Role::Admin);
+        assert!(session.require(Scope::Settings).is_ok());
+
+        assert_eq!(users.seed_admin().unwrap(), None);
+    }
+}
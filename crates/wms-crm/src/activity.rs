@@ -0,0 +1,128 @@
+//! Customer Activity
+//!
+//! A read model of what a customer does with us: one timeline built from
+//! their shipments, deliveries and logged interactions, and order totals
+//! for the customer detail page. Shipments and deliveries belong to other
+//! modules, so their tables are read by name.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Table of outbound shipments (orders)
+pub const SHIPMENTS_TABLE: &str = "shipments";
+
+/// Table of local deliveries
+pub const DELIVERIES_TABLE: &str = "deliveries";
+
+/// Table of logged customer interactions
+pub const INTERACTIONS_TABLE: &str = "customer_interactions";
+
+/// Condition on a shipments row that is still open: not yet shipped,
+/// delivered or cancelled
+pub const OPEN_SHIPMENT_CONDITION: &str = "status NOT IN ('shipped', 'delivered', 'cancelled')";
+
+/// Condition on a customer_interactions row logged against the customer
+/// bound to `?` or any customer merged into it. Interactions are a log,
+/// so a merge leaves them with the customer they were logged against.
+pub const MERGED_INTERACTION_CONDITION: &str = "customer_id IN (
+    WITH RECURSIVE merged(id) AS (
+        SELECT ?
+        UNION SELECT c.id FROM customers c JOIN merged m ON c.merged_into = m.id
+    )
+    SELECT id FROM merged
+)";
+
+/// Events shown when no limit is given
+pub const DEFAULT_ACTIVITY_LIMIT: u32 = 50;
+
+/// What happened
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ActivityKind {
+    ShipmentCreated,
+    ShipmentShipped,
+    ShipmentDelivered,
+    DeliveryCompleted,
+    DeliveryFailed,
+    Interaction,
+}
+
+/// One entry of a customer's activity timeline
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActivityEvent {
+    pub occurred_at: DateTime<Utc>,
+    pub kind: ActivityKind,
+    /// Shipment or delivery number; None for interactions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reference_number: Option<String>,
+    pub summary: String,
+}
+
+/// Order totals for a customer
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CustomerStats {
+    /// Shipments that were not cancelled
+    pub shipment_count: u32,
+    /// Shipments not yet shipped, delivered or cancelled
+    pub open_shipment_count: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_order_at: Option<DateTime<Utc>>,
+    /// Mean gap between consecutive orders; None with fewer than two
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub average_days_between_orders: Option<f64>,
+}
+
+/// Newest first, at most `limit` events
+pub fn build_timeline(mut events: Vec<ActivityEvent>, limit: usize) -> Vec<ActivityEvent> {
+    events.sort_by_key(|e| std::cmp::Reverse(e.occurred_at));
+    events.truncate(limit);
+    events
+}
+
+/// Totals from the creation times of a customer's non-cancelled orders
+pub fn order_stats(mut order_dates: Vec<DateTime<Utc>>, open_shipment_count: u32) -> CustomerStats {
+    order_dates.sort();
+    let average_days_between_orders = match (order_dates.first(), order_dates.last()) {
+        (Some(first), Some(last)) if order_dates.len() > 1 => {
+            let days = (*last - *first).num_seconds() as f64 / 86_400.0;
+            Some(days / (order_dates.len() - 1) as f64)
+        }
+        _ => None,
+    };
+
+    CustomerStats {
+        shipment_count: order_dates.len() as u32,
+        open_shipment_count,
+        last_order_at: order_dates.last().copied(),
+        average_days_between_orders,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(day: u32) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(&format!("2025-03-{:02}T09:00:00Z", day)).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_order_stats_averages_gaps() {
+        let stats = order_stats(vec![at(11), at(1), at(4)], 1);
+
+        assert_eq!(stats.shipment_count, 3);
+        assert_eq!(stats.open_shipment_count, 1);
+        assert_eq!(stats.last_order_at, Some(at(11)));
+        assert_eq!(stats.average_days_between_orders, Some(5.0));
+    }
+
+    #[test]
+    fn test_order_stats_without_orders() {
+        let stats = order_stats(Vec::new(), 0);
+
+        assert_eq!(stats.shipment_count, 0);
+        assert!(stats.last_order_at.is_none());
+        assert!(stats.average_days_between_orders.is_none());
+        assert!(order_stats(vec![at(2)], 0).average_days_between_orders.is_none());
+    }
+}
@@ -0,0 +1,50 @@
+//! Customer Export
+//!
+//! Renders active customers as CSV, with a column per custom customer
+//! field after the standard ones.
+
+use csv::Writer;
+use wms_core::custom_fields::{display_value, CustomFieldDefinition};
+use wms_core::error::{WmsError, Result};
+use crate::models::Customer;
+
+/// Render customers as CSV, one row per customer
+pub fn render_customers_csv(customers: &[Customer], custom_fields: &[CustomFieldDefinition]) -> Result<Vec<u8>> {
+    let csv_error = |e: csv::Error| WmsError::Export(format!("CSV write error: {}", e));
+    let mut writer = Writer::from_writer(Vec::new());
+
+    let mut header = vec![
+        "Customer Number",
+        "Company",
+        "First Name",
+        "Last Name",
+        "Email",
+        "Phone",
+        "Type",
+        "Credit Limit",
+        "Payment Terms",
+        "Currency",
+    ];
+    header.extend(custom_fields.iter().map(|f| f.label.as_str()));
+    writer.write_record(&header).map_err(csv_error)?;
+
+    for customer in customers {
+        let mut record = vec![
+            customer.customer_number.clone(),
+            customer.company_name.clone().unwrap_or_default(),
+            customer.first_name.clone().unwrap_or_default(),
+            customer.last_name.clone().unwrap_or_default(),
+            customer.email.clone().unwrap_or_default(),
+            customer.phone.clone().unwrap_or_default(),
+            format!("{:?}", customer.customer_type).to_lowercase(),
+            customer.credit_limit.map(|l| format!("{:.2}", l)).unwrap_or_default(),
+            customer.payment_terms.clone().unwrap_or_default(),
+            customer.currency_code.clone(),
+        ];
+        record.extend(custom_fields.iter().map(|f| display_value(customer.custom_fields.get(&f.key))));
+        writer.write_record(&record).map_err(csv_error)?;
+    }
+
+    writer.into_inner()
+        .map_err(|e| WmsError::Export(format!("CSV flush error: {}", e)))
+}
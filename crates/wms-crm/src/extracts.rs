@@ -0,0 +1,1030 @@
+//! Scheduled Customer Extracts
+//!
+//! Customers can get a regular file of their shipments, deliveries or
+//! consigned stock. Each extract definition names a dataset, a format
+//! (CSV or JSON), a cron-style schedule and a destination: a local folder
+//! or an SFTP server. A run writes the data file followed by a manifest
+//! listing each file's size and SHA-256 checksum, so the customer can tell
+//! a complete drop from one still arriving.
+//!
+//! Due extracts are run by a background worker. A failed run is retried
+//! with exponential backoff; once the retries are used up an
+//! `extract.failed` event alerts the user and the extract waits for its
+//! next scheduled run. Every attempt is kept in the run history.
+//!
+//! SFTP passwords are kept in the encrypted database and never returned
+//! to the frontend. Uploading needs the `sftp` feature; without it SFTP
+//! extracts fail with an explanation.
+
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use rusqlite::{params, params_from_iter};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use tracing::{debug, info, warn};
+use wms_core::clock::{SharedClock, system_clock};
+use wms_core::db::Database;
+use wms_core::error::{WmsError, Result};
+use wms_core::events::{SharedEmitter, WmsEvent, noop_emitter};
+use wms_core::shutdown::TaskHandle;
+use wms_core::types::{new_id, required_timestamp, stored_status, stored_timestamp, unreadable};
+use crate::schedule::Schedule;
+
+/// Default attempts per scheduled run before alerting
+const DEFAULT_MAX_ATTEMPTS: u32 = 4;
+
+/// Default delay before the first retry; doubles with each attempt
+const DEFAULT_RETRY_DELAY: Duration = Duration::from_secs(5 * 60);
+
+/// Longest wait between retries
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(2 * 60 * 60);
+
+/// Days of shipments and deliveries included in each extract
+pub const EXTRACT_LOOKBACK_DAYS: i64 = 30;
+
+/// Default SFTP port
+pub const DEFAULT_SFTP_PORT: u16 = 22;
+
+/// Data included in an extract
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExtractDataset {
+    /// The customer's shipments created in the lookback window
+    Shipments,
+    /// The customer's deliveries scheduled in the lookback window
+    Deliveries,
+    /// On-hand stock of items consigned to the customer
+    Stock,
+}
+
+impl ExtractDataset {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Shipments => "shipments",
+            Self::Deliveries => "deliveries",
+            Self::Stock => "stock",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "shipments" => Some(Self::Shipments),
+            "deliveries" => Some(Self::Deliveries),
+            "stock" => Some(Self::Stock),
+            _ => None,
+        }
+    }
+}
+
+/// File format of the data file
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExtractFormat {
+    Csv,
+    Json,
+}
+
+impl ExtractFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Json => "json",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "csv" => Some(Self::Csv),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+}
+
+/// SFTP server an extract is uploaded to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SftpDestination {
+    pub host: String,
+    #[serde(default = "default_sftp_port")]
+    pub port: u16,
+    pub username: String,
+    /// Only sent when setting or changing it; never returned
+    #[serde(default, skip_serializing)]
+    pub password: Option<String>,
+    /// Remote folder; the login folder when empty
+    #[serde(default)]
+    pub directory: String,
+    /// Expected SHA-256 of the server's host key (hex); unchecked when empty
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host_key_sha256: Option<String>,
+}
+
+fn default_sftp_port() -> u16 {
+    DEFAULT_SFTP_PORT
+}
+
+/// Where an extract's files go
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ExtractDestination {
+    /// A folder on this machine (or a mounted share)
+    Local { path: String },
+    Sftp(SftpDestination),
+}
+
+impl ExtractDestination {
+    fn type_str(&self) -> &'static str {
+        match self {
+            Self::Local { .. } => "local",
+            Self::Sftp(_) => "sftp",
+        }
+    }
+}
+
+/// A scheduled extract for one customer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExtractDefinition {
+    #[serde(default)]
+    pub id: String,
+    pub customer_id: String,
+    pub name: String,
+    pub dataset: ExtractDataset,
+    pub format: ExtractFormat,
+    /// Cron expression in UTC, e.g. "0 2 * * *" (see `Schedule`)
+    pub schedule: String,
+    pub destination: ExtractDestination,
+    #[serde(default = "default_true")]
+    pub is_active: bool,
+    /// When the worker runs it next (a retry time after a failure)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_run_at: Option<DateTime<Utc>>,
+    /// Failed attempts of the current run
+    #[serde(default)]
+    pub failed_attempts: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// What started a run
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExtractTrigger {
+    Schedule,
+    Retry,
+    Manual,
+}
+
+impl ExtractTrigger {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Schedule => "schedule",
+            Self::Retry => "retry",
+            Self::Manual => "manual",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "schedule" => Some(Self::Schedule),
+            "retry" => Some(Self::Retry),
+            "manual" => Some(Self::Manual),
+            _ => None,
+        }
+    }
+}
+
+/// A file written by a run, as listed in the manifest
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ExtractFileInfo {
+    pub name: String,
+    pub bytes: u64,
+    pub sha256: String,
+}
+
+/// A file ready to be delivered
+#[derive(Debug, Clone)]
+pub struct ExtractFile {
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+/// Manifest written after the data file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExtractManifest {
+    pub extract_id: String,
+    pub customer_number: String,
+    pub dataset: ExtractDataset,
+    pub format: ExtractFormat,
+    pub generated_at: DateTime<Utc>,
+    pub row_count: usize,
+    pub files: Vec<ExtractFileInfo>,
+}
+
+/// One attempt at running an extract
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExtractRun {
+    pub id: String,
+    pub definition_id: String,
+    pub trigger: ExtractTrigger,
+    pub succeeded: bool,
+    /// Attempt number within the scheduled run
+    pub attempt: u32,
+    pub row_count: usize,
+    /// Data files delivered, without the manifest
+    pub files: Vec<ExtractFileInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+}
+
+/// Connection to customers' SFTP servers
+pub trait SftpTransport: Send + Sync {
+    /// Upload `files`, in order, to the destination folder
+    fn upload(&self, destination: &SftpDestination, files: &[ExtractFile]) -> Result<()>;
+}
+
+/// Shared transport handle
+pub type SharedSftpTransport = Arc<dyn SftpTransport>;
+
+/// SFTP uploads over libssh2. Files are written under a `.part` name and
+/// renamed once complete.
+#[cfg(feature = "sftp")]
+pub struct Ssh2SftpTransport {
+    timeout: Duration,
+}
+
+#[cfg(feature = "sftp")]
+impl Ssh2SftpTransport {
+    /// Create a transport with the given connect and I/O timeout
+    pub fn new(timeout: Duration) -> Self {
+        Self { timeout }
+    }
+}
+
+#[cfg(feature = "sftp")]
+impl Default for Ssh2SftpTransport {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(30))
+    }
+}
+
+#[cfg(feature = "sftp")]
+impl SftpTransport for Ssh2SftpTransport {
+    fn upload(&self, destination: &SftpDestination, files: &[ExtractFile]) -> Result<()> {
+        use std::io::Write;
+        use std::net::{TcpStream, ToSocketAddrs};
+
+        let network = |e: ssh2::Error| WmsError::Network(format!("SFTP {}: {}", destination.host, e));
+        let address = (destination.host.as_str(), destination.port)
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| WmsError::Network(format!("Cannot resolve SFTP host {}", destination.host)))?;
+
+        let mut session = ssh2::Session::new().map_err(network)?;
+        session.set_tcp_stream(TcpStream::connect_timeout(&address, self.timeout)?);
+        session.set_timeout(self.timeout.as_millis() as u32);
+        session.handshake().map_err(network)?;
+
+        if let Some(expected) = destination.host_key_sha256.as_deref().filter(|k| !k.trim().is_empty()) {
+            let actual: String = session.host_key_hash(ssh2::HashType::Sha256)
+                .unwrap_or_default()
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect();
+            if !actual.eq_ignore_ascii_case(expected.trim()) {
+                return Err(WmsError::Network(format!(
+                    "SFTP host key for {} does not match the one on file", destination.host
+                )));
+            }
+        }
+
+        session
+            .userauth_password(&destination.username, destination.password.as_deref().unwrap_or_default())
+            .map_err(network)?;
+        let sftp = session.sftp().map_err(network)?;
+
+        let folder = Path::new(&destination.directory);
+        for file in files {
+            let partial = folder.join(format!("{}.part", file.name));
+            let mut remote = sftp.create(&partial).map_err(network)?;
+            remote.write_all(&file.data)?;
+            drop(remote);
+            sftp.rename(&partial, &folder.join(&file.name), None).map_err(network)?;
+        }
+        Ok(())
+    }
+}
+
+/// Write `files`, in order, into a local folder
+fn write_local(folder: &Path, files: &[ExtractFile]) -> Result<()> {
+    std::fs::create_dir_all(folder)?;
+    for file in files {
+        let partial = folder.join(format!("{}.part", file.name));
+        std::fs::write(&partial, &file.data)?;
+        std::fs::rename(&partial, folder.join(&file.name))?;
+    }
+    Ok(())
+}
+
+/// Backoff before retry number `attempts` (1-based)
+fn retry_delay(base: Duration, attempts: u32) -> Duration {
+    let factor = 2u32.saturating_pow(attempts.saturating_sub(1));
+    base.saturating_mul(factor).min(MAX_RETRY_DELAY)
+}
+
+/// Size and SHA-256 of a file
+fn file_info(file: &ExtractFile) -> ExtractFileInfo {
+    ExtractFileInfo {
+        name: file.name.clone(),
+        bytes: file.data.len() as u64,
+        sha256: format!("{:x}", Sha256::digest(&file.data)),
+    }
+}
+
+/// Render rows as CSV with a header row, or as a JSON array of objects
+pub fn render_extract(columns: &[&str], rows: &[Vec<Value>], format: ExtractFormat) -> Result<Vec<u8>> {
+    match format {
+        ExtractFormat::Csv => {
+            let csv_error = |e: csv::Error| WmsError::Export(format!("CSV write error: {}", e));
+            let mut writer = csv::Writer::from_writer(Vec::new());
+            writer.write_record(columns).map_err(csv_error)?;
+            for row in rows {
+                writer.write_record(row.iter().map(|value| match value {
+                    Value::Null => String::new(),
+                    Value::String(s) => s.clone(),
+                    other => other.to_string(),
+                })).map_err(csv_error)?;
+            }
+            writer.into_inner().map_err(|e| WmsError::Export(format!("CSV write error: {}", e)))
+        }
+        ExtractFormat::Json => {
+            let objects: Vec<Map<String, Value>> = rows.iter()
+                .map(|row| columns.iter().map(|c| c.to_string()).zip(row.iter().cloned()).collect())
+                .collect();
+            Ok(serde_json::to_vec_pretty(&objects)?)
+        }
+    }
+}
+
+/// Column names and query for a dataset. Queries take the customer id,
+/// then the lookback cutoff if the dataset is limited to recent records.
+fn dataset_query(dataset: ExtractDataset) -> (&'static [&'static str], &'static str) {
+    match dataset {
+        ExtractDataset::Shipments => (
+            &[
+                "shipment_number", "order_reference", "status", "carrier", "service_type",
+                "tracking_number", "ship_date", "expected_delivery_date", "actual_delivery_date",
+                "ship_to_name", "ship_to_city", "ship_to_state", "total_packages", "total_weight_kg",
+                "created_at",
+            ],
+            "SELECT s.shipment_number, s.order_reference, s.status, c.name, s.service_type,
+                    s.tracking_number, s.ship_date, s.expected_delivery_date, s.actual_delivery_date,
+                    s.ship_to_name, s.ship_to_city, s.ship_to_state, s.total_packages, s.total_weight_kg,
+                    s.created_at
+             FROM shipments s
+             LEFT JOIN carriers c ON c.id = s.carrier_id
+             WHERE s.customer_id = ?1 AND s.created_at >= ?2
+             ORDER BY s.created_at, s.shipment_number",
+        ),
+        ExtractDataset::Deliveries => (
+            &[
+                "delivery_number", "status", "scheduled_date", "time_window_start", "time_window_end",
+                "actual_arrival_time", "actual_departure_time", "signature_name", "failure_reason",
+            ],
+            "SELECT delivery_number, status, scheduled_date, scheduled_time_window_start,
+                    scheduled_time_window_end, actual_arrival_time, actual_departure_time,
+                    signature_name, failure_reason
+             FROM deliveries
+             WHERE customer_id = ?1 AND scheduled_date >= ?2
+             ORDER BY scheduled_date, delivery_number",
+        ),
+        ExtractDataset::Stock => (
+            &["sku", "name", "location", "lot_number", "expiry_date", "quantity", "unit_of_measure"],
+            "SELECT i.sku, i.name, l.code, st.lot_number, st.expiry_date, st.quantity, i.unit_of_measure
+             FROM inventory_stock st
+             JOIN inventory_items i ON i.id = st.item_id
+             JOIN locations l ON l.id = st.location_id
+             WHERE i.consignment_customer_id = ?1 AND st.quantity > 0
+             ORDER BY i.sku, l.code, st.lot_number",
+        ),
+    }
+}
+
+fn sql_to_json(value: rusqlite::types::Value) -> Value {
+    use rusqlite::types::Value as Sql;
+    match value {
+        Sql::Integer(n) => Value::from(n),
+        Sql::Real(n) => Value::from(n),
+        Sql::Text(s) => Value::String(s),
+        Sql::Null | Sql::Blob(_) => Value::Null,
+    }
+}
+
+/// Extract definitions, the scheduled runner and run history
+pub struct ExtractService {
+    db: Arc<Database>,
+    clock: SharedClock,
+    events: SharedEmitter,
+    sftp: Option<SharedSftpTransport>,
+    max_attempts: u32,
+    retry_delay: Duration,
+}
+
+impl ExtractService {
+    /// Create a service that can deliver to local folders only
+    pub fn new(db: Arc<Database>) -> Self {
+        Self {
+            db,
+            clock: system_clock(),
+            events: noop_emitter(),
+            sftp: None,
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            retry_delay: DEFAULT_RETRY_DELAY,
+        }
+    }
+
+    /// Read the current time (schedules, retries) from `clock`
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Alert on failed extracts through the given emitter
+    pub fn with_event_emitter(mut self, events: SharedEmitter) -> Self {
+        self.events = events;
+        self
+    }
+
+    /// Upload SFTP extracts through `transport`
+    pub fn with_sftp_transport(mut self, transport: SharedSftpTransport) -> Self {
+        self.sftp = Some(transport);
+        self
+    }
+
+    /// Set how many attempts a scheduled run gets and the first retry delay
+    pub fn with_retry_policy(mut self, max_attempts: u32, retry_delay: Duration) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self.retry_delay = retry_delay;
+        self
+    }
+
+    // ============ Definition Operations ============
+
+    /// Extract definitions, optionally for one customer
+    pub async fn list_definitions(&self, customer_id: Option<&str>) -> Result<Vec<ExtractDefinition>> {
+        self.db.query_map(
+            "SELECT * FROM extract_definitions
+             WHERE (?1 IS NULL OR customer_id = ?1)
+             ORDER BY name",
+            params![customer_id],
+            Self::row_to_definition,
+        )
+    }
+
+    /// Get a definition by ID
+    pub async fn get_definition(&self, id: &str) -> Result<ExtractDefinition> {
+        self.db.query_row(
+            "SELECT * FROM extract_definitions WHERE id = ?",
+            params![id],
+            Self::row_to_definition,
+        )?
+        .ok_or_else(|| WmsError::not_found(format!("Extract {} not found", id)))
+    }
+
+    /// Create a definition (empty `id`) or replace one. Saving schedules the
+    /// next run and clears any pending retry. Leaving the SFTP password out
+    /// keeps the stored one.
+    pub async fn save_definition(&self, definition: ExtractDefinition) -> Result<ExtractDefinition> {
+        let name = definition.name.trim();
+        if name.is_empty() {
+            return Err(WmsError::validation("Extract name is required"));
+        }
+        let customer: Option<i64> = self.db.query_row(
+            "SELECT 1 FROM customers WHERE id = ?",
+            params![&definition.customer_id],
+            |row| row.get(0),
+        )?;
+        if customer.is_none() {
+            return Err(WmsError::not_found(format!("Customer {} not found", definition.customer_id)));
+        }
+        let schedule = Schedule::parse(&definition.schedule)?;
+
+        let (local_path, sftp) = match &definition.destination {
+            ExtractDestination::Local { path } => {
+                if path.trim().is_empty() {
+                    return Err(WmsError::validation("Choose a folder for the extract"));
+                }
+                (Some(path.trim().to_string()), None)
+            }
+            ExtractDestination::Sftp(sftp) => {
+                if sftp.host.trim().is_empty() || sftp.username.trim().is_empty() {
+                    return Err(WmsError::validation("SFTP host and username are required"));
+                }
+                (None, Some(sftp))
+            }
+        };
+        let password = sftp.and_then(|s| s.password.as_deref()).filter(|p| !p.is_empty());
+
+        let now = self.clock.now();
+        let next_run_at = schedule.next_after(now).map(|t| t.to_rfc3339());
+        let id = if definition.id.is_empty() { new_id() } else { definition.id.clone() };
+
+        self.db.execute(
+            "INSERT INTO extract_definitions (
+                id, customer_id, name, dataset, format, schedule, destination_type, local_path,
+                sftp_host, sftp_port, sftp_username, sftp_password, sftp_directory, sftp_host_key,
+                is_active, next_run_at, failed_attempts, last_error, created_at
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, 0, NULL, ?17)
+            ON CONFLICT(id) DO UPDATE SET
+                customer_id = excluded.customer_id,
+                name = excluded.name,
+                dataset = excluded.dataset,
+                format = excluded.format,
+                schedule = excluded.schedule,
+                destination_type = excluded.destination_type,
+                local_path = excluded.local_path,
+                sftp_host = excluded.sftp_host,
+                sftp_port = excluded.sftp_port,
+                sftp_username = excluded.sftp_username,
+                sftp_password = CASE
+                    WHEN excluded.destination_type != 'sftp' THEN NULL
+                    ELSE COALESCE(excluded.sftp_password, sftp_password)
+                END,
+                sftp_directory = excluded.sftp_directory,
+                sftp_host_key = excluded.sftp_host_key,
+                is_active = excluded.is_active,
+                next_run_at = excluded.next_run_at,
+                failed_attempts = 0,
+                last_error = NULL,
+                updated_at = excluded.created_at",
+            params![
+                &id,
+                &definition.customer_id,
+                name,
+                definition.dataset.as_str(),
+                definition.format.as_str(),
+                definition.schedule.trim(),
+                definition.destination.type_str(),
+                &local_path,
+                sftp.map(|s| s.host.trim()),
+                sftp.map(|s| s.port),
+                sftp.map(|s| s.username.trim()),
+                password,
+                sftp.map(|s| s.directory.trim()),
+                sftp.and_then(|s| s.host_key_sha256.as_deref()).map(str::trim).filter(|k| !k.is_empty()),
+                definition.is_active,
+                &next_run_at,
+                now.to_rfc3339(),
+            ],
+        )?;
+
+        info!("Extract '{}' for customer {} scheduled {}", name, definition.customer_id, definition.schedule);
+        self.get_definition(&id).await
+    }
+
+    /// Delete a definition and its run history
+    pub async fn delete_definition(&self, id: &str) -> Result<()> {
+        let rows = self.db.execute("DELETE FROM extract_definitions WHERE id = ?", params![id])?;
+        if rows == 0 {
+            return Err(WmsError::not_found(format!("Extract {} not found", id)));
+        }
+        Ok(())
+    }
+
+    // ============ Run Operations ============
+
+    /// Run an extract now, outside its schedule. The outcome is recorded
+    /// but does not change the schedule or trigger retries.
+    pub async fn run_now(&self, id: &str) -> Result<ExtractRun> {
+        let definition = self.get_definition(id).await?;
+        self.execute(&definition, ExtractTrigger::Manual, 1).await
+    }
+
+    /// Runs of an extract, newest first
+    pub async fn get_run_history(&self, definition_id: &str, limit: u32) -> Result<Vec<ExtractRun>> {
+        self.db.query_map(
+            "SELECT * FROM extract_runs WHERE definition_id = ?
+             ORDER BY started_at DESC, rowid DESC
+             LIMIT ?",
+            params![definition_id, limit],
+            Self::row_to_run,
+        )
+    }
+
+    /// Run every active extract that is due; returns the runs attempted
+    pub async fn run_due(&self) -> Result<usize> {
+        let now = self.clock.now();
+        let due = self.db.query_map(
+            "SELECT * FROM extract_definitions
+             WHERE is_active = 1 AND next_run_at IS NOT NULL AND next_run_at <= ?
+             ORDER BY next_run_at",
+            params![now.to_rfc3339()],
+            Self::row_to_definition,
+        )?;
+
+        for definition in &due {
+            let attempt = definition.failed_attempts + 1;
+            let trigger = if attempt > 1 { ExtractTrigger::Retry } else { ExtractTrigger::Schedule };
+            let run = self.execute(definition, trigger, attempt).await?;
+            let next_scheduled = Schedule::parse(&definition.schedule)
+                .ok()
+                .and_then(|schedule| schedule.next_after(now))
+                .map(|t| t.to_rfc3339());
+
+            let (failed_attempts, next_run_at) = match &run.error {
+                None => (0, next_scheduled),
+                Some(error) if attempt < self.max_attempts => {
+                    let delay = retry_delay(self.retry_delay, attempt);
+                    let next = now + chrono::Duration::from_std(delay).unwrap_or(chrono::Duration::zero());
+                    debug!("Extract {} attempt {} failed, retrying at {}: {}", definition.name, attempt, next, error);
+                    (attempt, Some(next.to_rfc3339()))
+                }
+                Some(error) => {
+                    warn!("Extract {} failed after {} attempts: {}", definition.name, attempt, error);
+                    self.events.emit(WmsEvent::ExtractFailed {
+                        definition_id: definition.id.clone(),
+                        customer_id: definition.customer_id.clone(),
+                        name: definition.name.clone(),
+                        attempts: attempt,
+                        error: error.clone(),
+                    });
+                    (0, next_scheduled)
+                }
+            };
+
+            self.db.execute(
+                "UPDATE extract_definitions SET failed_attempts = ?, last_error = ?, next_run_at = ?
+                 WHERE id = ?",
+                params![failed_attempts, &run.error, &next_run_at, &definition.id],
+            )?;
+        }
+
+        Ok(due.len())
+    }
+
+    /// Run due extracts on an interval until shutdown; a run in progress
+    /// finishes first
+    pub async fn run_scheduler(self: Arc<Self>, interval: Duration, task: TaskHandle) {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {}
+                _ = task.shutdown_requested() => break,
+            }
+            if let Err(e) = self.run_due().await {
+                warn!("Extract pass failed: {}", e);
+            }
+        }
+        info!("Extract scheduler stopped");
+    }
+
+    // ============ Helper Methods ============
+
+    /// Generate and deliver an extract, recording the attempt
+    async fn execute(&self, definition: &ExtractDefinition, trigger: ExtractTrigger, attempt: u32) -> Result<ExtractRun> {
+        let started_at = self.clock.now();
+        let outcome = match self.generate(definition, started_at) {
+            Ok((row_count, files)) => self.deliver(definition, &files).await.map(|()| {
+                // The manifest is last; the history lists the data files
+                let data_files = &files[..files.len() - 1];
+                (row_count, data_files.iter().map(file_info).collect())
+            }),
+            Err(e) => Err(e),
+        };
+
+        let (row_count, files, error) = match outcome {
+            Ok((row_count, files)) => (row_count, files, None),
+            Err(e) => (0, Vec::new(), Some(e.to_string())),
+        };
+        let run = ExtractRun {
+            id: new_id(),
+            definition_id: definition.id.clone(),
+            trigger,
+            succeeded: error.is_none(),
+            attempt,
+            row_count,
+            files,
+            error,
+            started_at,
+            finished_at: self.clock.now(),
+        };
+
+        self.db.execute(
+            "INSERT INTO extract_runs (
+                id, definition_id, trigger_type, status, attempt, row_count, files, error,
+                started_at, finished_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            params![
+                &run.id,
+                &run.definition_id,
+                run.trigger.as_str(),
+                if run.succeeded { "succeeded" } else { "failed" },
+                run.attempt,
+                run.row_count as i64,
+                serde_json::to_string(&run.files)?,
+                &run.error,
+                run.started_at.to_rfc3339(),
+                run.finished_at.to_rfc3339(),
+            ],
+        )?;
+
+        if run.succeeded {
+            info!("Extract {} delivered {} rows", definition.name, run.row_count);
+        }
+        Ok(run)
+    }
+
+    /// Build the data file and its manifest (last)
+    fn generate(&self, definition: &ExtractDefinition, now: DateTime<Utc>) -> Result<(usize, Vec<ExtractFile>)> {
+        let customer_number: String = self.db.query_row(
+            "SELECT customer_number FROM customers WHERE id = ?",
+            params![&definition.customer_id],
+            |row| row.get(0),
+        )?
+        .ok_or_else(|| WmsError::not_found(format!("Customer {} not found", definition.customer_id)))?;
+
+        let (columns, sql) = dataset_query(definition.dataset);
+        let mut args = vec![definition.customer_id.clone()];
+        if definition.dataset != ExtractDataset::Stock {
+            args.push((now - chrono::Duration::days(EXTRACT_LOOKBACK_DAYS)).to_rfc3339());
+        }
+        let rows: Vec<Vec<Value>> = self.db.query_map(
+            sql,
+            params_from_iter(args.iter()),
+            |row| (0..columns.len())
+                .map(|i| row.get::<_, rusqlite::types::Value>(i).map(sql_to_json))
+                .collect(),
+        )?;
+
+        let base_name = format!(
+            "{}_{}_{}",
+            customer_number,
+            definition.dataset.as_str(),
+            now.format("%Y%m%dT%H%M%SZ")
+        );
+        let data = ExtractFile {
+            name: format!("{}.{}", base_name, definition.format.as_str()),
+            data: render_extract(columns, &rows, definition.format)?,
+        };
+        let manifest = ExtractManifest {
+            extract_id: definition.id.clone(),
+            customer_number,
+            dataset: definition.dataset,
+            format: definition.format,
+            generated_at: now,
+            row_count: rows.len(),
+            files: vec![file_info(&data)],
+        };
+        let manifest = ExtractFile {
+            name: format!("{}.manifest.json", base_name),
+            data: serde_json::to_vec_pretty(&manifest)?,
+        };
+
+        Ok((rows.len(), vec![data, manifest]))
+    }
+
+    async fn deliver(&self, definition: &ExtractDefinition, files: &[ExtractFile]) -> Result<()> {
+        let files = files.to_vec();
+        let delivered = match &definition.destination {
+            ExtractDestination::Local { path } => {
+                let folder = std::path::PathBuf::from(path);
+                tokio::task::spawn_blocking(move || write_local(&folder, &files)).await
+            }
+            ExtractDestination::Sftp(destination) => {
+                let transport = self.sftp.clone().ok_or_else(|| {
+                    WmsError::validation("SFTP delivery is not available in this build")
+                })?;
+                let destination = destination.clone();
+                tokio::task::spawn_blocking(move || transport.upload(&destination, &files)).await
+            }
+        };
+        delivered.map_err(|e| WmsError::Unknown(e.to_string()))?
+    }
+
+    fn row_to_definition(row: &rusqlite::Row) -> rusqlite::Result<ExtractDefinition> {
+        let destination_type: String = row.get("destination_type")?;
+        let destination = match destination_type.as_str() {
+            "sftp" => ExtractDestination::Sftp(SftpDestination {
+                host: row.get::<_, Option<String>>("sftp_host")?.unwrap_or_default(),
+                port: row.get::<_, Option<u16>>("sftp_port")?.unwrap_or(DEFAULT_SFTP_PORT),
+                username: row.get::<_, Option<String>>("sftp_username")?.unwrap_or_default(),
+                password: row.get("sftp_password")?,
+                directory: row.get::<_, Option<String>>("sftp_directory")?.unwrap_or_default(),
+                host_key_sha256: row.get("sftp_host_key")?,
+            }),
+            "local" => ExtractDestination::Local {
+                path: row.get::<_, Option<String>>("local_path")?.unwrap_or_default(),
+            },
+            _ => return Err(unreadable(row, "destination_type", &destination_type)),
+        };
+
+        Ok(ExtractDefinition {
+            id: row.get("id")?,
+            customer_id: row.get("customer_id")?,
+            name: row.get("name")?,
+            dataset: stored_status(row, "dataset", ExtractDataset::parse)?,
+            format: stored_status(row, "format", ExtractFormat::parse)?,
+            schedule: row.get("schedule")?,
+            destination,
+            is_active: row.get::<_, i32>("is_active")? == 1,
+            next_run_at: stored_timestamp(row, "next_run_at")?,
+            failed_attempts: row.get("failed_attempts")?,
+            last_error: row.get("last_error")?,
+        })
+    }
+
+    fn row_to_run(row: &rusqlite::Row) -> rusqlite::Result<ExtractRun> {
+        let files: Option<String> = row.get("files")?;
+
+        Ok(ExtractRun {
+            id: row.get("id")?,
+            definition_id: row.get("definition_id")?,
+            trigger: stored_status(row, "trigger_type", ExtractTrigger::parse)?,
+            succeeded: row.get::<_, String>("status")? == "succeeded",
+            attempt: row.get("attempt")?,
+            row_count: row.get::<_, i64>("row_count")? as usize,
+            files: files.as_deref().and_then(|f| serde_json::from_str(f).ok()).unwrap_or_default(),
+            error: row.get("error")?,
+            started_at: required_timestamp(row, "started_at")?,
+            finished_at: required_timestamp(row, "finished_at")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::sync::Mutex;
+    use wms_core::events::RecordingEmitter;
+    use wms_core::test_support::{CustomerBuilder, DeliveryBuilder, ShipmentBuilder, fixed_clock, test_db};
+
+    /// Fails the first `failures` uploads, then records what it was sent
+    struct MockSftp {
+        failures: Mutex<u32>,
+        uploads: Mutex<Vec<Vec<String>>>,
+    }
+
+    impl MockSftp {
+        fn failing(failures: u32) -> Arc<Self> {
+            Arc::new(Self { failures: Mutex::new(failures), uploads: Mutex::new(Vec::new()) })
+        }
+    }
+
+    impl SftpTransport for MockSftp {
+        fn upload(&self, _destination: &SftpDestination, files: &[ExtractFile]) -> Result<()> {
+            let mut failures = self.failures.lock().unwrap();
+            if *failures > 0 {
+                *failures -= 1;
+                return Err(WmsError::Network("connection refused".to_string()));
+            }
+            self.uploads.lock().unwrap().push(files.iter().map(|f| f.name.clone()).collect());
+            Ok(())
+        }
+    }
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("wms-extract-test-{}", new_id()))
+    }
+
+    fn definition(dataset: ExtractDataset, format: ExtractFormat, destination: ExtractDestination) -> ExtractDefinition {
+        ExtractDefinition {
+            id: String::new(),
+            customer_id: "cust-1".to_string(),
+            name: "Nightly".to_string(),
+            dataset,
+            format,
+            schedule: "0 2 * * *".to_string(),
+            destination,
+            is_active: true,
+            next_run_at: None,
+            failed_attempts: 0,
+            last_error: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_now_writes_data_and_manifest() {
+        let db = test_db();
+        CustomerBuilder::new("CUST-1").insert::<Value>(&db);
+        CustomerBuilder::new("CUST-2").insert::<Value>(&db);
+        ShipmentBuilder::new("SHP-1").customer("cust-1").insert::<Value>(&db);
+        ShipmentBuilder::new("SHP-2").customer("cust-2").insert::<Value>(&db);
+        let service = ExtractService::new(db).with_clock(fixed_clock());
+        let dir = temp_dir();
+
+        let saved = service.save_definition(definition(
+            ExtractDataset::Shipments,
+            ExtractFormat::Csv,
+            ExtractDestination::Local { path: dir.to_string_lossy().to_string() },
+        )).await.unwrap();
+        assert_eq!(saved.next_run_at.unwrap().to_rfc3339(), "2025-01-16T02:00:00+00:00");
+
+        let run = service.run_now(&saved.id).await.unwrap();
+        assert!(run.succeeded, "{:?}", run.error);
+        assert_eq!(run.trigger, ExtractTrigger::Manual);
+        assert_eq!(run.row_count, 1);
+
+        let data_name = "CUST-1_shipments_20250115T090000Z.csv";
+        let data = std::fs::read(dir.join(data_name)).unwrap();
+        assert!(String::from_utf8_lossy(&data).contains("SHP-1"));
+        assert!(!String::from_utf8_lossy(&data).contains("SHP-2"));
+        let manifest: ExtractManifest = serde_json::from_slice(
+            &std::fs::read(dir.join("CUST-1_shipments_20250115T090000Z.manifest.json")).unwrap(),
+        ).unwrap();
+        assert_eq!(manifest.row_count, 1);
+        assert_eq!(manifest.files, run.files);
+        assert_eq!(manifest.files[0].sha256, format!("{:x}", Sha256::digest(&data)));
+
+        let history = service.get_run_history(&saved.id, 10).await.unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].files, run.files);
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_failed_upload_retries_with_backoff_then_alerts() {
+        let db = test_db();
+        CustomerBuilder::new("CUST-1").insert::<Value>(&db);
+        DeliveryBuilder::new("DEL-1").customer("cust-1").insert::<Value>(&db);
+        let clock = fixed_clock();
+        let events = Arc::new(RecordingEmitter::new());
+        let sftp = MockSftp::failing(1);
+        let service = ExtractService::new(db)
+            .with_clock(clock.clone())
+            .with_event_emitter(events.clone())
+            .with_sftp_transport(sftp.clone())
+            .with_retry_policy(2, Duration::from_secs(60));
+
+        let saved = service.save_definition(definition(
+            ExtractDataset::Deliveries,
+            ExtractFormat::Json,
+            ExtractDestination::Sftp(SftpDestination {
+                host: "sftp.acme.example".to_string(),
+                port: DEFAULT_SFTP_PORT,
+                username: "acme".to_string(),
+                password: Some("secret".to_string()),
+                directory: "inbound".to_string(),
+                host_key_sha256: None,
+            }),
+        )).await.unwrap();
+        assert!(!serde_json::to_string(&saved).unwrap().contains("secret"));
+        assert_eq!(service.run_due().await.unwrap(), 0);
+
+        // 02:00 fails, the retry a minute later goes through
+        clock.advance(chrono::Duration::hours(17));
+        assert_eq!(service.run_due().await.unwrap(), 1);
+        let pending = service.get_definition(&saved.id).await.unwrap();
+        assert_eq!(pending.failed_attempts, 1);
+        assert_eq!(pending.next_run_at.unwrap().to_rfc3339(), "2025-01-16T02:01:00+00:00");
+        assert_eq!(service.run_due().await.unwrap(), 0);
+
+        clock.advance(chrono::Duration::minutes(1));
+        assert_eq!(service.run_due().await.unwrap(), 1);
+        assert_eq!(
+            sftp.uploads.lock().unwrap().clone(),
+            vec![vec![
+                "CUST-1_deliveries_20250116T020100Z.json".to_string(),
+                "CUST-1_deliveries_20250116T020100Z.manifest.json".to_string(),
+            ]],
+        );
+        let done = service.get_definition(&saved.id).await.unwrap();
+        assert_eq!(done.failed_attempts, 0);
+        assert_eq!(done.next_run_at.unwrap().to_rfc3339(), "2025-01-17T02:00:00+00:00");
+        assert!(events.events().is_empty());
+
+        // Out of attempts: alert and wait for the next night
+        *sftp.failures.lock().unwrap() = 5;
+        clock.advance(chrono::Duration::minutes(23 * 60 + 59));
+        service.run_due().await.unwrap();
+        clock.advance(chrono::Duration::minutes(1));
+        service.run_due().await.unwrap();
+        assert!(matches!(
+            events.events().as_slice(),
+            [WmsEvent::ExtractFailed { attempts: 2, .. }]
+        ));
+        let failed = service.get_definition(&saved.id).await.unwrap();
+        assert_eq!(failed.failed_attempts, 0);
+        assert_eq!(failed.last_error.as_deref(), Some("Network error: connection refused"));
+        assert_eq!(failed.next_run_at.unwrap().to_rfc3339(), "2025-01-18T02:00:00+00:00");
+
+        let history = service.get_run_history(&saved.id, 10).await.unwrap();
+        let outcomes: Vec<(ExtractTrigger, bool, usize)> = history.iter()
+            .map(|run| (run.trigger, run.succeeded, run.row_count))
+            .collect();
+        assert_eq!(outcomes, vec![
+            (ExtractTrigger::Retry, false, 0),
+            (ExtractTrigger::Schedule, false, 0),
+            (ExtractTrigger::Retry, true, 1),
+            (ExtractTrigger::Schedule, false, 0),
+        ]);
+    }
+}
@@ -2,16 +2,40 @@
 //! 
 //! Provides customer relationship management functionality including:
 //! - Customer master data management
+//! - Customer deactivation and merging of duplicates
 //! - Contact management
 //! - Address management
+//! - Customer shipping requirements (routing guides)
 //! - Data validation (email, phone)
 //! - Customer search and filtering
+//! - Customer activity timeline and order totals
+//! - Customer statements (PDF and CSV)
+//! - Customer list CSV export
+//! - Scheduled customer data extracts (local folder or SFTP)
 
+mod activity;
+mod export;
+mod extracts;
 mod models;
+mod schedule;
 mod service;
+mod statement;
 mod validation;
 
+pub use activity::{
+    build_timeline, order_stats, ActivityEvent, ActivityKind, CustomerStats, DEFAULT_ACTIVITY_LIMIT,
+};
+pub use export::render_customers_csv;
+pub use extracts::{
+    render_extract, ExtractDataset, ExtractDefinition, ExtractDestination, ExtractFile,
+    ExtractFileInfo, ExtractFormat, ExtractManifest, ExtractRun, ExtractService, ExtractTrigger,
+    SftpDestination, SftpTransport, SharedSftpTransport, DEFAULT_SFTP_PORT, EXTRACT_LOOKBACK_DAYS,
+};
+#[cfg(feature = "sftp")]
+pub use extracts::Ssh2SftpTransport;
 pub use models::*;
+pub use schedule::Schedule;
 pub use service::CrmService;
+pub use statement::*;
 pub use validation::*;
 
@@ -1,8 +1,9 @@
 //! CRM Data Models
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
 use validator::Validate;
+use wms_core::custom_fields::CustomFieldValues;
 use wms_core::types::Address;
 
 /// Customer record
@@ -47,9 +48,21 @@ pub struct Customer {
     pub tags: Vec<String>,
     #[serde(default = "default_true")]
     pub is_active: bool,
+    /// Customer this duplicate was merged into
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub merged_into: Option<String>,
+    /// Customer asked not to get delivery texts or emails
+    #[serde(default)]
+    pub notifications_opt_out: bool,
+    /// Values of the deployment's custom customer fields
+    #[serde(default, skip_serializing_if = "CustomFieldValues::is_empty")]
+    pub custom_fields: CustomFieldValues,
     pub created_at: DateTime<Utc>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub updated_at: Option<DateTime<Utc>>,
+    /// Row version; edits must send back the version they were based on
+    #[serde(default)]
+    pub version: i64,
     /// Associated addresses
     #[serde(default)]
     pub addresses: Vec<CustomerAddress>,
@@ -58,6 +71,23 @@ pub struct Customer {
     pub contacts: Vec<CustomerContact>,
 }
 
+impl Customer {
+    /// Company name, falling back to the person's name or customer number
+    pub fn display_name(&self) -> String {
+        if let Some(company) = self.company_name.as_ref().filter(|c| !c.is_empty()) {
+            return company.clone();
+        }
+
+        let name = [self.first_name.as_deref(), self.last_name.as_deref()]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        if name.is_empty() { self.customer_number.clone() } else { name }
+    }
+}
+
 fn default_currency() -> String {
     "USD".to_string()
 }
@@ -78,8 +108,31 @@ pub enum CustomerType {
     Internal,
 }
 
+impl CustomerType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Retail => "retail",
+            Self::Wholesale => "wholesale",
+            Self::Distributor => "distributor",
+            Self::Government => "government",
+            Self::Internal => "internal",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "retail" => Some(Self::Retail),
+            "wholesale" => Some(Self::Wholesale),
+            "distributor" => Some(Self::Distributor),
+            "government" => Some(Self::Government),
+            "internal" => Some(Self::Internal),
+            _ => None,
+        }
+    }
+}
+
 /// Customer address
-#[derive(Debug, Clone, Serialize, Deserialize, Validate)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Validate)]
 pub struct CustomerAddress {
     pub id: String,
     pub customer_id: String,
@@ -112,8 +165,32 @@ pub enum AddressType {
     Both,
 }
 
+impl AddressType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Shipping => "shipping",
+            Self::Billing => "billing",
+            Self::Both => "both",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "shipping" => Some(Self::Shipping),
+            "billing" => Some(Self::Billing),
+            "both" => Some(Self::Both),
+            _ => None,
+        }
+    }
+
+    /// Whether invoices can be sent to an address of this type
+    pub fn is_billing(&self) -> bool {
+        matches!(self, Self::Billing | Self::Both)
+    }
+}
+
 /// Customer contact person
-#[derive(Debug, Clone, Serialize, Deserialize, Validate)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Validate)]
 pub struct CustomerContact {
     pub id: String,
     pub customer_id: String,
@@ -178,27 +255,117 @@ pub enum InteractionType {
     Support,
 }
 
-/// Customer search query
+/// Routing guide a customer imposes on shipments to them. Checked when a
+/// shipment is confirmed; see `ShippingService::check_shipment_compliance`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ShippingRequirements {
+    pub customer_id: String,
+    /// Carrier every shipment must use
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub required_carrier_id: Option<String>,
+    /// Carrier service level, e.g. "GROUND"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub required_service: Option<String>,
+    /// Whether an order may ship in more than one shipment
+    #[serde(default = "default_true")]
+    pub allow_partial: bool,
+    /// Deliveries must be booked with the consignee
+    #[serde(default)]
+    pub appointment_required: bool,
+    /// Labelling instructions shown to packers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label_notes: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+/// Customer search query. Every filter that is set must match.
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct CustomerSearchQuery {
+    /// Text matched against names, email, customer number and custom fields
     #[serde(skip_serializing_if = "Option::is_none")]
     pub query: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub customer_type: Option<CustomerType>,
+    /// City of any of the customer's addresses
     #[serde(skip_serializing_if = "Option::is_none")]
     pub city: Option<String>,
+    /// State of any of the customer's addresses
     #[serde(skip_serializing_if = "Option::is_none")]
     pub state: Option<String>,
+    /// Tags the customer must all have
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tags: Option<Vec<String>>,
+    /// Active or inactive customers only; both when not set
     #[serde(skip_serializing_if = "Option::is_none")]
     pub is_active: Option<bool>,
+    /// First day of the created date range
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_from: Option<NaiveDate>,
+    /// Last day of the created date range
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_to: Option<NaiveDate>,
+    #[serde(default)]
+    pub sort_by: CustomerSortField,
+    #[serde(default)]
+    pub sort_dir: SortDirection,
     #[serde(default = "default_page")]
     pub page: u32,
     #[serde(default = "default_page_size")]
     pub page_size: u32,
 }
 
+/// Orders customer search results can be sorted in
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum CustomerSortField {
+    /// Company name, then the person's name
+    #[default]
+    Name,
+    CustomerNumber,
+    Email,
+    CreatedAt,
+}
+
+impl CustomerSortField {
+    /// Columns of the `customers` table sorted on, most significant first
+    pub fn columns(&self) -> &'static [&'static str] {
+        match self {
+            Self::Name => &["company_name", "last_name", "first_name"],
+            Self::CustomerNumber => &["customer_number"],
+            Self::Email => &["email"],
+            Self::CreatedAt => &["created_at"],
+        }
+    }
+}
+
+/// Sort direction
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SortDirection {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl SortDirection {
+    pub fn as_sql(&self) -> &'static str {
+        match self {
+            Self::Asc => "ASC",
+            Self::Desc => "DESC",
+        }
+    }
+}
+
+/// One page of search results with the number of matches across all pages
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchResult<T> {
+    pub items: Vec<T>,
+    pub total_count: u64,
+    pub page: u32,
+    pub page_size: u32,
+}
+
 fn default_page() -> u32 {
     1
 }
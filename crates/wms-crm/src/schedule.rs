@@ -0,0 +1,189 @@
+//! Extract Schedules
+//!
+//! Cron-style schedules for customer extracts: five fields (minute, hour,
+//! day of month, month, day of week) evaluated in UTC. Fields take `*`,
+//! numbers, ranges (`1-5`), lists (`1,15`) and steps (`*/15`, `0-30/10`).
+//! `@hourly`, `@daily`, `@nightly`, `@weekly` and `@monthly` are accepted
+//! as shorthands. As in cron, when both day of month and day of week are
+//! restricted a day matching either one runs.
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Timelike, Utc};
+use wms_core::error::{WmsError, Result};
+
+/// Days searched for the next run before giving up (covers Feb 29)
+const SEARCH_DAYS: u32 = 366 * 4 + 1;
+
+/// A parsed schedule
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schedule {
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    /// Day of month was `*`
+    any_day_of_month: bool,
+    /// Day of week was `*`
+    any_day_of_week: bool,
+}
+
+impl Schedule {
+    /// Parse a cron expression or shorthand
+    pub fn parse(expression: &str) -> Result<Self> {
+        let expression = expression.trim();
+        let expanded = match expression.to_ascii_lowercase().as_str() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@nightly" => "0 2 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            _ => expression,
+        };
+
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        let [minute, hour, day_of_month, month, day_of_week] = fields[..] else {
+            return Err(WmsError::validation(format!(
+                "Schedule '{}' needs five fields: minute hour day-of-month month day-of-week",
+                expression
+            )));
+        };
+
+        // Sunday may be written as 0 or 7
+        let mut days_of_week = parse_field(day_of_week, 0, 7, "day of week")?;
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week = (days_of_week & !(1 << 7)) | 1;
+        }
+
+        Ok(Self {
+            minutes: parse_field(minute, 0, 59, "minute")?,
+            hours: parse_field(hour, 0, 23, "hour")?,
+            days_of_month: parse_field(day_of_month, 1, 31, "day of month")?,
+            months: parse_field(month, 1, 12, "month")?,
+            days_of_week,
+            any_day_of_month: day_of_month == "*",
+            any_day_of_week: day_of_week == "*",
+        })
+    }
+
+    /// First run strictly after `after`, to the minute; None if the
+    /// schedule never fires (e.g. February 30th)
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let start = after.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let mut date = start.date_naive();
+
+        for _ in 0..SEARCH_DAYS {
+            if self.runs_on(date) {
+                let (first_hour, first_minute) = if date == start.date_naive() {
+                    (start.hour(), start.minute())
+                } else {
+                    (0, 0)
+                };
+                for hour in first_hour..24 {
+                    if !bit(self.hours, hour) {
+                        continue;
+                    }
+                    let from = if hour == first_hour { first_minute } else { 0 };
+                    if let Some(minute) = (from..60).find(|m| bit(self.minutes, *m)) {
+                        return date.and_hms_opt(hour, minute, 0).map(|dt| dt.and_utc());
+                    }
+                }
+            }
+            date = date.succ_opt()?;
+        }
+        None
+    }
+
+    fn runs_on(&self, date: NaiveDate) -> bool {
+        if !bit(self.months, date.month()) {
+            return false;
+        }
+        let day_of_month = bit(self.days_of_month, date.day());
+        let day_of_week = bit(self.days_of_week, date.weekday().num_days_from_sunday());
+        match (self.any_day_of_month, self.any_day_of_week) {
+            (false, false) => day_of_month || day_of_week,
+            _ => day_of_month && day_of_week,
+        }
+    }
+}
+
+fn bit(mask: u64, n: u32) -> bool {
+    mask & (1 << n) != 0
+}
+
+/// One field as a bitmask of the values it allows
+fn parse_field(field: &str, min: u32, max: u32, name: &str) -> Result<u64> {
+    let invalid = || WmsError::validation(format!("Invalid {} '{}' in schedule", name, field));
+    let number = |s: &str| -> Result<u32> {
+        s.parse::<u32>()
+            .ok()
+            .filter(|n| (min..=max).contains(n))
+            .ok_or_else(|| WmsError::validation(format!(
+                "{} {} is outside {}-{} in schedule", name, s, min, max
+            )))
+    };
+
+    let mut mask = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().ok().filter(|s| *s > 0).ok_or_else(invalid)?),
+            None => (part, 1),
+        };
+        let (from, to) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((from, to)) => (number(from)?, number(to)?),
+                // `5/15` means from 5 to the end in steps of 15
+                None if part.contains('/') => (number(range)?, max),
+                None => {
+                    let n = number(range)?;
+                    (n, n)
+                }
+            },
+        };
+        if from > to {
+            return Err(invalid());
+        }
+        for n in (from..=to).step_by(step as usize) {
+            mask |= 1 << n;
+        }
+    }
+    Ok(mask)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_next_run_after() {
+        let nightly = Schedule::parse("30 2 * * *").unwrap();
+        assert_eq!(nightly.next_after(at("2025-01-15T09:00:00Z")), Some(at("2025-01-16T02:30:00Z")));
+        assert_eq!(nightly.next_after(at("2025-01-15T02:29:59Z")), Some(at("2025-01-15T02:30:00Z")));
+        assert_eq!(nightly.next_after(at("2025-01-15T02:30:00Z")), Some(at("2025-01-16T02:30:00Z")));
+
+        // Saturday afternoon to Monday morning
+        let weekdays = Schedule::parse("*/15 6-18 * * 1-5").unwrap();
+        assert_eq!(weekdays.next_after(at("2025-01-18T15:00:00Z")), Some(at("2025-01-20T06:00:00Z")));
+        assert_eq!(weekdays.next_after(at("2025-01-20T06:07:00Z")), Some(at("2025-01-20T06:15:00Z")));
+
+        // Day of month or day of week
+        let either = Schedule::parse("0 0 1 * 7").unwrap();
+        assert_eq!(either.next_after(at("2025-01-15T09:00:00Z")), Some(at("2025-01-19T00:00:00Z")));
+
+        assert_eq!(Schedule::parse("@nightly").unwrap(), Schedule::parse("0 2 * * *").unwrap());
+        assert_eq!(Schedule::parse("0 0 30 2 *").unwrap().next_after(at("2025-01-15T09:00:00Z")), None);
+    }
+
+    #[test]
+    fn test_invalid_schedules_rejected() {
+        assert!(Schedule::parse("0 2 * *").is_err());
+        assert!(Schedule::parse("60 2 * * *").is_err());
+        assert!(Schedule::parse("0 2 * * mon").is_err());
+        assert!(Schedule::parse("*/0 * * * *").is_err());
+        assert!(Schedule::parse("0 5-2 * * *").is_err());
+    }
+}
@@ -3,25 +3,53 @@
 //! Core business logic for customer relationship management.
 
 use std::sync::Arc;
+use base64::Engine;
 use chrono::Utc;
-use rusqlite::params;
+use rusqlite::{params, params_from_iter};
 use tracing::{info, debug};
 use validator::Validate;
+use wms_core::changes::{outbox_recorder, ChangeKind, SharedRecorder};
+use wms_core::custom_fields::{
+    custom_fields_json, parse_custom_fields, search_condition, CustomFieldEntity, CustomFieldService,
+};
+use wms_core::credit::{check_credit, CreditCheck};
 use wms_core::db::Database;
 use wms_core::error::{WmsError, Result};
-use wms_core::types::new_id;
+use wms_core::sequences::DocumentSequence;
+use wms_core::types::{
+    new_id, required_date, required_timestamp, stored_date, stored_status, stored_timestamp,
+    unreadable, DateRange,
+};
+use crate::activity::*;
 use crate::models::*;
+use crate::export::render_customers_csv;
+use crate::statement::*;
 use crate::validation::validate_phone_number;
 
+/// Customers read per page when exporting
+const EXPORT_PAGE_SIZE: u32 = 500;
+
 /// CRM service
 pub struct CrmService {
     db: Arc<Database>,
+    custom_fields: CustomFieldService,
+    changes: SharedRecorder,
 }
 
 impl CrmService {
     /// Create a new CRM service
     pub fn new(db: Arc<Database>) -> Self {
-        Self { db }
+        Self {
+            custom_fields: CustomFieldService::new(db.clone()),
+            db,
+            changes: outbox_recorder(),
+        }
+    }
+    
+    /// Record changes for sync through the given recorder
+    pub fn with_change_recorder(mut self, changes: SharedRecorder) -> Self {
+        self.changes = changes;
+        self
     }
     
     /// Get all customers with pagination
@@ -39,6 +67,22 @@ impl CrmService {
         Ok(customers)
     }
     
+    /// Export every active customer as CSV
+    pub async fn export_customers_csv(&self) -> Result<Vec<u8>> {
+        let mut customers = Vec::new();
+        for page in 1.. {
+            let batch = self.get_customers(page, EXPORT_PAGE_SIZE).await?;
+            let done = batch.len() < EXPORT_PAGE_SIZE as usize;
+            customers.extend(batch);
+            if done {
+                break;
+            }
+        }
+        
+        let definitions = self.custom_fields.list_definitions(Some(CustomFieldEntity::Customer))?;
+        render_customers_csv(&customers, &definitions)
+    }
+    
     /// Get customer by ID
     pub async fn get_customer(&self, id: &str) -> Result<Option<Customer>> {
         let customer = self.db.query_row(
@@ -69,56 +113,63 @@ impl CrmService {
         if let Some(ref mobile) = customer.mobile {
             validate_phone_number(mobile)?;
         }
+        customer.custom_fields = self.custom_fields.validate(CustomFieldEntity::Customer, &customer.custom_fields)?;
         
         customer.id = new_id();
-        customer.customer_number = self.generate_customer_number()?;
         customer.created_at = Utc::now();
+        customer.version = 1;
         
         let tags_json = serde_json::to_string(&customer.tags).unwrap_or_default();
         
-        self.db.execute(
-            "INSERT INTO customers (
-                id, customer_number, company_name, first_name, last_name,
-                email, phone, mobile, fax, website, tax_id, customer_type,
-                credit_limit, payment_terms, currency_code, notes, tags,
-                is_active, created_at
-            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
-            params![
-                &customer.id,
-                &customer.customer_number,
-                &customer.company_name,
-                &customer.first_name,
-                &customer.last_name,
-                &customer.email,
-                &customer.phone,
-                &customer.mobile,
-                &customer.fax,
-                &customer.website,
-                &customer.tax_id,
-                format!("{:?}", customer.customer_type).to_lowercase(),
-                &customer.credit_limit,
-                &customer.payment_terms,
-                &customer.currency_code,
-                &customer.notes,
-                &tags_json,
-                customer.is_active,
-                customer.created_at.to_rfc3339(),
-            ],
-        )?;
-        
-        // Insert addresses
-        for mut addr in &mut customer.addresses {
-            addr.id = new_id();
-            addr.customer_id = customer.id.clone();
-            self.insert_address(&addr)?;
-        }
-        
-        // Insert contacts
-        for mut contact in &mut customer.contacts {
-            contact.id = new_id();
-            contact.customer_id = customer.id.clone();
-            self.insert_contact(&contact)?;
-        }
+        // The customer, its addresses and contacts and the change for sync
+        // are stored together or not at all
+        self.db.transaction(|conn| {
+            customer.customer_number = DocumentSequence::Customer.next_number_in(conn)?;
+            conn.execute(
+                "INSERT INTO customers (
+                    id, customer_number, company_name, first_name, last_name,
+                    email, phone, mobile, fax, website, tax_id, customer_type,
+                    credit_limit, payment_terms, currency_code, notes, tags,
+                    is_active, notifications_opt_out, custom_fields, created_at
+                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                params![
+                    &customer.id,
+                    &customer.customer_number,
+                    &customer.company_name,
+                    &customer.first_name,
+                    &customer.last_name,
+                    &customer.email,
+                    &customer.phone,
+                    &customer.mobile,
+                    &customer.fax,
+                    &customer.website,
+                    &customer.tax_id,
+                    customer.customer_type.as_str(),
+                    &customer.credit_limit,
+                    &customer.payment_terms,
+                    &customer.currency_code,
+                    &customer.notes,
+                    &tags_json,
+                    customer.is_active,
+                    customer.notifications_opt_out,
+                    custom_fields_json(&customer.custom_fields),
+                    customer.created_at.to_rfc3339(),
+                ],
+            )?;
+            
+            for addr in &mut customer.addresses {
+                addr.id = new_id();
+                addr.customer_id = customer.id.clone();
+                Self::insert_address(conn, addr)?;
+            }
+            
+            for contact in &mut customer.contacts {
+                contact.id = new_id();
+                contact.customer_id = customer.id.clone();
+                Self::insert_contact(conn, contact)?;
+            }
+            self.changes.record_model(conn, "customers", &customer.id, ChangeKind::Insert, &customer)
+        })?;
         
         info!("Created customer: {} - {:?}", 
               customer.customer_number, 
@@ -127,98 +178,644 @@ impl CrmService {
         Ok(customer)
     }
     
-    /// Update an existing customer
+    /// Update an existing customer. `customer.version` must be the version
+    /// the edit was based on; stale edits are rejected with the current record.
     pub async fn update_customer(&self, mut customer: Customer) -> Result<Customer> {
         // Validate
         customer.validate()
             .map_err(|e| WmsError::validation(format!("Invalid customer data: {}", e)))?;
+        customer.custom_fields = self.custom_fields.validate(CustomFieldEntity::Customer, &customer.custom_fields)?;
+        
+        // Addresses and contacts have their own methods; sending back the
+        // stored ones (or none) is fine, changing them here is not
+        let addresses = self.get_customer_addresses(&customer.id)?;
+        let contacts = self.get_customer_contacts(&customer.id)?;
+        if !customer.addresses.is_empty() && customer.addresses != addresses {
+            return Err(WmsError::validation(
+                "Addresses cannot be changed with a customer update; use the address methods",
+            ));
+        }
+        if !customer.contacts.is_empty() && customer.contacts != contacts {
+            return Err(WmsError::validation(
+                "Contacts cannot be changed with a customer update; use the contact methods",
+            ));
+        }
+        customer.addresses = addresses;
+        customer.contacts = contacts;
         
         customer.updated_at = Some(Utc::now());
-        let tags_json = serde_json::to_string(&customer.tags).unwrap_or_default();
         
-        let rows = self.db.execute(
-            "UPDATE customers SET
-                company_name = ?, first_name = ?, last_name = ?,
-                email = ?, phone = ?, mobile = ?, fax = ?, website = ?,
-                tax_id = ?, customer_type = ?, credit_limit = ?,
-                payment_terms = ?, currency_code = ?, notes = ?, tags = ?,
-                is_active = ?, updated_at = ?
-             WHERE id = ?",
-            params![
-                &customer.company_name,
-                &customer.first_name,
-                &customer.last_name,
-                &customer.email,
-                &customer.phone,
-                &customer.mobile,
-                &customer.fax,
-                &customer.website,
-                &customer.tax_id,
-                format!("{:?}", customer.customer_type).to_lowercase(),
-                &customer.credit_limit,
-                &customer.payment_terms,
-                &customer.currency_code,
-                &customer.notes,
-                &tags_json,
-                customer.is_active,
-                customer.updated_at.map(|t| t.to_rfc3339()),
-                &customer.id,
-            ],
-        )?;
+        // The edit and the change for sync are stored together
+        let rows = self.db.transaction(|conn| {
+            let rows = Self::update_customer_row(conn, &customer)?;
+            if rows > 0 {
+                customer.version += 1;
+                self.changes.record_model(conn, "customers", &customer.id, ChangeKind::Update, &customer)?;
+            }
+            Ok(rows)
+        })?;
         
         if rows == 0 {
-            return Err(WmsError::not_found("Customer not found"));
+            let current = self.get_customer(&customer.id).await?
+                .ok_or_else(|| WmsError::not_found("Customer not found"))?;
+            return Err(WmsError::version_conflict(
+                format!("Customer {} was changed by someone else (version {}, yours {})",
+                        current.customer_number, current.version, customer.version),
+                &current,
+            ));
         }
         
         debug!("Updated customer: {}", customer.customer_number);
         Ok(customer)
     }
     
-    /// Search customers by various criteria
-    pub async fn search_customers(&self, query: CustomerSearchQuery) -> Result<Vec<Customer>> {
+    /// Deactivate a customer. Nothing is deleted: its shipments, deliveries
+    /// and interactions stay attached, it just drops out of customer lists.
+    pub async fn deactivate_customer(&self, id: &str) -> Result<Customer> {
+        let mut customer = self.get_customer(id).await?
+            .ok_or_else(|| WmsError::not_found("Customer not found"))?;
+        if !customer.is_active {
+            return Ok(customer);
+        }
+        
+        customer.is_active = false;
+        customer.updated_at = Some(Utc::now());
+        customer.version += 1;
+        
+        self.db.transaction(|conn| {
+            conn.execute(
+                "UPDATE customers SET is_active = 0, updated_at = ?, version = version + 1 WHERE id = ?",
+                params![customer.updated_at.map(|t| t.to_rfc3339()), &customer.id],
+            )?;
+            self.changes.record_model(conn, "customers", &customer.id, ChangeKind::Update, &customer)
+        })?;
+        
+        info!("Deactivated customer: {}", customer.customer_number);
+        Ok(customer)
+    }
+    
+    /// Merge a duplicate customer into `survivor_id`. The duplicate's
+    /// shipments, deliveries, addresses and contacts move to the survivor,
+    /// its tags are added to the survivor's and fields the survivor leaves
+    /// blank are filled from it. The duplicate is kept, inactive, with
+    /// `merged_into` pointing at the survivor; its interactions stay with
+    /// it and are listed under the survivor.
+    pub async fn merge_customers(&self, survivor_id: &str, duplicate_id: &str) -> Result<Customer> {
+        if survivor_id == duplicate_id {
+            return Err(WmsError::validation("Cannot merge a customer into itself"));
+        }
+        
+        let mut survivor = self.get_customer(survivor_id).await?
+            .ok_or_else(|| WmsError::not_found("Customer not found"))?;
+        let mut duplicate = self.get_customer(duplicate_id).await?
+            .ok_or_else(|| WmsError::not_found("Duplicate customer not found"))?;
+        
+        if let Some(merged_into) = &duplicate.merged_into {
+            return Err(WmsError::conflict(format!(
+                "Customer {} was already merged into {}", duplicate.customer_number, merged_into
+            )));
+        }
+        if !survivor.is_active {
+            return Err(WmsError::validation(format!(
+                "Cannot merge into inactive customer {}", survivor.customer_number
+            )));
+        }
+        
+        fill_blanks(&mut survivor, &duplicate);
+        let now = Utc::now();
+        survivor.updated_at = Some(now);
+        duplicate.is_active = false;
+        duplicate.merged_into = Some(survivor.id.clone());
+        duplicate.updated_at = Some(now);
+        duplicate.version += 1;
+        
+        // The survivor keeps its own default address and primary contact
+        let keep_default = survivor.addresses.iter().any(|a| a.is_default);
+        let keep_primary = survivor.contacts.iter().any(|c| c.is_primary);
+        
+        // Everything moves or nothing does. Interactions stay where they
+        // were logged and are read through `merged_into`.
+        let moved_at = now.to_rfc3339();
+        self.db.transaction(|conn| {
+            for table in [SHIPMENTS_TABLE, DELIVERIES_TABLE] {
+                let moved = conn.prepare(&format!(
+                    "UPDATE {} SET customer_id = ?, updated_at = ?, version = version + 1
+                     WHERE customer_id = ?
+                     RETURNING id, version",
+                    table
+                ))?
+                .query_map(params![survivor_id, &moved_at, duplicate_id], |row| {
+                    Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+                for (id, version) in moved {
+                    let change = serde_json::json!({
+                        "id": &id,
+                        "customer_id": survivor_id,
+                        "updated_at": &moved_at,
+                        "version": version,
+                    });
+                    self.changes.record_model(conn, table, &id, ChangeKind::Update, &change)?;
+                }
+            }
+            conn.execute(
+                "UPDATE customer_addresses
+                 SET customer_id = ?, is_default = CASE WHEN ? THEN 0 ELSE is_default END
+                 WHERE customer_id = ?",
+                params![survivor_id, keep_default, duplicate_id],
+            )?;
+            conn.execute(
+                "UPDATE customer_contacts
+                 SET customer_id = ?, is_primary = CASE WHEN ? THEN 0 ELSE is_primary END
+                 WHERE customer_id = ?",
+                params![survivor_id, keep_primary, duplicate_id],
+            )?;
+            
+            if Self::update_customer_row(conn, &survivor)? == 0 {
+                return Err(WmsError::conflict(format!(
+                    "Customer {} was changed by someone else during the merge", survivor.customer_number
+                )));
+            }
+            survivor.version += 1;
+            
+            // Guarded so two merges of the same duplicate cannot both succeed
+            let rows = conn.execute(
+                "UPDATE customers SET is_active = 0, merged_into = ?, updated_at = ?, version = version + 1
+                 WHERE id = ? AND merged_into IS NULL",
+                params![survivor_id, &moved_at, duplicate_id],
+            )?;
+            if rows == 0 {
+                return Err(WmsError::conflict(format!(
+                    "Customer {} was already merged", duplicate.customer_number
+                )));
+            }
+            
+            self.changes.record_model(conn, "customers", &survivor.id, ChangeKind::Update, &survivor)?;
+            self.changes.record_model(conn, "customers", &duplicate.id, ChangeKind::Update, &duplicate)
+        })?;
+        
+        info!("Merged customer {} into {}", duplicate.customer_number, survivor.customer_number);
+        
+        self.get_customer(survivor_id).await?
+            .ok_or_else(|| WmsError::not_found("Customer not found"))
+    }
+    
+    /// Add an address to a customer. A default address replaces the
+    /// customer's previous default of the same type.
+    pub async fn add_address(&self, mut address: CustomerAddress) -> Result<CustomerAddress> {
+        address.validate()
+            .map_err(|e| WmsError::validation(format!("Invalid address: {}", e)))?;
+        if let Some(ref phone) = address.phone {
+            validate_phone_number(phone)?;
+        }
+        self.require_customer(&address.customer_id)?;
+        
+        address.id = new_id();
+        address.created_at = Utc::now();
+        
+        self.db.transaction(|conn| {
+            if address.is_default {
+                Self::clear_default_address(conn, &address.customer_id, address.address_type)?;
+            }
+            Self::insert_address(conn, &address)
+        })?;
+        
+        debug!("Added {} address to customer {}", address.address_type.as_str(), address.customer_id);
+        Ok(address)
+    }
+    
+    /// Update an address. Coordinates left out keep their geocoded values,
+    /// and making it the default replaces the previous default of its type.
+    pub async fn update_address(&self, mut address: CustomerAddress) -> Result<CustomerAddress> {
+        address.validate()
+            .map_err(|e| WmsError::validation(format!("Invalid address: {}", e)))?;
+        if let Some(ref phone) = address.phone {
+            validate_phone_number(phone)?;
+        }
+        let stored = self.get_address(&address.id)?
+            .ok_or_else(|| WmsError::not_found("Address not found"))?;
+        
+        address.customer_id = stored.customer_id;
+        address.created_at = stored.created_at;
+        if address.latitude.is_none() && address.longitude.is_none() {
+            address.latitude = stored.latitude;
+            address.longitude = stored.longitude;
+        }
+        
+        self.db.transaction(|conn| {
+            if address.is_default {
+                Self::clear_default_address(conn, &address.customer_id, address.address_type)?;
+            }
+            conn.execute(
+                "UPDATE customer_addresses SET
+                    address_type = ?, is_default = ?, contact_name = ?,
+                    address_line1 = ?, address_line2 = ?, city = ?, state = ?,
+                    postal_code = ?, country = ?, phone = ?, delivery_instructions = ?,
+                    latitude = ?, longitude = ?
+                 WHERE id = ?",
+                params![
+                    address.address_type.as_str(),
+                    address.is_default,
+                    &address.contact_name,
+                    &address.address.line1,
+                    &address.address.line2,
+                    &address.address.city,
+                    &address.address.state,
+                    &address.address.postal_code,
+                    &address.address.country,
+                    &address.phone,
+                    &address.delivery_instructions,
+                    &address.latitude,
+                    &address.longitude,
+                    &address.id,
+                ],
+            )?;
+            Ok(())
+        })?;
+        
+        Ok(address)
+    }
+    
+    /// Delete an address. The last billing address of a customer with open
+    /// shipments cannot be deleted.
+    pub async fn delete_address(&self, id: &str) -> Result<()> {
+        let address = self.get_address(id)?
+            .ok_or_else(|| WmsError::not_found("Address not found"))?;
+        
+        if address.address_type.is_billing() {
+            let other_billing: i64 = self.db.query_row(
+                "SELECT COUNT(*) FROM customer_addresses
+                 WHERE customer_id = ? AND id != ? AND address_type IN ('billing', 'both')",
+                params![&address.customer_id, id],
+                |row| row.get(0),
+            )?.unwrap_or(0);
+            let open_shipments: i64 = self.db.query_row(
+                &format!(
+                    "SELECT COUNT(*) FROM {} WHERE customer_id = ? AND {}",
+                    SHIPMENTS_TABLE, OPEN_SHIPMENT_CONDITION
+                ),
+                params![&address.customer_id],
+                |row| row.get(0),
+            )?.unwrap_or(0);
+            if other_billing == 0 && open_shipments > 0 {
+                return Err(WmsError::validation(format!(
+                    "Cannot delete the last billing address while the customer has {} open shipments",
+                    open_shipments
+                )));
+            }
+        }
+        
+        self.db.execute("DELETE FROM customer_addresses WHERE id = ?", params![id])?;
+        debug!("Deleted address {} of customer {}", id, address.customer_id);
+        Ok(())
+    }
+    
+    /// Make an address the customer's default for its type
+    pub async fn set_default_address(&self, id: &str) -> Result<CustomerAddress> {
+        let mut address = self.get_address(id)?
+            .ok_or_else(|| WmsError::not_found("Address not found"))?;
+        
+        self.db.transaction(|conn| {
+            Self::clear_default_address(conn, &address.customer_id, address.address_type)?;
+            conn.execute(
+                "UPDATE customer_addresses SET is_default = 1 WHERE id = ?",
+                params![id],
+            )?;
+            Ok(())
+        })?;
+        
+        address.is_default = true;
+        Ok(address)
+    }
+    
+    /// Add a contact to a customer. A primary contact replaces the
+    /// customer's previous primary contact.
+    pub async fn add_contact(&self, mut contact: CustomerContact) -> Result<CustomerContact> {
+        Self::validate_contact(&contact)?;
+        self.require_customer(&contact.customer_id)?;
+        
+        contact.id = new_id();
+        contact.created_at = Utc::now();
+        
+        self.db.transaction(|conn| {
+            if contact.is_primary {
+                Self::clear_primary_contact(conn, &contact.customer_id)?;
+            }
+            Self::insert_contact(conn, &contact)
+        })?;
+        
+        debug!("Added contact {} to customer {}", contact.full_name(), contact.customer_id);
+        Ok(contact)
+    }
+    
+    /// Update a contact; making it primary replaces the previous primary
+    pub async fn update_contact(&self, mut contact: CustomerContact) -> Result<CustomerContact> {
+        Self::validate_contact(&contact)?;
+        let stored = self.get_contact(&contact.id)?
+            .ok_or_else(|| WmsError::not_found("Contact not found"))?;
+        
+        contact.customer_id = stored.customer_id;
+        contact.created_at = stored.created_at;
+        
+        self.db.transaction(|conn| {
+            if contact.is_primary {
+                Self::clear_primary_contact(conn, &contact.customer_id)?;
+            }
+            conn.execute(
+                "UPDATE customer_contacts SET
+                    first_name = ?, last_name = ?, title = ?, department = ?,
+                    email = ?, phone = ?, mobile = ?, is_primary = ?, notes = ?
+                 WHERE id = ?",
+                params![
+                    &contact.first_name,
+                    &contact.last_name,
+                    &contact.title,
+                    &contact.department,
+                    &contact.email,
+                    &contact.phone,
+                    &contact.mobile,
+                    contact.is_primary,
+                    &contact.notes,
+                    &contact.id,
+                ],
+            )?;
+            Ok(())
+        })?;
+        
+        Ok(contact)
+    }
+    
+    /// Delete a contact
+    pub async fn delete_contact(&self, id: &str) -> Result<()> {
+        let rows = self.db.execute("DELETE FROM customer_contacts WHERE id = ?", params![id])?;
+        if rows == 0 {
+            return Err(WmsError::not_found("Contact not found"));
+        }
+        Ok(())
+    }
+    
+    /// Make a contact the customer's primary contact
+    pub async fn set_primary_contact(&self, id: &str) -> Result<CustomerContact> {
+        let mut contact = self.get_contact(id)?
+            .ok_or_else(|| WmsError::not_found("Contact not found"))?;
+        
+        self.db.transaction(|conn| {
+            Self::clear_primary_contact(conn, &contact.customer_id)?;
+            conn.execute(
+                "UPDATE customer_contacts SET is_primary = 1 WHERE id = ?",
+                params![id],
+            )?;
+            Ok(())
+        })?;
+        
+        contact.is_primary = true;
+        Ok(contact)
+    }
+    
+    /// Search customers. Every filter is bound as a parameter; the sort
+    /// order comes from a fixed set of columns.
+    pub async fn search_customers(&self, query: CustomerSearchQuery) -> Result<SearchResult<Customer>> {
         let offset = (query.page.saturating_sub(1)) * query.page_size;
+        let mut conditions: Vec<String> = Vec::new();
+        let mut params_vec: Vec<String> = Vec::new();
         
-        // Build dynamic search query
-        let mut sql = String::from(
-            "SELECT * FROM customers WHERE 1=1"
-        );
+        if let Some(text) = query.query.as_deref().map(str::trim).filter(|q| !q.is_empty()) {
+            conditions.push(format!(
+                "(c.company_name LIKE '%' || ? || '%' OR
+                  c.first_name LIKE '%' || ? || '%' OR
+                  c.last_name LIKE '%' || ? || '%' OR
+                  c.email LIKE '%' || ? || '%' OR
+                  c.customer_number LIKE '%' || ? || '%' OR
+                  {})",
+                search_condition(CustomFieldEntity::Customer, "c")
+            ));
+            params_vec.extend(std::iter::repeat_n(text.to_string(), 6));
+        }
+        
+        if let Some(active) = query.is_active {
+            conditions.push(format!("c.is_active = {}", i32::from(active)));
+        }
+        
+        if let Some(customer_type) = query.customer_type {
+            conditions.push("c.customer_type = ?".to_string());
+            params_vec.push(customer_type.as_str().to_string());
+        }
+        
+        for tag in query.tags.iter().flatten() {
+            conditions.push(
+                "EXISTS (SELECT 1 FROM json_each(COALESCE(c.tags, '[]')) t WHERE t.value = ?)".to_string()
+            );
+            params_vec.push(tag.clone());
+        }
+        
+        for (column, value) in [("city", &query.city), ("state", &query.state)] {
+            if let Some(value) = value {
+                conditions.push(format!(
+                    "EXISTS (SELECT 1 FROM customer_addresses a
+                        WHERE a.customer_id = c.id AND a.{} = ? COLLATE NOCASE)",
+                    column
+                ));
+                params_vec.push(value.clone());
+            }
+        }
+        
+        if let Some(from) = query.created_from {
+            conditions.push("substr(c.created_at, 1, 10) >= ?".to_string());
+            params_vec.push(from.to_string());
+        }
+        if let Some(to) = query.created_to {
+            conditions.push("substr(c.created_at, 1, 10) <= ?".to_string());
+            params_vec.push(to.to_string());
+        }
         
-        if query.query.is_some() {
-            sql.push_str(" AND (
-                company_name LIKE '%' || ? || '%' OR
-                first_name LIKE '%' || ? || '%' OR
-                last_name LIKE '%' || ? || '%' OR
-                email LIKE '%' || ? || '%' OR
-                customer_number LIKE '%' || ? || '%'
-            )");
-        }
-        
-        if query.is_active.is_some() {
-            sql.push_str(" AND is_active = ?");
-        }
-        
-        sql.push_str(" ORDER BY company_name, last_name LIMIT ? OFFSET ?");
-        
-        // For simplicity, execute basic search
-        let customers = if let Some(q) = &query.query {
-            self.db.query_map(
-                "SELECT * FROM customers 
-                 WHERE is_active = 1 AND (
-                    company_name LIKE '%' || ? || '%' OR
-                    first_name LIKE '%' || ? || '%' OR
-                    last_name LIKE '%' || ? || '%' OR
-                    email LIKE '%' || ? || '%'
-                 )
-                 ORDER BY company_name, last_name
-                 LIMIT ? OFFSET ?",
-                params![q, q, q, q, query.page_size, offset],
-                |row| Self::row_to_customer(row),
-            )?
+        let filter = if conditions.is_empty() {
+            String::new()
         } else {
-            self.get_customers(query.page, query.page_size).await?
+            format!(" WHERE {}", conditions.join(" AND "))
         };
         
-        Ok(customers)
+        let total_count: i64 = self.db.query_row(
+            &format!("SELECT COUNT(*) FROM customers c{}", filter),
+            params_from_iter(params_vec.iter()),
+            |row| row.get(0),
+        )?.unwrap_or(0);
+        
+        let direction = query.sort_dir.as_sql();
+        let order = query.sort_by.columns().iter()
+            .map(|column| format!("c.{} {}", column, direction))
+            .collect::<Vec<_>>()
+            .join(", ");
+        
+        let items = self.db.query_map(
+            &format!(
+                "SELECT c.* FROM customers c{}
+                 ORDER BY {}, c.customer_number {}
+                 LIMIT {} OFFSET {}",
+                filter, order, direction, query.page_size, offset
+            ),
+            params_from_iter(params_vec.iter()),
+            |row| Self::row_to_customer(row),
+        )?;
+        
+        Ok(SearchResult {
+            items,
+            total_count: total_count as u64,
+            page: query.page,
+            page_size: query.page_size,
+        })
+    }
+    
+    /// A customer's shipments, deliveries and interactions as one
+    /// timeline, newest first, at most `limit` events
+    pub async fn get_customer_activity(&self, customer_id: &str, limit: u32) -> Result<Vec<ActivityEvent>> {
+        self.require_customer(customer_id)?;
+        
+        // Each source returns the rows holding its `limit` newest events;
+        // shipments are ranked by their latest event, not their creation
+        let shipments = self.db.query_map(
+            &format!(
+                "SELECT shipment_number, status, tracking_number, created_at, ship_date, actual_delivery_date
+                 FROM {}
+                 WHERE customer_id = ?
+                 ORDER BY MAX(created_at, COALESCE(ship_date, created_at),
+                              COALESCE(actual_delivery_date, created_at)) DESC
+                 LIMIT ?",
+                SHIPMENTS_TABLE
+            ),
+            params![customer_id, limit],
+            |row| {
+                let number: String = row.get("shipment_number")?;
+                let status: String = row.get("status")?;
+                let tracking: Option<String> = row.get("tracking_number")?;
+                let shipped_at = stored_timestamp(row, "ship_date")?;
+                let delivered_at = stored_timestamp(row, "actual_delivery_date")?;
+                
+                let mut events = vec![ActivityEvent {
+                    occurred_at: required_timestamp(row, "created_at")?,
+                    kind: ActivityKind::ShipmentCreated,
+                    reference_number: Some(number.clone()),
+                    summary: if status == "cancelled" {
+                        format!("Order {} placed (since cancelled)", number)
+                    } else {
+                        format!("Order {} placed", number)
+                    },
+                }];
+                if let Some(shipped_at) = shipped_at {
+                    events.push(ActivityEvent {
+                        occurred_at: shipped_at,
+                        kind: ActivityKind::ShipmentShipped,
+                        reference_number: Some(number.clone()),
+                        summary: match tracking {
+                            Some(tracking) => format!("Order {} shipped, tracking {}", number, tracking),
+                            None => format!("Order {} shipped", number),
+                        },
+                    });
+                }
+                if let Some(delivered_at) = delivered_at {
+                    events.push(ActivityEvent {
+                        occurred_at: delivered_at,
+                        kind: ActivityKind::ShipmentDelivered,
+                        summary: format!("Order {} delivered", number),
+                        reference_number: Some(number),
+                    });
+                }
+                Ok(events)
+            },
+        )?;
+        
+        let deliveries = self.db.query_map(
+            &format!(
+                "SELECT delivery_number, status, failure_reason,
+                        COALESCE(actual_arrival_time, updated_at, created_at) AS occurred_at
+                 FROM {}
+                 WHERE customer_id = ? AND status IN ('delivered', 'failed')
+                 ORDER BY occurred_at DESC
+                 LIMIT ?",
+                DELIVERIES_TABLE
+            ),
+            params![customer_id, limit],
+            |row| {
+                let number: String = row.get("delivery_number")?;
+                let status: String = row.get("status")?;
+                let failure_reason: Option<String> = row.get("failure_reason")?;
+                let (kind, summary) = if status == "delivered" {
+                    (ActivityKind::DeliveryCompleted, format!("Delivery {} completed", number))
+                } else {
+                    (ActivityKind::DeliveryFailed, match failure_reason {
+                        Some(reason) => format!("Delivery {} failed: {}", number, reason),
+                        None => format!("Delivery {} failed", number),
+                    })
+                };
+                Ok(ActivityEvent {
+                    occurred_at: required_timestamp(row, "occurred_at")?,
+                    kind,
+                    reference_number: Some(number),
+                    summary,
+                })
+            },
+        )?;
+        
+        let interactions = self.db.query_map(
+            &format!(
+                "SELECT interaction_type, subject, created_at
+                 FROM {}
+                 WHERE {}
+                 ORDER BY created_at DESC
+                 LIMIT ?",
+                INTERACTIONS_TABLE, MERGED_INTERACTION_CONDITION
+            ),
+            params![customer_id, limit],
+            |row| {
+                let interaction_type: String = row.get("interaction_type")?;
+                let subject: Option<String> = row.get("subject")?;
+                Ok(ActivityEvent {
+                    occurred_at: required_timestamp(row, "created_at")?,
+                    kind: ActivityKind::Interaction,
+                    reference_number: None,
+                    summary: match non_blank(subject) {
+                        Some(subject) => format!("{}: {}", interaction_type, subject),
+                        None => interaction_type,
+                    },
+                })
+            },
+        )?;
+        
+        let events = shipments.into_iter().flatten().chain(deliveries).chain(interactions).collect();
+        Ok(build_timeline(events, limit as usize))
+    }
+    
+    /// Order totals for a customer
+    pub async fn get_customer_stats(&self, customer_id: &str) -> Result<CustomerStats> {
+        self.require_customer(customer_id)?;
+        
+        let order_dates = self.db.query_map(
+            &format!(
+                "SELECT created_at FROM {} WHERE customer_id = ? AND status != 'cancelled'",
+                SHIPMENTS_TABLE
+            ),
+            params![customer_id],
+            |row| required_timestamp(row, "created_at"),
+        )?;
+        let open_shipments: u32 = self.db.query_row(
+            &format!(
+                "SELECT COUNT(*) FROM {} WHERE customer_id = ? AND {}",
+                SHIPMENTS_TABLE, OPEN_SHIPMENT_CONDITION
+            ),
+            params![customer_id],
+            |row| row.get(0),
+        )?.unwrap_or(0);
+        
+        Ok(order_stats(order_dates, open_shipments))
+    }
+    
+    /// Check that an order worth `order_value` fits under the customer's
+    /// credit limit, alongside their shipments not yet delivered
+    pub async fn validate_credit(&self, customer_id: &str, order_value: f64) -> Result<CreditCheck> {
+        let credit = check_credit(&self.db, customer_id, order_value)?;
+        if credit.exceeded() {
+            return Err(credit.error());
+        }
+        Ok(credit)
     }
     
     /// Add an interaction/activity
@@ -249,20 +846,173 @@ impl CrmService {
         Ok(interaction)
     }
     
-    // Helper methods
+    /// Get a customer's shipping requirements, if any are set
+    pub async fn get_shipping_requirements(&self, customer_id: &str) -> Result<Option<ShippingRequirements>> {
+        self.db.query_row(
+            "SELECT * FROM customer_shipping_requirements WHERE customer_id = ?",
+            params![customer_id],
+            |row| Self::row_to_shipping_requirements(row),
+        )
+    }
     
-    fn generate_customer_number(&self) -> Result<String> {
-        let count: i64 = self.db.query_row(
-            "SELECT COUNT(*) + 1 FROM customers",
-            [],
-            |row| row.get(0),
-        )?.unwrap_or(1);
+    /// Create or replace a customer's shipping requirements
+    pub async fn upsert_shipping_requirements(
+        &self,
+        mut requirements: ShippingRequirements,
+    ) -> Result<ShippingRequirements> {
+        if self.get_customer(&requirements.customer_id).await?.is_none() {
+            return Err(WmsError::not_found("Customer not found"));
+        }
+        
+        // Blank fields from the form mean "no requirement"
+        requirements.required_carrier_id = non_blank(requirements.required_carrier_id);
+        requirements.required_service = non_blank(requirements.required_service);
+        requirements.label_notes = non_blank(requirements.label_notes);
+        
+        if let Some(carrier_id) = &requirements.required_carrier_id {
+            let exists = self.db.query_row(
+                "SELECT 1 FROM carriers WHERE id = ?",
+                params![carrier_id],
+                |row| row.get::<_, i32>(0),
+            )?.is_some();
+            if !exists {
+                return Err(WmsError::validation(format!("Unknown carrier {}", carrier_id)));
+            }
+        }
+        
+        requirements.updated_at = Some(Utc::now());
+        
+        self.db.execute(
+            "INSERT INTO customer_shipping_requirements (
+                customer_id, required_carrier_id, required_service, allow_partial,
+                appointment_required, label_notes, updated_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(customer_id) DO UPDATE SET
+                required_carrier_id = excluded.required_carrier_id,
+                required_service = excluded.required_service,
+                allow_partial = excluded.allow_partial,
+                appointment_required = excluded.appointment_required,
+                label_notes = excluded.label_notes,
+                updated_at = excluded.updated_at",
+            params![
+                &requirements.customer_id,
+                &requirements.required_carrier_id,
+                &requirements.required_service,
+                requirements.allow_partial,
+                requirements.appointment_required,
+                &requirements.label_notes,
+                requirements.updated_at.map(|t| t.to_rfc3339()),
+            ],
+        )?;
         
-        Ok(format!("CUS-{:06}", count))
+        debug!("Saved shipping requirements for customer {}", requirements.customer_id);
+        Ok(requirements)
     }
     
-    fn insert_address(&self, addr: &CustomerAddress) -> Result<()> {
-        self.db.execute(
+    /// Generate a customer statement for a date range.
+    ///
+    /// `format` is "pdf" for the statement document or "csv" for the
+    /// shipment lines behind it. A range without activity still produces a
+    /// statement.
+    pub async fn generate_customer_statement(
+        &self,
+        customer_id: &str,
+        range: DateRange,
+        format: &str,
+    ) -> Result<StatementExport> {
+        if range.end < range.start {
+            return Err(WmsError::validation("Statement end date is before start date"));
+        }
+
+        let customer = self.get_customer(customer_id).await?
+            .ok_or_else(|| WmsError::not_found("Customer not found"))?;
+        let start = range.start.to_string();
+        let end = range.end.to_string();
+
+        let shipments = self.db.query_map(
+            "SELECT shipment_number, created_at, total_packages, order_total
+             FROM shipments
+             WHERE customer_id = ? AND status != 'cancelled'
+               AND substr(created_at, 1, 10) BETWEEN ? AND ?
+             ORDER BY created_at",
+            params![customer_id, &start, &end],
+            |row| {
+                Ok(StatementShipment {
+                    shipment_number: row.get("shipment_number")?,
+                    date: required_date(row, "created_at")?,
+                    total_packages: row.get::<_, Option<u32>>("total_packages")?.unwrap_or(1),
+                    order_total: row.get("order_total")?,
+                })
+            },
+        )?;
+
+        let deliveries = self.db.query_map(
+            "SELECT delivery_number, scheduled_date, status, actual_arrival_time
+             FROM deliveries
+             WHERE customer_id = ?
+               AND substr(scheduled_date, 1, 10) BETWEEN ? AND ?",
+            params![customer_id, &start, &end],
+            |row| {
+                Ok(StatementDelivery {
+                    delivery_number: row.get("delivery_number")?,
+                    scheduled_date: required_date(row, "scheduled_date")?,
+                    status: row.get("status")?,
+                    arrival_date: stored_date(row, "actual_arrival_time")?,
+                })
+            },
+        )?;
+
+        let open_follow_ups = self.db.query_map(
+            &format!(
+                "SELECT id, subject, follow_up_date, follow_up_notes
+                 FROM {}
+                 WHERE {} AND follow_up_date IS NOT NULL
+                   AND follow_up_date >= ?
+                 ORDER BY follow_up_date",
+                INTERACTIONS_TABLE, MERGED_INTERACTION_CONDITION
+            ),
+            params![customer_id, Utc::now().date_naive().to_string()],
+            |row| {
+                Ok(OpenFollowUp {
+                    interaction_id: row.get("id")?,
+                    subject: row.get("subject")?,
+                    follow_up_date: required_timestamp(row, "follow_up_date")?,
+                    follow_up_notes: row.get("follow_up_notes")?,
+                })
+            },
+        )?;
+
+        let statement = build_statement(&customer, range, &shipments, &deliveries, open_follow_ups);
+
+        let (data, content_type, extension) = match format.to_lowercase().as_str() {
+            "pdf" => (render_statement_pdf(&statement), "application/pdf", "pdf"),
+            "csv" => {
+                let lines = self.get_statement_lines(customer_id, &start, &end)?;
+                (render_statement_csv(&lines)?, "text/csv", "csv")
+            }
+            _ => {
+                return Err(WmsError::validation(format!("Unsupported format: {}", format)));
+            }
+        };
+
+        info!("Generated {} statement for {} ({} to {})",
+              extension, customer.customer_number, start, end);
+
+        Ok(StatementExport {
+            filename: format!(
+                "statement_{}_{}_to_{}.{}",
+                customer.customer_number, start, end, extension
+            ),
+            statement,
+            data: base64::engine::general_purpose::STANDARD.encode(&data),
+            content_type: content_type.to_string(),
+        })
+    }
+
+    // Helper methods
+    
+    fn insert_address(conn: &rusqlite::Connection, addr: &CustomerAddress) -> Result<()> {
+        conn.execute(
             "INSERT INTO customer_addresses (
                 id, customer_id, address_type, is_default, contact_name,
                 address_line1, address_line2, city, state, postal_code, country,
@@ -271,7 +1021,7 @@ impl CrmService {
             params![
                 &addr.id,
                 &addr.customer_id,
-                format!("{:?}", addr.address_type).to_lowercase(),
+                addr.address_type.as_str(),
                 addr.is_default,
                 &addr.contact_name,
                 &addr.address.line1,
@@ -290,8 +1040,17 @@ impl CrmService {
         Ok(())
     }
     
-    fn insert_contact(&self, contact: &CustomerContact) -> Result<()> {
-        self.db.execute(
+    fn validate_contact(contact: &CustomerContact) -> Result<()> {
+        contact.validate()
+            .map_err(|e| WmsError::validation(format!("Invalid contact: {}", e)))?;
+        for phone in [&contact.phone, &contact.mobile].into_iter().flatten() {
+            validate_phone_number(phone)?;
+        }
+        Ok(())
+    }
+    
+    fn insert_contact(conn: &rusqlite::Connection, contact: &CustomerContact) -> Result<()> {
+        conn.execute(
             "INSERT INTO customer_contacts (
                 id, customer_id, first_name, last_name, title, department,
                 email, phone, mobile, is_primary, notes, created_at
@@ -314,61 +1073,191 @@ impl CrmService {
         Ok(())
     }
     
+    /// Write a customer's editable fields, if the stored version still
+    /// matches `customer.version`; returns the rows changed
+    fn update_customer_row(conn: &rusqlite::Connection, customer: &Customer) -> Result<usize> {
+        let tags_json = serde_json::to_string(&customer.tags).unwrap_or_default();
+        let rows = conn.execute(
+            "UPDATE customers SET
+                company_name = ?, first_name = ?, last_name = ?,
+                email = ?, phone = ?, mobile = ?, fax = ?, website = ?,
+                tax_id = ?, customer_type = ?, credit_limit = ?,
+                payment_terms = ?, currency_code = ?, notes = ?, tags = ?,
+                is_active = ?, notifications_opt_out = ?, custom_fields = ?, updated_at = ?,
+                version = version + 1
+             WHERE id = ? AND version = ?",
+            params![
+                &customer.company_name,
+                &customer.first_name,
+                &customer.last_name,
+                &customer.email,
+                &customer.phone,
+                &customer.mobile,
+                &customer.fax,
+                &customer.website,
+                &customer.tax_id,
+                customer.customer_type.as_str(),
+                &customer.credit_limit,
+                &customer.payment_terms,
+                &customer.currency_code,
+                &customer.notes,
+                &tags_json,
+                customer.is_active,
+                customer.notifications_opt_out,
+                custom_fields_json(&customer.custom_fields),
+                customer.updated_at.map(|t| t.to_rfc3339()),
+                &customer.id,
+                customer.version,
+            ],
+        )?;
+        Ok(rows)
+    }
+    
     fn get_customer_addresses(&self, customer_id: &str) -> Result<Vec<CustomerAddress>> {
         self.db.query_map(
-            "SELECT * FROM customer_addresses WHERE customer_id = ? ORDER BY is_default DESC",
+            "SELECT * FROM customer_addresses WHERE customer_id = ? ORDER BY is_default DESC, created_at",
             params![customer_id],
-            |row| {
-                Ok(CustomerAddress {
-                    id: row.get("id")?,
-                    customer_id: row.get("customer_id")?,
-                    address_type: AddressType::Shipping,
-                    is_default: row.get::<_, i32>("is_default")? == 1,
-                    contact_name: row.get("contact_name")?,
-                    address: wms_core::types::Address {
-                        line1: row.get("address_line1")?,
-                        line2: row.get("address_line2")?,
-                        city: row.get("city")?,
-                        state: row.get("state")?,
-                        postal_code: row.get("postal_code")?,
-                        country: row.get("country")?,
-                    },
-                    phone: row.get("phone")?,
-                    delivery_instructions: row.get("delivery_instructions")?,
-                    latitude: row.get("latitude")?,
-                    longitude: row.get("longitude")?,
-                    created_at: Utc::now(),
-                })
-            },
+            |row| Self::row_to_address(row),
         )
     }
     
     fn get_customer_contacts(&self, customer_id: &str) -> Result<Vec<CustomerContact>> {
         self.db.query_map(
-            "SELECT * FROM customer_contacts WHERE customer_id = ? ORDER BY is_primary DESC",
+            "SELECT * FROM customer_contacts WHERE customer_id = ? ORDER BY is_primary DESC, created_at",
             params![customer_id],
+            |row| Self::row_to_contact(row),
+        )
+    }
+    
+    fn get_address(&self, id: &str) -> Result<Option<CustomerAddress>> {
+        self.db.query_row(
+            "SELECT * FROM customer_addresses WHERE id = ?",
+            params![id],
+            |row| Self::row_to_address(row),
+        )
+    }
+    
+    fn get_contact(&self, id: &str) -> Result<Option<CustomerContact>> {
+        self.db.query_row(
+            "SELECT * FROM customer_contacts WHERE id = ?",
+            params![id],
+            |row| Self::row_to_contact(row),
+        )
+    }
+    
+    fn require_customer(&self, customer_id: &str) -> Result<()> {
+        self.db.query_row(
+            "SELECT id FROM customers WHERE id = ?",
+            params![customer_id],
+            |row| row.get::<_, String>(0),
+        )?
+        .map(|_| ())
+        .ok_or_else(|| WmsError::not_found("Customer not found"))
+    }
+    
+    /// Clear the customer's default address of the given type
+    fn clear_default_address(conn: &rusqlite::Connection, customer_id: &str, address_type: AddressType) -> Result<()> {
+        conn.execute(
+            "UPDATE customer_addresses SET is_default = 0 WHERE customer_id = ? AND address_type = ?",
+            params![customer_id, address_type.as_str()],
+        )?;
+        Ok(())
+    }
+    
+    /// Clear the customer's primary contact
+    fn clear_primary_contact(conn: &rusqlite::Connection, customer_id: &str) -> Result<()> {
+        conn.execute(
+            "UPDATE customer_contacts SET is_primary = 0 WHERE customer_id = ?",
+            params![customer_id],
+        )?;
+        Ok(())
+    }
+    
+    fn get_statement_lines(&self, customer_id: &str, start: &str, end: &str) -> Result<Vec<StatementLine>> {
+        self.db.query_map(
+            "SELECT s.shipment_number, s.created_at, i.sku, i.name,
+                    si.quantity_ordered, si.quantity_shipped,
+                    si.unit_price, si.extended_price
+             FROM shipment_items si
+             JOIN shipments s ON si.shipment_id = s.id
+             JOIN inventory_items i ON si.item_id = i.id
+             WHERE s.customer_id = ? AND s.status != 'cancelled'
+               AND substr(s.created_at, 1, 10) BETWEEN ? AND ?
+             ORDER BY s.created_at, s.shipment_number, i.sku",
+            params![customer_id, start, end],
             |row| {
-                Ok(CustomerContact {
-                    id: row.get("id")?,
-                    customer_id: row.get("customer_id")?,
-                    first_name: row.get("first_name")?,
-                    last_name: row.get("last_name")?,
-                    title: row.get("title")?,
-                    department: row.get("department")?,
-                    email: row.get("email")?,
-                    phone: row.get("phone")?,
-                    mobile: row.get("mobile")?,
-                    is_primary: row.get::<_, i32>("is_primary")? == 1,
-                    notes: row.get("notes")?,
-                    created_at: Utc::now(),
+                Ok(StatementLine {
+                    shipment_number: row.get("shipment_number")?,
+                    date: required_date(row, "created_at")?,
+                    sku: row.get("sku")?,
+                    item_name: row.get("name")?,
+                    quantity_ordered: row.get("quantity_ordered")?,
+                    quantity_shipped: row.get::<_, Option<f64>>("quantity_shipped")?.unwrap_or(0.0),
+                    unit_price: row.get("unit_price")?,
+                    extended_price: row.get("extended_price")?,
                 })
             },
         )
     }
     
+    fn row_to_address(row: &rusqlite::Row) -> rusqlite::Result<CustomerAddress> {
+        Ok(CustomerAddress {
+            id: row.get("id")?,
+            customer_id: row.get("customer_id")?,
+            address_type: stored_status(row, "address_type", AddressType::parse)?,
+            is_default: row.get::<_, i32>("is_default")? == 1,
+            contact_name: row.get("contact_name")?,
+            address: wms_core::types::Address {
+                line1: row.get("address_line1")?,
+                line2: row.get("address_line2")?,
+                city: row.get("city")?,
+                state: row.get("state")?,
+                postal_code: row.get("postal_code")?,
+                country: row.get("country")?,
+            },
+            phone: row.get("phone")?,
+            delivery_instructions: row.get("delivery_instructions")?,
+            latitude: row.get("latitude")?,
+            longitude: row.get("longitude")?,
+            created_at: required_timestamp(row, "created_at")?,
+        })
+    }
+    
+    fn row_to_contact(row: &rusqlite::Row) -> rusqlite::Result<CustomerContact> {
+        Ok(CustomerContact {
+            id: row.get("id")?,
+            customer_id: row.get("customer_id")?,
+            first_name: row.get("first_name")?,
+            last_name: row.get("last_name")?,
+            title: row.get("title")?,
+            department: row.get("department")?,
+            email: row.get("email")?,
+            phone: row.get("phone")?,
+            mobile: row.get("mobile")?,
+            is_primary: row.get::<_, i32>("is_primary")? == 1,
+            notes: row.get("notes")?,
+            created_at: required_timestamp(row, "created_at")?,
+        })
+    }
+    
+    fn row_to_shipping_requirements(row: &rusqlite::Row) -> rusqlite::Result<ShippingRequirements> {
+        Ok(ShippingRequirements {
+            customer_id: row.get("customer_id")?,
+            required_carrier_id: row.get("required_carrier_id")?,
+            required_service: row.get("required_service")?,
+            allow_partial: row.get::<_, i32>("allow_partial")? == 1,
+            appointment_required: row.get::<_, i32>("appointment_required")? == 1,
+            label_notes: row.get("label_notes")?,
+            updated_at: stored_timestamp(row, "updated_at")?,
+        })
+    }
+    
     fn row_to_customer(row: &rusqlite::Row) -> rusqlite::Result<Customer> {
         let tags_str: String = row.get("tags").unwrap_or_default();
         let tags: Vec<String> = serde_json::from_str(&tags_str).unwrap_or_default();
+        let customer_type = row.get::<_, Option<String>>("customer_type")?
+            .map(|t| CustomerType::parse(&t).ok_or_else(|| unreadable(row, "customer_type", &t)))
+            .transpose()?;
         
         Ok(Customer {
             id: row.get("id")?,
@@ -382,18 +1271,429 @@ impl CrmService {
             fax: row.get("fax")?,
             website: row.get("website")?,
             tax_id: row.get("tax_id")?,
-            customer_type: CustomerType::Retail,
+            customer_type: customer_type.unwrap_or_default(),
             credit_limit: row.get("credit_limit")?,
             payment_terms: row.get("payment_terms")?,
             currency_code: row.get("currency_code").unwrap_or_else(|_| "USD".to_string()),
             notes: row.get("notes")?,
             tags,
             is_active: row.get::<_, i32>("is_active")? == 1,
-            created_at: Utc::now(),
-            updated_at: None,
+            merged_into: row.get("merged_into")?,
+            notifications_opt_out: row.get::<_, i32>("notifications_opt_out")? == 1,
+            custom_fields: parse_custom_fields(row.get("custom_fields")?),
+            created_at: required_timestamp(row, "created_at")?,
+            updated_at: stored_timestamp(row, "updated_at")?,
+            version: row.get("version")?,
             addresses: Vec::new(),
             contacts: Vec::new(),
         })
     }
 }
 
+/// Fill the survivor's blank fields and custom fields from a duplicate and
+/// add the duplicate's tags
+fn fill_blanks(survivor: &mut Customer, duplicate: &Customer) {
+    let fields = [
+        (&mut survivor.company_name, &duplicate.company_name),
+        (&mut survivor.first_name, &duplicate.first_name),
+        (&mut survivor.last_name, &duplicate.last_name),
+        (&mut survivor.email, &duplicate.email),
+        (&mut survivor.phone, &duplicate.phone),
+        (&mut survivor.mobile, &duplicate.mobile),
+        (&mut survivor.fax, &duplicate.fax),
+        (&mut survivor.website, &duplicate.website),
+        (&mut survivor.tax_id, &duplicate.tax_id),
+        (&mut survivor.payment_terms, &duplicate.payment_terms),
+        (&mut survivor.notes, &duplicate.notes),
+    ];
+    for (field, fallback) in fields {
+        if field.as_deref().is_none_or(|v| v.trim().is_empty()) {
+            field.clone_from(fallback);
+        }
+    }
+    if survivor.credit_limit.is_none() {
+        survivor.credit_limit = duplicate.credit_limit;
+    }
+    for (key, value) in &duplicate.custom_fields {
+        survivor.custom_fields.entry(key.clone()).or_insert_with(|| value.clone());
+    }
+    for tag in &duplicate.tags {
+        if !survivor.tags.contains(tag) {
+            survivor.tags.push(tag.clone());
+        }
+    }
+}
+
+/// Trimmed text, or `None` when empty
+fn non_blank(value: Option<String>) -> Option<String> {
+    value.map(|v| v.trim().to_string()).filter(|v| !v.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use wms_core::test_support::{CustomerBuilder, DeliveryBuilder, ShipmentBuilder, test_db};
+
+    fn count(db: &Database, table: &str, customer_id: &str) -> i64 {
+        db.query_row(
+            &format!("SELECT COUNT(*) FROM {} WHERE customer_id = ?", table),
+            params![customer_id],
+            |row| row.get(0),
+        ).unwrap().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_merge_repoints_rows_and_fills_blanks() {
+        let db = test_db();
+        CustomerBuilder::new("CUST-1").insert::<Customer>(&db);
+        CustomerBuilder::new("CUST-2").email("orders@acme.test").payment_terms("NET30").insert::<Customer>(&db);
+        db.execute("UPDATE customers SET tags = '[\"vip\"]' WHERE id = 'cust-1'", []).unwrap();
+        db.execute("UPDATE customers SET tags = '[\"vip\",\"east\"]' WHERE id = 'cust-2'", []).unwrap();
+        ShipmentBuilder::new("SHP-1").customer("cust-2").insert::<serde_json::Value>(&db);
+        DeliveryBuilder::new("DEL-1").customer("cust-2").insert::<serde_json::Value>(&db);
+        db.execute(
+            "INSERT INTO customer_interactions (id, customer_id, interaction_type, subject, created_by, created_at)
+             VALUES ('i1', 'cust-2', 'call', 'Reorder', 'u1', '2025-01-15T09:00:00Z')",
+            [],
+        ).unwrap();
+        db.execute(
+            "INSERT INTO customer_addresses (id, customer_id, is_default, address_line1, city, state, postal_code)
+             VALUES ('a1', 'cust-2', 1, '1 Main St', 'Springfield', 'IL', '62701')",
+            [],
+        ).unwrap();
+        db.execute(
+            "INSERT INTO customer_contacts (id, customer_id, first_name, last_name, is_primary)
+             VALUES ('k1', 'cust-2', 'Ann', 'Lee', 1)",
+            [],
+        ).unwrap();
+        let service = CrmService::new(db.clone());
+        let before = service.get_customer("cust-1").await.unwrap().unwrap();
+
+        let survivor = service.merge_customers("cust-1", "cust-2").await.unwrap();
+
+        for table in ["shipments", "deliveries", "customer_addresses", "customer_contacts"] {
+            assert_eq!(count(&db, table, "cust-1"), 1, "{}", table);
+            assert_eq!(count(&db, table, "cust-2"), 0, "{}", table);
+        }
+        // Moved shipments and deliveries are sent for sync
+        let moved: Vec<(String, String)> = db.query_map(
+            "SELECT table_name, payload FROM sync_outbox
+             WHERE table_name IN ('shipments', 'deliveries') ORDER BY table_name",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        ).unwrap();
+        assert_eq!(moved.len(), 2);
+        for (table, payload) in &moved {
+            let payload: serde_json::Value = serde_json::from_str(payload).unwrap();
+            assert_eq!(payload["customer_id"], "cust-1", "{}", table);
+        }
+        // The interaction log is left alone and read through the merge
+        assert_eq!(count(&db, "customer_interactions", "cust-2"), 1);
+        let timeline = service.get_customer_activity("cust-1", 50).await.unwrap();
+        assert!(timeline.iter().any(|e| e.summary == "call: Reorder"));
+        assert_eq!(survivor.email.as_deref(), Some("orders@acme.test"));
+        assert_eq!(survivor.payment_terms.as_deref(), Some("NET30"));
+        assert_eq!(survivor.tags, vec!["vip", "east"]);
+        assert_eq!(survivor.addresses.len(), 1);
+        assert_eq!(survivor.version, before.version + 1);
+
+        let duplicate = service.get_customer("cust-2").await.unwrap().unwrap();
+        assert!(!duplicate.is_active);
+        assert_eq!(duplicate.merged_into.as_deref(), Some("cust-1"));
+    }
+
+    #[tokio::test]
+    async fn test_merge_rejects_merged_inactive_and_self() {
+        let db = test_db();
+        CustomerBuilder::new("CUST-1").insert::<Customer>(&db);
+        CustomerBuilder::new("CUST-2").insert::<Customer>(&db);
+        CustomerBuilder::new("CUST-3").insert::<Customer>(&db);
+        let service = CrmService::new(db.clone());
+
+        service.merge_customers("cust-1", "cust-2").await.unwrap();
+        let again = service.merge_customers("cust-3", "cust-2").await;
+        assert!(matches!(again, Err(WmsError::Conflict(_))));
+        assert_eq!(
+            service.get_customer("cust-2").await.unwrap().unwrap().merged_into.as_deref(),
+            Some("cust-1"),
+        );
+
+        let into_self = service.merge_customers("cust-1", "cust-1").await;
+        assert!(matches!(into_self, Err(WmsError::Validation(_))));
+
+        service.deactivate_customer("cust-1").await.unwrap();
+        let into_inactive = service.merge_customers("cust-1", "cust-3").await;
+        assert!(matches!(into_inactive, Err(WmsError::Validation(_))));
+        assert!(service.get_customer("cust-3").await.unwrap().unwrap().is_active);
+    }
+
+    fn address(customer_id: &str, address_type: AddressType, is_default: bool) -> CustomerAddress {
+        serde_json::from_value(serde_json::json!({
+            "id": "",
+            "customer_id": customer_id,
+            "address_type": address_type,
+            "is_default": is_default,
+            "address": {
+                "line1": "1 Main St",
+                "city": "Springfield",
+                "state": "IL",
+                "postal_code": "62701",
+                "country": "US"
+            },
+            "latitude": 39.78,
+            "longitude": -89.65,
+            "created_at": "2025-01-15T09:00:00Z"
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_one_default_address_per_type() {
+        let db = test_db();
+        CustomerBuilder::new("CUST-1").insert::<Customer>(&db);
+        let service = CrmService::new(db.clone());
+
+        let first = service.add_address(address("cust-1", AddressType::Billing, true)).await.unwrap();
+        let shipping = service.add_address(address("cust-1", AddressType::Shipping, true)).await.unwrap();
+        let second = service.add_address(address("cust-1", AddressType::Billing, true)).await.unwrap();
+
+        let defaults = |c: &Customer| -> Vec<String> {
+            c.addresses.iter().filter(|a| a.is_default).map(|a| a.id.clone()).collect()
+        };
+        let customer = service.get_customer("cust-1").await.unwrap().unwrap();
+        assert_eq!(customer.addresses.len(), 3);
+        let mut expected = vec![shipping.id.clone(), second.id.clone()];
+        let mut actual = defaults(&customer);
+        expected.sort();
+        actual.sort();
+        assert_eq!(actual, expected);
+
+        service.set_default_address(&first.id).await.unwrap();
+        let customer = service.get_customer("cust-1").await.unwrap().unwrap();
+        let mut actual = defaults(&customer);
+        let mut expected = vec![shipping.id.clone(), first.id.clone()];
+        expected.sort();
+        actual.sort();
+        assert_eq!(actual, expected);
+
+        // Coordinates survive an edit that leaves them out
+        let mut edit = customer.addresses.iter().find(|a| a.id == second.id).unwrap().clone();
+        edit.address.line2 = Some("Suite 2".to_string());
+        edit.latitude = None;
+        edit.longitude = None;
+        let updated = service.update_address(edit).await.unwrap();
+        assert_eq!(updated.latitude, Some(39.78));
+        assert_eq!(service.get_address(&second.id).unwrap().unwrap().longitude, Some(-89.65));
+    }
+
+    #[tokio::test]
+    async fn test_last_billing_address_kept_for_open_shipments() {
+        let db = test_db();
+        CustomerBuilder::new("CUST-1").insert::<Customer>(&db);
+        ShipmentBuilder::new("SHP-1").customer("cust-1").insert::<serde_json::Value>(&db);
+        let service = CrmService::new(db.clone());
+        let billing = service.add_address(address("cust-1", AddressType::Billing, true)).await.unwrap();
+        let spare = service.add_address(address("cust-1", AddressType::Both, false)).await.unwrap();
+
+        service.delete_address(&spare.id).await.unwrap();
+        let last = service.delete_address(&billing.id).await;
+        assert!(matches!(last, Err(WmsError::Validation(_))));
+
+        db.execute("UPDATE shipments SET status = 'shipped' WHERE id = 'shp-1'", []).unwrap();
+        service.delete_address(&billing.id).await.unwrap();
+        assert!(service.get_customer("cust-1").await.unwrap().unwrap().addresses.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_update_customer_rejects_changed_addresses() {
+        let db = test_db();
+        CustomerBuilder::new("CUST-1").insert::<Customer>(&db);
+        let service = CrmService::new(db.clone());
+        service.add_address(address("cust-1", AddressType::Shipping, true)).await.unwrap();
+
+        let mut customer = service.get_customer("cust-1").await.unwrap().unwrap();
+        customer.notes = Some("Dock 4".to_string());
+        let customer = service.update_customer(customer).await.unwrap();
+        assert_eq!(customer.addresses.len(), 1);
+
+        let mut changed = customer.clone();
+        changed.addresses[0].address.city = "Peoria".to_string();
+        assert!(matches!(service.update_customer(changed).await, Err(WmsError::Validation(_))));
+    }
+
+    fn search(query: Option<&str>) -> CustomerSearchQuery {
+        CustomerSearchQuery {
+            query: query.map(str::to_string),
+            page: 1,
+            page_size: 2,
+            ..Default::default()
+        }
+    }
+
+    fn seed_search_customers(db: &Database) {
+        for (number, name, customer_type, tags, active, created) in [
+            ("CUST-1", "Acme Corp", "wholesale", r#"["vip","east"]"#, 1, "2025-01-10T09:00:00Z"),
+            ("CUST-2", "Acme Outlet", "retail", r#"["east"]"#, 1, "2025-02-10T09:00:00Z"),
+            ("CUST-3", "Beta Supply", "wholesale", r#"["vip"]"#, 1, "2025-03-10T09:00:00Z"),
+            ("CUST-4", "Acme Old", "wholesale", r#"["vip"]"#, 0, "2025-01-20T09:00:00Z"),
+        ] {
+            CustomerBuilder::new(number).company_name(name).insert::<Customer>(db);
+            db.execute(
+                "UPDATE customers SET customer_type = ?, tags = ?, is_active = ?, created_at = ? WHERE customer_number = ?",
+                params![customer_type, tags, active, created, number],
+            ).unwrap();
+        }
+    }
+
+    fn numbers(result: &SearchResult<Customer>) -> Vec<&str> {
+        result.items.iter().map(|c| c.customer_number.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_search_combines_filters_and_counts_matches() {
+        let db = test_db();
+        seed_search_customers(&db);
+        let service = CrmService::new(db);
+
+        let all = service.search_customers(search(None)).await.unwrap();
+        assert_eq!(all.total_count, 4);
+        assert_eq!(all.items.len(), 2);
+
+        let query = CustomerSearchQuery {
+            customer_type: Some(CustomerType::Wholesale),
+            tags: Some(vec!["vip".to_string()]),
+            is_active: Some(true),
+            ..search(None)
+        };
+        let result = service.search_customers(query).await.unwrap();
+        assert_eq!(result.total_count, 2);
+        assert_eq!(numbers(&result), vec!["CUST-1", "CUST-3"]);
+        assert_eq!(result.items[0].customer_type, CustomerType::Wholesale);
+
+        let query = CustomerSearchQuery {
+            tags: Some(vec!["vip".to_string(), "east".to_string()]),
+            ..search(Some("acme"))
+        };
+        assert_eq!(numbers(&service.search_customers(query).await.unwrap()), vec!["CUST-1"]);
+
+        let query = CustomerSearchQuery {
+            created_from: NaiveDate::from_ymd_opt(2025, 1, 15),
+            created_to: NaiveDate::from_ymd_opt(2025, 2, 28),
+            sort_by: CustomerSortField::CreatedAt,
+            sort_dir: SortDirection::Desc,
+            ..search(Some("acme"))
+        };
+        let result = service.search_customers(query).await.unwrap();
+        assert_eq!(result.total_count, 2);
+        assert_eq!(numbers(&result), vec!["CUST-2", "CUST-4"]);
+
+        let query = CustomerSearchQuery { is_active: Some(false), ..search(None) };
+        assert_eq!(numbers(&service.search_customers(query).await.unwrap()), vec!["CUST-4"]);
+    }
+
+    #[tokio::test]
+    async fn test_search_text_is_literal() {
+        let db = test_db();
+        seed_search_customers(&db);
+        let service = CrmService::new(db.clone());
+
+        let injected = service.search_customers(search(Some("x' OR '1'='1"))).await.unwrap();
+        assert_eq!(injected.total_count, 0);
+        assert!(injected.items.is_empty());
+
+        let dropped = service.search_customers(search(Some("'); DROP TABLE customers; --"))).await.unwrap();
+        assert_eq!(dropped.total_count, 0);
+        assert_eq!(service.search_customers(search(None)).await.unwrap().total_count, 4);
+    }
+
+    #[tokio::test]
+    async fn test_customer_activity_is_newest_first() {
+        let db = test_db();
+        CustomerBuilder::new("CUST-1").insert::<Customer>(&db);
+        ShipmentBuilder::new("SHP-1").customer("cust-1").status("shipped").insert::<serde_json::Value>(&db);
+        ShipmentBuilder::new("SHP-2").customer("cust-1").insert::<serde_json::Value>(&db);
+        DeliveryBuilder::new("DEL-1").customer("cust-1").status("delivered").insert::<serde_json::Value>(&db);
+        db.execute(
+            "UPDATE shipments SET ship_date = '2025-01-16T10:00:00Z', tracking_number = '1Z999' WHERE id = 'shp-1'",
+            [],
+        ).unwrap();
+        db.execute("UPDATE shipments SET created_at = '2025-01-20T08:00:00Z' WHERE id = 'shp-2'", []).unwrap();
+        db.execute("UPDATE deliveries SET actual_arrival_time = '2025-01-17T12:00:00Z' WHERE id = 'del-1'", []).unwrap();
+        db.execute(
+            "INSERT INTO customer_interactions (id, customer_id, interaction_type, subject, created_by, created_at)
+             VALUES ('i1', 'cust-1', 'call', 'Asked about bulk pricing', 'u1', '2025-01-18T15:00:00Z')",
+            [],
+        ).unwrap();
+        let service = CrmService::new(db);
+
+        let timeline = service.get_customer_activity("cust-1", 50).await.unwrap();
+        let kinds: Vec<ActivityKind> = timeline.iter().map(|e| e.kind).collect();
+        assert_eq!(kinds, vec![
+            ActivityKind::ShipmentCreated,
+            ActivityKind::Interaction,
+            ActivityKind::DeliveryCompleted,
+            ActivityKind::ShipmentShipped,
+            ActivityKind::ShipmentCreated,
+        ]);
+        assert_eq!(timeline[0].reference_number.as_deref(), Some("SHP-2"));
+        assert_eq!(timeline[1].summary, "call: Asked about bulk pricing");
+        assert_eq!(timeline[3].summary, "Order SHP-1 shipped, tracking 1Z999");
+
+        let latest = service.get_customer_activity("cust-1", 2).await.unwrap();
+        assert_eq!(latest.len(), 2);
+        assert_eq!(latest[1].kind, ActivityKind::Interaction);
+
+        let stats = service.get_customer_stats("cust-1").await.unwrap();
+        assert_eq!(stats.shipment_count, 2);
+        assert_eq!(stats.open_shipment_count, 1);
+        assert_eq!(stats.last_order_at.map(|t| t.to_rfc3339()).as_deref(), Some("2025-01-20T08:00:00+00:00"));
+        assert!(stats.average_days_between_orders.is_some_and(|d| (d - 4.958).abs() < 0.01));
+    }
+
+    #[tokio::test]
+    async fn test_customer_activity_without_history() {
+        let db = test_db();
+        CustomerBuilder::new("CUST-1").insert::<Customer>(&db);
+        let service = CrmService::new(db);
+
+        assert!(service.get_customer_activity("cust-1", 50).await.unwrap().is_empty());
+        let stats = service.get_customer_stats("cust-1").await.unwrap();
+        assert_eq!(stats.shipment_count, 0);
+        assert_eq!(stats.open_shipment_count, 0);
+        assert!(stats.last_order_at.is_none());
+        assert!(stats.average_days_between_orders.is_none());
+
+        let unknown = service.get_customer_activity("nobody", 50).await;
+        assert!(matches!(unknown, Err(WmsError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_validate_credit_counts_open_shipments() {
+        let db = test_db();
+        CustomerBuilder::new("CUST-1").credit_limit(1000.0).insert::<Customer>(&db);
+        CustomerBuilder::new("CUST-2").insert::<Customer>(&db);
+        ShipmentBuilder::new("SHP-1").customer("cust-1").declared_value(400.0).insert::<serde_json::Value>(&db);
+        ShipmentBuilder::new("SHP-2").customer("cust-1").status("delivered").declared_value(900.0)
+            .insert::<serde_json::Value>(&db);
+        let service = CrmService::new(db);
+
+        // Up to the limit is fine; the delivered shipment no longer counts
+        let credit = service.validate_credit("cust-1", 600.0).await.unwrap();
+        assert_eq!(credit.exposure, 400.0);
+        assert_eq!(credit.remaining(), Some(0.0));
+
+        let err = service.validate_credit("cust-1", 600.5).await.unwrap_err();
+        assert!(matches!(
+            err,
+            WmsError::CreditLimitExceeded { limit, exposure, requested }
+                if limit == 1000.0 && exposure == 400.0 && requested == 600.5
+        ));
+
+        // No limit set
+        let unlimited = service.validate_credit("cust-2", 1_000_000.0).await.unwrap();
+        assert_eq!(unlimited.remaining(), None);
+
+        assert!(matches!(service.validate_credit("nobody", 1.0).await, Err(WmsError::NotFound(_))));
+    }
+}
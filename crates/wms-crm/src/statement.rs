@@ -0,0 +1,469 @@
+//! Customer Statements
+//!
+//! Aggregates a customer's shipments, deliveries and open follow-ups over a
+//! date range and renders them as a PDF statement, with the underlying
+//! shipment lines available as CSV.
+
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use csv::Writer;
+use serde::{Deserialize, Serialize};
+use wms_core::error::{WmsError, Result};
+use wms_core::pdf::PdfGenerator;
+use wms_core::types::DateRange;
+use crate::models::Customer;
+
+/// Lowest y position used before starting a new page
+const PAGE_BOTTOM_MM: f32 = 20.0;
+
+/// Statement summary for a customer over a date range
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CustomerStatement {
+    pub customer_id: String,
+    pub customer_number: String,
+    pub customer_name: String,
+    pub period: DateRange,
+    pub currency_code: String,
+    pub shipment_count: u32,
+    pub total_packages: u32,
+    /// Sum of priced order totals; None when no shipment carries a price
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_value: Option<f64>,
+    pub deliveries_completed: u32,
+    pub deliveries_on_time: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_time_percentage: Option<f64>,
+    pub returns: u32,
+    pub open_follow_ups: Vec<OpenFollowUp>,
+    /// One entry per calendar month in the period
+    pub months: Vec<MonthlyActivity>,
+}
+
+impl CustomerStatement {
+    /// Check if anything happened during the period
+    pub fn has_activity(&self) -> bool {
+        self.shipment_count > 0 || self.deliveries_completed > 0 || self.returns > 0
+    }
+}
+
+/// Activity within one calendar month
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MonthlyActivity {
+    /// Month as YYYY-MM
+    pub month: String,
+    pub shipments: u32,
+    pub packages: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<f64>,
+    pub deliveries: u32,
+    pub on_time: u32,
+}
+
+/// Interaction with a follow-up still due
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpenFollowUp {
+    pub interaction_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subject: Option<String>,
+    pub follow_up_date: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub follow_up_notes: Option<String>,
+}
+
+/// Shipment header counted on a statement
+#[derive(Debug, Clone)]
+pub struct StatementShipment {
+    pub shipment_number: String,
+    pub date: NaiveDate,
+    pub total_packages: u32,
+    pub order_total: Option<f64>,
+}
+
+/// Delivery counted on a statement
+#[derive(Debug, Clone)]
+pub struct StatementDelivery {
+    pub delivery_number: String,
+    pub scheduled_date: NaiveDate,
+    pub status: String,
+    pub arrival_date: Option<NaiveDate>,
+}
+
+impl StatementDelivery {
+    /// Delivered on or before the scheduled date
+    pub fn is_on_time(&self) -> bool {
+        self.status == "delivered"
+            && self.arrival_date.is_some_and(|d| d <= self.scheduled_date)
+    }
+}
+
+/// Shipment line exported in the CSV variant
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatementLine {
+    pub shipment_number: String,
+    pub date: NaiveDate,
+    pub sku: String,
+    pub item_name: String,
+    pub quantity_ordered: f64,
+    pub quantity_shipped: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unit_price: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extended_price: Option<f64>,
+}
+
+/// Rendered statement file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatementExport {
+    pub statement: CustomerStatement,
+    /// Base64 encoded file data
+    pub data: String,
+    /// MIME content type
+    pub content_type: String,
+    /// Suggested filename
+    pub filename: String,
+}
+
+/// Aggregate statement figures from the records in the period
+pub fn build_statement(
+    customer: &Customer,
+    period: DateRange,
+    shipments: &[StatementShipment],
+    deliveries: &[StatementDelivery],
+    open_follow_ups: Vec<OpenFollowUp>,
+) -> CustomerStatement {
+    let mut months: Vec<MonthlyActivity> = months_in(&period)
+        .into_iter()
+        .map(|month| MonthlyActivity {
+            month,
+            shipments: 0,
+            packages: 0,
+            value: None,
+            deliveries: 0,
+            on_time: 0,
+        })
+        .collect();
+
+    for shipment in shipments.iter().filter(|s| period.contains(s.date)) {
+        if let Some(month) = month_of(&mut months, shipment.date) {
+            month.shipments += 1;
+            month.packages += shipment.total_packages;
+            if let Some(total) = shipment.order_total {
+                *month.value.get_or_insert(0.0) += total;
+            }
+        }
+    }
+
+    let delivered: Vec<&StatementDelivery> = deliveries.iter()
+        .filter(|d| d.status == "delivered" && period.contains(d.scheduled_date))
+        .collect();
+    for delivery in &delivered {
+        if let Some(month) = month_of(&mut months, delivery.scheduled_date) {
+            month.deliveries += 1;
+            if delivery.is_on_time() {
+                month.on_time += 1;
+            }
+        }
+    }
+
+    let shipment_count = months.iter().map(|m| m.shipments).sum();
+    let total_packages = months.iter().map(|m| m.packages).sum();
+    let total_value = months.iter()
+        .filter_map(|m| m.value)
+        .fold(None, |acc: Option<f64>, v| Some(acc.unwrap_or(0.0) + v));
+    let deliveries_completed = delivered.len() as u32;
+    let deliveries_on_time = delivered.iter().filter(|d| d.is_on_time()).count() as u32;
+    let on_time_percentage = (deliveries_completed > 0)
+        .then(|| deliveries_on_time as f64 / deliveries_completed as f64 * 100.0);
+    let returns = deliveries.iter()
+        .filter(|d| d.status == "returned" && period.contains(d.scheduled_date))
+        .count() as u32;
+
+    CustomerStatement {
+        customer_id: customer.id.clone(),
+        customer_number: customer.customer_number.clone(),
+        customer_name: customer.display_name(),
+        period,
+        currency_code: customer.currency_code.clone(),
+        shipment_count,
+        total_packages,
+        total_value,
+        deliveries_completed,
+        deliveries_on_time,
+        on_time_percentage,
+        returns,
+        open_follow_ups,
+        months,
+    }
+}
+
+/// Calendar months (YYYY-MM) touched by the range
+fn months_in(period: &DateRange) -> Vec<String> {
+    let mut months = Vec::new();
+    let (mut year, mut month) = (period.start.year(), period.start.month());
+
+    while (year, month) <= (period.end.year(), period.end.month()) {
+        months.push(format!("{:04}-{:02}", year, month));
+        if month == 12 {
+            year += 1;
+            month = 1;
+        } else {
+            month += 1;
+        }
+    }
+
+    months
+}
+
+fn month_of(months: &mut [MonthlyActivity], date: NaiveDate) -> Option<&mut MonthlyActivity> {
+    let key = date.format("%Y-%m").to_string();
+    months.iter_mut().find(|m| m.month == key)
+}
+
+fn format_money(value: Option<f64>, currency: &str) -> String {
+    value
+        .map(|v| format!("{} {:.2}", currency, v))
+        .unwrap_or_else(|| "-".to_string())
+}
+
+/// Render the statement as a PDF document
+pub fn render_statement_pdf(statement: &CustomerStatement) -> Vec<u8> {
+    let mut pdf = PdfGenerator::new_shipping_doc("Customer Statement");
+
+    // Header
+    pdf.add_bold_text(20.0, 265.0, "CUSTOMER STATEMENT", 18.0);
+    pdf.add_text(
+        20.0,
+        255.0,
+        &format!("{} ({})", statement.customer_name, statement.customer_number),
+        12.0,
+    );
+    pdf.add_text(
+        20.0,
+        249.0,
+        &format!("Period: {} to {}", statement.period.start, statement.period.end),
+        10.0,
+    );
+
+    // Summary
+    pdf.add_bold_text(20.0, 235.0, "Summary", 12.0);
+    let mut y = 228.0;
+    if !statement.has_activity() {
+        pdf.add_text(20.0, y, "No activity in this period.", 10.0);
+        y -= 6.0;
+    }
+
+    let on_time = statement.on_time_percentage
+        .map(|p| format!("{:.1}%", p))
+        .unwrap_or_else(|| "-".to_string());
+    let summary = [
+        ("Shipments", statement.shipment_count.to_string()),
+        ("Packages", statement.total_packages.to_string()),
+        ("Order value", format_money(statement.total_value, &statement.currency_code)),
+        ("Deliveries completed", statement.deliveries_completed.to_string()),
+        ("On-time deliveries", on_time),
+        ("Returns", statement.returns.to_string()),
+        ("Open follow-ups", statement.open_follow_ups.len().to_string()),
+    ];
+    for (label, value) in &summary {
+        pdf.add_text(20.0, y, label, 10.0);
+        pdf.add_text(80.0, y, value, 10.0);
+        y -= 6.0;
+    }
+
+    // Month-by-month table
+    y -= 6.0;
+    pdf.add_bold_text(20.0, y, "Monthly Activity", 12.0);
+    y -= 8.0;
+    month_table_header(&pdf, y);
+    y -= 11.0;
+
+    for month in &statement.months {
+        if y < PAGE_BOTTOM_MM {
+            pdf.add_page();
+            y = 260.0;
+            month_table_header(&pdf, y);
+            y -= 11.0;
+        }
+        pdf.add_text(20.0, y, &month.month, 9.0);
+        pdf.add_text(50.0, y, &month.shipments.to_string(), 9.0);
+        pdf.add_text(75.0, y, &month.packages.to_string(), 9.0);
+        pdf.add_text(100.0, y, &format_money(month.value, &statement.currency_code), 9.0);
+        pdf.add_text(145.0, y, &month.deliveries.to_string(), 9.0);
+        pdf.add_text(170.0, y, &month.on_time.to_string(), 9.0);
+        y -= 6.0;
+    }
+
+    // Open follow-ups
+    if !statement.open_follow_ups.is_empty() {
+        if y < PAGE_BOTTOM_MM + 12.0 {
+            pdf.add_page();
+            y = 260.0;
+        }
+        y -= 6.0;
+        pdf.add_bold_text(20.0, y, "Open Follow-ups", 12.0);
+        y -= 8.0;
+
+        for follow_up in &statement.open_follow_ups {
+            if y < PAGE_BOTTOM_MM {
+                pdf.add_page();
+                y = 260.0;
+            }
+            pdf.add_text(20.0, y, &follow_up.follow_up_date.format("%Y-%m-%d").to_string(), 9.0);
+            pdf.add_text(50.0, y, follow_up.subject.as_deref().unwrap_or("(no subject)"), 9.0);
+            y -= 6.0;
+        }
+    }
+
+    pdf.save_to_bytes()
+}
+
+fn month_table_header(pdf: &PdfGenerator, y: f32) {
+    pdf.draw_line(20.0, y + 5.0, 195.0, y + 5.0);
+    pdf.add_bold_text(20.0, y, "Month", 10.0);
+    pdf.add_bold_text(50.0, y, "Shipments", 10.0);
+    pdf.add_bold_text(75.0, y, "Packages", 10.0);
+    pdf.add_bold_text(100.0, y, "Value", 10.0);
+    pdf.add_bold_text(145.0, y, "Deliveries", 10.0);
+    pdf.add_bold_text(170.0, y, "On Time", 10.0);
+    pdf.draw_line(20.0, y - 5.0, 195.0, y - 5.0);
+}
+
+/// Render shipment lines as CSV
+pub fn render_statement_csv(lines: &[StatementLine]) -> Result<Vec<u8>> {
+    let mut writer = Writer::from_writer(Vec::new());
+
+    writer.write_record(&[
+        "Shipment",
+        "Date",
+        "SKU",
+        "Item",
+        "Qty Ordered",
+        "Qty Shipped",
+        "Unit Price",
+        "Extended Price",
+    ]).map_err(|e| WmsError::Export(format!("CSV write error: {}", e)))?;
+
+    for line in lines {
+        writer.write_record(&[
+            line.shipment_number.clone(),
+            line.date.to_string(),
+            line.sku.clone(),
+            line.item_name.clone(),
+            line.quantity_ordered.to_string(),
+            line.quantity_shipped.to_string(),
+            line.unit_price.map(|p| format!("{:.2}", p)).unwrap_or_default(),
+            line.extended_price.map(|p| format!("{:.2}", p)).unwrap_or_default(),
+        ]).map_err(|e| WmsError::Export(format!("CSV write error: {}", e)))?;
+    }
+
+    writer.into_inner()
+        .map_err(|e| WmsError::Export(format!("CSV flush error: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, m, d).unwrap()
+    }
+
+    fn customer() -> Customer {
+        serde_json::from_value(serde_json::json!({
+            "id": "c1",
+            "customer_number": "CUS-000001",
+            "company_name": "Acme Corp",
+            "created_at": "2025-01-01T00:00:00Z",
+        }))
+        .unwrap()
+    }
+
+    fn delivery(number: &str, scheduled: NaiveDate, status: &str, arrived: Option<NaiveDate>) -> StatementDelivery {
+        StatementDelivery {
+            delivery_number: number.to_string(),
+            scheduled_date: scheduled,
+            status: status.to_string(),
+            arrival_date: arrived,
+        }
+    }
+
+    #[test]
+    fn test_empty_range_is_no_activity_statement() {
+        let statement = build_statement(
+            &customer(),
+            DateRange::new(date(3, 1), date(3, 31)),
+            &[],
+            &[],
+            Vec::new(),
+        );
+
+        assert!(!statement.has_activity());
+        assert_eq!(statement.months.len(), 1);
+        assert!(statement.total_value.is_none());
+        assert!(statement.on_time_percentage.is_none());
+
+        let pdf = render_statement_pdf(&statement);
+        assert!(pdf.starts_with(b"%PDF"));
+    }
+
+    #[test]
+    fn test_monthly_aggregation_and_on_time() {
+        let shipments = vec![
+            StatementShipment { shipment_number: "S1".into(), date: date(1, 5), total_packages: 2, order_total: Some(100.0) },
+            StatementShipment { shipment_number: "S2".into(), date: date(1, 20), total_packages: 1, order_total: None },
+            StatementShipment { shipment_number: "S3".into(), date: date(3, 2), total_packages: 4, order_total: Some(50.0) },
+        ];
+        let deliveries = vec![
+            delivery("D1", date(1, 6), "delivered", Some(date(1, 6))),
+            delivery("D2", date(1, 21), "delivered", Some(date(1, 23))),
+            delivery("D3", date(3, 3), "delivered", Some(date(3, 2))),
+            delivery("D4", date(3, 10), "returned", None),
+        ];
+
+        let statement = build_statement(
+            &customer(),
+            DateRange::new(date(1, 1), date(3, 31)),
+            &shipments,
+            &deliveries,
+            Vec::new(),
+        );
+
+        assert_eq!(statement.customer_name, "Acme Corp");
+        assert_eq!(statement.shipment_count, 3);
+        assert_eq!(statement.total_packages, 7);
+        assert_eq!(statement.total_value, Some(150.0));
+        assert_eq!(statement.deliveries_completed, 3);
+        assert_eq!(statement.deliveries_on_time, 2);
+        assert_eq!(statement.returns, 1);
+
+        // February has no activity but still gets a row
+        let months: Vec<&str> = statement.months.iter().map(|m| m.month.as_str()).collect();
+        assert_eq!(months, ["2025-01", "2025-02", "2025-03"]);
+        assert_eq!(statement.months[0].shipments, 2);
+        assert_eq!(statement.months[0].on_time, 1);
+        assert_eq!(statement.months[1].shipments, 0);
+        assert!(statement.months[1].value.is_none());
+
+        let pct = statement.on_time_percentage.unwrap();
+        assert!((pct - 66.67).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_csv_lines() {
+        let lines = vec![StatementLine {
+            shipment_number: "SHP-00000001".into(),
+            date: date(1, 5),
+            sku: "SKU-001".into(),
+            item_name: "Widget".into(),
+            quantity_ordered: 3.0,
+            quantity_shipped: 3.0,
+            unit_price: Some(2.5),
+            extended_price: Some(7.5),
+        }];
+
+        let csv = String::from_utf8(render_statement_csv(&lines).unwrap()).unwrap();
+        let mut rows = csv.lines();
+
+        assert!(rows.next().unwrap().starts_with("Shipment,Date,SKU"));
+        assert_eq!(rows.next().unwrap(), "SHP-00000001,2025-01-05,SKU-001,Widget,3,3,2.50,7.50");
+    }
+}
@@ -0,0 +1,95 @@
+//! Driver Breadcrumbs
+//!
+//! The trail of recorded driver locations drawn on the delivery map.
+//! Phones report far more often than a map needs, so a trail keeps at most
+//! a few readings per minute, spread over the minute, and drops readings
+//! whose accuracy is too poor to place the driver on the right street.
+//! Old readings are purged once past the retention period.
+
+use chrono::{DateTime, Utc};
+use crate::models::DriverLocation;
+
+/// Readings kept per minute of a breadcrumb trail
+pub const BREADCRUMB_POINTS_PER_MINUTE: usize = 6;
+
+/// Readings less accurate than this are left off breadcrumb trails
+pub const BREADCRUMB_MAX_ACCURACY_METERS: f64 = 50.0;
+
+/// Days driver locations are kept before the scheduled purge removes them
+pub const DRIVER_LOCATION_RETENTION_DAYS: u32 = 90;
+
+/// Seconds a reading may be stamped ahead of the clock, for device skew
+pub const LOCATION_CLOCK_SKEW_SECONDS: i64 = 60;
+
+/// Thin time-ordered readings to at most `per_minute` in each calendar
+/// minute, evenly spread, after dropping readings with a reported accuracy
+/// worse than `max_accuracy_meters`. Readings without an accuracy are kept.
+pub fn downsample(
+    readings: Vec<DriverLocation>,
+    per_minute: usize,
+    max_accuracy_meters: f64,
+) -> Vec<DriverLocation> {
+    let minute = |at: &DateTime<Utc>| at.timestamp().div_euclid(60);
+    let accurate: Vec<DriverLocation> = readings.into_iter()
+        .filter(|r| r.accuracy_meters.is_none_or(|a| a <= max_accuracy_meters))
+        .collect();
+
+    let mut trail = Vec::with_capacity(accurate.len());
+    for bucket in accurate.chunk_by(|a, b| minute(&a.recorded_at) == minute(&b.recorded_at)) {
+        if bucket.len() <= per_minute {
+            trail.extend_from_slice(bucket);
+        } else {
+            trail.extend((0..per_minute).map(|i| bucket[i * bucket.len() / per_minute].clone()));
+        }
+    }
+    trail
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use crate::models::GeoPoint;
+
+    fn reading(at: DateTime<Utc>, accuracy_meters: Option<f64>) -> DriverLocation {
+        DriverLocation {
+            id: at.timestamp().to_string(),
+            user_id: "u1".to_string(),
+            route_id: None,
+            location: GeoPoint::new(39.78, -89.65),
+            accuracy_meters,
+            speed_kmh: None,
+            heading: None,
+            recorded_at: at,
+        }
+    }
+
+    #[test]
+    fn test_downsample_caps_points_per_minute() {
+        let start = DateTime::parse_from_rfc3339("2025-03-03T09:00:00Z").unwrap().with_timezone(&Utc);
+        // One reading a second for two minutes, then a single one
+        let mut readings: Vec<DriverLocation> = (0..120)
+            .map(|s| reading(start + Duration::seconds(s), Some(5.0)))
+            .collect();
+        readings.push(reading(start + Duration::seconds(150), None));
+
+        let trail = downsample(readings, 6, BREADCRUMB_MAX_ACCURACY_METERS);
+
+        assert_eq!(trail.len(), 13);
+        let first_minute: Vec<i64> = trail[..6].iter().map(|r| (r.recorded_at - start).num_seconds()).collect();
+        assert_eq!(first_minute, vec![0, 10, 20, 30, 40, 50]);
+    }
+
+    #[test]
+    fn test_downsample_drops_inaccurate_readings() {
+        let start = DateTime::parse_from_rfc3339("2025-03-03T09:00:00Z").unwrap().with_timezone(&Utc);
+        let readings = vec![
+            reading(start, Some(8.0)),
+            reading(start + Duration::seconds(20), Some(400.0)),
+            reading(start + Duration::seconds(40), Some(50.0)),
+        ];
+
+        let kept: Vec<Option<f64>> = downsample(readings, 6, 50.0).iter().map(|r| r.accuracy_meters).collect();
+        assert_eq!(kept, vec![Some(8.0), Some(50.0)]);
+    }
+}
@@ -0,0 +1,186 @@
+//! Route ETAs
+//!
+//! Arrival estimates for the stops left on a route, worked out two ways.
+//! The naive estimate is the route's plan: leave at the planned (or actual)
+//! start, drive at the optimizer's speed and spend its service time at each
+//! stop. The adjusted estimate starts from the driver's last departure, so
+//! overruns at finished stops carry forward, and adds what the plan leaves
+//! out: the driver's scheduled breaks and waits for delivery windows to
+//! open.
+
+use chrono::{DateTime, Duration, NaiveTime, Utc};
+use crate::models::{Delivery, DeliveryRoute, DeliveryStatus, RouteBreak};
+use crate::routing::RouteOptimizer;
+
+/// Estimates for one stop left on a route
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct StopEta {
+    pub naive: Option<DateTime<Utc>>,
+    pub adjusted: Option<DateTime<Utc>>,
+    pub break_minutes: u32,
+    pub wait_minutes: u32,
+}
+
+/// Estimates for the stops left on a route
+#[derive(Debug, Clone)]
+pub(crate) struct RouteEtas {
+    /// Minutes behind the plan at the last finished stop
+    pub delay_minutes: i64,
+    /// One per unfinished stop, in driving order
+    pub stops: Vec<StopEta>,
+}
+
+/// Estimate the unfinished stops of `route` as of `now`. Breaks that start
+/// before the driver's last departure are taken to be behind them.
+pub(crate) fn estimate_route(
+    optimizer: &RouteOptimizer,
+    route: &DeliveryRoute,
+    breaks: &[RouteBreak],
+    now: DateTime<Utc>,
+) -> RouteEtas {
+    let service = Duration::minutes(optimizer.service_time_minutes() as i64);
+    let plan_start = route.planned_start.or(route.actual_start).unwrap_or(now);
+
+    // The plan, for every stop
+    let mut position = route.start_location;
+    let mut clock = plan_start;
+    let planned: Vec<Option<DateTime<Utc>>> = route.deliveries.iter()
+        .map(|delivery| {
+            let (Some(from), Some(to)) = (position, delivery.location) else {
+                return None;
+            };
+            let arrival = clock + minutes(optimizer.travel_minutes(from, to));
+            position = Some(to);
+            clock = arrival + service;
+            Some(arrival)
+        })
+        .collect();
+
+    let last_finished = route.deliveries.iter()
+        .enumerate()
+        .rev()
+        .find(|(_, d)| d.status.is_terminal() && d.location.is_some());
+    let (mut position, mut cursor, delay_minutes) = match last_finished {
+        Some((i, delivery)) => {
+            let departed = delivery.actual_departure.unwrap_or(now);
+            let delay = planned[i].map_or(0, |arrival| (departed - (arrival + service)).num_minutes());
+            (delivery.location, departed, delay)
+        }
+        None => {
+            let started = route.actual_start.unwrap_or(plan_start);
+            (route.start_location, started, (started - plan_start).num_minutes())
+        }
+    };
+
+    let mut pending: Vec<&RouteBreak> = breaks.iter().filter(|b| b.starts_at >= cursor).collect();
+    pending.sort_by_key(|b| b.starts_at);
+    let mut pending = pending.into_iter().peekable();
+
+    let stops = route.deliveries.iter()
+        .zip(planned)
+        .filter(|(delivery, _)| !delivery.status.is_terminal())
+        .map(|(delivery, naive)| {
+            let mut eta = StopEta { naive, adjusted: None, break_minutes: 0, wait_minutes: 0 };
+            let (Some(from), Some(to)) = (position, delivery.location) else {
+                return eta;
+            };
+
+            let mut arrival = match delivery.actual_arrival.filter(|_| delivery.status == DeliveryStatus::Arrived) {
+                Some(arrived) => arrived,
+                None => {
+                    let mut arrival = cursor + minutes(optimizer.travel_minutes(from, to));
+                    // Breaks due before the driver gets there are taken on the way
+                    while let Some(due) = pending.next_if(|b| b.starts_at < arrival) {
+                        arrival += Duration::minutes(due.duration_minutes as i64);
+                        eta.break_minutes += due.duration_minutes;
+                    }
+                    arrival.max(now)
+                }
+            };
+            if let Some(opens) = window_start(route, delivery).filter(|opens| arrival < *opens) {
+                eta.wait_minutes = (opens - arrival).num_minutes() as u32;
+                arrival = opens;
+            }
+
+            eta.adjusted = Some(arrival);
+            position = Some(to);
+            cursor = (arrival + service).max(now);
+            eta
+        })
+        .collect();
+
+    RouteEtas { delay_minutes, stops }
+}
+
+fn minutes(minutes: f64) -> Duration {
+    Duration::milliseconds((minutes * 60_000.0).round() as i64)
+}
+
+/// When the stop's delivery window opens on the route's day
+fn window_start(route: &DeliveryRoute, delivery: &Delivery) -> Option<DateTime<Utc>> {
+    let time = NaiveTime::parse_from_str(delivery.time_window_start.as_deref()?, "%H:%M").ok()?;
+    Some(route.route_date.date_naive().and_time(time).and_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn at(time: &str) -> DateTime<Utc> {
+        format!("2025-03-03T{}:00Z", time).parse().unwrap()
+    }
+
+    fn stop(id: &str, lat: f64, window_start: Option<&str>) -> serde_json::Value {
+        json!({
+            "id": id,
+            "delivery_number": id,
+            "status": "en_route",
+            "delivery_address": {
+                "name": id,
+                "address": { "line1": "1 Main St", "city": "Springfield", "state": "IL", "postal_code": "62701", "country": "US" }
+            },
+            "location": { "lat": lat, "lng": -89.65 },
+            "time_window_start": window_start,
+            "scheduled_date": "2025-03-03T00:00:00Z",
+            "created_at": "2025-03-03T00:00:00Z"
+        })
+    }
+
+    #[test]
+    fn test_breaks_and_windows_push_back_adjusted_etas() {
+        let route: DeliveryRoute = serde_json::from_value(json!({
+            "id": "r1",
+            "route_name": "RTE-1",
+            "route_date": "2025-03-03T00:00:00Z",
+            "status": "in_progress",
+            "start_location": { "lat": 39.78, "lng": -89.65 },
+            "planned_start": "2025-03-03T09:00:00Z",
+            "actual_start": "2025-03-03T09:00:00Z",
+            "created_by": "u1",
+            "created_at": "2025-03-03T00:00:00Z",
+            "deliveries": [stop("first", 39.80, None), stop("second", 39.85, Some("10:00"))]
+        })).unwrap();
+        let lunch = RouteBreak {
+            id: "b1".to_string(),
+            route_id: "r1".to_string(),
+            starts_at: at("09:02"),
+            duration_minutes: 15,
+        };
+
+        let naive = estimate_route(&RouteOptimizer::new(), &route, &[], at("09:00"));
+        assert_eq!(naive.delay_minutes, 0);
+        assert_eq!(naive.stops[0].adjusted, naive.stops[0].naive);
+
+        let etas = estimate_route(&RouteOptimizer::new(), &route, &[lunch], at("09:00"));
+        let first = &etas.stops[0];
+        assert_eq!(first.break_minutes, 15);
+        assert_eq!(first.adjusted.unwrap() - first.naive.unwrap(), Duration::minutes(15));
+
+        // Getting there early means waiting for the window
+        let second = &etas.stops[1];
+        assert_eq!(second.adjusted, Some(at("10:00")));
+        assert!(second.wait_minutes > 0);
+        assert!(second.naive.unwrap() < at("10:00"));
+    }
+}
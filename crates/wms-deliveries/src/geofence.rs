@@ -1,11 +1,21 @@
 //! Geofencing Module
 //! 
 //! Provides geospatial analysis for delivery zone detection using the geo crate.
+//!
+//! Tracked geofences remember whether the last reading was inside and since
+//! when, so a stay inside can be told apart from a single GPS reading that
+//! clips the edge: `Dwell` fires once the point has stayed inside for the
+//! dwell duration.
 
+use std::collections::HashMap;
+use chrono::{DateTime, Duration, Utc};
 use geo::{Contains, Point, Polygon, LineString, coord};
 use serde::{Deserialize, Serialize};
 use crate::models::GeoPoint;
 
+/// Seconds inside a geofence before `Dwell` fires
+pub const DEFAULT_DWELL_SECONDS: i64 = 180;
+
 /// Result of a geofence check
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GeofenceResult {
@@ -29,6 +39,18 @@ pub enum GeofenceTrigger {
     Dwell,
 }
 
+/// Tracked state of one geofence
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct GeofenceState {
+    /// Whether the last reading was inside
+    pub is_inside: bool,
+    /// When the current stay inside began
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entered_at: Option<DateTime<Utc>>,
+    /// Whether `Dwell` already fired for the current stay
+    pub dwell_reported: bool,
+}
+
 /// Geofence geometry types
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
@@ -43,19 +65,39 @@ pub enum GeofenceGeometry {
 }
 
 /// Geofence checker for spatial analysis
+#[derive(Debug, Clone)]
 pub struct GeofenceChecker {
-    /// Track previous states for enter/exit detection
-    previous_states: std::collections::HashMap<String, bool>,
+    /// Track previous states for enter/exit/dwell detection
+    previous_states: HashMap<String, GeofenceState>,
+    /// Time inside before `Dwell` fires
+    dwell_duration: Duration,
 }
 
 impl GeofenceChecker {
     /// Create a new geofence checker
     pub fn new() -> Self {
         Self {
-            previous_states: std::collections::HashMap::new(),
+            previous_states: HashMap::new(),
+            dwell_duration: Duration::seconds(DEFAULT_DWELL_SECONDS),
         }
     }
     
+    /// Configure how long a point must stay inside before `Dwell` fires
+    pub fn with_dwell_duration(mut self, duration: Duration) -> Self {
+        self.dwell_duration = duration;
+        self
+    }
+    
+    /// Tracked state of a geofence, if it has been checked
+    pub fn state(&self, geofence_id: &str) -> Option<GeofenceState> {
+        self.previous_states.get(geofence_id).copied()
+    }
+    
+    /// Resume tracking a geofence from a saved state
+    pub fn restore(&mut self, geofence_id: &str, state: GeofenceState) {
+        self.previous_states.insert(geofence_id.to_string(), state);
+    }
+    
     /// Check if a point is inside a circular geofence
     pub fn check_circle(
         &self,
@@ -115,12 +157,15 @@ impl GeofenceChecker {
         }
     }
     
-    /// Check geofence with enter/exit tracking
+    /// Check geofence with enter/exit/dwell tracking. `at` is when the
+    /// point was recorded; `Dwell` fires once per stay, on the first
+    /// reading at least the dwell duration after entering.
     pub fn check_with_tracking(
         &mut self,
         geofence_id: &str,
         point: GeoPoint,
         geometry: &GeofenceGeometry,
+        at: DateTime<Utc>,
     ) -> GeofenceResult {
         let is_inside = match geometry {
             GeofenceGeometry::Circle { center, radius_meters } => {
@@ -131,14 +176,23 @@ impl GeofenceChecker {
             }
         };
         
-        let previous = self.previous_states.get(geofence_id).copied().unwrap_or(false);
-        let trigger = match (previous, is_inside) {
-            (false, true) => Some(GeofenceTrigger::Enter),
-            (true, false) => Some(GeofenceTrigger::Exit),
-            _ => None,
+        let previous = self.state(geofence_id).unwrap_or_default();
+        let (trigger, state) = match (previous.is_inside, is_inside) {
+            (false, true) => (
+                Some(GeofenceTrigger::Enter),
+                GeofenceState { is_inside, entered_at: Some(at), dwell_reported: false },
+            ),
+            (true, false) => (Some(GeofenceTrigger::Exit), GeofenceState::default()),
+            (true, true) => {
+                let dwelled = !previous.dwell_reported
+                    && previous.entered_at.is_some_and(|entered| at - entered >= self.dwell_duration);
+                let state = GeofenceState { dwell_reported: previous.dwell_reported || dwelled, ..previous };
+                (dwelled.then_some(GeofenceTrigger::Dwell), state)
+            }
+            (false, false) => (None, previous),
         };
         
-        self.previous_states.insert(geofence_id.to_string(), is_inside);
+        self.previous_states.insert(geofence_id.to_string(), state);
         
         let distance = match geometry {
             GeofenceGeometry::Circle { center, radius_meters } => {
@@ -251,22 +305,50 @@ mod tests {
             radius_meters: 100.0,
         };
         
+        let now = Utc::now();
+        
         // Start outside
         let outside = GeoPoint::new(40.7200, -74.0100);
-        let result1 = checker.check_with_tracking("test", outside, &geofence);
+        let result1 = checker.check_with_tracking("test", outside, &geofence, now);
         assert!(!result1.is_inside);
         assert!(result1.trigger_type.is_none());
         
         // Enter
         let inside = GeoPoint::new(40.7129, -74.0061);
-        let result2 = checker.check_with_tracking("test", inside, &geofence);
+        let result2 = checker.check_with_tracking("test", inside, &geofence, now);
         assert!(result2.is_inside);
         assert_eq!(result2.trigger_type, Some(GeofenceTrigger::Enter));
         
         // Exit
-        let result3 = checker.check_with_tracking("test", outside, &geofence);
+        let result3 = checker.check_with_tracking("test", outside, &geofence, now);
         assert!(!result3.is_inside);
         assert_eq!(result3.trigger_type, Some(GeofenceTrigger::Exit));
     }
+    
+    #[test]
+    fn test_dwell_fires_once_after_staying_inside() {
+        let mut checker = GeofenceChecker::new().with_dwell_duration(Duration::minutes(3));
+        let geofence = GeofenceGeometry::Circle {
+            center: GeoPoint::new(40.7128, -74.0060),
+            radius_meters: 100.0,
+        };
+        let inside = GeoPoint::new(40.7129, -74.0061);
+        let start = Utc::now();
+        let at = |minutes: i64| start + Duration::minutes(minutes);
+        
+        let triggers: Vec<Option<GeofenceTrigger>> = [0, 1, 3, 4]
+            .into_iter()
+            .map(|minute| checker.check_with_tracking("test", inside, &geofence, at(minute)).trigger_type)
+            .collect();
+        assert_eq!(triggers, vec![Some(GeofenceTrigger::Enter), None, Some(GeofenceTrigger::Dwell), None]);
+        
+        // A restored checker carries on from the saved stay
+        let saved = checker.state("test").unwrap();
+        assert_eq!(saved.entered_at, Some(start));
+        let mut resumed = GeofenceChecker::new();
+        resumed.restore("other", GeofenceState { dwell_reported: false, ..saved });
+        let result = resumed.check_with_tracking("other", inside, &geofence, at(5));
+        assert_eq!(result.trigger_type, Some(GeofenceTrigger::Dwell));
+    }
 }
 
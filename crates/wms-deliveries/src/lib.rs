@@ -1,18 +1,48 @@
 //! WMS Deliveries Module
 //! 
 //! Provides delivery and logistics functionality including:
-//! - Delivery route management
+//! - Delivery route management (persisted, optimized multi-stop routes,
+//!   driver manifests and odometer readings)
 //! - Vehicle routing problem (VRP) optimization
-//! - Geofencing and location tracking
-//! - Driver management
+//! - Fleet planning that splits stops between vehicles by capacity and
+//!   shift length
+//! - Routes ordered to keep delivery time windows, with missed windows
+//!   reported and a configurable limit on them
+//! - Geofencing and location tracking, with arrival detected once the
+//!   driver stays inside the delivery's geofence
+//! - Proof of delivery: signatures, photos and notes captured at the door
+//! - Driver management, with breadcrumb trails of recorded locations
+//! - Route progress with arrival estimates that allow for breaks,
+//!   delivery windows and running delays
+//! - Customer notifications with arrival windows
 
 mod models;
 mod service;
 mod routing;
+mod eta;
+mod windows;
+mod proofs;
+mod breadcrumbs;
 mod geofence;
+mod notifications;
 
 pub use models::*;
 pub use service::DeliveryService;
-pub use routing::{RouteOptimizer, OptimizedRoute};
-pub use geofence::{GeofenceChecker, GeofenceResult};
+pub use routing::{
+    RouteOptimizer, OptimizedRoute, TimeWindow, WindowViolation, DEFAULT_LATE_PENALTY_KM_PER_MINUTE,
+    FleetPlan, StopDemand, UnassignedReason, UnassignedStop, VehicleRoute,
+};
+pub use windows::DeliveryWindowSettings;
+pub use breadcrumbs::{
+    downsample, BREADCRUMB_MAX_ACCURACY_METERS, BREADCRUMB_POINTS_PER_MINUTE, DRIVER_LOCATION_RETENTION_DAYS,
+    LOCATION_CLOCK_SKEW_SECONDS,
+};
+pub use proofs::{DeliveryProof, ProofType, DELIVERY_OWNER, MAX_PROOF_BYTES};
+pub use geofence::{
+    GeofenceChecker, GeofenceGeometry, GeofenceResult, GeofenceState, GeofenceTrigger, DEFAULT_DWELL_SECONDS,
+};
+pub use notifications::{
+    DeliveryNotification, NotificationChannel, NotificationRule, NotificationTrigger,
+    ETA_WINDOW_BUFFER_MINUTES, PLACEHOLDERS, pod_summary, render_template,
+};
 
@@ -1,37 +1,10 @@
 //! Delivery Data Models
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
+use wms_core::custom_fields::CustomFieldValues;
 use wms_core::types::Address;
-
-/// Geographic point (latitude/longitude)
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
-pub struct GeoPoint {
-    pub lat: f64,
-    pub lng: f64,
-}
-
-impl GeoPoint {
-    pub fn new(lat: f64, lng: f64) -> Self {
-        Self { lat, lng }
-    }
-    
-    /// Calculate distance to another point in kilometers (Haversine formula)
-    pub fn distance_to(&self, other: &GeoPoint) -> f64 {
-        const EARTH_RADIUS_KM: f64 = 6371.0;
-        
-        let lat1 = self.lat.to_radians();
-        let lat2 = other.lat.to_radians();
-        let dlat = (other.lat - self.lat).to_radians();
-        let dlng = (other.lng - self.lng).to_radians();
-        
-        let a = (dlat / 2.0).sin().powi(2) 
-            + lat1.cos() * lat2.cos() * (dlng / 2.0).sin().powi(2);
-        let c = 2.0 * a.sqrt().asin();
-        
-        EARTH_RADIUS_KM * c
-    }
-}
+pub use wms_core::types::GeoPoint;
 
 /// Individual delivery/stop
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -73,9 +46,18 @@ pub struct Delivery {
     pub delivery_notes: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub failure_reason: Option<String>,
+    /// Set when a temperature reading on the delivery was out of range
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature_warning: Option<String>,
+    /// Values of the deployment's custom delivery fields
+    #[serde(default, skip_serializing_if = "CustomFieldValues::is_empty")]
+    pub custom_fields: CustomFieldValues,
     pub created_at: DateTime<Utc>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub updated_at: Option<DateTime<Utc>>,
+    /// Row version; edits must send back the version they were based on
+    #[serde(default)]
+    pub version: i64,
 }
 
 fn default_geofence_radius() -> f64 {
@@ -113,10 +95,60 @@ impl Default for DeliveryStatus {
     }
 }
 
+impl DeliveryStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Assigned => "assigned",
+            Self::EnRoute => "en_route",
+            Self::Arrived => "arrived",
+            Self::Delivered => "delivered",
+            Self::Failed => "failed",
+            Self::Returned => "returned",
+            Self::Cancelled => "cancelled",
+        }
+    }
+    
+    /// Parse a stored status. Older rows store variants without
+    /// underscores (e.g. "enroute").
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "pending" => Some(Self::Pending),
+            "assigned" => Some(Self::Assigned),
+            "en_route" | "enroute" => Some(Self::EnRoute),
+            "arrived" => Some(Self::Arrived),
+            "delivered" => Some(Self::Delivered),
+            "failed" => Some(Self::Failed),
+            "returned" => Some(Self::Returned),
+            "cancelled" => Some(Self::Cancelled),
+            _ => None,
+        }
+    }
+    
+    /// Whether the delivery is finished, successfully or not
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, Self::Delivered | Self::Failed | Self::Returned | Self::Cancelled)
+    }
+}
+
+/// Which deliveries to list; unset fields match everything
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DeliveryQuery {
+    #[serde(default)]
+    pub status: Option<DeliveryStatus>,
+    /// Calendar day the delivery is scheduled for
+    #[serde(default)]
+    pub date: Option<NaiveDate>,
+    #[serde(default)]
+    pub route_id: Option<String>,
+}
+
 /// Delivery route (collection of stops)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DeliveryRoute {
     pub id: String,
+    #[serde(default)]
+    pub route_number: String,
     pub route_name: String,
     pub route_date: DateTime<Utc>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -142,6 +174,12 @@ pub struct DeliveryRoute {
     pub total_duration_minutes: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub optimization_score: Option<f64>,
+    /// Odometer reading when the route started
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_odometer_km: Option<f64>,
+    /// Odometer reading when the route completed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_odometer_km: Option<f64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub notes: Option<String>,
     pub created_by: String,
@@ -151,6 +189,44 @@ pub struct DeliveryRoute {
     pub deliveries: Vec<Delivery>,
 }
 
+/// Driver's printout of a route: its stops in driving order
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RouteManifest {
+    pub route_id: String,
+    pub route_number: String,
+    pub route_date: DateTime<Utc>,
+    pub status: RouteStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub driver_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vehicle_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_distance_km: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_duration_minutes: Option<u32>,
+    pub stops: Vec<ManifestStop>,
+}
+
+/// One stop on a route manifest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestStop {
+    /// Position on the route, from 1
+    pub sequence: u32,
+    pub delivery_id: String,
+    pub delivery_number: String,
+    pub status: DeliveryStatus,
+    pub delivery_address: DeliveryAddress,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<GeoPoint>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_window_start: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_window_end: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instructions: Option<String>,
+    pub signature_required: bool,
+}
+
 /// Route status
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
@@ -168,6 +244,91 @@ impl Default for RouteStatus {
     }
 }
 
+impl RouteStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Planning => "planning",
+            Self::Assigned => "assigned",
+            Self::InProgress => "in_progress",
+            Self::Completed => "completed",
+            Self::Cancelled => "cancelled",
+        }
+    }
+    
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "planning" => Some(Self::Planning),
+            "assigned" => Some(Self::Assigned),
+            "in_progress" => Some(Self::InProgress),
+            "completed" => Some(Self::Completed),
+            "cancelled" => Some(Self::Cancelled),
+            _ => None,
+        }
+    }
+    
+    /// Whether stops can still be added, removed or re-sequenced
+    pub fn is_planned(&self) -> bool {
+        matches!(self, Self::Planning | Self::Assigned)
+    }
+}
+
+/// A break the driver is scheduled to take on a route
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RouteBreak {
+    #[serde(default)]
+    pub id: String,
+    #[serde(default)]
+    pub route_id: String,
+    pub starts_at: DateTime<Utc>,
+    pub duration_minutes: u32,
+}
+
+/// How far along an in-progress route is, with fresh arrival estimates
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RouteProgress {
+    pub route_id: String,
+    pub route_number: String,
+    pub status: RouteStatus,
+    pub completed_stops: u32,
+    pub total_stops: u32,
+    /// Minutes the driver is behind the plan as of their last finished
+    /// stop; negative when ahead
+    pub delay_minutes: i64,
+    /// Stops still to do, in driving order
+    pub remaining: Vec<StopProgress>,
+    pub calculated_at: DateTime<Utc>,
+}
+
+impl RouteProgress {
+    /// The stop the driver is heading to
+    pub fn next_stop(&self) -> Option<&StopProgress> {
+        self.remaining.first()
+    }
+}
+
+/// A stop still to do on a route
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StopProgress {
+    pub delivery_id: String,
+    pub delivery_number: String,
+    pub status: DeliveryStatus,
+    /// Stops before this one; 0 for the next stop
+    pub stops_away: u32,
+    /// Estimated arrival from the driver's last finished stop, including
+    /// breaks and waits for delivery windows; None without coordinates
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub eta: Option<DateTime<Utc>>,
+    /// Arrival in the route's plan: driving and service time only
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub naive_eta: Option<DateTime<Utc>>,
+    /// Scheduled breaks taken on the way to this stop
+    #[serde(default)]
+    pub break_minutes: u32,
+    /// Waiting at this stop for its delivery window to open
+    #[serde(default)]
+    pub wait_minutes: u32,
+}
+
 /// Vehicle
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Vehicle {
@@ -182,6 +343,9 @@ pub struct Vehicle {
     pub capacity_kg: Option<f64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub capacity_m3: Option<f64>,
+    /// Longest route the vehicle may drive, in minutes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shift_minutes: Option<u32>,
     pub is_active: bool,
     pub created_at: DateTime<Utc>,
 }
@@ -196,6 +360,18 @@ pub enum VehicleType {
     Motorcycle,
 }
 
+impl VehicleType {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "car" => Some(Self::Car),
+            "van" => Some(Self::Van),
+            "truck" => Some(Self::Truck),
+            "motorcycle" => Some(Self::Motorcycle),
+            _ => None,
+        }
+    }
+}
+
 /// Driver location update
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DriverLocation {
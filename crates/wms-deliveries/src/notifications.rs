@@ -0,0 +1,231 @@
+//! Delivery Notifications
+//!
+//! Customers are told when their delivery leaves (with an arrival window),
+//! when the driver finishes the stop before theirs, and once it has been
+//! delivered. Each trigger has SMS and email templates in
+//! `notification_rules` with `{{placeholders}}` filled per delivery. Messages
+//! go to the phone and email on the delivery address and are queued in
+//! `delivery_notifications` for the sender; customers who opted out get
+//! nothing.
+
+use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Minutes either side of the estimated arrival in the window customers
+/// are given
+pub const ETA_WINDOW_BUFFER_MINUTES: i64 = 30;
+
+/// Placeholders templates may use
+pub const PLACEHOLDERS: [&str; 9] = [
+    "customer_name",
+    "delivery_number",
+    "window_start",
+    "window_end",
+    "eta",
+    "stops_away",
+    "delivered_at",
+    "signed_by",
+    "pod_summary",
+];
+
+/// When a customer is notified
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationTrigger {
+    /// The route was dispatched; sends the arrival window
+    RouteStarted,
+    /// The driver finished the stop before this one
+    UpNext,
+    /// Delivered, with the proof of delivery summary
+    Delivered,
+}
+
+impl NotificationTrigger {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::RouteStarted => "route_started",
+            Self::UpNext => "up_next",
+            Self::Delivered => "delivered",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "route_started" => Some(Self::RouteStarted),
+            "up_next" => Some(Self::UpNext),
+            "delivered" => Some(Self::Delivered),
+            _ => None,
+        }
+    }
+}
+
+/// How a notification reaches the customer
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum NotificationChannel {
+    Sms,
+    Email,
+}
+
+impl NotificationChannel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Sms => "sms",
+            Self::Email => "email",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "sms" => Some(Self::Sms),
+            "email" => Some(Self::Email),
+            _ => None,
+        }
+    }
+}
+
+/// Templates for one trigger. A channel without a template is not used.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationRule {
+    pub trigger: NotificationTrigger,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sms_template: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email_subject: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email_template: Option<String>,
+    #[serde(default = "default_true")]
+    pub is_active: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl NotificationRule {
+    /// Placeholders in the templates that `render_template` would not fill
+    pub fn unknown_placeholders(&self) -> Vec<String> {
+        [&self.sms_template, &self.email_subject, &self.email_template]
+            .into_iter()
+            .flatten()
+            .flat_map(|template| placeholders(template))
+            .filter(|name| !PLACEHOLDERS.contains(&name.as_str()))
+            .collect()
+    }
+}
+
+/// A message queued for a customer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeliveryNotification {
+    pub id: String,
+    pub delivery_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub route_id: Option<String>,
+    pub trigger: NotificationTrigger,
+    pub channel: NotificationChannel,
+    /// Phone number or email address
+    pub recipient: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subject: Option<String>,
+    pub body: String,
+    /// queued, sent or failed
+    pub status: String,
+    pub created_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sent_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Names of the `{{placeholders}}` in a template
+fn placeholders(template: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        names.push(rest[start + 2..start + 2 + end].trim().to_string());
+        rest = &rest[start + 2 + end + 2..];
+    }
+    names
+}
+
+/// Fill `{{placeholders}}` from `values`. Placeholders without a value are
+/// left blank.
+pub fn render_template(template: &str, values: &HashMap<&str, String>) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        rendered.push_str(&rest[..start]);
+        let name = rest[start + 2..start + 2 + end].trim();
+        if let Some(value) = values.get(name) {
+            rendered.push_str(value);
+        }
+        rest = &rest[start + 2 + end + 2..];
+    }
+    rendered.push_str(rest);
+    rendered.trim().to_string()
+}
+
+/// Time of day as shown in messages
+pub(crate) fn format_time(at: DateTime<Utc>) -> String {
+    at.format("%H:%M").to_string()
+}
+
+/// One line describing the proof of delivery
+pub fn pod_summary(signed_by: Option<&str>, notes: Option<&str>, photos: usize) -> String {
+    let mut parts = Vec::new();
+    if let Some(name) = signed_by.filter(|n| !n.trim().is_empty()) {
+        parts.push(format!("Signed for by {}.", name.trim()));
+    }
+    match photos {
+        0 => {}
+        1 => parts.push("1 photo taken.".to_string()),
+        n => parts.push(format!("{} photos taken.", n)),
+    }
+    if let Some(notes) = notes.filter(|n| !n.trim().is_empty()) {
+        parts.push(format!("Driver notes: {}", notes.trim()));
+    }
+    parts.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_fills_known_placeholders_and_pod_summary() {
+        let values = HashMap::from([
+            ("delivery_number", "DEL-00000001".to_string()),
+            ("eta", "10:15".to_string()),
+        ]);
+
+        assert_eq!(
+            render_template("{{ delivery_number }} arrives around {{eta}}. {{pod_summary}}", &values),
+            "DEL-00000001 arrives around 10:15.",
+        );
+        assert_eq!(render_template("Unclosed {{eta", &values), "Unclosed {{eta");
+        assert_eq!(
+            pod_summary(Some("Pat Lee"), Some("Left at back door"), 2),
+            "Signed for by Pat Lee. 2 photos taken. Driver notes: Left at back door",
+        );
+    }
+
+    #[test]
+    fn test_rule_reports_unknown_placeholders() {
+        let rule = NotificationRule {
+            trigger: NotificationTrigger::UpNext,
+            sms_template: Some("Arriving {{eta}} at {{adress}}".to_string()),
+            email_subject: None,
+            email_template: Some("{{customer_name}}".to_string()),
+            is_active: true,
+        };
+        assert_eq!(rule.unknown_placeholders(), vec!["adress".to_string()]);
+    }
+
+}
@@ -0,0 +1,69 @@
+//! Proof of Delivery
+//!
+//! Signatures, photos and notes captured at the door. Signatures and
+//! photos are stored as attachments owned by the delivery; notes are kept
+//! as text. A delivery that requires a signature can only be marked
+//! delivered once a signature has been captured.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use crate::models::GeoPoint;
+
+/// Attachment owner type of delivery proofs
+pub const DELIVERY_OWNER: &str = "delivery";
+
+/// Largest signature or photo accepted
+pub const MAX_PROOF_BYTES: usize = 5 * 1024 * 1024;
+
+/// What a proof of delivery is
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ProofType {
+    /// Image of the recipient's signature
+    Signature,
+    /// Photo of the delivered goods
+    Photo,
+    /// Free text, e.g. where the goods were left
+    Note,
+}
+
+impl ProofType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Signature => "signature",
+            Self::Photo => "photo",
+            Self::Note => "note",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "signature" => Some(Self::Signature),
+            "photo" => Some(Self::Photo),
+            "note" => Some(Self::Note),
+            _ => None,
+        }
+    }
+}
+
+/// A signature, photo or note captured for a delivery
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeliveryProof {
+    pub id: String,
+    pub delivery_id: String,
+    pub proof_type: ProofType,
+    /// Signature or photo attachment
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attachment_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    /// Where it was captured
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<GeoPoint>,
+    pub captured_by: String,
+    pub captured_at: DateTime<Utc>,
+    /// Base64 encoded image of a signature or photo, filled in when
+    /// proofs are fetched for display
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<String>,
+}
@@ -2,10 +2,45 @@
 //! 
 //! Vehicle Routing Problem (VRP) solver using heuristic algorithms.
 //! Runs entirely on-device for offline capability.
+//!
+//! Stops may have time windows. Arriving before a window opens means
+//! waiting; arriving after it closes is a violation, weighed against
+//! distance by a per-minute penalty so a slightly longer route that is on
+//! time beats a shorter late one.
+//!
+//! A fleet splits stops between vehicles by sweeping around the depot,
+//! filling each vehicle in turn up to its weight, volume and shift limits.
+//! Stops no vehicle can take are reported rather than dropped.
 
 use serde::{Deserialize, Serialize};
 use wms_core::error::{WmsError, Result};
-use crate::models::GeoPoint;
+use wms_core::operations::CancellationToken;
+use crate::models::{GeoPoint, Vehicle};
+
+/// Kilometres of driving one minute of lateness is weighed against
+pub const DEFAULT_LATE_PENALTY_KM_PER_MINUTE: f64 = 1.0;
+
+/// When a stop may be served, in minutes after the route departs
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub struct TimeWindow {
+    /// Arriving earlier means waiting until then
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub earliest: Option<u32>,
+    /// Arriving later violates the window
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latest: Option<u32>,
+}
+
+/// A stop reached after its window closes
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct WindowViolation {
+    pub delivery_id: String,
+    /// Minutes from start
+    pub arrival_minutes: u32,
+    /// Minutes from start the window closed at
+    pub latest_minutes: u32,
+    pub minutes_late: u32,
+}
 
 /// Optimized route result
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -20,8 +55,63 @@ pub struct OptimizedRoute {
     pub estimated_duration_minutes: u32,
     /// Estimated arrival times for each stop
     pub arrival_times: Vec<u32>, // Minutes from start
-    /// Optimization score (lower is better)
+    /// Optimization score (lower is better): distance plus the lateness
+    /// penalty
     pub optimization_score: f64,
+    /// Stops the route still reaches after their window closes
+    #[serde(default)]
+    pub window_violations: Vec<WindowViolation>,
+}
+
+/// Load a stop puts on a vehicle
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub struct StopDemand {
+    pub weight_kg: f64,
+    pub volume_m3: f64,
+}
+
+impl std::ops::AddAssign for StopDemand {
+    fn add_assign(&mut self, other: Self) {
+        self.weight_kg += other.weight_kg;
+        self.volume_m3 += other.volume_m3;
+    }
+}
+
+/// Why a stop is on none of the fleet's routes
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum UnassignedReason {
+    /// Heavier or bulkier than any vehicle carries
+    ExceedsCapacity,
+    /// No vehicle reaches it alone within its shift
+    ExceedsShift,
+    /// Would fit, but every vehicle is already full or out of shift time
+    FleetFull,
+}
+
+/// A stop left off every route
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnassignedStop {
+    pub delivery_id: String,
+    pub demand: StopDemand,
+    pub reason: UnassignedReason,
+}
+
+/// One vehicle's optimized route
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VehicleRoute {
+    pub vehicle_id: String,
+    pub route: OptimizedRoute,
+    /// Total demand of the route's stops
+    pub load: StopDemand,
+}
+
+/// Stops split between a fleet's vehicles
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FleetPlan {
+    /// Routes of the vehicles given stops, in fleet order
+    pub routes: Vec<VehicleRoute>,
+    pub unassigned: Vec<UnassignedStop>,
 }
 
 /// Route optimizer using nearest-neighbor heuristic and 2-opt improvement
@@ -30,6 +120,8 @@ pub struct RouteOptimizer {
     average_speed_kmh: f64,
     /// Time per stop in minutes (loading/unloading)
     service_time_minutes: u32,
+    /// Kilometres one minute late at a stop counts as
+    late_penalty_km_per_minute: f64,
 }
 
 impl RouteOptimizer {
@@ -38,6 +130,7 @@ impl RouteOptimizer {
         Self {
             average_speed_kmh: 40.0, // Urban delivery speed
             service_time_minutes: 5,
+            late_penalty_km_per_minute: DEFAULT_LATE_PENALTY_KM_PER_MINUTE,
         }
     }
     
@@ -53,19 +146,41 @@ impl RouteOptimizer {
         self
     }
     
+    /// Configure how many kilometres a minute of lateness counts as
+    pub fn with_late_penalty(mut self, km_per_minute: f64) -> Self {
+        self.late_penalty_km_per_minute = km_per_minute;
+        self
+    }
+    
     /// Optimize route for given stops
     /// 
-    /// Uses a two-phase approach:
+    /// Uses a three-phase approach:
     /// 1. Nearest-neighbor heuristic for initial solution
     /// 2. 2-opt local search for improvement
+    /// 3. With time windows, moving single stops and reversing segments
+    ///    while that lowers distance plus the lateness penalty
+    /// 
+    /// `windows` is empty or holds one window per stop.
+    /// `cancel` is checked, and progress reported, as the passes run.
     pub fn optimize(
         &self,
         start: GeoPoint,
         stops: Vec<(String, GeoPoint)>,
+        windows: &[TimeWindow],
+        cancel: &CancellationToken,
     ) -> Result<OptimizedRoute> {
         if stops.is_empty() {
             return Err(WmsError::RouteOptimization("No stops provided".to_string()));
         }
+        if !windows.is_empty() && windows.len() != stops.len() {
+            return Err(WmsError::RouteOptimization(format!(
+                "{} time windows for {} stops", windows.len(), stops.len()
+            )));
+        }
+        // Indexed like the distance matrix; the depot has no window
+        let windows: Vec<TimeWindow> = std::iter::once(TimeWindow::default())
+            .chain((0..stops.len()).map(|i| windows.get(i).copied().unwrap_or_default()))
+            .collect();
         
         // Build distance matrix
         let mut all_points: Vec<GeoPoint> = vec![start];
@@ -84,7 +199,12 @@ impl RouteOptimizer {
         let mut route = self.nearest_neighbor(&distances);
         
         // Phase 2: 2-opt improvement
-        route = self.two_opt_improve(&distances, route);
+        route = self.two_opt_improve(&distances, route, cancel)?;
+        
+        // Phase 3: arrive within the windows
+        if windows.iter().any(|w| w.latest.is_some()) {
+            route = self.time_window_improve(&distances, &windows, route, cancel)?;
+        }
         
         // Build result
         let stop_order: Vec<String> = route.iter()
@@ -97,19 +217,153 @@ impl RouteOptimizer {
             .collect();
         
         let total_distance = self.calculate_route_distance(&distances, &route);
-        let estimated_duration = self.calculate_duration(total_distance, stops.len());
-        let arrival_times = self.calculate_arrival_times(&distances, &route);
+        let (arrivals, wait_minutes) = self.schedule(&distances, &windows, &route);
+        let estimated_duration = self.calculate_duration(total_distance, stops.len()) + wait_minutes as u32;
+        
+        let window_violations: Vec<WindowViolation> = route.iter()
+            .skip(1)
+            .zip(&arrivals)
+            .filter_map(|(&i, &arrival)| {
+                let latest = windows[i].latest?;
+                (arrival > latest as f64).then(|| WindowViolation {
+                    delivery_id: stops[i - 1].0.clone(),
+                    arrival_minutes: arrival as u32,
+                    latest_minutes: latest,
+                    minutes_late: (arrival - latest as f64).ceil() as u32,
+                })
+            })
+            .collect();
         
         Ok(OptimizedRoute {
             stop_order,
             waypoints,
             total_distance_km: total_distance,
             estimated_duration_minutes: estimated_duration,
-            arrival_times,
-            optimization_score: total_distance,
+            arrival_times: arrivals.iter().map(|&t| t as u32).collect(),
+            optimization_score: self.route_cost(&distances, &windows, &route),
+            window_violations,
         })
     }
     
+    /// Split stops between vehicles and optimize each vehicle's route
+    /// 
+    /// Stops are taken in order of their bearing from `start`. Each vehicle
+    /// in turn takes every remaining stop that still fits its capacity and
+    /// keeps its optimized route within its shift, then the next vehicle
+    /// continues with what is left. Vehicles without a limit are not
+    /// bound by it.
+    pub fn optimize_fleet(
+        &self,
+        start: GeoPoint,
+        stops: Vec<(String, GeoPoint, StopDemand)>,
+        vehicles: &[Vehicle],
+        cancel: &CancellationToken,
+    ) -> Result<FleetPlan> {
+        if stops.is_empty() {
+            return Err(WmsError::RouteOptimization("No stops provided".to_string()));
+        }
+        if vehicles.is_empty() {
+            return Err(WmsError::RouteOptimization("No vehicles provided".to_string()));
+        }
+        
+        let bearing = |p: &GeoPoint| (p.lat - start.lat).atan2(p.lng - start.lng);
+        let mut remaining = stops;
+        remaining.sort_by(|a, b| bearing(&a.1).total_cmp(&bearing(&b.1)));
+        
+        let mut routes = Vec::new();
+        for vehicle in vehicles {
+            let mut assigned: Vec<(String, GeoPoint)> = Vec::new();
+            let mut load = StopDemand::default();
+            let mut left = Vec::new();
+            
+            for (id, point, demand) in remaining {
+                cancel.check()?;
+                let mut with_stop = load;
+                with_stop += demand;
+                let mut candidate = assigned.clone();
+                candidate.push((id.clone(), point));
+                
+                if Self::carries(vehicle, with_stop) && self.within_shift(start, vehicle, &candidate, cancel)? {
+                    assigned = candidate;
+                    load = with_stop;
+                } else {
+                    left.push((id, point, demand));
+                }
+            }
+            remaining = left;
+            
+            if !assigned.is_empty() {
+                routes.push(VehicleRoute {
+                    vehicle_id: vehicle.id.clone(),
+                    route: self.optimize(start, assigned, &[], cancel)?,
+                    load,
+                });
+            }
+        }
+        
+        let mut unassigned = Vec::new();
+        for (id, point, demand) in remaining {
+            let reason = if !vehicles.iter().any(|v| Self::carries(v, demand)) {
+                UnassignedReason::ExceedsCapacity
+            } else {
+                let alone = [(id.clone(), point)];
+                let mut reachable = false;
+                for vehicle in vehicles.iter().filter(|v| Self::carries(v, demand)) {
+                    reachable |= self.within_shift(start, vehicle, &alone, cancel)?;
+                }
+                if reachable { UnassignedReason::FleetFull } else { UnassignedReason::ExceedsShift }
+            };
+            unassigned.push(UnassignedStop { delivery_id: id, demand, reason });
+        }
+        
+        Ok(FleetPlan { routes, unassigned })
+    }
+    
+    /// Whether a vehicle's weight and volume limits allow `load`
+    fn carries(vehicle: &Vehicle, load: StopDemand) -> bool {
+        vehicle.capacity_kg.is_none_or(|max| load.weight_kg <= max)
+            && vehicle.capacity_m3.is_none_or(|max| load.volume_m3 <= max)
+    }
+    
+    /// Whether the optimized route through `stops` ends within the
+    /// vehicle's shift
+    fn within_shift(
+        &self,
+        start: GeoPoint,
+        vehicle: &Vehicle,
+        stops: &[(String, GeoPoint)],
+        cancel: &CancellationToken,
+    ) -> Result<bool> {
+        let Some(shift) = vehicle.shift_minutes else {
+            return Ok(true);
+        };
+        let route = self.optimize(start, stops.to_vec(), &[], cancel)?;
+        Ok(route.estimated_duration_minutes <= shift)
+    }
+    
+    /// Minutes from leaving `start` until reaching each of `stops`, visited
+    /// in the given order
+    pub fn estimate_arrivals(&self, start: GeoPoint, stops: &[GeoPoint]) -> Vec<u32> {
+        let points: Vec<GeoPoint> = std::iter::once(start).chain(stops.iter().copied()).collect();
+        let distances: Vec<Vec<f64>> = points.iter()
+            .map(|from| points.iter().map(|to| from.distance_to(to)).collect())
+            .collect();
+        let route: Vec<usize> = (0..points.len()).collect();
+        
+        let (arrivals, _) = self.schedule(&distances, &[], &route);
+        arrivals.iter().map(|&t| t as u32).collect()
+    }
+    
+    /// Minutes spent at each stop
+    pub fn service_time_minutes(&self) -> u32 {
+        self.service_time_minutes
+    }
+    
+    /// Minutes of driving from `from` to `to`
+    pub fn travel_minutes(&self, from: GeoPoint, to: GeoPoint) -> f64 {
+        from.distance_to(&to) / self.average_speed_kmh * 60.0
+    }
+    
     /// Nearest-neighbor heuristic
     fn nearest_neighbor(&self, distances: &[Vec<f64>]) -> Vec<usize> {
         let n = distances.len();
@@ -139,21 +393,28 @@ impl RouteOptimizer {
     }
     
     /// 2-opt local search improvement
-    fn two_opt_improve(&self, distances: &[Vec<f64>], mut route: Vec<usize>) -> Vec<usize> {
+    fn two_opt_improve(
+        &self,
+        distances: &[Vec<f64>],
+        mut route: Vec<usize>,
+        cancel: &CancellationToken,
+    ) -> Result<Vec<usize>> {
         let n = route.len();
         if n < 4 {
-            return route;
+            return Ok(route);
         }
         
         let mut improved = true;
-        let max_iterations = 1000;
+        let max_iterations: u64 = 1000;
         let mut iterations = 0;
         
         while improved && iterations < max_iterations {
             improved = false;
             iterations += 1;
+            cancel.report(iterations, Some(max_iterations));
             
             for i in 1..(n - 2) {
+                cancel.check()?;
                 for j in (i + 1)..(n - 1) {
                     let delta = self.two_opt_delta(distances, &route, i, j);
                     
@@ -166,7 +427,86 @@ impl RouteOptimizer {
             }
         }
         
-        route
+        Ok(route)
+    }
+    
+    /// Local search on distance plus lateness: move a stop elsewhere in the
+    /// route, or reverse a segment, while either lowers the cost
+    fn time_window_improve(
+        &self,
+        distances: &[Vec<f64>],
+        windows: &[TimeWindow],
+        mut route: Vec<usize>,
+        cancel: &CancellationToken,
+    ) -> Result<Vec<usize>> {
+        let n = route.len();
+        let mut best = self.route_cost(distances, windows, &route);
+        let max_passes: u64 = 100;
+        
+        for pass in 1..=max_passes {
+            cancel.report(pass, Some(max_passes));
+            let mut improved = false;
+            
+            for i in 1..n {
+                cancel.check()?;
+                for j in 1..n {
+                    if i == j {
+                        continue;
+                    }
+                    let mut moved = route.clone();
+                    let stop = moved.remove(i);
+                    moved.insert(j, stop);
+                    
+                    let mut reversed = route.clone();
+                    reversed[i.min(j)..=i.max(j)].reverse();
+                    
+                    for candidate in [moved, reversed] {
+                        let cost = self.route_cost(distances, windows, &candidate);
+                        if cost < best - 0.001 {
+                            route = candidate;
+                            best = cost;
+                            improved = true;
+                        }
+                    }
+                }
+            }
+            
+            if !improved {
+                break;
+            }
+        }
+        
+        Ok(route)
+    }
+    
+    /// Distance plus the lateness penalty
+    fn route_cost(&self, distances: &[Vec<f64>], windows: &[TimeWindow], route: &[usize]) -> f64 {
+        let (arrivals, _) = self.schedule(distances, windows, route);
+        let minutes_late: f64 = route.iter()
+            .skip(1)
+            .zip(arrivals)
+            .filter_map(|(&i, arrival)| windows.get(i)?.latest.map(|latest| (arrival - latest as f64).max(0.0)))
+            .sum();
+        self.calculate_route_distance(distances, route) + minutes_late * self.late_penalty_km_per_minute
+    }
+    
+    /// Minutes from start until arriving at each stop after the depot, and
+    /// the minutes spent waiting for windows to open. Service at a stop
+    /// starts once its window is open.
+    fn schedule(&self, distances: &[Vec<f64>], windows: &[TimeWindow], route: &[usize]) -> (Vec<f64>, f64) {
+        let mut arrivals = Vec::with_capacity(route.len().saturating_sub(1));
+        let mut clock = 0.0;
+        let mut waited = 0.0;
+        
+        for pair in route.windows(2) {
+            let arrival = clock + distances[pair[0]][pair[1]] / self.average_speed_kmh * 60.0;
+            let opens = windows.get(pair[1]).and_then(|w| w.earliest).map_or(0.0, |t| t as f64);
+            waited += (opens - arrival).max(0.0);
+            clock = arrival.max(opens) + self.service_time_minutes as f64;
+            arrivals.push(arrival);
+        }
+        
+        (arrivals, waited)
     }
     
     /// Calculate improvement delta for 2-opt swap
@@ -199,26 +539,6 @@ impl RouteOptimizer {
         let service_time = (num_stops as u32) * self.service_time_minutes;
         travel_time + service_time
     }
-    
-    /// Calculate arrival times at each stop
-    fn calculate_arrival_times(&self, distances: &[Vec<f64>], route: &[usize]) -> Vec<u32> {
-        let mut times = Vec::new();
-        let mut current_time: f64 = 0.0;
-        
-        for i in 0..(route.len() - 1) {
-            let dist = distances[route[i]][route[i + 1]];
-            let travel_time = dist / self.average_speed_kmh * 60.0;
-            current_time += travel_time;
-            
-            if i > 0 {
-                current_time += self.service_time_minutes as f64;
-            }
-            
-            times.push(current_time as u32);
-        }
-        
-        times
-    }
 }
 
 impl Default for RouteOptimizer {
@@ -242,13 +562,134 @@ mod tests {
             ("del3".to_string(), GeoPoint::new(40.6892, -74.0445)), // Statue of Liberty
         ];
         
-        let result = optimizer.optimize(start, stops).unwrap();
+        let result = optimizer.optimize(start, stops, &[], &CancellationToken::new()).unwrap();
         
         assert_eq!(result.stop_order.len(), 3);
         assert!(result.total_distance_km > 0.0);
         assert!(result.estimated_duration_minutes > 0);
     }
     
+    #[test]
+    fn test_cancelled_optimization_stops() {
+        let start = GeoPoint::new(40.7128, -74.0060);
+        let stops: Vec<(String, GeoPoint)> = (0..50)
+            .map(|i| (format!("del{}", i), GeoPoint::new(40.6 + (i * 7 % 13) as f64 * 0.01, -74.0 + i as f64 * 0.005)))
+            .collect();
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        
+        let result = RouteOptimizer::new().optimize(start, stops, &[], &cancel);
+        assert!(matches!(result, Err(WmsError::Cancelled(_))));
+    }
+    
+    #[test]
+    fn test_time_windows_reorder_stops() {
+        // Two stops a short hop east, one further west that must be
+        // reached within 20 minutes
+        let start = GeoPoint::new(39.78, -89.65);
+        let east = |km: f64| GeoPoint::new(39.78, -89.65 + km / 85.5);
+        let stops = vec![
+            ("near".to_string(), east(2.0)),
+            ("next".to_string(), east(4.0)),
+            ("urgent".to_string(), east(-6.0)),
+        ];
+        let windows = [
+            TimeWindow::default(),
+            TimeWindow::default(),
+            TimeWindow { earliest: None, latest: Some(20) },
+        ];
+        let optimizer = RouteOptimizer::new();
+        
+        let nearest = optimizer.optimize(start, stops.clone(), &[], &CancellationToken::new()).unwrap();
+        assert_eq!(nearest.stop_order, vec!["near", "next", "urgent"]);
+        assert!(nearest.arrival_times[2] > 20, "nearest-neighbor is late: {:?}", nearest.arrival_times);
+        
+        let timed = optimizer.optimize(start, stops, &windows, &CancellationToken::new()).unwrap();
+        assert_eq!(timed.stop_order[0], "urgent");
+        assert!(timed.window_violations.is_empty(), "{:?}", timed.window_violations);
+        assert!(timed.arrival_times[0] <= 20);
+        assert!(timed.total_distance_km > nearest.total_distance_km);
+    }
+    
+    #[test]
+    fn test_unavoidable_lateness_is_reported() {
+        let start = GeoPoint::new(39.78, -89.65);
+        let stops = vec![("far".to_string(), GeoPoint::new(39.98, -89.65))];
+        let windows = [TimeWindow { earliest: None, latest: Some(10) }];
+        
+        let route = RouteOptimizer::new().optimize(start, stops, &windows, &CancellationToken::new()).unwrap();
+        assert_eq!(route.window_violations.len(), 1);
+        let violation = &route.window_violations[0];
+        assert_eq!((violation.delivery_id.as_str(), violation.latest_minutes), ("far", 10));
+        assert!(violation.minutes_late > 0);
+        assert!(route.optimization_score > route.total_distance_km);
+    }
+    
+    fn van(id: &str, capacity_kg: f64, shift_minutes: Option<u32>) -> Vehicle {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "vehicle_number": id.to_uppercase(),
+            "vehicle_type": "van",
+            "capacity_kg": capacity_kg,
+            "shift_minutes": shift_minutes,
+            "is_active": true,
+            "created_at": "2025-03-03T00:00:00Z"
+        })).unwrap()
+    }
+    
+    #[test]
+    fn test_fleet_splits_stops_over_capacity() {
+        let start = GeoPoint::new(39.78, -89.65);
+        let stops: Vec<(String, GeoPoint, StopDemand)> = (0..6)
+            .map(|i| {
+                let point = GeoPoint::new(39.78 + (i % 3) as f64 * 0.01, -89.65 + if i < 3 { 0.03 } else { -0.03 });
+                (format!("del{}", i), point, StopDemand { weight_kg: 300.0, volume_m3: 1.0 })
+            })
+            .collect();
+        let vehicles = [van("van1", 1000.0, None), van("van2", 1000.0, None)];
+        
+        let plan = RouteOptimizer::new()
+            .optimize_fleet(start, stops, &vehicles, &CancellationToken::new())
+            .unwrap();
+        
+        assert_eq!(plan.routes.len(), 2);
+        assert!(plan.unassigned.is_empty(), "{:?}", plan.unassigned);
+        for (vehicle_route, vehicle) in plan.routes.iter().zip(&vehicles) {
+            assert_eq!(vehicle_route.vehicle_id, vehicle.id);
+            assert!(vehicle_route.load.weight_kg <= 1000.0);
+            assert_eq!(vehicle_route.load.weight_kg, vehicle_route.route.stop_order.len() as f64 * 300.0);
+        }
+        let served: usize = plan.routes.iter().map(|r| r.route.stop_order.len()).sum();
+        assert_eq!(served, 6);
+    }
+    
+    #[test]
+    fn test_fleet_reports_stops_no_vehicle_takes() {
+        let start = GeoPoint::new(39.78, -89.65);
+        let light = StopDemand { weight_kg: 100.0, volume_m3: 0.5 };
+        let stops = vec![
+            ("near".to_string(), GeoPoint::new(39.79, -89.65), light),
+            ("heavy".to_string(), GeoPoint::new(39.79, -89.64), StopDemand { weight_kg: 2000.0, volume_m3: 1.0 }),
+            ("far".to_string(), GeoPoint::new(40.78, -89.65), light),
+        ];
+        let vehicles = [van("van1", 1000.0, Some(60))];
+        
+        let plan = RouteOptimizer::new()
+            .optimize_fleet(start, stops, &vehicles, &CancellationToken::new())
+            .unwrap();
+        
+        assert_eq!(plan.routes.len(), 1);
+        assert_eq!(plan.routes[0].route.stop_order, vec!["near"]);
+        let mut reasons: Vec<(&str, UnassignedReason)> = plan.unassigned.iter()
+            .map(|u| (u.delivery_id.as_str(), u.reason))
+            .collect();
+        reasons.sort_by_key(|(id, _)| *id);
+        assert_eq!(reasons, vec![
+            ("far", UnassignedReason::ExceedsShift),
+            ("heavy", UnassignedReason::ExceedsCapacity),
+        ]);
+    }
+    
     #[test]
     fn test_distance_calculation() {
         let p1 = GeoPoint::new(40.7128, -74.0060); // NYC
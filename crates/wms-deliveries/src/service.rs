@@ -2,73 +2,129 @@
 //! 
 //! Core business logic for delivery and logistics operations.
 
+use std::collections::HashMap;
 use std::sync::Arc;
-use chrono::Utc;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
 use rusqlite::params;
-use tracing::{info, debug};
+use tracing::{info, debug, warn};
+use base64::Engine;
+use wms_core::attachments::{AttachmentKind, AttachmentService, NewAttachment};
+use wms_core::changes::{outbox_recorder, ChangeKind, SharedRecorder};
+use wms_core::clock::{SharedClock, system_clock};
+use wms_core::context::RequestContext;
+use wms_core::custom_fields::{custom_fields_json, parse_custom_fields, CustomFieldEntity, CustomFieldService};
 use wms_core::db::Database;
 use wms_core::error::{WmsError, Result};
-use wms_core::types::new_id;
+use wms_core::events::{SharedEmitter, WmsEvent, noop_emitter};
+use wms_core::operations::CancellationToken;
+use wms_core::sequences::DocumentSequence;
+use wms_core::shutdown::TaskHandle;
+use wms_core::temperature::{TemperatureOwner, TemperatureReading, TemperatureService};
+use wms_core::types::{new_id, parse_timestamp, required_timestamp, stored_status, stored_timestamp, unreadable};
 use crate::models::*;
-use crate::routing::{RouteOptimizer, OptimizedRoute};
-use crate::geofence::{GeofenceChecker, GeofenceResult};
+use crate::routing::{FleetPlan, RouteOptimizer, OptimizedRoute, StopDemand};
+use crate::eta::estimate_route;
+use crate::windows::DeliveryWindowSettings;
+use crate::breadcrumbs::{
+    downsample, BREADCRUMB_MAX_ACCURACY_METERS, BREADCRUMB_POINTS_PER_MINUTE, DRIVER_LOCATION_RETENTION_DAYS,
+    LOCATION_CLOCK_SKEW_SECONDS,
+};
+use crate::proofs::{DeliveryProof, ProofType, DELIVERY_OWNER, MAX_PROOF_BYTES};
+use crate::geofence::{GeofenceChecker, GeofenceGeometry, GeofenceResult, GeofenceState, GeofenceTrigger};
+use crate::notifications::{
+    DeliveryNotification, NotificationChannel, NotificationRule, NotificationTrigger,
+    ETA_WINDOW_BUFFER_MINUTES, PLACEHOLDERS, format_time, pod_summary, render_template,
+};
 
 /// Delivery management service
 pub struct DeliveryService {
     db: Arc<Database>,
     route_optimizer: RouteOptimizer,
     geofence_checker: GeofenceChecker,
+    events: SharedEmitter,
+    changes: SharedRecorder,
+    clock: SharedClock,
+    temperature: TemperatureService,
+    custom_fields: CustomFieldService,
+    attachments: AttachmentService,
 }
 
 impl DeliveryService {
     /// Create a new delivery service
     pub fn new(db: Arc<Database>) -> Self {
         Self {
-            db,
+            db: db.clone(),
             route_optimizer: RouteOptimizer::new(),
             geofence_checker: GeofenceChecker::new(),
+            events: noop_emitter(),
+            changes: outbox_recorder(),
+            clock: system_clock(),
+            temperature: TemperatureService::new(db.clone()),
+            custom_fields: CustomFieldService::new(db.clone()),
+            attachments: AttachmentService::new(db.clone()).with_max_size(MAX_PROOF_BYTES),
         }
     }
     
-    /// Get deliveries with optional filters
+    /// Publish domain events through the given emitter
+    pub fn with_event_emitter(mut self, events: SharedEmitter) -> Self {
+        self.events = events;
+        self
+    }
+    
+    /// Record changes for sync through the given recorder
+    pub fn with_change_recorder(mut self, changes: SharedRecorder) -> Self {
+        self.changes = changes;
+        self
+    }
+    
+    /// Mark deliveries arrived only after the driver has stayed inside
+    /// their geofence for `duration`
+    pub fn with_geofence_dwell(mut self, duration: Duration) -> Self {
+        self.geofence_checker = GeofenceChecker::new().with_dwell_duration(duration);
+        self
+    }
+    
+    /// Read the current time (arrival estimates, notifications) from `clock`
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.temperature = self.temperature.with_clock(clock.clone());
+        self.clock = clock;
+        self
+    }
+    
+    /// Get the deliveries matching `query`. With `restrict_to_driver` only
+    /// deliveries on that driver's routes are returned.
     pub async fn get_deliveries(
         &self,
-        status: Option<DeliveryStatus>,
-        date: Option<&str>,
+        query: &DeliveryQuery,
+        restrict_to_driver: Option<&str>,
     ) -> Result<Vec<Delivery>> {
-        let mut sql = String::from(
-            "SELECT * FROM deliveries WHERE 1=1"
-        );
-        let mut params_vec: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
-        
-        if let Some(s) = status {
-            sql.push_str(" AND status = ?");
-            params_vec.push(Box::new(format!("{:?}", s).to_lowercase()));
-        }
-        
-        if let Some(d) = date {
-            sql.push_str(" AND date(scheduled_date) = date(?)");
-            params_vec.push(Box::new(d.to_string()));
-        }
-        
-        sql.push_str(" ORDER BY scheduled_date ASC, sequence_number ASC");
-        
-        // Simplified query without dynamic params for now
-        let deliveries = self.db.query_map(
-            "SELECT * FROM deliveries ORDER BY scheduled_date ASC",
-            [],
+        // Older rows store statuses without underscores ("enroute").
+        // Scheduled dates are RFC3339 timestamps, so the day is their prefix.
+        self.db.query_map(
+            "SELECT * FROM deliveries
+             WHERE (?1 IS NULL OR replace(status, '_', '') = replace(?1, '_', ''))
+               AND (?2 IS NULL OR substr(scheduled_date, 1, 10) = ?2)
+               AND (?3 IS NULL OR route_id = ?3)
+               AND (?4 IS NULL OR route_id IN (SELECT id FROM delivery_routes WHERE driver_id = ?4))
+             ORDER BY scheduled_date ASC, sequence_number ASC",
+            params![
+                query.status.map(|s| s.as_str()),
+                query.date.map(|d| d.format("%Y-%m-%d").to_string()),
+                &query.route_id,
+                restrict_to_driver,
+            ],
             |row| Self::row_to_delivery(row),
-        )?;
-        
-        Ok(deliveries)
+        )
     }
     
     /// Create a new delivery
     pub async fn create_delivery(&self, mut delivery: Delivery) -> Result<Delivery> {
+        delivery.custom_fields = self.custom_fields.validate(CustomFieldEntity::Delivery, &delivery.custom_fields)?;
         delivery.id = new_id();
         delivery.delivery_number = self.generate_delivery_number()?;
         delivery.status = DeliveryStatus::Pending;
         delivery.created_at = Utc::now();
+        delivery.version = 1;
         
         self.db.execute(
             "INSERT INTO deliveries (
@@ -77,8 +133,8 @@ impl DeliveryService {
                 delivery_city, delivery_state, delivery_postal_code, delivery_country,
                 delivery_phone, delivery_email, latitude, longitude, geofence_radius_meters,
                 scheduled_date, scheduled_time_window_start, scheduled_time_window_end,
-                delivery_instructions, signature_required, created_at
-            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                delivery_instructions, signature_required, custom_fields, created_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
             params![
                 &delivery.id,
                 &delivery.delivery_number,
@@ -104,6 +160,7 @@ impl DeliveryService {
                 &delivery.time_window_end,
                 &delivery.delivery_instructions,
                 delivery.signature_required,
+                custom_fields_json(&delivery.custom_fields),
                 delivery.created_at.to_rfc3339(),
             ],
         )?;
@@ -119,50 +176,114 @@ impl DeliveryService {
         status: DeliveryStatus,
         location: Option<GeoPoint>,
     ) -> Result<Delivery> {
-        let status_str = format!("{:?}", status).to_lowercase();
-        
-        let rows = self.db.execute(
-            "UPDATE deliveries SET status = ?, updated_at = datetime('now') WHERE id = ?",
-            params![&status_str, delivery_id],
-        )?;
-        
-        if rows == 0 {
-            return Err(WmsError::not_found("Delivery not found"));
+        self.set_status(delivery_id, status, location, None).await
+    }
+    
+    /// Update the status of a delivery the caller last saw at
+    /// `expected_version`; fails with the current record if it has changed
+    pub async fn update_status_at_version(
+        &self,
+        delivery_id: &str,
+        status: DeliveryStatus,
+        location: Option<GeoPoint>,
+        expected_version: i64,
+    ) -> Result<Delivery> {
+        self.set_status(delivery_id, status, location, Some(expected_version)).await
+    }
+    
+    async fn set_status(
+        &self,
+        delivery_id: &str,
+        status: DeliveryStatus,
+        location: Option<GeoPoint>,
+        expected_version: Option<i64>,
+    ) -> Result<Delivery> {
+        if status == DeliveryStatus::Delivered {
+            self.check_signature(delivery_id).await?;
         }
         
-        // Record status history
-        self.record_status_history(delivery_id, status, location, None).await?;
-        
-        // Handle status-specific updates
-        match status {
-            DeliveryStatus::Arrived => {
-                self.db.execute(
-                    "UPDATE deliveries SET actual_arrival_time = datetime('now') WHERE id = ?",
-                    params![delivery_id],
-                )?;
+        // The history, the timestamps and the change for sync commit with
+        // the status they go with
+        let now = self.clock.now().to_rfc3339();
+        let delivery = self.db.transaction(|conn| {
+            let rows = conn.execute(
+                "UPDATE deliveries SET status = ?, updated_at = datetime('now')
+                 WHERE id = ? AND (?3 IS NULL OR version = ?3)",
+                params![status.as_str(), delivery_id, expected_version],
+            )?;
+            if rows == 0 {
+                return Ok(None);
             }
-            DeliveryStatus::Delivered => {
-                self.db.execute(
-                    "UPDATE deliveries SET actual_departure_time = datetime('now') WHERE id = ?",
-                    params![delivery_id],
-                )?;
+            
+            Self::record_status_history(conn, delivery_id, status, location, None)?;
+            
+            // Handle status-specific updates
+            match status {
+                DeliveryStatus::Arrived => {
+                    conn.execute(
+                        "UPDATE deliveries SET actual_arrival_time = ? WHERE id = ?",
+                        params![&now, delivery_id],
+                    )?;
+                }
+                DeliveryStatus::Delivered => {
+                    conn.execute(
+                        "UPDATE deliveries SET actual_departure_time = ? WHERE id = ?",
+                        params![&now, delivery_id],
+                    )?;
+                }
+                _ => {}
             }
-            _ => {}
-        }
+            
+            let delivery = conn.query_row(
+                "SELECT * FROM deliveries WHERE id = ?",
+                params![delivery_id],
+                Self::row_to_delivery,
+            )?;
+            self.changes.record_model(conn, "deliveries", &delivery.id, ChangeKind::Update, &delivery)?;
+            Ok(Some(delivery))
+        })?;
         
+        let Some(delivery) = delivery else {
+            let current = self.get_delivery(delivery_id).await?
+                .ok_or_else(|| WmsError::not_found("Delivery not found"))?;
+            return Err(WmsError::version_conflict(
+                format!("Delivery {} was changed by someone else (version {}, yours {})",
+                        current.delivery_number, current.version, expected_version.unwrap_or_default()),
+                &current,
+            ));
+        };
         debug!("Updated delivery {} status to {:?}", delivery_id, status);
-        self.get_delivery(delivery_id).await?
-            .ok_or_else(|| WmsError::not_found("Delivery not found"))
+        
+        if status.is_terminal()
+            && let Err(e) = self.notify_stop_finished(&delivery).await
+        {
+            warn!("Failed to queue notifications after {}: {}", delivery.delivery_number, e);
+        }
+        
+        self.events.emit(WmsEvent::DeliveryStatusChanged {
+            delivery_id: delivery.id.clone(),
+            delivery_number: delivery.delivery_number.clone(),
+            status: status.as_str().to_string(),
+        });
+        
+        Ok(delivery)
     }
     
-    /// Optimize route for multiple deliveries
+    /// Optimize route for multiple deliveries, keeping to their delivery
+    /// windows where possible. The order is not saved when it misses more
+    /// windows than `DeliveryWindowSettings` allow. Stops with
+    /// `WmsError::Cancelled` once `cancel` is cancelled.
     pub async fn optimize_route(
         &self,
         delivery_ids: &[String],
         start_location: GeoPoint,
+        cancel: &CancellationToken,
     ) -> Result<OptimizedRoute> {
-        // Get delivery locations
+        let settings = DeliveryWindowSettings::load(&self.db)?;
+        
+        // Get delivery locations and windows
         let mut stops: Vec<(String, GeoPoint)> = Vec::new();
+        let mut windows = Vec::new();
         
         for id in delivery_ids {
             let delivery = self.get_delivery(id).await?
@@ -170,6 +291,10 @@ impl DeliveryService {
             
             if let Some(loc) = delivery.location {
                 stops.push((id.clone(), loc));
+                windows.push(settings.window(
+                    delivery.time_window_start.as_deref(),
+                    delivery.time_window_end.as_deref(),
+                ));
             } else {
                 return Err(WmsError::validation(format!(
                     "Delivery {} has no location coordinates", id
@@ -178,7 +303,18 @@ impl DeliveryService {
         }
         
         // Run optimization
-        let optimized = self.route_optimizer.optimize(start_location, stops)?;
+        let optimized = self.route_optimizer.optimize(start_location, stops, &windows, cancel)?;
+        let missed = optimized.window_violations.len();
+        if !settings.allows(missed) {
+            return Err(WmsError::validation(format!(
+                "The best route misses {} delivery windows, more than the {} allowed",
+                missed,
+                settings.max_violations.unwrap_or_default(),
+            )));
+        }
+        if missed > 0 {
+            warn!("Optimized route misses {} delivery windows", missed);
+        }
         
         // Update sequence numbers
         for (seq, delivery_id) in optimized.stop_order.iter().enumerate() {
@@ -197,7 +333,418 @@ impl DeliveryService {
         Ok(optimized)
     }
     
-    /// Check if current location is within delivery geofence
+    /// Split deliveries between vehicles and optimize each vehicle's route.
+    /// A delivery's load is its shipment's packages; pallets count once,
+    /// not again for the cartons on them. `vehicle_ids` limits the fleet,
+    /// otherwise every active vehicle is used. Deliveries no vehicle can
+    /// take are listed in the plan with the reason.
+    pub async fn optimize_fleet(
+        &self,
+        delivery_ids: &[String],
+        start_location: GeoPoint,
+        vehicle_ids: Option<&[String]>,
+        cancel: &CancellationToken,
+    ) -> Result<FleetPlan> {
+        let mut vehicles = self.get_vehicles().await?;
+        if let Some(ids) = vehicle_ids {
+            if let Some(missing) = ids.iter().find(|id| !vehicles.iter().any(|v| &v.id == *id)) {
+                return Err(WmsError::not_found(format!("Active vehicle {} not found", missing)));
+            }
+            vehicles.retain(|v| ids.contains(&v.id));
+        }
+        
+        let mut stops = Vec::new();
+        for id in delivery_ids {
+            let delivery = self.get_delivery(id).await?
+                .ok_or_else(|| WmsError::not_found(format!("Delivery {} not found", id)))?;
+            let location = delivery.location.ok_or_else(|| WmsError::validation(format!(
+                "Delivery {} has no location coordinates", id
+            )))?;
+            stops.push((id.clone(), location, self.delivery_demand(&delivery)?));
+        }
+        
+        let plan = self.route_optimizer.optimize_fleet(start_location, stops, &vehicles, cancel)?;
+        if !plan.unassigned.is_empty() {
+            warn!("{} deliveries fit no vehicle in the fleet", plan.unassigned.len());
+        }
+        info!(
+            "Split {} deliveries over {} vehicle routes",
+            delivery_ids.len() - plan.unassigned.len(),
+            plan.routes.len()
+        );
+        
+        Ok(plan)
+    }
+    
+    /// Active vehicles by number
+    pub async fn get_vehicles(&self) -> Result<Vec<Vehicle>> {
+        self.db.query_map(
+            "SELECT * FROM vehicles WHERE is_active = 1 ORDER BY vehicle_number",
+            [],
+            |row| Self::row_to_vehicle(row),
+        )
+    }
+    
+    // ============ Route Operations ============
+    
+    /// Optimize the given deliveries into a route and persist it, linking
+    /// each delivery through its route_id. If the deliveries already
+    /// belong to one planned route, that route is re-optimized in place.
+    pub async fn create_route(
+        &self,
+        ctx: &RequestContext,
+        delivery_ids: &[String],
+        start_location: GeoPoint,
+        route_date: NaiveDate,
+        driver_id: Option<&str>,
+    ) -> Result<DeliveryRoute> {
+        let created_by = ctx.actor()?;
+        if delivery_ids.is_empty() {
+            return Err(WmsError::validation("A route needs at least one delivery"));
+        }
+        
+        let mut existing: Option<DeliveryRoute> = None;
+        for id in delivery_ids {
+            let delivery = self.get_delivery(id).await?
+                .ok_or_else(|| WmsError::not_found(format!("Delivery {} not found", id)))?;
+            let Some(route_id) = delivery.route_id else {
+                continue;
+            };
+            if existing.as_ref().is_some_and(|r| r.id == route_id) {
+                continue;
+            }
+            if existing.is_some() {
+                return Err(WmsError::conflict("Deliveries belong to different routes"));
+            }
+            let route = self.get_route_header(&route_id)?
+                .ok_or_else(|| WmsError::not_found(format!("Route {} not found", route_id)))?;
+            if !route.status.is_planned() {
+                return Err(WmsError::conflict(format!(
+                    "Delivery {} is on route {}, which is {}",
+                    delivery.delivery_number, route.route_number, route.status.as_str()
+                )));
+            }
+            existing = Some(route);
+        }
+        
+        let optimized = self.optimize_route(delivery_ids, start_location, &CancellationToken::new()).await?;
+        
+        let route_id = match &existing {
+            Some(route) => route.id.clone(),
+            None => new_id(),
+        };
+        let route_number = match &existing {
+            Some(route) => route.route_number.clone(),
+            None => self.generate_route_number()?,
+        };
+        
+        self.db.transaction(|conn| {
+            if existing.is_some() {
+                conn.execute(
+                    "UPDATE delivery_routes SET
+                        route_date = ?, driver_id = ?,
+                        start_location_lat = ?, start_location_lng = ?,
+                        total_distance_km = ?, total_duration_minutes = ?, optimization_score = ?,
+                        updated_at = datetime('now')
+                     WHERE id = ?",
+                    params![
+                        route_date.to_string(),
+                        driver_id,
+                        start_location.lat,
+                        start_location.lng,
+                        optimized.total_distance_km,
+                        optimized.estimated_duration_minutes,
+                        optimized.optimization_score,
+                        &route_id,
+                    ],
+                )?;
+                // Stops left out of the new plan go back to the unrouted pool
+                let mut members = conn.prepare("SELECT id FROM deliveries WHERE route_id = ?")?;
+                let members: Vec<String> = members
+                    .query_map(params![&route_id], |row| row.get(0))?
+                    .collect::<rusqlite::Result<_>>()?;
+                for member in members.iter().filter(|m| !delivery_ids.contains(m)) {
+                    conn.execute(
+                        "UPDATE deliveries SET route_id = NULL, sequence_number = NULL
+                         WHERE id = ?",
+                        params![member],
+                    )?;
+                }
+            } else {
+                conn.execute(
+                    "INSERT INTO delivery_routes (
+                        id, route_number, route_name, route_date, driver_id, status,
+                        start_location_lat, start_location_lng, total_distance_km,
+                        total_duration_minutes, optimization_score, created_by, created_at
+                    ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                    params![
+                        &route_id,
+                        &route_number,
+                        &route_number,
+                        route_date.to_string(),
+                        driver_id,
+                        RouteStatus::Planning.as_str(),
+                        start_location.lat,
+                        start_location.lng,
+                        optimized.total_distance_km,
+                        optimized.estimated_duration_minutes,
+                        optimized.optimization_score,
+                        created_by,
+                        Utc::now().to_rfc3339(),
+                    ],
+                )?;
+            }
+            
+            for delivery_id in &optimized.stop_order {
+                conn.execute(
+                    "UPDATE deliveries SET route_id = ? WHERE id = ?",
+                    params![&route_id, delivery_id],
+                )?;
+            }
+            Ok(())
+        })?;
+        
+        let action = if existing.is_some() { "Re-optimized" } else { "Created" };
+        info!(
+            "{} route {} with {} stops ({:.2} km)",
+            action,
+            route_number,
+            optimized.stop_order.len(),
+            optimized.total_distance_km
+        );
+        
+        self.get_route(&route_id).await?
+            .ok_or_else(|| WmsError::not_found("Route not found"))
+    }
+    
+    /// Re-run optimization for a planned route's current stops
+    pub async fn reoptimize_route(&self, route_id: &str) -> Result<DeliveryRoute> {
+        let route = self.get_route(route_id).await?
+            .ok_or_else(|| WmsError::not_found("Route not found"))?;
+        let start = route.start_location
+            .ok_or_else(|| WmsError::validation(format!("Route {} has no start location", route.route_number)))?;
+        let delivery_ids: Vec<String> = route.deliveries.iter().map(|d| d.id.clone()).collect();
+        
+        // Keeps the route's original creator
+        self.create_route(
+            &RequestContext::for_user(&route.created_by),
+            &delivery_ids,
+            start,
+            route.route_date.date_naive(),
+            route.driver_id.as_deref(),
+        ).await
+    }
+    
+    /// Dispatch a planned route, optionally recording the vehicle's
+    /// odometer. Its waiting deliveries go en route.
+    pub async fn start_route(&self, route_id: &str, start_odometer_km: Option<f64>) -> Result<DeliveryRoute> {
+        let route = self.get_route(route_id).await?
+            .ok_or_else(|| WmsError::not_found("Route not found"))?;
+        if !route.status.is_planned() {
+            return Err(WmsError::conflict(format!(
+                "Route {} is {} and cannot be started", route.route_number, route.status.as_str()
+            )));
+        }
+        
+        self.db.execute(
+            "UPDATE delivery_routes SET status = ?, actual_start_time = ?, start_odometer_km = ?,
+                updated_at = datetime('now')
+             WHERE id = ?",
+            params![RouteStatus::InProgress.as_str(), self.clock.now().to_rfc3339(), start_odometer_km, route_id],
+        )?;
+        
+        for delivery in &route.deliveries {
+            if matches!(delivery.status, DeliveryStatus::Pending | DeliveryStatus::Assigned) {
+                self.update_status(&delivery.id, DeliveryStatus::EnRoute, None).await?;
+            }
+        }
+        
+        if let Err(e) = self.notify_route_started(route_id).await {
+            warn!("Failed to queue arrival windows for route {}: {}", route.route_number, e);
+        }
+        
+        info!("Started route {}", route.route_number);
+        self.get_route(route_id).await?
+            .ok_or_else(|| WmsError::not_found("Route not found"))
+    }
+    
+    /// Close a route, optionally recording the vehicle's odometer. Every
+    /// stop not delivered needs a failure reason: stops still open are
+    /// failed with the reason given in `failure_reasons` (by delivery id),
+    /// and failed stops without a recorded reason take one from there too.
+    pub async fn complete_route(
+        &self,
+        route_id: &str,
+        end_odometer_km: Option<f64>,
+        failure_reasons: &HashMap<String, String>,
+    ) -> Result<DeliveryRoute> {
+        let route = self.get_route(route_id).await?
+            .ok_or_else(|| WmsError::not_found("Route not found"))?;
+        if route.status != RouteStatus::InProgress {
+            return Err(WmsError::conflict(format!(
+                "Route {} is {} and cannot be completed", route.route_number, route.status.as_str()
+            )));
+        }
+        let readings = route.start_odometer_km.zip(end_odometer_km);
+        if let Some((start, end)) = readings.filter(|(start, end)| end < start) {
+            return Err(WmsError::validation(format!(
+                "End odometer {} km is below the start reading of {} km", end, start
+            )));
+        }
+        
+        let reason = |delivery: &Delivery| {
+            failure_reasons.get(&delivery.id).map(|r| r.trim()).filter(|r| !r.is_empty())
+        };
+        let needs_reason = |delivery: &Delivery| {
+            !delivery.status.is_terminal()
+                || (delivery.status == DeliveryStatus::Failed && delivery.failure_reason.is_none())
+        };
+        let unexplained: Vec<&str> = route.deliveries.iter()
+            .filter(|d| needs_reason(d) && reason(d).is_none())
+            .map(|d| d.delivery_number.as_str())
+            .collect();
+        if !unexplained.is_empty() {
+            return Err(WmsError::validation(format!(
+                "Route {} has undelivered stops without a failure reason: {}",
+                route.route_number, unexplained.join(", ")
+            )));
+        }
+        
+        for delivery in route.deliveries.iter().filter(|d| needs_reason(d)) {
+            if !delivery.status.is_terminal() {
+                self.update_status(&delivery.id, DeliveryStatus::Failed, None).await?;
+            }
+            self.db.execute(
+                "UPDATE deliveries SET failure_reason = ? WHERE id = ?",
+                params![reason(delivery), &delivery.id],
+            )?;
+        }
+        
+        self.db.execute(
+            "UPDATE delivery_routes SET status = ?, actual_end_time = ?, end_odometer_km = ?,
+                updated_at = datetime('now')
+             WHERE id = ?",
+            params![RouteStatus::Completed.as_str(), Utc::now().to_rfc3339(), end_odometer_km, route_id],
+        )?;
+        
+        info!("Completed route {}", route.route_number);
+        self.get_route(route_id).await?
+            .ok_or_else(|| WmsError::not_found("Route not found"))
+    }
+    
+    /// Persist an optimized plan onto a planned route: its totals, and the
+    /// plan's stops in order. Stops on the route but not in the plan go
+    /// back to the unrouted pool.
+    pub async fn apply_optimized_route(&self, route_id: &str, optimized: &OptimizedRoute) -> Result<DeliveryRoute> {
+        let route = self.get_route_header(route_id)?
+            .ok_or_else(|| WmsError::not_found("Route not found"))?;
+        if !route.status.is_planned() {
+            return Err(WmsError::conflict(format!(
+                "Route {} is {} and cannot be re-planned", route.route_number, route.status.as_str()
+            )));
+        }
+        for id in &optimized.stop_order {
+            let delivery = self.get_delivery(id).await?
+                .ok_or_else(|| WmsError::not_found(format!("Delivery {} not found", id)))?;
+            if delivery.route_id.as_ref().is_some_and(|other| other != route_id) {
+                return Err(WmsError::conflict(format!(
+                    "Delivery {} is on another route", delivery.delivery_number
+                )));
+            }
+        }
+        
+        self.db.transaction(|conn| {
+            conn.execute(
+                "UPDATE delivery_routes SET
+                    total_distance_km = ?, total_duration_minutes = ?, optimization_score = ?,
+                    updated_at = datetime('now')
+                 WHERE id = ?",
+                params![
+                    optimized.total_distance_km,
+                    optimized.estimated_duration_minutes,
+                    optimized.optimization_score,
+                    route_id,
+                ],
+            )?;
+            conn.execute(
+                "UPDATE deliveries SET route_id = NULL, sequence_number = NULL WHERE route_id = ?",
+                params![route_id],
+            )?;
+            for (seq, delivery_id) in optimized.stop_order.iter().enumerate() {
+                conn.execute(
+                    "UPDATE deliveries SET route_id = ?, sequence_number = ? WHERE id = ?",
+                    params![route_id, seq as u32 + 1, delivery_id],
+                )?;
+            }
+            Ok(())
+        })?;
+        
+        info!("Saved optimized plan onto route {}", route.route_number);
+        self.get_route(route_id).await?
+            .ok_or_else(|| WmsError::not_found("Route not found"))
+    }
+    
+    /// A route's stops in driving order with addresses and instructions
+    pub async fn get_route_manifest(&self, route_id: &str) -> Result<RouteManifest> {
+        let route = self.get_route(route_id).await?
+            .ok_or_else(|| WmsError::not_found("Route not found"))?;
+        
+        let stops = route.deliveries.into_iter()
+            .enumerate()
+            .map(|(i, delivery)| ManifestStop {
+                sequence: i as u32 + 1,
+                delivery_id: delivery.id,
+                delivery_number: delivery.delivery_number,
+                status: delivery.status,
+                delivery_address: delivery.delivery_address,
+                location: delivery.location,
+                time_window_start: delivery.time_window_start,
+                time_window_end: delivery.time_window_end,
+                instructions: delivery.delivery_instructions,
+                signature_required: delivery.signature_required,
+            })
+            .collect();
+        
+        Ok(RouteManifest {
+            route_id: route.id,
+            route_number: route.route_number,
+            route_date: route.route_date,
+            status: route.status,
+            driver_id: route.driver_id,
+            vehicle_id: route.vehicle_id,
+            total_distance_km: route.total_distance_km,
+            total_duration_minutes: route.total_duration_minutes,
+            stops,
+        })
+    }
+    
+    /// Routes planned for a date (without their stops)
+    pub async fn get_routes(&self, date: NaiveDate) -> Result<Vec<DeliveryRoute>> {
+        self.db.query_map(
+            "SELECT * FROM delivery_routes WHERE route_date = ? ORDER BY route_number",
+            params![date.to_string()],
+            |row| Self::row_to_route(row),
+        )
+    }
+    
+    /// Get a route with its stops in driving order
+    pub async fn get_route(&self, id: &str) -> Result<Option<DeliveryRoute>> {
+        let Some(mut route) = self.get_route_header(id)? else {
+            return Ok(None);
+        };
+        
+        route.deliveries = self.db.query_map(
+            "SELECT * FROM deliveries WHERE route_id = ? ORDER BY sequence_number",
+            params![id],
+            |row| Self::row_to_delivery(row),
+        )?;
+        Ok(Some(route))
+    }
+    
+    /// Check a driver's location against the delivery's geofence. An en
+    /// route delivery turns Arrived once the driver has stayed inside for
+    /// the dwell duration, not on a single reading inside. The tracked
+    /// state is kept per delivery in the database.
     pub async fn check_geofence(
         &self,
         delivery_id: &str,
@@ -208,23 +755,72 @@ impl DeliveryService {
         
         let delivery_location = delivery.location
             .ok_or_else(|| WmsError::validation("Delivery has no location"))?;
+        let geometry = GeofenceGeometry::Circle {
+            center: delivery_location,
+            radius_meters: delivery.geofence_radius_meters,
+        };
         
-        let result = self.geofence_checker.check_circle(
-            current_location,
-            delivery_location,
-            delivery.geofence_radius_meters,
-        );
+        let mut checker = self.geofence_checker.clone();
+        if let Some(state) = self.get_geofence_state(delivery_id)? {
+            checker.restore(delivery_id, state);
+        }
+        let result = checker.check_with_tracking(delivery_id, current_location, &geometry, self.clock.now());
+        if let Some(state) = checker.state(delivery_id) {
+            self.save_geofence_state(delivery_id, &state)?;
+        }
         
-        // If just entered geofence, auto-update status
-        if result.is_inside && delivery.status == DeliveryStatus::EnRoute {
+        if result.trigger_type == Some(GeofenceTrigger::Dwell) && delivery.status == DeliveryStatus::EnRoute {
             self.update_status(delivery_id, DeliveryStatus::Arrived, Some(current_location)).await?;
         }
         
         Ok(result)
     }
     
-    /// Record driver location
+    fn get_geofence_state(&self, delivery_id: &str) -> Result<Option<GeofenceState>> {
+        self.db.query_row(
+            "SELECT is_inside, entered_at, dwell_reported FROM delivery_geofence_states WHERE delivery_id = ?",
+            params![delivery_id],
+            |row| Ok(GeofenceState {
+                is_inside: row.get::<_, i32>("is_inside")? == 1,
+                entered_at: stored_timestamp(row, "entered_at")?,
+                dwell_reported: row.get::<_, i32>("dwell_reported")? == 1,
+            }),
+        )
+    }
+    
+    fn save_geofence_state(&self, delivery_id: &str, state: &GeofenceState) -> Result<()> {
+        self.db.execute(
+            "INSERT INTO delivery_geofence_states (delivery_id, is_inside, entered_at, dwell_reported, updated_at)
+             VALUES (?, ?, ?, ?, ?)
+             ON CONFLICT(delivery_id) DO UPDATE SET
+                is_inside = excluded.is_inside,
+                entered_at = excluded.entered_at,
+                dwell_reported = excluded.dwell_reported,
+                updated_at = excluded.updated_at",
+            params![
+                delivery_id,
+                state.is_inside,
+                state.entered_at.map(|t| t.to_rfc3339()),
+                state.dwell_reported,
+                self.clock.now().to_rfc3339(),
+            ],
+        )?;
+        Ok(())
+    }
+    
+    /// Record driver location. Coordinates must be valid and the reading
+    /// not stamped in the future (beyond `LOCATION_CLOCK_SKEW_SECONDS`).
     pub async fn record_location(&self, location: DriverLocation) -> Result<()> {
+        let GeoPoint { lat, lng } = location.location;
+        if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lng) {
+            return Err(WmsError::validation(format!("Invalid coordinates {}, {}", lat, lng)));
+        }
+        if location.recorded_at > self.clock.now() + Duration::seconds(LOCATION_CLOCK_SKEW_SECONDS) {
+            return Err(WmsError::validation(format!(
+                "Location recorded at {} is in the future", location.recorded_at.to_rfc3339()
+            )));
+        }
+        
         self.db.execute(
             "INSERT INTO driver_locations (
                 id, user_id, route_id, latitude, longitude,
@@ -246,7 +842,556 @@ impl DeliveryService {
         Ok(())
     }
     
+    /// A route's driver trail between `from` and `to` (either open),
+    /// thinned for drawing on a map
+    pub async fn get_route_breadcrumbs(
+        &self,
+        route_id: &str,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+    ) -> Result<Vec<DriverLocation>> {
+        let readings = self.db.query_map(
+            "SELECT * FROM driver_locations
+             WHERE route_id = ?1 AND (?2 IS NULL OR recorded_at >= ?2) AND (?3 IS NULL OR recorded_at <= ?3)
+             ORDER BY recorded_at, rowid",
+            params![route_id, from.map(|t| t.to_rfc3339()), to.map(|t| t.to_rfc3339())],
+            |row| Self::row_to_driver_location(row),
+        )?;
+        
+        Ok(downsample(readings, BREADCRUMB_POINTS_PER_MINUTE, BREADCRUMB_MAX_ACCURACY_METERS))
+    }
+    
+    /// The latest location recorded for a driver
+    pub async fn get_last_known_location(&self, user_id: &str) -> Result<Option<DriverLocation>> {
+        self.db.query_row(
+            "SELECT * FROM driver_locations WHERE user_id = ? ORDER BY recorded_at DESC, rowid DESC LIMIT 1",
+            params![user_id],
+            |row| Self::row_to_driver_location(row),
+        )
+    }
+    
+    /// Delete driver locations recorded more than `older_than_days` ago,
+    /// returning how many were removed
+    pub async fn purge_locations(&self, older_than_days: u32) -> Result<usize> {
+        let cutoff = self.clock.now() - Duration::days(older_than_days as i64);
+        let purged = self.db.execute(
+            "DELETE FROM driver_locations WHERE recorded_at < ?",
+            params![cutoff.to_rfc3339()],
+        )?;
+        if purged > 0 {
+            info!("Purged {} driver locations from before {}", purged, cutoff.date_naive());
+        }
+        Ok(purged)
+    }
+    
+    /// Purge driver locations past `DRIVER_LOCATION_RETENTION_DAYS` every
+    /// `interval` until shutdown
+    pub async fn run_location_purge(self: Arc<Self>, interval: std::time::Duration, task: TaskHandle) {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {}
+                _ = task.shutdown_requested() => break,
+            }
+            if let Err(e) = self.purge_locations(DRIVER_LOCATION_RETENTION_DAYS).await {
+                warn!("Unable to purge driver locations: {}", e);
+            }
+        }
+        info!("Driver location purge stopped");
+    }
+    
+    // ============ Customer Notification Operations ============
+    
+    /// Where a route stands, with arrival estimates for the stops left.
+    /// Each stop gets the plan's naive estimate and one adjusted from the
+    /// driver's last departure for scheduled breaks and delivery windows.
+    pub async fn get_route_progress(&self, route_id: &str) -> Result<RouteProgress> {
+        let route = self.get_route(route_id).await?
+            .ok_or_else(|| WmsError::not_found("Route not found"))?;
+        let breaks = self.get_route_breaks(route_id).await?;
+        let now = self.clock.now();
+        
+        let etas = estimate_route(&self.route_optimizer, &route, &breaks, now);
+        let remaining: Vec<StopProgress> = route.deliveries.iter()
+            .filter(|d| !d.status.is_terminal())
+            .zip(etas.stops)
+            .enumerate()
+            .map(|(i, (d, eta))| StopProgress {
+                delivery_id: d.id.clone(),
+                delivery_number: d.delivery_number.clone(),
+                status: d.status,
+                stops_away: i as u32,
+                eta: eta.adjusted,
+                naive_eta: eta.naive,
+                break_minutes: eta.break_minutes,
+                wait_minutes: eta.wait_minutes,
+            })
+            .collect();
+        
+        Ok(RouteProgress {
+            route_id: route.id.clone(),
+            route_number: route.route_number.clone(),
+            status: route.status,
+            completed_stops: (route.deliveries.len() - remaining.len()) as u32,
+            total_stops: route.deliveries.len() as u32,
+            delay_minutes: etas.delay_minutes,
+            remaining,
+            calculated_at: now,
+        })
+    }
+    
+    /// The driver's scheduled breaks on a route, earliest first
+    pub async fn get_route_breaks(&self, route_id: &str) -> Result<Vec<RouteBreak>> {
+        self.db.query_map(
+            "SELECT * FROM route_breaks WHERE route_id = ? ORDER BY starts_at",
+            params![route_id],
+            |row| Ok(RouteBreak {
+                id: row.get("id")?,
+                route_id: row.get("route_id")?,
+                starts_at: required_timestamp(row, "starts_at")?,
+                duration_minutes: row.get("duration_minutes")?,
+            }),
+        )
+    }
+    
+    /// Replace the driver's scheduled breaks on a route
+    pub async fn set_route_breaks(&self, route_id: &str, breaks: Vec<RouteBreak>) -> Result<Vec<RouteBreak>> {
+        if self.get_route_header(route_id)?.is_none() {
+            return Err(WmsError::not_found("Route not found"));
+        }
+        if breaks.iter().any(|b| b.duration_minutes == 0) {
+            return Err(WmsError::validation("Breaks must last at least a minute"));
+        }
+        
+        self.db.transaction(|conn| {
+            conn.execute("DELETE FROM route_breaks WHERE route_id = ?", params![route_id])?;
+            for b in &breaks {
+                conn.execute(
+                    "INSERT INTO route_breaks (id, route_id, starts_at, duration_minutes) VALUES (?, ?, ?, ?)",
+                    params![new_id(), route_id, b.starts_at.to_rfc3339(), b.duration_minutes],
+                )?;
+            }
+            Ok(())
+        })?;
+        
+        debug!("Scheduled {} breaks on route {}", breaks.len(), route_id);
+        self.get_route_breaks(route_id).await
+    }
+    
+    /// Notification templates for every trigger
+    pub async fn get_notification_rules(&self) -> Result<Vec<NotificationRule>> {
+        self.db.query_map(
+            "SELECT * FROM notification_rules ORDER BY trigger_type",
+            [],
+            |row| Self::row_to_notification_rule(row),
+        )
+    }
+    
+    /// Replace a trigger's templates
+    pub async fn save_notification_rule(&self, rule: NotificationRule) -> Result<NotificationRule> {
+        let unknown = rule.unknown_placeholders();
+        if !unknown.is_empty() {
+            return Err(WmsError::validation(format!(
+                "Unknown placeholders: {}. Available: {}",
+                unknown.join(", "),
+                PLACEHOLDERS.join(", ")
+            )));
+        }
+        
+        self.db.execute(
+            "INSERT INTO notification_rules (
+                trigger_type, sms_template, email_subject, email_template, is_active, updated_at
+            ) VALUES (?, ?, ?, ?, ?, ?)
+            ON CONFLICT(trigger_type) DO UPDATE SET
+                sms_template = excluded.sms_template,
+                email_subject = excluded.email_subject,
+                email_template = excluded.email_template,
+                is_active = excluded.is_active,
+                updated_at = excluded.updated_at",
+            params![
+                rule.trigger.as_str(),
+                &rule.sms_template,
+                &rule.email_subject,
+                &rule.email_template,
+                rule.is_active,
+                self.clock.now().to_rfc3339(),
+            ],
+        )?;
+        
+        info!("Updated {} notification templates", rule.trigger.as_str());
+        Ok(rule)
+    }
+    
+    /// Notifications queued or sent for a delivery, oldest first
+    pub async fn get_delivery_notifications(&self, delivery_id: &str) -> Result<Vec<DeliveryNotification>> {
+        self.db.query_map(
+            "SELECT * FROM delivery_notifications WHERE delivery_id = ? ORDER BY created_at, rowid",
+            params![delivery_id],
+            |row| Self::row_to_notification(row),
+        )
+    }
+    
+    /// Notifications waiting for the sender, oldest first
+    pub async fn get_queued_notifications(&self, limit: u32) -> Result<Vec<DeliveryNotification>> {
+        self.db.query_map(
+            "SELECT * FROM delivery_notifications WHERE status = 'queued'
+             ORDER BY created_at, rowid LIMIT ?",
+            params![limit],
+            |row| Self::row_to_notification(row),
+        )
+    }
+    
+    /// Record the sender's outcome for a notification; `error` marks it failed
+    pub async fn mark_notification_sent(&self, id: &str, error: Option<&str>) -> Result<()> {
+        let rows = self.db.execute(
+            "UPDATE delivery_notifications SET status = ?, sent_at = ?, error = ? WHERE id = ?",
+            params![
+                if error.is_some() { "failed" } else { "sent" },
+                self.clock.now().to_rfc3339(),
+                error,
+                id,
+            ],
+        )?;
+        if rows == 0 {
+            return Err(WmsError::not_found("Notification not found"));
+        }
+        Ok(())
+    }
+    
+    /// Send every customer on a just-started route their arrival window
+    async fn notify_route_started(&self, route_id: &str) -> Result<()> {
+        let Some(rule) = self.active_notification_rule(NotificationTrigger::RouteStarted)? else {
+            return Ok(());
+        };
+        let progress = self.get_route_progress(route_id).await?;
+        let buffer = Duration::minutes(ETA_WINDOW_BUFFER_MINUTES);
+        
+        for stop in &progress.remaining {
+            let Some(eta) = stop.eta else {
+                continue;
+            };
+            let Some(delivery) = self.get_delivery(&stop.delivery_id).await? else {
+                continue;
+            };
+            let mut values = Self::notification_values(&delivery);
+            values.insert("window_start", format_time(eta - buffer));
+            values.insert("window_end", format_time(eta + buffer));
+            values.insert("eta", format_time(eta));
+            values.insert("stops_away", stop.stops_away.to_string());
+            self.queue_notification(&rule, &delivery, &values)?;
+        }
+        Ok(())
+    }
+    
+    /// A stop on a started route is finished: confirm it if delivered, and
+    /// tell the next customer they are up
+    async fn notify_stop_finished(&self, delivery: &Delivery) -> Result<()> {
+        let Some(route_id) = &delivery.route_id else {
+            return Ok(());
+        };
+        
+        if delivery.status == DeliveryStatus::Delivered
+            && let Some(rule) = self.active_notification_rule(NotificationTrigger::Delivered)?
+        {
+            let photos: i64 = self.db.query_row(
+                "SELECT COUNT(*) FROM attachments
+                 WHERE owner_type = ? AND owner_id = ? AND kind = ?",
+                params![DELIVERY_OWNER, &delivery.id, AttachmentKind::ProofOfDelivery.as_str()],
+                |row| row.get(0),
+            )?.unwrap_or(0);
+            let mut values = Self::notification_values(delivery);
+            values.insert("delivered_at", format_time(self.clock.now()));
+            values.insert("signed_by", delivery.signature_name.clone().unwrap_or_default());
+            values.insert("pod_summary", pod_summary(
+                delivery.signature_name.as_deref(),
+                delivery.delivery_notes.as_deref(),
+                photos as usize,
+            ));
+            self.queue_notification(&rule, delivery, &values)?;
+        }
+        
+        let progress = self.get_route_progress(route_id).await?;
+        if progress.status != RouteStatus::InProgress {
+            return Ok(());
+        }
+        let (Some(next), Some(rule)) = (
+            progress.next_stop(),
+            self.active_notification_rule(NotificationTrigger::UpNext)?,
+        ) else {
+            return Ok(());
+        };
+        let Some(next_delivery) = self.get_delivery(&next.delivery_id).await? else {
+            return Ok(());
+        };
+        let mut values = Self::notification_values(&next_delivery);
+        values.insert("eta", next.eta.map(format_time).unwrap_or_else(|| "soon".to_string()));
+        values.insert("stops_away", "0".to_string());
+        self.queue_notification(&rule, &next_delivery, &values)?;
+        Ok(())
+    }
+    
+    fn active_notification_rule(&self, trigger: NotificationTrigger) -> Result<Option<NotificationRule>> {
+        self.db.query_row(
+            "SELECT * FROM notification_rules WHERE trigger_type = ? AND is_active = 1",
+            params![trigger.as_str()],
+            |row| Self::row_to_notification_rule(row),
+        )
+    }
+    
+    /// Placeholder values every trigger has
+    fn notification_values(delivery: &Delivery) -> HashMap<&'static str, String> {
+        HashMap::from([
+            ("customer_name", delivery.delivery_address.name.clone()),
+            ("delivery_number", delivery.delivery_number.clone()),
+        ])
+    }
+    
+    /// Queue the rule's messages for the delivery's phone and email, unless
+    /// the customer opted out. A delivery gets each trigger once per channel.
+    fn queue_notification(
+        &self,
+        rule: &NotificationRule,
+        delivery: &Delivery,
+        values: &HashMap<&str, String>,
+    ) -> Result<usize> {
+        if let Some(customer_id) = &delivery.customer_id {
+            let opted_out = self.db.query_row(
+                "SELECT notifications_opt_out FROM customers WHERE id = ?",
+                params![customer_id],
+                |row| row.get::<_, i32>(0),
+            )?.unwrap_or(0) == 1;
+            if opted_out {
+                debug!("Customer {} opted out of delivery notifications", customer_id);
+                return Ok(0);
+            }
+        }
+        
+        let contact = |value: &Option<String>| {
+            value.as_deref().map(str::trim).filter(|v| !v.is_empty()).map(String::from)
+        };
+        let mut messages = Vec::new();
+        if let (Some(phone), Some(template)) = (contact(&delivery.delivery_address.phone), &rule.sms_template) {
+            messages.push((NotificationChannel::Sms, phone, None, render_template(template, values)));
+        }
+        if let (Some(email), Some(template)) = (contact(&delivery.delivery_address.email), &rule.email_template) {
+            let subject = rule.email_subject.as_deref().map(|s| render_template(s, values));
+            messages.push((NotificationChannel::Email, email, subject, render_template(template, values)));
+        }
+        
+        let now = self.clock.now().to_rfc3339();
+        let mut queued = 0;
+        for (channel, recipient, subject, body) in messages {
+            queued += self.db.execute(
+                "INSERT OR IGNORE INTO delivery_notifications (
+                    id, delivery_id, route_id, trigger_type, channel, recipient,
+                    subject, body, status, created_at
+                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, 'queued', ?)",
+                params![
+                    new_id(),
+                    &delivery.id,
+                    &delivery.route_id,
+                    rule.trigger.as_str(),
+                    channel.as_str(),
+                    &recipient,
+                    &subject,
+                    &body,
+                    &now,
+                ],
+            )?;
+        }
+        
+        if queued > 0 {
+            debug!("Queued {} {} notifications for {}", queued, rule.trigger.as_str(), delivery.delivery_number);
+        }
+        Ok(queued)
+    }
+    
+    // ============ Proof of Delivery Operations ============
+    
+    /// Record a signature, photo or note captured at the door. Signatures
+    /// and photos must be images of at most `MAX_PROOF_BYTES`; a note's
+    /// data is its UTF-8 text.
+    pub async fn attach_proof(
+        &self,
+        ctx: &RequestContext,
+        delivery_id: &str,
+        proof_type: ProofType,
+        data: Vec<u8>,
+        captured_at: DateTime<Utc>,
+        location: Option<GeoPoint>,
+    ) -> Result<DeliveryProof> {
+        let captured_by = ctx.actor()?;
+        let delivery = self.get_delivery(delivery_id).await?
+            .ok_or_else(|| WmsError::not_found("Delivery not found"))?;
+        
+        let (attachment, note) = match proof_type {
+            ProofType::Note => {
+                let note = String::from_utf8(data)
+                    .map_err(|_| WmsError::validation("A note must be UTF-8 text"))?;
+                let note = note.trim();
+                if note.is_empty() {
+                    return Err(WmsError::validation("A note can't be empty"));
+                }
+                (None, Some(note.to_string()))
+            }
+            ProofType::Signature | ProofType::Photo => {
+                let (kind, filename) = match proof_type {
+                    ProofType::Signature => (AttachmentKind::Signature, "signature"),
+                    _ => (AttachmentKind::ProofOfDelivery, "photo"),
+                };
+                let attachment = self.attachments.add(NewAttachment {
+                    owner_type: DELIVERY_OWNER.to_string(),
+                    owner_id: delivery.id.clone(),
+                    kind,
+                    filename: format!("{}-{}", delivery.delivery_number, filename),
+                    content: data,
+                    created_by: captured_by.to_string(),
+                }).await?;
+                (Some(attachment.id), None)
+            }
+        };
+        
+        let proof = DeliveryProof {
+            id: new_id(),
+            delivery_id: delivery.id.clone(),
+            proof_type,
+            attachment_id: attachment,
+            note,
+            location,
+            captured_by: captured_by.to_string(),
+            captured_at,
+            data: None,
+        };
+        self.db.execute(
+            "INSERT INTO delivery_proofs (
+                id, delivery_id, proof_type, attachment_id, note, latitude, longitude,
+                captured_by, captured_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            params![
+                &proof.id,
+                &proof.delivery_id,
+                proof.proof_type.as_str(),
+                &proof.attachment_id,
+                &proof.note,
+                proof.location.map(|p| p.lat),
+                proof.location.map(|p| p.lng),
+                &proof.captured_by,
+                proof.captured_at.to_rfc3339(),
+            ],
+        )?;
+        
+        info!("Captured {} for delivery {}", proof_type.as_str(), delivery.delivery_number);
+        Ok(proof)
+    }
+    
+    /// A delivery's proofs in capture order, signatures and photos with
+    /// their base64 encoded image
+    pub async fn get_proofs(&self, delivery_id: &str) -> Result<Vec<DeliveryProof>> {
+        let mut proofs = self.db.query_map(
+            "SELECT * FROM delivery_proofs WHERE delivery_id = ? ORDER BY captured_at, rowid",
+            params![delivery_id],
+            |row| Self::row_to_proof(row),
+        )?;
+        
+        for proof in &mut proofs {
+            if let Some(attachment_id) = &proof.attachment_id {
+                let content = self.attachments.content(attachment_id).await?;
+                proof.data = Some(base64::engine::general_purpose::STANDARD.encode(content));
+            }
+        }
+        Ok(proofs)
+    }
+    
+    /// Deliveries requiring a signature need one before they are delivered
+    async fn check_signature(&self, delivery_id: &str) -> Result<()> {
+        let Some(delivery) = self.get_delivery(delivery_id).await? else {
+            return Ok(());
+        };
+        if !delivery.signature_required {
+            return Ok(());
+        }
+        
+        let signatures: i64 = self.db.query_row(
+            "SELECT COUNT(*) FROM delivery_proofs WHERE delivery_id = ? AND proof_type = ?",
+            params![delivery_id, ProofType::Signature.as_str()],
+            |row| row.get(0),
+        )?.unwrap_or(0);
+        if signatures == 0 {
+            return Err(WmsError::validation(format!(
+                "Delivery {} requires a signature before it can be marked delivered",
+                delivery.delivery_number
+            )));
+        }
+        Ok(())
+    }
+    
+    // ============ Cold Chain Operations ============
+    
+    /// Log a probe reading for a delivery. A reading outside the strictest
+    /// range among the shipment's item categories sets a temperature
+    /// warning on the delivery.
+    pub async fn record_temperature(
+        &self,
+        ctx: &RequestContext,
+        delivery_id: &str,
+        probe_id: Option<&str>,
+        celsius: f64,
+    ) -> Result<TemperatureReading> {
+        let delivery = self.get_delivery(delivery_id).await?
+            .ok_or_else(|| WmsError::not_found(format!("Delivery {} not found", delivery_id)))?;
+        
+        let categories = match &delivery.shipment_id {
+            Some(shipment_id) => self.db.query_map(
+                "SELECT DISTINCT i.category FROM shipment_items si
+                 JOIN inventory_items i ON i.id = si.item_id
+                 WHERE si.shipment_id = ? AND i.category IS NOT NULL",
+                params![shipment_id],
+                |row| row.get::<_, String>(0),
+            )?,
+            None => Vec::new(),
+        };
+        let range = self.temperature.range_for_categories(&categories).await?;
+        let reading = self.temperature.record(
+            ctx,
+            TemperatureOwner::Delivery(delivery_id.to_string()),
+            probe_id,
+            celsius,
+            range.as_ref(),
+        ).await?;
+        
+        if let (true, Some(range)) = (reading.out_of_range, &range) {
+            let warning = format!(
+                "{:.1} °C at {}, outside {}",
+                celsius,
+                format_time(reading.recorded_at),
+                range.describe()
+            );
+            self.db.execute(
+                "UPDATE deliveries SET temperature_warning = ?, updated_at = ? WHERE id = ?",
+                params![&warning, self.clock.now().to_rfc3339(), delivery_id],
+            )?;
+            warn!("Delivery {} temperature warning: {}", delivery.delivery_number, warning);
+        }
+        
+        Ok(reading)
+    }
+    
     /// Get delivery by ID
+    /// Weight and volume of the delivery's shipment, from its top-level
+    /// packages; a pallet's weight already includes its cartons
+    fn delivery_demand(&self, delivery: &Delivery) -> Result<StopDemand> {
+        let Some(shipment_id) = &delivery.shipment_id else {
+            return Ok(StopDemand::default());
+        };
+        let demand = self.db.query_row(
+            "SELECT COALESCE(SUM(weight_kg), 0), COALESCE(SUM(length_cm * width_cm * height_cm), 0) / 1000000.0
+             FROM shipment_packages WHERE shipment_id = ? AND parent_package_id IS NULL",
+            params![shipment_id],
+            |row| Ok(StopDemand { weight_kg: row.get(0)?, volume_m3: row.get(1)? }),
+        )?;
+        Ok(demand.unwrap_or_default())
+    }
+    
     async fn get_delivery(&self, id: &str) -> Result<Option<Delivery>> {
         self.db.query_row(
             "SELECT * FROM deliveries WHERE id = ?",
@@ -255,22 +1400,30 @@ impl DeliveryService {
         )
     }
     
-    /// Record status history
-    async fn record_status_history(
-        &self,
+    fn get_route_header(&self, id: &str) -> Result<Option<DeliveryRoute>> {
+        self.db.query_row(
+            "SELECT * FROM delivery_routes WHERE id = ?",
+            params![id],
+            |row| Self::row_to_route(row),
+        )
+    }
+    
+    /// Record status history in the caller's transaction
+    fn record_status_history(
+        conn: &rusqlite::Connection,
         delivery_id: &str,
         status: DeliveryStatus,
         location: Option<GeoPoint>,
         notes: Option<&str>,
     ) -> Result<()> {
-        self.db.execute(
+        conn.execute(
             "INSERT INTO delivery_status_history (
                 id, delivery_id, status, latitude, longitude, notes, recorded_at
             ) VALUES (?, ?, ?, ?, ?, ?, datetime('now'))",
             params![
                 new_id(),
                 delivery_id,
-                format!("{:?}", status).to_lowercase(),
+                status.as_str(),
                 location.map(|l| l.lat),
                 location.map(|l| l.lng),
                 notes,
@@ -281,16 +1434,132 @@ impl DeliveryService {
     }
     
     fn generate_delivery_number(&self) -> Result<String> {
-        let count: i64 = self.db.query_row(
-            "SELECT COUNT(*) + 1 FROM deliveries",
-            [],
-            |row| row.get(0),
-        )?.unwrap_or(1);
+        DocumentSequence::Delivery.next_number(&self.db)
+    }
+    
+    fn generate_route_number(&self) -> Result<String> {
+        DocumentSequence::Route.next_number(&self.db)
+    }
+    
+    fn row_to_route(row: &rusqlite::Row) -> rusqlite::Result<DeliveryRoute> {
+        let point = |lat: &str, lng: &str| -> rusqlite::Result<Option<GeoPoint>> {
+            Ok(match (row.get::<_, Option<f64>>(lat)?, row.get::<_, Option<f64>>(lng)?) {
+                (Some(la), Some(ln)) => Some(GeoPoint::new(la, ln)),
+                _ => None,
+            })
+        };
+        let timestamp = |column: &str| stored_timestamp(row, column);
+        let route_date: String = row.get("route_date")?;
+        let route_date = route_date.get(..10)
+            .and_then(|d| d.parse::<NaiveDate>().ok())
+            .and_then(|d| d.and_hms_opt(0, 0, 0))
+            .map(|d| d.and_utc())
+            .ok_or_else(|| unreadable(row, "route_date", &route_date))?;
         
-        Ok(format!("DEL-{:08}", count))
+        Ok(DeliveryRoute {
+            id: row.get("id")?,
+            route_number: row.get::<_, Option<String>>("route_number")?.unwrap_or_default(),
+            route_name: row.get("route_name")?,
+            route_date,
+            driver_id: row.get("driver_id")?,
+            vehicle_id: row.get("vehicle_id")?,
+            status: stored_status(row, "status", RouteStatus::parse)?,
+            start_location: point("start_location_lat", "start_location_lng")?,
+            end_location: point("end_location_lat", "end_location_lng")?,
+            planned_start: timestamp("planned_start_time")?,
+            actual_start: timestamp("actual_start_time")?,
+            planned_end: timestamp("planned_end_time")?,
+            actual_end: timestamp("actual_end_time")?,
+            total_distance_km: row.get("total_distance_km")?,
+            total_duration_minutes: row.get("total_duration_minutes")?,
+            optimization_score: row.get("optimization_score")?,
+            start_odometer_km: row.get("start_odometer_km")?,
+            end_odometer_km: row.get("end_odometer_km")?,
+            notes: row.get("notes")?,
+            created_by: row.get("created_by")?,
+            created_at: required_timestamp(row, "created_at")?,
+            deliveries: Vec::new(),
+        })
+    }
+    
+    fn row_to_proof(row: &rusqlite::Row) -> rusqlite::Result<DeliveryProof> {
+        let location = match (row.get::<_, Option<f64>>("latitude")?, row.get::<_, Option<f64>>("longitude")?) {
+            (Some(lat), Some(lng)) => Some(GeoPoint::new(lat, lng)),
+            _ => None,
+        };
+        
+        Ok(DeliveryProof {
+            id: row.get("id")?,
+            delivery_id: row.get("delivery_id")?,
+            proof_type: stored_status(row, "proof_type", ProofType::parse)?,
+            attachment_id: row.get("attachment_id")?,
+            note: row.get("note")?,
+            location,
+            captured_by: row.get("captured_by")?,
+            captured_at: required_timestamp(row, "captured_at")?,
+            data: None,
+        })
+    }
+    
+    fn row_to_driver_location(row: &rusqlite::Row) -> rusqlite::Result<DriverLocation> {
+        Ok(DriverLocation {
+            id: row.get("id")?,
+            user_id: row.get("user_id")?,
+            route_id: row.get("route_id")?,
+            location: GeoPoint::new(row.get("latitude")?, row.get("longitude")?),
+            accuracy_meters: row.get("accuracy_meters")?,
+            speed_kmh: row.get("speed_kmh")?,
+            heading: row.get("heading")?,
+            recorded_at: required_timestamp(row, "recorded_at")?,
+        })
+    }
+    
+    fn row_to_vehicle(row: &rusqlite::Row) -> rusqlite::Result<Vehicle> {
+        Ok(Vehicle {
+            id: row.get("id")?,
+            vehicle_number: row.get("vehicle_number")?,
+            name: row.get("name")?,
+            vehicle_type: stored_status(row, "vehicle_type", VehicleType::parse)?,
+            license_plate: row.get("license_plate")?,
+            capacity_kg: row.get("capacity_kg")?,
+            capacity_m3: row.get("capacity_m3")?,
+            shift_minutes: row.get("shift_minutes")?,
+            is_active: row.get::<_, i32>("is_active")? == 1,
+            created_at: required_timestamp(row, "created_at")?,
+        })
+    }
+    
+    fn row_to_notification_rule(row: &rusqlite::Row) -> rusqlite::Result<NotificationRule> {
+        Ok(NotificationRule {
+            trigger: stored_status(row, "trigger_type", NotificationTrigger::parse)?,
+            sms_template: row.get("sms_template")?,
+            email_subject: row.get("email_subject")?,
+            email_template: row.get("email_template")?,
+            is_active: row.get::<_, i32>("is_active")? == 1,
+        })
+    }
+    
+    fn row_to_notification(row: &rusqlite::Row) -> rusqlite::Result<DeliveryNotification> {
+        Ok(DeliveryNotification {
+            id: row.get("id")?,
+            delivery_id: row.get("delivery_id")?,
+            route_id: row.get("route_id")?,
+            trigger: stored_status(row, "trigger_type", NotificationTrigger::parse)?,
+            channel: stored_status(row, "channel", NotificationChannel::parse)?,
+            recipient: row.get("recipient")?,
+            subject: row.get("subject")?,
+            body: row.get("body")?,
+            status: row.get("status")?,
+            created_at: required_timestamp(row, "created_at")?,
+            sent_at: stored_timestamp(row, "sent_at")?,
+            error: row.get("error")?,
+        })
     }
     
     fn row_to_delivery(row: &rusqlite::Row) -> rusqlite::Result<Delivery> {
+        let scheduled_date: String = row.get("scheduled_date")?;
+        let scheduled_date = parse_scheduled_date(&scheduled_date)
+            .ok_or_else(|| unreadable(row, "scheduled_date", &scheduled_date))?;
         let lat: Option<f64> = row.get("latitude")?;
         let lng: Option<f64> = row.get("longitude")?;
         let location = match (lat, lng) {
@@ -303,7 +1572,7 @@ impl DeliveryService {
             delivery_number: row.get("delivery_number")?,
             route_id: row.get("route_id")?,
             shipment_id: row.get("shipment_id")?,
-            status: DeliveryStatus::Pending, // Parse from string
+            status: stored_status(row, "status", DeliveryStatus::parse)?,
             sequence_number: row.get("sequence_number")?,
             customer_id: row.get("customer_id")?,
             delivery_address: DeliveryAddress {
@@ -321,20 +1590,678 @@ impl DeliveryService {
             },
             location,
             geofence_radius_meters: row.get("geofence_radius_meters").unwrap_or(100.0),
-            scheduled_date: Utc::now(), // Parse from string
+            scheduled_date,
             time_window_start: row.get("scheduled_time_window_start")?,
             time_window_end: row.get("scheduled_time_window_end")?,
             estimated_arrival: None,
-            actual_arrival: None,
-            actual_departure: None,
+            actual_arrival: stored_timestamp(row, "actual_arrival_time")?,
+            actual_departure: stored_timestamp(row, "actual_departure_time")?,
             delivery_instructions: row.get("delivery_instructions")?,
             signature_required: row.get::<_, i32>("signature_required").unwrap_or(0) == 1,
             signature_name: row.get("signature_name")?,
             delivery_notes: row.get("delivery_notes")?,
             failure_reason: row.get("failure_reason")?,
+            temperature_warning: row.get("temperature_warning")?,
+            custom_fields: parse_custom_fields(row.get("custom_fields")?),
             created_at: Utc::now(),
             updated_at: None,
+            version: row.get("version")?,
         })
     }
 }
 
+/// Scheduled date as stored: an RFC3339 timestamp, or a plain date
+/// meaning midnight UTC
+fn parse_scheduled_date(value: &str) -> Option<DateTime<Utc>> {
+    parse_timestamp(value).or_else(|| {
+        NaiveDate::parse_from_str(value.get(..10)?, "%Y-%m-%d").ok()
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+            .map(|time| time.and_utc())
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use wms_core::Clock;
+    use wms_core::events::RecordingEmitter;
+    use wms_core::test_support::{
+        DeliveryBuilder, FailingRecorder, ItemBuilder, ShipmentBuilder, fixed_clock, test_context, test_db,
+    };
+    use crate::routing::UnassignedReason;
+    
+    fn test_service() -> DeliveryService {
+        let db = Database::new(&PathBuf::from(":memory:"), "test-key").unwrap();
+        db.migrate_to_latest().unwrap();
+        db.execute(
+            "INSERT INTO users (id, username, email, full_name) VALUES ('u1', 'dispatch', 'dispatch@example.com', 'Dana Dispatch')",
+            [],
+        ).unwrap();
+        DeliveryService::new(Arc::new(db))
+    }
+    
+    async fn create_stop(service: &DeliveryService, name: &str, lat: f64, lng: f64) -> Delivery {
+        let delivery: Delivery = serde_json::from_value(serde_json::json!({
+            "id": "",
+            "delivery_number": "",
+            "status": "pending",
+            "delivery_address": {
+                "name": name,
+                "address": {
+                    "line1": "1 Main St",
+                    "city": "Springfield",
+                    "state": "IL",
+                    "postal_code": "62701",
+                    "country": "US"
+                }
+            },
+            "location": { "lat": lat, "lng": lng },
+            "scheduled_date": "2025-03-03T00:00:00Z",
+            "created_at": "2025-03-03T00:00:00Z"
+        })).unwrap();
+        service.create_delivery(delivery).await.unwrap()
+    }
+    
+    fn depot() -> GeoPoint {
+        GeoPoint::new(39.78, -89.65)
+    }
+    
+    fn date() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 3, 3).unwrap()
+    }
+    
+    #[tokio::test]
+    async fn test_create_route_persists_plan() {
+        let service = test_service();
+        let far = create_stop(&service, "Far", 39.90, -89.65).await;
+        let near = create_stop(&service, "Near", 39.80, -89.65).await;
+        let ids = vec![far.id.clone(), near.id.clone()];
+        
+        let route = service.create_route(&test_context(), &ids, depot(), date(), None).await.unwrap();
+        
+        assert_eq!(route.route_number, "RTE-00000001");
+        assert_eq!(route.status, RouteStatus::Planning);
+        assert!(route.total_distance_km.unwrap() > 0.0);
+        let stops: Vec<&str> = route.deliveries.iter().map(|d| d.id.as_str()).collect();
+        assert_eq!(stops, vec![near.id.as_str(), far.id.as_str()]);
+        assert!(route.deliveries.iter().all(|d| d.route_id.as_deref() == Some(route.id.as_str())));
+        
+        // Planning the same deliveries again updates the route in place
+        let again = service.create_route(&test_context(), &ids, depot(), date(), Some("u1")).await.unwrap();
+        assert_eq!(again.id, route.id);
+        assert_eq!(again.driver_id.as_deref(), Some("u1"));
+        assert_eq!(service.get_routes(date()).await.unwrap().len(), 1);
+    }
+    
+    #[tokio::test]
+    async fn test_route_keeps_delivery_windows() {
+        let service = test_service();
+        let east = |km: f64| -89.65 + km / 85.5;
+        let near = create_stop(&service, "Near", 39.78, east(2.0)).await;
+        let next = create_stop(&service, "Next", 39.78, east(4.0)).await;
+        let urgent = create_stop(&service, "Urgent", 39.78, east(-6.0)).await;
+        let window = |end: &str| {
+            service.db.execute(
+                "UPDATE deliveries SET scheduled_time_window_start = '08:00', scheduled_time_window_end = ?
+                 WHERE id = ?",
+                params![end, &urgent.id],
+            ).unwrap();
+        };
+        let ids = vec![near.id.clone(), next.id.clone(), urgent.id.clone()];
+        
+        // Routes leave at 08:00; nearest-first would reach Urgent at 08:31
+        window("08:20");
+        let route = service.optimize_route(&ids, depot(), &CancellationToken::new()).await.unwrap();
+        assert_eq!(route.stop_order[0], urgent.id);
+        assert!(route.window_violations.is_empty());
+        
+        // Nine minutes away, Urgent cannot make 08:05
+        window("08:05");
+        DeliveryWindowSettings { max_violations: Some(0), ..Default::default() }.save(&service.db).unwrap();
+        assert!(service.optimize_route(&ids, depot(), &CancellationToken::new()).await.is_err());
+        let sequenced: Option<u32> = service.db.query_row(
+            "SELECT sequence_number FROM deliveries WHERE id = ?",
+            params![&near.id],
+            |row| row.get(0),
+        ).unwrap().flatten();
+        assert_eq!(sequenced, Some(2), "the earlier order is kept");
+        
+        DeliveryWindowSettings::default().save(&service.db).unwrap();
+        let late = service.optimize_route(&ids, depot(), &CancellationToken::new()).await.unwrap();
+        assert_eq!(late.window_violations.len(), 1);
+        assert_eq!(late.window_violations[0].delivery_id, urgent.id);
+    }
+    
+    #[tokio::test]
+    async fn test_fleet_splits_deliveries_by_package_weight() {
+        let db = test_db();
+        for (n, lng) in [(1, -89.62), (2, -89.61), (3, -89.68)] {
+            let shipment = format!("shp-{}", n);
+            ShipmentBuilder::new(&format!("SHP-{}", n)).insert::<serde_json::Value>(&db);
+            DeliveryBuilder::new(&format!("DEL-{}", n)).shipment(&shipment).at(39.78, lng).insert::<serde_json::Value>(&db);
+            // A 200 kg pallet holding two 100 kg cartons
+            db.execute(
+                "INSERT INTO shipment_packages (id, shipment_id, package_number, weight_kg, length_cm, width_cm, height_cm)
+                 VALUES (?, ?, 1, 200, 120, 100, 100)",
+                params![format!("{}-pallet", shipment), &shipment],
+            ).unwrap();
+            for carton in 2..=3 {
+                db.execute(
+                    "INSERT INTO shipment_packages (id, shipment_id, package_number, weight_kg, parent_package_id)
+                     VALUES (?, ?, ?, 100, ?)",
+                    params![format!("{}-{}", shipment, carton), &shipment, carton, format!("{}-pallet", shipment)],
+                ).unwrap();
+            }
+        }
+        db.execute(
+            "INSERT INTO vehicles (id, vehicle_number, vehicle_type, capacity_kg, capacity_m3)
+             VALUES ('van-1', 'VAN-1', 'van', 500, 10), ('van-2', 'VAN-2', 'van', 500, 10),
+                    ('van-3', 'VAN-3', 'van', 500, 10)",
+            [],
+        ).unwrap();
+        db.execute("UPDATE vehicles SET is_active = 0 WHERE id = 'van-3'", []).unwrap();
+        let service = DeliveryService::new(db);
+        let ids: Vec<String> = (1..=3).map(|n| format!("del-{}", n)).collect();
+        
+        let plan = service.optimize_fleet(&ids, depot(), None, &CancellationToken::new()).await.unwrap();
+        assert!(plan.unassigned.is_empty());
+        let split: Vec<(&str, usize, f64)> = plan.routes.iter()
+            .map(|r| (r.vehicle_id.as_str(), r.route.stop_order.len(), r.load.weight_kg))
+            .collect();
+        assert_eq!(split, vec![("van-1", 2, 400.0), ("van-2", 1, 200.0)]);
+        assert!((plan.routes[1].load.volume_m3 - 1.2).abs() < 1e-9);
+        
+        let one_van = vec!["van-1".to_string()];
+        let plan = service.optimize_fleet(&ids, depot(), Some(&one_van), &CancellationToken::new()).await.unwrap();
+        assert_eq!(plan.routes.len(), 1);
+        assert_eq!(plan.unassigned.len(), 1);
+        assert_eq!(plan.unassigned[0].reason, UnassignedReason::FleetFull);
+        
+        let retired = vec!["van-3".to_string()];
+        assert!(service.optimize_fleet(&ids, depot(), Some(&retired), &CancellationToken::new()).await.is_err());
+    }
+    
+    #[tokio::test]
+    async fn test_status_change_emits_event() {
+        let service = test_service();
+        let stop = create_stop(&service, "Dock", 39.80, -89.65).await;
+        let events = Arc::new(RecordingEmitter::new());
+        let service = DeliveryService::new(service.db.clone()).with_event_emitter(events.clone());
+        
+        service.update_status(&stop.id, DeliveryStatus::EnRoute, None).await.unwrap();
+        
+        assert_eq!(events.events(), vec![WmsEvent::DeliveryStatusChanged {
+            delivery_id: stop.id.clone(),
+            delivery_number: stop.delivery_number.clone(),
+            status: "en_route".to_string(),
+        }]);
+    }
+    
+    #[tokio::test]
+    async fn test_status_change_is_undone_when_sync_cannot_record_it() {
+        let service = test_service();
+        let stop = create_stop(&service, "Dock", 39.80, -89.65).await;
+        
+        let failing = DeliveryService::new(service.db.clone()).with_change_recorder(Arc::new(FailingRecorder));
+        assert!(failing.update_status(&stop.id, DeliveryStatus::EnRoute, None).await.is_err());
+        let after = service.get_delivery(&stop.id).await.unwrap().unwrap();
+        assert_eq!((after.status, after.version), (DeliveryStatus::Pending, stop.version));
+        let history: i64 = service.db.query_row(
+            "SELECT COUNT(*) FROM delivery_status_history WHERE delivery_id = ?",
+            params![&stop.id],
+            |row| row.get(0),
+        ).unwrap().unwrap();
+        assert_eq!(history, 0);
+    }
+    
+    #[tokio::test]
+    async fn test_arrival_needs_dwell_inside_geofence() {
+        let clock = fixed_clock();
+        let service = test_service().with_clock(clock.clone());
+        let stop = create_stop(&service, "Dock", 39.80, -89.65).await;
+        service.update_status(&stop.id, DeliveryStatus::EnRoute, None).await.unwrap();
+        let (outside, edge, inside) = (
+            GeoPoint::new(39.81, -89.65),
+            GeoPoint::new(39.8008, -89.65),
+            GeoPoint::new(39.8001, -89.65),
+        );
+        
+        // Clipping the fence edge once
+        for point in [outside, edge, outside] {
+            service.check_geofence(&stop.id, point).await.unwrap();
+            clock.advance(Duration::minutes(1));
+        }
+        let current = service.get_delivery(&stop.id).await.unwrap().unwrap();
+        assert_eq!(current.status, DeliveryStatus::EnRoute);
+        
+        // Staying inside, checked by a freshly created service each time
+        let mut triggers = Vec::new();
+        for _ in 0..4 {
+            let service = DeliveryService::new(service.db.clone()).with_clock(clock.clone());
+            triggers.push(service.check_geofence(&stop.id, inside).await.unwrap().trigger_type);
+            clock.advance(Duration::minutes(1));
+        }
+        assert_eq!(triggers, vec![Some(GeofenceTrigger::Enter), None, None, Some(GeofenceTrigger::Dwell)]);
+        let current = service.get_delivery(&stop.id).await.unwrap().unwrap();
+        assert_eq!(current.status, DeliveryStatus::Arrived);
+    }
+    
+    fn reading(route_id: Option<&str>, at: DateTime<Utc>, accuracy_meters: f64) -> DriverLocation {
+        DriverLocation {
+            id: String::new(),
+            user_id: "u1".to_string(),
+            route_id: route_id.map(String::from),
+            location: GeoPoint::new(39.80, -89.65),
+            accuracy_meters: Some(accuracy_meters),
+            speed_kmh: None,
+            heading: None,
+            recorded_at: at,
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_location_history_and_purge() {
+        let clock = fixed_clock();
+        let service = test_service().with_clock(clock.clone());
+        let stop = create_stop(&service, "Tracked", 39.80, -89.65).await;
+        let route = service.create_route(&test_context(), std::slice::from_ref(&stop.id), depot(), date(), Some("u1")).await.unwrap();
+        let now = clock.now();
+        
+        // Twenty readings a minute for the last three minutes, one of them
+        // too inaccurate to draw
+        for second in (0..180).step_by(3) {
+            let accuracy = if second == 150 { 500.0 } else { 10.0 };
+            let at = now - Duration::seconds(180 - second);
+            service.record_location(reading(Some(&route.id), at, accuracy)).await.unwrap();
+        }
+        let trail = service.get_route_breadcrumbs(&route.id, None, None).await.unwrap();
+        assert!(trail.len() <= 3 * BREADCRUMB_POINTS_PER_MINUTE, "{} points", trail.len());
+        assert!(trail.iter().all(|r| r.accuracy_meters == Some(10.0)));
+        let last_minute = service.get_route_breadcrumbs(&route.id, Some(now - Duration::seconds(60)), None).await.unwrap();
+        assert!(last_minute.iter().all(|r| r.recorded_at >= now - Duration::seconds(60)));
+        assert!(!last_minute.is_empty() && last_minute.len() < trail.len());
+        
+        let latest = service.get_last_known_location("u1").await.unwrap().unwrap();
+        assert_eq!(latest.recorded_at, now - Duration::seconds(3));
+        
+        // Bad coordinates and readings from the future are refused
+        let mut bad = reading(None, now, 5.0);
+        bad.location = GeoPoint::new(91.0, -89.65);
+        assert!(service.record_location(bad).await.is_err());
+        assert!(service.record_location(reading(None, now + Duration::minutes(10), 5.0)).await.is_err());
+        
+        // Purging keeps readings newer than the cutoff
+        service.record_location(reading(None, now - Duration::days(31), 5.0)).await.unwrap();
+        service.record_location(reading(None, now - Duration::days(29), 5.0)).await.unwrap();
+        assert_eq!(service.purge_locations(30).await.unwrap(), 1);
+        assert_eq!(service.purge_locations(30).await.unwrap(), 0);
+        let remaining: i64 = service.db.query_row("SELECT COUNT(*) FROM driver_locations", [], |row| row.get(0))
+            .unwrap()
+            .unwrap();
+        assert_eq!(remaining, 61);
+    }
+    
+    #[tokio::test]
+    async fn test_route_lifecycle_cascades_to_stops() {
+        let service = test_service();
+        let first = create_stop(&service, "First", 39.80, -89.65).await;
+        let second = create_stop(&service, "Second", 39.85, -89.65).await;
+        let route = service
+            .create_route(&test_context(), &[first.id.clone(), second.id.clone()], depot(), date(), None)
+            .await
+            .unwrap();
+        
+        let started = service.start_route(&route.id, Some(1200.0)).await.unwrap();
+        assert_eq!(started.status, RouteStatus::InProgress);
+        assert_eq!(started.start_odometer_km, Some(1200.0));
+        assert!(started.deliveries.iter().all(|d| d.status == DeliveryStatus::EnRoute));
+        assert!(service.start_route(&route.id, None).await.is_err());
+        
+        // Started routes can't be re-planned
+        assert!(service.create_route(&test_context(), std::slice::from_ref(&first.id), depot(), date(), None).await.is_err());
+        
+        service.update_status(&first.id, DeliveryStatus::Delivered, None).await.unwrap();
+        let mut reasons = HashMap::new();
+        let err = service.complete_route(&route.id, Some(1260.0), &reasons).await.unwrap_err();
+        assert!(err.to_string().contains(&second.delivery_number));
+        
+        // A failed stop still needs its reason
+        service.update_status(&second.id, DeliveryStatus::Failed, None).await.unwrap();
+        assert!(service.complete_route(&route.id, Some(1260.0), &reasons).await.is_err());
+        
+        reasons.insert(second.id.clone(), "Gate locked".to_string());
+        assert!(service.complete_route(&route.id, Some(1100.0), &reasons).await.is_err(), "odometer went backwards");
+        let completed = service.complete_route(&route.id, Some(1260.0), &reasons).await.unwrap();
+        assert_eq!(completed.status, RouteStatus::Completed);
+        assert_eq!(completed.end_odometer_km, Some(1260.0));
+        assert!(completed.actual_end.is_some());
+        let failed = completed.deliveries.iter().find(|d| d.id == second.id).unwrap();
+        assert_eq!(failed.failure_reason.as_deref(), Some("Gate locked"));
+        assert!(service.complete_route(&route.id, None, &reasons).await.is_err());
+    }
+    
+    #[tokio::test]
+    async fn test_completing_route_fails_open_stops_with_reason() {
+        let service = test_service();
+        let stop = create_stop(&service, "Closed", 39.80, -89.65).await;
+        let route = service.create_route(&test_context(), std::slice::from_ref(&stop.id), depot(), date(), None).await.unwrap();
+        service.start_route(&route.id, None).await.unwrap();
+        
+        let reasons = HashMap::from([(stop.id.clone(), "Customer closed".to_string())]);
+        let completed = service.complete_route(&route.id, None, &reasons).await.unwrap();
+        assert_eq!(completed.deliveries[0].status, DeliveryStatus::Failed);
+        assert_eq!(completed.deliveries[0].failure_reason.as_deref(), Some("Customer closed"));
+    }
+    
+    #[tokio::test]
+    async fn test_manifest_lists_stops_in_driving_order() {
+        let service = test_service();
+        let far = create_stop(&service, "Far", 39.90, -89.65).await;
+        let near = create_stop(&service, "Near", 39.80, -89.65).await;
+        service.db.execute(
+            "UPDATE deliveries SET delivery_instructions = 'Leave at back door' WHERE id = ?",
+            params![&far.id],
+        ).unwrap();
+        let route = service
+            .create_route(&test_context(), &[far.id.clone(), near.id.clone()], depot(), date(), Some("u1"))
+            .await
+            .unwrap();
+        
+        let manifest = service.get_route_manifest(&route.id).await.unwrap();
+        assert_eq!(manifest.route_number, route.route_number);
+        assert_eq!(manifest.driver_id.as_deref(), Some("u1"));
+        let order: Vec<(u32, &str)> = manifest.stops.iter()
+            .map(|s| (s.sequence, s.delivery_address.name.as_str()))
+            .collect();
+        assert_eq!(order, vec![(1, "Near"), (2, "Far")]);
+        assert_eq!(manifest.stops[1].instructions.as_deref(), Some("Leave at back door"));
+        
+        // A plan saved onto the route replaces its order
+        let mut reversed = service.optimize_route(std::slice::from_ref(&near.id), depot(), &CancellationToken::new()).await.unwrap();
+        reversed.stop_order = vec![far.id.clone(), near.id.clone()];
+        service.apply_optimized_route(&route.id, &reversed).await.unwrap();
+        let manifest = service.get_route_manifest(&route.id).await.unwrap();
+        assert_eq!(manifest.stops[0].delivery_id, far.id);
+        assert_eq!(manifest.total_distance_km, Some(reversed.total_distance_km));
+    }
+    
+    #[tokio::test]
+    async fn test_driver_only_gets_deliveries_on_own_routes() {
+        let service = test_service();
+        service.db.execute(
+            "INSERT INTO users (id, username, email, full_name, role) VALUES ('d1', 'dee', 'dee@example.com', 'Dee Driver', 'driver')",
+            [],
+        ).unwrap();
+        let mine = create_stop(&service, "Mine", 39.80, -89.65).await;
+        let unassigned = create_stop(&service, "Unassigned", 39.85, -89.65).await;
+        service.create_route(&test_context(), std::slice::from_ref(&mine.id), depot(), date(), Some("d1")).await.unwrap();
+        
+        let all = service.get_deliveries(&DeliveryQuery::default(), None).await.unwrap();
+        assert_eq!(all.len(), 2);
+        let driver = service.get_deliveries(&DeliveryQuery::default(), Some("d1")).await.unwrap();
+        let ids: Vec<&str> = driver.iter().map(|d| d.id.as_str()).collect();
+        assert_eq!(ids, vec![mine.id.as_str()]);
+        let delivered = DeliveryQuery { status: Some(DeliveryStatus::Delivered), ..Default::default() };
+        assert!(service.get_deliveries(&delivered, None).await.unwrap().is_empty());
+        assert!(!ids.contains(&unassigned.id.as_str()));
+    }
+    
+    #[tokio::test]
+    async fn test_delivery_filters_combine() {
+        let service = test_service();
+        let day = |d: u32| NaiveDate::from_ymd_opt(2025, 3, d).unwrap();
+        let _: Delivery = DeliveryBuilder::new("DEL-1").scheduled(day(3)).insert(&service.db);
+        let _: Delivery = DeliveryBuilder::new("DEL-2").status("delivered").scheduled(day(3)).insert(&service.db);
+        let _: Delivery = DeliveryBuilder::new("DEL-3").scheduled(day(4)).insert(&service.db);
+        let _: Delivery = DeliveryBuilder::new("DEL-4").status("en_route").scheduled(day(3)).insert(&service.db);
+        let _: Delivery = DeliveryBuilder::new("DEL-5").scheduled(day(3)).insert(&service.db);
+        service.db.execute("UPDATE deliveries SET status = 'enroute' WHERE id = 'del-4'", []).unwrap();
+        service.db.execute(
+            "INSERT INTO delivery_routes (id, route_number, route_name, route_date, created_by) VALUES ('r1', 'RT-1', 'North', '2025-03-03', 'u1')",
+            [],
+        ).unwrap();
+        service.db.execute("UPDATE deliveries SET route_id = 'r1' WHERE id IN ('del-4', 'del-5')", []).unwrap();
+        
+        let numbers = |query: DeliveryQuery| {
+            let service = &service;
+            async move {
+                let mut numbers: Vec<String> = service.get_deliveries(&query, None).await.unwrap()
+                    .into_iter()
+                    .map(|d| d.delivery_number)
+                    .collect();
+                numbers.sort();
+                numbers
+            }
+        };
+        
+        let pending = DeliveryQuery { status: Some(DeliveryStatus::Pending), ..Default::default() };
+        assert_eq!(numbers(pending).await, vec!["DEL-1", "DEL-3", "DEL-5"]);
+        
+        // Stored as RFC3339, asked for as YYYY-MM-DD
+        let on_the_3rd = DeliveryQuery { date: Some(day(3)), ..Default::default() };
+        assert_eq!(numbers(on_the_3rd).await.len(), 4);
+        let today = service.get_deliveries(&DeliveryQuery { date: Some(day(4)), ..Default::default() }, None).await.unwrap();
+        assert_eq!(today.len(), 1);
+        assert_eq!(today[0].scheduled_date.date_naive(), day(4));
+        
+        let combined = DeliveryQuery {
+            status: Some(DeliveryStatus::Pending),
+            date: Some(day(3)),
+            route_id: Some("r1".to_string()),
+        };
+        assert_eq!(numbers(combined).await, vec!["DEL-5"]);
+        let en_route = DeliveryQuery { status: Some(DeliveryStatus::EnRoute), route_id: Some("r1".to_string()), ..Default::default() };
+        assert_eq!(numbers(en_route).await, vec!["DEL-4"]);
+    }
+    
+    #[tokio::test]
+    async fn test_customers_notified_as_route_progresses() {
+        let clock = fixed_clock();
+        let service = test_service().with_clock(clock.clone());
+        service.db.execute(
+            "INSERT INTO customers (id, customer_number, company_name, notifications_opt_out) VALUES
+                ('c1', 'CUST-1', 'First Co', 0),
+                ('c3', 'CUST-3', 'Quiet Co', 1)",
+            [],
+        ).unwrap();
+        let first = create_stop(&service, "First", 39.80, -89.65).await;
+        let second = create_stop(&service, "Second", 39.85, -89.65).await;
+        let third = create_stop(&service, "Third", 39.90, -89.65).await;
+        for (stop, customer, phone, email) in [
+            (&first, Some("c1"), Some("555-0101"), Some("first@example.com")),
+            (&second, None, Some("555-0102"), None),
+            (&third, Some("c3"), Some("555-0103"), Some("third@example.com")),
+        ] {
+            service.db.execute(
+                "UPDATE deliveries SET customer_id = ?, delivery_phone = ?, delivery_email = ? WHERE id = ?",
+                params![customer, phone, email, &stop.id],
+            ).unwrap();
+        }
+        let ids = vec![first.id.clone(), second.id.clone(), third.id.clone()];
+        let route = service.create_route(&test_context(), &ids, depot(), date(), None).await.unwrap();
+        let sent = |notifications: Vec<DeliveryNotification>| -> Vec<(NotificationTrigger, NotificationChannel)> {
+            notifications.iter().map(|n| (n.trigger, n.channel)).collect()
+        };
+        
+        // Starting the route sends everyone but the opted-out customer their window
+        service.start_route(&route.id, None).await.unwrap();
+        let first_sent = service.get_delivery_notifications(&first.id).await.unwrap();
+        assert_eq!(sent(first_sent.clone()), vec![
+            (NotificationTrigger::RouteStarted, NotificationChannel::Sms),
+            (NotificationTrigger::RouteStarted, NotificationChannel::Email),
+        ]);
+        assert!(first_sent[0].body.contains("between 08:33 and 09:33"), "{}", first_sent[0].body);
+        assert_eq!(first_sent[1].recipient, "first@example.com");
+        assert_eq!(sent(service.get_delivery_notifications(&second.id).await.unwrap()), vec![
+            (NotificationTrigger::RouteStarted, NotificationChannel::Sms),
+        ]);
+        assert!(service.get_delivery_notifications(&third.id).await.unwrap().is_empty());
+        
+        // Finishing the first stop confirms it and tells the second customer they're next
+        clock.advance(chrono::Duration::minutes(10));
+        service.db.execute("UPDATE deliveries SET signature_name = 'Pat Lee' WHERE id = ?", params![&first.id]).unwrap();
+        service.update_status(&first.id, DeliveryStatus::Delivered, None).await.unwrap();
+        let first_sent = service.get_delivery_notifications(&first.id).await.unwrap();
+        assert_eq!(first_sent.len(), 4);
+        assert!(first_sent[2].body.contains("delivered at 09:10. Signed for by Pat Lee."), "{}", first_sent[2].body);
+        let second_sent = service.get_delivery_notifications(&second.id).await.unwrap();
+        assert_eq!(sent(second_sent.clone())[1], (NotificationTrigger::UpNext, NotificationChannel::Sms));
+        assert!(second_sent[1].body.contains("around 09:18"), "{}", second_sent[1].body);
+        
+        let progress = service.get_route_progress(&route.id).await.unwrap();
+        assert_eq!((progress.completed_stops, progress.total_stops), (1, 3));
+        assert_eq!(progress.next_stop().unwrap().delivery_id, second.id);
+        assert_eq!(progress.remaining[1].stops_away, 1);
+        
+        // A failed stop gets no confirmation, and the next customer opted out
+        service.update_status(&second.id, DeliveryStatus::Failed, None).await.unwrap();
+        assert_eq!(service.get_delivery_notifications(&second.id).await.unwrap().len(), 2);
+        assert!(service.get_delivery_notifications(&third.id).await.unwrap().is_empty());
+        
+        // Repeating a status doesn't send the same message twice
+        service.update_status(&first.id, DeliveryStatus::Delivered, None).await.unwrap();
+        assert_eq!(service.get_delivery_notifications(&first.id).await.unwrap().len(), 4);
+        assert_eq!(service.get_queued_notifications(50).await.unwrap().len(), 6);
+    }
+    
+    #[tokio::test]
+    async fn test_running_delay_and_break_shift_downstream_etas() {
+        let clock = fixed_clock();
+        let service = test_service().with_clock(clock.clone());
+        let mut ids = Vec::new();
+        for (name, lat) in [("One", 39.80), ("Two", 39.85), ("Three", 39.90), ("Four", 39.95), ("Five", 40.00)] {
+            ids.push(create_stop(&service, name, lat, -89.65).await.id);
+        }
+        let route = service.create_route(&test_context(), &ids, depot(), date(), None).await.unwrap();
+        service.start_route(&route.id, None).await.unwrap();
+        let plan = service.get_route_progress(&route.id).await.unwrap();
+        let naive = |i: usize| plan.remaining[i].naive_eta.unwrap();
+        assert_eq!(plan.delay_minutes, 0);
+        assert!(plan.remaining.iter().all(|s| s.eta == s.naive_eta));
+        
+        // The driver leaves stop 3 twenty minutes later than planned
+        clock.set(naive(2) + Duration::minutes(5 + 20));
+        for id in &ids[..3] {
+            service.update_status(id, DeliveryStatus::Delivered, None).await.unwrap();
+        }
+        let behind = service.get_route_progress(&route.id).await.unwrap();
+        assert_eq!(behind.delay_minutes, 20);
+        assert_eq!(behind.remaining[0].naive_eta, Some(naive(3)));
+        assert_eq!(behind.remaining[0].eta, Some(naive(3) + Duration::minutes(20)));
+        assert_eq!(behind.remaining[1].eta, Some(naive(4) + Duration::minutes(20)));
+        
+        // Lunch falls between the last two stops once the delay is counted
+        let lunch = RouteBreak {
+            id: String::new(),
+            route_id: String::new(),
+            starts_at: naive(3) + Duration::minutes(25),
+            duration_minutes: 30,
+        };
+        service.set_route_breaks(&route.id, vec![lunch]).await.unwrap();
+        let progress = service.get_route_progress(&route.id).await.unwrap();
+        let (four, five) = (&progress.remaining[0], &progress.remaining[1]);
+        assert_eq!((four.eta, four.break_minutes), (Some(naive(3) + Duration::minutes(20)), 0));
+        assert_eq!((five.eta, five.break_minutes), (Some(naive(4) + Duration::minutes(50)), 30));
+        assert_eq!(five.naive_eta, Some(naive(4)));
+    }
+    
+    /// A 1x1 PNG
+    fn signature_png() -> Vec<u8> {
+        base64::engine::general_purpose::STANDARD
+            .decode("iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mNkYPhfDwAChwGA60e6kgAAAABJRU5ErkJggg==")
+            .unwrap()
+    }
+    
+    #[tokio::test]
+    async fn test_signature_required_before_delivered() {
+        let service = test_service();
+        let stop = create_stop(&service, "Signed", 39.80, -89.65).await;
+        service.db.execute("UPDATE deliveries SET signature_required = 1 WHERE id = ?", params![&stop.id]).unwrap();
+        let ctx = test_context();
+        
+        let err = service.update_status(&stop.id, DeliveryStatus::Delivered, None).await.unwrap_err();
+        assert!(matches!(err, WmsError::Validation(_)), "{}", err);
+        
+        service.attach_proof(&ctx, &stop.id, ProofType::Note, b"Left with reception".to_vec(), Utc::now(), None)
+            .await
+            .unwrap();
+        assert!(service.update_status(&stop.id, DeliveryStatus::Delivered, None).await.is_err());
+        
+        service.attach_proof(&ctx, &stop.id, ProofType::Signature, signature_png(), Utc::now(), None)
+            .await
+            .unwrap();
+        let delivered = service.update_status(&stop.id, DeliveryStatus::Delivered, None).await.unwrap();
+        assert_eq!(delivered.status, DeliveryStatus::Delivered);
+    }
+    
+    #[tokio::test]
+    async fn test_proofs_round_trip() {
+        let service = test_service();
+        let stop = create_stop(&service, "Photographed", 39.80, -89.65).await;
+        let ctx = test_context();
+        let door = GeoPoint::new(39.8001, -89.6502);
+        
+        service.attach_proof(&ctx, &stop.id, ProofType::Photo, signature_png(), Utc::now(), Some(door))
+            .await
+            .unwrap();
+        service.attach_proof(&ctx, &stop.id, ProofType::Note, b"  Behind the gate ".to_vec(), Utc::now(), None)
+            .await
+            .unwrap();
+        
+        let proofs = service.get_proofs(&stop.id).await.unwrap();
+        assert_eq!(proofs.len(), 2);
+        let photo = &proofs[0];
+        assert_eq!(photo.proof_type, ProofType::Photo);
+        assert_eq!(photo.location.map(|p| (p.lat, p.lng)), Some((door.lat, door.lng)));
+        assert_eq!(photo.captured_by, "u1");
+        let data = base64::engine::general_purpose::STANDARD.decode(photo.data.as_deref().unwrap()).unwrap();
+        assert_eq!(data, signature_png());
+        assert_eq!((proofs[1].note.as_deref(), proofs[1].data.as_deref()), (Some("Behind the gate"), None));
+    }
+    
+    #[tokio::test]
+    async fn test_oversized_proof_rejected() {
+        let service = test_service();
+        let stop = create_stop(&service, "Huge", 39.80, -89.65).await;
+        
+        let result = service
+            .attach_proof(&test_context(), &stop.id, ProofType::Photo, vec![0; MAX_PROOF_BYTES + 1], Utc::now(), None)
+            .await;
+        assert!(matches!(result, Err(WmsError::Validation(_))));
+        assert!(service.get_proofs(&stop.id).await.unwrap().is_empty());
+    }
+    
+    #[tokio::test]
+    async fn test_out_of_range_reading_warns_on_delivery() {
+        let db = test_db();
+        ItemBuilder::new("MLK-1").insert::<serde_json::Value>(&db);
+        ItemBuilder::new("BLT-1").insert::<serde_json::Value>(&db);
+        db.execute("UPDATE inventory_items SET category = 'Dairy' WHERE id = 'mlk-1'", []).unwrap();
+        db.execute(
+            "INSERT INTO category_temperature_ranges (category, min_celsius, max_celsius) VALUES ('Dairy', 0, 5)",
+            [],
+        ).unwrap();
+        ShipmentBuilder::new("SHP-1").line("mlk-1", 2.0).line("blt-1", 5.0).insert::<serde_json::Value>(&db);
+        DeliveryBuilder::new("DEL-1").shipment("shp-1").insert::<serde_json::Value>(&db);
+        let service = DeliveryService::new(db).with_clock(fixed_clock());
+        
+        let cold = service.record_temperature(&test_context(), "del-1", Some("TRUCK-7"), 4.0).await.unwrap();
+        assert!(!cold.out_of_range);
+        assert_eq!(cold.max_celsius, Some(5.0));
+        assert!(service.get_delivery("del-1").await.unwrap().unwrap().temperature_warning.is_none());
+        
+        let warm = service.record_temperature(&test_context(), "del-1", Some("TRUCK-7"), 8.5).await.unwrap();
+        assert!(warm.out_of_range);
+        let warning = service.get_delivery("del-1").await.unwrap().unwrap().temperature_warning.unwrap();
+        assert_eq!(warning, "8.5 °C at 09:00, outside 0.0–5.0 °C");
+        
+        let log = service.temperature
+            .get_temperature_log(&TemperatureOwner::Delivery("del-1".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(log.readings.len(), 2);
+        assert_eq!(log.out_of_range_count, 1);
+    }
+}
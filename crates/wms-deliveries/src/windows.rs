@@ -0,0 +1,85 @@
+//! Delivery Window Settings
+//!
+//! Delivery windows are clock times, so routes are planned as leaving the
+//! depot at a configured departure time. A route that still misses more
+//! windows than allowed after optimization is not saved. Without a
+//! configured limit any route is saved and its missed windows reported.
+
+use chrono::NaiveTime;
+use serde::{Deserialize, Serialize};
+use wms_core::db::Database;
+use wms_core::error::Result;
+use crate::routing::TimeWindow;
+
+/// Route planning configuration for delivery windows
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct DeliveryWindowSettings {
+    /// When routes leave the depot
+    pub departure_time: NaiveTime,
+    /// Most windows a saved route may miss; None saves any route
+    pub max_violations: Option<u32>,
+}
+
+impl Default for DeliveryWindowSettings {
+    fn default() -> Self {
+        Self {
+            departure_time: NaiveTime::from_hms_opt(8, 0, 0).expect("valid time"),
+            max_violations: None,
+        }
+    }
+}
+
+impl DeliveryWindowSettings {
+    const DEPARTURE_KEY: &'static str = "route_departure_time";
+    const MAX_VIOLATIONS_KEY: &'static str = "route_max_window_violations";
+
+    /// Load from the settings table, falling back to the defaults
+    pub fn load(db: &Database) -> Result<Self> {
+        let defaults = Self::default();
+        Ok(Self {
+            departure_time: db.get_setting(Self::DEPARTURE_KEY)?
+                .and_then(|value| NaiveTime::parse_from_str(&value, "%H:%M").ok())
+                .unwrap_or(defaults.departure_time),
+            max_violations: db.get_setting(Self::MAX_VIOLATIONS_KEY)?
+                .and_then(|value| value.parse().ok()),
+        })
+    }
+
+    /// Store in the settings table
+    pub fn save(&self, db: &Database) -> Result<()> {
+        db.set_setting(Self::DEPARTURE_KEY, &self.departure_time.format("%H:%M").to_string())?;
+        let value = self.max_violations.map(|n| n.to_string()).unwrap_or_default();
+        db.set_setting(Self::MAX_VIOLATIONS_KEY, &value)
+    }
+
+    /// A delivery's "HH:MM" window in minutes after departure. A window
+    /// that opened before departure is open from the start.
+    pub fn window(&self, start: Option<&str>, end: Option<&str>) -> TimeWindow {
+        let offset = |time: Option<&str>| {
+            let time = NaiveTime::parse_from_str(time?, "%H:%M").ok()?;
+            Some((time - self.departure_time).num_minutes().max(0) as u32)
+        };
+        TimeWindow { earliest: offset(start), latest: offset(end) }
+    }
+
+    /// Whether a route missing `violations` windows may be saved
+    pub fn allows(&self, violations: usize) -> bool {
+        self.max_violations.is_none_or(|max| violations <= max as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_windows_count_from_departure() {
+        let settings = DeliveryWindowSettings::default();
+        assert_eq!(
+            settings.window(Some("09:00"), Some("12:00")),
+            TimeWindow { earliest: Some(60), latest: Some(240) }
+        );
+        assert_eq!(settings.window(Some("07:00"), None), TimeWindow { earliest: Some(0), latest: None });
+        assert_eq!(settings.window(Some("noon"), None), TimeWindow::default());
+    }
+}
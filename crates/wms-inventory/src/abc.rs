@@ -0,0 +1,167 @@
+//! ABC Classification
+//!
+//! Ranks items by the value of what was picked over a period, scaled to a
+//! year, and splits the ranking at cumulative shares of the total value.
+//! By default the items making up the first 80% are A, the next 15% B and
+//! the rest C. An item is placed by the share ranked above it, so the item
+//! that crosses a threshold still belongs to the higher class. Items that
+//! weren't picked at all are C and flagged as having no movement.
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use crate::models::AbcClass;
+
+/// Cumulative share of usage value covered by A items
+pub const DEFAULT_A_THRESHOLD_PERCENT: f64 = 80.0;
+
+/// Cumulative share of usage value covered by A and B items together
+pub const DEFAULT_B_THRESHOLD_PERCENT: f64 = 95.0;
+
+/// An item's usage over the classification period
+#[derive(Debug, Clone)]
+pub(crate) struct ItemUsage {
+    pub item_id: String,
+    pub sku: String,
+    pub annual_usage_value: f64,
+    pub previous_class: Option<AbcClass>,
+}
+
+/// The class given to one item
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AbcItemClass {
+    pub item_id: String,
+    pub sku: String,
+    /// Picked quantity times unit cost over the period, scaled to a year
+    pub annual_usage_value: f64,
+    /// Share of the total usage value up to and including this item
+    pub cumulative_percent: f64,
+    pub class: AbcClass,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous_class: Option<AbcClass>,
+    /// Not picked at all during the period
+    pub no_movement: bool,
+}
+
+/// Items and usage value in one class
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AbcClassSummary {
+    pub class: AbcClass,
+    pub item_count: u32,
+    pub annual_usage_value: f64,
+    /// Share of the total usage value
+    pub value_percent: f64,
+}
+
+/// Result of a classification run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AbcClassificationReport {
+    pub period_start: NaiveDate,
+    pub period_end: NaiveDate,
+    pub a_threshold: f64,
+    pub b_threshold: f64,
+    /// A, B and C in that order
+    pub classes: Vec<AbcClassSummary>,
+    /// Items without movement, all of them in C
+    pub no_movement_count: u32,
+    /// Items whose class changed
+    pub changed_count: u32,
+    /// Every item, highest usage value first
+    pub items: Vec<AbcItemClass>,
+    pub classified_at: DateTime<Utc>,
+}
+
+/// Rank items by usage value and assign classes at the cumulative
+/// thresholds, given as percentages of the total value
+pub(crate) fn classify_items(mut usage: Vec<ItemUsage>, a_threshold: f64, b_threshold: f64) -> Vec<AbcItemClass> {
+    usage.sort_by(|a, b| {
+        b.annual_usage_value.total_cmp(&a.annual_usage_value).then_with(|| a.sku.cmp(&b.sku))
+    });
+    let total: f64 = usage.iter().map(|u| u.annual_usage_value.max(0.0)).sum();
+
+    let mut cumulative = 0.0;
+    usage.into_iter()
+        .map(|u| {
+            let no_movement = u.annual_usage_value <= 0.0;
+            let share_before = if total > 0.0 { cumulative / total * 100.0 } else { 100.0 };
+            let class = if no_movement {
+                AbcClass::C
+            } else if share_before < a_threshold - 1e-9 {
+                AbcClass::A
+            } else if share_before < b_threshold - 1e-9 {
+                AbcClass::B
+            } else {
+                AbcClass::C
+            };
+            cumulative += u.annual_usage_value.max(0.0);
+
+            AbcItemClass {
+                item_id: u.item_id,
+                sku: u.sku,
+                annual_usage_value: u.annual_usage_value,
+                cumulative_percent: if total > 0.0 { cumulative / total * 100.0 } else { 0.0 },
+                class,
+                previous_class: u.previous_class,
+                no_movement,
+            }
+        })
+        .collect()
+}
+
+/// Item counts and usage value per class
+pub(crate) fn summarize_classes(items: &[AbcItemClass]) -> Vec<AbcClassSummary> {
+    let total: f64 = items.iter().map(|i| i.annual_usage_value.max(0.0)).sum();
+    [AbcClass::A, AbcClass::B, AbcClass::C]
+        .into_iter()
+        .map(|class| {
+            let members = items.iter().filter(|i| i.class == class);
+            let value: f64 = members.clone().map(|i| i.annual_usage_value.max(0.0)).sum();
+            AbcClassSummary {
+                class,
+                item_count: members.count() as u32,
+                annual_usage_value: value,
+                value_percent: if total > 0.0 { value / total * 100.0 } else { 0.0 },
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(sku: &str, value: f64) -> ItemUsage {
+        ItemUsage {
+            item_id: sku.to_lowercase(),
+            sku: sku.to_string(),
+            annual_usage_value: value,
+            previous_class: None,
+        }
+    }
+
+    #[test]
+    fn test_threshold_crossing_item_stays_in_the_higher_class() {
+        let items = classify_items(
+            vec![usage("D", 5.0), usage("A", 70.0), usage("C", 10.0), usage("E", 3.0), usage("B", 12.0), usage("F", 0.0)],
+            DEFAULT_A_THRESHOLD_PERCENT,
+            DEFAULT_B_THRESHOLD_PERCENT,
+        );
+        let classes: Vec<(&str, AbcClass)> = items.iter().map(|i| (i.sku.as_str(), i.class)).collect();
+
+        // B crosses 80% and D crosses 95%; each stays in the class it started in
+        assert_eq!(classes, [
+            ("A", AbcClass::A),
+            ("B", AbcClass::A),
+            ("C", AbcClass::B),
+            ("D", AbcClass::B),
+            ("E", AbcClass::C),
+            ("F", AbcClass::C),
+        ]);
+        assert!(items[5].no_movement && !items[4].no_movement);
+        assert!((items[3].cumulative_percent - 97.0).abs() < 1e-9);
+
+        let summary = summarize_classes(&items);
+        assert_eq!((summary[0].item_count, summary[0].annual_usage_value), (2, 82.0));
+        assert_eq!(summary[2].item_count, 2);
+        assert!((summary[2].value_percent - 3.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,171 @@
+//! Forecast Accuracy
+//!
+//! Running a forecast records the demand it predicts for each day ahead,
+//! along with what every other model would have predicted from the same
+//! history. Once those days pass, `InventoryService::evaluate_forecasts`
+//! fills in the demand actually picked and stores rolling MAPE and bias per
+//! item and model, so we can see which model does best on our data.
+//!
+//! Items with fewer than `MIN_ACCURACY_SAMPLES` scored days in the window
+//! are left out rather than reported as perfectly accurate, and days with
+//! no demand are left out of MAPE, where they would divide by zero.
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use crate::forecast::ForecastModel;
+
+/// Days of past predictions the rolling metrics cover
+pub const ACCURACY_WINDOW_DAYS: u32 = 28;
+
+/// Scored predictions an item needs in the window before it is rated
+pub const MIN_ACCURACY_SAMPLES: usize = 7;
+
+/// `forecast_accuracy.item_id` of the metrics across all items
+pub(crate) const ALL_ITEMS: &str = "";
+
+/// Rolling accuracy of one model
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ModelAccuracy {
+    pub model_type: ForecastModel,
+    /// Predictions scored
+    pub samples: u32,
+    /// Mean absolute percentage error over days with demand; None when
+    /// every scored day had none
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mape: Option<f64>,
+    /// Mean of predicted minus actual; positive means over-forecasting
+    pub bias: f64,
+    /// Mean absolute error in units
+    pub mae: f64,
+}
+
+/// A model's rolling metrics as evaluated on a day
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AccuracyPoint {
+    pub date: NaiveDate,
+    pub model_type: ForecastModel,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mape: Option<f64>,
+    pub bias: f64,
+}
+
+/// How accurate forecasts have been for an item, or for all items
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForecastAccuracy {
+    /// None for the metrics across all items
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sku: Option<String>,
+    pub window_days: u32,
+    /// Latest rolling metrics of each model, most accurate first. Empty
+    /// until enough actuals are in.
+    pub models: Vec<ModelAccuracy>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub best_model: Option<ForecastModel>,
+    /// Every evaluation, oldest first, for charting
+    pub history: Vec<AccuracyPoint>,
+}
+
+impl ForecastAccuracy {
+    /// Build from stored evaluations: the comparison uses the latest day
+    pub(crate) fn from_evaluations(sku: Option<String>, evaluations: Vec<(NaiveDate, ModelAccuracy)>) -> Self {
+        let latest = evaluations.iter().map(|(date, _)| *date).max();
+        let mut models: Vec<ModelAccuracy> = evaluations.iter()
+            .filter(|(date, _)| Some(*date) == latest)
+            .map(|(_, accuracy)| accuracy.clone())
+            .collect();
+        // Lowest MAPE first; models without one rank by MAE after those with one
+        models.sort_by(|a, b| match (a.mape, b.mape) {
+            (Some(x), Some(y)) => x.total_cmp(&y),
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (None, None) => a.mae.total_cmp(&b.mae),
+        });
+
+        let mut history: Vec<AccuracyPoint> = evaluations.into_iter()
+            .map(|(date, accuracy)| AccuracyPoint {
+                date,
+                model_type: accuracy.model_type,
+                mape: accuracy.mape,
+                bias: accuracy.bias,
+            })
+            .collect();
+        history.sort_by_key(|point| point.date);
+
+        Self {
+            sku,
+            window_days: ACCURACY_WINDOW_DAYS,
+            best_model: models.first().map(|m| m.model_type),
+            models,
+            history,
+        }
+    }
+}
+
+/// Outcome of an evaluation run
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ForecastEvaluation {
+    /// Predictions whose actual demand was filled in by this run
+    pub evaluated_predictions: usize,
+    /// Items with enough actuals to be rated
+    pub rated_items: usize,
+}
+
+/// Score `(predicted, actual)` pairs. None with fewer than
+/// `MIN_ACCURACY_SAMPLES`.
+pub fn score_predictions(model_type: ForecastModel, pairs: &[(f64, f64)]) -> Option<ModelAccuracy> {
+    if pairs.len() < MIN_ACCURACY_SAMPLES {
+        return None;
+    }
+
+    let n = pairs.len() as f64;
+    let bias = pairs.iter().map(|(predicted, actual)| predicted - actual).sum::<f64>() / n;
+    let mae = pairs.iter().map(|(predicted, actual)| (predicted - actual).abs()).sum::<f64>() / n;
+
+    let percentage_errors: Vec<f64> = pairs.iter()
+        .filter(|(_, actual)| *actual > 0.0)
+        .map(|(predicted, actual)| ((predicted - actual) / actual).abs())
+        .collect();
+    let mape = (!percentage_errors.is_empty())
+        .then(|| percentage_errors.iter().sum::<f64>() / percentage_errors.len() as f64 * 100.0);
+
+    Some(ModelAccuracy { model_type, samples: pairs.len() as u32, mape, bias, mae })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_score_needs_enough_samples_and_skips_zero_demand_in_mape() {
+        assert_eq!(score_predictions(ForecastModel::Ets, &[(10.0, 8.0); 6]), None);
+
+        let mut pairs = vec![(10.0, 8.0); 6];
+        pairs.push((4.0, 0.0));
+        let score = score_predictions(ForecastModel::Ets, &pairs).unwrap();
+        assert_eq!(score.samples, 7);
+        assert_eq!(score.mape, Some(25.0));
+        assert_eq!(score.bias, 16.0 / 7.0);
+
+        let no_demand = score_predictions(ForecastModel::Naive, &[(1.0, 0.0); 7]).unwrap();
+        assert_eq!(no_demand.mape, None);
+        assert_eq!(no_demand.mae, 1.0);
+    }
+
+    #[test]
+    fn test_comparison_ranks_latest_evaluation_by_mape() {
+        let day = |d: u32| NaiveDate::from_ymd_opt(2025, 1, d).unwrap();
+        let accuracy = |model_type, mape, mae| ModelAccuracy { model_type, samples: 7, mape, bias: 0.0, mae };
+        let accuracy = ForecastAccuracy::from_evaluations(Some("WID-1".to_string()), vec![
+            (day(15), accuracy(ForecastModel::Ets, Some(30.0), 3.0)),
+            (day(15), accuracy(ForecastModel::Naive, None, 1.0)),
+            (day(15), accuracy(ForecastModel::Sma, Some(12.0), 2.0)),
+            (day(14), accuracy(ForecastModel::Ets, Some(5.0), 1.0)),
+        ]);
+
+        let ranked: Vec<_> = accuracy.models.iter().map(|m| m.model_type).collect();
+        assert_eq!(ranked, vec![ForecastModel::Sma, ForecastModel::Ets, ForecastModel::Naive]);
+        assert_eq!(accuracy.best_model, Some(ForecastModel::Sma));
+        assert_eq!(accuracy.history.len(), 4);
+        assert_eq!(accuracy.history[0].date, day(14));
+    }
+}
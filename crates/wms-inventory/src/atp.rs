@@ -0,0 +1,164 @@
+//! Available-to-Promise
+//!
+//! Projects day-by-day availability from on-hand stock, reservations,
+//! scheduled demand (open shipments) and scheduled supply (open receipts).
+
+use chrono::{Duration, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+/// Projected availability for an item over a horizon
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AvailabilityProjection {
+    pub item_id: String,
+    /// Stock on hand, excluding lots on quality hold
+    pub on_hand: f64,
+    pub reserved: f64,
+    /// One entry per day, starting today
+    pub days: Vec<DailyAvailability>,
+}
+
+/// Availability on a single day
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DailyAvailability {
+    pub date: NaiveDate,
+    /// Expected receipts landing on this day
+    pub supply: f64,
+    /// Open shipment demand due on this day
+    pub demand: f64,
+    /// Projected available quantity at end of day
+    pub available: f64,
+}
+
+/// Result of a promise check
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromiseCheck {
+    pub item_id: String,
+    pub quantity: f64,
+    pub need_by: NaiveDate,
+    pub can_promise: bool,
+    /// First date from which the quantity stays available, if any within the horizon
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub earliest_date: Option<NaiveDate>,
+}
+
+/// A dated supply (+) or demand (-) movement
+#[derive(Debug, Clone, Copy)]
+pub struct ScheduledMovement {
+    pub date: NaiveDate,
+    pub quantity: f64,
+}
+
+/// Build a day-by-day projection.
+///
+/// Movements dated before `start` are treated as due on `start`; movements
+/// after the horizon are ignored. With no movements the projection is flat.
+pub fn project_availability(
+    on_hand: f64,
+    reserved: f64,
+    movements: &[ScheduledMovement],
+    start: NaiveDate,
+    horizon_days: u32,
+) -> Vec<DailyAvailability> {
+    let mut available = on_hand - reserved;
+
+    (0..=horizon_days)
+        .map(|offset| {
+            let date = start + Duration::days(offset as i64);
+            let due_today = |m: &&ScheduledMovement| {
+                if offset == 0 { m.date <= date } else { m.date == date }
+            };
+
+            let supply: f64 = movements.iter()
+                .filter(due_today)
+                .filter(|m| m.quantity > 0.0)
+                .map(|m| m.quantity)
+                .sum();
+            let demand: f64 = movements.iter()
+                .filter(due_today)
+                .filter(|m| m.quantity < 0.0)
+                .map(|m| -m.quantity)
+                .sum();
+
+            available += supply - demand;
+
+            DailyAvailability { date, supply, demand, available }
+        })
+        .collect()
+}
+
+/// Earliest date from which `quantity` can be taken without driving any
+/// later day of the projection negative.
+pub fn earliest_available_date(days: &[DailyAvailability], quantity: f64) -> Option<NaiveDate> {
+    // Walk backwards tracking the minimum availability from each day onward
+    let mut earliest = None;
+    let mut min_after = f64::MAX;
+
+    for day in days.iter().rev() {
+        min_after = min_after.min(day.available);
+        if min_after >= quantity {
+            earliest = Some(day.date);
+        } else {
+            break;
+        }
+    }
+
+    earliest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 3, d).unwrap()
+    }
+
+    #[test]
+    fn test_flat_projection_without_movements() {
+        let days = project_availability(100.0, 20.0, &[], day(1), 7);
+
+        assert_eq!(days.len(), 8);
+        assert!(days.iter().all(|d| d.available == 80.0));
+        assert_eq!(earliest_available_date(&days, 80.0), Some(day(1)));
+        assert_eq!(earliest_available_date(&days, 81.0), None);
+    }
+
+    #[test]
+    fn test_supply_and_demand_applied_on_their_dates() {
+        let movements = vec![
+            ScheduledMovement { date: day(3), quantity: -30.0 },
+            ScheduledMovement { date: day(5), quantity: 200.0 },
+        ];
+        let days = project_availability(50.0, 0.0, &movements, day(1), 6);
+
+        assert_eq!(days[0].available, 50.0);
+        assert_eq!(days[2].demand, 30.0);
+        assert_eq!(days[2].available, 20.0);
+        assert_eq!(days[4].supply, 200.0);
+        assert_eq!(days[4].available, 220.0);
+    }
+
+    #[test]
+    fn test_overdue_movements_land_on_first_day() {
+        let movements = vec![ScheduledMovement { date: day(1), quantity: -10.0 }];
+        let days = project_availability(40.0, 0.0, &movements, day(4), 2);
+
+        assert_eq!(days[0].demand, 10.0);
+        assert_eq!(days[0].available, 30.0);
+    }
+
+    #[test]
+    fn test_earliest_date_respects_later_demand() {
+        // 100 available now, but 80 ships on day 3 and 200 arrives on day 6
+        let movements = vec![
+            ScheduledMovement { date: day(3), quantity: -80.0 },
+            ScheduledMovement { date: day(6), quantity: 200.0 },
+        ];
+        let days = project_availability(100.0, 0.0, &movements, day(1), 10);
+
+        // 50 units today would leave day 3 short, so the promise waits for the receipt
+        assert_eq!(earliest_available_date(&days, 50.0), Some(day(6)));
+        assert_eq!(earliest_available_date(&days, 20.0), Some(day(1)));
+        assert_eq!(earliest_available_date(&days, 500.0), None);
+    }
+}
@@ -0,0 +1,256 @@
+//! Cycle Counts
+//!
+//! A count sheet lists the expected quantity of every item, location and
+//! lot in a zone or location when it is created. Counters record what they
+//! find, and posting the sheet adjusts stock by the variances.
+//!
+//! How far a counted quantity may differ from the expected one before the
+//! line has to be counted again instead of posted is set per ABC class: by
+//! default any variance on an A item needs a recount, B items allow 2% and
+//! C items 5%. Unclassified items are treated as C.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use wms_core::db::Database;
+use wms_core::error::{WmsError, Result};
+use wms_core::types::{required_timestamp, stored_status, stored_timestamp, unreadable};
+use crate::models::{AbcClass, LocationZone};
+
+/// Count sheet lifecycle
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CountStatus {
+    /// Being counted
+    #[default]
+    Open,
+    /// Variances posted to stock; the sheet can't change any more
+    Posted,
+}
+
+impl CountStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Open => "open",
+            Self::Posted => "posted",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "open" => Some(Self::Open),
+            "posted" => Some(Self::Posted),
+            _ => None,
+        }
+    }
+}
+
+/// A cycle count of one zone or location
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CountSheet {
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub zone: Option<LocationZone>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location_id: Option<String>,
+    /// Only items of this class were listed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub abc_class: Option<AbcClass>,
+    pub status: CountStatus,
+    pub lines: Vec<CountLine>,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub posted_by: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub posted_at: Option<DateTime<Utc>>,
+}
+
+/// Expected and counted quantity of an item's lot at a location
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CountLine {
+    pub id: String,
+    pub count_id: String,
+    pub item_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub item_sku: Option<String>,
+    pub location_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location_code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lot_number: Option<String>,
+    /// On hand when the sheet was created
+    pub expected_quantity: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub counted_quantity: Option<f64>,
+    pub count_attempts: u32,
+    /// The first count was outside the tolerance; count it again
+    pub requires_recount: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub counted_by: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub counted_at: Option<DateTime<Utc>>,
+    /// When the line's variance was posted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub posted_at: Option<DateTime<Utc>>,
+}
+
+impl CountLine {
+    /// Counted minus expected, once counted
+    pub fn variance(&self) -> Option<f64> {
+        self.counted_quantity.map(|counted| counted - self.expected_quantity)
+    }
+}
+
+/// Largest variance accepted without a recount, as a percentage of the
+/// expected quantity
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct CountVarianceTolerances {
+    pub class_a_percent: f64,
+    pub class_b_percent: f64,
+    pub class_c_percent: f64,
+}
+
+impl Default for CountVarianceTolerances {
+    fn default() -> Self {
+        Self {
+            class_a_percent: 0.0,
+            class_b_percent: 2.0,
+            class_c_percent: 5.0,
+        }
+    }
+}
+
+impl CountVarianceTolerances {
+    const CLASS_A_KEY: &'static str = "count_tolerance_a_percent";
+    const CLASS_B_KEY: &'static str = "count_tolerance_b_percent";
+    const CLASS_C_KEY: &'static str = "count_tolerance_c_percent";
+
+    /// Load from the settings table; missing or invalid values are the
+    /// defaults
+    pub fn load(db: &Database) -> Result<Self> {
+        let percent = |key: &str, default: f64| -> Result<f64> {
+            Ok(db.get_setting(key)?
+                .and_then(|value| value.parse::<f64>().ok())
+                .filter(|p| p.is_finite() && *p >= 0.0)
+                .unwrap_or(default))
+        };
+        let defaults = Self::default();
+
+        Ok(Self {
+            class_a_percent: percent(Self::CLASS_A_KEY, defaults.class_a_percent)?,
+            class_b_percent: percent(Self::CLASS_B_KEY, defaults.class_b_percent)?,
+            class_c_percent: percent(Self::CLASS_C_KEY, defaults.class_c_percent)?,
+        })
+    }
+
+    /// Store in the settings table
+    pub fn save(&self, db: &Database) -> Result<()> {
+        let all = [self.class_a_percent, self.class_b_percent, self.class_c_percent];
+        if all.iter().any(|p| !p.is_finite() || *p < 0.0) {
+            return Err(WmsError::validation("Count tolerances must be zero or more"));
+        }
+        db.set_setting(Self::CLASS_A_KEY, &self.class_a_percent.to_string())?;
+        db.set_setting(Self::CLASS_B_KEY, &self.class_b_percent.to_string())?;
+        db.set_setting(Self::CLASS_C_KEY, &self.class_c_percent.to_string())?;
+        Ok(())
+    }
+
+    /// Tolerance for items of `class`
+    pub fn tolerance_percent(&self, class: Option<AbcClass>) -> f64 {
+        match class {
+            Some(AbcClass::A) => self.class_a_percent,
+            Some(AbcClass::B) => self.class_b_percent,
+            Some(AbcClass::C) | None => self.class_c_percent,
+        }
+    }
+
+    /// Whether a count of `counted` against `expected` is off by more than
+    /// the class allows. Any variance on an expected zero needs a recount.
+    pub fn requires_recount(&self, class: Option<AbcClass>, expected: f64, counted: f64) -> bool {
+        let variance = (counted - expected).abs();
+        if variance < f64::EPSILON {
+            return false;
+        }
+        if expected.abs() < f64::EPSILON {
+            return true;
+        }
+        variance / expected.abs() * 100.0 > self.tolerance_percent(class) + 1e-9
+    }
+}
+
+/// Convert database row to CountSheet, without its lines
+pub(crate) fn row_to_sheet(row: &rusqlite::Row) -> rusqlite::Result<CountSheet> {
+    Ok(CountSheet {
+        id: row.get("id")?,
+        zone: row.get::<_, Option<String>>("zone")?
+            .map(|z| LocationZone::parse(&z).ok_or_else(|| unreadable(row, "zone", &z)))
+            .transpose()?,
+        location_id: row.get("location_id")?,
+        abc_class: row.get::<_, Option<String>>("abc_class")?
+            .map(|c| AbcClass::parse(&c).ok_or_else(|| unreadable(row, "abc_class", &c)))
+            .transpose()?,
+        status: stored_status(row, "status", CountStatus::parse)?,
+        lines: Vec::new(),
+        created_by: row.get("created_by")?,
+        created_at: required_timestamp(row, "created_at")?,
+        posted_by: row.get("posted_by")?,
+        posted_at: stored_timestamp(row, "posted_at")?,
+    })
+}
+
+/// Convert a `count_lines` row joined with the item's `sku` and the
+/// location's `code` to CountLine
+pub(crate) fn row_to_count_line(row: &rusqlite::Row) -> rusqlite::Result<CountLine> {
+    Ok(CountLine {
+        id: row.get("id")?,
+        count_id: row.get("count_id")?,
+        item_id: row.get("item_id")?,
+        item_sku: row.get("sku")?,
+        location_id: row.get("location_id")?,
+        location_code: row.get("code")?,
+        lot_number: row.get::<_, Option<String>>("lot_number")?.filter(|l| !l.is_empty()),
+        expected_quantity: row.get("expected_quantity")?,
+        counted_quantity: row.get("counted_quantity")?,
+        count_attempts: row.get("count_attempts")?,
+        requires_recount: row.get("requires_recount")?,
+        counted_by: row.get("counted_by")?,
+        counted_at: stored_timestamp(row, "counted_at")?,
+        posted_at: stored_timestamp(row, "posted_at")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wms_core::test_support::test_db;
+
+    #[test]
+    fn test_tolerance_boundaries_per_class() {
+        let tolerances = CountVarianceTolerances::default();
+
+        assert!(!tolerances.requires_recount(Some(AbcClass::A), 100.0, 100.0));
+        assert!(tolerances.requires_recount(Some(AbcClass::A), 100.0, 99.0));
+
+        assert!(!tolerances.requires_recount(Some(AbcClass::B), 100.0, 102.0));
+        assert!(tolerances.requires_recount(Some(AbcClass::B), 100.0, 102.5));
+        assert!(!tolerances.requires_recount(Some(AbcClass::B), 50.0, 49.0));
+
+        assert!(!tolerances.requires_recount(Some(AbcClass::C), 100.0, 95.0));
+        assert!(tolerances.requires_recount(Some(AbcClass::C), 100.0, 94.0));
+        assert!(!tolerances.requires_recount(None, 200.0, 210.0));
+        assert!(tolerances.requires_recount(Some(AbcClass::C), 0.0, 1.0));
+    }
+
+    #[test]
+    fn test_tolerances_are_stored_in_settings() {
+        let db = test_db();
+        assert_eq!(CountVarianceTolerances::load(&db).unwrap(), CountVarianceTolerances::default());
+
+        let custom = CountVarianceTolerances { class_a_percent: 0.5, class_b_percent: 3.0, class_c_percent: 10.0 };
+        custom.save(&db).unwrap();
+        assert_eq!(CountVarianceTolerances::load(&db).unwrap(), custom);
+
+        let negative = CountVarianceTolerances { class_b_percent: -1.0, ..custom };
+        assert!(negative.save(&db).is_err());
+    }
+}
@@ -0,0 +1,208 @@
+//! Item Master Export
+//!
+//! Renders the active item master with on-hand quantities as CSV, with a
+//! column per custom item field after the standard ones. The Inventory
+//! page's export is a shorter stock listing, as CSV or XLSX, of the items
+//! its filters show.
+
+use csv::Writer;
+use rust_xlsxwriter::{Format, FormatBorder, Workbook};
+use serde::{Deserialize, Serialize};
+use wms_core::custom_fields::{display_value, CustomFieldDefinition};
+use wms_core::error::{WmsError, Result};
+use crate::models::InventoryItem;
+
+/// Columns of the stock listing export
+const STOCK_LIST_COLUMNS: [&str; 7] = [
+    "SKU",
+    "Name",
+    "Category",
+    "On Hand",
+    "Reorder Point",
+    "ABC Class",
+    "Status",
+];
+
+/// How an item's on-hand stock compares with its reorder point
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StockStatus {
+    InStock,
+    /// At or below the reorder point
+    LowStock,
+    OutOfStock,
+}
+
+impl StockStatus {
+    pub fn of(item: &InventoryItem) -> Self {
+        let on_hand = item.total_quantity.unwrap_or(0.0);
+        if on_hand <= 0.0 {
+            Self::OutOfStock
+        } else if item.reorder_point.is_some_and(|point| on_hand <= point) {
+            Self::LowStock
+        } else {
+            Self::InStock
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::InStock => "In Stock",
+            Self::LowStock => "Low Stock",
+            Self::OutOfStock => "Out of Stock",
+        }
+    }
+}
+
+/// Which items an inventory export includes: the Inventory page's search,
+/// category and status filters
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct InventoryExportFilter {
+    /// Matches SKU, name, barcode or a text custom field
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub query: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<StockStatus>,
+}
+
+/// File formats of the inventory export
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InventoryExportFormat {
+    Csv,
+    Xlsx,
+}
+
+impl InventoryExportFormat {
+    pub fn parse(format: &str) -> Result<Self> {
+        match format.to_lowercase().as_str() {
+            "csv" => Ok(Self::Csv),
+            "xlsx" | "excel" => Ok(Self::Xlsx),
+            _ => Err(WmsError::validation(format!("Unsupported format: {}", format))),
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Csv => "text/csv",
+            Self::Xlsx => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Xlsx => "xlsx",
+        }
+    }
+}
+
+/// Render items as a stock listing in `format`, one row per item
+pub fn render_stock_list(items: &[InventoryItem], format: InventoryExportFormat) -> Result<Vec<u8>> {
+    match format {
+        InventoryExportFormat::Csv => render_stock_list_csv(items),
+        InventoryExportFormat::Xlsx => render_stock_list_xlsx(items),
+    }
+}
+
+fn render_stock_list_csv(items: &[InventoryItem]) -> Result<Vec<u8>> {
+    let csv_error = |e: csv::Error| WmsError::Export(format!("CSV write error: {}", e));
+    let mut writer = Writer::from_writer(Vec::new());
+
+    writer.write_record(STOCK_LIST_COLUMNS).map_err(csv_error)?;
+    for item in items {
+        writer.write_record([
+            item.sku.clone(),
+            item.name.clone(),
+            item.category.clone().unwrap_or_default(),
+            item.total_quantity.unwrap_or(0.0).to_string(),
+            item.reorder_point.map(|p| p.to_string()).unwrap_or_default(),
+            item.abc_class.map(|c| c.as_str().to_string()).unwrap_or_default(),
+            StockStatus::of(item).label().to_string(),
+        ]).map_err(csv_error)?;
+    }
+
+    writer.into_inner()
+        .map_err(|e| WmsError::Export(format!("CSV flush error: {}", e)))
+}
+
+fn render_stock_list_xlsx(items: &[InventoryItem]) -> Result<Vec<u8>> {
+    let mut workbook = Workbook::new();
+    let header_format = Format::new()
+        .set_bold()
+        .set_border(FormatBorder::Thin);
+    let quantity_format = Format::new()
+        .set_num_format("#,##0.##");
+
+    let worksheet = workbook.add_worksheet();
+    worksheet.set_name("Inventory").ok();
+    worksheet.set_column_width(0, 16).ok();
+    worksheet.set_column_width(1, 30).ok();
+    worksheet.set_column_width(2, 18).ok();
+    worksheet.set_column_width(6, 14).ok();
+    for (col, header) in STOCK_LIST_COLUMNS.iter().enumerate() {
+        worksheet.write_string_with_format(0, col as u16, *header, &header_format).ok();
+    }
+
+    for (index, item) in items.iter().enumerate() {
+        let row = index as u32 + 1;
+        worksheet.write_string(row, 0, &item.sku).ok();
+        worksheet.write_string(row, 1, &item.name).ok();
+        if let Some(category) = &item.category {
+            worksheet.write_string(row, 2, category).ok();
+        }
+        worksheet.write_number_with_format(row, 3, item.total_quantity.unwrap_or(0.0), &quantity_format).ok();
+        if let Some(point) = item.reorder_point {
+            worksheet.write_number_with_format(row, 4, point, &quantity_format).ok();
+        }
+        if let Some(class) = item.abc_class {
+            worksheet.write_string(row, 5, class.as_str()).ok();
+        }
+        worksheet.write_string(row, 6, StockStatus::of(item).label()).ok();
+    }
+
+    workbook.save_to_buffer()
+        .map_err(|e| WmsError::Export(format!("Failed to create Excel file: {}", e)))
+}
+
+/// Render items as CSV, one row per item
+pub fn render_items_csv(items: &[InventoryItem], custom_fields: &[CustomFieldDefinition]) -> Result<Vec<u8>> {
+    let csv_error = |e: csv::Error| WmsError::Export(format!("CSV write error: {}", e));
+    let mut writer = Writer::from_writer(Vec::new());
+
+    let mut header = vec![
+        "SKU",
+        "Name",
+        "Category",
+        "Unit",
+        "Barcode",
+        "Parent Item ID",
+        "Reorder Point",
+        "Lead Time Days",
+        "ABC Class",
+        "On Hand",
+    ];
+    header.extend(custom_fields.iter().map(|f| f.label.as_str()));
+    writer.write_record(&header).map_err(csv_error)?;
+
+    for item in items {
+        let mut record = vec![
+            item.sku.clone(),
+            item.name.clone(),
+            item.category.clone().unwrap_or_default(),
+            format!("{:?}", item.unit_of_measure).to_lowercase(),
+            item.barcode.clone().unwrap_or_default(),
+            item.parent_item_id.clone().unwrap_or_default(),
+            item.reorder_point.map(|p| p.to_string()).unwrap_or_default(),
+            item.lead_time_days.to_string(),
+            item.abc_class.map(|c| format!("{:?}", c)).unwrap_or_default(),
+            item.total_quantity.unwrap_or(0.0).to_string(),
+        ];
+        record.extend(custom_fields.iter().map(|f| display_value(item.custom_fields.get(&f.key))));
+        writer.write_record(&record).map_err(csv_error)?;
+    }
+
+    writer.into_inner()
+        .map_err(|e| WmsError::Export(format!("CSV flush error: {}", e)))
+}
@@ -1,9 +1,50 @@
 //! Demand Forecasting Engine
 //! 
 //! Uses time series analysis (ETS/ARIMA) to predict future inventory demand.
+//! Sparse series are classified by their demand pattern, and intermittent
+//! or lumpy demand is forecast with Croston's method instead. Series with
+//! a clear weekly (or other configured) cycle get Holt-Winters.
 
 use serde::{Deserialize, Serialize};
 use wms_core::error::{WmsError, Result};
+use wms_core::operations::CancellationToken;
+
+/// Assumed replenishment lead time in days
+const LEAD_TIME_DAYS: f64 = 7.0;
+
+/// Days of supply covered by a suggested reorder
+const REORDER_COVER_DAYS: f64 = 30.0;
+
+/// Smoothing constant for Croston's size and interval estimates
+const CROSTON_ALPHA: f64 = 0.1;
+
+/// Periods in a moving average window unless configured
+const DEFAULT_WINDOW: usize = 7;
+
+/// History ETS needs to fit its smoothing constant
+const ETS_MIN_HISTORY: usize = 10;
+
+/// Periods in a season unless configured: a week of daily demand
+const DEFAULT_SEASON_LENGTH: usize = 7;
+
+/// Share of the detrended variance a repeating seasonal pattern must
+/// explain before Holt-Winters is chosen automatically
+const SEASONALITY_MIN_STRENGTH: f64 = 0.6;
+
+/// Smoothing constants tried when fitting Holt-Winters
+const HW_GRID: [f64; 5] = [0.1, 0.3, 0.5, 0.7, 0.9];
+
+/// Average inter-demand interval above which demand is intermittent
+/// (Syntetos-Boylan cut-off)
+const ADI_CUTOFF: f64 = 1.32;
+
+/// Squared coefficient of variation of demand sizes above which demand
+/// is variable (Syntetos-Boylan cut-off)
+const CV2_CUTOFF: f64 = 0.49;
+
+/// Share of zero periods above which demand is sparse unless configured,
+/// even when there were too few demands to classify
+const DEFAULT_SPARSE_ZERO_SHARE: f64 = 0.6;
 
 /// Forecast result with predictions and confidence intervals
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -20,22 +61,42 @@ pub struct ForecastResult {
     pub suggested_reorder_quantity: f64,
     /// Model used for forecasting
     pub model_type: ForecastModel,
+    /// Periods averaged, for moving-average models
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window: Option<usize>,
+    /// Weight of each period in the window, oldest first, summing to 1
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weights: Option<Vec<f64>>,
+    /// Periods in a season, for Holt-Winters
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub season_length: Option<usize>,
+    /// Demand pattern that chose the model; None with fewer than two
+    /// periods of demand
+    #[serde(default)]
+    pub classification: Option<DemandClassification>,
     /// Model fit metrics
     pub metrics: ForecastMetrics,
 }
 
 /// Available forecast models
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
 #[serde(rename_all = "UPPERCASE")]
 pub enum ForecastModel {
     /// Exponential Smoothing (Error, Trend, Seasonality)
     Ets,
     /// Simple Moving Average
     Sma,
-    /// Weighted Moving Average  
+    /// Weighted Moving Average
     Wma,
     /// Naive forecast (last value)
     Naive,
+    /// Croston's method for intermittent demand
+    Croston,
+    /// Syntetos-Boylan approximation (bias-corrected Croston)
+    Sba,
+    /// Holt-Winters: additive trend and seasonality
+    #[serde(rename = "HOLT_WINTERS")]
+    HoltWinters,
 }
 
 impl Default for ForecastModel {
@@ -44,6 +105,147 @@ impl Default for ForecastModel {
     }
 }
 
+impl ForecastModel {
+    pub const ALL: [Self; 7] = [
+        Self::Ets, Self::Sma, Self::Wma, Self::Naive, Self::Croston, Self::Sba, Self::HoltWinters,
+    ];
+    
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Ets => "ETS",
+            Self::Sma => "SMA",
+            Self::Wma => "WMA",
+            Self::Naive => "NAIVE",
+            Self::Croston => "CROSTON",
+            Self::Sba => "SBA",
+            Self::HoltWinters => "HOLT_WINTERS",
+        }
+    }
+    
+    /// Parse a stored model type
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_uppercase().as_str() {
+            "ETS" => Some(Self::Ets),
+            "SMA" => Some(Self::Sma),
+            "WMA" => Some(Self::Wma),
+            "NAIVE" => Some(Self::Naive),
+            "CROSTON" => Some(Self::Croston),
+            "SBA" => Some(Self::Sba),
+            "HOLT_WINTERS" => Some(Self::HoltWinters),
+            _ => None,
+        }
+    }
+}
+
+/// How a weighted moving average weighs the periods in its window
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WmaWeighting {
+    /// The newest period weighs `window`, the one before `window - 1`,
+    /// down to 1 for the oldest
+    Linear,
+    /// Each period weighs `decay` (above 0, at most 1) times the next
+    /// newer one
+    Exponential { decay: f64 },
+}
+
+/// Weighted moving average settings
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct WmaConfig {
+    /// Periods averaged
+    pub window: usize,
+    pub weighting: WmaWeighting,
+}
+
+impl Default for WmaConfig {
+    fn default() -> Self {
+        Self { window: DEFAULT_WINDOW, weighting: WmaWeighting::Linear }
+    }
+}
+
+impl WmaConfig {
+    /// Weights of the window's periods, oldest first, summing to 1
+    pub fn weights(&self) -> Vec<f64> {
+        let raw: Vec<f64> = (0..self.window)
+            .map(|i| match self.weighting {
+                WmaWeighting::Linear => (i + 1) as f64,
+                WmaWeighting::Exponential { decay } => decay.powi((self.window - 1 - i) as i32),
+            })
+            .collect();
+        let total: f64 = raw.iter().sum();
+        raw.into_iter().map(|w| w / total).collect()
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.window == 0 {
+            return Err(WmsError::Forecast("The WMA window needs at least one period".to_string()));
+        }
+        if let WmaWeighting::Exponential { decay } = self.weighting
+            && !(decay > 0.0 && decay <= 1.0)
+        {
+            return Err(WmsError::Forecast(format!(
+                "WMA decay must be above 0 and at most 1, not {}", decay
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Demand pattern by how often and how evenly an item is demanded
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DemandPattern {
+    /// Regular demand of similar size
+    Smooth,
+    /// Sporadic demand of similar size
+    Intermittent,
+    /// Sporadic demand of varying size
+    Lumpy,
+    /// Regular demand of varying size
+    Erratic,
+}
+
+impl DemandPattern {
+    /// Whether the series has many zero periods
+    pub fn is_sparse(&self) -> bool {
+        matches!(self, Self::Intermittent | Self::Lumpy)
+    }
+}
+
+/// How a demand history was classified
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct DemandClassification {
+    pub pattern: DemandPattern,
+    /// Average number of periods between demands (ADI)
+    pub average_interval: f64,
+    /// Squared coefficient of variation of non-zero demand sizes
+    pub cv_squared: f64,
+}
+
+/// Classify a demand history by average inter-demand interval and the
+/// variability of non-zero demand sizes. None when fewer than two
+/// periods had demand.
+pub fn classify_demand(history: &[f64]) -> Option<DemandClassification> {
+    let sizes: Vec<f64> = history.iter().copied().filter(|d| *d > 0.0).collect();
+    if sizes.len() < 2 {
+        return None;
+    }
+    
+    let average_interval = history.len() as f64 / sizes.len() as f64;
+    let mean = sizes.iter().sum::<f64>() / sizes.len() as f64;
+    let variance = sizes.iter().map(|d| (d - mean).powi(2)).sum::<f64>() / sizes.len() as f64;
+    let cv_squared = variance / (mean * mean);
+    
+    let pattern = match (average_interval >= ADI_CUTOFF, cv_squared >= CV2_CUTOFF) {
+        (false, false) => DemandPattern::Smooth,
+        (true, false) => DemandPattern::Intermittent,
+        (true, true) => DemandPattern::Lumpy,
+        (false, true) => DemandPattern::Erratic,
+    };
+    
+    Some(DemandClassification { pattern, average_interval, cv_squared })
+}
+
 /// Forecast model fit metrics
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ForecastMetrics {
@@ -61,6 +263,12 @@ pub struct ForecastMetrics {
 pub struct ForecastEngine {
     /// Default number of periods for moving average
     default_window: usize,
+    /// Window and weights of the weighted moving average
+    wma: WmaConfig,
+    /// Periods in a Holt-Winters season
+    season_length: usize,
+    /// Share of zero periods that makes demand sparse
+    sparse_zero_share: f64,
     /// Safety stock multiplier
     safety_stock_multiplier: f64,
 }
@@ -69,26 +277,119 @@ impl ForecastEngine {
     /// Create a new forecast engine
     pub fn new() -> Self {
         Self {
-            default_window: 7,
+            default_window: DEFAULT_WINDOW,
+            wma: WmaConfig::default(),
+            season_length: DEFAULT_SEASON_LENGTH,
+            sparse_zero_share: DEFAULT_SPARSE_ZERO_SHARE,
             safety_stock_multiplier: 1.65, // 95% service level
         }
     }
     
-    /// Run forecast on historical data
-    pub fn forecast(&self, history: &[f64], days_ahead: u32) -> Result<ForecastResult> {
+    /// Weigh weighted moving averages as configured
+    pub fn with_wma(mut self, wma: WmaConfig) -> Result<Self> {
+        wma.validate()?;
+        self.wma = wma;
+        Ok(self)
+    }
+    
+    /// Use seasons of `periods` periods for Holt-Winters
+    pub fn with_season_length(mut self, periods: usize) -> Result<Self> {
+        if periods < 2 {
+            return Err(WmsError::Forecast("A season needs at least two periods".to_string()));
+        }
+        self.season_length = periods;
+        Ok(self)
+    }
+    
+    /// Treat demand as sparse once more than `share` (0 to 1) of the
+    /// periods had none
+    pub fn with_sparse_threshold(mut self, share: f64) -> Result<Self> {
+        if !(0.0..1.0).contains(&share) {
+            return Err(WmsError::Forecast(format!(
+                "The sparse threshold is a share of periods from 0 up to 1, not {}", share
+            )));
+        }
+        self.sparse_zero_share = share;
+        Ok(self)
+    }
+    
+    /// Whether a history is forecast with Croston's method: classified as
+    /// intermittent or lumpy, or with more zero periods than the threshold
+    pub fn is_sparse(&self, history: &[f64]) -> bool {
+        if history.is_empty() {
+            return false;
+        }
+        let zeros = history.iter().filter(|d| **d <= 0.0).count();
+        classify_demand(history).is_some_and(|c| c.pattern.is_sparse())
+            || zeros as f64 / history.len() as f64 > self.sparse_zero_share
+    }
+    
+    /// Fewest periods of history `model` runs on when asked for by name
+    pub fn min_history(&self, model: ForecastModel) -> usize {
+        match model {
+            ForecastModel::Ets => ETS_MIN_HISTORY,
+            ForecastModel::Sma => self.default_window,
+            ForecastModel::Wma => self.wma.window,
+            ForecastModel::Naive => 1,
+            ForecastModel::Croston | ForecastModel::Sba => 2,
+            ForecastModel::HoltWinters => 2 * self.season_length,
+        }
+    }
+    
+    /// Run forecast on historical data, with `model` or, when None, the
+    /// one that suits the history best
+    pub fn forecast(&self, history: &[f64], days_ahead: u32, model: Option<ForecastModel>) -> Result<ForecastResult> {
+        self.forecast_with_cancel(history, days_ahead, model, &CancellationToken::new())
+    }
+    
+    /// `forecast`, stopping with `WmsError::Cancelled` once `cancel` is
+    /// cancelled
+    pub fn forecast_with_cancel(
+        &self,
+        history: &[f64],
+        days_ahead: u32,
+        model: Option<ForecastModel>,
+        cancel: &CancellationToken,
+    ) -> Result<ForecastResult> {
         if history.is_empty() {
             return Err(WmsError::Forecast("No historical data provided".to_string()));
         }
         
         let days = days_ahead as usize;
+        let classification = classify_demand(history);
         
-        // Try ETS first, fall back to simpler methods if needed
-        let (predictions, model) = if history.len() >= 30 {
-            (self.exponential_smoothing(history, days), ForecastModel::Ets)
-        } else if history.len() >= 7 {
-            (self.simple_moving_average(history, days), ForecastModel::Sma)
-        } else {
-            (self.naive_forecast(history, days), ForecastModel::Naive)
+        // Croston for sparse demand and Holt-Winters for seasonal demand;
+        // otherwise try ETS first, falling back to simpler methods if needed
+        let model = match (model, classification.map(|c| c.pattern)) {
+            (Some(model), _) => {
+                let needed = self.min_history(model);
+                if history.len() < needed {
+                    return Err(WmsError::Forecast(format!(
+                        "{} needs at least {} periods of history, only {} available",
+                        model.as_str(), needed, history.len()
+                    )));
+                }
+                model
+            }
+            (None, Some(DemandPattern::Lumpy)) => ForecastModel::Sba,
+            _ if self.is_sparse(history) => ForecastModel::Croston,
+            _ if history.len() >= 2 * self.season_length
+                && self.seasonality_strength(history) >= SEASONALITY_MIN_STRENGTH =>
+            {
+                ForecastModel::HoltWinters
+            }
+            _ if history.len() >= 30 => ForecastModel::Ets,
+            _ if history.len() >= 7 => ForecastModel::Sma,
+            _ => ForecastModel::Naive,
+        };
+        let predictions = self.run_model(model, history, days, cancel)?;
+        let (window, weights) = match model {
+            ForecastModel::Sma => {
+                let window = self.default_window.min(history.len());
+                (Some(window), Some(vec![1.0 / window as f64; window]))
+            }
+            ForecastModel::Wma => (Some(self.wma.window), Some(self.wma.weights())),
+            _ => (None, None),
         };
         
         // Calculate confidence intervals
@@ -103,15 +404,30 @@ impl ForecastEngine {
             .collect();
         
         // Calculate reorder suggestions
-        let avg_daily_demand = history.iter().sum::<f64>() / history.len() as f64;
-        let lead_time_demand = avg_daily_demand * 7.0; // Assume 7 day lead time
-        let safety_stock = margin * (7.0_f64).sqrt();
-        
-        let suggested_reorder_point = lead_time_demand + safety_stock;
-        let suggested_reorder_quantity = avg_daily_demand * 30.0; // 30 day supply
+        let (suggested_reorder_point, suggested_reorder_quantity) = match self.croston_estimates(history) {
+            Some((size, interval)) if matches!(model, ForecastModel::Croston | ForecastModel::Sba) => {
+                // Cover the demands expected within the lead time, each of
+                // a typical size, rather than a thin daily average
+                let sizes: Vec<f64> = history.iter().copied().filter(|d| *d > 0.0).collect();
+                let demands_in_lead_time = (LEAD_TIME_DAYS / interval).ceil().max(1.0);
+                let safety_stock = self.safety_stock_multiplier
+                    * self.calculate_std_dev(&sizes)
+                    * demands_in_lead_time.sqrt();
+                (
+                    size * demands_in_lead_time + safety_stock,
+                    (size * REORDER_COVER_DAYS / interval).max(size),
+                )
+            }
+            _ => {
+                let avg_daily_demand = history.iter().sum::<f64>() / history.len() as f64;
+                let lead_time_demand = avg_daily_demand * LEAD_TIME_DAYS;
+                let safety_stock = margin * LEAD_TIME_DAYS.sqrt();
+                (lead_time_demand + safety_stock, avg_daily_demand * REORDER_COVER_DAYS)
+            }
+        };
         
         // Calculate fit metrics using hold-out validation
-        let metrics = self.calculate_metrics(history);
+        let metrics = self.calculate_metrics(model, history, cancel)?;
         
         Ok(ForecastResult {
             predictions,
@@ -120,14 +436,46 @@ impl ForecastEngine {
             suggested_reorder_point,
             suggested_reorder_quantity,
             model_type: model,
+            window,
+            weights,
+            season_length: (model == ForecastModel::HoltWinters).then_some(self.season_length),
+            classification,
             metrics,
         })
     }
     
+    /// Predictions of a given model, regardless of which one `forecast`
+    /// would pick for this much history. Used to compare models.
+    pub fn predict(&self, model: ForecastModel, history: &[f64], days_ahead: u32) -> Result<Vec<f64>> {
+        if history.is_empty() {
+            return Err(WmsError::Forecast("No historical data provided".to_string()));
+        }
+        
+        self.run_model(model, history, days_ahead as usize, &CancellationToken::new())
+    }
+    
+    fn run_model(
+        &self,
+        model: ForecastModel,
+        history: &[f64],
+        periods: usize,
+        cancel: &CancellationToken,
+    ) -> Result<Vec<f64>> {
+        Ok(match model {
+            ForecastModel::Ets => self.exponential_smoothing(history, periods, cancel)?,
+            ForecastModel::Sma => self.simple_moving_average(history, periods),
+            ForecastModel::Wma => self.weighted_moving_average(history, periods),
+            ForecastModel::Naive => self.naive_forecast(history, periods),
+            ForecastModel::Croston => self.croston(history, periods, false),
+            ForecastModel::Sba => self.croston(history, periods, true),
+            ForecastModel::HoltWinters => self.holt_winters(history, periods, cancel)?,
+        })
+    }
+    
     /// Exponential Smoothing (Simple ETS)
-    fn exponential_smoothing(&self, history: &[f64], periods: usize) -> Vec<f64> {
+    fn exponential_smoothing(&self, history: &[f64], periods: usize, cancel: &CancellationToken) -> Result<Vec<f64>> {
         // Optimize alpha using grid search
-        let alpha = self.optimize_alpha(history);
+        let alpha = self.optimize_alpha(history, cancel)?;
         
         // Initialize with first value
         let mut level = history[0];
@@ -138,15 +486,16 @@ impl ForecastEngine {
         }
         
         // Forecast (flat for simple ETS)
-        vec![level; periods]
+        Ok(vec![level; periods])
     }
     
     /// Optimize smoothing parameter alpha
-    fn optimize_alpha(&self, history: &[f64]) -> f64 {
+    fn optimize_alpha(&self, history: &[f64], cancel: &CancellationToken) -> Result<f64> {
         let mut best_alpha = 0.3;
         let mut best_mse = f64::MAX;
         
         for alpha_int in 1..10 {
+            cancel.check()?;
             let alpha = alpha_int as f64 * 0.1;
             let mse = self.ets_mse(history, alpha);
             
@@ -156,7 +505,7 @@ impl ForecastEngine {
             }
         }
         
-        best_alpha
+        Ok(best_alpha)
     }
     
     /// Calculate MSE for given alpha
@@ -177,6 +526,96 @@ impl ForecastEngine {
         sum_sq_error / (history.len() - 1) as f64
     }
     
+    /// Holt-Winters with additive trend and seasonality. The smoothing
+    /// constants are fitted by grid search on one-step-ahead errors.
+    /// Histories shorter than two seasons get simple ETS instead.
+    fn holt_winters(&self, history: &[f64], periods: usize, cancel: &CancellationToken) -> Result<Vec<f64>> {
+        let m = self.season_length;
+        if history.len() < 2 * m {
+            return self.exponential_smoothing(history, periods, cancel);
+        }
+        
+        let mut best = (f64::MAX, 0.3, 0.1, 0.1);
+        for alpha in HW_GRID {
+            cancel.check()?;
+            for beta in HW_GRID {
+                for gamma in HW_GRID {
+                    let (sse, ..) = self.fit_holt_winters(history, alpha, beta, gamma);
+                    if sse < best.0 {
+                        best = (sse, alpha, beta, gamma);
+                    }
+                }
+            }
+        }
+        
+        let (_, level, trend, seasonals) = self.fit_holt_winters(history, best.1, best.2, best.3);
+        let n = history.len();
+        // Demand can't go negative, however steep the trend
+        Ok((1..=periods)
+            .map(|h| (level + h as f64 * trend + seasonals[n - m + (h - 1) % m]).max(0.0))
+            .collect())
+    }
+    
+    /// Run Holt-Winters over the history: the sum of squared one-step
+    /// errors, the final level and trend, and a seasonal index per period
+    fn fit_holt_winters(&self, history: &[f64], alpha: f64, beta: f64, gamma: f64) -> (f64, f64, f64, Vec<f64>) {
+        let m = self.season_length;
+        let first = history[..m].iter().sum::<f64>() / m as f64;
+        let second = history[m..2 * m].iter().sum::<f64>() / m as f64;
+        
+        // Start from the first season's mean, the change to the second's
+        // and the first season's deviations from its mean
+        let mut level = first;
+        let mut trend = (second - first) / m as f64;
+        let mut seasonals: Vec<f64> = history[..m].iter().map(|x| x - first).collect();
+        let mut sse = 0.0;
+        
+        for (t, &value) in history.iter().enumerate().skip(m) {
+            let seasonal = seasonals[t - m];
+            let error = value - (level + trend + seasonal);
+            sse += error * error;
+            
+            let previous_level = level;
+            level = alpha * (value - seasonal) + (1.0 - alpha) * (level + trend);
+            trend = beta * (level - previous_level) + (1.0 - beta) * trend;
+            seasonals.push(gamma * (value - level) + (1.0 - gamma) * seasonal);
+        }
+        
+        (sse, level, trend, seasonals)
+    }
+    
+    /// How much of the variation left after removing each season's mean
+    /// is a pattern repeating every season: 1 for a pure cycle, near 0 for
+    /// noise. Only whole seasons count.
+    fn seasonality_strength(&self, history: &[f64]) -> f64 {
+        let m = self.season_length;
+        let seasons = history.len() / m;
+        if seasons < 2 {
+            return 0.0;
+        }
+        
+        let detrended: Vec<f64> = history[..seasons * m]
+            .chunks(m)
+            .flat_map(|season| {
+                let mean = season.iter().sum::<f64>() / m as f64;
+                season.iter().map(move |x| x - mean)
+            })
+            .collect();
+        let indices: Vec<f64> = (0..m)
+            .map(|j| (0..seasons).map(|k| detrended[k * m + j]).sum::<f64>() / seasons as f64)
+            .collect();
+        
+        let total: f64 = detrended.iter().map(|d| d * d).sum();
+        if total <= f64::EPSILON {
+            return 0.0;
+        }
+        let remainder: f64 = detrended.iter()
+            .enumerate()
+            .map(|(i, d)| (d - indices[i % m]).powi(2))
+            .sum();
+        (1.0 - remainder / total).max(0.0)
+    }
+    
     /// Simple Moving Average forecast
     fn simple_moving_average(&self, history: &[f64], periods: usize) -> Vec<f64> {
         let window = self.default_window.min(history.len());
@@ -190,12 +629,70 @@ impl ForecastEngine {
         vec![avg; periods]
     }
     
+    /// Weighted Moving Average forecast (the most recent value weighs most)
+    /// Histories shorter than the window average what there is.
+    fn weighted_moving_average(&self, history: &[f64], periods: usize) -> Vec<f64> {
+        let window = self.wma.window.min(history.len());
+        let recent = &history[history.len() - window..];
+        
+        let weights = WmaConfig { window, ..self.wma }.weights();
+        let avg = recent.iter()
+            .zip(&weights)
+            .map(|(value, weight)| value * weight)
+            .sum();
+        vec![avg; periods]
+    }
+    
     /// Naive forecast (last value)
     fn naive_forecast(&self, history: &[f64], periods: usize) -> Vec<f64> {
         let last = *history.last().unwrap_or(&0.0);
         vec![last; periods]
     }
     
+    /// Croston's method: demand per period is the smoothed demand size over
+    /// the smoothed interval between demands. SBA scales it by
+    /// (1 - alpha/2) to remove Croston's upward bias.
+    fn croston(&self, history: &[f64], periods: usize, bias_corrected: bool) -> Vec<f64> {
+        let rate = match self.croston_estimates(history) {
+            Some((size, interval)) => size / interval,
+            None => 0.0,
+        };
+        let factor = if bias_corrected { 1.0 - CROSTON_ALPHA / 2.0 } else { 1.0 };
+        vec![rate * factor; periods]
+    }
+    
+    /// Smoothed (demand size, periods between demands), or None if there
+    /// was no demand
+    fn croston_estimates(&self, history: &[f64]) -> Option<(f64, f64)> {
+        let demand_days: Vec<usize> = history.iter()
+            .enumerate()
+            .filter(|(_, d)| **d > 0.0)
+            .map(|(i, _)| i)
+            .collect();
+        let (&first, &last) = demand_days.first().zip(demand_days.last())?;
+        
+        // Start from the first demand and the mean gap between demands
+        let mut size = history[first];
+        let mut interval = if demand_days.len() > 1 {
+            (last - first) as f64 / (demand_days.len() - 1) as f64
+        } else {
+            history.len() as f64
+        };
+        let mut since_demand = 1.0;
+        
+        for &demand in &history[first + 1..] {
+            if demand > 0.0 {
+                size += CROSTON_ALPHA * (demand - size);
+                interval += CROSTON_ALPHA * (since_demand - interval);
+                since_demand = 1.0;
+            } else {
+                since_demand += 1.0;
+            }
+        }
+        
+        Some((size, interval))
+    }
+    
     /// Calculate standard deviation
     fn calculate_std_dev(&self, data: &[f64]) -> f64 {
         if data.len() < 2 {
@@ -211,14 +708,19 @@ impl ForecastEngine {
     }
     
     /// Calculate forecast metrics using hold-out validation
-    fn calculate_metrics(&self, history: &[f64]) -> ForecastMetrics {
+    fn calculate_metrics(
+        &self,
+        model: ForecastModel,
+        history: &[f64],
+        cancel: &CancellationToken,
+    ) -> Result<ForecastMetrics> {
         if history.len() < 10 {
-            return ForecastMetrics {
+            return Ok(ForecastMetrics {
                 mae: 0.0,
                 mse: 0.0,
                 rmse: 0.0,
                 mape: 0.0,
-            };
+            });
         }
         
         // Use last 20% as test set
@@ -227,7 +729,7 @@ impl ForecastEngine {
         let test = &history[split..];
         
         // Generate predictions for test period
-        let predictions = self.exponential_smoothing(train, test.len());
+        let predictions = self.run_model(model, train, test.len(), cancel)?;
         
         // Calculate errors
         let errors: Vec<f64> = test.iter()
@@ -247,7 +749,7 @@ impl ForecastEngine {
             .map(|(actual, pred)| ((*actual - *pred) / *actual).abs())
             .sum::<f64>() / n * 100.0;
         
-        ForecastMetrics { mae, mse, rmse, mape }
+        Ok(ForecastMetrics { mae, mse, rmse, mape })
     }
 }
 
@@ -266,7 +768,7 @@ mod tests {
         let engine = ForecastEngine::new();
         let history: Vec<f64> = (0..50).map(|i| 100.0 + i as f64 * 2.0 + (i as f64 * 0.5).sin() * 10.0).collect();
         
-        let result = engine.forecast(&history, 7).unwrap();
+        let result = engine.forecast(&history, 7, None).unwrap();
         
         assert_eq!(result.predictions.len(), 7);
         assert!(result.predictions[0] > 0.0);
@@ -278,16 +780,204 @@ mod tests {
         let engine = ForecastEngine::new();
         let history: Vec<f64> = vec![];
         
-        let result = engine.forecast(&history, 7);
+        let result = engine.forecast(&history, 7, None);
         assert!(result.is_err());
     }
     
+    #[test]
+    fn test_predict_runs_the_requested_model() {
+        let engine = ForecastEngine::new();
+        let history = vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0];
+        
+        assert_eq!(engine.predict(ForecastModel::Naive, &history, 2).unwrap(), vec![7.0, 7.0]);
+        assert_eq!(engine.predict(ForecastModel::Sma, &history, 1).unwrap(), vec![4.0]);
+        // (1*1 + 2*2 + ... + 7*7) / 28
+        assert_eq!(engine.predict(ForecastModel::Wma, &history, 1).unwrap(), vec![5.0]);
+        assert_eq!(ForecastModel::parse(ForecastModel::Wma.as_str()), Some(ForecastModel::Wma));
+    }
+    
+    #[test]
+    fn test_wma_matches_hand_computed_averages() {
+        let history = vec![2.0, 4.0, 6.0, 8.0];
+        
+        // (4*1 + 6*2 + 8*3) / 6
+        let linear = ForecastEngine::new()
+            .with_wma(WmaConfig { window: 3, weighting: WmaWeighting::Linear })
+            .unwrap();
+        let result = linear.forecast(&history, 2, Some(ForecastModel::Wma)).unwrap();
+        assert!(result.predictions.iter().all(|p| (p - 40.0 / 6.0).abs() < 1e-9));
+        assert_eq!(result.window, Some(3));
+        let weights = result.weights.unwrap();
+        assert!((weights[0] - 1.0 / 6.0).abs() < 1e-9 && (weights[2] - 0.5).abs() < 1e-9);
+        
+        // (4*0.25 + 6*0.5 + 8*1) / 1.75
+        let exponential = ForecastEngine::new()
+            .with_wma(WmaConfig { window: 3, weighting: WmaWeighting::Exponential { decay: 0.5 } })
+            .unwrap();
+        let result = exponential.forecast(&history, 1, Some(ForecastModel::Wma)).unwrap();
+        assert!((result.predictions[0] - 12.0 / 1.75).abs() < 1e-9);
+        
+        let bad_decay = WmaConfig { window: 3, weighting: WmaWeighting::Exponential { decay: 1.5 } };
+        assert!(ForecastEngine::new().with_wma(bad_decay).is_err());
+    }
+    
+    #[test]
+    fn test_requested_model_needs_enough_history() {
+        let engine = ForecastEngine::new();
+        let history = vec![3.0, 5.0, 4.0];
+        
+        let err = engine.forecast(&history, 1, Some(ForecastModel::Wma)).unwrap_err();
+        assert!(err.to_string().contains("WMA needs at least 7 periods"), "{}", err);
+        assert!(engine.forecast(&history, 1, Some(ForecastModel::Ets)).is_err());
+        
+        let naive = engine.forecast(&history, 1, Some(ForecastModel::Naive)).unwrap();
+        assert_eq!((naive.model_type, naive.predictions[0]), (ForecastModel::Naive, 4.0));
+        assert_eq!(naive.window, None);
+        assert_eq!(engine.forecast(&history, 1, None).unwrap().model_type, ForecastModel::Naive);
+    }
+    
+    /// Demand of 4-6 units roughly every fifth day
+    fn intermittent_series() -> Vec<f64> {
+        (0..90).map(|day| match day % 5 {
+            0 if day % 10 == 0 => 4.0,
+            0 => 6.0,
+            _ => 0.0,
+        }).collect()
+    }
+    
+    #[test]
+    fn test_demand_classification() {
+        let steady = classify_demand(&[10.0; 30]).unwrap();
+        assert_eq!(steady.pattern, DemandPattern::Smooth);
+        assert_eq!(steady.average_interval, 1.0);
+        
+        let intermittent = classify_demand(&intermittent_series()).unwrap();
+        assert_eq!(intermittent.pattern, DemandPattern::Intermittent);
+        assert_eq!(intermittent.average_interval, 5.0);
+        
+        let mut lumpy = vec![0.0; 40];
+        lumpy[3] = 1.0;
+        lumpy[17] = 40.0;
+        lumpy[30] = 2.0;
+        assert_eq!(classify_demand(&lumpy).unwrap().pattern, DemandPattern::Lumpy);
+        
+        let erratic: Vec<f64> = (0..30).map(|i| if i % 2 == 0 { 1.0 } else { 30.0 }).collect();
+        assert_eq!(classify_demand(&erratic).unwrap().pattern, DemandPattern::Erratic);
+        
+        assert_eq!(classify_demand(&[0.0, 0.0, 5.0]), None);
+    }
+    
+    #[test]
+    fn test_intermittent_demand_uses_croston() {
+        let engine = ForecastEngine::new();
+        let history = intermittent_series();
+        
+        let result = engine.forecast(&history, 14, None).unwrap();
+        
+        assert_eq!(result.model_type, ForecastModel::Croston);
+        assert_eq!(result.classification.map(|c| c.pattern), Some(DemandPattern::Intermittent));
+        // About 5 units every 5 days
+        assert!(result.predictions.iter().all(|p| (p - result.predictions[0]).abs() < 1e-9));
+        assert!(result.predictions[0] > 0.8 && result.predictions[0] < 1.2);
+        
+        // Another quiet week barely moves the estimate
+        let mut quieter = history.clone();
+        quieter.extend([0.0; 4]);
+        let later = engine.forecast(&quieter, 1, None).unwrap();
+        assert_eq!(later.predictions[0], result.predictions[0]);
+        
+        // SBA is Croston scaled down by 1 - alpha/2
+        let sba = engine.predict(ForecastModel::Sba, &history, 1).unwrap();
+        assert!((sba[0] - result.predictions[0] * 0.95).abs() < 1e-9);
+        
+        // Two demands of ~5 fall inside a 7 day lead time
+        assert!(result.suggested_reorder_point >= 10.0);
+        assert!(result.suggested_reorder_point < 15.0);
+        assert!((result.suggested_reorder_quantity - 30.0).abs() < 3.0);
+    }
+    
+    /// A week-long cycle around 50 units a day, slightly uneven
+    fn weekly_series(days: usize) -> Vec<f64> {
+        (0..days)
+            .map(|t| {
+                let phase = 2.0 * std::f64::consts::PI * t as f64 / 7.0;
+                50.0 + 20.0 * phase.sin() + if t % 3 == 0 { 1.5 } else { -0.75 }
+            })
+            .collect()
+    }
+    
+    #[test]
+    fn test_weekly_cycle_uses_holt_winters() {
+        let engine = ForecastEngine::new();
+        let history = weekly_series(84);
+        
+        let result = engine.forecast(&history, 14, None).unwrap();
+        assert_eq!((result.model_type, result.season_length), (ForecastModel::HoltWinters, Some(7)));
+        let (low, high) = result.predictions.iter()
+            .fold((f64::MAX, f64::MIN), |(low, high), p| (low.min(*p), high.max(*p)));
+        assert!(high - low > 30.0, "predictions should follow the cycle: {:?}", result.predictions);
+        
+        // Held-out fortnight: the moving average predicts the weekly mean
+        let (train, actual) = history.split_at(70);
+        let rmse = |model| {
+            let predicted = engine.predict(model, train, 14).unwrap();
+            let sse: f64 = predicted.iter().zip(actual).map(|(p, a)| (p - a).powi(2)).sum();
+            (sse / 14.0).sqrt()
+        };
+        let (hw, sma) = (rmse(ForecastModel::HoltWinters), rmse(ForecastModel::Sma));
+        assert!(hw < sma / 4.0, "Holt-Winters RMSE {} vs SMA {}", hw, sma);
+        
+        // Flat demand, or too little of it, has no season to model
+        assert_eq!(engine.forecast(&[10.0; 60], 7, None).unwrap().model_type, ForecastModel::Ets);
+        assert!(engine.forecast(&history[..10], 7, Some(ForecastModel::HoltWinters)).is_err());
+        assert!(ForecastEngine::new().with_season_length(1).is_err());
+    }
+    
+    #[test]
+    fn test_croston_reorder_point_ignores_zero_days() {
+        let engine = ForecastEngine::new();
+        // 10 units every eighth day
+        let history: Vec<f64> = (0..120).map(|day| if day % 8 == 3 { 10.0 } else { 0.0 }).collect();
+        
+        let croston = engine.forecast(&history, 14, None).unwrap();
+        let sma = engine.forecast(&history, 14, Some(ForecastModel::Sma)).unwrap();
+        assert_eq!(croston.model_type, ForecastModel::Croston);
+        
+        // One demand falls in the lead time; the daily average pads it
+        // with safety stock for the swings between zero and ten
+        assert!((croston.suggested_reorder_point - 10.0).abs() < 1e-6, "{}", croston.suggested_reorder_point);
+        assert!(sma.suggested_reorder_point > 20.0, "{}", sma.suggested_reorder_point);
+        // Ten units per eight days over the 30 days covered
+        assert!((croston.suggested_reorder_quantity - 37.5).abs() < 1e-6);
+        assert_eq!(croston.predictions.len(), 14);
+        assert!(croston.upper_bound[0] > croston.predictions[0]);
+    }
+    
+    #[test]
+    fn test_single_demand_is_sparse_above_the_threshold() {
+        let mut history = vec![0.0; 90];
+        history[40] = 12.0;
+        assert_eq!(classify_demand(&history), None);
+        
+        let result = ForecastEngine::new().forecast(&history, 7, None).unwrap();
+        assert_eq!(result.model_type, ForecastModel::Croston);
+        assert!((result.predictions[0] - 12.0 / 90.0).abs() < 1e-9);
+        
+        // Mostly-busy demand only counts as sparse with a low threshold
+        let mostly_busy: Vec<f64> = (0..60).map(|day| if day % 5 == 0 { 0.0 } else { 4.0 }).collect();
+        assert!(!ForecastEngine::new().is_sparse(&mostly_busy));
+        let strict = ForecastEngine::new().with_sparse_threshold(0.1).unwrap();
+        assert!(strict.is_sparse(&mostly_busy));
+        assert_eq!(strict.forecast(&mostly_busy, 1, None).unwrap().model_type, ForecastModel::Croston);
+        assert!(ForecastEngine::new().with_sparse_threshold(1.0).is_err());
+    }
+    
     #[test]
     fn test_reorder_suggestions() {
         let engine = ForecastEngine::new();
         let history: Vec<f64> = vec![10.0; 60]; // Steady demand
         
-        let result = engine.forecast(&history, 30).unwrap();
+        let result = engine.forecast(&history, 30, None).unwrap();
         
         // With steady demand of 10/day, reorder point should be around 70 (7 days lead time)
         assert!(result.suggested_reorder_point > 50.0);
@@ -0,0 +1,362 @@
+//! Pick Heatmap
+//!
+//! Counts pick transactions per location over a period and rolls them up
+//! by aisle, so managers can see which bins are hit most. A slotting pass
+//! then flags A-class items stored far from the depot, where the front
+//! cross-aisle meets shipping, and suggests a closer empty location.
+
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashSet};
+use chrono::NaiveDate;
+use csv::WriterBuilder;
+use serde::{Deserialize, Serialize};
+use wms_core::error::{WmsError, Result};
+
+/// Picks over a period, by location and by aisle
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PickHeatmap {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    /// Busiest locations first
+    pub locations: Vec<LocationPicks>,
+    /// Ordered by aisle; locations without an aisle come last
+    pub aisles: Vec<AislePicks>,
+    pub slotting: Vec<SlottingSuggestion>,
+    pub total_picks: u32,
+    pub total_quantity: f64,
+}
+
+/// Picks from one location
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LocationPicks {
+    pub location_id: String,
+    pub location_code: String,
+    pub zone: String,
+    pub aisle: Option<String>,
+    pub rack: Option<String>,
+    pub picks: u32,
+    pub quantity: f64,
+}
+
+/// Picks from all locations in one aisle
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AislePicks {
+    pub aisle: Option<String>,
+    /// Locations in the aisle that were picked from
+    pub locations: u32,
+    pub picks: u32,
+    pub quantity: f64,
+    /// Share of all picks in the period
+    pub pick_percent: f64,
+}
+
+/// A fast mover that should move closer to the depot
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SlottingSuggestion {
+    pub item_id: String,
+    pub sku: String,
+    pub name: String,
+    /// Picks of the item in the period
+    pub picks: u32,
+    pub location_id: String,
+    pub location_code: String,
+    pub distance_m: f64,
+    pub suggested_location_id: String,
+    pub suggested_location_code: String,
+    pub suggested_distance_m: f64,
+}
+
+/// Exported pick heatmap
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PickHeatmapExport {
+    /// Base64 encoded file data
+    pub data: String,
+    /// MIME content type
+    pub content_type: String,
+    /// Suggested filename
+    pub filename: String,
+}
+
+/// A location with coordinates
+#[derive(Debug, Clone)]
+pub struct SlotLocation {
+    pub id: String,
+    pub code: String,
+    pub x_m: f64,
+    pub y_m: f64,
+}
+
+impl SlotLocation {
+    /// Walking distance from the depot at the origin, along the front
+    /// cross-aisle and then up the aisle
+    pub fn distance_m(&self) -> f64 {
+        self.x_m.abs() + self.y_m
+    }
+}
+
+/// An A-class item and the closest location it is stored in
+#[derive(Debug, Clone)]
+pub struct StoredItem {
+    pub item_id: String,
+    pub sku: String,
+    pub name: String,
+    pub picks: u32,
+    pub location: SlotLocation,
+}
+
+/// Convert a row with `id`, `code`, `x_m` and `y_m` columns to SlotLocation
+pub(crate) fn row_to_slot_location(row: &rusqlite::Row) -> rusqlite::Result<SlotLocation> {
+    Ok(SlotLocation {
+        id: row.get("id")?,
+        code: row.get("code")?,
+        x_m: row.get("x_m")?,
+        y_m: row.get("y_m")?,
+    })
+}
+
+/// Distance beyond which a location counts as far: the median distance
+/// of all pickable locations
+pub fn far_threshold_m(locations: &[SlotLocation]) -> Option<f64> {
+    let mut distances: Vec<f64> = locations.iter().map(SlotLocation::distance_m).collect();
+    if distances.is_empty() {
+        return None;
+    }
+    distances.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+    let mid = distances.len() / 2;
+    Some(if distances.len().is_multiple_of(2) {
+        (distances[mid - 1] + distances[mid]) / 2.0
+    } else {
+        distances[mid]
+    })
+}
+
+/// Suggest moves for items stored beyond `far_m`, busiest first. Each
+/// empty location within `far_m` is offered to at most one item, nearest
+/// first.
+pub fn suggest_slotting(items: &[StoredItem], empty: &[SlotLocation], far_m: f64) -> Vec<SlottingSuggestion> {
+    let mut candidates: Vec<&SlotLocation> = empty.iter().filter(|l| l.distance_m() <= far_m).collect();
+    candidates.sort_by(|a, b| {
+        a.distance_m().partial_cmp(&b.distance_m()).unwrap_or(Ordering::Equal).then_with(|| a.code.cmp(&b.code))
+    });
+
+    let mut far_items: Vec<&StoredItem> = items.iter().filter(|i| i.location.distance_m() > far_m).collect();
+    far_items.sort_by(|a, b| b.picks.cmp(&a.picks).then_with(|| a.sku.cmp(&b.sku)));
+
+    let mut taken = HashSet::new();
+    let mut suggestions = Vec::new();
+    for item in far_items {
+        let Some(target) = candidates.iter().find(|l| !taken.contains(&l.id)) else {
+            break;
+        };
+        taken.insert(target.id.clone());
+        suggestions.push(SlottingSuggestion {
+            item_id: item.item_id.clone(),
+            sku: item.sku.clone(),
+            name: item.name.clone(),
+            picks: item.picks,
+            location_id: item.location.id.clone(),
+            location_code: item.location.code.clone(),
+            distance_m: item.location.distance_m(),
+            suggested_location_id: target.id.clone(),
+            suggested_location_code: target.code.clone(),
+            suggested_distance_m: target.distance_m(),
+        });
+    }
+    suggestions
+}
+
+/// Assemble the heatmap, rolling location picks up by aisle
+pub fn build_pick_heatmap(
+    start: NaiveDate,
+    end: NaiveDate,
+    mut locations: Vec<LocationPicks>,
+    slotting: Vec<SlottingSuggestion>,
+) -> PickHeatmap {
+    locations.sort_by(|a, b| b.picks.cmp(&a.picks).then_with(|| a.location_code.cmp(&b.location_code)));
+    let total_picks: u32 = locations.iter().map(|l| l.picks).sum();
+    let total_quantity: f64 = locations.iter().map(|l| l.quantity).sum();
+
+    let mut by_aisle: BTreeMap<Option<String>, AislePicks> = BTreeMap::new();
+    for location in &locations {
+        let aisle = by_aisle.entry(location.aisle.clone()).or_insert_with(|| AislePicks {
+            aisle: location.aisle.clone(),
+            locations: 0,
+            picks: 0,
+            quantity: 0.0,
+            pick_percent: 0.0,
+        });
+        aisle.locations += 1;
+        aisle.picks += location.picks;
+        aisle.quantity += location.quantity;
+    }
+
+    let (unassigned, mut aisles): (Vec<_>, Vec<_>) = by_aisle.into_values().partition(|a| a.aisle.is_none());
+    aisles.extend(unassigned);
+    for aisle in &mut aisles {
+        if total_picks > 0 {
+            aisle.pick_percent = aisle.picks as f64 / total_picks as f64 * 100.0;
+        }
+    }
+
+    PickHeatmap {
+        start,
+        end,
+        locations,
+        aisles,
+        slotting,
+        total_picks,
+        total_quantity,
+    }
+}
+
+/// Render the heatmap as CSV: locations, then the aisle rollup, then
+/// slotting suggestions, separated by blank rows
+pub fn render_pick_heatmap_csv(heatmap: &PickHeatmap) -> Result<Vec<u8>> {
+    let csv_error = |e: csv::Error| WmsError::Export(format!("CSV write error: {}", e));
+    let mut writer = WriterBuilder::new().flexible(true).from_writer(Vec::new());
+
+    writer.write_record(["Location", "Zone", "Aisle", "Rack", "Picks", "Quantity"]).map_err(csv_error)?;
+    for location in &heatmap.locations {
+        writer.write_record([
+            location.location_code.clone(),
+            location.zone.clone(),
+            location.aisle.clone().unwrap_or_default(),
+            location.rack.clone().unwrap_or_default(),
+            location.picks.to_string(),
+            format!("{:.2}", location.quantity),
+        ]).map_err(csv_error)?;
+    }
+
+    writer.write_record([""]).map_err(csv_error)?;
+    writer.write_record(["Aisle", "Locations", "Picks", "Quantity", "% of Picks"]).map_err(csv_error)?;
+    for aisle in &heatmap.aisles {
+        writer.write_record([
+            aisle.aisle.clone().unwrap_or_else(|| "(none)".to_string()),
+            aisle.locations.to_string(),
+            aisle.picks.to_string(),
+            format!("{:.2}", aisle.quantity),
+            format!("{:.1}", aisle.pick_percent),
+        ]).map_err(csv_error)?;
+    }
+    writer.write_record([
+        "Total".to_string(),
+        heatmap.locations.len().to_string(),
+        heatmap.total_picks.to_string(),
+        format!("{:.2}", heatmap.total_quantity),
+        String::new(),
+    ]).map_err(csv_error)?;
+
+    if !heatmap.slotting.is_empty() {
+        writer.write_record([""]).map_err(csv_error)?;
+        writer.write_record([
+            "SKU",
+            "Name",
+            "Picks",
+            "Current Location",
+            "Distance (m)",
+            "Suggested Location",
+            "Suggested Distance (m)",
+        ]).map_err(csv_error)?;
+        for suggestion in &heatmap.slotting {
+            writer.write_record([
+                suggestion.sku.clone(),
+                suggestion.name.clone(),
+                suggestion.picks.to_string(),
+                suggestion.location_code.clone(),
+                format!("{:.1}", suggestion.distance_m),
+                suggestion.suggested_location_code.clone(),
+                format!("{:.1}", suggestion.suggested_distance_m),
+            ]).map_err(csv_error)?;
+        }
+    }
+
+    writer.into_inner()
+        .map_err(|e| WmsError::Export(format!("CSV flush error: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn location(code: &str, x_m: f64, y_m: f64) -> SlotLocation {
+        SlotLocation { id: code.to_lowercase(), code: code.to_string(), x_m, y_m }
+    }
+
+    fn stored(sku: &str, picks: u32, at: SlotLocation) -> StoredItem {
+        StoredItem {
+            item_id: sku.to_lowercase(),
+            sku: sku.to_string(),
+            name: sku.to_string(),
+            picks,
+            location: at,
+        }
+    }
+
+    fn picks(code: &str, aisle: Option<&str>, picks: u32, quantity: f64) -> LocationPicks {
+        LocationPicks {
+            location_id: code.to_lowercase(),
+            location_code: code.to_string(),
+            zone: "PICKING".to_string(),
+            aisle: aisle.map(String::from),
+            rack: None,
+            picks,
+            quantity,
+        }
+    }
+
+    #[test]
+    fn test_far_threshold_is_median_distance() {
+        let locations = vec![location("A", 2.0, 0.0), location("B", 2.0, 8.0), location("C", 20.0, 10.0)];
+        assert_eq!(far_threshold_m(&locations), Some(10.0));
+        assert_eq!(far_threshold_m(&locations[..2]), Some(6.0));
+        assert_eq!(far_threshold_m(&[]), None);
+    }
+
+    #[test]
+    fn test_busiest_far_items_get_nearest_empty_locations() {
+        let items = vec![
+            stored("SLOW", 5, location("D-10", 40.0, 20.0)),
+            stored("FAST", 50, location("D-09", 40.0, 18.0)),
+            stored("NEAR", 80, location("A-01", 2.0, 1.0)),
+        ];
+        let empty = vec![location("A-03", 2.0, 6.0), location("A-02", 2.0, 3.0), location("C-05", 30.0, 5.0)];
+
+        let suggestions = suggest_slotting(&items, &empty, 20.0);
+
+        assert_eq!(suggestions.len(), 2);
+        assert_eq!(suggestions[0].sku, "FAST");
+        assert_eq!(suggestions[0].suggested_location_code, "A-02");
+        assert_eq!(suggestions[0].suggested_distance_m, 5.0);
+        assert_eq!(suggestions[1].sku, "SLOW");
+        assert_eq!(suggestions[1].suggested_location_code, "A-03");
+
+        // Only one near empty slot left after FAST takes the first
+        let suggestions = suggest_slotting(&items, &empty[..1], 20.0);
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].sku, "FAST");
+    }
+
+    #[test]
+    fn test_aisle_rollup_and_csv() {
+        let start = NaiveDate::from_ymd_opt(2025, 1, 1).unwrap();
+        let end = NaiveDate::from_ymd_opt(2025, 1, 31).unwrap();
+        let heatmap = build_pick_heatmap(start, end, vec![
+            picks("B-01", Some("B"), 10, 20.0),
+            picks("A-02", Some("A"), 30, 45.0),
+            picks("FLOOR", None, 20, 20.0),
+            picks("A-01", Some("A"), 20, 25.0),
+        ], Vec::new());
+
+        assert_eq!(heatmap.total_picks, 80);
+        assert_eq!(heatmap.locations[0].location_code, "A-02");
+        let aisles: Vec<_> = heatmap.aisles.iter().map(|a| (a.aisle.as_deref(), a.locations, a.picks)).collect();
+        assert_eq!(aisles, vec![(Some("A"), 2, 50), (Some("B"), 1, 10), (None, 1, 20)]);
+        assert_eq!(heatmap.aisles[0].pick_percent, 62.5);
+
+        let csv = String::from_utf8(render_pick_heatmap_csv(&heatmap).unwrap()).unwrap();
+        assert!(csv.starts_with("Location,Zone,Aisle,Rack,Picks,Quantity\nA-02,PICKING,A,,30,45.00\n"));
+        assert!(csv.contains("A,2,50,70.00,62.5\n"));
+        assert!(csv.contains("(none),1,20,20.00,25.0\n"));
+        assert!(csv.contains("Total,4,80,110.00,\n"));
+    }
+}
@@ -0,0 +1,214 @@
+//! Quality Holds
+//!
+//! Quarantine for suspect lots. While a hold is active the lot is skipped
+//! by stock allocation and cannot be picked or transferred. Releasing a
+//! hold records what happened to the stock.
+
+use std::sync::Arc;
+use chrono::{DateTime, NaiveDate, Utc};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use wms_core::db::Database;
+use wms_core::error::Result;
+use wms_core::types::{required_timestamp, stored_timestamp, unreadable};
+
+/// SQL condition excluding stock rows (aliased `s`) whose lot is on hold
+pub(crate) const NOT_HELD: &str = "NOT EXISTS (
+    SELECT 1 FROM quality_holds h
+    WHERE h.item_id = s.item_id AND h.lot_number = s.lot_number AND h.released_at IS NULL
+)";
+
+/// What happens to held stock when the hold is released
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HoldDisposition {
+    /// Stock passed inspection and becomes available again
+    Release,
+    /// Stock is destroyed; a scrap adjustment is posted
+    Scrap,
+    /// Stock goes back to the supplier
+    ReturnToVendor,
+}
+
+impl HoldDisposition {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Release => "release",
+            Self::Scrap => "scrap",
+            Self::ReturnToVendor => "return_to_vendor",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "release" => Some(Self::Release),
+            "scrap" => Some(Self::Scrap),
+            "return_to_vendor" => Some(Self::ReturnToVendor),
+            _ => None,
+        }
+    }
+}
+
+/// Quality hold on one lot of an item
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QualityHold {
+    pub id: String,
+    pub item_id: String,
+    pub lot_number: String,
+    pub reason: String,
+    pub placed_by: String,
+    pub placed_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub released_by: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub released_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disposition: Option<HoldDisposition>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+}
+
+impl QualityHold {
+    /// Whether the hold still blocks the lot
+    pub fn is_active(&self) -> bool {
+        self.released_at.is_none()
+    }
+}
+
+/// Stock selected to fill a quantity
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StockAllocation {
+    pub location_id: String,
+    pub location_code: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lot_number: Option<String>,
+    pub quantity: f64,
+}
+
+/// Take quantity from candidates in the order given until `quantity` is
+/// covered. Returns less than requested when stock runs out.
+pub fn allocate_in_order(candidates: Vec<StockAllocation>, quantity: f64) -> Vec<StockAllocation> {
+    let mut remaining = quantity;
+
+    candidates.into_iter()
+        .filter(|c| c.quantity > 0.0)
+        .map_while(|mut candidate| {
+            if remaining <= 0.0 {
+                return None;
+            }
+            candidate.quantity = candidate.quantity.min(remaining);
+            remaining -= candidate.quantity;
+            Some(candidate)
+        })
+        .collect()
+}
+
+/// Chooses stock for outbound lines, skipping held lots
+pub struct StockAllocator {
+    db: Arc<Database>,
+}
+
+impl StockAllocator {
+    pub fn new(db: Arc<Database>) -> Self {
+        Self { db }
+    }
+
+    /// Allocate `quantity` of an item: earliest expiry first (FEFO), then
+    /// oldest stock first (FIFO). Lots on quality hold are never chosen,
+    /// nor lots that expired before `expired_before` when it is given.
+    pub async fn allocate(
+        &self,
+        item_id: &str,
+        quantity: f64,
+        expired_before: Option<NaiveDate>,
+    ) -> Result<Vec<StockAllocation>> {
+        let candidates = self.db.query_map(
+            &format!(
+                "SELECT s.location_id, l.code, s.lot_number, s.quantity
+                 FROM inventory_stock s
+                 JOIN locations l ON s.location_id = l.id
+                 WHERE s.item_id = ?1 AND s.quantity > 0 AND l.is_active = 1
+                   AND {}
+                   AND (?2 IS NULL OR s.expiry_date IS NULL OR date(s.expiry_date) >= ?2)
+                 ORDER BY s.expiry_date IS NULL, date(s.expiry_date), s.updated_at, l.code",
+                NOT_HELD
+            ),
+            params![item_id, expired_before.map(|d| d.to_string())],
+            |row| Ok(StockAllocation {
+                location_id: row.get("location_id")?,
+                location_code: row.get("code")?,
+                lot_number: row.get::<_, Option<String>>("lot_number")?.filter(|l| !l.is_empty()),
+                quantity: row.get("quantity")?,
+            }),
+        )?;
+
+        Ok(allocate_in_order(candidates, quantity))
+    }
+
+    /// Active hold on a lot, if any
+    pub async fn active_hold(&self, item_id: &str, lot_number: &str) -> Result<Option<QualityHold>> {
+        self.db.query_row(
+            "SELECT * FROM quality_holds
+             WHERE item_id = ? AND lot_number = ? AND released_at IS NULL",
+            params![item_id, lot_number],
+            row_to_hold,
+        )
+    }
+}
+
+/// Convert database row to QualityHold
+pub(crate) fn row_to_hold(row: &rusqlite::Row) -> rusqlite::Result<QualityHold> {
+    Ok(QualityHold {
+        id: row.get("id")?,
+        item_id: row.get("item_id")?,
+        lot_number: row.get("lot_number")?,
+        reason: row.get("reason")?,
+        placed_by: row.get("placed_by")?,
+        placed_at: required_timestamp(row, "placed_at")?,
+        released_by: row.get("released_by")?,
+        released_at: stored_timestamp(row, "released_at")?,
+        disposition: row.get::<_, Option<String>>("disposition")?
+            .map(|s| HoldDisposition::parse(&s).ok_or_else(|| unreadable(row, "disposition", &s)))
+            .transpose()?,
+        notes: row.get("notes")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stock(location: &str, lot: &str, quantity: f64) -> StockAllocation {
+        StockAllocation {
+            location_id: location.to_string(),
+            location_code: location.to_uppercase(),
+            lot_number: Some(lot.to_string()),
+            quantity,
+        }
+    }
+
+    #[test]
+    fn test_allocate_in_order_spans_lots() {
+        let candidates = vec![stock("a", "L1", 4.0), stock("b", "L2", 0.0), stock("c", "L3", 10.0)];
+
+        let allocated = allocate_in_order(candidates, 7.0);
+
+        assert_eq!(allocated, vec![stock("a", "L1", 4.0), stock("c", "L3", 3.0)]);
+    }
+
+    #[test]
+    fn test_allocate_in_order_short_stock() {
+        let allocated = allocate_in_order(vec![stock("a", "L1", 2.0)], 5.0);
+        assert_eq!(allocated, vec![stock("a", "L1", 2.0)]);
+
+        assert!(allocate_in_order(vec![stock("a", "L1", 2.0)], 0.0).is_empty());
+    }
+
+    #[test]
+    fn test_disposition_round_trip() {
+        for disposition in [HoldDisposition::Release, HoldDisposition::Scrap, HoldDisposition::ReturnToVendor] {
+            assert_eq!(HoldDisposition::parse(disposition.as_str()), Some(disposition));
+        }
+        assert_eq!(HoldDisposition::parse("destroy"), None);
+    }
+}
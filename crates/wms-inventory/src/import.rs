@@ -0,0 +1,242 @@
+//! Item Master Import
+//!
+//! Reads items from a CSV with a header row. Columns are found by header
+//! name rather than position, so sheets exported from another system can
+//! be imported as they are. Every row is checked before anything is
+//! written; bad rows are reported with their line and column and the rest
+//! of the file still goes in.
+
+use std::collections::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use wms_core::error::{WmsError, Result};
+use wms_core::types::UnitOfMeasure;
+use crate::models::ItemDimensions;
+
+/// Header names accepted for each column, after lowercasing and turning
+/// spaces into underscores
+const CSV_COLUMNS: &[(&str, &[&str])] = &[
+    ("sku", &["sku", "item_sku", "item_number", "part_number"]),
+    ("name", &["name", "item_name", "title"]),
+    ("description", &["description", "desc"]),
+    ("category", &["category"]),
+    ("unit_of_measure", &["unit_of_measure", "unit", "uom"]),
+    ("barcode", &["barcode", "upc", "ean", "gtin"]),
+    ("reorder_point", &["reorder_point", "min", "reorder_level"]),
+    ("reorder_quantity", &["reorder_quantity", "reorder_qty"]),
+    ("weight", &["weight", "weight_kg"]),
+    ("dimensions", &["dimensions", "dimensions_cm", "lxwxh"]),
+];
+
+/// How an import treats SKUs that already exist
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ImportOptions {
+    /// Update existing items from their rows instead of reporting them
+    #[serde(default)]
+    pub update_existing: bool,
+}
+
+/// Outcome of an item import
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ImportReport {
+    pub created: usize,
+    pub updated: usize,
+    /// Rows not imported because of the errors below
+    pub skipped: usize,
+    pub errors: Vec<RowError>,
+}
+
+/// Why a row was not imported
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RowError {
+    /// Line in the file, counting the header as line 1
+    pub line: usize,
+    /// Column at fault, when it is one column
+    pub field: Option<String>,
+    pub message: String,
+}
+
+impl RowError {
+    pub(crate) fn new(line: usize, field: Option<&str>, message: impl Into<String>) -> Self {
+        Self { line, field: field.map(String::from), message: message.into() }
+    }
+}
+
+/// One valid row. Optional fields left blank are None, which keeps an
+/// existing item's value on update.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ImportRow {
+    pub line: usize,
+    pub sku: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub category: Option<String>,
+    pub unit_of_measure: Option<UnitOfMeasure>,
+    pub barcode: Option<String>,
+    pub reorder_point: Option<f64>,
+    pub reorder_quantity: Option<f64>,
+    pub weight_kg: Option<f64>,
+    pub dimensions: Option<ItemDimensions>,
+}
+
+/// Parse an item CSV into valid rows and the errors of the rest. Fails
+/// outright only when the file can't be read or lacks a SKU or name
+/// column.
+pub(crate) fn parse_items_csv(data: &[u8]) -> Result<(Vec<ImportRow>, Vec<RowError>)> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .flexible(true)
+        .from_reader(data);
+
+    let headers: Vec<String> = reader.headers()
+        .map_err(|e| WmsError::validation(format!("Invalid item CSV: {}", e)))?
+        .iter()
+        .map(|h| h.trim_start_matches('\u{feff}').to_lowercase().replace(' ', "_"))
+        .collect();
+    let columns: HashMap<&str, usize> = CSV_COLUMNS.iter()
+        .filter_map(|(field, aliases)| {
+            headers.iter()
+                .position(|h| aliases.contains(&h.as_str()))
+                .map(|index| (*field, index))
+        })
+        .collect();
+    for required in ["sku", "name"] {
+        if !columns.contains_key(required) {
+            return Err(WmsError::validation(format!("Item CSV is missing the {} column", required)));
+        }
+    }
+
+    let mut rows = Vec::new();
+    let mut errors = Vec::new();
+    let mut seen = HashSet::new();
+    for (index, record) in reader.records().enumerate() {
+        let line = index + 2;
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                errors.push(RowError::new(line, None, format!("Unreadable row: {}", e)));
+                continue;
+            }
+        };
+        if record.iter().all(|value| value.is_empty()) {
+            continue;
+        }
+        let field = |name: &str| columns.get(name)
+            .and_then(|&i| record.get(i))
+            .filter(|v| !v.is_empty());
+
+        let mut row_errors = Vec::new();
+        let mut number = |name: &str| -> Option<f64> {
+            let value = field(name)?;
+            match value.replace(',', "").parse::<f64>() {
+                Ok(n) if n.is_finite() && n >= 0.0 => Some(n),
+                _ => {
+                    row_errors.push(RowError::new(line, Some(name), format!("'{}' is not a valid number", value)));
+                    None
+                }
+            }
+        };
+        let reorder_point = number("reorder_point");
+        let reorder_quantity = number("reorder_quantity");
+        let weight_kg = number("weight");
+
+        let unit_of_measure = field("unit_of_measure").and_then(|value| {
+            let unit = UnitOfMeasure::parse(value);
+            if unit.is_none() {
+                row_errors.push(RowError::new(line, Some("unit_of_measure"), format!("Unknown unit '{}'", value)));
+            }
+            unit
+        });
+        let dimensions = field("dimensions").and_then(|value| {
+            let dimensions = parse_dimensions(value);
+            if dimensions.is_none() {
+                row_errors.push(RowError::new(
+                    line,
+                    Some("dimensions"),
+                    format!("'{}' is not length x width x height in cm", value),
+                ));
+            }
+            dimensions
+        });
+
+        let sku = field("sku").unwrap_or_default().to_string();
+        if sku.is_empty() {
+            row_errors.push(RowError::new(line, Some("sku"), "SKU is required"));
+        } else if !seen.insert(sku.to_lowercase()) {
+            row_errors.push(RowError::new(line, Some("sku"), format!("SKU {} appears earlier in the file", sku)));
+        }
+        let name = field("name").unwrap_or_default().to_string();
+        if name.is_empty() {
+            row_errors.push(RowError::new(line, Some("name"), "Name is required"));
+        }
+
+        if row_errors.is_empty() {
+            rows.push(ImportRow {
+                line,
+                sku,
+                name,
+                description: field("description").map(String::from),
+                category: field("category").map(String::from),
+                unit_of_measure,
+                barcode: field("barcode").map(String::from),
+                reorder_point,
+                reorder_quantity,
+                weight_kg,
+                dimensions,
+            });
+        } else {
+            errors.extend(row_errors);
+        }
+    }
+    Ok((rows, errors))
+}
+
+/// "40x30x20" (also with "×", "*" or spaces) as length, width and height
+fn parse_dimensions(value: &str) -> Option<ItemDimensions> {
+    let parts: Vec<f64> = value.to_lowercase()
+        .trim_end_matches("cm")
+        .split(['x', '×', '*'])
+        .map(|part| part.trim().parse::<f64>().ok().filter(|n| n.is_finite() && *n >= 0.0))
+        .collect::<Option<_>>()?;
+    match parts[..] {
+        [length_cm, width_cm, height_cm] => Some(ItemDimensions { length_cm, width_cm, height_cm }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_columns_are_matched_by_header() {
+        let csv = "\u{feff}Item Name,SKU,UOM,Dimensions,Reorder Point\nBlue Widget,WID-1,cs,40 x 30 x 20,12\n";
+        let (rows, errors) = parse_items_csv(csv.as_bytes()).unwrap();
+
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(rows[0].sku, "WID-1");
+        assert_eq!(rows[0].name, "Blue Widget");
+        assert_eq!(rows[0].unit_of_measure, Some(UnitOfMeasure::Case));
+        assert_eq!(rows[0].dimensions, Some(ItemDimensions { length_cm: 40.0, width_cm: 30.0, height_cm: 20.0 }));
+        assert_eq!(rows[0].reorder_point, Some(12.0));
+        assert_eq!(rows[0].barcode, None);
+
+        let err = parse_items_csv(b"sku,description\nWID-1,Widget\n").unwrap_err();
+        assert!(err.to_string().contains("missing the name column"));
+    }
+
+    #[test]
+    fn test_bad_rows_are_reported_by_line_and_field() {
+        let csv = "sku,name,unit,weight\n\
+                   A-1,Apple,each,1.5\n\
+                   ,No SKU,each,\n\
+                   B-1,Banana,bunch,two\n";
+        let (rows, errors) = parse_items_csv(csv.as_bytes()).unwrap();
+
+        assert_eq!(rows.iter().map(|r| r.line).collect::<Vec<_>>(), vec![2]);
+        assert_eq!(errors, vec![
+            RowError::new(3, Some("sku"), "SKU is required"),
+            RowError::new(4, Some("weight"), "'two' is not a valid number"),
+            RowError::new(4, Some("unit_of_measure"), "Unknown unit 'bunch'"),
+        ]);
+    }
+}
@@ -3,15 +3,85 @@
 //! Provides inventory management functionality including:
 //! - Item master management
 //! - Stock level tracking
+//! - Multiple warehouses, with per-warehouse stock totals and low-stock checks
+//! - Reorder report with on-order stock, days of cover and CSV export
 //! - Inventory adjustments with CRDT support
+//! - Stock transfers between locations, as linked out and in transactions
 //! - Demand forecasting using time series analysis
-//! - ABC classification
+//! - Croston forecasts for intermittent demand
+//! - Forecast accuracy tracking with a comparison of models
+//! - ABC classification by picked value, with Pareto cutoffs
+//! - Cycle counts with variance posting and recounts outside the tolerance
+//!   for the item's ABC class
+//! - Price lists with per-customer overrides
+//! - Available-to-promise projections
+//! - Pick path optimization
+//! - Workload planning (required vs scheduled labor hours)
+//! - Pick heatmaps with slotting suggestions
+//! - Picker productivity leaderboard and personal stats
+//! - Quality holds and hold-aware stock allocation
+//! - Lot balances, expiry alerts and FEFO pick suggestions
+//! - Pick-face replenishment from bulk storage
+//! - Item images with thumbnails
+//! - Size/color variants under a parent SKU
+//! - Item master CSV export, and CSV import with a per-row error report
+//! - Filtered stock listing export as CSV or XLSX
 
 mod models;
 mod service;
 mod forecast;
+mod accuracy;
+mod pricing;
+mod atp;
+mod pickpath;
+mod workload;
+mod heatmap;
+mod productivity;
+mod holds;
+mod lots;
+mod reorder;
+mod replenishment;
+mod variants;
+mod counting;
+mod abc;
+mod export;
+mod import;
 
 pub use models::*;
-pub use service::InventoryService;
-pub use forecast::{ForecastEngine, ForecastResult};
+pub use service::{InventoryService, ITEM_ATTACHMENT_OWNER};
+pub use export::{render_items_csv, InventoryExportFilter, InventoryExportFormat, StockStatus};
+pub use import::{ImportOptions, ImportReport, RowError};
+pub use forecast::{
+    classify_demand, DemandClassification, DemandPattern, ForecastEngine, ForecastMetrics, ForecastModel,
+    ForecastResult, WmaConfig, WmaWeighting,
+};
+pub use accuracy::{
+    score_predictions, AccuracyPoint, ForecastAccuracy, ForecastEvaluation, ModelAccuracy,
+    ACCURACY_WINDOW_DAYS, MIN_ACCURACY_SAMPLES,
+};
+pub use pricing::{ItemPrice, PriceBook, PriceList, PriceListItem};
+pub use atp::{AvailabilityProjection, DailyAvailability, PromiseCheck};
+pub use pickpath::{PickPath, PickPathOptimizer, PickPathStrategy, PickStop};
+pub use workload::{WorkloadDay, WorkloadPlan, WorkloadPlanExport};
+pub use heatmap::{AislePicks, LocationPicks, PickHeatmap, PickHeatmapExport, SlottingSuggestion};
+pub use productivity::{MyPickerStats, PickerDay, PickerLeaderboard, PickerStats, STREAK_LOOKBACK_DAYS};
+pub use holds::{allocate_in_order, HoldDisposition, QualityHold, StockAllocation, StockAllocator};
+pub use lots::{ExpiringStock, LotBalance};
+pub use reorder::{
+    render_reorder_csv, ReorderLine, ReorderQuantitySource, ReorderReport, ReorderReportExport,
+    REORDER_DEMAND_DAYS,
+};
+pub use replenishment::{
+    replenishment_need, ReplenishmentPriority, ReplenishmentRule, ReplenishmentTask,
+    ReplenishmentTaskStatus,
+};
+pub use counting::{CountLine, CountSheet, CountStatus, CountVarianceTolerances};
+pub use abc::{
+    AbcClassSummary, AbcClassificationReport, AbcItemClass, DEFAULT_A_THRESHOLD_PERCENT,
+    DEFAULT_B_THRESHOLD_PERCENT,
+};
+pub use variants::{
+    variant_combinations, ItemSearchQuery, LowStockGroup, VariantAttributes, VariantAxis,
+    VariantMatrixResult, VariantSkuRules, VariantStock, VariantStockSummary,
+};
 
@@ -0,0 +1,59 @@
+//! Lots and Expiry
+//!
+//! Per-lot balances of an item and stock nearing its expiry date. Stock
+//! without a lot number is reported as one unnamed lot. A lot is expired
+//! from the day after its expiry date.
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+/// On-hand balance of one lot of an item, across locations
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LotBalance {
+    pub item_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lot_number: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expiry_date: Option<NaiveDate>,
+    pub quantity: f64,
+    /// Locations holding the lot
+    pub location_count: u32,
+    pub expired: bool,
+    /// Under an active quality hold
+    pub on_hold: bool,
+}
+
+/// Stock at one location whose lot expires within the alert window
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExpiringStock {
+    pub item_id: String,
+    pub sku: String,
+    pub item_name: String,
+    pub location_id: String,
+    pub location_code: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lot_number: Option<String>,
+    pub expiry_date: NaiveDate,
+    pub quantity: f64,
+    /// Negative once the lot has expired
+    pub days_until_expiry: i64,
+}
+
+/// Stored expiry dates are plain dates or RFC 3339 timestamps
+pub(crate) fn parse_expiry(value: Option<String>) -> Option<NaiveDate> {
+    value.and_then(|v| v.get(..10).and_then(|d| d.parse().ok()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_expiry_accepts_dates_and_timestamps() {
+        let expected = NaiveDate::from_ymd_opt(2026, 1, 31);
+        assert_eq!(parse_expiry(Some("2026-01-31".into())), expected);
+        assert_eq!(parse_expiry(Some("2026-01-31T00:00:00+00:00".into())), expected);
+        assert_eq!(parse_expiry(Some("soon".into())), None);
+        assert_eq!(parse_expiry(None), None);
+    }
+}
@@ -2,7 +2,9 @@
 
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use wms_core::custom_fields::CustomFieldValues;
 use wms_core::types::UnitOfMeasure;
+use crate::variants::VariantAttributes;
 
 /// Inventory item (product/SKU)
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -40,9 +42,21 @@ pub struct InventoryItem {
     pub abc_class: Option<AbcClass>,
     #[serde(default = "default_true")]
     pub is_active: bool,
+    /// Parent item when this item is a variant (size, color, ...)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_item_id: Option<String>,
+    /// Attribute values distinguishing this variant, e.g. size → M
+    #[serde(default, skip_serializing_if = "VariantAttributes::is_empty")]
+    pub variant_attributes: VariantAttributes,
+    /// Values of the deployment's custom item fields
+    #[serde(default, skip_serializing_if = "CustomFieldValues::is_empty")]
+    pub custom_fields: CustomFieldValues,
     pub created_at: DateTime<Utc>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub updated_at: Option<DateTime<Utc>>,
+    /// Row version; edits must send back the version they were based on
+    #[serde(default)]
+    pub version: i64,
     /// Computed field: total quantity across all locations
     #[serde(skip_serializing_if = "Option::is_none")]
     pub total_quantity: Option<f64>,
@@ -53,7 +67,7 @@ fn default_true() -> bool {
 }
 
 /// Item physical dimensions
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ItemDimensions {
     pub length_cm: f64,
     pub width_cm: f64,
@@ -88,11 +102,46 @@ pub enum AbcClass {
     C, // Low value, loose control
 }
 
+impl AbcClass {
+    /// Stored class letter
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::A => "A",
+            Self::B => "B",
+            Self::C => "C",
+        }
+    }
+    
+    /// Parse the stored class letter
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "A" => Some(Self::A),
+            "B" => Some(Self::B),
+            "C" => Some(Self::C),
+            _ => None,
+        }
+    }
+}
+
+/// A site holding locations
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Warehouse {
+    pub id: String,
+    pub code: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    pub is_active: bool,
+    pub created_at: DateTime<Utc>,
+}
+
 /// Warehouse location
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Location {
     pub id: String,
     pub code: String,
+    /// Warehouse the location, and the stock in it, belongs to
+    pub warehouse_id: String,
     pub zone: LocationZone,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub aisle: Option<String>,
@@ -102,6 +151,15 @@ pub struct Location {
     pub level: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub bin: Option<String>,
+    /// Position across aisles in meters (for pick path optimization)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub x_m: Option<f64>,
+    /// Position along the aisle from the front cross-aisle in meters
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub y_m: Option<f64>,
+    /// Whether the location's aisle can be walked through to the back
+    #[serde(default)]
+    pub aisle_traversal: AisleTraversal,
     pub is_active: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub capacity_units: Option<f64>,
@@ -133,6 +191,65 @@ pub enum LocationZone {
     Returns,
 }
 
+impl LocationZone {
+    /// Stored uppercase zone name
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Receiving => "RECEIVING",
+            Self::Storage => "STORAGE",
+            Self::Picking => "PICKING",
+            Self::Shipping => "SHIPPING",
+            Self::Staging => "STAGING",
+            Self::Quarantine => "QUARANTINE",
+            Self::Returns => "RETURNS",
+        }
+    }
+    
+    /// Parse the stored uppercase zone name
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "RECEIVING" => Some(Self::Receiving),
+            "STORAGE" => Some(Self::Storage),
+            "PICKING" => Some(Self::Picking),
+            "SHIPPING" => Some(Self::Shipping),
+            "STAGING" => Some(Self::Staging),
+            "QUARANTINE" => Some(Self::Quarantine),
+            "RETURNS" => Some(Self::Returns),
+            _ => None,
+        }
+    }
+}
+
+/// How pickers can walk an aisle
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AisleTraversal {
+    /// Open onto both the front and back cross-aisles
+    #[default]
+    Through,
+    /// Closed at the back; entered and left from the front
+    DeadEnd,
+}
+
+impl AisleTraversal {
+    /// Stored traversal name
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Through => "through",
+            Self::DeadEnd => "dead_end",
+        }
+    }
+    
+    /// Parse the stored traversal name
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "through" => Some(Self::Through),
+            "dead_end" => Some(Self::DeadEnd),
+            _ => None,
+        }
+    }
+}
+
 /// Inventory stock level at a specific location
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InventoryStock {
@@ -165,6 +282,9 @@ pub struct InventoryAdjustment {
     pub item_id: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub location_id: Option<String>,
+    /// Warehouse the location must belong to, when the caller states one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warehouse_id: Option<String>,
     pub adjustment_type: AdjustmentType,
     pub quantity: f64,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -173,32 +293,65 @@ pub struct InventoryAdjustment {
     pub reason_code: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub notes: Option<String>,
+    /// Who made the adjustment; commands set the signed-in user
+    #[serde(default)]
     pub user_id: String,
 }
 
+impl InventoryAdjustment {
+    /// Whether the adjustment takes stock away, whatever its type. Raw
+    /// adjustments that do are write-offs; picks and transfers that remove
+    /// stock go through their own workflows.
+    pub fn removes_stock(&self) -> bool {
+        self.quantity * self.adjustment_type.sign() < 0.0
+    }
+}
+
 /// Types of inventory adjustments
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "UPPERCASE")]
 pub enum AdjustmentType {
     Receive,   // Incoming goods
     Pick,      // Outbound pick (negative)
+    Unpick,    // Pick put back when its shipment is cancelled (positive)
     Adjust,    // Manual adjustment
     Transfer,  // Move between locations
     Count,     // Cycle count correction
     Damage,    // Damaged goods (negative)
     Return,    // Customer return (positive)
     Scrap,     // Scrap/dispose (negative)
+    #[serde(rename = "TRANSFER_OUT")]
+    TransferOut, // Source half of a transfer (negative)
+    #[serde(rename = "TRANSFER_IN")]
+    TransferIn,  // Destination half of a transfer (positive)
 }
 
 impl AdjustmentType {
     /// Get the sign multiplier for this adjustment type
     pub fn sign(&self) -> f64 {
         match self {
-            Self::Receive | Self::Return => 1.0,
-            Self::Pick | Self::Damage | Self::Scrap => -1.0,
+            Self::Receive | Self::Unpick | Self::Return | Self::TransferIn => 1.0,
+            Self::Pick | Self::Damage | Self::Scrap | Self::TransferOut => -1.0,
             Self::Adjust | Self::Transfer | Self::Count => 1.0, // Uses actual delta
         }
     }
+    
+    /// Stored transaction type
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Receive => "RECEIVE",
+            Self::Pick => "PICK",
+            Self::Unpick => "UNPICK",
+            Self::Adjust => "ADJUST",
+            Self::Transfer => "TRANSFER",
+            Self::Count => "COUNT",
+            Self::Damage => "DAMAGE",
+            Self::Return => "RETURN",
+            Self::Scrap => "SCRAP",
+            Self::TransferOut => "TRANSFER_OUT",
+            Self::TransferIn => "TRANSFER_IN",
+        }
+    }
 }
 
 /// Inventory transaction record
@@ -224,10 +377,31 @@ pub struct InventoryTransaction {
     pub reason_code: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub notes: Option<String>,
+    /// Shared by the two halves of a stock transfer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transfer_id: Option<String>,
     pub user_id: String,
     pub created_at: DateTime<Utc>,
 }
 
+/// A completed move of stock between two locations
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StockTransfer {
+    pub transfer_id: String,
+    pub item_id: String,
+    pub from_location_id: String,
+    pub to_location_id: String,
+    pub quantity: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lot_number: Option<String>,
+    /// Quantity left at the source for the lot
+    pub remaining_at_source: f64,
+    /// The destination is in the quarantine zone
+    pub to_quarantine: bool,
+    pub transferred_by: String,
+    pub transferred_at: DateTime<Utc>,
+}
+
 /// Stock level summary for an item
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StockSummary {
@@ -252,5 +426,44 @@ pub struct LocationStock {
     pub quantity: f64,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub lot_number: Option<String>,
+    /// Quantity blocked by a quality hold on the lot
+    #[serde(default)]
+    pub held_quantity: f64,
+    /// Quantity free to allocate
+    #[serde(default)]
+    pub available_quantity: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hold_reason: Option<String>,
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn adjustment(adjustment_type: AdjustmentType, quantity: f64) -> InventoryAdjustment {
+        InventoryAdjustment {
+            item_id: "widget".to_string(),
+            location_id: None,
+            warehouse_id: None,
+            adjustment_type,
+            quantity,
+            lot_number: None,
+            reason_code: None,
+            notes: None,
+            user_id: "u1".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_removing_stock_follows_the_signed_quantity() {
+        assert!(adjustment(AdjustmentType::Adjust, -3.0).removes_stock());
+        assert!(adjustment(AdjustmentType::Count, -1.0).removes_stock());
+        assert!(adjustment(AdjustmentType::Scrap, 2.0).removes_stock());
+        assert!(adjustment(AdjustmentType::Pick, 5.0).removes_stock());
+        assert!(adjustment(AdjustmentType::TransferOut, 5.0).removes_stock());
+        assert!(adjustment(AdjustmentType::Unpick, -5.0).removes_stock());
+        assert!(!adjustment(AdjustmentType::Adjust, 3.0).removes_stock());
+        assert!(!adjustment(AdjustmentType::Unpick, 5.0).removes_stock());
+    }
+}
@@ -0,0 +1,343 @@
+//! Pick Path Optimization
+//!
+//! Sequences pick lines through a rectangular aisle layout. Aisles run front
+//! to back (y axis) between a front and a back cross-aisle; the depot sits on
+//! the front cross-aisle. Uses the S-shape heuristic: every aisle containing
+//! a pick is traversed completely, alternating direction. Dead-end aisles,
+//! and a through aisle with no through aisle after it to return by, are
+//! entered and left from the front.
+
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use crate::models::AisleTraversal;
+
+/// Locations closer than this on the x axis are treated as the same aisle
+const AISLE_TOLERANCE_M: f64 = 1.0;
+
+/// A pick line to be sequenced
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PickStop {
+    /// Caller's identifier for the line
+    pub id: String,
+    pub location_code: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aisle: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub x_m: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub y_m: Option<f64>,
+    #[serde(default)]
+    pub traversal: AisleTraversal,
+}
+
+/// Sequenced pick path
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PickPath {
+    /// Stop IDs in walking order
+    pub stop_order: Vec<String>,
+    /// Estimated round-trip walk from the depot, when coordinates are known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub estimated_distance_m: Option<f64>,
+    pub strategy: PickPathStrategy,
+}
+
+/// Strategy used to sequence a pick path
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PickPathStrategy {
+    /// S-shape traversal over aisles
+    SShape,
+    /// Sorted by location code (coordinates missing)
+    Lexical,
+}
+
+/// Stop position, and whether its aisle reaches the back cross-aisle
+#[derive(Debug, Clone, Copy)]
+struct Point {
+    x: f64,
+    y: f64,
+    through: bool,
+}
+
+/// Pick path optimizer over an aisle layout
+pub struct PickPathOptimizer {
+    /// Depot position on the front cross-aisle
+    depot_x_m: f64,
+    /// Distance between the front and back cross-aisles
+    aisle_length_m: f64,
+}
+
+impl PickPathOptimizer {
+    /// Create a new optimizer with the depot at the origin
+    pub fn new() -> Self {
+        Self {
+            depot_x_m: 0.0,
+            aisle_length_m: 0.0,
+        }
+    }
+
+    /// Configure the depot position
+    pub fn with_depot(mut self, x_m: f64) -> Self {
+        self.depot_x_m = x_m;
+        self
+    }
+
+    /// Configure the aisle length (defaults to the deepest pick)
+    pub fn with_aisle_length(mut self, length_m: f64) -> Self {
+        self.aisle_length_m = length_m;
+        self
+    }
+
+    /// Sequence pick stops, falling back to location code order when any
+    /// stop is missing coordinates
+    pub fn optimize(&self, stops: &[PickStop]) -> PickPath {
+        let Some(points) = Self::points(stops) else {
+            return self.lexical(stops);
+        };
+        let aisle_length = self.aisle_length(&points);
+
+        // Group stops by aisle, using the aisle code when available
+        let mut groups: HashMap<String, Vec<usize>> = HashMap::new();
+        for (i, stop) in stops.iter().enumerate() {
+            let key = stop.aisle.clone()
+                .unwrap_or_else(|| format!("{:.0}", points[i].x / AISLE_TOLERANCE_M));
+            groups.entry(key).or_default().push(i);
+        }
+
+        let mut aisles: Vec<(f64, bool, Vec<usize>)> = groups.into_values()
+            .map(|members| {
+                let x = members.iter().map(|&i| points[i].x).sum::<f64>() / members.len() as f64;
+                let through = members.iter().all(|&i| points[i].through);
+                (x, through, members)
+            })
+            .collect();
+        aisles.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut order: Vec<usize> = Vec::with_capacity(stops.len());
+        let mut at_front = true;
+
+        for i in 0..aisles.len() {
+            let through = aisles[i].1;
+            // Crossing to the back only pays off if a through aisle comes
+            // next to walk back down
+            let next_through = aisles.get(i + 1).is_some_and(|next| next.1);
+            let members = &mut aisles[i].2;
+            members.sort_by(|&a, &b| points[a].y.total_cmp(&points[b].y));
+
+            if at_front {
+                order.extend(members.iter());
+                if through && next_through {
+                    at_front = false;
+                }
+            } else {
+                order.extend(members.iter().rev());
+                at_front = true;
+            }
+        }
+
+        let path: Vec<Point> = order.iter().map(|&i| points[i]).collect();
+
+        PickPath {
+            stop_order: order.iter().map(|&i| stops[i].id.clone()).collect(),
+            estimated_distance_m: Some(self.path_distance(&path, aisle_length)),
+            strategy: PickPathStrategy::SShape,
+        }
+    }
+
+    /// Estimate the round-trip walk for stops visited in the given order
+    pub fn route_distance(&self, stops: &[PickStop]) -> Option<f64> {
+        let points = Self::points(stops)?;
+        Some(self.path_distance(&points, self.aisle_length(&points)))
+    }
+
+    /// Stop positions, or None when any stop is missing coordinates
+    fn points(stops: &[PickStop]) -> Option<Vec<Point>> {
+        stops.iter()
+            .map(|s| {
+                let (x, y) = s.x_m.zip(s.y_m)?;
+                Some(Point { x, y, through: s.traversal == AisleTraversal::Through })
+            })
+            .collect()
+    }
+
+    /// Back cross-aisle: the configured length or the deepest through-aisle pick
+    fn aisle_length(&self, points: &[Point]) -> f64 {
+        points.iter()
+            .filter(|p| p.through)
+            .map(|p| p.y)
+            .fold(self.aisle_length_m, f64::max)
+    }
+
+    fn lexical(&self, stops: &[PickStop]) -> PickPath {
+        let mut sorted: Vec<&PickStop> = stops.iter().collect();
+        sorted.sort_by(|a, b| a.location_code.cmp(&b.location_code));
+
+        PickPath {
+            stop_order: sorted.iter().map(|s| s.id.clone()).collect(),
+            estimated_distance_m: None,
+            strategy: PickPathStrategy::Lexical,
+        }
+    }
+
+    fn path_distance(&self, points: &[Point], aisle_length: f64) -> f64 {
+        let depot = Point { x: self.depot_x_m, y: 0.0, through: false };
+        let mut total = 0.0;
+        let mut current = depot;
+
+        for &point in points {
+            total += Self::travel(current, point, aisle_length);
+            current = point;
+        }
+
+        total + Self::travel(current, depot, aisle_length)
+    }
+
+    /// Walking distance between two points, switching aisles via whichever
+    /// cross-aisle is shorter; only through aisles reach the back one
+    fn travel(from: Point, to: Point, aisle_length: f64) -> f64 {
+        if (from.x - to.x).abs() < AISLE_TOLERANCE_M {
+            (from.y - to.y).abs()
+        } else {
+            let via_front = from.y + to.y;
+            let via_back = if from.through && to.through {
+                (aisle_length - from.y) + (aisle_length - to.y)
+            } else {
+                f64::INFINITY
+            };
+            (from.x - to.x).abs() + via_front.min(via_back)
+        }
+    }
+}
+
+impl Default for PickPathOptimizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 10 aisles, 5m apart, 30m deep, three picks per aisle
+    fn ten_aisle_layout() -> Vec<PickStop> {
+        let mut stops = Vec::new();
+        for aisle in 0..10u32 {
+            let x = 2.5 + 5.0 * aisle as f64;
+            for y in [3 + (aisle * 7) % 10, 15, 27 - (aisle * 3) % 5] {
+                let code = format!("A{:02}-{:02}", aisle, y);
+                stops.push(PickStop {
+                    id: code.clone(),
+                    location_code: code,
+                    aisle: Some(format!("A{:02}", aisle)),
+                    x_m: Some(x),
+                    y_m: Some(y as f64),
+                    traversal: AisleTraversal::Through,
+                });
+            }
+        }
+        // Shuffle deterministically so input order carries no hint
+        stops.reverse();
+        stops.swap(3, 17);
+        stops
+    }
+
+    #[test]
+    fn test_s_shape_ordering() {
+        let stops = ten_aisle_layout();
+        let path = PickPathOptimizer::new().with_aisle_length(30.0).optimize(&stops);
+
+        assert_eq!(path.strategy, PickPathStrategy::SShape);
+        assert_eq!(path.stop_order.len(), 30);
+        assert_eq!(&path.stop_order[..6], &["A00-03", "A00-15", "A00-27", "A01-24", "A01-15", "A01-10"]);
+
+        // Aisles are visited left to right, alternating direction
+        for (aisle, chunk) in path.stop_order.chunks(3).enumerate() {
+            let prefix = format!("A{:02}-", aisle);
+            assert!(chunk.iter().all(|id| id.starts_with(&prefix)));
+
+            let ys: Vec<u32> = chunk.iter().map(|id| id[4..].parse().unwrap()).collect();
+            if aisle % 2 == 0 {
+                assert!(ys.windows(2).all(|w| w[0] < w[1]), "aisle {} should go up", aisle);
+            } else {
+                assert!(ys.windows(2).all(|w| w[0] > w[1]), "aisle {} should go down", aisle);
+            }
+        }
+    }
+
+    #[test]
+    fn test_dead_end_aisles_are_left_from_the_front() {
+        let mut stops = ten_aisle_layout();
+        for stop in stops.iter_mut().filter(|s| s.aisle.as_deref() == Some("A03")) {
+            stop.traversal = AisleTraversal::DeadEnd;
+        }
+        let path = PickPathOptimizer::new().with_aisle_length(30.0).optimize(&stops);
+
+        // A02 turns back to the front so A03 can be entered; the S resumes at A04
+        let upward = [true, false, true, true, true, false, true, false, true, false];
+        for (aisle, chunk) in path.stop_order.chunks(3).enumerate() {
+            let ys: Vec<u32> = chunk.iter().map(|id| id[4..].parse().unwrap()).collect();
+            let up = ys.windows(2).all(|w| w[0] < w[1]);
+            assert_eq!(up, upward[aisle], "aisle {} went {:?}", aisle, ys);
+        }
+    }
+
+    #[test]
+    fn test_dead_end_aisles_are_not_crossed_at_the_back() {
+        let stop = |id: &str, x: f64, traversal: AisleTraversal| PickStop {
+            id: id.to_string(),
+            location_code: id.to_string(),
+            aisle: None,
+            x_m: Some(x),
+            y_m: Some(25.0),
+            traversal,
+        };
+        let optimizer = PickPathOptimizer::new().with_aisle_length(30.0);
+
+        let through = [stop("a", 2.5, AisleTraversal::Through), stop("b", 7.5, AisleTraversal::Through)];
+        assert_eq!(optimizer.route_distance(&through), Some(27.5 + 15.0 + 32.5));
+
+        let dead_end = [stop("a", 2.5, AisleTraversal::DeadEnd), stop("b", 7.5, AisleTraversal::DeadEnd)];
+        assert_eq!(optimizer.route_distance(&dead_end), Some(27.5 + 55.0 + 32.5));
+    }
+
+    #[test]
+    fn test_s_shape_reduces_distance_vs_lexical() {
+        let stops = ten_aisle_layout();
+        let optimizer = PickPathOptimizer::new().with_aisle_length(30.0);
+
+        let mut lexical = stops.clone();
+        lexical.sort_by(|a, b| a.location_code.cmp(&b.location_code));
+        let lexical_distance = optimizer.route_distance(&lexical).unwrap();
+
+        let optimized = optimizer.optimize(&stops).estimated_distance_m.unwrap();
+
+        assert!(
+            optimized < lexical_distance * 0.8,
+            "S-shape {:.1}m should beat lexical {:.1}m by at least 20%",
+            optimized,
+            lexical_distance
+        );
+    }
+
+    #[test]
+    fn test_missing_coordinates_falls_back_to_lexical() {
+        let mut stops = ten_aisle_layout();
+        stops[5].x_m = None;
+
+        let path = PickPathOptimizer::new().optimize(&stops);
+
+        assert_eq!(path.strategy, PickPathStrategy::Lexical);
+        assert!(path.estimated_distance_m.is_none());
+        assert_eq!(path.stop_order.first().map(String::as_str), Some("A00-03"));
+        assert_eq!(path.stop_order.last().map(String::as_str), Some("A09-25"));
+    }
+
+    #[test]
+    fn test_empty_pick_list() {
+        let path = PickPathOptimizer::new().optimize(&[]);
+
+        assert!(path.stop_order.is_empty());
+        assert_eq!(path.estimated_distance_m, Some(0.0));
+    }
+}
@@ -0,0 +1,339 @@
+//! Item Pricing
+//!
+//! Price lists with effective dates. A list without a customer is a default
+//! list; a customer's own list always takes precedence over the default.
+
+use std::sync::Arc;
+use chrono::{DateTime, Utc};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+use wms_core::db::Database;
+use wms_core::error::{WmsError, Result};
+use wms_core::types::{new_id, parse_timestamp};
+
+/// Price list (default when `customer_id` is None)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PriceList {
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub customer_id: Option<String>,
+    #[serde(default = "default_currency")]
+    pub currency_code: String,
+    pub effective_from: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub effective_to: Option<DateTime<Utc>>,
+    #[serde(default = "default_true")]
+    pub is_active: bool,
+    #[serde(default)]
+    pub items: Vec<PriceListItem>,
+}
+
+fn default_currency() -> String {
+    "USD".to_string()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Price for a single item on a price list
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PriceListItem {
+    pub item_id: String,
+    pub unit_price: f64,
+}
+
+/// Resolved price for an item
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ItemPrice {
+    pub item_id: String,
+    pub price_list_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub customer_id: Option<String>,
+    pub unit_price: f64,
+    pub currency_code: String,
+    /// True when the price came from a customer-specific list
+    pub is_customer_price: bool,
+}
+
+/// Candidate price row considered during resolution
+#[derive(Debug, Clone)]
+pub struct PriceCandidate {
+    pub price_list_id: String,
+    pub customer_id: Option<String>,
+    pub currency_code: String,
+    pub effective_from: DateTime<Utc>,
+    pub effective_to: Option<DateTime<Utc>>,
+    pub unit_price: f64,
+}
+
+impl PriceCandidate {
+    /// Check if the candidate is in effect on the given date
+    pub fn is_effective(&self, date: DateTime<Utc>) -> bool {
+        self.effective_from <= date && self.effective_to.is_none_or(|to| date < to)
+    }
+}
+
+/// Pick the price in effect on `date`.
+///
+/// Customer lists win over default lists; within the same tier the most
+/// recently started list wins.
+pub fn select_price<'a>(
+    candidates: &'a [PriceCandidate],
+    customer_id: Option<&str>,
+    date: DateTime<Utc>,
+) -> Option<&'a PriceCandidate> {
+    let latest = |customer: Option<&str>| {
+        candidates.iter()
+            .filter(|c| c.customer_id.as_deref() == customer && c.is_effective(date))
+            .max_by_key(|c| c.effective_from)
+    };
+
+    customer_id
+        .and_then(|id| latest(Some(id)))
+        .or_else(|| latest(None))
+}
+
+/// Price list storage and price resolution
+pub struct PriceBook {
+    db: Arc<Database>,
+}
+
+impl PriceBook {
+    /// Create a new price book
+    pub fn new(db: Arc<Database>) -> Self {
+        Self { db }
+    }
+
+    /// Resolve the unit price for an item, falling back to the default list
+    pub async fn get_price(
+        &self,
+        item_id: &str,
+        customer_id: Option<&str>,
+        date: DateTime<Utc>,
+    ) -> Result<Option<ItemPrice>> {
+        let candidates = self.db.query_map(
+            "SELECT pl.id, pl.customer_id, pl.currency_code, pl.effective_from,
+                    pl.effective_to, pli.unit_price
+             FROM price_list_items pli
+             JOIN price_lists pl ON pli.price_list_id = pl.id
+             WHERE pli.item_id = ?
+               AND pl.is_active = 1
+               AND (pl.customer_id IS NULL OR pl.customer_id = ?)",
+            params![item_id, customer_id],
+            |row| {
+                let id: String = row.get("id")?;
+                let from: String = row.get("effective_from")?;
+                let to: Option<String> = row.get("effective_to")?;
+                // An unreadable start date would otherwise sort as 1970 and
+                // quietly win or lose against the other lists
+                let Some(effective_from) = parse_timestamp(&from) else {
+                    warn!("Price list {} has an unreadable effective_from {:?}; skipped", id, from);
+                    return Ok(None);
+                };
+                Ok(Some(PriceCandidate {
+                    price_list_id: id,
+                    customer_id: row.get("customer_id")?,
+                    currency_code: row.get("currency_code")?,
+                    effective_from,
+                    effective_to: to.and_then(|t| parse_timestamp(&t)),
+                    unit_price: row.get("unit_price")?,
+                }))
+            },
+        )?;
+        let candidates: Vec<PriceCandidate> = candidates.into_iter().flatten().collect();
+
+        Ok(select_price(&candidates, customer_id, date).map(|c| ItemPrice {
+            item_id: item_id.to_string(),
+            price_list_id: c.price_list_id.clone(),
+            customer_id: c.customer_id.clone(),
+            unit_price: c.unit_price,
+            currency_code: c.currency_code.clone(),
+            is_customer_price: c.customer_id.is_some(),
+        }))
+    }
+
+    /// Create or replace a price list and its entries
+    pub async fn upsert_price_list(&self, mut list: PriceList) -> Result<PriceList> {
+        if list.name.trim().is_empty() {
+            return Err(WmsError::validation("Price list name is required"));
+        }
+        if let Some(to) = list.effective_to
+            && to <= list.effective_from
+        {
+            return Err(WmsError::validation("effective_to must be after effective_from"));
+        }
+        if let Some(bad) = list.items.iter().find(|i| i.unit_price < 0.0 || !i.unit_price.is_finite()) {
+            return Err(WmsError::validation(format!("Invalid unit price for item {}", bad.item_id)));
+        }
+
+        if list.id.is_empty() {
+            list.id = new_id();
+        }
+
+        self.db.transaction(|conn| {
+            conn.execute(
+                "INSERT INTO price_lists (
+                    id, name, customer_id, currency_code, effective_from,
+                    effective_to, is_active, created_at
+                ) VALUES (?, ?, ?, ?, ?, ?, ?, datetime('now'))
+                 ON CONFLICT(id) DO UPDATE SET
+                    name = excluded.name,
+                    customer_id = excluded.customer_id,
+                    currency_code = excluded.currency_code,
+                    effective_from = excluded.effective_from,
+                    effective_to = excluded.effective_to,
+                    is_active = excluded.is_active,
+                    updated_at = datetime('now')",
+                params![
+                    &list.id,
+                    &list.name,
+                    &list.customer_id,
+                    &list.currency_code,
+                    list.effective_from.to_rfc3339(),
+                    list.effective_to.map(|t| t.to_rfc3339()),
+                    list.is_active,
+                ],
+            )?;
+
+            conn.execute(
+                "DELETE FROM price_list_items WHERE price_list_id = ?",
+                params![&list.id],
+            )?;
+
+            for item in &list.items {
+                conn.execute(
+                    "INSERT INTO price_list_items (id, price_list_id, item_id, unit_price)
+                     VALUES (?, ?, ?, ?)",
+                    params![new_id(), &list.id, &item.item_id, item.unit_price],
+                )?;
+            }
+
+            Ok(())
+        })?;
+
+        info!("Saved price list {} ({} items)", list.name, list.items.len());
+        Ok(list)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use wms_core::test_support::{test_db, ItemBuilder};
+    use crate::models::InventoryItem;
+
+    fn date(y: i32, m: u32, d: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, m, d, 0, 0, 0).unwrap()
+    }
+
+    fn candidate(
+        id: &str,
+        customer: Option<&str>,
+        from: DateTime<Utc>,
+        to: Option<DateTime<Utc>>,
+        price: f64,
+    ) -> PriceCandidate {
+        PriceCandidate {
+            price_list_id: id.to_string(),
+            customer_id: customer.map(String::from),
+            currency_code: "USD".to_string(),
+            effective_from: from,
+            effective_to: to,
+            unit_price: price,
+        }
+    }
+
+    #[test]
+    fn test_effective_date_selection() {
+        let candidates = vec![
+            candidate("2024", None, date(2024, 1, 1), Some(date(2025, 1, 1)), 10.0),
+            candidate("2025", None, date(2025, 1, 1), None, 12.0),
+        ];
+
+        let price = select_price(&candidates, None, date(2024, 6, 1)).unwrap();
+        assert_eq!(price.price_list_id, "2024");
+
+        // effective_to is exclusive, so the new list takes over on its start date
+        let price = select_price(&candidates, None, date(2025, 1, 1)).unwrap();
+        assert_eq!(price.price_list_id, "2025");
+
+        assert!(select_price(&candidates, None, date(2023, 12, 31)).is_none());
+    }
+
+    #[test]
+    fn test_latest_list_wins_on_overlap() {
+        let candidates = vec![
+            candidate("base", None, date(2024, 1, 1), None, 10.0),
+            candidate("promo", None, date(2024, 6, 1), Some(date(2024, 7, 1)), 8.0),
+        ];
+
+        assert_eq!(select_price(&candidates, None, date(2024, 6, 15)).unwrap().unit_price, 8.0);
+        assert_eq!(select_price(&candidates, None, date(2024, 7, 15)).unwrap().unit_price, 10.0);
+    }
+
+    #[test]
+    fn test_customer_override_precedence() {
+        let candidates = vec![
+            candidate("default", None, date(2024, 6, 1), None, 10.0),
+            candidate("acme", Some("cust-acme"), date(2024, 1, 1), None, 9.0),
+            candidate("other", Some("cust-other"), date(2024, 1, 1), None, 7.0),
+        ];
+
+        // Customer list wins even though the default list started later
+        let price = select_price(&candidates, Some("cust-acme"), date(2024, 8, 1)).unwrap();
+        assert_eq!(price.price_list_id, "acme");
+
+        // Customers without their own list fall back to the default
+        let price = select_price(&candidates, Some("cust-new"), date(2024, 8, 1)).unwrap();
+        assert_eq!(price.price_list_id, "default");
+
+        let price = select_price(&candidates, None, date(2024, 8, 1)).unwrap();
+        assert_eq!(price.price_list_id, "default");
+    }
+
+    #[tokio::test]
+    async fn test_unreadable_effective_from_is_skipped() {
+        let db = test_db();
+        let _: InventoryItem = ItemBuilder::new("WID-1").insert(&db);
+        let book = PriceBook::new(db.clone());
+
+        let list = PriceList {
+            id: String::new(),
+            name: "Base".to_string(),
+            customer_id: None,
+            currency_code: "USD".to_string(),
+            effective_from: date(2024, 1, 1),
+            effective_to: None,
+            is_active: true,
+            items: vec![PriceListItem { item_id: "wid-1".to_string(), unit_price: 10.0 }],
+        };
+        book.upsert_price_list(list.clone()).await.unwrap();
+        let broken = PriceList {
+            name: "Broken".to_string(),
+            effective_from: date(2024, 5, 1),
+            items: vec![PriceListItem { item_id: "wid-1".to_string(), unit_price: 1.0 }],
+            ..list
+        };
+        let broken = book.upsert_price_list(broken).await.unwrap();
+        db.execute("UPDATE price_lists SET effective_from = 'soon' WHERE id = ?", params![&broken.id]).unwrap();
+
+        let price = book.get_price("wid-1", None, date(2024, 6, 1)).await.unwrap().unwrap();
+        assert_eq!(price.unit_price, 10.0);
+    }
+
+    #[test]
+    fn test_expired_customer_list_falls_back_to_default() {
+        let candidates = vec![
+            candidate("default", None, date(2024, 1, 1), None, 10.0),
+            candidate("acme", Some("cust-acme"), date(2024, 1, 1), Some(date(2024, 3, 1)), 9.0),
+        ];
+
+        let price = select_price(&candidates, Some("cust-acme"), date(2024, 4, 1)).unwrap();
+        assert_eq!(price.price_list_id, "default");
+    }
+}
@@ -0,0 +1,243 @@
+//! Picker Productivity
+//!
+//! Per-picker stats for a period, built from pick transactions: lines and
+//! units picked, picks per hour and accuracy, ranked into a leaderboard.
+//! Hours come from clocked time on the same day. A day with picks but no
+//! clocked time (the picker forgot to clock in) falls back to the span
+//! from first to last pick, and the stats are flagged as estimated.
+//! Accuracy counts the mispicks recorded when a pick is corrected.
+
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use chrono::{Duration, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+/// Days looked back over when counting a picker's streak
+pub const STREAK_LOOKBACK_DAYS: i64 = 90;
+
+/// One picker's activity on one day
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PickerDay {
+    pub user_id: String,
+    pub date: NaiveDate,
+    pub lines_picked: u32,
+    pub units_picked: f64,
+    pub mispicks: u32,
+    /// Clocked hours, if the picker clocked in that day
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clocked_hours: Option<f64>,
+    /// Hours from the first pick of the day to the last
+    pub pick_span_hours: f64,
+}
+
+impl PickerDay {
+    /// Whether the day's hours come from the pick span, not the clock
+    pub fn hours_estimated(&self) -> bool {
+        !self.clocked_hours.is_some_and(|h| h > 0.0)
+    }
+
+    /// Hours worked: clocked time, or the pick span without it
+    pub fn hours(&self) -> f64 {
+        match self.clocked_hours {
+            Some(hours) if hours > 0.0 => hours,
+            _ => self.pick_span_hours,
+        }
+    }
+}
+
+/// One picker's totals over a period
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PickerStats {
+    /// Position on the leaderboard, from 1
+    pub rank: u32,
+    pub user_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub lines_picked: u32,
+    pub units_picked: f64,
+    pub hours: f64,
+    /// None when no time could be measured (a single unclocked pick)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub picks_per_hour: Option<f64>,
+    pub mispicks: u32,
+    /// Share of lines picked without a mispick
+    pub accuracy_percent: f64,
+    pub days_worked: u32,
+    /// Some hours were estimated from pick timestamps
+    pub hours_estimated: bool,
+}
+
+/// Ranked picker stats for a period
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PickerLeaderboard {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    /// Best first
+    pub pickers: Vec<PickerStats>,
+}
+
+/// A picker's own stats for a period
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MyPickerStats {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    /// None when the picker picked nothing in the period
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stats: Option<PickerStats>,
+    /// Pickers on the period's leaderboard
+    pub pickers: u32,
+    /// The picker's days in the period, oldest first
+    pub days: Vec<PickerDay>,
+    /// Most recent picking days in a row without a mispick
+    pub clean_streak_days: u32,
+    /// Longest run of such days in the lookback
+    pub best_clean_streak_days: u32,
+}
+
+/// Total up each picker's days and rank them: picks per hour, then
+/// accuracy, then lines picked. Pickers without measurable hours rank last.
+pub fn build_leaderboard(
+    start: NaiveDate,
+    end: NaiveDate,
+    days: &[PickerDay],
+    names: &BTreeMap<String, String>,
+) -> PickerLeaderboard {
+    let mut by_user: BTreeMap<&str, Vec<&PickerDay>> = BTreeMap::new();
+    for day in days {
+        by_user.entry(day.user_id.as_str()).or_default().push(day);
+    }
+
+    let mut pickers: Vec<PickerStats> = by_user.into_iter()
+        .map(|(user_id, days)| {
+            let lines_picked: u32 = days.iter().map(|d| d.lines_picked).sum();
+            let mispicks: u32 = days.iter().map(|d| d.mispicks).sum();
+            let hours: f64 = days.iter().map(|d| d.hours()).sum();
+            PickerStats {
+                rank: 0,
+                user_id: user_id.to_string(),
+                name: names.get(user_id).cloned(),
+                lines_picked,
+                units_picked: days.iter().map(|d| d.units_picked).sum(),
+                hours,
+                picks_per_hour: (hours > 0.0).then(|| lines_picked as f64 / hours),
+                mispicks,
+                accuracy_percent: accuracy_percent(lines_picked, mispicks),
+                days_worked: days.len() as u32,
+                hours_estimated: days.iter().any(|d| d.hours_estimated()),
+            }
+        })
+        .collect();
+
+    pickers.sort_by(|a, b| {
+        let rate = match (a.picks_per_hour, b.picks_per_hour) {
+            (Some(a), Some(b)) => b.partial_cmp(&a).unwrap_or(Ordering::Equal),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        };
+        rate.then(b.accuracy_percent.partial_cmp(&a.accuracy_percent).unwrap_or(Ordering::Equal))
+            .then(b.lines_picked.cmp(&a.lines_picked))
+            .then(a.user_id.cmp(&b.user_id))
+    });
+    for (i, picker) in pickers.iter_mut().enumerate() {
+        picker.rank = i as u32 + 1;
+    }
+
+    PickerLeaderboard { start, end, pickers }
+}
+
+fn accuracy_percent(lines: u32, mispicks: u32) -> f64 {
+    if lines == 0 {
+        return 100.0;
+    }
+    (lines.saturating_sub(mispicks)) as f64 * 100.0 / lines as f64
+}
+
+/// Current and longest runs of picking days without a mispick. Days off
+/// don't break a streak; only a day with a mispick does. `days` must be
+/// one picker's, oldest first.
+pub fn clean_streaks(days: &[PickerDay]) -> (u32, u32) {
+    let mut current = 0;
+    let mut best = 0;
+    for day in days.iter().filter(|d| d.lines_picked > 0) {
+        if day.mispicks == 0 {
+            current += 1;
+            best = best.max(current);
+        } else {
+            current = 0;
+        }
+    }
+    (current, best)
+}
+
+/// First day of the streak lookback ending on `end`
+pub fn streak_start(end: NaiveDate) -> NaiveDate {
+    end - Duration::days(STREAK_LOOKBACK_DAYS - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 1, day).unwrap()
+    }
+
+    fn day(user_id: &str, d: u32, lines: u32, mispicks: u32, clocked: Option<f64>, span: f64) -> PickerDay {
+        PickerDay {
+            user_id: user_id.to_string(),
+            date: date(d),
+            lines_picked: lines,
+            units_picked: lines as f64 * 2.0,
+            mispicks,
+            clocked_hours: clocked,
+            pick_span_hours: span,
+        }
+    }
+
+    #[test]
+    fn test_leaderboard_ranks_by_rate_and_falls_back_to_pick_span() {
+        let days = vec![
+            day("ann", 13, 80, 0, Some(8.0), 7.5),
+            day("ann", 14, 70, 2, Some(7.0), 6.0),
+            // Forgot to clock in: 60 picks over a 4 hour span
+            day("bob", 14, 60, 0, None, 4.0),
+            // A single unclocked pick has no measurable time
+            day("cat", 14, 1, 0, None, 0.0),
+        ];
+        let names = BTreeMap::from([("ann".to_string(), "Ann".to_string())]);
+        let board = build_leaderboard(date(13), date(14), &days, &names);
+
+        let order: Vec<&str> = board.pickers.iter().map(|p| p.user_id.as_str()).collect();
+        assert_eq!(order, ["bob", "ann", "cat"]);
+
+        let bob = &board.pickers[0];
+        assert_eq!(bob.rank, 1);
+        assert_eq!(bob.picks_per_hour, Some(15.0));
+        assert!(bob.hours_estimated);
+
+        let ann = &board.pickers[1];
+        assert_eq!(ann.name.as_deref(), Some("Ann"));
+        assert_eq!((ann.lines_picked, ann.hours, ann.days_worked), (150, 15.0, 2));
+        assert_eq!(ann.picks_per_hour, Some(10.0));
+        assert!((ann.accuracy_percent - 148.0 / 1.5).abs() < 1e-9);
+        assert!(!ann.hours_estimated);
+
+        assert_eq!(board.pickers[2].picks_per_hour, None);
+        assert_eq!(board.pickers[2].rank, 3);
+    }
+
+    #[test]
+    fn test_clean_streaks() {
+        let days = vec![
+            day("ann", 6, 10, 0, None, 1.0),
+            day("ann", 7, 10, 0, None, 1.0),
+            day("ann", 8, 10, 0, None, 1.0),
+            day("ann", 9, 10, 1, None, 1.0),
+            day("ann", 13, 10, 0, None, 1.0),
+            day("ann", 14, 10, 0, None, 1.0),
+        ];
+        assert_eq!(clean_streaks(&days), (2, 3));
+        assert_eq!(clean_streaks(&[]), (0, 0));
+    }
+}
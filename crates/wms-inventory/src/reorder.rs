@@ -0,0 +1,135 @@
+//! Reorder Report
+//!
+//! Items at or below their reorder point with what to order. Stock still
+//! expected on open receipts counts towards the item's position, so an
+//! item already covered by an inbound order is flagged rather than
+//! ordered twice. Days of cover divide on-hand stock by recent average
+//! daily demand; items without demand have no figure.
+
+use chrono::{DateTime, Utc};
+use csv::Writer;
+use serde::{Deserialize, Serialize};
+use wms_core::error::{WmsError, Result};
+
+/// Days of picks averaged for days of cover
+pub const REORDER_DEMAND_DAYS: u32 = 30;
+
+/// Where a suggested order quantity came from
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReorderQuantitySource {
+    /// The item's own reorder quantity
+    Item,
+    /// The demand forecast's suggested reorder quantity
+    Forecast,
+    /// Neither was available
+    None,
+}
+
+/// One item to reorder
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReorderLine {
+    pub item_id: String,
+    pub sku: String,
+    pub name: String,
+    pub reorder_point: f64,
+    pub on_hand: f64,
+    /// Expected but not yet received on open receipts
+    pub on_order: f64,
+    /// Quantity to order; zero when on-order stock already covers the item
+    pub suggested_quantity: f64,
+    pub quantity_source: ReorderQuantitySource,
+    pub average_daily_demand: f64,
+    /// On-hand stock over average daily demand; None without demand
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub days_of_cover: Option<f64>,
+    /// On-hand plus on-order stock is back above the reorder point
+    pub covered_by_on_order: bool,
+}
+
+/// Items at or below their reorder point, most short first
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReorderReport {
+    pub lines: Vec<ReorderLine>,
+    /// Days of picks behind the average daily demand
+    pub demand_days: u32,
+    pub generated_at: DateTime<Utc>,
+}
+
+/// Exported reorder report
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReorderReportExport {
+    /// Base64 encoded file data
+    pub data: String,
+    /// MIME content type
+    pub content_type: String,
+    /// Suggested filename
+    pub filename: String,
+}
+
+/// Render the report as CSV, one row per item
+pub fn render_reorder_csv(report: &ReorderReport) -> Result<Vec<u8>> {
+    let csv_error = |e: csv::Error| WmsError::Export(format!("CSV write error: {}", e));
+    let mut writer = Writer::from_writer(Vec::new());
+
+    writer.write_record([
+        "SKU",
+        "Name",
+        "Reorder Point",
+        "On Hand",
+        "On Order",
+        "Suggested Quantity",
+        "Quantity Source",
+        "Avg Daily Demand",
+        "Days of Cover",
+        "Covered by On Order",
+    ]).map_err(csv_error)?;
+    for line in &report.lines {
+        writer.write_record([
+            line.sku.clone(),
+            line.name.clone(),
+            line.reorder_point.to_string(),
+            line.on_hand.to_string(),
+            line.on_order.to_string(),
+            format!("{:.2}", line.suggested_quantity),
+            format!("{:?}", line.quantity_source).to_lowercase(),
+            format!("{:.2}", line.average_daily_demand),
+            line.days_of_cover.map(|d| format!("{:.1}", d)).unwrap_or_else(|| "no demand".to_string()),
+            if line.covered_by_on_order { "yes" } else { "no" }.to_string(),
+        ]).map_err(csv_error)?;
+    }
+
+    writer.into_inner()
+        .map_err(|e| WmsError::Export(format!("CSV flush error: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_marks_items_without_demand() {
+        let report = ReorderReport {
+            lines: vec![ReorderLine {
+                item_id: "bolt".to_string(),
+                sku: "BLT-1".to_string(),
+                name: "Bolt".to_string(),
+                reorder_point: 20.0,
+                on_hand: 5.0,
+                on_order: 0.0,
+                suggested_quantity: 100.0,
+                quantity_source: ReorderQuantitySource::Item,
+                average_daily_demand: 0.0,
+                days_of_cover: None,
+                covered_by_on_order: false,
+            }],
+            demand_days: REORDER_DEMAND_DAYS,
+            generated_at: Utc::now(),
+        };
+
+        let csv = String::from_utf8(render_reorder_csv(&report).unwrap()).unwrap();
+        let rows: Vec<&str> = csv.lines().collect();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[1], "BLT-1,Bolt,20,5,0,100.00,item,0.00,no demand,no");
+    }
+}
@@ -0,0 +1,178 @@
+//! Pick-Face Replenishment
+//!
+//! A replenishment rule keeps an item's pick face between a minimum and a
+//! maximum. When the face drops below its minimum, move tasks are created
+//! from bulk storage to bring it back up to the maximum. Bulk stock is
+//! taken earliest expiry first and held lots are skipped. Tasks stay open
+//! until a worker confirms the quantity actually moved.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use wms_core::types::{required_timestamp, stored_status, stored_timestamp};
+
+/// Min/max levels for an item at a pick-face location
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplenishmentRule {
+    #[serde(default)]
+    pub id: String,
+    pub item_id: String,
+    /// Pick-face location (PICKING zone)
+    pub location_id: String,
+    /// Replenish when the face holds less than this
+    pub min_quantity: f64,
+    /// Fill the face up to this
+    pub max_quantity: f64,
+    #[serde(default = "default_true")]
+    pub is_active: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// How soon a task should be worked
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReplenishmentPriority {
+    Normal,
+    /// A pick just took the face below its minimum
+    Urgent,
+}
+
+impl ReplenishmentPriority {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Normal => "normal",
+            Self::Urgent => "urgent",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "normal" => Some(Self::Normal),
+            "urgent" => Some(Self::Urgent),
+            _ => None,
+        }
+    }
+}
+
+/// Task lifecycle
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReplenishmentTaskStatus {
+    Open,
+    Completed,
+    Cancelled,
+}
+
+impl ReplenishmentTaskStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Open => "open",
+            Self::Completed => "completed",
+            Self::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "open" => Some(Self::Open),
+            "completed" => Some(Self::Completed),
+            "cancelled" => Some(Self::Cancelled),
+            _ => None,
+        }
+    }
+}
+
+/// A move from bulk storage to a pick face
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplenishmentTask {
+    pub id: String,
+    pub rule_id: String,
+    pub item_id: String,
+    pub from_location_id: String,
+    pub to_location_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lot_number: Option<String>,
+    /// Quantity to move
+    pub quantity: f64,
+    /// Quantity actually moved, once confirmed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quantity_moved: Option<f64>,
+    pub priority: ReplenishmentPriority,
+    pub status: ReplenishmentTaskStatus,
+    pub created_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub completed_by: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+/// Quantity to request for a face holding `on_face` with `in_transit`
+/// already covered by open tasks; zero while the face is at or above its
+/// minimum
+pub fn replenishment_need(rule: &ReplenishmentRule, on_face: f64, in_transit: f64) -> f64 {
+    let expected = on_face + in_transit;
+    if on_face >= rule.min_quantity || expected >= rule.max_quantity {
+        return 0.0;
+    }
+    rule.max_quantity - expected
+}
+
+/// Convert database row to ReplenishmentRule
+pub(crate) fn row_to_rule(row: &rusqlite::Row) -> rusqlite::Result<ReplenishmentRule> {
+    Ok(ReplenishmentRule {
+        id: row.get("id")?,
+        item_id: row.get("item_id")?,
+        location_id: row.get("location_id")?,
+        min_quantity: row.get("min_quantity")?,
+        max_quantity: row.get("max_quantity")?,
+        is_active: row.get("is_active")?,
+    })
+}
+
+/// Convert database row to ReplenishmentTask
+pub(crate) fn row_to_task(row: &rusqlite::Row) -> rusqlite::Result<ReplenishmentTask> {
+    Ok(ReplenishmentTask {
+        id: row.get("id")?,
+        rule_id: row.get("rule_id")?,
+        item_id: row.get("item_id")?,
+        from_location_id: row.get("from_location_id")?,
+        to_location_id: row.get("to_location_id")?,
+        lot_number: row.get::<_, Option<String>>("lot_number")?.filter(|l| !l.is_empty()),
+        quantity: row.get("quantity")?,
+        quantity_moved: row.get("quantity_moved")?,
+        priority: stored_status(row, "priority", ReplenishmentPriority::parse)?,
+        status: stored_status(row, "status", ReplenishmentTaskStatus::parse)?,
+        created_at: required_timestamp(row, "created_at")?,
+        completed_by: row.get("completed_by")?,
+        completed_at: stored_timestamp(row, "completed_at")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(min: f64, max: f64) -> ReplenishmentRule {
+        ReplenishmentRule {
+            id: "r1".to_string(),
+            item_id: "widget".to_string(),
+            location_id: "face".to_string(),
+            min_quantity: min,
+            max_quantity: max,
+            is_active: true,
+        }
+    }
+
+    #[test]
+    fn test_need_fills_to_max_once_below_min() {
+        let rule = rule(10.0, 50.0);
+
+        assert_eq!(replenishment_need(&rule, 10.0, 0.0), 0.0);
+        assert_eq!(replenishment_need(&rule, 4.0, 0.0), 46.0);
+        // Open tasks already on their way count toward the max
+        assert_eq!(replenishment_need(&rule, 4.0, 30.0), 16.0);
+        assert_eq!(replenishment_need(&rule, 4.0, 46.0), 0.0);
+    }
+}
@@ -2,59 +2,360 @@
 //! 
 //! Core business logic for inventory management operations.
 
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::sync::Arc;
-use chrono::Utc;
-use rusqlite::params;
-use tracing::{info, debug};
+use base64::Engine;
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, Utc};
+use rusqlite::{params, OptionalExtension};
+use tracing::{info, debug, warn};
+use wms_core::attachments::{Attachment, AttachmentKind, AttachmentService, NewAttachment};
+use wms_core::changes::{outbox_recorder, ChangeKind, SharedRecorder};
+use wms_core::clock::{SharedClock, system_clock};
+use wms_core::context::RequestContext;
+use wms_core::custom_fields::{
+    custom_fields_json, parse_custom_fields, search_condition, CustomFieldEntity, CustomFieldService,
+};
 use wms_core::db::Database;
+use wms_core::operations::CancellationToken;
 use wms_core::error::{WmsError, Result};
-use wms_core::types::new_id;
+use wms_core::events::{SharedEmitter, WmsEvent, noop_emitter};
+use wms_core::shutdown::TaskHandle;
+use wms_core::types::{new_id, DateRange, FileExport};
 use crate::models::*;
-use crate::forecast::{ForecastEngine, ForecastResult};
+use crate::export::{
+    render_items_csv, render_stock_list, InventoryExportFilter, InventoryExportFormat, StockStatus,
+};
+use crate::import::{parse_items_csv, ImportOptions, ImportReport, RowError};
+use crate::forecast::{ForecastEngine, ForecastModel, ForecastResult};
+use crate::accuracy::{
+    score_predictions, ForecastAccuracy, ForecastEvaluation, ModelAccuracy, ACCURACY_WINDOW_DAYS, ALL_ITEMS,
+};
+use crate::pricing::{ItemPrice, PriceBook, PriceList};
+use crate::atp::{
+    AvailabilityProjection, PromiseCheck, ScheduledMovement,
+    earliest_available_date, project_availability,
+};
+use crate::workload::{
+    DailyProductivity, WorkloadPlan, WorkloadPlanExport, PRODUCTIVITY_WEEKS,
+    build_workload_plan, render_workload_csv, shift_hours,
+};
+use crate::heatmap::{
+    build_pick_heatmap, far_threshold_m, render_pick_heatmap_csv, row_to_slot_location,
+    suggest_slotting, LocationPicks, PickHeatmap, PickHeatmapExport, StoredItem,
+};
+use crate::productivity::{
+    build_leaderboard, clean_streaks, streak_start, MyPickerStats, PickerDay, PickerLeaderboard,
+};
+use crate::holds::{
+    allocate_in_order, HoldDisposition, QualityHold, StockAllocation, StockAllocator, NOT_HELD,
+    row_to_hold,
+};
+use crate::replenishment::{
+    replenishment_need, row_to_rule, row_to_task, ReplenishmentPriority, ReplenishmentRule,
+    ReplenishmentTask, ReplenishmentTaskStatus,
+};
+use crate::abc::{classify_items, summarize_classes, AbcClassificationReport, AbcItemClass, ItemUsage};
+use crate::lots::{parse_expiry, ExpiringStock, LotBalance};
+use crate::reorder::{
+    render_reorder_csv, ReorderLine, ReorderQuantitySource, ReorderReport, ReorderReportExport,
+    REORDER_DEMAND_DAYS,
+};
+use crate::counting::{row_to_count_line, row_to_sheet, CountLine, CountSheet, CountStatus, CountVarianceTolerances};
+use crate::variants::{
+    variant_combinations, ItemSearchQuery, LowStockGroup, VariantAttributes, VariantAxis,
+    VariantMatrixResult, VariantSkuRules, VariantStockSummary,
+};
+
+/// Days past the need-by date searched when looking for the earliest promise date
+const PROMISE_LOOKAHEAD_DAYS: u32 = 60;
+
+/// Days of pick history fed to the workload forecast
+const WORKLOAD_HISTORY_DAYS: i64 = 90;
+
+/// Items read per page when exporting the item master
+const EXPORT_PAGE_SIZE: u32 = 500;
+
+/// Rows written per transaction when importing the item master
+const IMPORT_BATCH_SIZE: usize = 500;
+
+/// Attachment owner type for item images
+pub const ITEM_ATTACHMENT_OWNER: &str = "inventory_item";
+
+/// Stock of item `i` plus the stock of its variants
+const ROLLED_UP_QTY: &str = "COALESCE((SELECT SUM(s.quantity) FROM inventory_stock s
+        JOIN inventory_items v ON s.item_id = v.id
+        WHERE v.id = i.id OR v.parent_item_id = i.id), 0) AS total_qty";
+
+/// Stock rows (aliased `s`) in warehouse `?N`, or all of them when it is
+/// NULL
+fn in_warehouse(param: usize) -> String {
+    format!(
+        "(?{0} IS NULL OR s.location_id IN (SELECT id FROM locations WHERE warehouse_id = ?{0}))",
+        param
+    )
+}
+
+/// Units stocked at location `l`
+const STOCKED_UNITS: &str = "COALESCE((SELECT SUM(s.quantity) FROM inventory_stock s
+        WHERE s.location_id = l.id), 0) AS stocked_units";
 
 /// Inventory management service
 pub struct InventoryService {
     db: Arc<Database>,
     forecast_engine: ForecastEngine,
+    price_book: PriceBook,
+    allocator: StockAllocator,
+    attachments: AttachmentService,
+    custom_fields: CustomFieldService,
+    events: SharedEmitter,
+    changes: SharedRecorder,
+    clock: SharedClock,
 }
 
 impl InventoryService {
     /// Create a new inventory service
     pub fn new(db: Arc<Database>) -> Self {
         Self {
+            price_book: PriceBook::new(db.clone()),
+            allocator: StockAllocator::new(db.clone()),
+            attachments: AttachmentService::new(db.clone()),
+            custom_fields: CustomFieldService::new(db.clone()),
             db,
             forecast_engine: ForecastEngine::new(),
+            events: noop_emitter(),
+            changes: outbox_recorder(),
+            clock: system_clock(),
         }
     }
     
+    /// Publish domain events through the given emitter
+    pub fn with_event_emitter(mut self, events: SharedEmitter) -> Self {
+        self.events = events;
+        self
+    }
+    
+    /// Record changes for sync through the given recorder
+    pub fn with_change_recorder(mut self, changes: SharedRecorder) -> Self {
+        self.changes = changes;
+        self
+    }
+    
+    /// Read the current time from the given clock
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+    
+    /// Store item image content in files under `dir` instead of the database
+    pub fn with_attachment_dir(mut self, dir: impl Into<std::path::PathBuf>) -> Self {
+        self.attachments = self.attachments.with_storage_dir(dir);
+        self
+    }
+    
     /// Get all inventory items with pagination
-    pub async fn get_all_items(&self, page: u32, page_size: u32) -> Result<Vec<InventoryItem>> {
+    pub async fn get_all_items(
+        &self,
+        page: u32,
+        page_size: u32,
+        warehouse_id: Option<&str>,
+    ) -> Result<Vec<InventoryItem>> {
         let offset = (page.saturating_sub(1)) * page_size;
         
         let items = self.db.query_map(
-            "SELECT i.*, COALESCE(SUM(s.quantity), 0) as total_qty
-             FROM inventory_items i
-             LEFT JOIN inventory_stock s ON i.id = s.item_id
-             WHERE i.is_active = 1
-             GROUP BY i.id
-             ORDER BY i.sku
-             LIMIT ? OFFSET ?",
-            params![page_size, offset],
+            &format!(
+                "SELECT i.*, COALESCE(SUM(s.quantity), 0) as total_qty
+                 FROM inventory_items i
+                 LEFT JOIN inventory_stock s ON i.id = s.item_id AND {}
+                 WHERE i.is_active = 1
+                 GROUP BY i.id
+                 ORDER BY i.sku
+                 LIMIT ?1 OFFSET ?2",
+                in_warehouse(3)
+            ),
+            params![page_size, offset, warehouse_id],
             |row| Self::row_to_item(row),
         )?;
         
         Ok(items)
     }
     
+    /// Export every active item with its on-hand quantity as CSV
+    pub async fn export_items_csv(&self) -> Result<Vec<u8>> {
+        let mut items = Vec::new();
+        for page in 1.. {
+            let batch = self.get_all_items(page, EXPORT_PAGE_SIZE, None).await?;
+            let done = batch.len() < EXPORT_PAGE_SIZE as usize;
+            items.extend(batch);
+            if done {
+                break;
+            }
+        }
+        
+        let definitions = self.custom_fields.list_definitions(Some(CustomFieldEntity::InventoryItem))?;
+        render_items_csv(&items, &definitions)
+    }
+    
+    /// Export the stock listing of the items `filter` matches, as CSV or
+    /// XLSX, so the Inventory page can export what it shows
+    pub async fn export_items(&self, format: &str, filter: InventoryExportFilter) -> Result<FileExport> {
+        let format = InventoryExportFormat::parse(format)?;
+        let mut items = Vec::new();
+        for page in 1.. {
+            let batch = self.search_items(ItemSearchQuery {
+                query: filter.query.clone(),
+                category: filter.category.clone(),
+                group_by_parent: false,
+                page,
+                page_size: EXPORT_PAGE_SIZE,
+            }).await?;
+            let done = batch.len() < EXPORT_PAGE_SIZE as usize;
+            items.extend(batch.into_iter().filter(|item| {
+                filter.status.is_none_or(|status| StockStatus::of(item) == status)
+            }));
+            if done {
+                break;
+            }
+        }
+        
+        let data = render_stock_list(&items, format)?;
+        info!("Exported {} inventory items as {}", items.len(), format.extension());
+        
+        Ok(FileExport::new(
+            &data,
+            format.content_type(),
+            format!("inventory_{}.{}", self.clock.now().date_naive(), format.extension()),
+        ))
+    }
+    
+    /// Import items from CSV. Rows with errors, and rows for SKUs that
+    /// already exist unless `options.update_existing`, are reported and
+    /// skipped; the rest are written in batches, each in its own
+    /// transaction. On update, blank cells keep the item's current values.
+    /// Progress is reported to `cancel`; batches written before it is
+    /// cancelled are kept.
+    pub async fn import_items_csv(
+        &self,
+        data: &[u8],
+        options: ImportOptions,
+        cancel: &CancellationToken,
+    ) -> Result<ImportReport> {
+        let (rows, mut errors) = parse_items_csv(data)?;
+        let existing: HashMap<String, String> = self.db.query_map(
+            "SELECT lower(sku), id FROM inventory_items",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?.into_iter().collect();
+        
+        let mut writes = Vec::with_capacity(rows.len());
+        for row in rows {
+            match existing.get(&row.sku.to_lowercase()) {
+                Some(_) if !options.update_existing => errors.push(RowError::new(
+                    row.line,
+                    Some("sku"),
+                    format!("SKU {} already exists", row.sku),
+                )),
+                id => writes.push((row, id.cloned())),
+            }
+        }
+        errors.sort_by_key(|e| e.line);
+        let mut report = ImportReport {
+            skipped: errors.iter().map(|e| e.line).collect::<HashSet<_>>().len(),
+            errors,
+            ..ImportReport::default()
+        };
+        
+        let now = self.clock.now().to_rfc3339();
+        let total = writes.len() as u64;
+        for (index, batch) in writes.chunks(IMPORT_BATCH_SIZE).enumerate() {
+            cancel.check()?;
+            cancel.report((index * IMPORT_BATCH_SIZE) as u64, Some(total));
+            self.db.transaction(|conn| {
+                for (row, existing_id) in batch {
+                    let unit = row.unit_of_measure.map(|u| format!("{:?}", u).to_lowercase());
+                    let dimensions = row.dimensions.as_ref();
+                    match existing_id {
+                        Some(id) => conn.execute(
+                            "UPDATE inventory_items SET
+                                name = ?, description = COALESCE(?, description),
+                                category = COALESCE(?, category),
+                                unit_of_measure = COALESCE(?, unit_of_measure),
+                                barcode = COALESCE(?, barcode),
+                                reorder_point = COALESCE(?, reorder_point),
+                                reorder_quantity = COALESCE(?, reorder_quantity),
+                                weight_kg = COALESCE(?, weight_kg),
+                                length_cm = COALESCE(?, length_cm),
+                                width_cm = COALESCE(?, width_cm),
+                                height_cm = COALESCE(?, height_cm),
+                                updated_at = ?, version = version + 1
+                             WHERE id = ?",
+                            params![
+                                &row.name,
+                                &row.description,
+                                &row.category,
+                                unit,
+                                &row.barcode,
+                                row.reorder_point,
+                                row.reorder_quantity,
+                                row.weight_kg,
+                                dimensions.map(|d| d.length_cm),
+                                dimensions.map(|d| d.width_cm),
+                                dimensions.map(|d| d.height_cm),
+                                &now,
+                                id,
+                            ],
+                        )?,
+                        None => conn.execute(
+                            "INSERT INTO inventory_items (
+                                id, sku, name, description, category, unit_of_measure,
+                                barcode, reorder_point, reorder_quantity,
+                                weight_kg, length_cm, width_cm, height_cm, created_at
+                            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                            params![
+                                new_id(),
+                                &row.sku,
+                                &row.name,
+                                &row.description,
+                                &row.category,
+                                unit.unwrap_or_else(|| "each".to_string()),
+                                &row.barcode,
+                                row.reorder_point,
+                                row.reorder_quantity,
+                                row.weight_kg,
+                                dimensions.map(|d| d.length_cm),
+                                dimensions.map(|d| d.width_cm),
+                                dimensions.map(|d| d.height_cm),
+                                &now,
+                            ],
+                        )?,
+                    };
+                }
+                Ok(())
+            })?;
+            let updated = batch.iter().filter(|(_, id)| id.is_some()).count();
+            report.updated += updated;
+            report.created += batch.len() - updated;
+        }
+        
+        cancel.report(total, Some(total));
+        info!(
+            "Imported items: {} created, {} updated, {} skipped",
+            report.created, report.updated, report.skipped
+        );
+        Ok(report)
+    }
+    
     /// Get item by SKU
-    pub async fn get_item_by_sku(&self, sku: &str) -> Result<Option<InventoryItem>> {
+    pub async fn get_item_by_sku(&self, sku: &str, warehouse_id: Option<&str>) -> Result<Option<InventoryItem>> {
         self.db.query_row(
-            "SELECT i.*, COALESCE(SUM(s.quantity), 0) as total_qty
-             FROM inventory_items i
-             LEFT JOIN inventory_stock s ON i.id = s.item_id
-             WHERE i.sku = ?
-             GROUP BY i.id",
-            params![sku],
+            &format!(
+                "SELECT i.*, COALESCE(SUM(s.quantity), 0) as total_qty
+                 FROM inventory_items i
+                 LEFT JOIN inventory_stock s ON i.id = s.item_id AND {}
+                 WHERE i.sku = ?1
+                 GROUP BY i.id",
+                in_warehouse(2)
+            ),
+            params![sku, warehouse_id],
             |row| Self::row_to_item(row),
         )
     }
@@ -75,13 +376,15 @@ impl InventoryService {
     /// Create a new inventory item
     pub async fn create_item(&self, mut item: InventoryItem) -> Result<InventoryItem> {
         // Validate SKU uniqueness
-        let existing = self.get_item_by_sku(&item.sku).await?;
+        let existing = self.get_item_by_sku(&item.sku, None).await?;
         if existing.is_some() {
             return Err(WmsError::conflict(format!("SKU {} already exists", item.sku)));
         }
+        item.custom_fields = self.custom_fields.validate(CustomFieldEntity::InventoryItem, &item.custom_fields)?;
         
         item.id = new_id();
-        item.created_at = Utc::now();
+        item.created_at = self.clock.now();
+        item.version = 1;
         
         self.db.execute(
             "INSERT INTO inventory_items (
@@ -89,8 +392,8 @@ impl InventoryService {
                 unit_of_measure, weight_kg, length_cm, width_cm, height_cm,
                 barcode, barcode_type, min_stock_level, max_stock_level,
                 reorder_point, reorder_quantity, lead_time_days, abc_class,
-                is_active, created_at
-            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                is_active, parent_item_id, variant_attributes, custom_fields, created_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
             params![
                 &item.id,
                 &item.sku,
@@ -110,8 +413,11 @@ impl InventoryService {
                 &item.reorder_point,
                 &item.reorder_quantity,
                 &item.lead_time_days,
-                item.abc_class.map(|c| format!("{:?}", c)),
+                item.abc_class.map(|c| c.as_str()),
                 &item.is_active,
+                &item.parent_item_id,
+                attributes_json(&item.variant_attributes),
+                custom_fields_json(&item.custom_fields),
                 item.created_at.to_rfc3339(),
             ],
         )?;
@@ -120,9 +426,12 @@ impl InventoryService {
         Ok(item)
     }
     
-    /// Update an existing inventory item
+    /// Update an existing inventory item. `item.version` must be the version
+    /// the edit was based on; if someone else has saved since, the update is
+    /// rejected with the current record.
     pub async fn update_item(&self, mut item: InventoryItem) -> Result<InventoryItem> {
-        item.updated_at = Some(Utc::now());
+        item.custom_fields = self.custom_fields.validate(CustomFieldEntity::InventoryItem, &item.custom_fields)?;
+        item.updated_at = Some(self.clock.now());
         
         let rows = self.db.execute(
             "UPDATE inventory_items SET
@@ -130,8 +439,8 @@ impl InventoryService {
                 unit_of_measure = ?, weight_kg = ?, length_cm = ?, width_cm = ?, height_cm = ?,
                 barcode = ?, barcode_type = ?, min_stock_level = ?, max_stock_level = ?,
                 reorder_point = ?, reorder_quantity = ?, lead_time_days = ?, abc_class = ?,
-                is_active = ?, updated_at = ?
-             WHERE id = ?",
+                is_active = ?, custom_fields = ?, updated_at = ?, version = version + 1
+             WHERE id = ? AND version = ?",
             params![
                 &item.sku,
                 &item.name,
@@ -150,16 +459,25 @@ impl InventoryService {
                 &item.reorder_point,
                 &item.reorder_quantity,
                 &item.lead_time_days,
-                item.abc_class.map(|c| format!("{:?}", c)),
+                item.abc_class.map(|c| c.as_str()),
                 &item.is_active,
+                custom_fields_json(&item.custom_fields),
                 item.updated_at.map(|t| t.to_rfc3339()),
                 &item.id,
+                item.version,
             ],
         )?;
         
         if rows == 0 {
-            return Err(WmsError::not_found(format!("Item {} not found", item.id)));
+            let current = self.get_item_by_id(&item.id).await?
+                .ok_or_else(|| WmsError::not_found(format!("Item {} not found", item.id)))?;
+            return Err(WmsError::version_conflict(
+                format!("Item {} was changed by someone else (version {}, yours {})",
+                        current.sku, current.version, item.version),
+                &current,
+            ));
         }
+        item.version += 1;
         
         debug!("Updated inventory item: {}", item.sku);
         Ok(item)
@@ -167,12 +485,68 @@ impl InventoryService {
     
     /// Adjust inventory quantity
     pub async fn adjust_quantity(&self, adjustment: InventoryAdjustment) -> Result<InventoryItem> {
-        // Get current stock level
+        let item = self.check_adjustment(&adjustment).await?;
+        self.db.transaction(|conn| self.post_adjustment(conn, &adjustment))?;
+        
+        info!(
+            "Adjusted inventory: {} {} {} units (user: {})",
+            item.sku,
+            format!("{:?}", adjustment.adjustment_type),
+            adjustment.quantity,
+            adjustment.user_id
+        );
+        
+        self.after_adjustment(&adjustment).await
+    }
+    
+    /// Check an adjustment's item, warehouse and lot hold before it is
+    /// posted. Returns the item.
+    pub async fn check_adjustment(&self, adjustment: &InventoryAdjustment) -> Result<InventoryItem> {
         let item = self.get_item_by_id(&adjustment.item_id).await?
             .ok_or_else(|| WmsError::not_found("Item not found"))?;
         
-        let current_qty = item.total_quantity.unwrap_or(0.0);
+        if let Some(warehouse_id) = adjustment.warehouse_id.as_deref() {
+            let location_id = adjustment.location_id.as_deref()
+                .ok_or_else(|| WmsError::validation("A warehouse adjustment needs a location"))?;
+            self.check_location_warehouse(location_id, warehouse_id).await?;
+        }
+        
+        // Held lots cannot be picked or moved
+        let hold = match adjustment.lot_number.as_deref() {
+            Some(lot) if !lot.is_empty()
+                && matches!(
+                    adjustment.adjustment_type,
+                    AdjustmentType::Pick | AdjustmentType::Transfer | AdjustmentType::TransferOut
+                ) =>
+            {
+                self.allocator.active_hold(&adjustment.item_id, lot).await?
+            }
+            _ => None,
+        };
+        if let Some(hold) = hold {
+            return Err(WmsError::validation(format!(
+                "Lot {} of {} is on quality hold: {}",
+                hold.lot_number, item.sku, hold.reason
+            )));
+        }
+        Ok(item)
+    }
+    
+    /// Post a checked adjustment in the caller's transaction: the stock is
+    /// read and checked, then changed, and the transaction record and the
+    /// change for sync are stored. Taking stock from a location needs that
+    /// location and lot to hold it, not just the item's total.
+    pub fn post_adjustment(
+        &self,
+        conn: &rusqlite::Connection,
+        adjustment: &InventoryAdjustment,
+    ) -> Result<InventoryTransaction> {
         let delta = adjustment.quantity * adjustment.adjustment_type.sign();
+        let current_qty: f64 = conn.query_row(
+            "SELECT COALESCE(SUM(quantity), 0) FROM inventory_stock WHERE item_id = ?",
+            params![&adjustment.item_id],
+            |row| row.get(0),
+        )?;
         let new_qty = current_qty + delta;
         
         // Check for negative inventory (unless allowed)
@@ -183,19 +557,57 @@ impl InventoryService {
             )));
         }
         
-        // Create transaction record
-        let tx_id = new_id();
-        self.db.execute(
+        let lot_key = adjustment.lot_number.clone().unwrap_or_default();
+        if let Some(location_id) = &adjustment.location_id
+            && delta < 0.0
+        {
+            let available: f64 = conn.query_row(
+                "SELECT COALESCE(SUM(quantity), 0) FROM inventory_stock
+                 WHERE item_id = ? AND location_id = ? AND COALESCE(lot_number, '') = ?",
+                params![&adjustment.item_id, location_id, &lot_key],
+                |row| row.get(0),
+            )?;
+            if available + delta < -1e-9 {
+                let what = adjustment.lot_number.as_deref()
+                    .filter(|lot| !lot.is_empty())
+                    .map(|lot| format!("of lot {} ", lot))
+                    .unwrap_or_default();
+                return Err(WmsError::validation(format!(
+                    "Only {} {}available at location {}, requested {}",
+                    available, what, location_id, adjustment.quantity
+                )));
+            }
+        }
+        
+        let entry = InventoryTransaction {
+            id: new_id(),
+            item_id: adjustment.item_id.clone(),
+            location_id: adjustment.location_id.clone(),
+            transaction_type: adjustment.adjustment_type,
+            quantity: adjustment.quantity,
+            previous_quantity: Some(current_qty),
+            new_quantity: Some(new_qty),
+            reference_type: None,
+            reference_id: None,
+            lot_number: adjustment.lot_number.clone(),
+            reason_code: adjustment.reason_code.clone(),
+            notes: adjustment.notes.clone(),
+            transfer_id: None,
+            user_id: adjustment.user_id.clone(),
+            created_at: self.clock.now(),
+        };
+        
+        conn.execute(
             "INSERT INTO inventory_transactions (
                 id, item_id, location_id, transaction_type, quantity,
                 previous_quantity, new_quantity, lot_number, reason_code,
                 notes, user_id, created_at
             ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, datetime('now'))",
             params![
-                &tx_id,
+                &entry.id,
                 &adjustment.item_id,
                 &adjustment.location_id,
-                format!("{:?}", adjustment.adjustment_type).to_uppercase(),
+                adjustment.adjustment_type.as_str(),
                 &adjustment.quantity,
                 &current_qty,
                 &new_qty,
@@ -206,126 +618,3627 @@ impl InventoryService {
             ],
         )?;
         
-        // Update or insert stock record
         if let Some(location_id) = &adjustment.location_id {
-            self.db.execute(
-                "INSERT INTO inventory_stock (id, item_id, location_id, quantity, lot_number, updated_at)
-                 VALUES (?, ?, ?, ?, ?, datetime('now'))
-                 ON CONFLICT(item_id, location_id, lot_number) DO UPDATE SET
-                    quantity = quantity + ?,
-                    updated_at = datetime('now')",
-                params![
-                    new_id(),
-                    &adjustment.item_id,
-                    location_id,
-                    delta,
-                    &adjustment.lot_number.clone().unwrap_or_default(),
-                    delta,
-                ],
+            if delta < 0.0 {
+                Self::take_stock(conn, &adjustment.item_id, location_id, &lot_key, -delta)?;
+            } else {
+                conn.execute(
+                    "INSERT INTO inventory_stock (id, item_id, location_id, quantity, lot_number, updated_at)
+                     VALUES (?, ?, ?, ?, ?, datetime('now'))
+                     ON CONFLICT(item_id, location_id, lot_number) DO UPDATE SET
+                        quantity = quantity + ?,
+                        updated_at = datetime('now')",
+                    params![new_id(), &adjustment.item_id, location_id, delta, &lot_key, delta],
+                )?;
+            }
+        }
+        self.changes.record_model(conn, "inventory_transactions", &entry.id, ChangeKind::Insert, &entry)?;
+        Ok(entry)
+    }
+    
+    /// Take `quantity` of an item's lot from a location in the caller's
+    /// transaction, largest stock row first. Each row is only lowered by
+    /// what it still holds, so stock spent since it was read fails the
+    /// write instead of going negative.
+    fn take_stock(
+        conn: &rusqlite::Connection,
+        item_id: &str,
+        location_id: &str,
+        lot_key: &str,
+        quantity: f64,
+    ) -> Result<()> {
+        let rows = conn.prepare(
+            "SELECT id, quantity FROM inventory_stock
+             WHERE item_id = ? AND location_id = ? AND COALESCE(lot_number, '') = ? AND quantity > 0
+             ORDER BY quantity DESC",
+        )?
+        .query_map(params![item_id, location_id, lot_key], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, f64>(1)?))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+        
+        let mut remaining = quantity;
+        for (id, on_hand) in rows {
+            if remaining <= 1e-9 {
+                break;
+            }
+            let take = remaining.min(on_hand);
+            let changed = conn.execute(
+                "UPDATE inventory_stock SET quantity = quantity - ?1, updated_at = datetime('now')
+                 WHERE id = ?2 AND quantity >= ?1",
+                params![take, &id],
             )?;
+            if changed == 0 {
+                return Err(WmsError::conflict(format!(
+                    "Stock at location {} changed while it was being taken", location_id
+                )));
+            }
+            remaining -= take;
+        }
+        if remaining > 1e-9 {
+            return Err(WmsError::validation(format!(
+                "Only {} available at location {}, requested {}",
+                quantity - remaining, location_id, quantity
+            )));
+        }
+        Ok(())
+    }
+    
+    /// Follow up a committed adjustment: replenish the pick face after a
+    /// pick and notify when a decrease leaves the item at or below its
+    /// reorder point. Returns the updated item.
+    pub async fn after_adjustment(&self, adjustment: &InventoryAdjustment) -> Result<InventoryItem> {
+        if let Some(location_id) = &adjustment.location_id
+            && matches!(adjustment.adjustment_type, AdjustmentType::Pick)
+        {
+            self.replenish_after_pick(&adjustment.item_id, location_id).await;
         }
         
-        info!(
-            "Adjusted inventory: {} {} {} units (user: {})",
-            item.sku,
-            format!("{:?}", adjustment.adjustment_type),
-            adjustment.quantity,
-            adjustment.user_id
-        );
+        let updated = self.get_item_by_id(&adjustment.item_id).await?
+            .ok_or_else(|| WmsError::not_found("Item not found"))?;
+        
+        let delta = adjustment.quantity * adjustment.adjustment_type.sign();
+        if let Some(reorder_point) = updated.reorder_point {
+            let quantity = updated.total_quantity.unwrap_or(0.0);
+            if delta < 0.0 && quantity <= reorder_point {
+                self.events.emit(WmsEvent::InventoryLowStock {
+                    item_id: updated.id.clone(),
+                    sku: updated.sku.clone(),
+                    quantity,
+                    reorder_point,
+                });
+            }
+        }
         
-        // Return updated item
-        self.get_item_by_id(&adjustment.item_id).await?
-            .ok_or_else(|| WmsError::not_found("Item not found"))
+        Ok(updated)
     }
     
-    /// Get items below their reorder point
-    pub async fn get_low_stock_items(&self) -> Result<Vec<InventoryItem>> {
+    /// Get items below their reorder point. A parent item's quantity
+    /// includes the stock of its variants.
+    pub async fn get_low_stock_items(&self, warehouse_id: Option<&str>) -> Result<Vec<InventoryItem>> {
         let items = self.db.query_map(
-            "SELECT i.*, COALESCE(SUM(s.quantity), 0) as total_qty
-             FROM inventory_items i
-             LEFT JOIN inventory_stock s ON i.id = s.item_id
-             WHERE i.is_active = 1
-               AND i.reorder_point IS NOT NULL
-             GROUP BY i.id
-             HAVING total_qty <= i.reorder_point
-             ORDER BY (i.reorder_point - total_qty) DESC",
-            [],
+            &format!(
+                "SELECT i.*, COALESCE((SELECT SUM(s.quantity) FROM inventory_stock s
+                        JOIN inventory_items v ON s.item_id = v.id
+                        WHERE (v.id = i.id OR v.parent_item_id = i.id) AND {}), 0) AS total_qty
+                 FROM inventory_items i
+                 WHERE i.is_active = 1
+                   AND i.reorder_point IS NOT NULL
+                   AND total_qty <= i.reorder_point
+                 ORDER BY (i.reorder_point - total_qty) DESC",
+                in_warehouse(1)
+            ),
+            params![warehouse_id],
             |row| Self::row_to_item(row),
         )?;
         
         Ok(items)
     }
     
-    /// Run demand forecast for an item
-    pub async fn run_forecast(&self, sku: &str, days_ahead: u32) -> Result<ForecastResult> {
-        // Get historical transaction data
-        let history = self.get_transaction_history(sku, 365).await?;
+    /// What to order for each item at or below its reorder point: the
+    /// item's reorder quantity, or else the forecast's suggestion, with
+    /// on-hand and on-order stock and days of cover. Items that open
+    /// receipts will bring back above the reorder point are flagged and
+    /// get no suggestion.
+    pub async fn generate_reorder_report(&self) -> Result<ReorderReport> {
+        let today = self.clock.today();
+        let demand_start = today - Duration::days(REORDER_DEMAND_DAYS as i64);
+        
+        let mut lines = Vec::new();
+        for item in self.get_low_stock_items(None).await? {
+            let reorder_point = item.reorder_point.unwrap_or(0.0);
+            let on_hand = item.total_quantity.unwrap_or(0.0);
+            let on_order: f64 = self.db.query_row(
+                "SELECT COALESCE(SUM(MAX(ri.quantity_expected - COALESCE(ri.quantity_received, 0), 0)), 0)
+                 FROM receipt_items ri
+                 JOIN receipts r ON ri.receipt_id = r.id
+                 WHERE ri.item_id = ? AND r.status NOT IN ('completed', 'cancelled')",
+                params![&item.id],
+                |row| row.get(0),
+            )?.unwrap_or(0.0);
+            let picked: f64 = self.db.query_row(
+                "SELECT COALESCE(SUM(ABS(quantity)), 0) FROM inventory_transactions
+                 WHERE item_id = ? AND transaction_type = 'PICK'
+                   AND date(created_at) >= ? AND date(created_at) < ?",
+                params![&item.id, demand_start.to_string(), today.to_string()],
+                |row| row.get(0),
+            )?.unwrap_or(0.0);
+            let average_daily_demand = picked / REORDER_DEMAND_DAYS as f64;
+            
+            let covered_by_on_order = on_order > 0.0 && on_hand + on_order > reorder_point;
+            let (suggested_quantity, quantity_source) = match item.reorder_quantity {
+                Some(quantity) => (quantity, ReorderQuantitySource::Item),
+                None => {
+                    let history = self.get_daily_demand(&item.sku, 365).await?;
+                    if history.is_empty() {
+                        (0.0, ReorderQuantitySource::None)
+                    } else {
+                        let forecast = self.forecast_engine.forecast(&history, 1, None)?;
+                        (forecast.suggested_reorder_quantity.max(0.0), ReorderQuantitySource::Forecast)
+                    }
+                }
+            };
+            
+            lines.push(ReorderLine {
+                item_id: item.id,
+                sku: item.sku,
+                name: item.name,
+                reorder_point,
+                on_hand,
+                on_order,
+                suggested_quantity: if covered_by_on_order { 0.0 } else { suggested_quantity },
+                quantity_source,
+                average_daily_demand,
+                days_of_cover: (average_daily_demand > 0.0).then(|| on_hand / average_daily_demand),
+                covered_by_on_order,
+            });
+        }
+        
+        Ok(ReorderReport {
+            lines,
+            demand_days: REORDER_DEMAND_DAYS,
+            generated_at: self.clock.now(),
+        })
+    }
+    
+    /// The reorder report as CSV
+    pub async fn export_reorder_report(&self) -> Result<ReorderReportExport> {
+        let report = self.generate_reorder_report().await?;
+        let data = render_reorder_csv(&report)?;
+        
+        Ok(ReorderReportExport {
+            data: base64::engine::general_purpose::STANDARD.encode(&data),
+            content_type: "text/csv".to_string(),
+            filename: format!("reorder_report_{}.csv", report.generated_at.date_naive()),
+        })
+    }
+    
+    /// Run demand forecast for an item, with `model` or the one that suits
+    /// its history. The predictions are recorded so their accuracy can be
+    /// measured once the days have passed. Stops with
+    /// `WmsError::Cancelled` once `cancel` is cancelled.
+    pub async fn run_forecast(
+        &self,
+        sku: &str,
+        days_ahead: u32,
+        model: Option<ForecastModel>,
+        cancel: &CancellationToken,
+    ) -> Result<ForecastResult> {
+        // Get daily demand history
+        let history = self.get_daily_demand(sku, 365).await?;
         
-        if history.len() < 30 {
+        // A requested model checks its own minimum, and Croston only
+        // needs demand to have started
+        if model.is_none() && history.len() < 30 && !self.forecast_engine.is_sparse(&history) {
             return Err(WmsError::Forecast(
-                "Insufficient history for forecasting (need at least 30 data points)".to_string()
+                "Insufficient history for forecasting (need at least 30 days)".to_string()
             ));
         }
         
         // Run forecast
-        self.forecast_engine.forecast(&history, days_ahead)
+        let result = self.forecast_engine.forecast_with_cancel(&history, days_ahead, model, cancel)?;
+        self.record_predictions(sku, &history, &result)?;
+        
+        Ok(result)
+    }
+    
+    /// Project day-by-day available-to-promise quantity for an item
+    pub async fn get_available_to_promise(
+        &self,
+        item_id: &str,
+        horizon_days: u32,
+    ) -> Result<AvailabilityProjection> {
+        let item = self.get_item_by_id(item_id).await?
+            .ok_or_else(|| WmsError::not_found(format!("Item {} not found", item_id)))?;
+        let held: f64 = self.db.query_row(
+            &format!(
+                "SELECT COALESCE(SUM(s.quantity), 0) FROM inventory_stock s
+                 WHERE s.item_id = ? AND NOT {}",
+                NOT_HELD
+            ),
+            params![item_id],
+            |row| row.get(0),
+        )?.unwrap_or(0.0);
+        let on_hand = item.total_quantity.unwrap_or(0.0) - held;
+        let today = self.clock.today();
+        
+        let reserved: f64 = self.db.query_row(
+            "SELECT COALESCE(SUM(quantity), 0) FROM inventory_reservations
+             WHERE item_id = ? AND status = 'active'
+               AND (expires_at IS NULL OR datetime(expires_at) > datetime(?))",
+            params![item_id, self.clock.now().to_rfc3339()],
+            |row| row.get(0),
+        )?.unwrap_or(0.0);
+        
+        // Demand: unpicked quantities on confirmed shipments
+        let demand = self.db.query_map(
+            "SELECT substr(COALESCE(s.ship_date, s.expected_delivery_date, ?), 1, 10) AS due_date,
+                    SUM(si.quantity_ordered - COALESCE(si.quantity_picked, 0)) AS qty
+             FROM shipment_items si
+             JOIN shipments s ON si.shipment_id = s.id
+             WHERE si.item_id = ?
+               AND s.status IN ('confirmed', 'picking', 'packed')
+             GROUP BY due_date",
+            params![today.to_string(), item_id],
+            |row| Ok((row.get::<_, String>("due_date")?, -row.get::<_, f64>("qty")?)),
+        )?;
+        
+        // Supply: outstanding quantities on open receipts with an expected date
+        let supply = self.db.query_map(
+            "SELECT substr(r.expected_date, 1, 10) AS due_date,
+                    SUM(ri.quantity_expected - COALESCE(ri.quantity_received, 0)) AS qty
+             FROM receipt_items ri
+             JOIN receipts r ON ri.receipt_id = r.id
+             WHERE ri.item_id = ?
+               AND r.status IN ('pending', 'receiving')
+               AND r.expected_date IS NOT NULL
+             GROUP BY due_date",
+            params![item_id],
+            |row| Ok((row.get::<_, String>("due_date")?, row.get::<_, f64>("qty")?)),
+        )?;
+        
+        let movements: Vec<ScheduledMovement> = demand.into_iter()
+            .chain(supply)
+            .filter(|(_, qty)| *qty != 0.0)
+            .filter_map(|(date, quantity)| {
+                NaiveDate::parse_from_str(&date, "%Y-%m-%d")
+                    .ok()
+                    .map(|date| ScheduledMovement { date, quantity })
+            })
+            .collect();
+        
+        let days = project_availability(
+            on_hand,
+            reserved,
+            &movements,
+            today,
+            horizon_days,
+        );
+        
+        Ok(AvailabilityProjection {
+            item_id: item.id,
+            on_hand,
+            reserved,
+            days,
+        })
     }
     
-    /// Get transaction history for forecasting
-    async fn get_transaction_history(&self, sku: &str, days: u32) -> Result<Vec<f64>> {
-        let transactions: Vec<f64> = self.db.query_map(
-            "SELECT ABS(t.quantity) as qty
+    /// Check whether a quantity can be promised by a given date
+    pub async fn check_promise(
+        &self,
+        item_id: &str,
+        quantity: f64,
+        need_by: NaiveDate,
+    ) -> Result<PromiseCheck> {
+        if quantity <= 0.0 {
+            return Err(WmsError::validation("Quantity must be greater than zero"));
+        }
+        
+        let days_until = (need_by - self.clock.today()).num_days().max(0) as u32;
+        let projection = self
+            .get_available_to_promise(item_id, days_until + PROMISE_LOOKAHEAD_DAYS)
+            .await?;
+        
+        let earliest_date = earliest_available_date(&projection.days, quantity);
+        
+        Ok(PromiseCheck {
+            item_id: projection.item_id,
+            quantity,
+            need_by,
+            can_promise: earliest_date.is_some_and(|d| d <= need_by),
+            earliest_date,
+        })
+    }
+    
+    /// Plan labor for the week starting `week_start`: forecast A/B-class
+    /// picks per day, convert them to hours using historical picks per
+    /// labor hour, and compare with scheduled staff hours.
+    ///
+    /// Days already past use actual pick counts instead of the forecast.
+    pub async fn get_workload_plan(&self, week_start: NaiveDate) -> Result<WorkloadPlan> {
+        let today = self.clock.today();
+        let week_end = week_start + Duration::days(6);
+        let history_start = today - Duration::days(WORKLOAD_HISTORY_DAYS);
+        
+        // Daily pick counts of fast movers
+        let picks_by_day: HashMap<NaiveDate, f64> = self.db.query_map(
+            "SELECT date(t.created_at) AS day, COUNT(*) AS picks
              FROM inventory_transactions t
              JOIN inventory_items i ON t.item_id = i.id
-             WHERE i.sku = ?
-               AND t.transaction_type IN ('PICK', 'RECEIVE')
-               AND t.created_at >= date('now', '-' || ? || ' days')
-             ORDER BY t.created_at ASC",
-            params![sku, days],
-            |row| row.get(0),
+             WHERE t.transaction_type = 'PICK'
+               AND i.abc_class IN ('A', 'B')
+               AND date(t.created_at) >= ? AND date(t.created_at) < ?
+             GROUP BY day",
+            params![history_start.min(week_start).to_string(), today.to_string()],
+            |row| Ok((row.get::<_, String>("day")?, row.get::<_, f64>("picks")?)),
+        )?
+        .into_iter()
+        .filter_map(|(day, picks)| {
+            NaiveDate::parse_from_str(&day, "%Y-%m-%d").ok().map(|date| (date, picks))
+        })
+        .collect();
+        
+        let mut expected_picks: HashMap<NaiveDate, f64> = week_start.iter_days()
+            .take(7)
+            .filter(|date| *date < today)
+            .map(|date| (date, picks_by_day.get(&date).copied().unwrap_or(0.0)))
+            .collect();
+        
+        if week_end >= today {
+            let series: Vec<f64> = history_start.iter_days()
+                .take_while(|date| *date < today)
+                .map(|date| picks_by_day.get(&date).copied().unwrap_or(0.0))
+                .collect();
+            let days_ahead = (week_end - today).num_days() as u32 + 1;
+            let forecast = self.forecast_engine.forecast(&series, days_ahead, None)?;
+            
+            for (offset, predicted) in forecast.predictions.iter().enumerate() {
+                let date = today + Duration::days(offset as i64);
+                if date >= week_start {
+                    expected_picks.insert(date, predicted.max(0.0));
+                }
+            }
+        }
+        
+        // Picks per clocked hour, matched by user and day
+        let productivity_start = today - Duration::weeks(PRODUCTIVITY_WEEKS);
+        let history = self.db.query_map(
+            "SELECT p.day, SUM(p.picks) AS picks, SUM(h.hours) AS hours
+             FROM (
+                 SELECT user_id, date(created_at) AS day, COUNT(*) AS picks
+                 FROM inventory_transactions
+                 WHERE transaction_type = 'PICK'
+                   AND date(created_at) >= ? AND date(created_at) < ?
+                 GROUP BY user_id, day
+             ) p
+             JOIN (
+                 SELECT user_id, entry_date AS day, SUM(total_hours) AS hours
+                 FROM time_entries
+                 WHERE total_hours IS NOT NULL AND status != 'rejected'
+                 GROUP BY user_id, entry_date
+             ) h ON h.user_id = p.user_id AND h.day = p.day
+             GROUP BY p.day",
+            params![productivity_start.to_string(), today.to_string()],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, f64>(1)?, row.get::<_, f64>(2)?)),
+        )?
+        .into_iter()
+        .filter_map(|(day, picks, hours)| {
+            NaiveDate::parse_from_str(&day, "%Y-%m-%d")
+                .ok()
+                .map(|date| DailyProductivity { date, picks, hours })
+        })
+        .collect::<Vec<_>>();
+        
+        // Scheduled hours and headcount per day
+        let shifts = self.db.query_map(
+            "SELECT schedule_date, scheduled_start, scheduled_end FROM user_schedules
+             WHERE schedule_date >= ? AND schedule_date <= ?",
+            params![week_start.to_string(), week_end.to_string()],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?)),
         )?;
+        let mut scheduled: HashMap<NaiveDate, (f64, u32)> = HashMap::new();
+        for (day, start, end) in shifts {
+            let date = NaiveDate::parse_from_str(&day, "%Y-%m-%d").ok();
+            let (Some(date), Some(hours)) = (date, shift_hours(&start, &end)) else {
+                debug!("Skipping unreadable schedule {} {}-{}", day, start, end);
+                continue;
+            };
+            let entry = scheduled.entry(date).or_default();
+            entry.0 += hours;
+            entry.1 += 1;
+        }
         
-        Ok(transactions)
+        Ok(build_workload_plan(week_start, &expected_picks, &history, &scheduled, today))
     }
     
-    /// Convert database row to InventoryItem
-    fn row_to_item(row: &rusqlite::Row) -> rusqlite::Result<InventoryItem> {
-        let dimensions = match (
-            row.get::<_, Option<f64>>("length_cm")?,
-            row.get::<_, Option<f64>>("width_cm")?,
-            row.get::<_, Option<f64>>("height_cm")?,
-        ) {
-            (Some(l), Some(w), Some(h)) => Some(ItemDimensions {
-                length_cm: l,
-                width_cm: w,
-                height_cm: h,
+    /// Export the workload plan for a week as CSV
+    pub async fn export_workload_plan(&self, week_start: NaiveDate) -> Result<WorkloadPlanExport> {
+        let plan = self.get_workload_plan(week_start).await?;
+        let data = render_workload_csv(&plan)?;
+        
+        Ok(WorkloadPlanExport {
+            data: base64::engine::general_purpose::STANDARD.encode(&data),
+            content_type: "text/csv".to_string(),
+            filename: format!("workload_plan_{}.csv", week_start),
+        })
+    }
+    
+    /// Pick counts and quantities per location over `range`, rolled up by
+    /// aisle, with suggestions for moving far-stored A-class items closer
+    /// to the depot. Slotting only considers locations with coordinates.
+    pub async fn get_pick_heatmap(&self, range: DateRange) -> Result<PickHeatmap> {
+        let locations = self.db.query_map(
+            "SELECT l.id, l.code, l.zone, l.aisle, l.rack, COUNT(*) AS picks, SUM(ABS(t.quantity)) AS quantity
+             FROM inventory_transactions t
+             JOIN locations l ON t.location_id = l.id
+             WHERE t.transaction_type = 'PICK'
+               AND date(t.created_at) >= ? AND date(t.created_at) <= ?
+             GROUP BY l.id",
+            params![range.start.to_string(), range.end.to_string()],
+            |row| Ok(LocationPicks {
+                location_id: row.get(0)?,
+                location_code: row.get(1)?,
+                zone: row.get(2)?,
+                aisle: row.get(3)?,
+                rack: row.get(4)?,
+                picks: row.get(5)?,
+                quantity: row.get(6)?,
             }),
-            _ => None,
+        )?;
+        
+        // Pickable locations on the floor plan
+        let pickable = self.db.query_map(
+            "SELECT id, code, x_m, y_m FROM locations
+             WHERE is_active = 1 AND zone IN ('PICKING', 'STORAGE')
+               AND x_m IS NOT NULL AND y_m IS NOT NULL",
+            [],
+            row_to_slot_location,
+        )?;
+        
+        let slotting = match far_threshold_m(&pickable) {
+            Some(far_m) => {
+                let empty = self.db.query_map(
+                    "SELECT id, code, x_m, y_m FROM locations l
+                     WHERE is_active = 1 AND zone IN ('PICKING', 'STORAGE')
+                       AND x_m IS NOT NULL AND y_m IS NOT NULL
+                       AND NOT EXISTS (
+                           SELECT 1 FROM inventory_stock s WHERE s.location_id = l.id AND s.quantity > 0
+                       )",
+                    [],
+                    row_to_slot_location,
+                )?;
+                
+                let stock = self.db.query_map(
+                    "SELECT i.id AS item_id, i.sku, i.name,
+                            (SELECT COUNT(*) FROM inventory_transactions t
+                             WHERE t.item_id = i.id AND t.transaction_type = 'PICK'
+                               AND date(t.created_at) >= ? AND date(t.created_at) <= ?) AS picks,
+                            l.id, l.code, l.x_m, l.y_m
+                     FROM inventory_stock s
+                     JOIN inventory_items i ON s.item_id = i.id
+                     JOIN locations l ON s.location_id = l.id
+                     WHERE i.abc_class = 'A' AND i.is_active = 1 AND s.quantity > 0
+                       AND l.x_m IS NOT NULL AND l.y_m IS NOT NULL",
+                    params![range.start.to_string(), range.end.to_string()],
+                    |row| Ok(StoredItem {
+                        item_id: row.get("item_id")?,
+                        sku: row.get("sku")?,
+                        name: row.get("name")?,
+                        picks: row.get("picks")?,
+                        location: row_to_slot_location(row)?,
+                    }),
+                )?;
+                
+                // An item only needs moving if even its closest stock is far
+                let mut nearest: HashMap<String, StoredItem> = HashMap::new();
+                for item in stock {
+                    match nearest.get(&item.item_id) {
+                        Some(current) if current.location.distance_m() <= item.location.distance_m() => {}
+                        _ => {
+                            nearest.insert(item.item_id.clone(), item);
+                        }
+                    }
+                }
+                let items: Vec<StoredItem> = nearest.into_values().collect();
+                
+                suggest_slotting(&items, &empty, far_m)
+            }
+            None => Vec::new(),
         };
         
-        Ok(InventoryItem {
-            id: row.get("id")?,
-            sku: row.get("sku")?,
-            name: row.get("name")?,
-            description: row.get("description")?,
-            category: row.get("category")?,
-            subcategory: row.get("subcategory")?,
-            unit_of_measure: wms_core::types::UnitOfMeasure::Each, // Parse from string if needed
-            weight_kg: row.get("weight_kg")?,
-            dimensions,
-            barcode: row.get("barcode")?,
-            barcode_type: None, // Parse from string if needed
-            min_stock_level: row.get::<_, f64>("min_stock_level").unwrap_or(0.0),
-            max_stock_level: row.get("max_stock_level")?,
-            reorder_point: row.get("reorder_point")?,
-            reorder_quantity: row.get("reorder_quantity")?,
-            lead_time_days: row.get::<_, u32>("lead_time_days").unwrap_or(0),
-            abc_class: None, // Parse from string if needed
-            is_active: row.get::<_, i32>("is_active")? == 1,
-            created_at: chrono::Utc::now(), // Parse from string
-            updated_at: None,
-            total_quantity: row.get("total_qty").ok(),
+        Ok(build_pick_heatmap(range.start, range.end, locations, slotting))
+    }
+    
+    /// Export the pick heatmap for a period as CSV
+    pub async fn export_pick_heatmap(&self, range: DateRange) -> Result<PickHeatmapExport> {
+        let heatmap = self.get_pick_heatmap(range).await?;
+        let data = render_pick_heatmap_csv(&heatmap)?;
+        
+        Ok(PickHeatmapExport {
+            data: base64::engine::general_purpose::STANDARD.encode(&data),
+            content_type: "text/csv".to_string(),
+            filename: format!("pick_heatmap_{}_{}.csv", range.start, range.end),
         })
     }
-}
-
+    
+    // ============ ABC Classification Operations ============
+    
+    /// Classify active items by the value picked over the last
+    /// `period_days`: quantity times the item's average unit cost, or the
+    /// quantity alone when no cost is known. `a_threshold` and
+    /// `b_threshold` are the cumulative percentages of usage value that
+    /// end the A and B classes. The classes are written back to the items.
+    pub async fn run_abc_classification(
+        &self,
+        period_days: u32,
+        a_threshold: f64,
+        b_threshold: f64,
+    ) -> Result<AbcClassificationReport> {
+        if period_days == 0 {
+            return Err(WmsError::validation("Classification period must be at least a day"));
+        }
+        if !(a_threshold > 0.0 && a_threshold <= b_threshold && b_threshold <= 100.0) {
+            return Err(WmsError::validation("Thresholds must satisfy 0 < A <= B <= 100"));
+        }
+        
+        let period_end = self.clock.now().date_naive();
+        let period_start = period_end - Duration::days(period_days as i64 - 1);
+        let annualize = 365.0 / period_days as f64;
+        let usage = self.db.query_map(
+            "SELECT i.id, i.sku, i.abc_class,
+                    COALESCE(SUM(ABS(t.quantity) * COALESCE(c.unit_cost, 1.0)), 0) AS usage_value
+             FROM inventory_items i
+             LEFT JOIN inventory_transactions t ON t.item_id = i.id
+                AND t.transaction_type = 'PICK'
+                AND date(t.created_at) >= ? AND date(t.created_at) <= ?
+             LEFT JOIN (
+                SELECT item_id, AVG(cost_per_unit) AS unit_cost FROM inventory_stock
+                WHERE cost_per_unit IS NOT NULL
+                GROUP BY item_id
+             ) c ON c.item_id = i.id
+             WHERE i.is_active = 1
+             GROUP BY i.id",
+            params![period_start.to_string(), period_end.to_string()],
+            |row| Ok(ItemUsage {
+                item_id: row.get(0)?,
+                sku: row.get(1)?,
+                previous_class: row.get::<_, Option<String>>(2)?.and_then(|c| AbcClass::parse(&c)),
+                annual_usage_value: row.get::<_, f64>(3)? * annualize,
+            }),
+        )?;
+        
+        let items = classify_items(usage, a_threshold, b_threshold);
+        let changed: Vec<&AbcItemClass> = items.iter().filter(|i| i.previous_class != Some(i.class)).collect();
+        self.db.transaction(|conn| {
+            for item in &changed {
+                conn.execute(
+                    "UPDATE inventory_items SET abc_class = ? WHERE id = ?",
+                    params![item.class.as_str(), &item.item_id],
+                )?;
+            }
+            Ok(())
+        })?;
+        
+        let report = AbcClassificationReport {
+            period_start,
+            period_end,
+            a_threshold,
+            b_threshold,
+            classes: summarize_classes(&items),
+            no_movement_count: items.iter().filter(|i| i.no_movement).count() as u32,
+            changed_count: changed.len() as u32,
+            items,
+            classified_at: self.clock.now(),
+        };
+        info!(
+            "ABC classification over {} days: {} items, {} changed, {} without movement",
+            period_days, report.items.len(), report.changed_count, report.no_movement_count
+        );
+        Ok(report)
+    }
+    
+    // ============ Picker Productivity Operations ============
+    
+    /// Ranked per-picker stats over `range`: lines and units picked, picks
+    /// per hour and accuracy
+    pub async fn get_picker_stats(&self, range: DateRange) -> Result<PickerLeaderboard> {
+        let days = self.picker_days(range, None)?;
+        let names: BTreeMap<String, String> = self.db.query_map(
+            "SELECT id, full_name FROM users",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?.into_iter().collect();
+        
+        Ok(build_leaderboard(range.start, range.end, &days, &names))
+    }
+    
+    /// A picker's own stats and rank over `range`, with their day-by-day
+    /// numbers and mispick-free streaks
+    pub async fn get_my_stats(&self, user_id: &str, range: DateRange) -> Result<MyPickerStats> {
+        let board = self.get_picker_stats(range).await?;
+        let history = self.picker_days(DateRange::new(streak_start(range.end), range.end), Some(user_id))?;
+        let (clean_streak_days, best_clean_streak_days) = clean_streaks(&history);
+        
+        Ok(MyPickerStats {
+            start: range.start,
+            end: range.end,
+            stats: board.pickers.iter().find(|p| p.user_id == user_id).cloned(),
+            pickers: board.pickers.len() as u32,
+            days: history.into_iter().filter(|d| range.contains(d.date)).collect(),
+            clean_streak_days,
+            best_clean_streak_days,
+        })
+    }
+    
+    /// Picks, mispicks and clocked hours per picker per day, oldest first
+    fn picker_days(&self, range: DateRange, user_id: Option<&str>) -> Result<Vec<PickerDay>> {
+        let (start, end) = (range.start.to_string(), range.end.to_string());
+        
+        let mut clocked: HashMap<(String, String), f64> = HashMap::new();
+        for (user, day, hours) in self.db.query_map(
+            "SELECT user_id, entry_date, SUM(total_hours) FROM time_entries
+             WHERE total_hours IS NOT NULL AND status != 'rejected'
+               AND entry_date >= ? AND entry_date <= ?
+             GROUP BY user_id, entry_date",
+            params![&start, &end],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, f64>(2)?)),
+        )? {
+            clocked.insert((user, day), hours);
+        }
+        
+        // Dated by the pick that was wrong, not the correction
+        let mut mispicks: HashMap<(String, String), u32> = HashMap::new();
+        for (user, day, count) in self.db.query_map(
+            "SELECT picked_by, date(COALESCE(picked_at, corrected_at)) AS day, COUNT(*) FROM mispicks
+             WHERE date(COALESCE(picked_at, corrected_at)) >= ?
+               AND date(COALESCE(picked_at, corrected_at)) <= ?
+             GROUP BY picked_by, day",
+            params![&start, &end],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, u32>(2)?)),
+        )? {
+            mispicks.insert((user, day), count);
+        }
+        
+        let picks = self.db.query_map(
+            "SELECT user_id, date(created_at) AS day, COUNT(*), SUM(ABS(quantity)),
+                    (julianday(MAX(created_at)) - julianday(MIN(created_at))) * 24
+             FROM inventory_transactions
+             WHERE transaction_type = 'PICK'
+               AND date(created_at) >= ? AND date(created_at) <= ?
+               AND (?3 IS NULL OR user_id = ?3)
+             GROUP BY user_id, day
+             ORDER BY day, user_id",
+            params![&start, &end, user_id],
+            |row| Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, u32>(2)?,
+                row.get::<_, f64>(3)?,
+                row.get::<_, f64>(4)?,
+            )),
+        )?;
+        
+        Ok(picks.into_iter()
+            .filter_map(|(user, day, lines, units, span)| {
+                let date = NaiveDate::parse_from_str(&day, "%Y-%m-%d").ok()?;
+                let key = (user, day);
+                Some(PickerDay {
+                    date,
+                    lines_picked: lines,
+                    units_picked: units,
+                    mispicks: mispicks.get(&key).copied().unwrap_or(0),
+                    clocked_hours: clocked.get(&key).copied(),
+                    pick_span_hours: span,
+                    user_id: key.0,
+                })
+            })
+            .collect())
+    }
+    
+    /// Resolve an item's price for a customer on a given date
+    pub async fn get_price(
+        &self,
+        item_id: &str,
+        customer_id: Option<&str>,
+        date: DateTime<Utc>,
+    ) -> Result<Option<ItemPrice>> {
+        self.price_book.get_price(item_id, customer_id, date).await
+    }
+    
+    /// Create or replace a price list
+    pub async fn upsert_price_list(&self, list: PriceList) -> Result<PriceList> {
+        self.price_book.upsert_price_list(list).await
+    }
+    
+    // ============ Forecast Accuracy Operations ============
+    
+    /// Fill in actual demand for predictions whose day has passed, then
+    /// store rolling accuracy per item and model (and across all items)
+    /// as of today
+    pub async fn evaluate_forecasts(&self) -> Result<ForecastEvaluation> {
+        let today = self.clock.today();
+        let evaluated_predictions = self.db.execute(
+            "UPDATE forecast_predictions
+             SET actual_demand = COALESCE((
+                     SELECT SUM(ABS(t.quantity)) FROM inventory_transactions t
+                     WHERE t.item_id = forecast_predictions.item_id
+                       AND t.transaction_type = 'PICK'
+                       AND date(t.created_at) = forecast_predictions.target_date
+                 ), 0),
+                 evaluated_at = ?
+             WHERE actual_demand IS NULL AND target_date < ?",
+            params![self.clock.now().to_rfc3339(), today.to_string()],
+        )?;
+        
+        let window_start = today - Duration::days(ACCURACY_WINDOW_DAYS as i64);
+        let scored: Vec<(String, String, f64, f64)> = self.db.query_map(
+            "SELECT item_id, model_type, predicted_demand, actual_demand
+             FROM forecast_predictions
+             WHERE actual_demand IS NOT NULL AND target_date >= ? AND target_date < ?",
+            params![window_start.to_string(), today.to_string()],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )?;
+        
+        let mut pairs: HashMap<(String, ForecastModel), Vec<(f64, f64)>> = HashMap::new();
+        for (item_id, model, predicted, actual) in scored {
+            let Some(model) = ForecastModel::parse(&model) else {
+                continue;
+            };
+            pairs.entry((item_id, model)).or_default().push((predicted, actual));
+            pairs.entry((ALL_ITEMS.to_string(), model)).or_default().push((predicted, actual));
+        }
+        
+        let ratings: Vec<(String, ModelAccuracy)> = pairs.iter()
+            .filter_map(|((item_id, model), pairs)| {
+                score_predictions(*model, pairs).map(|accuracy| (item_id.clone(), accuracy))
+            })
+            .collect();
+        let rated_items = ratings.iter()
+            .filter(|(item_id, _)| item_id != ALL_ITEMS)
+            .map(|(item_id, _)| item_id)
+            .collect::<std::collections::HashSet<_>>()
+            .len();
+        
+        self.db.transaction(|conn| {
+            // Items that dropped below the sample minimum lose today's rating
+            conn.execute("DELETE FROM forecast_accuracy WHERE evaluated_on = ?", params![today.to_string()])?;
+            for (item_id, accuracy) in &ratings {
+                conn.execute(
+                    "INSERT INTO forecast_accuracy (item_id, model_type, evaluated_on, samples, mape, bias, mae)
+                     VALUES (?, ?, ?, ?, ?, ?, ?)",
+                    params![
+                        item_id,
+                        accuracy.model_type.as_str(),
+                        today.to_string(),
+                        accuracy.samples,
+                        accuracy.mape,
+                        accuracy.bias,
+                        accuracy.mae,
+                    ],
+                )?;
+            }
+            Ok(())
+        })?;
+        
+        info!(
+            "Evaluated {} forecast predictions; {} items rated",
+            evaluated_predictions, rated_items
+        );
+        Ok(ForecastEvaluation { evaluated_predictions, rated_items })
+    }
+    
+    /// Rolling forecast accuracy of an item, or across all items, with the
+    /// models compared
+    pub async fn get_forecast_accuracy(&self, sku: Option<&str>) -> Result<ForecastAccuracy> {
+        let item_id = match sku {
+            Some(sku) => self.db.query_row(
+                "SELECT id FROM inventory_items WHERE sku = ?",
+                params![sku],
+                |row| row.get::<_, String>(0),
+            )?.ok_or_else(|| WmsError::not_found(format!("Item {} not found", sku)))?,
+            None => ALL_ITEMS.to_string(),
+        };
+        
+        let evaluations = self.db.query_map(
+            "SELECT evaluated_on, model_type, samples, mape, bias, mae
+             FROM forecast_accuracy
+             WHERE item_id = ?
+             ORDER BY evaluated_on",
+            params![item_id],
+            |row| Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, u32>(2)?,
+                row.get::<_, Option<f64>>(3)?,
+                row.get::<_, f64>(4)?,
+                row.get::<_, f64>(5)?,
+            )),
+        )?
+        .into_iter()
+        .filter_map(|(date, model, samples, mape, bias, mae)| {
+            let date = NaiveDate::parse_from_str(&date, "%Y-%m-%d").ok()?;
+            let model_type = ForecastModel::parse(&model)?;
+            Some((date, ModelAccuracy { model_type, samples, mape, bias, mae }))
+        })
+        .collect();
+        
+        Ok(ForecastAccuracy::from_evaluations(sku.map(String::from), evaluations))
+    }
+    
+    /// Evaluate forecasts every `interval` until shutdown
+    pub async fn run_forecast_evaluator(self: Arc<Self>, interval: std::time::Duration, task: TaskHandle) {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {}
+                _ = task.shutdown_requested() => break,
+            }
+            if let Err(e) = self.evaluate_forecasts().await {
+                warn!("Unable to evaluate forecasts: {}", e);
+            }
+        }
+        info!("Forecast evaluator stopped");
+    }
+    
+    /// Store a forecast's daily predictions along with what each other
+    /// model would have predicted from the same history. The primary
+    /// predictions also replace the item's cached forecast.
+    fn record_predictions(&self, sku: &str, history: &[f64], result: &ForecastResult) -> Result<()> {
+        let Some(item_id) = self.db.query_row(
+            "SELECT id FROM inventory_items WHERE sku = ?",
+            params![sku],
+            |row| row.get::<_, String>(0),
+        )? else {
+            return Ok(());
+        };
+        
+        let today = self.clock.today();
+        let days_ahead = result.predictions.len() as u32;
+        let mut predictions = vec![(result.model_type, result.predictions.clone())];
+        for model in ForecastModel::ALL {
+            if model != result.model_type {
+                predictions.push((model, self.forecast_engine.predict(model, history, days_ahead)?));
+            }
+        }
+        
+        self.db.transaction(|conn| {
+            for (model, values) in &predictions {
+                for (offset, predicted) in values.iter().enumerate() {
+                    conn.execute(
+                        "INSERT INTO forecast_predictions (
+                            id, item_id, model_type, forecast_date, target_date, horizon_days, predicted_demand
+                        ) VALUES (?, ?, ?, ?, ?, ?, ?)
+                        ON CONFLICT(item_id, model_type, forecast_date, target_date)
+                        DO UPDATE SET predicted_demand = excluded.predicted_demand",
+                        params![
+                            new_id(),
+                            &item_id,
+                            model.as_str(),
+                            today.to_string(),
+                            (today + Duration::days(offset as i64)).to_string(),
+                            offset as i64,
+                            predicted.max(0.0),
+                        ],
+                    )?;
+                }
+            }
+            
+            for (offset, predicted) in result.predictions.iter().enumerate() {
+                conn.execute(
+                    "INSERT INTO inventory_forecasts (
+                        id, item_id, forecast_date, predicted_demand, confidence_lower, confidence_upper, model_type
+                    ) VALUES (?, ?, ?, ?, ?, ?, ?)
+                    ON CONFLICT(item_id, forecast_date) DO UPDATE SET
+                        predicted_demand = excluded.predicted_demand,
+                        confidence_lower = excluded.confidence_lower,
+                        confidence_upper = excluded.confidence_upper,
+                        model_type = excluded.model_type,
+                        created_at = CURRENT_TIMESTAMP",
+                    params![
+                        new_id(),
+                        &item_id,
+                        (today + Duration::days(offset as i64)).to_string(),
+                        predicted,
+                        result.lower_bound.get(offset),
+                        result.upper_bound.get(offset),
+                        result.model_type.as_str(),
+                    ],
+                )?;
+            }
+            Ok(())
+        })?;
+        
+        debug!("Recorded {}-day forecast for {}", days_ahead, sku);
+        Ok(())
+    }
+    
+    // ============ Variant Operations ============
+    
+    /// Create a variant of `parent_id` with the given attribute values. The
+    /// variant copies the parent's item details; its SKU and name are derived
+    /// from the parent's using `rules`.
+    pub async fn create_variant(
+        &self,
+        parent_id: &str,
+        attributes: VariantAttributes,
+        rules: &VariantSkuRules,
+    ) -> Result<InventoryItem> {
+        let parent = self.get_variant_parent(parent_id).await?;
+        if self.find_variant(&parent.id, &attributes)?.is_some() {
+            return Err(WmsError::conflict(format!(
+                "{} already has a variant {}",
+                parent.sku,
+                rules.variant_name(&parent.name, &attributes)
+            )));
+        }
+        self.insert_variant(&parent, attributes, rules).await
+    }
+    
+    /// Create a variant for every combination of the axis values, e.g. each
+    /// size in each color. Combinations that already exist are skipped.
+    pub async fn create_variant_matrix(
+        &self,
+        parent_id: &str,
+        axes: Vec<VariantAxis>,
+        rules: &VariantSkuRules,
+    ) -> Result<VariantMatrixResult> {
+        if axes.is_empty() || axes.iter().any(|a| a.values.is_empty()) {
+            return Err(WmsError::validation("Each variant attribute needs at least one value"));
+        }
+        let parent = self.get_variant_parent(parent_id).await?;
+        
+        let mut pending = Vec::new();
+        let mut skipped = Vec::new();
+        for attributes in variant_combinations(&axes) {
+            if self.find_variant(&parent.id, &attributes)?.is_some() {
+                skipped.push(attributes);
+            } else {
+                pending.push(attributes);
+            }
+        }
+        
+        // Check every generated SKU first so a clash doesn't leave half a matrix
+        for attributes in &pending {
+            let sku = rules.variant_sku(&parent.sku, attributes);
+            if self.get_item_by_sku(&sku, None).await?.is_some() {
+                return Err(WmsError::conflict(format!("SKU {} already exists", sku)));
+            }
+        }
+        
+        let mut created = Vec::with_capacity(pending.len());
+        for attributes in pending {
+            created.push(self.insert_variant(&parent, attributes, rules).await?);
+        }
+        
+        info!("Created {} variants of {} ({} existing skipped)", created.len(), parent.sku, skipped.len());
+        Ok(VariantMatrixResult { created, skipped })
+    }
+    
+    /// Variants of a parent item, by SKU
+    pub async fn get_variants(&self, parent_id: &str) -> Result<Vec<InventoryItem>> {
+        self.db.query_map(
+            "SELECT i.*, COALESCE(SUM(s.quantity), 0) as total_qty
+             FROM inventory_items i
+             LEFT JOIN inventory_stock s ON i.id = s.item_id
+             WHERE i.parent_item_id = ?
+             GROUP BY i.id
+             ORDER BY i.sku",
+            params![parent_id],
+            |row| Self::row_to_item(row),
+        )
+    }
+    
+    /// Stock of an item's parent and all its variants, rolled up. Given a
+    /// variant, reports on its parent.
+    pub async fn get_variant_stock(&self, item_id: &str) -> Result<VariantStockSummary> {
+        let item = self.get_item_by_id(item_id).await?
+            .ok_or_else(|| WmsError::not_found(format!("Item {} not found", item_id)))?;
+        let parent = match &item.parent_item_id {
+            Some(parent_id) => self.get_item_by_id(parent_id).await?
+                .ok_or_else(|| WmsError::not_found(format!("Item {} not found", parent_id)))?,
+            None => item,
+        };
+        let variants = self.get_variants(&parent.id).await?;
+        
+        Ok(VariantStockSummary::new(&parent, &variants))
+    }
+    
+    /// Search active items by SKU, name, barcode or text custom fields.
+    /// With `group_by_parent`, matching variants are returned as their
+    /// parent, whose quantity includes all its variants.
+    pub async fn search_items(&self, query: ItemSearchQuery) -> Result<Vec<InventoryItem>> {
+        let offset = (query.page.saturating_sub(1)) * query.page_size;
+        let mut params_vec: Vec<String> = Vec::new();
+        let text_match = |alias: &str| format!(
+            "({a}.sku LIKE '%' || ? || '%' OR {a}.name LIKE '%' || ? || '%' OR {a}.barcode = ? OR {custom})",
+            a = alias,
+            custom = search_condition(CustomFieldEntity::InventoryItem, alias),
+        );
+        
+        let mut sql = if query.group_by_parent {
+            format!(
+                "SELECT i.*, {} FROM inventory_items i
+                 WHERE i.is_active = 1 AND i.parent_item_id IS NULL",
+                ROLLED_UP_QTY
+            )
+        } else {
+            String::from(
+                "SELECT i.*, COALESCE((SELECT SUM(s.quantity) FROM inventory_stock s
+                    WHERE s.item_id = i.id), 0) AS total_qty
+                 FROM inventory_items i
+                 WHERE i.is_active = 1"
+            )
+        };
+        
+        if let Some(text) = query.query.as_deref().map(str::trim).filter(|q| !q.is_empty()) {
+            if query.group_by_parent {
+                sql.push_str(&format!(
+                    " AND EXISTS (SELECT 1 FROM inventory_items m
+                        WHERE (m.id = i.id OR m.parent_item_id = i.id) AND m.is_active = 1 AND {})",
+                    text_match("m")
+                ));
+            } else {
+                sql.push_str(&format!(" AND {}", text_match("i")));
+            }
+            params_vec.extend(std::iter::repeat_n(text.to_string(), 4));
+        }
+        
+        if let Some(category) = &query.category {
+            sql.push_str(" AND i.category = ?");
+            params_vec.push(category.clone());
+        }
+        
+        sql.push_str(&format!(" ORDER BY i.sku LIMIT {} OFFSET {}", query.page_size, offset));
+        
+        self.db.query_map(
+            &sql,
+            rusqlite::params_from_iter(params_vec.iter()),
+            |row| Self::row_to_item(row),
+        )
+    }
+    
+    /// Items below their reorder point, grouped under their parent item.
+    /// Parents carry the rolled-up quantity of all their variants; items
+    /// without a parent form a group of their own.
+    pub async fn get_low_stock_by_parent(&self) -> Result<Vec<LowStockGroup>> {
+        let mut groups: Vec<LowStockGroup> = Vec::new();
+        let mut group_index: HashMap<String, usize> = HashMap::new();
+        
+        for item in self.get_low_stock_items(None).await? {
+            let parent_id = item.parent_item_id.clone().unwrap_or_else(|| item.id.clone());
+            let index = match group_index.get(&parent_id) {
+                Some(&index) => index,
+                None => {
+                    let parent = self.db.query_row(
+                        &format!("SELECT i.*, {} FROM inventory_items i WHERE i.id = ?", ROLLED_UP_QTY),
+                        params![&parent_id],
+                        |row| Self::row_to_item(row),
+                    )?.ok_or_else(|| WmsError::not_found(format!("Item {} not found", parent_id)))?;
+                    groups.push(LowStockGroup { parent, variants: Vec::new() });
+                    group_index.insert(parent_id, groups.len() - 1);
+                    groups.len() - 1
+                }
+            };
+            if item.parent_item_id.is_some() {
+                groups[index].variants.push(item);
+            }
+        }
+        
+        Ok(groups)
+    }
+    
+    /// Load an item that variants can be created under
+    async fn get_variant_parent(&self, parent_id: &str) -> Result<InventoryItem> {
+        let parent = self.get_item_by_id(parent_id).await?
+            .ok_or_else(|| WmsError::not_found(format!("Item {} not found", parent_id)))?;
+        if parent.parent_item_id.is_some() {
+            return Err(WmsError::validation(format!(
+                "{} is itself a variant; variants cannot have variants", parent.sku
+            )));
+        }
+        Ok(parent)
+    }
+    
+    /// Id of the parent's variant with exactly these attribute values
+    fn find_variant(&self, parent_id: &str, attributes: &VariantAttributes) -> Result<Option<String>> {
+        self.db.query_row(
+            "SELECT id FROM inventory_items WHERE parent_item_id = ? AND variant_attributes = ?",
+            params![parent_id, attributes_json(attributes)],
+            |row| row.get(0),
+        )
+    }
+    
+    async fn insert_variant(
+        &self,
+        parent: &InventoryItem,
+        attributes: VariantAttributes,
+        rules: &VariantSkuRules,
+    ) -> Result<InventoryItem> {
+        if attributes.is_empty()
+            || attributes.iter().any(|(name, value)| name.trim().is_empty() || value.trim().is_empty())
+        {
+            return Err(WmsError::validation("Variant attributes need a name and a value"));
+        }
+        
+        let mut variant = parent.clone();
+        variant.sku = rules.variant_sku(&parent.sku, &attributes);
+        variant.name = rules.variant_name(&parent.name, &attributes);
+        variant.barcode = None;
+        variant.barcode_type = None;
+        variant.parent_item_id = Some(parent.id.clone());
+        variant.variant_attributes = attributes;
+        variant.updated_at = None;
+        variant.total_quantity = None;
+        
+        self.create_item(variant).await
+    }
+    
+    // ============ Lot Operations ============
+    
+    /// On-hand balance per lot of an item, earliest expiry first
+    pub async fn get_lots(&self, item_id: &str) -> Result<Vec<LotBalance>> {
+        let today = self.clock.now().date_naive();
+        self.db.query_map(
+            &format!(
+                "SELECT s.lot_number, MIN(s.expiry_date) AS expiry_date, SUM(s.quantity) AS quantity,
+                        COUNT(DISTINCT s.location_id) AS location_count,
+                        CASE WHEN {} THEN 0 ELSE 1 END AS on_hold
+                 FROM inventory_stock s
+                 WHERE s.item_id = ? AND s.quantity > 0
+                 GROUP BY COALESCE(s.lot_number, '')
+                 ORDER BY MIN(s.expiry_date) IS NULL, date(MIN(s.expiry_date)), COALESCE(s.lot_number, '')",
+                NOT_HELD
+            ),
+            params![item_id],
+            |row| {
+                let expiry_date = parse_expiry(row.get("expiry_date")?);
+                Ok(LotBalance {
+                    item_id: item_id.to_string(),
+                    lot_number: row.get::<_, Option<String>>("lot_number")?.filter(|l| !l.is_empty()),
+                    expiry_date,
+                    quantity: row.get("quantity")?,
+                    location_count: row.get("location_count")?,
+                    expired: expiry_date.is_some_and(|d| d < today),
+                    on_hold: row.get::<_, i64>("on_hold")? != 0,
+                })
+            },
+        )
+    }
+    
+    /// Stock expiring within `days_ahead` days, including stock that has
+    /// already expired, soonest first
+    pub async fn get_expiring_stock(&self, days_ahead: u32) -> Result<Vec<ExpiringStock>> {
+        let today = self.clock.now().date_naive();
+        let until = today + Duration::days(days_ahead as i64);
+        let rows = self.db.query_map(
+            "SELECT s.item_id, i.sku, i.name, s.location_id, l.code, s.lot_number, s.expiry_date, s.quantity
+             FROM inventory_stock s
+             JOIN inventory_items i ON s.item_id = i.id
+             JOIN locations l ON s.location_id = l.id
+             WHERE s.quantity > 0 AND s.expiry_date IS NOT NULL AND date(s.expiry_date) <= ?
+             ORDER BY date(s.expiry_date), i.sku, l.code",
+            params![until.to_string()],
+            |row| {
+                let Some(expiry_date) = parse_expiry(row.get("expiry_date")?) else {
+                    return Ok(None);
+                };
+                Ok(Some(ExpiringStock {
+                    item_id: row.get("item_id")?,
+                    sku: row.get("sku")?,
+                    item_name: row.get("name")?,
+                    location_id: row.get("location_id")?,
+                    location_code: row.get("code")?,
+                    lot_number: row.get::<_, Option<String>>("lot_number")?.filter(|l| !l.is_empty()),
+                    expiry_date,
+                    quantity: row.get("quantity")?,
+                    days_until_expiry: (expiry_date - today).num_days(),
+                }))
+            },
+        )?;
+        
+        Ok(rows.into_iter().flatten().collect())
+    }
+    
+    /// Where to pick `quantity` of an item from, first-expired first-out:
+    /// (location, lot, quantity) allocations in the order to take them.
+    /// Held lots are skipped, and so are expired lots unless
+    /// `include_expired` is set. Fails with the shortfall when the stock
+    /// left can't cover the quantity.
+    pub async fn suggest_pick_lots(
+        &self,
+        item_id: &str,
+        quantity: f64,
+        include_expired: bool,
+    ) -> Result<Vec<StockAllocation>> {
+        if quantity <= 0.0 {
+            return Err(WmsError::validation("Quantity to pick must be positive"));
+        }
+        let item = self.get_item_by_id(item_id).await?
+            .ok_or_else(|| WmsError::not_found("Item not found"))?;
+        let today = self.clock.now().date_naive();
+        
+        let allocations = self.allocator
+            .allocate(item_id, quantity, (!include_expired).then_some(today))
+            .await?;
+        let allocated: f64 = allocations.iter().map(|a| a.quantity).sum();
+        if allocated + 1e-9 < quantity {
+            let expired: f64 = if include_expired {
+                0.0
+            } else {
+                self.db.query_row(
+                    &format!(
+                        "SELECT COALESCE(SUM(s.quantity), 0) FROM inventory_stock s
+                         JOIN locations l ON s.location_id = l.id
+                         WHERE s.item_id = ? AND s.quantity > 0 AND l.is_active = 1
+                           AND date(s.expiry_date) < ? AND {}",
+                        NOT_HELD
+                    ),
+                    params![item_id, today.to_string()],
+                    |row| row.get(0),
+                )?.unwrap_or(0.0)
+            };
+            let mut message = format!(
+                "Short {} of {}: {} requested, {} available to pick",
+                quantity - allocated, item.sku, quantity, allocated
+            );
+            if expired > 0.0 {
+                message.push_str(&format!(" ({} more is expired)", expired));
+            }
+            return Err(WmsError::validation(message));
+        }
+        
+        Ok(allocations)
+    }
+    
+    // ============ Quality Hold Operations ============
+    
+    /// Quarantine a lot. Held stock stays on hand but cannot be allocated,
+    /// picked or transferred until the hold is released.
+    pub async fn place_hold(
+        &self,
+        ctx: &RequestContext,
+        item_id: &str,
+        lot_number: &str,
+        reason: &str,
+    ) -> Result<QualityHold> {
+        let user_id = ctx.actor()?;
+        if lot_number.trim().is_empty() {
+            return Err(WmsError::validation("Lot number is required"));
+        }
+        if reason.trim().is_empty() {
+            return Err(WmsError::validation("Hold reason is required"));
+        }
+        
+        let item = self.get_item_by_id(item_id).await?
+            .ok_or_else(|| WmsError::not_found(format!("Item {} not found", item_id)))?;
+        
+        if self.allocator.active_hold(item_id, lot_number).await?.is_some() {
+            return Err(WmsError::conflict(format!(
+                "Lot {} of {} is already on hold", lot_number, item.sku
+            )));
+        }
+        
+        let hold = QualityHold {
+            id: new_id(),
+            item_id: item_id.to_string(),
+            lot_number: lot_number.to_string(),
+            reason: reason.trim().to_string(),
+            placed_by: user_id.to_string(),
+            placed_at: self.clock.now(),
+            released_by: None,
+            released_at: None,
+            disposition: None,
+            notes: None,
+        };
+        
+        self.db.execute(
+            "INSERT INTO quality_holds (id, item_id, lot_number, reason, placed_by, placed_at)
+             VALUES (?, ?, ?, ?, ?, ?)",
+            params![
+                &hold.id,
+                &hold.item_id,
+                &hold.lot_number,
+                &hold.reason,
+                &hold.placed_by,
+                hold.placed_at.to_rfc3339(),
+            ],
+        )?;
+        
+        info!("Placed quality hold on {} lot {}: {}", item.sku, lot_number, hold.reason);
+        Ok(hold)
+    }
+    
+    /// Release a hold with a disposition. Scrapping posts a scrap
+    /// adjustment for the lot's stock at every location.
+    pub async fn release_hold(
+        &self,
+        ctx: &RequestContext,
+        hold_id: &str,
+        disposition: HoldDisposition,
+        notes: Option<String>,
+    ) -> Result<QualityHold> {
+        let user_id = ctx.actor()?;
+        let mut hold = self.get_hold(hold_id).await?
+            .ok_or_else(|| WmsError::not_found(format!("Quality hold {} not found", hold_id)))?;
+        
+        if !hold.is_active() {
+            return Err(WmsError::conflict(format!("Quality hold {} is already released", hold_id)));
+        }
+        
+        if disposition == HoldDisposition::Scrap {
+            let stock: Vec<(String, f64)> = self.db.query_map(
+                "SELECT location_id, quantity FROM inventory_stock
+                 WHERE item_id = ? AND lot_number = ? AND quantity > 0",
+                params![&hold.item_id, &hold.lot_number],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )?;
+            
+            for (location_id, quantity) in stock {
+                self.adjust_quantity(InventoryAdjustment {
+                    item_id: hold.item_id.clone(),
+                    location_id: Some(location_id),
+                    warehouse_id: None,
+                    adjustment_type: AdjustmentType::Scrap,
+                    quantity,
+                    lot_number: Some(hold.lot_number.clone()),
+                    reason_code: Some("QUALITY_HOLD".to_string()),
+                    notes: Some(hold.reason.clone()),
+                    user_id: user_id.to_string(),
+                }).await?;
+            }
+        }
+        
+        hold.released_by = Some(user_id.to_string());
+        hold.released_at = Some(self.clock.now());
+        hold.disposition = Some(disposition);
+        hold.notes = notes;
+        
+        self.db.execute(
+            "UPDATE quality_holds SET released_by = ?, released_at = ?, disposition = ?, notes = ?
+             WHERE id = ?",
+            params![
+                &hold.released_by,
+                hold.released_at.map(|t| t.to_rfc3339()),
+                disposition.as_str(),
+                &hold.notes,
+                &hold.id,
+            ],
+        )?;
+        
+        info!("Released quality hold {} ({})", hold.id, disposition.as_str());
+        Ok(hold)
+    }
+    
+    /// Get a quality hold by ID
+    pub async fn get_hold(&self, hold_id: &str) -> Result<Option<QualityHold>> {
+        self.db.query_row(
+            "SELECT * FROM quality_holds WHERE id = ?",
+            params![hold_id],
+            row_to_hold,
+        )
+    }
+    
+    /// List quality holds, newest first, optionally for one item
+    pub async fn list_holds(&self, item_id: Option<&str>, include_released: bool) -> Result<Vec<QualityHold>> {
+        self.db.query_map(
+            "SELECT * FROM quality_holds
+             WHERE (?1 IS NULL OR item_id = ?1)
+               AND (?2 OR released_at IS NULL)
+             ORDER BY placed_at DESC",
+            params![item_id, include_released],
+            row_to_hold,
+        )
+    }
+    
+    /// Stock per location and lot, with held quantities shown separately
+    /// from what is available
+    pub async fn get_item_stock_by_location(&self, item_id: &str) -> Result<Vec<LocationStock>> {
+        self.db.query_map(
+            "SELECT s.location_id, l.code, s.quantity, s.lot_number, h.reason
+             FROM inventory_stock s
+             JOIN locations l ON s.location_id = l.id
+             LEFT JOIN quality_holds h ON h.item_id = s.item_id
+                AND h.lot_number = s.lot_number AND h.released_at IS NULL
+             WHERE s.item_id = ? AND s.quantity != 0
+             ORDER BY l.code, s.lot_number",
+            params![item_id],
+            |row| {
+                let quantity: f64 = row.get("quantity")?;
+                let hold_reason: Option<String> = row.get("reason")?;
+                let held_quantity = if hold_reason.is_some() { quantity } else { 0.0 };
+                
+                Ok(LocationStock {
+                    location_id: row.get("location_id")?,
+                    location_code: row.get("code")?,
+                    quantity,
+                    lot_number: row.get::<_, Option<String>>("lot_number")?.filter(|l| !l.is_empty()),
+                    held_quantity,
+                    available_quantity: quantity - held_quantity,
+                    hold_reason,
+                })
+            },
+        )
+    }
+    
+    // ============ Warehouse Operations ============
+    
+    /// All warehouses, by code
+    pub async fn list_warehouses(&self) -> Result<Vec<Warehouse>> {
+        self.db.query_map(
+            "SELECT * FROM warehouses ORDER BY code",
+            [],
+            Self::row_to_warehouse,
+        )
+    }
+    
+    /// Add a warehouse. Codes are unique.
+    pub async fn create_warehouse(&self, code: &str, name: &str, address: Option<&str>) -> Result<Warehouse> {
+        let code = code.trim();
+        if code.is_empty() {
+            return Err(WmsError::validation("Warehouse code is required"));
+        }
+        let taken: Option<String> = self.db.query_row(
+            "SELECT id FROM warehouses WHERE code = ?",
+            params![code],
+            |row| row.get(0),
+        )?;
+        if taken.is_some() {
+            return Err(WmsError::conflict(format!("Warehouse {} already exists", code)));
+        }
+        
+        let id = new_id();
+        self.db.execute(
+            "INSERT INTO warehouses (id, code, name, address) VALUES (?, ?, ?, ?)",
+            params![id, code, name, address],
+        )?;
+        info!("Created warehouse {}", code);
+        
+        self.db.query_row("SELECT * FROM warehouses WHERE id = ?", params![id], Self::row_to_warehouse)?
+            .ok_or_else(|| WmsError::not_found("Warehouse not found"))
+    }
+    
+    /// Move a location into another warehouse. Its stock goes with it.
+    pub async fn set_location_warehouse(&self, location_id: &str, warehouse_id: &str) -> Result<()> {
+        let exists: Option<String> = self.db.query_row(
+            "SELECT id FROM warehouses WHERE id = ?",
+            params![warehouse_id],
+            |row| row.get(0),
+        )?;
+        if exists.is_none() {
+            return Err(WmsError::not_found(format!("Warehouse {} not found", warehouse_id)));
+        }
+        
+        let updated = self.db.execute(
+            "UPDATE locations SET warehouse_id = ? WHERE id = ?",
+            params![warehouse_id, location_id],
+        )?;
+        if updated == 0 {
+            return Err(WmsError::not_found(format!("Location {} not found", location_id)));
+        }
+        Ok(())
+    }
+    
+    /// Fail unless the location is in the given warehouse
+    async fn check_location_warehouse(&self, location_id: &str, warehouse_id: &str) -> Result<()> {
+        let actual: String = self.db.query_row(
+            "SELECT warehouse_id FROM locations WHERE id = ?",
+            params![location_id],
+            |row| row.get(0),
+        )?
+        .ok_or_else(|| WmsError::not_found(format!("Location {} not found", location_id)))?;
+        
+        if actual != warehouse_id {
+            return Err(WmsError::validation(format!(
+                "Location {} is in warehouse {}, not {}",
+                location_id, actual, warehouse_id
+            )));
+        }
+        Ok(())
+    }
+    
+    // ============ Stock Transfer Operations ============
+    
+    /// Move `quantity` of an item's lot (or its unlotted stock) from one
+    /// location to another. Both halves are stored together as a
+    /// TRANSFER_OUT and a TRANSFER_IN sharing a transfer id; their previous
+    /// and new quantities are the balances at each location. Moving into
+    /// quarantine is allowed and flagged on the result.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn transfer_stock(
+        &self,
+        item_id: &str,
+        from_location_id: &str,
+        to_location_id: &str,
+        quantity: f64,
+        lot_number: Option<&str>,
+        warehouse_id: Option<&str>,
+        user_id: &str,
+    ) -> Result<StockTransfer> {
+        if !(quantity > 0.0 && quantity.is_finite()) {
+            return Err(WmsError::validation("Transfer quantity must be positive"));
+        }
+        if from_location_id == to_location_id {
+            return Err(WmsError::validation("Source and destination locations are the same"));
+        }
+        let lot_number = lot_number.filter(|lot| !lot.is_empty());
+        if let Some(warehouse_id) = warehouse_id {
+            self.check_location_warehouse(from_location_id, warehouse_id).await?;
+            self.check_location_warehouse(to_location_id, warehouse_id).await?;
+        }
+        
+        let item = self.get_item_by_id(item_id).await?
+            .ok_or_else(|| WmsError::not_found("Item not found"))?;
+        let lot_key = lot_number.unwrap_or_default();
+        let transfer_id = new_id();
+        let transferred_at = self.clock.now();
+        
+        // Capacity, holds and both balances are read in the transaction
+        // that moves the stock, so a pick or another transfer committed
+        // meanwhile can't be spent twice.
+        let (transfer, destination) = self.db.transaction(|conn| {
+            let destination = conn.query_row(
+                &format!("SELECT l.*, {} FROM locations l WHERE l.id = ?", STOCKED_UNITS),
+                params![to_location_id],
+                Self::row_to_stocked_location,
+            )
+            .optional()?
+            .ok_or_else(|| WmsError::not_found(format!("Location {} not found", to_location_id)))?;
+            if !destination.is_active {
+                return Err(WmsError::validation(format!("Location {} is inactive", destination.code)));
+            }
+            if !destination.has_capacity(quantity) {
+                return Err(WmsError::validation(format!(
+                    "Location {} has room for {} more units",
+                    destination.code,
+                    destination.capacity_units.unwrap_or_default() - destination.current_units,
+                )));
+            }
+            
+            if let Some(lot) = lot_number
+                && let Some(hold) = conn.query_row(
+                    "SELECT * FROM quality_holds
+                     WHERE item_id = ? AND lot_number = ? AND released_at IS NULL",
+                    params![item_id, lot],
+                    row_to_hold,
+                ).optional()?
+            {
+                return Err(WmsError::validation(format!(
+                    "Lot {} of {} is on quality hold: {}",
+                    hold.lot_number, item.sku, hold.reason
+                )));
+            }
+            
+            let (available, expiry_date): (f64, Option<String>) = conn.query_row(
+                "SELECT COALESCE(SUM(quantity), 0), MAX(expiry_date) FROM inventory_stock
+                 WHERE item_id = ? AND location_id = ? AND COALESCE(lot_number, '') = ?",
+                params![item_id, from_location_id, lot_key],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )?;
+            if quantity > available + 1e-9 {
+                let what = lot_number.map(|lot| format!("of lot {} ", lot)).unwrap_or_default();
+                return Err(WmsError::validation(format!(
+                    "Only {} {}available at the source location", available, what
+                )));
+            }
+            let at_destination: f64 = conn.query_row(
+                "SELECT COALESCE(SUM(quantity), 0) FROM inventory_stock
+                 WHERE item_id = ? AND location_id = ? AND COALESCE(lot_number, '') = ?",
+                params![item_id, to_location_id, lot_key],
+                |row| row.get(0),
+            )?;
+            
+            let transfer = StockTransfer {
+                transfer_id: transfer_id.clone(),
+                item_id: item_id.to_string(),
+                from_location_id: from_location_id.to_string(),
+                to_location_id: to_location_id.to_string(),
+                quantity,
+                lot_number: lot_number.map(str::to_string),
+                remaining_at_source: available - quantity,
+                to_quarantine: destination.zone == LocationZone::Quarantine,
+                transferred_by: user_id.to_string(),
+                transferred_at,
+            };
+            let legs = [
+                (AdjustmentType::TransferOut, from_location_id, available),
+                (AdjustmentType::TransferIn, to_location_id, at_destination),
+            ].map(|(transaction_type, location_id, previous)| InventoryTransaction {
+                id: new_id(),
+                item_id: item_id.to_string(),
+                location_id: Some(location_id.to_string()),
+                transaction_type,
+                quantity,
+                previous_quantity: Some(previous),
+                new_quantity: Some(previous + quantity * transaction_type.sign()),
+                reference_type: None,
+                reference_id: None,
+                lot_number: transfer.lot_number.clone(),
+                reason_code: Some("TRANSFER".to_string()),
+                notes: None,
+                transfer_id: Some(transfer_id.clone()),
+                user_id: user_id.to_string(),
+                created_at: transferred_at,
+            });
+            
+            Self::take_stock(conn, item_id, from_location_id, lot_key, quantity)?;
+            conn.execute(
+                "INSERT INTO inventory_stock (id, item_id, location_id, quantity, lot_number, expiry_date, updated_at)
+                 VALUES (?, ?, ?, ?, ?, ?, datetime('now'))
+                 ON CONFLICT(item_id, location_id, lot_number) DO UPDATE SET
+                    quantity = quantity + excluded.quantity,
+                    updated_at = datetime('now')",
+                params![new_id(), item_id, to_location_id, quantity, lot_key, &expiry_date],
+            )?;
+            
+            for entry in &legs {
+                conn.execute(
+                    "INSERT INTO inventory_transactions (
+                        id, item_id, location_id, transaction_type, quantity,
+                        previous_quantity, new_quantity, lot_number, reason_code,
+                        transfer_id, user_id, created_at
+                    ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, datetime('now'))",
+                    params![
+                        &entry.id,
+                        &entry.item_id,
+                        &entry.location_id,
+                        entry.transaction_type.as_str(),
+                        entry.quantity,
+                        entry.previous_quantity,
+                        entry.new_quantity,
+                        &entry.lot_number,
+                        &entry.reason_code,
+                        &entry.transfer_id,
+                        &entry.user_id,
+                    ],
+                )?;
+                self.changes.record_model(conn, "inventory_transactions", &entry.id, ChangeKind::Insert, entry)?;
+            }
+            Ok((transfer, destination))
+        })?;
+        
+        if transfer.to_quarantine {
+            warn!(
+                "Transferred {} of {} into quarantine location {} (user: {})",
+                quantity, item.sku, destination.code, user_id
+            );
+        } else {
+            info!(
+                "Transferred {} of {} from {} to {} (user: {})",
+                quantity, item.sku, from_location_id, destination.code, user_id
+            );
+        }
+        Ok(transfer)
+    }
+    
+    // ============ Cycle Count Operations ============
+    
+    /// Start a count of a zone (e.g. `STORAGE`) or of one location, by id or
+    /// code, listing what is on hand there per item and lot. `abc_class`
+    /// limits the sheet to items of that class.
+    pub async fn create_count_sheet(
+        &self,
+        zone_or_location: &str,
+        abc_class: Option<AbcClass>,
+        created_by: &str,
+    ) -> Result<CountSheet> {
+        let (zone, location_id) = match LocationZone::parse(&zone_or_location.to_uppercase()) {
+            Some(zone) => (Some(zone), None),
+            None => {
+                let location_id: String = self.db.query_row(
+                    "SELECT id FROM locations WHERE id = ?1 OR code = ?1",
+                    params![zone_or_location],
+                    |row| row.get(0),
+                )?.ok_or_else(|| WmsError::not_found(format!("No zone or location {}", zone_or_location)))?;
+                (None, Some(location_id))
+            }
+        };
+        
+        let stock: Vec<(String, String, String, f64)> = self.db.query_map(
+            "SELECT s.item_id, s.location_id, COALESCE(s.lot_number, '') AS lot, SUM(s.quantity)
+             FROM inventory_stock s
+             JOIN locations l ON s.location_id = l.id
+             JOIN inventory_items i ON s.item_id = i.id
+             WHERE l.is_active = 1
+               AND (l.zone = ?1 OR l.id = ?2)
+               AND (?3 IS NULL OR i.abc_class = ?3)
+             GROUP BY s.item_id, s.location_id, lot
+             HAVING SUM(s.quantity) != 0
+             ORDER BY l.code, i.sku, lot",
+            params![zone.map(|z| z.as_str()), &location_id, abc_class.map(|c| c.as_str())],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )?;
+        if stock.is_empty() {
+            return Err(WmsError::validation(format!("Nothing on hand to count in {}", zone_or_location)));
+        }
+        
+        let id = new_id();
+        self.db.transaction(|conn| {
+            conn.execute(
+                "INSERT INTO count_sheets (id, zone, location_id, abc_class, status, created_by, created_at)
+                 VALUES (?, ?, ?, ?, ?, ?, ?)",
+                params![
+                    &id,
+                    zone.map(|z| z.as_str()),
+                    &location_id,
+                    abc_class.map(|c| c.as_str()),
+                    CountStatus::Open.as_str(),
+                    created_by,
+                    self.clock.now().to_rfc3339(),
+                ],
+            )?;
+            for (item_id, location_id, lot_number, quantity) in &stock {
+                conn.execute(
+                    "INSERT INTO count_lines (id, count_id, item_id, location_id, lot_number, expected_quantity)
+                     VALUES (?, ?, ?, ?, ?, ?)",
+                    params![new_id(), &id, item_id, location_id, lot_number, quantity],
+                )?;
+            }
+            Ok(())
+        })?;
+        
+        info!("Created count sheet {} for {} with {} lines", id, zone_or_location, stock.len());
+        self.get_count_sheet(&id).await?
+            .ok_or_else(|| WmsError::not_found("Count sheet not found"))
+    }
+    
+    /// A count sheet with its lines
+    pub async fn get_count_sheet(&self, count_id: &str) -> Result<Option<CountSheet>> {
+        let Some(mut sheet) = self.db.query_row(
+            "SELECT * FROM count_sheets WHERE id = ?",
+            params![count_id],
+            row_to_sheet,
+        )? else {
+            return Ok(None);
+        };
+        
+        sheet.lines = self.db.query_map(
+            "SELECT c.*, i.sku, l.code
+             FROM count_lines c
+             JOIN inventory_items i ON c.item_id = i.id
+             JOIN locations l ON c.location_id = l.id
+             WHERE c.count_id = ?
+             ORDER BY l.code, i.sku, c.lot_number",
+            params![count_id],
+            row_to_count_line,
+        )?;
+        Ok(Some(sheet))
+    }
+    
+    /// Record what was counted for an item at a location. `lot_number` is
+    /// only needed when the sheet lists several lots there. A first count
+    /// outside the tolerance for the item's ABC class flags the line for a
+    /// recount; the recount stands whatever it finds.
+    pub async fn record_count(
+        &self,
+        count_id: &str,
+        item_id: &str,
+        location_id: &str,
+        lot_number: Option<&str>,
+        counted_quantity: f64,
+        user_id: &str,
+    ) -> Result<CountLine> {
+        if !(counted_quantity >= 0.0 && counted_quantity.is_finite()) {
+            return Err(WmsError::validation("Counted quantity must be zero or more"));
+        }
+        let sheet = self.get_count_sheet(count_id).await?
+            .ok_or_else(|| WmsError::not_found(format!("Count sheet {} not found", count_id)))?;
+        if sheet.status != CountStatus::Open {
+            return Err(WmsError::conflict(format!("Count sheet {} is already {}", count_id, sheet.status.as_str())));
+        }
+        
+        let mut lines = sheet.lines.into_iter().filter(|l| {
+            l.item_id == item_id
+                && l.location_id == location_id
+                && lot_number.is_none_or(|lot| l.lot_number.as_deref().unwrap_or_default() == lot)
+        });
+        let mut line = match (lines.next(), lines.next()) {
+            (Some(line), None) => line,
+            (Some(_), Some(_)) => {
+                return Err(WmsError::validation(format!(
+                    "{} has several lots at {} on this sheet; say which lot was counted", item_id, location_id
+                )));
+            }
+            (None, _) => {
+                return Err(WmsError::not_found(format!("{} at {} is not on this count sheet", item_id, location_id)));
+            }
+        };
+        
+        let class: Option<String> = self.db.query_row(
+            "SELECT abc_class FROM inventory_items WHERE id = ?",
+            params![item_id],
+            |row| row.get(0),
+        )?.flatten();
+        let tolerances = CountVarianceTolerances::load(&self.db)?;
+        
+        line.count_attempts += 1;
+        line.counted_quantity = Some(counted_quantity);
+        line.requires_recount = line.count_attempts == 1
+            && tolerances.requires_recount(class.as_deref().and_then(AbcClass::parse), line.expected_quantity, counted_quantity);
+        line.counted_by = Some(user_id.to_string());
+        line.counted_at = Some(self.clock.now());
+        
+        self.db.execute(
+            "UPDATE count_lines SET
+                counted_quantity = ?, count_attempts = ?, requires_recount = ?, counted_by = ?, counted_at = ?
+             WHERE id = ?",
+            params![
+                counted_quantity,
+                line.count_attempts,
+                line.requires_recount,
+                user_id,
+                line.counted_at.map(|t| t.to_rfc3339()),
+                &line.id,
+            ],
+        )?;
+        
+        if line.requires_recount {
+            info!("Count of {} at {} is outside the tolerance; recount needed", item_id, location_id);
+        }
+        Ok(line)
+    }
+    
+    /// Post a fully counted sheet: each variance is adjusted as a COUNT
+    /// transaction against the expected quantity, and every counted stock
+    /// row gets its last count date, all in one transaction. Lines without
+    /// a variance post no transaction. A sheet is posted once.
+    pub async fn post_count(&self, count_id: &str, user_id: &str) -> Result<CountSheet> {
+        let sheet = self.get_count_sheet(count_id).await?
+            .ok_or_else(|| WmsError::not_found(format!("Count sheet {} not found", count_id)))?;
+        if sheet.status != CountStatus::Open {
+            return Err(WmsError::conflict(format!("Count sheet {} is already {}", count_id, sheet.status.as_str())));
+        }
+        
+        let uncounted = sheet.lines.iter().filter(|l| l.counted_quantity.is_none()).count();
+        if uncounted > 0 {
+            return Err(WmsError::validation(format!("{} lines have not been counted", uncounted)));
+        }
+        let recounts: Vec<String> = sheet.lines.iter()
+            .filter(|l| l.requires_recount)
+            .map(|l| format!(
+                "{} at {}",
+                l.item_sku.as_deref().unwrap_or(&l.item_id),
+                l.location_code.as_deref().unwrap_or(&l.location_id),
+            ))
+            .collect();
+        if !recounts.is_empty() {
+            return Err(WmsError::validation(format!("Recount needed before posting: {}", recounts.join(", "))));
+        }
+        
+        // Variances are checked up front and posted with the sheet in one
+        // transaction, so a failure leaves nothing half posted
+        let lines: Vec<(&CountLine, Option<InventoryAdjustment>)> = sheet.lines.iter()
+            .filter(|l| l.posted_at.is_none())
+            .map(|line| {
+                let variance = line.variance().unwrap_or_default();
+                let adjustment = (variance.abs() > 1e-9).then(|| InventoryAdjustment {
+                    item_id: line.item_id.clone(),
+                    location_id: Some(line.location_id.clone()),
+                    warehouse_id: None,
+                    adjustment_type: AdjustmentType::Count,
+                    quantity: variance,
+                    lot_number: line.lot_number.clone(),
+                    reason_code: Some("CYCLE_COUNT".to_string()),
+                    notes: Some(format!("Count sheet {}", sheet.id)),
+                    user_id: user_id.to_string(),
+                });
+                (line, adjustment)
+            })
+            .collect();
+        for adjustment in lines.iter().filter_map(|(_, adjustment)| adjustment.as_ref()) {
+            self.check_adjustment(adjustment).await?;
+        }
+        
+        let posted_at = self.clock.now().to_rfc3339();
+        self.db.transaction(|conn| {
+            // Claim the sheet so a second post can't run alongside this one
+            let claimed = conn.execute(
+                "UPDATE count_sheets SET status = ?, posted_by = ?, posted_at = ? WHERE id = ? AND status = ?",
+                params![
+                    CountStatus::Posted.as_str(),
+                    user_id,
+                    &posted_at,
+                    count_id,
+                    CountStatus::Open.as_str(),
+                ],
+            )?;
+            if claimed == 0 {
+                return Err(WmsError::conflict(format!("Count sheet {} is already posted", count_id)));
+            }
+            for (line, adjustment) in &lines {
+                self.post_count_line(conn, line, adjustment.as_ref(), &posted_at)?;
+            }
+            Ok(())
+        })?;
+        for adjustment in lines.iter().filter_map(|(_, adjustment)| adjustment.as_ref()) {
+            self.after_adjustment(adjustment).await?;
+        }
+        
+        info!("Posted count sheet {} (user: {})", count_id, user_id);
+        self.get_count_sheet(count_id).await?
+            .ok_or_else(|| WmsError::not_found("Count sheet not found"))
+    }
+    
+    /// Post one count line in the caller's transaction: its variance, if
+    /// any, then the counted stock's last count date and the line's
+    /// posted time
+    fn post_count_line(
+        &self,
+        conn: &rusqlite::Connection,
+        line: &CountLine,
+        adjustment: Option<&InventoryAdjustment>,
+        posted_at: &str,
+    ) -> Result<()> {
+        if let Some(adjustment) = adjustment {
+            self.post_adjustment(conn, adjustment)?;
+        }
+        
+        let counted_at = line.counted_at.unwrap_or_else(|| self.clock.now()).to_rfc3339();
+        conn.execute(
+            "UPDATE inventory_stock SET last_count_date = ?
+             WHERE item_id = ? AND location_id = ? AND COALESCE(lot_number, '') = ?",
+            params![
+                &counted_at,
+                &line.item_id,
+                &line.location_id,
+                line.lot_number.as_deref().unwrap_or_default(),
+            ],
+        )?;
+        conn.execute(
+            "UPDATE count_lines SET posted_at = ? WHERE id = ?",
+            params![posted_at, &line.id],
+        )?;
+        Ok(())
+    }
+    
+    // ============ Putaway Operations ============
+    
+    /// An active storage location with room for `quantity` more units of
+    /// the item. Locations already holding the item come first, then the
+    /// emptiest. Occupancy is counted from stock on hand.
+    pub async fn suggest_putaway_location(&self, item_id: &str, quantity: f64) -> Result<Option<Location>> {
+        let locations = self.db.query_map(
+            &format!(
+                "SELECT l.*, {},
+                        EXISTS(SELECT 1 FROM inventory_stock s
+                               WHERE s.location_id = l.id AND s.item_id = ? AND s.quantity > 0) AS holds_item
+                 FROM locations l
+                 WHERE l.zone = ? AND l.is_active = 1
+                 ORDER BY holds_item DESC, stocked_units, l.code",
+                STOCKED_UNITS
+            ),
+            params![item_id, LocationZone::Storage.as_str()],
+            Self::row_to_stocked_location,
+        )?;
+        
+        let suggestion = locations.into_iter().find(|l| l.has_capacity(quantity));
+        if suggestion.is_none() {
+            warn!("No storage location has room for {} units of {}", quantity, item_id);
+        }
+        Ok(suggestion)
+    }
+    
+    // ============ Replenishment Operations ============
+    
+    /// Create or update the replenishment rule for an item at a pick face
+    pub async fn save_replenishment_rule(&self, mut rule: ReplenishmentRule) -> Result<ReplenishmentRule> {
+        if rule.min_quantity < 0.0 || rule.max_quantity <= rule.min_quantity {
+            return Err(WmsError::validation(
+                "Replenishment max must be greater than min, and min cannot be negative",
+            ));
+        }
+        if self.get_item_by_id(&rule.item_id).await?.is_none() {
+            return Err(WmsError::not_found(format!("Item {} not found", rule.item_id)));
+        }
+        let zone: Option<String> = self.db.query_row(
+            "SELECT zone FROM locations WHERE id = ?",
+            params![&rule.location_id],
+            |row| row.get(0),
+        )?;
+        match zone.as_deref() {
+            None => return Err(WmsError::not_found(format!("Location {} not found", rule.location_id))),
+            Some("PICKING") => {}
+            Some(_) => return Err(WmsError::validation("Replenishment rules apply to PICKING zone locations")),
+        }
+        
+        let existing: Option<String> = self.db.query_row(
+            "SELECT id FROM replenishment_rules WHERE item_id = ? AND location_id = ?",
+            params![&rule.item_id, &rule.location_id],
+            |row| row.get(0),
+        )?;
+        rule.id = existing.unwrap_or_else(new_id);
+        
+        self.db.execute(
+            "INSERT INTO replenishment_rules (id, item_id, location_id, min_quantity, max_quantity, is_active)
+             VALUES (?, ?, ?, ?, ?, ?)
+             ON CONFLICT(item_id, location_id) DO UPDATE SET
+                min_quantity = excluded.min_quantity,
+                max_quantity = excluded.max_quantity,
+                is_active = excluded.is_active,
+                updated_at = datetime('now')",
+            params![
+                &rule.id,
+                &rule.item_id,
+                &rule.location_id,
+                rule.min_quantity,
+                rule.max_quantity,
+                rule.is_active,
+            ],
+        )?;
+        
+        Ok(rule)
+    }
+    
+    /// Active replenishment rules, optionally for one item
+    pub async fn list_replenishment_rules(&self, item_id: Option<&str>) -> Result<Vec<ReplenishmentRule>> {
+        self.db.query_map(
+            "SELECT * FROM replenishment_rules
+             WHERE is_active = 1 AND (?1 IS NULL OR item_id = ?1)
+             ORDER BY item_id, location_id",
+            params![item_id],
+            row_to_rule,
+        )
+    }
+    
+    /// Deactivate a rule and cancel its open tasks. Completed tasks are kept.
+    pub async fn delete_replenishment_rule(&self, rule_id: &str) -> Result<()> {
+        let rows = self.db.transaction(|conn| {
+            conn.execute(
+                "UPDATE replenishment_tasks SET status = 'cancelled' WHERE rule_id = ? AND status = 'open'",
+                params![rule_id],
+            )?;
+            Ok(conn.execute(
+                "UPDATE replenishment_rules SET is_active = 0, updated_at = datetime('now') WHERE id = ?",
+                params![rule_id],
+            )?)
+        })?;
+        if rows == 0 {
+            return Err(WmsError::not_found(format!("Replenishment rule {} not found", rule_id)));
+        }
+        Ok(())
+    }
+    
+    /// Open replenishment tasks, urgent first. Pick faces below their
+    /// minimum get tasks for whatever bulk stock is available, which may
+    /// be less than needed to reach the maximum.
+    pub async fn get_replenishment_tasks(&self) -> Result<Vec<ReplenishmentTask>> {
+        let rules = self.list_replenishment_rules(None).await?;
+        for rule in &rules {
+            self.replenish(rule, ReplenishmentPriority::Normal).await?;
+        }
+        
+        self.db.query_map(
+            "SELECT * FROM replenishment_tasks WHERE status = 'open'
+             ORDER BY priority = 'urgent' DESC, created_at, rowid",
+            [],
+            row_to_task,
+        )
+    }
+    
+    /// Move stock for a task from bulk to the pick face. Moving less than
+    /// planned completes the task; the shortfall is picked up by the next
+    /// evaluation.
+    pub async fn confirm_replenishment(
+        &self,
+        ctx: &RequestContext,
+        task_id: &str,
+        quantity_moved: f64,
+    ) -> Result<ReplenishmentTask> {
+        let user_id = ctx.actor()?;
+        let mut task = self.db.query_row(
+            "SELECT * FROM replenishment_tasks WHERE id = ?",
+            params![task_id],
+            row_to_task,
+        )?
+        .ok_or_else(|| WmsError::not_found(format!("Replenishment task {} not found", task_id)))?;
+        
+        if task.status != ReplenishmentTaskStatus::Open {
+            return Err(WmsError::conflict(format!("Replenishment task {} is already {}", task_id, task.status.as_str())));
+        }
+        if quantity_moved <= 0.0 || quantity_moved > task.quantity {
+            return Err(WmsError::validation(format!(
+                "Quantity moved must be between 0 and {}", task.quantity
+            )));
+        }
+        
+        let available: f64 = self.db.query_row(
+            "SELECT COALESCE(SUM(quantity), 0) FROM inventory_stock
+             WHERE item_id = ? AND location_id = ? AND COALESCE(lot_number, '') = COALESCE(?, '')",
+            params![&task.item_id, &task.from_location_id, &task.lot_number],
+            |row| row.get(0),
+        )?.unwrap_or(0.0);
+        if quantity_moved > available {
+            return Err(WmsError::validation(format!(
+                "Only {} available at the bulk location", available
+            )));
+        }
+        
+        let notes = Some(format!("Replenishment task {}", task.id));
+        for (location_id, quantity) in [
+            (&task.from_location_id, -quantity_moved),
+            (&task.to_location_id, quantity_moved),
+        ] {
+            self.adjust_quantity(InventoryAdjustment {
+                item_id: task.item_id.clone(),
+                location_id: Some(location_id.clone()),
+                warehouse_id: None,
+                adjustment_type: AdjustmentType::Transfer,
+                quantity,
+                lot_number: task.lot_number.clone(),
+                reason_code: Some("REPLENISHMENT".to_string()),
+                notes: notes.clone(),
+                user_id: user_id.to_string(),
+            }).await?;
+        }
+        
+        task.status = ReplenishmentTaskStatus::Completed;
+        task.quantity_moved = Some(quantity_moved);
+        task.completed_by = Some(user_id.to_string());
+        task.completed_at = Some(self.clock.now());
+        
+        self.db.execute(
+            "UPDATE replenishment_tasks SET status = ?, quantity_moved = ?, completed_by = ?, completed_at = ?
+             WHERE id = ?",
+            params![
+                task.status.as_str(),
+                quantity_moved,
+                &task.completed_by,
+                task.completed_at.map(|t| t.to_rfc3339()),
+                &task.id,
+            ],
+        )?;
+        
+        info!("Replenished {} of {} to {}", quantity_moved, task.item_id, task.to_location_id);
+        Ok(task)
+    }
+    
+    /// Queue urgent replenishment when a pick leaves a face below its
+    /// minimum. Problems are logged; they never fail the pick.
+    async fn replenish_after_pick(&self, item_id: &str, location_id: &str) {
+        let rule = self.db.query_row(
+            "SELECT * FROM replenishment_rules WHERE item_id = ? AND location_id = ? AND is_active = 1",
+            params![item_id, location_id],
+            row_to_rule,
+        );
+        let created = match rule {
+            Ok(Some(rule)) => self.replenish(&rule, ReplenishmentPriority::Urgent).await,
+            Ok(None) => return,
+            Err(e) => Err(e),
+        };
+        match created {
+            Ok(tasks) if !tasks.is_empty() => {
+                info!("Queued {} urgent replenishment tasks for {} at {}", tasks.len(), item_id, location_id);
+            }
+            Ok(_) => {}
+            Err(e) => warn!("Replenishment check for {} at {} failed: {}", item_id, location_id, e),
+        }
+    }
+    
+    /// Create tasks for a rule whose face is below its minimum, taking bulk
+    /// stock not already claimed by open tasks in FEFO order
+    async fn replenish(&self, rule: &ReplenishmentRule, priority: ReplenishmentPriority) -> Result<Vec<ReplenishmentTask>> {
+        let on_face: f64 = self.db.query_row(
+            &format!(
+                "SELECT COALESCE(SUM(s.quantity), 0) FROM inventory_stock s
+                 WHERE s.item_id = ? AND s.location_id = ? AND {}",
+                NOT_HELD
+            ),
+            params![&rule.item_id, &rule.location_id],
+            |row| row.get(0),
+        )?.unwrap_or(0.0);
+        let in_transit: f64 = self.db.query_row(
+            "SELECT COALESCE(SUM(quantity), 0) FROM replenishment_tasks WHERE rule_id = ? AND status = 'open'",
+            params![&rule.id],
+            |row| row.get(0),
+        )?.unwrap_or(0.0);
+        
+        let need = replenishment_need(rule, on_face, in_transit);
+        if need <= 0.0 {
+            return Ok(Vec::new());
+        }
+        
+        let candidates = self.db.query_map(
+            &format!(
+                "SELECT s.location_id, l.code, s.lot_number,
+                    SUM(s.quantity) - COALESCE((
+                        SELECT SUM(t.quantity) FROM replenishment_tasks t
+                        WHERE t.status = 'open' AND t.item_id = s.item_id
+                          AND t.from_location_id = s.location_id
+                          AND COALESCE(t.lot_number, '') = COALESCE(s.lot_number, '')
+                    ), 0) AS quantity
+                 FROM inventory_stock s
+                 JOIN locations l ON s.location_id = l.id
+                 WHERE s.item_id = ? AND l.zone = 'STORAGE' AND l.is_active = 1 AND {}
+                 GROUP BY s.location_id, COALESCE(s.lot_number, '')
+                 ORDER BY MIN(s.expiry_date) IS NULL, MIN(s.expiry_date), MIN(s.updated_at), l.code",
+                NOT_HELD
+            ),
+            params![&rule.item_id],
+            |row| Ok(StockAllocation {
+                location_id: row.get("location_id")?,
+                location_code: row.get("code")?,
+                lot_number: row.get::<_, Option<String>>("lot_number")?.filter(|l| !l.is_empty()),
+                quantity: row.get("quantity")?,
+            }),
+        )?;
+        
+        let allocations = allocate_in_order(candidates, need);
+        let planned: f64 = allocations.iter().map(|a| a.quantity).sum();
+        if planned < need {
+            info!(
+                "Bulk stock covers {} of {} needed for {} at {}",
+                planned, need, rule.item_id, rule.location_id
+            );
+        }
+        
+        let created_at = self.clock.now();
+        let mut tasks = Vec::with_capacity(allocations.len());
+        for allocation in allocations {
+            let task = ReplenishmentTask {
+                id: new_id(),
+                rule_id: rule.id.clone(),
+                item_id: rule.item_id.clone(),
+                from_location_id: allocation.location_id,
+                to_location_id: rule.location_id.clone(),
+                lot_number: allocation.lot_number,
+                quantity: allocation.quantity,
+                quantity_moved: None,
+                priority,
+                status: ReplenishmentTaskStatus::Open,
+                created_at,
+                completed_by: None,
+                completed_at: None,
+            };
+            self.db.execute(
+                "INSERT INTO replenishment_tasks (
+                    id, rule_id, item_id, from_location_id, to_location_id, lot_number,
+                    quantity, priority, status, created_at
+                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                params![
+                    &task.id,
+                    &task.rule_id,
+                    &task.item_id,
+                    &task.from_location_id,
+                    &task.to_location_id,
+                    &task.lot_number,
+                    task.quantity,
+                    task.priority.as_str(),
+                    task.status.as_str(),
+                    task.created_at.to_rfc3339(),
+                ],
+            )?;
+            tasks.push(task);
+        }
+        
+        Ok(tasks)
+    }
+    
+    // ============ Item Image Operations ============
+    
+    /// Attach a photo to an item. The first image becomes the primary one.
+    pub async fn add_item_image(
+        &self,
+        ctx: &RequestContext,
+        item_id: &str,
+        filename: &str,
+        content: Vec<u8>,
+    ) -> Result<Attachment> {
+        let user_id = ctx.actor()?;
+        let item = self.get_item_by_id(item_id).await?
+            .ok_or_else(|| WmsError::not_found(format!("Item {} not found", item_id)))?;
+        
+        let is_first = self.get_item_images(item_id).await?.is_empty();
+        let image = self.attachments.add(NewAttachment {
+            owner_type: ITEM_ATTACHMENT_OWNER.to_string(),
+            owner_id: item_id.to_string(),
+            kind: AttachmentKind::Image,
+            filename: filename.to_string(),
+            content,
+            created_by: user_id.to_string(),
+        }).await?;
+        
+        debug!("Added image {} to {}", image.filename, item.sku);
+        if is_first {
+            self.attachments.set_primary(&image.id).await
+        } else {
+            Ok(image)
+        }
+    }
+    
+    /// Images of an item, primary first
+    pub async fn get_item_images(&self, item_id: &str) -> Result<Vec<Attachment>> {
+        self.attachments.list(ITEM_ATTACHMENT_OWNER, item_id).await
+    }
+    
+    /// Choose which image represents an item in lists and pick lists
+    pub async fn set_primary_image(&self, item_id: &str, attachment_id: &str) -> Result<Attachment> {
+        let image = self.attachments.get(attachment_id).await?
+            .filter(|a| a.owner_type == ITEM_ATTACHMENT_OWNER && a.owner_id == item_id)
+            .ok_or_else(|| WmsError::not_found(format!("Image {} not found for item {}", attachment_id, item_id)))?;
+        
+        self.attachments.set_primary(&image.id).await
+    }
+    
+    /// Daily picked quantity over the last `days` days, from the first day
+    /// with a pick up to yesterday. Every pick on a day adds to one bucket,
+    /// and only picks count as demand: receipts, returns and adjustments
+    /// don't. Days without picks are zero so sparse demand keeps its gaps.
+    async fn get_daily_demand(&self, sku: &str, days: u32) -> Result<Vec<f64>> {
+        let today = self.clock.today();
+        let picks: HashMap<NaiveDate, f64> = self.db.query_map(
+            "SELECT date(t.created_at) AS day, SUM(ABS(t.quantity)) AS qty
+             FROM inventory_transactions t
+             JOIN inventory_items i ON t.item_id = i.id
+             WHERE i.sku = ?
+               AND t.transaction_type = 'PICK'
+               AND date(t.created_at) >= ? AND date(t.created_at) < ?
+             GROUP BY day",
+            params![sku, (today - Duration::days(days as i64)).to_string(), today.to_string()],
+            |row| Ok((row.get::<_, String>("day")?, row.get::<_, f64>("qty")?)),
+        )?
+        .into_iter()
+        .filter_map(|(day, qty)| {
+            NaiveDate::parse_from_str(&day, "%Y-%m-%d").ok().map(|date| (date, qty))
+        })
+        .collect();
+        
+        let Some(first) = picks.keys().min().copied() else {
+            return Ok(Vec::new());
+        };
+        Ok(first.iter_days()
+            .take_while(|date| *date < today)
+            .map(|date| picks.get(&date).copied().unwrap_or(0.0))
+            .collect())
+    }
+    
+    /// Convert database row to Location
+    fn row_to_location(row: &rusqlite::Row) -> rusqlite::Result<Location> {
+        let zone: String = row.get("zone")?;
+        let traversal: String = row.get("aisle_traversal")?;
+        let created_at: String = row.get("created_at")?;
+        
+        Ok(Location {
+            id: row.get("id")?,
+            code: row.get("code")?,
+            warehouse_id: row.get("warehouse_id")?,
+            zone: LocationZone::parse(&zone).ok_or_else(|| {
+                rusqlite::Error::InvalidColumnType(0, format!("zone '{}'", zone), rusqlite::types::Type::Text)
+            })?,
+            aisle: row.get("aisle")?,
+            rack: row.get("rack")?,
+            level: row.get("level")?,
+            bin: row.get("bin")?,
+            x_m: row.get("x_m")?,
+            y_m: row.get("y_m")?,
+            aisle_traversal: AisleTraversal::parse(&traversal).unwrap_or_default(),
+            is_active: row.get::<_, i32>("is_active")? == 1,
+            capacity_units: row.get("capacity_units")?,
+            current_units: row.get::<_, Option<f64>>("current_units")?.unwrap_or_default(),
+            created_at: NaiveDateTime::parse_from_str(&created_at, "%Y-%m-%d %H:%M:%S")
+                .map(|t| t.and_utc())
+                .unwrap_or_else(|_| Utc::now()),
+        })
+    }
+    
+    /// Convert database row to Warehouse
+    fn row_to_warehouse(row: &rusqlite::Row) -> rusqlite::Result<Warehouse> {
+        let created_at: String = row.get("created_at")?;
+        
+        Ok(Warehouse {
+            id: row.get("id")?,
+            code: row.get("code")?,
+            name: row.get("name")?,
+            address: row.get("address")?,
+            is_active: row.get::<_, i32>("is_active")? == 1,
+            created_at: NaiveDateTime::parse_from_str(&created_at, "%Y-%m-%d %H:%M:%S")
+                .map(|t| t.and_utc())
+                .unwrap_or_else(|_| Utc::now()),
+        })
+    }
+    
+    /// Convert a row selected with `STOCKED_UNITS` to Location
+    fn row_to_stocked_location(row: &rusqlite::Row) -> rusqlite::Result<Location> {
+        let mut location = Self::row_to_location(row)?;
+        location.current_units = row.get("stocked_units")?;
+        Ok(location)
+    }
+    
+    /// Convert database row to InventoryItem
+    fn row_to_item(row: &rusqlite::Row) -> rusqlite::Result<InventoryItem> {
+        let dimensions = match (
+            row.get::<_, Option<f64>>("length_cm")?,
+            row.get::<_, Option<f64>>("width_cm")?,
+            row.get::<_, Option<f64>>("height_cm")?,
+        ) {
+            (Some(l), Some(w), Some(h)) => Some(ItemDimensions {
+                length_cm: l,
+                width_cm: w,
+                height_cm: h,
+            }),
+            _ => None,
+        };
+        
+        Ok(InventoryItem {
+            id: row.get("id")?,
+            sku: row.get("sku")?,
+            name: row.get("name")?,
+            description: row.get("description")?,
+            category: row.get("category")?,
+            subcategory: row.get("subcategory")?,
+            unit_of_measure: row.get::<_, Option<String>>("unit_of_measure").ok().flatten()
+                .and_then(|unit| wms_core::types::UnitOfMeasure::parse(&unit))
+                .unwrap_or_default(),
+            weight_kg: row.get("weight_kg")?,
+            dimensions,
+            barcode: row.get("barcode")?,
+            barcode_type: None, // Parse from string if needed
+            min_stock_level: row.get::<_, f64>("min_stock_level").unwrap_or(0.0),
+            max_stock_level: row.get("max_stock_level")?,
+            reorder_point: row.get("reorder_point")?,
+            reorder_quantity: row.get("reorder_quantity")?,
+            lead_time_days: row.get::<_, u32>("lead_time_days").unwrap_or(0),
+            abc_class: row.get::<_, Option<String>>("abc_class")?.and_then(|c| AbcClass::parse(&c)),
+            is_active: row.get::<_, i32>("is_active")? == 1,
+            parent_item_id: row.get("parent_item_id")?,
+            variant_attributes: row.get::<_, Option<String>>("variant_attributes")?
+                .and_then(|json| serde_json::from_str(&json).ok())
+                .unwrap_or_default(),
+            custom_fields: parse_custom_fields(row.get("custom_fields")?),
+            created_at: chrono::Utc::now(), // Parse from string
+            updated_at: None,
+            version: row.get("version")?,
+            total_quantity: row.get("total_qty").ok(),
+        })
+    }
+}
+
+/// Stored form of variant attributes: a JSON object with sorted keys, or
+/// NULL for items that aren't variants
+fn attributes_json(attributes: &VariantAttributes) -> Option<String> {
+    if attributes.is_empty() {
+        None
+    } else {
+        serde_json::to_string(attributes).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wms_core::custom_fields::{CustomFieldDefinition, CustomFieldType};
+    use wms_core::db::DEFAULT_WAREHOUSE_ID;
+    use wms_core::events::RecordingEmitter;
+    use wms_core::test_support::{
+        fixed_clock, insert_location, test_context, test_db, ItemBuilder, ShipmentBuilder, TEST_USER_ID,
+    };
+    
+    fn test_service() -> InventoryService {
+        InventoryService::new(test_db())
+    }
+    
+    async fn create_widget(service: &InventoryService) -> InventoryItem {
+        let item = ItemBuilder::new("WID-1").name("Widget").build();
+        service.create_item(item).await.unwrap()
+    }
+    
+    /// Two good rows, a SKU repeated in the file, a malformed number and a
+    /// SKU that is already in the item master
+    const IMPORT_FIXTURE: &str = "\
+SKU,Name,Category,Unit,Barcode,Reorder Point,Reorder Qty,Weight,Dimensions
+NEW-1,New Widget,Widgets,cs,0001234567890,10,50,1.25,40x30x20
+NEW-2,Other Widget,,,,,,,
+NEW-1,Repeated Widget,,,,,,,
+NEW-3,Broken Widget,,,,ten,,,
+WID-1,Widget Renamed,,kg,,8,,,
+";
+    
+    #[tokio::test]
+    async fn test_import_reports_bad_rows_and_keeps_the_rest() {
+        let service = test_service();
+        create_widget(&service).await;
+        
+        let report = service
+            .import_items_csv(IMPORT_FIXTURE.as_bytes(), ImportOptions::default(), &CancellationToken::new())
+            .await
+            .unwrap();
+        assert_eq!((report.created, report.updated, report.skipped), (2, 0, 3));
+        assert_eq!(report.errors, vec![
+            RowError::new(4, Some("sku"), "SKU NEW-1 appears earlier in the file"),
+            RowError::new(5, Some("reorder_point"), "'ten' is not a valid number"),
+            RowError::new(6, Some("sku"), "SKU WID-1 already exists"),
+        ]);
+        
+        let item = service.get_item_by_sku("NEW-1", None).await.unwrap().unwrap();
+        assert_eq!(item.name, "New Widget");
+        assert_eq!(item.unit_of_measure, wms_core::types::UnitOfMeasure::Case);
+        assert_eq!(item.reorder_quantity, Some(50.0));
+        assert_eq!(item.dimensions.map(|d| d.height_cm), Some(20.0));
+        assert_eq!(service.get_item_by_sku("WID-1", None).await.unwrap().unwrap().name, "Widget");
+    }
+    
+    #[tokio::test]
+    async fn test_import_updates_existing_items_when_asked() {
+        let service = test_service();
+        create_widget(&service).await;
+        let options = ImportOptions { update_existing: true };
+        
+        let report = service
+            .import_items_csv(IMPORT_FIXTURE.as_bytes(), options, &CancellationToken::new())
+            .await
+            .unwrap();
+        assert_eq!((report.created, report.updated, report.skipped), (2, 1, 2));
+        
+        let widget = service.get_item_by_sku("WID-1", None).await.unwrap().unwrap();
+        assert_eq!(widget.name, "Widget Renamed");
+        assert_eq!(widget.unit_of_measure, wms_core::types::UnitOfMeasure::Kilogram);
+        assert_eq!(widget.reorder_point, Some(8.0));
+        assert_eq!(widget.version, 2);
+        
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        assert!(service.import_items_csv(IMPORT_FIXTURE.as_bytes(), options, &cancel).await.is_err());
+    }
+    
+    /// Three items in two categories: one stocked, one low and one empty
+    fn seed_export_items(db: &Database) {
+        ItemBuilder::new("EXP-1").name("Red Widget").reorder_point(10.0).stock("loc1", 40.0).stock("loc2", 2.0)
+            .insert::<InventoryItem>(db);
+        ItemBuilder::new("EXP-2").name("Blue Widget").reorder_point(10.0).stock("loc1", 5.0).insert::<InventoryItem>(db);
+        ItemBuilder::new("EXP-3").name("Gadget").insert::<InventoryItem>(db);
+        db.execute("UPDATE inventory_items SET category = 'Widgets', abc_class = 'A' WHERE id = 'exp-1'", []).unwrap();
+        db.execute("UPDATE inventory_items SET category = 'Widgets' WHERE id = 'exp-2'", []).unwrap();
+        db.execute("UPDATE inventory_items SET category = 'Gadgets' WHERE id = 'exp-3'", []).unwrap();
+    }
+    
+    fn decode(export: &FileExport) -> Vec<u8> {
+        base64::engine::general_purpose::STANDARD.decode(&export.data).unwrap()
+    }
+    
+    #[tokio::test]
+    async fn test_export_items_as_csv() {
+        let db = test_db();
+        seed_export_items(&db);
+        let service = InventoryService::new(db).with_clock(fixed_clock());
+        
+        let export = service.export_items("csv", InventoryExportFilter::default()).await.unwrap();
+        assert_eq!(export.content_type, "text/csv");
+        assert_eq!(export.filename, "inventory_2025-01-15.csv");
+        
+        let data = decode(&export);
+        let mut reader = csv::Reader::from_reader(data.as_slice());
+        assert_eq!(
+            reader.headers().unwrap().iter().collect::<Vec<_>>(),
+            vec!["SKU", "Name", "Category", "On Hand", "Reorder Point", "ABC Class", "Status"],
+        );
+        let rows: Vec<Vec<String>> = reader.records()
+            .map(|r| r.unwrap().iter().map(String::from).collect())
+            .collect();
+        assert_eq!(rows, vec![
+            vec!["EXP-1", "Red Widget", "Widgets", "42", "10", "A", "In Stock"],
+            vec!["EXP-2", "Blue Widget", "Widgets", "5", "10", "", "Low Stock"],
+            vec!["EXP-3", "Gadget", "Gadgets", "0", "", "", "Out of Stock"],
+        ]);
+        
+        let xlsx = service.export_items("xlsx", InventoryExportFilter::default()).await.unwrap();
+        assert!(xlsx.filename.ends_with(".xlsx"));
+        assert!(decode(&xlsx).starts_with(b"PK\x03\x04"));
+        assert!(service.export_items("pdf", InventoryExportFilter::default()).await.is_err());
+    }
+    
+    #[tokio::test]
+    async fn test_export_honors_the_list_filters() {
+        let db = test_db();
+        seed_export_items(&db);
+        let service = InventoryService::new(db);
+        let exported_skus = |export: FileExport| -> Vec<String> {
+            csv::Reader::from_reader(decode(&export).as_slice()).records()
+                .map(|r| r.unwrap()[0].to_string())
+                .collect()
+        };
+        
+        let filter = InventoryExportFilter { query: Some("widget".into()), ..Default::default() };
+        assert_eq!(exported_skus(service.export_items("csv", filter).await.unwrap()), vec!["EXP-1", "EXP-2"]);
+        
+        let filter = InventoryExportFilter { category: Some("Gadgets".into()), ..Default::default() };
+        assert_eq!(exported_skus(service.export_items("csv", filter).await.unwrap()), vec!["EXP-3"]);
+        
+        let filter = InventoryExportFilter {
+            category: Some("Widgets".into()),
+            status: Some(StockStatus::LowStock),
+            ..Default::default()
+        };
+        assert_eq!(exported_skus(service.export_items("csv", filter).await.unwrap()), vec!["EXP-2"]);
+    }
+    
+    #[tokio::test]
+    async fn test_second_writer_gets_conflict() {
+        let service = test_service();
+        let loaded = create_widget(&service).await;
+        
+        // Two users load version 1; the first to save wins
+        let mut first = loaded.clone();
+        first.name = "Widget Pro".to_string();
+        let saved = service.update_item(first).await.unwrap();
+        assert_eq!(saved.version, 2);
+        
+        let mut second = loaded.clone();
+        second.reorder_point = Some(10.0);
+        match service.update_item(second).await {
+            Err(WmsError::VersionConflict { current, .. }) => {
+                assert_eq!(current["name"], "Widget Pro");
+                assert_eq!(current["version"], 2);
+            }
+            other => panic!("expected a version conflict, got {:?}", other.map(|i| i.version)),
+        }
+        
+        // The first writer's change survived
+        let stored = service.get_item_by_id(&loaded.id).await.unwrap().unwrap();
+        assert_eq!(stored.name, "Widget Pro");
+        assert_eq!(stored.reorder_point, None);
+    }
+    
+    #[tokio::test]
+    async fn test_other_writes_bump_version() {
+        let service = test_service();
+        let loaded = create_widget(&service).await;
+        
+        // e.g. a sync inbox upsert that doesn't touch the version itself
+        service.db.execute(
+            "UPDATE inventory_items SET name = 'Widget (synced)' WHERE id = ?",
+            params![&loaded.id],
+        ).unwrap();
+        
+        let stored = service.get_item_by_id(&loaded.id).await.unwrap().unwrap();
+        assert_eq!(stored.version, 2);
+        assert!(matches!(service.update_item(loaded).await, Err(WmsError::VersionConflict { .. })));
+    }
+    
+    #[tokio::test]
+    async fn test_expired_reservations_stop_counting() {
+        let db = test_db();
+        let item: InventoryItem = ItemBuilder::new("WID-1").stock("loc1", 10.0).insert(&db);
+        db.execute(
+            "INSERT INTO inventory_reservations (id, item_id, quantity, expires_at, created_by)
+             VALUES ('r1', ?, 4, '2025-01-16T09:00:00Z', ?)",
+            params![&item.id, TEST_USER_ID],
+        ).unwrap();
+        let clock = fixed_clock();
+        let service = InventoryService::new(db).with_clock(clock.clone());
+        
+        let projection = service.get_available_to_promise(&item.id, 3).await.unwrap();
+        assert_eq!((projection.on_hand, projection.reserved), (10.0, 4.0));
+        
+        clock.advance(Duration::days(2));
+        let projection = service.get_available_to_promise(&item.id, 3).await.unwrap();
+        assert_eq!(projection.reserved, 0.0);
+        assert_eq!(projection.days[0].date, NaiveDate::from_ymd_opt(2025, 1, 17).unwrap());
+    }
+    
+    fn attributes(pairs: &[(&str, &str)]) -> VariantAttributes {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+    
+    fn axis(name: &str, values: &[&str]) -> VariantAxis {
+        VariantAxis { name: name.to_string(), values: values.iter().map(|v| v.to_string()).collect() }
+    }
+    
+    #[tokio::test]
+    async fn test_duplicate_variant_combination_rejected() {
+        let db = test_db();
+        let parent: InventoryItem = ItemBuilder::new("TSHIRT").name("T-Shirt").insert(&db);
+        let service = InventoryService::new(db);
+        let rules = VariantSkuRules::default();
+        
+        let variant = service
+            .create_variant(&parent.id, attributes(&[("size", "M"), ("color", "Red")]), &rules)
+            .await
+            .unwrap();
+        assert_eq!(variant.sku, "TSHIRT-RED-M");
+        assert_eq!(variant.parent_item_id.as_deref(), Some(parent.id.as_str()));
+        
+        // Same values in a different order are the same combination
+        let again = service
+            .create_variant(&parent.id, attributes(&[("color", "Red"), ("size", "M")]), &rules)
+            .await;
+        assert!(matches!(again, Err(WmsError::Conflict(_))));
+        
+        // Variants cannot be nested
+        let nested = service.create_variant(&variant.id, attributes(&[("fit", "Slim")]), &rules).await;
+        assert!(matches!(nested, Err(WmsError::Validation(_))));
+        
+        // The matrix skips the existing combination and creates the rest
+        let matrix = service
+            .create_variant_matrix(&parent.id, vec![axis("size", &["S", "M"]), axis("color", &["Red"])], &rules)
+            .await
+            .unwrap();
+        assert_eq!(matrix.created.len(), 1);
+        assert_eq!(matrix.created[0].sku, "TSHIRT-RED-S");
+        assert_eq!(matrix.skipped, vec![attributes(&[("size", "M"), ("color", "Red")])]);
+        assert_eq!(service.get_variants(&parent.id).await.unwrap().len(), 2);
+    }
+    
+    #[tokio::test]
+    async fn test_variant_stock_rolls_up_to_parent() {
+        let db = test_db();
+        let parent: InventoryItem = ItemBuilder::new("TSHIRT").reorder_point(20.0).stock("loc1", 2.0).insert(&db);
+        let service = InventoryService::new(db.clone());
+        let matrix = service
+            .create_variant_matrix(&parent.id, vec![axis("size", &["S", "M", "L"])], &VariantSkuRules::default())
+            .await
+            .unwrap();
+        for (variant, quantity) in matrix.created.iter().zip([5.0, 0.0, 8.5]) {
+            db.execute(
+                "INSERT INTO inventory_stock (id, item_id, location_id, quantity) VALUES (?, ?, 'loc1', ?)",
+                params![new_id(), &variant.id, quantity],
+            ).unwrap();
+        }
+        
+        let summary = service.get_variant_stock(&matrix.created[1].id).await.unwrap();
+        assert_eq!(summary.parent_id, parent.id);
+        assert_eq!(summary.parent_quantity, 2.0);
+        assert_eq!(summary.variants.iter().map(|v| v.quantity).collect::<Vec<_>>(), vec![8.5, 0.0, 5.0]);
+        assert_eq!(summary.total_quantity, 15.5);
+        
+        let grouped = service.search_items(ItemSearchQuery {
+            query: Some("TSHIRT-M".to_string()),
+            group_by_parent: true,
+            ..Default::default()
+        }).await.unwrap();
+        assert_eq!(grouped.len(), 1);
+        assert_eq!(grouped[0].id, parent.id);
+        assert_eq!(grouped[0].total_quantity, Some(15.5));
+        
+        // Every variant inherited the reorder point of 20; all are low, and
+        // so is the parent's rolled-up total
+        let low = service.get_low_stock_by_parent().await.unwrap();
+        assert_eq!(low.len(), 1);
+        assert_eq!(low[0].parent.total_quantity, Some(15.5));
+        assert_eq!(low[0].variants.len(), 3);
+    }
+    
+    /// Widget with a PICKING face ("face") and a replenishment rule of 10..50
+    async fn replenishment_fixture(face_qty: f64) -> (InventoryService, InventoryItem) {
+        let db = test_db();
+        let item: InventoryItem = ItemBuilder::new("WIDGET")
+            .stock("face", face_qty)
+            .lot("bulk-1", "L-LATE", 10.0, NaiveDate::from_ymd_opt(2025, 9, 1))
+            .lot("bulk-2", "L-SOON", 15.0, NaiveDate::from_ymd_opt(2025, 3, 1))
+            .lot("bulk-3", "L-HELD", 40.0, NaiveDate::from_ymd_opt(2025, 2, 1))
+            .insert(&db);
+        db.execute("UPDATE locations SET zone = 'PICKING' WHERE id = 'face'", []).unwrap();
+        let service = InventoryService::new(db).with_clock(fixed_clock());
+        service.place_hold(&test_context(), &item.id, "L-HELD", "Damaged pallet").await.unwrap();
+        service.save_replenishment_rule(ReplenishmentRule {
+            id: String::new(),
+            item_id: item.id.clone(),
+            location_id: "face".to_string(),
+            min_quantity: 10.0,
+            max_quantity: 50.0,
+            is_active: true,
+        }).await.unwrap();
+        (service, item)
+    }
+    
+    #[tokio::test]
+    async fn test_partial_replenishment_when_bulk_is_short() {
+        let (service, item) = replenishment_fixture(4.0).await;
+        
+        // 46 needed; only 25 unheld in bulk, soonest expiry first
+        let tasks = service.get_replenishment_tasks().await.unwrap();
+        let planned: Vec<(&str, f64)> = tasks.iter()
+            .map(|t| (t.lot_number.as_deref().unwrap(), t.quantity))
+            .collect();
+        assert_eq!(planned, vec![("L-SOON", 15.0), ("L-LATE", 10.0)]);
+        assert!(tasks.iter().all(|t| t.priority == ReplenishmentPriority::Normal));
+        
+        // Re-evaluating doesn't claim the same bulk stock twice
+        assert_eq!(service.get_replenishment_tasks().await.unwrap().len(), 2);
+        
+        // A short move completes the task
+        let done = service.confirm_replenishment(&test_context(), &tasks[0].id, 12.0).await.unwrap();
+        assert_eq!(done.status, ReplenishmentTaskStatus::Completed);
+        assert_eq!(done.quantity_moved, Some(12.0));
+        assert!(service.confirm_replenishment(&test_context(), &tasks[0].id, 1.0).await.is_err());
+        
+        let stock = service.get_item_stock_by_location(&item.id).await.unwrap();
+        let at = |location: &str| stock.iter()
+            .filter(|s| s.location_id == location)
+            .map(|s| s.quantity)
+            .sum::<f64>();
+        assert_eq!((at("face"), at("bulk-2")), (16.0, 3.0));
+    }
+    
+    #[tokio::test]
+    async fn test_pick_below_min_queues_urgent_task() {
+        let (service, item) = replenishment_fixture(12.0).await;
+        assert!(service.get_replenishment_tasks().await.unwrap().is_empty());
+        
+        service.adjust_quantity(InventoryAdjustment {
+            item_id: item.id.clone(),
+            location_id: Some("face".to_string()),
+            warehouse_id: None,
+            adjustment_type: AdjustmentType::Pick,
+            quantity: 5.0,
+            lot_number: None,
+            reason_code: None,
+            notes: None,
+            user_id: TEST_USER_ID.to_string(),
+        }).await.unwrap();
+        
+        let tasks = service.get_replenishment_tasks().await.unwrap();
+        assert_eq!(tasks.len(), 2);
+        assert!(tasks.iter().all(|t| t.priority == ReplenishmentPriority::Urgent));
+        assert_eq!(tasks.iter().map(|t| t.quantity).sum::<f64>(), 25.0);
+    }
+    
+    #[tokio::test]
+    async fn test_adjustment_below_zero_leaves_stock_untouched() {
+        let db = test_db();
+        ItemBuilder::new("WID-1").id("widget").stock("A-01", 5.0).insert::<InventoryItem>(&db);
+        let service = InventoryService::new(db);
+        
+        let err = service.adjust_quantity(InventoryAdjustment {
+            item_id: "widget".to_string(),
+            location_id: Some("A-01".to_string()),
+            warehouse_id: None,
+            adjustment_type: AdjustmentType::Pick,
+            quantity: 6.0,
+            lot_number: None,
+            reason_code: None,
+            notes: None,
+            user_id: TEST_USER_ID.to_string(),
+        }).await.unwrap_err();
+        assert!(err.to_string().contains("Insufficient inventory. Current: 5"), "{}", err);
+        
+        assert_eq!(stock_at(&service, "widget", "A-01"), 5.0);
+        let entries: i64 = service.db.query_row(
+            "SELECT COUNT(*) FROM inventory_transactions WHERE item_id = 'widget'",
+            [],
+            |row| row.get(0),
+        ).unwrap().unwrap();
+        assert_eq!(entries, 0);
+    }
+    
+    #[tokio::test]
+    async fn test_pick_to_reorder_point_emits_low_stock() {
+        let db = test_db();
+        ItemBuilder::new("WID-1").id("widget").reorder_point(10.0).stock("A-01", 12.0).insert::<InventoryItem>(&db);
+        let events = Arc::new(RecordingEmitter::new());
+        let service = InventoryService::new(db).with_event_emitter(events.clone());
+        let pick = |quantity: f64| InventoryAdjustment {
+            item_id: "widget".to_string(),
+            location_id: Some("A-01".to_string()),
+            warehouse_id: None,
+            adjustment_type: AdjustmentType::Pick,
+            quantity,
+            lot_number: None,
+            reason_code: None,
+            notes: None,
+            user_id: TEST_USER_ID.to_string(),
+        };
+        
+        service.adjust_quantity(pick(1.0)).await.unwrap();
+        assert!(events.events().is_empty());
+        
+        service.adjust_quantity(pick(3.0)).await.unwrap();
+        assert_eq!(events.events(), vec![WmsEvent::InventoryLowStock {
+            item_id: "widget".to_string(),
+            sku: "WID-1".to_string(),
+            quantity: 8.0,
+            reorder_point: 10.0,
+        }]);
+    }
+    
+    #[tokio::test]
+    async fn test_removal_needs_stock_at_that_location_and_lot() {
+        let db = test_db();
+        ItemBuilder::new("WID-1").id("widget")
+            .stock("A-01", 2.0)
+            .lot("B-01", "L1", 10.0, None)
+            .insert::<InventoryItem>(&db);
+        let service = InventoryService::new(db);
+        let pick = |location_id: &str, lot_number: Option<&str>, quantity: f64| InventoryAdjustment {
+            item_id: "widget".to_string(),
+            location_id: Some(location_id.to_string()),
+            warehouse_id: None,
+            adjustment_type: AdjustmentType::Pick,
+            quantity,
+            lot_number: lot_number.map(str::to_string),
+            reason_code: None,
+            notes: None,
+            user_id: TEST_USER_ID.to_string(),
+        };
+        
+        // 12 in total, but only 2 at A-01
+        let err = service.adjust_quantity(pick("A-01", None, 3.0)).await.unwrap_err();
+        assert!(err.to_string().contains("Only 2 available at location A-01"), "{}", err);
+        // Lot L1 is all at B-01
+        let err = service.adjust_quantity(pick("A-01", Some("L1"), 1.0)).await.unwrap_err();
+        assert!(err.to_string().contains("Only 0 of lot L1 available at location A-01"), "{}", err);
+        
+        service.adjust_quantity(pick("B-01", Some("L1"), 4.0)).await.unwrap();
+        assert_eq!((stock_at(&service, "widget", "A-01"), stock_at(&service, "widget", "B-01")), (2.0, 6.0));
+    }
+    
+    #[tokio::test]
+    async fn test_reorder_report_offsets_inbound_stock() {
+        let db = test_db();
+        for (id, stock, reorder_point) in [("bolt", 5.0, 20.0), ("nut", 2.0, 10.0), ("washer", 4.0, 10.0), ("screw", 100.0, 10.0)] {
+            ItemBuilder::new(&id.to_uppercase()).id(id).reorder_point(reorder_point).stock("loc1", stock)
+                .insert::<InventoryItem>(&db);
+        }
+        db.execute("UPDATE inventory_items SET reorder_quantity = 100 WHERE id IN ('bolt', 'nut')", []).unwrap();
+        // Bolts are on an open receipt; the nuts' receipt already arrived
+        for (receipt, status, item, expected, received) in [
+            ("r1", "receiving", "bolt", 30.0, 10.0),
+            ("r2", "completed", "nut", 50.0, 50.0),
+            ("r3", "cancelled", "washer", 50.0, 0.0),
+        ] {
+            db.execute(
+                "INSERT INTO receipts (id, receipt_number, status, created_by) VALUES (?, ?, ?, ?)",
+                params![receipt, receipt.to_uppercase(), status, TEST_USER_ID],
+            ).unwrap();
+            db.execute(
+                "INSERT INTO receipt_items (id, receipt_id, item_id, quantity_expected, quantity_received)
+                 VALUES (?, ?, ?, ?, ?)",
+                params![new_id(), receipt, item, expected, received],
+            ).unwrap();
+        }
+        for day in ["2025-01-02", "2025-01-08", "2025-01-14"] {
+            db.execute(
+                "INSERT INTO inventory_transactions (id, item_id, transaction_type, quantity, user_id, created_at)
+                 VALUES (?, 'washer', 'PICK', -10, 'u1', ?)",
+                params![new_id(), format!("{} 12:00:00", day)],
+            ).unwrap();
+        }
+        let service = InventoryService::new(db).with_clock(fixed_clock());
+        
+        let report = service.generate_reorder_report().await.unwrap();
+        let line = |id: &str| report.lines.iter().find(|l| l.item_id == id).unwrap();
+        assert_eq!(report.lines.len(), 3, "screw is well stocked");
+        
+        // 20 bolts still inbound lift 5 on hand above the reorder point
+        let bolt = line("bolt");
+        assert_eq!((bolt.on_order, bolt.covered_by_on_order, bolt.suggested_quantity), (20.0, true, 0.0));
+        
+        // No demand: no days of cover rather than a division by zero
+        let nut = line("nut");
+        assert_eq!((nut.on_order, nut.covered_by_on_order), (0.0, false));
+        assert_eq!((nut.suggested_quantity, nut.quantity_source), (100.0, ReorderQuantitySource::Item));
+        assert_eq!((nut.average_daily_demand, nut.days_of_cover), (0.0, None));
+        
+        // 30 picked over 30 days covers 4 days; no reorder quantity set
+        let washer = line("washer");
+        assert_eq!(washer.days_of_cover, Some(4.0));
+        assert_eq!(washer.quantity_source, ReorderQuantitySource::Forecast);
+        assert!(washer.suggested_quantity > 0.0);
+        
+        let export = service.export_reorder_report().await.unwrap();
+        let csv = base64::engine::general_purpose::STANDARD.decode(export.data).unwrap();
+        assert_eq!(String::from_utf8(csv).unwrap().lines().count(), 4);
+    }
+    
+    #[tokio::test]
+    async fn test_stock_totals_per_warehouse() {
+        let db = test_db();
+        insert_location(&db, "loc2");
+        let service = InventoryService::new(db.clone());
+        let north = service.create_warehouse("NORTH", "North depot", None).await.unwrap();
+        service.set_location_warehouse("loc2", &north.id).await.unwrap();
+        assert!(service.create_warehouse("NORTH", "Again", None).await.is_err());
+        
+        ItemBuilder::new("SKU-W").id("w").reorder_point(5.0).stock("loc1", 6.0).stock("loc2", 4.0)
+            .insert::<InventoryItem>(&db);
+        
+        for (warehouse, expected) in [(None, 10.0), (Some(DEFAULT_WAREHOUSE_ID), 6.0), (Some(north.id.as_str()), 4.0)] {
+            let item = service.get_item_by_sku("SKU-W", warehouse).await.unwrap().unwrap();
+            assert_eq!(item.total_quantity, Some(expected), "{:?}", warehouse);
+        }
+        let items = service.get_all_items(1, 50, Some(&north.id)).await.unwrap();
+        assert_eq!(items.iter().find(|i| i.id == "w").unwrap().total_quantity, Some(4.0));
+        
+        // Only the north warehouse is at its reorder point
+        let low = |items: Vec<InventoryItem>| items.iter().any(|i| i.id == "w");
+        assert!(!low(service.get_low_stock_items(None).await.unwrap()));
+        assert!(!low(service.get_low_stock_items(Some(DEFAULT_WAREHOUSE_ID)).await.unwrap()));
+        assert!(low(service.get_low_stock_items(Some(&north.id)).await.unwrap()));
+        
+        let err = service.adjust_quantity(InventoryAdjustment {
+            item_id: "w".to_string(),
+            location_id: Some("loc1".to_string()),
+            warehouse_id: Some(north.id.clone()),
+            adjustment_type: AdjustmentType::Pick,
+            quantity: 1.0,
+            lot_number: None,
+            reason_code: None,
+            notes: None,
+            user_id: TEST_USER_ID.to_string(),
+        }).await.unwrap_err();
+        assert!(err.to_string().contains("is in warehouse default"), "{}", err);
+        assert!(service.transfer_stock("w", "loc1", "loc2", 1.0, None, Some(&north.id), TEST_USER_ID).await.is_err());
+        service.transfer_stock("w", "loc1", "loc2", 1.0, None, None, TEST_USER_ID).await.unwrap();
+        let item = service.get_item_by_sku("SKU-W", Some(&north.id)).await.unwrap().unwrap();
+        assert_eq!(item.total_quantity, Some(5.0));
+    }
+    
+    #[tokio::test]
+    async fn test_daily_demand_has_one_bucket_per_day() {
+        let db = test_db();
+        ItemBuilder::new("WID-1").id("widget").insert::<InventoryItem>(&db);
+        let service = InventoryService::new(db).with_clock(fixed_clock());
+        let post = |transaction_type: &str, quantity: f64, day: &str| {
+            service.db.execute(
+                "INSERT INTO inventory_transactions (id, item_id, transaction_type, quantity, user_id, created_at)
+                 VALUES (?, 'widget', ?, ?, 'u1', ?)",
+                params![new_id(), transaction_type, quantity, format!("2025-01-{} 12:00:00", day)],
+            ).unwrap();
+        };
+        // Five small picks on the 10th, one big pick on the 13th
+        for _ in 0..5 {
+            post("PICK", -2.0, "10");
+        }
+        post("PICK", -10.0, "13");
+        post("RECEIVE", 100.0, "11");
+        post("RETURN", 3.0, "13");
+        
+        let history = service.get_daily_demand("WID-1", 30).await.unwrap();
+        assert_eq!(history, vec![10.0, 0.0, 0.0, 10.0, 0.0]);
+    }
+    
+    #[tokio::test]
+    async fn test_sparse_demand_forecasts_before_thirty_days() {
+        let db = test_db();
+        ItemBuilder::new("WID-1").id("widget").insert::<InventoryItem>(&db);
+        let service = InventoryService::new(db).with_clock(fixed_clock());
+        let today = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
+        for days_ago in [20, 12, 4] {
+            service.db.execute(
+                "INSERT INTO inventory_transactions (id, item_id, transaction_type, quantity, user_id, created_at)
+                 VALUES (?, 'widget', 'PICK', -5, 'u1', ?)",
+                params![new_id(), format!("{} 12:00:00", today - Duration::days(days_ago))],
+            ).unwrap();
+        }
+        
+        // Twenty days of history, nearly all of them without demand
+        let result = service.run_forecast("WID-1", 7, None, &CancellationToken::new()).await.unwrap();
+        assert_eq!(result.model_type, ForecastModel::Croston);
+    }
+    
+    #[tokio::test]
+    async fn test_forecast_accuracy_waits_for_enough_actuals() {
+        let db = test_db();
+        ItemBuilder::new("WID-1").id("widget").insert::<InventoryItem>(&db);
+        let clock = fixed_clock();
+        let service = InventoryService::new(db).with_clock(clock.clone());
+        let pick = |day: NaiveDate, quantity: f64| {
+            service.db.execute(
+                "INSERT INTO inventory_transactions (id, item_id, transaction_type, quantity, user_id, created_at)
+                 VALUES (?, 'widget', 'PICK', ?, 'u1', ?)",
+                params![new_id(), -quantity, format!("{} 12:00:00", day)],
+            ).unwrap();
+        };
+        let today = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
+        for days_ago in 1..=40 {
+            pick(today - Duration::days(days_ago), if days_ago % 2 == 0 { 4.0 } else { 6.0 });
+        }
+        
+        service.run_forecast("WID-1", 14, None, &CancellationToken::new()).await.unwrap();
+        let recorded: i64 = service.db.query_row(
+            "SELECT COUNT(*) FROM forecast_predictions", [], |row| row.get(0),
+        ).unwrap().unwrap();
+        assert_eq!(recorded, 14 * ForecastModel::ALL.len() as i64);
+        
+        for offset in 0..10 {
+            pick(today + Duration::days(offset), 5.0);
+        }
+        
+        // Three days in: too few actuals to rate anything
+        clock.advance(Duration::days(3));
+        let evaluation = service.evaluate_forecasts().await.unwrap();
+        assert_eq!(evaluation.evaluated_predictions, 3 * ForecastModel::ALL.len());
+        assert_eq!(evaluation.rated_items, 0);
+        let accuracy = service.get_forecast_accuracy(Some("WID-1")).await.unwrap();
+        assert!(accuracy.models.is_empty());
+        assert_eq!(accuracy.best_model, None);
+        
+        clock.advance(Duration::days(7));
+        let evaluation = service.evaluate_forecasts().await.unwrap();
+        assert_eq!(evaluation.evaluated_predictions, 7 * ForecastModel::ALL.len());
+        assert_eq!(evaluation.rated_items, 1);
+        
+        let accuracy = service.get_forecast_accuracy(Some("WID-1")).await.unwrap();
+        assert_eq!(accuracy.models.len(), ForecastModel::ALL.len());
+        assert!(accuracy.models.iter().all(|m| m.samples == 10 && m.mape.is_some()));
+        assert_eq!(accuracy.best_model, Some(accuracy.models[0].model_type));
+        let overall = service.get_forecast_accuracy(None).await.unwrap();
+        assert_eq!(overall.models.len(), ForecastModel::ALL.len());
+        assert!(service.get_forecast_accuracy(Some("NOPE")).await.is_err());
+    }
+    
+    #[tokio::test]
+    async fn test_pick_heatmap_suggests_moving_far_fast_movers() {
+        let db = test_db();
+        ItemBuilder::new("FAST-1").id("fast").stock("d-20", 40.0).stock("d-21", 5.0).insert::<InventoryItem>(&db);
+        ItemBuilder::new("NEAR-1").id("near").stock("a-01", 10.0).insert::<InventoryItem>(&db);
+        db.execute("UPDATE inventory_items SET abc_class = 'A'", []).unwrap();
+        for (id, aisle, x, y) in [
+            ("a-01", "A", 2.0, 2.0),
+            ("a-02", "A", 2.0, 4.0),
+            ("d-20", "D", 20.0, 20.0),
+            ("d-21", "D", 20.0, 22.0),
+        ] {
+            insert_location(&db, id);
+            db.execute(
+                "UPDATE locations SET zone = 'PICKING', aisle = ?, x_m = ?, y_m = ? WHERE id = ?",
+                params![aisle, x, y, id],
+            ).unwrap();
+        }
+        let pick = |item_id: &str, location_id: &str, day: &str| {
+            db.execute(
+                "INSERT INTO inventory_transactions (id, item_id, location_id, transaction_type, quantity, user_id, created_at)
+                 VALUES (?, ?, ?, 'PICK', -2, 'u1', ?)",
+                params![new_id(), item_id, location_id, format!("{} 10:00:00", day)],
+            ).unwrap();
+        };
+        pick("fast", "d-20", "2025-01-10");
+        pick("fast", "d-20", "2025-01-11");
+        pick("fast", "d-21", "2025-01-11");
+        pick("near", "a-01", "2025-01-12");
+        pick("near", "a-01", "2024-12-01");
+        
+        let service = InventoryService::new(db);
+        let range = DateRange::new(
+            NaiveDate::from_ymd_opt(2025, 1, 1).unwrap(),
+            NaiveDate::from_ymd_opt(2025, 1, 31).unwrap(),
+        );
+        let heatmap = service.get_pick_heatmap(range).await.unwrap();
+        
+        assert_eq!(heatmap.total_picks, 4);
+        assert_eq!(heatmap.locations[0].location_code, "d-20");
+        assert_eq!(heatmap.locations[0].quantity, 4.0);
+        let aisles: Vec<_> = heatmap.aisles.iter().map(|a| (a.aisle.clone(), a.picks)).collect();
+        assert_eq!(aisles, vec![(Some("A".to_string()), 1), (Some("D".to_string()), 3)]);
+        
+        // Only a-02 is empty and near the depot
+        assert_eq!(heatmap.slotting.len(), 1);
+        let suggestion = &heatmap.slotting[0];
+        assert_eq!(suggestion.sku, "FAST-1");
+        assert_eq!(suggestion.picks, 3);
+        assert_eq!(suggestion.location_code, "d-20");
+        assert_eq!(suggestion.suggested_location_code, "a-02");
+        
+        let export = service.export_pick_heatmap(range).await.unwrap();
+        assert_eq!(export.filename, "pick_heatmap_2025-01-01_2025-01-31.csv");
+    }
+    
+    #[tokio::test]
+    async fn test_picker_stats_join_clocked_hours() {
+        let db = test_db();
+        ItemBuilder::new("WID-1").id("wid").stock("loc1", 100.0).insert::<InventoryItem>(&db);
+        db.execute(
+            "INSERT INTO users (id, username, email, full_name) VALUES ('u2', 'late', 'late@example.com', 'Late Starter')",
+            [],
+        ).unwrap();
+        let pick = |user_id: &str, at: &str| {
+            db.execute(
+                "INSERT INTO inventory_transactions (id, item_id, location_id, transaction_type, quantity, user_id, created_at)
+                 VALUES (?, 'wid', 'loc1', 'PICK', -1, ?, ?)",
+                params![new_id(), user_id, at],
+            ).unwrap();
+        };
+        for hour in 8..16 {
+            pick("u1", &format!("2025-01-14 {:02}:00:00", hour));
+            pick("u1", &format!("2025-01-15 {:02}:00:00", hour));
+        }
+        // u2 never clocked in: six picks over three hours
+        for hour in 10..13 {
+            pick("u2", &format!("2025-01-15 {:02}:00:00", hour));
+            pick("u2", &format!("2025-01-15 {:02}:30:00", hour));
+        }
+        for day in ["2025-01-14", "2025-01-15"] {
+            db.execute(
+                "INSERT INTO time_entries (id, user_id, entry_date, clock_in_time, total_hours, status)
+                 VALUES (?, 'u1', ?, ?, 8.0, 'completed')",
+                params![new_id(), day, format!("{}T08:00:00Z", day)],
+            ).unwrap();
+        }
+        ShipmentBuilder::new("SHP-1").line("wid", 1.0).insert::<serde_json::Value>(&db);
+        db.execute(
+            "INSERT INTO mispicks (id, shipment_item_id, item_id, quantity, picked_by, picked_at, corrected_by, corrected_at)
+             VALUES ('m1', 'shp-1-line-1', 'wid', 1, 'u1', '2025-01-14T09:00:00+00:00', 'u2', '2025-01-15T08:00:00+00:00')",
+            [],
+        ).unwrap();
+        
+        let service = InventoryService::new(db);
+        let range = DateRange::new(
+            NaiveDate::from_ymd_opt(2025, 1, 14).unwrap(),
+            NaiveDate::from_ymd_opt(2025, 1, 15).unwrap(),
+        );
+        let board = service.get_picker_stats(range).await.unwrap();
+        
+        let order: Vec<_> = board.pickers.iter().map(|p| p.user_id.as_str()).collect();
+        assert_eq!(order, ["u2", "u1"]);
+        let late = &board.pickers[0];
+        assert_eq!(late.name.as_deref(), Some("Late Starter"));
+        assert!((late.hours - 2.5).abs() < 1e-6);
+        assert!(late.hours_estimated);
+        let clocked = &board.pickers[1];
+        assert_eq!((clocked.lines_picked, clocked.hours, clocked.mispicks), (16, 16.0, 1));
+        assert_eq!(clocked.picks_per_hour, Some(1.0));
+        assert!(!clocked.hours_estimated);
+        
+        let mine = service.get_my_stats("u1", range).await.unwrap();
+        assert_eq!(mine.stats.unwrap().rank, 2);
+        assert_eq!((mine.pickers, mine.days.len()), (2, 2));
+        assert_eq!((mine.clean_streak_days, mine.best_clean_streak_days), (1, 1));
+        assert!(service.get_my_stats("u3", range).await.unwrap().stats.is_none());
+    }
+    
+    #[tokio::test]
+    async fn test_custom_fields_are_validated_and_searchable() {
+        let db = test_db();
+        let fields = CustomFieldService::new(db.clone());
+        for (key, label, field_type, required) in [
+            ("hazmat_class", "Hazmat Class", CustomFieldType::Text, true),
+            ("shelf_life_days", "Shelf Life", CustomFieldType::Number, false),
+        ] {
+            fields.save_definition(CustomFieldDefinition {
+                id: String::new(),
+                entity: CustomFieldEntity::InventoryItem,
+                key: key.to_string(),
+                label: label.to_string(),
+                field_type,
+                options: Vec::new(),
+                required,
+                show_in_list: false,
+                sort_order: 0,
+                created_at: Utc::now(),
+            }).unwrap();
+        }
+        let service = InventoryService::new(db);
+        
+        let mut item: InventoryItem = ItemBuilder::new("ACID-1").name("Battery acid").build();
+        item.custom_fields.insert("shelf_life_days".to_string(), "soon".into());
+        let err = service.create_item(item.clone()).await.unwrap_err().to_string();
+        assert!(err.contains("Hazmat Class: is required"), "{}", err);
+        assert!(err.contains("Shelf Life: 'soon' is not a number"), "{}", err);
+        
+        item.custom_fields.insert("hazmat_class".to_string(), "Class 8".into());
+        item.custom_fields.insert("shelf_life_days".to_string(), "365".into());
+        let created = service.create_item(item).await.unwrap();
+        assert_eq!(created.custom_fields["shelf_life_days"], 365.0);
+        
+        let found = service.search_items(ItemSearchQuery {
+            query: Some("class 8".to_string()),
+            page: 1,
+            page_size: 50,
+            ..Default::default()
+        }).await.unwrap();
+        assert_eq!(found.iter().map(|i| i.sku.as_str()).collect::<Vec<_>>(), ["ACID-1"]);
+        assert_eq!(found[0].custom_fields["hazmat_class"], "Class 8");
+    }
+    
+    #[tokio::test]
+    async fn test_putaway_suggestion_respects_capacity() {
+        let db = test_db();
+        for (id, capacity) in [("A-01", Some(10.0)), ("B-01", Some(50.0)), ("C-01", None)] {
+            insert_location(&db, id);
+            db.execute("UPDATE locations SET capacity_units = ? WHERE id = ?", params![capacity, id]).unwrap();
+        }
+        db.execute("UPDATE locations SET is_active = 0 WHERE id = 'C-01'", []).unwrap();
+        ItemBuilder::new("WID-1").id("widget").stock("A-01", 8.0).insert::<InventoryItem>(&db);
+        ItemBuilder::new("BLT-1").id("bolt").stock("B-01", 45.0).insert::<InventoryItem>(&db);
+        let service = InventoryService::new(db);
+        
+        // The location already holding widgets is preferred while it has room
+        let location = service.suggest_putaway_location("widget", 2.0).await.unwrap().unwrap();
+        assert_eq!((location.id.as_str(), location.current_units), ("A-01", 8.0));
+        assert_eq!(location.zone, LocationZone::Storage);
+        assert_eq!(service.suggest_putaway_location("widget", 4.0).await.unwrap().unwrap().id, "B-01");
+        assert!(service.suggest_putaway_location("widget", 20.0).await.unwrap().is_none());
+    }
+    
+    fn stock_at(service: &InventoryService, item_id: &str, location_id: &str) -> f64 {
+        service.db.query_row(
+            "SELECT COALESCE(SUM(quantity), 0) FROM inventory_stock WHERE item_id = ? AND location_id = ?",
+            params![item_id, location_id],
+            |row| row.get(0),
+        ).unwrap().unwrap()
+    }
+    
+    #[tokio::test]
+    async fn test_transferring_a_whole_lot_empties_the_source() {
+        let db = test_db();
+        ItemBuilder::new("WID-1").id("widget")
+            .lot("RCV-01", "L1", 12.0, None)
+            .lot("RCV-01", "L2", 3.0, None)
+            .insert::<InventoryItem>(&db);
+        insert_location(&db, "A-01");
+        let service = InventoryService::new(db);
+        
+        let transfer = service.transfer_stock("widget", "RCV-01", "A-01", 12.0, Some("L1"), None, TEST_USER_ID).await.unwrap();
+        assert_eq!(transfer.remaining_at_source, 0.0);
+        assert!(!transfer.to_quarantine);
+        assert_eq!(stock_at(&service, "widget", "RCV-01"), 3.0);
+        assert_eq!(stock_at(&service, "widget", "A-01"), 12.0);
+        
+        let legs = service.db.query_map(
+            "SELECT transaction_type, quantity, previous_quantity, new_quantity FROM inventory_transactions
+             WHERE transfer_id = ? ORDER BY transaction_type DESC",
+            params![&transfer.transfer_id],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, f64>(1)?, row.get::<_, f64>(2)?, row.get::<_, f64>(3)?)),
+        ).unwrap();
+        assert_eq!(legs, [
+            ("TRANSFER_OUT".to_string(), 12.0, 12.0, 0.0),
+            ("TRANSFER_IN".to_string(), 12.0, 0.0, 12.0),
+        ]);
+        // The item's total is unchanged
+        let item = service.get_item_by_id("widget").await.unwrap().unwrap();
+        assert_eq!(item.total_quantity, Some(15.0));
+    }
+    
+    #[tokio::test]
+    async fn test_transfer_beyond_the_lot_balance_fails() {
+        let db = test_db();
+        ItemBuilder::new("WID-1").id("widget")
+            .lot("RCV-01", "L1", 5.0, None)
+            .lot("RCV-01", "L2", 20.0, None)
+            .insert::<InventoryItem>(&db);
+        insert_location(&db, "A-01");
+        let service = InventoryService::new(db);
+        
+        let err = service.transfer_stock("widget", "RCV-01", "A-01", 6.0, Some("L1"), None, TEST_USER_ID).await.unwrap_err();
+        assert!(err.to_string().contains("Only 5 of lot L1 available"), "{}", err);
+        
+        service.db.execute("UPDATE locations SET capacity_units = 4 WHERE id = 'A-01'", []).unwrap();
+        let err = service.transfer_stock("widget", "RCV-01", "A-01", 5.0, Some("L1"), None, TEST_USER_ID).await.unwrap_err();
+        assert!(err.to_string().contains("room for 4"), "{}", err);
+        
+        assert_eq!(stock_at(&service, "widget", "RCV-01"), 25.0);
+        assert_eq!(stock_at(&service, "widget", "A-01"), 0.0);
+    }
+    
+    #[tokio::test]
+    async fn test_transfer_after_a_competing_pick_is_checked_against_what_remains() {
+        let db = test_db();
+        ItemBuilder::new("WID-1").id("widget").stock("RCV-01", 10.0).insert::<InventoryItem>(&db);
+        insert_location(&db, "A-01");
+        let service = InventoryService::new(db);
+        
+        service.adjust_quantity(InventoryAdjustment {
+            item_id: "widget".to_string(),
+            location_id: Some("RCV-01".to_string()),
+            warehouse_id: None,
+            adjustment_type: AdjustmentType::Pick,
+            quantity: 6.0,
+            lot_number: None,
+            reason_code: None,
+            notes: None,
+            user_id: TEST_USER_ID.to_string(),
+        }).await.unwrap();
+        
+        let err = service.transfer_stock("widget", "RCV-01", "A-01", 5.0, None, None, TEST_USER_ID).await.unwrap_err();
+        assert!(err.to_string().contains("Only 4 available"), "{}", err);
+        assert_eq!(stock_at(&service, "widget", "RCV-01"), 4.0);
+        assert_eq!(stock_at(&service, "widget", "A-01"), 0.0);
+        
+        // Taking more than the rows hold fails without lowering any of them
+        let err = service.db.transaction(|conn| {
+            InventoryService::take_stock(conn, "widget", "RCV-01", "", 5.0)
+        }).unwrap_err();
+        assert!(matches!(err, WmsError::Validation(_)), "{}", err);
+        assert_eq!(stock_at(&service, "widget", "RCV-01"), 4.0);
+    }
+    
+    #[tokio::test]
+    async fn test_transfer_into_quarantine_is_flagged() {
+        let db = test_db();
+        ItemBuilder::new("WID-1").id("widget").stock("A-01", 10.0).insert::<InventoryItem>(&db);
+        db.execute("INSERT INTO locations (id, code, zone) VALUES ('QA-01', 'QA-01', 'QUARANTINE')", []).unwrap();
+        let service = InventoryService::new(db);
+        
+        let transfer = service.transfer_stock("widget", "A-01", "QA-01", 4.0, None, None, TEST_USER_ID).await.unwrap();
+        assert!(transfer.to_quarantine);
+        assert_eq!(transfer.remaining_at_source, 6.0);
+        assert_eq!(stock_at(&service, "widget", "QA-01"), 4.0);
+    }
+    
+    fn count_transactions(service: &InventoryService) -> Vec<(String, f64)> {
+        service.db.query_map(
+            "SELECT item_id, quantity FROM inventory_transactions WHERE transaction_type = 'COUNT' ORDER BY item_id",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        ).unwrap()
+    }
+    
+    #[tokio::test]
+    async fn test_count_posts_positive_and_negative_variances() {
+        let db = test_db();
+        ItemBuilder::new("WID-1").id("widget").stock("A-01", 10.0).insert::<InventoryItem>(&db);
+        ItemBuilder::new("BLT-1").id("bolt")
+            .lot("A-01", "L1", 20.0, None)
+            .lot("B-01", "L1", 4.0, None)
+            .insert::<InventoryItem>(&db);
+        ItemBuilder::new("GAD-1").id("gadget").stock("B-01", 5.0).insert::<InventoryItem>(&db);
+        let service = InventoryService::new(db);
+        
+        let sheet = service.create_count_sheet("storage", None, TEST_USER_ID).await.unwrap();
+        assert_eq!(sheet.lines.len(), 4);
+        
+        // 20% over is outside the 5% allowed for unclassified items
+        let line = service.record_count(&sheet.id, "widget", "A-01", None, 12.0, TEST_USER_ID).await.unwrap();
+        assert!(line.requires_recount);
+        assert_eq!(line.variance(), Some(2.0));
+        service.record_count(&sheet.id, "bolt", "A-01", Some("L1"), 19.0, TEST_USER_ID).await.unwrap();
+        service.record_count(&sheet.id, "bolt", "B-01", None, 4.0, TEST_USER_ID).await.unwrap();
+        service.record_count(&sheet.id, "gadget", "B-01", None, 5.0, TEST_USER_ID).await.unwrap();
+        
+        let err = service.post_count(&sheet.id, TEST_USER_ID).await.unwrap_err();
+        assert!(err.to_string().contains("Recount needed before posting: WID-1 at A-01"), "{}", err);
+        let line = service.record_count(&sheet.id, "widget", "A-01", None, 12.0, TEST_USER_ID).await.unwrap();
+        assert!(!line.requires_recount);
+        
+        let posted = service.post_count(&sheet.id, TEST_USER_ID).await.unwrap();
+        assert_eq!(posted.status, CountStatus::Posted);
+        assert!(posted.lines.iter().all(|l| l.posted_at.is_some()));
+        // Lines counted as expected post nothing
+        assert_eq!(count_transactions(&service), [("bolt".to_string(), -1.0), ("widget".to_string(), 2.0)]);
+        assert_eq!(stock_at(&service, "widget", "A-01"), 12.0);
+        assert_eq!(stock_at(&service, "bolt", "A-01"), 19.0);
+        assert_eq!(stock_at(&service, "gadget", "B-01"), 5.0);
+        
+        let uncounted: i64 = service.db.query_row(
+            "SELECT COUNT(*) FROM inventory_stock WHERE last_count_date IS NULL",
+            [],
+            |row| row.get(0),
+        ).unwrap().unwrap();
+        assert_eq!(uncounted, 0);
+    }
+    
+    #[tokio::test]
+    async fn test_posted_count_cannot_be_posted_again() {
+        let db = test_db();
+        ItemBuilder::new("WID-1").id("widget").stock("A-01", 10.0).insert::<InventoryItem>(&db);
+        let service = InventoryService::new(db);
+        assert!(service.create_count_sheet("A-01", Some(AbcClass::A), TEST_USER_ID).await.is_err());
+        
+        let sheet = service.create_count_sheet("A-01", None, TEST_USER_ID).await.unwrap();
+        assert_eq!(sheet.location_id.as_deref(), Some("A-01"));
+        let err = service.post_count(&sheet.id, TEST_USER_ID).await.unwrap_err();
+        assert!(err.to_string().contains("1 lines have not been counted"), "{}", err);
+        
+        for _ in 0..2 {
+            service.record_count(&sheet.id, "widget", "A-01", None, 9.0, TEST_USER_ID).await.unwrap();
+        }
+        service.post_count(&sheet.id, TEST_USER_ID).await.unwrap();
+        
+        assert!(matches!(service.post_count(&sheet.id, TEST_USER_ID).await, Err(WmsError::Conflict(_))));
+        assert!(service.record_count(&sheet.id, "widget", "A-01", None, 8.0, TEST_USER_ID).await.is_err());
+        assert_eq!(count_transactions(&service), [("widget".to_string(), -1.0)]);
+        assert_eq!(stock_at(&service, "widget", "A-01"), 9.0);
+    }
+    
+    #[tokio::test]
+    async fn test_count_failing_a_variance_posts_nothing() {
+        let db = test_db();
+        ItemBuilder::new("WID-1").id("widget").stock("A-01", 10.0).insert::<InventoryItem>(&db);
+        ItemBuilder::new("GAD-1").id("gadget").stock("A-01", 5.0).insert::<InventoryItem>(&db);
+        let service = InventoryService::new(db);
+        
+        let sheet = service.create_count_sheet("A-01", None, TEST_USER_ID).await.unwrap();
+        for _ in 0..2 {
+            service.record_count(&sheet.id, "widget", "A-01", None, 9.0, TEST_USER_ID).await.unwrap();
+            service.record_count(&sheet.id, "gadget", "A-01", None, 6.0, TEST_USER_ID).await.unwrap();
+        }
+        // The widgets are picked before the count is posted
+        service.adjust_quantity(InventoryAdjustment {
+            item_id: "widget".to_string(),
+            location_id: Some("A-01".to_string()),
+            warehouse_id: None,
+            adjustment_type: AdjustmentType::Pick,
+            quantity: 10.0,
+            lot_number: None,
+            reason_code: None,
+            notes: None,
+            user_id: TEST_USER_ID.to_string(),
+        }).await.unwrap();
+        
+        assert!(service.post_count(&sheet.id, TEST_USER_ID).await.is_err());
+        let sheet = service.get_count_sheet(&sheet.id).await.unwrap().unwrap();
+        assert_eq!(sheet.status, CountStatus::Open);
+        assert!(sheet.lines.iter().all(|l| l.posted_at.is_none()));
+        assert!(count_transactions(&service).is_empty());
+        assert_eq!(stock_at(&service, "gadget", "A-01"), 5.0);
+    }
+    
+    #[tokio::test]
+    async fn test_abc_classification_follows_pareto_cutoffs() {
+        let db = test_db();
+        // Picked value over the period: 800, 100, 50, 30, 20 and nothing
+        for (id, cost, picked) in [
+            ("heavy", Some(10.0), 80.0),
+            ("mid1", None, 100.0),
+            ("mid2", Some(2.0), 25.0),
+            ("low1", Some(1.0), 30.0),
+            ("low2", Some(1.0), 20.0),
+            ("idle", Some(5.0), 0.0),
+        ] {
+            ItemBuilder::new(&id.to_uppercase()).id(id).stock("loc1", 100.0).insert::<InventoryItem>(&db);
+            db.execute("UPDATE inventory_stock SET cost_per_unit = ? WHERE item_id = ?", params![cost, id]).unwrap();
+            if picked > 0.0 {
+                db.execute(
+                    "INSERT INTO inventory_transactions (id, item_id, location_id, transaction_type, quantity, user_id, created_at)
+                     VALUES (?, ?, 'loc1', 'PICK', ?, 'u1', '2025-01-10 10:00:00')",
+                    params![new_id(), id, picked],
+                ).unwrap();
+            }
+        }
+        // Before the period
+        db.execute(
+            "INSERT INTO inventory_transactions (id, item_id, location_id, transaction_type, quantity, user_id, created_at)
+             VALUES (?, 'idle', 'loc1', 'PICK', 500, 'u1', '2024-11-01 10:00:00')",
+            params![new_id()],
+        ).unwrap();
+        let service = InventoryService::new(db).with_clock(fixed_clock());
+        
+        let report = service.run_abc_classification(30, 80.0, 95.0).await.unwrap();
+        let classes: Vec<(&str, AbcClass, bool)> = report.items.iter()
+            .map(|i| (i.item_id.as_str(), i.class, i.no_movement))
+            .collect();
+        assert_eq!(classes, [
+            ("heavy", AbcClass::A, false),
+            ("mid1", AbcClass::B, false),
+            ("mid2", AbcClass::B, false),
+            ("low1", AbcClass::C, false),
+            ("low2", AbcClass::C, false),
+            ("idle", AbcClass::C, true),
+        ]);
+        assert!((report.items[0].annual_usage_value - 800.0 * 365.0 / 30.0).abs() < 1e-6);
+        let counts: Vec<u32> = report.classes.iter().map(|c| c.item_count).collect();
+        assert_eq!(counts, [1, 2, 3]);
+        assert_eq!((report.no_movement_count, report.changed_count), (1, 6));
+        
+        let heavy = service.get_item_by_id("heavy").await.unwrap().unwrap();
+        assert_eq!(heavy.abc_class, Some(AbcClass::A));
+        let again = service.run_abc_classification(30, 80.0, 95.0).await.unwrap();
+        assert_eq!(again.changed_count, 0);
+        assert!(service.run_abc_classification(30, 96.0, 95.0).await.is_err());
+    }
+    
+    #[tokio::test]
+    async fn test_fefo_suggestions_take_lots_in_expiry_order() {
+        let db = test_db();
+        insert_location(&db, "loc2");
+        let date = |m, d| NaiveDate::from_ymd_opt(2025, m, d);
+        ItemBuilder::new("MILK-1").id("milk")
+            .lot("loc1", "LATE", 5.0, date(3, 1))
+            .lot("loc2", "SOON", 4.0, date(1, 20))
+            .lot("loc1", "MID", 6.0, date(2, 1))
+            .lot("loc2", "OLD", 10.0, date(1, 10))
+            .insert::<InventoryItem>(&db);
+        let service = InventoryService::new(db).with_clock(fixed_clock());
+        let picks = |allocations: Vec<StockAllocation>| -> Vec<(String, String, f64)> {
+            allocations.into_iter()
+                .map(|a| (a.location_id, a.lot_number.unwrap(), a.quantity))
+                .collect()
+        };
+        let pick = |loc: &str, lot: &str, quantity: f64| (loc.to_string(), lot.to_string(), quantity);
+        
+        let suggested = service.suggest_pick_lots("milk", 12.0, false).await.unwrap();
+        assert_eq!(picks(suggested), [pick("loc2", "SOON", 4.0), pick("loc1", "MID", 6.0), pick("loc1", "LATE", 2.0)]);
+        
+        // The expired lot only counts when asked for
+        let err = service.suggest_pick_lots("milk", 20.0, false).await.unwrap_err();
+        assert!(err.to_string().contains("Short 5 of MILK-1"), "{}", err);
+        assert!(err.to_string().contains("10 more is expired"), "{}", err);
+        let suggested = service.suggest_pick_lots("milk", 20.0, true).await.unwrap();
+        assert_eq!(picks(suggested), [pick("loc2", "OLD", 10.0), pick("loc2", "SOON", 4.0), pick("loc1", "MID", 6.0)]);
+        
+        let lots = service.get_lots("milk").await.unwrap();
+        let summary: Vec<(&str, bool)> = lots.iter().map(|l| (l.lot_number.as_deref().unwrap(), l.expired)).collect();
+        assert_eq!(summary, [("OLD", true), ("SOON", false), ("MID", false), ("LATE", false)]);
+        
+        let expiring = service.get_expiring_stock(7).await.unwrap();
+        let alerts: Vec<(&str, i64)> = expiring.iter().map(|e| (e.lot_number.as_deref().unwrap(), e.days_until_expiry)).collect();
+        assert_eq!(alerts, [("OLD", -5), ("SOON", 5)]);
+    }
+}
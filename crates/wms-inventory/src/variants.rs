@@ -0,0 +1,234 @@
+//! Item Variants
+//!
+//! Parent/child SKUs for items sold in several sizes or colors. A variant is
+//! an ordinary inventory item with its own stock, linked to a parent item
+//! and described by attribute values such as `{"size": "M", "color": "Red"}`.
+//! Each combination of attribute values may exist only once per parent.
+
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use crate::models::InventoryItem;
+
+/// Attribute name to value, e.g. size → M
+pub type VariantAttributes = BTreeMap<String, String>;
+
+/// One attribute and the values to generate variants for
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VariantAxis {
+    pub name: String,
+    pub values: Vec<String>,
+}
+
+/// How variant SKUs are derived from the parent SKU
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VariantSkuRules {
+    /// Placed between the parent SKU and each value code
+    #[serde(default = "default_separator")]
+    pub separator: String,
+    /// Attributes in SKU order; attributes not listed follow alphabetically
+    #[serde(default)]
+    pub attribute_order: Vec<String>,
+    /// Short codes for attribute values, e.g. Red → RD. Values without a
+    /// code are uppercased with spaces removed.
+    #[serde(default)]
+    pub value_codes: BTreeMap<String, String>,
+}
+
+fn default_separator() -> String {
+    "-".to_string()
+}
+
+impl Default for VariantSkuRules {
+    fn default() -> Self {
+        Self {
+            separator: default_separator(),
+            attribute_order: Vec::new(),
+            value_codes: BTreeMap::new(),
+        }
+    }
+}
+
+impl VariantSkuRules {
+    /// Attribute values in SKU order
+    fn ordered_values<'a>(&self, attributes: &'a VariantAttributes) -> Vec<&'a str> {
+        let listed = self.attribute_order.iter()
+            .filter_map(|name| attributes.get(name));
+        let rest = attributes.iter()
+            .filter(|(name, _)| !self.attribute_order.contains(name))
+            .map(|(_, value)| value);
+        listed.chain(rest).map(String::as_str).collect()
+    }
+
+    /// SKU for a variant of `parent_sku`, e.g. TSHIRT-M-RD
+    pub fn variant_sku(&self, parent_sku: &str, attributes: &VariantAttributes) -> String {
+        let mut sku = parent_sku.to_string();
+        for value in self.ordered_values(attributes) {
+            let code = self.value_codes.get(value).cloned().unwrap_or_else(|| {
+                value.split_whitespace().collect::<String>().to_uppercase()
+            });
+            sku.push_str(&self.separator);
+            sku.push_str(&code);
+        }
+        sku
+    }
+
+    /// Display name for a variant, e.g. "T-Shirt (M, Red)"
+    pub fn variant_name(&self, parent_name: &str, attributes: &VariantAttributes) -> String {
+        format!("{} ({})", parent_name, self.ordered_values(attributes).join(", "))
+    }
+}
+
+/// Every combination of one value per axis, in axis order
+pub fn variant_combinations(axes: &[VariantAxis]) -> Vec<VariantAttributes> {
+    axes.iter().fold(vec![VariantAttributes::new()], |combinations, axis| {
+        combinations.iter()
+            .flat_map(|combination| {
+                axis.values.iter().map(move |value| {
+                    let mut next = combination.clone();
+                    next.insert(axis.name.clone(), value.clone());
+                    next
+                })
+            })
+            .collect()
+    })
+}
+
+/// Outcome of creating variants from attribute value lists
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VariantMatrixResult {
+    pub created: Vec<InventoryItem>,
+    /// Combinations that already existed and were left alone
+    pub skipped: Vec<VariantAttributes>,
+}
+
+/// Stock of one variant
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VariantStock {
+    pub item_id: String,
+    pub sku: String,
+    pub attributes: VariantAttributes,
+    pub quantity: f64,
+}
+
+/// Stock of a parent item with its variants rolled up
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VariantStockSummary {
+    pub parent_id: String,
+    pub parent_sku: String,
+    /// Stock held under the parent SKU itself
+    pub parent_quantity: f64,
+    pub variants: Vec<VariantStock>,
+    /// Parent quantity plus all variant quantities
+    pub total_quantity: f64,
+}
+
+impl VariantStockSummary {
+    pub fn new(parent: &InventoryItem, variants: &[InventoryItem]) -> Self {
+        let parent_quantity = parent.total_quantity.unwrap_or(0.0);
+        let variants: Vec<VariantStock> = variants.iter()
+            .map(|variant| VariantStock {
+                item_id: variant.id.clone(),
+                sku: variant.sku.clone(),
+                attributes: variant.variant_attributes.clone(),
+                quantity: variant.total_quantity.unwrap_or(0.0),
+            })
+            .collect();
+        let total_quantity = parent_quantity + variants.iter().map(|v| v.quantity).sum::<f64>();
+
+        Self {
+            parent_id: parent.id.clone(),
+            parent_sku: parent.sku.clone(),
+            parent_quantity,
+            variants,
+            total_quantity,
+        }
+    }
+}
+
+/// Low-stock items under a common parent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LowStockGroup {
+    /// The parent item, or the low item itself if it has no parent
+    pub parent: InventoryItem,
+    /// Variants below their reorder point (empty for standalone items)
+    pub variants: Vec<InventoryItem>,
+}
+
+/// Item search criteria
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ItemSearchQuery {
+    /// Matches SKU, name, barcode or a text custom field
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    /// Return parent items, with variant stock rolled up, in place of
+    /// matching variants
+    #[serde(default)]
+    pub group_by_parent: bool,
+    #[serde(default = "default_page")]
+    pub page: u32,
+    #[serde(default = "default_page_size")]
+    pub page_size: u32,
+}
+
+impl Default for ItemSearchQuery {
+    fn default() -> Self {
+        Self {
+            query: None,
+            category: None,
+            group_by_parent: false,
+            page: default_page(),
+            page_size: default_page_size(),
+        }
+    }
+}
+
+fn default_page() -> u32 {
+    1
+}
+
+fn default_page_size() -> u32 {
+    50
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attributes(pairs: &[(&str, &str)]) -> VariantAttributes {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_variant_sku_follows_rules() {
+        let rules = VariantSkuRules {
+            attribute_order: vec!["size".to_string()],
+            value_codes: BTreeMap::from([("Red".to_string(), "RD".to_string())]),
+            ..Default::default()
+        };
+        let attrs = attributes(&[("color", "Red"), ("size", "M")]);
+
+        assert_eq!(rules.variant_sku("TSHIRT", &attrs), "TSHIRT-M-RD");
+        assert_eq!(rules.variant_name("T-Shirt", &attrs), "T-Shirt (M, Red)");
+        assert_eq!(
+            VariantSkuRules::default().variant_sku("TSHIRT", &attributes(&[("color", "Navy Blue")])),
+            "TSHIRT-NAVYBLUE",
+        );
+    }
+
+    #[test]
+    fn test_combinations_are_the_cartesian_product() {
+        let axes = vec![
+            VariantAxis { name: "size".to_string(), values: vec!["S".to_string(), "M".to_string()] },
+            VariantAxis { name: "color".to_string(), values: vec!["Red".to_string(), "Blue".to_string()] },
+        ];
+
+        let combinations = variant_combinations(&axes);
+
+        assert_eq!(combinations.len(), 4);
+        assert_eq!(combinations[0], attributes(&[("size", "S"), ("color", "Red")]));
+        assert_eq!(combinations[3], attributes(&[("size", "M"), ("color", "Blue")]));
+        assert!(variant_combinations(&[]).iter().all(|c| c.is_empty()));
+    }
+}
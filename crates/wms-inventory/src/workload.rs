@@ -0,0 +1,275 @@
+//! Workload Planning
+//!
+//! Compares the labor hours needed to pick forecast demand against the
+//! hours staff are scheduled to work, day by day for a week. Productivity
+//! is measured as picks per labor hour from past pick transactions and
+//! clocked time.
+
+use std::collections::HashMap;
+use chrono::{Datelike, NaiveDate, NaiveTime, Weekday};
+use csv::Writer;
+use serde::{Deserialize, Serialize};
+use wms_core::error::{WmsError, Result};
+
+/// Weeks of same-weekday history used for a day's productivity
+pub const PRODUCTIVITY_WEEKS: i64 = 8;
+
+/// Trailing window used when a weekday has no history
+pub const FALLBACK_DAYS: i64 = 28;
+
+/// Workload plan for one week
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkloadPlan {
+    pub week_start: NaiveDate,
+    pub days: Vec<WorkloadDay>,
+    pub total_required_hours: f64,
+    pub total_scheduled_hours: f64,
+    /// Days where scheduled hours fall short of required hours
+    pub short_days: usize,
+}
+
+/// Required vs scheduled labor for one day
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkloadDay {
+    pub date: NaiveDate,
+    /// Forecast picks of A/B-class items
+    pub expected_picks: f64,
+    /// Picks per labor hour used for this day, if any history exists
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub picks_per_hour: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub required_hours: Option<f64>,
+    pub scheduled_hours: f64,
+    pub scheduled_staff: u32,
+    /// Required minus scheduled hours, when positive
+    pub shortfall_hours: f64,
+    /// Productivity came from the trailing average rather than this weekday
+    pub low_confidence: bool,
+}
+
+impl WorkloadDay {
+    /// Whether the day is understaffed
+    pub fn is_short(&self) -> bool {
+        self.shortfall_hours > 0.0
+    }
+}
+
+/// Exported workload plan
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkloadPlanExport {
+    /// Base64 encoded file data
+    pub data: String,
+    /// MIME content type
+    pub content_type: String,
+    /// Suggested filename
+    pub filename: String,
+}
+
+/// Picks and clocked hours for one past day
+#[derive(Debug, Clone, Copy)]
+pub struct DailyProductivity {
+    pub date: NaiveDate,
+    pub picks: f64,
+    pub hours: f64,
+}
+
+/// Picks per hour across the given days
+fn rate(days: impl Iterator<Item = DailyProductivity>) -> Option<f64> {
+    let (picks, hours) = days.fold((0.0, 0.0), |(p, h), d| (p + d.picks, h + d.hours));
+    (hours > 0.0).then(|| picks / hours)
+}
+
+/// Picks per hour for `date`, and whether it is a low-confidence estimate.
+///
+/// Uses the same weekday over the previous `PRODUCTIVITY_WEEKS` weeks,
+/// falling back to the `FALLBACK_DAYS` before `as_of`.
+pub fn productivity_for(
+    date: NaiveDate,
+    history: &[DailyProductivity],
+    as_of: NaiveDate,
+) -> (Option<f64>, bool) {
+    let weekday: Weekday = date.weekday();
+    let earliest = as_of - chrono::Duration::weeks(PRODUCTIVITY_WEEKS);
+
+    let same_weekday = rate(history.iter().copied().filter(|d| {
+        d.date.weekday() == weekday && d.date >= earliest && d.date < as_of
+    }));
+    if same_weekday.is_some() {
+        return (same_weekday, false);
+    }
+
+    let trailing_start = as_of - chrono::Duration::days(FALLBACK_DAYS);
+    let trailing = rate(history.iter().copied().filter(|d| d.date >= trailing_start && d.date < as_of));
+    (trailing, true)
+}
+
+/// Length of a scheduled shift in hours; shifts past midnight wrap
+pub fn shift_hours(start: &str, end: &str) -> Option<f64> {
+    let start = NaiveTime::parse_from_str(start, "%H:%M").ok()?;
+    let end = NaiveTime::parse_from_str(end, "%H:%M").ok()?;
+    let minutes = (end - start).num_minutes();
+    let minutes = if minutes < 0 { minutes + 24 * 60 } else { minutes };
+    Some(minutes as f64 / 60.0)
+}
+
+/// Build the plan from per-day inputs
+pub fn build_workload_plan(
+    week_start: NaiveDate,
+    expected_picks: &HashMap<NaiveDate, f64>,
+    history: &[DailyProductivity],
+    scheduled: &HashMap<NaiveDate, (f64, u32)>,
+    as_of: NaiveDate,
+) -> WorkloadPlan {
+    let days: Vec<WorkloadDay> = week_start.iter_days()
+        .take(7)
+        .map(|date| {
+            let expected_picks = expected_picks.get(&date).copied().unwrap_or(0.0);
+            let (picks_per_hour, low_confidence) = productivity_for(date, history, as_of);
+            let required_hours = picks_per_hour.map(|rate| expected_picks / rate);
+            let (scheduled_hours, scheduled_staff) = scheduled.get(&date).copied().unwrap_or((0.0, 0));
+
+            WorkloadDay {
+                date,
+                expected_picks,
+                picks_per_hour,
+                required_hours,
+                scheduled_hours,
+                scheduled_staff,
+                shortfall_hours: required_hours.map_or(0.0, |r| (r - scheduled_hours).max(0.0)),
+                low_confidence,
+            }
+        })
+        .collect();
+
+    WorkloadPlan {
+        week_start,
+        total_required_hours: days.iter().filter_map(|d| d.required_hours).sum(),
+        total_scheduled_hours: days.iter().map(|d| d.scheduled_hours).sum(),
+        short_days: days.iter().filter(|d| d.is_short()).count(),
+        days,
+    }
+}
+
+/// Render the plan as CSV
+pub fn render_workload_csv(plan: &WorkloadPlan) -> Result<Vec<u8>> {
+    let csv_error = |e: csv::Error| WmsError::Export(format!("CSV write error: {}", e));
+    let mut writer = Writer::from_writer(Vec::new());
+
+    writer.write_record([
+        "Date",
+        "Day",
+        "Expected Picks",
+        "Picks/Hour",
+        "Required Hours",
+        "Scheduled Hours",
+        "Scheduled Staff",
+        "Shortfall Hours",
+        "Short",
+        "Low Confidence",
+    ]).map_err(csv_error)?;
+
+    for day in &plan.days {
+        writer.write_record([
+            day.date.to_string(),
+            day.date.format("%a").to_string(),
+            format!("{:.0}", day.expected_picks),
+            day.picks_per_hour.map(|r| format!("{:.1}", r)).unwrap_or_default(),
+            day.required_hours.map(|h| format!("{:.2}", h)).unwrap_or_default(),
+            format!("{:.2}", day.scheduled_hours),
+            day.scheduled_staff.to_string(),
+            format!("{:.2}", day.shortfall_hours),
+            if day.is_short() { "yes" } else { "" }.to_string(),
+            if day.low_confidence { "yes" } else { "" }.to_string(),
+        ]).map_err(csv_error)?;
+    }
+
+    writer.write_record([
+        "Total".to_string(),
+        String::new(),
+        format!("{:.0}", plan.days.iter().map(|d| d.expected_picks).sum::<f64>()),
+        String::new(),
+        format!("{:.2}", plan.total_required_hours),
+        format!("{:.2}", plan.total_scheduled_hours),
+        String::new(),
+        format!("{:.2}", plan.days.iter().map(|d| d.shortfall_hours).sum::<f64>()),
+        plan.short_days.to_string(),
+        String::new(),
+    ]).map_err(csv_error)?;
+
+    writer.into_inner()
+        .map_err(|e| WmsError::Export(format!("CSV flush error: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(d: u32) -> NaiveDate {
+        // June 2025: the 2nd is a Monday
+        NaiveDate::from_ymd_opt(2025, 6, d).unwrap()
+    }
+
+    fn worked(date: NaiveDate, picks: f64, hours: f64) -> DailyProductivity {
+        DailyProductivity { date, picks, hours }
+    }
+
+    #[test]
+    fn test_shift_hours() {
+        assert_eq!(shift_hours("08:00", "16:30"), Some(8.5));
+        assert_eq!(shift_hours("22:00", "06:00"), Some(8.0));
+        assert_eq!(shift_hours("8am", "4pm"), None);
+    }
+
+    #[test]
+    fn test_same_weekday_productivity() {
+        let as_of = date(16);
+        let history = vec![
+            worked(date(2), 400.0, 10.0),  // Monday
+            worked(date(9), 600.0, 10.0),  // Monday
+            worked(date(10), 100.0, 10.0), // Tuesday
+        ];
+
+        let (rate, low_confidence) = productivity_for(date(23), &history, as_of);
+        assert_eq!(rate, Some(50.0));
+        assert!(!low_confidence);
+    }
+
+    #[test]
+    fn test_missing_weekday_falls_back_to_trailing_average() {
+        let as_of = date(16);
+        let history = vec![
+            worked(date(9), 300.0, 10.0),
+            worked(date(10), 100.0, 10.0),
+        ];
+
+        // No Saturday history
+        let (rate, low_confidence) = productivity_for(date(21), &history, as_of);
+        assert_eq!(rate, Some(20.0));
+        assert!(low_confidence);
+
+        assert_eq!(productivity_for(date(21), &[], as_of), (None, true));
+    }
+
+    #[test]
+    fn test_plan_flags_shortfall() {
+        let as_of = date(16);
+        let history = vec![worked(date(9), 500.0, 10.0), worked(date(10), 500.0, 10.0)];
+        let expected = HashMap::from([(date(16), 600.0), (date(17), 200.0)]);
+        let scheduled = HashMap::from([(date(16), (8.0, 1)), (date(17), (8.0, 1))]);
+
+        let plan = build_workload_plan(date(16), &expected, &history, &scheduled, as_of);
+
+        assert_eq!(plan.days.len(), 7);
+        let monday = &plan.days[0];
+        assert_eq!(monday.required_hours, Some(12.0));
+        assert_eq!(monday.shortfall_hours, 4.0);
+        assert!(monday.is_short());
+        assert!(!plan.days[1].is_short());
+        assert_eq!(plan.short_days, 1);
+        assert_eq!(plan.total_scheduled_hours, 16.0);
+
+        let csv = String::from_utf8(render_workload_csv(&plan).unwrap()).unwrap();
+        assert!(csv.starts_with("Date,Day,Expected Picks"));
+        assert!(csv.contains("2025-06-16,Mon,600,50.0,12.00,8.00,1,4.00,yes,"));
+    }
+}
@@ -0,0 +1,497 @@
+//! Advance Ship Notice Parsing
+//!
+//! Suppliers send ASNs ahead of a delivery either as a CSV file or as a
+//! simplified JSON rendering of an X12 856. Some also print a QR code on
+//! the trailer manifest carrying the cartons as JSON. All are parsed into
+//! an `AsnDocument` which the shipping service turns into a pending receipt.
+
+use std::collections::HashMap;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use wms_core::error::{WmsError, Result};
+use crate::models::Receipt;
+
+/// ASN file formats
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AsnFormat {
+    /// One row per line; header fields repeated on every row
+    Csv,
+    /// JSON mirroring the X12 856 segments we use
+    #[serde(rename = "x12_856_lite")]
+    X12_856Lite,
+    /// JSON payload of a trailer manifest QR code:
+    ///
+    /// ```json
+    /// {
+    ///   "supplier_ref": "ASN-1001",
+    ///   "supplier": "Acme Supply",
+    ///   "po": "PO-77",
+    ///   "ship_date": "2025-03-05",
+    ///   "lines": [
+    ///     { "gtin": "00012345678905", "qty": 24, "lot": "L1", "expiry": "2026-01-31" }
+    ///   ]
+    /// }
+    /// ```
+    ///
+    /// `supplier_ref` and, on every line, a GTIN-8/12/13/14 with a valid
+    /// check digit and a positive `qty` are required; the rest is optional.
+    QrManifest,
+}
+
+/// A parsed advance ship notice
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AsnDocument {
+    /// Supplier's ASN / shipment reference
+    pub supplier_reference: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub supplier_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub po_number: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expected_date: Option<NaiveDate>,
+    pub lines: Vec<AsnLine>,
+}
+
+/// A line on an advance ship notice
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AsnLine {
+    /// 1-based position in the file
+    pub line_number: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sku: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gtin: Option<String>,
+    /// Supplier's item description
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub quantity: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lot_number: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expiry_date: Option<NaiveDate>,
+}
+
+/// An ASN line that could not be matched to an inventory item
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnresolvedAsnLine {
+    pub line_number: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sku: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gtin: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub quantity: f64,
+}
+
+impl From<&AsnLine> for UnresolvedAsnLine {
+    fn from(line: &AsnLine) -> Self {
+        Self {
+            line_number: line.line_number,
+            sku: line.sku.clone(),
+            gtin: line.gtin.clone(),
+            description: line.description.clone(),
+            quantity: line.quantity,
+        }
+    }
+}
+
+/// Outcome of an ASN import or preview
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AsnImport {
+    /// The pending receipt (not saved when previewing)
+    pub receipt: Receipt,
+    /// Whether an existing pending receipt for this ASN was replaced
+    pub updated_existing: bool,
+    pub preview: bool,
+    /// Lines the user needs to map to items
+    pub unresolved: Vec<UnresolvedAsnLine>,
+}
+
+/// Parse an ASN file
+pub fn parse_asn(data: &[u8], format: AsnFormat) -> Result<AsnDocument> {
+    let document = match format {
+        AsnFormat::Csv => parse_csv(data)?,
+        AsnFormat::X12_856Lite => parse_x12_lite(data)?,
+        AsnFormat::QrManifest => parse_qr_manifest(data)?,
+    };
+
+    if document.supplier_reference.is_empty() {
+        return Err(WmsError::validation("ASN has no supplier reference"));
+    }
+    if document.lines.is_empty() {
+        return Err(WmsError::validation(format!(
+            "ASN {} has no lines",
+            document.supplier_reference
+        )));
+    }
+    for line in &document.lines {
+        if line.quantity <= 0.0 || !line.quantity.is_finite() {
+            return Err(WmsError::validation(format!(
+                "ASN line {}: quantity must be greater than zero",
+                line.line_number
+            )));
+        }
+    }
+
+    Ok(document)
+}
+
+/// Dates appear as ISO (2025-03-01) or X12 CCYYMMDD (20250301)
+fn parse_date(value: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .or_else(|_| NaiveDate::parse_from_str(value, "%Y%m%d"))
+        .map_err(|_| WmsError::validation(format!("Invalid ASN date '{}'", value)))
+}
+
+fn non_empty(value: Option<&str>) -> Option<String> {
+    value.map(str::trim).filter(|v| !v.is_empty()).map(str::to_string)
+}
+
+// ============ CSV ============
+
+/// Accepted CSV header names for each field (case-insensitive, spaces as underscores)
+const CSV_COLUMNS: &[(&str, &[&str])] = &[
+    ("supplier_reference", &["supplier_reference", "asn_number", "asn", "reference"]),
+    ("supplier_name", &["supplier_name", "supplier"]),
+    ("po_number", &["po_number", "po", "purchase_order"]),
+    ("expected_date", &["expected_date", "ship_date", "delivery_date"]),
+    ("sku", &["sku", "item_number", "vendor_part_number"]),
+    ("gtin", &["gtin", "upc", "ean", "barcode"]),
+    ("description", &["description", "item_description"]),
+    ("quantity", &["quantity", "qty"]),
+    ("lot_number", &["lot_number", "lot"]),
+    ("expiry_date", &["expiry_date", "expiry", "expiration_date"]),
+];
+
+fn parse_csv(data: &[u8]) -> Result<AsnDocument> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .flexible(true)
+        .from_reader(data);
+
+    let headers = reader.headers()
+        .map_err(|e| WmsError::validation(format!("Invalid ASN CSV: {}", e)))?
+        .clone();
+
+    let columns: HashMap<&str, usize> = CSV_COLUMNS.iter()
+        .filter_map(|(field, aliases)| {
+            headers.iter()
+                .position(|h| aliases.contains(&h.to_lowercase().replace(' ', "_").as_str()))
+                .map(|index| (*field, index))
+        })
+        .collect();
+
+    for required in ["supplier_reference", "quantity"] {
+        if !columns.contains_key(required) {
+            return Err(WmsError::validation(format!("ASN CSV is missing the {} column", required)));
+        }
+    }
+
+    let mut document = AsnDocument {
+        supplier_reference: String::new(),
+        supplier_name: None,
+        po_number: None,
+        expected_date: None,
+        lines: Vec::new(),
+    };
+
+    for (index, record) in reader.records().enumerate() {
+        let line_number = index + 1;
+        let record = record
+            .map_err(|e| WmsError::validation(format!("ASN line {}: {}", line_number, e)))?;
+        let field = |name: &str| non_empty(columns.get(name).and_then(|&i| record.get(i)));
+
+        if record.iter().all(|value| value.is_empty()) {
+            continue;
+        }
+
+        // Header fields come from the first row; every row must agree on the reference
+        let reference = field("supplier_reference").unwrap_or_default();
+        if document.supplier_reference.is_empty() {
+            document.supplier_reference = reference;
+            document.supplier_name = field("supplier_name");
+            document.po_number = field("po_number");
+            document.expected_date = field("expected_date")
+                .map(|d| parse_date(&d))
+                .transpose()?;
+        } else if !reference.is_empty() && reference != document.supplier_reference {
+            return Err(WmsError::validation(format!(
+                "ASN line {}: reference {} does not match {}",
+                line_number, reference, document.supplier_reference
+            )));
+        }
+
+        let quantity = field("quantity")
+            .and_then(|q| q.parse::<f64>().ok())
+            .ok_or_else(|| WmsError::validation(format!("ASN line {}: invalid quantity", line_number)))?;
+
+        document.lines.push(AsnLine {
+            line_number,
+            sku: field("sku"),
+            gtin: field("gtin"),
+            description: field("description"),
+            quantity,
+            lot_number: field("lot_number"),
+            expiry_date: field("expiry_date")
+                .map(|d| parse_date(&d))
+                .transpose()?,
+        });
+    }
+
+    Ok(document)
+}
+
+// ============ X12 856 (lite) ============
+
+/// Ship notice header. Field names follow the X12 856 elements they carry.
+#[derive(Deserialize)]
+struct X12ShipNotice {
+    /// BSN02
+    shipment_identification: String,
+    /// N1*SF name
+    #[serde(default)]
+    ship_from: Option<String>,
+    /// PRF01
+    #[serde(default)]
+    purchase_order: Option<String>,
+    /// DTM*017
+    #[serde(default)]
+    estimated_delivery: Option<String>,
+    /// HL item loops
+    #[serde(default)]
+    items: Vec<X12Item>,
+}
+
+/// Item loop: LIN, SN1, PID and the lot/expiry references
+#[derive(Deserialize)]
+struct X12Item {
+    /// LIN*VN
+    #[serde(default)]
+    vendor_part_number: Option<String>,
+    /// LIN*UK / LIN*UP
+    #[serde(default)]
+    gtin: Option<String>,
+    /// PID05
+    #[serde(default)]
+    description: Option<String>,
+    /// SN102
+    quantity: f64,
+    /// LIN*LT
+    #[serde(default)]
+    lot_number: Option<String>,
+    /// DTM*036
+    #[serde(default)]
+    expiration_date: Option<String>,
+}
+
+fn parse_x12_lite(data: &[u8]) -> Result<AsnDocument> {
+    let notice: X12ShipNotice = serde_json::from_slice(data)
+        .map_err(|e| WmsError::validation(format!("Invalid X12 856 JSON: {}", e)))?;
+
+    let lines = notice.items.iter()
+        .enumerate()
+        .map(|(index, item)| {
+            let line_number = index + 1;
+            Ok(AsnLine {
+                line_number,
+                sku: non_empty(item.vendor_part_number.as_deref()),
+                gtin: non_empty(item.gtin.as_deref()),
+                description: non_empty(item.description.as_deref()),
+                quantity: item.quantity,
+                lot_number: non_empty(item.lot_number.as_deref()),
+                expiry_date: non_empty(item.expiration_date.as_deref())
+                    .map(|d| parse_date(&d))
+                    .transpose()?,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(AsnDocument {
+        supplier_reference: notice.shipment_identification.trim().to_string(),
+        supplier_name: non_empty(notice.ship_from.as_deref()),
+        po_number: non_empty(notice.purchase_order.as_deref()),
+        expected_date: non_empty(notice.estimated_delivery.as_deref())
+            .map(|d| parse_date(&d))
+            .transpose()?,
+        lines,
+    })
+}
+
+// ============ QR manifest ============
+
+/// Whether `value` is a GTIN-8, -12, -13 or -14 with a valid check digit
+fn is_valid_gtin(value: &str) -> bool {
+    if !matches!(value.len(), 8 | 12 | 13 | 14) || !value.bytes().all(|b| b.is_ascii_digit()) {
+        return false;
+    }
+
+    // Weights alternate 3, 1, ... from the digit left of the check digit
+    let digits: Vec<u32> = value.bytes().map(|b| (b - b'0') as u32).collect();
+    let Some((check, body)) = digits.split_last() else {
+        return false;
+    };
+    let sum: u32 = body.iter()
+        .rev()
+        .enumerate()
+        .map(|(i, d)| if i % 2 == 0 { d * 3 } else { *d })
+        .sum();
+    (10 - sum % 10) % 10 == *check
+}
+
+fn parse_qr_manifest(data: &[u8]) -> Result<AsnDocument> {
+    let manifest: serde_json::Value = serde_json::from_slice(data)
+        .map_err(|e| WmsError::validation(format!("Manifest QR is not valid JSON: {}", e)))?;
+    let manifest = manifest.as_object()
+        .ok_or_else(|| WmsError::validation("Manifest QR must be a JSON object"))?;
+
+    let text = |value: Option<&serde_json::Value>, field: &str, at: &str| -> Result<Option<String>> {
+        match value {
+            None | Some(serde_json::Value::Null) => Ok(None),
+            Some(serde_json::Value::String(s)) => Ok(non_empty(Some(s.as_str()))),
+            Some(_) => Err(WmsError::validation(format!("Manifest {}{} must be a string", at, field))),
+        }
+    };
+
+    let supplier_reference = text(manifest.get("supplier_ref"), "supplier_ref", "")?
+        .ok_or_else(|| WmsError::validation("Manifest is missing supplier_ref"))?;
+    let lines = manifest.get("lines")
+        .and_then(|lines| lines.as_array())
+        .ok_or_else(|| WmsError::validation("Manifest lines must be an array"))?;
+
+    let lines = lines.iter()
+        .enumerate()
+        .map(|(index, line)| {
+            let at = format!("lines[{}].", index);
+            let invalid = |message: String| WmsError::validation(format!("Manifest lines[{}]: {}", index, message));
+            let line = line.as_object().ok_or_else(|| invalid("must be an object".to_string()))?;
+
+            let gtin = text(line.get("gtin"), "gtin", &at)?
+                .ok_or_else(|| invalid("gtin is required".to_string()))?;
+            if !is_valid_gtin(&gtin) {
+                return Err(invalid(format!("{} is not a valid GTIN", gtin)));
+            }
+            let quantity = line.get("qty")
+                .and_then(|q| q.as_f64())
+                .filter(|q| *q > 0.0 && q.is_finite())
+                .ok_or_else(|| invalid("qty must be a number greater than zero".to_string()))?;
+            let expiry_date = text(line.get("expiry"), "expiry", &at)?
+                .map(|d| parse_date(&d).map_err(|_| invalid(format!("invalid expiry '{}'", d))))
+                .transpose()?;
+
+            Ok(AsnLine {
+                line_number: index + 1,
+                sku: None,
+                gtin: Some(gtin),
+                description: None,
+                quantity,
+                lot_number: text(line.get("lot"), "lot", &at)?,
+                expiry_date,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(AsnDocument {
+        supplier_reference,
+        supplier_name: text(manifest.get("supplier"), "supplier", "")?,
+        po_number: text(manifest.get("po"), "po", "")?,
+        expected_date: text(manifest.get("ship_date"), "ship_date", "")?
+            .map(|d| parse_date(&d))
+            .transpose()?,
+        lines,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_csv() {
+        let data = b"ASN Number,Supplier,PO,Expected Date,SKU,UPC,Description,Qty,Lot,Expiry
+ASN-1001,Acme Supply,PO-77,2025-03-05,WID-1,,Blue widget,24,L1,2026-01-31
+ASN-1001,Acme Supply,PO-77,2025-03-05,,00012345678905,Red gadget,6,,
+";
+        let asn = parse_asn(data, AsnFormat::Csv).unwrap();
+
+        assert_eq!(asn.supplier_reference, "ASN-1001");
+        assert_eq!(asn.supplier_name.as_deref(), Some("Acme Supply"));
+        assert_eq!(asn.po_number.as_deref(), Some("PO-77"));
+        assert_eq!(asn.expected_date, NaiveDate::from_ymd_opt(2025, 3, 5));
+        assert_eq!(asn.lines.len(), 2);
+        assert_eq!(asn.lines[0].sku.as_deref(), Some("WID-1"));
+        assert_eq!(asn.lines[0].expiry_date, NaiveDate::from_ymd_opt(2026, 1, 31));
+        assert_eq!(asn.lines[1].sku, None);
+        assert_eq!(asn.lines[1].gtin.as_deref(), Some("00012345678905"));
+        assert_eq!(asn.lines[1].quantity, 6.0);
+    }
+
+    #[test]
+    fn test_csv_rejects_mixed_references_and_bad_quantities() {
+        let mixed = b"asn,sku,qty\nA-1,WID-1,1\nA-2,WID-2,1\n";
+        assert!(matches!(parse_asn(mixed, AsnFormat::Csv), Err(WmsError::Validation(_))));
+
+        let bad_qty = b"asn,sku,qty\nA-1,WID-1,lots\n";
+        assert!(matches!(parse_asn(bad_qty, AsnFormat::Csv), Err(WmsError::Validation(_))));
+
+        let zero_qty = b"asn,sku,qty\nA-1,WID-1,0\n";
+        assert!(matches!(parse_asn(zero_qty, AsnFormat::Csv), Err(WmsError::Validation(_))));
+
+        let no_reference = b"sku,qty\nWID-1,1\n";
+        assert!(matches!(parse_asn(no_reference, AsnFormat::Csv), Err(WmsError::Validation(_))));
+    }
+
+    #[test]
+    fn test_parse_x12_lite() {
+        let data = serde_json::json!({
+            "shipment_identification": "SN-778",
+            "ship_from": "Acme Supply",
+            "estimated_delivery": "20250305",
+            "items": [
+                { "vendor_part_number": "WID-1", "quantity": 12, "lot_number": "L9", "expiration_date": "20260131" },
+                { "gtin": "0012345678905", "description": "Red gadget", "quantity": 3 }
+            ]
+        });
+
+        let asn = parse_asn(data.to_string().as_bytes(), AsnFormat::X12_856Lite).unwrap();
+
+        assert_eq!(asn.supplier_reference, "SN-778");
+        assert_eq!(asn.expected_date, NaiveDate::from_ymd_opt(2025, 3, 5));
+        assert_eq!(asn.lines[0].lot_number.as_deref(), Some("L9"));
+        assert_eq!(asn.lines[0].expiry_date, NaiveDate::from_ymd_opt(2026, 1, 31));
+        assert_eq!(asn.lines[1].line_number, 2);
+        assert_eq!(asn.lines[1].description.as_deref(), Some("Red gadget"));
+
+        assert!(parse_asn(b"{\"items\": []}", AsnFormat::X12_856Lite).is_err());
+    }
+
+    #[test]
+    fn test_qr_manifest_errors_name_the_line() {
+        let manifest = serde_json::json!({
+            "supplier_ref": "TRL-42",
+            "ship_date": "2025-03-05",
+            "lines": [
+                { "gtin": "00012345678905", "qty": 24, "lot": "L1", "expiry": "2026-01-31" },
+                { "gtin": "96385074", "qty": 2 }
+            ]
+        });
+        let asn = parse_asn(manifest.to_string().as_bytes(), AsnFormat::QrManifest).unwrap();
+        assert_eq!(asn.supplier_reference, "TRL-42");
+        assert_eq!(asn.lines[0].expiry_date, NaiveDate::from_ymd_opt(2026, 1, 31));
+        assert_eq!(asn.lines[1].gtin.as_deref(), Some("96385074"));
+
+        let error = |lines: serde_json::Value| {
+            let manifest = serde_json::json!({ "supplier_ref": "TRL-42", "lines": lines });
+            parse_asn(manifest.to_string().as_bytes(), AsnFormat::QrManifest).unwrap_err().to_string()
+        };
+        let bad_check_digit = error(serde_json::json!([
+            { "gtin": "00012345678905", "qty": 1 },
+            { "gtin": "00012345678906", "qty": 1 }
+        ]));
+        assert!(bad_check_digit.contains("lines[1]"), "{}", bad_check_digit);
+        assert!(error(serde_json::json!([{ "gtin": "00012345678905", "qty": 0 }])).contains("lines[0]: qty"));
+        assert!(error(serde_json::json!([{ "gtin": "00012345678905", "qty": 1, "lot": 7 }])).contains("lines[0].lot"));
+        assert!(parse_asn(b"{\"lines\": []}", AsnFormat::QrManifest).is_err());
+    }
+}
@@ -1,14 +1,28 @@
 //! Barcode Scanning and Decoding
 //! 
 //! Uses rxing (Rust port of ZXing) for decoding barcodes from images.
+//! Code 128 content carrying GS1 application identifiers (GS1-128, also
+//! called EAN-128) is split into its fields.
+
+use std::collections::BTreeMap;
+use chrono::{Months, NaiveDate};
 
 use rxing::{BarcodeFormat, DecodeHintType, DecodeHintValue, DecodingHintDictionary, Luma8LuminanceSource, Reader};
 use rxing::multi::{GenericMultipleBarcodeReader, MultipleBarcodeReader};
 use rxing::oned::MultiFormatOneDReader;
+use rxing::qrcode::QRCodeReader;
 use rxing::common::HybridBinarizer;
 use rxing::BinaryBitmap;
 use serde::{Deserialize, Serialize};
 use wms_core::error::{WmsError, Result};
+use crate::sscc::gs1_check_digit;
+
+/// ASCII group separator; scanners send it for the FNC1 that ends a
+/// variable-length GS1 field
+pub const GS1_GROUP_SEPARATOR: char = '\u{1d}';
+
+/// Symbology identifier some scanners put before GS1-128 content
+const GS1_128_SYMBOLOGY_ID: &str = "]C1";
 
 /// Barcode decoder using rxing
 pub struct BarcodeDecoder {
@@ -67,7 +81,8 @@ impl BarcodeDecoder {
                     raw_bytes: Some(result.getRawBytes().to_vec()),
                     orientation: Some(0), // Could extract from ResultPoint
                     confidence: 1.0, // rxing doesn't provide confidence
-                })
+                    gs1: None,
+                }.with_gs1())
             }
             Err(e) => {
                 Err(WmsError::Barcode(format!("Failed to decode barcode: {:?}", e)))
@@ -75,6 +90,39 @@ impl BarcodeDecoder {
         }
     }
     
+    /// Decode a QR code from grayscale image data
+    pub fn decode_qr(&self, image_data: &[u8], width: u32, height: u32) -> Result<BarcodeResult> {
+        let source = Luma8LuminanceSource::new(image_data.to_vec(), width, height);
+        let mut bitmap = BinaryBitmap::new(HybridBinarizer::new(source));
+        let mut reader = QRCodeReader;
+        
+        match reader.decode_with_hints(&mut bitmap, &self.hints) {
+            Ok(result) => {
+                Ok(BarcodeResult {
+                    text: result.getText().to_string(),
+                    format: Self::format_to_string(result.getBarcodeFormat()),
+                    raw_bytes: Some(result.getRawBytes().to_vec()),
+                    orientation: None,
+                    confidence: 1.0,
+                    gs1: None,
+                }.with_gs1())
+            }
+            Err(e) => {
+                Err(WmsError::Barcode(format!("Failed to decode QR code: {:?}", e)))
+            }
+        }
+    }
+    
+    /// Decode a QR code from an encoded (JPEG, PNG, WebP) photo
+    pub fn decode_qr_image(&self, encoded: &[u8]) -> Result<BarcodeResult> {
+        let image = image::load_from_memory(encoded)
+            .map_err(|e| WmsError::Barcode(format!("Unreadable image: {}", e)))?
+            .to_luma8();
+        let (width, height) = image.dimensions();
+        
+        self.decode_qr(image.as_raw(), width, height)
+    }
+    
     /// Decode multiple barcodes from an image
     pub fn decode_multiple(&self, image_data: &[u8], width: u32, height: u32) -> Result<Vec<BarcodeResult>> {
         let source = Luma8LuminanceSource::new(
@@ -98,7 +146,8 @@ impl BarcodeDecoder {
                     raw_bytes: Some(r.getRawBytes().to_vec()),
                     orientation: None,
                     confidence: 1.0,
-                }).collect())
+                    gs1: None,
+                }.with_gs1()).collect())
             }
             Err(e) => {
                 Err(WmsError::Barcode(format!("Failed to decode barcodes: {:?}", e)))
@@ -182,6 +231,10 @@ pub struct BarcodeResult {
     pub orientation: Option<i32>,
     /// Confidence score (0.0 - 1.0)
     pub confidence: f64,
+    /// GS1 fields of Code 128 content that starts with an application
+    /// identifier
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gs1: Option<Gs1Data>,
 }
 
 impl BarcodeResult {
@@ -197,6 +250,199 @@ impl BarcodeResult {
     pub fn is_shipping_barcode(&self) -> bool {
         matches!(self.format.as_str(), "CODE-128" | "CODE-39")
     }
+    
+    /// Parse GS1 fields out of Code 128 content. Content that only looks
+    /// like GS1 but does not parse is left as plain text.
+    fn with_gs1(mut self) -> Self {
+        if self.format == "CODE-128" && Gs1Parser::starts_with_ai(&self.text) {
+            self.gs1 = Gs1Parser::parse(&self.text).ok();
+        }
+        self
+    }
+}
+
+/// Fields of a GS1-128 barcode
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct Gs1Data {
+    /// GTIN-14, AI (01)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gtin: Option<String>,
+    /// Batch or lot number, AI (10)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lot: Option<String>,
+    /// Expiry date, AI (17)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expiry_date: Option<NaiveDate>,
+    /// Count of items, AI (30)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quantity: Option<f64>,
+    /// Serial number, AI (21)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub serial: Option<String>,
+    /// Every field by application identifier, the ones above included
+    pub raw_ais: BTreeMap<String, String>,
+}
+
+/// Length of the data following an application identifier
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum AiLength {
+    /// Exactly this many digits; no separator follows
+    Fixed(usize),
+    /// Up to this many characters, ended by a group separator unless last
+    Variable(usize),
+}
+
+/// Parser for GS1 application identifier (AI) strings
+pub struct Gs1Parser;
+
+impl Gs1Parser {
+    /// Parse scanned GS1-128 content: AIs and their data run together,
+    /// with a group separator after each variable-length field that isn't
+    /// last. A leading `]C1` symbology identifier is skipped. The
+    /// human-readable form with AIs in parentheses is accepted as well.
+    pub fn parse(text: &str) -> Result<Gs1Data> {
+        if !text.is_ascii() {
+            return Err(WmsError::Barcode("GS1 data must be ASCII".to_string()));
+        }
+        let fields = if text.starts_with('(') {
+            Self::bracketed_fields(text)?
+        } else {
+            Self::raw_fields(text)?
+        };
+        if fields.is_empty() {
+            return Err(WmsError::Barcode("No GS1 application identifiers".to_string()));
+        }
+        
+        let mut data = Gs1Data::default();
+        for (ai, value) in fields {
+            match ai {
+                "01" => {
+                    let check = gs1_check_digit(&value[..13]).map(|d| char::from(b'0' + d));
+                    if check != value.chars().last() {
+                        return Err(WmsError::Barcode(format!("GTIN {} has a wrong check digit", value)));
+                    }
+                    data.gtin = Some(value.to_string());
+                }
+                "10" => data.lot = Some(value.to_string()),
+                "17" => {
+                    let date = parse_gs1_date(value)
+                        .ok_or_else(|| WmsError::Barcode(format!("Invalid expiry date {}", value)))?;
+                    data.expiry_date = Some(date);
+                }
+                "21" => data.serial = Some(value.to_string()),
+                "30" => {
+                    let count: u32 = value.parse()
+                        .map_err(|_| WmsError::Barcode(format!("Invalid count {}", value)))?;
+                    data.quantity = Some(count as f64);
+                }
+                _ => {}
+            }
+            if data.raw_ais.insert(ai.to_string(), value.to_string()).is_some() {
+                return Err(WmsError::Barcode(format!("Application identifier ({}) appears twice", ai)));
+            }
+        }
+        Ok(data)
+    }
+    
+    /// Whether `text` starts with a known application identifier
+    pub fn starts_with_ai(text: &str) -> bool {
+        let text = text.strip_prefix(GS1_128_SYMBOLOGY_ID).unwrap_or(text);
+        let text = text.strip_prefix('(').unwrap_or(text).trim_start_matches(GS1_GROUP_SEPARATOR);
+        ai_at(text).is_some()
+    }
+    
+    /// Split `01...10...<GS>30...` into (AI, data) pairs
+    fn raw_fields(text: &str) -> Result<Vec<(&str, &str)>> {
+        let mut rest = text.strip_prefix(GS1_128_SYMBOLOGY_ID).unwrap_or(text)
+            .trim_start_matches(GS1_GROUP_SEPARATOR);
+        let mut fields = Vec::new();
+        while !rest.is_empty() {
+            let (ai_len, length) = ai_at(rest)
+                .ok_or_else(|| WmsError::Barcode(format!("Unknown application identifier at '{}'", rest)))?;
+            let (ai, data) = rest.split_at(ai_len);
+            let end = match length {
+                AiLength::Fixed(digits) => digits.min(data.len()),
+                AiLength::Variable(_) => data.find(GS1_GROUP_SEPARATOR).unwrap_or(data.len()),
+            };
+            fields.push((ai, checked_value(ai, length, &data[..end])?));
+            rest = data[end..].trim_start_matches(GS1_GROUP_SEPARATOR);
+        }
+        Ok(fields)
+    }
+    
+    /// Split `(01)...(10)...` into (AI, data) pairs
+    fn bracketed_fields(text: &str) -> Result<Vec<(&str, &str)>> {
+        text.split('(')
+            .skip(1)
+            .map(|field| {
+                let (ai, data) = field.split_once(')')
+                    .ok_or_else(|| WmsError::Barcode(format!("Unclosed application identifier in '{}'", text)))?;
+                let (_, length) = ai_at(ai)
+                    .filter(|(ai_len, _)| *ai_len == ai.len())
+                    .ok_or_else(|| WmsError::Barcode(format!("Unknown application identifier ({})", ai)))?;
+                Ok((ai, checked_value(ai, length, data)?))
+            })
+            .collect()
+    }
+}
+
+/// Length of the application identifier `text` starts with, and of its
+/// data. Covers the identification, date, quantity and measure AIs used
+/// on trade item and logistics labels.
+fn ai_at(text: &str) -> Option<(usize, AiLength)> {
+    use AiLength::{Fixed, Variable};
+    
+    let ai = |n: usize| text.get(..n).filter(|ai| ai.bytes().all(|b| b.is_ascii_digit()));
+    Some(match ai(2)? {
+        "00" => (2, Fixed(18)),
+        "01" | "02" => (2, Fixed(14)),
+        "10" | "21" | "22" => (2, Variable(20)),
+        "11" | "12" | "13" | "15" | "16" | "17" => (2, Fixed(6)),
+        "20" => (2, Fixed(2)),
+        "30" | "37" => (2, Variable(8)),
+        "90" => (2, Variable(30)),
+        "91" | "92" | "93" | "94" | "95" | "96" | "97" | "98" | "99" => (2, Variable(90)),
+        // Trade measures: the fourth digit places the decimal point
+        "31" | "32" | "33" | "34" | "35" | "36" => {
+            ai(4)?;
+            (4, Fixed(6))
+        }
+        _ => match ai(3)? {
+            "240" | "241" | "250" | "251" | "400" | "401" | "403" => (3, Variable(30)),
+            "402" => (3, Fixed(17)),
+            "410" | "411" | "412" | "413" | "414" | "415" => (3, Fixed(13)),
+            "420" => (3, Variable(20)),
+            "421" => (3, Variable(12)),
+            "422" => (3, Fixed(3)),
+            _ => return None,
+        },
+    })
+}
+
+/// Check a field's data against its AI's length; fixed-length data is
+/// all digits
+fn checked_value<'a>(ai: &str, length: AiLength, value: &'a str) -> Result<&'a str> {
+    let valid = match length {
+        AiLength::Fixed(digits) => value.len() == digits && value.bytes().all(|b| b.is_ascii_digit()),
+        AiLength::Variable(max) => !value.is_empty() && value.len() <= max,
+    };
+    if !valid {
+        return Err(WmsError::Barcode(format!("Invalid data '{}' for application identifier ({})", value, ai)));
+    }
+    Ok(value)
+}
+
+/// A GS1 YYMMDD date in this century. Day 00 stands for the last day of
+/// the month.
+fn parse_gs1_date(value: &str) -> Option<NaiveDate> {
+    let year = 2000 + value.get(..2)?.parse::<i32>().ok()?;
+    let month = value.get(2..4)?.parse().ok()?;
+    match value.get(4..6)?.parse().ok()? {
+        0 => NaiveDate::from_ymd_opt(year, month, 1)?
+            .checked_add_months(Months::new(1))?
+            .pred_opt(),
+        day => NaiveDate::from_ymd_opt(year, month, day),
+    }
 }
 
 #[cfg(test)]
@@ -208,5 +454,53 @@ mod tests {
         let decoder = BarcodeDecoder::new();
         assert!(decoder.hints.len() > 0);
     }
+    
+    #[test]
+    fn test_gs1_parses_concatenated_ais() {
+        // GTIN, expiry and batch from the GS1 General Specifications;
+        // fixed-length fields need no separator
+        let data = Gs1Parser::parse("]C1010950110153000317140704").unwrap();
+        assert_eq!(data.gtin.as_deref(), Some("09501101530003"));
+        assert_eq!(data.expiry_date, NaiveDate::from_ymd_opt(2014, 7, 4));
+        
+        // Batch is variable length, so a group separator ends it
+        let data = Gs1Parser::parse("010001234567890510LOT-42\u{1d}3024\u{1d}17250200").unwrap();
+        assert_eq!(data.lot.as_deref(), Some("LOT-42"));
+        assert_eq!(data.quantity, Some(24.0));
+        assert_eq!(data.expiry_date, NaiveDate::from_ymd_opt(2025, 2, 28), "day 00 is month end");
+        assert_eq!(data.raw_ais.len(), 4);
+    }
+    
+    #[test]
+    fn test_gs1_parses_human_readable_form() {
+        let data = Gs1Parser::parse("(01)09501101530003(17)140704(10)AB-123(21)S1").unwrap();
+        assert_eq!(data.lot.as_deref(), Some("AB-123"));
+        assert_eq!(data.serial.as_deref(), Some("S1"));
+        assert_eq!(data.raw_ais.get("17").map(String::as_str), Some("140704"));
+    }
+    
+    #[test]
+    fn test_gs1_rejects_malformed_data() {
+        assert!(Gs1Parser::parse("0109501101530004").is_err(), "wrong check digit");
+        assert!(Gs1Parser::parse("01095011015300").is_err(), "short GTIN");
+        assert!(Gs1Parser::parse("23ABC").is_err(), "unknown AI");
+        assert!(Gs1Parser::parse("3012345678901").is_err(), "count too long");
+        assert!(Gs1Parser::parse("17141301").is_err(), "month 13");
+        assert!(!Gs1Parser::starts_with_ai("SHP-0001"));
+    }
+    
+    #[test]
+    fn test_only_code_128_results_carry_gs1() {
+        let result = |format: &str| BarcodeResult {
+            text: "0109501101530003".to_string(),
+            format: format.to_string(),
+            raw_bytes: None,
+            orientation: None,
+            confidence: 1.0,
+            gs1: None,
+        }.with_gs1();
+        assert_eq!(result("CODE-128").gs1.unwrap().gtin.as_deref(), Some("09501101530003"));
+        assert!(result("QR").gs1.is_none());
+    }
 }
 
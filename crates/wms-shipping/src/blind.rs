@@ -0,0 +1,278 @@
+//! Blind Receiving
+//!
+//! In a blind count receivers enter what they counted without seeing what
+//! the supplier said to expect, so the paperwork can't nudge the count.
+//! Expected quantities are left out of receipts returned to anyone who may
+//! not see them (`Scope::ReceivingVariances`). When the receipt is
+//! completed its counts are compared to the expectations, and lines off by
+//! more than the tolerance hold the receipt open until a supervisor
+//! acknowledges the variances.
+
+use serde::{Deserialize, Serialize};
+use wms_core::db::Database;
+use wms_core::error::{WmsError, Result};
+use crate::models::Receipt;
+
+/// Tolerance used until one is configured
+pub const DEFAULT_VARIANCE_TOLERANCE_PERCENT: f64 = 2.0;
+
+/// When receipts are counted blind
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum BlindReceivingMode {
+    /// Receivers always see expected quantities
+    #[default]
+    Off,
+    /// Receipts flagged `blind_count` are counted blind
+    PerReceipt,
+    /// Every receipt is counted blind
+    Always,
+}
+
+impl BlindReceivingMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Off => "off",
+            Self::PerReceipt => "per_receipt",
+            Self::Always => "always",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "off" => Some(Self::Off),
+            "per_receipt" => Some(Self::PerReceipt),
+            "always" => Some(Self::Always),
+            _ => None,
+        }
+    }
+}
+
+/// Blind receiving configuration
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct BlindReceivingSettings {
+    pub mode: BlindReceivingMode,
+    /// Largest difference between a line's count and its expected
+    /// quantity, as a percentage of the expected, accepted without a
+    /// supervisor's acknowledgment
+    pub tolerance_percent: f64,
+}
+
+impl Default for BlindReceivingSettings {
+    fn default() -> Self {
+        Self {
+            mode: BlindReceivingMode::Off,
+            tolerance_percent: DEFAULT_VARIANCE_TOLERANCE_PERCENT,
+        }
+    }
+}
+
+impl BlindReceivingSettings {
+    const MODE_KEY: &'static str = "blind_receiving";
+    const TOLERANCE_KEY: &'static str = "blind_receiving_tolerance_percent";
+
+    /// Load from the settings table; missing values are the defaults
+    pub fn load(db: &Database) -> Result<Self> {
+        let defaults = Self::default();
+        Ok(Self {
+            mode: db.get_setting(Self::MODE_KEY)?
+                .and_then(|value| BlindReceivingMode::parse(&value))
+                .unwrap_or(defaults.mode),
+            tolerance_percent: db.get_setting(Self::TOLERANCE_KEY)?
+                .and_then(|value| value.parse::<f64>().ok())
+                .filter(|t| t.is_finite() && *t >= 0.0)
+                .unwrap_or(defaults.tolerance_percent),
+        })
+    }
+
+    /// Store in the settings table
+    pub fn save(&self, db: &Database) -> Result<()> {
+        if !self.tolerance_percent.is_finite() || self.tolerance_percent < 0.0 {
+            return Err(WmsError::validation("Variance tolerance must be zero or more"));
+        }
+        db.set_setting(Self::MODE_KEY, self.mode.as_str())?;
+        db.set_setting(Self::TOLERANCE_KEY, &self.tolerance_percent.to_string())?;
+        Ok(())
+    }
+
+    /// Whether `receipt` is counted blind
+    pub fn applies_to(&self, receipt: &Receipt) -> bool {
+        self.applies(receipt.blind_count)
+    }
+
+    /// Whether a receipt with the given `blind_count` flag is counted blind
+    pub fn applies(&self, blind_count: bool) -> bool {
+        match self.mode {
+            BlindReceivingMode::Off => false,
+            BlindReceivingMode::PerReceipt => blind_count,
+            BlindReceivingMode::Always => true,
+        }
+    }
+}
+
+/// One line's count against its expected quantity
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VarianceLine {
+    pub receipt_item_id: String,
+    pub item_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub item_sku: Option<String>,
+    pub quantity_counted: f64,
+    /// Left out for users who may not see expected quantities
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quantity_expected: Option<f64>,
+    /// Counted less expected
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub variance: Option<f64>,
+    /// None when nothing was expected
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub variance_percent: Option<f64>,
+    pub exceeds_tolerance: bool,
+}
+
+/// Blind counts of a receipt compared to what was expected
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VarianceReport {
+    pub receipt_id: String,
+    pub receipt_number: String,
+    pub tolerance_percent: f64,
+    /// Lines whose count differs from the expected quantity
+    pub lines: Vec<VarianceLine>,
+    /// Some line is off by more than the tolerance
+    pub requires_acknowledgment: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub acknowledged_by: Option<String>,
+}
+
+impl VarianceReport {
+    /// Compare a receipt's counts to its expected quantities
+    pub fn build(receipt: &Receipt, tolerance_percent: f64) -> Self {
+        let lines: Vec<VarianceLine> = receipt.items.iter()
+            .filter_map(|item| {
+                let expected = item.quantity_expected.unwrap_or_default();
+                let variance = item.quantity_received - expected;
+                if variance.abs() < f64::EPSILON {
+                    return None;
+                }
+                let variance_percent = (expected > 0.0).then(|| variance / expected * 100.0);
+                Some(VarianceLine {
+                    receipt_item_id: item.id.clone(),
+                    item_id: item.item_id.clone(),
+                    item_sku: item.item_sku.clone(),
+                    quantity_counted: item.quantity_received,
+                    quantity_expected: Some(expected),
+                    variance: Some(variance),
+                    variance_percent,
+                    exceeds_tolerance: variance_percent.is_none_or(|p| p.abs() > tolerance_percent),
+                })
+            })
+            .collect();
+
+        Self {
+            receipt_id: receipt.id.clone(),
+            receipt_number: receipt.receipt_number.clone(),
+            tolerance_percent,
+            requires_acknowledgment: lines.iter().any(|l| l.exceeds_tolerance),
+            acknowledged_by: receipt.variance_acknowledged_by.clone(),
+            lines,
+        }
+    }
+
+    /// Whether the receipt still waits for a supervisor
+    pub fn awaiting_acknowledgment(&self) -> bool {
+        self.requires_acknowledgment && self.acknowledged_by.is_none()
+    }
+
+    /// Leave out expected quantities, keeping which lines are off
+    pub fn hide_expected_quantities(&mut self) {
+        for line in &mut self.lines {
+            line.quantity_expected = None;
+            line.variance = None;
+            line.variance_percent = None;
+        }
+    }
+}
+
+/// Outcome of completing a receipt
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReceiptCompletion {
+    /// Still `receiving` while variances wait for acknowledgment
+    pub receipt: Receipt,
+    /// Present for blind counts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub variances: Option<VarianceReport>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{ReceiptItem, ReceiptItemStatus, ReceiptStatus};
+    use chrono::Utc;
+
+    fn line(id: &str, expected: f64, counted: f64) -> ReceiptItem {
+        ReceiptItem {
+            id: id.to_string(),
+            receipt_id: "r1".to_string(),
+            item_id: format!("item-{}", id),
+            location_id: None,
+            quantity_expected: Some(expected),
+            quantity_received: counted,
+            quantity_damaged: 0.0,
+            quantity_put_away: 0.0,
+            lot_number: None,
+            expiry_date: None,
+            status: ReceiptItemStatus::Partial,
+            received_by: None,
+            received_at: None,
+            notes: None,
+            item_sku: None,
+            item_name: None,
+            evidence_count: 0,
+            cold_chain: false,
+        }
+    }
+
+    fn receipt(items: Vec<ReceiptItem>) -> Receipt {
+        Receipt {
+            id: "r1".to_string(),
+            receipt_number: "RCV-1".to_string(),
+            status: ReceiptStatus::Receiving,
+            po_number: None,
+            supplier_name: None,
+            supplier_reference: None,
+            expected_date: None,
+            received_date: None,
+            dock_door: None,
+            notes: None,
+            blind_count: true,
+            variance_acknowledged_by: None,
+            variance_acknowledged_at: None,
+            created_by: "u1".to_string(),
+            created_at: Utc::now(),
+            completed_at: None,
+            completed_by: None,
+            items,
+        }
+    }
+
+    #[test]
+    fn test_variances_beyond_tolerance_need_acknowledgment() {
+        let within = receipt(vec![line("a", 100.0, 100.0), line("b", 100.0, 98.0)]);
+        let report = VarianceReport::build(&within, 2.0);
+        assert_eq!(report.lines.len(), 1);
+        assert_eq!(report.lines[0].variance, Some(-2.0));
+        assert!(!report.requires_acknowledgment);
+
+        let mut beyond = receipt(vec![line("a", 100.0, 97.0), line("c", 0.0, 5.0)]);
+        let mut report = VarianceReport::build(&beyond, 2.0);
+        assert!(report.lines.iter().all(|l| l.exceeds_tolerance));
+        assert_eq!(report.lines[1].variance_percent, None);
+        assert!(report.awaiting_acknowledgment());
+
+        report.hide_expected_quantities();
+        assert!(report.lines.iter().all(|l| l.quantity_expected.is_none() && l.variance.is_none()));
+
+        beyond.variance_acknowledged_by = Some("sup".to_string());
+        assert!(!VarianceReport::build(&beyond, 2.0).awaiting_acknowledgment());
+    }
+}
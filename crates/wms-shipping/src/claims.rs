@@ -0,0 +1,233 @@
+//! Damage Claims
+//!
+//! Receivers record notes and photos against a damaged receipt line for
+//! the supplier claim; shipment lines take the same evidence for customer
+//! claims on returns. Photos are stored as attachments owned by the line,
+//! so they are validated and thumbnailed like proof-of-delivery photos. A
+//! claim report collects the damaged lines in a period and exports as a
+//! zip of the photos with a CSV manifest.
+
+use std::collections::HashMap;
+use std::io::{Cursor, Write};
+use chrono::{DateTime, NaiveDate, Utc};
+use csv::Writer;
+use serde::{Deserialize, Serialize};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+use wms_core::error::{WmsError, Result};
+use wms_core::types::DateRange;
+
+/// Attachment owner type for photos on receipt lines
+pub const RECEIPT_LINE_OWNER: &str = "receipt_item";
+
+/// Attachment owner type for photos on shipment lines
+pub const SHIPMENT_LINE_OWNER: &str = "shipment_item";
+
+/// A note and/or photo recorded against a receipt or shipment line
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LineEvidence {
+    pub id: String,
+    /// `RECEIPT_LINE_OWNER` or `SHIPMENT_LINE_OWNER`
+    pub line_type: String,
+    pub line_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    /// Photo attachment, if one was taken
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attachment_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filename: Option<String>,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Photo to attach as evidence
+#[derive(Debug, Clone)]
+pub struct EvidencePhoto {
+    pub filename: String,
+    pub content: Vec<u8>,
+}
+
+/// A damaged line and the evidence recorded for it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DamageClaimLine {
+    pub line_id: String,
+    /// Receipt or shipment the line belongs to
+    pub document_id: String,
+    pub document_number: String,
+    /// Supplier (receipts) or customer (shipments) the claim is against
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub party: Option<String>,
+    /// When the line was received, or when its damage was first recorded
+    pub date: NaiveDate,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub item_sku: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub item_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lot_number: Option<String>,
+    /// Quantity counted as damaged (receipt lines only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quantity_damaged: Option<f64>,
+    pub evidence: Vec<LineEvidence>,
+}
+
+/// Damaged lines in a period
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DamageClaimReport {
+    pub period: DateRange,
+    pub lines: Vec<DamageClaimLine>,
+}
+
+impl DamageClaimReport {
+    /// Evidence photos in the report
+    pub fn photos(&self) -> impl Iterator<Item = (&DamageClaimLine, &LineEvidence)> {
+        self.lines.iter()
+            .flat_map(|line| line.evidence.iter().map(move |e| (line, e)))
+            .filter(|(_, e)| e.attachment_id.is_some())
+    }
+}
+
+/// Exported damage claim bundle
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DamageClaimExport {
+    /// Base64 encoded file data
+    pub data: String,
+    /// MIME content type
+    pub content_type: String,
+    /// Suggested filename
+    pub filename: String,
+}
+
+/// Path of an evidence photo inside the claim zip
+fn photo_path(line: &DamageClaimLine, evidence: &LineEvidence) -> String {
+    let filename = evidence.filename.as_deref().unwrap_or("photo");
+    let safe = |s: &str| -> String {
+        s.chars()
+            .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') { c } else { '_' })
+            .collect()
+    };
+    format!("photos/{}/{}-{}", safe(&line.document_number), evidence.id, safe(filename))
+}
+
+/// Zip holding `manifest.csv` (one row per piece of evidence, or per line
+/// without any) and the photos. `photos` maps attachment ids to content.
+pub fn render_claims_zip(report: &DamageClaimReport, photos: &HashMap<String, Vec<u8>>) -> Result<Vec<u8>> {
+    let csv_error = |e: csv::Error| WmsError::Export(format!("CSV write error: {}", e));
+    let zip_error = |e: zip::result::ZipError| WmsError::Export(format!("Zip write error: {}", e));
+
+    let mut manifest = Writer::from_writer(Vec::new());
+    manifest.write_record([
+        "Document",
+        "Date",
+        "Party",
+        "SKU",
+        "Item",
+        "Lot",
+        "Quantity Damaged",
+        "Note",
+        "Photo",
+        "Recorded By",
+        "Recorded At",
+    ]).map_err(csv_error)?;
+
+    for line in &report.lines {
+        let row = |evidence: Option<&LineEvidence>| [
+            line.document_number.clone(),
+            line.date.to_string(),
+            line.party.clone().unwrap_or_default(),
+            line.item_sku.clone().unwrap_or_default(),
+            line.item_name.clone().unwrap_or_default(),
+            line.lot_number.clone().unwrap_or_default(),
+            line.quantity_damaged.map(|q| q.to_string()).unwrap_or_default(),
+            evidence.and_then(|e| e.note.clone()).unwrap_or_default(),
+            evidence.filter(|e| e.attachment_id.is_some())
+                .map(|e| photo_path(line, e))
+                .unwrap_or_default(),
+            evidence.map(|e| e.created_by.clone()).unwrap_or_default(),
+            evidence.map(|e| e.created_at.to_rfc3339()).unwrap_or_default(),
+        ];
+
+        if line.evidence.is_empty() {
+            manifest.write_record(row(None)).map_err(csv_error)?;
+        }
+        for evidence in &line.evidence {
+            manifest.write_record(row(Some(evidence))).map_err(csv_error)?;
+        }
+    }
+    let manifest = manifest.into_inner()
+        .map_err(|e| WmsError::Export(format!("CSV write error: {}", e)))?;
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.start_file("manifest.csv", SimpleFileOptions::default().compression_method(CompressionMethod::Deflated))
+        .map_err(zip_error)?;
+    zip.write_all(&manifest)?;
+
+    // Photos are already compressed images
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    for (line, evidence) in report.photos() {
+        let Some(content) = evidence.attachment_id.as_ref().and_then(|id| photos.get(id)) else {
+            continue;
+        };
+        zip.start_file(photo_path(line, evidence), stored).map_err(zip_error)?;
+        zip.write_all(content)?;
+    }
+
+    Ok(zip.finish().map_err(zip_error)?.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evidence(id: &str, note: &str, attachment_id: Option<&str>) -> LineEvidence {
+        LineEvidence {
+            id: id.to_string(),
+            line_type: RECEIPT_LINE_OWNER.to_string(),
+            line_id: "l1".to_string(),
+            note: Some(note.to_string()),
+            attachment_id: attachment_id.map(String::from),
+            filename: attachment_id.map(|_| "crushed carton.jpg".to_string()),
+            created_by: "u1".to_string(),
+            created_at: "2025-01-15T09:00:00Z".parse().unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_zip_has_manifest_row_per_evidence_and_photos() {
+        let date: NaiveDate = "2025-01-15".parse().unwrap();
+        let report = DamageClaimReport {
+            period: DateRange::new(date, date),
+            lines: vec![DamageClaimLine {
+                line_id: "l1".to_string(),
+                document_id: "r1".to_string(),
+                document_number: "RCV-00000001".to_string(),
+                party: Some("Acme Supply".to_string()),
+                date,
+                item_sku: Some("WID-1".to_string()),
+                item_name: Some("Widget".to_string()),
+                lot_number: None,
+                quantity_damaged: Some(2.0),
+                evidence: vec![
+                    evidence("e1", "Corner crushed", Some("a1")),
+                    evidence("e2", "Driver signed as damaged", None),
+                ],
+            }],
+        };
+        let photos = HashMap::from([("a1".to_string(), b"jpeg bytes".to_vec())]);
+
+        let data = render_claims_zip(&report, &photos).unwrap();
+        let mut archive = zip::ZipArchive::new(Cursor::new(data)).unwrap();
+        assert_eq!(archive.len(), 2);
+
+        let mut manifest = String::new();
+        std::io::Read::read_to_string(&mut archive.by_name("manifest.csv").unwrap(), &mut manifest).unwrap();
+        let rows: Vec<&str> = manifest.lines().collect();
+        assert_eq!(rows.len(), 3);
+        assert!(rows[1].contains("Corner crushed,photos/RCV-00000001/e1-crushed_carton.jpg"));
+        assert!(rows[2].contains("Driver signed as damaged,,u1"));
+
+        let photo = archive.by_name("photos/RCV-00000001/e1-crushed_carton.jpg").unwrap();
+        assert_eq!(photo.size(), 10);
+    }
+}
@@ -0,0 +1,171 @@
+//! Shipping Compliance
+//!
+//! Large retailers publish routing guides: which carrier and service to
+//! use, whether an order may arrive in several shipments, whether the
+//! delivery must be booked. The requirements are kept per customer (see
+//! `CrmService::upsert_shipping_requirements`) and checked here when a
+//! shipment is confirmed. Blocking violations stop the confirmation unless
+//! someone overrides them with a reason; warnings are only shown.
+
+use serde::{Deserialize, Serialize};
+use crate::models::Shipment;
+
+/// Requirement a violation was raised for
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ComplianceRule {
+    Carrier,
+    Service,
+    Partial,
+    Appointment,
+}
+
+/// Whether a violation stops confirmation
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ViolationSeverity {
+    Blocking,
+    Warning,
+}
+
+/// One way a shipment breaks its customer's requirements
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ComplianceViolation {
+    pub rule: ComplianceRule,
+    pub severity: ViolationSeverity,
+    pub message: String,
+}
+
+/// Result of checking a shipment against its customer's requirements
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComplianceReport {
+    pub shipment_id: String,
+    /// False when the customer has no requirements on file
+    pub has_requirements: bool,
+    pub violations: Vec<ComplianceViolation>,
+    /// Labelling instructions for packers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label_notes: Option<String>,
+}
+
+impl ComplianceReport {
+    /// Violations that stop confirmation
+    pub fn blocking(&self) -> impl Iterator<Item = &ComplianceViolation> {
+        self.violations.iter().filter(|v| v.severity == ViolationSeverity::Blocking)
+    }
+
+    /// Whether confirmation needs an override
+    pub fn is_blocked(&self) -> bool {
+        self.blocking().next().is_some()
+    }
+}
+
+/// Confirm a shipment despite blocking violations
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComplianceOverride {
+    pub reason: String,
+    pub user_id: String,
+}
+
+/// A customer's stored requirements, as far as the checks need them
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CustomerRequirements {
+    pub required_carrier_id: Option<String>,
+    pub required_service: Option<String>,
+    pub allow_partial: bool,
+    pub appointment_required: bool,
+    pub label_notes: Option<String>,
+}
+
+/// Compare a shipment with its customer's requirements. `is_partial` is
+/// whether the order is split across more than one shipment.
+pub(crate) fn check_compliance(
+    shipment: &Shipment,
+    requirements: &CustomerRequirements,
+    is_partial: bool,
+) -> Vec<ComplianceViolation> {
+    let mut violations = Vec::new();
+    let mut blocking = |rule, message: String| {
+        violations.push(ComplianceViolation { rule, severity: ViolationSeverity::Blocking, message });
+    };
+
+    if let Some(required) = &requirements.required_carrier_id {
+        match &shipment.carrier_id {
+            Some(carrier) if carrier == required => {}
+            Some(carrier) => blocking(
+                ComplianceRule::Carrier,
+                format!("Customer requires carrier {}, shipment uses {}", required, carrier),
+            ),
+            None => blocking(
+                ComplianceRule::Carrier,
+                format!("Customer requires carrier {}, shipment has no carrier", required),
+            ),
+        }
+    }
+
+    if let Some(required) = &requirements.required_service {
+        match &shipment.service_type {
+            Some(service) if service.trim().eq_ignore_ascii_case(required.trim()) => {}
+            Some(service) => blocking(
+                ComplianceRule::Service,
+                format!("Customer requires {} service, shipment uses {}", required, service),
+            ),
+            None => blocking(
+                ComplianceRule::Service,
+                format!("Customer requires {} service, shipment has no service", required),
+            ),
+        }
+    }
+
+    if !requirements.allow_partial && is_partial {
+        blocking(
+            ComplianceRule::Partial,
+            "Customer does not accept partial shipments; this order is split".to_string(),
+        );
+    }
+
+    // Appointments are booked with the consignee outside the system, so
+    // this can only be a reminder
+    if requirements.appointment_required {
+        violations.push(ComplianceViolation {
+            rule: ComplianceRule::Appointment,
+            severity: ViolationSeverity::Warning,
+            message: "Customer requires a delivery appointment; book it before the shipment leaves"
+                .to_string(),
+        });
+    }
+
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wms_core::test_support::ShipmentBuilder;
+
+    #[test]
+    fn test_each_rule_reports_its_severity() {
+        let shipment: Shipment = ShipmentBuilder::new("SHP-1")
+            .carrier("car_fedex")
+            .service_type("ground")
+            .build();
+        let requirements = CustomerRequirements {
+            required_carrier_id: Some("car_ups".to_string()),
+            required_service: Some("GROUND".to_string()),
+            allow_partial: false,
+            appointment_required: true,
+            label_notes: None,
+        };
+
+        let violations = check_compliance(&shipment, &requirements, true);
+        let rules: Vec<_> = violations.iter().map(|v| (v.rule, v.severity)).collect();
+        assert_eq!(rules, vec![
+            (ComplianceRule::Carrier, ViolationSeverity::Blocking),
+            (ComplianceRule::Partial, ViolationSeverity::Blocking),
+            (ComplianceRule::Appointment, ViolationSeverity::Warning),
+        ]);
+
+        let whole_order = CustomerRequirements { allow_partial: true, ..Default::default() };
+        assert!(check_compliance(&shipment, &whole_order, true).is_empty());
+    }
+}
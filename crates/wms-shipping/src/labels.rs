@@ -1,10 +1,14 @@
 //! Label Generation
 //! 
 //! ZPL (Zebra Programming Language) label generation for thermal printers
-//! and PDF generation for standard documents.
+//! and PDF generation for labels and standard documents.
 
-use printpdf::*;
-use std::io::BufWriter;
+use wms_core::error::Result;
+use wms_core::pdf::PdfGenerator;
+use wms_core::types::{Length, Measurement, MeasurementPreferences, UnitSystem, Weight};
+use crate::models::ShipmentPackage;
+use crate::raster::BarcodeSymbology;
+use crate::sscc::SSCC_AI;
 
 /// ZPL Label Builder
 /// 
@@ -61,6 +65,16 @@ impl ZplLabel {
         self
     }
     
+    /// Add a GS1-128 barcode of an SSCC, application identifier (00)
+    pub fn add_sscc_barcode(mut self, x: u32, y: u32, sscc: &str, height: u32) -> Self {
+        // >; = start in subset C, >8 = FNC1, which makes it GS1-128
+        self.elements.push(format!(
+            "^FO{},{}^BCN,{},N,N,N^FD>;>8{}{}^FS",
+            x, y, height, SSCC_AI, sscc
+        ));
+        self
+    }
+    
     /// Add a Code 39 barcode
     pub fn add_barcode_39(mut self, x: u32, y: u32, data: &str, height: u32) -> Self {
         // ^B3 = Code 39 barcode
@@ -150,122 +164,85 @@ impl Default for ZplLabel {
     }
 }
 
-/// PDF Generator for shipping documents
-pub struct PdfGenerator {
-    doc: PdfDocumentReference,
-    current_page: PdfPageIndex,
-    current_layer: PdfLayerIndex,
+/// Package dimensions as `L x W x H unit`, if all three are known
+pub fn format_dimensions(package: &ShipmentPackage, units: UnitSystem) -> Option<String> {
+    let unit = Length::unit(units);
+    let value = |cm: f64| format!("{:.*}", unit.decimals, Length::from_cm(cm).display_value(units));
+    
+    Some(format!(
+        "{} x {} x {} {}",
+        value(package.length_cm?),
+        value(package.width_cm?),
+        value(package.height_cm?),
+        unit.symbol
+    ))
 }
 
-impl PdfGenerator {
-    /// Create a new PDF document
-    pub fn new(title: &str) -> Self {
-        let (doc, page1, layer1) = PdfDocument::new(
-            title,
-            Mm(210.0), // A4 width
-            Mm(297.0), // A4 height
-            "Layer 1",
-        );
-
-        Self {
-            doc,
-            current_page: page1,
-            current_layer: layer1,
-        }
-    }
-
-    /// Create a shipping document (letter size)
-    pub fn new_shipping_doc(title: &str) -> Self {
-        let (doc, page1, layer1) = PdfDocument::new(
-            title,
-            Mm(215.9), // Letter width
-            Mm(279.4), // Letter height
-            "Layer 1",
-        );
+/// Shipping label page size: 4" x 6"
+const LABEL_WIDTH_MM: f32 = 101.6;
+const LABEL_HEIGHT_MM: f32 = 152.4;
+const LABEL_MARGIN_MM: f32 = 8.0;
 
-        Self {
-            doc,
-            current_page: page1,
-            current_layer: layer1,
+/// Create a 4" x 6" shipping label PDF for office printers: the address
+/// block, the shipment number as a Code 128 barcode, and the weight if known
+pub fn create_shipping_label_pdf(
+    shipment_number: &str,
+    ship_to: &[String],
+    weight: Option<&str>,
+) -> Result<Vec<u8>> {
+    let pdf = PdfGenerator::new_sized("Shipping Label", LABEL_WIDTH_MM, LABEL_HEIGHT_MM);
+    let right = LABEL_WIDTH_MM - LABEL_MARGIN_MM;
+    
+    // Address block; the first line is the recipient's name
+    pdf.add_bold_text(LABEL_MARGIN_MM, 140.0, "SHIP TO", 10.0);
+    let mut y = 131.0;
+    for (i, line) in ship_to.iter().enumerate() {
+        if i == 0 {
+            pdf.add_bold_text(LABEL_MARGIN_MM, y, line, 14.0);
+        } else {
+            pdf.add_text(LABEL_MARGIN_MM, y, line, 11.0);
         }
+        y -= 7.0;
     }
-
-    /// Add a new page
-    pub fn add_page(&mut self) {
-        let (page, layer) = self.doc.add_page(
-            Mm(215.9),
-            Mm(279.4),
-            "Layer 1",
-        );
-        self.current_page = page;
-        self.current_layer = layer;
-    }
-
-    /// Add text to the current page
-    pub fn add_text(&self, x: f32, y: f32, text: &str, font_size: f32) {
-        let font = self.doc.add_builtin_font(BuiltinFont::Helvetica).unwrap();
-        let current_layer = self.doc.get_page(self.current_page).get_layer(self.current_layer);
-
-        current_layer.use_text(text, font_size, Mm(x), Mm(y), &font);
-    }
-
-    /// Add bold text
-    pub fn add_bold_text(&self, x: f32, y: f32, text: &str, font_size: f32) {
-        let font = self.doc.add_builtin_font(BuiltinFont::HelveticaBold).unwrap();
-        let current_layer = self.doc.get_page(self.current_page).get_layer(self.current_layer);
-
-        current_layer.use_text(text, font_size, Mm(x), Mm(y), &font);
-    }
-
-    /// Draw a line
-    pub fn draw_line(&self, x1: f32, y1: f32, x2: f32, y2: f32) {
-        let current_layer = self.doc.get_page(self.current_page).get_layer(self.current_layer);
-
-        let points = vec![
-            (Point::new(Mm(x1), Mm(y1)), false),
-            (Point::new(Mm(x2), Mm(y2)), false),
-        ];
-
-        let line = Line {
-            points,
-            is_closed: false,
-        };
-
-        current_layer.add_line(line);
-    }
-
-    /// Draw a rectangle
-    pub fn draw_rect(&self, x: f32, y: f32, width: f32, height: f32) {
-        let current_layer = self.doc.get_page(self.current_page).get_layer(self.current_layer);
-
-        let points = vec![
-            (Point::new(Mm(x), Mm(y)), false),
-            (Point::new(Mm(x + width), Mm(y)), false),
-            (Point::new(Mm(x + width), Mm(y + height)), false),
-            (Point::new(Mm(x), Mm(y + height)), false),
-        ];
-
-        let rect = Line {
-            points,
-            is_closed: true,
-        };
-
-        current_layer.add_line(rect);
+    pdf.draw_line(LABEL_MARGIN_MM, 95.0, right, 95.0);
+    
+    draw_code128(&pdf, shipment_number, LABEL_MARGIN_MM, 60.0, right - LABEL_MARGIN_MM, 28.0)?;
+    pdf.add_text(LABEL_MARGIN_MM, 52.0, &format!("Ship #: {}", shipment_number), 11.0);
+    if let Some(weight) = weight {
+        pdf.add_text(LABEL_MARGIN_MM, 45.0, &format!("Weight: {}", weight), 11.0);
     }
+    
+    Ok(pdf.save_to_bytes())
+}
 
-    /// Save to bytes
-    pub fn save_to_bytes(self) -> Vec<u8> {
-        let mut buffer = BufWriter::new(Vec::new());
-        self.doc.save(&mut buffer).unwrap();
-        buffer.into_inner().unwrap()
+/// Draw `data` as a Code 128 barcode filling the given box, one filled
+/// rectangle per bar
+fn draw_code128(pdf: &PdfGenerator, data: &str, x: f32, y: f32, width: f32, height: f32) -> Result<()> {
+    let modules = BarcodeSymbology::Code128.encode(data)?;
+    let module_width = width / modules.len() as f32;
+    
+    let mut start = None;
+    for (i, dark) in modules.iter().chain([&false]).enumerate() {
+        match (dark, start) {
+            (true, None) => start = Some(i),
+            (false, Some(from)) => {
+                pdf.fill_rect(x + from as f32 * module_width, y, (i - from) as f32 * module_width, height);
+                start = None;
+            }
+            _ => {}
+        }
     }
+    Ok(())
 }
 
-/// Create a packing slip PDF
+/// Create a packing slip PDF, with package weights and dimensions in the
+/// configured units
 pub fn create_packing_slip(
     shipment_number: &str,
     ship_to: &str,
     items: &[(String, String, f64)], // (SKU, Name, Qty)
+    packages: &[ShipmentPackage],
+    units: &MeasurementPreferences,
 ) -> Vec<u8> {
     let pdf = PdfGenerator::new_shipping_doc("Packing Slip");
     
@@ -297,6 +274,26 @@ pub fn create_packing_slip(
         y -= 6.0;
     }
     
+    // Packages
+    if !packages.is_empty() {
+        y -= 4.0;
+        pdf.draw_line(20.0, y, 195.0, y);
+        pdf.add_bold_text(20.0, y - 5.0, "Package", 10.0);
+        pdf.add_bold_text(60.0, y - 5.0, "Weight", 10.0);
+        pdf.add_bold_text(100.0, y - 5.0, "Dimensions", 10.0);
+        y -= 12.0;
+        
+        for package in packages {
+            let weight = package.weight_kg
+                .map(|kg| Weight::from_kg(kg).to_display(units.weight))
+                .unwrap_or_default();
+            pdf.add_text(20.0, y, &package.package_number.to_string(), 9.0);
+            pdf.add_text(60.0, y, &weight, 9.0);
+            pdf.add_text(100.0, y, &format_dimensions(package, units.length).unwrap_or_default(), 9.0);
+            y -= 6.0;
+        }
+    }
+    
     pdf.save_to_bytes()
 }
 
@@ -318,6 +315,41 @@ mod tests {
         assert!(zpl.contains("123456789"));
     }
     
+    #[test]
+    fn test_package_dimensions_in_configured_units() {
+        let package = ShipmentPackage {
+            id: "p1".to_string(),
+            shipment_id: "s1".to_string(),
+            package_number: 1,
+            tracking_number: None,
+            weight_kg: Some(2.0),
+            length_cm: Some(30.48),
+            width_cm: Some(20.32),
+            height_cm: Some(10.0),
+            package_type: None,
+            lpn: None,
+            parent_package_id: None,
+            closed_at: None,
+            floor_loaded: false,
+            created_at: chrono::Utc::now(),
+        };
+        
+        assert_eq!(format_dimensions(&package, UnitSystem::Metric).as_deref(), Some("30.5 x 20.3 x 10.0 cm"));
+        assert_eq!(format_dimensions(&package, UnitSystem::Imperial).as_deref(), Some("12.00 x 8.00 x 3.94 in"));
+        
+        let unmeasured = ShipmentPackage { height_cm: None, ..package };
+        assert_eq!(format_dimensions(&unmeasured, UnitSystem::Metric), None);
+    }
+    
+    #[test]
+    fn test_sscc_barcode_is_gs1_128() {
+        let zpl = ZplLabel::new()
+            .add_sscc_barcode(50, 50, "376104250021234569", 150)
+            .build();
+        
+        assert!(zpl.contains("^FD>;>800376104250021234569^FS"));
+    }
+    
     #[test]
     fn test_zpl_qr_code() {
         let zpl = ZplLabel::new()
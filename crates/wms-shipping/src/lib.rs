@@ -2,18 +2,83 @@
 //! 
 //! Provides shipping and receiving functionality including:
 //! - Outbound shipment management
+//! - Shipment splitting for backorders
+//! - Customer routing guide compliance checks at confirmation
+//! - SSCC license plates on packages, with cartons built onto pallets
 //! - Inbound receipt processing
+//! - Blind receiving with supervisor-acknowledged count variances
+//! - Over-receipts confirmed by the receiver and recorded as discrepancies,
+//!   past a tolerance passed only with a supervisor override
+//! - Received stock posted on completion, damaged units into quarantine
+//! - Damage notes and photos on receipt and shipment lines, exported as claims
+//! - Advance ship notice (ASN) import, including trailer manifest QR codes
 //! - Barcode scanning and decoding
-//! - ZPL label generation for thermal printers
+//! - GS1-128 application identifiers (GTIN, lot, expiry, count) read from
+//!   supplier carton labels into receipt lines
+//! - Code 128, Code 39 and EAN-13 barcodes and item labels as PNG
+//! - Scan audit log with duplicate scan protection
+//! - Scanned barcodes resolved to their receipt line, or flagged as unexpected
+//! - Shipping labels as ZPL for thermal printers or PDF for office printers
+//! - Print queue with retries for network label printers
+//! - Carrier tracking ingestion (webhooks and polling)
+//! - Shipping cost reconciliation against carrier invoices
 //! - PDF document generation
 
 mod models;
 mod service;
 mod barcode;
 mod labels;
+mod raster;
+mod sscc;
+mod split;
+mod asn;
+mod blind;
+mod over_receipt;
+mod printing;
+mod tracking;
+mod reconciliation;
+mod scanning;
+mod compliance;
+mod claims;
 
 pub use models::*;
-pub use service::ShippingService;
-pub use barcode::{BarcodeDecoder, BarcodeResult};
-pub use labels::{ZplLabel, PdfGenerator};
+pub use service::{ShippingService, MAX_RECEIPT_PAGE_SIZE, MAX_SHIPMENT_PAGE_SIZE};
+pub use barcode::{BarcodeDecoder, BarcodeResult, Gs1Data, Gs1Parser, GS1_GROUP_SEPARATOR};
+pub use labels::ZplLabel;
+pub use raster::{ean13_check_digit, is_valid_ean13, item_label_png, BarcodeGenerator, BarcodeSymbology};
+pub use sscc::{gs1_check_digit, is_valid_sscc, SsccSettings, SSCC_AI};
+pub use asn::{parse_asn, AsnDocument, AsnFormat, AsnImport, AsnLine, UnresolvedAsnLine};
+pub use blind::{
+    BlindReceivingMode, BlindReceivingSettings, ReceiptCompletion, VarianceLine, VarianceReport,
+    DEFAULT_VARIANCE_TOLERANCE_PERCENT,
+};
+pub use over_receipt::OverReceiptSettings;
+pub use printing::{
+    PrintJob, PrintJobStatus, PrintQueue, Printer, PrinterService, PrinterStatus,
+    PrinterTransport, SharedTransport, TcpPrinterTransport, parse_host_status,
+};
+pub use compliance::{
+    ComplianceOverride, ComplianceReport, ComplianceRule, ComplianceViolation, ViolationSeverity,
+};
+pub use claims::{
+    render_claims_zip, DamageClaimExport, DamageClaimLine, DamageClaimReport, EvidencePhoto,
+    LineEvidence, RECEIPT_LINE_OWNER, SHIPMENT_LINE_OWNER,
+};
+pub use split::{plan_split, split_by_availability, LineSplit};
+pub use tracking::{
+    map_carrier_status, parse_tracking_webhook, CarrierTrackingProvider, SharedTrackingProvider,
+    TrackingEvent, TrackingSource, TrackingUpdater,
+};
+pub use reconciliation::{
+    build_reconciliation, parse_carrier_invoice, render_reconciliation_csv, CarrierCostSummary,
+    CarrierInvoiceImport, CarrierInvoiceLine, CostReconciliation, CostReconciliationExport,
+    CostVarianceLine, InvoicedShipment, Surcharge, UnmatchedInvoiceLine,
+    DEFAULT_VARIANCE_THRESHOLD_PERCENT,
+};
+pub use scanning::{
+    ReceiptScanMatch, ScanContext, ScanEvent, ScanLog, ScanResult, DEFAULT_DUPLICATE_SCAN_WINDOW,
+};
+#[cfg(feature = "carrier-tracking")]
+pub use tracking::HttpTrackingProvider;
+pub use wms_core::pdf::PdfGenerator;
 
@@ -1,8 +1,11 @@
 //! Shipping Data Models
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
+use wms_core::custom_fields::CustomFieldValues;
+use wms_core::error::{Result, WmsError};
 use wms_core::types::Address;
+use crate::barcode::Gs1Data;
 
 /// Outbound shipment
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -26,6 +29,11 @@ pub struct Shipment {
     pub expected_delivery_date: Option<DateTime<Utc>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub actual_delivery_date: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cancelled_at: Option<DateTime<Utc>>,
+    /// Why the shipment was cancelled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cancel_reason: Option<String>,
     pub ship_to: ShipToAddress,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub total_weight_kg: Option<f64>,
@@ -35,14 +43,35 @@ pub struct Shipment {
     pub shipping_cost: Option<f64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub insurance_value: Option<f64>,
+    /// Value of the goods, counted against the customer's credit limit
+    /// until delivered
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub declared_value: Option<f64>,
+    /// Sum of line extended prices (set at confirmation)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subtotal: Option<f64>,
+    /// Subtotal plus shipping cost (set at confirmation)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub order_total: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub currency_code: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub special_instructions: Option<String>,
+    /// Shipment this one was split from (backorders)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_shipment_id: Option<String>,
     #[serde(default)]
     pub label_printed: bool,
+    /// Values of the deployment's custom shipment fields
+    #[serde(default, skip_serializing_if = "CustomFieldValues::is_empty")]
+    pub custom_fields: CustomFieldValues,
     pub created_by: String,
     pub created_at: DateTime<Utc>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub updated_at: Option<DateTime<Utc>>,
+    /// Row version; edits must send back the version they were based on
+    #[serde(default)]
+    pub version: i64,
     /// Line items in this shipment
     #[serde(default)]
     pub items: Vec<ShipmentItem>,
@@ -74,6 +103,129 @@ impl Default for ShipmentStatus {
     }
 }
 
+impl ShipmentStatus {
+    /// Parse the stored lowercase status
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "draft" => Some(Self::Draft),
+            "confirmed" => Some(Self::Confirmed),
+            "picking" => Some(Self::Picking),
+            "packed" => Some(Self::Packed),
+            "shipped" => Some(Self::Shipped),
+            "delivered" => Some(Self::Delivered),
+            "cancelled" => Some(Self::Cancelled),
+            _ => None,
+        }
+    }
+    
+    /// Stored lowercase status
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Draft => "draft",
+            Self::Confirmed => "confirmed",
+            Self::Picking => "picking",
+            Self::Packed => "packed",
+            Self::Shipped => "shipped",
+            Self::Delivered => "delivered",
+            Self::Cancelled => "cancelled",
+        }
+    }
+    
+    /// Statuses a shipment can move to from this one: one step forward,
+    /// or cancelled until it has shipped
+    pub fn allowed_next(&self) -> &'static [ShipmentStatus] {
+        match self {
+            Self::Draft => &[Self::Confirmed, Self::Cancelled],
+            Self::Confirmed => &[Self::Picking, Self::Cancelled],
+            Self::Picking => &[Self::Packed, Self::Cancelled],
+            Self::Packed => &[Self::Shipped, Self::Cancelled],
+            Self::Shipped => &[Self::Delivered],
+            Self::Delivered | Self::Cancelled => &[],
+        }
+    }
+    
+    /// Fail with the allowed next statuses unless the shipment can move
+    /// from this status to `next`
+    pub fn check_transition(&self, next: ShipmentStatus) -> Result<()> {
+        if self.allowed_next().contains(&next) {
+            return Ok(());
+        }
+        Err(WmsError::InvalidTransition {
+            from: self.as_str().to_string(),
+            to: next.as_str().to_string(),
+            allowed: self.allowed_next().iter().map(|s| s.as_str().to_string()).collect(),
+        })
+    }
+    
+    /// Whether lines can still be moved to another shipment
+    pub fn can_split(&self) -> bool {
+        matches!(self, Self::Draft | Self::Confirmed | Self::Picking)
+    }
+}
+
+/// Which shipments to list; unset fields match everything
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShipmentFilter {
+    #[serde(default)]
+    pub status: Option<ShipmentStatus>,
+    #[serde(default)]
+    pub customer_id: Option<String>,
+    /// First day of the range, by ship date or, until shipped, created date
+    #[serde(default)]
+    pub date_from: Option<NaiveDate>,
+    /// Last day of the range
+    #[serde(default)]
+    pub date_to: Option<NaiveDate>,
+    /// Text matched against shipment number, tracking number and ship-to name
+    #[serde(default)]
+    pub search: Option<String>,
+    #[serde(default = "default_page")]
+    pub page: u32,
+    #[serde(default = "default_page_size")]
+    pub page_size: u32,
+}
+
+impl Default for ShipmentFilter {
+    fn default() -> Self {
+        Self {
+            status: None,
+            customer_id: None,
+            date_from: None,
+            date_to: None,
+            search: None,
+            page: default_page(),
+            page_size: default_page_size(),
+        }
+    }
+}
+
+fn default_page() -> u32 {
+    1
+}
+
+fn default_page_size() -> u32 {
+    25
+}
+
+/// One page of a list with the number of matches across all pages
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListPage<T> {
+    pub items: Vec<T>,
+    pub total_count: u64,
+    pub page: u32,
+    pub page_size: u32,
+    pub total_pages: u32,
+}
+
+impl<T> ListPage<T> {
+    pub fn new(items: Vec<T>, total_count: u64, page: u32, page_size: u32) -> Self {
+        let total_pages = total_count.div_ceil(u64::from(page_size.max(1))) as u32;
+        Self { items, total_count, page, page_size, total_pages }
+    }
+}
+
+pub type ShipmentPage = ListPage<Shipment>;
+
 /// Ship-to address with contact info
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ShipToAddress {
@@ -102,16 +254,27 @@ pub struct ShipmentItem {
     pub lot_number: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub serial_number: Option<String>,
+    /// Unit price resolved from the customer's price list at confirmation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unit_price: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extended_price: Option<f64>,
     pub status: ShipmentItemStatus,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub picked_by: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub picked_at: Option<DateTime<Utc>>,
+    /// Why the line was closed short of the quantity ordered
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub short_pick_reason: Option<String>,
     /// Item details (populated on read)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub item_sku: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub item_name: Option<String>,
+    /// Damage notes and photos recorded on the line
+    #[serde(default)]
+    pub evidence_count: u32,
 }
 
 /// Shipment item status
@@ -131,6 +294,46 @@ impl Default for ShipmentItemStatus {
     }
 }
 
+impl ShipmentItemStatus {
+    /// Parse the stored lowercase status
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "pending" => Some(Self::Pending),
+            "picking" => Some(Self::Picking),
+            "picked" => Some(Self::Picked),
+            "packed" => Some(Self::Packed),
+            "shipped" => Some(Self::Shipped),
+            _ => None,
+        }
+    }
+}
+
+/// Quantity of one line to move to a backorder shipment
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SplitLine {
+    pub shipment_item_id: String,
+    pub quantity: f64,
+}
+
+/// How to split a shipment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum SplitSpec {
+    /// Move the given line quantities
+    Manual { lines: Vec<SplitLine> },
+    /// Move whatever current availability cannot cover
+    AutoByAvailability,
+}
+
+/// Result of splitting a shipment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShipmentSplit {
+    /// The original shipment with reduced lines
+    pub parent: Shipment,
+    /// The new backorder shipment (Draft)
+    pub child: Shipment,
+}
+
 /// Package/carton in a shipment
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ShipmentPackage {
@@ -149,9 +352,119 @@ pub struct ShipmentPackage {
     pub height_cm: Option<f64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub package_type: Option<String>,
+    /// License plate number (SSCC-18)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lpn: Option<String>,
+    /// Pallet package this carton was built onto
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_package_id: Option<String>,
+    /// When packing finished; closed cartons must be palletized or
+    /// floor-loaded before the shipment ships
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub closed_at: Option<DateTime<Utc>>,
+    /// Loaded loose onto the trailer floor instead of onto a pallet
+    #[serde(default)]
+    pub floor_loaded: bool,
     pub created_at: DateTime<Utc>,
 }
 
+impl ShipmentPackage {
+    /// Package type of pallets built from cartons
+    pub const PALLET: &'static str = "pallet";
+
+    pub fn is_pallet(&self) -> bool {
+        self.package_type.as_deref() == Some(Self::PALLET)
+    }
+}
+
+/// Carton to add to a shipment
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NewPackage {
+    #[serde(default)]
+    pub weight_kg: Option<f64>,
+    #[serde(default)]
+    pub length_cm: Option<f64>,
+    #[serde(default)]
+    pub width_cm: Option<f64>,
+    #[serde(default)]
+    pub height_cm: Option<f64>,
+    /// box, envelope, tube, ...
+    #[serde(default)]
+    pub package_type: Option<String>,
+}
+
+/// Sequenced pick list for a shipment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PickList {
+    pub shipment_id: String,
+    pub shipment_number: String,
+    /// Lines in walking order
+    pub lines: Vec<PickListLine>,
+    /// Estimated walk in meters (None when locations lack coordinates)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub estimated_distance_m: Option<f64>,
+}
+
+/// Single line on a pick list
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PickListLine {
+    pub shipment_item_id: String,
+    pub item_id: String,
+    pub item_sku: String,
+    pub item_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location_code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lot_number: Option<String>,
+    pub quantity: f64,
+    /// Base64 JPEG thumbnail of the item's primary image
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thumbnail: Option<String>,
+}
+
+/// Pick list for a wave of shipments picked in one walk
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WavePickList {
+    pub shipment_ids: Vec<String>,
+    /// Lines in walking order; each item, location and lot is visited once
+    pub lines: Vec<WavePickLine>,
+    /// Estimated walk in meters (None when locations lack coordinates)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub estimated_distance_m: Option<f64>,
+}
+
+/// Single stop on a wave pick list
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WavePickLine {
+    pub item_id: String,
+    pub item_sku: String,
+    pub item_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location_code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lot_number: Option<String>,
+    /// Total to pick here across the wave
+    pub quantity: f64,
+    /// How the picked quantity is split between the shipments
+    pub allocations: Vec<WaveAllocation>,
+    /// Base64 JPEG thumbnail of the item's primary image
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thumbnail: Option<String>,
+}
+
+/// Share of a wave pick line owed to one shipment line
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WaveAllocation {
+    pub shipment_id: String,
+    pub shipment_number: String,
+    pub shipment_item_id: String,
+    pub quantity: f64,
+}
+
 /// Shipping carrier
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Carrier {
@@ -188,6 +501,14 @@ pub struct Receipt {
     pub dock_door: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub notes: Option<String>,
+    /// Counted blind when blind receiving is set per receipt
+    #[serde(default)]
+    pub blind_count: bool,
+    /// Supervisor who accepted the receipt's blind count variances
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub variance_acknowledged_by: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub variance_acknowledged_at: Option<DateTime<Utc>>,
     pub created_by: String,
     pub created_at: DateTime<Utc>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -215,6 +536,67 @@ impl Default for ReceiptStatus {
     }
 }
 
+impl ReceiptStatus {
+    /// Parse the stored lowercase status
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "pending" => Some(Self::Pending),
+            "receiving" => Some(Self::Receiving),
+            "completed" => Some(Self::Completed),
+            "cancelled" => Some(Self::Cancelled),
+            _ => None,
+        }
+    }
+}
+
+/// Which receipts to list; unset fields match everything
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReceiptFilter {
+    #[serde(default)]
+    pub status: Option<ReceiptStatus>,
+    /// Part of the supplier's name
+    #[serde(default)]
+    pub supplier: Option<String>,
+    /// First day of the expected date range (UTC)
+    #[serde(default)]
+    pub expected_from: Option<NaiveDate>,
+    /// Last day of the expected date range (UTC)
+    #[serde(default)]
+    pub expected_to: Option<NaiveDate>,
+    #[serde(default = "default_page")]
+    pub page: u32,
+    #[serde(default = "default_page_size")]
+    pub page_size: u32,
+}
+
+impl Default for ReceiptFilter {
+    fn default() -> Self {
+        Self {
+            status: None,
+            supplier: None,
+            expected_from: None,
+            expected_to: None,
+            page: default_page(),
+            page_size: default_page_size(),
+        }
+    }
+}
+
+pub type ReceiptPage = ListPage<Receipt>;
+
+/// Receiving dock workload for the dashboard
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ReceivingSummary {
+    /// Not started, expected today
+    pub pending: u64,
+    /// Being received now
+    pub receiving: u64,
+    /// Completed today
+    pub completed: u64,
+    /// Not completed and past their expected time
+    pub overdue: u64,
+}
+
 /// Receipt line item
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ReceiptItem {
@@ -223,11 +605,16 @@ pub struct ReceiptItem {
     pub item_id: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub location_id: Option<String>,
-    pub quantity_expected: f64,
+    /// Left out for users who may not see it on blind counts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quantity_expected: Option<f64>,
     #[serde(default)]
     pub quantity_received: f64,
     #[serde(default)]
     pub quantity_damaged: f64,
+    /// Already scanned into a location; completing the receipt posts the rest
+    #[serde(default)]
+    pub quantity_put_away: f64,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub lot_number: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -244,6 +631,29 @@ pub struct ReceiptItem {
     pub item_sku: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub item_name: Option<String>,
+    /// Damage notes and photos recorded on the line
+    #[serde(default)]
+    pub evidence_count: u32,
+    /// The item's category needs a cold chain; receivers are asked for a
+    /// temperature reading
+    #[serde(default)]
+    pub cold_chain: bool,
+}
+
+impl ReceiptItem {
+    /// Fill in the lot, expiry and quantity from a scanned GS1-128 carton
+    /// label, keeping what the receiver already entered
+    pub fn prefill_from_gs1(&mut self, gs1: &Gs1Data) {
+        if self.lot_number.is_none() {
+            self.lot_number = gs1.lot.clone();
+        }
+        if self.expiry_date.is_none() {
+            self.expiry_date = gs1.expiry_date.and_then(|d| d.and_hms_opt(0, 0, 0)).map(|d| d.and_utc());
+        }
+        if self.quantity_received == 0.0 {
+            self.quantity_received = gs1.quantity.unwrap_or_default();
+        }
+    }
 }
 
 /// Receipt item status
@@ -262,6 +672,59 @@ impl Default for ReceiptItemStatus {
     }
 }
 
+impl ReceiptItemStatus {
+    /// Parse the stored lowercase status
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "pending" => Some(Self::Pending),
+            "partial" => Some(Self::Partial),
+            "complete" => Some(Self::Complete),
+            "damaged" => Some(Self::Damaged),
+            _ => None,
+        }
+    }
+}
+
+/// A receipt line received differently from what was expected
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReceiptDiscrepancy {
+    pub id: String,
+    pub receipt_id: String,
+    pub receipt_item_id: String,
+    pub item_id: String,
+    pub discrepancy_type: DiscrepancyType,
+    pub quantity_expected: f64,
+    pub quantity_received: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recorded_by: Option<String>,
+    pub recorded_at: DateTime<Utc>,
+}
+
+/// Kind of receipt discrepancy
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DiscrepancyType {
+    /// More received than expected
+    #[default]
+    OverReceipt,
+}
+
+impl DiscrepancyType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::OverReceipt => "over_receipt",
+        }
+    }
+    
+    /// Parse the stored snake_case type
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "over_receipt" => Some(Self::OverReceipt),
+            _ => None,
+        }
+    }
+}
+
 /// Generated shipping label
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ShippingLabel {
@@ -273,6 +736,8 @@ pub struct ShippingLabel {
     pub format: LabelFormat,
     /// Base64 encoded label data
     pub data: String,
+    /// MIME type of the decoded data, for downloads and print dialogs
+    pub content_type: String,
     pub created_at: DateTime<Utc>,
 }
 
@@ -295,3 +760,28 @@ pub enum LabelFormat {
     Png,
 }
 
+impl Default for LabelFormat {
+    fn default() -> Self {
+        Self::Zpl
+    }
+}
+
+impl LabelFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Zpl => "zpl",
+            Self::Pdf => "pdf",
+            Self::Png => "png",
+        }
+    }
+    
+    /// MIME type of labels in this format
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Zpl => "application/zpl",
+            Self::Pdf => "application/pdf",
+            Self::Png => "image/png",
+        }
+    }
+}
+
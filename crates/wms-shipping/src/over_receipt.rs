@@ -0,0 +1,69 @@
+//! Over-Receipt Tolerance
+//!
+//! How much more of a line may be received than was expected before a
+//! supervisor has to approve it with an override. Without a configured
+//! tolerance any confirmed over-receipt is accepted. Blind counts are left
+//! to their variance review instead, since the receiver can't see the
+//! expectation.
+
+use serde::{Deserialize, Serialize};
+use wms_core::db::Database;
+use wms_core::error::{WmsError, Result};
+
+/// Over-receipt configuration
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub struct OverReceiptSettings {
+    /// Largest excess over the expected quantity, as a percentage of it;
+    /// None accepts any quantity
+    pub tolerance_percent: Option<f64>,
+}
+
+impl OverReceiptSettings {
+    const KEY: &'static str = "over_receipt_tolerance_percent";
+
+    /// Load from the settings table; missing or invalid values turn the
+    /// check off
+    pub fn load(db: &Database) -> Result<Self> {
+        Ok(Self {
+            tolerance_percent: db.get_setting(Self::KEY)?
+                .and_then(|value| value.parse::<f64>().ok())
+                .filter(|t| t.is_finite() && *t >= 0.0),
+        })
+    }
+
+    /// Store in the settings table
+    pub fn save(&self, db: &Database) -> Result<()> {
+        if self.tolerance_percent.is_some_and(|t| !t.is_finite() || t < 0.0) {
+            return Err(WmsError::validation("Over-receipt tolerance must be zero or more"));
+        }
+        let value = self.tolerance_percent.map(|t| t.to_string()).unwrap_or_default();
+        db.set_setting(Self::KEY, &value)
+    }
+
+    /// Most of a line expected at `expected` that may be received without
+    /// an override
+    pub fn limit(&self, expected: f64) -> Option<f64> {
+        self.tolerance_percent.map(|t| expected * (1.0 + t / 100.0))
+    }
+
+    /// Whether receiving `received` against `expected` needs an override
+    pub fn exceeded(&self, expected: f64, received: f64) -> bool {
+        self.limit(expected).is_some_and(|limit| received > limit + 1e-9)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tolerance_is_a_share_of_the_expected_quantity() {
+        assert!(!OverReceiptSettings::default().exceeded(10.0, 1000.0));
+
+        let settings = OverReceiptSettings { tolerance_percent: Some(10.0) };
+        assert!(!settings.exceeded(10.0, 11.0));
+        assert!(settings.exceeded(10.0, 11.5));
+        assert!(settings.exceeded(0.0, 1.0));
+        assert!(OverReceiptSettings { tolerance_percent: Some(-1.0) }.save(&wms_core::test_support::test_db()).is_err());
+    }
+}
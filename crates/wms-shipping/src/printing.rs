@@ -0,0 +1,784 @@
+//! Label Printing Queue
+//!
+//! Labels are sent to network printers through a persistent queue so a
+//! failed send (printer offline, out of paper) never loses a label. Jobs
+//! are drained serially per printer with bounded retries and exponential
+//! backoff; a printer reporting paper-out is paused until it recovers.
+
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+use wms_core::db::Database;
+use wms_core::error::{WmsError, Result};
+use wms_core::shutdown::{ShutdownSignal, TaskHandle};
+use wms_core::types::{new_id, required_timestamp, stored_status};
+
+/// Default attempts before a job is marked failed
+const DEFAULT_MAX_ATTEMPTS: u32 = 5;
+
+/// Default delay before the first retry; doubles with each attempt
+const DEFAULT_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// Longest wait between retries
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(300);
+
+/// Raw ZPL port used by most network label printers
+pub const DEFAULT_PRINTER_PORT: u16 = 9100;
+
+/// Network label printer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Printer {
+    pub id: String,
+    pub name: String,
+    pub host: String,
+    pub port: u16,
+    /// Why the queue is held, if it is
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub paused_reason: Option<String>,
+    pub is_active: bool,
+}
+
+/// Print job status
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PrintJobStatus {
+    Queued,
+    Printing,
+    Failed,
+    Done,
+    Cancelled,
+}
+
+impl PrintJobStatus {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "queued" => Some(Self::Queued),
+            "printing" => Some(Self::Printing),
+            "failed" => Some(Self::Failed),
+            "done" => Some(Self::Done),
+            "cancelled" => Some(Self::Cancelled),
+            _ => None,
+        }
+    }
+}
+
+/// A label waiting for, or sent to, a printer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrintJob {
+    pub id: String,
+    pub label_id: String,
+    pub printer_id: String,
+    pub status: PrintJobStatus,
+    pub attempts: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    /// Earliest time the next attempt may run
+    pub next_attempt_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A printer's queue as shown to users
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrintQueue {
+    pub printer: Printer,
+    /// Queued, printing and failed jobs, oldest first
+    pub jobs: Vec<PrintJob>,
+}
+
+/// Printer state from a `~HS` host status query
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrinterStatus {
+    pub paper_out: bool,
+    pub paused: bool,
+    pub head_open: bool,
+    pub ribbon_out: bool,
+}
+
+impl PrinterStatus {
+    /// Reason printing should wait, if any
+    pub fn hold_reason(&self) -> Option<&'static str> {
+        if self.paper_out {
+            Some("Paper out")
+        } else if self.head_open {
+            Some("Print head open")
+        } else if self.ribbon_out {
+            Some("Ribbon out")
+        } else {
+            None
+        }
+    }
+}
+
+/// Parse a `~HS` response.
+///
+/// The printer answers with three STX/ETX framed strings. In the first,
+/// field 2 is the paper-out flag and field 3 the pause flag; in the
+/// second, field 3 is head-up and field 4 ribbon-out.
+pub fn parse_host_status(response: &str) -> Option<PrinterStatus> {
+    let strings: Vec<Vec<&str>> = response
+        .split(['\u{2}', '\u{3}'])
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| s.split(',').map(str::trim).collect())
+        .collect();
+
+    let first = strings.first()?;
+    let second = strings.get(1)?;
+    let flag = |fields: &[&str], index: usize| fields.get(index).map(|f| *f == "1");
+
+    Some(PrinterStatus {
+        paper_out: flag(first, 1)?,
+        paused: flag(first, 2)?,
+        head_open: flag(second, 2)?,
+        ribbon_out: flag(second, 3)?,
+    })
+}
+
+/// Connection to physical printers
+pub trait PrinterTransport: Send + Sync {
+    /// Send raw label data to a printer
+    fn send(&self, printer: &Printer, data: &[u8]) -> Result<()>;
+
+    /// Query printer status; `None` if the printer can't report it
+    fn host_status(&self, printer: &Printer) -> Result<Option<PrinterStatus>>;
+}
+
+/// Shared transport handle
+pub type SharedTransport = Arc<dyn PrinterTransport>;
+
+/// Raw TCP transport (port 9100 style)
+pub struct TcpPrinterTransport {
+    timeout: Duration,
+}
+
+impl TcpPrinterTransport {
+    /// Create a transport with the given connect/read/write timeout
+    pub fn new(timeout: Duration) -> Self {
+        Self { timeout }
+    }
+
+    fn connect(&self, printer: &Printer) -> Result<TcpStream> {
+        let address = (printer.host.as_str(), printer.port)
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| WmsError::validation(format!("Cannot resolve printer host {}", printer.host)))?;
+
+        let stream = TcpStream::connect_timeout(&address, self.timeout)?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+        Ok(stream)
+    }
+}
+
+impl Default for TcpPrinterTransport {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(5))
+    }
+}
+
+impl PrinterTransport for TcpPrinterTransport {
+    fn send(&self, printer: &Printer, data: &[u8]) -> Result<()> {
+        let mut stream = self.connect(printer)?;
+        stream.write_all(data)?;
+        stream.flush()?;
+        Ok(())
+    }
+
+    fn host_status(&self, printer: &Printer) -> Result<Option<PrinterStatus>> {
+        let mut stream = self.connect(printer)?;
+        stream.write_all(b"~HS")?;
+
+        // Read until all three status strings have arrived
+        let mut response = Vec::new();
+        let mut buffer = [0u8; 256];
+        while response.iter().filter(|&&b| b == 0x03).count() < 3 {
+            match stream.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(n) => response.extend_from_slice(&buffer[..n]),
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock
+                    || e.kind() == std::io::ErrorKind::TimedOut => break,
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        Ok(parse_host_status(&String::from_utf8_lossy(&response)))
+    }
+}
+
+/// Backoff before retry number `attempts` (1-based)
+fn retry_delay(base: Duration, attempts: u32) -> Duration {
+    let factor = 2u32.saturating_pow(attempts.saturating_sub(1));
+    base.saturating_mul(factor).min(MAX_RETRY_DELAY)
+}
+
+/// Label printer management and print queue
+pub struct PrinterService {
+    db: Arc<Database>,
+    transport: SharedTransport,
+    max_attempts: u32,
+    retry_delay: Duration,
+}
+
+impl PrinterService {
+    /// Create a printer service using raw TCP printing
+    pub fn new(db: Arc<Database>) -> Self {
+        Self {
+            db,
+            transport: Arc::new(TcpPrinterTransport::default()),
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            retry_delay: DEFAULT_RETRY_DELAY,
+        }
+    }
+
+    /// Use a different printer transport
+    pub fn with_transport(mut self, transport: SharedTransport) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Set how many attempts a job gets and the first retry delay
+    pub fn with_retry_policy(mut self, max_attempts: u32, retry_delay: Duration) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self.retry_delay = retry_delay;
+        self
+    }
+
+    // ============ Printer Operations ============
+
+    /// Register a network printer
+    pub async fn add_printer(&self, name: &str, host: &str, port: Option<u16>) -> Result<Printer> {
+        if name.trim().is_empty() || host.trim().is_empty() {
+            return Err(WmsError::validation("Printer name and host are required"));
+        }
+
+        let printer = Printer {
+            id: new_id(),
+            name: name.trim().to_string(),
+            host: host.trim().to_string(),
+            port: port.unwrap_or(DEFAULT_PRINTER_PORT),
+            paused_reason: None,
+            is_active: true,
+        };
+
+        self.db.execute(
+            "INSERT INTO printers (id, name, host, port) VALUES (?, ?, ?, ?)",
+            params![&printer.id, &printer.name, &printer.host, printer.port],
+        )?;
+
+        info!("Added printer {} at {}:{}", printer.name, printer.host, printer.port);
+        Ok(printer)
+    }
+
+    /// List active printers
+    pub async fn list_printers(&self) -> Result<Vec<Printer>> {
+        self.db.query_map(
+            "SELECT * FROM printers WHERE is_active = 1 ORDER BY name",
+            [],
+            Self::row_to_printer,
+        )
+    }
+
+    // ============ Queue Operations ============
+
+    /// Queue a stored label for printing; voided labels are refused
+    pub async fn submit(&self, label_id: &str, printer_id: &str) -> Result<PrintJob> {
+        let voided_at: Option<Option<String>> = self.db.query_row(
+            "SELECT voided_at FROM shipping_labels WHERE id = ?",
+            params![label_id],
+            |row| row.get(0),
+        )?;
+        match voided_at {
+            None => return Err(WmsError::not_found(format!("Label {} not found", label_id))),
+            Some(Some(_)) => {
+                return Err(WmsError::validation(format!("Label {} was voided when its shipment was cancelled", label_id)))
+            }
+            Some(None) => {}
+        }
+        self.get_printer(printer_id)?;
+
+        let now = Utc::now();
+        let job = PrintJob {
+            id: new_id(),
+            label_id: label_id.to_string(),
+            printer_id: printer_id.to_string(),
+            status: PrintJobStatus::Queued,
+            attempts: 0,
+            last_error: None,
+            next_attempt_at: now,
+            created_at: now,
+        };
+
+        self.db.execute(
+            "INSERT INTO print_jobs (id, label_id, printer_id, status, next_attempt_at, created_at)
+             VALUES (?, ?, ?, 'queued', ?, ?)",
+            params![
+                &job.id,
+                &job.label_id,
+                &job.printer_id,
+                job.next_attempt_at.to_rfc3339(),
+                job.created_at.to_rfc3339(),
+            ],
+        )?;
+
+        debug!("Queued label {} on printer {}", label_id, printer_id);
+        Ok(job)
+    }
+
+    /// A printer's open jobs (queued, printing, failed)
+    pub async fn get_print_queue(&self, printer_id: &str) -> Result<PrintQueue> {
+        let printer = self.get_printer(printer_id)?;
+        let jobs = self.db.query_map(
+            "SELECT * FROM print_jobs
+             WHERE printer_id = ? AND status IN ('queued', 'printing', 'failed')
+             ORDER BY created_at, rowid",
+            params![printer_id],
+            Self::row_to_job,
+        )?;
+
+        Ok(PrintQueue { printer, jobs })
+    }
+
+    /// Put a failed job back in the queue with a fresh set of attempts
+    pub async fn requeue_failed(&self, job_id: &str) -> Result<PrintJob> {
+        let rows = self.db.execute(
+            "UPDATE print_jobs SET status = 'queued', attempts = 0, last_error = NULL,
+                next_attempt_at = ?, updated_at = datetime('now')
+             WHERE id = ? AND status = 'failed'",
+            params![Utc::now().to_rfc3339(), job_id],
+        )?;
+        if rows == 0 {
+            self.get_job(job_id)?;
+            return Err(WmsError::validation("Only failed print jobs can be requeued"));
+        }
+
+        self.get_job(job_id)
+    }
+
+    /// Cancel a job that has not printed
+    pub async fn cancel_job(&self, job_id: &str) -> Result<PrintJob> {
+        let rows = self.db.execute(
+            "UPDATE print_jobs SET status = 'cancelled', updated_at = datetime('now')
+             WHERE id = ? AND status IN ('queued', 'failed')",
+            params![job_id],
+        )?;
+        if rows == 0 {
+            self.get_job(job_id)?;
+            return Err(WmsError::validation("Only queued or failed print jobs can be cancelled"));
+        }
+
+        self.get_job(job_id)
+    }
+
+    /// Make one pass over every printer's queue; returns jobs printed
+    pub async fn process_queue(&self) -> Result<usize> {
+        self.process_queue_until(&ShutdownSignal::never()).await
+    }
+
+    /// One pass over the queues that stops between jobs once `stop` is raised
+    async fn process_queue_until(&self, stop: &ShutdownSignal) -> Result<usize> {
+        let printer_ids: Vec<String> = self.db.query_map(
+            "SELECT DISTINCT printer_id FROM print_jobs WHERE status = 'queued'",
+            [],
+            |row| row.get(0),
+        )?;
+
+        let mut printed = 0;
+        for printer_id in printer_ids {
+            if stop.is_requested() {
+                break;
+            }
+            printed += self.drain_printer(&printer_id, stop).await?;
+        }
+        Ok(printed)
+    }
+
+    /// Drain the queue on an interval until shutdown. A label being sent
+    /// when shutdown is requested finishes; jobs after it stay queued.
+    pub async fn run_worker(self: Arc<Self>, interval: Duration, task: TaskHandle) {
+        // Jobs interrupted mid-send are retried
+        if let Err(e) = self.db.execute(
+            "UPDATE print_jobs SET status = 'queued' WHERE status = 'printing'",
+            [],
+        ) {
+            warn!("Unable to reset interrupted print jobs: {}", e);
+        }
+
+        let mut ticker = tokio::time::interval(interval);
+        while !task.is_shutdown_requested() {
+            tokio::select! {
+                _ = ticker.tick() => {}
+                _ = task.shutdown_requested() => break,
+            }
+            if let Err(e) = self.process_queue_until(task.signal()).await {
+                warn!("Print queue pass failed: {}", e);
+            }
+        }
+
+        // A pass cut short by an error can leave a job marked as printing
+        if let Err(e) = self.db.execute(
+            "UPDATE print_jobs SET status = 'queued' WHERE status = 'printing'",
+            [],
+        ) {
+            warn!("Unable to requeue print jobs at shutdown: {}", e);
+        }
+        info!("Print queue worker stopped");
+    }
+
+    /// Print due jobs for one printer in order, stopping at the first retry
+    async fn drain_printer(&self, printer_id: &str, stop: &ShutdownSignal) -> Result<usize> {
+        let printer = self.get_printer(printer_id)?;
+
+        let transport = self.transport.clone();
+        let status_printer = printer.clone();
+        let status = tokio::task::spawn_blocking(move || transport.host_status(&status_printer))
+            .await
+            .map_err(|e| WmsError::Unknown(e.to_string()))?;
+
+        match status {
+            Ok(Some(status)) if status.hold_reason().is_some() => {
+                let reason = status.hold_reason().map(str::to_string);
+                if printer.paused_reason != reason {
+                    warn!("Pausing print queue for {}: {:?}", printer.name, reason);
+                    self.set_paused(printer_id, reason.as_deref())?;
+                }
+                return Ok(0);
+            }
+            Ok(_) => {}
+            // An unreachable printer shows up as a failed send below
+            Err(e) => debug!("Status check for printer {} failed: {}", printer.name, e),
+        }
+        if printer.paused_reason.is_some() {
+            info!("Resuming print queue for {}", printer.name);
+            self.set_paused(printer_id, None)?;
+        }
+
+        let jobs = self.db.query_map(
+            "SELECT * FROM print_jobs WHERE printer_id = ? AND status = 'queued'
+             ORDER BY created_at, rowid",
+            params![printer_id],
+            Self::row_to_job,
+        )?;
+
+        let mut printed = 0;
+        for job in jobs {
+            if job.next_attempt_at > Utc::now() || stop.is_requested() {
+                break;
+            }
+
+            self.db.execute(
+                "UPDATE print_jobs SET status = 'printing', updated_at = datetime('now') WHERE id = ?",
+                params![&job.id],
+            )?;
+
+            let data: Vec<u8> = self.db.query_row(
+                "SELECT label_data FROM shipping_labels WHERE id = ?",
+                params![&job.label_id],
+                |row| row.get(0),
+            )?.unwrap_or_default();
+
+            let transport = self.transport.clone();
+            let send_printer = printer.clone();
+            let sent = tokio::task::spawn_blocking(move || transport.send(&send_printer, &data))
+                .await
+                .map_err(|e| WmsError::Unknown(e.to_string()))?;
+
+            let attempts = job.attempts + 1;
+            match sent {
+                Ok(()) => {
+                    self.db.execute(
+                        "UPDATE print_jobs SET status = 'done', attempts = ?, last_error = NULL,
+                            updated_at = datetime('now')
+                         WHERE id = ?",
+                        params![attempts, &job.id],
+                    )?;
+                    printed += 1;
+                }
+                Err(e) if attempts >= self.max_attempts => {
+                    warn!("Print job {} failed after {} attempts: {}", job.id, attempts, e);
+                    self.db.execute(
+                        "UPDATE print_jobs SET status = 'failed', attempts = ?, last_error = ?,
+                            updated_at = datetime('now')
+                         WHERE id = ?",
+                        params![attempts, e.to_string(), &job.id],
+                    )?;
+                }
+                Err(e) => {
+                    let delay = retry_delay(self.retry_delay, attempts);
+                    let next = Utc::now() + chrono::Duration::from_std(delay).unwrap_or(chrono::Duration::zero());
+                    debug!("Print job {} attempt {} failed, retrying at {}: {}", job.id, attempts, next, e);
+                    self.db.execute(
+                        "UPDATE print_jobs SET status = 'queued', attempts = ?, last_error = ?,
+                            next_attempt_at = ?, updated_at = datetime('now')
+                         WHERE id = ?",
+                        params![attempts, e.to_string(), next.to_rfc3339(), &job.id],
+                    )?;
+                    // Keep labels in order: later jobs wait for this one
+                    break;
+                }
+            }
+        }
+
+        Ok(printed)
+    }
+
+    // ============ Helper Methods ============
+
+    fn get_printer(&self, printer_id: &str) -> Result<Printer> {
+        self.db.query_row(
+            "SELECT * FROM printers WHERE id = ?",
+            params![printer_id],
+            Self::row_to_printer,
+        )?
+        .ok_or_else(|| WmsError::not_found(format!("Printer {} not found", printer_id)))
+    }
+
+    fn get_job(&self, job_id: &str) -> Result<PrintJob> {
+        self.db.query_row(
+            "SELECT * FROM print_jobs WHERE id = ?",
+            params![job_id],
+            Self::row_to_job,
+        )?
+        .ok_or_else(|| WmsError::not_found(format!("Print job {} not found", job_id)))
+    }
+
+    fn set_paused(&self, printer_id: &str, reason: Option<&str>) -> Result<()> {
+        self.db.execute(
+            "UPDATE printers SET paused_reason = ? WHERE id = ?",
+            params![reason, printer_id],
+        )?;
+        Ok(())
+    }
+
+    fn row_to_printer(row: &rusqlite::Row) -> rusqlite::Result<Printer> {
+        Ok(Printer {
+            id: row.get("id")?,
+            name: row.get("name")?,
+            host: row.get("host")?,
+            port: row.get("port")?,
+            paused_reason: row.get("paused_reason")?,
+            is_active: row.get::<_, i32>("is_active")? == 1,
+        })
+    }
+
+    fn row_to_job(row: &rusqlite::Row) -> rusqlite::Result<PrintJob> {
+        Ok(PrintJob {
+            id: row.get("id")?,
+            label_id: row.get("label_id")?,
+            printer_id: row.get("printer_id")?,
+            status: stored_status(row, "status", PrintJobStatus::parse)?,
+            attempts: row.get("attempts")?,
+            last_error: row.get("last_error")?,
+            next_attempt_at: required_timestamp(row, "next_attempt_at")?,
+            created_at: required_timestamp(row, "created_at")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::sync::Mutex;
+
+    /// Printer that fails a set number of sends, then succeeds
+    #[derive(Default)]
+    struct FakePrinter {
+        failures_left: Mutex<u32>,
+        status: Mutex<Option<PrinterStatus>>,
+        printed: Mutex<Vec<Vec<u8>>>,
+        /// Requests shutdown after each successful send
+        shutdown_on_send: Option<Arc<wms_core::ShutdownCoordinator>>,
+    }
+
+    impl FakePrinter {
+        fn failing(times: u32) -> Self {
+            Self {
+                failures_left: Mutex::new(times),
+                ..Default::default()
+            }
+        }
+
+        fn printed(&self) -> Vec<String> {
+            self.printed.lock().unwrap()
+                .iter()
+                .map(|d| String::from_utf8_lossy(d).to_string())
+                .collect()
+        }
+    }
+
+    impl PrinterTransport for FakePrinter {
+        fn send(&self, _printer: &Printer, data: &[u8]) -> Result<()> {
+            let mut failures = self.failures_left.lock().unwrap();
+            if *failures > 0 {
+                *failures -= 1;
+                return Err(WmsError::Io(std::io::Error::new(
+                    std::io::ErrorKind::ConnectionRefused,
+                    "connection refused",
+                )));
+            }
+            self.printed.lock().unwrap().push(data.to_vec());
+            if let Some(coordinator) = &self.shutdown_on_send {
+                coordinator.request();
+            }
+            Ok(())
+        }
+
+        fn host_status(&self, _printer: &Printer) -> Result<Option<PrinterStatus>> {
+            Ok(*self.status.lock().unwrap())
+        }
+    }
+
+    fn test_db() -> Arc<Database> {
+        let db = Database::new(&PathBuf::from(":memory:"), "test-key").unwrap();
+        db.migrate_to_latest().unwrap();
+        db.execute(
+            "INSERT INTO users (id, username, email, full_name) VALUES ('u1', 'packer', 'packer@example.com', 'Pat Packer')",
+            [],
+        ).unwrap();
+        db.execute(
+            "INSERT INTO shipments (id, shipment_number, ship_to_name, ship_to_address_line1,
+                ship_to_city, ship_to_state, ship_to_postal_code, created_by)
+             VALUES ('s1', 'SHP-00000001', 'Acme', '1 Main St', 'Springfield', 'IL', '62701', 'u1')",
+            [],
+        ).unwrap();
+        for label in ["label-1", "label-2"] {
+            db.execute(
+                "INSERT INTO shipping_labels (id, shipment_id, label_type, label_format, label_data)
+                 VALUES (?, 's1', 'shipping', 'zpl', ?)",
+                params![label, format!("^XA{}^XZ", label).into_bytes()],
+            ).unwrap();
+        }
+        Arc::new(db)
+    }
+
+    fn service(db: Arc<Database>, printer: Arc<FakePrinter>) -> PrinterService {
+        PrinterService::new(db)
+            .with_transport(printer)
+            .with_retry_policy(3, Duration::ZERO)
+    }
+
+    #[test]
+    fn test_parse_host_status() {
+        let ok = "\u{2}030,0,0,1245,000,0,0,0,000,0,0,0\u{3}\r\n\u{2}001,0,0,0,1,2,6,0,00000000,1,000\u{3}\r\n\u{2}1234,0\u{3}";
+        assert_eq!(parse_host_status(ok), Some(PrinterStatus::default()));
+
+        let paper_out = "\u{2}030,1,1,1245,000,0,0,0,000,0,0,0\u{3}\u{2}001,0,0,0,1,2,6,0,00000000,1,000\u{3}";
+        let status = parse_host_status(paper_out).unwrap();
+        assert!(status.paper_out && status.paused);
+        assert_eq!(status.hold_reason(), Some("Paper out"));
+
+        assert_eq!(parse_host_status("garbage"), None);
+    }
+
+    #[test]
+    fn test_retry_delay_backs_off() {
+        let base = Duration::from_secs(5);
+        assert_eq!(retry_delay(base, 1), Duration::from_secs(5));
+        assert_eq!(retry_delay(base, 3), Duration::from_secs(20));
+        assert_eq!(retry_delay(base, 30), MAX_RETRY_DELAY);
+    }
+
+    #[tokio::test]
+    async fn test_job_retries_until_printed() {
+        let fake = Arc::new(FakePrinter::failing(2));
+        let service = service(test_db(), fake.clone());
+        let printer = service.add_printer("Dock 1", "10.0.0.5", None).await.unwrap();
+
+        let first = service.submit("label-1", &printer.id).await.unwrap();
+        let second = service.submit("label-2", &printer.id).await.unwrap();
+
+        // Two failed attempts keep both jobs queued, in order
+        assert_eq!(service.process_queue().await.unwrap(), 0);
+        assert_eq!(service.process_queue().await.unwrap(), 0);
+        let queue = service.get_print_queue(&printer.id).await.unwrap();
+        assert_eq!(queue.jobs[0].id, first.id);
+        assert_eq!(queue.jobs[0].attempts, 2);
+        assert!(queue.jobs[0].last_error.is_some());
+        assert_eq!(queue.jobs[1].attempts, 0);
+
+        assert_eq!(service.process_queue().await.unwrap(), 2);
+        assert_eq!(fake.printed(), vec!["^XAlabel-1^XZ", "^XAlabel-2^XZ"]);
+        assert!(service.get_print_queue(&printer.id).await.unwrap().jobs.is_empty());
+
+        let done = service.get_job(&first.id).unwrap();
+        assert_eq!(done.status, PrintJobStatus::Done);
+        assert_eq!(done.attempts, 3);
+        assert_eq!(service.get_job(&second.id).unwrap().attempts, 1);
+    }
+
+    #[tokio::test]
+    async fn test_exhausted_job_fails_and_can_be_requeued() {
+        let fake = Arc::new(FakePrinter::failing(3));
+        let service = service(test_db(), fake.clone());
+        let printer = service.add_printer("Dock 1", "10.0.0.5", None).await.unwrap();
+        let job = service.submit("label-1", &printer.id).await.unwrap();
+
+        for _ in 0..3 {
+            service.process_queue().await.unwrap();
+        }
+        let failed = service.get_job(&job.id).unwrap();
+        assert_eq!(failed.status, PrintJobStatus::Failed);
+        assert!(service.cancel_job(&job.id).await.is_ok_and(|j| j.status == PrintJobStatus::Cancelled));
+        assert!(matches!(service.cancel_job("missing").await, Err(WmsError::NotFound(_))));
+
+        let job = service.submit("label-1", &printer.id).await.unwrap();
+        *fake.failures_left.lock().unwrap() = 3;
+        for _ in 0..3 {
+            service.process_queue().await.unwrap();
+        }
+
+        let requeued = service.requeue_failed(&job.id).await.unwrap();
+        assert_eq!(requeued.status, PrintJobStatus::Queued);
+        assert_eq!(requeued.attempts, 0);
+        assert_eq!(service.process_queue().await.unwrap(), 1);
+        assert!(service.requeue_failed(&job.id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_paper_out_pauses_queue() {
+        let fake = Arc::new(FakePrinter::default());
+        *fake.status.lock().unwrap() = Some(PrinterStatus { paper_out: true, ..Default::default() });
+        let service = service(test_db(), fake.clone());
+        let printer = service.add_printer("Dock 1", "10.0.0.5", None).await.unwrap();
+        service.submit("label-1", &printer.id).await.unwrap();
+
+        assert_eq!(service.process_queue().await.unwrap(), 0);
+        let queue = service.get_print_queue(&printer.id).await.unwrap();
+        assert_eq!(queue.printer.paused_reason.as_deref(), Some("Paper out"));
+        assert_eq!(queue.jobs[0].attempts, 0);
+
+        *fake.status.lock().unwrap() = Some(PrinterStatus::default());
+        assert_eq!(service.process_queue().await.unwrap(), 1);
+        assert!(service.get_print_queue(&printer.id).await.unwrap().printer.paused_reason.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_worker_stops_between_jobs_on_shutdown() {
+        let coordinator = Arc::new(wms_core::ShutdownCoordinator::new());
+        let fake = Arc::new(FakePrinter {
+            shutdown_on_send: Some(coordinator.clone()),
+            ..Default::default()
+        });
+        let service = Arc::new(service(test_db(), fake.clone()));
+        let printer = service.add_printer("Dock 1", "10.0.0.5", None).await.unwrap();
+        service.submit("label-1", &printer.id).await.unwrap();
+        let second = service.submit("label-2", &printer.id).await.unwrap();
+
+        let task = coordinator.register("printing");
+        let worker = tokio::spawn(service.clone().run_worker(Duration::from_millis(5), task));
+        // The first send raises shutdown; the worker should stop on its own
+        tokio::time::timeout(Duration::from_secs(5), worker).await.unwrap().unwrap();
+
+        assert!(coordinator.running_tasks().is_empty());
+        assert_eq!(fake.printed(), vec!["^XAlabel-1^XZ"]);
+        let queue = service.get_print_queue(&printer.id).await.unwrap();
+        assert_eq!(queue.jobs.len(), 1);
+        assert_eq!(queue.jobs[0].id, second.id);
+        assert_eq!(queue.jobs[0].status, PrintJobStatus::Queued);
+    }
+}
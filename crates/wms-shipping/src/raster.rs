@@ -0,0 +1,326 @@
+//! Raster Barcodes
+//!
+//! Renders Code 128, Code 39 and EAN-13 barcodes to PNG for standard laser
+//! printers, and composes item labels with the name and SKU above the
+//! barcode. Text uses a built-in 5x7 pixel font, so labels need no font
+//! files.
+
+use std::io::Cursor;
+use image::{GrayImage, ImageFormat, Luma};
+use rxing::oned::{Code128Writer, Code39Writer, EAN13Writer, OneDimensionalCodeWriter};
+use serde::{Deserialize, Serialize};
+use wms_core::error::{WmsError, Result};
+use crate::sscc::gs1_check_digit;
+
+/// Characters Code 39 can carry
+const CODE39_CHARS: &str = "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ-. $/+%";
+
+const BLACK: Luma<u8> = Luma([0]);
+const WHITE: Luma<u8> = Luma([255]);
+
+/// Barcode symbologies that can be rendered
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BarcodeSymbology {
+    Code128,
+    Code39,
+    Ean13,
+}
+
+impl BarcodeSymbology {
+    /// EAN-13 for 12 or 13 digit codes that form a valid EAN, otherwise
+    /// Code 128, which carries any ASCII text
+    pub fn detect(data: &str) -> Self {
+        let digits = data.bytes().all(|b| b.is_ascii_digit());
+        match data.len() {
+            12 if digits => Self::Ean13,
+            13 if is_valid_ean13(data) => Self::Ean13,
+            _ => Self::Code128,
+        }
+    }
+
+    /// Bars and spaces for `data`, one entry per module, without quiet
+    /// zones. `true` is a bar.
+    pub fn encode(&self, data: &str) -> Result<Vec<bool>> {
+        let encoded = match self {
+            Self::Code128 => {
+                if data.is_empty() || !data.is_ascii() {
+                    return Err(WmsError::validation("Code 128 barcodes carry ASCII text"));
+                }
+                Code128Writer.encode_oned(data)
+            }
+            Self::Code39 => {
+                if let Some(c) = data.chars().find(|c| !CODE39_CHARS.contains(*c)) {
+                    return Err(WmsError::validation(format!(
+                        "Code 39 cannot encode '{}'; use digits, capital letters and - . $ / + % or space", c
+                    )));
+                }
+                if data.is_empty() {
+                    return Err(WmsError::validation("Code 39 barcodes need at least one character"));
+                }
+                Code39Writer.encode_oned(data)
+            }
+            Self::Ean13 => {
+                let code = match data.len() {
+                    12 => ean13_check_digit(data).map(|check| format!("{}{}", data, check)),
+                    13 => is_valid_ean13(data).then(|| data.to_string()),
+                    _ => None,
+                }.ok_or_else(|| WmsError::validation(format!(
+                    "{} is not an EAN-13: expected 12 digits, or 13 with a valid check digit", data
+                )))?;
+                EAN13Writer.encode_oned(&code)
+            }
+        };
+        encoded.map_err(|e| WmsError::validation(format!("Cannot encode {}: {}", data, e)))
+    }
+}
+
+/// EAN-13 check digit for the first 12 digits of a code
+pub fn ean13_check_digit(digits: &str) -> Option<u8> {
+    if digits.len() != 12 {
+        return None;
+    }
+    gs1_check_digit(digits)
+}
+
+/// Whether `code` is 13 digits ending in the right check digit
+pub fn is_valid_ean13(code: &str) -> bool {
+    code.len() == 13
+        && code.is_ascii()
+        && ean13_check_digit(&code[..12]).is_some_and(|check| code.ends_with(char::from(b'0' + check)))
+}
+
+/// Renders barcodes to PNG
+#[derive(Debug, Clone)]
+pub struct BarcodeGenerator {
+    /// Pixels per module, the narrowest bar
+    module_width: u32,
+    /// Bar height in pixels
+    height: u32,
+    /// Blank modules either side of the bars
+    quiet_zone: u32,
+    /// Pixels per font pixel for label text
+    text_scale: u32,
+}
+
+impl Default for BarcodeGenerator {
+    fn default() -> Self {
+        Self {
+            module_width: 2,
+            height: 80,
+            quiet_zone: 10,
+            text_scale: 2,
+        }
+    }
+}
+
+impl BarcodeGenerator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the width of the narrowest bar in pixels
+    pub fn with_module_width(mut self, pixels: u32) -> Self {
+        self.module_width = pixels.max(1);
+        self
+    }
+
+    /// Set the bar height in pixels
+    pub fn with_height(mut self, pixels: u32) -> Self {
+        self.height = pixels.max(1);
+        self
+    }
+
+    /// Render `data` as a PNG barcode
+    pub fn png(&self, symbology: BarcodeSymbology, data: &str) -> Result<Vec<u8>> {
+        let modules = symbology.encode(data)?;
+        let mut image = GrayImage::from_pixel(self.barcode_width(&modules), self.height, WHITE);
+        self.draw_bars(&mut image, &modules, 0, 0);
+        encode_png(&image)
+    }
+
+    /// Render an item label: name and SKU above the barcode, with the
+    /// barcode's text printed under it
+    pub fn item_label(&self, sku: &str, name: &str, barcode: &str, symbology: BarcodeSymbology) -> Result<Vec<u8>> {
+        let modules = symbology.encode(barcode)?;
+        let line_height = (GLYPH_HEIGHT + 3) * self.text_scale;
+        let margin = 4 * self.text_scale;
+
+        let lines = [name, sku];
+        let width = lines.iter().chain([&barcode])
+            .map(|text| self.text_width(text) + 2 * margin)
+            .chain([self.barcode_width(&modules)])
+            .max()
+            .unwrap_or_default();
+        let bars_top = margin + lines.len() as u32 * line_height + margin;
+        let height = bars_top + self.height + margin + line_height;
+
+        let mut image = GrayImage::from_pixel(width, height, WHITE);
+        for (i, text) in lines.iter().enumerate() {
+            self.draw_text(&mut image, text, margin, margin + i as u32 * line_height);
+        }
+        let bars_left = (width - self.barcode_width(&modules)) / 2;
+        self.draw_bars(&mut image, &modules, bars_left, bars_top);
+        let text_left = (width - self.text_width(barcode)) / 2;
+        self.draw_text(&mut image, barcode, text_left, bars_top + self.height + margin);
+
+        encode_png(&image)
+    }
+
+    fn barcode_width(&self, modules: &[bool]) -> u32 {
+        (modules.len() as u32 + 2 * self.quiet_zone) * self.module_width
+    }
+
+    fn draw_bars(&self, image: &mut GrayImage, modules: &[bool], left: u32, top: u32) {
+        let start = left + self.quiet_zone * self.module_width;
+        for (i, _) in modules.iter().enumerate().filter(|(_, dark)| **dark) {
+            let x = start + i as u32 * self.module_width;
+            fill(image, x, top, self.module_width, self.height);
+        }
+    }
+
+    fn text_width(&self, text: &str) -> u32 {
+        text.chars().count() as u32 * (GLYPH_WIDTH + 1) * self.text_scale
+    }
+
+    fn draw_text(&self, image: &mut GrayImage, text: &str, left: u32, top: u32) {
+        let scale = self.text_scale;
+        for (n, c) in text.chars().enumerate() {
+            let x0 = left + n as u32 * (GLYPH_WIDTH + 1) * scale;
+            for (row, bits) in glyph(c).iter().enumerate() {
+                for col in 0..GLYPH_WIDTH {
+                    if bits & (1 << (GLYPH_WIDTH - 1 - col)) != 0 {
+                        fill(image, x0 + col * scale, top + row as u32 * scale, scale, scale);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Render an item label with the default generator, choosing EAN-13 for
+/// EAN codes and Code 128 for anything else
+pub fn item_label_png(sku: &str, name: &str, barcode: &str) -> Result<Vec<u8>> {
+    BarcodeGenerator::default().item_label(sku, name, barcode, BarcodeSymbology::detect(barcode))
+}
+
+fn fill(image: &mut GrayImage, x: u32, y: u32, width: u32, height: u32) {
+    for px in x..(x + width).min(image.width()) {
+        for py in y..(y + height).min(image.height()) {
+            image.put_pixel(px, py, BLACK);
+        }
+    }
+}
+
+fn encode_png(image: &GrayImage) -> Result<Vec<u8>> {
+    let mut png = Cursor::new(Vec::new());
+    image.write_to(&mut png, ImageFormat::Png)
+        .map_err(|e| WmsError::Barcode(format!("Failed to encode PNG: {}", e)))?;
+    Ok(png.into_inner())
+}
+
+const GLYPH_WIDTH: u32 = 5;
+const GLYPH_HEIGHT: u32 = 7;
+
+/// 5x7 pixel glyph, one row per entry with the leftmost pixel in bit 4.
+/// Lowercase letters print as capitals; unknown characters as '?'.
+fn glyph(c: char) -> [u8; 7] {
+    match c.to_ascii_uppercase() {
+        '0' => [0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110],
+        '1' => [0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110],
+        '2' => [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111],
+        '3' => [0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110],
+        '4' => [0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010],
+        '5' => [0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110],
+        '6' => [0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110],
+        '7' => [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000],
+        '8' => [0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110],
+        '9' => [0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100],
+        'A' => [0b01110, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001],
+        'B' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10001, 0b10001, 0b11110],
+        'C' => [0b01110, 0b10001, 0b10000, 0b10000, 0b10000, 0b10001, 0b01110],
+        'D' => [0b11100, 0b10010, 0b10001, 0b10001, 0b10001, 0b10010, 0b11100],
+        'E' => [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b11111],
+        'F' => [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b10000],
+        'G' => [0b01110, 0b10001, 0b10000, 0b10111, 0b10001, 0b10001, 0b01111],
+        'H' => [0b10001, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001],
+        'I' => [0b01110, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110],
+        'J' => [0b00111, 0b00010, 0b00010, 0b00010, 0b00010, 0b10010, 0b01100],
+        'K' => [0b10001, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010, 0b10001],
+        'L' => [0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b11111],
+        'M' => [0b10001, 0b11011, 0b10101, 0b10101, 0b10001, 0b10001, 0b10001],
+        'N' => [0b10001, 0b10001, 0b11001, 0b10101, 0b10011, 0b10001, 0b10001],
+        'O' => [0b01110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110],
+        'P' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10000, 0b10000, 0b10000],
+        'Q' => [0b01110, 0b10001, 0b10001, 0b10001, 0b10101, 0b10010, 0b01101],
+        'R' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10100, 0b10010, 0b10001],
+        'S' => [0b01111, 0b10000, 0b10000, 0b01110, 0b00001, 0b00001, 0b11110],
+        'T' => [0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100],
+        'U' => [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110],
+        'V' => [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100],
+        'W' => [0b10001, 0b10001, 0b10001, 0b10101, 0b10101, 0b10101, 0b01010],
+        'X' => [0b10001, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001, 0b10001],
+        'Y' => [0b10001, 0b10001, 0b10001, 0b01010, 0b00100, 0b00100, 0b00100],
+        'Z' => [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b11111],
+        ' ' => [0; 7],
+        '-' => [0b00000, 0b00000, 0b00000, 0b11111, 0b00000, 0b00000, 0b00000],
+        '_' => [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b11111],
+        '.' => [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b01100],
+        ',' => [0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b00100, 0b01000],
+        ':' => [0b00000, 0b01100, 0b01100, 0b00000, 0b01100, 0b01100, 0b00000],
+        '/' => [0b00000, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b00000],
+        '+' => [0b00000, 0b00100, 0b00100, 0b11111, 0b00100, 0b00100, 0b00000],
+        '%' => [0b11000, 0b11001, 0b00010, 0b00100, 0b01000, 0b10011, 0b00011],
+        '$' => [0b00100, 0b01111, 0b10100, 0b01110, 0b00101, 0b11110, 0b00100],
+        '#' => [0b01010, 0b01010, 0b11111, 0b01010, 0b11111, 0b01010, 0b01010],
+        '&' => [0b01100, 0b10010, 0b10100, 0b01000, 0b10101, 0b10010, 0b01101],
+        '(' => [0b00010, 0b00100, 0b01000, 0b01000, 0b01000, 0b00100, 0b00010],
+        ')' => [0b01000, 0b00100, 0b00010, 0b00010, 0b00010, 0b00100, 0b01000],
+        '\'' => [0b01100, 0b00100, 0b01000, 0b00000, 0b00000, 0b00000, 0b00000],
+        _ => [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b00000, 0b00100],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ean13_check_digit() {
+        assert_eq!(ean13_check_digit("400638133393"), Some(1));
+        assert_eq!(ean13_check_digit("590123412345"), Some(7));
+        assert_eq!(ean13_check_digit("59012341234"), None);
+        assert_eq!(ean13_check_digit("59012341234X"), None);
+
+        assert!(is_valid_ean13("4006381333931"));
+        assert!(!is_valid_ean13("4006381333932"));
+        assert_eq!(BarcodeSymbology::detect("4006381333931"), BarcodeSymbology::Ean13);
+        assert_eq!(BarcodeSymbology::detect("4006381333932"), BarcodeSymbology::Code128);
+
+        // A wrong check digit is refused rather than silently fixed
+        assert!(BarcodeSymbology::Ean13.encode("4006381333932").is_err());
+        assert_eq!(BarcodeSymbology::Ean13.encode("400638133393").unwrap().len(), 95);
+    }
+
+    #[test]
+    fn test_code39_rejects_characters_it_cannot_carry() {
+        assert!(BarcodeSymbology::Code39.encode("BIN-A1.02").is_ok());
+        for bad in ["bin-a1", "A*B", "SKU#1", ""] {
+            let err = BarcodeSymbology::Code39.encode(bad).unwrap_err();
+            assert!(matches!(err, WmsError::Validation(_)), "{} was accepted", bad);
+        }
+    }
+
+    #[test]
+    fn test_png_size_follows_module_width_and_height() {
+        let generator = BarcodeGenerator::new().with_module_width(3).with_height(40);
+        let modules = BarcodeSymbology::Code128.encode("WID-1").unwrap().len() as u32;
+
+        let png = generator.png(BarcodeSymbology::Code128, "WID-1").unwrap();
+        let image = image::load_from_memory(&png).unwrap();
+        assert_eq!((image.width(), image.height()), ((modules + 20) * 3, 40));
+
+        let label = item_label_png("WID-1", "Widget", "4006381333931").unwrap();
+        assert!(label.starts_with(b"\x89PNG"));
+    }
+}
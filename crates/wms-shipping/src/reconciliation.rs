@@ -0,0 +1,500 @@
+//! Shipping Cost Reconciliation
+//!
+//! Carrier invoices are imported per carrier as CSV, one row per tracking
+//! number, and compared with the shipping cost we estimated for each
+//! shipment. Lines whose billed total strays from the estimate by more than
+//! a threshold percentage are flagged for finance.
+
+use std::collections::{BTreeMap, HashMap};
+use chrono::NaiveDate;
+use csv::Writer;
+use serde::{Deserialize, Serialize};
+use wms_core::error::{WmsError, Result};
+use wms_core::types::{DateRange, Measurement, UnitSystem, Weight};
+
+/// Variance (in percent of the estimate) above which a line is flagged
+pub const DEFAULT_VARIANCE_THRESHOLD_PERCENT: f64 = 10.0;
+
+/// Surcharge type used for a plain `surcharges` column
+const UNTYPED_SURCHARGE: &str = "other";
+
+/// One surcharge on a carrier invoice line, or a total per type
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Surcharge {
+    pub surcharge_type: String,
+    pub amount: f64,
+}
+
+/// A row of a carrier invoice
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CarrierInvoiceLine {
+    /// 1-based position in the file
+    pub line_number: usize,
+    pub tracking_number: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub invoice_number: Option<String>,
+    /// Transportation charge before surcharges
+    pub billed_amount: f64,
+    #[serde(default)]
+    pub surcharges: Vec<Surcharge>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub billed_weight_kg: Option<f64>,
+}
+
+impl CarrierInvoiceLine {
+    pub fn surcharge_total(&self) -> f64 {
+        self.surcharges.iter().map(|s| s.amount).sum()
+    }
+
+    /// Everything the carrier billed for the shipment
+    pub fn billed_total(&self) -> f64 {
+        self.billed_amount + self.surcharge_total()
+    }
+}
+
+/// An invoice row whose tracking number matches no shipment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnmatchedInvoiceLine {
+    pub line_number: usize,
+    pub tracking_number: String,
+    pub billed_total: f64,
+}
+
+/// Outcome of a carrier invoice import
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CarrierInvoiceImport {
+    pub carrier_id: String,
+    /// Rows stored for the first time
+    pub imported: usize,
+    /// Rows that replaced an earlier import of the same tracking number
+    pub updated: usize,
+    pub matched: usize,
+    /// Stored, but not tied to a shipment yet
+    pub unmatched: Vec<UnmatchedInvoiceLine>,
+}
+
+/// An invoiced shipment as loaded for the report
+#[derive(Debug, Clone)]
+pub struct InvoicedShipment {
+    pub shipment_id: String,
+    pub shipment_number: String,
+    pub ship_date: Option<NaiveDate>,
+    pub carrier_id: String,
+    pub carrier_code: String,
+    /// Shipping cost estimated when the shipment was rated
+    pub estimated_cost: Option<f64>,
+    pub invoice: CarrierInvoiceLine,
+}
+
+/// Estimated vs billed cost for one shipment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CostVarianceLine {
+    pub shipment_id: String,
+    pub shipment_number: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ship_date: Option<NaiveDate>,
+    pub carrier_id: String,
+    pub carrier_code: String,
+    pub tracking_number: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub invoice_number: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub estimated_cost: Option<f64>,
+    pub billed_amount: f64,
+    pub surcharges: Vec<Surcharge>,
+    pub billed_total: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub billed_weight_kg: Option<f64>,
+    /// Billed total minus estimate
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub variance: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub variance_percent: Option<f64>,
+    pub flagged: bool,
+}
+
+/// Totals for one carrier over the period
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CarrierCostSummary {
+    pub carrier_id: String,
+    pub carrier_code: String,
+    pub shipments: usize,
+    /// Estimates of shipments that have one
+    pub estimated_total: f64,
+    pub billed_total: f64,
+    /// Billed minus estimated, over shipments that have an estimate
+    pub variance: f64,
+    pub flagged: usize,
+    /// Surcharge totals by type, largest first
+    pub surcharges: Vec<Surcharge>,
+}
+
+/// Shipping cost reconciliation for a period
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CostReconciliation {
+    pub period: DateRange,
+    pub threshold_percent: f64,
+    pub lines: Vec<CostVarianceLine>,
+    pub carriers: Vec<CarrierCostSummary>,
+    pub flagged_count: usize,
+}
+
+/// Exported reconciliation lines
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CostReconciliationExport {
+    /// Base64 encoded file data
+    pub data: String,
+    /// MIME content type
+    pub content_type: String,
+    /// Suggested filename
+    pub filename: String,
+}
+
+// ============ Invoice CSV ============
+
+/// Accepted CSV header names for each field (case-insensitive, spaces as underscores)
+const CSV_COLUMNS: &[(&str, &[&str])] = &[
+    ("tracking_number", &["tracking_number", "tracking", "tracking_id", "awb"]),
+    ("invoice_number", &["invoice_number", "invoice"]),
+    ("billed_amount", &["billed_amount", "amount", "charge", "net_charge", "transportation_charge"]),
+    ("surcharges", &["surcharges", "surcharge", "accessorials"]),
+    ("billed_weight_kg", &["billed_weight_kg", "billed_weight", "weight_kg"]),
+    ("billed_weight_lb", &["billed_weight_lb", "billed_weight_lbs", "weight_lb"]),
+];
+
+/// Prefix of columns carrying one surcharge type each, e.g. `surcharge_fuel`
+const SURCHARGE_PREFIX: &str = "surcharge_";
+
+fn parse_amount(value: &str, line_number: usize, column: &str) -> Result<f64> {
+    value.trim_start_matches('$').replace(',', "").parse::<f64>().ok()
+        .filter(|v| v.is_finite())
+        .ok_or_else(|| WmsError::validation(format!(
+            "Invoice line {}: invalid {} '{}'", line_number, column, value
+        )))
+}
+
+/// Parse a carrier invoice CSV. Besides the fixed columns, every
+/// `surcharge_<type>` column is read as a surcharge of that type.
+pub fn parse_carrier_invoice(data: &[u8]) -> Result<Vec<CarrierInvoiceLine>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .flexible(true)
+        .from_reader(data);
+
+    let headers: Vec<String> = reader.headers()
+        .map_err(|e| WmsError::validation(format!("Invalid invoice CSV: {}", e)))?
+        .iter()
+        .map(|h| h.to_lowercase().replace(' ', "_"))
+        .collect();
+
+    let columns: HashMap<&str, usize> = CSV_COLUMNS.iter()
+        .filter_map(|(field, aliases)| {
+            headers.iter()
+                .position(|h| aliases.contains(&h.as_str()))
+                .map(|index| (*field, index))
+        })
+        .collect();
+    let surcharge_columns: Vec<(usize, &str)> = headers.iter()
+        .enumerate()
+        .filter_map(|(index, h)| h.strip_prefix(SURCHARGE_PREFIX).map(|t| (index, t)))
+        .filter(|(_, surcharge_type)| !surcharge_type.is_empty())
+        .collect();
+
+    for required in ["tracking_number", "billed_amount"] {
+        if !columns.contains_key(required) {
+            return Err(WmsError::validation(format!("Invoice CSV is missing the {} column", required)));
+        }
+    }
+
+    let mut lines = Vec::new();
+    for (index, record) in reader.records().enumerate() {
+        let line_number = index + 1;
+        let record = record
+            .map_err(|e| WmsError::validation(format!("Invoice line {}: {}", line_number, e)))?;
+        let field = |name: &str| columns.get(name)
+            .and_then(|&i| record.get(i))
+            .filter(|v| !v.is_empty());
+
+        if record.iter().all(|value| value.is_empty()) {
+            continue;
+        }
+
+        let tracking_number = field("tracking_number")
+            .ok_or_else(|| WmsError::validation(format!("Invoice line {}: missing tracking number", line_number)))?
+            .to_string();
+        let billed_amount = parse_amount(field("billed_amount").unwrap_or_default(), line_number, "billed amount")?;
+
+        let mut surcharges = Vec::new();
+        if let Some(value) = field("surcharges") {
+            surcharges.push(Surcharge {
+                surcharge_type: UNTYPED_SURCHARGE.to_string(),
+                amount: parse_amount(value, line_number, "surcharges")?,
+            });
+        }
+        for &(column, surcharge_type) in &surcharge_columns {
+            let Some(value) = record.get(column).filter(|v| !v.is_empty()) else {
+                continue;
+            };
+            let amount = parse_amount(value, line_number, surcharge_type)?;
+            if amount != 0.0 {
+                surcharges.push(Surcharge { surcharge_type: surcharge_type.to_string(), amount });
+            }
+        }
+
+        let billed_weight_kg = match (field("billed_weight_kg"), field("billed_weight_lb")) {
+            (Some(kg), _) => Some(parse_amount(kg, line_number, "billed weight")?),
+            (None, Some(lb)) => Some(
+                Weight::parse_from_display(lb, UnitSystem::Imperial)
+                    .ok_or_else(|| WmsError::validation(format!(
+                        "Invoice line {}: invalid billed weight '{}'", line_number, lb
+                    )))?
+                    .kg(),
+            ),
+            (None, None) => None,
+        };
+
+        lines.push(CarrierInvoiceLine {
+            line_number,
+            tracking_number,
+            invoice_number: field("invoice_number").map(str::to_string),
+            billed_amount,
+            surcharges,
+            billed_weight_kg,
+        });
+    }
+
+    if lines.is_empty() {
+        return Err(WmsError::validation("Invoice has no lines"));
+    }
+
+    Ok(lines)
+}
+
+// ============ Report ============
+
+fn round_money(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+/// Compare estimates with billed totals and summarize per carrier
+pub fn build_reconciliation(
+    period: DateRange,
+    shipments: Vec<InvoicedShipment>,
+    threshold_percent: f64,
+) -> CostReconciliation {
+    let lines: Vec<CostVarianceLine> = shipments.into_iter()
+        .map(|s| {
+            let billed_total = round_money(s.invoice.billed_total());
+            let variance = s.estimated_cost.map(|estimate| round_money(billed_total - estimate));
+            let variance_percent = s.estimated_cost
+                .filter(|estimate| *estimate > 0.0)
+                .zip(variance)
+                .map(|(estimate, variance)| variance * 100.0 / estimate);
+            // Billing against a zero estimate is always worth a look
+            let flagged = match (s.estimated_cost, variance_percent) {
+                (_, Some(percent)) => percent.abs() > threshold_percent,
+                (Some(_), None) => billed_total > 0.0,
+                (None, None) => false,
+            };
+
+            CostVarianceLine {
+                shipment_id: s.shipment_id,
+                shipment_number: s.shipment_number,
+                ship_date: s.ship_date,
+                carrier_id: s.carrier_id,
+                carrier_code: s.carrier_code,
+                tracking_number: s.invoice.tracking_number,
+                invoice_number: s.invoice.invoice_number,
+                estimated_cost: s.estimated_cost,
+                billed_amount: s.invoice.billed_amount,
+                surcharges: s.invoice.surcharges,
+                billed_total,
+                billed_weight_kg: s.invoice.billed_weight_kg,
+                variance,
+                variance_percent,
+                flagged,
+            }
+        })
+        .collect();
+
+    let mut by_carrier: BTreeMap<&str, (CarrierCostSummary, HashMap<&str, f64>)> = BTreeMap::new();
+    for line in &lines {
+        let (summary, surcharges) = by_carrier.entry(line.carrier_code.as_str()).or_insert_with(|| (
+            CarrierCostSummary {
+                carrier_id: line.carrier_id.clone(),
+                carrier_code: line.carrier_code.clone(),
+                shipments: 0,
+                estimated_total: 0.0,
+                billed_total: 0.0,
+                variance: 0.0,
+                flagged: 0,
+                surcharges: Vec::new(),
+            },
+            HashMap::new(),
+        ));
+        summary.shipments += 1;
+        summary.estimated_total += line.estimated_cost.unwrap_or(0.0);
+        summary.billed_total += line.billed_total;
+        summary.variance += line.variance.unwrap_or(0.0);
+        summary.flagged += usize::from(line.flagged);
+        for surcharge in &line.surcharges {
+            *surcharges.entry(surcharge.surcharge_type.as_str()).or_default() += surcharge.amount;
+        }
+    }
+
+    let carriers = by_carrier.into_values()
+        .map(|(mut summary, surcharges)| {
+            summary.estimated_total = round_money(summary.estimated_total);
+            summary.billed_total = round_money(summary.billed_total);
+            summary.variance = round_money(summary.variance);
+            summary.surcharges = surcharges.into_iter()
+                .map(|(surcharge_type, amount)| Surcharge {
+                    surcharge_type: surcharge_type.to_string(),
+                    amount: round_money(amount),
+                })
+                .collect();
+            summary.surcharges.sort_by(|a, b| b.amount.total_cmp(&a.amount)
+                .then_with(|| a.surcharge_type.cmp(&b.surcharge_type)));
+            summary
+        })
+        .collect();
+
+    CostReconciliation {
+        period,
+        threshold_percent,
+        flagged_count: lines.iter().filter(|l| l.flagged).count(),
+        lines,
+        carriers,
+    }
+}
+
+/// Render the variance lines as CSV
+pub fn render_reconciliation_csv(report: &CostReconciliation) -> Result<Vec<u8>> {
+    let csv_error = |e: csv::Error| WmsError::Export(format!("CSV write error: {}", e));
+    let mut writer = Writer::from_writer(Vec::new());
+
+    writer.write_record([
+        "Shipment",
+        "Ship Date",
+        "Carrier",
+        "Tracking Number",
+        "Invoice",
+        "Estimated",
+        "Billed Amount",
+        "Surcharges",
+        "Billed Total",
+        "Billed Weight (kg)",
+        "Variance",
+        "Variance %",
+        "Flagged",
+    ]).map_err(csv_error)?;
+
+    for line in &report.lines {
+        let surcharges = line.surcharges.iter()
+            .map(|s| format!("{} {:.2}", s.surcharge_type, s.amount))
+            .collect::<Vec<_>>()
+            .join("; ");
+
+        writer.write_record([
+            line.shipment_number.clone(),
+            line.ship_date.map(|d| d.to_string()).unwrap_or_default(),
+            line.carrier_code.clone(),
+            line.tracking_number.clone(),
+            line.invoice_number.clone().unwrap_or_default(),
+            line.estimated_cost.map(|c| format!("{:.2}", c)).unwrap_or_default(),
+            format!("{:.2}", line.billed_amount),
+            surcharges,
+            format!("{:.2}", line.billed_total),
+            line.billed_weight_kg.map(|w| format!("{:.2}", w)).unwrap_or_default(),
+            line.variance.map(|v| format!("{:.2}", v)).unwrap_or_default(),
+            line.variance_percent.map(|p| format!("{:.1}", p)).unwrap_or_default(),
+            if line.flagged { "yes" } else { "" }.to_string(),
+        ]).map_err(csv_error)?;
+    }
+
+    writer.into_inner()
+        .map_err(|e| WmsError::Export(format!("CSV write error: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, m, d).unwrap()
+    }
+
+    fn invoiced(number: &str, carrier: &str, estimate: Option<f64>, billed: f64, surcharges: &[(&str, f64)]) -> InvoicedShipment {
+        InvoicedShipment {
+            shipment_id: number.to_lowercase(),
+            shipment_number: number.to_string(),
+            ship_date: Some(date(3, 3)),
+            carrier_id: format!("car_{}", carrier.to_lowercase()),
+            carrier_code: carrier.to_string(),
+            estimated_cost: estimate,
+            invoice: CarrierInvoiceLine {
+                line_number: 1,
+                tracking_number: format!("1Z{}", number),
+                invoice_number: None,
+                billed_amount: billed,
+                surcharges: surcharges.iter()
+                    .map(|(t, a)| Surcharge { surcharge_type: t.to_string(), amount: *a })
+                    .collect(),
+                billed_weight_kg: None,
+            },
+        }
+    }
+
+    #[test]
+    fn test_parse_invoice_with_typed_surcharges() {
+        let csv = b"Tracking Number,Billed Amount,Surcharge Fuel,Surcharge Residential,Billed Weight LB\n\
+                    1ZA,12.50,1.25,,10\n\
+                    1ZB,\"$1,020.00\",3.00,4.75,\n";
+
+        let lines = parse_carrier_invoice(csv).unwrap();
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].surcharges, vec![Surcharge { surcharge_type: "fuel".into(), amount: 1.25 }]);
+        assert!((lines[0].billed_weight_kg.unwrap() - 4.5359).abs() < 0.001);
+        assert_eq!(lines[1].billed_amount, 1020.0);
+        assert_eq!(lines[1].billed_total(), 1027.75);
+        assert_eq!(lines[1].billed_weight_kg, None);
+    }
+
+    #[test]
+    fn test_parse_invoice_requires_columns() {
+        assert!(parse_carrier_invoice(b"tracking,weight\n1ZA,3\n").is_err());
+        assert!(parse_carrier_invoice(b"tracking,amount\n1ZA,abc\n").is_err());
+        assert!(parse_carrier_invoice(b"tracking,amount\n").is_err());
+    }
+
+    #[test]
+    fn test_variances_flagged_and_summarized() {
+        let period = DateRange::new(date(3, 1), date(3, 31));
+        let report = build_reconciliation(period, vec![
+            invoiced("SHP-1", "UPS", Some(10.0), 10.0, &[("fuel", 0.5)]),
+            invoiced("SHP-2", "UPS", Some(20.0), 20.0, &[("fuel", 1.0), ("residential", 5.0)]),
+            invoiced("SHP-3", "FEDEX", None, 8.0, &[]),
+        ], 10.0);
+
+        assert_eq!(report.lines[0].variance, Some(0.5));
+        assert!(!report.lines[0].flagged);
+        assert_eq!(report.lines[1].variance_percent, Some(30.0));
+        assert!(report.lines[1].flagged);
+        assert!(!report.lines[2].flagged);
+        assert_eq!(report.flagged_count, 1);
+
+        let fedex = &report.carriers[0];
+        assert_eq!((fedex.carrier_code.as_str(), fedex.shipments, fedex.variance), ("FEDEX", 1, 0.0));
+        let ups = &report.carriers[1];
+        assert_eq!((ups.shipments, ups.estimated_total, ups.billed_total), (2, 30.0, 36.5));
+        assert_eq!(ups.surcharges, vec![
+            Surcharge { surcharge_type: "residential".into(), amount: 5.0 },
+            Surcharge { surcharge_type: "fuel".into(), amount: 1.5 },
+        ]);
+
+        let csv = String::from_utf8(render_reconciliation_csv(&report).unwrap()).unwrap();
+        assert_eq!(csv.lines().count(), 4);
+        assert!(csv.contains("SHP-2,2025-03-03,UPS,1ZSHP-2,,20.00,20.00,fuel 1.00; residential 5.00,26.00,,6.00,30.0,yes"));
+    }
+}
@@ -0,0 +1,221 @@
+//! Scan Log
+//!
+//! Records every accepted barcode scan in receiving, picking and putaway.
+//! A scanner trigger held down sends the same barcode several times in a
+//! row; a repeat of the same barcode in the same context inside the
+//! duplicate window is reported instead of processed again.
+
+use std::sync::Arc;
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use wms_core::db::Database;
+use wms_core::error::Result;
+use wms_core::types::{new_id, required_timestamp, stored_status};
+use wms_inventory::{InventoryItem, Location};
+use crate::models::ReceiptItem;
+
+/// Repeats of a barcode within this window are treated as duplicates
+pub const DEFAULT_DUPLICATE_SCAN_WINDOW: Duration = Duration::from_secs(3);
+
+/// Workflow a scan belongs to
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ScanContext {
+    /// Receiving against a receipt
+    Receipt,
+    /// Picking a shipment
+    Pick,
+    /// Putting received goods away
+    Putaway,
+}
+
+impl ScanContext {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Receipt => "receipt",
+            Self::Pick => "pick",
+            Self::Putaway => "putaway",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "receipt" => Some(Self::Receipt),
+            "pick" => Some(Self::Pick),
+            "putaway" => Some(Self::Putaway),
+            _ => None,
+        }
+    }
+}
+
+/// An accepted scan
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanEvent {
+    pub id: String,
+    pub context: ScanContext,
+    /// Receipt or shipment the scan was made against
+    pub context_id: String,
+    pub barcode: String,
+    /// What the barcode resolved to, e.g. "inventory_item"
+    pub entity_type: String,
+    pub entity_id: String,
+    pub quantity: f64,
+    pub user_id: String,
+    pub scanned_at: DateTime<Utc>,
+}
+
+/// Outcome of a scan command
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ScanResult<T> {
+    /// The scan was recorded and applied
+    Processed { scan: ScanEvent, result: T },
+    /// The same barcode was scanned moments ago; nothing was changed
+    Duplicate { previous: ScanEvent, message: String },
+}
+
+/// What a barcode scanned against a receipt refers to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ReceiptScanMatch {
+    /// The item and the receipt line for it
+    OnReceipt {
+        item: Box<InventoryItem>,
+        line: Box<ReceiptItem>,
+        /// Expected but not yet received; left out on blind counts
+        #[serde(skip_serializing_if = "Option::is_none")]
+        remaining_quantity: Option<f64>,
+        /// Storage location with room for the item
+        #[serde(skip_serializing_if = "Option::is_none")]
+        suggested_location: Option<Box<Location>>,
+    },
+    /// A known item the receipt has no line for
+    UnexpectedItem { item: Box<InventoryItem> },
+}
+
+/// Whether a scan was recorded or matched an earlier one
+pub(crate) enum RecordedScan {
+    New(ScanEvent),
+    Duplicate(ScanEvent),
+}
+
+/// Scan event storage with duplicate detection
+pub struct ScanLog {
+    db: Arc<Database>,
+    duplicate_window: Duration,
+}
+
+impl ScanLog {
+    pub fn new(db: Arc<Database>) -> Self {
+        Self {
+            db,
+            duplicate_window: DEFAULT_DUPLICATE_SCAN_WINDOW,
+        }
+    }
+
+    /// Treat repeats within `window` as duplicates (zero disables the check)
+    pub fn with_duplicate_window(mut self, window: Duration) -> Self {
+        self.duplicate_window = window;
+        self
+    }
+
+    /// Record a scan unless the same barcode was scanned in the same
+    /// context within the duplicate window. The check and the insert run
+    /// in one transaction so simultaneous submissions cannot both pass.
+    pub(crate) fn record(&self, scan: ScanEvent, allow_duplicate: bool) -> Result<RecordedScan> {
+        let window = chrono::Duration::from_std(self.duplicate_window).unwrap_or(chrono::Duration::MAX);
+
+        self.db.transaction(|conn| {
+            if !allow_duplicate && !self.duplicate_window.is_zero() {
+                let previous = conn.query_row(
+                    "SELECT * FROM scan_events
+                     WHERE context_id = ? AND context = ? AND barcode = ?
+                     ORDER BY scanned_at DESC LIMIT 1",
+                    params![&scan.context_id, scan.context.as_str(), &scan.barcode],
+                    row_to_scan,
+                );
+                match previous {
+                    Ok(previous) if scan.scanned_at - previous.scanned_at < window => {
+                        return Ok(RecordedScan::Duplicate(previous));
+                    }
+                    Ok(_) | Err(rusqlite::Error::QueryReturnedNoRows) => {}
+                    Err(e) => return Err(e.into()),
+                }
+            }
+
+            conn.execute(
+                "INSERT INTO scan_events (
+                    id, context, context_id, barcode, entity_type, entity_id,
+                    quantity, user_id, scanned_at
+                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                params![
+                    &scan.id,
+                    scan.context.as_str(),
+                    &scan.context_id,
+                    &scan.barcode,
+                    &scan.entity_type,
+                    &scan.entity_id,
+                    scan.quantity,
+                    &scan.user_id,
+                    scan.scanned_at.to_rfc3339(),
+                ],
+            )?;
+            Ok(RecordedScan::New(scan))
+        })
+    }
+
+    /// Remove a scan whose processing failed, so a retry is not rejected
+    pub(crate) fn discard(&self, scan_id: &str) -> Result<()> {
+        self.db.execute("DELETE FROM scan_events WHERE id = ?", params![scan_id])?;
+        Ok(())
+    }
+
+    /// All scans against a receipt or shipment, oldest first
+    pub async fn history(&self, context_id: &str) -> Result<Vec<ScanEvent>> {
+        self.db.query_map(
+            "SELECT * FROM scan_events WHERE context_id = ? ORDER BY scanned_at, rowid",
+            params![context_id],
+            row_to_scan,
+        )
+    }
+}
+
+/// Build a scan event for an item barcode
+pub(crate) fn item_scan(
+    context: ScanContext,
+    context_id: &str,
+    barcode: &str,
+    item_id: &str,
+    quantity: f64,
+    user_id: &str,
+    scanned_at: DateTime<Utc>,
+) -> ScanEvent {
+    ScanEvent {
+        id: new_id(),
+        context,
+        context_id: context_id.to_string(),
+        barcode: barcode.to_string(),
+        entity_type: "inventory_item".to_string(),
+        entity_id: item_id.to_string(),
+        quantity,
+        user_id: user_id.to_string(),
+        scanned_at,
+    }
+}
+
+/// Convert database row to ScanEvent
+fn row_to_scan(row: &rusqlite::Row) -> rusqlite::Result<ScanEvent> {
+    Ok(ScanEvent {
+        id: row.get("id")?,
+        context: stored_status(row, "context", ScanContext::parse)?,
+        context_id: row.get("context_id")?,
+        barcode: row.get("barcode")?,
+        entity_type: row.get("entity_type")?,
+        entity_id: row.get("entity_id")?,
+        quantity: row.get("quantity")?,
+        user_id: row.get("user_id")?,
+        scanned_at: required_timestamp(row, "scanned_at")?,
+    })
+}
@@ -2,79 +2,240 @@
 //! 
 //! Core business logic for shipping and receiving operations.
 
+use std::collections::HashMap;
 use std::sync::Arc;
 use base64::Engine;
-use chrono::Utc;
-use rusqlite::params;
-use tracing::{info, debug};
+use rusqlite::{params, OptionalExtension};
+use tracing::{info, debug, warn};
+use wms_core::attachments::{AttachmentKind, AttachmentService, NewAttachment};
+use wms_core::auth::Scope;
+use wms_core::changes::{outbox_recorder, ChangeKind, SharedRecorder};
+use wms_core::clock::{SharedClock, system_clock};
+use wms_core::context::RequestContext;
+use wms_core::credit::{check_credit_in, CUSTOMER_OWNER};
+use wms_core::custom_fields::{custom_fields_json, parse_custom_fields, CustomFieldEntity, CustomFieldService};
 use wms_core::db::Database;
 use wms_core::error::{WmsError, Result};
-use wms_core::types::new_id;
+use wms_core::events::{SharedEmitter, WmsEvent, noop_emitter};
+use wms_core::operations::CancellationToken;
+use wms_core::overrides::{OverrideAction, OverrideService, OverrideToken};
+use wms_core::sequences::DocumentSequence;
+use wms_core::temperature::{TemperatureOwner, TemperatureReading, TemperatureService};
+use wms_core::types::{
+    new_id, required_timestamp, stored_status, stored_timestamp, DateRange, Measurement,
+    MeasurementPreferences, Weight,
+};
+use wms_inventory::{
+    AdjustmentType, AisleTraversal, InventoryAdjustment, InventoryItem, InventoryService, LocationZone,
+    PickPath, PickPathOptimizer, PickStop, PriceBook, StockAllocator, ITEM_ATTACHMENT_OWNER,
+};
 use crate::models::*;
-use crate::barcode::{BarcodeDecoder, BarcodeResult};
-use crate::labels::ZplLabel;
+use crate::barcode::{BarcodeDecoder, BarcodeResult, Gs1Parser};
+use crate::labels::{create_packing_slip, create_shipping_label_pdf, ZplLabel};
+use crate::split::{plan_split, split_by_availability};
+use crate::asn::{parse_asn, AsnFormat, AsnImport, UnresolvedAsnLine};
+use crate::blind::{BlindReceivingSettings, ReceiptCompletion, VarianceReport};
+use crate::over_receipt::OverReceiptSettings;
+use crate::sscc::{SsccSettings, SSCC_AI};
+use crate::reconciliation::{
+    build_reconciliation, parse_carrier_invoice, render_reconciliation_csv, CarrierInvoiceImport,
+    CarrierInvoiceLine, CostReconciliation, CostReconciliationExport, InvoicedShipment,
+    UnmatchedInvoiceLine, DEFAULT_VARIANCE_THRESHOLD_PERCENT,
+};
+use crate::claims::{
+    render_claims_zip, DamageClaimExport, DamageClaimLine, DamageClaimReport, EvidencePhoto, LineEvidence,
+    RECEIPT_LINE_OWNER, SHIPMENT_LINE_OWNER,
+};
+use crate::compliance::{
+    check_compliance, ComplianceOverride, ComplianceReport, ComplianceViolation, CustomerRequirements,
+};
+use crate::scanning::{item_scan, ReceiptScanMatch, RecordedScan, ScanContext, ScanEvent, ScanLog, ScanResult};
+
+/// Most shipments `list_shipments` returns per page
+pub const MAX_SHIPMENT_PAGE_SIZE: u32 = 100;
+
+/// Most receipts `list_receipts` returns per page
+pub const MAX_RECEIPT_PAGE_SIZE: u32 = 100;
+
+/// Lines of a shipment with their item and evidence count
+const SHIPMENT_ITEMS_SQL: &str = "SELECT si.*, i.sku, i.name,
+        (SELECT COUNT(*) FROM line_evidence e
+         WHERE e.line_type = ? AND e.line_id = si.id) AS evidence_count
+     FROM shipment_items si
+     LEFT JOIN inventory_items i ON si.item_id = i.id
+     WHERE si.shipment_id = ?";
+
+/// Who cancelled a shipment and why
+struct Cancellation<'a> {
+    reason: &'a str,
+    user_id: &'a str,
+}
+
+/// What confirming a shipment may override
+struct Confirmation<'a> {
+    compliance_override: Option<&'a ComplianceOverride>,
+    override_token: Option<&'a OverrideToken>,
+}
+
+/// Line prices and totals resolved for a shipment being confirmed
+struct ShipmentPricing {
+    /// Line id, unit price and extended price
+    lines: Vec<(String, Option<f64>, Option<f64>)>,
+    subtotal: f64,
+    order_total: f64,
+    currency_code: Option<String>,
+}
+
+impl ShipmentPricing {
+    /// Store the prices on the lines and the totals on the shipment
+    fn store(&self, conn: &rusqlite::Connection, shipment_id: &str) -> Result<()> {
+        for (line_id, unit_price, extended_price) in &self.lines {
+            conn.execute(
+                "UPDATE shipment_items SET unit_price = ?, extended_price = ? WHERE id = ?",
+                params![unit_price, extended_price, line_id],
+            )?;
+        }
+        conn.execute(
+            "UPDATE shipments SET subtotal = ?, order_total = ?, currency_code = ? WHERE id = ?",
+            params![self.subtotal, self.order_total, &self.currency_code, shipment_id],
+        )?;
+        Ok(())
+    }
+}
 
 /// Shipping management service
 pub struct ShippingService {
     db: Arc<Database>,
     barcode_decoder: BarcodeDecoder,
+    price_book: PriceBook,
+    allocator: StockAllocator,
+    attachments: AttachmentService,
+    custom_fields: CustomFieldService,
+    inventory: InventoryService,
+    temperature: TemperatureService,
+    overrides: OverrideService,
+    scans: ScanLog,
+    events: SharedEmitter,
+    changes: SharedRecorder,
+    clock: SharedClock,
 }
 
 impl ShippingService {
     /// Create a new shipping service
     pub fn new(db: Arc<Database>) -> Self {
         Self {
+            price_book: PriceBook::new(db.clone()),
+            allocator: StockAllocator::new(db.clone()),
+            attachments: AttachmentService::new(db.clone()),
+            custom_fields: CustomFieldService::new(db.clone()),
+            inventory: InventoryService::new(db.clone()),
+            temperature: TemperatureService::new(db.clone()),
+            overrides: OverrideService::new(db.clone()),
+            scans: ScanLog::new(db.clone()),
             db,
             barcode_decoder: BarcodeDecoder::new(),
+            events: noop_emitter(),
+            changes: outbox_recorder(),
+            clock: system_clock(),
         }
     }
     
+    /// Publish domain events through the given emitter
+    pub fn with_event_emitter(mut self, events: SharedEmitter) -> Self {
+        self.inventory = self.inventory.with_event_emitter(events.clone());
+        self.events = events;
+        self
+    }
+    
+    /// Record changes for sync through the given recorder
+    pub fn with_change_recorder(mut self, changes: SharedRecorder) -> Self {
+        self.inventory = self.inventory.with_change_recorder(changes.clone());
+        self.changes = changes;
+        self
+    }
+    
+    /// Read the current time from the given clock
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.inventory = self.inventory.with_clock(clock.clone());
+        self.temperature = self.temperature.with_clock(clock.clone());
+        self.overrides = self.overrides.with_clock(clock.clone());
+        self.clock = clock;
+        self
+    }
+    
+    /// Reject repeats of a barcode within `window` in scan commands
+    pub fn with_duplicate_scan_window(mut self, window: std::time::Duration) -> Self {
+        self.scans = self.scans.with_duplicate_window(window);
+        self
+    }
+    
     // ============ Shipment Operations ============
     
-    /// Create a new shipment
-    pub async fn create_shipment(&self, mut shipment: Shipment) -> Result<Shipment> {
+    /// Create a new shipment. Its declared value, with the customer's
+    /// other shipments not yet delivered, must fit under the customer's
+    /// credit limit; past it a supervisor's `override_token` for the
+    /// customer is needed, and is used up.
+    pub async fn create_shipment(
+        &self,
+        mut shipment: Shipment,
+        override_token: Option<&OverrideToken>,
+    ) -> Result<Shipment> {
+        shipment.custom_fields = self.custom_fields.validate(CustomFieldEntity::Shipment, &shipment.custom_fields)?;
+        
         shipment.id = new_id();
-        shipment.shipment_number = self.generate_shipment_number()?;
         shipment.status = ShipmentStatus::Draft;
-        shipment.created_at = Utc::now();
+        shipment.created_at = self.clock.now();
+        shipment.version = 1;
         
-        self.db.execute(
-            "INSERT INTO shipments (
-                id, shipment_number, status, order_reference, customer_id,
-                carrier_id, service_type, ship_to_name, ship_to_address_line1,
-                ship_to_address_line2, ship_to_city, ship_to_state,
-                ship_to_postal_code, ship_to_country, ship_to_phone, ship_to_email,
-                special_instructions, created_by, created_at
-            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
-            params![
-                &shipment.id,
-                &shipment.shipment_number,
-                "draft",
-                &shipment.order_reference,
-                &shipment.customer_id,
-                &shipment.carrier_id,
-                &shipment.service_type,
-                &shipment.ship_to.name,
-                &shipment.ship_to.address.line1,
-                &shipment.ship_to.address.line2,
-                &shipment.ship_to.address.city,
-                &shipment.ship_to.address.state,
-                &shipment.ship_to.address.postal_code,
-                &shipment.ship_to.address.country,
-                &shipment.ship_to.phone,
-                &shipment.ship_to.email,
-                &shipment.special_instructions,
-                &shipment.created_by,
-                shipment.created_at.to_rfc3339(),
-            ],
-        )?;
-        
-        // Insert line items
-        for mut item in &mut shipment.items {
-            item.id = new_id();
-            item.shipment_id = shipment.id.clone();
-            self.insert_shipment_item(&item)?;
-        }
+        // The credit check, any override it uses up, the header, its lines,
+        // its number and the change for sync are stored together or not at all
+        self.db.transaction(|conn| {
+            if let Some(customer_id) = &shipment.customer_id {
+                self.require_credit(conn, customer_id, shipment.declared_value.unwrap_or(0.0), None, override_token)?;
+            }
+            shipment.shipment_number = DocumentSequence::Shipment.next_number_in(conn)?;
+            conn.execute(
+                "INSERT INTO shipments (
+                    id, shipment_number, status, order_reference, customer_id,
+                    carrier_id, service_type, ship_to_name, ship_to_address_line1,
+                    ship_to_address_line2, ship_to_city, ship_to_state,
+                    ship_to_postal_code, ship_to_country, ship_to_phone, ship_to_email,
+                    expected_delivery_date, special_instructions, declared_value, custom_fields,
+                    created_by, created_at
+                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                params![
+                    &shipment.id,
+                    &shipment.shipment_number,
+                    "draft",
+                    &shipment.order_reference,
+                    &shipment.customer_id,
+                    &shipment.carrier_id,
+                    &shipment.service_type,
+                    &shipment.ship_to.name,
+                    &shipment.ship_to.address.line1,
+                    &shipment.ship_to.address.line2,
+                    &shipment.ship_to.address.city,
+                    &shipment.ship_to.address.state,
+                    &shipment.ship_to.address.postal_code,
+                    &shipment.ship_to.address.country,
+                    &shipment.ship_to.phone,
+                    &shipment.ship_to.email,
+                    shipment.expected_delivery_date.map(|d| d.to_rfc3339()),
+                    &shipment.special_instructions,
+                    &shipment.declared_value,
+                    custom_fields_json(&shipment.custom_fields),
+                    &shipment.created_by,
+                    shipment.created_at.to_rfc3339(),
+                ],
+            )?;
+            
+            for item in &mut shipment.items {
+                item.id = new_id();
+                item.shipment_id = shipment.id.clone();
+                Self::insert_shipment_item(conn, item)?;
+            }
+            self.changes.record_model(conn, "shipments", &shipment.id, ChangeKind::Insert, &shipment)
+        })?;
         
         info!("Created shipment: {}", shipment.shipment_number);
         Ok(shipment)
@@ -100,61 +261,670 @@ impl ShippingService {
         }
     }
     
-    /// Update shipment status
+    /// One page of shipments matching `filter`, newest first, with how
+    /// many match in all. Lines are loaded; packages are not.
+    pub async fn list_shipments(&self, filter: ShipmentFilter) -> Result<ShipmentPage> {
+        let page = filter.page.max(1);
+        let page_size = filter.page_size.clamp(1, MAX_SHIPMENT_PAGE_SIZE);
+        let mut conditions: Vec<&str> = Vec::new();
+        let mut params_vec: Vec<String> = Vec::new();
+        
+        if let Some(status) = filter.status {
+            conditions.push("s.status = ?");
+            params_vec.push(format!("{:?}", status).to_lowercase());
+        }
+        if let Some(customer_id) = filter.customer_id {
+            conditions.push("s.customer_id = ?");
+            params_vec.push(customer_id);
+        }
+        if let Some(from) = filter.date_from {
+            conditions.push("substr(COALESCE(s.ship_date, s.created_at), 1, 10) >= ?");
+            params_vec.push(from.to_string());
+        }
+        if let Some(to) = filter.date_to {
+            conditions.push("substr(COALESCE(s.ship_date, s.created_at), 1, 10) <= ?");
+            params_vec.push(to.to_string());
+        }
+        if let Some(text) = filter.search.as_deref().map(str::trim).filter(|q| !q.is_empty()) {
+            conditions.push(
+                "(s.shipment_number LIKE '%' || ? || '%' OR
+                  s.tracking_number LIKE '%' || ? || '%' OR
+                  s.ship_to_name LIKE '%' || ? || '%')"
+            );
+            params_vec.extend(std::iter::repeat_n(text.to_string(), 3));
+        }
+        
+        let where_clause = if conditions.is_empty() {
+            String::new()
+        } else {
+            format!(" WHERE {}", conditions.join(" AND "))
+        };
+        
+        let total_count: i64 = self.db.query_row(
+            &format!("SELECT COUNT(*) FROM shipments s{}", where_clause),
+            rusqlite::params_from_iter(params_vec.iter()),
+            |row| row.get(0),
+        )?.unwrap_or(0);
+        
+        let mut shipments = self.db.query_map(
+            &format!(
+                "SELECT s.* FROM shipments s{}
+                 ORDER BY s.created_at DESC, s.shipment_number DESC
+                 LIMIT {} OFFSET {}",
+                where_clause, page_size, (page - 1) * page_size
+            ),
+            rusqlite::params_from_iter(params_vec.iter()),
+            |row| Self::row_to_shipment(row),
+        )?;
+        for shipment in &mut shipments {
+            shipment.items = self.get_shipment_items(&shipment.id)?;
+        }
+        
+        Ok(ShipmentPage::new(shipments, total_count as u64, page, page_size))
+    }
+    
+    /// Update shipment status. Only the next step of fulfilment is
+    /// allowed; cancelling needs a reason and goes through `cancel_shipment`.
     pub async fn update_status(&self, id: &str, status: ShipmentStatus) -> Result<Shipment> {
-        let status_str = format!("{:?}", status).to_lowercase();
+        self.set_status(id, status, None, None, None).await
+    }
+    
+    /// Update the status of a shipment the caller last saw at
+    /// `expected_version`; fails with the current record if it has changed
+    pub async fn update_status_at_version(
+        &self,
+        id: &str,
+        status: ShipmentStatus,
+        expected_version: i64,
+    ) -> Result<Shipment> {
+        self.set_status(id, status, Some(expected_version), None, None).await
+    }
+    
+    /// Confirm a shipment. Blocking violations of the customer's shipping
+    /// requirements reject the confirmation unless `compliance_override`
+    /// gives a reason, which is logged with the violations it overrode.
+    /// The priced order must fit under the customer's credit limit; past
+    /// it a supervisor's `override_token` for the customer is needed, and
+    /// is used up.
+    pub async fn confirm_shipment(
+        &self,
+        id: &str,
+        expected_version: Option<i64>,
+        compliance_override: Option<ComplianceOverride>,
+        override_token: Option<&OverrideToken>,
+    ) -> Result<Shipment> {
+        let confirmation = Confirmation { compliance_override: compliance_override.as_ref(), override_token };
+        self.set_status(id, ShipmentStatus::Confirmed, expected_version, Some(&confirmation), None).await
+    }
+    
+    /// Cancel a shipment that hasn't shipped. Whatever was picked for it
+    /// goes back into stock, its labels are voided and `reason` is kept on
+    /// the shipment.
+    pub async fn cancel_shipment(&self, ctx: &RequestContext, id: &str, reason: &str) -> Result<Shipment> {
+        let user_id = ctx.actor()?;
+        let reason = reason.trim();
+        if reason.is_empty() {
+            return Err(WmsError::validation("A reason is required to cancel a shipment"));
+        }
+        let cancellation = Cancellation { reason, user_id };
+        self.set_status(id, ShipmentStatus::Cancelled, None, None, Some(&cancellation)).await
+    }
+    
+    async fn set_status(
+        &self,
+        id: &str,
+        status: ShipmentStatus,
+        expected_version: Option<i64>,
+        confirmation: Option<&Confirmation<'_>>,
+        cancellation: Option<&Cancellation<'_>>,
+    ) -> Result<Shipment> {
+        let compliance_override = confirmation.and_then(|c| c.compliance_override);
+        let current = self.get_shipment(id).await?
+            .ok_or_else(|| WmsError::not_found("Shipment not found"))?;
+        current.status.check_transition(status)?;
+        if status == ShipmentStatus::Cancelled && cancellation.is_none() {
+            return Err(WmsError::validation("Cancelling a shipment needs a reason; use cancel_shipment"));
+        }
+        let version = expected_version.unwrap_or(current.version);
+        
+        let mut overridden = None;
+        let mut pricing = None;
+        
+        if status == ShipmentStatus::Shipped {
+            self.require_packages_loaded(id)?;
+        }
+        if status == ShipmentStatus::Confirmed {
+            let report = self.check_shipment_compliance(id).await?;
+            if report.is_blocked() {
+                overridden = Some(Self::require_override(&report, compliance_override)?);
+            }
+            
+            // Lock in line prices and totals before the order is committed to
+            pricing = Some(self.price_shipment(&current).await?);
+        }
+        
+        let status_str = status.as_str();
+        let now = self.clock.now().to_rfc3339();
+        
+        // Only moves from the status and version read above, so two clients
+        // can't both cancel and put the same picks back. Whatever goes with
+        // the new status, and the change for sync, is written in the same
+        // transaction.
+        let changed = self.db.transaction(|conn| {
+            let rows = conn.execute(
+                "UPDATE shipments SET status = ?, updated_at = datetime('now')
+                 WHERE id = ? AND status = ? AND version = ?",
+                params![status_str, id, current.status.as_str(), version],
+            )?;
+            if rows == 0 {
+                return Ok(None);
+            }
+            
+            let mut released = Vec::new();
+            match status {
+                ShipmentStatus::Confirmed => {
+                    if let Some(pricing) = &pricing {
+                        if let Some(customer_id) = &current.customer_id {
+                            let override_token = confirmation.and_then(|c| c.override_token);
+                            self.require_credit(conn, customer_id, pricing.order_total, Some(id), override_token)?;
+                        }
+                        pricing.store(conn, id)?;
+                    }
+                }
+                ShipmentStatus::Shipped => {
+                    conn.execute(
+                        "UPDATE shipments SET ship_date = ? WHERE id = ?",
+                        params![&now, id],
+                    )?;
+                }
+                ShipmentStatus::Delivered => {
+                    conn.execute(
+                        "UPDATE shipments SET actual_delivery_date = ? WHERE id = ?",
+                        params![&now, id],
+                    )?;
+                }
+                ShipmentStatus::Cancelled => {
+                    if let Some(cancellation) = cancellation {
+                        released = self.release_cancelled_shipment(conn, &current, cancellation, &now)?;
+                    }
+                }
+                _ => {}
+            }
+            
+            if let (Some(violations), Some(compliance_override)) = (&overridden, compliance_override) {
+                self.log_compliance_override(conn, id, violations, compliance_override)?;
+            }
+            
+            let shipment = Self::load_shipment(conn, id)?;
+            self.changes.record_model(conn, "shipments", &shipment.id, ChangeKind::Update, &shipment)?;
+            Ok(Some((shipment, released)))
+        })?;
+        
+        let Some((shipment, released)) = changed else {
+            let current = self.get_shipment(id).await?
+                .ok_or_else(|| WmsError::not_found("Shipment not found"))?;
+            return Err(Self::stale_shipment(&current, Some(version)));
+        };
+        for adjustment in &released {
+            self.inventory.after_adjustment(adjustment).await?;
+        }
+        
+        debug!("Updated shipment {} status to {:?}", id, status);
         
-        let rows = self.db.execute(
-            "UPDATE shipments SET status = ?, updated_at = datetime('now') WHERE id = ?",
-            params![&status_str, id],
+        self.events.emit(WmsEvent::ShipmentStatusChanged {
+            shipment_id: shipment.id.clone(),
+            shipment_number: shipment.shipment_number.clone(),
+            status: status_str.to_string(),
+        });
+        
+        Ok(shipment)
+    }
+    
+    /// Conflict carrying the shipment as it is now
+    fn stale_shipment(current: &Shipment, expected_version: Option<i64>) -> WmsError {
+        WmsError::version_conflict(
+            format!("Shipment {} was changed by someone else (version {}, yours {})",
+                    current.shipment_number, current.version, expected_version.unwrap_or_default()),
+            current,
+        )
+    }
+    
+    /// Put the picked quantities of a shipment being cancelled back into
+    /// stock as UNPICK transactions, void its labels and record when and
+    /// why it was cancelled, all in the caller's transaction. Picks go back
+    /// to the locations and lots they were taken from, as with
+    /// `correct_pick`, and are credited to `cancellation.user_id`. Returns
+    /// the adjustments posted, for `InventoryService::after_adjustment`
+    /// once committed.
+    fn release_cancelled_shipment(
+        &self,
+        conn: &rusqlite::Connection,
+        shipment: &Shipment,
+        cancellation: &Cancellation<'_>,
+        now: &str,
+    ) -> Result<Vec<InventoryAdjustment>> {
+        // Lines are read here rather than taken from `shipment`, so a pick
+        // made since it was loaded is put back too
+        let mut picked = conn.prepare(
+            "SELECT id, item_id, location_id, lot_number, quantity_picked FROM shipment_items
+             WHERE shipment_id = ? AND quantity_picked > 0",
         )?;
+        let picked: Vec<(String, InventoryAdjustment)> = picked
+            .query_map(params![&shipment.id], |row| {
+                let line_id: String = row.get(0)?;
+                Ok((line_id.clone(), InventoryAdjustment {
+                    item_id: row.get(1)?,
+                    location_id: row.get(2)?,
+                    warehouse_id: None,
+                    adjustment_type: AdjustmentType::Unpick,
+                    quantity: row.get(4)?,
+                    lot_number: row.get(3)?,
+                    reason_code: None,
+                    notes: Some(format!("Shipment line {} cancelled", line_id)),
+                    user_id: cancellation.user_id.to_string(),
+                }))
+            })?
+            .collect::<rusqlite::Result<_>>()?;
         
-        if rows == 0 {
-            return Err(WmsError::not_found("Shipment not found"));
+        let mut released = Vec::new();
+        for (line_id, line) in picked {
+            for adjustment in Self::take_back_picks(conn, &line_id, &line)? {
+                self.inventory.post_adjustment(conn, &adjustment)?;
+                released.push(adjustment);
+            }
+            conn.execute(
+                "UPDATE shipment_items SET quantity_picked = 0, status = 'pending' WHERE id = ?",
+                params![&line_id],
+            )?;
         }
         
-        // Handle status-specific actions
-        match status {
-            ShipmentStatus::Shipped => {
-                self.db.execute(
-                    "UPDATE shipments SET ship_date = datetime('now') WHERE id = ?",
-                    params![id],
-                )?;
+        conn.execute(
+            "UPDATE shipments SET cancelled_at = ?, cancel_reason = ?, label_printed = 0 WHERE id = ?",
+            params![now, cancellation.reason, &shipment.id],
+        )?;
+        let voided = conn.execute(
+            "UPDATE shipping_labels SET voided_at = ? WHERE shipment_id = ? AND voided_at IS NULL",
+            params![now, &shipment.id],
+        )?;
+        
+        info!(
+            "Cancelling shipment {}: {} pick(s) put back, {} label(s) voided",
+            shipment.shipment_number, released.len(), voided,
+        );
+        Ok(released)
+    }
+    
+    /// Check a shipment against its customer's shipping requirements
+    pub async fn check_shipment_compliance(&self, shipment_id: &str) -> Result<ComplianceReport> {
+        let shipment = self.get_shipment(shipment_id).await?
+            .ok_or_else(|| WmsError::not_found("Shipment not found"))?;
+        
+        let requirements = match &shipment.customer_id {
+            Some(customer_id) => self.db.query_row(
+                "SELECT * FROM customer_shipping_requirements WHERE customer_id = ?",
+                params![customer_id],
+                |row| Ok(CustomerRequirements {
+                    required_carrier_id: row.get("required_carrier_id")?,
+                    required_service: row.get("required_service")?,
+                    allow_partial: row.get::<_, i32>("allow_partial")? == 1,
+                    appointment_required: row.get::<_, i32>("appointment_required")? == 1,
+                    label_notes: row.get("label_notes")?,
+                }),
+            )?,
+            None => None,
+        };
+        
+        let Some(requirements) = requirements else {
+            return Ok(ComplianceReport {
+                shipment_id: shipment.id,
+                has_requirements: false,
+                violations: Vec::new(),
+                label_notes: None,
+            });
+        };
+        
+        // Split off from another shipment, or has backorders split from it
+        let split_count: i64 = self.db.query_row(
+            "SELECT COUNT(*) FROM shipments WHERE parent_shipment_id = ?",
+            params![&shipment.id],
+            |row| row.get(0),
+        )?.unwrap_or(0);
+        let is_partial = shipment.parent_shipment_id.is_some() || split_count > 0;
+        
+        Ok(ComplianceReport {
+            violations: check_compliance(&shipment, &requirements, is_partial),
+            shipment_id: shipment.id,
+            has_requirements: true,
+            label_notes: requirements.label_notes,
+        })
+    }
+    
+    /// The blocking violations an override covers, or the error that stops
+    /// confirmation when there is no usable override
+    fn require_override(
+        report: &ComplianceReport,
+        compliance_override: Option<&ComplianceOverride>,
+    ) -> Result<Vec<ComplianceViolation>> {
+        let violations: Vec<ComplianceViolation> = report.blocking().cloned().collect();
+        let messages = violations.iter().map(|v| v.message.as_str()).collect::<Vec<_>>().join("; ");
+        
+        match compliance_override {
+            Some(o) if !o.reason.trim().is_empty() => Ok(violations),
+            Some(_) => Err(WmsError::validation("A reason is required to override shipping requirements")),
+            None => Err(WmsError::validation(format!(
+                "Shipment breaks the customer's shipping requirements: {}; confirm with an override reason to proceed",
+                messages
+            ))),
+        }
+    }
+    
+    /// Record who confirmed a shipment despite which violations, and why
+    fn log_compliance_override(
+        &self,
+        conn: &rusqlite::Connection,
+        shipment_id: &str,
+        violations: &[ComplianceViolation],
+        compliance_override: &ComplianceOverride,
+    ) -> Result<()> {
+        let violations_json = serde_json::to_string(violations)?;
+        
+        conn.execute(
+            "INSERT INTO shipment_compliance_overrides (
+                id, shipment_id, violations, reason, overridden_by, created_at
+            ) VALUES (?, ?, ?, ?, ?, ?)",
+            params![
+                new_id(),
+                shipment_id,
+                &violations_json,
+                compliance_override.reason.trim(),
+                &compliance_override.user_id,
+                self.clock.now().to_rfc3339(),
+            ],
+        )?;
+        
+        info!(
+            "Shipment {} confirmed by {} overriding {} shipping requirement violation(s): {}",
+            shipment_id, compliance_override.user_id, violations.len(), compliance_override.reason.trim()
+        );
+        Ok(())
+    }
+    
+    /// Resolve line prices from the customer's price list and totals for
+    /// a shipment
+    async fn price_shipment(&self, shipment: &Shipment) -> Result<ShipmentPricing> {
+        let today = self.clock.now();
+        let mut lines = Vec::with_capacity(shipment.items.len());
+        let mut subtotal = 0.0;
+        let mut currency_code: Option<String> = None;
+        
+        for item in &shipment.items {
+            let price = self.price_book
+                .get_price(&item.item_id, shipment.customer_id.as_deref(), today)
+                .await?;
+            
+            let Some(price) = price else {
+                debug!("No price found for item {} on shipment {}", item.item_id, shipment.shipment_number);
+                lines.push((item.id.clone(), None, None));
+                continue;
+            };
+            
+            match &currency_code {
+                Some(code) if *code != price.currency_code => {
+                    return Err(WmsError::validation(format!(
+                        "Shipment {} mixes currencies {} and {}",
+                        shipment.shipment_number, code, price.currency_code
+                    )));
+                }
+                Some(_) => {}
+                None => currency_code = Some(price.currency_code.clone()),
             }
-            ShipmentStatus::Delivered => {
-                self.db.execute(
-                    "UPDATE shipments SET actual_delivery_date = datetime('now') WHERE id = ?",
-                    params![id],
+            
+            let extended = price.unit_price * item.quantity_ordered;
+            lines.push((item.id.clone(), Some(price.unit_price), Some(extended)));
+            subtotal += extended;
+        }
+        
+        let order_total = subtotal + shipment.shipping_cost.unwrap_or(0.0);
+        
+        debug!("Priced shipment {}: subtotal {:.2}, total {:.2}", shipment.shipment_number, subtotal, order_total);
+        Ok(ShipmentPricing { lines, subtotal, order_total, currency_code })
+    }
+    
+    /// Reject an order worth `requested` that takes the customer past their
+    /// credit limit, unless `override_token` approves it; the token is used
+    /// up in the caller's transaction. `shipment_id` is the order itself
+    /// when it is already stored.
+    fn require_credit(
+        &self,
+        conn: &rusqlite::Connection,
+        customer_id: &str,
+        requested: f64,
+        shipment_id: Option<&str>,
+        override_token: Option<&OverrideToken>,
+    ) -> Result<()> {
+        let credit = check_credit_in(conn, customer_id, requested, shipment_id)?;
+        if credit.exceeded() {
+            let Some(token) = override_token else {
+                return Err(credit.error());
+            };
+            self.overrides.consume_in(conn, token, OverrideAction::CreditLimit, CUSTOMER_OWNER, customer_id)?;
+            info!("Credit limit of customer {} overridden by {}", customer_id, token.id);
+        }
+        Ok(())
+    }
+    
+    /// Split a shipment, moving quantities onto a new backorder shipment.
+    ///
+    /// The backorder starts as a Draft with the same ship-to and carrier
+    /// details; the original keeps its status and can continue to picking.
+    pub async fn split_shipment(&self, shipment_id: &str, spec: SplitSpec) -> Result<ShipmentSplit> {
+        let parent = self.get_shipment(shipment_id).await?
+            .ok_or_else(|| WmsError::not_found("Shipment not found"))?;
+        
+        if !parent.status.can_split() {
+            return Err(WmsError::validation(format!(
+                "Shipment {} is {:?} and can no longer be split",
+                parent.shipment_number, parent.status
+            )));
+        }
+        
+        let lines = match spec {
+            SplitSpec::Manual { lines } => lines,
+            SplitSpec::AutoByAvailability => {
+                let available = self.available_for_split(&parent)?;
+                split_by_availability(&parent.items, &available)
+            }
+        };
+        let plan = plan_split(&parent.items, &lines)?;
+        
+        let child_id = new_id();
+        let child_number = self.generate_shipment_number()?;
+        
+        self.db.transaction(|conn| {
+            conn.execute(
+                "INSERT INTO shipments (
+                    id, shipment_number, status, order_reference, customer_id,
+                    carrier_id, service_type, ship_to_name, ship_to_address_line1,
+                    ship_to_address_line2, ship_to_city, ship_to_state,
+                    ship_to_postal_code, ship_to_country, ship_to_phone, ship_to_email,
+                    currency_code, special_instructions, parent_shipment_id,
+                    custom_fields, created_by, created_at
+                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                params![
+                    &child_id,
+                    &child_number,
+                    "draft",
+                    &parent.order_reference,
+                    &parent.customer_id,
+                    &parent.carrier_id,
+                    &parent.service_type,
+                    &parent.ship_to.name,
+                    &parent.ship_to.address.line1,
+                    &parent.ship_to.address.line2,
+                    &parent.ship_to.address.city,
+                    &parent.ship_to.address.state,
+                    &parent.ship_to.address.postal_code,
+                    &parent.ship_to.address.country,
+                    &parent.ship_to.phone,
+                    &parent.ship_to.email,
+                    &parent.currency_code,
+                    &parent.special_instructions,
+                    &parent.id,
+                    custom_fields_json(&parent.custom_fields),
+                    &parent.created_by,
+                    self.clock.now().to_rfc3339(),
+                ],
+            )?;
+            
+            for line in &plan {
+                let item = parent.items.iter()
+                    .find(|i| i.id == line.shipment_item_id)
+                    .ok_or_else(|| WmsError::not_found("Shipment line not found"))?;
+                
+                conn.execute(
+                    "INSERT INTO shipment_items (
+                        id, shipment_id, item_id, location_id, quantity_ordered,
+                        quantity_picked, quantity_shipped, lot_number, serial_number,
+                        unit_price, extended_price, status
+                    ) VALUES (?, ?, ?, ?, ?, 0, 0, ?, ?, ?, ?, 'pending')",
+                    params![
+                        new_id(),
+                        &child_id,
+                        &item.item_id,
+                        &item.location_id,
+                        line.backorder,
+                        &item.lot_number,
+                        &item.serial_number,
+                        item.unit_price,
+                        item.unit_price.map(|p| p * line.backorder),
+                    ],
                 )?;
+                
+                if line.removes_line() {
+                    conn.execute("DELETE FROM shipment_items WHERE id = ?", params![&item.id])?;
+                } else {
+                    conn.execute(
+                        "UPDATE shipment_items SET quantity_ordered = ?, extended_price = ?
+                         WHERE id = ?",
+                        params![line.keep, item.unit_price.map(|p| p * line.keep), &item.id],
+                    )?;
+                }
+            }
+            
+            // Priced shipments carry their share of the subtotal; shipping
+            // cost stays with the original
+            if parent.subtotal.is_some() {
+                let totals = [(&parent.id, parent.shipping_cost), (&child_id, None)];
+                for (id, shipping_cost) in totals {
+                    conn.execute(
+                        "UPDATE shipments
+                         SET subtotal = t.subtotal, order_total = t.subtotal + ?2,
+                             updated_at = datetime('now')
+                         FROM (SELECT COALESCE(SUM(extended_price), 0) AS subtotal
+                               FROM shipment_items WHERE shipment_id = ?1) AS t
+                         WHERE id = ?1",
+                        params![id, shipping_cost.unwrap_or(0.0)],
+                    )?;
+                }
+            }
+            
+            Ok(())
+        })?;
+        
+        info!(
+            "Split shipment {}: {} line(s) backordered on {}",
+            parent.shipment_number, plan.len(), child_number
+        );
+        
+        let parent = self.get_shipment(shipment_id).await?
+            .ok_or_else(|| WmsError::not_found("Shipment not found"))?;
+        let child = self.get_shipment(&child_id).await?
+            .ok_or_else(|| WmsError::not_found("Backorder shipment not found"))?;
+        
+        Ok(ShipmentSplit { parent, child })
+    }
+    
+    /// Stock available now for each item on a shipment, after quality holds,
+    /// reservations and the unpicked demand of other committed shipments
+    fn available_for_split(&self, shipment: &Shipment) -> Result<HashMap<String, f64>> {
+        let mut available = HashMap::new();
+        
+        for item in &shipment.items {
+            if available.contains_key(&item.item_id) {
+                continue;
             }
-            _ => {}
+            
+            let quantity: f64 = self.db.query_row(
+                "SELECT
+                    (SELECT COALESCE(SUM(st.quantity), 0) FROM inventory_stock st
+                     WHERE st.item_id = ?1
+                       AND NOT EXISTS (SELECT 1 FROM quality_holds h
+                                       WHERE h.item_id = st.item_id AND h.lot_number = st.lot_number
+                                         AND h.released_at IS NULL))
+                  - (SELECT COALESCE(SUM(quantity), 0) FROM inventory_reservations
+                     WHERE item_id = ?1 AND status = 'active'
+                       AND (expires_at IS NULL OR datetime(expires_at) > datetime(?3)))
+                  - (SELECT COALESCE(SUM(si.quantity_ordered - COALESCE(si.quantity_picked, 0)), 0)
+                     FROM shipment_items si
+                     JOIN shipments s ON si.shipment_id = s.id
+                     WHERE si.item_id = ?1 AND s.id != ?2
+                       AND s.status IN ('confirmed', 'picking', 'packed'))",
+                params![&item.item_id, &shipment.id, self.clock.now().to_rfc3339()],
+                |row| row.get(0),
+            )?.unwrap_or(0.0);
+            
+            available.insert(item.item_id.clone(), quantity.max(0.0));
         }
         
-        debug!("Updated shipment {} status to {:?}", id, status);
-        self.get_shipment(id).await?
-            .ok_or_else(|| WmsError::not_found("Shipment not found"))
+        Ok(available)
     }
     
-    /// Generate shipping label
-    pub async fn generate_label(&self, shipment_id: &str) -> Result<ShippingLabel> {
+    /// Generate a 4" x 6" shipping label: ZPL for thermal printers or PDF
+    /// for office printers
+    pub async fn generate_label(&self, shipment_id: &str, format: LabelFormat) -> Result<ShippingLabel> {
         let shipment = self.get_shipment(shipment_id).await?
             .ok_or_else(|| WmsError::not_found("Shipment not found"))?;
         
-        // Generate ZPL label
-        let zpl = ZplLabel::new()
-            .set_size(4, 6) // 4" x 6" label
-            .add_text(50, 50, &shipment.ship_to.name, 'A', 40)
-            .add_text(50, 100, &shipment.ship_to.address.line1, 'A', 30)
-            .add_text(50, 140, &format!(
-                "{}, {} {}",
-                shipment.ship_to.address.city,
-                shipment.ship_to.address.state,
-                shipment.ship_to.address.postal_code
-            ), 'A', 30)
-            .add_barcode_128(50, 200, &shipment.shipment_number, 80)
-            .add_text(50, 300, &format!("Ship #: {}", shipment.shipment_number), 'A', 25);
+        let units = MeasurementPreferences::load(&self.db)?;
+        let weight = shipment.total_weight_kg
+            .or_else(|| {
+                let weights: Vec<f64> = shipment.packages.iter().filter_map(|p| p.weight_kg).collect();
+                (!weights.is_empty()).then(|| weights.iter().sum())
+            })
+            .map(|kg| Weight::from_kg(kg).to_display(units.weight));
+        let city_line = format!(
+            "{}, {} {}",
+            shipment.ship_to.address.city,
+            shipment.ship_to.address.state,
+            shipment.ship_to.address.postal_code
+        );
         
-        let zpl_data = zpl.build();
+        let data = match format {
+            LabelFormat::Zpl => {
+                let mut zpl = ZplLabel::new()
+                    .set_size(4, 6) // 4" x 6" label
+                    .add_text(50, 50, &shipment.ship_to.name, 'A', 40)
+                    .add_text(50, 100, &shipment.ship_to.address.line1, 'A', 30)
+                    .add_text(50, 140, &city_line, 'A', 30)
+                    .add_barcode_128(50, 200, &shipment.shipment_number, 80)
+                    .add_text(50, 300, &format!("Ship #: {}", shipment.shipment_number), 'A', 25);
+                if let Some(weight) = &weight {
+                    zpl = zpl.add_text(50, 340, &format!("Weight: {}", weight), 'A', 25);
+                }
+                zpl.build().into_bytes()
+            }
+            LabelFormat::Pdf => {
+                let address = [
+                    shipment.ship_to.name.clone(),
+                    shipment.ship_to.address.line1.clone(),
+                    city_line,
+                ];
+                create_shipping_label_pdf(&shipment.shipment_number, &address, weight.as_deref())?
+            }
+            LabelFormat::Png => {
+                return Err(WmsError::validation("Shipping labels are generated as ZPL or PDF"));
+            }
+        };
         
         // Store label
         let label = ShippingLabel {
@@ -162,11 +932,25 @@ impl ShippingService {
             shipment_id: shipment_id.to_string(),
             package_id: None,
             label_type: LabelType::Shipping,
-            format: LabelFormat::Zpl,
-            data: base64::engine::general_purpose::STANDARD.encode(&zpl_data),
-            created_at: Utc::now(),
+            format,
+            data: base64::engine::general_purpose::STANDARD.encode(&data),
+            content_type: format.content_type().to_string(),
+            created_at: self.clock.now(),
         };
         
+        self.save_label(&label, "shipping", &data)?;
+        
+        // Mark label as printed
+        self.db.execute(
+            "UPDATE shipments SET label_printed = 1 WHERE id = ?",
+            params![shipment_id],
+        )?;
+        
+        info!("Generated shipping label for {}", shipment.shipment_number);
+        Ok(label)
+    }
+    
+    fn save_label(&self, label: &ShippingLabel, label_type: &str, data: &[u8]) -> Result<()> {
         self.db.execute(
             "INSERT INTO shipping_labels (id, shipment_id, package_id, label_type, label_format, label_data, created_at)
              VALUES (?, ?, ?, ?, ?, ?, ?)",
@@ -174,358 +958,4254 @@ impl ShippingService {
                 &label.id,
                 &label.shipment_id,
                 &label.package_id,
-                "shipping",
-                "zpl",
-                zpl_data.as_bytes(),
+                label_type,
+                label.format.as_str(),
+                data,
                 label.created_at.to_rfc3339(),
             ],
         )?;
+        Ok(())
+    }
+    
+    /// Generate a packing slip PDF, with weights and dimensions in the
+    /// configured display units
+    pub async fn generate_packing_slip(&self, shipment_id: &str) -> Result<Vec<u8>> {
+        let shipment = self.get_shipment(shipment_id).await?
+            .ok_or_else(|| WmsError::not_found("Shipment not found"))?;
+        let units = MeasurementPreferences::load(&self.db)?;
         
-        // Mark label as printed
-        self.db.execute(
-            "UPDATE shipments SET label_printed = 1 WHERE id = ?",
-            params![shipment_id],
-        )?;
-        
-        info!("Generated shipping label for {}", shipment.shipment_number);
-        Ok(label)
+        let ship_to = format!("{}\n{}", shipment.ship_to.name, shipment.ship_to.address.full_address());
+        Ok(create_packing_slip(
+            &shipment.shipment_number,
+            &ship_to,
+            &Self::packing_slip_lines(&shipment),
+            &shipment.packages,
+            &units,
+        ))
     }
     
-    // ============ Receipt Operations ============
+    /// (SKU, name, quantity) for each shipment line; lines whose item has
+    /// no SKU show the item id
+    fn packing_slip_lines(shipment: &Shipment) -> Vec<(String, String, f64)> {
+        shipment.items.iter()
+            .map(|item| (
+                item.item_sku.clone().unwrap_or_else(|| item.item_id.clone()),
+                item.item_name.clone().unwrap_or_default(),
+                item.quantity_ordered,
+            ))
+            .collect()
+    }
     
-    /// Create a new receipt
-    pub async fn create_receipt(&self, mut receipt: Receipt) -> Result<Receipt> {
-        receipt.id = new_id();
-        receipt.receipt_number = self.generate_receipt_number()?;
-        receipt.status = ReceiptStatus::Pending;
-        receipt.created_at = Utc::now();
+    // ============ Packages and Pallets ============
+    
+    /// Add a carton to a shipment, numbered after its last package and
+    /// given an SSCC license plate
+    pub async fn add_package(&self, shipment_id: &str, package: NewPackage) -> Result<ShipmentPackage> {
+        let shipment = self.get_shipment(shipment_id).await?
+            .ok_or_else(|| WmsError::not_found("Shipment not found"))?;
+        Self::require_packing_open(&shipment)?;
+        if package.package_type.as_deref() == Some(ShipmentPackage::PALLET) {
+            return Err(WmsError::validation("Pallets are built from closed cartons"));
+        }
         
+        let id = new_id();
+        let lpn = SsccSettings::load(&self.db)?.next_sscc(&self.db)?;
         self.db.execute(
-            "INSERT INTO receipts (
-                id, receipt_number, status, po_number, supplier_name,
-                supplier_reference, expected_date, dock_door, notes,
-                created_by, created_at
-            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            "INSERT INTO shipment_packages (
+                id, shipment_id, package_number, weight_kg, length_cm, width_cm, height_cm, package_type, lpn
+            ) VALUES (
+                ?1, ?2, (SELECT COALESCE(MAX(package_number), 0) + 1 FROM shipment_packages WHERE shipment_id = ?2),
+                ?3, ?4, ?5, ?6, ?7, ?8
+            )",
             params![
-                &receipt.id,
-                &receipt.receipt_number,
-                "pending",
-                &receipt.po_number,
-                &receipt.supplier_name,
-                &receipt.supplier_reference,
-                receipt.expected_date.map(|d| d.to_rfc3339()),
-                &receipt.dock_door,
-                &receipt.notes,
-                &receipt.created_by,
-                receipt.created_at.to_rfc3339(),
+                &id,
+                shipment_id,
+                package.weight_kg,
+                package.length_cm,
+                package.width_cm,
+                package.height_cm,
+                &package.package_type,
+                &lpn,
             ],
         )?;
         
-        // Insert line items
-        for mut item in &mut receipt.items {
-            item.id = new_id();
-            item.receipt_id = receipt.id.clone();
-            self.insert_receipt_item(&item)?;
-        }
-        
-        info!("Created receipt: {}", receipt.receipt_number);
-        Ok(receipt)
+        debug!("Added package {} to {}", lpn, shipment.shipment_number);
+        self.get_package(&id)
     }
     
-    /// Process a receipt item (scan and receive)
-    pub async fn process_receipt_item(&self, receipt_id: &str, mut item: ReceiptItem) -> Result<Receipt> {
-        // Update receipt status to receiving
+    /// Mark a package as packed. Closed cartons have to be palletized or
+    /// floor-loaded before the shipment can ship.
+    pub async fn close_package(&self, package_id: &str) -> Result<ShipmentPackage> {
         self.db.execute(
-            "UPDATE receipts SET status = 'receiving', received_date = COALESCE(received_date, datetime('now'))
-             WHERE id = ?",
-            params![receipt_id],
+            "UPDATE shipment_packages SET closed_at = COALESCE(closed_at, ?) WHERE id = ?",
+            params![self.clock.now().to_rfc3339(), package_id],
         )?;
-        
-        // Update item quantities
-        item.status = if item.quantity_received >= item.quantity_expected {
-            ReceiptItemStatus::Complete
-        } else if item.quantity_received > 0.0 {
-            ReceiptItemStatus::Partial
-        } else {
-            ReceiptItemStatus::Pending
-        };
-        
-        item.received_at = Some(Utc::now());
+        self.get_package(package_id)
+    }
+    
+    /// Mark a closed carton as loaded loose onto the trailer floor, or
+    /// take the mark off again
+    pub async fn set_floor_loaded(&self, lpn: &str, floor_loaded: bool) -> Result<ShipmentPackage> {
+        let carton = self.get_package_by_lpn(lpn)?;
+        if carton.floor_loaded == floor_loaded {
+            return Ok(carton);
+        }
+        let shipment = self.get_shipment(&carton.shipment_id).await?
+            .ok_or_else(|| WmsError::not_found("Shipment not found"))?;
+        Self::require_packing_open(&shipment)?;
+        if floor_loaded {
+            Self::check_palletizable(&carton, &shipment)?;
+            if let Some(pallet_id) = &carton.parent_package_id {
+                return Err(WmsError::validation(format!(
+                    "Carton {} is on pallet {}", lpn, self.get_package(pallet_id)?.lpn.unwrap_or_default()
+                )));
+            }
+        }
         
         self.db.execute(
-            "UPDATE receipt_items SET
-                quantity_received = ?, quantity_damaged = ?, lot_number = ?,
-                expiry_date = ?, status = ?, received_by = ?, received_at = ?, notes = ?
-             WHERE id = ?",
-            params![
-                item.quantity_received,
-                item.quantity_damaged,
-                &item.lot_number,
-                item.expiry_date.map(|d| d.to_rfc3339()),
-                format!("{:?}", item.status).to_lowercase(),
-                &item.received_by,
-                item.received_at.map(|d| d.to_rfc3339()),
-                &item.notes,
-                &item.id,
-            ],
+            "UPDATE shipment_packages SET floor_loaded = ? WHERE id = ?",
+            params![floor_loaded, &carton.id],
         )?;
-        
-        debug!("Processed receipt item: {} received {}", item.id, item.quantity_received);
-        
-        // Return updated receipt
-        self.get_receipt(receipt_id).await?
-            .ok_or_else(|| WmsError::not_found("Receipt not found"))
+        self.get_package(&carton.id)
     }
     
-    /// Complete a receipt and update inventory
-    pub async fn complete_receipt(&self, receipt_id: &str) -> Result<Receipt> {
-        let receipt = self.get_receipt(receipt_id).await?
-            .ok_or_else(|| WmsError::not_found("Receipt not found"))?;
+    /// Build closed cartons of a shipment onto a new pallet, which gets its
+    /// own license plate and the cartons' combined weight
+    pub async fn build_pallet(&self, shipment_id: &str, carton_lpns: &[String]) -> Result<ShipmentPackage> {
+        if carton_lpns.is_empty() {
+            return Err(WmsError::validation("A pallet needs at least one carton"));
+        }
+        let shipment = self.get_shipment(shipment_id).await?
+            .ok_or_else(|| WmsError::not_found("Shipment not found"))?;
+        Self::require_packing_open(&shipment)?;
         
-        // Verify all items are received
-        for item in &receipt.items {
-            if item.status == ReceiptItemStatus::Pending {
-                return Err(WmsError::validation("Not all items have been received"));
+        let mut cartons: Vec<ShipmentPackage> = Vec::with_capacity(carton_lpns.len());
+        for lpn in carton_lpns {
+            let carton = self.get_package_by_lpn(lpn)?;
+            Self::check_palletizable(&carton, &shipment)?;
+            if let Some(pallet_id) = &carton.parent_package_id {
+                return Err(WmsError::validation(format!(
+                    "Carton {} is already on pallet {}", lpn, self.get_package(pallet_id)?.lpn.unwrap_or_default()
+                )));
+            }
+            if cartons.iter().any(|c| c.id == carton.id) {
+                return Err(WmsError::validation(format!("Carton {} is listed twice", lpn)));
             }
+            cartons.push(carton);
         }
         
-        // Update receipt status
-        self.db.execute(
-            "UPDATE receipts SET status = 'completed', completed_at = datetime('now')
-             WHERE id = ?",
-            params![receipt_id],
-        )?;
+        let pallet_id = new_id();
+        let lpn = SsccSettings::load(&self.db)?.next_sscc(&self.db)?;
+        let now = self.clock.now().to_rfc3339();
+        self.db.transaction(|conn| {
+            conn.execute(
+                "INSERT INTO shipment_packages (id, shipment_id, package_number, package_type, lpn, closed_at)
+                 VALUES (
+                    ?1, ?2, (SELECT COALESCE(MAX(package_number), 0) + 1 FROM shipment_packages WHERE shipment_id = ?2),
+                    ?3, ?4, ?5
+                 )",
+                params![&pallet_id, shipment_id, ShipmentPackage::PALLET, &lpn, &now],
+            )?;
+            for carton in &cartons {
+                Self::nest_carton(conn, carton, &pallet_id)?;
+            }
+            Self::refresh_pallet(conn, &pallet_id)
+        })?;
         
-        info!("Completed receipt: {}", receipt.receipt_number);
-        self.get_receipt(receipt_id).await?
-            .ok_or_else(|| WmsError::not_found("Receipt not found"))
+        info!("Built pallet {} of {} cartons for {}", lpn, cartons.len(), shipment.shipment_number);
+        self.get_package(&pallet_id)
     }
     
-    /// Get receipt by ID
-    async fn get_receipt(&self, id: &str) -> Result<Option<Receipt>> {
-        let receipt = self.db.query_row(
-            "SELECT * FROM receipts WHERE id = ?",
-            params![id],
-            |row| Self::row_to_receipt(row),
+    /// Take a pallet apart, leaving its cartons loose. Returns the cartons.
+    pub async fn unbuild_pallet(&self, pallet_lpn: &str) -> Result<Vec<ShipmentPackage>> {
+        let pallet = self.get_pallet(pallet_lpn).await?;
+        let carton_ids: Vec<String> = self.db.query_map(
+            "SELECT id FROM shipment_packages WHERE parent_package_id = ? ORDER BY package_number",
+            params![&pallet.id],
+            |row| row.get(0),
         )?;
         
-        if let Some(mut r) = receipt {
-            r.items = self.get_receipt_items(&r.id)?;
-            Ok(Some(r))
-        } else {
-            Ok(None)
-        }
+        self.db.transaction(|conn| {
+            conn.execute(
+                "UPDATE shipment_packages SET parent_package_id = NULL WHERE parent_package_id = ?",
+                params![&pallet.id],
+            )?;
+            Self::refresh_pallet(conn, &pallet.id)
+        })?;
+        
+        info!("Unbuilt pallet {}", pallet_lpn);
+        carton_ids.iter().map(|id| self.get_package(id)).collect()
     }
     
-    // ============ Barcode Operations ============
-    
-    /// Decode a barcode from image data
-    pub async fn decode_barcode(&self, image_data: &[u8], width: u32, height: u32) -> Result<BarcodeResult> {
-        self.barcode_decoder.decode(image_data, width, height)
+    /// Move a closed carton onto a pallet of the same shipment, off the
+    /// pallet it was on if any. A pallet left without cartons is removed.
+    /// Returns the pallet it was moved onto.
+    pub async fn move_carton(&self, carton_lpn: &str, pallet_lpn: &str) -> Result<ShipmentPackage> {
+        let pallet = self.get_pallet(pallet_lpn).await?;
+        let carton = self.get_package_by_lpn(carton_lpn)?;
+        let shipment = self.get_shipment(&pallet.shipment_id).await?
+            .ok_or_else(|| WmsError::not_found("Shipment not found"))?;
+        Self::check_palletizable(&carton, &shipment)?;
+        if carton.parent_package_id.as_deref() == Some(pallet.id.as_str()) {
+            return Ok(pallet);
+        }
+        
+        self.db.transaction(|conn| {
+            Self::nest_carton(conn, &carton, &pallet.id)?;
+            Self::refresh_pallet(conn, &pallet.id)?;
+            match &carton.parent_package_id {
+                Some(previous) => Self::refresh_pallet(conn, previous),
+                None => Ok(()),
+            }
+        })?;
+        
+        debug!("Moved carton {} onto pallet {}", carton_lpn, pallet_lpn);
+        self.get_package(&pallet.id)
     }
     
-    // ============ Helper Methods ============
-    
-    fn generate_shipment_number(&self) -> Result<String> {
-        let count: i64 = self.db.query_row(
-            "SELECT COUNT(*) + 1 FROM shipments",
-            [],
-            |row| row.get(0),
-        )?.unwrap_or(1);
+    /// Generate a pallet's SSCC label
+    pub async fn generate_pallet_label(&self, pallet_lpn: &str) -> Result<ShippingLabel> {
+        let pallet = self.get_package_by_lpn(pallet_lpn)?;
+        if !pallet.is_pallet() {
+            return Err(WmsError::validation(format!("{} is not a pallet", pallet_lpn)));
+        }
+        let shipment = self.get_shipment(&pallet.shipment_id).await?
+            .ok_or_else(|| WmsError::not_found("Shipment not found"))?;
+        let cartons = shipment.packages.iter()
+            .filter(|p| p.parent_package_id.as_deref() == Some(pallet.id.as_str()))
+            .count();
+        let units = MeasurementPreferences::load(&self.db)?;
+        
+        let mut zpl = ZplLabel::new()
+            .set_size(4, 6)
+            .add_text(50, 40, "SHIP TO", 'A', 25)
+            .add_text(50, 75, &shipment.ship_to.name, 'A', 40)
+            .add_text(50, 125, &shipment.ship_to.address.line1, 'A', 30)
+            .add_text(50, 165, &format!(
+                "{}, {} {}",
+                shipment.ship_to.address.city,
+                shipment.ship_to.address.state,
+                shipment.ship_to.address.postal_code
+            ), 'A', 30)
+            .add_line(50, 220, 712, 3)
+            .add_text(50, 250, &format!("Ship #: {}", shipment.shipment_number), 'A', 25)
+            .add_text(50, 290, &format!("Pallet {}: {} cartons", pallet.package_number, cartons), 'A', 25);
+        if let Some(kg) = pallet.weight_kg {
+            zpl = zpl.add_text(50, 330, &format!("Weight: {}", Weight::from_kg(kg).to_display(units.weight)), 'A', 25);
+        }
+        let zpl_data = zpl
+            .add_line(50, 390, 712, 3)
+            .add_text(50, 420, "SSCC", 'A', 25)
+            .add_sscc_barcode(50, 460, pallet_lpn, 200)
+            .add_text(50, 690, &format!("({}) {}", SSCC_AI, pallet_lpn), 'A', 35)
+            .build();
+        
+        let label = ShippingLabel {
+            id: new_id(),
+            shipment_id: shipment.id.clone(),
+            package_id: Some(pallet.id.clone()),
+            label_type: LabelType::Pallet,
+            format: LabelFormat::Zpl,
+            data: base64::engine::general_purpose::STANDARD.encode(&zpl_data),
+            content_type: LabelFormat::Zpl.content_type().to_string(),
+            created_at: self.clock.now(),
+        };
+        self.save_label(&label, "pallet", zpl_data.as_bytes())?;
         
-        Ok(format!("SHP-{:08}", count))
+        info!("Generated pallet label {} for {}", pallet_lpn, shipment.shipment_number);
+        Ok(label)
     }
     
-    fn generate_receipt_number(&self) -> Result<String> {
-        let count: i64 = self.db.query_row(
-            "SELECT COUNT(*) + 1 FROM receipts",
-            [],
-            |row| row.get(0),
-        )?.unwrap_or(1);
-        
-        Ok(format!("RCV-{:08}", count))
+    async fn get_pallet(&self, lpn: &str) -> Result<ShipmentPackage> {
+        let pallet = self.get_package_by_lpn(lpn)?;
+        if !pallet.is_pallet() {
+            return Err(WmsError::validation(format!("{} is not a pallet", lpn)));
+        }
+        let shipment = self.get_shipment(&pallet.shipment_id).await?
+            .ok_or_else(|| WmsError::not_found("Shipment not found"))?;
+        Self::require_packing_open(&shipment)?;
+        Ok(pallet)
     }
     
-    fn insert_shipment_item(&self, item: &ShipmentItem) -> Result<()> {
-        self.db.execute(
-            "INSERT INTO shipment_items (
-                id, shipment_id, item_id, location_id, quantity_ordered,
-                quantity_picked, quantity_shipped, lot_number, serial_number, status
-            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
-            params![
-                &item.id,
-                &item.shipment_id,
-                &item.item_id,
-                &item.location_id,
-                item.quantity_ordered,
-                item.quantity_picked,
-                item.quantity_shipped,
-                &item.lot_number,
-                &item.serial_number,
-                "pending",
-            ],
+    fn require_packing_open(shipment: &Shipment) -> Result<()> {
+        if matches!(shipment.status, ShipmentStatus::Shipped | ShipmentStatus::Delivered | ShipmentStatus::Cancelled) {
+            return Err(WmsError::validation(format!(
+                "Shipment {} is {:?}; its packages can no longer change",
+                shipment.shipment_number, shipment.status
+            )));
+        }
+        Ok(())
+    }
+    
+    /// Whether `carton` may go onto a pallet of `shipment`, wherever it is now
+    fn check_palletizable(carton: &ShipmentPackage, shipment: &Shipment) -> Result<()> {
+        let lpn = carton.lpn.as_deref().unwrap_or_default();
+        if carton.is_pallet() {
+            return Err(WmsError::validation(format!("{} is a pallet, not a carton", lpn)));
+        }
+        if carton.shipment_id != shipment.id {
+            return Err(WmsError::validation(format!(
+                "Carton {} belongs to another shipment than {}", lpn, shipment.shipment_number
+            )));
+        }
+        if carton.closed_at.is_none() {
+            return Err(WmsError::validation(format!("Carton {} is still open", lpn)));
+        }
+        if carton.floor_loaded {
+            return Err(WmsError::validation(format!("Carton {} is marked floor-loaded", lpn)));
+        }
+        Ok(())
+    }
+    
+    /// Put `carton` on the pallet, provided nobody moved it since it was read
+    fn nest_carton(conn: &rusqlite::Connection, carton: &ShipmentPackage, pallet_id: &str) -> Result<()> {
+        let rows = conn.execute(
+            "UPDATE shipment_packages SET parent_package_id = ?1
+             WHERE id = ?2 AND parent_package_id IS ?3 AND floor_loaded = 0",
+            params![pallet_id, &carton.id, &carton.parent_package_id],
+        )?;
+        if rows == 0 {
+            return Err(WmsError::conflict(format!(
+                "Carton {} was moved by someone else", carton.lpn.as_deref().unwrap_or_default()
+            )));
+        }
+        Ok(())
+    }
+    
+    /// Recompute a pallet's weight from its cartons, or remove the pallet
+    /// and its labels once no cartons are left on it
+    fn refresh_pallet(conn: &rusqlite::Connection, pallet_id: &str) -> Result<()> {
+        let cartons: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM shipment_packages WHERE parent_package_id = ?",
+            params![pallet_id],
+            |row| row.get(0),
         )?;
+        if cartons == 0 {
+            conn.execute("DELETE FROM shipping_labels WHERE package_id = ?", params![pallet_id])?;
+            conn.execute("DELETE FROM shipment_packages WHERE id = ?", params![pallet_id])?;
+        } else {
+            conn.execute(
+                "UPDATE shipment_packages
+                 SET weight_kg = (SELECT SUM(weight_kg) FROM shipment_packages WHERE parent_package_id = ?1)
+                 WHERE id = ?1",
+                params![pallet_id],
+            )?;
+        }
         Ok(())
     }
     
-    fn insert_receipt_item(&self, item: &ReceiptItem) -> Result<()> {
-        self.db.execute(
-            "INSERT INTO receipt_items (
-                id, receipt_id, item_id, location_id, quantity_expected,
-                quantity_received, quantity_damaged, lot_number, expiry_date, status
-            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
-            params![
-                &item.id,
-                &item.receipt_id,
-                &item.item_id,
-                &item.location_id,
-                item.quantity_expected,
-                item.quantity_received,
-                item.quantity_damaged,
-                &item.lot_number,
-                item.expiry_date.map(|d| d.to_rfc3339()),
-                "pending",
-            ],
+    /// Refuse to ship while closed cartons are neither palletized nor
+    /// marked floor-loaded
+    fn require_packages_loaded(&self, shipment_id: &str) -> Result<()> {
+        let loose: Vec<String> = self.db.query_map(
+            "SELECT COALESCE(lpn, CAST(package_number AS TEXT)) FROM shipment_packages
+             WHERE shipment_id = ? AND closed_at IS NOT NULL AND parent_package_id IS NULL
+               AND floor_loaded = 0 AND COALESCE(package_type, '') != ?
+             ORDER BY package_number",
+            params![shipment_id, ShipmentPackage::PALLET],
+            |row| row.get(0),
         )?;
+        if !loose.is_empty() {
+            return Err(WmsError::validation(format!(
+                "Cartons {} must be palletized or marked floor-loaded before shipping",
+                loose.join(", ")
+            )));
+        }
         Ok(())
     }
     
-    fn get_shipment_items(&self, shipment_id: &str) -> Result<Vec<ShipmentItem>> {
-        self.db.query_map(
-            "SELECT si.*, i.sku, i.name 
+    // ============ Picking Operations ============
+    
+    /// Generate a pick list for a shipment, sequenced to minimize walking.
+    ///
+    /// Lines without a location, or assigned to a lot that has since gone on
+    /// quality hold, are allocated from unheld, unexpired stock (FEFO, then
+    /// FIFO) and may span several locations.
+    pub async fn generate_pick_list(&self, shipment_id: &str) -> Result<PickList> {
+        let shipment = self.get_shipment(shipment_id).await?
+            .ok_or_else(|| WmsError::not_found("Shipment not found"))?;
+        
+        let picks = self.pick_stops(shipment_id).await?;
+        let (mut lines, path) = self.sequence_picks(picks)?;
+        
+        // Photos help pickers confirm they have the right item
+        let item_ids: Vec<String> = lines.iter().map(|l| l.item_id.clone()).collect();
+        let thumbnails = self.attachments.primary_thumbnails(ITEM_ATTACHMENT_OWNER, &item_ids).await?;
+        for line in &mut lines {
+            line.thumbnail = thumbnails.get(&line.item_id).cloned();
+        }
+        
+        debug!(
+            "Generated pick list for {} ({} lines, {:?} strategy)",
+            shipment.shipment_number, lines.len(), path.strategy
+        );
+        
+        Ok(PickList {
+            shipment_id: shipment.id,
+            shipment_number: shipment.shipment_number,
+            lines,
+            estimated_distance_m: path.estimated_distance_m,
+        })
+    }
+    
+    /// Generate one pick list for a wave of shipments, walked in a single
+    /// trip. Lines for the same item, location and lot become one stop whose
+    /// quantity is split between the shipments after picking.
+    pub async fn generate_wave_pick_list(&self, shipment_ids: &[String]) -> Result<WavePickList> {
+        if shipment_ids.is_empty() {
+            return Err(WmsError::validation("A wave needs at least one shipment"));
+        }
+        
+        let mut wave_ids: Vec<String> = Vec::with_capacity(shipment_ids.len());
+        let mut picks: Vec<(WavePickLine, PickStop)> = Vec::new();
+        for shipment_id in shipment_ids {
+            if wave_ids.contains(shipment_id) {
+                continue;
+            }
+            let shipment = self.get_shipment(shipment_id).await?
+                .ok_or_else(|| WmsError::not_found(format!("Shipment {} not found", shipment_id)))?;
+            
+            for (line, stop) in self.pick_stops(shipment_id).await? {
+                let allocation = WaveAllocation {
+                    shipment_id: shipment.id.clone(),
+                    shipment_number: shipment.shipment_number.clone(),
+                    shipment_item_id: line.shipment_item_id,
+                    quantity: line.quantity,
+                };
+                let same_stop = picks.iter_mut().find(|(wave_line, _)| {
+                    wave_line.item_id == line.item_id
+                        && wave_line.location_id == line.location_id
+                        && wave_line.lot_number == line.lot_number
+                });
+                match same_stop {
+                    Some((wave_line, _)) => {
+                        wave_line.quantity += allocation.quantity;
+                        wave_line.allocations.push(allocation);
+                    }
+                    None => picks.push((
+                        WavePickLine {
+                            item_id: line.item_id,
+                            item_sku: line.item_sku,
+                            item_name: line.item_name,
+                            location_id: line.location_id,
+                            location_code: line.location_code,
+                            lot_number: line.lot_number,
+                            quantity: allocation.quantity,
+                            allocations: vec![allocation],
+                            thumbnail: None,
+                        },
+                        stop,
+                    )),
+                }
+            }
+            wave_ids.push(shipment.id);
+        }
+        
+        let (mut lines, path) = self.sequence_picks(picks)?;
+        
+        let item_ids: Vec<String> = lines.iter().map(|l| l.item_id.clone()).collect();
+        let thumbnails = self.attachments.primary_thumbnails(ITEM_ATTACHMENT_OWNER, &item_ids).await?;
+        for line in &mut lines {
+            line.thumbnail = thumbnails.get(&line.item_id).cloned();
+        }
+        
+        debug!(
+            "Generated wave pick list for {} shipments ({} stops, {:?} strategy)",
+            wave_ids.len(), lines.len(), path.strategy
+        );
+        
+        Ok(WavePickList {
+            shipment_ids: wave_ids,
+            lines,
+            estimated_distance_m: path.estimated_distance_m,
+        })
+    }
+    
+    /// Remaining lines of a shipment, each with the stop it is picked from.
+    /// Lines needing allocation are split per allocated location and lot.
+    async fn pick_stops(&self, shipment_id: &str) -> Result<Vec<(PickListLine, PickStop)>> {
+        let rows = self.db.query_map(
+            "SELECT si.id, si.item_id, si.location_id, si.lot_number,
+                    si.quantity_ordered - COALESCE(si.quantity_picked, 0) AS remaining,
+                    i.sku, i.name, l.code, l.aisle, l.x_m, l.y_m, l.aisle_traversal
              FROM shipment_items si
-             LEFT JOIN inventory_items i ON si.item_id = i.id
-             WHERE si.shipment_id = ?",
+             JOIN inventory_items i ON si.item_id = i.id
+             LEFT JOIN locations l ON si.location_id = l.id
+             WHERE si.shipment_id = ?
+               AND si.quantity_ordered > COALESCE(si.quantity_picked, 0)",
             params![shipment_id],
             |row| {
-                Ok(ShipmentItem {
-                    id: row.get("id")?,
-                    shipment_id: row.get("shipment_id")?,
+                let line = PickListLine {
+                    shipment_item_id: row.get("id")?,
                     item_id: row.get("item_id")?,
-                    location_id: row.get("location_id")?,
-                    quantity_ordered: row.get("quantity_ordered")?,
-                    quantity_picked: row.get("quantity_picked")?,
-                    quantity_shipped: row.get("quantity_shipped")?,
-                    lot_number: row.get("lot_number")?,
-                    serial_number: row.get("serial_number")?,
-                    status: ShipmentItemStatus::Pending,
-                    picked_by: row.get("picked_by")?,
-                    picked_at: None,
                     item_sku: row.get("sku")?,
                     item_name: row.get("name")?,
-                })
+                    location_id: row.get("location_id")?,
+                    location_code: row.get("code")?,
+                    lot_number: row.get("lot_number")?,
+                    quantity: row.get("remaining")?,
+                    thumbnail: None,
+                };
+                let stop = PickStop {
+                    id: line.shipment_item_id.clone(),
+                    location_code: line.location_code.clone().unwrap_or_default(),
+                    ..Self::row_to_pick_stop(row)?
+                };
+                Ok((line, stop))
             },
-        )
+        )?;
+        
+        let mut picks = Vec::with_capacity(rows.len());
+        for (line, stop) in rows {
+            let held = match line.lot_number.as_deref() {
+                Some(lot) => self.allocator.active_hold(&line.item_id, lot).await?.is_some(),
+                None => false,
+            };
+            if line.location_id.is_some() && !held {
+                picks.push((line, stop));
+                continue;
+            }
+            
+            let allocations = self.allocator
+                .allocate(&line.item_id, line.quantity, Some(self.clock.now().date_naive()))
+                .await?;
+            if allocations.is_empty() {
+                // Nothing pickable; never send the picker to a held lot
+                if held {
+                    let line = PickListLine { location_id: None, location_code: None, lot_number: None, ..line };
+                    let stop = PickStop { location_code: String::new(), aisle: None, x_m: None, y_m: None, ..stop };
+                    picks.push((line, stop));
+                } else {
+                    picks.push((line, stop));
+                }
+                continue;
+            }
+            
+            for allocation in allocations {
+                let stop = self.db.query_row(
+                    "SELECT code, aisle, x_m, y_m, aisle_traversal FROM locations WHERE id = ?",
+                    params![&allocation.location_id],
+                    Self::row_to_pick_stop,
+                )?.ok_or_else(|| WmsError::not_found("Location not found"))?;
+                
+                picks.push((
+                    PickListLine {
+                        location_id: Some(allocation.location_id),
+                        location_code: Some(allocation.location_code),
+                        lot_number: allocation.lot_number,
+                        quantity: allocation.quantity,
+                        ..line.clone()
+                    },
+                    stop,
+                ));
+            }
+        }
+        
+        Ok(picks)
     }
     
-    fn get_shipment_packages(&self, shipment_id: &str) -> Result<Vec<ShipmentPackage>> {
-        self.db.query_map(
-            "SELECT * FROM shipment_packages WHERE shipment_id = ? ORDER BY package_number",
-            params![shipment_id],
-            |row| {
-                Ok(ShipmentPackage {
-                    id: row.get("id")?,
-                    shipment_id: row.get("shipment_id")?,
-                    package_number: row.get("package_number")?,
-                    tracking_number: row.get("tracking_number")?,
-                    weight_kg: row.get("weight_kg")?,
-                    length_cm: row.get("length_cm")?,
-                    width_cm: row.get("width_cm")?,
-                    height_cm: row.get("height_cm")?,
-                    package_type: row.get("package_type")?,
-                    created_at: Utc::now(),
-                })
-            },
-        )
+    /// Put picks into walking order, returning the path walked
+    fn sequence_picks<T>(&self, picks: Vec<(T, PickStop)>) -> Result<(Vec<T>, PickPath)> {
+        // Back cross-aisle sits at the deepest location in a through aisle
+        let aisle_length: f64 = self.db.query_row(
+            "SELECT MAX(y_m) FROM locations WHERE is_active = 1 AND aisle_traversal = 'through'",
+            [],
+            |row| row.get::<_, Option<f64>>(0),
+        )?.flatten().unwrap_or(0.0);
+        
+        // A line may appear at several stops, so key stops by position
+        let stops: Vec<PickStop> = picks.iter()
+            .enumerate()
+            .map(|(i, (_, stop))| PickStop { id: i.to_string(), ..stop.clone() })
+            .collect();
+        let path = PickPathOptimizer::new()
+            .with_aisle_length(aisle_length)
+            .optimize(&stops);
+        
+        let mut ordered: Vec<(usize, T)> = picks.into_iter()
+            .map(|(line, _)| line)
+            .enumerate()
+            .collect();
+        ordered.sort_by_key(|(i, _)| {
+            path.stop_order.iter()
+                .position(|id| *id == i.to_string())
+                .unwrap_or(usize::MAX)
+        });
+        
+        Ok((ordered.into_iter().map(|(_, line)| line).collect(), path))
     }
     
-    fn get_receipt_items(&self, receipt_id: &str) -> Result<Vec<ReceiptItem>> {
+    /// Convert a location row to an unnamed pick stop
+    fn row_to_pick_stop(row: &rusqlite::Row) -> rusqlite::Result<PickStop> {
+        let traversal: Option<String> = row.get("aisle_traversal")?;
+        Ok(PickStop {
+            id: String::new(),
+            location_code: row.get::<_, Option<String>>("code")?.unwrap_or_default(),
+            aisle: row.get("aisle")?,
+            x_m: row.get("x_m")?,
+            y_m: row.get("y_m")?,
+            traversal: traversal.as_deref().and_then(AisleTraversal::parse).unwrap_or_default(),
+        })
+    }
+    
+    // ============ Receipt Operations ============
+    
+    /// Create a new receipt
+    pub async fn create_receipt(&self, mut receipt: Receipt) -> Result<Receipt> {
+        receipt.id = new_id();
+        receipt.status = ReceiptStatus::Pending;
+        receipt.created_at = self.clock.now();
+        
+        self.db.transaction(|conn| {
+            receipt.receipt_number = DocumentSequence::Receipt.next_number_in(conn)?;
+            conn.execute(
+                "INSERT INTO receipts (
+                    id, receipt_number, status, po_number, supplier_name,
+                    supplier_reference, expected_date, dock_door, notes,
+                    blind_count, created_by, created_at
+                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                params![
+                    &receipt.id,
+                    &receipt.receipt_number,
+                    "pending",
+                    &receipt.po_number,
+                    &receipt.supplier_name,
+                    &receipt.supplier_reference,
+                    receipt.expected_date.map(|d| d.to_rfc3339()),
+                    &receipt.dock_door,
+                    &receipt.notes,
+                    receipt.blind_count,
+                    &receipt.created_by,
+                    receipt.created_at.to_rfc3339(),
+                ],
+            )?;
+            
+            for item in &mut receipt.items {
+                item.id = new_id();
+                item.receipt_id = receipt.id.clone();
+                Self::insert_receipt_item(conn, item)?;
+            }
+            Ok(())
+        })?;
+        
+        info!("Created receipt: {}", receipt.receipt_number);
+        Ok(receipt)
+    }
+    
+    /// Process a receipt item (scan and receive). The line's status comes
+    /// from its stored expected quantity, which blind counts don't send.
+    ///
+    /// Receiving more than expected needs `allow_over_receipt` and is
+    /// recorded as a discrepancy on the line. Past the over-receipt
+    /// tolerance a supervisor's `override_token` for the line is needed as
+    /// well, and is used up. Blind counts skip both checks.
+    pub async fn process_receipt_item(
+        &self,
+        receipt_id: &str,
+        mut item: ReceiptItem,
+        allow_over_receipt: bool,
+        override_token: Option<&OverrideToken>,
+    ) -> Result<Receipt> {
+        let (item_id, expected, blind_count, status): (String, f64, bool, String) = self.db.query_row(
+            "SELECT ri.item_id, ri.quantity_expected, r.blind_count, r.status
+             FROM receipt_items ri JOIN receipts r ON r.id = ri.receipt_id
+             WHERE ri.id = ? AND ri.receipt_id = ?",
+            params![&item.id, receipt_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )?.ok_or_else(|| WmsError::not_found("Receipt item not found"))?;
+        item.item_id = item_id;
+        if status == "completed" {
+            return Err(WmsError::validation("Receipt is already completed"));
+        }
+        
+        if item.quantity_damaged < 0.0 || item.quantity_damaged > item.quantity_received {
+            return Err(WmsError::validation("Damaged quantity must be between zero and the quantity received"));
+        }
+        
+        let blind = self.blind_receiving()?.applies(blind_count);
+        if item.quantity_received > expected && !blind && !allow_over_receipt {
+            return Err(WmsError::validation(format!(
+                "Receiving {} is more than the {} expected; confirm the over-receipt to continue",
+                item.quantity_received, expected,
+            )));
+        }
+        
+        let over_receipt = OverReceiptSettings::load(&self.db)?;
+        let override_token = if over_receipt.exceeded(expected, item.quantity_received) && !blind {
+            let Some(token) = override_token else {
+                return Err(WmsError::validation(format!(
+                    "Receiving {} is over the limit of {}; a supervisor override is needed",
+                    item.quantity_received,
+                    over_receipt.limit(expected).unwrap_or(expected),
+                )));
+            };
+            Some(token)
+        } else {
+            None
+        };
+        
+        item.status = if item.quantity_received >= expected {
+            ReceiptItemStatus::Complete
+        } else if item.quantity_received > 0.0 {
+            ReceiptItemStatus::Partial
+        } else {
+            ReceiptItemStatus::Pending
+        };
+        
+        item.received_at = Some(self.clock.now());
+        
+        // Only while the receipt is open, so a completed receipt's stock
+        // can't be posted again; the override is used up with the count
+        self.db.transaction(|conn| {
+            let open = conn.execute(
+                "UPDATE receipts SET status = 'receiving', received_date = COALESCE(received_date, datetime('now'))
+                 WHERE id = ? AND status != 'completed'",
+                params![receipt_id],
+            )?;
+            if open == 0 {
+                return Err(WmsError::validation("Receipt is already completed"));
+            }
+            if let Some(token) = override_token {
+                self.overrides.consume_in(conn, token, OverrideAction::OverReceipt, RECEIPT_LINE_OWNER, &item.id)?;
+            }
+            
+            conn.execute(
+                "UPDATE receipt_items SET
+                    quantity_received = ?, quantity_damaged = ?, lot_number = ?,
+                    expiry_date = ?, status = ?, received_by = ?, received_at = ?, notes = ?
+                 WHERE id = ?",
+                params![
+                    item.quantity_received,
+                    item.quantity_damaged,
+                    &item.lot_number,
+                    item.expiry_date.map(|d| d.to_rfc3339()),
+                    format!("{:?}", item.status).to_lowercase(),
+                    &item.received_by,
+                    item.received_at.map(|d| d.to_rfc3339()),
+                    &item.notes,
+                    &item.id,
+                ],
+            )?;
+            
+            self.record_over_receipt(conn, receipt_id, &item, expected)
+        })?;
+        
+        debug!("Processed receipt item: {} received {}", item.id, item.quantity_received);
+        
+        // Return updated receipt
+        self.get_receipt(receipt_id).await?
+            .ok_or_else(|| WmsError::not_found("Receipt not found"))
+    }
+    
+    /// Process a receipt item from a scanned GS1-128 carton label. The
+    /// label's lot, expiry and count fill whatever `item` leaves empty, then
+    /// the line goes through `process_receipt_item`. A GTIN on the label
+    /// must belong to the line's item.
+    pub async fn process_receipt_gs1(
+        &self,
+        receipt_id: &str,
+        mut item: ReceiptItem,
+        scan: &str,
+        allow_over_receipt: bool,
+        override_token: Option<&OverrideToken>,
+    ) -> Result<Receipt> {
+        let gs1 = Gs1Parser::parse(scan)?;
+        if let Some(gtin) = gs1.gtin.as_deref() {
+            let line_item: Option<String> = self.db.query_row(
+                "SELECT item_id FROM receipt_items WHERE id = ? AND receipt_id = ?",
+                params![&item.id, receipt_id],
+                |row| row.get(0),
+            )?;
+            let scanned = self.resolve_asn_item(Some(gtin), None)?;
+            if scanned.is_some() && scanned != line_item {
+                return Err(WmsError::validation(format!("GTIN {} is not the item on this receipt line", gtin)));
+            }
+        }
+        
+        item.prefill_from_gs1(&gs1);
+        self.process_receipt_item(receipt_id, item, allow_over_receipt, override_token).await
+    }
+    
+    /// Complete a receipt and update inventory. Received units not yet put
+    /// away are posted to stock at the line's location, or at a suggested
+    /// storage location when it has none; damaged units go to quarantine.
+    ///
+    /// Blind counts come back with a variance report. When a line is off by
+    /// more than the tolerance and no supervisor has acknowledged the
+    /// variances yet, the receipt is left open instead.
+    pub async fn complete_receipt(&self, ctx: &RequestContext, receipt_id: &str) -> Result<ReceiptCompletion> {
+        let receipt = self.get_receipt(receipt_id).await?
+            .ok_or_else(|| WmsError::not_found("Receipt not found"))?;
+        if receipt.status == ReceiptStatus::Completed {
+            return Err(WmsError::validation("Receipt is already completed"));
+        }
+        
+        // Verify all items are received
+        for item in &receipt.items {
+            if item.status == ReceiptItemStatus::Pending {
+                return Err(WmsError::validation("Not all items have been received"));
+            }
+        }
+        
+        let settings = self.blind_receiving()?;
+        let variances = settings.applies_to(&receipt)
+            .then(|| VarianceReport::build(&receipt, settings.tolerance_percent));
+        
+        if let Some(report) = variances.as_ref().filter(|r| r.awaiting_acknowledgment()) {
+            info!(
+                "Receipt {} has {} blind count variances awaiting acknowledgment",
+                receipt.receipt_number, report.lines.len()
+            );
+            return Ok(self.receipt_completion(ctx, receipt, variances));
+        }
+        
+        let postings = self.received_stock(ctx, &receipt).await?;
+        for adjustment in &postings {
+            self.inventory.check_adjustment(adjustment).await?;
+        }
+        
+        // The stock goes in with the status change, and only while the
+        // receipt is still open, so a retry or a second client completing
+        // it at the same time can't post it twice
+        let completed = self.db.transaction(|conn| {
+            let rows = conn.execute(
+                "UPDATE receipts SET status = 'completed', completed_at = datetime('now'), completed_by = ?
+                 WHERE id = ? AND status != 'completed'",
+                params![&ctx.user_id, receipt_id],
+            )?;
+            if rows == 0 {
+                return Ok(false);
+            }
+            for adjustment in &postings {
+                self.inventory.post_adjustment(conn, adjustment)?;
+            }
+            Ok(true)
+        })?;
+        if !completed {
+            return Err(WmsError::validation("Receipt is already completed"));
+        }
+        for adjustment in &postings {
+            self.inventory.after_adjustment(adjustment).await?;
+        }
+        
+        info!("Completed receipt: {}", receipt.receipt_number);
+        let receipt = self.get_receipt(receipt_id).await?
+            .ok_or_else(|| WmsError::not_found("Receipt not found"))?;
+        Ok(self.receipt_completion(ctx, receipt, variances))
+    }
+    
+    /// What each line left on the dock, to be posted: good units into the
+    /// line's location or a suggested one, damaged units into quarantine.
+    /// Every location is settled before anything is posted.
+    async fn received_stock(&self, ctx: &RequestContext, receipt: &Receipt) -> Result<Vec<InventoryAdjustment>> {
+        let user_id = ctx.actor()?;
+        let quarantine: Option<String> = self.db.query_row(
+            "SELECT id FROM locations WHERE zone = ? AND is_active = 1 ORDER BY code LIMIT 1",
+            params![LocationZone::Quarantine.as_str()],
+            |row| row.get(0),
+        )?;
+        
+        let mut postings = Vec::new();
+        for line in &receipt.items {
+            let label = line.item_sku.as_deref().unwrap_or(&line.item_id);
+            let damaged = line.quantity_damaged.min(line.quantity_received).max(0.0);
+            let good = line.quantity_received - damaged - line.quantity_put_away;
+            
+            if good > 0.0 {
+                let location_id = match &line.location_id {
+                    Some(location_id) => location_id.clone(),
+                    None => self.inventory.suggest_putaway_location(&line.item_id, good).await?
+                        .map(|location| location.id)
+                        .ok_or_else(|| WmsError::validation(format!(
+                            "No storage location has room for {} of {}", good, label
+                        )))?,
+                };
+                postings.push((line, location_id, good, "RECEIPT"));
+            }
+            if damaged > 0.0 {
+                let location_id = quarantine.clone().ok_or_else(|| WmsError::validation(format!(
+                    "No active quarantine location for the {} damaged {}", damaged, label
+                )))?;
+                postings.push((line, location_id, damaged, "DAMAGED"));
+            }
+        }
+        
+        Ok(postings.into_iter()
+            .map(|(line, location_id, quantity, reason)| InventoryAdjustment {
+                item_id: line.item_id.clone(),
+                location_id: Some(location_id),
+                warehouse_id: None,
+                adjustment_type: AdjustmentType::Receive,
+                quantity,
+                lot_number: line.lot_number.clone(),
+                reason_code: Some(reason.to_string()),
+                notes: Some(format!("Receipt {}", receipt.receipt_number)),
+                user_id: user_id.to_string(),
+            })
+            .collect())
+    }
+    
+    /// Keep the line's over-receipt discrepancy in step with its count
+    fn record_over_receipt(
+        &self,
+        conn: &rusqlite::Connection,
+        receipt_id: &str,
+        item: &ReceiptItem,
+        expected: f64,
+    ) -> Result<()> {
+        let discrepancy_type = DiscrepancyType::OverReceipt.as_str();
+        if item.quantity_received > expected {
+            conn.execute(
+                "INSERT INTO receipt_discrepancies (
+                    id, receipt_id, receipt_item_id, item_id, discrepancy_type,
+                    quantity_expected, quantity_received, recorded_by, recorded_at
+                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+                ON CONFLICT (receipt_item_id, discrepancy_type) DO UPDATE SET
+                    quantity_expected = excluded.quantity_expected,
+                    quantity_received = excluded.quantity_received,
+                    recorded_by = excluded.recorded_by,
+                    recorded_at = excluded.recorded_at",
+                params![
+                    new_id(),
+                    receipt_id,
+                    &item.id,
+                    &item.item_id,
+                    discrepancy_type,
+                    expected,
+                    item.quantity_received,
+                    &item.received_by,
+                    self.clock.now().to_rfc3339(),
+                ],
+            )?;
+        } else {
+            conn.execute(
+                "DELETE FROM receipt_discrepancies WHERE receipt_item_id = ? AND discrepancy_type = ?",
+                params![&item.id, discrepancy_type],
+            )?;
+        }
+        Ok(())
+    }
+    
+    /// Discrepancies recorded against a receipt's lines
+    pub async fn get_receipt_discrepancies(&self, receipt_id: &str) -> Result<Vec<ReceiptDiscrepancy>> {
         self.db.query_map(
-            "SELECT ri.*, i.sku, i.name
-             FROM receipt_items ri
-             LEFT JOIN inventory_items i ON ri.item_id = i.id
-             WHERE ri.receipt_id = ?",
+            "SELECT * FROM receipt_discrepancies WHERE receipt_id = ? ORDER BY recorded_at",
             params![receipt_id],
             |row| {
-                Ok(ReceiptItem {
+                Ok(ReceiptDiscrepancy {
                     id: row.get("id")?,
                     receipt_id: row.get("receipt_id")?,
+                    receipt_item_id: row.get("receipt_item_id")?,
                     item_id: row.get("item_id")?,
-                    location_id: row.get("location_id")?,
+                    discrepancy_type: stored_status(row, "discrepancy_type", DiscrepancyType::parse)?,
                     quantity_expected: row.get("quantity_expected")?,
                     quantity_received: row.get("quantity_received")?,
-                    quantity_damaged: row.get("quantity_damaged")?,
-                    lot_number: row.get("lot_number")?,
-                    expiry_date: None,
-                    status: ReceiptItemStatus::Pending,
-                    received_by: row.get("received_by")?,
-                    received_at: None,
-                    notes: row.get("notes")?,
-                    item_sku: row.get("sku")?,
-                    item_name: row.get("name")?,
+                    recorded_by: row.get("recorded_by")?,
+                    recorded_at: required_timestamp(row, "recorded_at")?,
                 })
             },
         )
     }
     
-    fn row_to_shipment(row: &rusqlite::Row) -> rusqlite::Result<Shipment> {
-        Ok(Shipment {
-            id: row.get("id")?,
-            shipment_number: row.get("shipment_number")?,
-            status: ShipmentStatus::Draft,
-            order_reference: row.get("order_reference")?,
-            customer_id: row.get("customer_id")?,
-            carrier_id: row.get("carrier_id")?,
-            service_type: row.get("service_type")?,
-            tracking_number: row.get("tracking_number")?,
-            ship_date: None,
-            expected_delivery_date: None,
-            actual_delivery_date: None,
-            ship_to: ShipToAddress {
-                name: row.get("ship_to_name")?,
-                address: wms_core::types::Address {
-                    line1: row.get("ship_to_address_line1")?,
-                    line2: row.get("ship_to_address_line2")?,
-                    city: row.get("ship_to_city")?,
-                    state: row.get("ship_to_state")?,
-                    postal_code: row.get("ship_to_postal_code")?,
-                    country: row.get("ship_to_country")?,
-                },
-                phone: row.get("ship_to_phone")?,
-                email: row.get("ship_to_email")?,
-            },
-            total_weight_kg: row.get("total_weight_kg")?,
-            total_packages: row.get::<_, u32>("total_packages").unwrap_or(1),
-            shipping_cost: row.get("shipping_cost")?,
-            insurance_value: row.get("insurance_value")?,
-            special_instructions: row.get("special_instructions")?,
-            label_printed: row.get::<_, i32>("label_printed")? == 1,
-            created_by: row.get("created_by")?,
-            created_at: Utc::now(),
-            updated_at: None,
-            items: Vec::new(),
-            packages: Vec::new(),
-        })
+    /// Accept a blind count's variances as the supervisor in `ctx` and
+    /// complete the receipt
+    pub async fn acknowledge_receipt_variances(&self, ctx: &RequestContext, receipt_id: &str) -> Result<ReceiptCompletion> {
+        ctx.require(Scope::ReceivingVariances)?;
+        let user_id = ctx.actor()?;
+        
+        let updated = self.db.execute(
+            "UPDATE receipts SET variance_acknowledged_by = ?, variance_acknowledged_at = ?
+             WHERE id = ? AND status != 'completed'",
+            params![user_id, self.clock.now().to_rfc3339(), receipt_id],
+        )?;
+        if updated == 0 {
+            return Err(WmsError::not_found("Open receipt not found"));
+        }
+        
+        info!("Receipt variances acknowledged by {}: {}", user_id, receipt_id);
+        self.complete_receipt(ctx, receipt_id).await
     }
     
-    fn row_to_receipt(row: &rusqlite::Row) -> rusqlite::Result<Receipt> {
-        Ok(Receipt {
-            id: row.get("id")?,
-            receipt_number: row.get("receipt_number")?,
+    /// A receipt as `ctx` may see it
+    pub async fn get_receipt_for(&self, ctx: &RequestContext, id: &str) -> Result<Receipt> {
+        let receipt = self.get_receipt(id).await?
+            .ok_or_else(|| WmsError::not_found("Receipt not found"))?;
+        Ok(self.redact_receipt(ctx, receipt))
+    }
+    
+    /// Leave expected quantities out of a blind-counted receipt unless
+    /// `ctx` may see them
+    pub fn redact_receipt(&self, ctx: &RequestContext, mut receipt: Receipt) -> Receipt {
+        if !self.hides_expected_quantities(ctx, &receipt) {
+            return receipt;
+        }
+        for item in &mut receipt.items {
+            item.quantity_expected = None;
+        }
+        receipt
+    }
+    
+    /// Blind receiving configuration
+    pub fn blind_receiving(&self) -> Result<BlindReceivingSettings> {
+        BlindReceivingSettings::load(&self.db)
+    }
+    
+    /// Whether `ctx` counts `receipt` blind. Unreadable settings fail
+    /// closed.
+    fn hides_expected_quantities(&self, ctx: &RequestContext, receipt: &Receipt) -> bool {
+        if ctx.require(Scope::ReceivingVariances).is_ok() {
+            return false;
+        }
+        self.blind_receiving().map_or(true, |settings| settings.applies_to(receipt))
+    }
+    
+    fn receipt_completion(
+        &self,
+        ctx: &RequestContext,
+        receipt: Receipt,
+        mut variances: Option<VarianceReport>,
+    ) -> ReceiptCompletion {
+        if self.hides_expected_quantities(ctx, &receipt)
+            && let Some(report) = &mut variances
+        {
+            report.hide_expected_quantities();
+        }
+        ReceiptCompletion { receipt: self.redact_receipt(ctx, receipt), variances }
+    }
+    
+    /// Import an advance ship notice as a pending receipt.
+    ///
+    /// Lines are matched to items by GTIN (item barcode) or SKU; lines that
+    /// don't match are reported back for mapping. Re-importing an ASN
+    /// replaces the lines of its pending receipt. With `preview` set the
+    /// file is only parsed and resolved.
+    pub async fn import_asn(
+        &self,
+        ctx: &RequestContext,
+        data: &[u8],
+        format: AsnFormat,
+        preview: bool,
+    ) -> Result<AsnImport> {
+        let created_by = ctx.actor()?;
+        let asn = parse_asn(data, format)?;
+        
+        let existing = self.db.query_row(
+            "SELECT id, receipt_number, status FROM receipts
+             WHERE supplier_reference = ? AND COALESCE(supplier_name, '') = COALESCE(?, '')
+             ORDER BY created_at DESC LIMIT 1",
+            params![&asn.supplier_reference, &asn.supplier_name],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?)),
+        )?;
+        
+        if let Some((_, number, status)) = &existing
+            && status != "pending"
+        {
+            return Err(WmsError::conflict(format!(
+                "ASN {} was already received on {} ({})",
+                asn.supplier_reference, number, status
+            )));
+        }
+        
+        let (id, receipt_number) = match &existing {
+            Some((id, number, _)) => (id.clone(), number.clone()),
+            None if preview => (String::new(), String::new()),
+            None => (new_id(), self.generate_receipt_number()?),
+        };
+        
+        let mut items = Vec::new();
+        let mut unresolved = Vec::new();
+        for line in &asn.lines {
+            let item_id = self.resolve_asn_item(line.gtin.as_deref(), line.sku.as_deref())?;
+            let Some(item_id) = item_id else {
+                unresolved.push(UnresolvedAsnLine::from(line));
+                continue;
+            };
+            
+            items.push(ReceiptItem {
+                id: if preview { String::new() } else { new_id() },
+                receipt_id: id.clone(),
+                item_id,
+                location_id: None,
+                quantity_expected: Some(line.quantity),
+                quantity_received: 0.0,
+                quantity_damaged: 0.0,
+                quantity_put_away: 0.0,
+                lot_number: line.lot_number.clone(),
+                expiry_date: line.expiry_date.and_then(|d| d.and_hms_opt(0, 0, 0)).map(|d| d.and_utc()),
+                status: ReceiptItemStatus::Pending,
+                received_by: None,
+                received_at: None,
+                notes: None,
+                item_sku: None,
+                item_name: None,
+                evidence_count: 0,
+                cold_chain: false,
+            });
+        }
+        
+        let mut receipt = Receipt {
+            id,
+            receipt_number,
             status: ReceiptStatus::Pending,
-            po_number: row.get("po_number")?,
-            supplier_name: row.get("supplier_name")?,
-            supplier_reference: row.get("supplier_reference")?,
-            expected_date: None,
+            po_number: asn.po_number.clone(),
+            supplier_name: asn.supplier_name.clone(),
+            supplier_reference: Some(asn.supplier_reference.clone()),
+            expected_date: asn.expected_date.and_then(|d| d.and_hms_opt(0, 0, 0)).map(|d| d.and_utc()),
             received_date: None,
-            dock_door: row.get("dock_door")?,
-            notes: row.get("notes")?,
-            created_by: row.get("created_by")?,
-            created_at: Utc::now(),
+            dock_door: None,
+            notes: None,
+            blind_count: false,
+            variance_acknowledged_by: None,
+            variance_acknowledged_at: None,
+            created_by: created_by.to_string(),
+            created_at: self.clock.now(),
             completed_at: None,
-            completed_by: row.get("completed_by")?,
-            items: Vec::new(),
+            completed_by: None,
+            items,
+        };
+        
+        if !preview {
+            self.save_asn_receipt(&receipt, existing.is_some())?;
+            receipt = self.get_receipt(&receipt.id).await?
+                .ok_or_else(|| WmsError::not_found("Receipt not found"))?;
+            info!(
+                "Imported ASN {} into receipt {} ({} unresolved line(s))",
+                asn.supplier_reference, receipt.receipt_number, unresolved.len()
+            );
+        }
+        
+        Ok(AsnImport {
+            receipt,
+            updated_existing: existing.is_some(),
+            preview,
+            unresolved,
         })
     }
-}
-
+    
+    /// Create a pending receipt from the JSON payload of a trailer manifest
+    /// QR code (schema on `AsnFormat::QrManifest`). Lines are matched to
+    /// items by GTIN; those that match nothing come back as unresolved.
+    pub async fn create_receipt_from_manifest(&self, ctx: &RequestContext, qr_payload: &str) -> Result<AsnImport> {
+        self.import_asn(ctx, qr_payload.trim().as_bytes(), AsnFormat::QrManifest, false).await
+    }
+    
+    /// Match an ASN line to an item by GTIN, then SKU
+    fn resolve_asn_item(&self, gtin: Option<&str>, sku: Option<&str>) -> Result<Option<String>> {
+        // GTIN-14, EAN-13 and UPC-A differ only by leading zeros
+        if let Some(gtin) = gtin {
+            let found = self.db.query_row(
+                "SELECT id FROM inventory_items WHERE ltrim(barcode, '0') = ltrim(?, '0') LIMIT 1",
+                params![gtin],
+                |row| row.get(0),
+            )?;
+            if found.is_some() {
+                return Ok(found);
+            }
+        }
+        
+        if let Some(sku) = sku {
+            return self.db.query_row(
+                "SELECT id FROM inventory_items WHERE sku = ?1 OR barcode = ?1
+                 ORDER BY sku = ?1 DESC LIMIT 1",
+                params![sku],
+                |row| row.get(0),
+            );
+        }
+        
+        Ok(None)
+    }
+    
+    /// Insert or replace the receipt built from an ASN
+    fn save_asn_receipt(&self, receipt: &Receipt, replace: bool) -> Result<()> {
+        self.db.transaction(|conn| {
+            if replace {
+                conn.execute(
+                    "UPDATE receipts SET po_number = ?, supplier_name = ?, expected_date = ?
+                     WHERE id = ?",
+                    params![
+                        &receipt.po_number,
+                        &receipt.supplier_name,
+                        receipt.expected_date.map(|d| d.to_rfc3339()),
+                        &receipt.id,
+                    ],
+                )?;
+                conn.execute("DELETE FROM receipt_items WHERE receipt_id = ?", params![&receipt.id])?;
+            } else {
+                conn.execute(
+                    "INSERT INTO receipts (
+                        id, receipt_number, status, po_number, supplier_name,
+                        supplier_reference, expected_date, created_by, created_at
+                    ) VALUES (?, ?, 'pending', ?, ?, ?, ?, ?, ?)",
+                    params![
+                        &receipt.id,
+                        &receipt.receipt_number,
+                        &receipt.po_number,
+                        &receipt.supplier_name,
+                        &receipt.supplier_reference,
+                        receipt.expected_date.map(|d| d.to_rfc3339()),
+                        &receipt.created_by,
+                        receipt.created_at.to_rfc3339(),
+                    ],
+                )?;
+            }
+            
+            for item in &receipt.items {
+                conn.execute(
+                    "INSERT INTO receipt_items (
+                        id, receipt_id, item_id, quantity_expected, quantity_received,
+                        quantity_damaged, lot_number, expiry_date, status
+                    ) VALUES (?, ?, ?, ?, 0, 0, ?, ?, 'pending')",
+                    params![
+                        &item.id,
+                        &item.receipt_id,
+                        &item.item_id,
+                        item.quantity_expected.unwrap_or_default(),
+                        &item.lot_number,
+                        item.expiry_date.map(|d| d.to_rfc3339()),
+                    ],
+                )?;
+            }
+            
+            Ok(())
+        })
+    }
+    
+    /// Get receipt by ID, with expected quantities whatever the blind
+    /// count settings; use `get_receipt_for` to show it to a user
+    pub async fn get_receipt(&self, id: &str) -> Result<Option<Receipt>> {
+        let receipt = self.db.query_row(
+            "SELECT * FROM receipts WHERE id = ?",
+            params![id],
+            |row| Self::row_to_receipt(row),
+        )?;
+        
+        if let Some(mut r) = receipt {
+            r.items = self.get_receipt_items(&r.id)?;
+            Ok(Some(r))
+        } else {
+            Ok(None)
+        }
+    }
+    
+    /// One page of receipts matching `filter`, by expected date, latest
+    /// first, with how many match in all. Lines are not loaded, so blind
+    /// counts give nothing away.
+    pub async fn list_receipts(&self, filter: ReceiptFilter) -> Result<ReceiptPage> {
+        let page = filter.page.max(1);
+        let page_size = filter.page_size.clamp(1, MAX_RECEIPT_PAGE_SIZE);
+        let mut conditions: Vec<&str> = Vec::new();
+        let mut params_vec: Vec<String> = Vec::new();
+        
+        if let Some(status) = filter.status {
+            conditions.push("r.status = ?");
+            params_vec.push(format!("{:?}", status).to_lowercase());
+        }
+        if let Some(supplier) = filter.supplier.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+            conditions.push("r.supplier_name LIKE '%' || ? || '%'");
+            params_vec.push(supplier.to_string());
+        }
+        // date() turns stored offsets into UTC before comparing
+        if let Some(from) = filter.expected_from {
+            conditions.push("date(r.expected_date) >= ?");
+            params_vec.push(from.to_string());
+        }
+        if let Some(to) = filter.expected_to {
+            conditions.push("date(r.expected_date) <= ?");
+            params_vec.push(to.to_string());
+        }
+        
+        let where_clause = if conditions.is_empty() {
+            String::new()
+        } else {
+            format!(" WHERE {}", conditions.join(" AND "))
+        };
+        
+        let total_count: i64 = self.db.query_row(
+            &format!("SELECT COUNT(*) FROM receipts r{}", where_clause),
+            rusqlite::params_from_iter(params_vec.iter()),
+            |row| row.get(0),
+        )?.unwrap_or(0);
+        
+        let receipts = self.db.query_map(
+            &format!(
+                "SELECT r.* FROM receipts r{}
+                 ORDER BY julianday(COALESCE(r.expected_date, r.created_at)) DESC, r.receipt_number DESC
+                 LIMIT {} OFFSET {}",
+                where_clause, page_size, (page - 1) * page_size
+            ),
+            rusqlite::params_from_iter(params_vec.iter()),
+            |row| Self::row_to_receipt(row),
+        )?;
+        
+        Ok(ReceiptPage::new(receipts, total_count as u64, page, page_size))
+    }
+    
+    /// Receipts expected or completed today (UTC), in progress, and overdue
+    pub async fn get_receiving_summary(&self) -> Result<ReceivingSummary> {
+        let now = self.clock.now();
+        let summary = self.db.query_row(
+            "SELECT
+                COALESCE(SUM(status = 'pending' AND date(expected_date) = ?1), 0),
+                COALESCE(SUM(status = 'receiving'), 0),
+                COALESCE(SUM(status = 'completed' AND date(completed_at) = ?1), 0),
+                COALESCE(SUM(status IN ('pending', 'receiving') AND julianday(expected_date) < julianday(?2)), 0)
+             FROM receipts",
+            params![now.date_naive().to_string(), now.to_rfc3339()],
+            |row| Ok(ReceivingSummary {
+                pending: row.get::<_, i64>(0)? as u64,
+                receiving: row.get::<_, i64>(1)? as u64,
+                completed: row.get::<_, i64>(2)? as u64,
+                overdue: row.get::<_, i64>(3)? as u64,
+            }),
+        )?;
+        
+        Ok(summary.unwrap_or_default())
+    }
+    
+    // ============ Scan Operations ============
+    
+    /// What a barcode scanned against a receipt refers to: the item with
+    /// that barcode (or the GTIN of a GS1-128 label), else that SKU, and
+    /// the receipt line for it with what is left to receive and where to
+    /// store it. An item the receipt has no line for comes back as
+    /// unexpected, so a line can be added. Remaining quantities are left
+    /// out where `ctx` counts the receipt blind.
+    pub async fn resolve_scan(&self, ctx: &RequestContext, receipt_id: &str, barcode: &str) -> Result<ReceiptScanMatch> {
+        let receipt = self.get_receipt(receipt_id).await?
+            .ok_or_else(|| WmsError::not_found("Receipt not found"))?;
+        let gtin = Gs1Parser::parse(barcode).ok().and_then(|gs1| gs1.gtin);
+        let item_id = self.resolve_asn_item(Some(gtin.as_deref().unwrap_or(barcode)), Some(barcode))?
+            .ok_or_else(|| WmsError::not_found(format!("No item with barcode {}", barcode)))?;
+        let item = self.inventory.get_item_by_id(&item_id).await?
+            .ok_or_else(|| WmsError::not_found("Item not found"))?;
+        
+        let Some(mut line) = Self::open_receipt_line(&receipt, &item_id) else {
+            return Ok(ReceiptScanMatch::UnexpectedItem { item: Box::new(item) });
+        };
+        if self.hides_expected_quantities(ctx, &receipt) {
+            line.quantity_expected = None;
+        }
+        let remaining_quantity = line.quantity_expected
+            .map(|expected| (expected - line.quantity_received).max(0.0));
+        let suggested_location = self.inventory
+            .suggest_putaway_location(&item_id, remaining_quantity.filter(|q| *q > 0.0).unwrap_or(1.0))
+            .await?;
+        
+        Ok(ReceiptScanMatch::OnReceipt {
+            item: Box::new(item),
+            line: Box::new(line),
+            remaining_quantity,
+            suggested_location: suggested_location.map(Box::new),
+        })
+    }
+    
+    /// Scan an item into a receipt, adding `quantity` to its line.
+    /// A repeat of the same barcode within the duplicate window is reported
+    /// as a duplicate unless `allow_duplicate` is set (e.g. two identical
+    /// cartons). Scans past the expected quantity need `allow_over_receipt`,
+    /// and past the over-receipt tolerance an `override_token`, as in
+    /// `process_receipt_item`.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn scan_receipt_item(
+        &self,
+        ctx: &RequestContext,
+        receipt_id: &str,
+        barcode: &str,
+        quantity: f64,
+        allow_duplicate: bool,
+        allow_over_receipt: bool,
+        override_token: Option<&OverrideToken>,
+    ) -> Result<ScanResult<Receipt>> {
+        let user_id = ctx.actor()?;
+        let receipt = self.get_receipt(receipt_id).await?
+            .ok_or_else(|| WmsError::not_found("Receipt not found"))?;
+        let item_id = self.resolve_scanned_item(barcode, quantity)?;
+        let mut line = Self::scanned_receipt_line(&receipt, &item_id, barcode)?;
+        
+        let scan = match self.record_scan(
+            item_scan(ScanContext::Receipt, receipt_id, barcode, &item_id, quantity, user_id, self.clock.now()),
+            allow_duplicate,
+        )? {
+            Ok(scan) => scan,
+            Err(duplicate) => return Ok(duplicate),
+        };
+        
+        line.quantity_received += quantity;
+        line.received_by = Some(user_id.to_string());
+        let result = self.process_receipt_item(receipt_id, line, allow_over_receipt, override_token).await;
+        self.finish_scan(scan, result)
+    }
+    
+    /// Scan an item off a shipment line at its pick location. Posts a pick
+    /// adjustment and advances the line's picked quantity.
+    pub async fn scan_pick_item(
+        &self,
+        ctx: &RequestContext,
+        shipment_id: &str,
+        barcode: &str,
+        quantity: f64,
+        allow_duplicate: bool,
+    ) -> Result<ScanResult<ShipmentItem>> {
+        let user_id = ctx.actor()?;
+        let item_id = self.resolve_scanned_item(barcode, quantity)?;
+        let line = self.get_shipment_items(shipment_id)?
+            .into_iter()
+            .find(|l| l.item_id == item_id && l.quantity_picked < l.quantity_ordered)
+            .ok_or_else(|| WmsError::validation(format!("{} is not on this shipment or already picked", barcode)))?;
+        if line.quantity_picked + quantity > line.quantity_ordered {
+            return Err(WmsError::validation(format!(
+                "Only {} of {} left to pick", line.quantity_ordered - line.quantity_picked, barcode
+            )));
+        }
+        
+        let scan = match self.record_scan(
+            item_scan(ScanContext::Pick, shipment_id, barcode, &item_id, quantity, user_id, self.clock.now()),
+            allow_duplicate,
+        )? {
+            Ok(scan) => scan,
+            Err(duplicate) => return Ok(duplicate),
+        };
+        
+        let location_id = line.location_id.clone();
+        let lot_number = line.lot_number.clone();
+        let result = self.apply_pick(line, vec![(location_id, lot_number, quantity)], user_id, false).await;
+        self.finish_scan(scan, result)
+    }
+    
+    /// Scan received goods into a location, posting a receive adjustment
+    /// there with the receipt line's lot. Completing the receipt later only
+    /// posts what wasn't put away.
+    pub async fn scan_putaway(
+        &self,
+        ctx: &RequestContext,
+        receipt_id: &str,
+        barcode: &str,
+        location_id: &str,
+        quantity: f64,
+        allow_duplicate: bool,
+    ) -> Result<ScanResult<InventoryItem>> {
+        let user_id = ctx.actor()?;
+        let receipt = self.get_receipt(receipt_id).await?
+            .ok_or_else(|| WmsError::not_found("Receipt not found"))?;
+        if receipt.status == ReceiptStatus::Completed {
+            return Err(WmsError::validation("Receipt is already completed"));
+        }
+        let item_id = self.resolve_scanned_item(barcode, quantity)?;
+        let line = Self::scanned_receipt_line(&receipt, &item_id, barcode)?;
+        
+        let scan = match self.record_scan(
+            item_scan(ScanContext::Putaway, receipt_id, barcode, &item_id, quantity, user_id, self.clock.now()),
+            allow_duplicate,
+        )? {
+            Ok(scan) => scan,
+            Err(duplicate) => return Ok(duplicate),
+        };
+        
+        let adjustment = InventoryAdjustment {
+            item_id,
+            location_id: Some(location_id.to_string()),
+            warehouse_id: None,
+            adjustment_type: AdjustmentType::Receive,
+            quantity,
+            lot_number: line.lot_number,
+            reason_code: Some("PUTAWAY".to_string()),
+            notes: Some(format!("Receipt {}", receipt.receipt_number)),
+            user_id: user_id.to_string(),
+        };
+        // The stock and the line's put-away quantity change together, and
+        // only while the receipt is open, or completing it would post the
+        // same units again
+        let result = async {
+            self.inventory.check_adjustment(&adjustment).await?;
+            self.db.transaction(|conn| {
+                let open: bool = conn.query_row(
+                    "SELECT status != 'completed' FROM receipts WHERE id = ?",
+                    params![receipt_id],
+                    |row| row.get(0),
+                )?;
+                if !open {
+                    return Err(WmsError::validation("Receipt is already completed"));
+                }
+                self.inventory.post_adjustment(conn, &adjustment)?;
+                conn.execute(
+                    "UPDATE receipt_items SET quantity_put_away = quantity_put_away + ? WHERE id = ?",
+                    params![quantity, &line.id],
+                )?;
+                Ok(())
+            })?;
+            self.inventory.after_adjustment(&adjustment).await
+        }.await;
+        self.finish_scan(scan, result)
+    }
+    
+    /// Accepted scans against a receipt or shipment, oldest first
+    pub async fn get_scan_history(&self, context_id: &str) -> Result<Vec<ScanEvent>> {
+        self.scans.history(context_id).await
+    }
+    
+    fn resolve_scanned_item(&self, barcode: &str, quantity: f64) -> Result<String> {
+        if quantity <= 0.0 {
+            return Err(WmsError::validation("Scanned quantity must be positive"));
+        }
+        self.resolve_asn_item(Some(barcode), Some(barcode))?
+            .ok_or_else(|| WmsError::not_found(format!("No item with barcode {}", barcode)))
+    }
+    
+    /// The receipt line for a scanned item
+    fn scanned_receipt_line(receipt: &Receipt, item_id: &str, barcode: &str) -> Result<ReceiptItem> {
+        Self::open_receipt_line(receipt, item_id)
+            .ok_or_else(|| WmsError::validation(format!("{} is not on receipt {}", barcode, receipt.receipt_number)))
+    }
+    
+    /// The receipt line for an item, preferring one still expecting stock
+    fn open_receipt_line(receipt: &Receipt, item_id: &str) -> Option<ReceiptItem> {
+        let mut lines = receipt.items.iter().filter(|l| l.item_id == item_id);
+        let first = lines.clone().next();
+        lines.find(|l| l.quantity_received < l.quantity_expected.unwrap_or_default())
+            .or(first)
+            .cloned()
+    }
+    
+    /// Record a scan. `Err` carries the duplicate result to return instead.
+    fn record_scan<T>(&self, scan: ScanEvent, allow_duplicate: bool) -> Result<std::result::Result<ScanEvent, ScanResult<T>>> {
+        let (barcode, context, context_id) = (scan.barcode.clone(), scan.context, scan.context_id.clone());
+        match self.scans.record(scan, allow_duplicate)? {
+            RecordedScan::New(scan) => Ok(Ok(scan)),
+            RecordedScan::Duplicate(previous) => {
+                debug!("Duplicate scan of {} in {} {}", barcode, context.as_str(), context_id);
+                Ok(Err(ScanResult::Duplicate {
+                    message: format!(
+                        "Duplicate scan: {} was already scanned at {}",
+                        barcode, previous.scanned_at.format("%H:%M:%S")
+                    ),
+                    previous,
+                }))
+            }
+        }
+    }
+    
+    /// Wrap a processed scan, dropping the scan record if processing failed
+    fn finish_scan<T>(&self, scan: ScanEvent, result: Result<T>) -> Result<ScanResult<T>> {
+        match result {
+            Ok(result) => Ok(ScanResult::Processed { scan, result }),
+            Err(e) => {
+                self.scans.discard(&scan.id)?;
+                Err(e)
+            }
+        }
+    }
+    
+    /// Take picks of (location, lot, quantity) for a line out of stock and
+    /// advance its picked quantity. The last location becomes the line's.
+    /// The stock, the line and the record of where each pick came from
+    /// change in one transaction, which also moves a confirmed shipment to
+    /// picking. The line is re-read there, so a pick by someone else since
+    /// `line` was loaded counts towards the over-pick check, and a shipment
+    /// cancelled in the meantime can't be picked.
+    async fn apply_pick(
+        &self,
+        mut line: ShipmentItem,
+        picks: Vec<(Option<String>, Option<String>, f64)>,
+        user_id: &str,
+        allow_over_pick: bool,
+    ) -> Result<ShipmentItem> {
+        let adjustments: Vec<InventoryAdjustment> = picks.into_iter()
+            .map(|(location_id, lot_number, quantity)| InventoryAdjustment {
+                item_id: line.item_id.clone(),
+                location_id,
+                warehouse_id: None,
+                adjustment_type: AdjustmentType::Pick,
+                quantity,
+                lot_number,
+                reason_code: None,
+                notes: Some(format!("Shipment line {}", line.id)),
+                user_id: user_id.to_string(),
+            })
+            .collect();
+        for adjustment in &adjustments {
+            self.inventory.check_adjustment(adjustment).await?;
+        }
+        
+        let quantity: f64 = adjustments.iter().map(|a| a.quantity).sum();
+        if let Some(last) = adjustments.last() {
+            line.location_id = last.location_id.clone();
+        }
+        line.picked_by = Some(user_id.to_string());
+        line.picked_at = Some(self.clock.now());
+        
+        let picked_at = line.picked_at.map(|t| t.to_rfc3339());
+        let (quantity_picked, started) = self.db.transaction(|conn| {
+            let started = self.start_picking(conn, &line.shipment_id)?;
+            
+            let quantity_picked: Option<f64> = conn.query_row(
+                "UPDATE shipment_items
+                 SET quantity_picked = quantity_picked + ?1,
+                     status = CASE WHEN quantity_picked + ?1 >= quantity_ordered THEN 'picked' ELSE 'picking' END,
+                     location_id = ?2, picked_by = ?3, picked_at = ?4
+                 WHERE id = ?5 AND (?6 OR quantity_picked + ?1 <= quantity_ordered)
+                 RETURNING quantity_picked",
+                params![quantity, &line.location_id, user_id, &picked_at, &line.id, allow_over_pick],
+                |row| row.get(0),
+            ).optional()?;
+            let Some(quantity_picked) = quantity_picked else {
+                let remaining: f64 = conn.query_row(
+                    "SELECT quantity_ordered - quantity_picked FROM shipment_items WHERE id = ?",
+                    params![&line.id],
+                    |row| row.get(0),
+                )?;
+                return Err(WmsError::validation(format!(
+                    "Only {} left to pick on this line; confirm an over-pick to take {}", remaining.max(0.0), quantity
+                )));
+            };
+            
+            for adjustment in &adjustments {
+                self.inventory.post_adjustment(conn, adjustment)?;
+                conn.execute(
+                    "INSERT INTO shipment_line_picks (
+                        id, shipment_item_id, location_id, lot_number, quantity, picked_by, picked_at
+                    ) VALUES (?, ?, ?, ?, ?, ?, ?)",
+                    params![
+                        new_id(),
+                        &line.id,
+                        &adjustment.location_id,
+                        &adjustment.lot_number,
+                        adjustment.quantity,
+                        user_id,
+                        &picked_at,
+                    ],
+                )?;
+            }
+            Ok((quantity_picked, started))
+        })?;
+        
+        line.quantity_picked = quantity_picked;
+        line.status = if line.quantity_picked >= line.quantity_ordered {
+            ShipmentItemStatus::Picked
+        } else {
+            ShipmentItemStatus::Picking
+        };
+        for adjustment in &adjustments {
+            self.inventory.after_adjustment(adjustment).await?;
+        }
+        if let Some(shipment) = started {
+            debug!("Updated shipment {} status to {:?}", shipment.id, shipment.status);
+            self.events.emit(WmsEvent::ShipmentStatusChanged {
+                shipment_id: shipment.id.clone(),
+                shipment_number: shipment.shipment_number.clone(),
+                status: shipment.status.as_str().to_string(),
+            });
+        }
+        Ok(line)
+    }
+    
+    /// Check that a shipment can still be picked, moving it from confirmed
+    /// to picking. Returns the shipment when it moved.
+    fn start_picking(&self, conn: &rusqlite::Connection, shipment_id: &str) -> Result<Option<Shipment>> {
+        let status = Self::require_pickable(conn, shipment_id)?;
+        if status != ShipmentStatus::Confirmed {
+            return Ok(None);
+        }
+        conn.execute(
+            "UPDATE shipments SET status = ?, updated_at = datetime('now') WHERE id = ? AND status = ?",
+            params![ShipmentStatus::Picking.as_str(), shipment_id, status.as_str()],
+        )?;
+        let shipment = Self::load_shipment(conn, shipment_id)?;
+        self.changes.record_model(conn, "shipments", &shipment.id, ChangeKind::Update, &shipment)?;
+        Ok(Some(shipment))
+    }
+    
+    /// The status of a shipment that is confirmed or being picked
+    fn require_pickable(conn: &rusqlite::Connection, shipment_id: &str) -> Result<ShipmentStatus> {
+        let (number, status): (String, String) = conn.query_row(
+            "SELECT shipment_number, status FROM shipments WHERE id = ?",
+            params![shipment_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        ).optional()?.ok_or_else(|| WmsError::not_found("Shipment not found"))?;
+        match ShipmentStatus::parse(&status) {
+            Some(status @ (ShipmentStatus::Confirmed | ShipmentStatus::Picking)) => Ok(status),
+            _ => Err(WmsError::validation(format!(
+                "Shipment {} is {}; only confirmed shipments can be picked", number, status
+            ))),
+        }
+    }
+    
+    /// Split `put_back`, an adjustment at the line's location and lot,
+    /// over the line's recorded picks, latest first, taking its quantity
+    /// off them. Quantity picked before picks were recorded stays at the
+    /// line's own location and lot.
+    fn take_back_picks(
+        conn: &rusqlite::Connection,
+        line_id: &str,
+        put_back: &InventoryAdjustment,
+    ) -> Result<Vec<InventoryAdjustment>> {
+        let mut picks = conn.prepare(
+            "SELECT id, location_id, lot_number, quantity FROM shipment_line_picks
+             WHERE shipment_item_id = ? ORDER BY picked_at DESC, rowid DESC",
+        )?;
+        let picks: Vec<(String, InventoryAdjustment)> = picks
+            .query_map(params![line_id], |row| {
+                Ok((row.get(0)?, InventoryAdjustment {
+                    location_id: row.get(1)?,
+                    lot_number: row.get(2)?,
+                    quantity: row.get(3)?,
+                    ..put_back.clone()
+                }))
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        
+        let mut taken: Vec<InventoryAdjustment> = Vec::new();
+        let mut take_back = |adjustment: InventoryAdjustment| {
+            let same_place = |t: &&mut InventoryAdjustment| {
+                t.location_id == adjustment.location_id && t.lot_number == adjustment.lot_number
+            };
+            match taken.iter_mut().find(same_place) {
+                Some(existing) => existing.quantity += adjustment.quantity,
+                None => taken.push(adjustment),
+            }
+        };
+        
+        let mut remaining = put_back.quantity;
+        for (id, pick) in picks {
+            if remaining <= 1e-9 {
+                break;
+            }
+            let take = pick.quantity.min(remaining);
+            if pick.quantity - take <= 1e-9 {
+                conn.execute("DELETE FROM shipment_line_picks WHERE id = ?", params![&id])?;
+            } else {
+                conn.execute(
+                    "UPDATE shipment_line_picks SET quantity = quantity - ? WHERE id = ?",
+                    params![take, &id],
+                )?;
+            }
+            take_back(InventoryAdjustment { quantity: take, ..pick });
+            remaining -= take;
+        }
+        if remaining > 1e-9 {
+            take_back(InventoryAdjustment { quantity: remaining, ..put_back.clone() });
+        }
+        Ok(taken)
+    }
+    
+    /// Correct a wrong pick: put `quantity` back where the latest picks
+    /// for the line were taken from, take it off the line's picked
+    /// quantity and record a mispick against whoever picked it. Only a
+    /// shipment still being picked can be corrected.
+    pub async fn correct_pick(
+        &self,
+        ctx: &RequestContext,
+        shipment_item_id: &str,
+        quantity: f64,
+        reason: Option<&str>,
+    ) -> Result<ShipmentItem> {
+        let corrected_by = ctx.actor()?;
+        let shipment_id: String = self.db.query_row(
+            "SELECT shipment_id FROM shipment_items WHERE id = ?",
+            params![shipment_item_id],
+            |row| row.get(0),
+        )?.ok_or_else(|| WmsError::not_found("Shipment line not found"))?;
+        
+        // The shipment and the line are read as the correction is written,
+        // so a line packed or shipped meanwhile can't be put back
+        let (line, picked_by, adjustments) = self.db.transaction(|conn| {
+            Self::require_pickable(conn, &shipment_id)?;
+            let mut line = conn.query_row(
+                &format!("{} AND si.id = ?", SHIPMENT_ITEMS_SQL),
+                params![SHIPMENT_LINE_OWNER, &shipment_id, shipment_item_id],
+                Self::row_to_shipment_item,
+            )?;
+            let picked_by = line.picked_by.clone()
+                .ok_or_else(|| WmsError::validation("Line has not been picked"))?;
+            if quantity <= 0.0 || quantity > line.quantity_picked {
+                return Err(WmsError::validation(format!(
+                    "Correction must be between 0 and the {} picked", line.quantity_picked
+                )));
+            }
+            
+            line.quantity_picked -= quantity;
+            line.status = if line.quantity_picked > 0.0 {
+                ShipmentItemStatus::Picking
+            } else {
+                ShipmentItemStatus::Pending
+            };
+            
+            let put_back = InventoryAdjustment {
+                item_id: line.item_id.clone(),
+                location_id: line.location_id.clone(),
+                warehouse_id: None,
+                adjustment_type: AdjustmentType::Adjust,
+                quantity,
+                lot_number: line.lot_number.clone(),
+                reason_code: Some("MISPICK".to_string()),
+                notes: Some(format!("Shipment line {}", line.id)),
+                user_id: corrected_by.to_string(),
+            };
+            let adjustments = Self::take_back_picks(conn, &line.id, &put_back)?;
+            for adjustment in &adjustments {
+                self.inventory.post_adjustment(conn, adjustment)?;
+            }
+            
+            conn.execute(
+                "UPDATE shipment_items SET quantity_picked = ?, status = ? WHERE id = ?",
+                params![line.quantity_picked, format!("{:?}", line.status).to_lowercase(), &line.id],
+            )?;
+            conn.execute(
+                "INSERT INTO mispicks (
+                    id, shipment_item_id, item_id, location_id, quantity, picked_by, picked_at,
+                    reason, corrected_by, corrected_at
+                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                params![
+                    new_id(),
+                    &line.id,
+                    &line.item_id,
+                    &line.location_id,
+                    quantity,
+                    &picked_by,
+                    line.picked_at.map(|t| t.to_rfc3339()),
+                    reason,
+                    corrected_by,
+                    self.clock.now().to_rfc3339(),
+                ],
+            )?;
+            Ok((line, picked_by, adjustments))
+        })?;
+        for adjustment in &adjustments {
+            self.inventory.after_adjustment(adjustment).await?;
+        }
+        
+        info!("Mispick on shipment line {} by {}: {} put back", line.id, picked_by, quantity);
+        Ok(line)
+    }
+    
+    /// Pick `quantity` of an item for a shipment from `location_id`. The
+    /// first pick moves a confirmed shipment to picking. Picking more than
+    /// is left on the line is rejected unless `allow_over_pick`.
+    pub async fn pick_item(
+        &self,
+        ctx: &RequestContext,
+        shipment_id: &str,
+        item_id: &str,
+        location_id: &str,
+        quantity: f64,
+        allow_over_pick: bool,
+    ) -> Result<ShipmentItem> {
+        let user_id = ctx.actor()?;
+        let line = self.line_to_pick(shipment_id, item_id, quantity, allow_over_pick).await?;
+        let lot_number = line.lot_number.clone();
+        self.apply_pick(line, vec![(Some(location_id.to_string()), lot_number, quantity)], user_id, allow_over_pick).await
+    }
+    
+    /// Pick an item for a shipment from the lots that expire first. The
+    /// quantity is taken from the locations and lots suggested by
+    /// `InventoryService::suggest_pick_lots`, skipping held and expired
+    /// lots, and fails without picking anything when they can't cover it.
+    pub async fn pick_item_fefo(
+        &self,
+        ctx: &RequestContext,
+        shipment_id: &str,
+        item_id: &str,
+        quantity: f64,
+        allow_over_pick: bool,
+    ) -> Result<ShipmentItem> {
+        let user_id = ctx.actor()?;
+        let line = self.line_to_pick(shipment_id, item_id, quantity, allow_over_pick).await?;
+        let allocations = self.inventory.suggest_pick_lots(item_id, quantity, false).await?;
+        
+        let picks = allocations.into_iter()
+            .map(|allocation| (Some(allocation.location_id), allocation.lot_number, allocation.quantity))
+            .collect();
+        self.apply_pick(line, picks, user_id, allow_over_pick).await
+    }
+    
+    /// The line to pick `quantity` of an item on
+    async fn line_to_pick(
+        &self,
+        shipment_id: &str,
+        item_id: &str,
+        quantity: f64,
+        allow_over_pick: bool,
+    ) -> Result<ShipmentItem> {
+        if quantity <= 0.0 {
+            return Err(WmsError::validation("Picked quantity must be positive"));
+        }
+        let shipment = self.get_shipment(shipment_id).await?
+            .ok_or_else(|| WmsError::not_found("Shipment not found"))?;
+        if !matches!(shipment.status, ShipmentStatus::Confirmed | ShipmentStatus::Picking) {
+            return Err(WmsError::validation(format!(
+                "Shipment {} is {:?}; only confirmed shipments can be picked",
+                shipment.shipment_number, shipment.status
+            )));
+        }
+        
+        // Prefer a line still waiting for stock when an item is on several
+        let lines: Vec<_> = shipment.items.into_iter().filter(|l| l.item_id == item_id).collect();
+        let line = lines.iter()
+            .find(|l| l.quantity_picked < l.quantity_ordered && l.short_pick_reason.is_none())
+            .or(lines.first())
+            .cloned()
+            .ok_or_else(|| WmsError::validation(format!(
+                "Item {} is not on shipment {}", item_id, shipment.shipment_number
+            )))?;
+        let remaining = line.quantity_ordered - line.quantity_picked;
+        if quantity > remaining && !allow_over_pick {
+            return Err(WmsError::validation(format!(
+                "Only {} left to pick on this line; confirm an over-pick to take {}", remaining.max(0.0), quantity
+            )));
+        }
+        
+        Ok(line)
+    }
+    
+    /// Close a line with less picked than ordered. The reason is kept on
+    /// the line and lets picking be confirmed complete without the rest.
+    pub async fn short_pick_item(
+        &self,
+        ctx: &RequestContext,
+        shipment_item_id: &str,
+        reason: &str,
+    ) -> Result<ShipmentItem> {
+        let user_id = ctx.actor()?;
+        let reason = reason.trim();
+        if reason.is_empty() {
+            return Err(WmsError::validation("A short pick needs a reason"));
+        }
+        let shipment_id: String = self.db.query_row(
+            "SELECT shipment_id FROM shipment_items WHERE id = ?",
+            params![shipment_item_id],
+            |row| row.get(0),
+        )?.ok_or_else(|| WmsError::not_found("Shipment line not found"))?;
+        let mut line = self.get_shipment_items(&shipment_id)?
+            .into_iter()
+            .find(|l| l.id == shipment_item_id)
+            .ok_or_else(|| WmsError::not_found("Shipment line not found"))?;
+        if matches!(line.status, ShipmentItemStatus::Packed | ShipmentItemStatus::Shipped) {
+            return Err(WmsError::validation(format!("A {:?} line can no longer be short-picked", line.status)));
+        }
+        if line.quantity_picked >= line.quantity_ordered {
+            return Err(WmsError::validation("Line is already fully picked"));
+        }
+        
+        line.short_pick_reason = Some(reason.to_string());
+        line.status = ShipmentItemStatus::Picked;
+        // Checked again as it is written, in case the line was picked or
+        // the shipment cancelled since it was read
+        self.db.transaction(|conn| {
+            Self::require_pickable(conn, &shipment_id)?;
+            let closed = conn.execute(
+                "UPDATE shipment_items SET short_pick_reason = ?, status = ?
+                 WHERE id = ? AND quantity_picked < quantity_ordered AND status NOT IN ('packed', 'shipped')",
+                params![reason, format!("{:?}", line.status).to_lowercase(), &line.id],
+            )?;
+            if closed == 0 {
+                return Err(WmsError::validation("Line was fully picked or packed in the meantime"));
+            }
+            Ok(())
+        })?;
+        
+        info!(
+            "Shipment line {} short-picked by {}: {} of {} ({})",
+            line.id, user_id, line.quantity_picked, line.quantity_ordered, reason
+        );
+        Ok(line)
+    }
+    
+    /// Finish picking a shipment, moving it from picking to packed. Every
+    /// line must be fully picked or short-picked with a reason.
+    pub async fn confirm_picking_complete(&self, shipment_id: &str) -> Result<Shipment> {
+        let shipment = self.get_shipment(shipment_id).await?
+            .ok_or_else(|| WmsError::not_found("Shipment not found"))?;
+        if shipment.status != ShipmentStatus::Picking {
+            return Err(WmsError::validation(format!(
+                "Shipment {} is {:?}, not being picked", shipment.shipment_number, shipment.status
+            )));
+        }
+        
+        let open: Vec<_> = shipment.items.iter()
+            .filter(|l| l.quantity_picked < l.quantity_ordered && l.short_pick_reason.is_none())
+            .map(|l| format!(
+                "{} ({} of {})",
+                l.item_sku.as_deref().unwrap_or(&l.item_id), l.quantity_picked, l.quantity_ordered
+            ))
+            .collect();
+        if !open.is_empty() {
+            return Err(WmsError::validation(format!(
+                "Lines not fully picked: {}. Pick the rest or short-pick them with a reason.",
+                open.join(", ")
+            )));
+        }
+        
+        self.set_status(shipment_id, ShipmentStatus::Packed, Some(shipment.version), None, None).await
+    }
+    
+    // ============ Damage Claim Operations ============
+    
+    /// Record a note and/or photo against a damaged receipt line, for the
+    /// supplier claim
+    pub async fn add_receipt_line_evidence(
+        &self,
+        ctx: &RequestContext,
+        receipt_item_id: &str,
+        note: Option<&str>,
+        photo: Option<EvidencePhoto>,
+    ) -> Result<LineEvidence> {
+        let user_id = ctx.actor()?;
+        let exists = self.db.query_row(
+            "SELECT 1 FROM receipt_items WHERE id = ?",
+            params![receipt_item_id],
+            |row| row.get::<_, i32>(0),
+        )?.is_some();
+        if !exists {
+            return Err(WmsError::not_found("Receipt line not found"));
+        }
+        
+        self.add_line_evidence(RECEIPT_LINE_OWNER, receipt_item_id, note, photo, user_id).await
+    }
+    
+    /// Record a note and/or photo against a shipment line, for a customer
+    /// damage claim on a return
+    pub async fn add_shipment_line_evidence(
+        &self,
+        ctx: &RequestContext,
+        shipment_item_id: &str,
+        note: Option<&str>,
+        photo: Option<EvidencePhoto>,
+    ) -> Result<LineEvidence> {
+        let user_id = ctx.actor()?;
+        let exists = self.db.query_row(
+            "SELECT 1 FROM shipment_items WHERE id = ?",
+            params![shipment_item_id],
+            |row| row.get::<_, i32>(0),
+        )?.is_some();
+        if !exists {
+            return Err(WmsError::not_found("Shipment line not found"));
+        }
+        
+        self.add_line_evidence(SHIPMENT_LINE_OWNER, shipment_item_id, note, photo, user_id).await
+    }
+    
+    /// Evidence recorded against a receipt or shipment line, oldest first
+    pub async fn get_line_evidence(&self, line_id: &str) -> Result<Vec<LineEvidence>> {
+        self.db.query_map(
+            "SELECT e.*, a.filename
+             FROM line_evidence e
+             LEFT JOIN attachments a ON a.id = e.attachment_id
+             WHERE e.line_id = ?
+             ORDER BY e.created_at, e.rowid",
+            params![line_id],
+            |row| Self::row_to_evidence(row),
+        )
+    }
+    
+    /// Receipt lines received in `period` that were counted as damaged or
+    /// have evidence, optionally for one supplier (matched ignoring case)
+    pub async fn get_damage_claims(&self, period: DateRange, supplier: Option<&str>) -> Result<DamageClaimReport> {
+        let lines = self.db.query_map(
+            "SELECT ri.id, ri.receipt_id, r.receipt_number, r.supplier_name,
+                    substr(COALESCE(ri.received_at, r.received_date, r.created_at), 1, 10) AS day,
+                    i.sku, i.name, ri.lot_number, ri.quantity_damaged
+             FROM receipt_items ri
+             JOIN receipts r ON r.id = ri.receipt_id
+             LEFT JOIN inventory_items i ON i.id = ri.item_id
+             WHERE (ri.quantity_damaged > 0 OR EXISTS (
+                    SELECT 1 FROM line_evidence e WHERE e.line_type = ?4 AND e.line_id = ri.id
+                 ))
+               AND day BETWEEN ?1 AND ?2
+               AND (?3 IS NULL OR lower(r.supplier_name) = lower(?3))
+             ORDER BY day, r.receipt_number",
+            params![period.start.to_string(), period.end.to_string(), supplier, RECEIPT_LINE_OWNER],
+            |row| Self::row_to_claim_line(row, period, Some(row.get::<_, Option<f64>>("quantity_damaged")?.unwrap_or(0.0))),
+        )?;
+        
+        self.with_evidence(period, lines).await
+    }
+    
+    /// Shipment lines whose damage evidence was first recorded in `period`,
+    /// optionally for one customer
+    pub async fn get_customer_damage_claims(
+        &self,
+        period: DateRange,
+        customer_id: Option<&str>,
+    ) -> Result<DamageClaimReport> {
+        let lines = self.db.query_map(
+            "SELECT si.id, si.shipment_id AS document_id, s.shipment_number,
+                    COALESCE(
+                        c.company_name,
+                        NULLIF(TRIM(COALESCE(c.first_name, '') || ' ' || COALESCE(c.last_name, '')), ''),
+                        s.ship_to_name
+                    ) AS party,
+                    (SELECT substr(MIN(e.created_at), 1, 10) FROM line_evidence e
+                     WHERE e.line_type = ?4 AND e.line_id = si.id) AS day,
+                    i.sku, i.name, si.lot_number
+             FROM shipment_items si
+             JOIN shipments s ON s.id = si.shipment_id
+             LEFT JOIN customers c ON c.id = s.customer_id
+             LEFT JOIN inventory_items i ON i.id = si.item_id
+             WHERE day BETWEEN ?1 AND ?2
+               AND (?3 IS NULL OR s.customer_id = ?3)
+             ORDER BY day, s.shipment_number",
+            params![period.start.to_string(), period.end.to_string(), customer_id, SHIPMENT_LINE_OWNER],
+            |row| Self::row_to_claim_line(row, period, None),
+        )?;
+        
+        self.with_evidence(period, lines).await
+    }
+    
+    /// Supplier damage claims as a zip of photos with a CSV manifest
+    pub async fn export_damage_claims(&self, period: DateRange, supplier: Option<&str>) -> Result<DamageClaimExport> {
+        let report = self.get_damage_claims(period, supplier).await?;
+        self.claims_zip(&report, format!("damage_claims_{}_{}.zip", period.start, period.end)).await
+    }
+    
+    /// Customer damage claims as a zip of photos with a CSV manifest
+    pub async fn export_customer_damage_claims(
+        &self,
+        period: DateRange,
+        customer_id: Option<&str>,
+    ) -> Result<DamageClaimExport> {
+        let report = self.get_customer_damage_claims(period, customer_id).await?;
+        self.claims_zip(&report, format!("customer_damage_claims_{}_{}.zip", period.start, period.end)).await
+    }
+    
+    /// Store the photo as an attachment owned by the line, then the
+    /// evidence record linking it with the note
+    async fn add_line_evidence(
+        &self,
+        line_type: &str,
+        line_id: &str,
+        note: Option<&str>,
+        photo: Option<EvidencePhoto>,
+        user_id: &str,
+    ) -> Result<LineEvidence> {
+        let note = note.map(str::trim).filter(|n| !n.is_empty()).map(String::from);
+        if note.is_none() && photo.is_none() {
+            return Err(WmsError::validation("Evidence needs a note or a photo"));
+        }
+        
+        let attachment = match photo {
+            Some(photo) => Some(self.attachments.add(NewAttachment {
+                owner_type: line_type.to_string(),
+                owner_id: line_id.to_string(),
+                kind: AttachmentKind::DamageEvidence,
+                filename: photo.filename,
+                content: photo.content,
+                created_by: user_id.to_string(),
+            }).await?),
+            None => None,
+        };
+        
+        let evidence = LineEvidence {
+            id: new_id(),
+            line_type: line_type.to_string(),
+            line_id: line_id.to_string(),
+            note,
+            attachment_id: attachment.as_ref().map(|a| a.id.clone()),
+            filename: attachment.map(|a| a.filename),
+            created_by: user_id.to_string(),
+            created_at: self.clock.now(),
+        };
+        
+        self.db.execute(
+            "INSERT INTO line_evidence (id, line_type, line_id, note, attachment_id, created_by, created_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
+            params![
+                &evidence.id,
+                &evidence.line_type,
+                &evidence.line_id,
+                &evidence.note,
+                &evidence.attachment_id,
+                &evidence.created_by,
+                evidence.created_at.to_rfc3339(),
+            ],
+        )?;
+        
+        debug!("Recorded damage evidence on {} {}", line_type, line_id);
+        Ok(evidence)
+    }
+    
+    /// Attach each claim line's evidence and build the report
+    async fn with_evidence(&self, period: DateRange, mut lines: Vec<DamageClaimLine>) -> Result<DamageClaimReport> {
+        for line in &mut lines {
+            line.evidence = self.get_line_evidence(&line.line_id).await?;
+        }
+        Ok(DamageClaimReport { period, lines })
+    }
+    
+    async fn claims_zip(&self, report: &DamageClaimReport, filename: String) -> Result<DamageClaimExport> {
+        let mut photos = HashMap::new();
+        for (_, evidence) in report.photos() {
+            if let Some(id) = &evidence.attachment_id {
+                photos.insert(id.clone(), self.attachments.content(id).await?);
+            }
+        }
+        let data = render_claims_zip(report, &photos)?;
+        
+        Ok(DamageClaimExport {
+            data: base64::engine::general_purpose::STANDARD.encode(&data),
+            content_type: "application/zip".to_string(),
+            filename,
+        })
+    }
+    
+    // ============ Cold Chain Operations ============
+    
+    /// Log a probe reading for a receipt line. A reading outside the range
+    /// of the item's category puts the line's lot on quality hold.
+    pub async fn record_receipt_temperature(
+        &self,
+        ctx: &RequestContext,
+        receipt_item_id: &str,
+        probe_id: Option<&str>,
+        celsius: f64,
+    ) -> Result<TemperatureReading> {
+        let (item_id, sku, category, lot_number) = self.db.query_row(
+            "SELECT ri.item_id, i.sku, i.category, ri.lot_number
+             FROM receipt_items ri
+             JOIN inventory_items i ON i.id = ri.item_id
+             WHERE ri.id = ?",
+            params![receipt_item_id],
+            |row| Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Option<String>>(2)?,
+                row.get::<_, Option<String>>(3)?,
+            )),
+        )?.ok_or_else(|| WmsError::not_found("Receipt line not found"))?;
+        
+        let range = self.temperature
+            .range_for_categories(&category.into_iter().collect::<Vec<_>>())
+            .await?;
+        let reading = self.temperature.record(
+            ctx,
+            TemperatureOwner::ReceiptItem(receipt_item_id.to_string()),
+            probe_id,
+            celsius,
+            range.as_ref(),
+        ).await?;
+        
+        if let (true, Some(range)) = (reading.out_of_range, &range) {
+            let reason = format!("Received at {:.1} °C, outside {}", celsius, range.describe());
+            match lot_number.as_deref().filter(|lot| !lot.trim().is_empty()) {
+                Some(lot) => match self.inventory.place_hold(ctx, &item_id, lot, &reason).await {
+                    // Already quarantined by an earlier reading
+                    Ok(_) | Err(WmsError::Conflict(_)) => {}
+                    Err(e) => return Err(e),
+                },
+                None => {
+                    self.db.execute(
+                        "UPDATE receipt_items SET notes = trim(COALESCE(notes || ' ', '') || ?) WHERE id = ?",
+                        params![&reason, receipt_item_id],
+                    )?;
+                    warn!("{} line {} has no lot to hold: {}", sku, receipt_item_id, reason);
+                }
+            }
+        }
+        
+        Ok(reading)
+    }
+    
+    // ============ Carrier Invoice Operations ============
+    
+    /// Import a carrier's invoice CSV. Rows are matched to shipments by
+    /// tracking number; importing a tracking number again replaces the
+    /// earlier row instead of adding to it. Rows matched so far are reported
+    /// to `cancel`; once it is cancelled the import stops with
+    /// `WmsError::Cancelled` and nothing is saved.
+    pub async fn import_carrier_invoice(
+        &self,
+        carrier_id: &str,
+        data: &[u8],
+        cancel: &CancellationToken,
+    ) -> Result<CarrierInvoiceImport> {
+        let lines = parse_carrier_invoice(data)?;
+        
+        let carrier = self.db.query_row(
+            "SELECT id FROM carriers WHERE id = ?",
+            params![carrier_id],
+            |row| row.get::<_, String>(0),
+        )?;
+        if carrier.is_none() {
+            return Err(WmsError::not_found(format!("Carrier {} not found", carrier_id)));
+        }
+        
+        let mut result = CarrierInvoiceImport {
+            carrier_id: carrier_id.to_string(),
+            imported: 0,
+            updated: 0,
+            matched: 0,
+            unmatched: Vec::new(),
+        };
+        let mut rows: Vec<(&CarrierInvoiceLine, Option<String>)> = Vec::with_capacity(lines.len());
+        for (index, line) in lines.iter().enumerate() {
+            cancel.check()?;
+            cancel.report(index as u64, Some(lines.len() as u64));
+            let shipment_id = self.find_shipment_by_tracking(carrier_id, &line.tracking_number)?;
+            let existing = self.db.query_row(
+                "SELECT id FROM carrier_invoices WHERE carrier_id = ? AND tracking_number = ?",
+                params![carrier_id, &line.tracking_number],
+                |row| row.get::<_, String>(0),
+            )?;
+            
+            // A tracking number repeated within the file also counts as an update
+            let seen = rows.iter().any(|(l, _)| l.tracking_number == line.tracking_number);
+            if existing.is_some() || seen {
+                result.updated += 1;
+            } else {
+                result.imported += 1;
+            }
+            match &shipment_id {
+                Some(_) => result.matched += 1,
+                None => result.unmatched.push(UnmatchedInvoiceLine {
+                    line_number: line.line_number,
+                    tracking_number: line.tracking_number.clone(),
+                    billed_total: line.billed_total(),
+                }),
+            }
+            rows.push((line, shipment_id));
+        }
+        
+        let imported_at = self.clock.now().to_rfc3339();
+        self.db.transaction(|conn| {
+            for (line, shipment_id) in &rows {
+                cancel.check()?;
+                conn.execute(
+                    "INSERT INTO carrier_invoices (
+                        id, carrier_id, tracking_number, shipment_id, invoice_number,
+                        billed_amount, surcharges, surcharge_total, billed_weight_kg, imported_at
+                    ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                    ON CONFLICT(carrier_id, tracking_number) DO UPDATE SET
+                        shipment_id = excluded.shipment_id,
+                        invoice_number = excluded.invoice_number,
+                        billed_amount = excluded.billed_amount,
+                        surcharges = excluded.surcharges,
+                        surcharge_total = excluded.surcharge_total,
+                        billed_weight_kg = excluded.billed_weight_kg,
+                        imported_at = excluded.imported_at",
+                    params![
+                        new_id(),
+                        carrier_id,
+                        &line.tracking_number,
+                        shipment_id,
+                        &line.invoice_number,
+                        line.billed_amount,
+                        serde_json::to_string(&line.surcharges)?,
+                        line.surcharge_total(),
+                        line.billed_weight_kg,
+                        &imported_at,
+                    ],
+                )?;
+            }
+            Ok(())
+        })?;
+        
+        cancel.report(lines.len() as u64, Some(lines.len() as u64));
+        info!("Imported {} invoice lines for carrier {} ({} unmatched)",
+              rows.len(), carrier_id, result.unmatched.len());
+        Ok(result)
+    }
+    
+    /// Estimated vs billed shipping cost for shipments shipped in `period`.
+    /// Lines whose variance exceeds `threshold_percent` of the estimate are
+    /// flagged.
+    pub async fn get_cost_reconciliation(
+        &self,
+        period: DateRange,
+        threshold_percent: Option<f64>,
+    ) -> Result<CostReconciliation> {
+        if period.end < period.start {
+            return Err(WmsError::validation("Reconciliation end date is before start date"));
+        }
+        let threshold_percent = threshold_percent.unwrap_or(DEFAULT_VARIANCE_THRESHOLD_PERCENT);
+        if threshold_percent.is_nan() || threshold_percent < 0.0 {
+            return Err(WmsError::validation("Variance threshold must not be negative"));
+        }
+        
+        // Invoices can arrive before the shipment got its tracking number
+        let pending = self.db.query_map(
+            "SELECT id, carrier_id, tracking_number FROM carrier_invoices WHERE shipment_id IS NULL",
+            [],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?)),
+        )?;
+        for (id, carrier_id, tracking_number) in pending {
+            if let Some(shipment_id) = self.find_shipment_by_tracking(&carrier_id, &tracking_number)? {
+                self.db.execute(
+                    "UPDATE carrier_invoices SET shipment_id = ? WHERE id = ?",
+                    params![&shipment_id, &id],
+                )?;
+            }
+        }
+        
+        let shipments = self.db.query_map(
+            "SELECT ci.*, s.shipment_number, s.shipping_cost,
+                    COALESCE(s.ship_date, s.created_at) AS shipped_on, c.code AS carrier_code
+             FROM carrier_invoices ci
+             JOIN shipments s ON ci.shipment_id = s.id
+             JOIN carriers c ON ci.carrier_id = c.id
+             WHERE substr(COALESCE(s.ship_date, s.created_at), 1, 10) BETWEEN ? AND ?
+             ORDER BY c.code, shipped_on, s.shipment_number",
+            params![period.start.to_string(), period.end.to_string()],
+            |row| {
+                let surcharges: String = row.get("surcharges")?;
+                let shipped_on: String = row.get("shipped_on")?;
+                Ok(InvoicedShipment {
+                    shipment_id: row.get("shipment_id")?,
+                    shipment_number: row.get("shipment_number")?,
+                    ship_date: shipped_on.get(..10)
+                        .and_then(|d| chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d").ok()),
+                    carrier_id: row.get("carrier_id")?,
+                    carrier_code: row.get("carrier_code")?,
+                    estimated_cost: row.get("shipping_cost")?,
+                    invoice: CarrierInvoiceLine {
+                        line_number: 0,
+                        tracking_number: row.get("tracking_number")?,
+                        invoice_number: row.get("invoice_number")?,
+                        billed_amount: row.get("billed_amount")?,
+                        surcharges: serde_json::from_str(&surcharges).unwrap_or_default(),
+                        billed_weight_kg: row.get("billed_weight_kg")?,
+                    },
+                })
+            },
+        )?;
+        
+        Ok(build_reconciliation(period, shipments, threshold_percent))
+    }
+    
+    /// Export the reconciliation's variance lines as CSV
+    pub async fn export_cost_reconciliation(
+        &self,
+        period: DateRange,
+        threshold_percent: Option<f64>,
+    ) -> Result<CostReconciliationExport> {
+        let report = self.get_cost_reconciliation(period, threshold_percent).await?;
+        let data = render_reconciliation_csv(&report)?;
+        
+        Ok(CostReconciliationExport {
+            data: base64::engine::general_purpose::STANDARD.encode(&data),
+            content_type: "text/csv".to_string(),
+            filename: format!("shipping_cost_reconciliation_{}_{}.csv", period.start, period.end),
+        })
+    }
+    
+    /// Shipment carrying a tracking number, preferring the invoicing carrier's
+    fn find_shipment_by_tracking(&self, carrier_id: &str, tracking_number: &str) -> Result<Option<String>> {
+        self.db.query_row(
+            "SELECT id FROM shipments WHERE tracking_number = ?
+             ORDER BY COALESCE(carrier_id = ?, 0) DESC, created_at DESC
+             LIMIT 1",
+            params![tracking_number, carrier_id],
+            |row| row.get(0),
+        )
+    }
+    
+    // ============ Barcode Operations ============
+    
+    /// Decode a barcode from image data
+    pub async fn decode_barcode(&self, image_data: &[u8], width: u32, height: u32) -> Result<BarcodeResult> {
+        self.barcode_decoder.decode(image_data, width, height)
+    }
+    
+    /// Decode a QR code from a photo (JPEG, PNG or WebP)
+    pub async fn decode_qr_image(&self, encoded: &[u8]) -> Result<BarcodeResult> {
+        self.barcode_decoder.decode_qr_image(encoded)
+    }
+    
+    // ============ Helper Methods ============
+    
+    fn generate_shipment_number(&self) -> Result<String> {
+        DocumentSequence::Shipment.next_number(&self.db)
+    }
+    
+    fn generate_receipt_number(&self) -> Result<String> {
+        DocumentSequence::Receipt.next_number(&self.db)
+    }
+    
+    fn insert_shipment_item(conn: &rusqlite::Connection, item: &ShipmentItem) -> Result<()> {
+        conn.execute(
+            "INSERT INTO shipment_items (
+                id, shipment_id, item_id, location_id, quantity_ordered,
+                quantity_picked, quantity_shipped, lot_number, serial_number, status
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            params![
+                &item.id,
+                &item.shipment_id,
+                &item.item_id,
+                &item.location_id,
+                item.quantity_ordered,
+                item.quantity_picked,
+                item.quantity_shipped,
+                &item.lot_number,
+                &item.serial_number,
+                "pending",
+            ],
+        )?;
+        Ok(())
+    }
+    
+    fn insert_receipt_item(conn: &rusqlite::Connection, item: &ReceiptItem) -> Result<()> {
+        conn.execute(
+            "INSERT INTO receipt_items (
+                id, receipt_id, item_id, location_id, quantity_expected,
+                quantity_received, quantity_damaged, lot_number, expiry_date, status
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            params![
+                &item.id,
+                &item.receipt_id,
+                &item.item_id,
+                &item.location_id,
+                item.quantity_expected.unwrap_or_default(),
+                item.quantity_received,
+                item.quantity_damaged,
+                &item.lot_number,
+                item.expiry_date.map(|d| d.to_rfc3339()),
+                "pending",
+            ],
+        )?;
+        Ok(())
+    }
+    
+    fn get_shipment_items(&self, shipment_id: &str) -> Result<Vec<ShipmentItem>> {
+        self.db.query_map(SHIPMENT_ITEMS_SQL, params![SHIPMENT_LINE_OWNER, shipment_id], Self::row_to_shipment_item)
+    }
+    
+    /// A shipment with its lines and packages, read in the caller's transaction
+    fn load_shipment(conn: &rusqlite::Connection, id: &str) -> Result<Shipment> {
+        let mut shipment = conn.query_row(
+            "SELECT * FROM shipments WHERE id = ?",
+            params![id],
+            Self::row_to_shipment,
+        )?;
+        shipment.items = conn.prepare(SHIPMENT_ITEMS_SQL)?
+            .query_map(params![SHIPMENT_LINE_OWNER, id], Self::row_to_shipment_item)?
+            .collect::<rusqlite::Result<_>>()?;
+        shipment.packages = conn.prepare("SELECT * FROM shipment_packages WHERE shipment_id = ? ORDER BY package_number")?
+            .query_map(params![id], Self::row_to_package)?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(shipment)
+    }
+    
+    fn get_shipment_packages(&self, shipment_id: &str) -> Result<Vec<ShipmentPackage>> {
+        self.db.query_map(
+            "SELECT * FROM shipment_packages WHERE shipment_id = ? ORDER BY package_number",
+            params![shipment_id],
+            |row| Self::row_to_package(row),
+        )
+    }
+    
+    fn get_package(&self, id: &str) -> Result<ShipmentPackage> {
+        self.db.query_row(
+            "SELECT * FROM shipment_packages WHERE id = ?",
+            params![id],
+            |row| Self::row_to_package(row),
+        )?.ok_or_else(|| WmsError::not_found("Package not found"))
+    }
+    
+    fn get_package_by_lpn(&self, lpn: &str) -> Result<ShipmentPackage> {
+        self.db.query_row(
+            "SELECT * FROM shipment_packages WHERE lpn = ?",
+            params![lpn],
+            |row| Self::row_to_package(row),
+        )?.ok_or_else(|| WmsError::not_found(format!("No package with license plate {}", lpn)))
+    }
+    
+    fn row_to_package(row: &rusqlite::Row) -> rusqlite::Result<ShipmentPackage> {
+        Ok(ShipmentPackage {
+            id: row.get("id")?,
+            shipment_id: row.get("shipment_id")?,
+            package_number: row.get("package_number")?,
+            tracking_number: row.get("tracking_number")?,
+            weight_kg: row.get("weight_kg")?,
+            length_cm: row.get("length_cm")?,
+            width_cm: row.get("width_cm")?,
+            height_cm: row.get("height_cm")?,
+            package_type: row.get("package_type")?,
+            lpn: row.get("lpn")?,
+            parent_package_id: row.get("parent_package_id")?,
+            closed_at: stored_timestamp(row, "closed_at")?,
+            floor_loaded: row.get("floor_loaded")?,
+            created_at: required_timestamp(row, "created_at")?,
+        })
+    }
+    
+    fn get_receipt_items(&self, receipt_id: &str) -> Result<Vec<ReceiptItem>> {
+        self.db.query_map(
+            "SELECT ri.*, i.sku, i.name,
+                    (SELECT COUNT(*) FROM line_evidence e
+                     WHERE e.line_type = ? AND e.line_id = ri.id) AS evidence_count,
+                    EXISTS(SELECT 1 FROM category_temperature_ranges t
+                           WHERE lower(t.category) = lower(i.category)) AS cold_chain
+             FROM receipt_items ri
+             LEFT JOIN inventory_items i ON ri.item_id = i.id
+             WHERE ri.receipt_id = ?",
+            params![RECEIPT_LINE_OWNER, receipt_id],
+            |row| {
+                Ok(ReceiptItem {
+                    id: row.get("id")?,
+                    receipt_id: row.get("receipt_id")?,
+                    item_id: row.get("item_id")?,
+                    location_id: row.get("location_id")?,
+                    quantity_expected: Some(row.get("quantity_expected")?),
+                    quantity_received: row.get("quantity_received")?,
+                    quantity_damaged: row.get("quantity_damaged")?,
+                    quantity_put_away: row.get("quantity_put_away")?,
+                    lot_number: row.get("lot_number")?,
+                    expiry_date: stored_timestamp(row, "expiry_date")?,
+                    status: stored_status(row, "status", ReceiptItemStatus::parse)?,
+                    received_by: row.get("received_by")?,
+                    received_at: stored_timestamp(row, "received_at")?,
+                    notes: row.get("notes")?,
+                    item_sku: row.get("sku")?,
+                    item_name: row.get("name")?,
+                    evidence_count: row.get("evidence_count")?,
+                    cold_chain: row.get::<_, i32>("cold_chain")? == 1,
+                })
+            },
+        )
+    }
+    
+    fn row_to_evidence(row: &rusqlite::Row) -> rusqlite::Result<LineEvidence> {
+        Ok(LineEvidence {
+            id: row.get("id")?,
+            line_type: row.get("line_type")?,
+            line_id: row.get("line_id")?,
+            note: row.get("note")?,
+            attachment_id: row.get("attachment_id")?,
+            filename: row.get("filename")?,
+            created_by: row.get("created_by")?,
+            created_at: required_timestamp(row, "created_at")?,
+        })
+    }
+    
+    /// Claim line from a row of `get_damage_claims` or
+    /// `get_customer_damage_claims`; evidence is filled in afterwards
+    fn row_to_claim_line(
+        row: &rusqlite::Row,
+        period: DateRange,
+        quantity_damaged: Option<f64>,
+    ) -> rusqlite::Result<DamageClaimLine> {
+        let day: String = row.get("day")?;
+        
+        Ok(DamageClaimLine {
+            line_id: row.get(0)?,
+            document_id: row.get(1)?,
+            document_number: row.get(2)?,
+            party: row.get(3)?,
+            date: day.parse().unwrap_or(period.start),
+            item_sku: row.get("sku")?,
+            item_name: row.get("name")?,
+            lot_number: row.get("lot_number")?,
+            quantity_damaged,
+            evidence: Vec::new(),
+        })
+    }
+    
+    fn row_to_shipment_item(row: &rusqlite::Row) -> rusqlite::Result<ShipmentItem> {
+        Ok(ShipmentItem {
+            id: row.get("id")?,
+            shipment_id: row.get("shipment_id")?,
+            item_id: row.get("item_id")?,
+            location_id: row.get("location_id")?,
+            quantity_ordered: row.get("quantity_ordered")?,
+            quantity_picked: row.get("quantity_picked")?,
+            quantity_shipped: row.get("quantity_shipped")?,
+            lot_number: row.get("lot_number")?,
+            serial_number: row.get("serial_number")?,
+            unit_price: row.get("unit_price")?,
+            extended_price: row.get("extended_price")?,
+            status: stored_status(row, "status", ShipmentItemStatus::parse)?,
+            picked_by: row.get("picked_by")?,
+            picked_at: stored_timestamp(row, "picked_at")?,
+            short_pick_reason: row.get("short_pick_reason")?,
+            item_sku: row.get("sku")?,
+            item_name: row.get("name")?,
+            evidence_count: row.get("evidence_count")?,
+        })
+    }
+    
+    fn row_to_shipment(row: &rusqlite::Row) -> rusqlite::Result<Shipment> {
+        Ok(Shipment {
+            id: row.get("id")?,
+            shipment_number: row.get("shipment_number")?,
+            status: stored_status(row, "status", ShipmentStatus::parse)?,
+            order_reference: row.get("order_reference")?,
+            customer_id: row.get("customer_id")?,
+            carrier_id: row.get("carrier_id")?,
+            service_type: row.get("service_type")?,
+            tracking_number: row.get("tracking_number")?,
+            ship_date: stored_timestamp(row, "ship_date")?,
+            expected_delivery_date: stored_timestamp(row, "expected_delivery_date")?,
+            actual_delivery_date: stored_timestamp(row, "actual_delivery_date")?,
+            cancelled_at: stored_timestamp(row, "cancelled_at")?,
+            cancel_reason: row.get("cancel_reason")?,
+            ship_to: ShipToAddress {
+                name: row.get("ship_to_name")?,
+                address: wms_core::types::Address {
+                    line1: row.get("ship_to_address_line1")?,
+                    line2: row.get("ship_to_address_line2")?,
+                    city: row.get("ship_to_city")?,
+                    state: row.get("ship_to_state")?,
+                    postal_code: row.get("ship_to_postal_code")?,
+                    country: row.get("ship_to_country")?,
+                },
+                phone: row.get("ship_to_phone")?,
+                email: row.get("ship_to_email")?,
+            },
+            total_weight_kg: row.get("total_weight_kg")?,
+            total_packages: row.get::<_, u32>("total_packages").unwrap_or(1),
+            shipping_cost: row.get("shipping_cost")?,
+            insurance_value: row.get("insurance_value")?,
+            declared_value: row.get("declared_value")?,
+            subtotal: row.get("subtotal")?,
+            order_total: row.get("order_total")?,
+            currency_code: row.get("currency_code")?,
+            special_instructions: row.get("special_instructions")?,
+            parent_shipment_id: row.get("parent_shipment_id")?,
+            label_printed: row.get::<_, i32>("label_printed")? == 1,
+            custom_fields: parse_custom_fields(row.get("custom_fields")?),
+            created_by: row.get("created_by")?,
+            created_at: required_timestamp(row, "created_at")?,
+            updated_at: stored_timestamp(row, "updated_at")?,
+            version: row.get("version")?,
+            items: Vec::new(),
+            packages: Vec::new(),
+        })
+    }
+    
+    fn row_to_receipt(row: &rusqlite::Row) -> rusqlite::Result<Receipt> {
+        Ok(Receipt {
+            id: row.get("id")?,
+            receipt_number: row.get("receipt_number")?,
+            status: stored_status(row, "status", ReceiptStatus::parse)?,
+            po_number: row.get("po_number")?,
+            supplier_name: row.get("supplier_name")?,
+            supplier_reference: row.get("supplier_reference")?,
+            expected_date: stored_timestamp(row, "expected_date")?,
+            received_date: stored_timestamp(row, "received_date")?,
+            dock_door: row.get("dock_door")?,
+            notes: row.get("notes")?,
+            blind_count: row.get("blind_count")?,
+            variance_acknowledged_by: row.get("variance_acknowledged_by")?,
+            variance_acknowledged_at: stored_timestamp(row, "variance_acknowledged_at")?,
+            created_by: row.get("created_by")?,
+            created_at: required_timestamp(row, "created_at")?,
+            completed_at: stored_timestamp(row, "completed_at")?,
+            completed_by: row.get("completed_by")?,
+            items: Vec::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use wms_core::events::RecordingEmitter;
+    use wms_core::test_support::{
+        fixed_clock, fixture_time, insert_location, test_context, test_db, CustomerBuilder, FailingRecorder,
+        ItemBuilder, ShipmentBuilder, TEST_USER_ID,
+    };
+    
+    fn draft_shipment() -> Shipment {
+        ShipmentBuilder::new("SHP-1").build()
+    }
+    
+    /// Move a shipment one step at a time until it reaches `status`
+    async fn advance_to(service: &ShippingService, id: &str, status: ShipmentStatus) {
+        let mut current = service.get_shipment(id).await.unwrap().unwrap().status;
+        while current != status {
+            current = service.update_status(id, current.allowed_next()[0]).await.unwrap().status;
+        }
+    }
+    
+    /// One shipment per status tab, plus a second draft with tracking
+    fn seed_shipment_list(db: &Database) {
+        for (number, status) in [
+            ("SHP-0001", "draft"),
+            ("SHP-0002", "picking"),
+            ("SHP-0003", "packed"),
+            ("SHP-0004", "shipped"),
+            ("SHP-0005", "draft"),
+        ] {
+            ShipmentBuilder::new(number).status(status).insert::<Shipment>(db);
+        }
+        db.execute(
+            "UPDATE shipments SET tracking_number = '1Z999AA10123456784', ship_to_name = 'Globex'
+             WHERE id = 'shp-0004'",
+            [],
+        ).unwrap();
+    }
+    
+    fn numbers(page: &ShipmentPage) -> Vec<&str> {
+        page.items.iter().map(|s| s.shipment_number.as_str()).collect()
+    }
+    
+    #[tokio::test]
+    async fn test_list_shipments_by_status_tab() {
+        let db = test_db();
+        seed_shipment_list(&db);
+        let service = ShippingService::new(db);
+        let tab = |status| ShipmentFilter { status, ..Default::default() };
+        
+        let all = service.list_shipments(tab(None)).await.unwrap();
+        assert_eq!(all.total_count, 5);
+        assert_eq!(numbers(&all), vec!["SHP-0005", "SHP-0004", "SHP-0003", "SHP-0002", "SHP-0001"]);
+        
+        let drafts = service.list_shipments(tab(Some(ShipmentStatus::Draft))).await.unwrap();
+        assert_eq!(numbers(&drafts), vec!["SHP-0005", "SHP-0001"]);
+        assert!(drafts.items.iter().all(|s| s.status == ShipmentStatus::Draft));
+        for (status, number) in [
+            (ShipmentStatus::Picking, "SHP-0002"),
+            (ShipmentStatus::Packed, "SHP-0003"),
+            (ShipmentStatus::Shipped, "SHP-0004"),
+        ] {
+            let page = service.list_shipments(tab(Some(status))).await.unwrap();
+            assert_eq!(numbers(&page), vec![number], "{:?}", status);
+            assert_eq!(page.items[0].status, status);
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_list_shipments_search_and_pages() {
+        let db = test_db();
+        seed_shipment_list(&db);
+        let service = ShippingService::new(db);
+        let search = |text: &str| ShipmentFilter { search: Some(text.to_string()), ..Default::default() };
+        
+        assert_eq!(numbers(&service.list_shipments(search("1Z999AA")).await.unwrap()), vec!["SHP-0004"]);
+        assert_eq!(numbers(&service.list_shipments(search("globex")).await.unwrap()), vec!["SHP-0004"]);
+        assert_eq!(service.list_shipments(search("SHP-000")).await.unwrap().total_count, 5);
+        
+        let page = |page| ShipmentFilter { page, page_size: 2, ..Default::default() };
+        let first = service.list_shipments(page(1)).await.unwrap();
+        assert_eq!((first.total_count, first.total_pages), (5, 3));
+        assert_eq!(numbers(&first), vec!["SHP-0005", "SHP-0004"]);
+        let last = service.list_shipments(page(3)).await.unwrap();
+        assert_eq!(numbers(&last), vec!["SHP-0001"]);
+        assert!(service.list_shipments(page(4)).await.unwrap().items.is_empty());
+        
+        let clamped = service.list_shipments(ShipmentFilter { page: 0, page_size: 0, ..Default::default() }).await.unwrap();
+        assert_eq!((clamped.page, clamped.page_size, clamped.total_pages), (1, 1, 5));
+    }
+    
+    fn insert_receipt(db: &Database, number: &str, status: &str, supplier: &str, expected_date: &str) {
+        db.execute(
+            "INSERT INTO receipts (id, receipt_number, status, supplier_name, expected_date, created_by, created_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
+            params![number.to_lowercase(), number, status, supplier, expected_date, TEST_USER_ID, expected_date],
+        ).unwrap();
+    }
+    
+    #[tokio::test]
+    async fn test_list_receipts_filters() {
+        let db = test_db();
+        insert_receipt(&db, "RCV-1", "pending", "Global Supplies", "2025-01-14T10:00:00Z");
+        insert_receipt(&db, "RCV-2", "receiving", "Parts Direct", "2025-01-15T10:00:00Z");
+        insert_receipt(&db, "RCV-3", "completed", "Global Supplies", "2025-01-16T10:00:00Z");
+        insert_receipt(&db, "RCV-4", "pending", "Parts Direct", "2025-01-17T10:00:00Z");
+        let service = ShippingService::new(db);
+        let numbers = |page: ReceiptPage| page.items.into_iter().map(|r| r.receipt_number).collect::<Vec<_>>();
+        
+        let all = service.list_receipts(ReceiptFilter::default()).await.unwrap();
+        assert_eq!(all.total_count, 4);
+        assert_eq!(numbers(all), vec!["RCV-4", "RCV-3", "RCV-2", "RCV-1"]);
+        
+        let pending = service.list_receipts(ReceiptFilter { status: Some(ReceiptStatus::Pending), ..Default::default() })
+            .await.unwrap();
+        assert!(pending.items.iter().all(|r| r.status == ReceiptStatus::Pending));
+        assert_eq!(numbers(pending), vec!["RCV-4", "RCV-1"]);
+        
+        let filter = ReceiptFilter {
+            supplier: Some("global".into()),
+            expected_from: NaiveDate::from_ymd_opt(2025, 1, 15),
+            ..Default::default()
+        };
+        assert_eq!(numbers(service.list_receipts(filter).await.unwrap()), vec!["RCV-3"]);
+        
+        let second = service.list_receipts(ReceiptFilter { page: 2, page_size: 3, ..Default::default() }).await.unwrap();
+        assert_eq!((second.total_count, second.total_pages), (4, 2));
+        assert_eq!(numbers(second), vec!["RCV-1"]);
+    }
+    
+    #[tokio::test]
+    async fn test_receiving_summary_compares_instants_not_strings() {
+        // The fixed clock reads 2025-01-15T09:00:00Z
+        let db = test_db();
+        // 07:00Z, two hours ago, though its local time reads later than 09:00
+        insert_receipt(&db, "RCV-1", "pending", "A", "2025-01-15T12:00:00+05:00");
+        // 10:00Z, an hour from now, though its local time reads earlier
+        insert_receipt(&db, "RCV-2", "pending", "A", "2025-01-15T05:00:00-05:00");
+        // 2025-01-14T23:30Z: yesterday in UTC and late, still being received
+        insert_receipt(&db, "RCV-3", "receiving", "A", "2025-01-15T01:30:00+02:00");
+        // Past due but done, and due tomorrow in UTC
+        insert_receipt(&db, "RCV-4", "completed", "A", "2025-01-14T09:00:00Z");
+        insert_receipt(&db, "RCV-5", "pending", "A", "2025-01-15T20:00:00-06:00");
+        db.execute("UPDATE receipts SET completed_at = '2025-01-15 08:30:00' WHERE id = 'rcv-4'", []).unwrap();
+        let service = ShippingService::new(db).with_clock(fixed_clock());
+        
+        let summary = service.get_receiving_summary().await.unwrap();
+        assert_eq!(summary, ReceivingSummary { pending: 2, receiving: 1, completed: 1, overdue: 2 });
+        
+        let filter = ReceiptFilter {
+            expected_from: NaiveDate::from_ymd_opt(2025, 1, 15),
+            expected_to: NaiveDate::from_ymd_opt(2025, 1, 15),
+            ..Default::default()
+        };
+        let today: Vec<String> = service.list_receipts(filter).await.unwrap()
+            .items.into_iter().map(|r| r.receipt_number).collect();
+        assert_eq!(today, vec!["RCV-2", "RCV-1"]);
+    }
+    
+    #[tokio::test]
+    async fn test_status_change_emits_event() {
+        let events = Arc::new(RecordingEmitter::new());
+        let service = ShippingService::new(test_db()).with_event_emitter(events.clone());
+        
+        let shipment = service.create_shipment(draft_shipment(), None).await.unwrap();
+        service.update_status(&shipment.id, ShipmentStatus::Confirmed).await.unwrap();
+        service.update_status(&shipment.id, ShipmentStatus::Picking).await.unwrap();
+        
+        assert_eq!(events.events(), vec![
+            WmsEvent::ShipmentStatusChanged {
+                shipment_id: shipment.id.clone(),
+                shipment_number: shipment.shipment_number.clone(),
+                status: "confirmed".to_string(),
+            },
+            WmsEvent::ShipmentStatusChanged {
+                shipment_id: shipment.id.clone(),
+                shipment_number: shipment.shipment_number.clone(),
+                status: "picking".to_string(),
+            },
+        ]);
+    }
+    
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_creates_get_distinct_numbers() {
+        let service = Arc::new(ShippingService::new(test_db()));
+        let tasks: Vec<_> = (0..16)
+            .map(|_| {
+                let service = service.clone();
+                tokio::spawn(async move { service.create_shipment(draft_shipment(), None).await })
+            })
+            .collect();
+        
+        let mut numbers = Vec::new();
+        for task in tasks {
+            numbers.push(task.await.unwrap().unwrap().shipment_number);
+        }
+        numbers.sort();
+        numbers.dedup();
+        assert_eq!(numbers.len(), 16);
+        
+        // Deleting a shipment doesn't hand its number out again
+        service.db.execute("DELETE FROM shipments WHERE shipment_number = 'SHP-00000016'", []).unwrap();
+        let next = service.create_shipment(draft_shipment(), None).await.unwrap();
+        assert_eq!(next.shipment_number, "SHP-00000017");
+    }
+    
+    #[tokio::test]
+    async fn test_failed_line_insert_leaves_no_shipment() {
+        let service = ShippingService::new(stocked_db());
+        
+        // The second line references an item that doesn't exist
+        let result = service.create_shipment(shipment_with_lines(&[("widget", 1.0), ("missing", 1.0)]), None).await;
+        assert!(result.is_err());
+        
+        let headers: Option<i64> = service.db
+            .query_row("SELECT COUNT(*) FROM shipments", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(headers, Some(0));
+        
+        // The rolled-back create didn't use up a number either
+        let next = service.create_shipment(shipment_with_lines(&[("widget", 1.0)]), None).await.unwrap();
+        assert_eq!(next.shipment_number, "SHP-00000001");
+    }
+    
+    #[tokio::test]
+    async fn test_loaded_shipment_keeps_status_and_dates() {
+        let service = ShippingService::new(stocked_db()).with_clock(fixed_clock());
+        let mut draft = shipment_with_lines(&[("widget", 2.0)]);
+        draft.expected_delivery_date = Some(fixture_time() + chrono::Duration::days(3));
+        
+        let shipment = service.create_shipment(draft, None).await.unwrap();
+        advance_to(&service, &shipment.id, ShipmentStatus::Shipped).await;
+        service.db.execute(
+            "UPDATE shipment_items SET status = 'picked' WHERE shipment_id = ?",
+            params![&shipment.id],
+        ).unwrap();
+        
+        let loaded = service.get_shipment(&shipment.id).await.unwrap().unwrap();
+        assert_eq!(loaded.status, ShipmentStatus::Shipped);
+        assert_eq!(loaded.ship_date, Some(fixture_time()));
+        assert_eq!(loaded.expected_delivery_date, Some(fixture_time() + chrono::Duration::days(3)));
+        assert_eq!(loaded.created_at, fixture_time());
+        assert_eq!(loaded.items[0].status, ShipmentItemStatus::Picked);
+        
+        // Rows written by SQLite's datetime('now') read back too
+        service.db.execute(
+            "UPDATE shipments SET ship_date = '2025-03-03 10:00:00' WHERE id = ?",
+            params![&shipment.id],
+        ).unwrap();
+        let loaded = service.get_shipment(&shipment.id).await.unwrap().unwrap();
+        assert_eq!(loaded.ship_date.unwrap().to_rfc3339(), "2025-03-03T10:00:00+00:00");
+        
+        // Values nothing could have written fail the load instead of being
+        // read as a default
+        for (column, value) in [("status", "bogus"), ("ship_date", "last tuesday")] {
+            service.db.execute(
+                &format!("UPDATE shipments SET status = 'shipped', ship_date = NULL, {} = ? WHERE id = ?", column),
+                params![value, &shipment.id],
+            ).unwrap();
+            assert!(service.get_shipment(&shipment.id).await.is_err(), "{} = {}", column, value);
+        }
+    }
+    
+    fn stocked_db() -> Arc<Database> {
+        let db = test_db();
+        for (id, sku, stock) in [("widget", "WID-1", 6.0), ("gadget", "GAD-1", 0.0), ("bolt", "BLT-1", 50.0)] {
+            ItemBuilder::new(sku).id(id).stock("loc1", stock).insert::<InventoryItem>(&db);
+        }
+        db
+    }
+    
+    fn shipment_with_lines(lines: &[(&str, f64)]) -> Shipment {
+        lines.iter()
+            .fold(ShipmentBuilder::new("SHP-1").service_type("ground"), |shipment, (item_id, qty)| {
+                shipment.line(item_id, *qty)
+            })
+            .build()
+    }
+    
+    fn ordered(shipment: &Shipment, item_id: &str) -> Option<f64> {
+        shipment.items.iter()
+            .find(|i| i.item_id == item_id)
+            .map(|i| i.quantity_ordered)
+    }
+    
+    #[tokio::test]
+    async fn test_auto_split_backorders_unavailable_quantities() {
+        let service = ShippingService::new(stocked_db());
+        let shipment = service
+            .create_shipment(shipment_with_lines(&[("widget", 10.0), ("gadget", 3.0), ("bolt", 5.0)]), None)
+            .await
+            .unwrap();
+        advance_to(&service, &shipment.id, ShipmentStatus::Picking).await;
+        
+        let split = service.split_shipment(&shipment.id, SplitSpec::AutoByAvailability).await.unwrap();
+        
+        assert_eq!(split.parent.status, ShipmentStatus::Picking);
+        assert_eq!(ordered(&split.parent, "widget"), Some(6.0));
+        assert_eq!(ordered(&split.parent, "gadget"), None);
+        assert_eq!(ordered(&split.parent, "bolt"), Some(5.0));
+        
+        assert_eq!(split.child.status, ShipmentStatus::Draft);
+        assert_eq!(split.child.parent_shipment_id.as_deref(), Some(shipment.id.as_str()));
+        assert_eq!(split.child.ship_to.name, "Acme Corp");
+        assert_eq!(split.child.service_type.as_deref(), Some("ground"));
+        assert_eq!(ordered(&split.child, "widget"), Some(4.0));
+        assert_eq!(ordered(&split.child, "gadget"), Some(3.0));
+        assert_eq!(ordered(&split.child, "bolt"), None);
+    }
+    
+    #[tokio::test]
+    async fn test_manual_split_and_status_guard() {
+        let service = ShippingService::new(stocked_db());
+        let shipment = service
+            .create_shipment(shipment_with_lines(&[("bolt", 8.0)]), None)
+            .await
+            .unwrap();
+        let line_id = shipment.items[0].id.clone();
+        
+        let split = service.split_shipment(&shipment.id, SplitSpec::Manual {
+            lines: vec![SplitLine { shipment_item_id: line_id.clone(), quantity: 2.5 }],
+        }).await.unwrap();
+        assert_eq!(ordered(&split.parent, "bolt"), Some(5.5));
+        assert_eq!(ordered(&split.child, "bolt"), Some(2.5));
+        
+        advance_to(&service, &shipment.id, ShipmentStatus::Packed).await;
+        let result = service.split_shipment(&shipment.id, SplitSpec::Manual {
+            lines: vec![SplitLine { shipment_item_id: line_id, quantity: 1.0 }],
+        }).await;
+        assert!(matches!(result, Err(WmsError::Validation(_))));
+    }
+    
+    #[tokio::test]
+    async fn test_wave_pick_list_visits_each_stop_once() {
+        let db = test_db();
+        for (sku, location) in [("WID", "A-01"), ("GEAR", "B-01"), ("BOLT", "C-01")] {
+            ItemBuilder::new(sku).stock(location, 20.0).insert::<InventoryItem>(&db);
+        }
+        for (id, aisle, x, y) in [("A-01", "A", 2.5, 20.0), ("B-01", "B", 7.5, 5.0), ("C-01", "C", 12.5, 10.0)] {
+            db.execute(
+                "UPDATE locations SET aisle = ?, x_m = ?, y_m = ? WHERE id = ?",
+                params![aisle, x, y, id],
+            ).unwrap();
+        }
+        ShipmentBuilder::new("SHP-1").line_from("wid", 2.0, "A-01", None).line_from("bolt", 1.0, "C-01", None)
+            .insert::<Shipment>(&db);
+        ShipmentBuilder::new("SHP-2").line_from("wid", 3.0, "A-01", None).line_from("gear", 1.0, "B-01", None)
+            .insert::<Shipment>(&db);
+        let service = ShippingService::new(db);
+        
+        let ids = ["shp-1", "shp-2", "shp-1"].map(String::from);
+        let wave = service.generate_wave_pick_list(&ids).await.unwrap();
+        
+        assert_eq!(wave.shipment_ids, vec!["shp-1", "shp-2"]);
+        let skus: Vec<&str> = wave.lines.iter().map(|l| l.item_sku.as_str()).collect();
+        assert_eq!(skus, vec!["WID", "GEAR", "BOLT"]);
+        let split: Vec<(&str, f64)> = wave.lines[0].allocations.iter()
+            .map(|a| (a.shipment_item_id.as_str(), a.quantity))
+            .collect();
+        assert_eq!(wave.lines[0].quantity, 5.0);
+        assert_eq!(split, vec![("shp-1-line-1", 2.0), ("shp-2-line-1", 3.0)]);
+        
+        // One walk for the wave beats walking each shipment on its own
+        let mut separate = 0.0;
+        for id in ["shp-1", "shp-2"] {
+            separate += service.generate_pick_list(id).await.unwrap().estimated_distance_m.unwrap();
+        }
+        assert!(wave.estimated_distance_m.unwrap() < separate);
+        
+        assert!(service.generate_wave_pick_list(&[]).await.is_err());
+    }
+    
+    #[tokio::test]
+    async fn test_held_lot_is_reallocated_on_pick_list() {
+        let db = stocked_db();
+        // Only lotted stock, so the held lot can only be replaced by L2
+        db.execute("DELETE FROM inventory_stock WHERE item_id = 'bolt'", []).unwrap();
+        insert_location(&db, "loc2");
+        for (location, lot, quantity) in [("loc1", "L1", 5.0), ("loc2", "L2", 10.0)] {
+            db.execute(
+                "INSERT INTO inventory_stock (id, item_id, location_id, quantity, lot_number)
+                 VALUES (?, 'bolt', ?, ?, ?)",
+                params![new_id(), location, quantity, lot],
+            ).unwrap();
+        }
+        let service = ShippingService::new(db.clone());
+        let inventory = wms_inventory::InventoryService::new(db);
+        
+        let mut request = shipment_with_lines(&[("bolt", 4.0)]);
+        request.items[0].location_id = Some("loc1".to_string());
+        request.items[0].lot_number = Some("L1".to_string());
+        let shipment = service.create_shipment(request, None).await.unwrap();
+        
+        let before = service.generate_pick_list(&shipment.id).await.unwrap();
+        assert_eq!(before.lines[0].lot_number.as_deref(), Some("L1"));
+        
+        inventory.place_hold(&test_context(), "bolt", "L1", "Supplier recall").await.unwrap();
+        
+        let after = service.generate_pick_list(&shipment.id).await.unwrap();
+        assert_eq!(after.lines.len(), 1);
+        assert_eq!(after.lines[0].location_id.as_deref(), Some("loc2"));
+        assert_eq!(after.lines[0].lot_number.as_deref(), Some("L2"));
+        assert_eq!(after.lines[0].quantity, 4.0);
+        
+        // Explicit picks against the held lot fail with the hold reason
+        let pick = inventory.adjust_quantity(wms_inventory::InventoryAdjustment {
+            item_id: "bolt".to_string(),
+            location_id: Some("loc1".to_string()),
+            warehouse_id: None,
+            adjustment_type: wms_inventory::AdjustmentType::Pick,
+            quantity: 4.0,
+            lot_number: Some("L1".to_string()),
+            reason_code: None,
+            notes: None,
+            user_id: "u1".to_string(),
+        }).await;
+        assert!(matches!(pick, Err(WmsError::Validation(msg)) if msg.contains("Supplier recall")));
+        
+        let stock = inventory.get_item_stock_by_location("bolt").await.unwrap();
+        let held = stock.iter().find(|s| s.lot_number.as_deref() == Some("L1")).unwrap();
+        assert_eq!((held.held_quantity, held.available_quantity), (5.0, 0.0));
+        
+        // Scrapping the lot removes it from stock
+        let hold = inventory.list_holds(Some("bolt"), false).await.unwrap().remove(0);
+        inventory.release_hold(&test_context(), &hold.id, wms_inventory::HoldDisposition::Scrap, None).await.unwrap();
+        let stock = inventory.get_item_stock_by_location("bolt").await.unwrap();
+        assert!(stock.iter().all(|s| s.lot_number.as_deref() != Some("L1")));
+        assert!(inventory.list_holds(Some("bolt"), false).await.unwrap().is_empty());
+    }
+    
+    #[tokio::test]
+    async fn test_import_asn_and_reimport_updates_pending_receipt() {
+        let db = stocked_db();
+        db.execute("UPDATE inventory_items SET barcode = '012345678905' WHERE id = 'gadget'", []).unwrap();
+        let service = ShippingService::new(db);
+        
+        let asn = b"asn,supplier,expected_date,sku,gtin,description,qty
+ASN-1001,Acme Supply,2025-03-05,WID-1,,Blue widget,24
+ASN-1001,Acme Supply,2025-03-05,,00012345678905,Red gadget,6
+ASN-1001,Acme Supply,2025-03-05,ACME-99,,Mystery part,2
+";
+        
+        let preview = service.import_asn(&test_context(), asn, AsnFormat::Csv, true).await.unwrap();
+        assert!(preview.preview);
+        assert_eq!(preview.receipt.items.len(), 2);
+        assert_eq!(preview.unresolved.len(), 1);
+        assert_eq!(preview.unresolved[0].description.as_deref(), Some("Mystery part"));
+        let saved: i64 = service.db.query_row("SELECT COUNT(*) FROM receipts", [], |row| row.get(0))
+            .unwrap()
+            .unwrap();
+        assert_eq!(saved, 0);
+        
+        let first = service.import_asn(&test_context(), asn, AsnFormat::Csv, false).await.unwrap();
+        assert!(!first.updated_existing);
+        assert_eq!(first.receipt.status, ReceiptStatus::Pending);
+        assert_eq!(first.receipt.supplier_reference.as_deref(), Some("ASN-1001"));
+        assert_eq!(first.receipt.items.len(), 2);
+        
+        let revised = b"asn,supplier,sku,qty\nASN-1001,Acme Supply,WID-1,30\n";
+        let second = service.import_asn(&test_context(), revised, AsnFormat::Csv, false).await.unwrap();
+        assert!(second.updated_existing);
+        assert_eq!(second.receipt.id, first.receipt.id);
+        assert_eq!(second.receipt.items.len(), 1);
+        assert_eq!(second.receipt.items[0].quantity_expected, Some(30.0));
+        
+        service.db.execute("UPDATE receipts SET status = 'receiving'", []).unwrap();
+        let late = service.import_asn(&test_context(), revised, AsnFormat::Csv, false).await;
+        assert!(matches!(late, Err(WmsError::Conflict(_))));
+    }
+    
+    #[tokio::test]
+    async fn test_cold_receipt_reading_out_of_range_holds_lot() {
+        let db = stocked_db();
+        db.execute("UPDATE inventory_items SET category = 'Dairy' WHERE id = 'widget'", []).unwrap();
+        let service = ShippingService::new(db.clone());
+        service.temperature.save_range(wms_core::temperature::TemperatureRange {
+            category: "dairy".to_string(),
+            min_celsius: Some(0.0),
+            max_celsius: Some(5.0),
+        }).await.unwrap();
+        let asn = b"asn,supplier,sku,qty,lot\nASN-9,Acme Supply,WID-1,10,L5\nASN-9,Acme Supply,BLT-1,4,B1\n";
+        let imported = service.import_asn(&test_context(), asn, AsnFormat::Csv, false).await.unwrap().receipt;
+        let receipt = service.get_receipt(&imported.id).await.unwrap().unwrap();
+        let line = |item_id: &str| receipt.items.iter().find(|i| i.item_id == item_id).unwrap().clone();
+        assert!(line("widget").cold_chain);
+        assert!(!line("bolt").cold_chain);
+        let inventory = InventoryService::new(db);
+        
+        let ok = service.record_receipt_temperature(&test_context(), &line("widget").id, Some("P-1"), 3.5).await.unwrap();
+        assert!(!ok.out_of_range);
+        assert!(inventory.list_holds(Some("widget"), false).await.unwrap().is_empty());
+        
+        let warm = service.record_receipt_temperature(&test_context(), &line("widget").id, Some("P-1"), 9.0).await.unwrap();
+        assert!(warm.out_of_range);
+        let holds = inventory.list_holds(Some("widget"), false).await.unwrap();
+        assert_eq!(holds.len(), 1);
+        assert_eq!(holds[0].lot_number, "L5");
+        assert!(holds[0].reason.contains("9.0 °C"));
+        
+        // Further breaches keep the one hold; items without a range are never flagged
+        service.record_receipt_temperature(&test_context(), &line("widget").id, None, 11.0).await.unwrap();
+        assert_eq!(inventory.list_holds(Some("widget"), false).await.unwrap().len(), 1);
+        let bolt = service.record_receipt_temperature(&test_context(), &line("bolt").id, None, 30.0).await.unwrap();
+        assert!(!bolt.out_of_range);
+    }
+    
+    #[tokio::test]
+    async fn test_receipt_from_manifest_qr_prefills_lines() {
+        let db = stocked_db();
+        db.execute("UPDATE inventory_items SET barcode = '012345678905' WHERE id = 'gadget'", []).unwrap();
+        let service = ShippingService::new(db);
+        let payload = serde_json::json!({
+            "supplier_ref": "TRL-42",
+            "supplier": "Acme Supply",
+            "lines": [
+                { "gtin": "00012345678905", "qty": 6, "lot": "L7", "expiry": "2026-01-31" },
+                { "gtin": "96385074", "qty": 2 }
+            ]
+        });
+        
+        let import = service.create_receipt_from_manifest(&test_context(), &payload.to_string()).await.unwrap();
+        assert_eq!(import.receipt.supplier_reference.as_deref(), Some("TRL-42"));
+        assert_eq!(import.receipt.items.len(), 1);
+        let line = &import.receipt.items[0];
+        assert_eq!((line.item_id.as_str(), line.quantity_expected), ("gadget", Some(6.0)));
+        assert_eq!(line.lot_number.as_deref(), Some("L7"));
+        assert!(line.expiry_date.is_some());
+        assert_eq!(import.unresolved.len(), 1);
+        assert_eq!(import.unresolved[0].line_number, 2);
+        
+        let bad = serde_json::json!({ "supplier_ref": "TRL-43", "lines": [{ "gtin": "123", "qty": 1 }] });
+        let err = service.create_receipt_from_manifest(&test_context(), &bad.to_string()).await.unwrap_err();
+        assert!(err.to_string().contains("lines[0]"));
+    }
+    
+    #[tokio::test]
+    async fn test_blind_receipt_hides_expected_quantities_from_pickers() {
+        use crate::blind::{BlindReceivingMode, BlindReceivingSettings};
+        use wms_core::auth::{Role, Session};
+        
+        let service = ShippingService::new(stocked_db());
+        BlindReceivingSettings { mode: BlindReceivingMode::Always, tolerance_percent: 2.0 }
+            .save(&service.db)
+            .unwrap();
+        let as_role = |role| RequestContext::new(Some(&Session { user_id: TEST_USER_ID.to_string(), role }));
+        let (picker, supervisor) = (as_role(Role::Picker), as_role(Role::Supervisor));
+        
+        let asn = b"asn,supplier,expected_date,sku,gtin,description,qty
+ASN-2001,Acme Supply,2025-03-05,WID-1,,Blue widget,10
+";
+        let receipt = service.import_asn(&test_context(), asn, AsnFormat::Csv, false).await.unwrap().receipt;
+        
+        let blind = service.get_receipt_for(&picker, &receipt.id).await.unwrap();
+        assert!(blind.items.iter().all(|i| i.quantity_expected.is_none()));
+        assert!(!serde_json::to_string(&blind).unwrap().contains("quantity_expected"));
+        let full = service.get_receipt_for(&supervisor, &receipt.id).await.unwrap();
+        assert_eq!(full.items[0].quantity_expected, Some(10.0));
+        
+        // The picker's count is judged against the stored expectation
+        let mut line = blind.items[0].clone();
+        line.quantity_received = 8.0;
+        let counted = service.process_receipt_item(&receipt.id, line, false, None).await.unwrap();
+        assert_eq!(counted.items[0].status, ReceiptItemStatus::Partial);
+        
+        let held = service.complete_receipt(&picker, &receipt.id).await.unwrap();
+        assert_eq!(held.receipt.status, ReceiptStatus::Receiving);
+        assert!(held.receipt.items[0].quantity_expected.is_none());
+        let report = held.variances.unwrap();
+        assert!(report.awaiting_acknowledgment());
+        assert_eq!((report.lines[0].quantity_counted, report.lines[0].variance), (8.0, None));
+        
+        assert!(service.acknowledge_receipt_variances(&picker, &receipt.id).await.is_err());
+        let done = service.acknowledge_receipt_variances(&supervisor, &receipt.id).await.unwrap();
+        assert_eq!(done.receipt.status, ReceiptStatus::Completed);
+        assert_eq!(done.receipt.variance_acknowledged_by.as_deref(), Some(TEST_USER_ID));
+        assert_eq!(done.variances.unwrap().lines[0].variance, Some(-2.0));
+    }
+    
+    #[tokio::test]
+    async fn test_over_receipt_needs_a_single_use_override() {
+        use wms_core::overrides::{OverrideApproval, OverrideRequest};
+        
+        let service = ShippingService::new(stocked_db());
+        OverReceiptSettings { tolerance_percent: Some(10.0) }.save(&service.db).unwrap();
+        let asn = b"asn,supplier,expected_date,sku,gtin,description,qty
+ASN-3001,Acme Supply,2025-03-05,WID-1,,Blue widget,10
+";
+        let receipt = service.import_asn(&test_context(), asn, AsnFormat::Csv, false).await.unwrap().receipt;
+        let mut line = receipt.items[0].clone();
+        
+        line.quantity_received = 11.0;
+        service.process_receipt_item(&receipt.id, line.clone(), true, None).await.unwrap();
+        line.quantity_received = 14.0;
+        let err = service.process_receipt_item(&receipt.id, line.clone(), true, None).await.unwrap_err();
+        assert!(err.to_string().contains("override"), "{}", err);
+        
+        let token = service.overrides.request_override(&test_context(), OverrideRequest {
+            action: OverrideAction::OverReceipt,
+            entity_type: RECEIPT_LINE_OWNER.to_string(),
+            entity_id: line.id.clone(),
+            reason_code: "SUPPLIER_OVERSHIP".to_string(),
+            note: None,
+            approval: OverrideApproval::Session,
+        }).await.unwrap();
+        let received = service.process_receipt_item(&receipt.id, line.clone(), true, Some(&token)).await.unwrap();
+        assert_eq!(received.items[0].quantity_received, 14.0);
+        
+        // The approval covered that one receipt of the line, not the next
+        line.quantity_received = 15.0;
+        assert!(service.process_receipt_item(&receipt.id, line, true, Some(&token)).await.is_err());
+    }
+    
+    #[tokio::test]
+    async fn test_over_receipt_needs_the_flag_and_is_recorded() {
+        let service = ShippingService::new(stocked_db());
+        let asn = b"asn,supplier,expected_date,sku,gtin,description,qty
+ASN-3002,Acme Supply,2025-03-05,WID-1,,Blue widget,10
+";
+        let receipt = service.import_asn(&test_context(), asn, AsnFormat::Csv, false).await.unwrap().receipt;
+        let mut line = receipt.items[0].clone();
+        
+        line.quantity_received = 12.0;
+        let err = service.process_receipt_item(&receipt.id, line.clone(), false, None).await.unwrap_err();
+        assert!(err.to_string().contains("more than the 10 expected"), "{}", err);
+        assert!(service.get_receipt_discrepancies(&receipt.id).await.unwrap().is_empty());
+        
+        service.process_receipt_item(&receipt.id, line.clone(), true, None).await.unwrap();
+        let discrepancies = service.get_receipt_discrepancies(&receipt.id).await.unwrap();
+        assert_eq!(discrepancies.len(), 1);
+        assert_eq!(discrepancies[0].discrepancy_type, DiscrepancyType::OverReceipt);
+        assert_eq!((discrepancies[0].quantity_expected, discrepancies[0].quantity_received), (10.0, 12.0));
+        
+        // A recount back to the expected quantity clears it
+        line.quantity_received = 10.0;
+        service.process_receipt_item(&receipt.id, line, false, None).await.unwrap();
+        assert!(service.get_receipt_discrepancies(&receipt.id).await.unwrap().is_empty());
+    }
+    
+    #[tokio::test]
+    async fn test_gs1_carton_label_prefills_receipt_line() {
+        let service = ShippingService::new(stocked_db());
+        service.db.execute("UPDATE inventory_items SET barcode = '0012345678905' WHERE id = 'widget'", []).unwrap();
+        let asn = b"asn,supplier,expected_date,sku,gtin,description,qty
+ASN-3001,Acme Supply,2025-03-05,WID-1,,Blue widget,24
+ASN-3001,Acme Supply,2025-03-05,BLT-1,,Bolt,10
+";
+        let receipt = service.import_asn(&test_context(), asn, AsnFormat::Csv, false).await.unwrap().receipt;
+        let line = |item_id: &str| receipt.items.iter().find(|l| l.item_id == item_id).unwrap().clone();
+        // (01) GTIN, (17) expiry at the end of March 2026, (10) lot, (30) count
+        let label = "]C101000123456789051726030010L-77\u{1d}3024";
+        
+        let err = service.process_receipt_gs1(&receipt.id, line("bolt"), label, false, None).await.unwrap_err();
+        assert!(err.to_string().contains("not the item"), "{}", err);
+        
+        let received = service.process_receipt_gs1(&receipt.id, line("widget"), label, false, None).await.unwrap();
+        let widget = received.items.iter().find(|l| l.item_id == "widget").unwrap();
+        assert_eq!(widget.lot_number.as_deref(), Some("L-77"));
+        assert_eq!(widget.expiry_date.map(|d| d.date_naive()), NaiveDate::from_ymd_opt(2026, 3, 31));
+        assert_eq!((widget.quantity_received, widget.status), (24.0, ReceiptItemStatus::Complete));
+    }
+    
+    #[tokio::test]
+    async fn test_resolve_scan_matches_barcode_then_sku() {
+        let service = ShippingService::new(stocked_db());
+        service.db.execute("UPDATE inventory_items SET barcode = '4006381333931' WHERE id = 'widget'", []).unwrap();
+        let asn = b"asn,supplier,expected_date,sku,gtin,description,qty
+ASN-3002,Acme Supply,2025-03-05,WID-1,,Blue widget,24
+ASN-3002,Acme Supply,2025-03-05,BLT-1,,Bolt,10
+";
+        let receipt = service.import_asn(&test_context(), asn, AsnFormat::Csv, false).await.unwrap().receipt;
+        let ctx = test_context();
+        let resolve = |barcode: &'static str| service.resolve_scan(&ctx, &receipt.id, barcode);
+        
+        let ReceiptScanMatch::OnReceipt { item, line, remaining_quantity, suggested_location } =
+            resolve("4006381333931").await.unwrap()
+        else {
+            panic!("widget is on the receipt");
+        };
+        assert_eq!((item.id.as_str(), line.item_id.as_str()), ("widget", "widget"));
+        assert_eq!(remaining_quantity, Some(24.0));
+        assert_eq!(suggested_location.map(|l| l.id), Some("loc1".to_string()));
+        
+        // No item has the bolt's SKU as its barcode
+        let by_sku = resolve("BLT-1").await.unwrap();
+        assert!(matches!(by_sku, ReceiptScanMatch::OnReceipt { ref item, .. } if item.id == "bolt"));
+        
+        let unexpected = resolve("GAD-1").await.unwrap();
+        assert!(matches!(unexpected, ReceiptScanMatch::UnexpectedItem { ref item } if item.id == "gadget"));
+        assert!(resolve("NO-SUCH-CODE").await.is_err());
+    }
+    
+    #[tokio::test]
+    async fn test_completing_a_receipt_posts_stock_and_quarantines_damage() {
+        let db = stocked_db();
+        db.execute("INSERT INTO locations (id, code, zone) VALUES ('qa1', 'QA-01', 'QUARANTINE')", []).unwrap();
+        let service = ShippingService::new(db);
+        let stock_at = |item_id: &str, location_id: &str| -> f64 {
+            service.db.query_row(
+                "SELECT COALESCE(SUM(quantity), 0) FROM inventory_stock WHERE item_id = ? AND location_id = ?",
+                params![item_id, location_id],
+                |row| row.get(0),
+            ).unwrap().unwrap()
+        };
+        let asn = b"asn,supplier,expected_date,sku,gtin,description,qty
+ASN-3003,Acme Supply,2025-03-05,WID-1,,Blue widget,10
+ASN-3003,Acme Supply,2025-03-05,BLT-1,,Bolt,20
+";
+        let receipt = service.import_asn(&test_context(), asn, AsnFormat::Csv, false).await.unwrap().receipt;
+        
+        for mut line in receipt.items.clone() {
+            line.quantity_received = line.quantity_expected.unwrap();
+            if line.item_id == "widget" {
+                line.quantity_damaged = 3.0;
+            }
+            service.process_receipt_item(&receipt.id, line, false, None).await.unwrap();
+        }
+        // Some bolts are put away before the receipt is closed
+        service.scan_putaway(&test_context(), &receipt.id, "BLT-1", "loc1", 5.0, false).await.unwrap();
+        
+        let done = service.complete_receipt(&test_context(), &receipt.id).await.unwrap();
+        assert_eq!(done.receipt.status, ReceiptStatus::Completed);
+        assert_eq!(stock_at("widget", "loc1"), 6.0 + 7.0);
+        assert_eq!(stock_at("widget", "qa1"), 3.0);
+        assert_eq!(stock_at("bolt", "loc1"), 50.0 + 20.0);
+        
+        assert!(service.complete_receipt(&test_context(), &receipt.id).await.is_err());
+        assert_eq!(stock_at("widget", "loc1"), 13.0);
+        
+        // A completed receipt can't be reopened by recounting or putting away
+        let line = done.receipt.items[0].clone();
+        assert!(service.process_receipt_item(&receipt.id, line, false, None).await.is_err());
+        assert!(service.scan_putaway(&test_context(), &receipt.id, "BLT-1", "loc1", 1.0, false).await.is_err());
+        let receipt = service.get_receipt(&receipt.id).await.unwrap().unwrap();
+        assert_eq!(receipt.status, ReceiptStatus::Completed);
+        assert_eq!(stock_at("bolt", "loc1"), 70.0);
+    }
+    
+    #[tokio::test]
+    async fn test_failed_receipt_completion_posts_nothing() {
+        let service = ShippingService::new(stocked_db());
+        let asn = b"asn,supplier,expected_date,sku,gtin,description,qty
+ASN-3004,Acme Supply,2025-03-05,WID-1,,Blue widget,10
+ASN-3004,Acme Supply,2025-03-05,BLT-1,,Bolt,20
+";
+        let receipt = service.import_asn(&test_context(), asn, AsnFormat::Csv, false).await.unwrap().receipt;
+        for mut line in receipt.items.clone() {
+            line.quantity_received = line.quantity_expected.unwrap();
+            service.process_receipt_item(&receipt.id, line, false, None).await.unwrap();
+        }
+        // Posting the bolts fails after the widgets have gone in
+        service.db.execute(
+            "CREATE TRIGGER fail_bolts BEFORE INSERT ON inventory_transactions WHEN NEW.item_id = 'bolt'
+             BEGIN SELECT RAISE(ABORT, 'disk full'); END",
+            [],
+        ).unwrap();
+        
+        assert!(service.complete_receipt(&test_context(), &receipt.id).await.is_err());
+        let widgets: f64 = service.db.query_row(
+            "SELECT SUM(quantity) FROM inventory_stock WHERE item_id = 'widget'",
+            [],
+            |row| row.get(0),
+        ).unwrap().unwrap();
+        assert_eq!(widgets, 6.0);
+        let reopened = service.get_receipt(&receipt.id).await.unwrap().unwrap();
+        assert_ne!(reopened.status, ReceiptStatus::Completed);
+    }
+    
+    #[tokio::test]
+    async fn test_pallet_nesting_stays_consistent() {
+        use crate::sscc::is_valid_sscc;
+        
+        let service = ShippingService::new(test_db());
+        let shipment = service.create_shipment(draft_shipment(), None).await.unwrap();
+        let other = service.create_shipment(draft_shipment(), None).await.unwrap();
+        
+        let mut lpns = Vec::new();
+        for weight in [10.0, 12.5, 4.0, 3.0] {
+            let carton = service.add_package(&shipment.id, NewPackage {
+                weight_kg: Some(weight),
+                ..Default::default()
+            }).await.unwrap();
+            assert!(is_valid_sscc(carton.lpn.as_deref().unwrap()));
+            service.close_package(&carton.id).await.unwrap();
+            lpns.push(carton.lpn.unwrap());
+        }
+        let foreign = service.add_package(&other.id, NewPackage::default()).await.unwrap();
+        service.close_package(&foreign.id).await.unwrap();
+        let open = service.add_package(&shipment.id, NewPackage::default()).await.unwrap();
+        
+        // Only this shipment's closed, loose cartons go on a pallet
+        let build = |lpns: &[&String]| lpns.iter().map(|l| l.to_string()).collect::<Vec<_>>();
+        for bad in [build(&[&lpns[0], foreign.lpn.as_ref().unwrap()]), build(&[open.lpn.as_ref().unwrap()]), build(&[&lpns[0], &lpns[0]])] {
+            assert!(service.build_pallet(&shipment.id, &bad).await.is_err());
+        }
+        let first = service.build_pallet(&shipment.id, &build(&[&lpns[0], &lpns[1]])).await.unwrap();
+        assert_eq!(first.weight_kg, Some(22.5));
+        assert!(service.build_pallet(&shipment.id, &build(&[&lpns[1], &lpns[2]])).await.is_err());
+        let first_lpn = first.lpn.unwrap();
+        let second = service.build_pallet(&shipment.id, &build(&[&lpns[2]])).await.unwrap();
+        let second_lpn = second.lpn.unwrap();
+        
+        // Carton 4 is still loose
+        service.close_package(&open.id).await.unwrap();
+        service.set_floor_loaded(open.lpn.as_deref().unwrap(), true).await.unwrap();
+        advance_to(&service, &shipment.id, ShipmentStatus::Packed).await;
+        let err = service.update_status(&shipment.id, ShipmentStatus::Shipped).await.unwrap_err();
+        assert!(err.to_string().contains(&lpns[3]), "{}", err);
+        
+        let moved = service.move_carton(&lpns[1], &second_lpn).await.unwrap();
+        assert_eq!(moved.weight_kg, Some(16.5));
+        service.move_carton(&lpns[3], &first_lpn).await.unwrap();
+        // Moving the last carton off a pallet removes it
+        service.move_carton(&lpns[0], &second_lpn).await.unwrap();
+        service.move_carton(&lpns[3], &second_lpn).await.unwrap();
+        assert!(service.generate_pallet_label(&first_lpn).await.is_err());
+        
+        let label = service.generate_pallet_label(&second_lpn).await.unwrap();
+        assert_eq!((label.label_type, label.package_id.as_deref()), (LabelType::Pallet, Some(second.id.as_str())));
+        let zpl = String::from_utf8(base64::engine::general_purpose::STANDARD.decode(&label.data).unwrap()).unwrap();
+        assert!(zpl.contains(&format!(">;>800{}", second_lpn)));
+        
+        let released = service.unbuild_pallet(&second_lpn).await.unwrap();
+        assert_eq!(released.len(), 4);
+        assert!(released.iter().all(|c| c.parent_package_id.is_none()));
+        let packages = service.get_shipment(&shipment.id).await.unwrap().unwrap().packages;
+        assert!(packages.iter().all(|p| !p.is_pallet()));
+        
+        let pallet = service.build_pallet(&shipment.id, &build(&lpns.iter().collect::<Vec<_>>())).await.unwrap();
+        assert_eq!(pallet.weight_kg, Some(29.5));
+        service.update_status(&shipment.id, ShipmentStatus::Shipped).await.unwrap();
+        assert!(service.unbuild_pallet(pallet.lpn.as_deref().unwrap()).await.is_err());
+    }
+    
+    #[tokio::test]
+    async fn test_failed_update_emits_nothing() {
+        let events = Arc::new(RecordingEmitter::new());
+        let service = ShippingService::new(test_db()).with_event_emitter(events.clone());
+        
+        assert!(service.update_status("missing", ShipmentStatus::Shipped).await.is_err());
+        assert!(events.events().is_empty());
+    }
+    
+    #[tokio::test]
+    async fn test_invoice_reimport_replaces_rows() {
+        let service = ShippingService::new(test_db());
+        let shipment = service.create_shipment(draft_shipment(), None).await.unwrap();
+        service.db.execute(
+            "UPDATE shipments SET carrier_id = 'car_ups', tracking_number = '1ZA', shipping_cost = 10.0,
+                ship_date = '2025-03-03 10:00:00' WHERE id = ?",
+            params![&shipment.id],
+        ).unwrap();
+        
+        let invoice = b"tracking_number,billed_amount,surcharge_fuel\n1ZA,11.00,1.00\n1ZX,5.00,\n";
+        let first = service.import_carrier_invoice("car_ups", invoice, &CancellationToken::new()).await.unwrap();
+        assert_eq!((first.imported, first.updated, first.matched), (2, 0, 1));
+        assert_eq!(first.unmatched[0].tracking_number, "1ZX");
+        
+        let corrected = b"tracking_number,billed_amount,surcharge_fuel\n1ZA,12.00,1.00\n";
+        let second = service.import_carrier_invoice("car_ups", corrected, &CancellationToken::new()).await.unwrap();
+        assert_eq!((second.imported, second.updated), (0, 1));
+        
+        let period = DateRange::new(
+            chrono::NaiveDate::from_ymd_opt(2025, 3, 1).unwrap(),
+            chrono::NaiveDate::from_ymd_opt(2025, 3, 31).unwrap(),
+        );
+        let report = service.get_cost_reconciliation(period, None).await.unwrap();
+        assert_eq!(report.lines.len(), 1);
+        assert_eq!(report.lines[0].billed_total, 13.0);
+        assert!(report.lines[0].flagged);
+        assert_eq!(report.carriers[0].billed_total, 13.0);
+        
+        assert!(service.import_carrier_invoice("car_nope", corrected, &CancellationToken::new()).await.is_err());
+        
+        // A cancelled import leaves the earlier rows as they were
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let recorrected = b"tracking_number,billed_amount\n1ZA,99.00\n";
+        let err = service.import_carrier_invoice("car_ups", recorrected, &cancel).await.unwrap_err();
+        assert!(matches!(err, WmsError::Cancelled(_)));
+        let report = service.get_cost_reconciliation(period, None).await.unwrap();
+        assert_eq!(report.lines[0].billed_total, 13.0);
+    }
+    
+    #[tokio::test]
+    async fn test_stale_status_change_conflicts() {
+        let service = ShippingService::new(test_db());
+        let loaded = service.create_shipment(draft_shipment(), None).await.unwrap();
+        assert_eq!(loaded.version, 1);
+        
+        // Two users open the same shipment; the first one saves
+        let first = service.update_status_at_version(&loaded.id, ShipmentStatus::Confirmed, loaded.version).await.unwrap();
+        assert!(first.version > loaded.version);
+        
+        // The second one still holds the old version
+        let second = service.update_status_at_version(&loaded.id, ShipmentStatus::Picking, loaded.version).await;
+        match second {
+            Err(WmsError::VersionConflict { current, .. }) => {
+                assert_eq!(current["status"], "confirmed");
+                assert_eq!(current["version"], first.version);
+            }
+            other => panic!("expected a version conflict, got {:?}", other.map(|s| s.status)),
+        }
+        
+        // Overwriting with the current version goes through
+        let overwritten = service.update_status_at_version(&loaded.id, ShipmentStatus::Picking, first.version).await.unwrap();
+        assert_eq!(overwritten.status, ShipmentStatus::Picking);
+    }
+    
+    #[tokio::test]
+    async fn test_status_change_is_undone_when_sync_cannot_record_it() {
+        let db = test_db();
+        let service = ShippingService::new(db.clone());
+        let shipment = service.create_shipment(draft_shipment(), None).await.unwrap();
+        
+        let failing = ShippingService::new(db).with_change_recorder(Arc::new(FailingRecorder));
+        assert!(failing.update_status(&shipment.id, ShipmentStatus::Confirmed).await.is_err());
+        let after = service.get_shipment(&shipment.id).await.unwrap().unwrap();
+        assert_eq!((after.status, after.version), (ShipmentStatus::Draft, shipment.version));
+    }
+    
+    #[tokio::test]
+    async fn test_every_illegal_status_change_is_rejected() {
+        use ShipmentStatus::*;
+        let db = test_db();
+        let service = ShippingService::new(db.clone());
+        let statuses = [Draft, Confirmed, Picking, Packed, Shipped, Delivered, Cancelled];
+        
+        let mut rejected = 0;
+        for from in statuses {
+            for to in statuses.into_iter().filter(|to| !from.allowed_next().contains(to)) {
+                rejected += 1;
+                let number = format!("SHP-{:02}", rejected);
+                let id: String = ShipmentBuilder::new(&number).status(from.as_str()).insert::<Shipment>(&db).id;
+                let before = service.get_shipment(&id).await.unwrap().unwrap();
+                
+                match service.update_status(&id, to).await {
+                    Err(WmsError::InvalidTransition { from: was, to: wanted, allowed }) => {
+                        assert_eq!((was.as_str(), wanted.as_str()), (from.as_str(), to.as_str()));
+                        assert_eq!(allowed, from.allowed_next().iter().map(|s| s.as_str()).collect::<Vec<_>>());
+                    }
+                    other => panic!("{:?} to {:?} went through: {:?}", from, to, other.map(|s| s.status)),
+                }
+                let after = service.get_shipment(&id).await.unwrap().unwrap();
+                assert_eq!((after.status, after.version), (from, before.version));
+            }
+        }
+        // 49 pairs less the 9 allowed moves
+        assert_eq!(rejected, 40);
+        
+        let err = Draft.check_transition(Delivered).unwrap_err();
+        assert_eq!(err.to_string(), "Cannot change status from draft to delivered; it can move to confirmed, cancelled");
+        let err = Shipped.check_transition(Cancelled).unwrap_err();
+        assert_eq!(err.to_string(), "Cannot change status from shipped to cancelled; it can move to delivered");
+    }
+    
+    #[tokio::test]
+    async fn test_cancel_after_partial_pick_restores_stock() {
+        let service = ShippingService::new(stocked_db()).with_clock(fixed_clock());
+        let shipment = service.create_shipment(shipment_with_lines(&[("bolt", 5.0), ("widget", 2.0)]), None).await.unwrap();
+        service.update_status(&shipment.id, ShipmentStatus::Confirmed).await.unwrap();
+        service.pick_item(&test_context(), &shipment.id, "bolt", "loc1", 3.0, false).await.unwrap();
+        let label = service.generate_label(&shipment.id, LabelFormat::Zpl).await.unwrap();
+        let stock = |item: &str| -> f64 {
+            service.db.query_row(
+                "SELECT SUM(quantity) FROM inventory_stock WHERE item_id = ? AND location_id = 'loc1'",
+                params![item],
+                |row| row.get(0),
+            ).unwrap().unwrap()
+        };
+        assert_eq!(stock("bolt"), 47.0);
+        
+        assert!(matches!(
+            service.cancel_shipment(&test_context(), &shipment.id, "  ").await,
+            Err(WmsError::Validation(_))
+        ));
+        // A plain status change can't cancel without a reason
+        assert!(matches!(
+            service.update_status(&shipment.id, ShipmentStatus::Cancelled).await,
+            Err(WmsError::Validation(_))
+        ));
+        assert_eq!(stock("bolt"), 47.0);
+        let cancelled = service.cancel_shipment(&test_context(), &shipment.id, "Customer changed the order").await.unwrap();
+        assert_eq!(cancelled.status, ShipmentStatus::Cancelled);
+        assert_eq!(cancelled.cancel_reason.as_deref(), Some("Customer changed the order"));
+        assert_eq!(cancelled.cancelled_at, Some(fixture_time()));
+        assert!(!cancelled.label_printed);
+        assert!(cancelled.items.iter().all(|l| l.quantity_picked == 0.0 && l.status == ShipmentItemStatus::Pending));
+        
+        // The 3 picked bolts are back; the unpicked widgets were never taken
+        assert_eq!((stock("bolt"), stock("widget")), (50.0, 6.0));
+        let unpicks: Vec<(String, f64, String)> = service.db.query_map(
+            "SELECT item_id, quantity, user_id FROM inventory_transactions WHERE transaction_type = 'UNPICK'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        ).unwrap();
+        assert_eq!(unpicks, vec![("bolt".to_string(), 3.0, TEST_USER_ID.to_string())]);
+        
+        let voided: Option<Option<String>> = service.db.query_row(
+            "SELECT voided_at FROM shipping_labels WHERE id = ?",
+            params![&label.id],
+            |row| row.get(0),
+        ).unwrap();
+        assert!(voided.flatten().is_some());
+        
+        // Cancelling twice doesn't put the stock back twice
+        let err = service.cancel_shipment(&test_context(), &shipment.id, "Again").await.unwrap_err();
+        assert!(matches!(err, WmsError::InvalidTransition { .. }));
+        assert_eq!(stock("bolt"), 50.0);
+    }
+    
+    fn pick_transactions(service: &ShippingService) -> i64 {
+        service.db.query_row(
+            "SELECT COUNT(*) FROM inventory_transactions WHERE transaction_type = 'PICK'",
+            [],
+            |row| row.get(0),
+        ).unwrap().unwrap()
+    }
+    
+    #[tokio::test]
+    async fn test_rapid_duplicate_scans_pick_once() {
+        let service = ShippingService::new(stocked_db());
+        let shipment = service.create_shipment(shipment_with_lines(&[("bolt", 5.0)]), None).await.unwrap();
+        service.update_status(&shipment.id, ShipmentStatus::Confirmed).await.unwrap();
+        
+        // Trigger held down: the same barcode arrives twice at once
+        let ctx = test_context();
+        let (first, second) = tokio::join!(
+            service.scan_pick_item(&ctx, &shipment.id, "BLT-1", 1.0, false),
+            service.scan_pick_item(&ctx, &shipment.id, "BLT-1", 1.0, false),
+        );
+        let results = [first.unwrap(), second.unwrap()];
+        
+        let processed = results.iter().filter(|r| matches!(r, ScanResult::Processed { .. })).count();
+        let duplicates = results.iter().filter(|r| matches!(r, ScanResult::Duplicate { .. })).count();
+        assert_eq!((processed, duplicates), (1, 1));
+        assert_eq!(pick_transactions(&service), 1);
+        
+        // A second identical carton is confirmed explicitly
+        match service.scan_pick_item(&test_context(), &shipment.id, "BLT-1", 1.0, true).await.unwrap() {
+            ScanResult::Processed { result, .. } => assert_eq!(result.quantity_picked, 2.0),
+            ScanResult::Duplicate { message, .. } => panic!("unexpected duplicate: {}", message),
+        }
+        assert_eq!(pick_transactions(&service), 2);
+        
+        let history = service.get_scan_history(&shipment.id).await.unwrap();
+        assert_eq!(history.len(), 2);
+        assert!(history.iter().all(|s| s.context == ScanContext::Pick && s.entity_id == "bolt"));
+    }
+    
+    #[tokio::test]
+    async fn test_pick_rechecks_line_and_shipment_when_written() {
+        let service = ShippingService::new(stocked_db());
+        let shipment = service.create_shipment(shipment_with_lines(&[("bolt", 3.0)]), None).await.unwrap();
+        service.update_status(&shipment.id, ShipmentStatus::Confirmed).await.unwrap();
+        let from_loc1 = |quantity: f64| vec![(Some("loc1".to_string()), None::<String>, quantity)];
+        
+        // Two handhelds read the line before either picks
+        let first = service.line_to_pick(&shipment.id, "bolt", 2.0, false).await.unwrap();
+        let second = service.line_to_pick(&shipment.id, "bolt", 2.0, false).await.unwrap();
+        let picked = service.apply_pick(first, from_loc1(2.0), TEST_USER_ID, false).await.unwrap();
+        assert_eq!(picked.quantity_picked, 2.0);
+        assert!(matches!(
+            service.apply_pick(second, from_loc1(2.0), TEST_USER_ID, false).await,
+            Err(WmsError::Validation(_))
+        ));
+        assert_eq!(pick_transactions(&service), 1);
+        let picking = service.get_shipment(&shipment.id).await.unwrap().unwrap();
+        assert_eq!(picking.status, ShipmentStatus::Picking);
+        assert_eq!(picking.items[0].quantity_picked, 2.0);
+        
+        // A pick read before the shipment was cancelled takes nothing
+        let stale = service.line_to_pick(&shipment.id, "bolt", 1.0, false).await.unwrap();
+        service.cancel_shipment(&test_context(), &shipment.id, "Customer cancelled").await.unwrap();
+        assert!(service.apply_pick(stale.clone(), from_loc1(1.0), TEST_USER_ID, false).await.is_err());
+        assert!(service.short_pick_item(&test_context(), &stale.id, "Damaged").await.is_err());
+        let bolts: f64 = service.db.query_row(
+            "SELECT SUM(quantity) FROM inventory_stock WHERE item_id = 'bolt'",
+            [],
+            |row| row.get(0),
+        ).unwrap().unwrap();
+        assert_eq!(bolts, 50.0);
+    }
+    
+    #[tokio::test]
+    async fn test_repeat_scan_outside_window_is_processed() {
+        let service = ShippingService::new(stocked_db())
+            .with_duplicate_scan_window(std::time::Duration::ZERO);
+        let shipment = service.create_shipment(shipment_with_lines(&[("bolt", 2.0)]), None).await.unwrap();
+        service.update_status(&shipment.id, ShipmentStatus::Confirmed).await.unwrap();
+        
+        for _ in 0..2 {
+            let result = service.scan_pick_item(&test_context(), &shipment.id, "BLT-1", 1.0, false).await.unwrap();
+            assert!(matches!(result, ScanResult::Processed { .. }));
+        }
+        assert_eq!(pick_transactions(&service), 2);
+        
+        // Nothing left to pick; rejected scans are not recorded
+        assert!(service.scan_pick_item(&test_context(), &shipment.id, "BLT-1", 1.0, false).await.is_err());
+        assert_eq!(service.get_scan_history(&shipment.id).await.unwrap().len(), 2);
+    }
+    
+    #[tokio::test]
+    async fn test_correcting_a_pick_records_a_mispick() {
+        let service = ShippingService::new(stocked_db());
+        let shipment = service.create_shipment(shipment_with_lines(&[("bolt", 3.0)]), None).await.unwrap();
+        service.update_status(&shipment.id, ShipmentStatus::Confirmed).await.unwrap();
+        let line = match service.scan_pick_item(&test_context(), &shipment.id, "BLT-1", 2.0, false).await.unwrap() {
+            ScanResult::Processed { result, .. } => result,
+            ScanResult::Duplicate { message, .. } => panic!("unexpected duplicate: {}", message),
+        };
+        
+        let corrected = service.correct_pick(&test_context(), &line.id, 1.0, Some("wrong size")).await.unwrap();
+        assert_eq!(corrected.quantity_picked, 1.0);
+        assert_eq!(corrected.status, ShipmentItemStatus::Picking);
+        
+        let (picked_by, reason): (String, String) = service.db.query_row(
+            "SELECT picked_by, reason FROM mispicks WHERE shipment_item_id = ?",
+            params![&line.id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        ).unwrap().unwrap();
+        assert_eq!((picked_by.as_str(), reason.as_str()), (TEST_USER_ID, "wrong size"));
+        
+        // Can't put back more than is still picked
+        assert!(service.correct_pick(&test_context(), &line.id, 2.0, None).await.is_err());
+        assert!(service.correct_pick(&test_context(), "nope", 1.0, None).await.is_err());
+    }
+    
+    #[tokio::test]
+    async fn test_correcting_a_pick_on_a_shipped_shipment_fails() {
+        let service = ShippingService::new(stocked_db());
+        let shipment = service.create_shipment(shipment_with_lines(&[("bolt", 3.0)]), None).await.unwrap();
+        service.update_status(&shipment.id, ShipmentStatus::Confirmed).await.unwrap();
+        let line = service.pick_item(&test_context(), &shipment.id, "bolt", "loc1", 3.0, false).await.unwrap();
+        service.db.execute("UPDATE shipments SET status = 'shipped' WHERE id = ?", params![&shipment.id]).unwrap();
+        
+        let err = service.correct_pick(&test_context(), &line.id, 1.0, None).await.unwrap_err();
+        assert!(matches!(err, WmsError::Validation(_)), "{}", err);
+        let shipment = service.get_shipment(&shipment.id).await.unwrap().unwrap();
+        assert_eq!(shipment.items[0].quantity_picked, 3.0);
+        let mispicks: i64 = service.db.query_row("SELECT COUNT(*) FROM mispicks", [], |row| row.get(0)).unwrap().unwrap();
+        assert_eq!(mispicks, 0);
+    }
+    
+    #[tokio::test]
+    async fn test_partial_picks_advance_the_line() {
+        let service = ShippingService::new(stocked_db());
+        let shipment = service.create_shipment(shipment_with_lines(&[("bolt", 5.0)]), None).await.unwrap();
+        
+        // Draft shipments aren't ready to pick
+        assert!(service.pick_item(&test_context(), &shipment.id, "bolt", "loc1", 2.0, false).await.is_err());
+        service.update_status(&shipment.id, ShipmentStatus::Confirmed).await.unwrap();
+        
+        let line = service.pick_item(&test_context(), &shipment.id, "bolt", "loc1", 2.0, false).await.unwrap();
+        assert_eq!((line.quantity_picked, line.status), (2.0, ShipmentItemStatus::Picking));
+        assert_eq!(line.location_id.as_deref(), Some("loc1"));
+        assert_eq!(line.picked_by.as_deref(), Some(TEST_USER_ID));
+        let shipment = service.get_shipment(&shipment.id).await.unwrap().unwrap();
+        assert_eq!(shipment.status, ShipmentStatus::Picking);
+        
+        let line = service.pick_item(&test_context(), &shipment.id, "bolt", "loc1", 3.0, false).await.unwrap();
+        assert_eq!((line.quantity_picked, line.status), (5.0, ShipmentItemStatus::Picked));
+        assert_eq!(pick_transactions(&service), 2);
+        
+        let stock: f64 = service.db.query_row(
+            "SELECT SUM(quantity) FROM inventory_stock WHERE item_id = 'bolt' AND location_id = 'loc1'",
+            [],
+            |row| row.get(0),
+        ).unwrap().unwrap();
+        assert_eq!(stock, 45.0);
+    }
+    
+    #[tokio::test]
+    async fn test_pick_needs_stock_at_the_location() {
+        let db = stocked_db();
+        insert_location(&db, "loc2");
+        let service = ShippingService::new(db);
+        let shipment = service.create_shipment(shipment_with_lines(&[("widget", 2.0)]), None).await.unwrap();
+        service.update_status(&shipment.id, ShipmentStatus::Confirmed).await.unwrap();
+        
+        // All 6 widgets are at loc1
+        let err = service.pick_item(&test_context(), &shipment.id, "widget", "loc2", 2.0, false).await.unwrap_err();
+        assert!(err.to_string().contains("Only 0 available at location loc2"), "{}", err);
+        assert_eq!(pick_transactions(&service), 0);
+        let shipment = service.get_shipment(&shipment.id).await.unwrap().unwrap();
+        assert_eq!(shipment.items[0].quantity_picked, 0.0);
+    }
+    
+    #[tokio::test]
+    async fn test_fefo_pick_takes_soonest_lots_first() {
+        let db = test_db();
+        let date = |m, d| NaiveDate::from_ymd_opt(2025, m, d);
+        ItemBuilder::new("MILK-1").id("milk")
+            .lot("loc1", "LATE", 10.0, date(3, 1))
+            .lot("loc1", "SOON", 3.0, date(1, 20))
+            .lot("loc1", "GONE", 8.0, date(1, 10))
+            .insert::<InventoryItem>(&db);
+        let service = ShippingService::new(db).with_clock(fixed_clock());
+        let shipment = service.create_shipment(shipment_with_lines(&[("milk", 5.0)]), None).await.unwrap();
+        service.update_status(&shipment.id, ShipmentStatus::Confirmed).await.unwrap();
+        
+        let line = service.pick_item_fefo(&test_context(), &shipment.id, "milk", 5.0, false).await.unwrap();
+        assert_eq!((line.quantity_picked, line.status), (5.0, ShipmentItemStatus::Picked));
+        
+        let lot = |lot: &str| -> f64 {
+            service.db.query_row(
+                "SELECT quantity FROM inventory_stock WHERE item_id = 'milk' AND lot_number = ?",
+                params![lot],
+                |row| row.get(0),
+            ).unwrap().unwrap()
+        };
+        assert_eq!((lot("SOON"), lot("LATE"), lot("GONE")), (0.0, 8.0, 8.0));
+        assert_eq!(pick_transactions(&service), 2);
+    }
+    
+    #[tokio::test]
+    async fn test_cancelled_fefo_pick_goes_back_to_its_lots() {
+        let db = test_db();
+        insert_location(&db, "loc2");
+        let date = |m, d| NaiveDate::from_ymd_opt(2025, m, d);
+        ItemBuilder::new("MILK-1").id("milk")
+            .lot("loc1", "LATE", 10.0, date(3, 1))
+            .lot("loc2", "SOON", 3.0, date(1, 20))
+            .insert::<InventoryItem>(&db);
+        let service = ShippingService::new(db).with_clock(fixed_clock());
+        let shipment = service.create_shipment(shipment_with_lines(&[("milk", 6.0)]), None).await.unwrap();
+        service.update_status(&shipment.id, ShipmentStatus::Confirmed).await.unwrap();
+        
+        let stock = |location: &str, lot: &str| -> f64 {
+            service.db.query_row(
+                "SELECT quantity FROM inventory_stock WHERE item_id = 'milk' AND location_id = ? AND lot_number = ?",
+                params![location, lot],
+                |row| row.get(0),
+            ).unwrap().unwrap()
+        };
+        
+        // SOON 3 from loc2 and LATE 2 from loc1; the line now points at loc1
+        let line = service.pick_item_fefo(&test_context(), &shipment.id, "milk", 5.0, false).await.unwrap();
+        assert_eq!((stock("loc2", "SOON"), stock("loc1", "LATE")), (0.0, 8.0));
+        
+        // A mispick of 1 comes off the latest pick
+        service.correct_pick(&test_context(), &line.id, 1.0, None).await.unwrap();
+        assert_eq!((stock("loc2", "SOON"), stock("loc1", "LATE")), (0.0, 9.0));
+        
+        service.cancel_shipment(&test_context(), &shipment.id, "Order withdrawn").await.unwrap();
+        assert_eq!((stock("loc2", "SOON"), stock("loc1", "LATE")), (3.0, 10.0));
+        let unpicks: Vec<(String, String, f64)> = service.db.query_map(
+            "SELECT location_id, lot_number, quantity FROM inventory_transactions
+             WHERE transaction_type = 'UNPICK' ORDER BY lot_number",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        ).unwrap();
+        assert_eq!(unpicks, vec![
+            ("loc1".to_string(), "LATE".to_string(), 1.0),
+            ("loc2".to_string(), "SOON".to_string(), 3.0),
+        ]);
+    }
+    
+    #[tokio::test]
+    async fn test_over_pick_needs_confirmation() {
+        let service = ShippingService::new(stocked_db());
+        let shipment = service.create_shipment(shipment_with_lines(&[("bolt", 2.0)]), None).await.unwrap();
+        advance_to(&service, &shipment.id, ShipmentStatus::Picking).await;
+        
+        let err = service.pick_item(&test_context(), &shipment.id, "bolt", "loc1", 3.0, false).await.unwrap_err();
+        assert!(matches!(err, WmsError::Validation(_)));
+        assert_eq!(pick_transactions(&service), 0);
+        
+        let line = service.pick_item(&test_context(), &shipment.id, "bolt", "loc1", 3.0, true).await.unwrap();
+        assert_eq!((line.quantity_picked, line.status), (3.0, ShipmentItemStatus::Picked));
+        
+        // Items that aren't on the shipment can't be picked at all
+        assert!(service.pick_item(&test_context(), &shipment.id, "widget", "loc1", 1.0, true).await.is_err());
+    }
+    
+    #[tokio::test]
+    async fn test_picking_completes_only_when_every_line_is_done() {
+        let service = ShippingService::new(stocked_db());
+        let shipment = service.create_shipment(shipment_with_lines(&[("bolt", 4.0), ("widget", 6.0)]), None).await.unwrap();
+        
+        // Not being picked yet
+        assert!(service.confirm_picking_complete(&shipment.id).await.is_err());
+        advance_to(&service, &shipment.id, ShipmentStatus::Picking).await;
+        
+        service.pick_item(&test_context(), &shipment.id, "bolt", "loc1", 4.0, false).await.unwrap();
+        let widget = service.pick_item(&test_context(), &shipment.id, "widget", "loc1", 5.0, false).await.unwrap();
+        let err = service.confirm_picking_complete(&shipment.id).await.unwrap_err();
+        assert!(err.to_string().contains("WID-1 (5 of 6)"));
+        
+        assert!(service.short_pick_item(&test_context(), &widget.id, "  ").await.is_err());
+        let widget = service.short_pick_item(&test_context(), &widget.id, "Last one damaged").await.unwrap();
+        assert_eq!(widget.short_pick_reason.as_deref(), Some("Last one damaged"));
+        
+        let packed = service.confirm_picking_complete(&shipment.id).await.unwrap();
+        assert_eq!(packed.status, ShipmentStatus::Packed);
+        let widget = packed.items.iter().find(|l| l.item_id == "widget").unwrap();
+        assert_eq!(widget.short_pick_reason.as_deref(), Some("Last one damaged"));
+    }
+    
+    #[tokio::test]
+    async fn test_pdf_label_and_packing_slip() {
+        let service = ShippingService::new(stocked_db());
+        let shipment = service.create_shipment(shipment_with_lines(&[("bolt", 4.0), ("widget", 1.0)]), None).await.unwrap();
+        
+        let label = service.generate_label(&shipment.id, LabelFormat::Pdf).await.unwrap();
+        assert_eq!(label.content_type, "application/pdf");
+        let pdf = base64::engine::general_purpose::STANDARD.decode(&label.data).unwrap();
+        assert!(pdf.starts_with(b"%PDF"));
+        
+        let zpl = service.generate_label(&shipment.id, LabelFormat::Zpl).await.unwrap();
+        assert_eq!(zpl.format, LabelFormat::Zpl);
+        
+        let shipment = service.get_shipment(&shipment.id).await.unwrap().unwrap();
+        let mut skus: Vec<_> = ShippingService::packing_slip_lines(&shipment).into_iter().map(|(sku, _, _)| sku).collect();
+        skus.sort();
+        assert_eq!(skus, vec!["BLT-1", "WID-1"]);
+        assert!(service.generate_packing_slip(&shipment.id).await.unwrap().starts_with(b"%PDF"));
+    }
+    
+    #[tokio::test]
+    async fn test_repeat_scan_after_window_is_processed() {
+        let clock = fixed_clock();
+        let service = ShippingService::new(stocked_db()).with_clock(clock.clone());
+        let shipment = service.create_shipment(shipment_with_lines(&[("bolt", 2.0)]), None).await.unwrap();
+        service.update_status(&shipment.id, ShipmentStatus::Confirmed).await.unwrap();
+        
+        service.scan_pick_item(&test_context(), &shipment.id, "BLT-1", 1.0, false).await.unwrap();
+        clock.advance(chrono::Duration::seconds(1));
+        let repeat = service.scan_pick_item(&test_context(), &shipment.id, "BLT-1", 1.0, false).await.unwrap();
+        assert!(matches!(repeat, ScanResult::Duplicate { .. }));
+        
+        clock.advance(chrono::Duration::seconds(5));
+        let later = service.scan_pick_item(&test_context(), &shipment.id, "BLT-1", 1.0, false).await.unwrap();
+        assert!(matches!(later, ScanResult::Processed { .. }));
+        assert_eq!(pick_transactions(&service), 2);
+    }
+    
+    #[tokio::test]
+    async fn test_confirm_blocked_by_shipping_requirements_until_overridden() {
+        let db = test_db();
+        CustomerBuilder::new("C-BIGBOX").id("bigbox").insert::<serde_json::Value>(&db);
+        db.execute(
+            "INSERT INTO customer_shipping_requirements (
+                customer_id, required_carrier_id, allow_partial, appointment_required, label_notes
+            ) VALUES ('bigbox', 'car_ups', 0, 1, 'Label every carton with the PO')",
+            [],
+        ).unwrap();
+        let shipment: Shipment = ShipmentBuilder::new("SHP-1").customer("bigbox").carrier("car_fedex").insert(&db);
+        let service = ShippingService::new(db.clone()).with_clock(fixed_clock());
+        
+        let report = service.check_shipment_compliance(&shipment.id).await.unwrap();
+        assert!(report.is_blocked());
+        assert_eq!(report.violations.len(), 2);
+        assert_eq!(report.label_notes.as_deref(), Some("Label every carton with the PO"));
+        
+        let err = service.confirm_shipment(&shipment.id, None, None, None).await.unwrap_err();
+        assert!(err.to_string().contains("requires carrier car_ups"));
+        let err = service.update_status(&shipment.id, ShipmentStatus::Confirmed).await.unwrap_err();
+        assert!(matches!(err, WmsError::Validation(_)));
+        
+        let blank = ComplianceOverride { reason: " ".to_string(), user_id: "u1".to_string() };
+        assert!(service.confirm_shipment(&shipment.id, None, Some(blank), None).await.is_err());
+        
+        let approved = ComplianceOverride {
+            reason: "Customer approved FedEx by email".to_string(),
+            user_id: "u1".to_string(),
+        };
+        let confirmed = service.confirm_shipment(&shipment.id, None, Some(approved), None).await.unwrap();
+        assert_eq!(confirmed.status, ShipmentStatus::Confirmed);
+        
+        let logged: Vec<(String, String)> = db.query_map(
+            "SELECT reason, violations FROM shipment_compliance_overrides WHERE shipment_id = ?",
+            params![&shipment.id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        ).unwrap();
+        assert_eq!(logged.len(), 1);
+        assert_eq!(logged[0].0, "Customer approved FedEx by email");
+        assert!(logged[0].1.contains("\"carrier\""));
+        assert!(!logged[0].1.contains("appointment"));
+    }
+    
+    #[tokio::test]
+    async fn test_damage_evidence_feeds_supplier_claims() {
+        let db = stocked_db();
+        let service = ShippingService::new(db).with_clock(fixed_clock());
+        let asn = b"asn,supplier,sku,qty\nASN-2001,Acme Supply,WID-1,10\nASN-2001,Acme Supply,GAD-1,4\n";
+        let receipt = service.import_asn(&test_context(), asn, AsnFormat::Csv, false).await.unwrap().receipt;
+        let line = &receipt.items[0];
+        service.db.execute(
+            "UPDATE receipt_items SET quantity_damaged = 2, received_at = '2025-01-15T08:30:00Z' WHERE id = ?",
+            params![&line.id],
+        ).unwrap();
+        
+        let empty = service.add_receipt_line_evidence(&test_context(), &line.id, Some("  "), None).await;
+        assert!(matches!(empty, Err(WmsError::Validation(_))));
+        let not_a_photo = EvidencePhoto { filename: "carton.jpg".to_string(), content: b"not an image".to_vec() };
+        assert!(service.add_receipt_line_evidence(&test_context(), &line.id, None, Some(not_a_photo)).await.is_err());
+        assert!(service.add_receipt_line_evidence(&test_context(), "missing", Some("Crushed"), None).await.is_err());
+        
+        let evidence = service.add_receipt_line_evidence(&test_context(), &line.id, Some(" Corner crushed "), None)
+            .await
+            .unwrap();
+        assert_eq!(evidence.note.as_deref(), Some("Corner crushed"));
+        let receipt = service.get_receipt(&receipt.id).await.unwrap().unwrap();
+        let counts: Vec<u32> = receipt.items.iter().map(|i| i.evidence_count).collect();
+        assert_eq!(counts.iter().sum::<u32>(), 1);
+        
+        let day = "2025-01-15".parse().unwrap();
+        let claims = service.get_damage_claims(DateRange::new(day, day), Some("acme supply")).await.unwrap();
+        assert_eq!(claims.lines.len(), 1);
+        assert_eq!(claims.lines[0].quantity_damaged, Some(2.0));
+        assert_eq!(claims.lines[0].party.as_deref(), Some("Acme Supply"));
+        assert_eq!(claims.lines[0].evidence.len(), 1);
+        assert!(service.get_damage_claims(DateRange::new(day, day), Some("Other Co")).await.unwrap().lines.is_empty());
+        
+        let export = service.export_damage_claims(DateRange::new(day, day), None).await.unwrap();
+        assert_eq!(export.content_type, "application/zip");
+        assert_eq!(export.filename, "damage_claims_2025-01-15_2025-01-15.zip");
+    }
+    
+    #[tokio::test]
+    async fn test_credit_limit_on_create_shipment() {
+        use wms_core::overrides::{OverrideApproval, OverrideRequest};
+        
+        let db = test_db();
+        CustomerBuilder::new("CUST-1").credit_limit(1000.0).insert::<serde_json::Value>(&db);
+        CustomerBuilder::new("CUST-2").insert::<serde_json::Value>(&db);
+        ShipmentBuilder::new("SHP-OLD").customer("cust-1").status("shipped").declared_value(700.0)
+            .insert::<serde_json::Value>(&db);
+        let service = ShippingService::new(db);
+        let order = |customer_id: &str, value: f64| {
+            let mut draft = draft_shipment();
+            draft.customer_id = Some(customer_id.to_string());
+            draft.declared_value = Some(value);
+            draft
+        };
+        
+        // Exactly at the limit
+        let at_limit = service.create_shipment(order("cust-1", 300.0), None).await.unwrap();
+        assert_eq!(at_limit.declared_value, Some(300.0));
+        
+        let err = service.create_shipment(order("cust-1", 50.0), None).await.unwrap_err();
+        assert!(matches!(
+            err,
+            WmsError::CreditLimitExceeded { limit, exposure, requested }
+                if limit == 1000.0 && exposure == 1000.0 && requested == 50.0
+        ));
+        
+        let token = service.overrides.request_override(&test_context(), OverrideRequest {
+            action: OverrideAction::CreditLimit,
+            entity_type: CUSTOMER_OWNER.to_string(),
+            entity_id: "cust-1".to_string(),
+            reason_code: "CREDIT_APPROVED".to_string(),
+            note: None,
+            approval: OverrideApproval::Session,
+        }).await.unwrap();
+        service.create_shipment(order("cust-1", 50.0), Some(&token)).await.unwrap();
+        // The override covered that one order
+        assert!(service.create_shipment(order("cust-1", 50.0), Some(&token)).await.is_err());
+        
+        // No limit set
+        service.create_shipment(order("cust-2", 1_000_000.0), None).await.unwrap();
+    }
+    
+    #[tokio::test]
+    async fn test_credit_limit_on_confirm_uses_priced_total() {
+        use wms_core::overrides::{OverrideApproval, OverrideRequest};
+        
+        let db = stocked_db();
+        CustomerBuilder::new("CUST-1").credit_limit(1000.0).insert::<serde_json::Value>(&db);
+        ShipmentBuilder::new("SHP-OLD").customer("cust-1").status("shipped").declared_value(700.0)
+            .insert::<serde_json::Value>(&db);
+        db.execute(
+            "INSERT INTO price_lists (id, name, currency_code, effective_from, is_active, created_at)
+             VALUES ('pl-std', 'Standard', 'USD', '2020-01-01T00:00:00Z', 1, datetime('now'))",
+            [],
+        ).unwrap();
+        db.execute(
+            "INSERT INTO price_list_items (id, price_list_id, item_id, unit_price) VALUES ('pli-1', 'pl-std', 'bolt', 100.0)",
+            [],
+        ).unwrap();
+        let service = ShippingService::new(db);
+        let mut draft = shipment_with_lines(&[("bolt", 4.0)]);
+        draft.customer_id = Some("cust-1".to_string());
+        let shipment = service.create_shipment(draft, None).await.unwrap();
+        
+        // Declared nothing at create, but prices at 400 against 300 left
+        let err = service.confirm_shipment(&shipment.id, None, None, None).await.unwrap_err();
+        assert!(matches!(
+            err,
+            WmsError::CreditLimitExceeded { exposure, requested, .. } if exposure == 700.0 && requested == 400.0
+        ));
+        let unchanged = service.get_shipment(&shipment.id).await.unwrap().unwrap();
+        assert_eq!(unchanged.status, ShipmentStatus::Draft);
+        assert_eq!(unchanged.order_total, None);
+        
+        let token = service.overrides.request_override(&test_context(), OverrideRequest {
+            action: OverrideAction::CreditLimit,
+            entity_type: CUSTOMER_OWNER.to_string(),
+            entity_id: "cust-1".to_string(),
+            reason_code: "CREDIT_APPROVED".to_string(),
+            note: None,
+            approval: OverrideApproval::Session,
+        }).await.unwrap();
+        let confirmed = service.confirm_shipment(&shipment.id, None, None, Some(&token)).await.unwrap();
+        assert_eq!(confirmed.status, ShipmentStatus::Confirmed);
+        assert_eq!(confirmed.order_total, Some(400.0));
+        
+        // The confirmed order now counts at its priced total
+        let mut next = draft_shipment();
+        next.customer_id = Some("cust-1".to_string());
+        next.declared_value = Some(1.0);
+        let err = service.create_shipment(next, None).await.unwrap_err();
+        assert!(matches!(err, WmsError::CreditLimitExceeded { exposure, .. } if exposure == 1100.0));
+    }
+}
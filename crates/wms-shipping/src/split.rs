@@ -0,0 +1,212 @@
+//! Shipment Splitting
+//!
+//! Quantity math for moving unavailable quantities off a shipment onto a
+//! backorder shipment. Only unpicked quantity can move; picked stock stays
+//! with the original shipment.
+
+use std::collections::HashMap;
+use wms_core::error::{WmsError, Result};
+use crate::models::{ShipmentItem, SplitLine};
+
+/// Quantities below this are treated as zero
+const QUANTITY_EPSILON: f64 = 1e-9;
+
+/// Resulting quantities for one original line
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineSplit {
+    pub shipment_item_id: String,
+    /// Quantity left on the original shipment
+    pub keep: f64,
+    /// Quantity moved to the backorder shipment
+    pub backorder: f64,
+}
+
+impl LineSplit {
+    /// Whether the original line ends up empty and should be removed
+    pub fn removes_line(&self) -> bool {
+        self.keep <= QUANTITY_EPSILON
+    }
+}
+
+fn open_quantity(item: &ShipmentItem) -> f64 {
+    (item.quantity_ordered - item.quantity_picked).max(0.0)
+}
+
+/// Backorder whatever the available stock cannot cover.
+///
+/// Lines for the same item draw from its availability in line order.
+/// Items missing from `available` are treated as out of stock.
+pub fn split_by_availability(items: &[ShipmentItem], available: &HashMap<String, f64>) -> Vec<SplitLine> {
+    let mut remaining = available.clone();
+
+    items.iter()
+        .filter_map(|item| {
+            let open = open_quantity(item);
+            let stock = remaining.entry(item.item_id.clone()).or_insert(0.0);
+            let allocated = open.min(stock.max(0.0));
+            *stock -= allocated;
+
+            let short = open - allocated;
+            (short > QUANTITY_EPSILON).then(|| SplitLine {
+                shipment_item_id: item.id.clone(),
+                quantity: short,
+            })
+        })
+        .collect()
+}
+
+/// Validate requested backorder quantities and compute the new line
+/// quantities. Requests for the same line are combined.
+pub fn plan_split(items: &[ShipmentItem], lines: &[SplitLine]) -> Result<Vec<LineSplit>> {
+    let mut requested: Vec<(String, f64)> = Vec::new();
+    for line in lines {
+        if line.quantity <= 0.0 || !line.quantity.is_finite() {
+            return Err(WmsError::validation(format!(
+                "Split quantity for line {} must be greater than zero",
+                line.shipment_item_id
+            )));
+        }
+        match requested.iter_mut().find(|(id, _)| *id == line.shipment_item_id) {
+            Some((_, qty)) => *qty += line.quantity,
+            None => requested.push((line.shipment_item_id.clone(), line.quantity)),
+        }
+    }
+
+    if requested.is_empty() {
+        return Err(WmsError::validation("Nothing to split: all quantities are available"));
+    }
+
+    let splits = requested.into_iter()
+        .map(|(id, backorder)| {
+            let item = items.iter()
+                .find(|i| i.id == id)
+                .ok_or_else(|| WmsError::not_found(format!("Shipment line {} not found", id)))?;
+
+            if backorder > open_quantity(item) + QUANTITY_EPSILON {
+                return Err(WmsError::validation(format!(
+                    "Cannot backorder {} of line {}: only {} is unpicked",
+                    backorder, id, open_quantity(item)
+                )));
+            }
+
+            Ok(LineSplit {
+                shipment_item_id: id,
+                keep: item.quantity_ordered - backorder,
+                backorder,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let emptied = items.iter().all(|item| {
+        splits.iter()
+            .find(|s| s.shipment_item_id == item.id)
+            .is_some_and(LineSplit::removes_line)
+    });
+    if emptied {
+        return Err(WmsError::validation(
+            "Nothing would remain on the original shipment; reschedule it instead of splitting",
+        ));
+    }
+
+    Ok(splits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ShipmentItemStatus;
+
+    fn line(id: &str, item_id: &str, ordered: f64, picked: f64) -> ShipmentItem {
+        ShipmentItem {
+            id: id.to_string(),
+            shipment_id: "s1".to_string(),
+            item_id: item_id.to_string(),
+            location_id: None,
+            quantity_ordered: ordered,
+            quantity_picked: picked,
+            quantity_shipped: 0.0,
+            lot_number: None,
+            serial_number: None,
+            unit_price: None,
+            extended_price: None,
+            status: ShipmentItemStatus::Pending,
+            picked_by: None,
+            picked_at: None,
+            short_pick_reason: None,
+            item_sku: None,
+            item_name: None,
+            evidence_count: 0,
+        }
+    }
+
+    fn split(id: &str, quantity: f64) -> SplitLine {
+        SplitLine { shipment_item_id: id.to_string(), quantity }
+    }
+
+    #[test]
+    fn test_auto_split_partial_and_full_lines() {
+        let items = vec![
+            line("l1", "widget", 10.0, 0.0),
+            line("l2", "gadget", 5.0, 0.0),
+            line("l3", "bolt", 8.0, 0.0),
+        ];
+        let available = HashMap::from([
+            ("widget".to_string(), 6.0),
+            ("bolt".to_string(), 20.0),
+        ]);
+
+        let lines = split_by_availability(&items, &available);
+
+        // Widget splits partially, gadget moves entirely, bolt stays
+        assert_eq!(lines, vec![split("l1", 4.0), split("l2", 5.0)]);
+
+        let plan = plan_split(&items, &lines).unwrap();
+        assert_eq!(plan[0], LineSplit { shipment_item_id: "l1".into(), keep: 6.0, backorder: 4.0 });
+        assert!(!plan[0].removes_line());
+        assert!(plan[1].removes_line());
+    }
+
+    #[test]
+    fn test_shared_item_availability_consumed_in_order() {
+        let items = vec![
+            line("l1", "widget", 4.0, 0.0),
+            line("l2", "widget", 4.0, 0.0),
+        ];
+        let available = HashMap::from([("widget".to_string(), 5.0)]);
+
+        assert_eq!(split_by_availability(&items, &available), vec![split("l2", 3.0)]);
+    }
+
+    #[test]
+    fn test_picked_quantity_cannot_move() {
+        let items = vec![
+            line("l1", "widget", 10.0, 7.0),
+            line("l2", "gadget", 1.0, 0.0),
+        ];
+
+        // Only the 3 unpicked units are open
+        let auto = split_by_availability(&items, &HashMap::from([("gadget".to_string(), 1.0)]));
+        assert_eq!(auto, vec![split("l1", 3.0)]);
+
+        let plan = plan_split(&items, &auto).unwrap();
+        assert_eq!(plan[0].keep, 7.0);
+
+        assert!(plan_split(&items, &[split("l1", 4.0)]).is_err());
+    }
+
+    #[test]
+    fn test_invalid_splits_rejected() {
+        let items = vec![line("l1", "widget", 10.0, 0.0)];
+
+        assert!(plan_split(&items, &[]).is_err());
+        assert!(plan_split(&items, &[split("l1", 0.0)]).is_err());
+        assert!(plan_split(&items, &[split("missing", 1.0)]).is_err());
+        // Moving everything would leave an empty original
+        assert!(plan_split(&items, &[split("l1", 10.0)]).is_err());
+
+        // Repeated lines are combined
+        let plan = plan_split(&items, &[split("l1", 2.0), split("l1", 3.0)]).unwrap();
+        assert_eq!(plan[0].backorder, 5.0);
+        assert_eq!(plan[0].keep, 5.0);
+    }
+}
@@ -0,0 +1,135 @@
+//! Serial Shipping Container Codes
+//!
+//! Every shipment package is identified by a license plate number (LPN),
+//! an SSCC-18: an extension digit, the GS1 company prefix, a serial
+//! reference padded to fill 17 digits, and a GS1 mod-10 check digit. On
+//! labels it is printed as GS1-128 with application identifier (00).
+
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use wms_core::db::Database;
+use wms_core::error::{WmsError, Result};
+
+/// Application identifier that marks an SSCC in GS1-128 barcodes
+pub const SSCC_AI: &str = "00";
+
+/// GS1 mod-10 check digit over `digits`: weights of 3 and 1 alternate
+/// leftwards from the rightmost digit
+pub fn gs1_check_digit(digits: &str) -> Option<u8> {
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let sum: u32 = digits.bytes()
+        .rev()
+        .enumerate()
+        .map(|(i, b)| (b - b'0') as u32 * if i % 2 == 0 { 3 } else { 1 })
+        .sum();
+    Some(((10 - sum % 10) % 10) as u8)
+}
+
+/// Whether `code` is 18 digits ending in the right check digit
+pub fn is_valid_sscc(code: &str) -> bool {
+    code.len() == 18
+        && code.is_ascii()
+        && gs1_check_digit(&code[..17]).is_some_and(|check| code.ends_with(char::from(b'0' + check)))
+}
+
+/// SSCC numbering configuration
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SsccSettings {
+    /// Packaging indicator the company assigns, 0-9
+    pub extension_digit: u8,
+    /// GS1 company prefix, 7 to 10 digits
+    pub company_prefix: String,
+}
+
+impl Default for SsccSettings {
+    /// Numbers are well-formed but not globally unique until the company
+    /// prefix is configured
+    fn default() -> Self {
+        Self { extension_digit: 0, company_prefix: "0000000".to_string() }
+    }
+}
+
+impl SsccSettings {
+    const EXTENSION_KEY: &'static str = "sscc_extension_digit";
+    const PREFIX_KEY: &'static str = "sscc_company_prefix";
+    const SERIAL_KEY: &'static str = "sscc_last_serial";
+
+    /// Load from the settings table, falling back to the defaults
+    pub fn load(db: &Database) -> Result<Self> {
+        let defaults = Self::default();
+        Ok(Self {
+            extension_digit: db.get_setting(Self::EXTENSION_KEY)?
+                .and_then(|value| value.parse().ok())
+                .filter(|digit| *digit <= 9)
+                .unwrap_or(defaults.extension_digit),
+            company_prefix: db.get_setting(Self::PREFIX_KEY)?
+                .filter(|prefix| Self::valid_prefix(prefix))
+                .unwrap_or(defaults.company_prefix),
+        })
+    }
+
+    /// Store in the settings table
+    pub fn save(&self, db: &Database) -> Result<()> {
+        if self.extension_digit > 9 {
+            return Err(WmsError::validation("SSCC extension digit must be 0-9"));
+        }
+        if !Self::valid_prefix(&self.company_prefix) {
+            return Err(WmsError::validation("GS1 company prefix must be 7 to 10 digits"));
+        }
+        db.set_setting(Self::EXTENSION_KEY, &self.extension_digit.to_string())?;
+        db.set_setting(Self::PREFIX_KEY, &self.company_prefix)
+    }
+
+    fn valid_prefix(prefix: &str) -> bool {
+        (7..=10).contains(&prefix.len()) && prefix.bytes().all(|b| b.is_ascii_digit())
+    }
+
+    /// SSCC for serial reference `serial`, which wraps around once it no
+    /// longer fits beside the company prefix
+    pub fn sscc(&self, serial: u64) -> String {
+        let width = 16 - self.company_prefix.len();
+        let serial = serial % 10u64.pow(width as u32);
+        let body = format!("{}{}{:0width$}", self.extension_digit, self.company_prefix, serial, width = width);
+        let check = gs1_check_digit(&body).unwrap_or_default();
+        format!("{}{}", body, check)
+    }
+
+    /// Allocate the next SSCC
+    pub fn next_sscc(&self, db: &Database) -> Result<String> {
+        let serial: i64 = db.query_row(
+            "INSERT INTO settings (key, value, updated_at) VALUES (?1, '1', datetime('now'))
+             ON CONFLICT(key) DO UPDATE SET value = CAST(value AS INTEGER) + 1, updated_at = datetime('now')
+             RETURNING CAST(value AS INTEGER)",
+            params![Self::SERIAL_KEY],
+            |row| row.get(0),
+        )?.unwrap_or(1);
+        Ok(self.sscc(serial as u64))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sscc_check_digit() {
+        // GS1 General Specifications example
+        assert_eq!(gs1_check_digit("37610425002123456"), Some(9));
+        assert!(is_valid_sscc("376104250021234569"));
+        assert!(!is_valid_sscc("376104250021234568"));
+        assert!(!is_valid_sscc("37610425002123456"));
+        assert_eq!(gs1_check_digit("12a"), None);
+
+        let settings = SsccSettings { extension_digit: 3, company_prefix: "7610425".to_string() };
+        assert_eq!(settings.sscc(2123456), "376104250021234569");
+
+        let db = wms_core::test_support::test_db();
+        let first = settings.next_sscc(&db).unwrap();
+        let second = settings.next_sscc(&db).unwrap();
+        assert_eq!((first.as_str(), second.as_str()), ("376104250000000017", "376104250000000024"));
+        assert!(is_valid_sscc(&first) && is_valid_sscc(&second));
+        assert!(SsccSettings { extension_digit: 1, company_prefix: "12".to_string() }.save(&db).is_err());
+    }
+}
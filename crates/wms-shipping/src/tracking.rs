@@ -0,0 +1,653 @@
+//! Carrier Tracking
+//!
+//! Tracking scans arrive either as carrier webhooks or by polling carrier
+//! APIs for shipped, undelivered shipments. Both are normalized into
+//! tracking events stored per shipment, and a delivery scan moves a
+//! shipped shipment to delivered.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::{debug, info, warn};
+use wms_core::db::Database;
+use wms_core::error::{WmsError, Result};
+use wms_core::shutdown::TaskHandle;
+use wms_core::types::{new_id, parse_timestamp, required_timestamp, unreadable};
+use crate::models::ShipmentStatus;
+use crate::service::ShippingService;
+
+/// Default minimum spacing between requests to one carrier
+const DEFAULT_RATE_LIMIT: Duration = Duration::from_secs(1);
+
+const TRACKING_NUMBER_FIELDS: &[&str] = &["tracking_number", "trackingNumber", "tracking_id", "trackingId"];
+const STATUS_FIELDS: &[&str] = &["status", "status_code", "statusCode", "event_code", "eventCode"];
+const DESCRIPTION_FIELDS: &[&str] = &["description", "status_description", "statusDescription", "message"];
+const LOCATION_FIELDS: &[&str] = &["location", "city"];
+const TIME_FIELDS: &[&str] = &["occurred_at", "timestamp", "event_time", "eventTime", "date"];
+const EVENT_LIST_FIELDS: &[&str] = &["events", "activities", "scans"];
+
+/// Where a tracking event came from
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TrackingSource {
+    Webhook,
+    Poll,
+}
+
+impl TrackingSource {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Webhook => "webhook",
+            Self::Poll => "poll",
+        }
+    }
+}
+
+/// A carrier scan for a shipment
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TrackingEvent {
+    #[serde(default)]
+    pub id: String,
+    #[serde(default)]
+    pub shipment_id: String,
+    pub carrier_code: String,
+    pub tracking_number: String,
+    /// Carrier's own status code or text
+    pub carrier_status: String,
+    /// Shipment status this event implies, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<ShipmentStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+    pub occurred_at: DateTime<Utc>,
+}
+
+/// Map a carrier status code or text to our shipment status.
+///
+/// In-transit style scans map to Shipped and delivery scans to Delivered;
+/// exceptions, label creation and anything unrecognized map to None.
+pub fn map_carrier_status(status: &str) -> Option<ShipmentStatus> {
+    let code: String = status.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .collect::<String>()
+        .to_ascii_uppercase();
+
+    match code.as_str() {
+        "DELIVERED" | "DL" | "D" => Some(ShipmentStatus::Delivered),
+        _ if code.starts_with("DELIVERED") => Some(ShipmentStatus::Delivered),
+        "INTRANSIT" | "IT" | "I" | "TRANSIT" | "PICKEDUP" | "PU" | "P" | "ACCEPTED"
+        | "DEPARTED" | "DP" | "ARRIVED" | "AR" | "OUTFORDELIVERY" | "OD" | "OFD" => {
+            Some(ShipmentStatus::Shipped)
+        }
+        _ => None,
+    }
+}
+
+/// Parse a carrier webhook body into tracking events.
+///
+/// Accepts a single event object, an array of events, or an envelope with
+/// an `events` (or `activities`/`scans`) array whose tracking number may sit
+/// on the envelope. Common field spellings are recognized.
+pub fn parse_tracking_webhook(carrier_code: &str, body: &[u8]) -> Result<Vec<TrackingEvent>> {
+    parse_events(carrier_code, body, None)
+}
+
+fn parse_events(carrier_code: &str, body: &[u8], tracking_number: Option<&str>) -> Result<Vec<TrackingEvent>> {
+    let value: Value = serde_json::from_slice(body)
+        .map_err(|e| WmsError::validation(format!("Invalid tracking payload: {}", e)))?;
+    let carrier_code = carrier_code.trim().to_uppercase();
+
+    let (envelope_number, events): (Option<String>, Vec<&Value>) = match &value {
+        Value::Array(items) => (None, items.iter().collect()),
+        Value::Object(map) => {
+            let events = EVENT_LIST_FIELDS.iter()
+                .find_map(|key| map.get(*key))
+                .and_then(Value::as_array);
+            match events {
+                Some(items) => (string_field(&value, TRACKING_NUMBER_FIELDS), items.iter().collect()),
+                None => (None, vec![&value]),
+            }
+        }
+        _ => return Err(WmsError::validation("Tracking payload must be a JSON object or array")),
+    };
+    let default_number = envelope_number.or_else(|| tracking_number.map(str::to_string));
+
+    events.into_iter()
+        .map(|event| {
+            let tracking_number = string_field(event, TRACKING_NUMBER_FIELDS)
+                .or_else(|| default_number.clone())
+                .ok_or_else(|| WmsError::validation("Tracking event has no tracking number"))?;
+            let carrier_status = string_field(event, STATUS_FIELDS)
+                .ok_or_else(|| WmsError::validation(format!("Tracking event for {} has no status", tracking_number)))?;
+            let occurred_at = string_field(event, TIME_FIELDS)
+                .and_then(|s| parse_event_time(&s))
+                .ok_or_else(|| WmsError::validation(format!(
+                    "Tracking event for {} has no valid timestamp", tracking_number
+                )))?;
+
+            Ok(TrackingEvent {
+                id: String::new(),
+                shipment_id: String::new(),
+                carrier_code: carrier_code.clone(),
+                status: map_carrier_status(&carrier_status),
+                description: string_field(event, DESCRIPTION_FIELDS),
+                location: location_field(event),
+                tracking_number,
+                carrier_status,
+                occurred_at,
+            })
+        })
+        .collect()
+}
+
+/// First non-empty string (or number) among the given keys
+fn string_field(value: &Value, keys: &[&str]) -> Option<String> {
+    keys.iter().find_map(|key| match value.get(*key)? {
+        Value::String(s) if !s.trim().is_empty() => Some(s.trim().to_string()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    })
+}
+
+/// Location as a string, or joined from a `{city, state, country}` object
+fn location_field(value: &Value) -> Option<String> {
+    if let Some(location) = string_field(value, LOCATION_FIELDS) {
+        return Some(location);
+    }
+    let object = value.get("location")?;
+    let parts: Vec<String> = ["city", "state", "country"].iter()
+        .filter_map(|key| string_field(object, &[*key]))
+        .collect();
+    (!parts.is_empty()).then(|| parts.join(", "))
+}
+
+/// RFC 3339, or a naive date-time taken as UTC
+fn parse_event_time(s: &str) -> Option<DateTime<Utc>> {
+    parse_timestamp(s).or_else(|| {
+        ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M:%S"].iter()
+            .find_map(|format| NaiveDateTime::parse_from_str(s, format).ok())
+            .map(|dt| dt.and_utc())
+    })
+}
+
+/// Source of tracking history for one carrier
+pub trait CarrierTrackingProvider: Send + Sync {
+    /// Carrier code this provider answers for (matches `carriers.code`)
+    fn carrier_code(&self) -> &str;
+
+    /// Tracking history for a tracking number
+    fn fetch_status(&self, tracking_number: &str) -> Result<Vec<TrackingEvent>>;
+}
+
+/// Shared provider handle
+pub type SharedTrackingProvider = Arc<dyn CarrierTrackingProvider>;
+
+/// Carrier tracking API reached over HTTP. The response body is parsed
+/// like a webhook payload.
+#[cfg(feature = "carrier-tracking")]
+pub struct HttpTrackingProvider {
+    carrier_code: String,
+    url_template: String,
+    api_key: Option<String>,
+    client: reqwest::blocking::Client,
+}
+
+#[cfg(feature = "carrier-tracking")]
+impl HttpTrackingProvider {
+    /// Create a provider; `url_template` contains a `{tracking_number}` placeholder
+    pub fn new(carrier_code: &str, url_template: &str) -> Self {
+        Self {
+            carrier_code: carrier_code.trim().to_uppercase(),
+            url_template: url_template.to_string(),
+            api_key: None,
+            client: reqwest::blocking::Client::builder()
+                .timeout(Duration::from_secs(15))
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Send the key as a bearer token
+    pub fn with_api_key(mut self, api_key: &str) -> Self {
+        self.api_key = Some(api_key.to_string());
+        self
+    }
+
+    /// Configure from `WMS_TRACKING_<CODE>_URL` and optional `WMS_TRACKING_<CODE>_KEY`
+    pub fn from_env(carrier_code: &str) -> Option<Self> {
+        let prefix = format!("WMS_TRACKING_{}", carrier_code.trim().to_uppercase());
+        let url = std::env::var(format!("{}_URL", prefix)).ok()?;
+        let provider = Self::new(carrier_code, &url);
+        Some(match std::env::var(format!("{}_KEY", prefix)) {
+            Ok(key) => provider.with_api_key(&key),
+            Err(_) => provider,
+        })
+    }
+}
+
+#[cfg(feature = "carrier-tracking")]
+impl CarrierTrackingProvider for HttpTrackingProvider {
+    fn carrier_code(&self) -> &str {
+        &self.carrier_code
+    }
+
+    fn fetch_status(&self, tracking_number: &str) -> Result<Vec<TrackingEvent>> {
+        let url = self.url_template.replace("{tracking_number}", tracking_number);
+        let mut request = self.client.get(url);
+        if let Some(key) = &self.api_key {
+            request = request.bearer_auth(key);
+        }
+
+        let body = request.send()
+            .and_then(|response| response.error_for_status())
+            .and_then(|response| response.bytes())
+            .map_err(|e| WmsError::Network(format!("{} tracking request failed: {}", self.carrier_code, e)))?;
+
+        parse_events(&self.carrier_code, &body, Some(tracking_number))
+    }
+}
+
+/// Ingests carrier tracking and advances delivered shipments
+pub struct TrackingUpdater {
+    db: Arc<Database>,
+    shipping: Arc<ShippingService>,
+    providers: HashMap<String, SharedTrackingProvider>,
+    rate_limits: HashMap<String, Duration>,
+    last_request: Mutex<HashMap<String, Instant>>,
+}
+
+impl TrackingUpdater {
+    /// Create an updater with no polling providers
+    pub fn new(db: Arc<Database>, shipping: Arc<ShippingService>) -> Self {
+        Self {
+            db,
+            shipping,
+            providers: HashMap::new(),
+            rate_limits: HashMap::new(),
+            last_request: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Poll a carrier through `provider`, at most one request per `min_interval`
+    pub fn with_provider(mut self, provider: SharedTrackingProvider, min_interval: Duration) -> Self {
+        let code = provider.carrier_code().trim().to_uppercase();
+        self.rate_limits.insert(code.clone(), min_interval);
+        self.providers.insert(code, provider);
+        self
+    }
+
+    /// Register an HTTP provider for each active carrier configured in the
+    /// environment (see `HttpTrackingProvider::from_env`)
+    #[cfg(feature = "carrier-tracking")]
+    pub fn with_env_providers(mut self) -> Result<Self> {
+        let codes: Vec<String> = self.db.query_map(
+            "SELECT code FROM carriers WHERE is_active = 1",
+            [],
+            |row| row.get(0),
+        )?;
+
+        for code in codes {
+            if let Some(provider) = HttpTrackingProvider::from_env(&code) {
+                info!("Polling {} tracking", code);
+                self = self.with_provider(Arc::new(provider), DEFAULT_RATE_LIMIT);
+            }
+        }
+        Ok(self)
+    }
+
+    // ============ Ingestion ============
+
+    /// Ingest a carrier webhook body; returns the newly recorded events.
+    /// Events for unknown tracking numbers are skipped.
+    pub async fn ingest_webhook(&self, carrier_code: &str, body: &[u8]) -> Result<Vec<TrackingEvent>> {
+        let mut recorded = Vec::new();
+
+        for event in parse_tracking_webhook(carrier_code, body)? {
+            let shipment_id: Option<String> = self.db.query_row(
+                "SELECT s.id FROM shipments s
+                 LEFT JOIN carriers c ON s.carrier_id = c.id
+                 WHERE s.tracking_number = ? AND (c.code IS NULL OR upper(c.code) = ?)
+                 ORDER BY s.created_at DESC
+                 LIMIT 1",
+                params![&event.tracking_number, &event.carrier_code],
+                |row| row.get(0),
+            )?;
+
+            match shipment_id {
+                Some(shipment_id) => {
+                    if let Some(event) = self.record_event(&shipment_id, event, TrackingSource::Webhook).await? {
+                        recorded.push(event);
+                    }
+                }
+                None => debug!("No shipment for {} tracking number {}", event.carrier_code, event.tracking_number),
+            }
+        }
+
+        Ok(recorded)
+    }
+
+    /// Poll carriers once for shipped, undelivered shipments, least
+    /// recently checked first. Shipments whose carrier is rate limited are
+    /// left for a later pass. Returns the number of new events.
+    pub async fn poll_once(&self) -> Result<usize> {
+        let shipments: Vec<(String, String, String)> = self.db.query_map(
+            "SELECT s.id, s.tracking_number, upper(c.code)
+             FROM shipments s
+             JOIN carriers c ON s.carrier_id = c.id
+             WHERE s.status = 'shipped'
+               AND s.tracking_number IS NOT NULL AND s.tracking_number != ''
+             ORDER BY s.tracking_checked_at IS NOT NULL, s.tracking_checked_at, s.ship_date",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )?;
+
+        let mut recorded = 0;
+        for (shipment_id, tracking_number, carrier_code) in shipments {
+            let Some(provider) = self.providers.get(&carrier_code).cloned() else {
+                continue;
+            };
+            if !self.try_acquire(&carrier_code)? {
+                continue;
+            }
+
+            let fetched = tokio::task::spawn_blocking(move || provider.fetch_status(&tracking_number))
+                .await
+                .map_err(|e| WmsError::Unknown(e.to_string()))?;
+
+            self.db.execute(
+                "UPDATE shipments SET tracking_checked_at = ? WHERE id = ?",
+                params![Utc::now().to_rfc3339(), &shipment_id],
+            )?;
+
+            match fetched {
+                Ok(events) => {
+                    for event in events {
+                        if self.record_event(&shipment_id, event, TrackingSource::Poll).await?.is_some() {
+                            recorded += 1;
+                        }
+                    }
+                }
+                Err(e) => warn!("Tracking poll for shipment {} failed: {}", shipment_id, e),
+            }
+        }
+
+        Ok(recorded)
+    }
+
+    /// Poll on an interval until shutdown; a pass in progress finishes first
+    pub async fn run_poller(self: Arc<Self>, interval: Duration, task: TaskHandle) {
+        if self.providers.is_empty() {
+            debug!("No carrier tracking providers configured; polling disabled");
+            return;
+        }
+
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {}
+                _ = task.shutdown_requested() => break,
+            }
+            if let Err(e) = self.poll_once().await {
+                warn!("Tracking poll failed: {}", e);
+            }
+        }
+        info!("Tracking poller stopped");
+    }
+
+    /// Tracking events for a shipment, oldest first
+    pub async fn get_tracking_events(&self, shipment_id: &str) -> Result<Vec<TrackingEvent>> {
+        self.db.query_map(
+            "SELECT * FROM tracking_events WHERE shipment_id = ? ORDER BY occurred_at, received_at",
+            params![shipment_id],
+            Self::row_to_event,
+        )
+    }
+
+    // ============ Helper Methods ============
+
+    /// Store an event unless already seen; a delivery scan on a shipped
+    /// shipment marks it delivered
+    async fn record_event(
+        &self,
+        shipment_id: &str,
+        mut event: TrackingEvent,
+        source: TrackingSource,
+    ) -> Result<Option<TrackingEvent>> {
+        event.id = new_id();
+        event.shipment_id = shipment_id.to_string();
+
+        let inserted = self.db.execute(
+            "INSERT OR IGNORE INTO tracking_events (
+                id, shipment_id, carrier_code, tracking_number, carrier_status,
+                status, description, location, occurred_at, source
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            params![
+                &event.id,
+                &event.shipment_id,
+                &event.carrier_code,
+                &event.tracking_number,
+                &event.carrier_status,
+                event.status.map(|s| format!("{:?}", s).to_lowercase()),
+                &event.description,
+                &event.location,
+                event.occurred_at.to_rfc3339(),
+                source.as_str(),
+            ],
+        )?;
+        if inserted == 0 {
+            return Ok(None);
+        }
+
+        if event.status == Some(ShipmentStatus::Delivered) {
+            let current: Option<String> = self.db.query_row(
+                "SELECT status FROM shipments WHERE id = ?",
+                params![shipment_id],
+                |row| row.get(0),
+            )?;
+            if current.as_deref() == Some("shipped") {
+                info!("Carrier reported shipment {} delivered", shipment_id);
+                self.shipping.update_status(shipment_id, ShipmentStatus::Delivered).await?;
+            }
+        }
+
+        Ok(Some(event))
+    }
+
+    /// Claim a request slot for a carrier if its rate limit allows
+    fn try_acquire(&self, carrier_code: &str) -> Result<bool> {
+        let min_interval = self.rate_limits.get(carrier_code).copied().unwrap_or(DEFAULT_RATE_LIMIT);
+        let mut last_request = self.last_request.lock().map_err(|_| WmsError::LockError)?;
+
+        let now = Instant::now();
+        if last_request.get(carrier_code).is_some_and(|last| now.duration_since(*last) < min_interval) {
+            return Ok(false);
+        }
+        last_request.insert(carrier_code.to_string(), now);
+        Ok(true)
+    }
+
+    fn row_to_event(row: &rusqlite::Row) -> rusqlite::Result<TrackingEvent> {
+        Ok(TrackingEvent {
+            id: row.get("id")?,
+            shipment_id: row.get("shipment_id")?,
+            carrier_code: row.get("carrier_code")?,
+            tracking_number: row.get("tracking_number")?,
+            carrier_status: row.get("carrier_status")?,
+            status: row.get::<_, Option<String>>("status")?
+                .map(|s| ShipmentStatus::parse(&s).ok_or_else(|| unreadable(row, "status", &s)))
+                .transpose()?,
+            description: row.get("description")?,
+            location: row.get("location")?,
+            occurred_at: required_timestamp(row, "occurred_at")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// Carrier API returning canned events and counting requests
+    struct MockProvider {
+        code: String,
+        events: Mutex<HashMap<String, Vec<TrackingEvent>>>,
+        requests: Mutex<Vec<String>>,
+    }
+
+    impl MockProvider {
+        fn new(code: &str) -> Self {
+            Self {
+                code: code.to_string(),
+                events: Mutex::new(HashMap::new()),
+                requests: Mutex::new(Vec::new()),
+            }
+        }
+
+        fn respond(&self, tracking_number: &str, status: &str, occurred_at: &str) {
+            self.events.lock().unwrap()
+                .entry(tracking_number.to_string())
+                .or_default()
+                .push(TrackingEvent {
+                    id: String::new(),
+                    shipment_id: String::new(),
+                    carrier_code: self.code.clone(),
+                    tracking_number: tracking_number.to_string(),
+                    carrier_status: status.to_string(),
+                    status: map_carrier_status(status),
+                    description: None,
+                    location: None,
+                    occurred_at: parse_event_time(occurred_at).unwrap(),
+                });
+        }
+
+        fn requests(&self) -> usize {
+            self.requests.lock().unwrap().len()
+        }
+    }
+
+    impl CarrierTrackingProvider for MockProvider {
+        fn carrier_code(&self) -> &str {
+            &self.code
+        }
+
+        fn fetch_status(&self, tracking_number: &str) -> Result<Vec<TrackingEvent>> {
+            self.requests.lock().unwrap().push(tracking_number.to_string());
+            Ok(self.events.lock().unwrap().get(tracking_number).cloned().unwrap_or_default())
+        }
+    }
+
+    fn test_db() -> Arc<Database> {
+        let db = Database::new(&PathBuf::from(":memory:"), "test-key").unwrap();
+        db.migrate_to_latest().unwrap();
+        db.execute(
+            "INSERT INTO users (id, username, email, full_name) VALUES ('u1', 'packer', 'packer@example.com', 'Pat Packer')",
+            [],
+        ).unwrap();
+        for (id, tracking) in [("s1", "1Z001"), ("s2", "1Z002")] {
+            db.execute(
+                "INSERT INTO shipments (id, shipment_number, status, carrier_id, tracking_number,
+                    ship_to_name, ship_to_address_line1, ship_to_city, ship_to_state,
+                    ship_to_postal_code, created_by)
+                 VALUES (?, ?, 'shipped', 'car_ups', ?, 'Acme', '1 Main St', 'Springfield', 'IL', '62701', 'u1')",
+                params![id, format!("SHP-{}", id), tracking],
+            ).unwrap();
+        }
+        Arc::new(db)
+    }
+
+    fn updater(db: Arc<Database>) -> TrackingUpdater {
+        TrackingUpdater::new(db.clone(), Arc::new(ShippingService::new(db)))
+    }
+
+    async fn status(updater: &TrackingUpdater, id: &str) -> ShipmentStatus {
+        updater.shipping.get_shipment(id).await.unwrap().unwrap().status
+    }
+
+    #[test]
+    fn test_map_carrier_status() {
+        assert_eq!(map_carrier_status("Delivered"), Some(ShipmentStatus::Delivered));
+        assert_eq!(map_carrier_status("DELIVERED - Front Door"), Some(ShipmentStatus::Delivered));
+        assert_eq!(map_carrier_status("in_transit"), Some(ShipmentStatus::Shipped));
+        assert_eq!(map_carrier_status("Out for Delivery"), Some(ShipmentStatus::Shipped));
+        assert_eq!(map_carrier_status("Delivery attempted"), None);
+        assert_eq!(map_carrier_status("exception"), None);
+    }
+
+    #[test]
+    fn test_parse_webhook_shapes() {
+        let envelope = br#"{
+            "trackingNumber": "1Z001",
+            "events": [
+                {"status": "IT", "timestamp": "2025-03-01T08:00:00Z", "location": {"city": "Louisville", "state": "KY"}},
+                {"status": "DL", "timestamp": "2025-03-02 14:30:00", "description": "Left at front door"}
+            ]
+        }"#;
+        let events = parse_tracking_webhook("ups", envelope).unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].carrier_code, "UPS");
+        assert_eq!(events[0].tracking_number, "1Z001");
+        assert_eq!(events[0].location.as_deref(), Some("Louisville, KY"));
+        assert_eq!(events[1].status, Some(ShipmentStatus::Delivered));
+        assert_eq!(events[1].occurred_at.to_rfc3339(), "2025-03-02T14:30:00+00:00");
+
+        let flat = br#"{"tracking_number": "1Z002", "status_code": "in_transit", "occurred_at": "2025-03-01T08:00:00Z"}"#;
+        assert_eq!(parse_tracking_webhook("UPS", flat).unwrap()[0].status, Some(ShipmentStatus::Shipped));
+
+        assert!(parse_tracking_webhook("UPS", br#"[{"status": "DL", "timestamp": "2025-03-01T08:00:00Z"}]"#).is_err());
+        assert!(parse_tracking_webhook("UPS", b"not json").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_webhook_delivery_marks_shipment_delivered() {
+        let updater = updater(test_db());
+        let body = br#"[
+            {"tracking_number": "1Z001", "status": "in_transit", "timestamp": "2025-03-01T08:00:00Z"},
+            {"tracking_number": "1Z001", "status": "delivered", "timestamp": "2025-03-02T14:30:00Z"},
+            {"tracking_number": "UNKNOWN", "status": "delivered", "timestamp": "2025-03-02T14:30:00Z"}
+        ]"#;
+
+        let recorded = updater.ingest_webhook("UPS", body).await.unwrap();
+        assert_eq!(recorded.len(), 2);
+        assert_eq!(status(&updater, "s1").await, ShipmentStatus::Delivered);
+        assert_eq!(status(&updater, "s2").await, ShipmentStatus::Shipped);
+
+        // Redelivered webhooks are ignored
+        assert!(updater.ingest_webhook("UPS", body).await.unwrap().is_empty());
+        let events = updater.get_tracking_events("s1").await.unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1].carrier_status, "delivered");
+    }
+
+    #[tokio::test]
+    async fn test_polling_respects_carrier_rate_limit() {
+        let provider = Arc::new(MockProvider::new("UPS"));
+        provider.respond("1Z001", "DL", "2025-03-02T14:30:00Z");
+        provider.respond("1Z002", "IT", "2025-03-01T08:00:00Z");
+
+        let updater = updater(test_db()).with_provider(provider.clone(), Duration::from_secs(3600));
+
+        // One request per pass for a rate-limited carrier
+        assert_eq!(updater.poll_once().await.unwrap(), 1);
+        assert_eq!(provider.requests(), 1);
+        assert_eq!(updater.poll_once().await.unwrap(), 0);
+        assert_eq!(provider.requests(), 1);
+
+        let updater = updater.with_provider(provider.clone(), Duration::ZERO);
+        assert_eq!(updater.poll_once().await.unwrap(), 1);
+        assert_eq!(provider.requests(), 2);
+        assert_eq!(status(&updater, "s1").await, ShipmentStatus::Delivered);
+        assert_eq!(status(&updater, "s2").await, ShipmentStatus::Shipped);
+
+        // Delivered shipments are no longer polled, and repeat scans are not re-recorded
+        assert_eq!(updater.poll_once().await.unwrap(), 0);
+        assert_eq!(provider.requests(), 3);
+        assert_eq!(updater.get_tracking_events("s2").await.unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,264 @@
+//! Connectivity Monitor
+//!
+//! Probes the sync server now and then and tracks whether it is reachable,
+//! and how quickly. A status only changes after consecutive probes agree,
+//! so one dropped request doesn't flip the app offline.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info};
+use wms_core::error::{WmsError, Result};
+use wms_core::shutdown::TaskHandle;
+
+/// How long a probe may take before it counts as a failure
+pub const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Probes slower than this mark the connection as slow
+pub const SLOW_LATENCY: Duration = Duration::from_secs(2);
+
+/// Probes in a row that must agree before the status changes
+const PROBES_TO_CHANGE: u32 = 2;
+
+/// Network connection status
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ConnectionStatus {
+    Online,
+    Offline,
+    Slow,
+    Unknown,
+}
+
+impl Default for ConnectionStatus {
+    fn default() -> Self {
+        Self::Unknown
+    }
+}
+
+/// Checks whether the sync server answers
+pub(crate) trait ConnectionProber: Send + Sync {
+    /// Round-trip time of one request, or an error if none came back
+    fn probe(&self) -> BoxFuture<'_, Result<Duration>>;
+}
+
+/// Sends a HEAD request to the server URL. Any HTTP response counts as
+/// reachable; only timeouts and connection errors fail.
+pub(crate) struct HttpProber {
+    server_url: String,
+    client: reqwest::Client,
+}
+
+impl HttpProber {
+    pub(crate) fn new(server_url: &str) -> Self {
+        Self {
+            server_url: server_url.to_string(),
+            client: reqwest::Client::builder()
+                .timeout(PROBE_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl ConnectionProber for HttpProber {
+    fn probe(&self) -> BoxFuture<'_, Result<Duration>> {
+        Box::pin(async move {
+            let started = Instant::now();
+            self.client.head(&self.server_url).send().await
+                .map(|_| started.elapsed())
+                .map_err(|e| WmsError::Network(format!("Sync server unreachable: {}", e)))
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+struct MonitorState {
+    status: ConnectionStatus,
+    /// Status the latest probes point to, and how many in a row did
+    candidate: Option<(ConnectionStatus, u32)>,
+    /// Offline mode chosen by the user, whatever the probes say
+    forced_offline: bool,
+}
+
+/// Tracks the sync server's reachability. Shared between the sync engine,
+/// which checks it before syncing, and the task that runs the probes.
+pub struct ConnectivityMonitor {
+    prober: Option<Arc<dyn ConnectionProber>>,
+    state: Mutex<MonitorState>,
+}
+
+impl ConnectivityMonitor {
+    /// Monitor for the given server; without one there is nothing to probe
+    /// and the status stays unknown
+    pub fn new(server_url: Option<&str>) -> Self {
+        Self::with_prober(server_url.map(|url| Arc::new(HttpProber::new(url)) as Arc<dyn ConnectionProber>))
+    }
+
+    pub(crate) fn with_prober(prober: Option<Arc<dyn ConnectionProber>>) -> Self {
+        Self {
+            prober,
+            state: Mutex::new(MonitorState::default()),
+        }
+    }
+
+    /// Current status; offline while offline mode is on
+    pub fn status(&self) -> ConnectionStatus {
+        let state = self.state.lock().unwrap();
+        if state.forced_offline {
+            ConnectionStatus::Offline
+        } else {
+            state.status
+        }
+    }
+
+    /// Turn offline mode on or off. Probes keep running underneath, so the
+    /// status is current again as soon as it's turned off.
+    pub fn set_forced_offline(&self, offline: bool) {
+        self.state.lock().unwrap().forced_offline = offline;
+    }
+
+    /// Probe the server once and return the resulting status
+    pub async fn probe(&self) -> ConnectionStatus {
+        let Some(prober) = self.prober.clone() else {
+            return self.status();
+        };
+        let result = prober.probe().await;
+        self.record(result)
+    }
+
+    /// Probe every `interval` until `task` is told to stop, calling
+    /// `on_change` with each new status
+    pub async fn run_prober(
+        self: Arc<Self>,
+        interval: Duration,
+        task: TaskHandle,
+        on_change: impl Fn(ConnectionStatus),
+    ) {
+        if self.prober.is_none() {
+            debug!("No sync server configured; connectivity probes disabled");
+            return;
+        }
+
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {}
+                _ = task.shutdown_requested() => break,
+            }
+            let before = self.status();
+            let after = self.probe().await;
+            if after != before {
+                on_change(after);
+            }
+        }
+        info!("Connectivity probes stopped");
+    }
+
+    /// Fold one probe result into the status
+    fn record(&self, result: Result<Duration>) -> ConnectionStatus {
+        let reading = match &result {
+            Ok(latency) if *latency > SLOW_LATENCY => ConnectionStatus::Slow,
+            Ok(_) => ConnectionStatus::Online,
+            Err(e) => {
+                debug!("Connectivity probe failed: {}", e);
+                ConnectionStatus::Offline
+            }
+        };
+
+        let mut state = self.state.lock().unwrap();
+        if reading == state.status {
+            state.candidate = None;
+        } else {
+            let seen = match state.candidate {
+                Some((candidate, seen)) if candidate == reading => seen + 1,
+                _ => 1,
+            };
+            // The first reading settles an unknown status straight away
+            if seen >= PROBES_TO_CHANGE || state.status == ConnectionStatus::Unknown {
+                info!("Connection status changed from {:?} to {:?}", state.status, reading);
+                state.status = reading;
+                state.candidate = None;
+            } else {
+                state.candidate = Some((reading, seen));
+            }
+        }
+        drop(state);
+        self.status()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    /// Answers with the queued results in order
+    struct MockProber {
+        results: Mutex<VecDeque<Result<Duration>>>,
+    }
+
+    impl ConnectionProber for MockProber {
+        fn probe(&self) -> BoxFuture<'_, Result<Duration>> {
+            let result = self.results.lock().unwrap().pop_front().expect("no probe result queued");
+            Box::pin(async { result })
+        }
+    }
+
+    fn fast() -> Result<Duration> {
+        Ok(Duration::from_millis(50))
+    }
+
+    fn slow() -> Result<Duration> {
+        Ok(SLOW_LATENCY + Duration::from_millis(500))
+    }
+
+    fn failed() -> Result<Duration> {
+        Err(WmsError::Network("connection refused".to_string()))
+    }
+
+    fn monitor(results: Vec<Result<Duration>>) -> ConnectivityMonitor {
+        ConnectivityMonitor::with_prober(Some(Arc::new(MockProber {
+            results: Mutex::new(results.into()),
+        })))
+    }
+
+    #[tokio::test]
+    async fn test_two_failures_in_a_row_go_offline() {
+        let monitor = monitor(vec![fast(), failed(), fast(), failed(), failed(), fast(), fast()]);
+
+        assert_eq!(monitor.probe().await, ConnectionStatus::Online);
+        // A single failure between successes is ignored
+        assert_eq!(monitor.probe().await, ConnectionStatus::Online);
+        assert_eq!(monitor.probe().await, ConnectionStatus::Online);
+        assert_eq!(monitor.probe().await, ConnectionStatus::Online);
+        assert_eq!(monitor.probe().await, ConnectionStatus::Offline);
+        // Coming back takes two successes too
+        assert_eq!(monitor.probe().await, ConnectionStatus::Offline);
+        assert_eq!(monitor.probe().await, ConnectionStatus::Online);
+    }
+
+    #[tokio::test]
+    async fn test_high_latency_is_slow() {
+        let monitor = monitor(vec![slow(), fast(), slow(), fast(), fast()]);
+
+        assert_eq!(monitor.probe().await, ConnectionStatus::Slow);
+        assert_eq!(monitor.probe().await, ConnectionStatus::Slow);
+        assert_eq!(monitor.probe().await, ConnectionStatus::Slow);
+        assert_eq!(monitor.probe().await, ConnectionStatus::Slow);
+        assert_eq!(monitor.probe().await, ConnectionStatus::Online);
+    }
+
+    #[tokio::test]
+    async fn test_offline_mode_overrides_probes() {
+        let monitor = monitor(vec![fast(), fast()]);
+        monitor.set_forced_offline(true);
+
+        assert_eq!(monitor.probe().await, ConnectionStatus::Offline);
+
+        monitor.set_forced_offline(false);
+        assert_eq!(monitor.status(), ConnectionStatus::Online);
+        assert_eq!(monitor.probe().await, ConnectionStatus::Online);
+    }
+}
@@ -3,13 +3,50 @@
 //! Manages bidirectional sync between local SQLite and remote server
 //! using CRDTs for conflict-free merging.
 
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, MutexGuard};
+use base64::Engine;
 use chrono::{DateTime, Utc};
+use rusqlite::{Connection, OptionalExtension};
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use tracing::{info, warn, error, debug};
+use wms_core::attachments::Attachment;
 use wms_core::db::Database;
 use wms_core::error::{WmsError, Result};
+use wms_core::events::{SharedEmitter, WmsEvent, noop_emitter};
+use wms_core::shutdown::ShutdownSignal;
+use wms_core::types::parse_timestamp;
+use crate::connectivity::{ConnectionStatus, ConnectivityMonitor};
 use crate::crdt::CrdtDocument;
+use crate::policy::{last_writer, merge_policy, same_values, MergePolicy, Resolution};
+use crate::protocol::{ChangeOperation, ChangeRecord, SyncAck, SyncMessage};
+use crate::transport::{HttpTransport, SyncTransport};
+
+/// Attachment content is sent in pieces of this size so a dropped
+/// connection only loses one piece
+pub const ATTACHMENT_CHUNK_BYTES: usize = 256 * 1024;
+
+/// Outbox table name for attachment metadata and content chunks
+const ATTACHMENTS_TABLE: &str = "attachments";
+
+/// Outbox rows sent to the server per round trip
+pub const SYNC_BATCH_SIZE: usize = 100;
+
+/// Times a change the server rejects is sent again before it is left for
+/// someone to look at, counted in `SyncStatus::stuck_changes`
+pub const MAX_SYNC_RETRIES: i64 = 5;
+
+/// Settings key holding the server's cursor from the last pull
+const SYNC_CURSOR_KEY: &str = "sync_cursor";
+
+/// Table whose CRDT documents carry stock adjustments
+const STOCK_TABLE: &str = "inventory_stock";
+
+/// List of adjustment operations in a stock document
+const ADJUSTMENTS_KEY: &str = "adjustments";
+
+/// Author of ledger entries written by sync merges (see 038_sync_merges)
+const SYNC_USER_ID: &str = "sync";
 
 /// Synchronization status
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -17,53 +54,97 @@ pub struct SyncStatus {
     pub is_syncing: bool,
     pub last_sync_at: Option<DateTime<Utc>>,
     pub pending_changes: u64,
+    /// Pending changes the server rejected `MAX_SYNC_RETRIES` times; they
+    /// are no longer sent
+    pub stuck_changes: u64,
     pub sync_errors: u64,
     pub last_error: Option<String>,
     pub connection_status: ConnectionStatus,
 }
 
-/// Network connection status
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
-#[serde(rename_all = "lowercase")]
-pub enum ConnectionStatus {
-    Online,
-    Offline,
-    Slow,
-    Unknown,
+/// Sync state saved by `SyncEngine::checkpoint`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncCheckpoint {
+    /// Changes that were sent but never acknowledged, queued to send again
+    pub requeued_changes: u64,
+    pub pending_changes: u64,
+    pub last_sync_at: Option<DateTime<Utc>>,
 }
 
-impl Default for ConnectionStatus {
-    fn default() -> Self {
-        Self::Unknown
-    }
+/// Outcome of `SyncEngine::compact_crdt_documents`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrdtCompaction {
+    pub documents: u64,
+    /// Documents whose stored form got smaller
+    pub rewritten: u64,
+    pub bytes_before: u64,
+    pub bytes_after: u64,
 }
 
 /// The main synchronization engine
 pub struct SyncEngine {
     db: Arc<Database>,
-    status: SyncStatus,
+    /// Locked only briefly, never across an await, so status reads and
+    /// other engine calls don't wait on a sync's network round trips
+    status: Mutex<SyncStatus>,
     server_url: Option<String>,
     device_id: String,
+    events: SharedEmitter,
+    /// Set when a server URL is configured
+    transport: Option<Arc<dyn SyncTransport>>,
+    connectivity: Arc<ConnectivityMonitor>,
+    shutdown: ShutdownSignal,
 }
 
 impl SyncEngine {
     /// Create a new sync engine
     pub fn new(db: Arc<Database>) -> Result<Self> {
         let device_id = Self::get_or_create_device_id(&db)?;
+        let last_sync_at: Option<String> = db.query_row(
+            "SELECT MAX(last_sync_at) FROM sync_status",
+            [],
+            |row| row.get(0),
+        )?.flatten();
+        let server_url = std::env::var("WMS_SERVER_URL").ok();
+        let transport = server_url.as_deref()
+            .map(|url| HttpTransport::new(url, &device_id))
+            .transpose()?
+            .map(|transport| Arc::new(transport) as Arc<dyn SyncTransport>);
+        let connectivity = Arc::new(ConnectivityMonitor::new(server_url.as_deref()));
         
-        Ok(Self {
+        let engine = Self {
             db,
-            status: SyncStatus {
+            status: Mutex::new(SyncStatus {
                 is_syncing: false,
-                last_sync_at: None,
+                last_sync_at: last_sync_at.as_deref().and_then(parse_timestamp),
                 pending_changes: 0,
+                stuck_changes: 0,
                 sync_errors: 0,
                 last_error: None,
                 connection_status: ConnectionStatus::Unknown,
-            },
-            server_url: std::env::var("WMS_SERVER_URL").ok(),
+            }),
+            server_url,
             device_id,
-        })
+            events: noop_emitter(),
+            transport,
+            connectivity,
+            shutdown: ShutdownSignal::never(),
+        };
+        engine.update_pending_count()?;
+        
+        Ok(engine)
+    }
+    
+    /// Publish domain events through the given emitter
+    pub fn with_event_emitter(mut self, events: SharedEmitter) -> Self {
+        self.events = events;
+        self
+    }
+    
+    /// Stop syncing at the next batch boundary once `shutdown` is raised
+    pub fn with_shutdown_signal(mut self, shutdown: ShutdownSignal) -> Self {
+        self.shutdown = shutdown;
+        self
     }
     
     /// Get or create a unique device ID
@@ -87,80 +168,179 @@ impl SyncEngine {
         Ok(new_id)
     }
     
-    /// Get current sync status
-    pub fn get_status(&self) -> SyncStatus {
-        self.status.clone()
+    /// The engine's own status fields. A sync that panicked leaves them
+    /// readable rather than poisoning every later call.
+    fn state(&self) -> MutexGuard<'_, SyncStatus> {
+        self.status.lock().unwrap_or_else(|e| e.into_inner())
     }
     
-    /// Perform synchronization
-    pub async fn sync_now(&mut self) -> Result<SyncStatus> {
-        if self.status.is_syncing {
-            return Err(WmsError::SyncError("Sync already in progress".to_string()));
+    /// Get current sync status. Pending changes are counted afresh, since
+    /// the services queue them without going through the engine.
+    pub fn get_status(&self) -> SyncStatus {
+        let mut status = self.state().clone();
+        status.connection_status = self.connectivity.status();
+        match self.pending_count() {
+            Ok(pending) => status.pending_changes = pending,
+            Err(e) => warn!("Unable to count pending sync changes: {}", e),
         }
+        match self.stuck_count() {
+            Ok(stuck) => status.stuck_changes = stuck,
+            Err(e) => warn!("Unable to count stuck sync changes: {}", e),
+        }
+        status
+    }
+    
+    /// Perform synchronization. Only one sync runs at a time; a second
+    /// call while one is in flight fails straight away.
+    pub async fn sync_now(&self) -> Result<SyncStatus> {
+        let (Some(server_url), Some(transport)) = (self.server_url.clone(), self.transport.clone()) else {
+            return Err(WmsError::SyncError("No server URL configured".to_string()));
+        };
         
-        let server_url = self.server_url.clone()
-            .ok_or_else(|| WmsError::SyncError("No server URL configured".to_string()))?;
+        if self.connectivity.status() == ConnectionStatus::Offline {
+            return Err(WmsError::SyncError("offline".to_string()));
+        }
         
-        self.status.is_syncing = true;
+        {
+            let mut status = self.state();
+            if status.is_syncing {
+                return Err(WmsError::SyncError("Sync already in progress".to_string()));
+            }
+            status.is_syncing = true;
+        }
         info!("Starting synchronization with server: {}", server_url);
         
-        match self.perform_sync(&server_url).await {
-            Ok(_) => {
-                self.status.last_sync_at = Some(Utc::now());
-                self.status.sync_errors = 0;
-                self.status.last_error = None;
-                info!("Synchronization completed successfully");
-            }
-            Err(e) => {
-                self.status.sync_errors += 1;
-                self.status.last_error = Some(e.to_string());
-                error!("Synchronization failed: {}", e);
+        let outcome = self.perform_sync(transport.as_ref()).await;
+        {
+            let mut status = self.state();
+            match outcome {
+                Ok(true) => {
+                    status.last_sync_at = Some(Utc::now());
+                    status.sync_errors = 0;
+                    status.last_error = None;
+                    info!("Synchronization completed successfully");
+                }
+                Ok(false) => {
+                    status.last_error = Some("Sync stopped for shutdown".to_string());
+                    info!("Synchronization stopped at a batch boundary for shutdown");
+                }
+                Err(e) => {
+                    status.sync_errors += 1;
+                    status.last_error = Some(e.to_string());
+                    error!("Synchronization failed: {}", e);
+                }
             }
+            status.is_syncing = false;
         }
-        
-        self.status.is_syncing = false;
         self.update_pending_count()?;
         
-        Ok(self.status.clone())
+        let (pending_changes, error) = {
+            let status = self.state();
+            (status.pending_changes, status.last_error.clone())
+        };
+        self.events.emit(WmsEvent::SyncCompleted {
+            success: error.is_none(),
+            pending_changes,
+            error,
+        });
+        
+        Ok(self.get_status())
     }
     
-    /// Internal sync logic
-    async fn perform_sync(&self, server_url: &str) -> Result<()> {
-        // Step 1: Get pending outbox items
-        let pending = self.get_pending_changes()?;
-        debug!("Found {} pending changes to sync", pending.len());
+    /// Internal sync logic. Returns false if shutdown stopped it early.
+    async fn perform_sync(&self, transport: &dyn SyncTransport) -> Result<bool> {
+        // Changes the server turned down last time are offered again
+        self.requeue_rejected_changes()?;
         
-        // Step 2: Send local changes to server
-        for change in &pending {
-            self.send_change(server_url, change).await?;
+        // Step 1: Send local changes a batch at a time. Each batch is
+        // acknowledged before the next is read, so stopping between batches
+        // leaves nothing sent but unacknowledged. Rejected changes stay
+        // marked as sent so they aren't read again in this sync.
+        let mut rejected = 0;
+        loop {
+            if self.shutdown.is_requested() {
+                return Ok(false);
+            }
+            
+            let batch = self.get_pending_changes()?;
+            if batch.is_empty() {
+                break;
+            }
+            debug!("Sending batch of {} changes", batch.len());
+            
+            let records = batch.iter()
+                .map(|change| change.to_change_record(&self.device_id))
+                .collect::<Result<Vec<_>>>()?;
+            let message = SyncMessage::push(&self.device_id, records);
+            
+            self.mark_changes_sent(&batch)?;
+            let ack = match transport.push(&message).await {
+                Ok(ack) => ack,
+                Err(e) => {
+                    self.requeue_changes(&batch)?;
+                    return Err(e);
+                }
+            };
+            rejected += self.settle_changes(&batch, &ack)?;
         }
         
-        // Step 3: Get server changes since last sync
-        let server_changes = self.fetch_server_changes(server_url).await?;
-        debug!("Received {} changes from server", server_changes.len());
-        
-        // Step 4: Apply server changes using CRDT merge
-        for change in server_changes {
-            self.apply_server_change(&change)?;
+        // Step 2: Get server changes a page at a time, starting where the
+        // last pull stopped. The cursor is saved once a page is in the
+        // inbox, so an interrupted sync doesn't fetch it again.
+        loop {
+            if self.shutdown.is_requested() {
+                return Ok(false);
+            }
+            
+            let since = self.db.get_setting(SYNC_CURSOR_KEY)?;
+            let page = transport.pull(since.as_deref()).await?;
+            debug!("Received {} changes from server", page.changes.len());
+            
+            // Step 3: Merge server changes under each table's policy
+            for record in page.changes {
+                self.apply_server_change(&ServerChange::from(record))?;
+            }
+            
+            match page.cursor {
+                Some(cursor) if since.as_deref() != Some(cursor.as_str()) => {
+                    self.db.set_setting(SYNC_CURSOR_KEY, &cursor)?;
+                    if !page.has_more {
+                        break;
+                    }
+                }
+                // Without a new cursor there is no way to ask for the next page
+                _ => break,
+            }
         }
         
-        // Step 5: Mark sent changes as acknowledged
-        for change in &pending {
-            self.mark_change_acknowledged(&change.id)?;
-        }
+        // Step 4: Write the merged records into their tables
+        let applied = self.process_inbox()?;
+        debug!("Applied {} inbox changes", applied);
         
-        Ok(())
+        if rejected > 0 {
+            return Err(WmsError::SyncError(format!(
+                "Server rejected {} changes; each is sent again on later syncs, up to {} times",
+                rejected, MAX_SYNC_RETRIES
+            )));
+        }
+        Ok(true)
     }
     
-    /// Get pending changes from outbox
+    /// Get the next batch of pending changes from the outbox
     fn get_pending_changes(&self) -> Result<Vec<OutboxItem>> {
         let items = self.db.query_map(
             "SELECT id, table_name, record_id, operation, payload, version, created_at 
              FROM sync_outbox 
              WHERE sent_at IS NULL 
-             ORDER BY created_at ASC 
-             LIMIT 100",
-            [],
+               AND (?1 = 0 OR table_name != ?2)
+             ORDER BY created_at ASC, rowid ASC 
+             LIMIT ?3",
+            // Attachments wait for a better connection
+            rusqlite::params![
+                self.connectivity.status() == ConnectionStatus::Slow,
+                ATTACHMENTS_TABLE,
+                SYNC_BATCH_SIZE as i64,
+            ],
             |row| {
                 Ok(OutboxItem {
                     id: row.get(0)?,
@@ -177,32 +357,87 @@ impl SyncEngine {
         Ok(items)
     }
     
-    /// Send a change to the server
-    async fn send_change(&self, _server_url: &str, change: &OutboxItem) -> Result<()> {
-        // TODO: Implement actual gRPC/HTTP call
-        debug!("Sending change: {} {} {}", change.table_name, change.operation, change.record_id);
+    /// Mark a batch as in flight
+    fn mark_changes_sent(&self, changes: &[OutboxItem]) -> Result<()> {
+        self.db.transaction(|conn| {
+            for change in changes {
+                conn.execute(
+                    "UPDATE sync_outbox SET sent_at = datetime('now') WHERE id = ?",
+                    rusqlite::params![&change.id],
+                )?;
+            }
+            Ok(())
+        })
+    }
+    
+    /// Record the server's verdict on a sent batch: accepted changes are
+    /// acknowledged, the rest keep the server's error. Returns the number
+    /// not accepted.
+    fn settle_changes(&self, changes: &[OutboxItem], ack: &SyncAck) -> Result<u64> {
+        let accepted: Vec<&OutboxItem> = changes.iter()
+            .filter(|change| ack.change_ids.contains(&change.id))
+            .collect();
+        self.mark_changes_acknowledged(&accepted)?;
         
-        // Mark as sent
+        self.db.transaction(|conn| {
+            let mut rejected = 0;
+            for change in changes.iter().filter(|change| !ack.change_ids.contains(&change.id)) {
+                let error = ack.errors.iter()
+                    .find(|error| error.change_id == change.id)
+                    .map(|error| format!("{}: {}", error.error_code, error.message))
+                    .unwrap_or_else(|| "Not acknowledged by the server".to_string());
+                warn!("Server rejected {} {} {}: {}", change.operation, change.table_name, change.record_id, error);
+                conn.execute(
+                    "UPDATE sync_outbox SET retry_count = retry_count + 1, last_error = ? WHERE id = ?",
+                    rusqlite::params![&error, &change.id],
+                )?;
+                rejected += 1;
+            }
+            Ok(rejected)
+        })
+    }
+    
+    /// Queue changes rejected by an earlier sync to be sent again, unless
+    /// they have been rejected `MAX_SYNC_RETRIES` times already
+    fn requeue_rejected_changes(&self) -> Result<()> {
         self.db.execute(
-            "UPDATE sync_outbox SET sent_at = datetime('now') WHERE id = ?",
-            rusqlite::params![&change.id],
+            "UPDATE sync_outbox SET sent_at = NULL
+             WHERE sent_at IS NOT NULL AND acknowledged_at IS NULL AND last_error IS NOT NULL
+               AND retry_count < ?",
+            [MAX_SYNC_RETRIES],
         )?;
-        
         Ok(())
     }
     
-    /// Fetch changes from server
-    async fn fetch_server_changes(&self, _server_url: &str) -> Result<Vec<ServerChange>> {
-        // TODO: Implement actual gRPC/HTTP call
-        // For now, return empty list
-        Ok(Vec::new())
+    /// Put a batch the server didn't take back in the queue
+    fn requeue_changes(&self, changes: &[OutboxItem]) -> Result<()> {
+        self.db.transaction(|conn| {
+            for change in changes {
+                conn.execute(
+                    "UPDATE sync_outbox SET sent_at = NULL, retry_count = retry_count + 1 WHERE id = ?",
+                    rusqlite::params![&change.id],
+                )?;
+            }
+            Ok(())
+        })
     }
     
-    /// Apply a server change using CRDT merge
+    /// Apply a server change under its table's merge policy. Only
+    /// CRDT-merged tables keep CRDT documents; other changes go straight
+    /// to the inbox with the record the server sent.
     fn apply_server_change(&self, change: &ServerChange) -> Result<()> {
         debug!("Applying server change: {} {} {}", 
                change.table_name, change.operation, change.record_id);
         
+        let policy = merge_policy(&change.table_name)?;
+        if policy != MergePolicy::CrdtMerge {
+            let payload = change.payload.as_deref().ok_or_else(|| WmsError::SyncError(format!(
+                "{} change to {} {} has no record",
+                policy.as_str(), change.table_name, change.record_id
+            )))?;
+            return self.queue_inbox(&change.table_name, &change.record_id, &change.operation, payload);
+        }
+        
         // Load existing CRDT document
         let existing_doc = self.load_crdt_document(&change.table_name, &change.record_id)?;
         
@@ -270,40 +505,173 @@ impl SyncEngine {
     /// Apply CRDT document state to SQL table
     fn apply_to_sql_table(&self, table_name: &str, record_id: &str, doc: &CrdtDocument) -> Result<()> {
         let data = doc.to_json()?;
-        
-        // Insert into inbox for later processing
+        self.queue_inbox(table_name, record_id, "MERGE", &data)
+    }
+    
+    /// Insert a received record into the inbox for `process_inbox`. Writes
+    /// made when it is applied bump row versions (see 016_row_versions), so
+    /// local edits based on the pre-sync record conflict instead of
+    /// overwriting it.
+    fn queue_inbox(&self, table_name: &str, record_id: &str, operation: &str, payload: &str) -> Result<()> {
         self.db.execute(
             "INSERT INTO sync_inbox (id, table_name, record_id, operation, payload, server_version, received_at)
-             VALUES (?, ?, ?, 'MERGE', ?, 0, datetime('now'))",
+             VALUES (?, ?, ?, ?, ?, 0, datetime('now'))",
             rusqlite::params![
                 uuid::Uuid::new_v4().to_string(),
                 table_name,
                 record_id,
-                &data,
+                operation,
+                payload,
             ],
         )?;
         
         Ok(())
     }
     
-    /// Mark a change as acknowledged
-    fn mark_change_acknowledged(&self, change_id: &str) -> Result<()> {
-        self.db.execute(
-            "UPDATE sync_outbox SET acknowledged_at = datetime('now') WHERE id = ?",
-            rusqlite::params![change_id],
+    /// Write received records into their tables, each under its table's
+    /// merge policy. Returns the number applied; a record that cannot be
+    /// written stays in the inbox for the next sync.
+    pub fn process_inbox(&self) -> Result<u64> {
+        let pending = self.db.query_map(
+            "SELECT id, table_name, record_id, operation, payload FROM sync_inbox
+             WHERE applied_at IS NULL
+             ORDER BY received_at ASC, rowid ASC",
+            [],
+            |row| Ok(InboxItem {
+                id: row.get(0)?,
+                table_name: row.get(1)?,
+                record_id: row.get(2)?,
+                operation: row.get(3)?,
+                payload: row.get(4)?,
+            }),
         )?;
-        Ok(())
+        
+        let mut applied = 0;
+        for item in &pending {
+            match self.apply_inbox_item(item) {
+                Ok(()) => applied += 1,
+                Err(e) => warn!(
+                    "Unable to apply {} to {} {}: {}",
+                    item.operation, item.table_name, item.record_id, e
+                ),
+            }
+        }
+        Ok(applied)
     }
     
-    /// Update pending change count in status
-    fn update_pending_count(&mut self) -> Result<()> {
+    fn apply_inbox_item(&self, item: &InboxItem) -> Result<()> {
+        let policy = merge_policy(&item.table_name)?;
+        let remote = match serde_json::from_str(&item.payload)? {
+            Value::Object(remote) => remote,
+            _ => return Err(WmsError::SyncError("Inbox payload is not a record".to_string())),
+        };
+        let table = item.table_name.as_str();
+        let id = item.record_id.as_str();
+        let delete = item.operation == "DELETE";
+        
+        self.db.transaction(|conn| {
+            let local = load_row(conn, table, id)?;
+            let conflict = match (policy, local) {
+                (MergePolicy::CrdtMerge, local) if table == STOCK_TABLE => {
+                    apply_stock_document(conn, id, &remote, local)?;
+                    None
+                }
+                (MergePolicy::AppendOnlyLog, None) if item.operation == "INSERT" => {
+                    write_row(conn, table, id, &remote, false)?;
+                    None
+                }
+                // The same entry delivered again
+                (MergePolicy::AppendOnlyLog, Some(local))
+                    if item.operation == "INSERT" && same_values(&local, &remote) => None,
+                (MergePolicy::AppendOnlyLog, local) => {
+                    Some((local.unwrap_or_default(), Resolution::Rejected))
+                }
+                // Other merged documents overwrite the fields they carry,
+                // unless this device wrote the record later
+                (MergePolicy::LastWriterWins | MergePolicy::CrdtMerge, Some(local)) => match last_writer(&local, &remote) {
+                    Resolution::RemoteWins => {
+                        let overwritten = has_pending_changes(conn, table, id)?;
+                        if delete {
+                            delete_row(conn, table, id)?;
+                        } else {
+                            write_row(conn, table, id, &remote, true)?;
+                        }
+                        overwritten.then_some((local, Resolution::RemoteWins))
+                    }
+                    // A stale copy of what we already have is no conflict
+                    _ if same_values(&local, &remote) => None,
+                    resolution => Some((local, resolution)),
+                },
+                (_, local) => {
+                    if delete {
+                        delete_row(conn, table, id)?;
+                    } else {
+                        write_row(conn, table, id, &remote, local.is_some())?;
+                    }
+                    None
+                }
+            };
+            
+            let resolution = conflict.as_ref().map(|(_, resolution)| *resolution);
+            if let Some((local, resolution)) = &conflict {
+                record_conflict(conn, table, id, local, &remote, policy, *resolution)?;
+                info!(
+                    "Sync conflict on {} {} resolved by {}: {}",
+                    table, id, policy.as_str(), resolution.as_str()
+                );
+            }
+            conn.execute(
+                "UPDATE sync_inbox SET applied_at = datetime('now'), conflict_resolved = ?,
+                    resolution_strategy = ?
+                 WHERE id = ?",
+                rusqlite::params![
+                    resolution.is_some(),
+                    resolution.map_or(policy.as_str(), |r| r.as_str()),
+                    &item.id,
+                ],
+            )?;
+            Ok(())
+        })
+    }
+    
+    /// Mark changes as stored by the server
+    fn mark_changes_acknowledged(&self, changes: &[&OutboxItem]) -> Result<()> {
+        self.db.transaction(|conn| {
+            for change in changes {
+                conn.execute(
+                    "UPDATE sync_outbox SET acknowledged_at = datetime('now') WHERE id = ?",
+                    rusqlite::params![&change.id],
+                )?;
+            }
+            Ok(())
+        })
+    }
+    
+    /// Changes the server hasn't acknowledged yet
+    fn pending_count(&self) -> Result<u64> {
         let count: i64 = self.db.query_row(
             "SELECT COUNT(*) FROM sync_outbox WHERE acknowledged_at IS NULL",
             [],
             |row| row.get(0),
         )?.unwrap_or(0);
-        
-        self.status.pending_changes = count as u64;
+        Ok(count as u64)
+    }
+    
+    /// Count rejected changes that are no longer sent
+    fn stuck_count(&self) -> Result<u64> {
+        let count: i64 = self.db.query_row(
+            "SELECT COUNT(*) FROM sync_outbox
+             WHERE acknowledged_at IS NULL AND last_error IS NOT NULL AND retry_count >= ?",
+            [MAX_SYNC_RETRIES],
+            |row| row.get(0),
+        )?.unwrap_or(0);
+        Ok(count as u64)
+    }
+    
+    /// Update pending change count in status
+    fn update_pending_count(&self) -> Result<()> {
+        let pending = self.pending_count()?;
+        self.state().pending_changes = pending;
         Ok(())
     }
     
@@ -323,15 +691,165 @@ impl SyncEngine {
         Ok(())
     }
     
-    /// Update connection status
-    pub fn set_connection_status(&mut self, status: ConnectionStatus) {
-        self.status.connection_status = status;
+    /// Queue an attachment for sync: its metadata, then its content as
+    /// base64 chunks of `ATTACHMENT_CHUNK_BYTES`. Used for item images and
+    /// proof-of-delivery photos alike.
+    pub fn queue_attachment(&self, attachment: &Attachment, content: &[u8]) -> Result<()> {
+        let metadata = serde_json::to_string(attachment)?;
+        let chunks: Vec<&[u8]> = content.chunks(ATTACHMENT_CHUNK_BYTES).collect();
+        let total = chunks.len();
+        
+        self.db.transaction(|conn| {
+            conn.execute(
+                "INSERT INTO sync_outbox (id, table_name, record_id, operation, payload, version, created_at)
+                 VALUES (?, ?, ?, 'INSERT', ?, 1, datetime('now'))",
+                rusqlite::params![
+                    uuid::Uuid::new_v4().to_string(),
+                    ATTACHMENTS_TABLE,
+                    &attachment.id,
+                    &metadata,
+                ],
+            )?;
+            for (index, chunk) in chunks.iter().enumerate() {
+                let payload = serde_json::json!({
+                    "index": index,
+                    "total": total,
+                    "data": base64::engine::general_purpose::STANDARD.encode(chunk),
+                });
+                conn.execute(
+                    "INSERT INTO sync_outbox (id, table_name, record_id, operation, payload, version, created_at)
+                     VALUES (?, ?, ?, 'CHUNK', ?, 1, datetime('now'))",
+                    rusqlite::params![
+                        uuid::Uuid::new_v4().to_string(),
+                        ATTACHMENTS_TABLE,
+                        &attachment.id,
+                        payload.to_string(),
+                    ],
+                )?;
+            }
+            Ok(())
+        })?;
+        
+        debug!("Queued attachment {} in {} chunks", attachment.id, total);
+        Ok(())
+    }
+    
+    /// Reachability of the sync server, shared with the task probing it
+    pub fn connectivity(&self) -> Arc<ConnectivityMonitor> {
+        self.connectivity.clone()
+    }
+    
+    /// Re-save every stored CRDT document in Automerge's compact form,
+    /// replacing change chunks appended by earlier merges
+    pub fn compact_crdt_documents(&self) -> Result<CrdtCompaction> {
+        let documents: Vec<(String, Vec<u8>)> = self.db.query_map(
+            "SELECT id, compressed_changes FROM crdt_documents",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        
+        let mut compaction = CrdtCompaction {
+            documents: documents.len() as u64,
+            rewritten: 0,
+            bytes_before: 0,
+            bytes_after: 0,
+        };
+        for (id, stored) in documents {
+            let compacted = CrdtDocument::from_changes(&stored)?.save()?;
+            compaction.bytes_before += stored.len() as u64;
+            if compacted.len() < stored.len() {
+                self.db.execute(
+                    "UPDATE crdt_documents SET compressed_changes = ?, updated_at = datetime('now') WHERE id = ?",
+                    rusqlite::params![&compacted, &id],
+                )?;
+                compaction.rewritten += 1;
+                compaction.bytes_after += compacted.len() as u64;
+            } else {
+                compaction.bytes_after += stored.len() as u64;
+            }
+        }
+        
+        info!(
+            "Compacted {} CRDT documents: {} -> {} bytes",
+            compaction.documents, compaction.bytes_before, compaction.bytes_after
+        );
+        Ok(compaction)
+    }
+    
+    /// Leave sync state safe to resume after the app exits: changes sent
+    /// but never acknowledged go back in the queue and the sync cursor is
+    /// saved. Call once any in-flight sync has stopped.
+    pub fn checkpoint(&self) -> Result<SyncCheckpoint> {
+        let last_sync_at = self.state().last_sync_at.map(|t| t.to_rfc3339());
+        let requeued = self.db.transaction(|conn| {
+            let requeued = conn.execute(
+                "UPDATE sync_outbox SET sent_at = NULL
+                 WHERE sent_at IS NOT NULL AND acknowledged_at IS NULL",
+                [],
+            )?;
+            conn.execute(
+                "UPDATE sync_status SET
+                    last_sync_at = COALESCE(?, last_sync_at),
+                    pending_changes = (SELECT COUNT(*) FROM sync_outbox o
+                                       WHERE o.table_name = sync_status.table_name
+                                         AND o.acknowledged_at IS NULL),
+                    updated_at = datetime('now')",
+                rusqlite::params![last_sync_at],
+            )?;
+            Ok(requeued)
+        })?;
+        self.update_pending_count()?;
+        
+        if requeued > 0 {
+            warn!("Requeued {} unacknowledged sync changes", requeued);
+        }
+        let status = self.state();
+        Ok(SyncCheckpoint {
+            requeued_changes: requeued as u64,
+            pending_changes: status.pending_changes,
+            last_sync_at: status.last_sync_at,
+        })
+    }
+    
+    /// Forget all sync cursors after the database was replaced (e.g. by a
+    /// backup restore) so the next sync does a full handshake with the server
+    pub fn require_full_resync(&self) -> Result<()> {
+        self.db.transaction(|conn| {
+            conn.execute(
+                "UPDATE sync_status SET last_sync_at = NULL, last_sync_version = 0,
+                    updated_at = datetime('now')",
+                [],
+            )?;
+            // Anything sent but never acknowledged must be sent again
+            conn.execute(
+                "UPDATE sync_outbox SET sent_at = NULL WHERE acknowledged_at IS NULL",
+                [],
+            )?;
+            conn.execute(
+                "DELETE FROM settings WHERE key = ?",
+                rusqlite::params![SYNC_CURSOR_KEY],
+            )?;
+            // Keep this device's identity even if the backup came from another one
+            conn.execute(
+                "INSERT INTO settings (key, value, description)
+                 VALUES ('device_id', ?, 'Unique device identifier')
+                 ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+                rusqlite::params![&self.device_id],
+            )?;
+            Ok(())
+        })?;
+        
+        self.state().last_sync_at = None;
+        self.update_pending_count()?;
+        
+        warn!("Sync state reset; next sync will perform a full handshake");
+        Ok(())
     }
 }
 
 /// Outbox item representing a pending local change
 #[derive(Debug)]
-struct OutboxItem {
+pub(crate) struct OutboxItem {
     id: String,
     table_name: String,
     record_id: String,
@@ -341,12 +859,826 @@ struct OutboxItem {
     created_at: String,
 }
 
+impl OutboxItem {
+    /// The change as sent to the server, under the outbox row's id so the
+    /// server's acknowledgement can be matched back to it
+    fn to_change_record(&self, actor_id: &str) -> Result<ChangeRecord> {
+        let operation = ChangeOperation::parse(&self.operation).ok_or_else(|| WmsError::SyncError(format!(
+            "Unknown operation {} queued for {} {}",
+            self.operation, self.table_name, self.record_id
+        )))?;
+        Ok(ChangeRecord {
+            id: self.id.clone(),
+            table_name: self.table_name.clone(),
+            record_id: self.record_id.clone(),
+            operation,
+            version: self.version,
+            timestamp: outbox_timestamp(&self.created_at),
+            actor_id: actor_id.to_string(),
+            json_payload: Some(self.payload.clone()),
+            crdt_changes: None,
+        })
+    }
+}
+
+/// Outbox rows are stamped by SQLite's `datetime('now')`, in UTC
+fn outbox_timestamp(value: &str) -> DateTime<Utc> {
+    parse_timestamp(value)
+        .or_else(|| {
+            chrono::NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S")
+                .ok()
+                .map(|t| t.and_utc())
+        })
+        .unwrap_or_else(Utc::now)
+}
+
 /// Server change to be applied locally
 #[derive(Debug)]
-struct ServerChange {
+pub(crate) struct ServerChange {
     table_name: String,
     record_id: String,
     operation: String,
     crdt_changes: Vec<u8>,
+    /// The record as JSON, for tables merged without CRDT documents
+    payload: Option<String>,
+}
+
+impl From<ChangeRecord> for ServerChange {
+    fn from(record: ChangeRecord) -> Self {
+        Self {
+            table_name: record.table_name,
+            record_id: record.record_id,
+            operation: record.operation.as_str().to_string(),
+            crdt_changes: record.crdt_changes.unwrap_or_default(),
+            payload: record.json_payload,
+        }
+    }
+}
+
+/// Received record waiting in the inbox
+#[derive(Debug)]
+struct InboxItem {
+    id: String,
+    table_name: String,
+    record_id: String,
+    operation: String,
+    payload: String,
+}
+
+/// Columns of a synced table. Table names come from the merge policy
+/// registry, never from the server, before they reach SQL.
+fn table_columns(conn: &Connection, table: &str) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare("SELECT name FROM pragma_table_info(?)")?;
+    let columns = stmt.query_map([table], |row| row.get(0))?
+        .collect::<rusqlite::Result<Vec<String>>>()?;
+    Ok(columns)
 }
 
+/// A row as a JSON object, if it exists
+fn load_row(conn: &Connection, table: &str, id: &str) -> Result<Option<Map<String, Value>>> {
+    let row = conn.query_row(
+        &format!("SELECT * FROM {} WHERE id = ?", table),
+        [id],
+        |row| {
+            let names: Vec<String> = row.as_ref().column_names().iter().map(|n| n.to_string()).collect();
+            let mut record = Map::new();
+            for (i, name) in names.into_iter().enumerate() {
+                let value = match row.get_ref(i)? {
+                    rusqlite::types::ValueRef::Null | rusqlite::types::ValueRef::Blob(_) => Value::Null,
+                    rusqlite::types::ValueRef::Integer(n) => Value::from(n),
+                    rusqlite::types::ValueRef::Real(n) => Value::from(n),
+                    rusqlite::types::ValueRef::Text(text) => Value::from(String::from_utf8_lossy(text).into_owned()),
+                };
+                record.insert(name, value);
+            }
+            Ok(record)
+        },
+    ).optional()?;
+    Ok(row)
+}
+
+fn sql_value(value: &Value) -> rusqlite::types::Value {
+    use rusqlite::types::Value as Sql;
+    match value {
+        Value::Null => Sql::Null,
+        Value::Bool(b) => Sql::Integer(*b as i64),
+        Value::Number(n) => n.as_i64().map(Sql::Integer).unwrap_or_else(|| Sql::Real(n.as_f64().unwrap_or_default())),
+        Value::String(s) => Sql::Text(s.clone()),
+        other => Sql::Text(other.to_string()),
+    }
+}
+
+/// Insert a record, or when the row `exists` overwrite the columns the
+/// record has. Fields that aren't columns of the table are ignored.
+fn write_row(conn: &Connection, table: &str, id: &str, record: &Map<String, Value>, exists: bool) -> Result<()> {
+    let columns: Vec<String> = table_columns(conn, table)?
+        .into_iter()
+        .filter(|c| c != "id" && record.contains_key(c))
+        .collect();
+    let mut values: Vec<_> = columns.iter().map(|c| sql_value(&record[c])).collect();
+    values.push(rusqlite::types::Value::Text(id.to_string()));
+    
+    let sql = if exists {
+        if columns.is_empty() {
+            return Ok(());
+        }
+        let updates: Vec<String> = columns.iter().map(|c| format!("{} = ?", c)).collect();
+        format!("UPDATE {} SET {} WHERE id = ?", table, updates.join(", "))
+    } else {
+        format!(
+            "INSERT INTO {} ({}id) VALUES ({}?)",
+            table,
+            columns.iter().map(|c| format!("{}, ", c)).collect::<String>(),
+            "?, ".repeat(columns.len()),
+        )
+    };
+    conn.execute(&sql, rusqlite::params_from_iter(values))?;
+    Ok(())
+}
+
+/// Set a stock row to the quantity its CRDT document adds up to, and log
+/// the difference as a MERGE ledger entry. Setting the total rather than
+/// adding the newest operations means applying a document twice changes
+/// nothing the second time. Fails, leaving the document for a later sync,
+/// while this device has changes to the row that haven't been sent.
+fn apply_stock_document(
+    conn: &Connection,
+    id: &str,
+    remote: &Map<String, Value>,
+    local: Option<Map<String, Value>>,
+) -> Result<()> {
+    let stored: Vec<u8> = conn.query_row(
+        "SELECT compressed_changes FROM crdt_documents WHERE document_type = ? AND record_id = ?",
+        [STOCK_TABLE, id],
+        |row| row.get(0),
+    ).optional()?
+        .ok_or_else(|| WmsError::SyncError(format!("No CRDT document for stock {}", id)))?;
+    let quantity = CrdtDocument::from_changes(&stored)?.calculate_sum(ADJUSTMENTS_KEY)?;
+    
+    let field = |record: &Map<String, Value>, key: &str| record.get(key).and_then(Value::as_str).map(str::to_string);
+    let item_id = field(remote, "item_id");
+    let location_id = field(remote, "location_id");
+    let lot_number = field(remote, "lot_number");
+    
+    // This device may hold the same item, location and lot under another id
+    let local = match local {
+        Some(local) => Some(local),
+        None => {
+            let local_id: Option<String> = conn.query_row(
+                "SELECT id FROM inventory_stock
+                 WHERE item_id = ? AND location_id = ? AND COALESCE(lot_number, '') = COALESCE(?, '')",
+                rusqlite::params![&item_id, &location_id, &lot_number],
+                |row| row.get(0),
+            ).optional()?;
+            match local_id {
+                Some(local_id) => load_row(conn, STOCK_TABLE, &local_id)?,
+                None => None,
+            }
+        }
+    };
+    
+    // Adjustments made here and not yet acknowledged are missing from the
+    // document, and setting its total would drop them. The document stays
+    // in the inbox until they have been sent and merged into it.
+    let key = |name: &str| local.as_ref().and_then(|local| field(local, name)).or_else(|| field(remote, name));
+    let local_id = local.as_ref().and_then(|local| field(local, "id"));
+    if stock_has_pending_changes(conn, id, local_id.as_deref(), &key("item_id"), &key("location_id"), &key("lot_number"))? {
+        return Err(WmsError::SyncError(format!(
+            "Stock {} has local adjustments waiting to be sent; its document is applied after they are",
+            id
+        )));
+    }
+    
+    let previous = match &local {
+        Some(local) => {
+            conn.execute(
+                "UPDATE inventory_stock SET quantity = ?, updated_at = datetime('now') WHERE id = ?",
+                rusqlite::params![quantity, field(local, "id")],
+            )?;
+            local.get("quantity").and_then(Value::as_f64).unwrap_or(0.0)
+        }
+        None => {
+            let (Some(item_id), Some(location_id)) = (&item_id, &location_id) else {
+                return Err(WmsError::SyncError(format!("Stock document {} has no item or location", id)));
+            };
+            // Stock without a lot is keyed by an empty lot, as adjustments
+            // store it; a NULL would never match their row
+            conn.execute(
+                "INSERT INTO inventory_stock (id, item_id, location_id, quantity, lot_number, updated_at)
+                 VALUES (?, ?, ?, ?, ?, datetime('now'))",
+                rusqlite::params![id, item_id, location_id, quantity, lot_number.clone().unwrap_or_default()],
+            )?;
+            0.0
+        }
+    };
+    
+    let delta = quantity - previous;
+    if delta.abs() > f64::EPSILON {
+        let local = local.unwrap_or_default();
+        conn.execute(
+            "INSERT INTO inventory_transactions (
+                id, item_id, location_id, transaction_type, quantity,
+                previous_quantity, new_quantity, lot_number, reason_code, user_id
+            ) VALUES (?, ?, ?, 'MERGE', ?, ?, ?, ?, 'SYNC_MERGE', ?)",
+            rusqlite::params![
+                uuid::Uuid::new_v4().to_string(),
+                field(&local, "item_id").or(item_id),
+                field(&local, "location_id").or(location_id),
+                delta,
+                previous,
+                quantity,
+                field(&local, "lot_number").or(lot_number),
+                SYNC_USER_ID,
+            ],
+        )?;
+    }
+    Ok(())
+}
+
+fn delete_row(conn: &Connection, table: &str, id: &str) -> Result<()> {
+    conn.execute(&format!("DELETE FROM {} WHERE id = ?", table), [id])?;
+    Ok(())
+}
+
+/// Whether this device has changes to the record the server hasn't taken
+fn has_pending_changes(conn: &Connection, table: &str, id: &str) -> Result<bool> {
+    let pending = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sync_outbox
+                        WHERE table_name = ? AND record_id = ? AND acknowledged_at IS NULL)",
+        [table, id],
+        |row| row.get(0),
+    )?;
+    Ok(pending)
+}
+
+/// Whether this device has unacknowledged changes to a stock row, either
+/// to the row itself or as ledger entries for its item, location and lot.
+/// Changes rejected `MAX_SYNC_RETRIES` times are never sent again, so
+/// they don't count.
+fn stock_has_pending_changes(
+    conn: &Connection,
+    id: &str,
+    local_id: Option<&str>,
+    item_id: &Option<String>,
+    location_id: &Option<String>,
+    lot_number: &Option<String>,
+) -> Result<bool> {
+    let pending = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sync_outbox o
+                        LEFT JOIN inventory_transactions t
+                          ON o.table_name = 'inventory_transactions' AND t.id = o.record_id
+                        WHERE o.acknowledged_at IS NULL
+                          AND NOT (o.last_error IS NOT NULL AND o.retry_count >= ?6)
+                          AND ((o.table_name = 'inventory_stock' AND o.record_id IN (?1, ?2))
+                               OR (t.item_id = ?3 AND t.location_id = ?4
+                                   AND COALESCE(t.lot_number, '') = COALESCE(?5, ''))))",
+        rusqlite::params![id, local_id.unwrap_or(id), item_id, location_id, lot_number, MAX_SYNC_RETRIES],
+        |row| row.get(0),
+    )?;
+    Ok(pending)
+}
+
+/// Log a conflict with the policy that resolved it and how
+fn record_conflict(
+    conn: &Connection,
+    table: &str,
+    id: &str,
+    local: &Map<String, Value>,
+    remote: &Map<String, Value>,
+    policy: MergePolicy,
+    resolution: Resolution,
+) -> Result<()> {
+    let kept = match resolution {
+        Resolution::RemoteWins => remote,
+        Resolution::LocalWins | Resolution::Rejected => local,
+    };
+    conn.execute(
+        "INSERT INTO sync_conflicts (
+            id, table_name, record_id, local_version, remote_version, resolution,
+            resolution_strategy, merge_policy, resolved_at
+        ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, datetime('now'))",
+        rusqlite::params![
+            uuid::Uuid::new_v4().to_string(),
+            table,
+            id,
+            Value::Object(local.clone()).to_string(),
+            Value::Object(remote.clone()).to_string(),
+            Value::Object(kept.clone()).to_string(),
+            resolution.as_str(),
+            policy.as_str(),
+        ],
+    )?;
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+    use futures::future::BoxFuture;
+    use wms_core::shutdown::ShutdownCoordinator;
+    use wms_core::events::RecordingEmitter;
+    use wms_core::test_support::{test_db, TEST_USER_ID};
+    use crate::protocol::{SyncError, SyncPayload, SyncResponse};
+
+    /// Records pushed batch sizes and pull cursors; raises shutdown after
+    /// `stop_after` batches and rejects changes to the records in `reject`
+    struct MockTransport {
+        pushed: Mutex<Vec<usize>>,
+        pulls: Mutex<Vec<Option<String>>>,
+        pages: Mutex<VecDeque<SyncResponse>>,
+        reject: Mutex<Vec<String>>,
+        coordinator: Arc<ShutdownCoordinator>,
+        stop_after: usize,
+    }
+
+    impl SyncTransport for MockTransport {
+        fn push<'a>(&'a self, message: &'a SyncMessage) -> BoxFuture<'a, Result<SyncAck>> {
+            let SyncPayload::Push(push) = &message.payload else {
+                panic!("pushed a message without changes");
+            };
+            let mut pushed = self.pushed.lock().unwrap();
+            pushed.push(push.changes.len());
+            if pushed.len() == self.stop_after {
+                self.coordinator.request();
+            }
+            
+            let reject = self.reject.lock().unwrap();
+            let (rejected, accepted): (Vec<_>, Vec<_>) = push.changes.iter()
+                .partition(|change| reject.contains(&change.record_id));
+            let ack = SyncAck {
+                change_ids: accepted.iter().map(|change| change.id.clone()).collect(),
+                success: rejected.is_empty(),
+                errors: rejected.iter().map(|change| SyncError {
+                    change_id: change.id.clone(),
+                    error_code: "invalid".to_string(),
+                    message: "Quantity below zero".to_string(),
+                }).collect(),
+            };
+            Box::pin(async { Ok(ack) })
+        }
+
+        fn pull<'a>(&'a self, since: Option<&'a str>) -> BoxFuture<'a, Result<SyncResponse>> {
+            self.pulls.lock().unwrap().push(since.map(str::to_string));
+            let page = self.pages.lock().unwrap().pop_front().unwrap_or_else(|| SyncResponse {
+                changes: Vec::new(),
+                has_more: false,
+                server_time: Utc::now(),
+                cursor: since.map(str::to_string),
+            });
+            Box::pin(async { Ok(page) })
+        }
+    }
+
+    fn engine_with(stop_after: usize) -> (SyncEngine, Arc<MockTransport>) {
+        let coordinator = Arc::new(ShutdownCoordinator::new());
+        let transport = Arc::new(MockTransport {
+            pushed: Mutex::new(Vec::new()),
+            pulls: Mutex::new(Vec::new()),
+            pages: Mutex::new(VecDeque::new()),
+            reject: Mutex::new(Vec::new()),
+            coordinator: coordinator.clone(),
+            stop_after,
+        });
+        let mut engine = SyncEngine::new(test_db()).unwrap()
+            .with_shutdown_signal(coordinator.signal());
+        engine.transport = Some(transport.clone());
+        engine.server_url = Some("https://sync.test".to_string());
+        (engine, transport)
+    }
+
+    fn outbox_counts(engine: &SyncEngine) -> (i64, i64) {
+        engine.db.query_row(
+            "SELECT
+                COUNT(*) FILTER (WHERE acknowledged_at IS NOT NULL),
+                COUNT(*) FILTER (WHERE sent_at IS NOT NULL AND acknowledged_at IS NULL)
+             FROM sync_outbox",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        ).unwrap().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_stops_sync_between_batches() {
+        let (engine, transport) = engine_with(2);
+        for i in 0..250 {
+            engine.queue_change("inventory_items", &format!("item-{}", i), "UPDATE", "{}").unwrap();
+        }
+
+        let status = engine.sync_now().await.unwrap();
+
+        assert_eq!(*transport.pushed.lock().unwrap(), vec![SYNC_BATCH_SIZE, SYNC_BATCH_SIZE]);
+        assert!(transport.pulls.lock().unwrap().is_empty());
+        assert_eq!(outbox_counts(&engine), (200, 0));
+        assert_eq!(status.pending_changes, 50);
+        assert_eq!(status.sync_errors, 0);
+        assert!(status.last_sync_at.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_only_accepted_changes_are_acknowledged_and_pulls_resume_from_cursor() {
+        let (engine, transport) = engine_with(0);
+        for id in ["item-1", "item-2", "item-3"] {
+            engine.queue_change("inventory_items", id, "UPDATE", "{}").unwrap();
+        }
+        transport.reject.lock().unwrap().push("item-2".to_string());
+        let customer = serde_json::json!({"customer_number": "C-1", "company_name": "Acme"});
+        transport.pages.lock().unwrap().extend([
+            SyncResponse {
+                changes: vec![ChangeRecord::json(
+                    "customers", "c1", ChangeOperation::Insert, "server", &customer.to_string(),
+                )],
+                has_more: true,
+                server_time: Utc::now(),
+                cursor: Some("page-1".to_string()),
+            },
+            SyncResponse {
+                changes: Vec::new(),
+                has_more: false,
+                server_time: Utc::now(),
+                cursor: Some("page-2".to_string()),
+            },
+        ]);
+        let acknowledged = |engine: &SyncEngine| -> Vec<String> {
+            engine.db.query_map(
+                "SELECT record_id FROM sync_outbox WHERE acknowledged_at IS NOT NULL ORDER BY record_id",
+                [],
+                |row| row.get(0),
+            ).unwrap()
+        };
+
+        let status = engine.sync_now().await.unwrap();
+
+        assert_eq!(acknowledged(&engine), vec!["item-1", "item-3"]);
+        let (retries, error): (i64, String) = engine.db.query_row(
+            "SELECT retry_count, last_error FROM sync_outbox WHERE record_id = 'item-2'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        ).unwrap().unwrap();
+        assert_eq!(retries, 1);
+        assert_eq!(error, "invalid: Quantity below zero");
+        assert_eq!(status.pending_changes, 1);
+        assert_eq!(status.sync_errors, 1);
+        assert!(status.last_error.unwrap().contains("rejected 1 changes"));
+
+        // Both pages were pulled and what they carried was applied
+        assert_eq!(*transport.pulls.lock().unwrap(), vec![None, Some("page-1".to_string())]);
+        assert_eq!(engine.db.get_setting(SYNC_CURSOR_KEY).unwrap().as_deref(), Some("page-2"));
+        let company: Option<String> = engine.db.query_row(
+            "SELECT company_name FROM customers WHERE id = 'c1'", [], |row| row.get(0),
+        ).unwrap();
+        assert_eq!(company.as_deref(), Some("Acme"));
+
+        // Once the server takes it, the rejected change goes through
+        transport.reject.lock().unwrap().clear();
+        let status = engine.sync_now().await.unwrap();
+
+        assert!(status.last_error.is_none());
+        assert_eq!(status.pending_changes, 0);
+        assert_eq!(acknowledged(&engine), vec!["item-1", "item-2", "item-3"]);
+        assert_eq!(*transport.pushed.lock().unwrap(), vec![3, 1]);
+        assert_eq!(transport.pulls.lock().unwrap().last().cloned().flatten().as_deref(), Some("page-2"));
+    }
+
+    #[tokio::test]
+    async fn test_sync_emits_completion_with_what_is_left() {
+        let (engine, transport) = engine_with(0);
+        let events = Arc::new(RecordingEmitter::new());
+        let engine = engine.with_event_emitter(events.clone());
+        for id in ["item-1", "item-2"] {
+            engine.queue_change("inventory_items", id, "UPDATE", "{}").unwrap();
+        }
+        transport.reject.lock().unwrap().push("item-2".to_string());
+
+        engine.sync_now().await.unwrap();
+
+        let Some(WmsEvent::SyncCompleted { success, pending_changes, error }) = events.events().pop() else {
+            panic!("no sync event");
+        };
+        assert!(!success);
+        assert_eq!(pending_changes, 1);
+        assert!(error.unwrap().contains("rejected 1 changes"));
+    }
+
+    #[tokio::test]
+    async fn test_a_change_rejected_too_often_is_left_stuck() {
+        let (engine, transport) = engine_with(0);
+        engine.queue_change("inventory_items", "item-1", "UPDATE", "{}").unwrap();
+        transport.reject.lock().unwrap().push("item-1".to_string());
+
+        for _ in 0..MAX_SYNC_RETRIES {
+            let status = engine.sync_now().await.unwrap();
+            assert!(status.last_error.is_some());
+        }
+        let status = engine.get_status();
+        assert_eq!((status.pending_changes, status.stuck_changes), (1, 1));
+
+        // It isn't offered to the server again
+        let status = engine.sync_now().await.unwrap();
+        assert!(status.last_error.is_none());
+        assert_eq!(transport.pushed.lock().unwrap().len(), MAX_SYNC_RETRIES as usize);
+        assert_eq!(status.stuck_changes, 1);
+    }
+
+    #[tokio::test]
+    async fn test_inventory_adjustments_are_queued_for_sync() {
+        use wms_core::test_support::ItemBuilder;
+        use wms_inventory::{AdjustmentType, InventoryAdjustment, InventoryItem, InventoryService};
+
+        let (engine, _) = engine_with(0);
+        ItemBuilder::new("WID-1").id("widget").stock("loc1", 10.0).insert::<InventoryItem>(&engine.db);
+        let inventory = InventoryService::new(engine.db.clone());
+        for (adjustment_type, quantity) in [(AdjustmentType::Pick, 3.0), (AdjustmentType::Receive, 5.0)] {
+            inventory.adjust_quantity(InventoryAdjustment {
+                item_id: "widget".to_string(),
+                location_id: Some("loc1".to_string()),
+                warehouse_id: None,
+                adjustment_type,
+                quantity,
+                lot_number: None,
+                reason_code: None,
+                notes: None,
+                user_id: TEST_USER_ID.to_string(),
+            }).await.unwrap();
+        }
+
+        let queued: Vec<(String, String, String, String)> = engine.db.query_map(
+            "SELECT o.table_name, o.record_id, o.operation, o.payload FROM sync_outbox o
+             JOIN inventory_transactions t ON t.id = o.record_id
+             ORDER BY t.created_at, t.rowid",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        ).unwrap();
+        assert_eq!(queued.len(), 2);
+        for ((table, _, operation, payload), quantity) in queued.iter().zip([3.0, 5.0]) {
+            assert_eq!(table, "inventory_transactions");
+            assert_eq!(operation, "INSERT");
+            let entry: Value = serde_json::from_str(payload).unwrap();
+            assert_eq!(entry["item_id"], "widget");
+            assert_eq!(entry["quantity"], quantity);
+        }
+        assert_eq!(engine.get_status().pending_changes, 2);
+    }
+
+    #[test]
+    fn test_compaction_never_grows_documents() {
+        let (engine, _) = engine_with(0);
+        let mut doc = CrdtDocument::new();
+        doc.set("sku", crate::crdt::CrdtValue::String("WIDGET".to_string())).unwrap();
+        doc.set("name", crate::crdt::CrdtValue::String("Widget".to_string())).unwrap();
+        let stored = doc.save().unwrap();
+        engine.db.execute(
+            "INSERT INTO crdt_documents (id, document_type, record_id, actor_id, heads, compressed_changes)
+             VALUES ('doc-1', 'inventory_items', 'item-1', 'device', '[]', ?)",
+            rusqlite::params![&stored],
+        ).unwrap();
+
+        let compaction = engine.compact_crdt_documents().unwrap();
+
+        assert_eq!(compaction.documents, 1);
+        assert!(compaction.bytes_after <= compaction.bytes_before);
+        let reloaded = engine.load_crdt_document("inventory_items", "item-1").unwrap().unwrap();
+        assert_eq!(reloaded.get_string("name").as_deref(), Some("Widget"));
+    }
+
+    #[tokio::test]
+    async fn test_checkpoint_requeues_unacknowledged_changes() {
+        let (engine, _) = engine_with(0);
+        engine.queue_change("inventory_items", "item-1", "UPDATE", "{}").unwrap();
+        engine.queue_change("inventory_items", "item-2", "UPDATE", "{}").unwrap();
+        engine.db.execute("UPDATE sync_outbox SET sent_at = datetime('now')", []).unwrap();
+
+        let checkpoint = engine.checkpoint().unwrap();
+
+        assert_eq!(checkpoint.requeued_changes, 2);
+        assert_eq!(checkpoint.pending_changes, 2);
+        assert_eq!(outbox_counts(&engine), (0, 0));
+
+        engine.sync_now().await.unwrap();
+        assert_eq!(outbox_counts(&engine), (2, 0));
+    }
+
+    fn server_change(table: &str, record_id: &str, operation: &str, record: Value) -> ServerChange {
+        ServerChange {
+            table_name: table.to_string(),
+            record_id: record_id.to_string(),
+            operation: operation.to_string(),
+            crdt_changes: Vec::new(),
+            payload: Some(record.to_string()),
+        }
+    }
+
+    fn receive(engine: &SyncEngine, change: ServerChange) {
+        engine.apply_server_change(&change).unwrap();
+        assert_eq!(engine.process_inbox().unwrap(), 1);
+    }
+
+    fn conflicts(engine: &SyncEngine) -> Vec<(String, String, String)> {
+        engine.db.query_map(
+            "SELECT record_id, merge_policy, resolution_strategy FROM sync_conflicts ORDER BY created_at, rowid",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        ).unwrap()
+    }
+
+    #[test]
+    fn test_last_writer_wins_compares_versions_without_crdt_documents() {
+        let (engine, _) = engine_with(0);
+        engine.db.execute(
+            "INSERT INTO customers (id, customer_number, company_name, notes, updated_at, version)
+             VALUES ('c1', 'C-1', 'Acme', 'call before noon', '2025-01-14T12:00:00Z', 2)",
+            [],
+        ).unwrap();
+        let notes = || -> String {
+            engine.db.query_row("SELECT notes FROM customers WHERE id = 'c1'", [], |row| row.get(0)).unwrap().unwrap()
+        };
+
+        // An older edit from another device loses to ours and is logged
+        receive(&engine, server_change("customers", "c1", "UPDATE", serde_json::json!({
+            "notes": "deliveries at the back", "version": 2, "updated_at": "2025-01-14T11:00:00Z",
+        })));
+        assert_eq!(notes(), "call before noon");
+        assert_eq!(conflicts(&engine), vec![
+            ("c1".to_string(), "last_writer_wins".to_string(), "local_wins".to_string()),
+        ]);
+
+        // A later version replaces it
+        receive(&engine, server_change("customers", "c1", "UPDATE", serde_json::json!({
+            "notes": "closed fridays", "version": 3, "updated_at": "2025-01-15T09:00:00Z",
+        })));
+        assert_eq!(notes(), "closed fridays");
+        assert_eq!(conflicts(&engine).len(), 1);
+
+        assert!(engine.load_crdt_document("customers", "c1").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_append_only_log_rejects_rewrites() {
+        let (engine, _) = engine_with(0);
+        engine.db.execute(
+            "INSERT INTO customers (id, customer_number) VALUES ('c1', 'C-1')",
+            [],
+        ).unwrap();
+        let note = serde_json::json!({
+            "customer_id": "c1", "interaction_type": "call", "description": "Asked for a quote",
+            "created_by": TEST_USER_ID,
+        });
+
+        receive(&engine, server_change("customer_interactions", "n1", "INSERT", note.clone()));
+        // Delivered twice, the same entry is not a conflict
+        receive(&engine, server_change("customer_interactions", "n1", "INSERT", note.clone()));
+        assert!(conflicts(&engine).is_empty());
+
+        let mut rewritten = note.clone();
+        rewritten["description"] = "Never called".into();
+        receive(&engine, server_change("customer_interactions", "n1", "UPDATE", rewritten));
+
+        let description: String = engine.db.query_row(
+            "SELECT description FROM customer_interactions WHERE id = 'n1'",
+            [],
+            |row| row.get(0),
+        ).unwrap().unwrap();
+        assert_eq!(description, "Asked for a quote");
+        assert_eq!(conflicts(&engine), vec![
+            ("n1".to_string(), "append_only_log".to_string(), "rejected".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_stock_documents_merge_adjustments_from_both_devices() {
+        use wms_core::test_support::ItemBuilder;
+        use wms_inventory::InventoryItem;
+        use crate::crdt::{CrdtOperation, CrdtValue};
+
+        let (engine, _) = engine_with(0);
+        ItemBuilder::new("WID-1").id("widget").stock("loc1", 4.0).insert::<InventoryItem>(&engine.db);
+
+        // Both devices started from the same document
+        let mut base = CrdtDocument::new();
+        base.set("item_id", CrdtValue::String("widget".to_string())).unwrap();
+        base.set("location_id", CrdtValue::String("loc1".to_string())).unwrap();
+        base.create_list(ADJUSTMENTS_KEY).unwrap();
+        let base = base.save().unwrap();
+        let device = |operations: &[(&str, f64)]| -> ServerChange {
+            let mut doc = CrdtDocument::from_changes(&base).unwrap();
+            for (op_type, delta) in operations {
+                doc.push_operation(ADJUSTMENTS_KEY, &CrdtOperation::new(op_type, *delta, TEST_USER_ID)).unwrap();
+            }
+            ServerChange {
+                table_name: STOCK_TABLE.to_string(),
+                record_id: "stock-1".to_string(),
+                operation: "MERGE".to_string(),
+                crdt_changes: doc.save().unwrap(),
+                payload: None,
+            }
+        };
+        engine.apply_server_change(&device(&[("receive", 10.0), ("pick", -3.0)])).unwrap();
+        engine.apply_server_change(&device(&[("receive", 5.0), ("pick", -1.0)])).unwrap();
+        assert_eq!(engine.process_inbox().unwrap(), 2);
+
+        let stock = |engine: &SyncEngine| -> (f64, i64) {
+            engine.db.query_row(
+                "SELECT (SELECT quantity FROM inventory_stock WHERE item_id = 'widget'),
+                        (SELECT COUNT(*) FROM inventory_transactions WHERE transaction_type = 'MERGE')",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            ).unwrap().unwrap()
+        };
+        assert_eq!(stock(&engine), (11.0, 1));
+        let (delta, previous): (f64, f64) = engine.db.query_row(
+            "SELECT quantity, previous_quantity FROM inventory_transactions WHERE transaction_type = 'MERGE'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        ).unwrap().unwrap();
+        assert_eq!((delta, previous), (7.0, 4.0));
+
+        // Applying the same documents again changes nothing
+        engine.db.execute("UPDATE sync_inbox SET applied_at = NULL", []).unwrap();
+        assert_eq!(engine.process_inbox().unwrap(), 2);
+        assert_eq!(stock(&engine), (11.0, 1));
+    }
+
+    #[tokio::test]
+    async fn test_stock_document_waits_for_local_adjustments_to_be_sent() {
+        use wms_core::test_support::{insert_location, ItemBuilder};
+        use wms_inventory::{AdjustmentType, InventoryAdjustment, InventoryItem, InventoryService};
+        use crate::crdt::{CrdtOperation, CrdtValue};
+
+        let (engine, _) = engine_with(0);
+        insert_location(&engine.db, "loc2");
+        ItemBuilder::new("WID-1").id("widget").stock("loc1", 4.0).insert::<InventoryItem>(&engine.db);
+        let document = |record_id: &str, location_id: &str, total: f64| -> ServerChange {
+            let mut doc = CrdtDocument::new();
+            doc.set("item_id", CrdtValue::String("widget".to_string())).unwrap();
+            doc.set("location_id", CrdtValue::String(location_id.to_string())).unwrap();
+            doc.create_list(ADJUSTMENTS_KEY).unwrap();
+            doc.push_operation(ADJUSTMENTS_KEY, &CrdtOperation::new("receive", total, TEST_USER_ID)).unwrap();
+            ServerChange {
+                table_name: STOCK_TABLE.to_string(),
+                record_id: record_id.to_string(),
+                operation: "MERGE".to_string(),
+                crdt_changes: doc.save().unwrap(),
+                payload: None,
+            }
+        };
+        let stock = |location_id: &str| -> (f64, Option<String>) {
+            engine.db.query_row(
+                "SELECT quantity, lot_number FROM inventory_stock WHERE item_id = 'widget' AND location_id = ?",
+                [location_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            ).unwrap().unwrap()
+        };
+
+        // A pick made here that the server hasn't seen yet
+        InventoryService::new(engine.db.clone()).adjust_quantity(InventoryAdjustment {
+            item_id: "widget".to_string(),
+            location_id: Some("loc1".to_string()),
+            warehouse_id: None,
+            adjustment_type: AdjustmentType::Pick,
+            quantity: 1.0,
+            lot_number: None,
+            reason_code: None,
+            notes: None,
+            user_id: TEST_USER_ID.to_string(),
+        }).await.unwrap();
+
+        engine.apply_server_change(&document("stock-1", "loc1", 10.0)).unwrap();
+        assert_eq!(engine.process_inbox().unwrap(), 0);
+        assert_eq!(stock("loc1").0, 3.0);
+
+        // A pick the server has rejected for good is never sent, so it no
+        // longer holds the document back
+        engine.db.execute(
+            "UPDATE sync_outbox SET sent_at = datetime('now'), last_error = 'rejected', retry_count = ?",
+            [MAX_SYNC_RETRIES],
+        ).unwrap();
+        assert_eq!(engine.process_inbox().unwrap(), 1);
+        assert_eq!(stock("loc1").0, 10.0);
+
+        // A row new to this device is keyed without a lot, so later
+        // adjustments land on it rather than beside it
+        engine.apply_server_change(&document("stock-2", "loc2", 2.0)).unwrap();
+        assert_eq!(engine.process_inbox().unwrap(), 1);
+        assert_eq!(stock("loc2"), (2.0, Some(String::new())));
+        InventoryService::new(engine.db.clone()).adjust_quantity(InventoryAdjustment {
+            item_id: "widget".to_string(),
+            location_id: Some("loc2".to_string()),
+            warehouse_id: None,
+            adjustment_type: AdjustmentType::Receive,
+            quantity: 3.0,
+            lot_number: None,
+            reason_code: None,
+            notes: None,
+            user_id: TEST_USER_ID.to_string(),
+        }).await.unwrap();
+        assert_eq!(stock("loc2"), (5.0, Some(String::new())));
+    }
+
+    #[test]
+    fn test_tables_without_a_policy_are_refused() {
+        let (engine, _) = engine_with(0);
+        let change = server_change("users", "u2", "INSERT", serde_json::json!({"username": "eve"}));
+        assert!(engine.apply_server_change(&change).is_err());
+    }
+}
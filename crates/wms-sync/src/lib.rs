@@ -3,11 +3,22 @@
 //! This crate provides offline-first synchronization using CRDTs (Automerge)
 //! and a custom sync protocol for the Warehouse Management System.
 
+mod connectivity;
 mod engine;
 mod crdt;
 mod protocol;
+mod policy;
+mod schedule;
+mod transport;
 
-pub use engine::{SyncEngine, SyncStatus};
+pub use connectivity::{ConnectionStatus, ConnectivityMonitor, PROBE_TIMEOUT, SLOW_LATENCY};
+pub use engine::{
+    CrdtCompaction, SyncCheckpoint, SyncEngine, SyncStatus, ATTACHMENT_CHUNK_BYTES, MAX_SYNC_RETRIES,
+    SYNC_BATCH_SIZE,
+};
 pub use crdt::{CrdtDocument, CrdtOperation};
+pub use policy::{merge_policy, MergePolicy, Resolution, MERGE_POLICIES};
 pub use protocol::{SyncMessage, SyncRequest, SyncResponse};
+pub use schedule::{SyncSettings, DEFAULT_SYNC_INTERVAL_MINUTES};
+pub use transport::SYNC_REQUEST_TIMEOUT;
 
@@ -0,0 +1,173 @@
+//! Merge Policies
+//!
+//! How changes from the server are merged into each synced table. Records
+//! edited field by field on several devices merge through their CRDT
+//! documents. Tables where the latest edit should simply win skip CRDT
+//! documents and compare row versions and `updated_at`. Logs such as the
+//! stock ledger only ever gain entries; a change rewriting an existing
+//! entry is rejected.
+//!
+//! Every synced table needs a policy here; changes to any other table are
+//! refused rather than merged the wrong way.
+
+use std::cmp::Ordering;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use wms_core::error::{WmsError, Result};
+use wms_core::types::parse_timestamp;
+
+/// How changes to a table are merged
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MergePolicy {
+    /// Merge field by field through the record's CRDT document
+    CrdtMerge,
+    /// Keep whichever side has the higher version, then the later
+    /// `updated_at`
+    LastWriterWins,
+    /// Only accept new entries; existing ones are never rewritten
+    AppendOnlyLog,
+}
+
+impl MergePolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::CrdtMerge => "crdt_merge",
+            Self::LastWriterWins => "last_writer_wins",
+            Self::AppendOnlyLog => "append_only_log",
+        }
+    }
+}
+
+/// Policy of every synced table
+pub const MERGE_POLICIES: &[(&str, MergePolicy)] = &[
+    ("inventory_items", MergePolicy::CrdtMerge),
+    // Stock quantities merge as sums of adjustments so none are lost
+    ("inventory_stock", MergePolicy::CrdtMerge),
+    ("inventory_transactions", MergePolicy::AppendOnlyLog),
+    ("shipments", MergePolicy::CrdtMerge),
+    ("receipts", MergePolicy::CrdtMerge),
+    ("deliveries", MergePolicy::CrdtMerge),
+    ("customers", MergePolicy::LastWriterWins),
+    ("customer_interactions", MergePolicy::AppendOnlyLog),
+    ("time_entries", MergePolicy::LastWriterWins),
+    // Attachment content never changes once stored
+    ("attachments", MergePolicy::AppendOnlyLog),
+];
+
+/// The merge policy of `table_name`
+pub fn merge_policy(table_name: &str) -> Result<MergePolicy> {
+    MERGE_POLICIES.iter()
+        .find(|(table, _)| *table == table_name)
+        .map(|(_, policy)| *policy)
+        .ok_or_else(|| WmsError::SyncError(format!("No merge policy for table {}", table_name)))
+}
+
+/// How a conflicting change was resolved, as logged in `sync_conflicts`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Resolution {
+    LocalWins,
+    RemoteWins,
+    /// The change would have rewritten an append-only entry
+    Rejected,
+}
+
+impl Resolution {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::LocalWins => "local_wins",
+            Self::RemoteWins => "remote_wins",
+            Self::Rejected => "rejected",
+        }
+    }
+}
+
+/// Which of two copies of a row was written last: the higher version, or
+/// on equal or unknown versions the later `updated_at`. Ties go to the
+/// remote copy, so every device settles on the server's.
+pub fn last_writer(local: &Map<String, Value>, remote: &Map<String, Value>) -> Resolution {
+    let version = |row: &Map<String, Value>| row.get("version").and_then(Value::as_i64);
+    let updated_at = |row: &Map<String, Value>| {
+        row.get("updated_at").and_then(Value::as_str).and_then(row_timestamp)
+    };
+
+    let by_version = match (version(local), version(remote)) {
+        (Some(local), Some(remote)) => local.cmp(&remote),
+        _ => Ordering::Equal,
+    };
+    let order = by_version.then_with(|| match (updated_at(local), updated_at(remote)) {
+        (Some(local), Some(remote)) => local.cmp(&remote),
+        (Some(_), None) => Ordering::Greater,
+        _ => Ordering::Equal,
+    });
+
+    if order == Ordering::Greater {
+        Resolution::LocalWins
+    } else {
+        Resolution::RemoteWins
+    }
+}
+
+/// A stored timestamp: RFC 3339 as services write it, or SQLite's
+/// `datetime('now')` form
+fn row_timestamp(s: &str) -> Option<DateTime<Utc>> {
+    parse_timestamp(s).or_else(|| {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S").ok().map(|t| t.and_utc())
+    })
+}
+
+/// Whether `remote` holds only values `local` already has
+pub fn same_values(local: &Map<String, Value>, remote: &Map<String, Value>) -> bool {
+    remote.iter().all(|(key, value)| match (local.get(key), value) {
+        (Some(Value::Number(a)), Value::Number(b)) => a.as_f64() == b.as_f64(),
+        (Some(local), value) => local == value,
+        (None, Value::Null) => true,
+        (None, _) => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use wms_core::test_support::test_db;
+
+    fn row(value: Value) -> Map<String, Value> {
+        value.as_object().cloned().unwrap()
+    }
+
+    #[test]
+    fn test_every_synced_table_has_a_policy() {
+        let db = test_db();
+        let tables: Vec<String> = db.query_map(
+            "SELECT table_name FROM sync_status",
+            [],
+            |row| row.get(0),
+        ).unwrap();
+
+        assert!(tables.iter().any(|t| t == "attachments"));
+        for table in &tables {
+            assert!(merge_policy(table).is_ok(), "{} has no merge policy", table);
+        }
+        assert!(merge_policy("users").is_err());
+    }
+
+    #[test]
+    fn test_last_writer_compares_version_then_updated_at() {
+        let older = row(json!({"version": 3, "updated_at": "2025-01-14T10:00:00Z"}));
+        let newer = row(json!({"version": 3, "updated_at": "2025-01-14T11:00:00Z"}));
+        let bumped = row(json!({"version": 4, "updated_at": "2025-01-14 09:00:00"}));
+
+        assert_eq!(last_writer(&newer, &older), Resolution::LocalWins);
+        assert_eq!(last_writer(&older, &newer), Resolution::RemoteWins);
+        assert_eq!(last_writer(&newer, &bumped), Resolution::RemoteWins);
+        assert_eq!(last_writer(&bumped, &newer), Resolution::LocalWins);
+        assert_eq!(last_writer(&older, &older), Resolution::RemoteWins);
+
+        // Tables without versions fall back to timestamps
+        let clocked = row(json!({"updated_at": "2025-01-14T12:00:00Z"}));
+        assert_eq!(last_writer(&clocked, &row(json!({"notes": "x"}))), Resolution::LocalWins);
+    }
+}
@@ -41,6 +41,9 @@ pub struct SyncResponse {
     pub changes: Vec<ChangeRecord>,
     pub has_more: bool,
     pub server_time: DateTime<Utc>,
+    /// Where the next pull continues from
+    #[serde(default)]
+    pub cursor: Option<String>,
 }
 
 /// Push local changes to server
@@ -52,8 +55,10 @@ pub struct SyncPush {
 /// Acknowledgment of received changes
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SyncAck {
+    /// Changes the server stored
     pub change_ids: Vec<String>,
     pub success: bool,
+    /// Changes the server rejected, and why
     pub errors: Vec<SyncError>,
 }
 
@@ -92,6 +97,30 @@ pub enum ChangeOperation {
     Update,
     Delete,
     Merge, // CRDT merge
+    Chunk, // Piece of attachment content
+}
+
+impl ChangeOperation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Insert => "INSERT",
+            Self::Update => "UPDATE",
+            Self::Delete => "DELETE",
+            Self::Merge => "MERGE",
+            Self::Chunk => "CHUNK",
+        }
+    }
+    
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "INSERT" => Some(Self::Insert),
+            "UPDATE" => Some(Self::Update),
+            "DELETE" => Some(Self::Delete),
+            "MERGE" => Some(Self::Merge),
+            "CHUNK" => Some(Self::Chunk),
+            _ => None,
+        }
+    }
 }
 
 /// Sync error
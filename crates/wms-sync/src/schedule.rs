@@ -0,0 +1,89 @@
+//! Sync Schedule
+//!
+//! How often the app syncs in the background, kept in the settings table.
+
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use wms_core::db::Database;
+use wms_core::error::{WmsError, Result};
+
+/// Settings key for the background sync interval in minutes; empty means
+/// manual sync only
+const SYNC_INTERVAL_SETTING: &str = "sync_interval_minutes";
+
+/// Background sync interval used until one is chosen
+pub const DEFAULT_SYNC_INTERVAL_MINUTES: u32 = 15;
+
+/// Longest background sync interval: one day
+const MAX_SYNC_INTERVAL_MINUTES: u32 = 24 * 60;
+
+/// Background sync settings
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SyncSettings {
+    /// Minutes between background syncs; None syncs only on request
+    pub interval_minutes: Option<u32>,
+}
+
+impl Default for SyncSettings {
+    fn default() -> Self {
+        Self {
+            interval_minutes: Some(DEFAULT_SYNC_INTERVAL_MINUTES),
+        }
+    }
+}
+
+impl SyncSettings {
+    /// Load from the settings table. A stored empty value means manual
+    /// sync; missing or invalid values use the default.
+    pub fn load(db: &Database) -> Result<Self> {
+        let interval_minutes = match db.get_setting(SYNC_INTERVAL_SETTING)?.as_deref() {
+            Some("") => None,
+            Some(value) => Some(
+                value.parse::<u32>().ok()
+                    .filter(|minutes| (1..=MAX_SYNC_INTERVAL_MINUTES).contains(minutes))
+                    .unwrap_or(DEFAULT_SYNC_INTERVAL_MINUTES),
+            ),
+            None => Some(DEFAULT_SYNC_INTERVAL_MINUTES),
+        };
+        Ok(Self { interval_minutes })
+    }
+
+    /// Store in the settings table
+    pub fn save(&self, db: &Database) -> Result<()> {
+        if let Some(minutes) = self.interval_minutes
+            && !(1..=MAX_SYNC_INTERVAL_MINUTES).contains(&minutes)
+        {
+            return Err(WmsError::validation(format!(
+                "Sync interval must be between 1 and {} minutes",
+                MAX_SYNC_INTERVAL_MINUTES
+            )));
+        }
+        let value = self.interval_minutes.map(|m| m.to_string()).unwrap_or_default();
+        db.set_setting(SYNC_INTERVAL_SETTING, &value)
+    }
+
+    /// Time between background syncs, if they run at all
+    pub fn interval(&self) -> Option<Duration> {
+        self.interval_minutes.map(|minutes| Duration::from_secs(u64::from(minutes) * 60))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wms_core::test_support::test_db;
+
+    #[test]
+    fn test_manual_sync_round_trips() {
+        let db = test_db();
+        assert_eq!(SyncSettings::load(&db).unwrap(), SyncSettings::default());
+
+        SyncSettings { interval_minutes: None }.save(&db).unwrap();
+        assert_eq!(SyncSettings::load(&db).unwrap().interval(), None);
+
+        SyncSettings { interval_minutes: Some(5) }.save(&db).unwrap();
+        assert_eq!(SyncSettings::load(&db).unwrap().interval(), Some(Duration::from_secs(300)));
+
+        assert!(SyncSettings { interval_minutes: Some(0) }.save(&db).is_err());
+    }
+}
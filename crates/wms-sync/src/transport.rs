@@ -0,0 +1,93 @@
+//! Sync Transport
+//!
+//! Carries sync protocol messages between this device and the server.
+
+use std::time::Duration;
+use futures::future::BoxFuture;
+use wms_core::error::{WmsError, Result};
+use crate::protocol::{SyncAck, SyncMessage, SyncPayload, SyncResponse};
+
+/// How long a single push or pull may take before the sync gives up
+pub const SYNC_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Connection to the sync server
+pub(crate) trait SyncTransport: Send + Sync {
+    /// Send a batch of local changes; resolves with the server's verdict on
+    /// each: the ids it stored and the ones it rejected
+    fn push<'a>(&'a self, message: &'a SyncMessage) -> BoxFuture<'a, Result<SyncAck>>;
+
+    /// Changes made elsewhere after `since`, the cursor returned by the
+    /// previous pull. `None` asks for everything.
+    fn pull<'a>(&'a self, since: Option<&'a str>) -> BoxFuture<'a, Result<SyncResponse>>;
+}
+
+/// Sync server reached over HTTP. Pushes are POSTed to `/sync/push` and
+/// pulls read from `/sync/pull`, both as JSON `SyncMessage`s.
+pub(crate) struct HttpTransport {
+    server_url: String,
+    device_id: String,
+    client: reqwest::Client,
+}
+
+impl HttpTransport {
+    /// Fails if the HTTP client can't be set up, e.g. when no TLS backend
+    /// is available
+    pub(crate) fn new(server_url: &str, device_id: &str) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(SYNC_REQUEST_TIMEOUT)
+            .build()
+            .map_err(|e| WmsError::SyncError(format!("Unable to set up the sync client: {}", e)))?;
+        Ok(Self {
+            server_url: server_url.trim_end_matches('/').to_string(),
+            device_id: device_id.to_string(),
+            client,
+        })
+    }
+
+    async fn send(&self, request: reqwest::RequestBuilder, what: &str) -> Result<SyncPayload> {
+        let response = request.send().await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| request_error(what, e))?;
+        let message: SyncMessage = response.json().await
+            .map_err(|e| request_error(what, e))?;
+        Ok(message.payload)
+    }
+}
+
+impl SyncTransport for HttpTransport {
+    fn push<'a>(&'a self, message: &'a SyncMessage) -> BoxFuture<'a, Result<SyncAck>> {
+        Box::pin(async move {
+            let request = self.client
+                .post(format!("{}/sync/push", self.server_url))
+                .json(message);
+            match self.send(request, "push").await? {
+                SyncPayload::Ack(ack) => Ok(ack),
+                _ => Err(WmsError::SyncError("Server answered a push without an acknowledgement".to_string())),
+            }
+        })
+    }
+
+    fn pull<'a>(&'a self, since: Option<&'a str>) -> BoxFuture<'a, Result<SyncResponse>> {
+        Box::pin(async move {
+            let mut query = vec![("device_id", self.device_id.as_str())];
+            if let Some(since) = since {
+                query.push(("since", since));
+            }
+            let request = self.client
+                .get(format!("{}/sync/pull", self.server_url))
+                .query(&query);
+            match self.send(request, "pull").await? {
+                SyncPayload::Response(response) => Ok(response),
+                _ => Err(WmsError::SyncError("Server answered a pull without changes".to_string())),
+            }
+        })
+    }
+}
+
+fn request_error(what: &str, e: reqwest::Error) -> WmsError {
+    if e.is_timeout() {
+        WmsError::Network(format!("Sync {} timed out after {}s", what, SYNC_REQUEST_TIMEOUT.as_secs()))
+    } else {
+        WmsError::Network(format!("Sync {} failed: {}", what, e))
+    }
+}
@@ -0,0 +1,111 @@
+//! Break Policy
+//!
+//! Unpaid breaks come off a shift's hours and paid ones don't. Shifts long
+//! enough to need a break but without any unpaid break logged have a break
+//! deducted at clock out, on the assumption that one was taken and not
+//! recorded. Long shifts without a meal break are flagged on the
+//! timesheet.
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use crate::models::{BreakType, TimeBreak, TimeEntry};
+
+/// Break rules applied to time entries
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct BreakPolicy {
+    /// Shifts longer than this many hours without an unpaid break have
+    /// `auto_deduct_minutes` deducted; None never deducts
+    pub auto_deduct_after_hours: Option<f64>,
+    pub auto_deduct_minutes: u32,
+    /// Shifts longer than this many hours need a meal break; None never
+    /// flags
+    pub meal_break_after_hours: Option<f64>,
+}
+
+impl Default for BreakPolicy {
+    fn default() -> Self {
+        Self {
+            auto_deduct_after_hours: Some(6.0),
+            auto_deduct_minutes: 30,
+            meal_break_after_hours: Some(8.0),
+        }
+    }
+}
+
+impl BreakPolicy {
+    /// No deductions and no violations
+    pub fn disabled() -> Self {
+        Self { auto_deduct_after_hours: None, auto_deduct_minutes: 0, meal_break_after_hours: None }
+    }
+
+    /// Minutes to deduct from a finished shift for a break that wasn't
+    /// logged
+    pub fn auto_deduction(&self, entry: &TimeEntry, breaks: &[TimeBreak]) -> u32 {
+        let logged_unpaid = breaks.iter()
+            .any(|b| !b.break_type.is_paid() && b.duration_minutes.unwrap_or(0) > 0);
+        match (self.auto_deduct_after_hours, shift_hours(entry)) {
+            (Some(threshold), Some(hours)) if hours > threshold && !logged_unpaid => self.auto_deduct_minutes,
+            _ => 0,
+        }
+    }
+
+    /// Break rules a finished shift broke; `entry.breaks` must be loaded
+    pub fn violations(&self, entry: &TimeEntry) -> Vec<BreakViolation> {
+        let Some(hours) = shift_hours(entry) else {
+            return Vec::new();
+        };
+        let violation = |kind, message: String| BreakViolation {
+            time_entry_id: entry.id.clone(),
+            entry_date: entry.entry_date,
+            kind,
+            shift_hours: hours,
+            message,
+        };
+
+        let mut violations = Vec::new();
+        let had_meal = entry.breaks.iter().any(|b| b.break_type == BreakType::Meal);
+        if let Some(threshold) = self.meal_break_after_hours
+            && hours > threshold
+            && !had_meal
+        {
+            violations.push(violation(
+                BreakViolationKind::MissingMealBreak,
+                format!("No meal break in a {:.1} hour shift", hours),
+            ));
+        }
+        if entry.auto_break_minutes > 0 {
+            violations.push(violation(
+                BreakViolationKind::BreakAutoDeducted,
+                format!("{} minute break deducted because none was logged", entry.auto_break_minutes),
+            ));
+        }
+        violations
+    }
+}
+
+/// Elapsed hours from clock in to clock out, breaks included
+fn shift_hours(entry: &TimeEntry) -> Option<f64> {
+    let clock_out = entry.clock_out_time?;
+    Some(clock_out.signed_duration_since(entry.clock_in_time).num_minutes() as f64 / 60.0)
+}
+
+/// A shift that broke the break policy
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BreakViolation {
+    pub time_entry_id: String,
+    pub entry_date: NaiveDate,
+    pub kind: BreakViolationKind,
+    /// Elapsed hours of the shift, breaks included
+    pub shift_hours: f64,
+    pub message: String,
+}
+
+/// Which break rule a shift broke
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakViolationKind {
+    /// Long shift without a meal break
+    MissingMealBreak,
+    /// No unpaid break logged, so one was deducted
+    BreakAutoDeducted,
+}
@@ -1,23 +1,20 @@
 //! Timesheet Export Functionality
 //! 
-//! Exports timesheets to Excel (XLSX) and CSV formats.
+//! Exports timesheets and team summaries to Excel (XLSX) and CSV formats,
+//! and timesheets to PDF for signing.
 
-use rust_xlsxwriter::{Workbook, Format, FormatAlign, FormatBorder};
+use rust_xlsxwriter::{Workbook, Worksheet, Format, FormatAlign, FormatBorder};
 use csv::Writer;
-use serde::{Deserialize, Serialize};
 use wms_core::error::{WmsError, Result};
-use crate::models::Timesheet;
+use wms_core::pdf::PdfGenerator;
+use wms_core::types::FileExport;
+use crate::models::{TeamTimesheetSummary, Timesheet};
+
+/// Lowest a line of the PDF may be printed, in mm from the bottom
+const PAGE_BOTTOM_MM: f32 = 20.0;
 
 /// Exported timesheet data
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct TimesheetExport {
-    /// Base64 encoded file data
-    pub data: String,
-    /// MIME content type
-    pub content_type: String,
-    /// Suggested filename
-    pub filename: String,
-}
+pub type TimesheetExport = FileExport;
 
 /// Excel exporter for timesheets
 pub struct ExcelExporter;
@@ -26,19 +23,102 @@ impl ExcelExporter {
     /// Export timesheet to XLSX format
     pub fn export(timesheet: &Timesheet) -> Result<Vec<u8>> {
         let mut workbook = Workbook::new();
-        let worksheet = workbook.add_worksheet();
+        Self::write_timesheet(workbook.add_worksheet(), timesheet);
         
-        // Define formats
+        // Save to buffer
+        let buffer = workbook.save_to_buffer()
+            .map_err(|e| WmsError::Export(format!("Failed to create Excel file: {}", e)))?;
+        
+        Ok(buffer)
+    }
+    
+    /// Export a team summary to XLSX format: a summary sheet with a row per
+    /// user, then a sheet of each user's entries
+    pub fn export_team(summary: &TeamTimesheetSummary, timesheets: &[Timesheet]) -> Result<Vec<u8>> {
+        let mut workbook = Workbook::new();
         let header_format = Format::new()
             .set_bold()
             .set_align(FormatAlign::Center)
             .set_border(FormatBorder::Thin);
+        let hours_format = Format::new()
+            .set_num_format("0.00");
         
-        let date_format = Format::new()
-            .set_num_format("yyyy-mm-dd");
+        let worksheet = workbook.add_worksheet();
+        worksheet.set_name("Summary").ok();
+        worksheet.set_column_width(0, 25).ok();
+        worksheet.write_string(0, 0, "Team Timesheet").ok();
+        worksheet.write_string(1, 0, &format!("Period: {} to {}",
+            summary.start_date, summary.end_date)).ok();
+        
+        let headers = ["Employee", "Regular", "Overtime", "Total", "Break (min)", "Days", "Status"];
+        for (col, header) in headers.iter().enumerate() {
+            worksheet.write_string_with_format(3, col as u16, *header, &header_format).ok();
+        }
+        
+        let mut row = 4u32;
+        for member in &summary.members {
+            worksheet.write_string(row, 0, &member.user_name).ok();
+            worksheet.write_number_with_format(row, 1, member.regular_hours, &hours_format).ok();
+            worksheet.write_number_with_format(row, 2, member.overtime_hours, &hours_format).ok();
+            worksheet.write_number_with_format(row, 3, member.total_hours, &hours_format).ok();
+            worksheet.write_number(row, 4, member.total_breaks_minutes as f64).ok();
+            worksheet.write_number(row, 5, member.days_worked as f64).ok();
+            worksheet.write_string(row, 6, member.status.as_str()).ok();
+            row += 1;
+        }
         
-        let time_format = Format::new()
-            .set_num_format("hh:mm");
+        let totals = &summary.totals;
+        worksheet.write_string_with_format(row, 0, "Total", &header_format).ok();
+        worksheet.write_number_with_format(row, 1, totals.regular_hours, &hours_format).ok();
+        worksheet.write_number_with_format(row, 2, totals.overtime_hours, &hours_format).ok();
+        worksheet.write_number_with_format(row, 3, totals.total_hours, &hours_format).ok();
+        worksheet.write_number(row, 4, totals.total_breaks_minutes as f64).ok();
+        worksheet.write_number(row, 5, totals.days_worked as f64).ok();
+        
+        let mut names = vec!["Summary".to_string()];
+        for timesheet in timesheets {
+            let name = Self::sheet_name(&timesheet.user_name, &names);
+            let worksheet = workbook.add_worksheet();
+            worksheet.set_name(&name)
+                .map_err(|e| WmsError::Export(format!("Failed to name worksheet {}: {}", name, e)))?;
+            Self::write_timesheet(worksheet, timesheet);
+            names.push(name);
+        }
+        
+        let buffer = workbook.save_to_buffer()
+            .map_err(|e| WmsError::Export(format!("Failed to create Excel file: {}", e)))?;
+        
+        Ok(buffer)
+    }
+    
+    /// A worksheet name for `user_name` that Excel accepts and that isn't
+    /// one of `taken`
+    fn sheet_name(user_name: &str, taken: &[String]) -> String {
+        let base: String = user_name.chars()
+            .map(|c| if "[]:*?/\\".contains(c) { '_' } else { c })
+            .collect::<String>()
+            .trim_matches(|c: char| c == '\'' || c.is_whitespace())
+            .chars()
+            .take(25)
+            .collect();
+        let base = if base.is_empty() { "Employee".to_string() } else { base };
+        
+        let mut name = base.clone();
+        let mut n = 2;
+        while taken.iter().any(|t| t.eq_ignore_ascii_case(&name)) {
+            name = format!("{} ({})", base, n);
+            n += 1;
+        }
+        name
+    }
+    
+    /// Write a timesheet's entries and summary to a worksheet
+    fn write_timesheet(worksheet: &mut Worksheet, timesheet: &Timesheet) {
+        // Define formats
+        let header_format = Format::new()
+            .set_bold()
+            .set_align(FormatAlign::Center)
+            .set_border(FormatBorder::Thin);
         
         let hours_format = Format::new()
             .set_num_format("0.00");
@@ -100,12 +180,6 @@ impl ExcelExporter {
         row += 1;
         worksheet.write_string(row, 0, "Days Worked:").ok();
         worksheet.write_number(row, 1, timesheet.days_worked as f64).ok();
-        
-        // Save to buffer
-        let buffer = workbook.save_to_buffer()
-            .map_err(|e| WmsError::Export(format!("Failed to create Excel file: {}", e)))?;
-        
-        Ok(buffer)
     }
 }
 
@@ -178,6 +252,156 @@ impl CsvExporter {
         
         Ok(data)
     }
+    
+    /// Export a team summary to CSV format, a row per user and a total row
+    pub fn export_team(summary: &TeamTimesheetSummary) -> Result<Vec<u8>> {
+        let mut writer = Writer::from_writer(Vec::new());
+        let csv_error = |e: csv::Error| WmsError::Export(format!("CSV write error: {}", e));
+        
+        writer.write_record(&[
+            "Employee ID",
+            "Employee",
+            "Regular Hours",
+            "Overtime Hours",
+            "Total Hours",
+            "Break (min)",
+            "Days Worked",
+            "Status",
+        ]).map_err(csv_error)?;
+        
+        for member in &summary.members {
+            writer.write_record(&[
+                member.user_id.clone(),
+                member.user_name.clone(),
+                format!("{:.2}", member.regular_hours),
+                format!("{:.2}", member.overtime_hours),
+                format!("{:.2}", member.total_hours),
+                member.total_breaks_minutes.to_string(),
+                member.days_worked.to_string(),
+                member.status.as_str().to_string(),
+            ]).map_err(csv_error)?;
+        }
+        
+        let totals = &summary.totals;
+        writer.write_record(&[
+            String::new(),
+            "Total".to_string(),
+            format!("{:.2}", totals.regular_hours),
+            format!("{:.2}", totals.overtime_hours),
+            format!("{:.2}", totals.total_hours),
+            totals.total_breaks_minutes.to_string(),
+            totals.days_worked.to_string(),
+            String::new(),
+        ]).map_err(csv_error)?;
+        
+        let data = writer.into_inner()
+            .map_err(|e| WmsError::Export(format!("CSV flush error: {}", e)))?;
+        
+        Ok(data)
+    }
+}
+
+/// PDF exporter for timesheets, with lines for the employee and their
+/// supervisor to sign
+pub struct PdfExporter;
+
+impl PdfExporter {
+    /// Export timesheet to PDF format
+    pub fn export(timesheet: &Timesheet) -> Result<Vec<u8>> {
+        let mut pdf = PdfGenerator::new_shipping_doc("Timesheet");
+        
+        // Header
+        pdf.add_bold_text(20.0, 265.0, "TIMESHEET", 18.0);
+        pdf.add_text(
+            20.0,
+            255.0,
+            &format!("Employee: {} ({})", timesheet.user_name, timesheet.user_id),
+            12.0,
+        );
+        pdf.add_text(
+            20.0,
+            249.0,
+            &format!("Period: {} to {}", timesheet.start_date, timesheet.end_date),
+            10.0,
+        );
+        pdf.add_text(20.0, 243.0, &format!("Status: {}", timesheet.status.as_str()), 10.0);
+        
+        // Entries, repeating the table header on each page
+        let mut y = 230.0;
+        Self::table_header(&pdf, y);
+        y -= 11.0;
+        
+        for entry in &timesheet.entries {
+            if y < PAGE_BOTTOM_MM {
+                pdf.add_page();
+                y = 260.0;
+                Self::table_header(&pdf, y);
+                y -= 11.0;
+            }
+            let clock_out = entry.clock_out_time
+                .map(|t| t.format("%H:%M").to_string())
+                .unwrap_or_else(|| "-".to_string());
+            let hours = entry.total_hours
+                .map(|h| format!("{:.2}", h))
+                .unwrap_or_else(|| "-".to_string());
+            
+            pdf.add_text(20.0, y, &entry.entry_date.to_string(), 9.0);
+            pdf.add_text(50.0, y, &entry.clock_in_time.format("%H:%M").to_string(), 9.0);
+            pdf.add_text(75.0, y, &clock_out, 9.0);
+            pdf.add_text(100.0, y, &entry.break_duration_minutes.to_string(), 9.0);
+            pdf.add_text(125.0, y, &hours, 9.0);
+            pdf.add_text(150.0, y, &format!("{:.2}", entry.overtime_hours), 9.0);
+            pdf.add_text(172.0, y, &format!("{:?}", entry.status), 9.0);
+            y -= 6.0;
+        }
+        
+        // Summary box and signatures stay together
+        if y < PAGE_BOTTOM_MM + 80.0 {
+            pdf.add_page();
+            y = 260.0;
+        }
+        y -= 6.0;
+        pdf.add_bold_text(20.0, y, "Summary", 12.0);
+        y -= 4.0;
+        let summary = [
+            ("Regular Hours", format!("{:.2}", timesheet.regular_hours)),
+            ("Overtime Hours", format!("{:.2}", timesheet.overtime_hours)),
+            ("Total Hours", format!("{:.2}", timesheet.total_hours)),
+            ("Breaks (min)", timesheet.total_breaks_minutes.to_string()),
+            ("Days Worked", timesheet.days_worked.to_string()),
+        ];
+        let box_height = summary.len() as f32 * 6.0 + 4.0;
+        pdf.draw_rect(20.0, y - box_height, 90.0, box_height);
+        y -= 6.0;
+        for (label, value) in &summary {
+            pdf.add_text(24.0, y, label, 10.0);
+            pdf.add_text(80.0, y, value, 10.0);
+            y -= 6.0;
+        }
+        
+        y -= 20.0;
+        for signer in ["Employee", "Supervisor"] {
+            pdf.draw_line(20.0, y, 110.0, y);
+            pdf.draw_line(125.0, y, 180.0, y);
+            pdf.add_text(20.0, y - 5.0, &format!("{} signature", signer), 9.0);
+            pdf.add_text(125.0, y - 5.0, "Date", 9.0);
+            y -= 20.0;
+        }
+        
+        Ok(pdf.save_to_bytes())
+    }
+    
+    fn table_header(pdf: &PdfGenerator, y: f32) {
+        pdf.draw_line(20.0, y + 5.0, 195.0, y + 5.0);
+        pdf.add_bold_text(20.0, y, "Date", 10.0);
+        pdf.add_bold_text(50.0, y, "Clock In", 10.0);
+        pdf.add_bold_text(75.0, y, "Clock Out", 10.0);
+        pdf.add_bold_text(100.0, y, "Break (min)", 10.0);
+        pdf.add_bold_text(125.0, y, "Hours", 10.0);
+        pdf.add_bold_text(150.0, y, "Overtime", 10.0);
+        pdf.add_bold_text(172.0, y, "Status", 10.0);
+        pdf.draw_line(20.0, y - 5.0, 195.0, y - 5.0);
+    }
 }
 
 #[cfg(test)]
@@ -208,6 +432,7 @@ mod tests {
             approved_by: None,
             approved_at: None,
             notes: None,
+            violations: vec![],
             entries: vec![
                 TimeEntry {
                     id: "entry1".to_string(),
@@ -221,7 +446,9 @@ mod tests {
                     clock_out_method: Some(ClockMethod::Biometric),
                     clock_in_device: None,
                     clock_out_device: None,
+                    flagged_out_of_area: false,
                     break_duration_minutes: 30,
+                    auto_break_minutes: 0,
                     total_hours: Some(8.5),
                     overtime_hours: 0.5,
                     status: TimeEntryStatus::Completed,
@@ -261,5 +488,55 @@ mod tests {
         assert!(content.contains("Date,Clock In,Clock Out"));
         assert!(content.contains("Summary"));
     }
+    
+    #[test]
+    fn test_team_export() {
+        let mut other = create_test_timesheet();
+        other.user_id = "user2".to_string();
+        other.user_name = "Jane: Doe".to_string();
+        let timesheets = vec![create_test_timesheet(), other];
+        let summary = TeamTimesheetSummary::new(timesheets[0].start_date, timesheets[0].end_date, &timesheets);
+        assert_eq!(summary.totals.total_hours, 90.0);
+        
+        let data = ExcelExporter::export_team(&summary, &timesheets).unwrap();
+        assert_eq!(&data[0..2], &[0x50, 0x4B]);
+        
+        let content = String::from_utf8(CsvExporter::export_team(&summary).unwrap()).unwrap();
+        assert_eq!(content.lines().count(), 4);
+        assert!(content.contains("user2,Jane: Doe,40.00,5.00,45.00,150,5,draft"));
+        assert!(content.contains(",Total,80.00,10.00,90.00,300,10,"));
+    }
+    
+    #[test]
+    fn test_sheet_names_are_valid_and_unique() {
+        let taken = vec!["Summary".to_string(), "Jane Doe".to_string()];
+        assert_eq!(ExcelExporter::sheet_name("Jane Doe", &taken), "Jane Doe (2)");
+        assert_eq!(ExcelExporter::sheet_name("A/B [ops]", &taken), "A_B _ops_");
+        assert_eq!(ExcelExporter::sheet_name("summary", &taken), "summary (2)");
+    }
+    
+    /// Pages in a PDF, counted from its page objects
+    fn page_count(pdf: &[u8]) -> usize {
+        let text = String::from_utf8_lossy(pdf);
+        text.matches("/Type/Page").count() - text.matches("/Type/Pages").count()
+    }
+    
+    #[test]
+    fn test_pdf_export() {
+        let timesheet = create_test_timesheet();
+        let data = PdfExporter::export(&timesheet).unwrap();
+        assert!(data.starts_with(b"%PDF"));
+        assert_eq!(page_count(&data), 1);
+    }
+    
+    #[test]
+    fn test_long_pdf_export_runs_onto_more_pages() {
+        let mut timesheet = create_test_timesheet();
+        let entry = timesheet.entries[0].clone();
+        timesheet.entries = (0..60).map(|i| TimeEntry { id: format!("entry{}", i), ..entry.clone() }).collect();
+        
+        let data = PdfExporter::export(&timesheet).unwrap();
+        assert!(data.starts_with(b"%PDF"));
+        assert!(page_count(&data) > 1);
+    }
 }
-
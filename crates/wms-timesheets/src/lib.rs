@@ -1,17 +1,23 @@
 //! WMS Timesheets Module
 //! 
 //! Provides workforce management functionality including:
-//! - Clock in/out with biometric verification
+//! - Clock in/out with biometric verification, on site only
 //! - Time entry management
-//! - Break tracking
+//! - Break tracking, with auto-deducted and flagged missing breaks
 //! - Timesheet summaries and reporting
-//! - Excel/CSV export
+//! - Submitting timesheets for approval, and locking approved time
+//! - Audited manual corrections of time entries
+//! - Excel/CSV export, and PDF for signing
 
 mod models;
+mod breaks;
+mod site;
 mod service;
 mod export;
 
 pub use models::*;
+pub use breaks::{BreakPolicy, BreakViolation, BreakViolationKind};
+pub use site::{ClockSiteSettings, OutOfAreaPolicy, DEFAULT_SITE_RADIUS_METERS};
 pub use service::TimesheetService;
-pub use export::{ExcelExporter, CsvExporter, TimesheetExport};
+pub use export::{ExcelExporter, CsvExporter, PdfExporter, TimesheetExport};
 
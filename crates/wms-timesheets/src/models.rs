@@ -2,6 +2,8 @@
 
 use chrono::{DateTime, NaiveDate, Utc, Duration};
 use serde::{Deserialize, Serialize};
+pub use wms_core::types::GeoPoint;
+use crate::breaks::BreakViolation;
 
 /// Time entry (clock in/out record)
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -13,9 +15,9 @@ pub struct TimeEntry {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub clock_out_time: Option<DateTime<Utc>>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub clock_in_location: Option<GeoLocation>,
+    pub clock_in_location: Option<GeoPoint>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub clock_out_location: Option<GeoLocation>,
+    pub clock_out_location: Option<GeoPoint>,
     pub clock_in_method: ClockMethod,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub clock_out_method: Option<ClockMethod>,
@@ -23,8 +25,16 @@ pub struct TimeEntry {
     pub clock_in_device: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub clock_out_device: Option<String>,
+    /// Clocked in or out from outside the site, recorded for review
+    #[serde(default)]
+    pub flagged_out_of_area: bool,
+    /// Unpaid break minutes, deducted from the hours worked
     #[serde(default)]
     pub break_duration_minutes: u32,
+    /// Part of `break_duration_minutes` deducted by the break policy
+    /// because no unpaid break was logged
+    #[serde(default)]
+    pub auto_break_minutes: u32,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub total_hours: Option<f64>,
     #[serde(default)]
@@ -63,11 +73,56 @@ impl TimeEntry {
     }
 }
 
-/// Geographic location for clock events
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
-pub struct GeoLocation {
-    pub lat: f64,
-    pub lng: f64,
+/// A manual correction of a time entry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimeEntryAudit {
+    pub id: String,
+    pub time_entry_id: String,
+    pub action: TimeEntryAuditAction,
+    /// Times before the correction; None for added entries
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub before_clock_in: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub before_clock_out: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub before_total_hours: Option<f64>,
+    pub after_clock_in: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after_clock_out: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after_total_hours: Option<f64>,
+    pub reason: String,
+    pub edited_by: String,
+    pub edited_at: DateTime<Utc>,
+}
+
+/// What a time entry correction did
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeEntryAuditAction {
+    /// Times of an existing entry changed
+    #[default]
+    Edited,
+    /// Entry added by hand for a missed day
+    Added,
+}
+
+impl TimeEntryAuditAction {
+    /// Parse the stored lowercase action
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "edited" => Some(Self::Edited),
+            "added" => Some(Self::Added),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Edited => "edited",
+            Self::Added => "added",
+        }
+    }
 }
 
 /// Clock in/out methods
@@ -82,6 +137,30 @@ pub enum ClockMethod {
     Pin,
 }
 
+impl ClockMethod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Biometric => "biometric",
+            Self::Manual => "manual",
+            Self::AutoGeofence => "auto_geofence",
+            Self::Badge => "badge",
+            Self::Pin => "pin",
+        }
+    }
+    
+    /// Parse the stored snake_case method
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "biometric" => Some(Self::Biometric),
+            "manual" => Some(Self::Manual),
+            "auto_geofence" => Some(Self::AutoGeofence),
+            "badge" => Some(Self::Badge),
+            "pin" => Some(Self::Pin),
+            _ => None,
+        }
+    }
+}
+
 /// Time entry status
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
 #[serde(rename_all = "lowercase")]
@@ -94,6 +173,30 @@ pub enum TimeEntryStatus {
     Rejected,
 }
 
+impl TimeEntryStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Active => "active",
+            Self::Completed => "completed",
+            Self::Edited => "edited",
+            Self::Approved => "approved",
+            Self::Rejected => "rejected",
+        }
+    }
+    
+    /// Parse the stored lowercase status
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "active" => Some(Self::Active),
+            "completed" => Some(Self::Completed),
+            "edited" => Some(Self::Edited),
+            "approved" => Some(Self::Approved),
+            "rejected" => Some(Self::Rejected),
+            _ => None,
+        }
+    }
+}
+
 /// Break record
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TimeBreak {
@@ -130,6 +233,24 @@ pub enum BreakType {
     Rest,
 }
 
+impl BreakType {
+    /// Paid breaks count as time worked; the others are deducted
+    pub fn is_paid(&self) -> bool {
+        matches!(self, Self::Paid)
+    }
+    
+    /// Parse the stored lowercase break type
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "unpaid" => Some(Self::Unpaid),
+            "paid" => Some(Self::Paid),
+            "meal" => Some(Self::Meal),
+            "rest" => Some(Self::Rest),
+            _ => None,
+        }
+    }
+}
+
 /// Timesheet summary for a period
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Timesheet {
@@ -157,10 +278,75 @@ pub struct Timesheet {
     pub approved_at: Option<DateTime<Utc>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub notes: Option<String>,
+    /// Shifts that broke the break policy
+    #[serde(default)]
+    pub violations: Vec<BreakViolation>,
     /// Individual time entries
     pub entries: Vec<TimeEntry>,
 }
 
+/// One user's totals on a team timesheet
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TeamMemberHours {
+    pub user_id: String,
+    pub user_name: String,
+    pub regular_hours: f64,
+    pub overtime_hours: f64,
+    pub total_hours: f64,
+    pub total_breaks_minutes: u32,
+    pub days_worked: u32,
+    pub status: TimesheetStatus,
+}
+
+impl From<&Timesheet> for TeamMemberHours {
+    fn from(timesheet: &Timesheet) -> Self {
+        Self {
+            user_id: timesheet.user_id.clone(),
+            user_name: timesheet.user_name.clone(),
+            regular_hours: timesheet.regular_hours,
+            overtime_hours: timesheet.overtime_hours,
+            total_hours: timesheet.total_hours,
+            total_breaks_minutes: timesheet.total_breaks_minutes,
+            days_worked: timesheet.days_worked,
+            status: timesheet.status,
+        }
+    }
+}
+
+/// Totals across a team; `days_worked` counts each user's days
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct TeamTimesheetTotals {
+    pub regular_hours: f64,
+    pub overtime_hours: f64,
+    pub total_hours: f64,
+    pub total_breaks_minutes: u32,
+    pub days_worked: u32,
+}
+
+/// Hours of each user on a team over a period
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TeamTimesheetSummary {
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    pub members: Vec<TeamMemberHours>,
+    pub totals: TeamTimesheetTotals,
+}
+
+impl TeamTimesheetSummary {
+    pub fn new(start_date: NaiveDate, end_date: NaiveDate, timesheets: &[Timesheet]) -> Self {
+        let members: Vec<TeamMemberHours> = timesheets.iter().map(TeamMemberHours::from).collect();
+        let totals = members.iter().fold(TeamTimesheetTotals::default(), |mut totals, member| {
+            totals.regular_hours += member.regular_hours;
+            totals.overtime_hours += member.overtime_hours;
+            totals.total_hours += member.total_hours;
+            totals.total_breaks_minutes += member.total_breaks_minutes;
+            totals.days_worked += member.days_worked;
+            totals
+        });
+        Self { start_date, end_date, members, totals }
+    }
+}
+
 /// Timesheet status
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
 #[serde(rename_all = "lowercase")]
@@ -172,6 +358,52 @@ pub enum TimesheetStatus {
     Rejected,
 }
 
+impl TimesheetStatus {
+    /// Parse the stored lowercase status
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "draft" => Some(Self::Draft),
+            "submitted" => Some(Self::Submitted),
+            "approved" => Some(Self::Approved),
+            "rejected" => Some(Self::Rejected),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Draft => "draft",
+            Self::Submitted => "submitted",
+            Self::Approved => "approved",
+            Self::Rejected => "rejected",
+        }
+    }
+}
+
+/// A user's timesheet for a date range once submitted for approval. The
+/// totals are frozen as they stood at submission.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimesheetPeriod {
+    pub id: String,
+    pub user_id: String,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    pub status: TimesheetStatus,
+    pub regular_hours: f64,
+    pub overtime_hours: f64,
+    pub total_hours: f64,
+    pub total_breaks_minutes: u32,
+    pub days_worked: u32,
+    pub submitted_at: DateTime<Utc>,
+    /// Who approved or rejected it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reviewed_by: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reviewed_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rejection_reason: Option<String>,
+}
+
 /// Pay period
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PayPeriod {
@@ -4,14 +4,20 @@
 
 use std::sync::Arc;
 use base64::Engine;
-use chrono::{Utc, NaiveDate, Datelike};
-use rusqlite::params;
+use chrono::{DateTime, Utc, NaiveDate, NaiveTime};
+use rusqlite::{params, params_from_iter};
 use tracing::{info, debug, warn};
+use wms_core::clock::{SharedClock, system_clock};
 use wms_core::db::Database;
 use wms_core::error::{WmsError, Result};
-use wms_core::types::new_id;
+use wms_core::events::{SharedEmitter, WmsEvent, noop_emitter};
+use wms_core::types::{
+    new_id, required_date, required_timestamp, stored_status, stored_timestamp, unreadable,
+};
 use crate::models::*;
-use crate::export::{ExcelExporter, CsvExporter, TimesheetExport};
+use crate::breaks::BreakPolicy;
+use crate::site::ClockSiteSettings;
+use crate::export::{ExcelExporter, CsvExporter, PdfExporter, TimesheetExport};
 
 /// Timesheet service
 pub struct TimesheetService {
@@ -20,6 +26,9 @@ pub struct TimesheetService {
     standard_hours: f64,
     /// Weekly overtime threshold
     weekly_overtime_threshold: f64,
+    breaks: BreakPolicy,
+    events: SharedEmitter,
+    clock: SharedClock,
 }
 
 impl TimesheetService {
@@ -29,6 +38,9 @@ impl TimesheetService {
             db,
             standard_hours: 8.0,
             weekly_overtime_threshold: 40.0,
+            breaks: BreakPolicy::default(),
+            events: noop_emitter(),
+            clock: system_clock(),
         }
     }
     
@@ -39,28 +51,56 @@ impl TimesheetService {
         self
     }
     
-    /// Clock in for a user
-    pub async fn clock_in(&self, user_id: &str) -> Result<TimeEntry> {
+    /// Apply the given break rules
+    pub fn with_break_policy(mut self, breaks: BreakPolicy) -> Self {
+        self.breaks = breaks;
+        self
+    }
+    
+    /// Publish domain events through the given emitter
+    pub fn with_event_emitter(mut self, events: SharedEmitter) -> Self {
+        self.events = events;
+        self
+    }
+    
+    /// Read the current time from the given clock
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+    
+    /// Clock in for a user from `location` on `device_id`. Punches from
+    /// outside the configured site are rejected or flagged.
+    pub async fn clock_in(
+        &self,
+        user_id: &str,
+        location: Option<GeoPoint>,
+        device_id: Option<&str>,
+    ) -> Result<TimeEntry> {
         // Check for existing open entry
         let existing = self.get_active_entry(user_id).await?;
         if existing.is_some() {
             return Err(WmsError::validation("User is already clocked in"));
         }
         
-        let now = Utc::now();
+        let now = self.clock.now();
+        self.require_unlocked_date(user_id, now.date_naive())?;
+        let flagged = ClockSiteSettings::load(&self.db)?.check(location)?;
         let entry = TimeEntry {
             id: new_id(),
             user_id: user_id.to_string(),
             entry_date: now.date_naive(),
             clock_in_time: now,
             clock_out_time: None,
-            clock_in_location: None,
+            clock_in_location: location,
             clock_out_location: None,
             clock_in_method: ClockMethod::Biometric,
             clock_out_method: None,
-            clock_in_device: None,
+            clock_in_device: device_id.map(String::from),
             clock_out_device: None,
+            flagged_out_of_area: flagged,
             break_duration_minutes: 0,
+            auto_break_minutes: 0,
             total_hours: None,
             overtime_hours: 0.0,
             status: TimeEntryStatus::Active,
@@ -76,52 +116,80 @@ impl TimesheetService {
         
         self.db.execute(
             "INSERT INTO time_entries (
-                id, user_id, entry_date, clock_in_time, clock_in_method,
-                status, created_at
-            ) VALUES (?, ?, ?, ?, ?, ?, ?)",
+                id, user_id, entry_date, clock_in_time, clock_in_location_lat, clock_in_location_lng,
+                clock_in_method, clock_in_device, flagged_out_of_area, status, created_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
             params![
                 &entry.id,
                 &entry.user_id,
                 entry.entry_date.to_string(),
                 entry.clock_in_time.to_rfc3339(),
+                location.map(|p| p.lat),
+                location.map(|p| p.lng),
                 "biometric",
+                &entry.clock_in_device,
+                entry.flagged_out_of_area,
                 "active",
                 entry.created_at.to_rfc3339(),
             ],
         )?;
         
         info!("User {} clocked in at {}", user_id, now);
+        if flagged {
+            warn!("User {} clocked in outside the site", user_id);
+        }
+        self.events.emit(WmsEvent::TimesheetClockedIn {
+            entry_id: entry.id.clone(),
+            user_id: entry.user_id.clone(),
+            clock_in_time: entry.clock_in_time.to_rfc3339(),
+        });
         Ok(entry)
     }
     
-    /// Clock out for a user
-    pub async fn clock_out(&self, user_id: &str) -> Result<TimeEntry> {
+    /// Clock out for a user from `location` on `device_id`. Punches from
+    /// outside the configured site are rejected or flagged.
+    pub async fn clock_out(
+        &self,
+        user_id: &str,
+        location: Option<GeoPoint>,
+        device_id: Option<&str>,
+    ) -> Result<TimeEntry> {
         let mut entry = self.get_active_entry(user_id).await?
             .ok_or_else(|| WmsError::validation("User is not clocked in"))?;
+        let flagged = ClockSiteSettings::load(&self.db)?.check(location)?;
         
-        let now = Utc::now();
+        let now = self.clock.now();
         entry.clock_out_time = Some(now);
+        entry.clock_out_location = location;
         entry.clock_out_method = Some(ClockMethod::Biometric);
+        entry.clock_out_device = device_id.map(String::from);
+        entry.flagged_out_of_area |= flagged;
         entry.status = TimeEntryStatus::Completed;
+        entry.breaks = self.get_breaks(&entry.id)?;
+        entry.auto_break_minutes = self.breaks.auto_deduction(&entry, &entry.breaks);
+        entry.break_duration_minutes += entry.auto_break_minutes;
         entry.total_hours = entry.calculate_hours();
         entry.updated_at = Some(now);
         
-        // Calculate overtime
-        if let Some(hours) = entry.total_hours {
-            if hours > self.standard_hours {
-                entry.overtime_hours = hours - self.standard_hours;
-            }
-        }
+        entry.overtime_hours = self.overtime(entry.total_hours);
         
         self.db.execute(
             "UPDATE time_entries SET
-                clock_out_time = ?, clock_out_method = ?, status = ?,
-                total_hours = ?, overtime_hours = ?, updated_at = ?
+                clock_out_time = ?, clock_out_location_lat = ?, clock_out_location_lng = ?,
+                clock_out_method = ?, clock_out_device = ?, flagged_out_of_area = ?, status = ?,
+                break_duration_minutes = ?, auto_break_minutes = ?, total_hours = ?,
+                overtime_hours = ?, updated_at = ?
              WHERE id = ?",
             params![
                 entry.clock_out_time.map(|t| t.to_rfc3339()),
+                location.map(|p| p.lat),
+                location.map(|p| p.lng),
                 "biometric",
+                &entry.clock_out_device,
+                entry.flagged_out_of_area,
                 "completed",
+                entry.break_duration_minutes,
+                entry.auto_break_minutes,
                 entry.total_hours,
                 entry.overtime_hours,
                 entry.updated_at.map(|t| t.to_rfc3339()),
@@ -131,6 +199,12 @@ impl TimesheetService {
         
         info!("User {} clocked out at {}, worked {:.2} hours", 
               user_id, now, entry.total_hours.unwrap_or(0.0));
+        if flagged {
+            warn!("User {} clocked out outside the site", user_id);
+        }
+        if entry.auto_break_minutes > 0 {
+            info!("Deducted an unlogged {} minute break from time entry {}", entry.auto_break_minutes, entry.id);
+        }
         
         Ok(entry)
     }
@@ -153,13 +227,78 @@ impl TimesheetService {
         start_date: &str,
         end_date: &str,
     ) -> Result<Timesheet> {
+        let (start, end) = Self::parse_range(start_date, end_date)?;
+        
+        // Get user name
+        let user_name: String = self.db.query_row(
+            "SELECT full_name FROM users WHERE id = ?",
+            params![user_id],
+            |row| row.get(0),
+        )?.unwrap_or_else(|| "Unknown".to_string());
+        
+        self.build_timesheet(user_id, user_name, start, end)
+    }
+    
+    /// Timesheets of several users over a date range, by name. Without
+    /// `user_ids`, everyone with time entries in the range.
+    fn team_timesheets(
+        &self,
+        start: NaiveDate,
+        end: NaiveDate,
+        user_ids: Option<Vec<String>>,
+    ) -> Result<Vec<Timesheet>> {
+        let user_ids = match user_ids {
+            Some(mut ids) => {
+                let mut seen = std::collections::HashSet::new();
+                ids.retain(|id| seen.insert(id.clone()));
+                ids
+            }
+            None => self.db.query_map(
+                "SELECT DISTINCT user_id FROM time_entries WHERE entry_date >= ? AND entry_date <= ?",
+                params![start.to_string(), end.to_string()],
+                |row| row.get(0),
+            )?,
+        };
+        if user_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        
+        let placeholders = vec!["?"; user_ids.len()].join(", ");
+        let names: std::collections::HashMap<String, String> = self.db.query_map(
+            &format!("SELECT id, full_name FROM users WHERE id IN ({})", placeholders),
+            params_from_iter(user_ids.iter()),
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?.into_iter().collect();
+        
+        let mut timesheets = user_ids.iter()
+            .map(|user_id| {
+                let user_name = names.get(user_id).cloned().unwrap_or_else(|| "Unknown".to_string());
+                self.build_timesheet(user_id, user_name, start, end)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        timesheets.sort_by(|a, b| a.user_name.cmp(&b.user_name).then_with(|| a.user_id.cmp(&b.user_id)));
+        Ok(timesheets)
+    }
+    
+    fn parse_range(start_date: &str, end_date: &str) -> Result<(NaiveDate, NaiveDate)> {
         let start = NaiveDate::parse_from_str(start_date, "%Y-%m-%d")
             .map_err(|_| WmsError::validation("Invalid start date format"))?;
         let end = NaiveDate::parse_from_str(end_date, "%Y-%m-%d")
             .map_err(|_| WmsError::validation("Invalid end date format"))?;
-        
+        Ok((start, end))
+    }
+    
+    /// A user's timesheet, with the totals of their submitted timesheet
+    /// for the range if there is one
+    fn build_timesheet(
+        &self,
+        user_id: &str,
+        user_name: String,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> Result<Timesheet> {
         // Get time entries
-        let entries = self.db.query_map(
+        let mut entries = self.db.query_map(
             "SELECT * FROM time_entries 
              WHERE user_id = ? AND entry_date >= ? AND entry_date <= ?
              ORDER BY entry_date, clock_in_time",
@@ -167,6 +306,18 @@ impl TimesheetService {
             |row| Self::row_to_entry(row),
         )?;
         
+        for entry in &mut entries {
+            entry.breaks = self.get_breaks(&entry.id)?;
+            let recorded: u32 = entry.breaks.iter()
+                .filter(|b| !b.break_type.is_paid())
+                .filter_map(|b| b.duration_minutes)
+                .sum::<u32>() + entry.auto_break_minutes;
+            if recorded != entry.break_duration_minutes {
+                warn!("Time entry {} records {} break minutes but its unpaid breaks add up to {}",
+                      entry.id, entry.break_duration_minutes, recorded);
+            }
+        }
+        
         // Calculate summary
         let mut regular_hours = 0.0;
         let mut overtime_hours = 0.0;
@@ -185,14 +336,7 @@ impl TimesheetService {
             days_worked.insert(entry.entry_date);
         }
         
-        // Get user name
-        let user_name: String = self.db.query_row(
-            "SELECT full_name FROM users WHERE id = ?",
-            params![user_id],
-            |row| row.get(0),
-        )?.unwrap_or_else(|| "Unknown".to_string());
-        
-        Ok(Timesheet {
+        let mut timesheet = Timesheet {
             user_id: user_id.to_string(),
             user_name,
             start_date: start,
@@ -213,8 +357,29 @@ impl TimesheetService {
             approved_by: None,
             approved_at: None,
             notes: None,
+            violations: entries.iter().flat_map(|e| self.breaks.violations(e)).collect(),
             entries,
-        })
+        };
+        
+        // Submitted timesheets report the totals they were submitted with
+        if let Some(period) = self.find_period(user_id, start, end)? {
+            timesheet.status = period.status;
+            timesheet.submitted_at = Some(period.submitted_at);
+            if matches!(period.status, TimesheetStatus::Submitted | TimesheetStatus::Approved) {
+                timesheet.regular_hours = period.regular_hours;
+                timesheet.overtime_hours = period.overtime_hours;
+                timesheet.total_hours = period.total_hours;
+                timesheet.total_breaks_minutes = period.total_breaks_minutes;
+                timesheet.days_worked = period.days_worked;
+            }
+            if period.status == TimesheetStatus::Approved {
+                timesheet.approved_by = period.reviewed_by;
+                timesheet.approved_at = period.reviewed_at;
+            }
+            timesheet.notes = period.rejection_reason;
+        }
+        
+        Ok(timesheet)
     }
     
     /// Export timesheet to file format
@@ -244,6 +409,14 @@ impl TimesheetService {
                 );
                 (data, "text/csv", filename)
             }
+            "pdf" => {
+                let data = PdfExporter::export(&timesheet)?;
+                let filename = format!(
+                    "timesheet_{}_{}_to_{}.pdf",
+                    user_id, start_date, end_date
+                );
+                (data, "application/pdf", filename)
+            }
             _ => {
                 return Err(WmsError::validation(format!("Unsupported format: {}", format)));
             }
@@ -258,12 +431,541 @@ impl TimesheetService {
         })
     }
     
+    /// Totals of each user on a team over a date range, with the team's
+    /// totals. Without `user_ids`, everyone with time entries in the range;
+    /// requested users without any appear with zeros.
+    pub async fn get_team_summary(
+        &self,
+        start_date: &str,
+        end_date: &str,
+        user_ids: Option<Vec<String>>,
+    ) -> Result<TeamTimesheetSummary> {
+        let (start, end) = Self::parse_range(start_date, end_date)?;
+        let timesheets = self.team_timesheets(start, end, user_ids)?;
+        Ok(TeamTimesheetSummary::new(start, end, &timesheets))
+    }
+    
+    /// Export a team summary, with a sheet of entries per user in the
+    /// Excel format
+    pub async fn export_team_summary(
+        &self,
+        start_date: &str,
+        end_date: &str,
+        user_ids: Option<Vec<String>>,
+        format: &str,
+    ) -> Result<TimesheetExport> {
+        let (start, end) = Self::parse_range(start_date, end_date)?;
+        let timesheets = self.team_timesheets(start, end, user_ids)?;
+        let summary = TeamTimesheetSummary::new(start, end, &timesheets);
+        
+        let (data, content_type, extension) = match format.to_lowercase().as_str() {
+            "xlsx" | "excel" => (
+                ExcelExporter::export_team(&summary, &timesheets)?,
+                "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+                "xlsx",
+            ),
+            "csv" => (CsvExporter::export_team(&summary)?, "text/csv", "csv"),
+            _ => {
+                return Err(WmsError::validation(format!("Unsupported format: {}", format)));
+            }
+        };
+        
+        info!("Exported the team timesheet for {} to {} in {} format", start, end, format);
+        
+        Ok(TimesheetExport {
+            data: base64::engine::general_purpose::STANDARD.encode(&data),
+            content_type: content_type.to_string(),
+            filename: format!("team_timesheet_{}_to_{}.{}", start, end, extension),
+        })
+    }
+    
+    // ============ Approval ============
+    
+    /// Submit a user's timesheet for a date range, freezing its totals. A
+    /// rejected timesheet can be corrected and submitted again.
+    pub async fn submit_timesheet(
+        &self,
+        user_id: &str,
+        start_date: &str,
+        end_date: &str,
+    ) -> Result<TimesheetPeriod> {
+        let timesheet = self.get_timesheet(user_id, start_date, end_date).await?;
+        let (start, end) = (timesheet.start_date, timesheet.end_date);
+        if end < start {
+            return Err(WmsError::validation("End date must not be before the start date"));
+        }
+        if !matches!(timesheet.status, TimesheetStatus::Draft | TimesheetStatus::Rejected) {
+            return Err(WmsError::conflict(format!(
+                "Timesheet for {} to {} is {} and cannot be submitted", start, end, timesheet.status.as_str()
+            )));
+        }
+        if timesheet.entries.iter().any(|e| e.is_clocked_in()) {
+            return Err(WmsError::validation("Clock out before submitting the timesheet"));
+        }
+        
+        let overlapping: Option<String> = self.db.query_row(
+            "SELECT id FROM timesheet_periods
+             WHERE user_id = ? AND status != 'rejected' AND start_date <= ? AND end_date >= ?",
+            params![user_id, end.to_string(), start.to_string()],
+            |row| row.get(0),
+        )?;
+        if overlapping.is_some() {
+            return Err(WmsError::conflict("Timesheet overlaps one already submitted"));
+        }
+        
+        self.db.execute(
+            "INSERT INTO timesheet_periods (
+                id, user_id, start_date, end_date, status, regular_hours, overtime_hours,
+                total_hours, total_breaks_minutes, days_worked, submitted_at
+            ) VALUES (?, ?, ?, ?, 'submitted', ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(user_id, start_date, end_date) DO UPDATE SET
+                status = 'submitted', regular_hours = excluded.regular_hours,
+                overtime_hours = excluded.overtime_hours, total_hours = excluded.total_hours,
+                total_breaks_minutes = excluded.total_breaks_minutes, days_worked = excluded.days_worked,
+                submitted_at = excluded.submitted_at, reviewed_by = NULL, reviewed_at = NULL,
+                rejection_reason = NULL",
+            params![
+                new_id(),
+                user_id,
+                start.to_string(),
+                end.to_string(),
+                timesheet.regular_hours,
+                timesheet.overtime_hours,
+                timesheet.total_hours,
+                timesheet.total_breaks_minutes,
+                timesheet.days_worked,
+                self.clock.now().to_rfc3339(),
+            ],
+        )?;
+        
+        info!("User {} submitted their timesheet for {} to {}", user_id, start, end);
+        self.find_period(user_id, start, end)?
+            .ok_or_else(|| WmsError::not_found("Timesheet not found"))
+    }
+    
+    /// Approve a submitted timesheet, locking its time entries. Nobody can
+    /// approve their own timesheet, and a timesheet with a shift still open
+    /// can't be approved, since the open entry could never be clocked out.
+    pub async fn approve_timesheet(
+        &self,
+        user_id: &str,
+        period_id: &str,
+        approver_id: &str,
+    ) -> Result<TimesheetPeriod> {
+        let period = self.require_submitted(user_id, period_id, approver_id, "approved")?;
+        let now = self.clock.now().to_rfc3339();
+        
+        self.db.transaction(|conn| {
+            let rows = conn.execute(
+                "UPDATE timesheet_periods SET status = 'approved', reviewed_by = ?, reviewed_at = ?
+                 WHERE id = ? AND status = 'submitted'",
+                params![approver_id, &now, period_id],
+            )?;
+            if rows == 0 {
+                return Err(WmsError::conflict("Timesheet was reviewed by someone else"));
+            }
+            let open: i64 = conn.query_row(
+                "SELECT COUNT(*) FROM time_entries
+                 WHERE user_id = ? AND entry_date >= ? AND entry_date <= ? AND clock_out_time IS NULL",
+                params![user_id, period.start_date.to_string(), period.end_date.to_string()],
+                |row| row.get(0),
+            )?;
+            if open > 0 {
+                return Err(WmsError::validation(
+                    "The timesheet has a shift that is still clocked in; reject it so it can be corrected",
+                ));
+            }
+            conn.execute(
+                "UPDATE time_entries SET status = 'approved', approved_by = ?, approved_at = ?, updated_at = ?
+                 WHERE user_id = ? AND entry_date >= ? AND entry_date <= ?",
+                params![
+                    approver_id,
+                    &now,
+                    &now,
+                    user_id,
+                    period.start_date.to_string(),
+                    period.end_date.to_string(),
+                ],
+            )?;
+            Ok(())
+        })?;
+        
+        info!("Timesheet {} of user {} approved by {}", period_id, user_id, approver_id);
+        self.get_period(period_id)?
+            .ok_or_else(|| WmsError::not_found("Timesheet not found"))
+    }
+    
+    /// Send a submitted timesheet back to the user with a reason, so they
+    /// can correct and submit it again
+    pub async fn reject_timesheet(
+        &self,
+        user_id: &str,
+        period_id: &str,
+        approver_id: &str,
+        reason: &str,
+    ) -> Result<TimesheetPeriod> {
+        let reason = reason.trim();
+        if reason.is_empty() {
+            return Err(WmsError::validation("A reason is required to reject a timesheet"));
+        }
+        self.require_submitted(user_id, period_id, approver_id, "rejected")?;
+        
+        let rows = self.db.execute(
+            "UPDATE timesheet_periods SET status = 'rejected', reviewed_by = ?, reviewed_at = ?,
+                rejection_reason = ?
+             WHERE id = ? AND status = 'submitted'",
+            params![approver_id, self.clock.now().to_rfc3339(), reason, period_id],
+        )?;
+        if rows == 0 {
+            return Err(WmsError::conflict("Timesheet was reviewed by someone else"));
+        }
+        
+        info!("Timesheet {} of user {} rejected by {}", period_id, user_id, approver_id);
+        self.get_period(period_id)?
+            .ok_or_else(|| WmsError::not_found("Timesheet not found"))
+    }
+    
+    /// Correct the times of an entry on an approved timesheet. This is the
+    /// admin path around the lock: it needs a reason, is audited like any
+    /// other correction and leaves the approved totals as they were.
+    pub async fn edit_locked_entry(
+        &self,
+        entry_id: &str,
+        clock_in_time: DateTime<Utc>,
+        clock_out_time: DateTime<Utc>,
+        editor_id: &str,
+        reason: &str,
+    ) -> Result<TimeEntry> {
+        let reason = Self::correction_reason(reason, clock_in_time, clock_out_time)?;
+        let entry = self.get_entry(entry_id)?
+            .ok_or_else(|| WmsError::not_found("Time entry not found"))?;
+        if entry.status != TimeEntryStatus::Approved {
+            return Err(WmsError::validation("Time entry is not locked"));
+        }
+        
+        let entry = self.correct_entry(entry, clock_in_time, clock_out_time, editor_id, reason)?;
+        info!("Locked time entry {} edited by {}: {}", entry.id, editor_id, reason);
+        Ok(entry)
+    }
+    
+    // ============ Corrections ============
+    
+    /// Correct the clock times of an entry, e.g. when someone forgot to
+    /// clock out. Hours are recomputed and the change is audited. Entries on
+    /// approved timesheets are locked; see `edit_locked_entry`.
+    pub async fn edit_entry(
+        &self,
+        entry_id: &str,
+        new_clock_in: DateTime<Utc>,
+        new_clock_out: DateTime<Utc>,
+        editor_id: &str,
+        reason: &str,
+    ) -> Result<TimeEntry> {
+        let reason = Self::correction_reason(reason, new_clock_in, new_clock_out)?;
+        let mut entry = self.get_entry(entry_id)?
+            .ok_or_else(|| WmsError::not_found("Time entry not found"))?;
+        if entry.status == TimeEntryStatus::Approved {
+            return Err(WmsError::validation("Time entry is on an approved timesheet and is locked"));
+        }
+        self.require_unlocked_date(&entry.user_id, new_clock_in.date_naive())?;
+        self.require_no_overlap(&entry.user_id, Some(&entry.id), new_clock_in, new_clock_out)?;
+        
+        entry.entry_date = new_clock_in.date_naive();
+        entry.status = TimeEntryStatus::Edited;
+        let entry = self.correct_entry(entry, new_clock_in, new_clock_out, editor_id, reason)?;
+        info!("Time entry {} edited by {}: {}", entry.id, editor_id, reason);
+        Ok(entry)
+    }
+    
+    /// Add an entry by hand for a day the user worked but didn't clock.
+    /// A clock out before the clock in is on the next day.
+    pub async fn add_manual_entry(
+        &self,
+        user_id: &str,
+        date: NaiveDate,
+        clock_in: NaiveTime,
+        clock_out: NaiveTime,
+        reason: &str,
+        editor_id: &str,
+    ) -> Result<TimeEntry> {
+        let clock_in_time = date.and_time(clock_in).and_utc();
+        let mut clock_out_time = date.and_time(clock_out).and_utc();
+        if clock_out < clock_in {
+            clock_out_time += chrono::Duration::days(1);
+        }
+        let reason = Self::correction_reason(reason, clock_in_time, clock_out_time)?;
+        self.require_unlocked_date(user_id, date)?;
+        self.require_no_overlap(user_id, None, clock_in_time, clock_out_time)?;
+        
+        let now = self.clock.now();
+        let mut entry = TimeEntry {
+            id: new_id(),
+            user_id: user_id.to_string(),
+            entry_date: date,
+            clock_in_time,
+            clock_out_time: Some(clock_out_time),
+            clock_in_location: None,
+            clock_out_location: None,
+            clock_in_method: ClockMethod::Manual,
+            clock_out_method: Some(ClockMethod::Manual),
+            clock_in_device: None,
+            clock_out_device: None,
+            flagged_out_of_area: false,
+            break_duration_minutes: 0,
+            auto_break_minutes: 0,
+            total_hours: None,
+            overtime_hours: 0.0,
+            status: TimeEntryStatus::Edited,
+            notes: None,
+            edited_by: Some(editor_id.to_string()),
+            edited_reason: Some(reason.to_string()),
+            approved_by: None,
+            approved_at: None,
+            created_at: now,
+            updated_at: None,
+            breaks: Vec::new(),
+        };
+        entry.total_hours = entry.calculate_hours();
+        entry.overtime_hours = self.overtime(entry.total_hours);
+        
+        self.db.transaction(|conn| {
+            conn.execute(
+                "INSERT INTO time_entries (
+                    id, user_id, entry_date, clock_in_time, clock_out_time, clock_in_method,
+                    clock_out_method, total_hours, overtime_hours, status, edited_by, edited_reason,
+                    created_at
+                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                params![
+                    &entry.id,
+                    &entry.user_id,
+                    entry.entry_date.to_string(),
+                    entry.clock_in_time.to_rfc3339(),
+                    entry.clock_out_time.map(|t| t.to_rfc3339()),
+                    entry.clock_in_method.as_str(),
+                    entry.clock_out_method.map(|m| m.as_str()),
+                    entry.total_hours,
+                    entry.overtime_hours,
+                    entry.status.as_str(),
+                    &entry.edited_by,
+                    &entry.edited_reason,
+                    entry.created_at.to_rfc3339(),
+                ],
+            )?;
+            Self::insert_audit(conn, &entry, None, TimeEntryAuditAction::Added, reason, editor_id, now)
+        })?;
+        
+        info!("Manual time entry {} added for user {} by {}: {}", entry.id, user_id, editor_id, reason);
+        Ok(entry)
+    }
+    
+    /// Corrections made to a time entry, oldest first
+    pub async fn get_entry_audit(&self, entry_id: &str) -> Result<Vec<TimeEntryAudit>> {
+        self.db.query_map(
+            "SELECT * FROM time_entry_audit WHERE time_entry_id = ? ORDER BY edited_at, rowid",
+            params![entry_id],
+            |row| Self::row_to_audit(row),
+        )
+    }
+    
+    /// The trimmed reason for a correction, after checking its times
+    fn correction_reason(reason: &str, clock_in: DateTime<Utc>, clock_out: DateTime<Utc>) -> Result<&str> {
+        let reason = reason.trim();
+        if reason.is_empty() {
+            return Err(WmsError::validation("A reason is required to correct a time entry"));
+        }
+        if clock_out <= clock_in {
+            return Err(WmsError::validation("Clock out must be after clock in"));
+        }
+        Ok(reason)
+    }
+    
+    /// Fail if the user has another entry overlapping the given times. An
+    /// entry still clocked in runs until now.
+    fn require_no_overlap(
+        &self,
+        user_id: &str,
+        except_id: Option<&str>,
+        clock_in: DateTime<Utc>,
+        clock_out: DateTime<Utc>,
+    ) -> Result<()> {
+        // Shifts run over midnight, so look at the days either side too
+        let first = clock_in.date_naive().pred_opt().unwrap_or(NaiveDate::MIN);
+        let last = clock_out.date_naive().succ_opt().unwrap_or(NaiveDate::MAX);
+        let entries = self.db.query_map(
+            "SELECT * FROM time_entries WHERE user_id = ? AND entry_date >= ? AND entry_date <= ?",
+            params![user_id, first.to_string(), last.to_string()],
+            |row| Self::row_to_entry(row),
+        )?;
+        
+        let now = self.clock.now();
+        let overlapping = entries.iter()
+            .filter(|e| Some(e.id.as_str()) != except_id)
+            .find(|e| e.clock_in_time < clock_out && e.clock_out_time.unwrap_or(now) > clock_in);
+        match overlapping {
+            Some(other) => Err(WmsError::validation(format!(
+                "Overlaps the entry from {} on {}",
+                other.clock_in_time.format("%H:%M"), other.entry_date
+            ))),
+            None => Ok(()),
+        }
+    }
+    
+    /// Store new clock times for an entry, with recomputed hours and an
+    /// audit row of the change
+    fn correct_entry(
+        &self,
+        mut entry: TimeEntry,
+        clock_in_time: DateTime<Utc>,
+        clock_out_time: DateTime<Utc>,
+        editor_id: &str,
+        reason: &str,
+    ) -> Result<TimeEntry> {
+        let before = entry.clone();
+        let now = self.clock.now();
+        entry.clock_in_time = clock_in_time;
+        entry.clock_out_time = Some(clock_out_time);
+        entry.total_hours = entry.calculate_hours();
+        entry.overtime_hours = self.overtime(entry.total_hours);
+        entry.edited_by = Some(editor_id.to_string());
+        entry.edited_reason = Some(reason.to_string());
+        entry.updated_at = Some(now);
+        
+        self.db.transaction(|conn| {
+            conn.execute(
+                "UPDATE time_entries SET
+                    entry_date = ?, clock_in_time = ?, clock_out_time = ?, total_hours = ?,
+                    overtime_hours = ?, status = ?, edited_by = ?, edited_reason = ?, updated_at = ?
+                 WHERE id = ?",
+                params![
+                    entry.entry_date.to_string(),
+                    entry.clock_in_time.to_rfc3339(),
+                    entry.clock_out_time.map(|t| t.to_rfc3339()),
+                    entry.total_hours,
+                    entry.overtime_hours,
+                    entry.status.as_str(),
+                    &entry.edited_by,
+                    &entry.edited_reason,
+                    entry.updated_at.map(|t| t.to_rfc3339()),
+                    &entry.id,
+                ],
+            )?;
+            Self::insert_audit(conn, &entry, Some(&before), TimeEntryAuditAction::Edited, reason, editor_id, now)
+        })?;
+        
+        entry.breaks = self.get_breaks(&entry.id)?;
+        Ok(entry)
+    }
+    
+    fn insert_audit(
+        conn: &rusqlite::Connection,
+        entry: &TimeEntry,
+        before: Option<&TimeEntry>,
+        action: TimeEntryAuditAction,
+        reason: &str,
+        editor_id: &str,
+        at: DateTime<Utc>,
+    ) -> Result<()> {
+        conn.execute(
+            "INSERT INTO time_entry_audit (
+                id, time_entry_id, action, before_clock_in, before_clock_out, before_total_hours,
+                after_clock_in, after_clock_out, after_total_hours, reason, edited_by, edited_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            params![
+                new_id(),
+                &entry.id,
+                action.as_str(),
+                before.map(|b| b.clock_in_time.to_rfc3339()),
+                before.and_then(|b| b.clock_out_time).map(|t| t.to_rfc3339()),
+                before.and_then(|b| b.total_hours),
+                entry.clock_in_time.to_rfc3339(),
+                entry.clock_out_time.map(|t| t.to_rfc3339()),
+                entry.total_hours,
+                reason,
+                editor_id,
+                at.to_rfc3339(),
+            ],
+        )?;
+        Ok(())
+    }
+    
+    /// The user's submitted timesheet for exactly this date range
+    fn find_period(&self, user_id: &str, start: NaiveDate, end: NaiveDate) -> Result<Option<TimesheetPeriod>> {
+        self.db.query_row(
+            "SELECT * FROM timesheet_periods WHERE user_id = ? AND start_date = ? AND end_date = ?",
+            params![user_id, start.to_string(), end.to_string()],
+            |row| Self::row_to_period(row),
+        )
+    }
+    
+    fn get_period(&self, period_id: &str) -> Result<Option<TimesheetPeriod>> {
+        self.db.query_row(
+            "SELECT * FROM timesheet_periods WHERE id = ?",
+            params![period_id],
+            |row| Self::row_to_period(row),
+        )
+    }
+    
+    /// The user's timesheet awaiting review, after checking the reviewer
+    /// isn't its owner
+    fn require_submitted(
+        &self,
+        user_id: &str,
+        period_id: &str,
+        reviewer_id: &str,
+        outcome: &str,
+    ) -> Result<TimesheetPeriod> {
+        let period = self.get_period(period_id)?
+            .filter(|p| p.user_id == user_id)
+            .ok_or_else(|| WmsError::not_found("Timesheet not found"))?;
+        if reviewer_id == period.user_id {
+            return Err(WmsError::permission_denied("You cannot review your own timesheet"));
+        }
+        if period.status != TimesheetStatus::Submitted {
+            return Err(WmsError::conflict(format!(
+                "Timesheet for {} to {} is {} and cannot be {}",
+                period.start_date, period.end_date, period.status.as_str(), outcome
+            )));
+        }
+        Ok(period)
+    }
+    
+    /// Fail if `date` is on one of the user's submitted or approved
+    /// timesheets; a submitted one must be rejected before it changes
+    fn require_unlocked_date(&self, user_id: &str, date: NaiveDate) -> Result<()> {
+        let locked: Option<String> = self.db.query_row(
+            "SELECT status FROM timesheet_periods
+             WHERE user_id = ? AND status IN ('submitted', 'approved') AND start_date <= ? AND end_date >= ?",
+            params![user_id, date.to_string(), date.to_string()],
+            |row| row.get(0),
+        )?;
+        match locked {
+            Some(status) => Err(WmsError::validation(format!("The timesheet for {} is already {}", date, status))),
+            None => Ok(()),
+        }
+    }
+    
+    fn get_entry(&self, entry_id: &str) -> Result<Option<TimeEntry>> {
+        self.db.query_row(
+            "SELECT * FROM time_entries WHERE id = ?",
+            params![entry_id],
+            |row| Self::row_to_entry(row),
+        )
+    }
+    
+    /// Hours past the standard day
+    fn overtime(&self, total_hours: Option<f64>) -> f64 {
+        total_hours.map_or(0.0, |hours| (hours - self.standard_hours).max(0.0))
+    }
+    
     /// Start a break
     pub async fn start_break(&self, user_id: &str, break_type: BreakType) -> Result<TimeBreak> {
         let entry = self.get_active_entry(user_id).await?
             .ok_or_else(|| WmsError::validation("User is not clocked in"))?;
+        if self.get_breaks(&entry.id)?.iter().any(|b| b.end_time.is_none()) {
+            return Err(WmsError::validation("User is already on a break"));
+        }
         
-        let now = Utc::now();
+        let now = self.clock.now();
         let time_break = TimeBreak {
             id: new_id(),
             time_entry_id: entry.id.clone(),
@@ -303,21 +1005,10 @@ impl TimesheetService {
              WHERE time_entry_id = ? AND end_time IS NULL
              ORDER BY start_time DESC LIMIT 1",
             params![&entry.id],
-            |row| {
-                Ok(TimeBreak {
-                    id: row.get("id")?,
-                    time_entry_id: row.get("time_entry_id")?,
-                    break_type: BreakType::Unpaid,
-                    start_time: Utc::now(),
-                    end_time: None,
-                    duration_minutes: None,
-                    notes: row.get("notes")?,
-                    created_at: Utc::now(),
-                })
-            },
+            |row| Self::row_to_break(row),
         )?.ok_or_else(|| WmsError::validation("No active break found"))?;
         
-        let now = Utc::now();
+        let now = self.clock.now();
         time_break.end_time = Some(now);
         time_break.duration_minutes = time_break.calculate_duration();
         
@@ -330,8 +1021,8 @@ impl TimesheetService {
             ],
         )?;
         
-        // Update total break time on entry
-        if let Some(duration) = time_break.duration_minutes {
+        // Unpaid breaks come off the entry's hours
+        if let Some(duration) = time_break.duration_minutes.filter(|_| !time_break.break_type.is_paid()) {
             self.db.execute(
                 "UPDATE time_entries SET break_duration_minutes = break_duration_minutes + ? WHERE id = ?",
                 params![duration, &entry.id],
@@ -344,32 +1035,569 @@ impl TimesheetService {
         Ok(time_break)
     }
     
+    /// Breaks taken during a time entry, in order
+    fn get_breaks(&self, time_entry_id: &str) -> Result<Vec<TimeBreak>> {
+        self.db.query_map(
+            "SELECT * FROM time_breaks WHERE time_entry_id = ? ORDER BY start_time",
+            params![time_entry_id],
+            |row| Self::row_to_break(row),
+        )
+    }
+    
+    fn row_to_break(row: &rusqlite::Row) -> rusqlite::Result<TimeBreak> {
+        Ok(TimeBreak {
+            id: row.get("id")?,
+            time_entry_id: row.get("time_entry_id")?,
+            break_type: stored_status(row, "break_type", BreakType::parse)?,
+            start_time: required_timestamp(row, "start_time")?,
+            end_time: stored_timestamp(row, "end_time")?,
+            duration_minutes: row.get("duration_minutes")?,
+            notes: row.get("notes")?,
+            created_at: required_timestamp(row, "created_at")?,
+        })
+    }
+    
+    fn row_to_audit(row: &rusqlite::Row) -> rusqlite::Result<TimeEntryAudit> {
+        Ok(TimeEntryAudit {
+            id: row.get("id")?,
+            time_entry_id: row.get("time_entry_id")?,
+            action: stored_status(row, "action", TimeEntryAuditAction::parse)?,
+            before_clock_in: stored_timestamp(row, "before_clock_in")?,
+            before_clock_out: stored_timestamp(row, "before_clock_out")?,
+            before_total_hours: row.get("before_total_hours")?,
+            after_clock_in: required_timestamp(row, "after_clock_in")?,
+            after_clock_out: stored_timestamp(row, "after_clock_out")?,
+            after_total_hours: row.get("after_total_hours")?,
+            reason: row.get("reason")?,
+            edited_by: row.get("edited_by")?,
+            edited_at: required_timestamp(row, "edited_at")?,
+        })
+    }
+    
+    fn row_to_period(row: &rusqlite::Row) -> rusqlite::Result<TimesheetPeriod> {
+        Ok(TimesheetPeriod {
+            id: row.get("id")?,
+            user_id: row.get("user_id")?,
+            start_date: required_date(row, "start_date")?,
+            end_date: required_date(row, "end_date")?,
+            status: stored_status(row, "status", TimesheetStatus::parse)?,
+            regular_hours: row.get("regular_hours")?,
+            overtime_hours: row.get("overtime_hours")?,
+            total_hours: row.get("total_hours")?,
+            total_breaks_minutes: row.get("total_breaks_minutes")?,
+            days_worked: row.get("days_worked")?,
+            submitted_at: required_timestamp(row, "submitted_at")?,
+            reviewed_by: row.get("reviewed_by")?,
+            reviewed_at: stored_timestamp(row, "reviewed_at")?,
+            rejection_reason: row.get("rejection_reason")?,
+        })
+    }
+    
     fn row_to_entry(row: &rusqlite::Row) -> rusqlite::Result<TimeEntry> {
+        let location = |lat: &str, lng: &str| -> rusqlite::Result<Option<GeoPoint>> {
+            Ok(match (row.get::<_, Option<f64>>(lat)?, row.get::<_, Option<f64>>(lng)?) {
+                (Some(lat), Some(lng)) => Some(GeoPoint::new(lat, lng)),
+                _ => None,
+            })
+        };
+        
         Ok(TimeEntry {
             id: row.get("id")?,
             user_id: row.get("user_id")?,
-            entry_date: NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(), // Parse from string
-            clock_in_time: Utc::now(), // Parse from string
-            clock_out_time: None,
-            clock_in_location: None,
-            clock_out_location: None,
-            clock_in_method: ClockMethod::Biometric,
-            clock_out_method: None,
+            entry_date: required_date(row, "entry_date")?,
+            clock_in_time: required_timestamp(row, "clock_in_time")?,
+            clock_out_time: stored_timestamp(row, "clock_out_time")?,
+            clock_in_location: location("clock_in_location_lat", "clock_in_location_lng")?,
+            clock_out_location: location("clock_out_location_lat", "clock_out_location_lng")?,
+            clock_in_method: stored_status(row, "clock_in_method", ClockMethod::parse)?,
+            clock_out_method: row.get::<_, Option<String>>("clock_out_method")?
+                .map(|method| ClockMethod::parse(&method).ok_or_else(|| unreadable(row, "clock_out_method", &method)))
+                .transpose()?,
             clock_in_device: row.get("clock_in_device")?,
             clock_out_device: row.get("clock_out_device")?,
+            flagged_out_of_area: row.get::<_, i32>("flagged_out_of_area").unwrap_or(0) == 1,
             break_duration_minutes: row.get::<_, u32>("break_duration_minutes").unwrap_or(0),
+            auto_break_minutes: row.get::<_, u32>("auto_break_minutes").unwrap_or(0),
             total_hours: row.get("total_hours")?,
             overtime_hours: row.get::<_, f64>("overtime_hours").unwrap_or(0.0),
-            status: TimeEntryStatus::Active,
+            status: stored_status(row, "status", TimeEntryStatus::parse)?,
             notes: row.get("notes")?,
             edited_by: row.get("edited_by")?,
             edited_reason: row.get("edited_reason")?,
             approved_by: row.get("approved_by")?,
-            approved_at: None,
-            created_at: Utc::now(),
-            updated_at: None,
+            approved_at: stored_timestamp(row, "approved_at")?,
+            created_at: required_timestamp(row, "created_at")?,
+            updated_at: stored_timestamp(row, "updated_at")?,
             breaks: Vec::new(),
         })
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use crate::breaks::BreakViolationKind;
+    use crate::site::OutOfAreaPolicy;
+    use wms_core::events::RecordingEmitter;
+    use wms_core::test_support::{fixed_clock, fixture_time, test_db, TEST_USER_ID};
+    
+    #[tokio::test]
+    async fn test_shift_hours_follow_the_clock() {
+        let clock = fixed_clock();
+        let service = TimesheetService::new(test_db()).with_clock(clock.clone());
+        
+        service.clock_in(TEST_USER_ID, None, None).await.unwrap();
+        clock.advance(Duration::hours(4));
+        service.start_break(TEST_USER_ID, BreakType::Meal).await.unwrap();
+        clock.advance(Duration::minutes(30));
+        let time_break = service.end_break(TEST_USER_ID).await.unwrap();
+        assert_eq!(time_break.duration_minutes, Some(30));
+        
+        clock.advance(Duration::hours(5));
+        let entry = service.clock_out(TEST_USER_ID, None, None).await.unwrap();
+        assert_eq!(entry.break_duration_minutes, 30);
+        assert_eq!(entry.total_hours, Some(9.0));
+        assert_eq!(entry.overtime_hours, 1.0);
+    }
+    
+    #[tokio::test]
+    async fn test_clock_in_emits_event() {
+        let events = Arc::new(RecordingEmitter::new());
+        let service = TimesheetService::new(test_db())
+            .with_clock(fixed_clock())
+            .with_event_emitter(events.clone());
+        
+        let entry = service.clock_in(TEST_USER_ID, None, None).await.unwrap();
+        
+        assert_eq!(events.events(), vec![WmsEvent::TimesheetClockedIn {
+            entry_id: entry.id.clone(),
+            user_id: TEST_USER_ID.to_string(),
+            clock_in_time: fixture_time().to_rfc3339(),
+        }]);
+    }
+    
+    #[tokio::test]
+    async fn test_timesheet_reads_back_stored_entries() {
+        let clock = fixed_clock();
+        let service = TimesheetService::new(test_db()).with_clock(clock.clone());
+        
+        let clocked_in = service.clock_in(TEST_USER_ID, None, None).await.unwrap();
+        clock.advance(Duration::hours(2));
+        service.start_break(TEST_USER_ID, BreakType::Rest).await.unwrap();
+        clock.advance(Duration::minutes(15));
+        service.end_break(TEST_USER_ID).await.unwrap();
+        clock.advance(Duration::hours(6));
+        let clocked_out = service.clock_out(TEST_USER_ID, None, None).await.unwrap();
+        
+        let day = clocked_in.clock_in_time.date_naive().to_string();
+        let timesheet = service.get_timesheet(TEST_USER_ID, &day, &day).await.unwrap();
+        assert_eq!(timesheet.entries.len(), 1);
+        let entry = &timesheet.entries[0];
+        assert_eq!(entry.entry_date, clocked_in.clock_in_time.date_naive());
+        assert_eq!(entry.clock_in_time, clocked_in.clock_in_time);
+        assert_eq!(entry.clock_out_time, clocked_out.clock_out_time);
+        assert_eq!(entry.status, TimeEntryStatus::Completed);
+        assert_eq!(entry.created_at, clocked_in.created_at);
+        assert!(entry.total_hours.unwrap() > 0.0);
+        assert!(timesheet.total_hours > 0.0);
+        
+        assert_eq!(entry.breaks.len(), 1);
+        assert_eq!(entry.breaks[0].break_type, BreakType::Rest);
+        assert_eq!(entry.breaks[0].duration_minutes, Some(entry.break_duration_minutes));
+        
+        // Values nothing could have written fail the load instead of being
+        // read as 1970 or as an editable entry
+        for (column, value) in [("clock_in_time", "yesterday"), ("entry_date", "soon"), ("status", "frozen")] {
+            let original: String = service.db.query_row(
+                &format!("SELECT {} FROM time_entries WHERE id = ?", column),
+                params![&entry.id],
+                |row| row.get(0),
+            ).unwrap().unwrap();
+            let set = |value: &str| {
+                service.db.execute(
+                    &format!("UPDATE time_entries SET {} = ? WHERE id = ?", column),
+                    params![value, &entry.id],
+                ).unwrap();
+            };
+            set(value);
+            assert!(service.get_timesheet(TEST_USER_ID, &day, &day).await.is_err(), "{} = {}", column, value);
+            set(&original);
+        }
+    }
+    
+    /// A day's shift for the test user: 8 hours with a 30 minute break
+    async fn worked_day(service: &TimesheetService, clock: &wms_core::clock::FixedClock) -> TimeEntry {
+        service.clock_in(TEST_USER_ID, None, None).await.unwrap();
+        clock.advance(Duration::hours(4));
+        service.start_break(TEST_USER_ID, BreakType::Meal).await.unwrap();
+        clock.advance(Duration::minutes(30));
+        service.end_break(TEST_USER_ID).await.unwrap();
+        clock.advance(Duration::hours(4));
+        service.clock_out(TEST_USER_ID, None, None).await.unwrap()
+    }
+    
+    fn insert_supervisor(db: &Database) {
+        db.execute(
+            "INSERT INTO users (id, username, email, full_name) VALUES ('u2', 'boss', 'boss@example.com', 'Boss')",
+            [],
+        ).unwrap();
+    }
+    
+    #[tokio::test]
+    async fn test_timesheet_approval_transitions() {
+        let clock = fixed_clock();
+        let db = test_db();
+        insert_supervisor(&db);
+        let service = TimesheetService::new(db).with_clock(clock.clone());
+        worked_day(&service, &clock).await;
+        let day = "2025-01-15";
+        
+        let period = service.submit_timesheet(TEST_USER_ID, day, day).await.unwrap();
+        assert_eq!(period.status, TimesheetStatus::Submitted);
+        assert_eq!((period.total_hours, period.days_worked), (8.0, 1));
+        assert!(matches!(service.submit_timesheet(TEST_USER_ID, day, day).await, Err(WmsError::Conflict(_))));
+        assert!(service.submit_timesheet(TEST_USER_ID, "2025-01-14", day).await.is_err());
+        
+        // A submitted day takes no new time
+        clock.advance(Duration::hours(1));
+        assert!(matches!(service.clock_in(TEST_USER_ID, None, None).await, Err(WmsError::Validation(_))));
+        
+        // A shift left open on the day (e.g. synced from another device)
+        // stops approval rather than being locked open
+        service.db.execute(
+            "INSERT INTO time_entries (id, user_id, entry_date, clock_in_time, status)
+             VALUES ('open-shift', ?, ?, ?, 'active')",
+            params![TEST_USER_ID, day, (fixture_time() + Duration::hours(9)).to_rfc3339()],
+        ).unwrap();
+        assert!(matches!(
+            service.approve_timesheet(TEST_USER_ID, &period.id, "u2").await,
+            Err(WmsError::Validation(_))
+        ));
+        let still_submitted = service.get_timesheet(TEST_USER_ID, day, day).await.unwrap();
+        assert_eq!(still_submitted.status, TimesheetStatus::Submitted);
+        service.db.execute("DELETE FROM time_entries WHERE id = 'open-shift'", []).unwrap();
+        
+        // Nobody reviews their own timesheet, and rejecting needs a reason
+        let own = service.approve_timesheet(TEST_USER_ID, &period.id, TEST_USER_ID).await;
+        assert!(matches!(own, Err(WmsError::PermissionDenied(_))));
+        assert!(service.reject_timesheet(TEST_USER_ID, &period.id, "u2", " ").await.is_err());
+        assert!(service.approve_timesheet("u2", &period.id, "u2").await.is_err());
+        
+        let rejected = service.reject_timesheet(TEST_USER_ID, &period.id, "u2", "Missing Friday").await.unwrap();
+        assert_eq!(rejected.status, TimesheetStatus::Rejected);
+        assert_eq!(rejected.rejection_reason.as_deref(), Some("Missing Friday"));
+        assert!(matches!(
+            service.approve_timesheet(TEST_USER_ID, &period.id, "u2").await,
+            Err(WmsError::Conflict(_))
+        ));
+        let timesheet = service.get_timesheet(TEST_USER_ID, day, day).await.unwrap();
+        assert_eq!(timesheet.status, TimesheetStatus::Rejected);
+        assert_eq!(timesheet.notes.as_deref(), Some("Missing Friday"));
+        
+        // Corrected and sent again
+        let resubmitted = service.submit_timesheet(TEST_USER_ID, day, day).await.unwrap();
+        assert_eq!(resubmitted.id, period.id);
+        assert_eq!(resubmitted.status, TimesheetStatus::Submitted);
+        assert!(resubmitted.reviewed_by.is_none() && resubmitted.rejection_reason.is_none());
+        
+        let approved = service.approve_timesheet(TEST_USER_ID, &period.id, "u2").await.unwrap();
+        assert_eq!(approved.status, TimesheetStatus::Approved);
+        assert_eq!(approved.reviewed_by.as_deref(), Some("u2"));
+        for outcome in [
+            service.approve_timesheet(TEST_USER_ID, &period.id, "u2").await,
+            service.reject_timesheet(TEST_USER_ID, &period.id, "u2", "Too late").await,
+            service.submit_timesheet(TEST_USER_ID, day, day).await,
+        ] {
+            assert!(matches!(outcome, Err(WmsError::Conflict(_))));
+        }
+        
+        let timesheet = service.get_timesheet(TEST_USER_ID, day, day).await.unwrap();
+        assert_eq!(timesheet.status, TimesheetStatus::Approved);
+        assert_eq!(timesheet.approved_by.as_deref(), Some("u2"));
+        assert_eq!(timesheet.entries[0].status, TimeEntryStatus::Approved);
+        assert_eq!(timesheet.entries[0].approved_by.as_deref(), Some("u2"));
+    }
+    
+    #[tokio::test]
+    async fn test_approved_entries_are_locked() {
+        let clock = fixed_clock();
+        let db = test_db();
+        insert_supervisor(&db);
+        let service = TimesheetService::new(db).with_clock(clock.clone());
+        let entry = worked_day(&service, &clock).await;
+        let day = "2025-01-15";
+        
+        // Unlocked entries go through the ordinary path
+        let early = entry.clock_in_time - Duration::hours(1);
+        let end = entry.clock_out_time.unwrap();
+        assert!(service.edit_locked_entry(&entry.id, early, end, "u2", "Came in early").await.is_err());
+        
+        let period = service.submit_timesheet(TEST_USER_ID, day, day).await.unwrap();
+        service.approve_timesheet(TEST_USER_ID, &period.id, "u2").await.unwrap();
+        
+        // No more time on an approved day
+        clock.advance(Duration::hours(1));
+        assert!(service.clock_in(TEST_USER_ID, None, None).await.is_err());
+        
+        assert!(service.edit_locked_entry(&entry.id, early, end, "u2", "").await.is_err());
+        assert!(service.edit_locked_entry(&entry.id, end, early, "u2", "Backwards").await.is_err());
+        let edited = service.edit_locked_entry(&entry.id, early, end, "u2", "Came in early").await.unwrap();
+        assert_eq!(edited.status, TimeEntryStatus::Approved);
+        assert_eq!(edited.total_hours, Some(9.0));
+        assert_eq!(edited.overtime_hours, 1.0);
+        assert_eq!(edited.edited_by.as_deref(), Some("u2"));
+        assert_eq!(edited.edited_reason.as_deref(), Some("Came in early"));
+        
+        // The approved totals stay as they were approved
+        let timesheet = service.get_timesheet(TEST_USER_ID, day, day).await.unwrap();
+        assert_eq!(timesheet.total_hours, 8.0);
+        assert_eq!(timesheet.entries[0].total_hours, Some(9.0));
+    }
+    
+    #[tokio::test]
+    async fn test_cannot_submit_while_clocked_in() {
+        let service = TimesheetService::new(test_db()).with_clock(fixed_clock());
+        service.clock_in(TEST_USER_ID, None, None).await.unwrap();
+        
+        let result = service.submit_timesheet(TEST_USER_ID, "2025-01-15", "2025-01-15").await;
+        assert!(matches!(result, Err(WmsError::Validation(_))));
+    }
+    
+    fn time(hour: u32, minute: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, minute, 0).unwrap()
+    }
+    
+    #[tokio::test]
+    async fn test_manual_entries_span_midnight_and_cannot_overlap() {
+        let service = TimesheetService::new(test_db()).with_clock(fixed_clock());
+        let day = NaiveDate::from_ymd_opt(2025, 1, 10).unwrap();
+        
+        let night = service.add_manual_entry(TEST_USER_ID, day, time(22, 0), time(6, 30), "Forgot badge", "u1")
+            .await
+            .unwrap();
+        assert_eq!(night.entry_date, day);
+        assert_eq!(night.clock_out_time.unwrap().date_naive(), day.succ_opt().unwrap());
+        assert_eq!(night.total_hours, Some(8.5));
+        assert_eq!(night.overtime_hours, 0.5);
+        assert_eq!(night.clock_in_method, ClockMethod::Manual);
+        
+        // The next morning's shift starts before the night shift ended
+        let next = day.succ_opt().unwrap();
+        let overlap = service.add_manual_entry(TEST_USER_ID, next, time(6, 0), time(14, 0), "Missed", "u1").await;
+        assert!(matches!(overlap, Err(WmsError::Validation(_))));
+        service.add_manual_entry(TEST_USER_ID, next, time(6, 30), time(14, 0), "Missed", "u1").await.unwrap();
+        
+        assert!(service.add_manual_entry(TEST_USER_ID, day, time(8, 0), time(8, 0), "Zero", "u1").await.is_err());
+        assert!(service.add_manual_entry(TEST_USER_ID, day, time(8, 0), time(12, 0), "  ", "u1").await.is_err());
+        
+        let audit = service.get_entry_audit(&night.id).await.unwrap();
+        assert_eq!(audit.len(), 1);
+        assert_eq!(audit[0].action, TimeEntryAuditAction::Added);
+        assert!(audit[0].before_clock_in.is_none());
+        assert_eq!(audit[0].after_total_hours, Some(8.5));
+    }
+    
+    #[tokio::test]
+    async fn test_forgotten_clock_out_is_corrected_and_audited() {
+        let clock = fixed_clock();
+        let service = TimesheetService::new(test_db()).with_clock(clock.clone());
+        let entry = service.clock_in(TEST_USER_ID, None, None).await.unwrap();
+        clock.advance(Duration::days(1));
+        
+        let clock_out = entry.clock_in_time + Duration::hours(9);
+        assert!(service.edit_entry(&entry.id, entry.clock_in_time, clock_out, "u1", "").await.is_err());
+        let edited = service.edit_entry(&entry.id, entry.clock_in_time, clock_out, "u1", " Forgot to clock out ")
+            .await
+            .unwrap();
+        assert_eq!(edited.status, TimeEntryStatus::Edited);
+        assert_eq!(edited.total_hours, Some(9.0));
+        assert_eq!(edited.overtime_hours, 1.0);
+        assert_eq!(edited.edited_reason.as_deref(), Some("Forgot to clock out"));
+        
+        // A second entry can't be moved on top of the first
+        let day = entry.clock_in_time.date_naive();
+        let other = service.add_manual_entry(TEST_USER_ID, day, time(20, 0), time(22, 0), "Stocktake", "u1")
+            .await
+            .unwrap();
+        let moved = service.edit_entry(&other.id, clock_out - Duration::hours(1), clock_out + Duration::hours(1), "u1", "Earlier")
+            .await;
+        assert!(matches!(moved, Err(WmsError::Validation(_))));
+        
+        let audit = service.get_entry_audit(&entry.id).await.unwrap();
+        assert_eq!(audit.len(), 1);
+        let row = &audit[0];
+        assert_eq!(row.action, TimeEntryAuditAction::Edited);
+        assert_eq!((row.before_clock_in, row.before_clock_out, row.before_total_hours), (Some(entry.clock_in_time), None, None));
+        assert_eq!((row.after_clock_in, row.after_clock_out), (entry.clock_in_time, Some(clock_out)));
+        assert_eq!(row.after_total_hours, Some(9.0));
+        assert_eq!((row.reason.as_str(), row.edited_by.as_str()), ("Forgot to clock out", "u1"));
+        assert_eq!(row.edited_at, fixture_time() + Duration::days(1));
+    }
+    
+    #[tokio::test]
+    async fn test_unlogged_break_is_deducted_and_flagged() {
+        let clock = fixed_clock();
+        let service = TimesheetService::new(test_db()).with_clock(clock.clone());
+        
+        service.clock_in(TEST_USER_ID, None, None).await.unwrap();
+        clock.advance(Duration::hours(9));
+        let entry = service.clock_out(TEST_USER_ID, None, None).await.unwrap();
+        assert_eq!((entry.auto_break_minutes, entry.break_duration_minutes), (30, 30));
+        assert_eq!(entry.total_hours, Some(8.5));
+        
+        let timesheet = service.get_timesheet(TEST_USER_ID, "2025-01-15", "2025-01-15").await.unwrap();
+        assert_eq!(timesheet.entries[0].auto_break_minutes, 30);
+        let kinds: Vec<BreakViolationKind> = timesheet.violations.iter().map(|v| v.kind).collect();
+        assert_eq!(kinds, vec![BreakViolationKind::MissingMealBreak, BreakViolationKind::BreakAutoDeducted]);
+        assert_eq!(timesheet.violations[0].shift_hours, 9.0);
+        assert_eq!(timesheet.violations[0].time_entry_id, entry.id);
+    }
+    
+    #[tokio::test]
+    async fn test_logged_unpaid_break_suppresses_deduction() {
+        let clock = fixed_clock();
+        let service = TimesheetService::new(test_db()).with_clock(clock.clone());
+        
+        service.clock_in(TEST_USER_ID, None, None).await.unwrap();
+        clock.advance(Duration::hours(3));
+        service.start_break(TEST_USER_ID, BreakType::Unpaid).await.unwrap();
+        clock.advance(Duration::minutes(15));
+        service.end_break(TEST_USER_ID).await.unwrap();
+        clock.advance(Duration::hours(4));
+        let entry = service.clock_out(TEST_USER_ID, None, None).await.unwrap();
+        
+        assert_eq!((entry.auto_break_minutes, entry.break_duration_minutes), (0, 15));
+        assert_eq!(entry.total_hours, Some(7.0));
+        let timesheet = service.get_timesheet(TEST_USER_ID, "2025-01-15", "2025-01-15").await.unwrap();
+        assert!(timesheet.violations.is_empty());
+    }
+    
+    #[tokio::test]
+    async fn test_paid_breaks_count_as_worked() {
+        let clock = fixed_clock();
+        let service = TimesheetService::new(test_db())
+            .with_clock(clock.clone())
+            .with_break_policy(BreakPolicy::disabled());
+        
+        service.clock_in(TEST_USER_ID, None, None).await.unwrap();
+        clock.advance(Duration::hours(2));
+        service.start_break(TEST_USER_ID, BreakType::Paid).await.unwrap();
+        assert!(service.start_break(TEST_USER_ID, BreakType::Meal).await.is_err());
+        clock.advance(Duration::minutes(15));
+        service.end_break(TEST_USER_ID).await.unwrap();
+        clock.advance(Duration::hours(2));
+        let entry = service.clock_out(TEST_USER_ID, None, None).await.unwrap();
+        
+        assert_eq!(entry.break_duration_minutes, 0);
+        assert_eq!(entry.total_hours, Some(4.25));
+    }
+    
+    fn site_db(out_of_area: OutOfAreaPolicy) -> Arc<Database> {
+        let db = test_db();
+        ClockSiteSettings {
+            site: Some(GeoPoint::new(41.8781, -87.6298)),
+            radius_meters: 150.0,
+            out_of_area,
+        }.save(&db).unwrap();
+        db
+    }
+    
+    #[tokio::test]
+    async fn test_on_site_punches_record_location_and_device() {
+        let clock = fixed_clock();
+        let service = TimesheetService::new(site_db(OutOfAreaPolicy::Reject)).with_clock(clock.clone());
+        let dock = GeoPoint::new(41.8790, -87.6298);
+        
+        service.clock_in(TEST_USER_ID, Some(dock), Some("kiosk-1")).await.unwrap();
+        clock.advance(Duration::hours(4));
+        service.clock_out(TEST_USER_ID, Some(dock), Some("kiosk-2")).await.unwrap();
+        
+        let timesheet = service.get_timesheet(TEST_USER_ID, "2025-01-15", "2025-01-15").await.unwrap();
+        let entry = &timesheet.entries[0];
+        assert_eq!(entry.clock_in_location, Some(dock));
+        assert_eq!(entry.clock_out_location, Some(dock));
+        assert_eq!(entry.clock_in_device.as_deref(), Some("kiosk-1"));
+        assert_eq!(entry.clock_out_device.as_deref(), Some("kiosk-2"));
+        assert!(!entry.flagged_out_of_area);
+    }
+    
+    #[tokio::test]
+    async fn test_off_site_punches_are_rejected() {
+        let service = TimesheetService::new(site_db(OutOfAreaPolicy::Reject)).with_clock(fixed_clock());
+        let downtown = GeoPoint::new(41.8881, -87.6298);
+        
+        assert!(service.clock_in(TEST_USER_ID, Some(downtown), None).await.is_err());
+        assert!(service.clock_in(TEST_USER_ID, None, None).await.is_err());
+        assert!(service.get_active_entry(TEST_USER_ID).await.unwrap().is_none());
+    }
+    
+    #[tokio::test]
+    async fn test_off_site_punches_are_flagged_under_flag_policy() {
+        let clock = fixed_clock();
+        let service = TimesheetService::new(site_db(OutOfAreaPolicy::Flag)).with_clock(clock.clone());
+        let dock = GeoPoint::new(41.8790, -87.6298);
+        let downtown = GeoPoint::new(41.8881, -87.6298);
+        
+        let entry = service.clock_in(TEST_USER_ID, Some(dock), None).await.unwrap();
+        assert!(!entry.flagged_out_of_area);
+        clock.advance(Duration::hours(4));
+        let entry = service.clock_out(TEST_USER_ID, Some(downtown), None).await.unwrap();
+        assert!(entry.flagged_out_of_area);
+        
+        let timesheet = service.get_timesheet(TEST_USER_ID, "2025-01-15", "2025-01-15").await.unwrap();
+        assert!(timesheet.entries[0].flagged_out_of_area);
+        assert_eq!(timesheet.entries[0].clock_out_location, Some(downtown));
+    }
+    
+    #[tokio::test]
+    async fn test_team_summary_totals_each_user() {
+        let clock = fixed_clock();
+        let db = test_db();
+        insert_supervisor(&db);
+        let service = TimesheetService::new(db)
+            .with_clock(clock.clone())
+            .with_break_policy(BreakPolicy::disabled());
+        
+        service.clock_in(TEST_USER_ID, None, None).await.unwrap();
+        clock.advance(Duration::hours(9));
+        service.clock_out(TEST_USER_ID, None, None).await.unwrap();
+        service.clock_in("u2", None, None).await.unwrap();
+        clock.advance(Duration::hours(4));
+        service.clock_out("u2", None, None).await.unwrap();
+        
+        let summary = service.get_team_summary("2025-01-13", "2025-01-19", None).await.unwrap();
+        let names: Vec<&str> = summary.members.iter().map(|m| m.user_name.as_str()).collect();
+        assert_eq!(names, ["Boss", "Test User"]);
+        assert_eq!(summary.members[0].total_hours, 4.0);
+        assert_eq!(summary.members[1].regular_hours, 8.0);
+        assert_eq!(summary.members[1].overtime_hours, 1.0);
+        assert_eq!(summary.totals, TeamTimesheetTotals {
+            regular_hours: 12.0,
+            overtime_hours: 1.0,
+            total_hours: 13.0,
+            total_breaks_minutes: 0,
+            days_worked: 2,
+        });
+    }
+    
+    #[tokio::test]
+    async fn test_requested_users_without_time_appear_with_zeros() {
+        let clock = fixed_clock();
+        let db = test_db();
+        insert_supervisor(&db);
+        let service = TimesheetService::new(db).with_clock(clock.clone());
+        worked_day(&service, &clock).await;
+        
+        let everyone = service.get_team_summary("2025-01-13", "2025-01-19", None).await.unwrap();
+        assert_eq!(everyone.members.len(), 1);
+        
+        let requested = vec!["u2".to_string(), TEST_USER_ID.to_string()];
+        let summary = service.get_team_summary("2025-01-13", "2025-01-19", Some(requested)).await.unwrap();
+        assert_eq!(summary.members.len(), 2);
+        let boss = &summary.members[0];
+        assert_eq!((boss.user_id.as_str(), boss.total_hours, boss.days_worked), ("u2", 0.0, 0));
+        assert_eq!(boss.status, TimesheetStatus::Draft);
+        assert_eq!(summary.totals.total_hours, summary.members[1].total_hours);
+    }
+}
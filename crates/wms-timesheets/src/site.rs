@@ -0,0 +1,148 @@
+//! Clock Site Settings
+//!
+//! Clocking in and out is only meant to happen on site. With a site
+//! configured, each punch is checked against a circle around it; a punch
+//! from outside, or one that didn't send a location, is either rejected or
+//! recorded and flagged for review. Without a site punches are accepted
+//! from anywhere.
+
+use serde::{Deserialize, Serialize};
+use wms_core::db::Database;
+use wms_core::error::{WmsError, Result};
+use wms_core::types::GeoPoint;
+
+/// Radius around the site when none is configured
+pub const DEFAULT_SITE_RADIUS_METERS: f64 = 200.0;
+
+/// What happens to a punch from outside the site
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum OutOfAreaPolicy {
+    #[default]
+    Reject,
+    /// Record it with `flagged_out_of_area` set
+    Flag,
+}
+
+impl OutOfAreaPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Reject => "reject",
+            Self::Flag => "flag",
+        }
+    }
+
+    /// Parse the stored snake_case policy
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "reject" => Some(Self::Reject),
+            "flag" => Some(Self::Flag),
+            _ => None,
+        }
+    }
+}
+
+/// Where clock punches are accepted from
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct ClockSiteSettings {
+    /// Center of the site; None accepts punches from anywhere
+    pub site: Option<GeoPoint>,
+    pub radius_meters: f64,
+    pub out_of_area: OutOfAreaPolicy,
+}
+
+impl Default for ClockSiteSettings {
+    fn default() -> Self {
+        Self {
+            site: None,
+            radius_meters: DEFAULT_SITE_RADIUS_METERS,
+            out_of_area: OutOfAreaPolicy::default(),
+        }
+    }
+}
+
+impl ClockSiteSettings {
+    const LAT_KEY: &'static str = "clock_site_lat";
+    const LNG_KEY: &'static str = "clock_site_lng";
+    const RADIUS_KEY: &'static str = "clock_site_radius_meters";
+    const POLICY_KEY: &'static str = "clock_out_of_area_policy";
+
+    /// Load from the settings table, falling back to the defaults
+    pub fn load(db: &Database) -> Result<Self> {
+        let number = |key| -> Result<Option<f64>> {
+            Ok(db.get_setting(key)?
+                .and_then(|value| value.parse::<f64>().ok())
+                .filter(|n| n.is_finite()))
+        };
+        let defaults = Self::default();
+        Ok(Self {
+            site: match (number(Self::LAT_KEY)?, number(Self::LNG_KEY)?) {
+                (Some(lat), Some(lng)) => Some(GeoPoint::new(lat, lng)),
+                _ => None,
+            },
+            radius_meters: number(Self::RADIUS_KEY)?
+                .filter(|r| *r > 0.0)
+                .unwrap_or(defaults.radius_meters),
+            out_of_area: db.get_setting(Self::POLICY_KEY)?
+                .and_then(|value| OutOfAreaPolicy::parse(&value))
+                .unwrap_or(defaults.out_of_area),
+        })
+    }
+
+    /// Store in the settings table
+    pub fn save(&self, db: &Database) -> Result<()> {
+        if !self.radius_meters.is_finite() || self.radius_meters <= 0.0 {
+            return Err(WmsError::validation("Site radius must be more than zero"));
+        }
+        if self.site.is_some_and(|p| !(-90.0..=90.0).contains(&p.lat) || !(-180.0..=180.0).contains(&p.lng)) {
+            return Err(WmsError::validation("Site coordinates are out of range"));
+        }
+        db.set_setting(Self::LAT_KEY, &self.site.map(|p| p.lat.to_string()).unwrap_or_default())?;
+        db.set_setting(Self::LNG_KEY, &self.site.map(|p| p.lng.to_string()).unwrap_or_default())?;
+        db.set_setting(Self::RADIUS_KEY, &self.radius_meters.to_string())?;
+        db.set_setting(Self::POLICY_KEY, self.out_of_area.as_str())
+    }
+
+    /// Whether a punch from `location` is on site. A punch without a
+    /// location is only on site when no site is configured.
+    pub fn is_on_site(&self, location: Option<GeoPoint>) -> bool {
+        match (self.site, location) {
+            (None, _) => true,
+            (Some(site), Some(location)) => site.distance_to(&location) * 1000.0 <= self.radius_meters,
+            (Some(_), None) => false,
+        }
+    }
+
+    /// Check a punch, returning whether to record it as flagged
+    pub fn check(&self, location: Option<GeoPoint>) -> Result<bool> {
+        if self.is_on_site(location) {
+            return Ok(false);
+        }
+        match self.out_of_area {
+            OutOfAreaPolicy::Reject if location.is_none() => {
+                Err(WmsError::validation("Location is required to clock in or out"))
+            }
+            OutOfAreaPolicy::Reject => Err(WmsError::validation("You can only clock in or out on site")),
+            OutOfAreaPolicy::Flag => Ok(true),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_punches_are_checked_against_the_site_radius() {
+        let settings = ClockSiteSettings {
+            site: Some(GeoPoint::new(41.8781, -87.6298)),
+            radius_meters: 150.0,
+            out_of_area: OutOfAreaPolicy::Reject,
+        };
+        // About 100 m north, and about 1.1 km north
+        assert!(settings.is_on_site(Some(GeoPoint::new(41.8790, -87.6298))));
+        assert!(!settings.is_on_site(Some(GeoPoint::new(41.8881, -87.6298))));
+        assert!(!settings.is_on_site(None));
+        assert!(ClockSiteSettings::default().is_on_site(None));
+    }
+}
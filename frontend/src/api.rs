@@ -4,26 +4,113 @@
 
 use serde::{Deserialize, Serialize};
 use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::spawn_local;
+use crate::state::{AppState, LiveUpdate, OperationProgress, ToastType};
+use crate::units::MeasurementPreferences;
 
 #[wasm_bindgen]
 extern "C" {
-    #[wasm_bindgen(js_namespace = ["window", "__TAURI__", "core"])]
-    async fn invoke(cmd: &str, args: JsValue) -> JsValue;
+    #[wasm_bindgen(js_namespace = ["window", "__TAURI__", "core"], catch)]
+    async fn invoke(cmd: &str, args: JsValue) -> Result<JsValue, JsValue>;
+}
+
+/// Stable error codes of failed commands (mirrors
+/// `wms_core::error::ErrorCode`)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    Database,
+    /// Another writer has the database; worth retrying
+    DatabaseBusy,
+    Serialization,
+    Validation,
+    NotFound,
+    Conflict,
+    /// `details.current` is the record as it is now
+    VersionConflict,
+    /// `details` has the limit, exposure and requested values
+    CreditLimitExceeded,
+    /// `details.allowed` lists the statuses the record can move to
+    InvalidTransition,
+    LockError,
+    SyncError,
+    Migration,
+    Io,
+    Network,
+    Auth,
+    PermissionDenied,
+    Barcode,
+    RouteOptimization,
+    Forecast,
+    Export,
+    Cancelled,
+    /// Anything this build doesn't know, including failures to reach the
+    /// backend at all
+    #[serde(other)]
+    Unknown,
+}
+
+/// Why a command failed
+#[derive(Clone, Debug, Deserialize)]
+pub struct ApiError {
+    pub code: ErrorCode,
+    pub message: String,
+    #[serde(default)]
+    pub details: Option<serde_json::Value>,
+}
+
+impl ApiError {
+    fn unknown(message: String) -> Self {
+        Self { code: ErrorCode::Unknown, message, details: None }
+    }
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
 }
 
 /// Invoke a Tauri command
-pub async fn tauri_invoke<T, R>(cmd: &str, args: &T) -> Result<R, String>
+pub async fn tauri_invoke<T, R>(cmd: &str, args: &T) -> Result<R, ApiError>
 where
     T: Serialize,
     R: for<'de> Deserialize<'de>,
 {
     let args_js = serde_wasm_bindgen::to_value(args)
-        .map_err(|e| format!("Serialization error: {}", e))?;
-    
-    let result = invoke(cmd, args_js).await;
+        .map_err(|e| ApiError::unknown(format!("Serialization error: {}", e)))?;
     
-    serde_wasm_bindgen::from_value(result)
-        .map_err(|e| format!("Deserialization error: {}", e))
+    match invoke(cmd, args_js).await {
+        Ok(result) => serde_wasm_bindgen::from_value(result)
+            .map_err(|e| ApiError::unknown(format!("Deserialization error: {}", e))),
+        Err(error) => Err(serde_wasm_bindgen::from_value(error.clone())
+            .unwrap_or_else(|_| ApiError::unknown(error.as_string().unwrap_or_else(|| format!("{:?}", error))))),
+    }
+}
+
+// ============ Record Versions ============
+
+/// Why saving an edited record failed
+#[derive(Clone, Debug)]
+pub enum SaveError<T> {
+    /// Someone else saved first; `current` is the record as it is now.
+    /// Offer to reload it, or overwrite by saving again with `current`'s version.
+    Conflict { message: String, current: T },
+    Failed(ApiError),
+}
+
+impl<T: for<'de> Deserialize<'de>> SaveError<T> {
+    fn from_error(error: ApiError) -> Self {
+        let current = match (error.code, &error.details) {
+            (ErrorCode::VersionConflict, Some(details)) => details.get("current")
+                .and_then(|current| T::deserialize(current).ok()),
+            _ => None,
+        };
+        match current {
+            Some(current) => Self::Conflict { message: error.message, current },
+            None => Self::Failed(error),
+        }
+    }
 }
 
 // ============ Inventory API ============
@@ -42,15 +129,769 @@ pub struct InventoryItem {
     pub description: Option<String>,
     pub category: Option<String>,
     pub total_quantity: Option<f64>,
+    #[serde(default)]
+    pub version: i64,
 }
 
-pub async fn get_all_items(page: u32, page_size: u32) -> Result<Vec<InventoryItem>, String> {
+pub async fn get_all_items(page: u32, page_size: u32) -> Result<Vec<InventoryItem>, ApiError> {
     tauri_invoke("get_all_items", &GetItemsArgs {
         page: Some(page),
         page_size: Some(page_size),
     }).await
 }
 
+#[derive(Serialize)]
+struct UpdateItemArgs<'a, T> {
+    item: &'a T,
+}
+
+/// Save an edited item. Pass the full record as loaded, including its
+/// `version`; a conflict means it was changed since.
+pub async fn update_item<T>(item: &T) -> Result<T, SaveError<T>>
+where
+    T: Serialize + for<'de> Deserialize<'de>,
+{
+    tauri_invoke("update_item", &UpdateItemArgs { item }).await
+        .map_err(SaveError::from_error)
+}
+
+#[derive(Serialize)]
+pub struct SkuArgs {
+    pub sku: String,
+}
+
+pub async fn get_item_by_sku(sku: &str) -> Result<Option<InventoryItem>, ApiError> {
+    tauri_invoke("get_item_by_sku", &SkuArgs { sku: sku.to_string() }).await
+}
+
+#[derive(Serialize)]
+struct ItemLabelArgs<'a> {
+    sku: &'a str,
+    /// "code128", "code39" or "ean13"; None uses the item's barcode type
+    format: Option<&'a str>,
+}
+
+/// Printable item label as a base64 PNG
+pub async fn generate_item_label(sku: &str, format: Option<&str>) -> Result<String, ApiError> {
+    tauri_invoke("generate_item_label", &ItemLabelArgs { sku, format }).await
+}
+
+#[derive(Serialize)]
+pub struct AvailabilityArgs {
+    pub item_id: String,
+    pub horizon_days: Option<u32>,
+}
+
+#[derive(Deserialize)]
+pub struct AvailabilityProjection {
+    pub item_id: String,
+    pub on_hand: f64,
+    pub reserved: f64,
+    pub days: Vec<DailyAvailability>,
+}
+
+#[derive(Deserialize)]
+pub struct DailyAvailability {
+    pub date: String,
+    pub supply: f64,
+    pub demand: f64,
+    pub available: f64,
+}
+
+pub async fn get_available_to_promise(item_id: &str, horizon_days: u32) -> Result<AvailabilityProjection, ApiError> {
+    tauri_invoke("get_available_to_promise", &AvailabilityArgs {
+        item_id: item_id.to_string(),
+        horizon_days: Some(horizon_days),
+    }).await
+}
+
+/// Formats `export_inventory` accepts, with their labels
+pub const INVENTORY_EXPORT_FORMATS: [(&str, &str); 2] = [
+    ("csv", "CSV"),
+    ("xlsx", "Excel"),
+];
+
+/// The Inventory page's filters, to export what it shows
+#[derive(Serialize, Default)]
+pub struct InventoryExportFilter {
+    pub query: Option<String>,
+    pub category: Option<String>,
+    /// "in_stock", "low_stock" or "out_of_stock"
+    pub status: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ExportInventoryArgs {
+    format: String,
+    filter: InventoryExportFilter,
+}
+
+pub async fn export_inventory(format: &str, filter: InventoryExportFilter) -> Result<FileExport, ApiError> {
+    tauri_invoke("export_inventory", &ExportInventoryArgs {
+        format: format.to_string(),
+        filter,
+    }).await
+}
+
+#[derive(Serialize)]
+struct ImportItemsArgs {
+    data: String,
+    options: ImportOptions,
+    operation_id: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ImportOptions {
+    update_existing: bool,
+}
+
+/// Why a row of an item import was skipped
+#[derive(Clone, Deserialize)]
+pub struct RowError {
+    pub line: usize,
+    pub field: Option<String>,
+    pub message: String,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct ImportReport {
+    pub created: usize,
+    pub updated: usize,
+    pub skipped: usize,
+    pub errors: Vec<RowError>,
+}
+
+/// Import items from a base64-encoded CSV, cancellable through
+/// `operation`. Batches written before a cancel are kept.
+pub async fn import_items_csv(
+    data: String,
+    update_existing: bool,
+    operation: &Operation,
+) -> Result<ImportReport, ApiError> {
+    tauri_invoke("import_items_csv", &ImportItemsArgs {
+        data,
+        options: ImportOptions { update_existing },
+        operation_id: Some(operation.id().to_string()),
+    }).await
+}
+
+#[derive(Serialize)]
+pub struct GetPriceArgs {
+    pub item_id: String,
+    pub customer_id: Option<String>,
+    pub date: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ItemPrice {
+    pub item_id: String,
+    pub price_list_id: String,
+    pub unit_price: f64,
+    pub currency_code: String,
+    pub is_customer_price: bool,
+}
+
+pub async fn get_price(item_id: &str, customer_id: Option<&str>) -> Result<Option<ItemPrice>, ApiError> {
+    tauri_invoke("get_price", &GetPriceArgs {
+        item_id: item_id.to_string(),
+        customer_id: customer_id.map(String::from),
+        date: None,
+    }).await
+}
+
+#[derive(Serialize)]
+pub struct ItemIdArgs {
+    pub item_id: String,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct ItemImage {
+    pub id: String,
+    pub filename: String,
+    pub content_type: String,
+    /// Base64 JPEG thumbnail
+    pub thumbnail: Option<String>,
+    pub is_primary: bool,
+}
+
+pub async fn get_item_images(item_id: &str) -> Result<Vec<ItemImage>, ApiError> {
+    tauri_invoke("get_item_images", &ItemIdArgs { item_id: item_id.to_string() }).await
+}
+
+#[derive(Serialize)]
+pub struct RunForecastArgs {
+    pub sku: String,
+    pub days_ahead: u32,
+    /// "ETS", "SMA", "WMA", "NAIVE", "CROSTON", "SBA" or "HOLT_WINTERS";
+    /// None picks one to suit the item's history
+    pub model: Option<String>,
+    pub operation_id: Option<String>,
+}
+
+/// Demand forecast for an item
+#[derive(Clone, Deserialize)]
+pub struct ForecastResult {
+    pub predictions: Vec<f64>,
+    pub lower_bound: Vec<f64>,
+    pub upper_bound: Vec<f64>,
+    pub suggested_reorder_point: f64,
+    pub suggested_reorder_quantity: f64,
+    pub model_type: String,
+    /// Periods averaged, for moving-average models
+    #[serde(default)]
+    pub window: Option<usize>,
+    /// Weight of each period in the window, oldest first
+    #[serde(default)]
+    pub weights: Option<Vec<f64>>,
+}
+
+/// Run a forecast, cancellable through `operation`
+pub async fn run_forecast(
+    sku: &str,
+    days_ahead: u32,
+    model: Option<&str>,
+    operation: &Operation,
+) -> Result<ForecastResult, ApiError> {
+    tauri_invoke("run_forecast", &RunForecastArgs {
+        sku: sku.to_string(),
+        days_ahead,
+        model: model.map(String::from),
+        operation_id: Some(operation.id().to_string()),
+    }).await
+}
+
+#[derive(Serialize)]
+pub struct ForecastAccuracyArgs {
+    pub sku: Option<String>,
+}
+
+/// Rolling accuracy of one forecast model
+#[derive(Clone, Deserialize)]
+pub struct ModelAccuracy {
+    pub model_type: String,
+    pub samples: u32,
+    /// Mean absolute percentage error
+    pub mape: Option<f64>,
+    /// Positive when the model over-forecasts
+    pub bias: f64,
+    pub mae: f64,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct ForecastAccuracy {
+    pub window_days: u32,
+    /// Most accurate first; empty until enough actual demand is in
+    pub models: Vec<ModelAccuracy>,
+    pub best_model: Option<String>,
+}
+
+pub async fn get_forecast_accuracy(sku: Option<&str>) -> Result<ForecastAccuracy, ApiError> {
+    tauri_invoke("get_forecast_accuracy", &ForecastAccuracyArgs { sku: sku.map(String::from) }).await
+}
+
+#[derive(Serialize)]
+struct PickHeatmapArgs {
+    start_date: String,
+    end_date: String,
+}
+
+/// Picks from all locations in one aisle
+#[derive(Clone, Deserialize)]
+pub struct AislePicks {
+    /// None for locations without an aisle
+    pub aisle: Option<String>,
+    pub locations: u32,
+    pub picks: u32,
+    pub quantity: f64,
+    pub pick_percent: f64,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct PickHeatmap {
+    pub aisles: Vec<AislePicks>,
+    pub total_picks: u32,
+}
+
+/// Picks per aisle between two dates ("YYYY-MM-DD", inclusive)
+pub async fn get_pick_heatmap(start_date: &str, end_date: &str) -> Result<PickHeatmap, ApiError> {
+    tauri_invoke("get_pick_heatmap", &PickHeatmapArgs {
+        start_date: start_date.to_string(),
+        end_date: end_date.to_string(),
+    }).await
+}
+
+#[derive(Serialize)]
+struct PickerStatsArgs {
+    start_date: String,
+    end_date: Option<String>,
+}
+
+/// One picker's totals over a period
+#[derive(Clone, Deserialize)]
+pub struct PickerStats {
+    pub rank: u32,
+    pub user_id: String,
+    pub name: Option<String>,
+    pub lines_picked: u32,
+    pub units_picked: f64,
+    pub picks_per_hour: Option<f64>,
+    pub accuracy_percent: f64,
+    /// Some hours were estimated from pick timestamps (not clocked in)
+    pub hours_estimated: bool,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct PickerLeaderboard {
+    pub pickers: Vec<PickerStats>,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct MyPickerStats {
+    pub stats: Option<PickerStats>,
+    pub pickers: u32,
+    pub clean_streak_days: u32,
+    pub best_clean_streak_days: u32,
+}
+
+/// Picker leaderboard for a day ("YYYY-MM-DD")
+pub async fn get_picker_stats(date: &str) -> Result<PickerLeaderboard, ApiError> {
+    tauri_invoke("get_picker_stats", &PickerStatsArgs { start_date: date.to_string(), end_date: None }).await
+}
+
+/// The signed-in picker's own stats for a day ("YYYY-MM-DD")
+pub async fn get_my_stats(date: &str) -> Result<MyPickerStats, ApiError> {
+    tauri_invoke("get_my_stats", &PickerStatsArgs { start_date: date.to_string(), end_date: None }).await
+}
+
+// ============ Shipping API ============
+
+#[derive(Serialize)]
+struct ShipmentIdArgs {
+    shipment_id: String,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct Shipment {
+    pub id: String,
+    pub shipment_number: String,
+    pub status: String,
+    #[serde(default)]
+    pub tracking_number: Option<String>,
+    #[serde(default)]
+    pub ship_date: Option<String>,
+    #[serde(default)]
+    pub ship_to: ShipTo,
+    #[serde(default)]
+    pub items: Vec<ShipmentLine>,
+    #[serde(default)]
+    pub version: i64,
+}
+
+#[derive(Clone, Default, Deserialize)]
+pub struct ShipTo {
+    pub name: String,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct ShipmentLine {
+    pub item_id: String,
+    pub quantity_ordered: f64,
+}
+
+/// Which shipments `list_shipments` returns; unset fields match everything
+#[derive(Serialize)]
+pub struct ShipmentFilter {
+    /// Stored status, e.g. "picking"
+    pub status: Option<String>,
+    pub customer_id: Option<String>,
+    pub date_from: Option<String>,
+    pub date_to: Option<String>,
+    pub search: Option<String>,
+    pub page: u32,
+    pub page_size: u32,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct ShipmentPage {
+    pub items: Vec<Shipment>,
+    pub total_count: u64,
+    pub total_pages: u32,
+}
+
+#[derive(Serialize)]
+struct ListShipmentsArgs {
+    filter: ShipmentFilter,
+}
+
+/// One page of shipments, newest first
+pub async fn list_shipments(filter: ShipmentFilter) -> Result<ShipmentPage, ApiError> {
+    tauri_invoke("list_shipments", &ListShipmentsArgs { filter }).await
+}
+
+/// One way a shipment breaks its customer's routing guide
+#[derive(Clone, Deserialize)]
+pub struct ComplianceViolation {
+    /// "carrier", "service", "partial" or "appointment"
+    pub rule: String,
+    /// "blocking" or "warning"
+    pub severity: String,
+    pub message: String,
+}
+
+impl ComplianceViolation {
+    pub fn is_blocking(&self) -> bool {
+        self.severity == "blocking"
+    }
+}
+
+#[derive(Clone, Deserialize)]
+pub struct ComplianceReport {
+    pub shipment_id: String,
+    pub has_requirements: bool,
+    pub violations: Vec<ComplianceViolation>,
+    pub label_notes: Option<String>,
+}
+
+pub async fn get_shipment(shipment_id: &str) -> Result<Option<Shipment>, ApiError> {
+    tauri_invoke("get_shipment", &ShipmentIdArgs { shipment_id: shipment_id.to_string() }).await
+}
+
+pub async fn check_shipment_compliance(shipment_id: &str) -> Result<ComplianceReport, ApiError> {
+    tauri_invoke("check_shipment_compliance", &ShipmentIdArgs { shipment_id: shipment_id.to_string() }).await
+}
+
+#[derive(Serialize)]
+pub struct ComplianceOverride {
+    pub reason: String,
+    pub user_id: String,
+}
+
+#[derive(Serialize)]
+struct ConfirmShipmentArgs {
+    shipment_id: String,
+    expected_version: Option<i64>,
+    compliance_override: Option<ComplianceOverride>,
+}
+
+/// Confirm a shipment. Blocking violations need `compliance_override`.
+pub async fn confirm_shipment(
+    shipment: &Shipment,
+    compliance_override: Option<ComplianceOverride>,
+) -> Result<Shipment, ApiError> {
+    tauri_invoke("confirm_shipment", &ConfirmShipmentArgs {
+        shipment_id: shipment.id.clone(),
+        expected_version: Some(shipment.version),
+        compliance_override,
+    }).await
+}
+
+#[derive(Serialize)]
+struct CancelShipmentArgs {
+    shipment_id: String,
+    reason: String,
+}
+
+/// Cancel a shipment that hasn't shipped; what was picked for it goes
+/// back into stock
+pub async fn cancel_shipment(shipment_id: &str, reason: &str) -> Result<Shipment, ApiError> {
+    tauri_invoke("cancel_shipment", &CancelShipmentArgs {
+        shipment_id: shipment_id.to_string(),
+        reason: reason.to_string(),
+    }).await
+}
+
+#[derive(Serialize)]
+struct ImportCarrierInvoiceArgs {
+    carrier_id: String,
+    data: String,
+    operation_id: Option<String>,
+}
+
+/// An invoice row not tied to a shipment yet
+#[derive(Clone, Deserialize)]
+pub struct UnmatchedInvoiceLine {
+    pub line_number: usize,
+    pub tracking_number: String,
+    pub billed_total: f64,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct CarrierInvoiceImport {
+    pub carrier_id: String,
+    pub imported: usize,
+    pub updated: usize,
+    pub matched: usize,
+    pub unmatched: Vec<UnmatchedInvoiceLine>,
+}
+
+/// Import a base64-encoded carrier invoice, cancellable through
+/// `operation`. A cancelled import stores nothing.
+pub async fn import_carrier_invoice(
+    carrier_id: &str,
+    data: String,
+    operation: &Operation,
+) -> Result<CarrierInvoiceImport, ApiError> {
+    tauri_invoke("import_carrier_invoice", &ImportCarrierInvoiceArgs {
+        carrier_id: carrier_id.to_string(),
+        data,
+        operation_id: Some(operation.id().to_string()),
+    }).await
+}
+
+// ============ Receiving API ============
+
+#[derive(Clone, Deserialize)]
+pub struct Receipt {
+    pub id: String,
+    pub receipt_number: String,
+    /// "pending", "receiving", "completed" or "cancelled"
+    pub status: String,
+    #[serde(default)]
+    pub po_number: Option<String>,
+    #[serde(default)]
+    pub supplier_name: Option<String>,
+    #[serde(default)]
+    pub expected_date: Option<String>,
+}
+
+/// Which receipts `list_receipts` returns; unset fields match everything
+#[derive(Serialize)]
+pub struct ReceiptFilter {
+    pub status: Option<String>,
+    pub supplier: Option<String>,
+    pub expected_from: Option<String>,
+    pub expected_to: Option<String>,
+    pub page: u32,
+    pub page_size: u32,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct ReceiptPage {
+    pub items: Vec<Receipt>,
+    pub total_count: u64,
+    pub total_pages: u32,
+}
+
+#[derive(Clone, Default, Deserialize)]
+pub struct ReceivingSummary {
+    pub pending: u64,
+    pub receiving: u64,
+    pub completed: u64,
+    pub overdue: u64,
+}
+
+#[derive(Serialize)]
+struct ListReceiptsArgs {
+    filter: ReceiptFilter,
+}
+
+/// One page of receipts, latest expected first
+pub async fn list_receipts(filter: ReceiptFilter) -> Result<ReceiptPage, ApiError> {
+    tauri_invoke("list_receipts", &ListReceiptsArgs { filter }).await
+}
+
+/// Receipts expected and completed today, in progress, and overdue
+pub async fn get_receiving_summary() -> Result<ReceivingSummary, ApiError> {
+    tauri_invoke("get_receiving_summary", &()).await
+}
+
+// ============ Deliveries API ============
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct GeoPoint {
+    pub lat: f64,
+    pub lng: f64,
+}
+
+#[derive(Serialize)]
+struct OptimizeRouteArgs {
+    delivery_ids: Vec<String>,
+    start_location: GeoPoint,
+    operation_id: Option<String>,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct OptimizedRoute {
+    pub stop_order: Vec<String>,
+    pub waypoints: Vec<GeoPoint>,
+    pub total_distance_km: f64,
+    pub estimated_duration_minutes: u32,
+    /// Minutes from the start to each stop
+    pub arrival_times: Vec<u32>,
+    pub optimization_score: f64,
+    /// Stops reached after their delivery window closes
+    #[serde(default)]
+    pub window_violations: Vec<WindowViolation>,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct WindowViolation {
+    pub delivery_id: String,
+    pub arrival_minutes: u32,
+    pub latest_minutes: u32,
+    pub minutes_late: u32,
+}
+
+/// Order deliveries into a route, cancellable through `operation`
+pub async fn optimize_route(
+    delivery_ids: Vec<String>,
+    start_location: GeoPoint,
+    operation: &Operation,
+) -> Result<OptimizedRoute, ApiError> {
+    tauri_invoke("optimize_route", &OptimizeRouteArgs {
+        delivery_ids,
+        start_location,
+        operation_id: Some(operation.id().to_string()),
+    }).await
+}
+
+// ============ CRM API ============
+
+#[derive(Serialize)]
+struct UpdateCustomerArgs<'a, T> {
+    customer: &'a T,
+}
+
+/// Save an edited customer. Pass the full record as loaded, including its
+/// `version`; a conflict means it was changed since.
+pub async fn update_customer<T>(customer: &T) -> Result<T, SaveError<T>>
+where
+    T: Serialize + for<'de> Deserialize<'de>,
+{
+    tauri_invoke("update_customer", &UpdateCustomerArgs { customer }).await
+        .map_err(SaveError::from_error)
+}
+
+/// Customer search filters (mirrors `wms_crm::CustomerSearchQuery`)
+#[derive(Clone, Serialize)]
+pub struct CustomerSearchQuery {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query: Option<String>,
+    /// "retail", "wholesale", "distributor", "government" or "internal"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub customer_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub city: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_active: Option<bool>,
+    /// YYYY-MM-DD
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_from: Option<String>,
+    /// YYYY-MM-DD
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_to: Option<String>,
+    /// "name", "customer_number", "email" or "created_at"
+    pub sort_by: String,
+    /// "asc" or "desc"
+    pub sort_dir: String,
+    pub page: u32,
+    pub page_size: u32,
+}
+
+impl Default for CustomerSearchQuery {
+    fn default() -> Self {
+        Self {
+            query: None,
+            customer_type: None,
+            city: None,
+            state: None,
+            tags: None,
+            is_active: Some(true),
+            created_from: None,
+            created_to: None,
+            sort_by: "name".to_string(),
+            sort_dir: "asc".to_string(),
+            page: 1,
+            page_size: 50,
+        }
+    }
+}
+
+/// One page of search results
+#[derive(Clone, Deserialize)]
+pub struct SearchResult<T> {
+    pub items: Vec<T>,
+    /// Matches across all pages
+    pub total_count: u64,
+    pub page: u32,
+    pub page_size: u32,
+}
+
+impl<T> SearchResult<T> {
+    pub fn total_pages(&self) -> u32 {
+        if self.page_size == 0 {
+            return 0;
+        }
+        self.total_count.div_ceil(u64::from(self.page_size)) as u32
+    }
+}
+
+#[derive(Serialize)]
+struct SearchCustomersArgs<'a> {
+    query: &'a CustomerSearchQuery,
+}
+
+/// Search customers; `T` is the customer shape the caller needs
+pub async fn search_customers<T>(query: &CustomerSearchQuery) -> Result<SearchResult<T>, ApiError>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    tauri_invoke("search_customers", &SearchCustomersArgs { query }).await
+}
+
+#[derive(Serialize)]
+pub struct StatementArgs {
+    pub customer_id: String,
+    pub start_date: String,
+    pub end_date: String,
+    pub format: Option<String>,
+}
+
+/// A generated file, such as a statement or timesheet export
+#[derive(Deserialize)]
+pub struct FileExport {
+    /// Base64 encoded file data
+    pub data: String,
+    pub content_type: String,
+    pub filename: String,
+}
+
+impl FileExport {
+    /// Hand the file to the browser as a download
+    pub fn save(&self) {
+        let Some(document) = web_sys::window().and_then(|w| w.document()) else {
+            return;
+        };
+        let Some(link) = document.create_element("a").ok()
+            .and_then(|el| el.dyn_into::<web_sys::HtmlAnchorElement>().ok())
+        else {
+            return;
+        };
+
+        link.set_href(&format!("data:{};base64,{}", self.content_type, self.data));
+        link.set_download(&self.filename);
+        link.click();
+    }
+}
+
+pub async fn generate_customer_statement(
+    customer_id: &str,
+    start_date: &str,
+    end_date: &str,
+    format: &str,
+) -> Result<FileExport, ApiError> {
+    tauri_invoke("generate_customer_statement", &StatementArgs {
+        customer_id: customer_id.to_string(),
+        start_date: start_date.to_string(),
+        end_date: end_date.to_string(),
+        format: Some(format.to_string()),
+    }).await
+}
+
 // ============ Sync API ============
 
 #[derive(Deserialize)]
@@ -58,17 +899,43 @@ pub struct SyncStatus {
     pub is_syncing: bool,
     pub last_sync_at: Option<String>,
     pub pending_changes: u64,
+    /// Pending changes the server keeps rejecting; no longer sent
+    #[serde(default)]
+    pub stuck_changes: u64,
     pub sync_errors: u64,
+    /// "online", "slow", "offline" or "unknown"
+    #[serde(default)]
+    pub connection_status: String,
 }
 
-pub async fn get_sync_status() -> Result<SyncStatus, String> {
+pub async fn get_sync_status() -> Result<SyncStatus, ApiError> {
     tauri_invoke("get_sync_status", &()).await
 }
 
-pub async fn sync_now() -> Result<SyncStatus, String> {
+pub async fn sync_now() -> Result<SyncStatus, ApiError> {
     tauri_invoke("sync_now", &()).await
 }
 
+/// Background sync schedule
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SyncSettings {
+    /// Minutes between background syncs; None syncs only on request
+    pub interval_minutes: Option<u32>,
+}
+
+#[derive(Serialize)]
+struct SetSyncIntervalArgs {
+    minutes: Option<u32>,
+}
+
+pub async fn get_sync_settings() -> Result<SyncSettings, ApiError> {
+    tauri_invoke("get_sync_settings", &()).await
+}
+
+pub async fn set_sync_interval(minutes: Option<u32>) -> Result<SyncSettings, ApiError> {
+    tauri_invoke("set_sync_interval", &SetSyncIntervalArgs { minutes }).await
+}
+
 // ============ Barcode API ============
 
 #[derive(Serialize)]
@@ -84,7 +951,7 @@ pub struct BarcodeResult {
     pub format: String,
 }
 
-pub async fn scan_barcode(image_data: Vec<u8>, width: u32, height: u32) -> Result<BarcodeResult, String> {
+pub async fn scan_barcode(image_data: Vec<u8>, width: u32, height: u32) -> Result<BarcodeResult, ApiError> {
     tauri_invoke("scan_barcode", &ScanBarcodeArgs {
         image_data,
         width,
@@ -98,6 +965,8 @@ pub async fn scan_barcode(image_data: Vec<u8>, width: u32, height: u32) -> Resul
 pub struct ClockArgs {
     pub user_id: String,
     pub biometric_verified: bool,
+    pub location: Option<GeoPoint>,
+    pub device_id: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -108,17 +977,576 @@ pub struct TimeEntry {
     pub total_hours: Option<f64>,
 }
 
-pub async fn clock_in(user_id: &str, biometric_verified: bool) -> Result<TimeEntry, String> {
+pub async fn clock_in(
+    user_id: &str,
+    biometric_verified: bool,
+    location: Option<GeoPoint>,
+    device_id: Option<String>,
+) -> Result<TimeEntry, ApiError> {
     tauri_invoke("clock_in", &ClockArgs {
         user_id: user_id.to_string(),
         biometric_verified,
+        location,
+        device_id,
     }).await
 }
 
-pub async fn clock_out(user_id: &str, biometric_verified: bool) -> Result<TimeEntry, String> {
+pub async fn clock_out(
+    user_id: &str,
+    biometric_verified: bool,
+    location: Option<GeoPoint>,
+    device_id: Option<String>,
+) -> Result<TimeEntry, ApiError> {
     tauri_invoke("clock_out", &ClockArgs {
         user_id: user_id.to_string(),
         biometric_verified,
+        location,
+        device_id,
+    }).await
+}
+
+/// Formats `export_timesheet` accepts, with their labels
+pub const TIMESHEET_EXPORT_FORMATS: [(&str, &str); 3] = [
+    ("xlsx", "Excel"),
+    ("csv", "CSV"),
+    ("pdf", "PDF"),
+];
+
+#[derive(Serialize)]
+pub struct ExportTimesheetArgs {
+    pub user_id: String,
+    pub start_date: String,
+    pub end_date: String,
+    pub format: String,
+}
+
+pub async fn export_timesheet(
+    user_id: &str,
+    start_date: &str,
+    end_date: &str,
+    format: &str,
+) -> Result<FileExport, ApiError> {
+    tauri_invoke("export_timesheet", &ExportTimesheetArgs {
+        user_id: user_id.to_string(),
+        start_date: start_date.to_string(),
+        end_date: end_date.to_string(),
+        format: format.to_string(),
+    }).await
+}
+
+// ============ Backup API ============
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = ["window", "__TAURI__", "dialog"], js_name = open)]
+    async fn dialog_open(options: JsValue) -> JsValue;
+}
+
+#[derive(Serialize)]
+struct DialogOptions {
+    directory: bool,
+    multiple: bool,
+}
+
+/// Ask the user for a directory (None when cancelled)
+pub async fn pick_directory() -> Option<String> {
+    let options = serde_wasm_bindgen::to_value(&DialogOptions { directory: true, multiple: false }).ok()?;
+    dialog_open(options).await.as_string()
+}
+
+/// Ask the user for a file (None when cancelled)
+pub async fn pick_file() -> Option<String> {
+    let options = serde_wasm_bindgen::to_value(&DialogOptions { directory: false, multiple: false }).ok()?;
+    dialog_open(options).await.as_string()
+}
+
+#[derive(Serialize)]
+pub struct CreateBackupArgs {
+    pub directory: String,
+    pub passphrase: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct RestoreBackupArgs {
+    pub path: String,
+    pub passphrase: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct BackupInfo {
+    pub path: String,
+    pub file_name: String,
+    pub size_bytes: u64,
+    pub created_at: String,
+}
+
+pub async fn create_backup(directory: &str, passphrase: Option<String>) -> Result<BackupInfo, ApiError> {
+    tauri_invoke("create_backup", &CreateBackupArgs {
+        directory: directory.to_string(),
+        passphrase,
+    }).await
+}
+
+pub async fn restore_backup(path: &str, passphrase: Option<String>) -> Result<(), ApiError> {
+    tauri_invoke("restore_backup", &RestoreBackupArgs {
+        path: path.to_string(),
+        passphrase,
+    }).await
+}
+
+// ============ Settings API ============
+
+#[derive(Serialize)]
+struct SetMeasurementPreferencesArgs {
+    preferences: MeasurementPreferences,
+}
+
+pub async fn get_measurement_preferences() -> Result<MeasurementPreferences, ApiError> {
+    tauri_invoke("get_measurement_preferences", &()).await
+}
+
+pub async fn set_measurement_preferences(preferences: MeasurementPreferences) -> Result<(), ApiError> {
+    tauri_invoke("set_measurement_preferences", &SetMeasurementPreferencesArgs { preferences }).await
+}
+
+/// A deployment's extra field on items, customers, shipments or deliveries
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CustomFieldDefinition {
+    #[serde(default)]
+    pub id: String,
+    /// inventory_item, customer, shipment or delivery
+    pub entity: String,
+    pub key: String,
+    pub label: String,
+    /// text, number, date or select
+    pub field_type: String,
+    #[serde(default)]
+    pub options: Vec<String>,
+    #[serde(default)]
+    pub required: bool,
+    #[serde(default)]
+    pub show_in_list: bool,
+    #[serde(default)]
+    pub sort_order: i32,
+}
+
+#[derive(Serialize)]
+struct CustomFieldEntityArgs {
+    entity: Option<String>,
+}
+
+#[derive(Serialize)]
+struct SaveCustomFieldArgs<'a> {
+    definition: &'a CustomFieldDefinition,
+}
+
+#[derive(Serialize)]
+struct CustomFieldIdArgs {
+    id: String,
+}
+
+pub async fn get_custom_field_definitions(entity: Option<&str>) -> Result<Vec<CustomFieldDefinition>, ApiError> {
+    tauri_invoke("get_custom_field_definitions", &CustomFieldEntityArgs { entity: entity.map(String::from) }).await
+}
+
+pub async fn save_custom_field_definition(definition: &CustomFieldDefinition) -> Result<CustomFieldDefinition, ApiError> {
+    tauri_invoke("save_custom_field_definition", &SaveCustomFieldArgs { definition }).await
+}
+
+pub async fn delete_custom_field_definition(id: &str) -> Result<(), ApiError> {
+    tauri_invoke("delete_custom_field_definition", &CustomFieldIdArgs { id: id.to_string() }).await
+}
+
+// ============ Session API ============
+
+/// What the signed-in user may use
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Permissions {
+    pub user_id: Option<String>,
+    pub role: Option<String>,
+    pub scopes: Vec<String>,
+}
+
+pub async fn get_my_permissions() -> Result<Permissions, ApiError> {
+    tauri_invoke("get_my_permissions", &()).await
+}
+
+#[derive(Serialize)]
+struct LoginArgs<'a> {
+    username: &'a str,
+    pin: &'a str,
+}
+
+/// Sign in, returning what the user may now use
+pub async fn login(username: &str, pin: &str) -> Result<Permissions, ApiError> {
+    tauri_invoke("login", &LoginArgs { username, pin }).await
+}
+
+pub async fn logout() -> Result<(), ApiError> {
+    tauri_invoke("end_session", &()).await
+}
+
+/// The signed-in user, or None when nobody is
+pub async fn get_current_user() -> Result<Option<crate::state::User>, ApiError> {
+    tauri_invoke("get_current_user", &()).await
+}
+
+/// PIN of the admin account created on first run; only returned once
+pub async fn take_initial_admin_pin() -> Result<Option<String>, ApiError> {
+    tauri_invoke("take_initial_admin_pin", &()).await
+}
+
+// ============ Search API ============
+
+#[derive(Serialize)]
+struct GlobalSearchArgs<'a> {
+    query: &'a str,
+    limit: Option<u32>,
+}
+
+/// A record matching a global search
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct SearchHit {
+    pub id: String,
+    pub title: String,
+    pub subtitle: Option<String>,
+    /// Page to open for the record
+    pub route: String,
+}
+
+/// Global search matches by kind (mirrors `wms_core::search::GlobalSearchResults`)
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+pub struct GlobalSearchResults {
+    pub inventory: Vec<SearchHit>,
+    pub customers: Vec<SearchHit>,
+    pub shipments: Vec<SearchHit>,
+    pub deliveries: Vec<SearchHit>,
+}
+
+impl GlobalSearchResults {
+    /// Non-empty groups with their headings
+    pub fn groups(&self) -> Vec<(&'static str, Vec<SearchHit>)> {
+        [
+            ("Inventory", &self.inventory),
+            ("Customers", &self.customers),
+            ("Shipments", &self.shipments),
+            ("Deliveries", &self.deliveries),
+        ]
+        .into_iter()
+        .filter(|(_, hits)| !hits.is_empty())
+        .map(|(heading, hits)| (heading, hits.clone()))
+        .collect()
+    }
+}
+
+pub async fn global_search(query: &str, limit: Option<u32>) -> Result<GlobalSearchResults, ApiError> {
+    tauri_invoke("global_search", &GlobalSearchArgs { query, limit }).await
+}
+
+// ============ Dashboard API ============
+
+#[derive(Serialize)]
+struct DashboardMetricsArgs {
+    period: String,
+}
+
+#[derive(Clone, Copy, Deserialize)]
+pub struct MetricComparison {
+    pub current: f64,
+    /// None hides the trend chip
+    pub change_percent: Option<f64>,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct DashboardMetrics {
+    pub shipments: MetricComparison,
+    pub deliveries: MetricComparison,
+    pub low_stock_items: MetricComparison,
+    pub inventory_total: MetricComparison,
+    pub active_items: u64,
+    /// Monday first
+    pub shipments_this_week: Vec<ShipmentDay>,
+    pub deliveries_today: DeliveryProgress,
+    pub pending_sync_changes: u64,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct ShipmentDay {
+    /// YYYY-MM-DD
+    pub date: String,
+    pub total: u64,
+    pub by_status: std::collections::BTreeMap<String, u64>,
+}
+
+#[derive(Clone, Copy, Deserialize)]
+pub struct DeliveryProgress {
+    pub completed: u64,
+    pub pending: u64,
+}
+
+/// `period` is "day", "week" or "month"
+pub async fn get_dashboard_metrics(period: &str) -> Result<DashboardMetrics, ApiError> {
+    tauri_invoke("get_dashboard_metrics", &DashboardMetricsArgs { period: period.to_string() }).await
+}
+
+#[derive(Serialize)]
+struct MetricHistoryArgs {
+    metric: String,
+    days: Option<u32>,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct MetricPoint {
+    pub date: String,
+    pub value: f64,
+}
+
+/// Daily values of `metric` ("shipments", "deliveries", "picks",
+/// "low_stock_items" or "inventory_total") over the last `days` days
+pub async fn get_metric_history(metric: &str, days: u32) -> Result<Vec<MetricPoint>, ApiError> {
+    tauri_invoke("get_metric_history", &MetricHistoryArgs {
+        metric: metric.to_string(),
+        days: Some(days),
     }).await
 }
 
+#[derive(Serialize)]
+struct RecentActivityArgs {
+    limit: Option<u32>,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct ActivityEntry {
+    /// "shipment", "receipt", "delivery" or "adjustment"
+    pub kind: String,
+    pub record_id: String,
+    pub title: String,
+    /// RFC 3339
+    pub occurred_at: String,
+}
+
+/// Latest shipment, receipt, delivery and stock adjustment activity,
+/// newest first
+pub async fn recent_activity(limit: Option<u32>) -> Result<Vec<ActivityEntry>, ApiError> {
+    tauri_invoke("recent_activity", &RecentActivityArgs { limit }).await
+}
+
+// ============ Operations API ============
+
+/// Prefix of the error returned by a cancelled or timed-out operation
+/// (mirrors `wms_core::error::WmsError::Cancelled`)
+const CANCELLED_PREFIX: &str = "Cancelled: ";
+
+/// Whether a command failed because its operation was cancelled or ran
+/// past its time limit, rather than because of a real error
+pub fn is_cancelled(error: &str) -> bool {
+    error.starts_with(CANCELLED_PREFIX)
+}
+
+#[derive(Serialize)]
+struct OperationIdArgs {
+    operation_id: String,
+}
+
+/// Handle to a long-running forecast, route optimization or import. Pass
+/// it to the command, then call `cancel` from a Cancel button; progress
+/// shows up in `AppState::operation_progress` under `id`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Operation {
+    id: String,
+}
+
+impl Operation {
+    pub fn new() -> Self {
+        Self { id: uuid::Uuid::new_v4().to_string() }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Ask the backend to stop the operation. False when it had already
+    /// finished.
+    pub async fn cancel(&self) -> Result<bool, ApiError> {
+        tauri_invoke("cancel_operation", &OperationIdArgs { operation_id: self.id.clone() }).await
+    }
+
+    /// Forget the operation's progress once the command has returned
+    pub fn finish(&self, state: &AppState) {
+        state.operation_progress.update(|progress| {
+            progress.remove(&self.id);
+        });
+    }
+}
+
+impl Default for Operation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// ============ Events API ============
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = ["window", "__TAURI__", "event"])]
+    async fn listen(event: &str, handler: &Closure<dyn FnMut(JsValue)>) -> JsValue;
+}
+
+/// Envelope delivered by the Tauri event API
+#[derive(Deserialize)]
+struct EventEnvelope<T> {
+    payload: T,
+}
+
+#[derive(Deserialize)]
+struct ShipmentStatusChanged {
+    shipment_id: String,
+    status: String,
+}
+
+#[derive(Deserialize)]
+struct DeliveryStatusChanged {
+    delivery_id: String,
+    status: String,
+}
+
+#[derive(Deserialize)]
+struct InventoryLowStock {
+    item_id: String,
+    sku: String,
+    quantity: f64,
+}
+
+#[derive(Deserialize)]
+struct SyncCompleted {
+    success: bool,
+    pending_changes: u32,
+}
+
+#[derive(Deserialize)]
+struct TimesheetClockedIn {
+    entry_id: String,
+}
+
+#[derive(Deserialize)]
+struct BackupProgress {
+    completed: u64,
+    total: u64,
+}
+
+#[derive(Deserialize)]
+struct OperationProgressEvent {
+    operation_id: String,
+    kind: String,
+    completed: u64,
+    total: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct ExtractFailed {
+    name: String,
+    error: String,
+}
+
+/// Listen for a backend event for the lifetime of the app
+async fn listen_to<T, F>(event: &str, mut handler: F)
+where
+    T: for<'de> Deserialize<'de> + 'static,
+    F: FnMut(T) + 'static,
+{
+    let closure = Closure::<dyn FnMut(JsValue)>::new(move |raw: JsValue| {
+        if let Ok(envelope) = serde_wasm_bindgen::from_value::<EventEnvelope<T>>(raw) {
+            handler(envelope.payload);
+        }
+    });
+    listen(event, &closure).await;
+    closure.forget();
+}
+
+/// Subscribe to backend events and dispatch them into the app state
+pub fn subscribe_events(state: AppState) {
+    spawn_local(async move {
+        let s = state.clone();
+        listen_to("shipment.status_changed", move |e: ShipmentStatusChanged| {
+            s.live_update.set(Some(LiveUpdate {
+                event: "shipment.status_changed".into(),
+                entity_id: e.shipment_id,
+                status: Some(e.status),
+            }));
+        }).await;
+
+        let s = state.clone();
+        listen_to("delivery.status_changed", move |e: DeliveryStatusChanged| {
+            s.live_update.set(Some(LiveUpdate {
+                event: "delivery.status_changed".into(),
+                entity_id: e.delivery_id,
+                status: Some(e.status),
+            }));
+        }).await;
+
+        let s = state.clone();
+        listen_to("inventory.low_stock", move |e: InventoryLowStock| {
+            s.toast(&format!("Low stock: {} ({} left)", e.sku, e.quantity), ToastType::Warning);
+            s.live_update.set(Some(LiveUpdate {
+                event: "inventory.low_stock".into(),
+                entity_id: e.item_id,
+                status: None,
+            }));
+        }).await;
+
+        let s = state.clone();
+        listen_to("sync.completed", move |e: SyncCompleted| {
+            s.sync_status.update(|status| {
+                status.is_syncing = false;
+                status.pending_changes = e.pending_changes;
+                if e.success {
+                    status.last_sync = Some(chrono::Utc::now().to_rfc3339());
+                }
+            });
+        }).await;
+
+        // Sent after every sync attempt, background or manual
+        let s = state.clone();
+        listen_to("sync://status-changed", move |e: SyncStatus| {
+            s.sync_status.update(|status| {
+                status.is_syncing = e.is_syncing;
+                status.pending_changes = e.pending_changes as u32;
+                status.stuck_changes = e.stuck_changes as u32;
+                status.last_sync = e.last_sync_at;
+                status.is_online = e.connection_status != "offline";
+            });
+        }).await;
+
+        let s = state.clone();
+        listen_to("timesheet.clocked_in", move |e: TimesheetClockedIn| {
+            s.live_update.set(Some(LiveUpdate {
+                event: "timesheet.clocked_in".into(),
+                entity_id: e.entry_id,
+                status: None,
+            }));
+        }).await;
+
+        let s = state.clone();
+        listen_to("backup.progress", move |e: BackupProgress| {
+            let fraction = if e.total == 0 { 1.0 } else { e.completed as f64 / e.total as f64 };
+            s.backup_progress.set((fraction < 1.0).then_some(fraction));
+        }).await;
+
+        let s = state.clone();
+        listen_to("operation.progress", move |e: OperationProgressEvent| {
+            s.operation_progress.update(|progress| {
+                progress.insert(e.operation_id, OperationProgress {
+                    kind: e.kind,
+                    completed: e.completed,
+                    total: e.total,
+                });
+            });
+        }).await;
+
+        let s = state;
+        listen_to("extract.failed", move |e: ExtractFailed| {
+            s.toast(&format!("Extract '{}' failed: {}", e.name, e.error), ToastType::Error);
+        }).await;
+    });
+}
@@ -2,17 +2,44 @@
 
 use leptos::prelude::*;
 use leptos::context::provide_context;
+use leptos::task::spawn_local;
 use leptos_router::components::{Router, Route, Routes};
 use leptos_router::path;
 use crate::components::*;
 use crate::pages::*;
+use crate::api::{get_current_user, get_measurement_preferences, get_my_permissions, subscribe_events};
 use crate::state::AppState;
 
 /// Main application component with routing
 #[component]
 pub fn App() -> impl IntoView {
     // Provide global state
-    provide_context(AppState::new());
+    let state = AppState::new();
+    provide_context(state.clone());
+    
+    // Load display units so measurements render as configured
+    let units = state.measurement_units;
+    spawn_local(async move {
+        if let Ok(preferences) = get_measurement_preferences().await {
+            units.set(preferences);
+        }
+    });
+    
+    // Load who is signed in and what they may see so the sidebar can hide
+    // the rest
+    let scopes = state.scopes;
+    let user = state.user;
+    spawn_local(async move {
+        if let Ok(permissions) = get_my_permissions().await {
+            scopes.set(Some(permissions.scopes));
+        }
+        if let Ok(current) = get_current_user().await {
+            user.set(current);
+        }
+    });
+    
+    // Keep state fresh with live updates from the backend
+    subscribe_events(state);
 
     view! {
         <Router>
@@ -27,6 +54,7 @@ pub fn App() -> impl IntoView {
                             <Route path=path!("/inventory/:id") view=InventoryDetailPage/>
                             <Route path=path!("/shipping") view=ShippingPage/>
                             <Route path=path!("/shipping/new") view=NewShipmentPage/>
+                            <Route path=path!("/shipping/:id") view=ShipmentDetailPage/>
                             <Route path=path!("/receiving") view=ReceivingPage/>
                             <Route path=path!("/deliveries") view=DeliveriesPage/>
                             <Route path=path!("/deliveries/:id") view=DeliveryDetailPage/>
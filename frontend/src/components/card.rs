@@ -1,6 +1,7 @@
 //! Card Component
 
 use leptos::prelude::*;
+use crate::components::Sparkline;
 
 /// Card container component
 #[component]
@@ -28,11 +29,12 @@ pub fn Card(
 pub fn StatCard(
     title: &'static str,
     #[prop(into)] value: Signal<String>,
-    #[prop(optional, into)] trend: Option<f64>,
+    #[prop(optional, into)] trend: MaybeProp<f64>,
     #[prop(optional)] icon: Option<&'static str>,
+    /// Recent daily values drawn as a sparkline under the number
+    #[prop(optional, into)] history: MaybeProp<Vec<f64>>,
 ) -> impl IntoView {
     let icon_str = icon;
-    let trend_val = trend;
 
     view! {
         <div class="stat-card">
@@ -40,7 +42,7 @@ pub fn StatCard(
             <div class="stat-content">
                 <span class="stat-title">{title}</span>
                 <span class="stat-value">{move || value.get()}</span>
-                {trend_val.map(|t| {
+                {move || trend.get().map(|t| {
                     let trend_class = if t >= 0.0 { "positive" } else { "negative" };
                     let trend_icon = if t >= 0.0 { "↑" } else { "↓" };
                     view! {
@@ -49,6 +51,7 @@ pub fn StatCard(
                         </span>
                     }
                 })}
+                {move || history.get().filter(|h| !h.is_empty()).map(|data| view! { <Sparkline data=data /> })}
             </div>
         </div>
     }
@@ -19,8 +19,9 @@ pub fn Chart(
     #[prop(optional)] show_labels: bool,
 ) -> impl IntoView {
     let height = height.unwrap_or(200);
-    let max_value = data.iter().map(|d| d.value).fold(0.0_f64, f64::max);
-    let bar_width = 100.0 / (data.len() as f64 * 1.5);
+    let max_value = data.iter().map(|d| d.value).filter(|v| v.is_finite()).fold(0.0_f64, f64::max);
+    // No bars at all for empty data rather than an infinite width
+    let bar_width = if data.is_empty() { 0.0 } else { 100.0 / (data.len() as f64 * 1.5) };
     
     view! {
         <div class="chart-container">
@@ -30,8 +31,8 @@ pub fn Chart(
                 preserveAspectRatio="none"
             >
                 {data.iter().enumerate().map(|(i, point)| {
-                    let bar_height = if max_value > 0.0 {
-                        (point.value / max_value) * (height as f64 - 20.0)
+                    let bar_height = if max_value > 0.0 && point.value.is_finite() {
+                        (point.value.max(0.0) / max_value) * (height as f64 - 20.0)
                     } else {
                         0.0
                     };
@@ -78,19 +79,7 @@ pub fn Sparkline(
 ) -> impl IntoView {
     let width = 100.0;
     let height = 30.0;
-    let max_val = data.iter().fold(0.0_f64, |a, &b| a.max(b));
-    let min_val = data.iter().fold(f64::MAX, |a, &b| a.min(b));
-    let range = max_val - min_val;
-    
-    let points: String = data.iter().enumerate().map(|(i, &val)| {
-        let x = (i as f64 / (data.len() - 1) as f64) * width;
-        let y = if range > 0.0 {
-            height - ((val - min_val) / range * height)
-        } else {
-            height / 2.0
-        };
-        format!("{:.1},{:.1}", x, y)
-    }).collect::<Vec<_>>().join(" ");
+    let points = sparkline_points(&data, width, height);
     
     view! {
         <svg class="sparkline" viewBox=format!("0 0 {} {}", width, height)>
@@ -104,3 +93,29 @@ pub fn Sparkline(
     }
 }
 
+/// SVG polyline points scaling `data` into `width` x `height`. Empty data
+/// draws nothing, a single value draws a flat line across the middle, and
+/// non-finite values are skipped.
+fn sparkline_points(data: &[f64], width: f64, height: f64) -> String {
+    let values: Vec<f64> = data.iter().copied().filter(|v| v.is_finite()).collect();
+    
+    match values.len() {
+        0 => String::new(),
+        1 => format!("0.0,{:.1} {:.1},{:.1}", height / 2.0, width, height / 2.0),
+        count => {
+            let max_val = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+            let min_val = values.iter().copied().fold(f64::INFINITY, f64::min);
+            let range = max_val - min_val;
+            
+            values.iter().enumerate().map(|(i, &val)| {
+                let x = (i as f64 / (count - 1) as f64) * width;
+                let y = if range > 0.0 {
+                    height - ((val - min_val) / range * height)
+                } else {
+                    height / 2.0
+                };
+                format!("{:.1},{:.1}", x, y)
+            }).collect::<Vec<_>>().join(" ")
+        }
+    }
+}
@@ -0,0 +1,26 @@
+//! Conflict Dialog Component
+
+use leptos::prelude::*;
+use crate::components::Modal;
+
+/// Shown when saving an edit fails because someone else changed the record
+/// first. `message` is the conflict message; the dialog is open while set.
+#[component]
+pub fn ConflictDialog(
+    #[prop(into)] message: Signal<Option<String>>,
+    /// Discard the edit and load the current record
+    #[prop(into)] on_reload: Callback<()>,
+    /// Save the edit over the current record
+    #[prop(into)] on_overwrite: Callback<()>,
+) -> impl IntoView {
+    view! {
+        <Modal open=Signal::derive(move || message.get().is_some()) title="Record changed".to_string()>
+            <p class="conflict-message">{move || message.get().unwrap_or_default()}</p>
+            <p class="subtitle">"Reload to see their changes, or overwrite them with yours."</p>
+            <div class="modal-actions">
+                <button class="btn btn-secondary" on:click=move |_| on_reload.run(())>"Reload"</button>
+                <button class="btn btn-danger" on:click=move |_| on_overwrite.run(())>"Overwrite"</button>
+            </div>
+        </Modal>
+    }
+}
@@ -0,0 +1,110 @@
+//! Custom Field Inputs
+
+use leptos::prelude::*;
+use leptos::ev::Event;
+use serde_json::{Map, Value};
+use wasm_bindgen::JsCast;
+use crate::api::CustomFieldDefinition;
+
+/// Value of the input or select that fired the event
+fn field_value(ev: &Event) -> String {
+    let target = ev.target();
+    target.clone()
+        .and_then(|t| t.dyn_into::<web_sys::HtmlInputElement>().ok())
+        .map(|t| t.value())
+        .or_else(|| target
+            .and_then(|t| t.dyn_into::<web_sys::HtmlSelectElement>().ok())
+            .map(|t| t.value()))
+        .unwrap_or_default()
+}
+
+/// Value of a custom field as the text an input shows
+fn display(value: Option<&Value>) -> String {
+    match value {
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Null) | None => String::new(),
+        Some(other) => other.to_string(),
+    }
+}
+
+/// One input per custom field definition, editing `values` by field key.
+/// Values are sent as entered; the service checks and converts them.
+#[component]
+pub fn CustomFieldInputs(
+    #[prop(into)] definitions: Signal<Vec<CustomFieldDefinition>>,
+    values: RwSignal<Map<String, Value>>,
+) -> impl IntoView {
+    let set_value = move |key: String, value: String| {
+        values.update(|values| {
+            if value.is_empty() {
+                values.remove(&key);
+            } else {
+                values.insert(key, Value::String(value));
+            }
+        });
+    };
+
+    view! {
+        <div class="custom-fields">
+            <For
+                each=move || definitions.get()
+                key=|definition| definition.key.clone()
+                children=move |definition| {
+                    let key = definition.key.clone();
+                    let current = {
+                        let key = key.clone();
+                        move || display(values.get().get(&key))
+                    };
+                    let on_change = move |ev: Event| set_value(key.clone(), field_value(&ev));
+                    let label = if definition.required {
+                        format!("{} *", definition.label)
+                    } else {
+                        definition.label.clone()
+                    };
+
+                    let input = if definition.field_type == "select" {
+                        let options = definition.options.clone();
+                        let selected = current.clone();
+                        view! {
+                            <select class="form-select" on:change=on_change>
+                                <option value="" selected=move || selected().is_empty()>"—"</option>
+                                {options.into_iter().map(|option| {
+                                    let is_selected = {
+                                        let option = option.clone();
+                                        let current = current.clone();
+                                        move || current() == option
+                                    };
+                                    view! {
+                                        <option value=option.clone() selected=is_selected>{option.clone()}</option>
+                                    }
+                                }).collect::<Vec<_>>()}
+                            </select>
+                        }.into_any()
+                    } else {
+                        let input_type = match definition.field_type.as_str() {
+                            "number" => "number",
+                            "date" => "date",
+                            _ => "text",
+                        };
+                        view! {
+                            <input
+                                type=input_type
+                                class="form-input"
+                                required=definition.required
+                                prop:value=current
+                                on:change=on_change
+                            />
+                        }.into_any()
+                    };
+
+                    view! {
+                        <div class="form-group">
+                            <label class="form-label">{label}</label>
+                            {input}
+                        </div>
+                    }
+                }
+            />
+        </div>
+    }
+}
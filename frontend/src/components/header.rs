@@ -2,8 +2,15 @@
 
 use leptos::prelude::*;
 use leptos::context::use_context;
+use leptos::html;
+use leptos::task::spawn_local;
+use leptos_router::hooks::use_navigate;
+use crate::api::{self, GlobalSearchResults};
 use crate::state::AppState;
 
+/// Characters typed before the header search runs
+const MIN_SEARCH_CHARS: usize = 2;
+
 /// Application header
 #[component]
 pub fn Header() -> impl IntoView {
@@ -18,6 +25,37 @@ pub fn Header() -> impl IntoView {
         });
     };
     
+    let query = RwSignal::new(String::new());
+    let results = RwSignal::new(None::<GlobalSearchResults>);
+    let search_input = NodeRef::<html::Input>::new();
+    let navigate = use_navigate();
+
+    let on_search_input = move |ev| {
+        let text = event_target_value(&ev);
+        query.set(text.clone());
+        if text.trim().chars().count() < MIN_SEARCH_CHARS {
+            results.set(None);
+            return;
+        }
+        spawn_local(async move {
+            let found = api::global_search(&text, None).await.unwrap_or_default();
+            // Drop answers to queries the user has typed past
+            if query.get_untracked() == text {
+                results.set(Some(found));
+            }
+        });
+    };
+
+    // ⌘K / Ctrl+K jumps to the search box
+    let _shortcut = window_event_listener(leptos::ev::keydown, move |ev| {
+        if (ev.meta_key() || ev.ctrl_key()) && ev.key().eq_ignore_ascii_case("k") {
+            ev.prevent_default();
+            if let Some(input) = search_input.get() {
+                let _ = input.focus();
+            }
+        }
+    });
+
     view! {
         <header class="app-header">
             <div class="header-left">
@@ -27,8 +65,55 @@ pub fn Header() -> impl IntoView {
                         type="text" 
                         placeholder="Search inventory, shipments, customers..."
                         class="search-input"
+                        node_ref=search_input
+                        prop:value=move || query.get()
+                        on:input=on_search_input
+                        on:keydown=move |ev| {
+                            if ev.key() == "Escape" {
+                                query.set(String::new());
+                                results.set(None);
+                            }
+                        }
                     />
                     <kbd class="search-shortcut">"⌘K"</kbd>
+                    {move || results.get().map(|found| {
+                        let navigate = navigate.clone();
+                        let groups = found.groups();
+                        view! {
+                            <div class="search-results">
+                                {groups.is_empty().then(|| view! {
+                                    <div class="search-empty">"No matches"</div>
+                                })}
+                                {groups.into_iter().map(|(heading, hits)| {
+                                    let navigate = navigate.clone();
+                                    view! {
+                                        <div class="search-group">
+                                            <div class="search-group-heading">{heading}</div>
+                                            {hits.into_iter().map(|hit| {
+                                                let navigate = navigate.clone();
+                                                let route = hit.route.clone();
+                                                view! {
+                                                    <button
+                                                        class="search-hit"
+                                                        on:click=move |_| {
+                                                            query.set(String::new());
+                                                            results.set(None);
+                                                            navigate(&route, Default::default());
+                                                        }
+                                                    >
+                                                        <span class="search-hit-title">{hit.title}</span>
+                                                        {hit.subtitle.map(|subtitle| view! {
+                                                            <span class="search-hit-subtitle">{subtitle}</span>
+                                                        })}
+                                                    </button>
+                                                }
+                                            }).collect_view()}
+                                        </div>
+                                    }
+                                }).collect_view()}
+                            </div>
+                        }
+                    })}
                 </div>
             </div>
             
@@ -46,16 +131,113 @@ pub fn Header() -> impl IntoView {
                     {move || if matches!(state.theme.get(), crate::state::Theme::Dark) { "☀️" } else { "🌙" }}
                 </button>
                 
-                <div class="user-menu">
-                    <button class="user-btn">
-                        <span class="user-avatar">"👤"</span>
-                        <span class="user-name">
-                            {move || state.user.get().map(|u| u.full_name).unwrap_or_else(|| "Guest".to_string())}
-                        </span>
-                    </button>
-                </div>
+                <UserMenu/>
             </div>
         </header>
     }
 }
 
+/// Signed-in user, with sign in and sign out
+#[component]
+fn UserMenu() -> impl IntoView {
+    let state = use_context::<AppState>().expect("AppState not found");
+    let open = RwSignal::new(false);
+    let username = RwSignal::new(String::new());
+    let pin = RwSignal::new(String::new());
+    let error = RwSignal::new(None::<String>);
+    let initial_pin = RwSignal::new(None::<String>);
+
+    // First run: show the seeded admin's PIN, which the backend hands out once
+    spawn_local(async move {
+        if let Ok(Some(pin)) = api::take_initial_admin_pin().await {
+            username.set("admin".to_string());
+            initial_pin.set(Some(pin));
+            open.set(true);
+        }
+    });
+
+    // Reload who is signed in and what they may see
+    let refresh = move || spawn_local(async move {
+        if let Ok(permissions) = api::get_my_permissions().await {
+            state.scopes.set(Some(permissions.scopes));
+        }
+        if let Ok(user) = api::get_current_user().await {
+            state.user.set(user);
+        }
+        if let Ok(preferences) = api::get_measurement_preferences().await {
+            state.measurement_units.set(preferences);
+        }
+    });
+
+    let sign_in = move |ev: leptos::ev::SubmitEvent| {
+        ev.prevent_default();
+        spawn_local(async move {
+            match api::login(&username.get_untracked(), &pin.get_untracked()).await {
+                Ok(_) => {
+                    pin.set(String::new());
+                    initial_pin.set(None);
+                    error.set(None);
+                    open.set(false);
+                    refresh();
+                }
+                Err(e) => error.set(Some(e.to_string())),
+            }
+        });
+    };
+    let sign_out = move |_| {
+        spawn_local(async move {
+            if api::logout().await.is_ok() {
+                open.set(false);
+                refresh();
+            }
+        });
+    };
+
+    view! {
+        <div class="user-menu">
+            <button class="user-btn" on:click=move |_| open.update(|o| *o = !*o)>
+                <span class="user-avatar">"👤"</span>
+                <span class="user-name">
+                    {move || state.user.get().map(|u| u.full_name).unwrap_or_else(|| "Guest".to_string())}
+                </span>
+            </button>
+            <Show when=move || open.get()>
+                <div class="user-dropdown">
+                    {move || match state.user.get() {
+                        Some(user) => view! {
+                            <p class="subtitle">{format!("{} · {}", user.username, user.role)}</p>
+                            <button class="btn btn-secondary btn-sm" on:click=sign_out>"Sign out"</button>
+                        }.into_any(),
+                        None => view! {
+                            {move || initial_pin.get().map(|pin| view! {
+                                <p class="subtitle">
+                                    {format!("First run: sign in as admin with PIN {} and change it", pin)}
+                                </p>
+                            })}
+                            <form on:submit=sign_in>
+                                <input
+                                    class="form-input"
+                                    placeholder="Username"
+                                    autocomplete="username"
+                                    prop:value=move || username.get()
+                                    on:input=move |ev| username.set(event_target_value(&ev))
+                                />
+                                <input
+                                    class="form-input"
+                                    type="password"
+                                    inputmode="numeric"
+                                    placeholder="PIN"
+                                    autocomplete="current-password"
+                                    prop:value=move || pin.get()
+                                    on:input=move |ev| pin.set(event_target_value(&ev))
+                                />
+                                {move || error.get().map(|e| view! { <span class="form-error">{e}</span> })}
+                                <button type="submit" class="btn btn-primary btn-sm">"Sign in"</button>
+                            </form>
+                        }.into_any(),
+                    }}
+                </div>
+            </Show>
+        </div>
+    }
+}
@@ -80,8 +80,9 @@ pub fn Select(
 #[component]
 pub fn SearchInput(
     #[prop(optional)] placeholder: Option<&'static str>,
+    #[prop(optional)] value: Option<RwSignal<String>>,
 ) -> impl IntoView {
-    let query = RwSignal::new(String::new());
+    let query = value.unwrap_or_else(|| RwSignal::new(String::new()));
 
     view! {
         <div class="search-input-wrapper">
@@ -7,10 +7,13 @@ mod card;
 mod button;
 mod input;
 mod modal;
+mod conflict_dialog;
 mod toast;
 mod loading;
+mod operation_progress;
 mod badge;
 mod chart;
+mod custom_fields;
 
 pub use sidebar::Sidebar;
 pub use header::Header;
@@ -19,8 +22,11 @@ pub use card::{Card, StatCard};
 pub use button::*;
 pub use input::*;
 pub use modal::Modal;
+pub use conflict_dialog::ConflictDialog;
 pub use toast::ToastContainer;
 pub use loading::Loading;
-pub use badge::Badge;
+pub use operation_progress::OperationProgressBar;
+pub use badge::{Badge, BadgeVariant};
 pub use chart::{Chart, DataPoint, Sparkline};
+pub use custom_fields::CustomFieldInputs;
 
@@ -0,0 +1,57 @@
+//! Operation Progress Component
+
+use leptos::prelude::*;
+use leptos::context::use_context;
+use leptos::task::spawn_local;
+use crate::api::Operation;
+use crate::state::{AppState, ToastType};
+
+/// Progress bar with a Cancel button for a running forecast, route
+/// optimization or import
+#[component]
+pub fn OperationProgressBar(operation: Operation) -> impl IntoView {
+    let state = use_context::<AppState>().expect("AppState not found");
+    let cancelling = RwSignal::new(false);
+    let id = operation.id().to_string();
+
+    let operation_progress = state.operation_progress;
+    let progress = move || operation_progress.with(|all| all.get(&id).cloned());
+
+    let cancel = move |_| {
+        let state = state.clone();
+        let operation = operation.clone();
+        cancelling.set(true);
+        spawn_local(async move {
+            if let Err(e) = operation.cancel().await {
+                state.toast(&format!("Could not cancel: {}", e), ToastType::Error);
+                cancelling.set(false);
+            }
+        });
+    };
+
+    view! {
+        <div class="operation-progress">
+            {move || match progress().and_then(|p| p.fraction().map(|f| (p, f))) {
+                Some((p, fraction)) => view! {
+                    <progress max="100" value=format!("{:.0}", fraction * 100.0)></progress>
+                    <span class="operation-progress-count">
+                        {format!("{} / {}", p.completed, p.total.unwrap_or_default())}
+                    </span>
+                }.into_any(),
+                None => view! {
+                    <progress></progress>
+                    <span class="operation-progress-count">
+                        {progress().map(|p| p.completed.to_string()).unwrap_or_default()}
+                    </span>
+                }.into_any(),
+            }}
+            <button
+                class="btn btn-secondary"
+                on:click=cancel
+                disabled=move || cancelling.get()
+            >
+                {move || if cancelling.get() { "Cancelling..." } else { "Cancel" }}
+            </button>
+        </div>
+    }
+}
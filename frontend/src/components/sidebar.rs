@@ -42,18 +42,21 @@ pub fn Sidebar() -> impl IntoView {
             <nav class="sidebar-nav">
                 <ul>
                     {modules.into_iter().map(|module| {
+                        let state = state.clone();
                         view! {
-                            <li>
-                                <A
-                                    href=module.path()
-                                    attr:class="nav-link"
-                                >
-                                    <span class="nav-icon">{module.icon()}</span>
-                                    <Show when=move || !collapsed.get()>
-                                        <span class="nav-text">{module.title()}</span>
-                                    </Show>
-                                </A>
-                            </li>
+                            <Show when=move || state.can_access(module)>
+                                <li>
+                                    <A
+                                        href=module.path()
+                                        attr:class="nav-link"
+                                    >
+                                        <span class="nav-icon">{module.icon()}</span>
+                                        <Show when=move || !collapsed.get()>
+                                            <span class="nav-text">{module.title()}</span>
+                                        </Show>
+                                    </A>
+                                </li>
+                            </Show>
                         }
                     }).collect::<Vec<_>>()}
                 </ul>
@@ -71,6 +74,8 @@ pub fn Sidebar() -> impl IntoView {
                                 let status = state.sync_status.get();
                                 if status.is_syncing {
                                     "Syncing...".to_string()
+                                } else if status.is_online && status.stuck_changes > 0 {
+                                    format!("{} pending, {} stuck", status.pending_changes, status.stuck_changes)
                                 } else if status.is_online {
                                     format!("{} pending", status.pending_changes)
                                 } else {
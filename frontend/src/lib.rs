@@ -8,6 +8,8 @@ mod components;
 mod pages;
 mod api;
 mod state;
+mod list_state;
+mod units;
 
 pub use app::App;
 
@@ -0,0 +1,338 @@
+//! List Page State
+//!
+//! Filters, sort order, page and scroll position of the list pages, kept
+//! per route in `AppState` so leaving a list and coming back finds it as it
+//! was left. Filters are also mirrored into the URL query string, so a
+//! filtered view can be bookmarked or shared and the browser's back button
+//! steps back through earlier filters.
+
+use std::collections::{BTreeMap, HashMap};
+use leptos::prelude::*;
+use leptos::context::use_context;
+use leptos_router::hooks::{use_location, use_navigate};
+use leptos_router::NavigateOptions;
+use crate::state::AppState;
+
+/// Rows shown per page of a list
+pub const PAGE_SIZE: usize = 25;
+
+/// How a list page was last left
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ListState {
+    /// Filter values by name; empty values are left out
+    pub filters: BTreeMap<String, String>,
+    /// Column the rows are sorted by
+    pub sort: Option<String>,
+    pub sort_desc: bool,
+    /// Zero-based page number
+    pub page: usize,
+    /// Window scroll offset in pixels
+    pub scroll_y: f64,
+}
+
+/// Encode filters as a query string (without the leading `?`). Keys come
+/// out sorted so the same filters always give the same URL.
+pub fn encode_query(filters: &BTreeMap<String, String>) -> String {
+    filters.iter()
+        .filter(|(_, value)| !value.is_empty())
+        .map(|(key, value)| format!("{}={}", percent_encode(key), percent_encode(value)))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// Decode a query string, with or without its leading `?`. `+` is read as a
+/// space and malformed escapes are kept as they were written.
+pub fn decode_query(query: &str) -> BTreeMap<String, String> {
+    query.trim_start_matches('?')
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .filter_map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let (key, value) = (percent_decode(key), percent_decode(value));
+            (!key.is_empty() && !value.is_empty()).then_some((key, value))
+        })
+        .collect()
+}
+
+fn percent_encode(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.' | b'~') {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' => {
+                let escaped = text.get(i + 1..i + 3)
+                    .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+                if let Some(byte) = escaped {
+                    decoded.push(byte);
+                    i += 2;
+                } else {
+                    decoded.push(b'%');
+                }
+            }
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Rows on `page`, and how many pages there are
+pub fn paginate<T: Clone>(rows: &[T], page: usize) -> (Vec<T>, usize) {
+    let pages = rows.len().div_ceil(PAGE_SIZE).max(1);
+    let start = page.min(pages - 1) * PAGE_SIZE;
+    (rows.iter().skip(start).take(PAGE_SIZE).cloned().collect(), pages)
+}
+
+/// A list page's state, bound to the store and the URL
+#[derive(Clone, Copy)]
+pub struct ListStateHandle {
+    route: &'static str,
+    state: RwSignal<ListState>,
+    store: RwSignal<HashMap<String, ListState>>,
+}
+
+impl ListStateHandle {
+    /// Current value of a filter, empty when unset
+    pub fn filter(&self, key: &str) -> String {
+        self.state.with(|s| s.filters.get(key).cloned().unwrap_or_default())
+    }
+
+    /// A filter as a signal, for inputs that bind one
+    pub fn bind_filter(&self, key: &'static str) -> RwSignal<String> {
+        let list = *self;
+        let value = RwSignal::new(list.state.with_untracked(|s| s.filters.get(key).cloned().unwrap_or_default()));
+        Effect::new(move |_| {
+            let current = list.filter(key);
+            if value.get_untracked() != current {
+                value.set(current);
+            }
+        });
+        Effect::new(move |_| {
+            let typed = value.get();
+            if list.state.with_untracked(|s| s.filters.get(key).map_or(typed.is_empty(), |v| *v == typed)) {
+                return;
+            }
+            list.set_filter(key, typed);
+        });
+        value
+    }
+
+    /// Change a filter, going back to the first page
+    pub fn set_filter(&self, key: &str, value: String) {
+        self.state.update(|s| {
+            if value.is_empty() {
+                s.filters.remove(key);
+            } else {
+                s.filters.insert(key.to_string(), value);
+            }
+            s.page = 0;
+        });
+    }
+
+    /// Sort column and whether it is descending
+    pub fn sort(&self) -> Option<(String, bool)> {
+        self.state.with(|s| s.sort.clone().map(|column| (column, s.sort_desc)))
+    }
+
+    /// Sort by `column`, flipping the direction if it already is
+    pub fn sort_by(&self, column: &str) {
+        self.state.update(|s| {
+            if s.sort.as_deref() == Some(column) {
+                s.sort_desc = !s.sort_desc;
+            } else {
+                s.sort = Some(column.to_string());
+                s.sort_desc = false;
+            }
+        });
+    }
+
+    /// Arrow for a column header
+    pub fn sort_indicator(&self, column: &str) -> &'static str {
+        match self.sort() {
+            Some((sorted, false)) if sorted == column => " ▲",
+            Some((sorted, true)) if sorted == column => " ▼",
+            _ => "",
+        }
+    }
+
+    pub fn page(&self) -> usize {
+        self.state.with(|s| s.page)
+    }
+
+    pub fn set_page(&self, page: usize) {
+        self.state.update(|s| s.page = page);
+    }
+
+    /// Whether anything differs from a freshly opened list
+    pub fn is_modified(&self) -> bool {
+        self.state.with(|s| !s.filters.is_empty() || s.sort.is_some() || s.page > 0)
+    }
+
+    /// Clear filters, sort and page, and forget the saved state
+    pub fn reset(&self) {
+        self.state.set(ListState::default());
+        self.store.update(|store| {
+            store.remove(self.route);
+        });
+        if let Some(window) = web_sys::window() {
+            window.scroll_to_with_x_and_y(0.0, 0.0);
+        }
+    }
+}
+
+/// State of the list page at `route`, restored from earlier in the session.
+/// Filters in the URL take precedence over the saved ones.
+pub fn use_list_state(route: &'static str) -> ListStateHandle {
+    let store = use_context::<AppState>().expect("AppState not found").list_states;
+    let location = use_location();
+    let navigate = use_navigate();
+
+    let mut initial = store.with_untracked(|store| store.get(route).cloned()).unwrap_or_default();
+    let url_filters = decode_query(&location.search.get_untracked());
+    if !url_filters.is_empty() && url_filters != initial.filters {
+        initial = ListState { filters: url_filters, ..Default::default() };
+    }
+    let scroll_y = initial.scroll_y;
+    let state = RwSignal::new(initial);
+    let handle = ListStateHandle { route, state, store };
+
+    // Back and forward buttons change the URL; follow them
+    Effect::new(move |previous: Option<()>| {
+        let search = location.search.get();
+        // The URL was already read above, and may still lack saved filters
+        if previous.is_none() || location.pathname.get_untracked() != route {
+            return;
+        }
+        let filters = decode_query(&search);
+        if state.with_untracked(|s| s.filters != filters) {
+            state.update(|s| {
+                s.filters = filters;
+                s.page = 0;
+            });
+        }
+    });
+
+    // Save every change, and put the filters in the URL
+    Effect::new(move |previous: Option<()>| {
+        let current = state.get();
+        let query = encode_query(&current.filters);
+        store.update(|store| {
+            store.insert(route.to_string(), current);
+        });
+
+        if location.pathname.get_untracked() != route
+            || decode_query(&location.search.get_untracked()) == decode_query(&query)
+        {
+            return;
+        }
+        let url = if query.is_empty() { route.to_string() } else { format!("{}?{}", route, query) };
+        navigate(&url, NavigateOptions {
+            // Restoring saved filters shouldn't add a history entry
+            replace: previous.is_none(),
+            scroll: false,
+            ..Default::default()
+        });
+    });
+
+    if scroll_y > 0.0 {
+        request_animation_frame(move || {
+            if let Some(window) = web_sys::window() {
+                window.scroll_to_with_x_and_y(0.0, scroll_y);
+            }
+        });
+    }
+    on_cleanup(move || {
+        let Some(scroll_y) = web_sys::window().and_then(|w| w.scroll_y().ok()) else {
+            return;
+        };
+        store.update(|store| {
+            if let Some(saved) = store.get_mut(route) {
+                saved.scroll_y = scroll_y;
+            }
+        });
+    });
+
+    handle
+}
+
+/// Previous/next controls under a paged list
+#[component]
+pub fn ListPager(list: ListStateHandle, #[prop(into)] pages: Signal<usize>) -> impl IntoView {
+    let page = move || list.page().min(pages.get().saturating_sub(1));
+
+    view! {
+        <Show when=move || { pages.get() > 1 }>
+            <div class="table-pager">
+                <button
+                    class="btn btn-sm btn-ghost"
+                    disabled=move || page() == 0
+                    on:click=move |_| list.set_page(page().saturating_sub(1))
+                >
+                    "← Previous"
+                </button>
+                <span>{move || format!("Page {} of {}", page() + 1, pages.get())}</span>
+                <button
+                    class="btn btn-sm btn-ghost"
+                    disabled=move || page() + 1 >= pages.get()
+                    on:click=move |_| list.set_page(page() + 1)
+                >
+                    "Next →"
+                </button>
+            </div>
+        </Show>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    fn filters(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[wasm_bindgen_test]
+    fn test_query_round_trips_special_characters() {
+        let original = filters(&[
+            ("q", "50% off & more = \"deals\" #1+2 / café 📦"),
+            ("status", "Low Stock"),
+        ]);
+        let query = encode_query(&original);
+
+        assert_eq!(query, "q=50%25%20off%20%26%20more%20%3D%20%22deals%22%20%231%2B2%20%2F%20caf%C3%A9%20%F0%9F%93%A6&status=Low%20Stock");
+        assert_eq!(decode_query(&query), original);
+        assert_eq!(decode_query(&format!("?{}", query)), original);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_empty_filters_are_left_out() {
+        assert_eq!(encode_query(&filters(&[("q", ""), ("type", "retail")])), "type=retail");
+        assert_eq!(encode_query(&BTreeMap::new()), "");
+        assert!(decode_query("").is_empty());
+        assert!(decode_query("?q=&&=x").is_empty());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_decode_accepts_hand_written_queries() {
+        assert_eq!(decode_query("q=acme+corp&status=Shipped"), filters(&[("q", "acme corp"), ("status", "Shipped")]));
+        assert_eq!(decode_query("q=100%&x=%zz%4"), filters(&[("q", "100%"), ("x", "%zz%4")]));
+        assert_eq!(decode_query("q=%+1"), filters(&[("q", "% 1")]));
+        assert_eq!(decode_query("q=a%3Db=c"), filters(&[("q", "a=b=c")]));
+    }
+}
@@ -1,12 +1,65 @@
 //! Customers Page
 
+use chrono::{Datelike, Local};
 use leptos::prelude::*;
+use leptos::task::spawn_local;
 use leptos_router::components::A;
 use leptos_router::hooks::use_params_map;
-use crate::components::Card;
+use crate::api;
+use crate::components::{Card, SearchInput};
+use crate::list_state::{paginate, use_list_state, ListPager};
 
 #[component]
 pub fn CustomersPage() -> impl IntoView {
+    let customers = RwSignal::new(vec![
+        CustomerRow {
+            number: "CUS-000001".into(),
+            company: "Acme Corporation".into(),
+            contact: "Jane Doe".into(),
+            email: "jane@acme.com".into(),
+            customer_type: "Wholesale".into(),
+        },
+        CustomerRow {
+            number: "CUS-000002".into(),
+            company: "Tech Solutions".into(),
+            contact: "Bob Smith".into(),
+            email: "bob@techsol.com".into(),
+            customer_type: "Retail".into(),
+        },
+    ]);
+    let list = use_list_state("/customers");
+    let search = list.bind_filter("q");
+
+    let visible = Memo::new(move |_| {
+        let query = list.filter("q").to_lowercase();
+        let customer_type = list.filter("type");
+        let mut rows: Vec<CustomerRow> = customers.get().into_iter()
+            .filter(|row| query.is_empty()
+                || [&row.number, &row.company, &row.contact, &row.email]
+                    .iter()
+                    .any(|field| field.to_lowercase().contains(&query)))
+            .filter(|row| customer_type.is_empty() || row.customer_type == customer_type)
+            .collect();
+        if let Some((column, desc)) = list.sort() {
+            rows.sort_by(|a, b| match column.as_str() {
+                "company" => a.company.cmp(&b.company),
+                "contact" => a.contact.cmp(&b.contact),
+                "email" => a.email.cmp(&b.email),
+                "type" => a.customer_type.cmp(&b.customer_type),
+                _ => a.number.cmp(&b.number),
+            });
+            if desc {
+                rows.reverse();
+            }
+        }
+        paginate(&rows, list.page())
+    });
+    let header = move |column: &'static str, label: &'static str| view! {
+        <th class="sortable" on:click=move |_| list.sort_by(column)>
+            {label} {move || list.sort_indicator(column)}
+        </th>
+    };
+
     view! {
         <div class="page customers-page">
             <div class="page-header">
@@ -15,62 +68,115 @@ pub fn CustomersPage() -> impl IntoView {
                     <p class="subtitle">"Manage customer relationships"</p>
                 </div>
                 <div class="page-actions">
+                    <button class="btn btn-ghost" disabled=move || !list.is_modified() on:click=move |_| list.reset()>
+                        "Reset View"
+                    </button>
                     <button class="btn btn-primary">"+ Add Customer"</button>
                 </div>
             </div>
 
             <Card>
+                <div class="table-toolbar">
+                    <SearchInput placeholder="Search by name, contact or email..." value=search />
+                    <select
+                        class="form-select"
+                        prop:value=move || list.filter("type")
+                        on:change=move |ev| list.set_filter("type", event_target_value(&ev))
+                    >
+                        <option value="">"All Types"</option>
+                        <option value="Wholesale">"Wholesale"</option>
+                        <option value="Retail">"Retail"</option>
+                    </select>
+                </div>
+
                 <table class="data-table">
                     <thead>
                         <tr>
-                            <th>"Customer #"</th>
-                            <th>"Company"</th>
-                            <th>"Contact"</th>
-                            <th>"Email"</th>
-                            <th>"Type"</th>
+                            {header("number", "Customer #")}
+                            {header("company", "Company")}
+                            {header("contact", "Contact")}
+                            {header("email", "Email")}
+                            {header("type", "Type")}
                             <th>"Actions"</th>
                         </tr>
                     </thead>
                     <tbody>
-                        <tr>
-                            <td><code>"CUS-000001"</code></td>
-                            <td>"Acme Corporation"</td>
-                            <td>"Jane Doe"</td>
-                            <td>"jane@acme.com"</td>
-                            <td><span class="badge">"Wholesale"</span></td>
-                            <td>
-                                <A href="/customers/CUS-000001" attr:class="btn btn-sm btn-ghost">"View"</A>
-                            </td>
-                        </tr>
-                        <tr>
-                            <td><code>"CUS-000002"</code></td>
-                            <td>"Tech Solutions"</td>
-                            <td>"Bob Smith"</td>
-                            <td>"bob@techsol.com"</td>
-                            <td><span class="badge">"Retail"</span></td>
-                            <td>
-                                <A href="/customers/CUS-000002" attr:class="btn btn-sm btn-ghost">"View"</A>
-                            </td>
-                        </tr>
+                        <For
+                            each=move || visible.get().0
+                            key=|customer| customer.number.clone()
+                            children=move |customer| view! {
+                                <tr>
+                                    <td><code>{customer.number.clone()}</code></td>
+                                    <td>{customer.company.clone()}</td>
+                                    <td>{customer.contact.clone()}</td>
+                                    <td>{customer.email.clone()}</td>
+                                    <td><span class="badge">{customer.customer_type.clone()}</span></td>
+                                    <td>
+                                        <A href=format!("/customers/{}", customer.number) attr:class="btn btn-sm btn-ghost">"View"</A>
+                                    </td>
+                                </tr>
+                            }
+                        />
                     </tbody>
                 </table>
+                <ListPager list=list pages=Signal::derive(move || visible.get().1) />
             </Card>
         </div>
     }
 }
 
+#[derive(Clone, PartialEq)]
+struct CustomerRow {
+    number: String,
+    company: String,
+    contact: String,
+    email: String,
+    customer_type: String,
+}
+
 #[component]
 pub fn CustomerDetailPage() -> impl IntoView {
     let params = use_params_map();
     let id = move || params.get().get("id").map(|s| s.clone()).unwrap_or_default();
 
+    // Year-to-date statement download
+    let statement_status = RwSignal::new(None::<String>);
+    let download_statement = move |_| {
+        let customer_id = id();
+        let today = Local::now().date_naive();
+        let start = today.with_ordinal(1).unwrap_or(today);
+        statement_status.set(Some("Generating statement...".to_string()));
+
+        spawn_local(async move {
+            match api::generate_customer_statement(
+                &customer_id,
+                &start.to_string(),
+                &today.to_string(),
+                "pdf",
+            ).await {
+                Ok(export) => {
+                    export.save();
+                    statement_status.set(None);
+                }
+                Err(e) => statement_status.set(Some(e.to_string())),
+            }
+        });
+    };
+
     view! {
         <div class="page customer-detail">
             <div class="page-header">
                 <A href="/customers" attr:class="back-link">"← Back to Customers"</A>
                 <h1>"Customer: " {id}</h1>
+                <div class="page-actions">
+                    <button class="btn btn-secondary" on:click=download_statement>
+                        "Download Statement"
+                    </button>
+                </div>
             </div>
 
+            {move || statement_status.get().map(|msg| view! { <p class="status-message">{msg}</p> })}
+
             <div class="detail-grid">
                 <Card title="Contact Information">
                     <dl class="detail-list">
@@ -1,95 +1,282 @@
 //! Dashboard Page
 
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, Utc};
+use leptos::context::use_context;
 use leptos::prelude::*;
+use leptos::task::spawn_local;
+use crate::api::{self, MetricComparison};
 use crate::components::{Card, StatCard, Chart, DataPoint};
+use crate::state::AppState;
+
+/// Days of history in each stat card's sparkline
+const SPARKLINE_DAYS: u32 = 14;
+
+/// Days of picks shown on the aisle heatmap
+const HEATMAP_DAYS: i64 = 30;
+
+/// Pickers shown on the leaderboard card
+const LEADERBOARD_SIZE: usize = 5;
+
+/// Entries in the recent activity card
+const ACTIVITY_SIZE: u32 = 8;
 
 /// Main dashboard with key metrics
 #[component]
 pub fn Dashboard() -> impl IntoView {
-    // Mock data - would come from Tauri commands
-    let (inventory_count, _) = signal("12,456".to_string());
-    let (pending_shipments, _) = signal("47".to_string());
-    let (deliveries_today, _) = signal("23".to_string());
-    let (low_stock_items, _) = signal("8".to_string());
-
-    // Convert to Signal<String> for StatCard
-    let inventory_signal: Signal<String> = Signal::from(inventory_count);
-    let pending_signal: Signal<String> = Signal::from(pending_shipments);
-    let deliveries_signal: Signal<String> = Signal::from(deliveries_today);
-    let low_stock_signal: Signal<String> = Signal::from(low_stock_items);
-
-    let chart_data = vec![
-        DataPoint { label: "Mon".to_string(), value: 120.0 },
-        DataPoint { label: "Tue".to_string(), value: 150.0 },
-        DataPoint { label: "Wed".to_string(), value: 180.0 },
-        DataPoint { label: "Thu".to_string(), value: 140.0 },
-        DataPoint { label: "Fri".to_string(), value: 200.0 },
-        DataPoint { label: "Sat".to_string(), value: 90.0 },
-        DataPoint { label: "Sun".to_string(), value: 60.0 },
-    ];
+    // Comparison period: "day", "week" or "month"
+    let period = RwSignal::new("week".to_string());
+    let metrics = RwSignal::new(None::<api::DashboardMetrics>);
+    Effect::new(move |_| {
+        let period = period.get();
+        spawn_local(async move {
+            if let Ok(loaded) = api::get_dashboard_metrics(&period).await {
+                metrics.set(Some(loaded));
+            }
+        });
+    });
+
+    let value = move |metric: fn(&api::DashboardMetrics) -> MetricComparison| Signal::derive(move || {
+        metrics.get()
+            .map(|m| format_count(metric(&m).current))
+            .unwrap_or_else(|| "—".to_string())
+    });
+    let trend = move |metric: fn(&api::DashboardMetrics) -> MetricComparison| Signal::derive(move || {
+        metrics.get().and_then(|m| metric(&m).change_percent)
+    });
+
+    // Daily history behind each card's sparkline
+    let history = |metric: &'static str| {
+        let points = RwSignal::new(Vec::<f64>::new());
+        spawn_local(async move {
+            if let Ok(history) = api::get_metric_history(metric, SPARKLINE_DAYS).await {
+                points.set(history.into_iter().map(|p| p.value).collect());
+            }
+        });
+        Signal::derive(move || Some(points.get()))
+    };
+
+    // Picks per aisle, for slotting fast movers
+    let aisle_picks = RwSignal::new(Vec::<DataPoint>::new());
+    spawn_local(async move {
+        let today = Local::now().date_naive();
+        let start = today - Duration::days(HEATMAP_DAYS - 1);
+        if let Ok(heatmap) = api::get_pick_heatmap(&start.to_string(), &today.to_string()).await {
+            aisle_picks.set(heatmap.aisles.into_iter().map(|a| DataPoint {
+                label: a.aisle.unwrap_or_else(|| "—".to_string()),
+                value: a.picks as f64,
+            }).collect());
+        }
+    });
+
+    // Today's picker leaderboard for supervisors, personal stats for pickers
+    let state = use_context::<AppState>().expect("AppState not found");
+    let show_leaderboard = state.has_scope("team_timesheets");
+    let is_picker = state.user.with_untracked(|u| u.as_ref().is_some_and(|u| u.role == "picker"));
+    let leaderboard = RwSignal::new(Vec::<api::PickerStats>::new());
+    let my_stats = RwSignal::new(None::<api::MyPickerStats>);
+    spawn_local(async move {
+        let today = Local::now().date_naive().to_string();
+        if show_leaderboard {
+            if let Ok(board) = api::get_picker_stats(&today).await {
+                leaderboard.set(board.pickers.into_iter().take(LEADERBOARD_SIZE).collect());
+            }
+        }
+        if is_picker {
+            if let Ok(stats) = api::get_my_stats(&today).await {
+                my_stats.set(Some(stats));
+            }
+        }
+    });
+
+    // Shipments created each day this week, whatever the period
+    let chart_data = move || {
+        metrics.get().map(|m| {
+            m.shipments_this_week.into_iter().map(|day| DataPoint {
+                label: NaiveDate::parse_from_str(&day.date, "%Y-%m-%d")
+                    .map(|date| date.weekday().to_string())
+                    .unwrap_or(day.date),
+                value: day.total as f64,
+            }).collect::<Vec<_>>()
+        }).unwrap_or_default()
+    };
+
+    let activity = RwSignal::new(None::<Vec<api::ActivityEntry>>);
+    spawn_local(async move {
+        if let Ok(entries) = api::recent_activity(Some(ACTIVITY_SIZE)).await {
+            activity.set(Some(entries));
+        }
+    });
 
     view! {
         <div class="dashboard">
             <div class="page-header">
                 <h1>"Dashboard"</h1>
                 <p class="subtitle">"Welcome back! Here's your warehouse overview."</p>
+                <select
+                    class="form-select period-select"
+                    on:change=move |ev| period.set(event_target_value(&ev))
+                >
+                    <option value="day">"Today vs same day last week"</option>
+                    <option value="week" selected=true>"This week vs last week"</option>
+                    <option value="month">"This month vs last month"</option>
+                </select>
             </div>
 
             <div class="stats-grid">
                 <StatCard
                     title="Total Inventory"
-                    value=inventory_signal
+                    value=value(|m| m.inventory_total)
                     icon="📦"
-                    trend=5.2
+                    trend=trend(|m| m.inventory_total)
+                    history=history("inventory_total")
                 />
                 <StatCard
-                    title="Pending Shipments"
-                    value=pending_signal
+                    title="Shipments"
+                    value=value(|m| m.shipments)
                     icon="🚚"
-                    trend=-2.1
+                    trend=trend(|m| m.shipments)
+                    history=history("shipments")
                 />
                 <StatCard
-                    title="Deliveries Today"
-                    value=deliveries_signal
+                    title="Deliveries"
+                    value=value(|m| m.deliveries)
                     icon="📍"
-                    trend=12.5
+                    trend=trend(|m| m.deliveries)
+                    history=history("deliveries")
                 />
                 <StatCard
                     title="Low Stock Alerts"
-                    value=low_stock_signal
+                    value=value(|m| m.low_stock_items)
                     icon="⚠️"
-                    trend=-3.0
+                    trend=trend(|m| m.low_stock_items)
+                    history=history("low_stock_items")
                 />
             </div>
 
             <div class="dashboard-grid">
                 <Card title="Shipments This Week">
-                    <Chart data=chart_data.clone() show_labels=true />
+                    {move || view! { <Chart data=chart_data() show_labels=true /> }}
+                </Card>
+
+                <Card title="Today">
+                    {move || match metrics.get() {
+                        Some(m) => view! {
+                            <div class="my-stats">
+                                <p>{format!(
+                                    "{} of {} deliveries completed",
+                                    m.deliveries_today.completed,
+                                    m.deliveries_today.completed + m.deliveries_today.pending
+                                )}</p>
+                                <p>{format!("{} active items", format_count(m.active_items as f64))}</p>
+                                <p class="subtitle">{match m.pending_sync_changes {
+                                    0 => "All changes synced".to_string(),
+                                    n => format!("{} change(s) waiting to sync", n),
+                                }}</p>
+                            </div>
+                        }.into_any(),
+                        None => view! { <p class="subtitle">"—"</p> }.into_any(),
+                    }}
+                </Card>
+
+                <Card title="Picks by Aisle (30 days)">
+                    {move || {
+                        let data = aisle_picks.get();
+                        if data.is_empty() {
+                            view! { <p class="subtitle">"No picks recorded"</p> }.into_any()
+                        } else {
+                            view! { <Chart data=data show_labels=true /> }.into_any()
+                        }
+                    }}
                 </Card>
 
+                <Show when=move || show_leaderboard>
+                    <Card title="Picker Leaderboard (today)">
+                        {move || {
+                            let pickers = leaderboard.get();
+                            if pickers.is_empty() {
+                                view! { <p class="subtitle">"No picks recorded"</p> }.into_any()
+                            } else {
+                                view! {
+                                    <table class="mini-table">
+                                        <thead>
+                                            <tr>
+                                                <th>"#"</th>
+                                                <th>"Picker"</th>
+                                                <th>"Lines"</th>
+                                                <th>"Picks/h"</th>
+                                                <th>"Accuracy"</th>
+                                            </tr>
+                                        </thead>
+                                        <tbody>
+                                            {pickers.into_iter().map(|p| {
+                                                let hint = if p.hours_estimated { "Estimated: not clocked in" } else { "" };
+                                                view! {
+                                                    <tr>
+                                                        <td>{p.rank}</td>
+                                                        <td>{p.name.unwrap_or(p.user_id)}</td>
+                                                        <td>{p.lines_picked}</td>
+                                                        <td title=hint>{format_rate(p.picks_per_hour, p.hours_estimated)}</td>
+                                                        <td>{format!("{:.1}%", p.accuracy_percent)}</td>
+                                                    </tr>
+                                                }
+                                            }).collect_view()}
+                                        </tbody>
+                                    </table>
+                                }.into_any()
+                            }
+                        }}
+                    </Card>
+                </Show>
+
+                <Show when=move || is_picker>
+                    <Card title="My Picks Today">
+                        {move || match my_stats.get() {
+                            Some(mine) => {
+                                let streak = format!(
+                                    "{} day(s) without a mispick (best {})",
+                                    mine.clean_streak_days, mine.best_clean_streak_days
+                                );
+                                match mine.stats {
+                                    Some(stats) => view! {
+                                        <div class="my-stats">
+                                            <p>{format!("#{} of {}", stats.rank, mine.pickers)}</p>
+                                            <p>{format!("{} lines, {} units", stats.lines_picked, format_count(stats.units_picked))}</p>
+                                            <p>{format!("{} picks/h", format_rate(stats.picks_per_hour, stats.hours_estimated))}</p>
+                                            <p>{format!("{:.1}% accuracy", stats.accuracy_percent)}</p>
+                                            <p class="subtitle">{streak}</p>
+                                        </div>
+                                    }.into_any(),
+                                    None => view! {
+                                        <div class="my-stats">
+                                            <p class="subtitle">"No picks yet today"</p>
+                                            <p class="subtitle">{streak}</p>
+                                        </div>
+                                    }.into_any(),
+                                }
+                            }
+                            None => view! { <p class="subtitle">"—"</p> }.into_any(),
+                        }}
+                    </Card>
+                </Show>
+
                 <Card title="Recent Activity">
-                    <div class="activity-list">
-                        <ActivityItem
-                            icon="📥"
-                            title="Receipt RCV-00000123 completed"
-                            time="5 minutes ago"
-                        />
-                        <ActivityItem
-                            icon="🚚"
-                            title="Shipment SHP-00000456 dispatched"
-                            time="12 minutes ago"
-                        />
-                        <ActivityItem
-                            icon="📍"
-                            title="Delivery DEL-00000789 delivered"
-                            time="25 minutes ago"
-                        />
-                        <ActivityItem
-                            icon="⚠️"
-                            title="Low stock alert: SKU-12345"
-                            time="1 hour ago"
-                        />
-                    </div>
+                    {move || match activity.get() {
+                        Some(entries) if entries.is_empty() => {
+                            view! { <p class="subtitle">"Nothing has happened yet"</p> }.into_any()
+                        }
+                        Some(entries) => view! {
+                            <div class="activity-list">
+                                {entries.into_iter().map(|entry| view! {
+                                    <ActivityItem
+                                        icon=activity_icon(&entry.kind)
+                                        title=entry.title
+                                        time=time_ago(&entry.occurred_at)
+                                    />
+                                }).collect_view()}
+                            </div>
+                        }.into_any(),
+                        None => view! { <p class="subtitle">"—"</p> }.into_any(),
+                    }}
                 </Card>
 
                 <Card title="Quick Actions">
@@ -126,8 +313,8 @@ pub fn Dashboard() -> impl IntoView {
 #[component]
 fn ActivityItem(
     icon: &'static str,
-    title: &'static str,
-    time: &'static str,
+    title: String,
+    time: String,
 ) -> impl IntoView {
     view! {
         <div class="activity-item">
@@ -152,3 +339,49 @@ fn QuickActionButton(
         </button>
     }
 }
+
+/// Whole number with thousands separators, e.g. 12456 -> "12,456"
+fn format_count(value: f64) -> String {
+    let digits = (value.round() as i64).abs().to_string();
+    let mut grouped = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(c);
+    }
+    if value < 0.0 { format!("-{}", grouped) } else { grouped }
+}
+
+fn activity_icon(kind: &str) -> &'static str {
+    match kind {
+        "shipment" => "🚚",
+        "receipt" => "📥",
+        "delivery" => "📍",
+        _ => "📝",
+    }
+}
+
+/// How long ago an RFC 3339 timestamp was, e.g. "12 minutes ago"
+fn time_ago(timestamp: &str) -> String {
+    let Ok(at) = DateTime::parse_from_rfc3339(timestamp) else {
+        return String::new();
+    };
+    let minutes = Utc::now().signed_duration_since(at).num_minutes().max(0);
+    let (count, unit) = match minutes {
+        0 => return "just now".to_string(),
+        m if m < 60 => (m, "minute"),
+        m if m < 60 * 24 => (m / 60, "hour"),
+        m => (m / (60 * 24), "day"),
+    };
+    format!("{} {}{} ago", count, unit, if count == 1 { "" } else { "s" })
+}
+
+/// Picks per hour, starred when hours were estimated from pick timestamps
+fn format_rate(picks_per_hour: Option<f64>, estimated: bool) -> String {
+    match picks_per_hour {
+        Some(rate) if estimated => format!("{:.1}*", rate),
+        Some(rate) => format!("{:.1}", rate),
+        None => "—".to_string(),
+    }
+}
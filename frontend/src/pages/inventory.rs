@@ -1,9 +1,12 @@
 //! Inventory Page
 
 use leptos::prelude::*;
+use leptos::task::spawn_local;
 use leptos_router::components::A;
 use leptos_router::hooks::use_params_map;
-use crate::components::{Card, SearchInput};
+use crate::api;
+use crate::components::{Badge, BadgeVariant, Card, Chart, DataPoint, SearchInput};
+use crate::list_state::{paginate, use_list_state, ListPager};
 
 /// Inventory list page
 #[component]
@@ -14,6 +17,65 @@ pub fn InventoryPage() -> impl IntoView {
         InventoryRow { sku: "SKU-003".into(), name: "Tool Master".into(), qty: 45, reorder: 100, status: "Low Stock".into() },
         InventoryRow { sku: "SKU-004".into(), name: "Part Essential".into(), qty: 512, reorder: 50, status: "In Stock".into() },
     ]);
+    let list = use_list_state("/inventory");
+    let search = list.bind_filter("q");
+
+    let visible = Memo::new(move |_| {
+        let query = list.filter("q").to_lowercase();
+        let status = list.filter("status");
+        let mut rows: Vec<InventoryRow> = items.get().into_iter()
+            .filter(|row| query.is_empty()
+                || row.sku.to_lowercase().contains(&query)
+                || row.name.to_lowercase().contains(&query))
+            .filter(|row| status.is_empty() || row.status == status)
+            .collect();
+        if let Some((column, desc)) = list.sort() {
+            rows.sort_by(|a, b| match column.as_str() {
+                "name" => a.name.cmp(&b.name),
+                "qty" => a.qty.cmp(&b.qty),
+                "reorder" => a.reorder.cmp(&b.reorder),
+                "status" => a.status.cmp(&b.status),
+                _ => a.sku.cmp(&b.sku),
+            });
+            if desc {
+                rows.reverse();
+            }
+        }
+        paginate(&rows, list.page())
+    });
+    // Download of the rows the filters show
+    let export_format = RwSignal::new("csv".to_string());
+    let export_status = RwSignal::new(None::<String>);
+    let export = move |_| {
+        let query = list.filter("q");
+        let status = match list.filter("status").as_str() {
+            "In Stock" => Some("in_stock"),
+            "Low Stock" => Some("low_stock"),
+            _ => None,
+        };
+        let filter = api::InventoryExportFilter {
+            query: (!query.is_empty()).then_some(query),
+            category: None,
+            status: status.map(String::from),
+        };
+        let format = export_format.get_untracked();
+        export_status.set(Some("Exporting inventory...".to_string()));
+
+        spawn_local(async move {
+            match api::export_inventory(&format, filter).await {
+                Ok(export) => {
+                    export.save();
+                    export_status.set(None);
+                }
+                Err(e) => export_status.set(Some(e.to_string())),
+            }
+        });
+    };
+    let header = move |column: &'static str, label: &'static str| view! {
+        <th class="sortable" on:click=move |_| list.sort_by(column)>
+            {label} {move || list.sort_indicator(column)}
+        </th>
+    };
 
     view! {
         <div class="page inventory-page">
@@ -23,35 +85,53 @@ pub fn InventoryPage() -> impl IntoView {
                     <p class="subtitle">"Manage your warehouse inventory"</p>
                 </div>
                 <div class="page-actions">
-                    <button class="btn btn-secondary">"Export"</button>
+                    <button class="btn btn-ghost" disabled=move || !list.is_modified() on:click=move |_| list.reset()>
+                        "Reset View"
+                    </button>
+                    <select
+                        class="form-select"
+                        prop:value=move || export_format.get()
+                        on:change=move |ev| export_format.set(event_target_value(&ev))
+                    >
+                        {api::INVENTORY_EXPORT_FORMATS.iter().map(|(value, label)| view! {
+                            <option value=*value>{*label}</option>
+                        }).collect_view()}
+                    </select>
+                    <button class="btn btn-secondary" on:click=export>"Export"</button>
                     <button class="btn btn-primary">"+ Add Item"</button>
                 </div>
             </div>
 
+            {move || export_status.get().map(|msg| view! { <p class="status-message">{msg}</p> })}
+
             <Card>
                 <div class="table-toolbar">
-                    <SearchInput placeholder="Search by SKU or name..." />
-                    <select class="form-select">
-                        <option>"All Categories"</option>
-                        <option>"Electronics"</option>
-                        <option>"Hardware"</option>
+                    <SearchInput placeholder="Search by SKU or name..." value=search />
+                    <select
+                        class="form-select"
+                        prop:value=move || list.filter("status")
+                        on:change=move |ev| list.set_filter("status", event_target_value(&ev))
+                    >
+                        <option value="">"All Statuses"</option>
+                        <option value="In Stock">"In Stock"</option>
+                        <option value="Low Stock">"Low Stock"</option>
                     </select>
                 </div>
 
                 <table class="data-table">
                     <thead>
                         <tr>
-                            <th>"SKU"</th>
-                            <th>"Name"</th>
-                            <th>"Quantity"</th>
-                            <th>"Reorder Point"</th>
-                            <th>"Status"</th>
+                            {header("sku", "SKU")}
+                            {header("name", "Name")}
+                            {header("qty", "Quantity")}
+                            {header("reorder", "Reorder Point")}
+                            {header("status", "Status")}
                             <th>"Actions"</th>
                         </tr>
                     </thead>
                     <tbody>
                         <For
-                            each=move || items.get()
+                            each=move || visible.get().0
                             key=|item| item.sku.clone()
                             children=move |item| {
                                 let status_class = if item.status == "Low Stock" { "badge-warning" } else { "badge-success" };
@@ -71,12 +151,13 @@ pub fn InventoryPage() -> impl IntoView {
                         />
                     </tbody>
                 </table>
+                <ListPager list=list pages=Signal::derive(move || visible.get().1) />
             </Card>
         </div>
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq)]
 struct InventoryRow {
     sku: String,
     name: String,
@@ -91,6 +172,33 @@ pub fn InventoryDetailPage() -> impl IntoView {
     let params = use_params_map();
     let id = move || params.get().get("id").map(|s| s.clone()).unwrap_or_default();
 
+    // Available-to-promise projection for the next two weeks
+    let projection = RwSignal::new(Vec::<DataPoint>::new());
+    // Primary image thumbnail (base64 JPEG)
+    let thumbnail = RwSignal::new(None::<String>);
+    // How past forecasts compared with actual demand, per model
+    let accuracy = RwSignal::new(None::<api::ForecastAccuracy>);
+    Effect::new(move |_| {
+        let sku = id();
+        spawn_local(async move {
+            if let Ok(result) = api::get_forecast_accuracy(Some(&sku)).await {
+                accuracy.set(Some(result));
+            }
+            let Ok(Some(item)) = api::get_item_by_sku(&sku).await else {
+                return;
+            };
+            if let Ok(images) = api::get_item_images(&item.id).await {
+                thumbnail.set(images.into_iter().find(|i| i.is_primary).and_then(|i| i.thumbnail));
+            }
+            if let Ok(atp) = api::get_available_to_promise(&item.id, 14).await {
+                projection.set(atp.days.iter().map(|d| DataPoint {
+                    label: d.date.get(5..).unwrap_or(&d.date).to_string(),
+                    value: d.available.max(0.0),
+                }).collect());
+            }
+        });
+    });
+
     view! {
         <div class="page inventory-detail">
             <div class="page-header">
@@ -100,6 +208,9 @@ pub fn InventoryDetailPage() -> impl IntoView {
 
             <div class="detail-grid">
                 <Card title="Item Details">
+                    {move || thumbnail.get().map(|data| view! {
+                        <img class="item-thumbnail" src=format!("data:image/jpeg;base64,{}", data) alt="Item photo" />
+                    })}
                     <dl class="detail-list">
                         <dt>"SKU"</dt><dd>{id}</dd>
                         <dt>"Name"</dt><dd>"Widget Pro"</dd>
@@ -125,6 +236,53 @@ pub fn InventoryDetailPage() -> impl IntoView {
                         </div>
                     </div>
                 </Card>
+
+                <Card title="Available to Promise">
+                    <Show
+                        when=move || !projection.get().is_empty()
+                        fallback=|| view! { <p class="subtitle">"No projection available"</p> }
+                    >
+                        {move || view! { <Chart data=projection.get() show_labels=true /> }}
+                    </Show>
+                </Card>
+
+                <Card title="Forecast Accuracy">
+                    {move || match accuracy.get() {
+                        Some(accuracy) if !accuracy.models.is_empty() => {
+                            let best = accuracy.best_model.clone();
+                            view! {
+                                <p class="subtitle">{format!("Last {} days", accuracy.window_days)}</p>
+                                <table class="mini-table">
+                                    <thead>
+                                        <tr>
+                                            <th>"Model"</th>
+                                            <th>"MAPE"</th>
+                                            <th>"Bias"</th>
+                                            <th>"Days"</th>
+                                        </tr>
+                                    </thead>
+                                    <tbody>
+                                        {accuracy.models.into_iter().map(|model| {
+                                            let is_best = best.as_deref() == Some(model.model_type.as_str());
+                                            view! {
+                                                <tr>
+                                                    <td>
+                                                        {model.model_type.clone()}
+                                                        {is_best.then(|| view! { <Badge variant=BadgeVariant::Success>"Best"</Badge> })}
+                                                    </td>
+                                                    <td>{model.mape.map(|m| format!("{:.1}%", m)).unwrap_or_else(|| "-".to_string())}</td>
+                                                    <td>{format!("{:+.1}", model.bias)}</td>
+                                                    <td>{model.samples}</td>
+                                                </tr>
+                                            }
+                                        }).collect::<Vec<_>>()}
+                                    </tbody>
+                                </table>
+                            }.into_any()
+                        }
+                        _ => view! { <p class="subtitle">"Not enough actual demand yet to score forecasts"</p> }.into_any(),
+                    }}
+                </Card>
             </div>
         </div>
     }
@@ -12,7 +12,7 @@ mod not_found;
 
 pub use dashboard::Dashboard;
 pub use inventory::{InventoryPage, InventoryDetailPage};
-pub use shipping::{ShippingPage, NewShipmentPage};
+pub use shipping::{ShippingPage, NewShipmentPage, ShipmentDetailPage};
 pub use receiving::ReceivingPage;
 pub use deliveries::{DeliveriesPage, DeliveryDetailPage};
 pub use customers::{CustomersPage, CustomerDetailPage};
@@ -1,10 +1,43 @@
 //! Receiving Page
 
 use leptos::prelude::*;
-use crate::components::Card;
+use leptos::task::spawn_local;
+use crate::api;
+use crate::components::{Card, StatCard};
+use crate::list_state::{use_list_state, ListPager, PAGE_SIZE};
 
 #[component]
 pub fn ReceivingPage() -> impl IntoView {
+    let receipts = RwSignal::new(Vec::<api::Receipt>::new());
+    let pages = RwSignal::new(1usize);
+    let summary = RwSignal::new(api::ReceivingSummary::default());
+    let list = use_list_state("/receiving");
+
+    spawn_local(async move {
+        if let Ok(result) = api::get_receiving_summary().await {
+            summary.set(result);
+        }
+    });
+    Effect::new(move |_| {
+        let filter = api::ReceiptFilter {
+            status: Some(list.filter("status")).filter(|s| !s.is_empty()),
+            supplier: None,
+            expected_from: None,
+            expected_to: None,
+            page: list.page() as u32 + 1,
+            page_size: PAGE_SIZE as u32,
+        };
+        spawn_local(async move {
+            if let Ok(page) = api::list_receipts(filter).await {
+                pages.set((page.total_pages as usize).max(1));
+                receipts.set(page.items);
+            }
+        });
+    });
+    let count = move |value: fn(&api::ReceivingSummary) -> u64| {
+        Signal::derive(move || summary.with(value).to_string())
+    };
+
     view! {
         <div class="page receiving-page">
             <div class="page-header">
@@ -13,10 +46,27 @@ pub fn ReceivingPage() -> impl IntoView {
                     <p class="subtitle">"Process inbound shipments"</p>
                 </div>
                 <div class="page-actions">
+                    <select
+                        class="form-select"
+                        prop:value=move || list.filter("status")
+                        on:change=move |ev| list.set_filter("status", event_target_value(&ev))
+                    >
+                        <option value="">"All Statuses"</option>
+                        <option value="pending">"Pending"</option>
+                        <option value="receiving">"Receiving"</option>
+                        <option value="completed">"Completed"</option>
+                    </select>
                     <button class="btn btn-primary">"+ New Receipt"</button>
                 </div>
             </div>
 
+            <div class="stats-grid">
+                <StatCard title="Expected Today" value=count(|s| s.pending) icon="📦" />
+                <StatCard title="Receiving" value=count(|s| s.receiving) icon="🚚" />
+                <StatCard title="Completed Today" value=count(|s| s.completed) icon="✅" />
+                <StatCard title="Overdue" value=count(|s| s.overdue) icon="⏰" />
+            </div>
+
             <Card>
                 <table class="data-table">
                     <thead>
@@ -30,29 +80,41 @@ pub fn ReceivingPage() -> impl IntoView {
                         </tr>
                     </thead>
                     <tbody>
-                        <tr>
-                            <td><code>"RCV-00000045"</code></td>
-                            <td>"PO-2024-0123"</td>
-                            <td>"Global Supplies Inc"</td>
-                            <td>"Today"</td>
-                            <td><span class="badge badge-warning">"Receiving"</span></td>
-                            <td>
-                                <button class="btn btn-sm btn-primary">"Continue"</button>
-                            </td>
-                        </tr>
-                        <tr>
-                            <td><code>"RCV-00000044"</code></td>
-                            <td>"PO-2024-0122"</td>
-                            <td>"Parts Direct"</td>
-                            <td>"Yesterday"</td>
-                            <td><span class="badge badge-success">"Completed"</span></td>
-                            <td>
-                                <button class="btn btn-sm btn-ghost">"View"</button>
-                            </td>
-                        </tr>
+                        <For
+                            each=move || receipts.get()
+                            key=|receipt| receipt.id.clone()
+                            children=move |receipt| {
+                                let (badge, action) = match receipt.status.as_str() {
+                                    "completed" => ("badge-success", "View"),
+                                    "cancelled" => ("badge-danger", "View"),
+                                    "receiving" => ("badge-warning", "Continue"),
+                                    _ => ("badge-info", "Start"),
+                                };
+                                let button = if action == "View" { "btn btn-sm btn-ghost" } else { "btn btn-sm btn-primary" };
+                                view! {
+                                    <tr>
+                                        <td><code>{receipt.receipt_number.clone()}</code></td>
+                                        <td>{receipt.po_number.clone().unwrap_or_default()}</td>
+                                        <td>{receipt.supplier_name.clone().unwrap_or_default()}</td>
+                                        <td>{receipt.expected_date.as_deref().map(|d| d.chars().take(10).collect::<String>()).unwrap_or_else(|| "-".to_string())}</td>
+                                        <td><span class=format!("badge {}", badge)>{status_label(&receipt.status)}</span></td>
+                                        <td><button class=button>{action}</button></td>
+                                    </tr>
+                                }
+                            }
+                        />
                     </tbody>
                 </table>
+                <ListPager list=list pages=pages />
             </Card>
         </div>
     }
 }
+
+fn status_label(status: &str) -> String {
+    let mut label = status.to_string();
+    if let Some(first) = label.get_mut(..1) {
+        first.make_ascii_uppercase();
+    }
+    label
+}
@@ -3,9 +3,12 @@
 use leptos::prelude::*;
 use leptos::context::use_context;
 use leptos::ev::Event;
+use leptos::task::spawn_local;
 use wasm_bindgen::JsCast;
-use crate::components::Card;
-use crate::state::{AppState, Theme};
+use crate::api;
+use crate::components::{Card, CustomFieldInputs};
+use crate::state::{AppState, Theme, ToastType};
+use crate::units::{format_measurement, MeasurementKind, MeasurementPreferences, UnitSystem};
 
 fn event_target_value(ev: &Event) -> String {
     ev.target()
@@ -14,10 +17,174 @@ fn event_target_value(ev: &Event) -> String {
         .unwrap_or_default()
 }
 
+fn input_target_value(ev: &Event) -> String {
+    ev.target()
+        .and_then(|t| t.dyn_into::<web_sys::HtmlInputElement>().ok())
+        .map(|t| t.value())
+        .unwrap_or_default()
+}
+
+fn input_target_checked(ev: &Event) -> bool {
+    ev.target()
+        .and_then(|t| t.dyn_into::<web_sys::HtmlInputElement>().ok())
+        .is_some_and(|t| t.checked())
+}
+
+/// Records custom fields can be added to, with their labels
+const CUSTOM_FIELD_ENTITIES: [(&str, &str); 4] = [
+    ("inventory_item", "Items"),
+    ("customer", "Customers"),
+    ("shipment", "Shipments"),
+    ("delivery", "Deliveries"),
+];
+
+/// Field key derived from a label, e.g. "Hazmat Class" → "hazmat_class"
+fn field_key(label: &str) -> String {
+    label.trim()
+        .to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
 #[component]
 pub fn SettingsPage() -> impl IntoView {
     let state = use_context::<AppState>().expect("AppState not found");
 
+    // Optional passphrase protecting (or unlocking) a backup
+    let passphrase = RwSignal::new(String::new());
+    let passphrase_value = move || Some(passphrase.get()).filter(|p| !p.is_empty());
+
+    let backup_state = state.clone();
+    let create_backup = move |_| {
+        let state = backup_state.clone();
+        spawn_local(async move {
+            let Some(directory) = api::pick_directory().await else {
+                return;
+            };
+            match api::create_backup(&directory, passphrase_value()).await {
+                Ok(backup) => state.toast(&format!("Backup saved: {}", backup.file_name), ToastType::Success),
+                Err(e) => state.toast(&format!("Backup failed: {}", e), ToastType::Error),
+            }
+            state.backup_progress.set(None);
+        });
+    };
+
+    let restore_state = state.clone();
+    let restore_backup = move |_| {
+        let state = restore_state.clone();
+        spawn_local(async move {
+            let Some(path) = api::pick_file().await else {
+                return;
+            };
+            match api::restore_backup(&path, passphrase_value()).await {
+                Ok(()) => state.toast("Database restored; a full sync will run next", ToastType::Success),
+                Err(e) => state.toast(&format!("Restore failed: {}", e), ToastType::Error),
+            }
+            state.backup_progress.set(None);
+        });
+    };
+
+    let units = state.measurement_units;
+    let units_state = state.clone();
+    let save_units = move |preferences: MeasurementPreferences| {
+        let state = units_state.clone();
+        units.set(preferences);
+        spawn_local(async move {
+            if let Err(e) = api::set_measurement_preferences(preferences).await {
+                state.toast(&format!("Failed to save units: {}", e), ToastType::Error);
+            }
+        });
+    };
+    let save_weight = save_units.clone();
+    let save_length = save_units.clone();
+    let save_distance = save_units;
+    let example = move |value: f64, kind: MeasurementKind| {
+        format!("Shown as {}", format_measurement(value, kind, &units.get()))
+    };
+
+    let sync_interval = RwSignal::new(None::<u32>);
+    spawn_local(async move {
+        if let Ok(settings) = api::get_sync_settings().await {
+            sync_interval.set(settings.interval_minutes);
+        }
+    });
+    let interval_state = state.clone();
+    let save_sync_interval = move |ev: Event| {
+        let state = interval_state.clone();
+        let minutes = event_target_value(&ev).parse::<u32>().ok();
+        sync_interval.set(minutes);
+        spawn_local(async move {
+            if let Err(e) = api::set_sync_interval(minutes).await {
+                state.toast(&format!("Failed to save sync interval: {}", e), ToastType::Error);
+            }
+        });
+    };
+
+    // Custom fields of the chosen kind of record, with a preview of the
+    // inputs their forms will show
+    let field_entity = RwSignal::new(CUSTOM_FIELD_ENTITIES[0].0.to_string());
+    let definitions = RwSignal::new(Vec::<api::CustomFieldDefinition>::new());
+    let draft = RwSignal::new(api::CustomFieldDefinition {
+        field_type: "text".to_string(),
+        ..Default::default()
+    });
+    let draft_options = RwSignal::new(String::new());
+    let preview = RwSignal::new(serde_json::Map::new());
+    let load_definitions = move || {
+        let entity = field_entity.get_untracked();
+        spawn_local(async move {
+            if let Ok(loaded) = api::get_custom_field_definitions(Some(&entity)).await {
+                definitions.set(loaded);
+            }
+        });
+    };
+    Effect::new(move |_| {
+        field_entity.track();
+        preview.set(serde_json::Map::new());
+        load_definitions();
+    });
+
+    let fields_state = state.clone();
+    let add_field = move |_| {
+        let state = fields_state.clone();
+        let mut definition = draft.get();
+        definition.entity = field_entity.get();
+        definition.key = field_key(&definition.label);
+        definition.options = draft_options.get()
+            .split(',')
+            .map(|o| o.trim().to_string())
+            .filter(|o| !o.is_empty())
+            .collect();
+        definition.sort_order = definitions.with(|d| d.len() as i32);
+        spawn_local(async move {
+            match api::save_custom_field_definition(&definition).await {
+                Ok(saved) => {
+                    state.toast(&format!("Added field {}", saved.label), ToastType::Success);
+                    draft.update(|d| d.label.clear());
+                    draft_options.set(String::new());
+                    load_definitions();
+                }
+                Err(e) => state.toast(&format!("Failed to add field: {}", e), ToastType::Error),
+            }
+        });
+    };
+
+    let delete_state = state.clone();
+    let delete_field = move |definition: api::CustomFieldDefinition| {
+        let state = delete_state.clone();
+        spawn_local(async move {
+            match api::delete_custom_field_definition(&definition.id).await {
+                Ok(()) => {
+                    state.toast(&format!("Deleted field {}", definition.label), ToastType::Success);
+                    load_definitions();
+                }
+                Err(e) => state.toast(&format!("Failed to delete field: {}", e), ToastType::Error),
+            }
+        });
+    };
+
     view! {
         <div class="page settings-page">
             <div class="page-header">
@@ -45,6 +212,163 @@ pub fn SettingsPage() -> impl IntoView {
                     </div>
                 </Card>
 
+                <Card title="Units">
+                    <div class="setting-item">
+                        <div class="setting-info">
+                            <span class="setting-label">"Weight"</span>
+                            <span class="setting-description">{move || example(12.5, MeasurementKind::Weight)}</span>
+                        </div>
+                        <select
+                            class="form-select"
+                            on:change=move |ev| {
+                                if let Some(weight) = UnitSystem::parse(&event_target_value(&ev)) {
+                                    save_weight(MeasurementPreferences { weight, ..units.get() });
+                                }
+                            }
+                        >
+                            <option value="metric" selected=move || units.get().weight == UnitSystem::Metric>"Kilograms (kg)"</option>
+                            <option value="imperial" selected=move || units.get().weight == UnitSystem::Imperial>"Pounds (lb)"</option>
+                        </select>
+                    </div>
+                    <div class="setting-item">
+                        <div class="setting-info">
+                            <span class="setting-label">"Dimensions"</span>
+                            <span class="setting-description">{move || example(30.0, MeasurementKind::Length)}</span>
+                        </div>
+                        <select
+                            class="form-select"
+                            on:change=move |ev| {
+                                if let Some(length) = UnitSystem::parse(&event_target_value(&ev)) {
+                                    save_length(MeasurementPreferences { length, ..units.get() });
+                                }
+                            }
+                        >
+                            <option value="metric" selected=move || units.get().length == UnitSystem::Metric>"Centimeters (cm)"</option>
+                            <option value="imperial" selected=move || units.get().length == UnitSystem::Imperial>"Inches (in)"</option>
+                        </select>
+                    </div>
+                    <div class="setting-item">
+                        <div class="setting-info">
+                            <span class="setting-label">"Distance"</span>
+                            <span class="setting-description">{move || example(42.0, MeasurementKind::Distance)}</span>
+                        </div>
+                        <select
+                            class="form-select"
+                            on:change=move |ev| {
+                                if let Some(distance) = UnitSystem::parse(&event_target_value(&ev)) {
+                                    save_distance(MeasurementPreferences { distance, ..units.get() });
+                                }
+                            }
+                        >
+                            <option value="metric" selected=move || units.get().distance == UnitSystem::Metric>"Kilometers (km)"</option>
+                            <option value="imperial" selected=move || units.get().distance == UnitSystem::Imperial>"Miles (mi)"</option>
+                        </select>
+                    </div>
+                </Card>
+
+                <Card title="Custom Fields">
+                    <div class="setting-item">
+                        <div class="setting-info">
+                            <span class="setting-label">"Record"</span>
+                            <span class="setting-description">"Extra fields shown on its forms and exports"</span>
+                        </div>
+                        <select
+                            class="form-select"
+                            on:change=move |ev| field_entity.set(event_target_value(&ev))
+                        >
+                            {CUSTOM_FIELD_ENTITIES.into_iter().map(|(entity, label)| view! {
+                                <option value=entity selected=move || field_entity.get() == entity>{label}</option>
+                            }).collect::<Vec<_>>()}
+                        </select>
+                    </div>
+                    <table class="mini-table">
+                        <tbody>
+                            <For
+                                each=move || definitions.get()
+                                key=|definition| definition.id.clone()
+                                children=move |definition| {
+                                    let delete_field = delete_field.clone();
+                                    let details = format!(
+                                        "{}{}{}",
+                                        definition.field_type,
+                                        if definition.required { ", required" } else { "" },
+                                        if definition.show_in_list { ", in list" } else { "" },
+                                    );
+                                    view! {
+                                        <tr>
+                                            <td>{definition.label.clone()}</td>
+                                            <td><code>{definition.key.clone()}</code></td>
+                                            <td>{details}</td>
+                                            <td>
+                                                <button
+                                                    class="btn btn-sm btn-ghost"
+                                                    on:click=move |_| delete_field(definition.clone())
+                                                >
+                                                    "Delete"
+                                                </button>
+                                            </td>
+                                        </tr>
+                                    }
+                                }
+                            />
+                        </tbody>
+                    </table>
+                    <div class="setting-item">
+                        <input
+                            class="form-input"
+                            placeholder="Label"
+                            prop:value=move || draft.with(|d| d.label.clone())
+                            on:input=move |ev| draft.update(|d| d.label = input_target_value(&ev))
+                        />
+                        <select
+                            class="form-select"
+                            on:change=move |ev| draft.update(|d| d.field_type = event_target_value(&ev))
+                        >
+                            <option value="text">"Text"</option>
+                            <option value="number">"Number"</option>
+                            <option value="date">"Date"</option>
+                            <option value="select">"Select"</option>
+                        </select>
+                        <Show when=move || draft.with(|d| d.field_type == "select")>
+                            <input
+                                class="form-input"
+                                placeholder="Options, comma separated"
+                                prop:value=move || draft_options.get()
+                                on:input=move |ev| draft_options.set(input_target_value(&ev))
+                            />
+                        </Show>
+                    </div>
+                    <div class="setting-item">
+                        <label>
+                            <input
+                                type="checkbox"
+                                prop:checked=move || draft.with(|d| d.required)
+                                on:change=move |ev| draft.update(|d| d.required = input_target_checked(&ev))
+                            />
+                            " Required"
+                        </label>
+                        <label>
+                            <input
+                                type="checkbox"
+                                prop:checked=move || draft.with(|d| d.show_in_list)
+                                on:change=move |ev| draft.update(|d| d.show_in_list = input_target_checked(&ev))
+                            />
+                            " Show in list"
+                        </label>
+                        <button
+                            class="btn btn-secondary"
+                            disabled=move || draft.with(|d| field_key(&d.label).is_empty())
+                            on:click=add_field
+                        >
+                            "Add Field"
+                        </button>
+                    </div>
+                    <Show when=move || definitions.with(|d| !d.is_empty())>
+                        <span class="setting-description">"Form preview"</span>
+                        <CustomFieldInputs definitions=definitions values=preview />
+                    </Show>
+                </Card>
+
                 <Card title="Synchronization">
                     <div class="setting-item">
                         <div class="setting-info">
@@ -61,15 +385,47 @@ pub fn SettingsPage() -> impl IntoView {
                             <span class="setting-label">"Sync Interval"</span>
                             <span class="setting-description">"How often to sync in background"</span>
                         </div>
-                        <select class="form-select">
-                            <option>"Every 5 minutes"</option>
-                            <option>"Every 15 minutes"</option>
-                            <option>"Every 30 minutes"</option>
-                            <option>"Manual only"</option>
+                        <select
+                            class="form-select"
+                            prop:value=move || sync_interval.get().map(|m| m.to_string()).unwrap_or_default()
+                            on:change=save_sync_interval
+                        >
+                            <option value="5">"Every 5 minutes"</option>
+                            <option value="15">"Every 15 minutes"</option>
+                            <option value="30">"Every 30 minutes"</option>
+                            <option value="">"Manual only"</option>
                         </select>
                     </div>
                 </Card>
 
+                <Card title="Backup & Restore">
+                    <div class="setting-item">
+                        <div class="setting-info">
+                            <span class="setting-label">"Passphrase"</span>
+                            <span class="setting-description">"Optional; required again to restore"</span>
+                        </div>
+                        <input
+                            type="password"
+                            class="form-input"
+                            prop:value=move || passphrase.get()
+                            on:input=move |ev| passphrase.set(input_target_value(&ev))
+                        />
+                    </div>
+                    <div class="setting-item">
+                        <div class="setting-info">
+                            <span class="setting-label">"Local Database"</span>
+                            <span class="setting-description">"Back up before replacing a device"</span>
+                        </div>
+                        <div class="setting-actions">
+                            <button class="btn btn-secondary" on:click=create_backup>"Create Backup"</button>
+                            <button class="btn btn-secondary" on:click=restore_backup>"Restore Backup"</button>
+                        </div>
+                    </div>
+                    {move || state.backup_progress.get().map(|p| view! {
+                        <progress class="backup-progress" max="100" value=format!("{:.0}", p * 100.0)></progress>
+                    })}
+                </Card>
+
                 <Card title="Notifications">
                     <div class="setting-item">
                         <div class="setting-info">
@@ -1,12 +1,81 @@
 //! Shipping Page
 
 use leptos::prelude::*;
+use leptos::context::use_context;
+use leptos::ev::Event;
+use leptos::task::spawn_local;
 use leptos_router::components::A;
-use crate::components::Card;
+use leptos_router::hooks::use_params_map;
+use wasm_bindgen::JsCast;
+use crate::api;
+use crate::components::{Badge, BadgeVariant, Card, SearchInput};
+use crate::list_state::{use_list_state, ListPager, PAGE_SIZE};
+use crate::state::{AppState, ToastType};
+
+fn input_target_value(ev: &Event) -> String {
+    ev.target()
+        .and_then(|t| t.dyn_into::<web_sys::HtmlInputElement>().ok())
+        .map(|t| t.value())
+        .unwrap_or_default()
+}
 
 /// Shipping list page
 #[component]
 pub fn ShippingPage() -> impl IntoView {
+    let shipments = RwSignal::new(Vec::<ShipmentRow>::new());
+    let pages = RwSignal::new(1usize);
+    let list = use_list_state("/shipping");
+    let search = list.bind_filter("q");
+
+    // Filtering and paging happen in the backend; sorting within a page here
+    Effect::new(move |_| {
+        let filter = api::ShipmentFilter {
+            status: Some(list.filter("status")).filter(|s| !s.is_empty()),
+            customer_id: None,
+            date_from: None,
+            date_to: None,
+            search: Some(list.filter("q")).filter(|q| !q.is_empty()),
+            page: list.page() as u32 + 1,
+            page_size: PAGE_SIZE as u32,
+        };
+        spawn_local(async move {
+            if let Ok(page) = api::list_shipments(filter).await {
+                pages.set((page.total_pages as usize).max(1));
+                shipments.set(page.items.into_iter().map(ShipmentRow::from).collect());
+            }
+        });
+    });
+
+    let visible = Memo::new(move |_| {
+        let mut rows = shipments.get();
+        if let Some((column, desc)) = list.sort() {
+            rows.sort_by(|a, b| match column.as_str() {
+                "customer" => a.customer.cmp(&b.customer),
+                "items" => a.items.cmp(&b.items),
+                "ship_date" => a.ship_date.cmp(&b.ship_date),
+                "status" => a.status.cmp(&b.status),
+                _ => a.number.cmp(&b.number),
+            });
+            if desc {
+                rows.reverse();
+            }
+        }
+        rows
+    });
+    let header = move |column: &'static str, label: &'static str| view! {
+        <th class="sortable" on:click=move |_| list.sort_by(column)>
+            {label} {move || list.sort_indicator(column)}
+        </th>
+    };
+    let tab = move |status: &'static str, label: &'static str| view! {
+        <button
+            class=move || if list.filter("status") == status { "tab active" } else { "tab" }
+            on:click=move |_| list.set_filter("status", status.to_string())
+        >
+            {label}
+        </button>
+    };
+
     view! {
         <div class="page shipping-page">
             <div class="page-header">
@@ -15,60 +84,98 @@ pub fn ShippingPage() -> impl IntoView {
                     <p class="subtitle">"Manage outbound shipments"</p>
                 </div>
                 <div class="page-actions">
+                    <button class="btn btn-ghost" disabled=move || !list.is_modified() on:click=move |_| list.reset()>
+                        "Reset View"
+                    </button>
                     <A href="/shipping/new" attr:class="btn btn-primary">"+ New Shipment"</A>
                 </div>
             </div>
 
             <div class="shipments-tabs">
-                <button class="tab active">"All"</button>
-                <button class="tab">"Draft"</button>
-                <button class="tab">"Picking"</button>
-                <button class="tab">"Packed"</button>
-                <button class="tab">"Shipped"</button>
+                {tab("", "All")}
+                {tab("draft", "Draft")}
+                {tab("picking", "Picking")}
+                {tab("packed", "Packed")}
+                {tab("shipped", "Shipped")}
             </div>
 
             <Card>
+                <div class="table-toolbar">
+                    <SearchInput placeholder="Search by shipment #, tracking # or customer..." value=search />
+                </div>
+
                 <table class="data-table">
                     <thead>
                         <tr>
-                            <th>"Shipment #"</th>
-                            <th>"Customer"</th>
-                            <th>"Items"</th>
-                            <th>"Ship Date"</th>
-                            <th>"Status"</th>
+                            {header("number", "Shipment #")}
+                            {header("customer", "Customer")}
+                            {header("items", "Items")}
+                            {header("ship_date", "Ship Date")}
+                            {header("status", "Status")}
                             <th>"Actions"</th>
                         </tr>
                     </thead>
                     <tbody>
-                        <tr>
-                            <td><code>"SHP-00000123"</code></td>
-                            <td>"Acme Corp"</td>
-                            <td>"5"</td>
-                            <td>"2024-01-15"</td>
-                            <td><span class="badge badge-warning">"Picking"</span></td>
-                            <td>
-                                <button class="btn btn-sm btn-ghost">"View"</button>
-                                <button class="btn btn-sm btn-primary">"Print Label"</button>
-                            </td>
-                        </tr>
-                        <tr>
-                            <td><code>"SHP-00000122"</code></td>
-                            <td>"Tech Solutions"</td>
-                            <td>"12"</td>
-                            <td>"2024-01-15"</td>
-                            <td><span class="badge badge-success">"Shipped"</span></td>
-                            <td>
-                                <button class="btn btn-sm btn-ghost">"View"</button>
-                                <button class="btn btn-sm btn-ghost">"Track"</button>
-                            </td>
-                        </tr>
+                        <For
+                            each=move || visible.get()
+                            key=|shipment| shipment.number.clone()
+                            children=move |shipment| {
+                                let shipped = shipment.status == "Shipped";
+                                let status_class = if shipped { "badge-success" } else { "badge-warning" };
+                                view! {
+                                    <tr>
+                                        <td><code>{shipment.number.clone()}</code></td>
+                                        <td>{shipment.customer.clone()}</td>
+                                        <td>{shipment.items.to_string()}</td>
+                                        <td>{shipment.ship_date.clone()}</td>
+                                        <td><span class=format!("badge {}", status_class)>{shipment.status.clone()}</span></td>
+                                        <td>
+                                            <button class="btn btn-sm btn-ghost">"View"</button>
+                                            {if shipped {
+                                                view! { <button class="btn btn-sm btn-ghost">"Track"</button> }.into_any()
+                                            } else {
+                                                view! { <button class="btn btn-sm btn-primary">"Print Label"</button> }.into_any()
+                                            }}
+                                        </td>
+                                    </tr>
+                                }
+                            }
+                        />
                     </tbody>
                 </table>
+                <ListPager list=list pages=pages />
             </Card>
         </div>
     }
 }
 
+#[derive(Clone, PartialEq)]
+struct ShipmentRow {
+    number: String,
+    customer: String,
+    items: u32,
+    ship_date: String,
+    status: String,
+}
+
+impl From<api::Shipment> for ShipmentRow {
+    fn from(shipment: api::Shipment) -> Self {
+        let mut status = shipment.status;
+        if let Some(first) = status.get_mut(..1) {
+            first.make_ascii_uppercase();
+        }
+        Self {
+            number: shipment.shipment_number,
+            customer: shipment.ship_to.name,
+            items: shipment.items.len() as u32,
+            ship_date: shipment.ship_date
+                .map(|date| date.chars().take(10).collect())
+                .unwrap_or_else(|| "-".to_string()),
+            status,
+        }
+    }
+}
+
 /// New shipment page
 #[component]
 pub fn NewShipmentPage() -> impl IntoView {
@@ -128,3 +235,128 @@ pub fn NewShipmentPage() -> impl IntoView {
         </div>
     }
 }
+
+/// Shipment detail page; shows routing guide violations before confirming
+#[component]
+pub fn ShipmentDetailPage() -> impl IntoView {
+    let state = use_context::<AppState>().expect("AppState not found");
+    let params = use_params_map();
+    let id = move || params.get().get("id").map(|s| s.clone()).unwrap_or_default();
+
+    let shipment = RwSignal::new(None::<api::Shipment>);
+    let report = RwSignal::new(None::<api::ComplianceReport>);
+    let override_reason = RwSignal::new(String::new());
+
+    let load = move |shipment_id: String| {
+        spawn_local(async move {
+            if let Ok(found) = api::get_shipment(&shipment_id).await {
+                shipment.set(found);
+            }
+            if let Ok(checked) = api::check_shipment_compliance(&shipment_id).await {
+                report.set(Some(checked));
+            }
+        });
+    };
+    Effect::new(move |_| load(id()));
+
+    let is_blocked = move || report.get()
+        .is_some_and(|r| r.violations.iter().any(|v| v.is_blocking()));
+    let can_confirm = move || shipment.get().is_some_and(|s| s.status == "draft");
+
+    let confirm = move |_| {
+        let Some(current) = shipment.get_untracked() else {
+            return;
+        };
+        let compliance_override = if is_blocked() {
+            let reason = override_reason.get_untracked();
+            let Some(user) = state.user.get_untracked() else {
+                state.toast("Sign in to override shipping requirements", ToastType::Error);
+                return;
+            };
+            if reason.trim().is_empty() {
+                state.toast("Enter a reason to override shipping requirements", ToastType::Error);
+                return;
+            }
+            Some(api::ComplianceOverride { reason, user_id: user.id })
+        } else {
+            None
+        };
+
+        let state = state.clone();
+        spawn_local(async move {
+            match api::confirm_shipment(&current, compliance_override).await {
+                Ok(confirmed) => {
+                    state.toast(&format!("Shipment {} confirmed", confirmed.shipment_number), ToastType::Success);
+                    shipment.set(Some(confirmed));
+                }
+                Err(e) => {
+                    state.toast(&format!("Confirmation failed: {}", e), ToastType::Error);
+                    load(current.id);
+                }
+            }
+        });
+    };
+
+    view! {
+        <div class="page shipment-detail">
+            <div class="page-header">
+                <A href="/shipping" attr:class="back-link">"← Back to Shipping"</A>
+                <h1>"Shipment: " {move || shipment.get().map(|s| s.shipment_number).unwrap_or_else(id)}</h1>
+                <div class="page-actions">
+                    <button
+                        class="btn btn-primary"
+                        disabled=move || !can_confirm()
+                        on:click=confirm
+                    >
+                        {move || if is_blocked() { "Confirm with Override" } else { "Confirm Shipment" }}
+                    </button>
+                </div>
+            </div>
+
+            <Card title="Customer Requirements">
+                {move || match report.get() {
+                    None => view! { <p class="subtitle">"Checking requirements..."</p> }.into_any(),
+                    Some(r) if !r.has_requirements => {
+                        view! { <p class="subtitle">"No shipping requirements for this customer"</p> }.into_any()
+                    }
+                    Some(r) => view! {
+                        {r.label_notes.map(|notes| view! { <p class="label-notes">"Labels: " {notes}</p> })}
+                        {if r.violations.is_empty() {
+                            view! { <p class="subtitle">"Meets all customer requirements"</p> }.into_any()
+                        } else {
+                            view! {
+                                <ul class="compliance-violations">
+                                    {r.violations.into_iter().map(|v| {
+                                        let (variant, label) = if v.is_blocking() {
+                                            (BadgeVariant::Danger, "Blocking")
+                                        } else {
+                                            (BadgeVariant::Warning, "Warning")
+                                        };
+                                        view! {
+                                            <li>
+                                                <Badge variant=variant>{label}</Badge>
+                                                " " {v.message}
+                                            </li>
+                                        }
+                                    }).collect_view()}
+                                </ul>
+                            }.into_any()
+                        }}
+                    }.into_any(),
+                }}
+                <Show when=move || is_blocked() && can_confirm()>
+                    <div class="form-group">
+                        <label>"Override reason"</label>
+                        <input
+                            type="text"
+                            class="form-input"
+                            placeholder="Why this shipment may break the customer's requirements"
+                            prop:value=move || override_reason.get()
+                            on:input=move |ev| override_reason.set(input_target_value(&ev))
+                        />
+                    </div>
+                </Show>
+            </Card>
+        </div>
+    }
+}
@@ -1,7 +1,12 @@
 //! Timesheets Page
 
+use chrono::{Datelike, Duration, Local};
+use leptos::context::use_context;
 use leptos::prelude::*;
+use leptos::task::spawn_local;
+use crate::api;
 use crate::components::Card;
+use crate::state::AppState;
 
 #[component]
 pub fn TimesheetsPage() -> impl IntoView {
@@ -11,6 +16,30 @@ pub fn TimesheetsPage() -> impl IntoView {
         clocked_in.update(|c| *c = !*c);
     };
 
+    // This week's timesheet download
+    let state = use_context::<AppState>().expect("AppState not found");
+    let export_format = RwSignal::new("xlsx".to_string());
+    let export_status = RwSignal::new(None::<String>);
+    let export = move |_| {
+        let Some(user_id) = state.user.with_untracked(|u| u.as_ref().map(|u| u.id.clone())) else {
+            return;
+        };
+        let today = Local::now().date_naive();
+        let start = today - Duration::days(today.weekday().num_days_from_monday() as i64);
+        let format = export_format.get_untracked();
+        export_status.set(Some("Exporting timesheet...".to_string()));
+
+        spawn_local(async move {
+            match api::export_timesheet(&user_id, &start.to_string(), &today.to_string(), &format).await {
+                Ok(export) => {
+                    export.save();
+                    export_status.set(None);
+                }
+                Err(e) => export_status.set(Some(e.to_string())),
+            }
+        });
+    };
+
     view! {
         <div class="page timesheets-page">
             <div class="page-header">
@@ -19,10 +48,21 @@ pub fn TimesheetsPage() -> impl IntoView {
                     <p class="subtitle">"Track your work hours"</p>
                 </div>
                 <div class="page-actions">
-                    <button class="btn btn-secondary">"Export"</button>
+                    <select
+                        class="form-select"
+                        prop:value=move || export_format.get()
+                        on:change=move |ev| export_format.set(event_target_value(&ev))
+                    >
+                        {api::TIMESHEET_EXPORT_FORMATS.iter().map(|(value, label)| view! {
+                            <option value=*value>{*label}</option>
+                        }).collect_view()}
+                    </select>
+                    <button class="btn btn-secondary" on:click=export>"Export"</button>
                 </div>
             </div>
 
+            {move || export_status.get().map(|msg| view! { <p class="status-message">{msg}</p> })}
+
             <Card class="clock-card">
                 <div class="clock-section">
                     <div class="current-time">"09:45 AM"</div>
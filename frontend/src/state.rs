@@ -1,13 +1,18 @@
 //! Application State Management
 
+use std::collections::HashMap;
 use leptos::prelude::*;
 use serde::{Deserialize, Serialize};
+use crate::list_state::ListState;
+use crate::units::MeasurementPreferences;
 
 /// Global application state
 #[derive(Clone, Debug)]
 pub struct AppState {
     /// Current user
     pub user: RwSignal<Option<User>>,
+    /// Permission scopes of the signed-in user (None until loaded)
+    pub scopes: RwSignal<Option<Vec<String>>>,
     /// Sync status
     pub sync_status: RwSignal<SyncStatus>,
     /// Current module/page
@@ -16,16 +21,33 @@ pub struct AppState {
     pub toasts: RwSignal<Vec<Toast>>,
     /// Theme
     pub theme: RwSignal<Theme>,
+    /// Display units for weights, dimensions and distances
+    pub measurement_units: RwSignal<MeasurementPreferences>,
+    /// Most recent live update pushed by the backend
+    pub live_update: RwSignal<Option<LiveUpdate>>,
+    /// Progress (0.0-1.0) of a running backup or restore
+    pub backup_progress: RwSignal<Option<f64>>,
+    /// Progress of running forecasts, route optimizations and imports,
+    /// by operation id
+    pub operation_progress: RwSignal<HashMap<String, OperationProgress>>,
+    /// Filters, sort and position of list pages, by route, for this session
+    pub list_states: RwSignal<HashMap<String, ListState>>,
 }
 
 impl AppState {
     pub fn new() -> Self {
         Self {
             user: RwSignal::new(None),
+            scopes: RwSignal::new(None),
             sync_status: RwSignal::new(SyncStatus::default()),
             current_module: RwSignal::new(Module::Dashboard),
             toasts: RwSignal::new(Vec::new()),
             theme: RwSignal::new(Theme::Dark),
+            measurement_units: RwSignal::new(MeasurementPreferences::default()),
+            live_update: RwSignal::new(None),
+            backup_progress: RwSignal::new(None),
+            operation_progress: RwSignal::new(HashMap::new()),
+            list_states: RwSignal::new(HashMap::new()),
         }
     }
     
@@ -41,6 +63,19 @@ impl AppState {
         self.toasts.set(toasts);
     }
     
+    /// Whether the signed-in user may open `module`
+    pub fn can_access(&self, module: Module) -> bool {
+        self.has_scope(module.scope())
+    }
+    
+    /// Whether the signed-in user has a permission scope, e.g. "team_timesheets"
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.with(|scopes| match scopes {
+            Some(scopes) => scopes.iter().any(|s| s == scope),
+            None => true,
+        })
+    }
+    
     /// Remove a toast
     pub fn dismiss_toast(&self, id: &str) {
         let toasts: Vec<Toast> = self.toasts.get()
@@ -66,9 +101,39 @@ pub struct SyncStatus {
     pub is_syncing: bool,
     pub is_online: bool,
     pub pending_changes: u32,
+    /// Changes the server keeps rejecting, which need someone to look at them
+    pub stuck_changes: u32,
     pub last_sync: Option<String>,
 }
 
+/// Live update pushed from the backend (pages refresh when it changes)
+#[derive(Clone, Debug, PartialEq)]
+pub struct LiveUpdate {
+    /// Event name, e.g. "shipment.status_changed"
+    pub event: String,
+    /// ID of the record that changed
+    pub entity_id: String,
+    pub status: Option<String>,
+}
+
+/// Progress of a long-running operation
+#[derive(Clone, Debug, PartialEq)]
+pub struct OperationProgress {
+    /// "forecast", "route_optimization" or "import"
+    pub kind: String,
+    /// Rows processed or iterations done
+    pub completed: u64,
+    /// None when the amount of work isn't known up front
+    pub total: Option<u64>,
+}
+
+impl OperationProgress {
+    /// Fraction done (0.0-1.0), when the total is known
+    pub fn fraction(&self) -> Option<f64> {
+        self.total.map(|total| if total == 0 { 1.0 } else { (self.completed as f64 / total as f64).min(1.0) })
+    }
+}
+
 /// Application modules
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
 pub enum Module {
@@ -110,6 +175,20 @@ impl Module {
         }
     }
     
+    /// Permission scope the module needs (mirrors `wms_core::auth::Scope`)
+    pub fn scope(&self) -> &'static str {
+        match self {
+            Self::Dashboard => "dashboard",
+            Self::Inventory => "inventory",
+            Self::Shipping => "shipping",
+            Self::Receiving => "receiving",
+            Self::Deliveries => "deliveries",
+            Self::Customers => "customers",
+            Self::Timesheets => "timesheets",
+            Self::Settings => "settings",
+        }
+    }
+    
     pub fn path(&self) -> &'static str {
         match self {
             Self::Dashboard => "/",
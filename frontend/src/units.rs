@@ -0,0 +1,71 @@
+//! Measurement Units
+//!
+//! The backend always sends weights in kg, dimensions in cm and distances
+//! in km. These helpers render them in the units chosen in settings, using
+//! the same factors and precision as `wms_core::types`.
+
+use serde::{Deserialize, Serialize};
+
+/// Unit system for one kind of measurement
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UnitSystem {
+    #[default]
+    Metric,
+    Imperial,
+}
+
+impl UnitSystem {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "metric" => Some(Self::Metric),
+            "imperial" => Some(Self::Imperial),
+            _ => None,
+        }
+    }
+}
+
+/// Display units configured in settings
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct MeasurementPreferences {
+    pub weight: UnitSystem,
+    pub length: UnitSystem,
+    pub distance: UnitSystem,
+}
+
+/// What a canonical value measures
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MeasurementKind {
+    /// Canonical unit: kg
+    Weight,
+    /// Canonical unit: cm
+    Length,
+    /// Canonical unit: km
+    Distance,
+}
+
+/// Symbol, display units per canonical unit, and decimals shown
+fn display_unit(kind: MeasurementKind, system: UnitSystem) -> (&'static str, f64, usize) {
+    match (kind, system) {
+        (MeasurementKind::Weight, UnitSystem::Metric) => ("kg", 1.0, 2),
+        (MeasurementKind::Weight, UnitSystem::Imperial) => ("lb", 2.204622621848776, 2),
+        (MeasurementKind::Length, UnitSystem::Metric) => ("cm", 1.0, 1),
+        (MeasurementKind::Length, UnitSystem::Imperial) => ("in", 1.0 / 2.54, 2),
+        (MeasurementKind::Distance, UnitSystem::Metric) => ("km", 1.0, 2),
+        (MeasurementKind::Distance, UnitSystem::Imperial) => ("mi", 0.621371192237334, 2),
+    }
+}
+
+/// Format a canonical (metric) value in the configured display unit,
+/// e.g. `format_measurement(1.0, MeasurementKind::Weight, &prefs)` gives
+/// "2.20 lb" for imperial weights.
+pub fn format_measurement(value: f64, kind: MeasurementKind, prefs: &MeasurementPreferences) -> String {
+    let system = match kind {
+        MeasurementKind::Weight => prefs.weight,
+        MeasurementKind::Length => prefs.length,
+        MeasurementKind::Distance => prefs.distance,
+    };
+    let (symbol, per_canonical, decimals) = display_unit(kind, system);
+
+    format!("{:.*} {}", decimals, value * per_canonical, symbol)
+}
@@ -0,0 +1,89 @@
+//! Background Sync
+//! 
+//! Syncs with the server on the interval chosen in the settings and tells
+//! every window the resulting status, so the sidebar updates without polling.
+
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+use tokio::sync::{watch, RwLock};
+use tracing::{debug, info, warn};
+use wms_core::shutdown::TaskHandle;
+use wms_sync::{ConnectivityMonitor, SyncEngine, SyncSettings, SyncStatus};
+
+/// Event carrying the `SyncStatus` after every sync attempt
+pub const SYNC_STATUS_EVENT: &str = "sync://status-changed";
+
+/// Send the sync status to every window
+pub fn emit_sync_status(app: &AppHandle, status: &SyncStatus) {
+    if let Err(e) = app.emit(SYNC_STATUS_EVENT, status) {
+        warn!("Failed to emit {}: {}", SYNC_STATUS_EVENT, e);
+    }
+}
+
+/// Sync every interval until shutdown, skipping runs in offline mode. The
+/// wait starts once the previous sync has finished, so runs never overlap,
+/// and an interval sent through `settings` applies straight away.
+pub async fn run(
+    app: AppHandle,
+    engine: Arc<RwLock<SyncEngine>>,
+    offline_mode: Arc<RwLock<bool>>,
+    mut settings: watch::Receiver<SyncSettings>,
+    task: TaskHandle,
+) {
+    while !task.is_shutdown_requested() {
+        let interval = settings.borrow_and_update().interval();
+        let wait = async move {
+            match interval {
+                Some(interval) => tokio::time::sleep(interval).await,
+                // Manual sync only; wait for the interval to change
+                None => std::future::pending().await,
+            }
+        };
+        
+        tokio::select! {
+            _ = wait => {}
+            changed = settings.changed() => {
+                if changed.is_err() {
+                    break;
+                }
+                debug!("Background sync interval changed to {:?} minutes", settings.borrow().interval_minutes);
+                continue;
+            }
+            _ = task.shutdown_requested() => break,
+        }
+        
+        if *offline_mode.read().await {
+            debug!("Skipping background sync in offline mode");
+            continue;
+        }
+        
+        // Failures are recorded in the status; an error here means the sync
+        // could not start (no server configured, or one already running)
+        let result = engine.read().await.sync_now().await;
+        match result {
+            Ok(status) => emit_sync_status(&app, &status),
+            Err(e) => debug!("Background sync not started: {}", e),
+        }
+    }
+    info!("Background sync stopped");
+}
+
+/// Probe the sync server every interval until shutdown, sending the sync
+/// status to every window whenever the connection status changes
+pub async fn watch_connection(
+    app: AppHandle,
+    engine: Arc<RwLock<SyncEngine>>,
+    connectivity: Arc<ConnectivityMonitor>,
+    interval: Duration,
+    task: TaskHandle,
+) {
+    connectivity.run_prober(interval, task, move |_| {
+        let app = app.clone();
+        let engine = engine.clone();
+        tauri::async_runtime::spawn(async move {
+            let status = engine.read().await.get_status();
+            emit_sync_status(&app, &status);
+        });
+    }).await;
+}
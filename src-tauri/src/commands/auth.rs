@@ -0,0 +1,99 @@
+//! Session Command Handlers
+
+use tauri::State;
+use crate::AppState;
+use wms_core::auth::{Permissions, Scope};
+use wms_core::error::ErrorPayload;
+use wms_core::users::{NewUser, User};
+
+/// Sign in with a username and PIN; later commands are limited to what
+/// the user's role may do
+#[tauri::command]
+pub async fn login(
+    state: State<'_, AppState>,
+    username: String,
+    pin: String,
+) -> Result<Permissions, ErrorPayload> {
+    let (_, session) = state.users.login(&username, &pin).await?;
+    let permissions = Permissions::of(Some(&session));
+    *state.session.write().await = Some(session);
+    Ok(permissions)
+}
+
+/// Sign out
+#[tauri::command]
+pub async fn end_session(state: State<'_, AppState>) -> Result<(), ErrorPayload> {
+    state.context().await?;
+    
+    *state.session.write().await = None;
+    Ok(())
+}
+
+/// PIN of the admin account created on first run, for the first-run UI to
+/// show. Returned once; None afterwards and on later runs.
+#[tauri::command]
+pub async fn take_initial_admin_pin(state: State<'_, AppState>) -> Result<Option<String>, ErrorPayload> {
+    Ok(state.initial_admin_pin.write().await.take())
+}
+
+/// The signed-in user, if any
+#[tauri::command]
+pub async fn get_current_user(state: State<'_, AppState>) -> Result<Option<User>, ErrorPayload> {
+    let Some(user_id) = state.session.read().await.as_ref().map(|s| s.user_id.clone()) else {
+        return Ok(None);
+    };
+    state.users.get_user(&user_id).await.map_err(ErrorPayload::from)
+}
+
+/// Scopes the signed-in user may use, so the UI can hide the rest
+#[tauri::command]
+pub async fn get_my_permissions(state: State<'_, AppState>) -> Result<Permissions, ErrorPayload> {
+    Ok(Permissions::of(state.current_session().await?.as_ref()))
+}
+
+/// Every user account
+#[tauri::command]
+pub async fn list_users(state: State<'_, AppState>) -> Result<Vec<User>, ErrorPayload> {
+    let ctx = state.require(Scope::Settings).await?;
+
+    state.users.list_users(&ctx).await.map_err(ErrorPayload::from)
+}
+
+/// Create a user who signs in with `user.password`
+#[tauri::command]
+pub async fn create_user(
+    state: State<'_, AppState>,
+    user: NewUser,
+) -> Result<User, ErrorPayload> {
+    let ctx = state.require(Scope::Settings).await?;
+
+    state.users.create_user(&ctx, user).await.map_err(ErrorPayload::from)
+}
+
+/// Change a user's PIN; the signed-in user's own by default
+#[tauri::command]
+pub async fn set_user_password(
+    state: State<'_, AppState>,
+    user_id: Option<String>,
+    pin: String,
+) -> Result<(), ErrorPayload> {
+    let ctx = state.context().await?;
+    let user_id = match user_id {
+        Some(user_id) => user_id,
+        None => ctx.actor()?.to_string(),
+    };
+
+    state.users.set_password(&ctx, &user_id, &pin).await.map_err(ErrorPayload::from)
+}
+
+/// Enable or disable a user account
+#[tauri::command]
+pub async fn set_user_active(
+    state: State<'_, AppState>,
+    user_id: String,
+    active: bool,
+) -> Result<User, ErrorPayload> {
+    let ctx = state.require(Scope::Settings).await?;
+
+    state.users.set_active(&ctx, &user_id, active).await.map_err(ErrorPayload::from)
+}
@@ -0,0 +1,59 @@
+//! Backup Command Handlers
+
+use std::path::Path;
+use tauri::State;
+use crate::AppState;
+use wms_core::auth::Scope;
+use wms_core::backup::BackupInfo;
+use wms_core::error::ErrorPayload;
+
+/// Back up the database into a directory chosen by the user
+#[tauri::command]
+pub async fn create_backup(
+    state: State<'_, AppState>,
+    directory: String,
+    passphrase: Option<String>,
+) -> Result<BackupInfo, ErrorPayload> {
+    state.require(Scope::Settings).await?;
+    
+    state.backup
+        .create_backup(Path::new(&directory), passphrase.as_deref())
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// List backups in a directory, newest first
+#[tauri::command]
+pub async fn list_backups(
+    state: State<'_, AppState>,
+    directory: String,
+) -> Result<Vec<BackupInfo>, ErrorPayload> {
+    state.require(Scope::Settings).await?;
+    
+    state.backup
+        .list_backups(Path::new(&directory))
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Restore the database from a backup file
+#[tauri::command]
+pub async fn restore_backup(
+    state: State<'_, AppState>,
+    path: String,
+    passphrase: Option<String>,
+) -> Result<(), ErrorPayload> {
+    state.require(Scope::Settings).await?;
+    
+    // Syncs run under the read lock, so taking the write lock waits for any
+    // in-flight sync and keeps new ones off a half-swapped database
+    let sync_engine = state.sync_engine.write().await;
+    
+    state.backup
+        .restore_backup(Path::new(&path), passphrase.as_deref())
+        .await?;
+    
+    sync_engine
+        .require_full_resync()
+        .map_err(ErrorPayload::from)
+}
@@ -2,7 +2,16 @@
 
 use tauri::State;
 use crate::AppState;
-use wms_crm::{Customer, CustomerSearchQuery};
+use wms_core::auth::Scope;
+use wms_core::credit::CreditCheck;
+use chrono::NaiveDate;
+use wms_core::error::ErrorPayload;
+use wms_core::types::DateRange;
+use wms_crm::{
+    ActivityEvent, Customer, CustomerAddress, CustomerContact, CustomerSearchQuery, CustomerStats,
+    ExtractDefinition, ExtractRun, SearchResult, ShippingRequirements, StatementExport,
+    DEFAULT_ACTIVITY_LIMIT,
+};
 
 /// Get all customers with pagination
 #[tauri::command]
@@ -10,14 +19,16 @@ pub async fn get_customers(
     state: State<'_, AppState>,
     page: Option<u32>,
     page_size: Option<u32>,
-) -> Result<Vec<Customer>, String> {
+) -> Result<Vec<Customer>, ErrorPayload> {
+    state.require(Scope::Customers).await?;
+    
     let page = page.unwrap_or(1);
     let page_size = page_size.unwrap_or(50);
     
     state.crm
         .get_customers(page, page_size)
         .await
-        .map_err(|e| e.to_string())
+        .map_err(ErrorPayload::from)
 }
 
 /// Get a single customer by ID
@@ -25,11 +36,13 @@ pub async fn get_customers(
 pub async fn get_customer(
     state: State<'_, AppState>,
     customer_id: String,
-) -> Result<Option<Customer>, String> {
+) -> Result<Option<Customer>, ErrorPayload> {
+    state.require(Scope::Customers).await?;
+    
     state.crm
         .get_customer(&customer_id)
         .await
-        .map_err(|e| e.to_string())
+        .map_err(ErrorPayload::from)
 }
 
 /// Create a new customer
@@ -37,34 +50,342 @@ pub async fn get_customer(
 pub async fn create_customer(
     state: State<'_, AppState>,
     customer: Customer,
-) -> Result<Customer, String> {
+) -> Result<Customer, ErrorPayload> {
+    state.require(Scope::Customers).await?;
+    
     state.crm
         .create_customer(customer)
         .await
-        .map_err(|e| e.to_string())
+        .map_err(ErrorPayload::from)
 }
 
-/// Update an existing customer
+/// Update an existing customer; `customer.version` must match the stored version
 #[tauri::command]
 pub async fn update_customer(
     state: State<'_, AppState>,
     customer: Customer,
-) -> Result<Customer, String> {
+) -> Result<Customer, ErrorPayload> {
+    state.require(Scope::Customers).await?;
+    
     state.crm
         .update_customer(customer)
         .await
-        .map_err(|e| e.to_string())
+        .map_err(ErrorPayload::from)
+}
+
+/// Deactivate a customer, keeping its history
+#[tauri::command]
+pub async fn deactivate_customer(
+    state: State<'_, AppState>,
+    customer_id: String,
+) -> Result<Customer, ErrorPayload> {
+    state.require(Scope::Customers).await?;
+    
+    state.crm
+        .deactivate_customer(&customer_id)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Merge a duplicate customer into a survivor
+#[tauri::command]
+pub async fn merge_customers(
+    state: State<'_, AppState>,
+    survivor_id: String,
+    duplicate_id: String,
+) -> Result<Customer, ErrorPayload> {
+    state.require(Scope::MergeCustomers).await?;
+    
+    state.crm
+        .merge_customers(&survivor_id, &duplicate_id)
+        .await
+        .map_err(ErrorPayload::from)
 }
 
-/// Search customers by various criteria
+/// Add an address to a customer
+#[tauri::command]
+pub async fn add_customer_address(
+    state: State<'_, AppState>,
+    address: CustomerAddress,
+) -> Result<CustomerAddress, ErrorPayload> {
+    state.require(Scope::Customers).await?;
+    
+    state.crm
+        .add_address(address)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Update a customer address; coordinates left out are kept
+#[tauri::command]
+pub async fn update_customer_address(
+    state: State<'_, AppState>,
+    address: CustomerAddress,
+) -> Result<CustomerAddress, ErrorPayload> {
+    state.require(Scope::Customers).await?;
+    
+    state.crm
+        .update_address(address)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Delete a customer address
+#[tauri::command]
+pub async fn delete_customer_address(
+    state: State<'_, AppState>,
+    address_id: String,
+) -> Result<(), ErrorPayload> {
+    state.require(Scope::Customers).await?;
+    
+    state.crm
+        .delete_address(&address_id)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Make an address the customer's default for its type
+#[tauri::command]
+pub async fn set_default_customer_address(
+    state: State<'_, AppState>,
+    address_id: String,
+) -> Result<CustomerAddress, ErrorPayload> {
+    state.require(Scope::Customers).await?;
+    
+    state.crm
+        .set_default_address(&address_id)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Add a contact to a customer
+#[tauri::command]
+pub async fn add_customer_contact(
+    state: State<'_, AppState>,
+    contact: CustomerContact,
+) -> Result<CustomerContact, ErrorPayload> {
+    state.require(Scope::Customers).await?;
+    
+    state.crm
+        .add_contact(contact)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Update a customer contact
+#[tauri::command]
+pub async fn update_customer_contact(
+    state: State<'_, AppState>,
+    contact: CustomerContact,
+) -> Result<CustomerContact, ErrorPayload> {
+    state.require(Scope::Customers).await?;
+    
+    state.crm
+        .update_contact(contact)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Delete a customer contact
+#[tauri::command]
+pub async fn delete_customer_contact(
+    state: State<'_, AppState>,
+    contact_id: String,
+) -> Result<(), ErrorPayload> {
+    state.require(Scope::Customers).await?;
+    
+    state.crm
+        .delete_contact(&contact_id)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Make a contact the customer's primary contact
+#[tauri::command]
+pub async fn set_primary_customer_contact(
+    state: State<'_, AppState>,
+    contact_id: String,
+) -> Result<CustomerContact, ErrorPayload> {
+    state.require(Scope::Customers).await?;
+    
+    state.crm
+        .set_primary_contact(&contact_id)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Search customers with filters, sorting and a total count for paging
 #[tauri::command]
 pub async fn search_customers(
     state: State<'_, AppState>,
     query: CustomerSearchQuery,
-) -> Result<Vec<Customer>, String> {
+) -> Result<SearchResult<Customer>, ErrorPayload> {
+    state.require(Scope::Customers).await?;
+    
     state.crm
         .search_customers(query)
         .await
-        .map_err(|e| e.to_string())
+        .map_err(ErrorPayload::from)
 }
 
+/// A customer's shipments, deliveries and interactions, newest first
+#[tauri::command]
+pub async fn get_customer_activity(
+    state: State<'_, AppState>,
+    customer_id: String,
+    limit: Option<u32>,
+) -> Result<Vec<ActivityEvent>, ErrorPayload> {
+    state.require(Scope::Customers).await?;
+    
+    state.crm
+        .get_customer_activity(&customer_id, limit.unwrap_or(DEFAULT_ACTIVITY_LIMIT))
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Order totals for a customer
+#[tauri::command]
+pub async fn get_customer_stats(
+    state: State<'_, AppState>,
+    customer_id: String,
+) -> Result<CustomerStats, ErrorPayload> {
+    state.require(Scope::Customers).await?;
+    
+    state.crm
+        .get_customer_stats(&customer_id)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Check that an order fits under the customer's credit limit
+#[tauri::command]
+pub async fn validate_customer_credit(
+    state: State<'_, AppState>,
+    customer_id: String,
+    order_value: f64,
+) -> Result<CreditCheck, ErrorPayload> {
+    state.require(Scope::Customers).await?;
+    
+    state.crm
+        .validate_credit(&customer_id, order_value)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Generate a customer statement (PDF) or shipment line export (CSV)
+#[tauri::command]
+pub async fn generate_customer_statement(
+    state: State<'_, AppState>,
+    customer_id: String,
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+    format: Option<String>,
+) -> Result<StatementExport, ErrorPayload> {
+    state.require(Scope::Customers).await?;
+    
+    let format = format.unwrap_or_else(|| "pdf".to_string());
+
+    state.crm
+        .generate_customer_statement(&customer_id, DateRange::new(start_date, end_date), &format)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Get a customer's shipping requirements (routing guide), if any
+#[tauri::command]
+pub async fn get_shipping_requirements(
+    state: State<'_, AppState>,
+    customer_id: String,
+) -> Result<Option<ShippingRequirements>, ErrorPayload> {
+    state.require(Scope::Customers).await?;
+    
+    state.crm
+        .get_shipping_requirements(&customer_id)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Create or replace a customer's shipping requirements
+#[tauri::command]
+pub async fn upsert_shipping_requirements(
+    state: State<'_, AppState>,
+    requirements: ShippingRequirements,
+) -> Result<ShippingRequirements, ErrorPayload> {
+    state.require(Scope::Customers).await?;
+    
+    state.crm
+        .upsert_shipping_requirements(requirements)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Get scheduled data extracts, optionally for one customer
+#[tauri::command]
+pub async fn get_extract_definitions(
+    state: State<'_, AppState>,
+    customer_id: Option<String>,
+) -> Result<Vec<ExtractDefinition>, ErrorPayload> {
+    state.require(Scope::Customers).await?;
+    
+    state.extracts
+        .list_definitions(customer_id.as_deref())
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Create or replace a scheduled data extract
+#[tauri::command]
+pub async fn save_extract_definition(
+    state: State<'_, AppState>,
+    definition: ExtractDefinition,
+) -> Result<ExtractDefinition, ErrorPayload> {
+    state.require(Scope::Customers).await?;
+    
+    state.extracts
+        .save_definition(definition)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Delete a scheduled data extract and its run history
+#[tauri::command]
+pub async fn delete_extract_definition(
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<(), ErrorPayload> {
+    state.require(Scope::Customers).await?;
+    
+    state.extracts
+        .delete_definition(&id)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Run a data extract now, outside its schedule
+#[tauri::command]
+pub async fn run_extract_now(
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<ExtractRun, ErrorPayload> {
+    state.require(Scope::Customers).await?;
+    
+    state.extracts
+        .run_now(&id)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Get a data extract's recent runs, newest first
+#[tauri::command]
+pub async fn get_extract_history(
+    state: State<'_, AppState>,
+    definition_id: String,
+    limit: Option<u32>,
+) -> Result<Vec<ExtractRun>, ErrorPayload> {
+    state.require(Scope::Customers).await?;
+    
+    state.extracts
+        .get_run_history(&definition_id, limit.unwrap_or(50))
+        .await
+        .map_err(ErrorPayload::from)
+}
@@ -0,0 +1,56 @@
+//! Dashboard Command Handlers
+
+use chrono::Utc;
+use tauri::State;
+use crate::AppState;
+use wms_core::auth::Scope;
+use wms_core::dashboard::{
+    ActivityEntry, DailyMetric, DashboardMetrics, MetricPeriod, MetricPoint, DEFAULT_ACTIVITY_LIMIT, SPARKLINE_DAYS,
+};
+use wms_core::error::ErrorPayload;
+
+/// Get the headline dashboard metrics, each compared with the same point
+/// in the previous period (week by default)
+#[tauri::command]
+pub async fn get_dashboard_metrics(
+    state: State<'_, AppState>,
+    period: Option<MetricPeriod>,
+) -> Result<DashboardMetrics, ErrorPayload> {
+    state.require(Scope::Dashboard).await?;
+    
+    state.dashboard
+        .get_metrics(period.unwrap_or_default(), Utc::now().date_naive())
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Recorded daily values of a metric over the last `days` days (14 by
+/// default), oldest first
+#[tauri::command]
+pub async fn get_metric_history(
+    state: State<'_, AppState>,
+    metric: DailyMetric,
+    days: Option<u32>,
+) -> Result<Vec<MetricPoint>, ErrorPayload> {
+    state.require(Scope::Dashboard).await?;
+    
+    state.dashboard
+        .get_metric_history(metric, days.unwrap_or(SPARKLINE_DAYS), Utc::now().date_naive())
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Latest changes to shipments, receipts and deliveries and stock
+/// adjustments, newest first (10 by default)
+#[tauri::command]
+pub async fn recent_activity(
+    state: State<'_, AppState>,
+    limit: Option<u32>,
+) -> Result<Vec<ActivityEntry>, ErrorPayload> {
+    state.require(Scope::Dashboard).await?;
+    
+    state.dashboard
+        .recent_activity(limit.unwrap_or(DEFAULT_ACTIVITY_LIMIT))
+        .await
+        .map_err(ErrorPayload::from)
+}
@@ -1,20 +1,34 @@
 //! Delivery Command Handlers
 
+use std::collections::HashMap;
+use base64::Engine;
+use chrono::{DateTime, NaiveDate, Utc};
 use tauri::State;
+use tracing::Instrument;
 use crate::AppState;
-use wms_deliveries::{Delivery, DeliveryStatus, OptimizedRoute, GeoPoint, GeofenceResult};
+use wms_core::auth::Scope;
+use wms_core::error::{ErrorPayload, WmsError};
+use wms_core::operations::OperationKind;
+use wms_core::temperature::TemperatureReading;
+use wms_deliveries::{
+    Delivery, DeliveryQuery, DeliveryRoute, DeliveryStatus, OptimizedRoute, GeoPoint,
+    GeofenceResult, RouteBreak, RouteProgress, NotificationRule, DeliveryNotification,
+    FleetPlan, Vehicle, RouteManifest, DeliveryProof, ProofType, DriverLocation,
+};
 
-/// Get all deliveries with optional filters
+/// Get deliveries by status, scheduled day and route. Drivers only get
+/// the deliveries on their own routes.
 #[tauri::command]
 pub async fn get_deliveries(
     state: State<'_, AppState>,
-    status: Option<DeliveryStatus>,
-    date: Option<String>,
-) -> Result<Vec<Delivery>, String> {
+    query: Option<DeliveryQuery>,
+) -> Result<Vec<Delivery>, ErrorPayload> {
+    let ctx = state.require(Scope::Deliveries).await?;
+    
     state.deliveries
-        .get_deliveries(status, date.as_deref())
+        .get_deliveries(&query.unwrap_or_default(), ctx.delivery_driver())
         .await
-        .map_err(|e| e.to_string())
+        .map_err(ErrorPayload::from)
 }
 
 /// Create a new delivery
@@ -22,38 +36,284 @@ pub async fn get_deliveries(
 pub async fn create_delivery(
     state: State<'_, AppState>,
     delivery: Delivery,
-) -> Result<Delivery, String> {
+) -> Result<Delivery, ErrorPayload> {
+    state.require(Scope::Deliveries).await?;
+    
     state.deliveries
         .create_delivery(delivery)
         .await
-        .map_err(|e| e.to_string())
+        .map_err(ErrorPayload::from)
 }
 
-/// Update delivery status
+/// Update delivery status. With `expected_version` the change is rejected
+/// if the delivery was modified since the caller loaded it.
 #[tauri::command]
 pub async fn update_delivery_status(
     state: State<'_, AppState>,
     delivery_id: String,
     status: DeliveryStatus,
     location: Option<GeoPoint>,
-) -> Result<Delivery, String> {
+    expected_version: Option<i64>,
+) -> Result<Delivery, ErrorPayload> {
+    state.require(Scope::Deliveries).await?;
+    
+    match expected_version {
+        Some(version) => state.deliveries.update_status_at_version(&delivery_id, status, location, version).await,
+        None => state.deliveries.update_status(&delivery_id, status, location).await,
+    }
+    .map_err(ErrorPayload::from)
+}
+
+/// Capture a signature, photo or note (base64 `data`) at the door.
+/// Signatures and photos are also queued for sync.
+#[tauri::command]
+pub async fn attach_delivery_proof(
+    state: State<'_, AppState>,
+    delivery_id: String,
+    proof_type: ProofType,
+    data: String,
+    captured_at: Option<DateTime<Utc>>,
+    location: Option<GeoPoint>,
+) -> Result<DeliveryProof, ErrorPayload> {
+    let ctx = state.require(Scope::Deliveries).await?;
+    let content = base64::engine::general_purpose::STANDARD
+        .decode(data.trim())
+        .map_err(|e| WmsError::validation(format!("Invalid proof data: {}", e)))?;
+    
+    let proof = state.deliveries
+        .attach_proof(&ctx, &delivery_id, proof_type, content.clone(), captured_at.unwrap_or_else(Utc::now), location)
+        .instrument(ctx.span())
+        .await?;
+    
+    if let Some(attachment_id) = &proof.attachment_id {
+        let attachment = state.attachments
+            .get(attachment_id)
+            .await?
+            .ok_or_else(|| WmsError::not_found("Proof attachment not found"))?;
+        state.sync_engine.read().await
+            .queue_attachment(&attachment, &content)?;
+    }
+    Ok(proof)
+}
+
+/// Get a delivery's signatures, photos (base64) and notes
+#[tauri::command]
+pub async fn get_delivery_proofs(
+    state: State<'_, AppState>,
+    delivery_id: String,
+) -> Result<Vec<DeliveryProof>, ErrorPayload> {
+    state.require(Scope::Deliveries).await?;
+    
     state.deliveries
-        .update_status(&delivery_id, status, location)
+        .get_proofs(&delivery_id)
         .await
-        .map_err(|e| e.to_string())
+        .map_err(ErrorPayload::from)
 }
 
-/// Optimize route for multiple delivery stops
+/// Optimize route for multiple delivery stops, keeping to their delivery
+/// windows where possible. Cancellable under `operation_id`; progress is
+/// emitted per improvement pass. With `route_id` the result is saved onto
+/// that planned route.
 #[tauri::command]
 pub async fn optimize_route(
     state: State<'_, AppState>,
     delivery_ids: Vec<String>,
     start_location: GeoPoint,
-) -> Result<OptimizedRoute, String> {
+    route_id: Option<String>,
+    operation_id: Option<String>,
+) -> Result<OptimizedRoute, ErrorPayload> {
+    state.require(Scope::Deliveries).await?;
+    let operation = state.start_operation(OperationKind::RouteOptimization, operation_id)?;
+    
+    let optimized = state.deliveries
+        .optimize_route(&delivery_ids, start_location, operation.token())
+        .await?;
+    if let Some(route_id) = route_id {
+        state.deliveries
+            .apply_optimized_route(&route_id, &optimized)
+            .await?;
+    }
+    Ok(optimized)
+}
+
+/// Split deliveries between vehicles by capacity and shift, optimizing
+/// each vehicle's route. All active vehicles are used unless
+/// `vehicle_ids` is given. Cancellable under `operation_id`.
+#[tauri::command]
+pub async fn optimize_fleet(
+    state: State<'_, AppState>,
+    delivery_ids: Vec<String>,
+    start_location: GeoPoint,
+    vehicle_ids: Option<Vec<String>>,
+    operation_id: Option<String>,
+) -> Result<FleetPlan, ErrorPayload> {
+    state.require(Scope::Deliveries).await?;
+    let operation = state.start_operation(OperationKind::RouteOptimization, operation_id)?;
+    
+    state.deliveries
+        .optimize_fleet(&delivery_ids, start_location, vehicle_ids.as_deref(), operation.token())
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Get active vehicles
+#[tauri::command]
+pub async fn get_vehicles(state: State<'_, AppState>) -> Result<Vec<Vehicle>, ErrorPayload> {
+    state.require(Scope::Deliveries).await?;
+    
     state.deliveries
-        .optimize_route(&delivery_ids, start_location)
+        .get_vehicles()
         .await
-        .map_err(|e| e.to_string())
+        .map_err(ErrorPayload::from)
+}
+
+/// Optimize deliveries into a persisted route. Deliveries already on a
+/// planned route re-optimize that route instead of creating another.
+#[tauri::command]
+pub async fn create_route(
+    state: State<'_, AppState>,
+    delivery_ids: Vec<String>,
+    start_location: GeoPoint,
+    route_date: NaiveDate,
+    driver_id: Option<String>,
+) -> Result<DeliveryRoute, ErrorPayload> {
+    let ctx = state.require(Scope::Deliveries).await?;
+    
+    state.deliveries
+        .create_route(&ctx, &delivery_ids, start_location, route_date, driver_id.as_deref())
+        .instrument(ctx.span())
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Re-run optimization for a planned route
+#[tauri::command]
+pub async fn reoptimize_route(
+    state: State<'_, AppState>,
+    route_id: String,
+) -> Result<DeliveryRoute, ErrorPayload> {
+    state.require(Scope::Deliveries).await?;
+    
+    state.deliveries
+        .reoptimize_route(&route_id)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Start a route; its waiting deliveries go en route
+#[tauri::command]
+pub async fn start_route(
+    state: State<'_, AppState>,
+    route_id: String,
+    start_odometer_km: Option<f64>,
+) -> Result<DeliveryRoute, ErrorPayload> {
+    state.require(Scope::Deliveries).await?;
+    
+    state.deliveries
+        .start_route(&route_id, start_odometer_km)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Complete a route. Undelivered stops need a reason in
+/// `failure_reasons`, keyed by delivery id.
+#[tauri::command]
+pub async fn complete_route(
+    state: State<'_, AppState>,
+    route_id: String,
+    end_odometer_km: Option<f64>,
+    failure_reasons: Option<HashMap<String, String>>,
+) -> Result<DeliveryRoute, ErrorPayload> {
+    state.require(Scope::Deliveries).await?;
+    
+    state.deliveries
+        .complete_route(&route_id, end_odometer_km, &failure_reasons.unwrap_or_default())
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Get a route's stops in driving order with addresses and instructions.
+/// Drivers only get their own routes.
+#[tauri::command]
+pub async fn get_route_manifest(
+    state: State<'_, AppState>,
+    route_id: String,
+) -> Result<RouteManifest, ErrorPayload> {
+    let ctx = state.require(Scope::Deliveries).await?;
+    
+    let manifest = state.deliveries
+        .get_route_manifest(&route_id)
+        .await?;
+    if ctx.delivery_driver().is_some_and(|driver_id| manifest.driver_id.as_deref() != Some(driver_id)) {
+        return Err(WmsError::not_found("Route not found").into());
+    }
+    Ok(manifest)
+}
+
+/// Get the routes planned for a date. Drivers only get their own.
+#[tauri::command]
+pub async fn get_routes(
+    state: State<'_, AppState>,
+    date: NaiveDate,
+) -> Result<Vec<DeliveryRoute>, ErrorPayload> {
+    let ctx = state.require(Scope::Deliveries).await?;
+    
+    let mut routes = state.deliveries
+        .get_routes(date)
+        .await?;
+    if let Some(driver_id) = ctx.delivery_driver() {
+        routes.retain(|route| route.driver_id.as_deref() == Some(driver_id));
+    }
+    Ok(routes)
+}
+
+/// Get a route with its stops in driving order. Drivers only get their
+/// own routes.
+#[tauri::command]
+pub async fn get_route(
+    state: State<'_, AppState>,
+    route_id: String,
+) -> Result<Option<DeliveryRoute>, ErrorPayload> {
+    let ctx = state.require(Scope::Deliveries).await?;
+    
+    let route = state.deliveries
+        .get_route(&route_id)
+        .await?;
+    Ok(match ctx.delivery_driver() {
+        Some(driver_id) => route.filter(|route| route.driver_id.as_deref() == Some(driver_id)),
+        None => route,
+    })
+}
+
+/// Get a route's driver trail for the map, thinned and without
+/// inaccurate readings
+#[tauri::command]
+pub async fn get_route_breadcrumbs(
+    state: State<'_, AppState>,
+    route_id: String,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+) -> Result<Vec<DriverLocation>, ErrorPayload> {
+    state.require(Scope::Deliveries).await?;
+    
+    state.deliveries
+        .get_route_breadcrumbs(&route_id, from, to)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Get a driver's latest recorded location
+#[tauri::command]
+pub async fn get_last_known_location(
+    state: State<'_, AppState>,
+    user_id: String,
+) -> Result<Option<DriverLocation>, ErrorPayload> {
+    state.require(Scope::Deliveries).await?;
+    
+    state.deliveries
+        .get_last_known_location(&user_id)
+        .await
+        .map_err(ErrorPayload::from)
 }
 
 /// Check if current location is within a delivery geofence
@@ -62,10 +322,119 @@ pub async fn check_geofence(
     state: State<'_, AppState>,
     delivery_id: String,
     current_location: GeoPoint,
-) -> Result<GeofenceResult, String> {
+) -> Result<GeofenceResult, ErrorPayload> {
+    state.require(Scope::Deliveries).await?;
+    
     state.deliveries
         .check_geofence(&delivery_id, current_location)
         .await
-        .map_err(|e| e.to_string())
+        .map_err(ErrorPayload::from)
+}
+
+/// Progress of a route with fresh arrival estimates. Drivers only get
+/// their own routes.
+#[tauri::command]
+pub async fn get_route_progress(
+    state: State<'_, AppState>,
+    route_id: String,
+) -> Result<RouteProgress, ErrorPayload> {
+    let ctx = state.require(Scope::Deliveries).await?;
+    
+    if let Some(driver_id) = ctx.delivery_driver() {
+        let route = state.deliveries
+            .get_route(&route_id)
+            .await?;
+        if !route.is_some_and(|route| route.driver_id.as_deref() == Some(driver_id)) {
+            return Err(WmsError::not_found("Route not found").into());
+        }
+    }
+    
+    state.deliveries
+        .get_route_progress(&route_id)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Replace the driver's scheduled breaks on a route, which arrival
+/// estimates allow for. Drivers can only plan their own.
+#[tauri::command]
+pub async fn set_route_breaks(
+    state: State<'_, AppState>,
+    route_id: String,
+    breaks: Vec<RouteBreak>,
+) -> Result<Vec<RouteBreak>, ErrorPayload> {
+    let ctx = state.require(Scope::Deliveries).await?;
+    
+    if let Some(driver_id) = ctx.delivery_driver() {
+        let route = state.deliveries
+            .get_route(&route_id)
+            .await?;
+        if !route.is_some_and(|route| route.driver_id.as_deref() == Some(driver_id)) {
+            return Err(WmsError::not_found("Route not found").into());
+        }
+    }
+    
+    state.deliveries
+        .set_route_breaks(&route_id, breaks)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Get the customer notification templates
+#[tauri::command]
+pub async fn get_notification_rules(
+    state: State<'_, AppState>,
+) -> Result<Vec<NotificationRule>, ErrorPayload> {
+    state.require(Scope::Deliveries).await?;
+    
+    state.deliveries
+        .get_notification_rules()
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Replace a trigger's notification templates
+#[tauri::command]
+pub async fn save_notification_rule(
+    state: State<'_, AppState>,
+    rule: NotificationRule,
+) -> Result<NotificationRule, ErrorPayload> {
+    state.require(Scope::Settings).await?;
+    
+    state.deliveries
+        .save_notification_rule(rule)
+        .await
+        .map_err(ErrorPayload::from)
 }
 
+/// Get the notifications queued or sent for a delivery
+#[tauri::command]
+pub async fn get_delivery_notifications(
+    state: State<'_, AppState>,
+    delivery_id: String,
+) -> Result<Vec<DeliveryNotification>, ErrorPayload> {
+    state.require(Scope::Deliveries).await?;
+    
+    state.deliveries
+        .get_delivery_notifications(&delivery_id)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Log a probe reading for a delivery. An out-of-range reading sets a
+/// temperature warning on the delivery.
+#[tauri::command]
+pub async fn record_delivery_temperature(
+    state: State<'_, AppState>,
+    delivery_id: String,
+    probe_id: Option<String>,
+    celsius: f64,
+) -> Result<TemperatureReading, ErrorPayload> {
+    let ctx = state.require(Scope::Deliveries).await?;
+    
+    state.deliveries
+        .record_temperature(&ctx, &delivery_id, probe_id.as_deref(), celsius)
+        .instrument(ctx.span())
+        .await
+        .map_err(ErrorPayload::from)
+}
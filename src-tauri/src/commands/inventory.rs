@@ -1,35 +1,93 @@
 //! Inventory Command Handlers
 
+use base64::Engine;
+use chrono::{DateTime, NaiveDate, Utc};
 use tauri::State;
+use tracing::Instrument;
 use crate::AppState;
-use wms_inventory::{InventoryItem, InventoryAdjustment, ForecastResult};
+use wms_core::auth::Scope;
+use wms_core::error::{ErrorPayload, WmsError};
+use wms_core::operations::OperationKind;
+use wms_core::attachments::Attachment;
+use wms_core::types::{DateRange, FileExport};
+use wms_inventory::{
+    InventoryItem, InventoryAdjustment, ForecastResult, ItemPrice, PriceList,
+    AvailabilityProjection, PromiseCheck, WorkloadPlan, WorkloadPlanExport,
+    LocationStock, QualityHold, HoldDisposition, ItemSearchQuery, LowStockGroup,
+    VariantAttributes, VariantAxis, VariantMatrixResult, VariantSkuRules, VariantStockSummary,
+    ReplenishmentRule, ReplenishmentTask, ForecastAccuracy, ForecastEvaluation, ForecastModel,
+    PickHeatmap, PickHeatmapExport, PickerLeaderboard, MyPickerStats, BarcodeType, StockTransfer,
+    AbcClass, CountLine, CountSheet, AbcClassificationReport,
+    DEFAULT_A_THRESHOLD_PERCENT, DEFAULT_B_THRESHOLD_PERCENT, LotBalance, ExpiringStock, StockAllocation,
+    ReorderReport, ReorderReportExport, Warehouse, ImportOptions, ImportReport,
+    InventoryExportFilter,
+};
+use wms_shipping::{item_label_png, BarcodeGenerator, BarcodeSymbology};
 
-/// Get all inventory items with optional pagination
+/// Get all inventory items with optional pagination. With `warehouse_id`,
+/// quantities count only that warehouse's stock.
 #[tauri::command]
 pub async fn get_all_items(
     state: State<'_, AppState>,
     page: Option<u32>,
     page_size: Option<u32>,
-) -> Result<Vec<InventoryItem>, String> {
+    warehouse_id: Option<String>,
+) -> Result<Vec<InventoryItem>, ErrorPayload> {
+    state.require(Scope::Inventory).await?;
+    
     let page = page.unwrap_or(1);
     let page_size = page_size.unwrap_or(50);
     
     state.inventory
-        .get_all_items(page, page_size)
+        .get_all_items(page, page_size, warehouse_id.as_deref())
         .await
-        .map_err(|e| e.to_string())
+        .map_err(ErrorPayload::from)
 }
 
-/// Get a single inventory item by SKU
+/// Get a single inventory item by SKU, with its quantity in one warehouse
+/// or across all of them
 #[tauri::command]
 pub async fn get_item_by_sku(
     state: State<'_, AppState>,
     sku: String,
-) -> Result<Option<InventoryItem>, String> {
+    warehouse_id: Option<String>,
+) -> Result<Option<InventoryItem>, ErrorPayload> {
+    state.require(Scope::Inventory).await?;
+    
     state.inventory
-        .get_item_by_sku(&sku)
+        .get_item_by_sku(&sku, warehouse_id.as_deref())
         .await
-        .map_err(|e| e.to_string())
+        .map_err(ErrorPayload::from)
+}
+
+/// Render a printable item label (PNG, base64 encoded) with the item's
+/// name, SKU and barcode. Items without a barcode get their SKU encoded;
+/// `format` picks the symbology, otherwise the item's barcode type decides.
+#[tauri::command]
+pub async fn generate_item_label(
+    state: State<'_, AppState>,
+    sku: String,
+    format: Option<BarcodeSymbology>,
+) -> Result<String, ErrorPayload> {
+    state.require(Scope::Inventory).await?;
+    
+    let item = state.inventory
+        .get_item_by_sku(&sku, None)
+        .await?
+        .ok_or_else(|| WmsError::not_found(format!("No item with SKU {}", sku)))?;
+    let barcode = item.barcode.clone().unwrap_or_else(|| item.sku.clone());
+    let format = format.or(match item.barcode_type {
+        Some(BarcodeType::Ean13) => Some(BarcodeSymbology::Ean13),
+        Some(BarcodeType::Code39) => Some(BarcodeSymbology::Code39),
+        Some(BarcodeType::Code128) => Some(BarcodeSymbology::Code128),
+        _ => None,
+    });
+    
+    let png = match format {
+        Some(format) => BarcodeGenerator::default().item_label(&item.sku, &item.name, &barcode, format),
+        None => item_label_png(&item.sku, &item.name, &barcode),
+    }?;
+    Ok(base64::engine::general_purpose::STANDARD.encode(png))
 }
 
 /// Create a new inventory item
@@ -37,58 +95,850 @@ pub async fn get_item_by_sku(
 pub async fn create_item(
     state: State<'_, AppState>,
     item: InventoryItem,
-) -> Result<InventoryItem, String> {
+) -> Result<InventoryItem, ErrorPayload> {
+    state.require(Scope::Inventory).await?;
+    
     state.inventory
         .create_item(item)
         .await
-        .map_err(|e| e.to_string())
+        .map_err(ErrorPayload::from)
 }
 
-/// Update an existing inventory item
+/// Update an existing inventory item; `item.version` must match the stored version
 #[tauri::command]
 pub async fn update_item(
     state: State<'_, AppState>,
     item: InventoryItem,
-) -> Result<InventoryItem, String> {
+) -> Result<InventoryItem, ErrorPayload> {
+    state.require(Scope::Inventory).await?;
+    
     state.inventory
         .update_item(item)
         .await
-        .map_err(|e| e.to_string())
+        .map_err(ErrorPayload::from)
 }
 
-/// Adjust inventory quantity (pick, receive, count, etc.)
+/// Import items from a CSV (base64 file contents). Rows that fail
+/// validation come back in the report instead of failing the import.
+/// Cancellable under `operation_id`.
+#[tauri::command]
+pub async fn import_items_csv(
+    state: State<'_, AppState>,
+    data: String,
+    options: Option<ImportOptions>,
+    operation_id: Option<String>,
+) -> Result<ImportReport, ErrorPayload> {
+    state.require(Scope::Inventory).await?;
+    let operation = state.start_operation(OperationKind::Import, operation_id)?;
+    
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(data.trim())
+        .map_err(|e| WmsError::validation(format!("Invalid file data: {}", e)))?;
+    
+    state.inventory
+        .import_items_csv(&bytes, options.unwrap_or_default(), operation.token())
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Adjust inventory quantity (pick, receive, count, etc.). Anything that
+/// takes stock away needs `WriteOffStock`, and is recorded under the
+/// signed-in user.
 #[tauri::command]
 pub async fn adjust_quantity(
     state: State<'_, AppState>,
-    adjustment: InventoryAdjustment,
-) -> Result<InventoryItem, String> {
+    mut adjustment: InventoryAdjustment,
+) -> Result<InventoryItem, ErrorPayload> {
+    let ctx = state.require(Scope::Inventory).await?;
+    if adjustment.removes_stock() {
+        ctx.require(Scope::WriteOffStock)?;
+    }
+    adjustment.user_id = ctx.actor()?.to_string();
+    
     state.inventory
         .adjust_quantity(adjustment)
         .await
-        .map_err(|e| e.to_string())
+        .map_err(ErrorPayload::from)
 }
 
-/// Get items below their reorder point
+/// Move stock of an item (and lot) between two locations. With
+/// `warehouse_id`, both locations must be in that warehouse.
+#[tauri::command]
+pub async fn transfer_stock(
+    state: State<'_, AppState>,
+    item_id: String,
+    from_location_id: String,
+    to_location_id: String,
+    quantity: f64,
+    lot_number: Option<String>,
+    warehouse_id: Option<String>,
+) -> Result<StockTransfer, ErrorPayload> {
+    let ctx = state.require(Scope::Inventory).await?;
+    let user_id = ctx.actor()?;
+    
+    state.inventory
+        .transfer_stock(&item_id, &from_location_id, &to_location_id, quantity, lot_number.as_deref(), warehouse_id.as_deref(), user_id)
+        .instrument(ctx.span())
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Get items below their reorder point, in one warehouse or overall
 #[tauri::command]
 pub async fn get_low_stock_items(
     state: State<'_, AppState>,
-) -> Result<Vec<InventoryItem>, String> {
+    warehouse_id: Option<String>,
+) -> Result<Vec<InventoryItem>, ErrorPayload> {
+    state.require(Scope::Inventory).await?;
+    
+    state.inventory
+        .get_low_stock_items(warehouse_id.as_deref())
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// List warehouses
+#[tauri::command]
+pub async fn list_warehouses(
+    state: State<'_, AppState>,
+) -> Result<Vec<Warehouse>, ErrorPayload> {
+    state.require(Scope::Inventory).await?;
+    
     state.inventory
-        .get_low_stock_items()
+        .list_warehouses()
         .await
-        .map_err(|e| e.to_string())
+        .map_err(ErrorPayload::from)
 }
 
-/// Run demand forecasting for an item
+/// Add a warehouse
+#[tauri::command]
+pub async fn create_warehouse(
+    state: State<'_, AppState>,
+    code: String,
+    name: String,
+    address: Option<String>,
+) -> Result<Warehouse, ErrorPayload> {
+    state.require(Scope::Inventory).await?;
+    
+    state.inventory
+        .create_warehouse(&code, &name, address.as_deref())
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Move a location into another warehouse
+#[tauri::command]
+pub async fn set_location_warehouse(
+    state: State<'_, AppState>,
+    location_id: String,
+    warehouse_id: String,
+) -> Result<(), ErrorPayload> {
+    state.require(Scope::Inventory).await?;
+    
+    state.inventory
+        .set_location_warehouse(&location_id, &warehouse_id)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Get what to order for items at or below their reorder point
+#[tauri::command]
+pub async fn get_reorder_report(
+    state: State<'_, AppState>,
+) -> Result<ReorderReport, ErrorPayload> {
+    state.require(Scope::Inventory).await?;
+    
+    state.inventory
+        .generate_reorder_report()
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Export the reorder report as CSV
+#[tauri::command]
+pub async fn export_reorder_report(
+    state: State<'_, AppState>,
+) -> Result<ReorderReportExport, ErrorPayload> {
+    state.require(Scope::Inventory).await?;
+    
+    state.inventory
+        .export_reorder_report()
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Export the items matching the Inventory page's filters as CSV or XLSX
+#[tauri::command]
+pub async fn export_inventory(
+    state: State<'_, AppState>,
+    format: String,
+    filter: Option<InventoryExportFilter>,
+) -> Result<FileExport, ErrorPayload> {
+    state.require(Scope::Inventory).await?;
+    
+    state.inventory
+        .export_items(&format, filter.unwrap_or_default())
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Run demand forecasting for an item, with the given model or the one
+/// that suits its history. Cancellable under `operation_id`.
 #[tauri::command]
 pub async fn run_forecast(
     state: State<'_, AppState>,
     sku: String,
     days_ahead: u32,
-) -> Result<ForecastResult, String> {
+    model: Option<ForecastModel>,
+    operation_id: Option<String>,
+) -> Result<ForecastResult, ErrorPayload> {
+    state.require(Scope::Inventory).await?;
+    let operation = state.start_operation(OperationKind::Forecast, operation_id)?;
+    
+    state.inventory
+        .run_forecast(&sku, days_ahead, model, operation.token())
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Score past forecast predictions against actual demand now, instead of
+/// waiting for the background evaluator
+#[tauri::command]
+pub async fn evaluate_forecasts(
+    state: State<'_, AppState>,
+) -> Result<ForecastEvaluation, ErrorPayload> {
+    state.require(Scope::Inventory).await?;
+    
+    state.inventory
+        .evaluate_forecasts()
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Rolling forecast accuracy of an item, or of all items without a SKU,
+/// comparing the forecast models
+#[tauri::command]
+pub async fn get_forecast_accuracy(
+    state: State<'_, AppState>,
+    sku: Option<String>,
+) -> Result<ForecastAccuracy, ErrorPayload> {
+    state.require(Scope::Inventory).await?;
+    
+    state.inventory
+        .get_forecast_accuracy(sku.as_deref())
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+
+/// Resolve an item's price for a customer (defaults to today's price)
+#[tauri::command]
+pub async fn get_price(
+    state: State<'_, AppState>,
+    item_id: String,
+    customer_id: Option<String>,
+    date: Option<DateTime<Utc>>,
+) -> Result<Option<ItemPrice>, ErrorPayload> {
+    state.require(Scope::Inventory).await?;
+    
+    state.inventory
+        .get_price(&item_id, customer_id.as_deref(), date.unwrap_or_else(Utc::now))
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Create or replace a price list
+#[tauri::command]
+pub async fn upsert_price_list(
+    state: State<'_, AppState>,
+    price_list: PriceList,
+) -> Result<PriceList, ErrorPayload> {
+    state.require(Scope::Inventory).await?;
+    
+    state.inventory
+        .upsert_price_list(price_list)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Get the day-by-day available-to-promise projection for an item
+#[tauri::command]
+pub async fn get_available_to_promise(
+    state: State<'_, AppState>,
+    item_id: String,
+    horizon_days: Option<u32>,
+) -> Result<AvailabilityProjection, ErrorPayload> {
+    state.require(Scope::Inventory).await?;
+    
+    state.inventory
+        .get_available_to_promise(&item_id, horizon_days.unwrap_or(14))
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Check whether a quantity can be promised by a date
+#[tauri::command]
+pub async fn check_promise(
+    state: State<'_, AppState>,
+    item_id: String,
+    quantity: f64,
+    need_by: NaiveDate,
+) -> Result<PromiseCheck, ErrorPayload> {
+    state.require(Scope::Inventory).await?;
+    
+    state.inventory
+        .check_promise(&item_id, quantity, need_by)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Get required vs scheduled labor hours for a week
+#[tauri::command]
+pub async fn get_workload_plan(
+    state: State<'_, AppState>,
+    week_start: NaiveDate,
+) -> Result<WorkloadPlan, ErrorPayload> {
+    state.require(Scope::Inventory).await?;
+    
+    state.inventory
+        .get_workload_plan(week_start)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Export a week's workload plan as CSV
+#[tauri::command]
+pub async fn export_workload_plan(
+    state: State<'_, AppState>,
+    week_start: NaiveDate,
+) -> Result<WorkloadPlanExport, ErrorPayload> {
+    state.require(Scope::Inventory).await?;
+    
+    state.inventory
+        .export_workload_plan(week_start)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Picks per location and aisle over a period, with slotting suggestions
+#[tauri::command]
+pub async fn get_pick_heatmap(
+    state: State<'_, AppState>,
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+) -> Result<PickHeatmap, ErrorPayload> {
+    state.require(Scope::Inventory).await?;
+    
+    state.inventory
+        .get_pick_heatmap(DateRange::new(start_date, end_date))
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Export a period's pick heatmap as CSV
+#[tauri::command]
+pub async fn export_pick_heatmap(
+    state: State<'_, AppState>,
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+) -> Result<PickHeatmapExport, ErrorPayload> {
+    state.require(Scope::Inventory).await?;
+    
+    state.inventory
+        .export_pick_heatmap(DateRange::new(start_date, end_date))
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Picker leaderboard for a day, or for a period when `end_date` is given
+#[tauri::command]
+pub async fn get_picker_stats(
+    state: State<'_, AppState>,
+    start_date: NaiveDate,
+    end_date: Option<NaiveDate>,
+) -> Result<PickerLeaderboard, ErrorPayload> {
+    // Ranks other people's work, so it needs the team view
+    state.require(Scope::TeamTimesheets).await?;
+    
+    state.inventory
+        .get_picker_stats(DateRange::new(start_date, end_date.unwrap_or(start_date)))
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// The signed-in picker's own stats and streaks for a day or period
+#[tauri::command]
+pub async fn get_my_stats(
+    state: State<'_, AppState>,
+    start_date: NaiveDate,
+    end_date: Option<NaiveDate>,
+) -> Result<MyPickerStats, ErrorPayload> {
+    let ctx = state.require(Scope::Dashboard).await?;
+    let user_id = ctx.actor()?;
+    
+    state.inventory
+        .get_my_stats(user_id, DateRange::new(start_date, end_date.unwrap_or(start_date)))
+        .instrument(ctx.span())
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Get an item's stock by location, with held quantities shown separately
+#[tauri::command]
+pub async fn get_item_stock_by_location(
+    state: State<'_, AppState>,
+    item_id: String,
+) -> Result<Vec<LocationStock>, ErrorPayload> {
+    state.require(Scope::Inventory).await?;
+    
+    state.inventory
+        .get_item_stock_by_location(&item_id)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Get an item's on-hand balance per lot, earliest expiry first
+#[tauri::command]
+pub async fn get_lots(
+    state: State<'_, AppState>,
+    item_id: String,
+) -> Result<Vec<LotBalance>, ErrorPayload> {
+    state.require(Scope::Inventory).await?;
+    
+    state.inventory
+        .get_lots(&item_id)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Get stock expiring within `days_ahead` days (30 by default), including
+/// stock already expired
+#[tauri::command]
+pub async fn get_expiring_stock(
+    state: State<'_, AppState>,
+    days_ahead: Option<u32>,
+) -> Result<Vec<ExpiringStock>, ErrorPayload> {
+    state.require(Scope::Inventory).await?;
+    
     state.inventory
-        .run_forecast(&sku, days_ahead)
+        .get_expiring_stock(days_ahead.unwrap_or(30))
         .await
-        .map_err(|e| e.to_string())
+        .map_err(ErrorPayload::from)
 }
 
+/// Suggest where to pick a quantity from, first-expired first-out
+#[tauri::command]
+pub async fn suggest_pick_lots(
+    state: State<'_, AppState>,
+    item_id: String,
+    quantity: f64,
+    include_expired: Option<bool>,
+) -> Result<Vec<StockAllocation>, ErrorPayload> {
+    state.require(Scope::Inventory).await?;
+    
+    state.inventory
+        .suggest_pick_lots(&item_id, quantity, include_expired.unwrap_or(false))
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Place a quality hold on a lot
+#[tauri::command]
+pub async fn place_quality_hold(
+    state: State<'_, AppState>,
+    item_id: String,
+    lot_number: String,
+    reason: String,
+) -> Result<QualityHold, ErrorPayload> {
+    let ctx = state.require(Scope::Inventory).await?;
+    
+    state.inventory
+        .place_hold(&ctx, &item_id, &lot_number, &reason)
+        .instrument(ctx.span())
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Release a quality hold with a disposition
+#[tauri::command]
+pub async fn release_quality_hold(
+    state: State<'_, AppState>,
+    hold_id: String,
+    disposition: HoldDisposition,
+    notes: Option<String>,
+) -> Result<QualityHold, ErrorPayload> {
+    let ctx = state.require(Scope::Inventory).await?;
+    
+    state.inventory
+        .release_hold(&ctx, &hold_id, disposition, notes)
+        .instrument(ctx.span())
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// List quality holds (active only unless `include_released`)
+#[tauri::command]
+pub async fn list_quality_holds(
+    state: State<'_, AppState>,
+    item_id: Option<String>,
+    include_released: Option<bool>,
+) -> Result<Vec<QualityHold>, ErrorPayload> {
+    state.require(Scope::Inventory).await?;
+    
+    state.inventory
+        .list_holds(item_id.as_deref(), include_released.unwrap_or(false))
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// List active replenishment rules, optionally for one item
+#[tauri::command]
+pub async fn get_replenishment_rules(
+    state: State<'_, AppState>,
+    item_id: Option<String>,
+) -> Result<Vec<ReplenishmentRule>, ErrorPayload> {
+    state.require(Scope::Inventory).await?;
+    
+    state.inventory
+        .list_replenishment_rules(item_id.as_deref())
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Create or update a pick-face replenishment rule
+#[tauri::command]
+pub async fn save_replenishment_rule(
+    state: State<'_, AppState>,
+    rule: ReplenishmentRule,
+) -> Result<ReplenishmentRule, ErrorPayload> {
+    state.require(Scope::Inventory).await?;
+    
+    state.inventory
+        .save_replenishment_rule(rule)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Remove a replenishment rule, cancelling its open tasks
+#[tauri::command]
+pub async fn delete_replenishment_rule(
+    state: State<'_, AppState>,
+    rule_id: String,
+) -> Result<(), ErrorPayload> {
+    state.require(Scope::Inventory).await?;
+    
+    state.inventory
+        .delete_replenishment_rule(&rule_id)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Open replenishment tasks, urgent first
+#[tauri::command]
+pub async fn get_replenishment_tasks(
+    state: State<'_, AppState>,
+) -> Result<Vec<ReplenishmentTask>, ErrorPayload> {
+    state.require(Scope::Inventory).await?;
+    
+    state.inventory
+        .get_replenishment_tasks()
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Confirm the quantity moved for a replenishment task
+#[tauri::command]
+pub async fn confirm_replenishment(
+    state: State<'_, AppState>,
+    task_id: String,
+    quantity_moved: f64,
+) -> Result<ReplenishmentTask, ErrorPayload> {
+    let ctx = state.require(Scope::Inventory).await?;
+    
+    state.inventory
+        .confirm_replenishment(&ctx, &task_id, quantity_moved)
+        .instrument(ctx.span())
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Start a cycle count of a zone or location, optionally for one ABC class
+#[tauri::command]
+pub async fn create_count_sheet(
+    state: State<'_, AppState>,
+    zone_or_location: String,
+    abc_class: Option<AbcClass>,
+) -> Result<CountSheet, ErrorPayload> {
+    let ctx = state.require(Scope::Inventory).await?;
+    let user_id = ctx.actor()?;
+    
+    state.inventory
+        .create_count_sheet(&zone_or_location, abc_class, user_id)
+        .instrument(ctx.span())
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Get a count sheet with its lines
+#[tauri::command]
+pub async fn get_count_sheet(
+    state: State<'_, AppState>,
+    count_id: String,
+) -> Result<Option<CountSheet>, ErrorPayload> {
+    state.require(Scope::Inventory).await?;
+    
+    state.inventory
+        .get_count_sheet(&count_id)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Record the quantity counted for an item (and lot) at a location
+#[tauri::command]
+pub async fn record_count(
+    state: State<'_, AppState>,
+    count_id: String,
+    item_id: String,
+    location_id: String,
+    lot_number: Option<String>,
+    counted_quantity: f64,
+) -> Result<CountLine, ErrorPayload> {
+    let ctx = state.require(Scope::Inventory).await?;
+    let user_id = ctx.actor()?;
+    
+    state.inventory
+        .record_count(&count_id, &item_id, &location_id, lot_number.as_deref(), counted_quantity, user_id)
+        .instrument(ctx.span())
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Post a counted sheet's variances to stock
+#[tauri::command]
+pub async fn post_count(
+    state: State<'_, AppState>,
+    count_id: String,
+) -> Result<CountSheet, ErrorPayload> {
+    let ctx = state.require(Scope::Inventory).await?;
+    let user_id = ctx.actor()?;
+    
+    state.inventory
+        .post_count(&count_id, user_id)
+        .instrument(ctx.span())
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Reclassify all items A/B/C by picked value. Defaults to the last year
+/// and 80/95% cutoffs.
+#[tauri::command]
+pub async fn run_abc_classification(
+    state: State<'_, AppState>,
+    period_days: Option<u32>,
+    a_threshold: Option<f64>,
+    b_threshold: Option<f64>,
+) -> Result<AbcClassificationReport, ErrorPayload> {
+    state.require(Scope::Inventory).await?;
+    
+    state.inventory
+        .run_abc_classification(
+            period_days.unwrap_or(365),
+            a_threshold.unwrap_or(DEFAULT_A_THRESHOLD_PERCENT),
+            b_threshold.unwrap_or(DEFAULT_B_THRESHOLD_PERCENT),
+        )
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Search items; with `group_by_parent` variants are shown as their parent
+#[tauri::command]
+pub async fn search_items(
+    state: State<'_, AppState>,
+    query: ItemSearchQuery,
+) -> Result<Vec<InventoryItem>, ErrorPayload> {
+    state.require(Scope::Inventory).await?;
+    
+    state.inventory
+        .search_items(query)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Get low-stock items grouped under their parent item
+#[tauri::command]
+pub async fn get_low_stock_by_parent(
+    state: State<'_, AppState>,
+) -> Result<Vec<LowStockGroup>, ErrorPayload> {
+    state.require(Scope::Inventory).await?;
+    
+    state.inventory
+        .get_low_stock_by_parent()
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Create one variant of a parent item
+#[tauri::command]
+pub async fn create_item_variant(
+    state: State<'_, AppState>,
+    parent_id: String,
+    attributes: VariantAttributes,
+    rules: Option<VariantSkuRules>,
+) -> Result<InventoryItem, ErrorPayload> {
+    state.require(Scope::Inventory).await?;
+    
+    state.inventory
+        .create_variant(&parent_id, attributes, &rules.unwrap_or_default())
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Create a variant for every combination of attribute values
+#[tauri::command]
+pub async fn create_item_variant_matrix(
+    state: State<'_, AppState>,
+    parent_id: String,
+    axes: Vec<VariantAxis>,
+    rules: Option<VariantSkuRules>,
+) -> Result<VariantMatrixResult, ErrorPayload> {
+    state.require(Scope::Inventory).await?;
+    
+    state.inventory
+        .create_variant_matrix(&parent_id, axes, &rules.unwrap_or_default())
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// List the variants of a parent item
+#[tauri::command]
+pub async fn get_item_variants(
+    state: State<'_, AppState>,
+    parent_id: String,
+) -> Result<Vec<InventoryItem>, ErrorPayload> {
+    state.require(Scope::Inventory).await?;
+    
+    state.inventory
+        .get_variants(&parent_id)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Get a parent item's stock with its variants rolled up
+#[tauri::command]
+pub async fn get_variant_stock(
+    state: State<'_, AppState>,
+    item_id: String,
+) -> Result<VariantStockSummary, ErrorPayload> {
+    state.require(Scope::Inventory).await?;
+    
+    state.inventory
+        .get_variant_stock(&item_id)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Upload an item image (base64 file contents). The image is also queued
+/// for sync.
+#[tauri::command]
+pub async fn upload_item_image(
+    state: State<'_, AppState>,
+    item_id: String,
+    filename: String,
+    data: String,
+) -> Result<Attachment, ErrorPayload> {
+    let ctx = state.require(Scope::Inventory).await?;
+    
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(data.trim())
+        .map_err(|e| WmsError::validation(format!("Invalid file data: {}", e)))?;
+    
+    let image = state.inventory
+        .add_item_image(&ctx, &item_id, &filename, bytes.clone())
+        .instrument(ctx.span())
+        .await?;
+    
+    state.sync_engine.read().await
+        .queue_attachment(&image, &bytes)?;
+    
+    Ok(image)
+}
+
+/// Get an item's images (with thumbnails), primary first
+#[tauri::command]
+pub async fn get_item_images(
+    state: State<'_, AppState>,
+    item_id: String,
+) -> Result<Vec<Attachment>, ErrorPayload> {
+    state.require(Scope::Inventory).await?;
+    
+    state.inventory
+        .get_item_images(&item_id)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Make an image the item's primary image
+#[tauri::command]
+pub async fn set_primary_item_image(
+    state: State<'_, AppState>,
+    item_id: String,
+    attachment_id: String,
+) -> Result<Attachment, ErrorPayload> {
+    state.require(Scope::Inventory).await?;
+    
+    state.inventory
+        .set_primary_image(&item_id, &attachment_id)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Get an attachment's full content (base64 encoded)
+#[tauri::command]
+pub async fn get_attachment_content(
+    state: State<'_, AppState>,
+    attachment_id: String,
+) -> Result<String, ErrorPayload> {
+    state.require(Scope::Inventory).await?;
+    
+    let content = state.attachments
+        .content(&attachment_id)
+        .await?;
+    
+    Ok(base64::engine::general_purpose::STANDARD.encode(content))
+}
+
+/// Delete an attachment
+#[tauri::command]
+pub async fn delete_attachment(
+    state: State<'_, AppState>,
+    attachment_id: String,
+) -> Result<(), ErrorPayload> {
+    state.require(Scope::Inventory).await?;
+    
+    state.attachments
+        .delete(&attachment_id)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use wms_core::error::{ErrorCode, ErrorPayload};
+    use wms_core::test_support::{test_db, ItemBuilder};
+    use wms_inventory::{InventoryItem, InventoryService};
+
+    #[tokio::test]
+    async fn test_duplicate_sku_reaches_the_frontend_as_conflict() {
+        let inventory = InventoryService::new(test_db());
+        let item: InventoryItem = ItemBuilder::new("WID-1").build();
+        inventory.create_item(item).await.unwrap();
+
+        // Same conversion create_item applies before Tauri serializes the error
+        let duplicate: InventoryItem = ItemBuilder::new("WID-1").id("wid-1-again").build();
+        let error = inventory.create_item(duplicate).await.map_err(ErrorPayload::from).unwrap_err();
+        assert_eq!(error.code, ErrorCode::Conflict);
+        let sent = serde_json::to_value(&error).unwrap();
+        assert_eq!(sent["code"], "CONFLICT");
+        assert_eq!(sent["message"], "Conflict: SKU WID-1 already exists");
+    }
+}
@@ -6,8 +6,17 @@
 pub mod inventory;
 pub mod shipping;
 pub mod receiving;
+pub mod temperature;
+pub mod printing;
 pub mod deliveries;
 pub mod crm;
 pub mod timesheets;
 pub mod sync;
+pub mod backup;
+pub mod settings;
+pub mod dashboard;
+pub mod search;
+pub mod auth;
+pub mod overrides;
+pub mod operations;
 
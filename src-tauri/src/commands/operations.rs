@@ -0,0 +1,17 @@
+//! Long-Running Operation Command Handlers
+
+use tauri::State;
+use crate::AppState;
+use wms_core::error::ErrorPayload;
+
+/// Cancel a running forecast, route optimization or import started with
+/// `operation_id`. False when it is no longer running.
+#[tauri::command]
+pub async fn cancel_operation(
+    state: State<'_, AppState>,
+    operation_id: String,
+) -> Result<bool, ErrorPayload> {
+    state.context().await?;
+    
+    Ok(state.operations.cancel(&operation_id))
+}
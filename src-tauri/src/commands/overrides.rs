@@ -0,0 +1,100 @@
+//! Supervisor Override Command Handlers
+
+use tauri::State;
+use tracing::Instrument;
+use crate::AppState;
+use wms_core::auth::Scope;
+use wms_core::error::ErrorPayload;
+use wms_core::overrides::{
+    OverrideAction, OverrideGrouping, OverrideQuery, OverrideRecord, OverrideRequest, OverrideTally,
+    OverrideToken, ReasonCode,
+};
+use wms_core::types::DateRange;
+
+/// Reason codes to offer in the approval prompt, for one action or all
+#[tauri::command]
+pub async fn get_override_reason_codes(
+    state: State<'_, AppState>,
+    action: Option<OverrideAction>,
+) -> Result<Vec<ReasonCode>, ErrorPayload> {
+    state.require(action.map_or(Scope::ApproveOverrides, |a| a.scope())).await?;
+    
+    state.overrides
+        .get_reason_codes(action)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Add or change a reason code
+#[tauri::command]
+pub async fn save_override_reason_code(
+    state: State<'_, AppState>,
+    code: ReasonCode,
+) -> Result<ReasonCode, ErrorPayload> {
+    state.require(Scope::Settings).await?;
+    
+    state.overrides
+        .save_reason_code(code)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Approve an override from the supervisor prompt. The signed-in user
+/// approves it themselves, or a supervisor enters their PIN; the token
+/// goes back with the guarded command.
+#[tauri::command]
+pub async fn request_override(
+    state: State<'_, AppState>,
+    request: OverrideRequest,
+) -> Result<OverrideToken, ErrorPayload> {
+    let ctx = state.require(request.action.scope()).await?;
+    
+    state.overrides
+        .request_override(&ctx, request)
+        .instrument(ctx.span())
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Set the signed-in supervisor's override PIN
+#[tauri::command]
+pub async fn set_override_pin(
+    state: State<'_, AppState>,
+    pin: String,
+) -> Result<(), ErrorPayload> {
+    let ctx = state.require(Scope::ApproveOverrides).await?;
+    
+    state.overrides
+        .set_override_pin(&ctx, &pin)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Recorded overrides by user, action or period
+#[tauri::command]
+pub async fn list_overrides(
+    state: State<'_, AppState>,
+    query: Option<OverrideQuery>,
+) -> Result<Vec<OverrideRecord>, ErrorPayload> {
+    state.require(Scope::ApproveOverrides).await?;
+    
+    state.overrides
+        .list_overrides(&query.unwrap_or_default())
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Override counts over a period by approver, action, reason or day
+#[tauri::command]
+pub async fn get_override_report(
+    state: State<'_, AppState>,
+    period: DateRange,
+    grouping: OverrideGrouping,
+) -> Result<Vec<OverrideTally>, ErrorPayload> {
+    state.require(Scope::ApproveOverrides).await?;
+    
+    state.overrides
+        .override_report(&period, grouping)
+        .await
+        .map_err(ErrorPayload::from)
+}
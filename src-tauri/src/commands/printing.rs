@@ -0,0 +1,93 @@
+//! Label Printing Command Handlers
+
+use tauri::State;
+use crate::AppState;
+use wms_core::auth::Scope;
+use wms_core::error::ErrorPayload;
+use wms_shipping::{PrintJob, PrintQueue, Printer};
+
+/// Register a network label printer
+#[tauri::command]
+pub async fn add_printer(
+    state: State<'_, AppState>,
+    name: String,
+    host: String,
+    port: Option<u16>,
+) -> Result<Printer, ErrorPayload> {
+    state.require(Scope::Shipping).await?;
+    
+    state.printing
+        .add_printer(&name, &host, port)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// List configured printers
+#[tauri::command]
+pub async fn list_printers(
+    state: State<'_, AppState>,
+) -> Result<Vec<Printer>, ErrorPayload> {
+    state.require(Scope::Shipping).await?;
+    
+    state.printing
+        .list_printers()
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Queue a stored label for printing
+#[tauri::command]
+pub async fn print_label(
+    state: State<'_, AppState>,
+    label_id: String,
+    printer_id: String,
+) -> Result<PrintJob, ErrorPayload> {
+    state.require(Scope::Shipping).await?;
+    
+    state.printing
+        .submit(&label_id, &printer_id)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Get a printer's open print jobs
+#[tauri::command]
+pub async fn get_print_queue(
+    state: State<'_, AppState>,
+    printer_id: String,
+) -> Result<PrintQueue, ErrorPayload> {
+    state.require(Scope::Shipping).await?;
+    
+    state.printing
+        .get_print_queue(&printer_id)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Retry a failed print job
+#[tauri::command]
+pub async fn requeue_print_job(
+    state: State<'_, AppState>,
+    job_id: String,
+) -> Result<PrintJob, ErrorPayload> {
+    state.require(Scope::Shipping).await?;
+    
+    state.printing
+        .requeue_failed(&job_id)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Cancel a print job that has not printed
+#[tauri::command]
+pub async fn cancel_print_job(
+    state: State<'_, AppState>,
+    job_id: String,
+) -> Result<PrintJob, ErrorPayload> {
+    state.require(Scope::Shipping).await?;
+    
+    state.printing
+        .cancel_job(&job_id)
+        .await
+        .map_err(ErrorPayload::from)
+}
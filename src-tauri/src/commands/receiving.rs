@@ -1,43 +1,378 @@
 //! Receiving Command Handlers
 
+use base64::Engine;
+use chrono::NaiveDate;
 use tauri::State;
+use tracing::Instrument;
 use crate::AppState;
-use wms_shipping::{Receipt, ReceiptItem, ReceiptStatus};
+use wms_core::auth::Scope;
+use wms_core::error::{ErrorPayload, WmsError};
+use wms_core::overrides::OverrideToken;
+use wms_core::temperature::TemperatureReading;
+use crate::commands::shipping::{decode_evidence_photo, queue_evidence_photo};
+use wms_core::types::DateRange;
+use wms_inventory::{InventoryItem, Location};
+use wms_shipping::{
+    AsnFormat, AsnImport, DamageClaimExport, DamageClaimReport, LineEvidence, Receipt, ReceiptCompletion,
+    ReceiptDiscrepancy, ReceiptFilter, ReceiptItem, ReceiptPage, ReceiptScanMatch, ReceiptStatus, ReceivingSummary,
+    ScanResult,
+};
 
 /// Create a new receipt for incoming goods
 #[tauri::command]
 pub async fn create_receipt(
     state: State<'_, AppState>,
     receipt: Receipt,
-) -> Result<Receipt, String> {
+) -> Result<Receipt, ErrorPayload> {
+    state.require(Scope::Receiving).await?;
+    
     state.shipping
         .create_receipt(receipt)
         .await
-        .map_err(|e| e.to_string())
+        .map_err(ErrorPayload::from)
 }
 
-/// Process a single item in a receipt (scan and verify)
+/// Get a receipt with its lines. Expected quantities of blind counts are
+/// left out unless the user may see them.
+#[tauri::command]
+pub async fn get_receipt(
+    state: State<'_, AppState>,
+    receipt_id: String,
+) -> Result<Receipt, ErrorPayload> {
+    let ctx = state.require(Scope::Receiving).await?;
+    
+    state.shipping
+        .get_receipt_for(&ctx, &receipt_id)
+        .instrument(ctx.span())
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// List receipts one page at a time, latest expected first
+#[tauri::command]
+pub async fn list_receipts(
+    state: State<'_, AppState>,
+    filter: Option<ReceiptFilter>,
+) -> Result<ReceiptPage, ErrorPayload> {
+    state.require(Scope::Receiving).await?;
+    
+    state.shipping
+        .list_receipts(filter.unwrap_or_default())
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Receipts expected and completed today, in progress, and overdue
+#[tauri::command]
+pub async fn get_receiving_summary(state: State<'_, AppState>) -> Result<ReceivingSummary, ErrorPayload> {
+    state.require(Scope::Receiving).await?;
+    
+    state.shipping
+        .get_receiving_summary()
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Process a single item in a receipt (scan and verify). Quantities over
+/// the expected need `allow_over_receipt`, and over the over-receipt
+/// tolerance a supervisor's `override_token` too. A scanned GS1-128 carton
+/// label in `gs1_scan` fills in the lot, expiry and quantity left empty.
 #[tauri::command]
 pub async fn process_receipt_item(
     state: State<'_, AppState>,
     receipt_id: String,
     item: ReceiptItem,
-) -> Result<Receipt, String> {
+    allow_over_receipt: Option<bool>,
+    override_token: Option<OverrideToken>,
+    gs1_scan: Option<String>,
+) -> Result<Receipt, ErrorPayload> {
+    let ctx = state.require(Scope::Receiving).await?;
+    let allow_over_receipt = allow_over_receipt.unwrap_or(false);
+    
+    let receipt = match gs1_scan {
+        Some(scan) => state.shipping
+            .process_receipt_gs1(&receipt_id, item, &scan, allow_over_receipt, override_token.as_ref())
+            .await,
+        None => state.shipping
+            .process_receipt_item(&receipt_id, item, allow_over_receipt, override_token.as_ref())
+            .await,
+    };
+    receipt
+        .map(|receipt| state.shipping.redact_receipt(&ctx, receipt))
+        .map_err(ErrorPayload::from)
+}
+
+/// Decode a barcode photographed while receiving and find what it refers
+/// to on the receipt: the item and its line, or an item the receipt has
+/// no line for. Nothing is received yet.
+#[tauri::command]
+pub async fn scan_receipt_image(
+    state: State<'_, AppState>,
+    receipt_id: String,
+    image_data: Vec<u8>,
+    width: u32,
+    height: u32,
+) -> Result<ReceiptScanMatch, ErrorPayload> {
+    let ctx = state.require(Scope::Receiving).await?;
+    
+    let barcode = state.shipping
+        .decode_barcode(&image_data, width, height)
+        .await?;
+    state.shipping
+        .resolve_scan(&ctx, &receipt_id, &barcode.text)
+        .instrument(ctx.span())
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Scan an item into a receipt. Repeats of the same barcode within a few
+/// seconds come back as duplicates unless `allow_duplicate`; scans past the
+/// expected quantity need `allow_over_receipt`.
+#[tauri::command]
+pub async fn scan_receipt_item(
+    state: State<'_, AppState>,
+    receipt_id: String,
+    barcode: String,
+    quantity: Option<f64>,
+    allow_duplicate: Option<bool>,
+    allow_over_receipt: Option<bool>,
+    override_token: Option<OverrideToken>,
+) -> Result<ScanResult<Receipt>, ErrorPayload> {
+    let ctx = state.require(Scope::Receiving).await?;
+    
+    let result = state.shipping
+        .scan_receipt_item(
+            &ctx,
+            &receipt_id,
+            &barcode,
+            quantity.unwrap_or(1.0),
+            allow_duplicate.unwrap_or(false),
+            allow_over_receipt.unwrap_or(false),
+            override_token.as_ref(),
+        )
+        .instrument(ctx.span())
+        .await?;
+    
+    Ok(match result {
+        ScanResult::Processed { scan, result } => ScanResult::Processed {
+            scan,
+            result: state.shipping.redact_receipt(&ctx, result),
+        },
+        duplicate => duplicate,
+    })
+}
+
+/// Scan received goods into a storage location
+#[tauri::command]
+pub async fn scan_putaway(
+    state: State<'_, AppState>,
+    receipt_id: String,
+    barcode: String,
+    location_id: String,
+    quantity: Option<f64>,
+    allow_duplicate: Option<bool>,
+) -> Result<ScanResult<InventoryItem>, ErrorPayload> {
+    let ctx = state.require(Scope::Receiving).await?;
+    
     state.shipping
-        .process_receipt_item(&receipt_id, item)
+        .scan_putaway(
+            &ctx,
+            &receipt_id,
+            &barcode,
+            &location_id,
+            quantity.unwrap_or(1.0),
+            allow_duplicate.unwrap_or(false),
+        )
+        .instrument(ctx.span())
         .await
-        .map_err(|e| e.to_string())
+        .map_err(ErrorPayload::from)
 }
 
-/// Complete a receipt and update inventory
+/// Over-receipts and other discrepancies recorded on a receipt's lines
+#[tauri::command]
+pub async fn get_receipt_discrepancies(
+    state: State<'_, AppState>,
+    receipt_id: String,
+) -> Result<Vec<ReceiptDiscrepancy>, ErrorPayload> {
+    state.require(Scope::Receiving).await?;
+    
+    state.shipping
+        .get_receipt_discrepancies(&receipt_id)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// A storage location with room for `quantity` of the item, for putaway
+#[tauri::command]
+pub async fn suggest_putaway_location(
+    state: State<'_, AppState>,
+    item_id: String,
+    quantity: f64,
+) -> Result<Option<Location>, ErrorPayload> {
+    state.require(Scope::Receiving).await?;
+    
+    state.inventory
+        .suggest_putaway_location(&item_id, quantity)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Complete a receipt and post what wasn't put away to stock, damaged
+/// units into quarantine. Blind counts off by more than the tolerance stay
+/// open until a supervisor acknowledges them.
 #[tauri::command]
 pub async fn complete_receipt(
     state: State<'_, AppState>,
     receipt_id: String,
-) -> Result<Receipt, String> {
+) -> Result<ReceiptCompletion, ErrorPayload> {
+    let ctx = state.require(Scope::Receiving).await?;
+    
+    state.shipping
+        .complete_receipt(&ctx, &receipt_id)
+        .instrument(ctx.span())
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Accept a blind count's variances and complete the receipt
+#[tauri::command]
+pub async fn acknowledge_receipt_variances(
+    state: State<'_, AppState>,
+    receipt_id: String,
+) -> Result<ReceiptCompletion, ErrorPayload> {
+    let ctx = state.require(Scope::ReceivingVariances).await?;
+    
+    state.shipping
+        .acknowledge_receipt_variances(&ctx, &receipt_id)
+        .instrument(ctx.span())
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Import a supplier's advance ship notice (base64 file contents) as a
+/// pending receipt. With `preview` the file is only parsed and matched.
+#[tauri::command]
+pub async fn import_asn(
+    state: State<'_, AppState>,
+    data: String,
+    format: AsnFormat,
+    preview: Option<bool>,
+) -> Result<AsnImport, ErrorPayload> {
+    let ctx = state.require(Scope::Receiving).await?;
+    
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(data.trim())
+        .map_err(|e| WmsError::validation(format!("Invalid file data: {}", e)))?;
+    
+    state.shipping
+        .import_asn(&ctx, &bytes, format, preview.unwrap_or(false))
+        .instrument(ctx.span())
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Create a pending receipt from a trailer manifest QR code. Pass either
+/// the decoded `payload` or a base64 `image` of the code, which is decoded
+/// here so the mobile flow is a single scan.
+#[tauri::command]
+pub async fn receive_manifest(
+    state: State<'_, AppState>,
+    payload: Option<String>,
+    image: Option<String>,
+) -> Result<AsnImport, ErrorPayload> {
+    let ctx = state.require(Scope::Receiving).await?;
+    
+    let payload = match (payload, image) {
+        (Some(payload), _) => payload,
+        (None, Some(image)) => {
+            let bytes = base64::engine::general_purpose::STANDARD
+                .decode(image.trim())
+                .map_err(|e| WmsError::validation(format!("Invalid image data: {}", e)))?;
+            state.shipping
+                .decode_qr_image(&bytes)
+                .await?
+                .text
+        }
+        (None, None) => return Err(WmsError::validation("Scan the manifest QR code or pass its payload").into()),
+    };
+    
     state.shipping
-        .complete_receipt(&receipt_id)
+        .create_receipt_from_manifest(&ctx, &payload)
+        .instrument(ctx.span())
         .await
-        .map_err(|e| e.to_string())
+        .map_err(ErrorPayload::from)
 }
 
+/// Record a damage note and/or photo (base64 file contents) against a
+/// receipt line. The photo is also queued for sync.
+#[tauri::command]
+pub async fn add_receipt_line_evidence(
+    state: State<'_, AppState>,
+    receipt_item_id: String,
+    note: Option<String>,
+    filename: Option<String>,
+    data: Option<String>,
+) -> Result<LineEvidence, ErrorPayload> {
+    let ctx = state.require(Scope::Receiving).await?;
+    
+    let photo = decode_evidence_photo(filename, data)?;
+    let bytes = photo.as_ref().map(|p| p.content.clone());
+    
+    let evidence = state.shipping
+        .add_receipt_line_evidence(&ctx, &receipt_item_id, note.as_deref(), photo)
+        .instrument(ctx.span())
+        .await?;
+    
+    queue_evidence_photo(&state, &evidence, bytes).await?;
+    Ok(evidence)
+}
+
+/// Damaged receipt lines in a period, optionally for one supplier
+#[tauri::command]
+pub async fn get_damage_claims(
+    state: State<'_, AppState>,
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+    supplier: Option<String>,
+) -> Result<DamageClaimReport, ErrorPayload> {
+    state.require(Scope::Receiving).await?;
+    
+    state.shipping
+        .get_damage_claims(DateRange::new(start_date, end_date), supplier.as_deref())
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Export supplier damage claims as a zip of photos with a CSV manifest
+#[tauri::command]
+pub async fn export_damage_claims(
+    state: State<'_, AppState>,
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+    supplier: Option<String>,
+) -> Result<DamageClaimExport, ErrorPayload> {
+    state.require(Scope::Receiving).await?;
+    
+    state.shipping
+        .export_damage_claims(DateRange::new(start_date, end_date), supplier.as_deref())
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Log a probe reading for a cold-chain receipt line. An out-of-range
+/// reading puts the line's lot on quality hold.
+#[tauri::command]
+pub async fn record_receipt_temperature(
+    state: State<'_, AppState>,
+    receipt_item_id: String,
+    probe_id: Option<String>,
+    celsius: f64,
+) -> Result<TemperatureReading, ErrorPayload> {
+    let ctx = state.require(Scope::Receiving).await?;
+    
+    state.shipping
+        .record_receipt_temperature(&ctx, &receipt_item_id, probe_id.as_deref(), celsius)
+        .instrument(ctx.span())
+        .await
+        .map_err(ErrorPayload::from)
+}
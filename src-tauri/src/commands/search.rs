@@ -0,0 +1,23 @@
+//! Search Command Handlers
+
+use tauri::State;
+use crate::AppState;
+use wms_core::error::ErrorPayload;
+use wms_core::search::GlobalSearchResults;
+
+/// Search items, customers, shipments and deliveries for the header search
+/// box, returning up to `limit` matches of each (5 by default). Groups the
+/// user's role can't see come back empty.
+#[tauri::command]
+pub async fn global_search(
+    state: State<'_, AppState>,
+    query: String,
+    limit: Option<u32>,
+) -> Result<GlobalSearchResults, ErrorPayload> {
+    let ctx = state.context().await?;
+    
+    state.search
+        .global_search(&ctx, &query, limit)
+        .await
+        .map_err(ErrorPayload::from)
+}
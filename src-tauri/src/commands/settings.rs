@@ -0,0 +1,257 @@
+//! Settings Command Handlers
+
+use tauri::State;
+use crate::AppState;
+use wms_core::auth::Scope;
+use wms_core::custom_fields::{CustomFieldDefinition, CustomFieldEntity};
+use wms_core::error::ErrorPayload;
+use wms_core::operations::OperationTimeouts;
+use wms_core::sequences::SequenceFormat;
+use wms_core::types::MeasurementPreferences;
+use wms_deliveries::DeliveryWindowSettings;
+use wms_inventory::CountVarianceTolerances;
+use wms_shipping::{BlindReceivingSettings, OverReceiptSettings, SsccSettings};
+use wms_timesheets::ClockSiteSettings;
+
+/// Get the display units for weights, dimensions and distances
+#[tauri::command]
+pub async fn get_measurement_preferences(
+    state: State<'_, AppState>,
+) -> Result<MeasurementPreferences, ErrorPayload> {
+    state.context().await?;
+    
+    MeasurementPreferences::load(&state.db).map_err(ErrorPayload::from)
+}
+
+/// Set the display units for weights, dimensions and distances
+#[tauri::command]
+pub async fn set_measurement_preferences(
+    state: State<'_, AppState>,
+    preferences: MeasurementPreferences,
+) -> Result<MeasurementPreferences, ErrorPayload> {
+    state.require(Scope::Settings).await?;
+    
+    preferences.save(&state.db)?;
+    Ok(preferences)
+}
+
+/// Get whether receipts are counted blind and the variance tolerance
+#[tauri::command]
+pub async fn get_blind_receiving_settings(
+    state: State<'_, AppState>,
+) -> Result<BlindReceivingSettings, ErrorPayload> {
+    state.context().await?;
+    
+    BlindReceivingSettings::load(&state.db).map_err(ErrorPayload::from)
+}
+
+/// Set whether receipts are counted blind and the variance tolerance
+#[tauri::command]
+pub async fn set_blind_receiving_settings(
+    state: State<'_, AppState>,
+    settings: BlindReceivingSettings,
+) -> Result<BlindReceivingSettings, ErrorPayload> {
+    state.require(Scope::Settings).await?;
+    
+    settings.save(&state.db)?;
+    Ok(settings)
+}
+
+/// Get how much more than expected may be received without a supervisor
+/// override
+#[tauri::command]
+pub async fn get_over_receipt_settings(
+    state: State<'_, AppState>,
+) -> Result<OverReceiptSettings, ErrorPayload> {
+    state.context().await?;
+    
+    OverReceiptSettings::load(&state.db).map_err(ErrorPayload::from)
+}
+
+/// Set how much more than expected may be received without a supervisor
+/// override
+#[tauri::command]
+pub async fn set_over_receipt_settings(
+    state: State<'_, AppState>,
+    settings: OverReceiptSettings,
+) -> Result<OverReceiptSettings, ErrorPayload> {
+    state.require(Scope::Settings).await?;
+    
+    settings.save(&state.db)?;
+    Ok(settings)
+}
+
+/// Get the route departure time and how many delivery windows a saved
+/// route may miss
+#[tauri::command]
+pub async fn get_delivery_window_settings(
+    state: State<'_, AppState>,
+) -> Result<DeliveryWindowSettings, ErrorPayload> {
+    state.context().await?;
+    
+    DeliveryWindowSettings::load(&state.db).map_err(ErrorPayload::from)
+}
+
+/// Set the route departure time and how many delivery windows a saved
+/// route may miss
+#[tauri::command]
+pub async fn set_delivery_window_settings(
+    state: State<'_, AppState>,
+    settings: DeliveryWindowSettings,
+) -> Result<DeliveryWindowSettings, ErrorPayload> {
+    state.require(Scope::Settings).await?;
+    
+    settings.save(&state.db)?;
+    Ok(settings)
+}
+
+/// Get the site clock punches must come from
+#[tauri::command]
+pub async fn get_clock_site_settings(
+    state: State<'_, AppState>,
+) -> Result<ClockSiteSettings, ErrorPayload> {
+    state.context().await?;
+    
+    ClockSiteSettings::load(&state.db).map_err(ErrorPayload::from)
+}
+
+/// Set the site clock punches must come from and what happens to punches
+/// from elsewhere
+#[tauri::command]
+pub async fn set_clock_site_settings(
+    state: State<'_, AppState>,
+    settings: ClockSiteSettings,
+) -> Result<ClockSiteSettings, ErrorPayload> {
+    state.require(Scope::Settings).await?;
+    
+    settings.save(&state.db)?;
+    Ok(settings)
+}
+
+/// Get the GS1 company prefix and extension digit of package license plates
+#[tauri::command]
+pub async fn get_sscc_settings(
+    state: State<'_, AppState>,
+) -> Result<SsccSettings, ErrorPayload> {
+    state.context().await?;
+    
+    SsccSettings::load(&state.db).map_err(ErrorPayload::from)
+}
+
+/// Set the GS1 company prefix and extension digit of package license plates
+#[tauri::command]
+pub async fn set_sscc_settings(
+    state: State<'_, AppState>,
+    settings: SsccSettings,
+) -> Result<SsccSettings, ErrorPayload> {
+    state.require(Scope::Settings).await?;
+    
+    settings.save(&state.db)?;
+    Ok(settings)
+}
+
+/// Get how long forecasts, route optimizations and imports may run
+/// before they are cancelled
+#[tauri::command]
+pub async fn get_operation_timeouts(
+    state: State<'_, AppState>,
+) -> Result<OperationTimeouts, ErrorPayload> {
+    state.context().await?;
+    
+    OperationTimeouts::load(&state.db).map_err(ErrorPayload::from)
+}
+
+/// Set how long forecasts, route optimizations and imports may run
+/// before they are cancelled
+#[tauri::command]
+pub async fn set_operation_timeouts(
+    state: State<'_, AppState>,
+    timeouts: OperationTimeouts,
+) -> Result<OperationTimeouts, ErrorPayload> {
+    state.require(Scope::Settings).await?;
+    
+    timeouts.save(&state.db)?;
+    Ok(timeouts)
+}
+
+/// Get the prefix and padding of shipment, receipt, delivery, route and
+/// customer numbers
+#[tauri::command]
+pub async fn get_document_number_formats(
+    state: State<'_, AppState>,
+) -> Result<Vec<SequenceFormat>, ErrorPayload> {
+    state.context().await?;
+    
+    SequenceFormat::load_all(&state.db).map_err(ErrorPayload::from)
+}
+
+/// Set the prefix and padding of one kind of document number. Numbers
+/// already issued keep their form.
+#[tauri::command]
+pub async fn set_document_number_format(
+    state: State<'_, AppState>,
+    format: SequenceFormat,
+) -> Result<SequenceFormat, ErrorPayload> {
+    state.require(Scope::Settings).await?;
+    
+    format.save(&state.db)?;
+    Ok(format)
+}
+
+/// Get how far counts may differ from expected, per ABC class, before a
+/// recount is needed
+#[tauri::command]
+pub async fn get_count_variance_tolerances(
+    state: State<'_, AppState>,
+) -> Result<CountVarianceTolerances, ErrorPayload> {
+    state.context().await?;
+    
+    CountVarianceTolerances::load(&state.db).map_err(ErrorPayload::from)
+}
+
+/// Set how far counts may differ from expected, per ABC class, before a
+/// recount is needed
+#[tauri::command]
+pub async fn set_count_variance_tolerances(
+    state: State<'_, AppState>,
+    tolerances: CountVarianceTolerances,
+) -> Result<CountVarianceTolerances, ErrorPayload> {
+    state.require(Scope::Settings).await?;
+    
+    tolerances.save(&state.db)?;
+    Ok(tolerances)
+}
+
+
+/// Get the custom field definitions, optionally for one kind of record
+#[tauri::command]
+pub async fn get_custom_field_definitions(
+    state: State<'_, AppState>,
+    entity: Option<CustomFieldEntity>,
+) -> Result<Vec<CustomFieldDefinition>, ErrorPayload> {
+    state.context().await?;
+    
+    state.custom_fields.list_definitions(entity).map_err(ErrorPayload::from)
+}
+
+/// Create a custom field, or update one when the definition has an id
+#[tauri::command]
+pub async fn save_custom_field_definition(
+    state: State<'_, AppState>,
+    definition: CustomFieldDefinition,
+) -> Result<CustomFieldDefinition, ErrorPayload> {
+    state.require(Scope::Settings).await?;
+    
+    state.custom_fields.save_definition(definition).map_err(ErrorPayload::from)
+}
+
+/// Delete a custom field and its values on every record
+#[tauri::command]
+pub async fn delete_custom_field_definition(
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<(), ErrorPayload> {
+    state.require(Scope::Settings).await?;
+    
+    state.custom_fields.delete_definition(&id).map_err(ErrorPayload::from)
+}
@@ -1,19 +1,36 @@
 //! Shipping Command Handlers
 
+use base64::Engine;
+use chrono::NaiveDate;
 use tauri::State;
+use tracing::Instrument;
 use crate::AppState;
-use wms_shipping::{Shipment, ShipmentStatus, ShippingLabel, BarcodeResult};
+use wms_core::auth::Scope;
+use wms_core::error::{ErrorPayload, WmsError};
+use wms_core::operations::OperationKind;
+use wms_core::overrides::OverrideToken;
+use wms_core::types::DateRange;
+use wms_shipping::{
+    ComplianceOverride, ComplianceReport, Shipment, ShipmentSplit, ShipmentStatus, ShippingLabel, BarcodeResult, PickList, SplitSpec,
+    TrackingEvent, WavePickList, CarrierInvoiceImport, CostReconciliation, CostReconciliationExport,
+    ScanEvent, ScanResult, ShipmentItem, DamageClaimExport, DamageClaimReport, EvidencePhoto, LineEvidence,
+    NewPackage, ShipmentPackage, LabelFormat, ShipmentFilter, ShipmentPage,
+};
 
-/// Create a new shipment
+/// Create a new shipment. Orders past the customer's credit limit need a
+/// supervisor's `override_token`.
 #[tauri::command]
 pub async fn create_shipment(
     state: State<'_, AppState>,
     shipment: Shipment,
-) -> Result<Shipment, String> {
+    override_token: Option<OverrideToken>,
+) -> Result<Shipment, ErrorPayload> {
+    state.require(Scope::Shipping).await?;
+    
     state.shipping
-        .create_shipment(shipment)
+        .create_shipment(shipment, override_token.as_ref())
         .await
-        .map_err(|e| e.to_string())
+        .map_err(ErrorPayload::from)
 }
 
 /// Get a shipment by ID
@@ -21,36 +38,350 @@ pub async fn create_shipment(
 pub async fn get_shipment(
     state: State<'_, AppState>,
     shipment_id: String,
-) -> Result<Option<Shipment>, String> {
+) -> Result<Option<Shipment>, ErrorPayload> {
+    state.require(Scope::Shipping).await?;
+    
     state.shipping
         .get_shipment(&shipment_id)
         .await
-        .map_err(|e| e.to_string())
+        .map_err(ErrorPayload::from)
 }
 
-/// Update shipment status
+/// List shipments one page at a time, newest first
+#[tauri::command]
+pub async fn list_shipments(
+    state: State<'_, AppState>,
+    filter: Option<ShipmentFilter>,
+) -> Result<ShipmentPage, ErrorPayload> {
+    state.require(Scope::Shipping).await?;
+    
+    state.shipping
+        .list_shipments(filter.unwrap_or_default())
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Update shipment status. With `expected_version` the change is rejected
+/// if the shipment was modified since the caller loaded it. Cancelling
+/// needs a reason and goes through `cancel_shipment`.
 #[tauri::command]
 pub async fn update_shipment_status(
     state: State<'_, AppState>,
     shipment_id: String,
     status: ShipmentStatus,
-) -> Result<Shipment, String> {
+    expected_version: Option<i64>,
+) -> Result<Shipment, ErrorPayload> {
+    state.require(Scope::Shipping).await?;
+    
+    match expected_version {
+        Some(version) => state.shipping.update_status_at_version(&shipment_id, status, version).await,
+        None => state.shipping.update_status(&shipment_id, status).await,
+    }
+    .map_err(ErrorPayload::from)
+}
+
+/// Cancel a shipment that hasn't shipped, putting back what was picked
+/// for it and voiding its labels
+#[tauri::command]
+pub async fn cancel_shipment(
+    state: State<'_, AppState>,
+    shipment_id: String,
+    reason: String,
+) -> Result<Shipment, ErrorPayload> {
+    let ctx = state.require(Scope::Shipping).await?;
+    
+    state.shipping
+        .cancel_shipment(&ctx, &shipment_id, &reason)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Check a shipment against its customer's shipping requirements
+#[tauri::command]
+pub async fn check_shipment_compliance(
+    state: State<'_, AppState>,
+    shipment_id: String,
+) -> Result<ComplianceReport, ErrorPayload> {
+    state.require(Scope::Shipping).await?;
+    
+    state.shipping
+        .check_shipment_compliance(&shipment_id)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Confirm a shipment. Blocking compliance violations need
+/// `compliance_override` with a reason; the override is logged against
+/// the signed-in user when there is one. An order past the customer's
+/// credit limit needs a supervisor's `override_token`.
+#[tauri::command]
+pub async fn confirm_shipment(
+    state: State<'_, AppState>,
+    shipment_id: String,
+    expected_version: Option<i64>,
+    compliance_override: Option<ComplianceOverride>,
+    override_token: Option<OverrideToken>,
+) -> Result<Shipment, ErrorPayload> {
+    let ctx = state.require(Scope::Shipping).await?;
+    
+    // A signed-in user always overrides as themselves
+    let compliance_override = compliance_override.map(|mut o| {
+        if let Some(user_id) = &ctx.user_id {
+            o.user_id = user_id.clone();
+        }
+        o
+    });
+    
+    state.shipping
+        .confirm_shipment(&shipment_id, expected_version, compliance_override, override_token.as_ref())
+        .instrument(ctx.span())
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Split a shipment, moving short quantities onto a backorder shipment
+#[tauri::command]
+pub async fn split_shipment(
+    state: State<'_, AppState>,
+    shipment_id: String,
+    spec: SplitSpec,
+) -> Result<ShipmentSplit, ErrorPayload> {
+    state.require(Scope::Shipping).await?;
+    
     state.shipping
-        .update_status(&shipment_id, status)
+        .split_shipment(&shipment_id, spec)
         .await
-        .map_err(|e| e.to_string())
+        .map_err(ErrorPayload::from)
 }
 
-/// Generate a shipping label (ZPL format for thermal printers)
+/// Generate a shipping label: ZPL for thermal printers (the default) or
+/// PDF. The label carries base64 data and its content type.
 #[tauri::command]
 pub async fn generate_shipping_label(
     state: State<'_, AppState>,
     shipment_id: String,
-) -> Result<ShippingLabel, String> {
+    format: Option<LabelFormat>,
+) -> Result<ShippingLabel, ErrorPayload> {
+    state.require(Scope::Shipping).await?;
+    
+    state.shipping
+        .generate_label(&shipment_id, format.unwrap_or_default())
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Add a carton to a shipment, with a new license plate
+#[tauri::command]
+pub async fn add_shipment_package(
+    state: State<'_, AppState>,
+    shipment_id: String,
+    package: NewPackage,
+) -> Result<ShipmentPackage, ErrorPayload> {
+    state.require(Scope::Shipping).await?;
+    
+    state.shipping
+        .add_package(&shipment_id, package)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Mark a package as packed
+#[tauri::command]
+pub async fn close_shipment_package(
+    state: State<'_, AppState>,
+    package_id: String,
+) -> Result<ShipmentPackage, ErrorPayload> {
+    state.require(Scope::Shipping).await?;
+    
+    state.shipping
+        .close_package(&package_id)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Mark a closed carton as floor-loaded, or take the mark off
+#[tauri::command]
+pub async fn set_package_floor_loaded(
+    state: State<'_, AppState>,
+    lpn: String,
+    floor_loaded: bool,
+) -> Result<ShipmentPackage, ErrorPayload> {
+    state.require(Scope::Shipping).await?;
+    
+    state.shipping
+        .set_floor_loaded(&lpn, floor_loaded)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Build closed cartons of a shipment onto a new pallet
+#[tauri::command]
+pub async fn build_pallet(
+    state: State<'_, AppState>,
+    shipment_id: String,
+    carton_lpns: Vec<String>,
+) -> Result<ShipmentPackage, ErrorPayload> {
+    state.require(Scope::Shipping).await?;
+    
+    state.shipping
+        .build_pallet(&shipment_id, &carton_lpns)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Take a pallet apart, returning its cartons
+#[tauri::command]
+pub async fn unbuild_pallet(
+    state: State<'_, AppState>,
+    pallet_lpn: String,
+) -> Result<Vec<ShipmentPackage>, ErrorPayload> {
+    state.require(Scope::Shipping).await?;
+    
+    state.shipping
+        .unbuild_pallet(&pallet_lpn)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Move a carton onto another pallet of its shipment
+#[tauri::command]
+pub async fn move_carton(
+    state: State<'_, AppState>,
+    carton_lpn: String,
+    pallet_lpn: String,
+) -> Result<ShipmentPackage, ErrorPayload> {
+    state.require(Scope::Shipping).await?;
+    
+    state.shipping
+        .move_carton(&carton_lpn, &pallet_lpn)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Generate a pallet's SSCC label (ZPL)
+#[tauri::command]
+pub async fn generate_pallet_label(
+    state: State<'_, AppState>,
+    pallet_lpn: String,
+) -> Result<ShippingLabel, ErrorPayload> {
+    state.require(Scope::Shipping).await?;
+    
+    state.shipping
+        .generate_pallet_label(&pallet_lpn)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Generate a packing slip PDF (base64 encoded)
+#[tauri::command]
+pub async fn generate_packing_slip(
+    state: State<'_, AppState>,
+    shipment_id: String,
+) -> Result<String, ErrorPayload> {
+    state.require(Scope::Shipping).await?;
+    
+    let pdf = state.shipping
+        .generate_packing_slip(&shipment_id)
+        .await?;
+    
+    Ok(base64::engine::general_purpose::STANDARD.encode(pdf))
+}
+
+/// Generate a walking-order pick list for a shipment
+#[tauri::command]
+pub async fn generate_pick_list(
+    state: State<'_, AppState>,
+    shipment_id: String,
+) -> Result<PickList, ErrorPayload> {
+    state.require(Scope::Shipping).await?;
+    
+    state.shipping
+        .generate_pick_list(&shipment_id)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Generate one walking-order pick list for a wave of shipments
+#[tauri::command]
+pub async fn generate_wave_pick_list(
+    state: State<'_, AppState>,
+    shipment_ids: Vec<String>,
+) -> Result<WavePickList, ErrorPayload> {
+    state.require(Scope::Shipping).await?;
+    
+    state.shipping
+        .generate_wave_pick_list(&shipment_ids)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Get carrier tracking events for a shipment
+#[tauri::command]
+pub async fn get_tracking_events(
+    state: State<'_, AppState>,
+    shipment_id: String,
+) -> Result<Vec<TrackingEvent>, ErrorPayload> {
+    state.require(Scope::Shipping).await?;
+    
+    state.tracking
+        .get_tracking_events(&shipment_id)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Import a carrier invoice (base64 CSV contents), matching rows to
+/// shipments by tracking number. Cancellable under `operation_id`;
+/// progress is emitted as rows are matched.
+#[tauri::command]
+pub async fn import_carrier_invoice(
+    state: State<'_, AppState>,
+    carrier_id: String,
+    data: String,
+    operation_id: Option<String>,
+) -> Result<CarrierInvoiceImport, ErrorPayload> {
+    state.require(Scope::Shipping).await?;
+    let operation = state.start_operation(OperationKind::Import, operation_id)?;
+    
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(data.trim())
+        .map_err(|e| WmsError::validation(format!("Invalid file data: {}", e)))?;
+    
+    state.shipping
+        .import_carrier_invoice(&carrier_id, &bytes, operation.token())
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Compare estimated and billed shipping costs for shipments in a period
+#[tauri::command]
+pub async fn get_cost_reconciliation(
+    state: State<'_, AppState>,
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+    threshold_percent: Option<f64>,
+) -> Result<CostReconciliation, ErrorPayload> {
+    state.require(Scope::Shipping).await?;
+    
+    state.shipping
+        .get_cost_reconciliation(DateRange::new(start_date, end_date), threshold_percent)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Export the cost reconciliation's variance lines as CSV
+#[tauri::command]
+pub async fn export_cost_reconciliation(
+    state: State<'_, AppState>,
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+    threshold_percent: Option<f64>,
+) -> Result<CostReconciliationExport, ErrorPayload> {
+    state.require(Scope::Shipping).await?;
+    
     state.shipping
-        .generate_label(&shipment_id)
+        .export_cost_reconciliation(DateRange::new(start_date, end_date), threshold_percent)
         .await
-        .map_err(|e| e.to_string())
+        .map_err(ErrorPayload::from)
 }
 
 /// Decode a barcode from image data
@@ -60,10 +391,239 @@ pub async fn scan_barcode(
     image_data: Vec<u8>,
     width: u32,
     height: u32,
-) -> Result<BarcodeResult, String> {
+) -> Result<BarcodeResult, ErrorPayload> {
+    state.require(Scope::Shipping).await?;
+    
     state.shipping
         .decode_barcode(&image_data, width, height)
         .await
-        .map_err(|e| e.to_string())
+        .map_err(ErrorPayload::from)
+}
+
+
+/// Scan an item while picking a shipment. Repeats of the same barcode
+/// within a few seconds come back as duplicates unless `allow_duplicate`.
+#[tauri::command]
+pub async fn scan_pick_item(
+    state: State<'_, AppState>,
+    shipment_id: String,
+    barcode: String,
+    quantity: Option<f64>,
+    allow_duplicate: Option<bool>,
+) -> Result<ScanResult<ShipmentItem>, ErrorPayload> {
+    let ctx = state.require(Scope::Shipping).await?;
+    
+    state.shipping
+        .scan_pick_item(&ctx, &shipment_id, &barcode, quantity.unwrap_or(1.0), allow_duplicate.unwrap_or(false))
+        .instrument(ctx.span())
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Put back a wrongly picked quantity, recording a mispick against the
+/// picker
+#[tauri::command]
+pub async fn correct_pick(
+    state: State<'_, AppState>,
+    shipment_item_id: String,
+    quantity: f64,
+    reason: Option<String>,
+) -> Result<ShipmentItem, ErrorPayload> {
+    let ctx = state.require(Scope::Shipping).await?;
+    
+    state.shipping
+        .correct_pick(&ctx, &shipment_item_id, quantity, reason.as_deref())
+        .instrument(ctx.span())
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Pick an item for a shipment from a location. Picking more than is left
+/// on the line needs `allow_over_pick`.
+#[tauri::command]
+pub async fn pick_item(
+    state: State<'_, AppState>,
+    shipment_id: String,
+    item_id: String,
+    location_id: String,
+    quantity: f64,
+    allow_over_pick: Option<bool>,
+) -> Result<ShipmentItem, ErrorPayload> {
+    let ctx = state.require(Scope::Shipping).await?;
+    
+    state.shipping
+        .pick_item(&ctx, &shipment_id, &item_id, &location_id, quantity, allow_over_pick.unwrap_or(false))
+        .instrument(ctx.span())
+        .await
+        .map_err(ErrorPayload::from)
 }
 
+/// Pick an item for a shipment from the lots that expire first
+#[tauri::command]
+pub async fn pick_item_fefo(
+    state: State<'_, AppState>,
+    shipment_id: String,
+    item_id: String,
+    quantity: f64,
+    allow_over_pick: Option<bool>,
+) -> Result<ShipmentItem, ErrorPayload> {
+    let ctx = state.require(Scope::Shipping).await?;
+    
+    state.shipping
+        .pick_item_fefo(&ctx, &shipment_id, &item_id, quantity, allow_over_pick.unwrap_or(false))
+        .instrument(ctx.span())
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Close a shipment line with less picked than ordered
+#[tauri::command]
+pub async fn short_pick_item(
+    state: State<'_, AppState>,
+    shipment_item_id: String,
+    reason: String,
+) -> Result<ShipmentItem, ErrorPayload> {
+    let ctx = state.require(Scope::Shipping).await?;
+    
+    state.shipping
+        .short_pick_item(&ctx, &shipment_item_id, &reason)
+        .instrument(ctx.span())
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Finish picking a shipment, moving it to packed once every line is
+/// picked or short-picked
+#[tauri::command]
+pub async fn confirm_picking_complete(
+    state: State<'_, AppState>,
+    shipment_id: String,
+) -> Result<Shipment, ErrorPayload> {
+    state.require(Scope::Shipping).await?;
+    
+    state.shipping
+        .confirm_picking_complete(&shipment_id)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Get the scans made against a receipt or shipment
+#[tauri::command]
+pub async fn get_scan_history(
+    state: State<'_, AppState>,
+    context_id: String,
+) -> Result<Vec<ScanEvent>, ErrorPayload> {
+    state.require(Scope::Shipping).await?;
+    
+    state.shipping
+        .get_scan_history(&context_id)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Record a damage note and/or photo (base64 file contents) against a
+/// shipment line. The photo is also queued for sync.
+#[tauri::command]
+pub async fn add_shipment_line_evidence(
+    state: State<'_, AppState>,
+    shipment_item_id: String,
+    note: Option<String>,
+    filename: Option<String>,
+    data: Option<String>,
+) -> Result<LineEvidence, ErrorPayload> {
+    let ctx = state.require(Scope::Shipping).await?;
+    
+    let photo = decode_evidence_photo(filename, data)?;
+    let bytes = photo.as_ref().map(|p| p.content.clone());
+    
+    let evidence = state.shipping
+        .add_shipment_line_evidence(&ctx, &shipment_item_id, note.as_deref(), photo)
+        .instrument(ctx.span())
+        .await?;
+    
+    queue_evidence_photo(&state, &evidence, bytes).await?;
+    Ok(evidence)
+}
+
+/// Get the damage notes and photos recorded on a receipt or shipment line
+#[tauri::command]
+pub async fn get_line_evidence(
+    state: State<'_, AppState>,
+    line_id: String,
+) -> Result<Vec<LineEvidence>, ErrorPayload> {
+    state.require(Scope::Shipping).await?;
+    
+    state.shipping
+        .get_line_evidence(&line_id)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Shipment lines with damage evidence recorded in a period
+#[tauri::command]
+pub async fn get_customer_damage_claims(
+    state: State<'_, AppState>,
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+    customer_id: Option<String>,
+) -> Result<DamageClaimReport, ErrorPayload> {
+    state.require(Scope::Shipping).await?;
+    
+    state.shipping
+        .get_customer_damage_claims(DateRange::new(start_date, end_date), customer_id.as_deref())
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Export customer damage claims as a zip of photos with a CSV manifest
+#[tauri::command]
+pub async fn export_customer_damage_claims(
+    state: State<'_, AppState>,
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+    customer_id: Option<String>,
+) -> Result<DamageClaimExport, ErrorPayload> {
+    state.require(Scope::Shipping).await?;
+    
+    state.shipping
+        .export_customer_damage_claims(DateRange::new(start_date, end_date), customer_id.as_deref())
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Decode an uploaded evidence photo, if one was sent
+pub(crate) fn decode_evidence_photo(
+    filename: Option<String>,
+    data: Option<String>,
+) -> Result<Option<EvidencePhoto>, ErrorPayload> {
+    let Some(data) = data else {
+        return Ok(None);
+    };
+    let content = base64::engine::general_purpose::STANDARD
+        .decode(data.trim())
+        .map_err(|e| WmsError::validation(format!("Invalid file data: {}", e)))?;
+    
+    Ok(Some(EvidencePhoto {
+        filename: filename.unwrap_or_else(|| "photo.jpg".to_string()),
+        content,
+    }))
+}
+
+/// Queue the photo stored with `evidence` for sync
+pub(crate) async fn queue_evidence_photo(
+    state: &AppState,
+    evidence: &LineEvidence,
+    bytes: Option<Vec<u8>>,
+) -> Result<(), ErrorPayload> {
+    let (Some(attachment_id), Some(bytes)) = (&evidence.attachment_id, bytes) else {
+        return Ok(());
+    };
+    let attachment = state.attachments
+        .get(attachment_id)
+        .await?
+        .ok_or_else(|| WmsError::not_found("Evidence photo not found"))?;
+    
+    state.sync_engine.read().await
+        .queue_attachment(&attachment, &bytes)
+        .map_err(ErrorPayload::from)
+}
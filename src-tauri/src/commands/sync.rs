@@ -1,43 +1,82 @@
 //! Sync Command Handlers
 
-use tauri::State;
+use tauri::{AppHandle, State};
+use crate::auto_sync::emit_sync_status;
 use crate::AppState;
-use wms_sync::SyncStatus;
+use wms_core::auth::Scope;
+use wms_core::error::ErrorPayload;
+use wms_sync::{SyncSettings, SyncStatus};
 
 /// Trigger a manual synchronization with the server
 #[tauri::command]
 pub async fn sync_now(
+    app: AppHandle,
     state: State<'_, AppState>,
-) -> Result<SyncStatus, String> {
-    let offline = *state.offline_mode.read().await;
-    if offline {
-        return Err("Cannot sync while in offline mode".to_string());
-    }
+) -> Result<SyncStatus, ErrorPayload> {
+    state.context().await?;
     
-    let mut sync_engine = state.sync_engine.write().await;
-    sync_engine
+    // A read lock, so status queries still answer during the round trip;
+    // the engine itself refuses a second concurrent sync
+    let sync_engine = state.sync_engine.read().await;
+    let status = sync_engine
         .sync_now()
-        .await
-        .map_err(|e| e.to_string())
+        .await?;
+    emit_sync_status(&app, &status);
+    Ok(status)
 }
 
 /// Get the current synchronization status
 #[tauri::command]
 pub async fn get_sync_status(
     state: State<'_, AppState>,
-) -> Result<SyncStatus, String> {
+) -> Result<SyncStatus, ErrorPayload> {
+    state.context().await?;
+    
     let sync_engine = state.sync_engine.read().await;
     Ok(sync_engine.get_status())
 }
 
-/// Enable or disable offline mode
+/// Enable or disable offline mode. While it is on the connection counts
+/// as offline whatever the probes find, and syncs are refused.
 #[tauri::command]
 pub async fn set_offline_mode(
+    app: AppHandle,
     state: State<'_, AppState>,
     offline: bool,
-) -> Result<bool, String> {
+) -> Result<bool, ErrorPayload> {
+    state.context().await?;
+    
     let mut mode = state.offline_mode.write().await;
     *mode = offline;
+    
+    let sync_engine = state.sync_engine.read().await;
+    sync_engine.connectivity().set_forced_offline(offline);
+    emit_sync_status(&app, &sync_engine.get_status());
     Ok(*mode)
 }
 
+
+/// Get how often the app syncs in the background
+#[tauri::command]
+pub async fn get_sync_settings(
+    state: State<'_, AppState>,
+) -> Result<SyncSettings, ErrorPayload> {
+    state.context().await?;
+    
+    SyncSettings::load(&state.db).map_err(ErrorPayload::from)
+}
+
+/// Set the minutes between background syncs; `None` syncs only on
+/// request. The running sync loop picks the change up straight away.
+#[tauri::command]
+pub async fn set_sync_interval(
+    state: State<'_, AppState>,
+    minutes: Option<u32>,
+) -> Result<SyncSettings, ErrorPayload> {
+    state.require(Scope::Settings).await?;
+    
+    let settings = SyncSettings { interval_minutes: minutes };
+    settings.save(&state.db)?;
+    state.sync_settings.send_replace(settings);
+    Ok(settings)
+}
@@ -0,0 +1,85 @@
+//! Cold-Chain Temperature Command Handlers
+
+use tauri::State;
+use crate::AppState;
+use wms_core::auth::Scope;
+use wms_core::error::ErrorPayload;
+use wms_core::temperature::{TemperatureLog, TemperatureLogExport, TemperatureOwner, TemperatureRange};
+
+/// Readings on receipt lines need receiving access, readings on
+/// deliveries need delivery access
+fn owner_scope(owner: &TemperatureOwner) -> Scope {
+    match owner {
+        TemperatureOwner::ReceiptItem(_) => Scope::Receiving,
+        TemperatureOwner::Delivery(_) => Scope::Deliveries,
+    }
+}
+
+/// Get every reading taken on a receipt line or delivery
+#[tauri::command]
+pub async fn get_temperature_log(
+    state: State<'_, AppState>,
+    owner: TemperatureOwner,
+) -> Result<TemperatureLog, ErrorPayload> {
+    state.require(owner_scope(&owner)).await?;
+    
+    state.temperature
+        .get_temperature_log(&owner)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Export a receipt line's or delivery's readings as CSV for audits
+#[tauri::command]
+pub async fn export_temperature_log(
+    state: State<'_, AppState>,
+    owner: TemperatureOwner,
+) -> Result<TemperatureLogExport, ErrorPayload> {
+    state.require(owner_scope(&owner)).await?;
+    
+    state.temperature
+        .export_temperature_log(&owner)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Get the temperature ranges of cold-chain item categories
+#[tauri::command]
+pub async fn get_temperature_ranges(
+    state: State<'_, AppState>,
+) -> Result<Vec<TemperatureRange>, ErrorPayload> {
+    state.require(Scope::Settings).await?;
+    
+    state.temperature
+        .get_ranges()
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Set the allowed temperatures for an item category
+#[tauri::command]
+pub async fn save_temperature_range(
+    state: State<'_, AppState>,
+    range: TemperatureRange,
+) -> Result<TemperatureRange, ErrorPayload> {
+    state.require(Scope::Settings).await?;
+    
+    state.temperature
+        .save_range(range)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Stop treating an item category as cold-chain
+#[tauri::command]
+pub async fn delete_temperature_range(
+    state: State<'_, AppState>,
+    category: String,
+) -> Result<(), ErrorPayload> {
+    state.require(Scope::Settings).await?;
+    
+    state.temperature
+        .delete_range(&category)
+        .await
+        .map_err(ErrorPayload::from)
+}
@@ -1,58 +1,84 @@
 //! Timesheet Command Handlers
 
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
 use tauri::State;
 use crate::AppState;
-use wms_timesheets::{TimeEntry, Timesheet, TimesheetExport};
+use wms_core::auth::Scope;
+use wms_core::error::{ErrorPayload, WmsError};
+use wms_core::types::GeoPoint;
+use wms_timesheets::{
+    TeamTimesheetSummary, TimeEntry, TimeEntryAudit, Timesheet, TimesheetExport, TimesheetPeriod,
+};
 
-/// Clock in for the current user
+/// Clock in for the current user from `location`, which must be on site
+/// unless the site settings flag instead. Only supervisors may clock in
+/// someone else.
 #[tauri::command]
 pub async fn clock_in(
     state: State<'_, AppState>,
     user_id: String,
     biometric_verified: bool,
-) -> Result<TimeEntry, String> {
+    location: Option<GeoPoint>,
+    device_id: Option<String>,
+) -> Result<TimeEntry, ErrorPayload> {
+    let ctx = state.require(Scope::Timesheets).await?;
+    ctx.require_timesheet_of(&user_id)?;
+    
     if !biometric_verified {
-        return Err("Biometric verification required for clock in".to_string());
+        return Err(WmsError::Auth("Biometric verification required for clock in".to_string()).into());
     }
     
+    let device_id = device_id.or(ctx.device_id);
     state.timesheets
-        .clock_in(&user_id)
+        .clock_in(&user_id, location, device_id.as_deref())
         .await
-        .map_err(|e| e.to_string())
+        .map_err(ErrorPayload::from)
 }
 
-/// Clock out for the current user
+/// Clock out for the current user from `location`, which must be on site
+/// unless the site settings flag instead. Only supervisors may clock out
+/// someone else.
 #[tauri::command]
 pub async fn clock_out(
     state: State<'_, AppState>,
     user_id: String,
     biometric_verified: bool,
-) -> Result<TimeEntry, String> {
+    location: Option<GeoPoint>,
+    device_id: Option<String>,
+) -> Result<TimeEntry, ErrorPayload> {
+    let ctx = state.require(Scope::Timesheets).await?;
+    ctx.require_timesheet_of(&user_id)?;
+    
     if !biometric_verified {
-        return Err("Biometric verification required for clock out".to_string());
+        return Err(WmsError::Auth("Biometric verification required for clock out".to_string()).into());
     }
     
+    let device_id = device_id.or(ctx.device_id);
     state.timesheets
-        .clock_out(&user_id)
+        .clock_out(&user_id, location, device_id.as_deref())
         .await
-        .map_err(|e| e.to_string())
+        .map_err(ErrorPayload::from)
 }
 
-/// Get timesheet for a user within a date range
+/// Get timesheet for a user within a date range. Only supervisors may
+/// see other users' timesheets.
 #[tauri::command]
 pub async fn get_timesheet(
     state: State<'_, AppState>,
     user_id: String,
     start_date: String,
     end_date: String,
-) -> Result<Timesheet, String> {
+) -> Result<Timesheet, ErrorPayload> {
+    state.require(Scope::Timesheets).await?
+        .require_timesheet_of(&user_id)?;
+    
     state.timesheets
         .get_timesheet(&user_id, &start_date, &end_date)
         .await
-        .map_err(|e| e.to_string())
+        .map_err(ErrorPayload::from)
 }
 
-/// Export timesheet data to Excel/CSV format
+/// Export timesheet data to Excel, CSV or PDF format
 #[tauri::command]
 pub async fn export_timesheet(
     state: State<'_, AppState>,
@@ -60,10 +86,168 @@ pub async fn export_timesheet(
     start_date: String,
     end_date: String,
     format: String,
-) -> Result<TimesheetExport, String> {
+) -> Result<TimesheetExport, ErrorPayload> {
+    state.require(Scope::Timesheets).await?
+        .require_timesheet_of(&user_id)?;
+    
     state.timesheets
         .export_timesheet(&user_id, &start_date, &end_date, &format)
         .await
-        .map_err(|e| e.to_string())
+        .map_err(ErrorPayload::from)
+}
+
+/// Get each user's totals over a date range with the team's totals.
+/// Without `user_ids`, everyone who worked in the range.
+#[tauri::command]
+pub async fn get_team_timesheets(
+    state: State<'_, AppState>,
+    start_date: String,
+    end_date: String,
+    user_ids: Option<Vec<String>>,
+) -> Result<TeamTimesheetSummary, ErrorPayload> {
+    state.require(Scope::TeamTimesheets).await?;
+    
+    state.timesheets
+        .get_team_summary(&start_date, &end_date, user_ids)
+        .await
+        .map_err(ErrorPayload::from)
 }
 
+/// Export the team summary to Excel, with a sheet per user, or CSV
+#[tauri::command]
+pub async fn export_team_timesheets(
+    state: State<'_, AppState>,
+    start_date: String,
+    end_date: String,
+    user_ids: Option<Vec<String>>,
+    format: String,
+) -> Result<TimesheetExport, ErrorPayload> {
+    state.require(Scope::TeamTimesheets).await?;
+    
+    state.timesheets
+        .export_team_summary(&start_date, &end_date, user_ids, &format)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Submit a user's timesheet for approval. Only supervisors may submit
+/// someone else's.
+#[tauri::command]
+pub async fn submit_timesheet(
+    state: State<'_, AppState>,
+    user_id: String,
+    start_date: String,
+    end_date: String,
+) -> Result<TimesheetPeriod, ErrorPayload> {
+    state.require(Scope::Timesheets).await?
+        .require_timesheet_of(&user_id)?;
+    
+    state.timesheets
+        .submit_timesheet(&user_id, &start_date, &end_date)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Approve a submitted timesheet as the signed-in supervisor
+#[tauri::command]
+pub async fn approve_timesheet(
+    state: State<'_, AppState>,
+    user_id: String,
+    period_id: String,
+) -> Result<TimesheetPeriod, ErrorPayload> {
+    let ctx = state.require(Scope::TeamTimesheets).await?;
+    let approver_id = ctx.actor()?;
+    
+    state.timesheets
+        .approve_timesheet(&user_id, &period_id, approver_id)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Send a submitted timesheet back to its user with a reason
+#[tauri::command]
+pub async fn reject_timesheet(
+    state: State<'_, AppState>,
+    user_id: String,
+    period_id: String,
+    reason: String,
+) -> Result<TimesheetPeriod, ErrorPayload> {
+    let ctx = state.require(Scope::TeamTimesheets).await?;
+    let approver_id = ctx.actor()?;
+    
+    state.timesheets
+        .reject_timesheet(&user_id, &period_id, approver_id, &reason)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Correct an entry on an approved timesheet (admins only)
+#[tauri::command]
+pub async fn edit_locked_time_entry(
+    state: State<'_, AppState>,
+    entry_id: String,
+    clock_in_time: DateTime<Utc>,
+    clock_out_time: DateTime<Utc>,
+    reason: String,
+) -> Result<TimeEntry, ErrorPayload> {
+    let ctx = state.require(Scope::Settings).await?;
+    let editor_id = ctx.actor()?;
+    
+    state.timesheets
+        .edit_locked_entry(&entry_id, clock_in_time, clock_out_time, editor_id, &reason)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Correct a time entry's clock times, e.g. a forgotten clock out
+/// (supervisors only)
+#[tauri::command]
+pub async fn edit_time_entry(
+    state: State<'_, AppState>,
+    entry_id: String,
+    clock_in_time: DateTime<Utc>,
+    clock_out_time: DateTime<Utc>,
+    reason: String,
+) -> Result<TimeEntry, ErrorPayload> {
+    let ctx = state.require(Scope::TeamTimesheets).await?;
+    let editor_id = ctx.actor()?;
+    
+    state.timesheets
+        .edit_entry(&entry_id, clock_in_time, clock_out_time, editor_id, &reason)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Add a time entry for a day a user worked without clocking
+/// (supervisors only)
+#[tauri::command]
+pub async fn add_manual_time_entry(
+    state: State<'_, AppState>,
+    user_id: String,
+    date: NaiveDate,
+    clock_in: NaiveTime,
+    clock_out: NaiveTime,
+    reason: String,
+) -> Result<TimeEntry, ErrorPayload> {
+    let ctx = state.require(Scope::TeamTimesheets).await?;
+    let editor_id = ctx.actor()?;
+    
+    state.timesheets
+        .add_manual_entry(&user_id, date, clock_in, clock_out, &reason, editor_id)
+        .await
+        .map_err(ErrorPayload::from)
+}
+
+/// Corrections made to a time entry
+#[tauri::command]
+pub async fn get_time_entry_audit(
+    state: State<'_, AppState>,
+    entry_id: String,
+) -> Result<Vec<TimeEntryAudit>, ErrorPayload> {
+    state.require(Scope::TeamTimesheets).await?;
+    
+    state.timesheets
+        .get_entry_audit(&entry_id)
+        .await
+        .map_err(ErrorPayload::from)
+}
@@ -0,0 +1,28 @@
+//! Event Bridge
+//! 
+//! Forwards domain events from the services to every webview window
+//! through the Tauri event system.
+
+use tauri::{AppHandle, Emitter};
+use tracing::warn;
+use wms_core::events::{EventEmitter, WmsEvent};
+
+/// Event emitter backed by the Tauri app handle
+pub struct TauriEventEmitter {
+    app: AppHandle,
+}
+
+impl TauriEventEmitter {
+    /// Create a new emitter for the given app
+    pub fn new(app: AppHandle) -> Self {
+        Self { app }
+    }
+}
+
+impl EventEmitter for TauriEventEmitter {
+    fn emit(&self, event: WmsEvent) {
+        if let Err(e) = self.app.emit(event.name(), &event) {
+            warn!("Failed to emit {}: {}", event.name(), e);
+        }
+    }
+}
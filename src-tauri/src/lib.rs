@@ -3,10 +3,12 @@
 //! This module provides the Tauri command handlers and plugin initialization
 //! for the Warehouse Management System.
 
-use tauri::Manager;
-use tracing::info;
+use tauri::{Manager, RunEvent};
+use tracing::{info, warn};
 
+mod auto_sync;
 mod commands;
+mod events;
 mod state;
 
 pub use state::AppState;
@@ -38,44 +40,283 @@ pub fn run() {
             // Inventory commands
             commands::inventory::get_all_items,
             commands::inventory::get_item_by_sku,
+            commands::inventory::generate_item_label,
             commands::inventory::create_item,
             commands::inventory::update_item,
+            commands::inventory::import_items_csv,
             commands::inventory::adjust_quantity,
+            commands::inventory::transfer_stock,
             commands::inventory::get_low_stock_items,
+            commands::inventory::get_reorder_report,
+            commands::inventory::export_reorder_report,
+            commands::inventory::export_inventory,
+            commands::inventory::list_warehouses,
+            commands::inventory::create_warehouse,
+            commands::inventory::set_location_warehouse,
             commands::inventory::run_forecast,
+            commands::inventory::evaluate_forecasts,
+            commands::inventory::get_forecast_accuracy,
+            commands::inventory::get_price,
+            commands::inventory::upsert_price_list,
+            commands::inventory::get_available_to_promise,
+            commands::inventory::check_promise,
+            commands::inventory::get_workload_plan,
+            commands::inventory::export_workload_plan,
+            commands::inventory::get_pick_heatmap,
+            commands::inventory::get_picker_stats,
+            commands::inventory::get_my_stats,
+            commands::inventory::export_pick_heatmap,
+            commands::inventory::get_item_stock_by_location,
+            commands::inventory::get_lots,
+            commands::inventory::get_expiring_stock,
+            commands::inventory::suggest_pick_lots,
+            commands::inventory::place_quality_hold,
+            commands::inventory::release_quality_hold,
+            commands::inventory::list_quality_holds,
+            commands::inventory::get_replenishment_rules,
+            commands::inventory::save_replenishment_rule,
+            commands::inventory::delete_replenishment_rule,
+            commands::inventory::get_replenishment_tasks,
+            commands::inventory::confirm_replenishment,
+            commands::inventory::create_count_sheet,
+            commands::inventory::get_count_sheet,
+            commands::inventory::record_count,
+            commands::inventory::post_count,
+            commands::inventory::run_abc_classification,
+            commands::inventory::search_items,
+            commands::inventory::get_low_stock_by_parent,
+            commands::inventory::create_item_variant,
+            commands::inventory::create_item_variant_matrix,
+            commands::inventory::get_item_variants,
+            commands::inventory::get_variant_stock,
+            commands::inventory::upload_item_image,
+            commands::inventory::get_item_images,
+            commands::inventory::set_primary_item_image,
+            commands::inventory::get_attachment_content,
+            commands::inventory::delete_attachment,
             // Shipping commands
             commands::shipping::create_shipment,
             commands::shipping::get_shipment,
+            commands::shipping::list_shipments,
             commands::shipping::update_shipment_status,
+            commands::shipping::cancel_shipment,
+            commands::shipping::check_shipment_compliance,
+            commands::shipping::confirm_shipment,
+            commands::shipping::split_shipment,
             commands::shipping::generate_shipping_label,
+            commands::shipping::add_shipment_package,
+            commands::shipping::close_shipment_package,
+            commands::shipping::set_package_floor_loaded,
+            commands::shipping::build_pallet,
+            commands::shipping::unbuild_pallet,
+            commands::shipping::move_carton,
+            commands::shipping::generate_pallet_label,
+            commands::shipping::generate_packing_slip,
+            commands::shipping::generate_pick_list,
+            commands::shipping::generate_wave_pick_list,
+            commands::shipping::get_tracking_events,
+            commands::shipping::import_carrier_invoice,
+            commands::shipping::get_cost_reconciliation,
+            commands::shipping::export_cost_reconciliation,
             commands::shipping::scan_barcode,
+            commands::shipping::scan_pick_item,
+            commands::shipping::correct_pick,
+            commands::shipping::pick_item,
+            commands::shipping::pick_item_fefo,
+            commands::shipping::short_pick_item,
+            commands::shipping::confirm_picking_complete,
+            commands::shipping::get_scan_history,
+            commands::shipping::add_shipment_line_evidence,
+            commands::shipping::get_line_evidence,
+            commands::shipping::get_customer_damage_claims,
+            commands::shipping::export_customer_damage_claims,
             // Receiving commands
             commands::receiving::create_receipt,
+            commands::receiving::get_receipt,
+            commands::receiving::list_receipts,
+            commands::receiving::get_receiving_summary,
             commands::receiving::process_receipt_item,
+            commands::receiving::scan_receipt_item,
+            commands::receiving::scan_receipt_image,
+            commands::receiving::scan_putaway,
+            commands::receiving::get_receipt_discrepancies,
+            commands::receiving::suggest_putaway_location,
             commands::receiving::complete_receipt,
+            commands::receiving::acknowledge_receipt_variances,
+            commands::receiving::import_asn,
+            commands::receiving::receive_manifest,
+            commands::receiving::add_receipt_line_evidence,
+            commands::receiving::get_damage_claims,
+            commands::receiving::export_damage_claims,
+            commands::receiving::record_receipt_temperature,
+            // Printing commands
+            commands::printing::add_printer,
+            commands::printing::list_printers,
+            commands::printing::print_label,
+            commands::printing::get_print_queue,
+            commands::printing::requeue_print_job,
+            commands::printing::cancel_print_job,
             // Delivery commands
             commands::deliveries::get_deliveries,
             commands::deliveries::create_delivery,
             commands::deliveries::update_delivery_status,
+            commands::deliveries::attach_delivery_proof,
+            commands::deliveries::get_delivery_proofs,
             commands::deliveries::optimize_route,
+            commands::deliveries::optimize_fleet,
+            commands::deliveries::get_vehicles,
+            commands::deliveries::create_route,
+            commands::deliveries::reoptimize_route,
+            commands::deliveries::start_route,
+            commands::deliveries::complete_route,
+            commands::deliveries::get_route_manifest,
+            commands::deliveries::get_routes,
+            commands::deliveries::get_route,
             commands::deliveries::check_geofence,
+            commands::deliveries::get_route_breadcrumbs,
+            commands::deliveries::get_last_known_location,
+            commands::deliveries::get_route_progress,
+            commands::deliveries::set_route_breaks,
+            commands::deliveries::get_notification_rules,
+            commands::deliveries::save_notification_rule,
+            commands::deliveries::get_delivery_notifications,
+            commands::deliveries::record_delivery_temperature,
+            // Cold-chain commands
+            commands::temperature::get_temperature_log,
+            commands::temperature::export_temperature_log,
+            commands::temperature::get_temperature_ranges,
+            commands::temperature::save_temperature_range,
+            commands::temperature::delete_temperature_range,
             // CRM commands
             commands::crm::get_customers,
             commands::crm::get_customer,
             commands::crm::create_customer,
             commands::crm::update_customer,
+            commands::crm::deactivate_customer,
+            commands::crm::merge_customers,
+            commands::crm::add_customer_address,
+            commands::crm::update_customer_address,
+            commands::crm::delete_customer_address,
+            commands::crm::set_default_customer_address,
+            commands::crm::add_customer_contact,
+            commands::crm::update_customer_contact,
+            commands::crm::delete_customer_contact,
+            commands::crm::set_primary_customer_contact,
             commands::crm::search_customers,
+            commands::crm::get_customer_activity,
+            commands::crm::get_customer_stats,
+            commands::crm::validate_customer_credit,
+            commands::crm::generate_customer_statement,
+            commands::crm::get_shipping_requirements,
+            commands::crm::upsert_shipping_requirements,
+            commands::crm::get_extract_definitions,
+            commands::crm::save_extract_definition,
+            commands::crm::delete_extract_definition,
+            commands::crm::run_extract_now,
+            commands::crm::get_extract_history,
             // Timesheet commands
             commands::timesheets::clock_in,
             commands::timesheets::clock_out,
             commands::timesheets::get_timesheet,
             commands::timesheets::export_timesheet,
+            commands::timesheets::get_team_timesheets,
+            commands::timesheets::export_team_timesheets,
+            commands::timesheets::submit_timesheet,
+            commands::timesheets::approve_timesheet,
+            commands::timesheets::reject_timesheet,
+            commands::timesheets::edit_locked_time_entry,
+            commands::timesheets::edit_time_entry,
+            commands::timesheets::add_manual_time_entry,
+            commands::timesheets::get_time_entry_audit,
             // Sync commands
             commands::sync::sync_now,
             commands::sync::get_sync_status,
             commands::sync::set_offline_mode,
+            commands::sync::get_sync_settings,
+            commands::sync::set_sync_interval,
+            // Backup commands
+            commands::backup::create_backup,
+            commands::backup::list_backups,
+            commands::backup::restore_backup,
+            // Settings commands
+            commands::settings::get_measurement_preferences,
+            commands::settings::set_measurement_preferences,
+            commands::settings::get_blind_receiving_settings,
+            commands::settings::set_blind_receiving_settings,
+            commands::settings::get_over_receipt_settings,
+            commands::settings::set_over_receipt_settings,
+            commands::settings::get_delivery_window_settings,
+            commands::settings::set_delivery_window_settings,
+            commands::settings::get_clock_site_settings,
+            commands::settings::set_clock_site_settings,
+            commands::settings::get_sscc_settings,
+            commands::settings::set_sscc_settings,
+            commands::settings::get_operation_timeouts,
+            commands::settings::set_operation_timeouts,
+            commands::settings::get_document_number_formats,
+            commands::settings::set_document_number_format,
+            commands::settings::get_count_variance_tolerances,
+            commands::settings::set_count_variance_tolerances,
+            commands::settings::get_custom_field_definitions,
+            commands::settings::save_custom_field_definition,
+            commands::settings::delete_custom_field_definition,
+            // Dashboard commands
+            commands::dashboard::get_dashboard_metrics,
+            commands::dashboard::get_metric_history,
+            commands::dashboard::recent_activity,
+            // Search commands
+            commands::search::global_search,
+            // Session commands
+            commands::auth::login,
+            commands::auth::end_session,
+            commands::auth::get_current_user,
+            commands::auth::get_my_permissions,
+            commands::auth::take_initial_admin_pin,
+            commands::auth::list_users,
+            commands::auth::create_user,
+            commands::auth::set_user_password,
+            commands::auth::set_user_active,
+            // Override commands
+            commands::overrides::get_override_reason_codes,
+            commands::overrides::save_override_reason_code,
+            commands::overrides::request_override,
+            commands::overrides::set_override_pin,
+            commands::overrides::list_overrides,
+            commands::overrides::get_override_report,
+            // Long-running operation commands
+            commands::operations::cancel_operation,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            if let RunEvent::ExitRequested { api, .. } = event {
+                // Hold the exit until background tasks reach a safe checkpoint
+                let Some(state) = app.try_state::<AppState>() else {
+                    return;
+                };
+                if state.shutdown.is_requested() {
+                    return;
+                }
+                api.prevent_exit();
+                
+                let shutdown = state.shutdown.clone();
+                let grace = state.shutdown_grace;
+                let app = app.clone();
+                tauri::async_runtime::spawn(async move {
+                    info!("Shutting down background tasks");
+                    let summary = shutdown.shutdown(grace).await;
+                    if summary.is_clean() {
+                        info!("Background tasks stopped: {}", summary.drained.join(", "));
+                    } else {
+                        warn!(
+                            "Background tasks still running after {:?}: {}",
+                            grace,
+                            summary.interrupted.join(", ")
+                        );
+                    }
+                    app.exit(0);
+                });
+            }
+        });
 }
 
@@ -4,19 +4,59 @@
 //! sync engine, and module services.
 
 use std::sync::Arc;
+use std::time::Duration;
 use tauri::{AppHandle, Manager};
-use tokio::sync::RwLock;
+use tokio::sync::{watch, RwLock};
 use anyhow::Result;
-use tracing::info;
+use tracing::{info, warn};
 
+use wms_core::attachments::AttachmentService;
+use wms_core::auth::{Scope, Session};
+use wms_core::context::RequestContext;
+use wms_core::custom_fields::CustomFieldService;
+use wms_core::backup::BackupService;
+use wms_core::dashboard::DashboardService;
+use wms_core::search::SearchService;
 use wms_core::db::Database;
-use wms_sync::SyncEngine;
+use wms_core::error::{ErrorPayload, WmsError};
+use wms_core::events::SharedEmitter;
+use wms_core::instance_lock::InstanceLock;
+use wms_core::operations::{OperationKind, OperationRegistry, OperationTimeouts, RunningOperation};
+use wms_core::overrides::OverrideService;
+use wms_core::temperature::TemperatureService;
+use wms_core::users::{UserService, INITIAL_ADMIN_USERNAME};
+use wms_core::shutdown::{ShutdownCoordinator, DEFAULT_SHUTDOWN_GRACE, SHUTDOWN_GRACE_SETTING};
+use wms_sync::{SyncEngine, SyncSettings};
 use wms_inventory::InventoryService;
-use wms_shipping::ShippingService;
+use wms_shipping::{PrinterService, ShippingService, TrackingUpdater};
 use wms_deliveries::DeliveryService;
-use wms_crm::CrmService;
+use wms_crm::{CrmService, ExtractService};
 use wms_timesheets::TimesheetService;
 
+use crate::auto_sync;
+use crate::events::TauriEventEmitter;
+
+/// How often the print queue is checked for due jobs
+const PRINT_QUEUE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How often carriers are polled for undelivered shipments
+const TRACKING_POLL_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// How often today's dashboard metrics are written to the history
+const METRICS_HISTORY_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// How often past forecast predictions are scored against actual demand
+const FORECAST_ACCURACY_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// How often customer extracts are checked for due runs
+const EXTRACT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How often driver locations past their retention are purged
+const LOCATION_PURGE_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How often the sync server is probed for reachability
+const CONNECTIVITY_PROBE_INTERVAL: Duration = Duration::from_secs(30);
+
 /// Global application state shared across all Tauri commands
 pub struct AppState {
     /// Database connection pool
@@ -27,14 +67,55 @@ pub struct AppState {
     pub inventory: Arc<InventoryService>,
     /// Shipping management service
     pub shipping: Arc<ShippingService>,
+    /// Label printers and print queue
+    pub printing: Arc<PrinterService>,
+    /// Carrier tracking ingestion
+    pub tracking: Arc<TrackingUpdater>,
     /// Delivery management service
     pub deliveries: Arc<DeliveryService>,
     /// CRM service
     pub crm: Arc<CrmService>,
+    /// Scheduled customer data extracts
+    pub extracts: Arc<ExtractService>,
     /// Timesheet service
     pub timesheets: Arc<TimesheetService>,
+    /// Database backup and restore
+    pub backup: Arc<BackupService>,
+    /// Item images and other attachments
+    pub attachments: Arc<AttachmentService>,
+    /// Custom field definitions
+    pub custom_fields: Arc<CustomFieldService>,
+    /// Dashboard metrics
+    pub dashboard: Arc<DashboardService>,
+    /// Header search across items, customers, shipments and deliveries
+    pub search: Arc<SearchService>,
+    /// Cold-chain temperature ranges and readings
+    pub temperature: Arc<TemperatureService>,
+    /// Supervisor overrides and their reason codes
+    pub overrides: Arc<OverrideService>,
+    /// User accounts and signing in
+    pub users: Arc<UserService>,
+    /// Running long-running operations, cancellable by operation id
+    pub operations: OperationRegistry,
     /// Offline mode flag
     pub offline_mode: Arc<RwLock<bool>>,
+    /// Background sync interval; changes reach the sync loop at once
+    pub sync_settings: watch::Sender<SyncSettings>,
+    /// Signed-in user; without one only signing in is allowed
+    pub session: Arc<RwLock<Option<Session>>>,
+    /// PIN generated for the seeded admin on first run, until the UI has
+    /// shown it
+    pub initial_admin_pin: Arc<RwLock<Option<String>>>,
+    /// Warehouse this install serves, from the `warehouse_id` setting
+    pub warehouse_id: Option<String>,
+    /// This install's sync device id
+    pub device_id: Option<String>,
+    /// Stops background tasks when the app exits
+    pub shutdown: Arc<ShutdownCoordinator>,
+    /// How long background tasks get to stop on exit
+    pub shutdown_grace: Duration,
+    /// Keeps admin tools off the database while the app runs
+    _instance_lock: InstanceLock,
 }
 
 impl AppState {
@@ -46,6 +127,7 @@ impl AppState {
         
         let db_path = app_dir.join("wms.db");
         info!("Initializing database at {:?}", db_path);
+        let instance_lock = InstanceLock::acquire(&db_path, "desktop app")?;
         
         // Initialize encrypted database
         let encryption_key = std::env::var("WMS_DB_KEY")
@@ -53,32 +135,213 @@ impl AppState {
         
         let db = Arc::new(Database::new(&db_path, &encryption_key)?);
         
-        // Run migrations
-        db.run_migrations()?;
-        info!("Database migrations completed");
+        // Refuses to start on a schema from a newer build
+        let applied = db.migrate_to_latest()?;
+        info!("Database migrations completed ({} applied)", applied.len());
+        
+        // Domain events are forwarded to all windows
+        let events: SharedEmitter = Arc::new(TauriEventEmitter::new(app.clone()));
+        
+        let shutdown = Arc::new(ShutdownCoordinator::new());
+        let shutdown_grace = db.get_setting(SHUTDOWN_GRACE_SETTING)?
+            .and_then(|secs| secs.parse().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_SHUTDOWN_GRACE);
         
         // Initialize sync engine
-        let sync_engine = Arc::new(RwLock::new(SyncEngine::new(db.clone())?));
+        let sync_engine = SyncEngine::new(db.clone())?
+            .with_event_emitter(events.clone())
+            .with_shutdown_signal(shutdown.signal());
+        let connectivity = sync_engine.connectivity();
+        let sync_engine = Arc::new(RwLock::new(sync_engine));
+        
+        // Stamped on every request context
+        let warehouse_id = db.get_setting("warehouse_id")?;
+        let device_id = db.get_setting("device_id")?;
         
         // Initialize services
-        let inventory = Arc::new(InventoryService::new(db.clone()));
-        let shipping = Arc::new(ShippingService::new(db.clone()));
-        let deliveries = Arc::new(DeliveryService::new(db.clone()));
+        let inventory = Arc::new(InventoryService::new(db.clone()).with_event_emitter(events.clone()));
+        let shipping = Arc::new(ShippingService::new(db.clone()).with_event_emitter(events.clone()));
+        let printing = Arc::new(PrinterService::new(db.clone()));
+        let tracking = TrackingUpdater::new(db.clone(), shipping.clone());
+        #[cfg(feature = "carrier-tracking")]
+        let tracking = tracking.with_env_providers()?;
+        let tracking = Arc::new(tracking);
+        let deliveries = Arc::new(DeliveryService::new(db.clone()).with_event_emitter(events.clone()));
         let crm = Arc::new(CrmService::new(db.clone()));
-        let timesheets = Arc::new(TimesheetService::new(db.clone()));
+        let extracts = ExtractService::new(db.clone()).with_event_emitter(events.clone());
+        #[cfg(feature = "sftp")]
+        let extracts = extracts.with_sftp_transport(Arc::new(wms_crm::Ssh2SftpTransport::default()));
+        let extracts = Arc::new(extracts);
+        let timesheets = Arc::new(TimesheetService::new(db.clone()).with_event_emitter(events.clone()));
+        let operations = OperationRegistry::new().with_event_emitter(events.clone());
+        let backup = Arc::new(BackupService::new(db.clone()).with_event_emitter(events));
+        let attachments = Arc::new(AttachmentService::new(db.clone()));
+        let custom_fields = Arc::new(CustomFieldService::new(db.clone()));
+        let dashboard = Arc::new(DashboardService::new(db.clone()));
+        let search = Arc::new(SearchService::new(db.clone()));
+        let temperature = Arc::new(TemperatureService::new(db.clone()));
+        let overrides = Arc::new(OverrideService::new(db.clone()));
+        let users = Arc::new(UserService::new(db.clone()));
+        
+        // First run: nobody can sign in until the admin has a PIN, which
+        // is shown once by the first-run UI rather than logged
+        let initial_admin_pin = users.seed_admin()?;
+        if initial_admin_pin.is_some() {
+            warn!("Created user '{}'; sign in with the PIN shown and change it", INITIAL_ADMIN_USERNAME);
+        }
         
         info!("All services initialized successfully");
         
+        // Drain the print queue in the background
+        let task = shutdown.register("printing");
+        tauri::async_runtime::spawn(printing.clone().run_worker(PRINT_QUEUE_INTERVAL, task));
+        
+        // Poll carriers for delivery scans
+        let task = shutdown.register("tracking");
+        tauri::async_runtime::spawn(tracking.clone().run_poller(TRACKING_POLL_INTERVAL, task));
+        
+        // Keep the daily metrics history behind the dashboard sparklines current
+        let task = shutdown.register("dashboard-metrics");
+        tauri::async_runtime::spawn(dashboard.clone().run_aggregator(METRICS_HISTORY_INTERVAL, task));
+        
+        // Score forecasts once the days they predicted have passed
+        let task = shutdown.register("forecast-accuracy");
+        tauri::async_runtime::spawn(inventory.clone().run_forecast_evaluator(FORECAST_ACCURACY_INTERVAL, task));
+        
+        // Send customer extracts as they come due
+        let task = shutdown.register("extracts");
+        tauri::async_runtime::spawn(extracts.clone().run_scheduler(EXTRACT_INTERVAL, task));
+        
+        // Keep the driver location history bounded
+        let task = shutdown.register("location-purge");
+        tauri::async_runtime::spawn(deliveries.clone().run_location_purge(LOCATION_PURGE_INTERVAL, task));
+        
+        // Sync in the background on the configured interval
+        let offline_mode = Arc::new(RwLock::new(false));
+        let (sync_settings, sync_schedule) = watch::channel(SyncSettings::load(&db)?);
+        let task = shutdown.register("auto-sync");
+        tauri::async_runtime::spawn(auto_sync::run(
+            app.clone(),
+            sync_engine.clone(),
+            offline_mode.clone(),
+            sync_schedule,
+            task,
+        ));
+        
+        // Probe the sync server so the status shows when it can't be reached
+        let task = shutdown.register("connectivity");
+        tauri::async_runtime::spawn(auto_sync::watch_connection(
+            app.clone(),
+            sync_engine.clone(),
+            connectivity,
+            CONNECTIVITY_PROBE_INTERVAL,
+            task,
+        ));
+        
+        // On exit, wait for any running sync (which holds the read lock) to
+        // stop at a batch boundary, then requeue unacknowledged changes and
+        // save the sync cursor
+        let task = shutdown.register("sync");
+        let engine = sync_engine.clone();
+        tauri::async_runtime::spawn(async move {
+            task.shutdown_requested().await;
+            match engine.write().await.checkpoint() {
+                Ok(checkpoint) => info!(
+                    "Sync checkpoint saved with {} pending changes",
+                    checkpoint.pending_changes
+                ),
+                Err(e) => warn!("Unable to checkpoint sync state: {}", e),
+            }
+        });
+        
         Ok(Self {
             db,
             sync_engine,
             inventory,
             shipping,
+            printing,
+            tracking,
             deliveries,
             crm,
+            extracts,
             timesheets,
-            offline_mode: Arc::new(RwLock::new(false)),
+            backup,
+            attachments,
+            custom_fields,
+            dashboard,
+            search,
+            temperature,
+            overrides,
+            users,
+            operations,
+            offline_mode,
+            sync_settings,
+            session: Arc::new(RwLock::new(None)),
+            initial_admin_pin: Arc::new(RwLock::new(initial_admin_pin)),
+            warehouse_id,
+            device_id,
+            shutdown,
+            shutdown_grace,
+            _instance_lock: instance_lock,
         })
     }
+    
+    /// The signed-in user's session, with their role as it is now. The
+    /// account is read again on every call, so disabling a user or
+    /// changing their role applies to their next command; a disabled or
+    /// removed user is signed out.
+    pub async fn current_session(&self) -> std::result::Result<Option<Session>, ErrorPayload> {
+        let mut current = self.session.write().await;
+        let Some(user_id) = current.as_ref().map(|s| s.user_id.clone()) else {
+            return Ok(None);
+        };
+        match Session::load(&self.db, &user_id) {
+            Ok(session) => {
+                *current = Some(session.clone());
+                Ok(Some(session))
+            }
+            Err(WmsError::NotFound(_)) => {
+                *current = None;
+                info!("Signed out user {}, whose account is no longer active", user_id);
+                Err(WmsError::permission_denied("Your account has been disabled; sign in again").into())
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+    
+    /// Context of the current request, for commands that check scopes
+    /// themselves. Fails when nobody is signed in.
+    pub async fn context(&self) -> std::result::Result<RequestContext, ErrorPayload> {
+        let Some(session) = self.current_session().await? else {
+            return Err(WmsError::permission_denied("Sign in to continue").into());
+        };
+        Ok(RequestContext::new(Some(&session))
+            .with_warehouse(self.warehouse_id.clone())
+            .with_device(self.device_id.clone()))
+    }
+    
+    /// Fail unless the signed-in user's role may use `scope`. Returns the
+    /// request's context so commands can narrow what they return and pass
+    /// the acting user on to services.
+    pub async fn require(&self, scope: Scope) -> std::result::Result<RequestContext, ErrorPayload> {
+        let ctx = self.context().await?;
+        ctx.require(scope)?;
+        Ok(ctx)
+    }
+    
+    /// Start a cancellable operation of `kind` under the frontend's
+    /// operation id, cancelled once it runs past the configured time
+    /// limit for its kind
+    pub fn start_operation(
+        &self,
+        kind: OperationKind,
+        operation_id: Option<String>,
+    ) -> std::result::Result<RunningOperation, ErrorPayload> {
+        let timeouts = OperationTimeouts::load(&self.db)?;
+        self.operations
+            .start(kind, operation_id, timeouts.limit(kind))
+            .map_err(ErrorPayload::from)
+    }
 }
 